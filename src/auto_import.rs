@@ -0,0 +1,207 @@
+//! Caps on contacts and chats created by incoming pings and messages
+//!
+//! A valid ping from an unknown sender imports it as a contact and opens a
+//! chat; a message from an unknown sender opens a chat. Scripted senders
+//! could use that to fill the database, so `AutoImportLimiter` allows at
+//! most `Settings::auto_import_contacts_per_hour` new contacts and
+//! `Settings::auto_import_chats_per_hour` new chats in any sliding hour.
+//! Requests over a cap get `Error::RateLimited` (HTTP 429) and nothing is
+//! stored. Instead of one log row per refusal, the request log keeps a
+//! single entry summing up the last hour (`audit_suppressed_imports`),
+//! which counts sources by the socket address of the request, never by the
+//! address a token advertises.
+//! Known contacts and existing chats are never limited, and the caps can be
+//! lifted for a while when onboarding many peers on purpose.
+
+use crate::{storage::Storage, Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+
+/// Length of the sliding window the caps apply to
+pub const AUTO_IMPORT_WINDOW_SECS: i64 = 60 * 60;
+
+/// Default cap on contacts auto-imported per hour
+pub const DEFAULT_AUTO_IMPORT_CONTACTS_PER_HOUR: u32 = 10;
+
+/// Default cap on chats auto-created per hour
+pub const DEFAULT_AUTO_IMPORT_CHATS_PER_HOUR: u32 = 10;
+
+/// How long the caps stay lifted from Settings
+pub const AUTO_IMPORT_LIFT_MINUTES: i64 = 60;
+
+/// Request type of the aggregated entry in the request log
+pub const AUTO_IMPORT_AUDIT_TYPE: &str = "auto_import";
+
+/// Refusals within the window, summed up for the audit log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuppressionSummary {
+    /// Refused creations
+    pub count: usize,
+    /// Distinct source addresses among them
+    pub sources: usize,
+}
+
+impl fmt::Display for SuppressionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "suppressed {} auto-import{} from {} source IP{} in the last hour",
+            self.count,
+            if self.count == 1 { "" } else { "s" },
+            self.sources,
+            if self.sources == 1 { "" } else { "s" }
+        )
+    }
+}
+
+/// Sliding-window limiter for handler-initiated contact and chat creation
+#[derive(Debug, Clone)]
+pub struct AutoImportLimiter {
+    max_contacts: u32,
+    max_chats: u32,
+    contacts: VecDeque<DateTime<Utc>>,
+    chats: VecDeque<DateTime<Utc>>,
+    suppressed: VecDeque<(DateTime<Utc>, Option<String>)>,
+    lifted_until: Option<DateTime<Utc>>,
+}
+
+impl Default for AutoImportLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_AUTO_IMPORT_CONTACTS_PER_HOUR, DEFAULT_AUTO_IMPORT_CHATS_PER_HOUR)
+    }
+}
+
+impl AutoImportLimiter {
+    /// Create a limiter with the given hourly caps (0 = unlimited)
+    pub fn new(max_contacts: u32, max_chats: u32) -> Self {
+        Self {
+            max_contacts,
+            max_chats,
+            contacts: VecDeque::new(),
+            chats: VecDeque::new(),
+            suppressed: VecDeque::new(),
+            lifted_until: None,
+        }
+    }
+
+    /// Change the hourly caps (0 = unlimited); creations already counted stay
+    pub fn set_caps(&mut self, max_contacts: u32, max_chats: u32) {
+        self.max_contacts = max_contacts;
+        self.max_chats = max_chats;
+    }
+
+    /// Lift the caps until `until` (None restores them)
+    pub fn lift_until(&mut self, until: Option<DateTime<Utc>>) {
+        self.lifted_until = until;
+    }
+
+    /// End of the current lift, if the caps are lifted at `now`
+    pub fn lifted_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.lifted_until.filter(|until| *until > now)
+    }
+
+    /// Count a creation, or refuse it if it would exceed a cap
+    ///
+    /// # Arguments
+    /// * `new_contact` - The request would add a contact
+    /// * `new_chat` - The request would add a chat
+    /// * `source` - Socket address the request came from, for the audit summary
+    /// * `now` - Current time
+    ///
+    /// # Errors
+    /// `Error::RateLimited` (with the current summary) if a cap is reached;
+    /// nothing is counted then except the refusal
+    pub fn admit(&mut self, new_contact: bool, new_chat: bool, source: Option<&str>, now: DateTime<Utc>) -> Result<()> {
+        self.prune(now);
+        let full = |recent: &VecDeque<DateTime<Utc>>, cap: u32| cap > 0 && recent.len() >= cap as usize;
+        let over_cap = (new_contact && full(&self.contacts, self.max_contacts))
+            || (new_chat && full(&self.chats, self.max_chats));
+        if over_cap && self.lifted_until(now).is_none() {
+            self.suppressed.push_back((now, source.map(str::to_string)));
+            let summary = self.suppression_summary(now).unwrap_or(SuppressionSummary { count: 1, sources: 0 });
+            return Err(Error::RateLimited(summary.to_string()));
+        }
+        if new_contact {
+            self.contacts.push_back(now);
+        }
+        if new_chat {
+            self.chats.push_back(now);
+        }
+        Ok(())
+    }
+
+    /// Refusals within the last hour (None if there were none)
+    pub fn suppression_summary(&self, now: DateTime<Utc>) -> Option<SuppressionSummary> {
+        let window_start = now - Duration::seconds(AUTO_IMPORT_WINDOW_SECS);
+        let recent: Vec<_> = self.suppressed.iter().filter(|(at, _)| *at > window_start).collect();
+        if recent.is_empty() {
+            return None;
+        }
+        let sources: HashSet<_> = recent.iter().filter_map(|(_, source)| source.as_deref()).collect();
+        Some(SuppressionSummary {
+            count: recent.len(),
+            sources: sources.len(),
+        })
+    }
+
+    /// Drop everything older than the window
+    fn prune(&mut self, now: DateTime<Utc>) {
+        let window_start = now - Duration::seconds(AUTO_IMPORT_WINDOW_SECS);
+        for times in [&mut self.contacts, &mut self.chats] {
+            while times.front().is_some_and(|at| *at <= window_start) {
+                times.pop_front();
+            }
+        }
+        while self.suppressed.front().is_some_and(|(at, _)| *at <= window_start) {
+            self.suppressed.pop_front();
+        }
+    }
+}
+
+/// Replace this hour's aggregated refusal entry in the request log
+///
+/// Entries from earlier hours are kept, so the log holds at most one entry
+/// per hour of refusals.
+///
+/// # Errors
+/// Returns an error if the log cannot be written
+pub fn audit_suppressed_imports(storage: &Storage, summary: &SuppressionSummary, now: DateTime<Utc>) -> Result<()> {
+    tracing::warn!("Auto-import cap reached: {}", summary);
+    let window_start = now - Duration::seconds(AUTO_IMPORT_WINDOW_SECS);
+    storage.delete_request_logs_since(AUTO_IMPORT_AUDIT_TYPE, window_start.timestamp_millis())?;
+    storage.log_request(
+        "incoming",
+        AUTO_IMPORT_AUDIT_TYPE,
+        None,
+        None,
+        Some(429),
+        false,
+        Some(&summary.to_string()),
+        None,
+    )
+}
+
+/// Ask `limiter` to admit a creation, updating the audit entry if refused
+///
+/// # Errors
+/// `Error::RateLimited` if a cap is reached (nothing may be stored then)
+pub fn admit_or_audit(
+    storage: &Storage,
+    limiter: &Mutex<AutoImportLimiter>,
+    new_contact: bool,
+    new_chat: bool,
+    source: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let mut limiter = limiter.lock().unwrap();
+    let refused = limiter.admit(new_contact, new_chat, source, now);
+    if refused.is_err()
+        && let Some(summary) = limiter.suppression_summary(now)
+        && let Err(e) = audit_suppressed_imports(storage, &summary, now)
+    {
+        tracing::debug!("Failed to audit suppressed auto-imports: {}", e);
+    }
+    refused
+}
@@ -3,51 +3,132 @@
 //! A terminal-based user interface for Pure2P messaging.
 
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture, Event, KeyCode},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use pure2p::tui::{App, Screen, ui::ui};
+use pure2p::connectivity::MappingProtocol;
+use pure2p::logging::{init_file_logging, install_panic_hook, LOGS_DIR};
+use pure2p::queue::MessageQueue;
+use pure2p::transport::capture::{list_captures, write_diagnostics_bundle, CAPTURES_DIR};
+use pure2p::storage::{live_instance, migration, process_alive, Chat, ErrorSeverity, PrivacySignal, LEGACY_STATE_FILE, RUNTIME_INFO_FILE};
+use pure2p::tui::alerts;
+use pure2p::tui::{App, CaptureScreen, ChatViewScreen, ExpiryChoice, SaveTarget, Screen, SettingsScreen, TerminalTitle, CHAT_VIEW_PAGE_SIZE, ui::ui};
 use ratatui::{
     backend::CrosstermBackend,
     Terminal,
 };
-use std::io;
+use std::io::{self, IsTerminal, Write};
+
+/// Queue database used by the app (see `App::new`)
+const QUEUE_DB_PATH: &str = "./app_data/message_queue.db";
+
+/// Longest wait for input before the loop runs again
+const EVENT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--instance") {
+        return show_instance();
+    }
+    if args.iter().any(|arg| arg == "--migrate-dry-run") {
+        return migrate_dry_run();
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--export-queue") {
+        return export_queue(args.get(i + 1));
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--export-bundle") {
+        let with_captures = args.iter().any(|arg| arg == "--with-captures");
+        return export_bundle(args.get(i + 1), with_captures);
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--import-queue") {
+        let debug = args.iter().any(|arg| arg == "--debug");
+        return import_queue(debug, args.get(i + 1), args.get(i + 2));
+    }
+
+    let inspect_dir = args.iter().position(|arg| arg == "--inspect").map(|i| args.get(i + 1));
+    if inspect_dir == Some(None) {
+        eprintln!("Usage: pure2p-tui --inspect <app_data copy>");
+        std::process::exit(2);
+    }
+
+    // Create app state (minimal startup path only) before taking over the
+    // terminal, so a failed legacy migration (or a refused inspection) is
+    // reported readably
+    let app = match inspect_dir.flatten() {
+        Some(dir) => App::new_for_inspection(dir),
+        None => App::new(),
+    };
+    let mut app = match app {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            if inspect_dir.is_none() && std::path::Path::new(LEGACY_STATE_FILE).exists() {
+                eprintln!("{} and the database were left unchanged.", LEGACY_STATE_FILE);
+            }
+            std::process::exit(1);
+        }
+    };
+
+    // Log to a rotated file; a panic in any thread reaches it and the error banner.
+    // Inspection leaves both our logs and the inspected ones alone
+    let settings = &app.app_state.settings;
+    if app.inspection.is_none() {
+        match init_file_logging(std::path::Path::new(LOGS_DIR), settings.log_max_file_bytes, settings.log_files_kept) {
+            Ok(log) => {
+                let reporter = app.error_reports.clone();
+                install_panic_hook(log.clone(), move |message| reporter.report(ErrorSeverity::Error, "panic", message));
+                app.log_file = Some(log);
+            }
+            Err(e) => eprintln!("Warning: no log file: {}", e),
+        }
+    }
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableFocusChange)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Create app state
-    let mut app = App::new()?;
-
-    // Start transport server in background
-    app.start_transport()?;
-
-    // Trigger background connectivity diagnostics on startup
-    // (retry worker will start automatically after connectivity is established)
-    app.trigger_startup_connectivity();
+    // Show the UI before loading history and starting background services
+    terminal.draw(|f| ui(f, &app))?;
+    app.complete_deferred_startup()?;
 
     // Run main loop
-    let res = run_app(&mut terminal, &mut app);
+    let mut title = TerminalTitle::for_stdout();
+    let res = run_app(&mut terminal, &mut app, &mut title);
 
-    // Save application state before exit
+    // Save application state before exit (nothing is saved while inspecting)
     if let Err(e) = app.save_state() {
         eprintln!("Warning: Failed to save application state: {}", e);
     }
+    // State is saved: the next start need not recover from this session
+    app.end_session();
+    if app.deferred_writes.is_pending() {
+        eprintln!(
+            "Warning: Database is locked, {} changes could not be saved",
+            app.deferred_writes.pending_count()
+        );
+    }
+    if let Some(Err(e)) = app.log_file.as_ref().map(|log| log.flush()) {
+        eprintln!("Warning: Failed to flush the log file: {}", e);
+    }
 
     // Restore terminal
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableFocusChange
     )?;
     terminal.show_cursor()?;
+    if let Some(escape) = title.clear() {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(escape.as_bytes());
+        let _ = stdout.flush();
+    }
 
     if let Err(err) = res {
         println!("Error: {:?}", err);
@@ -56,13 +137,170 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Print the running instance's pid and port (exit code 1 if none is running)
+fn show_instance() -> Result<(), Box<dyn std::error::Error>> {
+    match live_instance(std::path::Path::new(RUNTIME_INFO_FILE), process_alive) {
+        Some(info) => {
+            let since = chrono::DateTime::from_timestamp_millis(info.started_at)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            println!("pid {} listening on port {} (since {})", info.pid, info.port, since);
+            Ok(())
+        }
+        None => {
+            eprintln!("No running instance");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Report what the legacy state migration would import, without writing
+fn migrate_dry_run() -> Result<(), Box<dyn std::error::Error>> {
+    match migration::dry_run(LEGACY_STATE_FILE) {
+        Ok(Some(report)) => {
+            println!("{} would import: {}", LEGACY_STATE_FILE, report);
+            Ok(())
+        }
+        Ok(None) => {
+            println!("No {} found, nothing to migrate", LEGACY_STATE_FILE);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Write a redacted queue report (no message contents, truncated UIDs)
+fn export_queue(path: Option<&String>) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(path) = path else {
+        eprintln!("Usage: pure2p-tui --export-queue <report.json>");
+        std::process::exit(2);
+    };
+    match MessageQueue::new_with_path(QUEUE_DB_PATH).and_then(|queue| queue.export_debug(path)) {
+        Ok(rows) => {
+            println!("Wrote {} queued messages to {}", rows, path);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Write a diagnostics bundle: the queue report, plus the debug captures
+/// only with `--with-captures`
+fn export_bundle(dir: Option<&String>, with_captures: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(dir) = dir else {
+        eprintln!("Usage: pure2p-tui --export-bundle <new folder> [--with-captures]");
+        std::process::exit(2);
+    };
+    let captures = if with_captures {
+        list_captures(std::path::Path::new(CAPTURES_DIR))
+    } else {
+        Vec::new()
+    };
+    let result = MessageQueue::new_with_path(QUEUE_DB_PATH)
+        .and_then(|queue| write_diagnostics_bundle(std::path::Path::new(dir), &queue, &captures));
+    match result {
+        Ok(files) => {
+            println!("Wrote {} files to {}", files.len(), dir);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Rebuild a user's queue report into a scratch database to reproduce it
+fn import_queue(
+    debug: bool,
+    report: Option<&String>,
+    scratch: Option<&String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (Some(report), Some(scratch)) = (report, scratch) else {
+        eprintln!("Usage: pure2p-tui --debug --import-queue <report.json> <scratch.db>");
+        std::process::exit(2);
+    };
+    if std::path::Path::new(scratch) == std::path::Path::new(QUEUE_DB_PATH) {
+        eprintln!("Error: refusing to import into the live queue; pick a scratch database");
+        std::process::exit(1);
+    }
+    let result = MessageQueue::new_with_path(scratch).and_then(|mut queue| {
+        queue.set_debug_import(debug);
+        queue.import_debug(report)
+    });
+    match result {
+        Ok(rows) => {
+            println!("Rebuilt {} queued messages into {}", rows, scratch);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            if !debug {
+                eprintln!("Pass --debug to allow importing a queue report.");
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
 fn run_app<B: ratatui::backend::Backend>(
     terminal: &mut Terminal<B>,
     app: &mut App,
+    title: &mut TerminalTitle,
 ) -> io::Result<()> {
     loop {
+        // Pick up messages and pings stored by background handlers
+        app.process_incoming_updates();
+
+        // Apply delivery status changes published by the retry worker and senders
+        app.process_delivery_events();
+
+        // Fill in introduction ping results of a batch import
+        app.poll_batch_import();
+
+        // Re-derive the footer connectivity segment when its inputs changed
+        app.poll_transport_status();
+        app.poll_health_check();
+
+        let now = std::time::Instant::now();
+        app.effects.prune(now);
         terminal.draw(|f| ui(f, app))?;
 
+        // Ring the bell for new messages (never into redirected output)
+        if app.take_bell() {
+            let mut stdout = io::stdout();
+            let is_tty = stdout.is_terminal();
+            alerts::ring_bell(&mut stdout, is_tty)?;
+        }
+
+        // Reflect unread/pending counts in the terminal title (rate-limited)
+        if let Some(escape) = title.update(&app.chat_summary().terminal_title(), std::time::Instant::now()) {
+            let mut stdout = io::stdout();
+            stdout.write_all(escape.as_bytes())?;
+            stdout.flush()?;
+        }
+
+        // Track quiet hours (holds back notifications, summarizes when they end)
+        app.update_quiet_hours();
+
+        // Warn and remove temporary contacts whose lifetime is ending
+        app.run_ephemeral_maintenance(chrono::Utc::now());
+
+        // Expire the undo offer and purge deletes past their undo window
+        app.run_soft_delete_maintenance(chrono::Utc::now());
+
+        // Hold back queued messages to contacts whose token expired
+        app.maybe_sync_expired_blocks(chrono::Utc::now());
+
+        // Write changes held back while the database was locked
+        app.flush_deferred_writes();
+
         // Poll for startup connectivity completion (runs in background on all screens)
         // When connectivity completes, retry worker will start automatically
         // BUT: skip if on Diagnostics screen, since poll_diagnostics_result() handles it
@@ -75,9 +313,61 @@ fn run_app<B: ratatui::backend::Backend>(
         if app.current_screen == Screen::Diagnostics {
             app.poll_diagnostics_result();
         }
+        app.poll_diagnostics_action();
+
+        // Start the startup stages whose dependencies reported done
+        app.poll_startup(std::time::Instant::now());
+
+        // Daily release manifest check (only when enabled in Settings)
+        app.maybe_check_for_updates(chrono::Utc::now());
+        app.poll_update_check();
+
+        // Wake up in time to end a running effect such as the header flash;
+        // in the background, tick (and redraw) at most once a second
+        let tick = app.focus.poll_interval(EVENT_POLL_INTERVAL);
+        let poll_timeout = app.effects.next_change(now).map_or(tick, |left| left.min(tick));
+        if event::poll(poll_timeout)? {
+            let event = event::read()?;
+            match event {
+                Event::FocusLost => app.focus_lost(),
+                Event::FocusGained => {
+                    app.focus_gained(std::time::Instant::now());
+                    terminal.clear()?;
+                }
+                _ => {}
+            }
+            if let Event::Key(key) = event {
+                // The save-path overlay takes every key while it is open
+                if let Some(picker) = &mut app.path_picker {
+                    if picker.pending_overwrite.is_some() {
+                        app.answer_overwrite_prompt(matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')));
+                    } else {
+                        match key.code {
+                            KeyCode::Enter => app.submit_path_picker(),
+                            KeyCode::Esc => app.cancel_path_picker(),
+                            KeyCode::Backspace => picker.backspace(),
+                            KeyCode::Char(c) if !c.is_control() => picker.add_char(c),
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
+
+                // Ctrl+X dismisses the error banner from any screen
+                if key.code == KeyCode::Char('x')
+                    && key.modifiers.contains(event::KeyModifiers::CONTROL)
+                    && app.dismiss_error_banner()
+                {
+                    continue;
+                }
+
+                // 'D' (Ctrl+D while typing) jumps to Diagnostics from any screen
+                if let KeyCode::Char(c) = key.code
+                    && app.handle_diagnostics_jump(c, key.modifiers.contains(event::KeyModifiers::CONTROL))
+                {
+                    continue;
+                }
 
-        if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
                 match app.current_screen {
                     Screen::MainMenu => {
                         match key.code {
@@ -110,28 +400,176 @@ fn run_app<B: ratatui::backend::Backend>(
                         }
                     }
                     Screen::ShareContact => {
+                        if let Some(screen) = &mut app.share_contact_screen {
+                            if screen.new_endpoint_input.is_some() {
+                                // Typing a new endpoint
+                                match key.code {
+                                    KeyCode::Enter => {
+                                        screen.confirm_endpoint();
+                                    }
+                                    KeyCode::Esc => {
+                                        screen.cancel_adding_endpoint();
+                                    }
+                                    KeyCode::Backspace => {
+                                        screen.backspace();
+                                    }
+                                    KeyCode::Char(c) if !c.is_control() => {
+                                        screen.add_char(c);
+                                    }
+                                    _ => {}
+                                }
+                                continue;
+                            }
+                            if screen.editing_endpoints {
+                                // Endpoint editor
+                                match key.code {
+                                    KeyCode::Down | KeyCode::Char('j') => {
+                                        screen.next_endpoint();
+                                    }
+                                    KeyCode::Up | KeyCode::Char('k') => {
+                                        screen.previous_endpoint();
+                                    }
+                                    KeyCode::Char('K') => {
+                                        screen.move_endpoint_up();
+                                    }
+                                    KeyCode::Char('J') => {
+                                        screen.move_endpoint_down();
+                                    }
+                                    KeyCode::Char(' ') => {
+                                        screen.toggle_endpoint();
+                                    }
+                                    KeyCode::Char('a') => {
+                                        screen.start_adding_endpoint();
+                                    }
+                                    KeyCode::Char('x') | KeyCode::Delete => {
+                                        screen.remove_endpoint();
+                                    }
+                                    KeyCode::Enter | KeyCode::Esc | KeyCode::Char('e') => {
+                                        screen.close_endpoint_editor(&app.keypair);
+                                    }
+                                    _ => {}
+                                }
+                                continue;
+                            }
+                        }
+
                         match key.code {
                             KeyCode::Esc => {
                                 app.back_to_main_menu();
                             }
+                            KeyCode::Char('e') => {
+                                if let Some(screen) = &mut app.share_contact_screen {
+                                    screen.open_endpoint_editor();
+                                }
+                            }
                             KeyCode::Char('c') => {
                                 if let Some(screen) = &mut app.share_contact_screen {
                                     screen.copy_to_clipboard();
                                 }
                             }
                             KeyCode::Char('s') => {
+                                app.open_path_picker(SaveTarget::ContactToken);
+                            }
+                            KeyCode::Char('p') => {
+                                app.copy_saved_path();
+                            }
+                            KeyCode::Char('a') => {
                                 if let Some(screen) = &mut app.share_contact_screen {
-                                    screen.save_to_file();
+                                    screen.toggle_armored();
                                 }
                             }
+                            KeyCode::Char('i') => {
+                                app.create_invite();
+                            }
                             _ => {}
                         }
                     }
+                    Screen::ImportContact
+                        if app.import_contact_screen.as_ref().and_then(|s| s.batch.as_ref()).is_some_and(|b| b.report.is_some()) =>
+                    {
+                        // Batch import report: any of these starts a new batch
+                        if matches!(key.code, KeyCode::Esc | KeyCode::Enter)
+                            && let Some(screen) = &mut app.import_contact_screen
+                        {
+                            screen.input.clear();
+                            screen.back_to_batch_input();
+                        }
+                    }
+                    Screen::ImportContact
+                        if app.import_contact_screen.as_ref().and_then(|s| s.batch.as_ref()).is_some_and(|b| b.in_review()) =>
+                    {
+                        // Batch import review table
+                        match key.code {
+                            KeyCode::Enter => {
+                                app.confirm_batch_import();
+                            }
+                            KeyCode::Esc => {
+                                if let Some(screen) = &mut app.import_contact_screen {
+                                    screen.back_to_batch_input();
+                                }
+                            }
+                            KeyCode::Tab => {
+                                if let Some(screen) = &mut app.import_contact_screen {
+                                    screen.cycle_temporary();
+                                }
+                            }
+                            KeyCode::Char('t') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                if let Some(screen) = &mut app.import_contact_screen {
+                                    screen.toggle_trust();
+                                }
+                            }
+                            code => {
+                                if let Some(batch) = app.import_contact_screen.as_mut().and_then(|s| s.batch.as_mut()) {
+                                    match code {
+                                        KeyCode::Up | KeyCode::Char('k') => batch.move_selection(false),
+                                        KeyCode::Down | KeyCode::Char('j') => batch.move_selection(true),
+                                        KeyCode::Char(' ') => {
+                                            batch.toggle_selected();
+                                        }
+                                        _ => {}
+                                    }
+                                }
+                            }
+                        }
+                    }
                     Screen::ImportContact => {
+                        let batch_mode = app.import_contact_screen.as_ref().is_some_and(|s| s.batch.is_some());
+                        let typing_armor = app.import_contact_screen.as_ref().is_some_and(|s| s.awaits_armor_lines());
+                        let control = key.modifiers.contains(event::KeyModifiers::CONTROL);
                         match key.code {
                             KeyCode::Esc => {
                                 app.back_to_main_menu();
                             }
+                            KeyCode::Char('b') if control => {
+                                app.toggle_batch_import();
+                            }
+                            KeyCode::Char('r') if control && batch_mode => {
+                                app.review_token_batch();
+                            }
+                            KeyCode::Char('o') if control && batch_mode => {
+                                app.open_path_picker(SaveTarget::TokenBatch);
+                            }
+                            KeyCode::Char('t') if control => {
+                                if let Some(screen) = &mut app.import_contact_screen {
+                                    screen.toggle_trust();
+                                }
+                            }
+                            KeyCode::Char('p') if control => {
+                                app.open_path_picker(SaveTarget::IntroductionList);
+                            }
+                            KeyCode::Char('l') if control && !batch_mode => {
+                                if let Some(screen) = &mut app.import_contact_screen {
+                                    screen.cycle_link();
+                                }
+                            }
+                            KeyCode::Char('d') if control && !batch_mode => {
+                                app.dismiss_linked_introduction();
+                            }
+                            KeyCode::Enter if batch_mode || typing_armor => {
+                                if let Some(screen) = &mut app.import_contact_screen {
+                                    screen.add_char('\n');
+                                }
+                            }
                             KeyCode::Char(c) if c.is_ascii() && !c.is_control() => {
                                 if let Some(screen) = &mut app.import_contact_screen {
                                     screen.add_char(c);
@@ -164,10 +602,162 @@ fn run_app<B: ratatui::backend::Backend>(
                                     screen.clear();
                                 }
                             }
+                            KeyCode::Tab => {
+                                if let Some(screen) = &mut app.import_contact_screen {
+                                    screen.cycle_temporary();
+                                }
+                            }
                             _ => {}
                         }
                     }
                     Screen::ChatList => {
+                        // Identity conflict review popup
+                        if app.chat_list_screen.as_ref().is_some_and(|s| s.conflict_review.is_some()) {
+                            match key.code {
+                                KeyCode::Esc | KeyCode::Char('!') => {
+                                    app.close_conflict_review();
+                                }
+                                KeyCode::Down | KeyCode::Char('j') => {
+                                    app.move_conflict_selection(true);
+                                }
+                                KeyCode::Up | KeyCode::Char('k') => {
+                                    app.move_conflict_selection(false);
+                                }
+                                KeyCode::Char('x') => {
+                                    app.dismiss_selected_conflict();
+                                }
+                                _ => {}
+                            }
+                            continue; // Don't process other keys while popup is shown
+                        }
+
+                        // Check if contact details popup is shown
+                        if let Some(popup) = app.chat_list_screen.as_mut().and_then(|s| s.contact_details.as_mut()) {
+                            if let Some(editor) = &mut popup.notes_editor {
+                                // Notes editor overlay
+                                match key.code {
+                                    KeyCode::Char('s') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                        app.save_contact_notes();
+                                    }
+                                    KeyCode::Esc => {
+                                        app.cancel_editing_notes();
+                                    }
+                                    KeyCode::Enter => {
+                                        editor.newline();
+                                    }
+                                    KeyCode::Backspace => {
+                                        editor.backspace();
+                                    }
+                                    KeyCode::Char(c) if !c.is_control() => {
+                                        editor.add_char(c);
+                                    }
+                                    _ => {}
+                                }
+                            } else if popup.offset_input.is_some() {
+                                // UTC offset input
+                                match key.code {
+                                    KeyCode::Enter => {
+                                        app.save_contact_utc_offset();
+                                    }
+                                    KeyCode::Esc => {
+                                        popup.offset_input = None;
+                                    }
+                                    KeyCode::Backspace => {
+                                        if let Some(input) = &mut popup.offset_input {
+                                            input.pop();
+                                        }
+                                    }
+                                    KeyCode::Char(c) => {
+                                        popup.offset_add_char(c);
+                                    }
+                                    _ => {}
+                                }
+                            } else if popup.capture_prompt.is_some() {
+                                // Capture consent: y, b (bodies), Esc
+                                if let KeyCode::Char(c) = key.code {
+                                    app.answer_capture_prompt(c);
+                                } else {
+                                    popup.capture_prompt = None;
+                                }
+                            } else if popup.confirm_delete {
+                                // Keep or discard notes when deleting the contact
+                                match key.code {
+                                    KeyCode::Char('k') => {
+                                        app.confirm_delete_contact(true);
+                                    }
+                                    KeyCode::Char('d') => {
+                                        app.confirm_delete_contact(false);
+                                    }
+                                    KeyCode::Esc => {
+                                        popup.confirm_delete = false;
+                                    }
+                                    _ => {}
+                                }
+                            } else {
+                                match key.code {
+                                    KeyCode::Char('i') | KeyCode::Esc => {
+                                        app.close_contact_details();
+                                    }
+                                    KeyCode::Char('e') => {
+                                        popup.toggle_notes_expanded();
+                                    }
+                                    KeyCode::Char('n') => {
+                                        app.start_editing_notes();
+                                    }
+                                    KeyCode::Char('h') => {
+                                        app.cycle_chat_history_limit();
+                                    }
+                                    KeyCode::Char('m') => {
+                                        app.toggle_chat_muted();
+                                    }
+                                    KeyCode::Char('s') => {
+                                        app.cycle_contact_alert_style();
+                                    }
+                                    KeyCode::Char('b') => {
+                                        app.test_contact_alert_style();
+                                    }
+                                    KeyCode::Char('v') => {
+                                        app.toggle_contact_verified();
+                                    }
+                                    KeyCode::Char('z') => {
+                                        app.start_editing_utc_offset();
+                                    }
+                                    KeyCode::Char('a') => {
+                                        app.resolve_address_change(true);
+                                    }
+                                    KeyCode::Char('g') => {
+                                        app.resolve_address_change(false);
+                                    }
+                                    KeyCode::Char('x') => {
+                                        app.request_delete_contact();
+                                    }
+                                    KeyCode::Char('k') => {
+                                        app.make_contact_permanent();
+                                    }
+                                    KeyCode::Char('r') => {
+                                        app.cycle_contact_signal(PrivacySignal::ReadReceipts);
+                                    }
+                                    KeyCode::Char('t') => {
+                                        app.cycle_contact_signal(PrivacySignal::Typing);
+                                    }
+                                    KeyCode::Char('p') => {
+                                        app.cycle_contact_signal(PrivacySignal::Presence);
+                                    }
+                                    KeyCode::Char('u') => {
+                                        app.toggle_contact_trust();
+                                    }
+                                    KeyCode::Char('c') => {
+                                        app.toggle_contact_capture();
+                                    }
+                                    KeyCode::Char('w') => {
+                                        app.show_capture_screen();
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            continue; // Don't process other keys while popup is shown
+                        }
+
                         // Check if delete confirmation popup is shown
                         if let Some(chat_list) = &app.chat_list_screen {
                             if chat_list.show_delete_confirmation {
@@ -183,12 +773,21 @@ fn run_app<B: ratatui::backend::Backend>(
                                 }
                                 continue; // Don't process other keys while popup is shown
                             }
+                            if chat_list.show_bulk_delete_confirmation {
+                                app.answer_bulk_delete(matches!(key.code, KeyCode::Char('y') | KeyCode::Enter));
+                                continue;
+                            }
                         }
 
                         // Normal chat list navigation
+                        let marking = app.chat_list_screen.as_ref().is_some_and(|s| !s.marked.is_empty());
                         match key.code {
                             KeyCode::Esc => {
-                                app.back_to_main_menu();
+                                // Esc drops the marks first
+                                let had_marks = app.clear_chat_marks();
+                                if !had_marks {
+                                    app.back_to_main_menu();
+                                }
                             }
                             KeyCode::Down | KeyCode::Char('j') => {
                                 if let Some(screen) = &mut app.chat_list_screen {
@@ -203,46 +802,347 @@ fn run_app<B: ratatui::backend::Backend>(
                             KeyCode::Enter => {
                                 app.open_selected_chat();
                             }
+                            KeyCode::Char('d') | KeyCode::Delete if marking => {
+                                app.request_bulk_delete();
+                            }
                             KeyCode::Char('d') | KeyCode::Delete => {
                                 app.show_delete_confirmation();
                             }
+                            KeyCode::Char(' ') => {
+                                app.toggle_chat_marked();
+                            }
+                            KeyCode::Char('V') => {
+                                app.mark_all_chats();
+                            }
+                            KeyCode::Char('a') => {
+                                app.archive_marked_chats();
+                            }
+                            KeyCode::Char('r') => {
+                                app.mark_marked_chats_read();
+                            }
+                            KeyCode::Char('m') => {
+                                app.mute_marked_chats();
+                            }
+                            KeyCode::Char('i') => {
+                                app.show_contact_details();
+                            }
+                            KeyCode::Char('!') => {
+                                app.open_conflict_review();
+                            }
+                            KeyCode::Char(c @ '1'..='9') => {
+                                app.open_chat_number(c as usize - '0' as usize);
+                            }
+                            KeyCode::Tab => {
+                                app.toggle_recent_chat_selection();
+                            }
+                            KeyCode::Char('u') | KeyCode::Char('U') => {
+                                app.undo_last_delete(chrono::Utc::now());
+                            }
+                            _ => {}
+                        }
+                    }
+                    Screen::ChatView if app.chat_view_screen.as_ref().is_some_and(|s| s.template_picker.is_some()) => {
+                        // Template picker: type to filter, Enter inserts
+                        let match_count = app.template_picker_matches().len();
+                        let Some(picker) = app.chat_view_screen.as_mut().and_then(|s| s.template_picker.as_mut()) else {
+                            continue;
+                        };
+                        match key.code {
+                            KeyCode::Esc => {
+                                app.cancel_template_picker();
+                            }
+                            KeyCode::Enter => {
+                                app.insert_selected_template();
+                            }
+                            KeyCode::Down => {
+                                picker.next(match_count);
+                            }
+                            KeyCode::Up => {
+                                picker.previous(match_count);
+                            }
+                            KeyCode::Backspace => {
+                                // Deleting past the query removes the '%' as well
+                                if !picker.pop_char()
+                                    && let Some(screen) = &mut app.chat_view_screen
+                                {
+                                    screen.close_template_picker();
+                                }
+                            }
+                            KeyCode::Char(c) if !c.is_control() => {
+                                picker.push_char(c);
+                            }
+                            _ => {}
+                        }
+                    }
+                    Screen::ChatView if app.chat_view_screen.as_ref().is_some_and(|s| s.duplicate_prompt) => {
+                        // "send duplicate? [y/N]": only 'y' sends
+                        app.answer_duplicate_prompt(matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')));
+                    }
+                    Screen::ChatView if app.chat_view_screen.as_ref().is_some_and(|s| s.expiry_warning.is_some()) => {
+                        // Token expiry warning: Enter sends, r sends and asks for a fresh token, Esc keeps the input
+                        match key.code {
+                            KeyCode::Enter => app.answer_expiry_warning(ExpiryChoice::SendAnyway),
+                            KeyCode::Char('r') | KeyCode::Char('R') => app.answer_expiry_warning(ExpiryChoice::SendAndRequestRefresh),
+                            KeyCode::Esc => app.answer_expiry_warning(ExpiryChoice::Cancel),
+                            _ => {}
+                        }
+                    }
+                    Screen::ChatView if app.chat_view_screen.as_ref().is_some_and(|s| s.send_preview.is_some()) => {
+                        // Send preview: Enter sends, Esc keeps the input
+                        match key.code {
+                            KeyCode::Enter => app.answer_send_preview(true),
+                            KeyCode::Esc => app.answer_send_preview(false),
+                            _ => {}
+                        }
+                    }
+                    Screen::ChatView if app.chat_view_screen.as_ref().is_some_and(|s| s.failed_review.is_some()) => {
+                        // Failed messages review: mark some or re-queue all
+                        let review = app.chat_view_screen.as_mut().and_then(|s| s.failed_review.as_mut());
+                        match key.code {
+                            KeyCode::Esc => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.failed_review = None;
+                                }
+                            }
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                if let Some(review) = review {
+                                    review.move_selection(true);
+                                }
+                            }
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                if let Some(review) = review {
+                                    review.move_selection(false);
+                                }
+                            }
+                            KeyCode::Char(' ') => {
+                                if let Some(review) = review {
+                                    review.toggle_marked();
+                                }
+                            }
+                            KeyCode::Enter => app.requeue_failed(false),
+                            KeyCode::Char('a') | KeyCode::Char('A') => app.requeue_failed(true),
+                            _ => {}
+                        }
+                    }
+                    Screen::ChatView if app.chat_view_screen.as_ref().is_some_and(|s| s.pinned_focus.is_some()) => {
+                        // Pinned strip has focus
+                        match key.code {
+                            KeyCode::Esc => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.unfocus_pinned_strip();
+                                }
+                            }
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                with_chat_view(app, |screen, chat| screen.next_pinned(chat));
+                            }
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.previous_pinned();
+                                }
+                            }
+                            KeyCode::Enter => {
+                                with_chat_view(app, |screen, chat| screen.jump_to_focused_pinned(chat));
+                            }
+                            KeyCode::Char('p') | KeyCode::Delete => {
+                                app.unpin_focused();
+                            }
+                            _ => {}
+                        }
+                    }
+                    Screen::ChatView if app.chat_view_screen.as_ref().is_some_and(|s| s.is_selecting()) => {
+                        // Selection mode: act on one message
+                        match key.code {
+                            KeyCode::Esc => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.end_selection();
+                                }
+                            }
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                with_chat_view(app, |screen, chat| screen.select_next(chat));
+                            }
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                with_chat_view(app, |screen, chat| screen.select_previous(chat));
+                            }
+                            KeyCode::Char('p') => {
+                                app.toggle_pin_selected();
+                            }
+                            KeyCode::Char('*') => {
+                                app.toggle_star_selected();
+                            }
+                            KeyCode::Char('s') | KeyCode::Char('S') if !key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                app.save_selected_content();
+                            }
+                            KeyCode::Char('e') | KeyCode::Char('E') if !key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                app.start_edit_selected();
+                            }
+                            KeyCode::Tab => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.toggle_message_details();
+                                }
+                            }
                             _ => {}
                         }
                     }
                     Screen::ChatView => {
                         match key.code {
+                            KeyCode::Esc if app.chat_view_screen.as_ref().is_some_and(|s| s.editing.is_some()) => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.cancel_edit();
+                                    screen.set_status("Edit cancelled".to_string());
+                                }
+                            }
                             KeyCode::Esc => {
                                 app.back_to_chat_list();
                             }
+                            KeyCode::Char('s') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                with_chat_view(app, |screen, chat| screen.start_selection(chat));
+                            }
+                            KeyCode::Char('p') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                with_chat_view(app, |screen, chat| screen.focus_pinned_strip(chat));
+                            }
+                            KeyCode::Char('t') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                with_chat_view(app, |screen, chat| screen.toggle_starred_only(chat));
+                            }
+                            KeyCode::Char('e') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                app.export_chat_jsonl();
+                            }
+                            KeyCode::Char('g') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                app.export_chat_archive();
+                            }
+                            KeyCode::Char('y') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                app.copy_saved_path();
+                            }
+                            KeyCode::Char('r') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                app.delivery_banner_action(chrono::Utc::now());
+                            }
+                            KeyCode::Char('o') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                app.preview_chat_input();
+                            }
+                            KeyCode::Char('f') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                app.open_failed_review();
+                            }
+                            KeyCode::Char('l') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.toggle_protection_markers();
+                                }
+                            }
+                            KeyCode::Char('%') if app.open_template_picker() => {}
+                            KeyCode::Char('`') if app.switch_to_previous_chat() => {}
                             KeyCode::Char(c) if c.is_ascii() && !c.is_control() => {
                                 if let Some(screen) = &mut app.chat_view_screen {
                                     screen.add_char(c);
                                 }
+                                app.note_typing();
                             }
                             KeyCode::Backspace => {
                                 if let Some(screen) = &mut app.chat_view_screen {
                                     screen.backspace();
                                 }
                             }
+                            KeyCode::Left => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.cursor_left();
+                                }
+                            }
+                            KeyCode::Right => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.cursor_right();
+                                }
+                            }
+                            KeyCode::Home => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.cursor_home();
+                                }
+                            }
+                            KeyCode::End => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.cursor_end();
+                                }
+                            }
+                            KeyCode::Enter if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.newline();
+                                }
+                            }
                             KeyCode::Enter => {
                                 app.send_message_in_chat();
                             }
+                            KeyCode::Tab => {
+                                if let Some(screen) = &mut app.chat_view_screen {
+                                    screen.toggle_message_details();
+                                }
+                            }
                             KeyCode::Up => {
                                 if let Some(screen) = &mut app.chat_view_screen {
                                     screen.scroll_up();
                                 }
                             }
                             KeyCode::Down => {
-                                if let Some(screen) = &mut app.chat_view_screen {
-                                    // Calculate max offset based on message count
-                                    let max_offset = app.app_state.chats
-                                        .iter()
-                                        .find(|c| c.contact_uid == screen.contact_uid)
-                                        .map(|c| c.messages.len().saturating_sub(10))
-                                        .unwrap_or(0);
+                                with_chat_view(app, |screen, chat| {
+                                    // Calculate max offset based on the messages shown
+                                    let max_offset = screen
+                                        .visible_messages(chat)
+                                        .len()
+                                        .saturating_sub(CHAT_VIEW_PAGE_SIZE);
                                     screen.scroll_down(max_offset);
+                                });
+                            }
+                            _ => {}
+                        }
+                    }
+                    Screen::Settings if app.settings_screen.as_ref().is_some_and(|s| s.template_editor.is_some()) => {
+                        // Template editor: Tab switches name/body, Enter adds a line, Ctrl+S saves
+                        let Some(editor) = app.settings_screen.as_mut().and_then(|s| s.template_editor.as_mut()) else {
+                            continue;
+                        };
+                        match key.code {
+                            KeyCode::Esc => {
+                                app.cancel_template_editor();
+                            }
+                            KeyCode::Char('s') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                                app.save_template_editor();
+                            }
+                            KeyCode::Tab | KeyCode::BackTab => {
+                                editor.toggle_field();
+                            }
+                            KeyCode::Enter => {
+                                editor.newline();
+                            }
+                            KeyCode::Backspace => {
+                                editor.backspace();
+                            }
+                            KeyCode::Char(c) if !c.is_control() => {
+                                editor.add_char(c);
+                            }
+                            _ => {}
+                        }
+                    }
+                    Screen::Settings if app.settings_screen.as_ref().is_some_and(|s| s.template_selected.is_some()) => {
+                        // Template list
+                        let template_count = app.app_state.settings.templates.len();
+                        match key.code {
+                            KeyCode::Esc => {
+                                app.close_template_manager();
+                            }
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                if let Some(screen) = &mut app.settings_screen {
+                                    screen.next_template(template_count);
                                 }
                             }
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                if let Some(screen) = &mut app.settings_screen {
+                                    screen.previous_template(template_count);
+                                }
+                            }
+                            KeyCode::Char('n') => {
+                                app.new_template();
+                            }
+                            KeyCode::Enter | KeyCode::Char('e') => {
+                                app.edit_selected_template();
+                            }
+                            KeyCode::Char('d') | KeyCode::Delete => {
+                                app.delete_selected_template();
+                            }
                             _ => {}
                         }
                     }
@@ -251,7 +1151,23 @@ fn run_app<B: ratatui::backend::Backend>(
                             KeyCode::Esc => {
                                 app.back_to_main_menu();
                             }
-                            KeyCode::Char(c) if c.is_ascii_digit() => {
+                            KeyCode::Down | KeyCode::Tab => {
+                                if let Some(screen) = &mut app.settings_screen {
+                                    screen.next_field();
+                                }
+                            }
+                            KeyCode::Up | KeyCode::BackTab => {
+                                if let Some(screen) = &mut app.settings_screen {
+                                    screen.previous_field();
+                                }
+                            }
+                            KeyCode::Char('a')
+                                if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                                    && app.settings_screen.as_ref().is_some_and(|s| s.selected_field == SettingsScreen::FIELD_HISTORY_LIMIT) =>
+                            {
+                                app.apply_history_limit_now();
+                            }
+                            KeyCode::Char(c) => {
                                 if let Some(screen) = &mut app.settings_screen {
                                     screen.add_char(c);
                                 }
@@ -261,24 +1177,12 @@ fn run_app<B: ratatui::backend::Backend>(
                                     screen.backspace();
                                 }
                             }
+                            KeyCode::Enter if app.settings_screen.as_ref().is_some_and(|s| s.selected_field == SettingsScreen::FIELD_TEMPLATES) => {
+                                app.open_template_manager();
+                            }
                             KeyCode::Enter => {
-                                // Validate input first
-                                let validated_minutes = app.settings_screen.as_mut()
-                                    .and_then(|screen| screen.validate());
-
-                                // If valid, update app_state and save
-                                if let Some(minutes) = validated_minutes {
-                                    app.app_state.settings.retry_interval_minutes = minutes;
-                                    app.app_state.settings.global_retry_interval_ms = (minutes as u64) * 60 * 1000;
-
-                                    // Save app state
-                                    let _ = app.save_state();
-
-                                    // Update screen with success message
-                                    if let Some(screen) = &mut app.settings_screen {
-                                        screen.set_saved_message(minutes);
-                                    }
-                                }
+                                // Validate all fields, update app_state and save
+                                app.save_settings();
                             }
                             KeyCode::Delete => {
                                 if let Some(screen) = &mut app.settings_screen {
@@ -288,19 +1192,250 @@ fn run_app<B: ratatui::backend::Backend>(
                             _ => {}
                         }
                     }
+                    Screen::Diagnostics if app.diagnostics_screen.as_ref().is_some_and(|s| s.show_error_log) => {
+                        // Error log overlay
+                        if matches!(key.code, KeyCode::Esc | KeyCode::Char('e'))
+                            && let Some(screen) = &mut app.diagnostics_screen
+                        {
+                            screen.show_error_log = false;
+                        }
+                    }
+                    Screen::Diagnostics if app.diagnostics_screen.as_ref().is_some_and(|s| s.queue_rows.is_some()) => {
+                        // Queue contents overlay
+                        if matches!(key.code, KeyCode::Esc | KeyCode::Char('q')) {
+                            app.toggle_queue_view();
+                        }
+                    }
+                    Screen::Diagnostics if app.diagnostics_screen.as_ref().is_some_and(|s| s.probe_picker.is_some()) => {
+                        // Contact picker for a peer-assisted reachability test
+                        let candidates: Vec<String> = app.probe_candidates().iter().map(|c| c.uid.clone()).collect();
+                        let Some(screen) = &mut app.diagnostics_screen else {
+                            continue;
+                        };
+                        let selected = screen.probe_picker.unwrap_or(0);
+                        match key.code {
+                            KeyCode::Up => {
+                                screen.probe_picker = Some(selected.saturating_sub(1));
+                            }
+                            KeyCode::Down => {
+                                screen.probe_picker = Some((selected + 1).min(candidates.len().saturating_sub(1)));
+                            }
+                            KeyCode::Enter => {
+                                screen.probe_picker = None;
+                                if let Some(uid) = candidates.get(selected) {
+                                    app.request_peer_probe(uid);
+                                }
+                            }
+                            KeyCode::Esc => {
+                                screen.probe_picker = None;
+                            }
+                            _ => {}
+                        }
+                    }
+                    Screen::Diagnostics if app.diagnostics_screen.as_ref().is_some_and(|s| s.port_prompt.is_some()) => {
+                        // Alternate port prompt
+                        let Some(screen) = &mut app.diagnostics_screen else {
+                            continue;
+                        };
+                        match key.code {
+                            KeyCode::Enter => {
+                                if let Err(e) = screen.submit_port_prompt() {
+                                    screen.set_status_message(e);
+                                }
+                            }
+                            KeyCode::Esc => {
+                                screen.port_prompt = None;
+                            }
+                            KeyCode::Backspace => {
+                                screen.port_prompt_pop();
+                            }
+                            KeyCode::Char(c) => {
+                                screen.port_prompt_push(c);
+                            }
+                            _ => {}
+                        }
+                    }
+                    Screen::Diagnostics if app.diagnostics_screen.as_ref().is_some_and(|s| s.confirm_delete) => {
+                        // Confirm deletion of the active mapping
+                        match key.code {
+                            KeyCode::Char('y') | KeyCode::Enter => {
+                                app.confirm_delete_mapping();
+                            }
+                            KeyCode::Char('n') | KeyCode::Esc => {
+                                app.cancel_delete_mapping();
+                            }
+                            _ => {}
+                        }
+                    }
                     Screen::Diagnostics => {
                         match key.code {
                             KeyCode::Esc => {
                                 app.back_to_main_menu();
                             }
                             KeyCode::Char('r') | KeyCode::F(5) => {
+                                let busy = app.diagnostics_busy();
+                                if let Some(screen) = &mut app.diagnostics_screen
+                                    && !screen.is_refreshing
+                                    && !busy
+                                {
+                                    screen.start_refresh();
+                                    app.trigger_diagnostics_refresh();
+                                }
+                            }
+                            KeyCode::Char('1') => {
+                                app.run_single_protocol_test(MappingProtocol::PCP);
+                            }
+                            KeyCode::Char('2') => {
+                                app.run_single_protocol_test(MappingProtocol::NATPMP);
+                            }
+                            KeyCode::Char('3') => {
+                                app.run_single_protocol_test(MappingProtocol::UPnP);
+                            }
+                            KeyCode::Char('d') => {
+                                app.request_delete_mapping();
+                            }
+                            KeyCode::Char('e') => {
                                 if let Some(screen) = &mut app.diagnostics_screen {
-                                    if !screen.is_refreshing {
-                                        screen.start_refresh();
-                                        app.trigger_diagnostics_refresh();
+                                    screen.show_error_log = true;
+                                }
+                            }
+                            KeyCode::Char('s') => {
+                                app.show_snapshots_screen();
+                            }
+                            KeyCode::Char('m') => {
+                                app.show_maintenance_screen();
+                            }
+                            KeyCode::Char('v') => {
+                                app.verify_journal();
+                            }
+                            KeyCode::Char('i') => {
+                                app.run_integrity_report();
+                            }
+                            KeyCode::Char('q') => {
+                                app.toggle_queue_view();
+                            }
+                            KeyCode::Char('l') => {
+                                app.open_log_viewer();
+                            }
+                            KeyCode::Char('j') => {
+                                app.open_path_picker(SaveTarget::JournalExport);
+                            }
+                            KeyCode::Char('a') => {
+                                app.open_path_picker(SaveTarget::ArchiveVerify);
+                            }
+                            KeyCode::Char('c') => {
+                                let has_candidates = !app.probe_candidates().is_empty();
+                                if let Some(screen) = &mut app.diagnostics_screen {
+                                    if has_candidates {
+                                        screen.probe_picker = Some(0);
+                                    } else {
+                                        screen.set_status_message("No contact to ask for a reachability test".to_string());
                                     }
                                 }
                             }
+                            KeyCode::Char('p') => {
+                                if !app.diagnostics_busy()
+                                    && let Some(screen) = &mut app.diagnostics_screen
+                                {
+                                    screen.start_port_prompt();
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    Screen::Snapshots => {
+                        let Some(screen) = &mut app.snapshots_screen else {
+                            continue;
+                        };
+                        let viewing_diff = screen.comparison.is_some();
+                        match key.code {
+                            KeyCode::Esc if viewing_diff => {
+                                screen.close_comparison();
+                            }
+                            KeyCode::Esc => {
+                                app.show_diagnostics_screen();
+                            }
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                screen.move_selection(false);
+                            }
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                screen.move_selection(true);
+                            }
+                            KeyCode::Char(' ') if !viewing_diff => {
+                                screen.toggle_mark();
+                            }
+                            KeyCode::Enter if !viewing_diff => {
+                                app.compare_snapshots();
+                            }
+                            KeyCode::Char('s') if !viewing_diff => {
+                                app.take_snapshot();
+                            }
+                            KeyCode::Char('x') if screen.diff().is_some() => {
+                                app.open_path_picker(SaveTarget::SnapshotDiff);
+                            }
+                            _ => {}
+                        }
+                    }
+                    Screen::Maintenance => {
+                        let count = app.undo.entries().len();
+                        let Some(screen) = &mut app.maintenance_screen else {
+                            continue;
+                        };
+                        match key.code {
+                            KeyCode::Esc => {
+                                app.show_diagnostics_screen();
+                            }
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                screen.move_selection(false, count);
+                            }
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                screen.move_selection(true, count);
+                            }
+                            KeyCode::Char('u') | KeyCode::Enter => {
+                                app.undo_selected_delete();
+                            }
+                            _ => {}
+                        }
+                    }
+                    Screen::Capture => {
+                        let Some(screen) = &mut app.capture_screen else {
+                            continue;
+                        };
+                        match key.code {
+                            KeyCode::Esc => {
+                                app.close_capture_screen();
+                            }
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                screen.scroll_by(-1);
+                            }
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                screen.scroll_by(1);
+                            }
+                            KeyCode::PageUp => {
+                                screen.scroll_by(-(CaptureScreen::PAGE_LINES as isize));
+                            }
+                            KeyCode::PageDown => {
+                                screen.scroll_by(CaptureScreen::PAGE_LINES as isize);
+                            }
+                            KeyCode::Char('r') => {
+                                screen.reload();
+                            }
+                            _ => {}
+                        }
+                    }
+                    Screen::Recovery => {
+                        match key.code {
+                            KeyCode::Enter | KeyCode::Esc => {
+                                app.dismiss_recovery_screen();
+                            }
+                            KeyCode::Char('d') => {
+                                if let Some(screen) = &mut app.recovery_screen {
+                                    screen.toggle_details();
+                                }
+                            }
+                            KeyCode::Char('l') => {
+                                app.open_recovery_logs();
+                            }
                             _ => {}
                         }
                     }
@@ -313,3 +1448,13 @@ fn run_app<B: ratatui::backend::Backend>(
         }
     }
 }
+
+/// Run `f` with the chat view screen and the chat it shows
+fn with_chat_view(app: &mut App, f: impl FnOnce(&mut ChatViewScreen, &Chat)) {
+    let Some(screen) = app.chat_view_screen.as_mut() else {
+        return;
+    };
+    if let Some(chat) = app.app_state.chat_by_uid(&screen.contact_uid) {
+        f(screen, chat);
+    }
+}
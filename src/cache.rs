@@ -0,0 +1,385 @@
+//! Size-bounded in-memory caches with hit, miss and eviction counters
+//!
+//! Per-peer and per-source state kept in memory (learned endpoints, rate
+//! limiter buckets, probe attempts) used to be plain `HashMap`s that only
+//! ever grew, so an instance seeing many distinct sources (a port scan) kept
+//! an entry for each of them until it exited. `BoundedCache` holds at most
+//! `max_entries`: inserting past that drops the least recently used entry.
+//! An optional time to live drops entries that were not written for that
+//! long. Callers only ever keep state here that may be lost, so an evicted
+//! or expired entry reads as a miss, the same as one never stored.
+//!
+//! Entries are spread over shards by key hash, each behind its own mutex, so
+//! threads touching different keys rarely wait for each other. Recency is
+//! kept per shard: the entry dropped is the least recently used of its
+//! shard, which with evenly spread keys is close to the global one.
+//!
+//! Every cache registers itself under a name; `cache_stats` lists the live
+//! ones for Diagnostics.
+
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+
+/// Shards of a cache unless it is too small for them
+pub const DEFAULT_CACHE_SHARDS: usize = 16;
+
+/// Counters and size of one cache (or of several, summed)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// Name the cache was created with
+    pub name: String,
+    /// Entries held now
+    pub entries: usize,
+    /// Most entries the cache holds
+    pub capacity: usize,
+    /// Lookups that found a live entry
+    pub hits: u64,
+    /// Lookups that found nothing (including evicted and expired entries)
+    pub misses: u64,
+    /// Entries dropped to make room
+    pub evictions: u64,
+    /// Entries dropped because their time to live ran out
+    pub expirations: u64,
+}
+
+impl CacheStats {
+    /// Sum of `stats`, named "all caches"
+    pub fn total(stats: &[CacheStats]) -> CacheStats {
+        stats.iter().fold(CacheStats { name: "all caches".to_string(), ..CacheStats::default() }, |acc, s| CacheStats {
+            name: acc.name,
+            entries: acc.entries + s.entries,
+            capacity: acc.capacity + s.capacity,
+            hits: acc.hits + s.hits,
+            misses: acc.misses + s.misses,
+            evictions: acc.evictions + s.evictions,
+            expirations: acc.expirations + s.expirations,
+        })
+    }
+
+    /// Share of lookups that hit, in percent (`None` before any lookup)
+    pub fn hit_rate_percent(&self) -> Option<u64> {
+        let lookups = self.hits + self.misses;
+        (lookups > 0).then(|| self.hits * 100 / lookups)
+    }
+
+    /// One-line summary for Diagnostics, e.g. "12/4096 entries, 80% hits, 3 evicted"
+    pub fn summary(&self) -> String {
+        let hits = match self.hit_rate_percent() {
+            Some(percent) => format!("{}% hits", percent),
+            None => "no lookups".to_string(),
+        };
+        format!(
+            "{}/{} entries, {}, {} evicted, {} expired",
+            self.entries, self.capacity, hits, self.evictions, self.expirations
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    written_at: DateTime<Utc>,
+    /// Position in the shard's recency order
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct Shard<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    /// Keys by last use, least recent first
+    order: BTreeMap<u64, K>,
+    next_tick: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> Shard<K, V> {
+    fn new() -> Self {
+        Self { entries: HashMap::new(), order: BTreeMap::new(), next_tick: 0 }
+    }
+
+    fn remove(&mut self, key: &K) -> Option<Entry<V>> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.tick);
+        Some(entry)
+    }
+
+    /// Move `key` to the most recent end
+    fn touch(&mut self, key: &K) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.order.remove(&entry.tick);
+            entry.tick = self.next_tick;
+            self.order.insert(self.next_tick, key.clone());
+            self.next_tick += 1;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    expirations: AtomicU64,
+}
+
+impl Counters {
+    fn bump(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Anything `cache_stats` can ask for its numbers
+trait StatsSource: Send + Sync {
+    fn stats(&self) -> CacheStats;
+}
+
+/// Live caches, for `cache_stats`
+fn registry() -> &'static Mutex<Vec<Weak<dyn StatsSource>>> {
+    static REGISTRY: OnceLock<Mutex<Vec<Weak<dyn StatsSource>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Stats of every cache still alive, in creation order
+pub fn cache_stats() -> Vec<CacheStats> {
+    let mut caches = registry().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    caches.retain(|cache| cache.strong_count() > 0);
+    caches.iter().filter_map(Weak::upgrade).map(|cache| cache.stats()).collect()
+}
+
+struct Inner<K, V> {
+    name: String,
+    shards: Box<[Mutex<Shard<K, V>>]>,
+    shard_capacity: usize,
+    ttl: Option<Duration>,
+    hasher: std::collections::hash_map::RandomState,
+    counters: Counters,
+}
+
+impl<K, V> Inner<K, V> {
+    // State here may be lost anyway; a panic elsewhere must not stop the cache
+    fn lock(shard: &Mutex<Shard<K, V>>) -> MutexGuard<'_, Shard<K, V>> {
+        shard.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<K: Send, V: Send> StatsSource for Inner<K, V> {
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            name: self.name.clone(),
+            entries: self.shards.iter().map(|shard| Self::lock(shard).entries.len()).sum(),
+            capacity: self.shard_capacity * self.shards.len(),
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+            expirations: self.counters.expirations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Thread-safe LRU map of at most `capacity` entries, with an optional time to live
+///
+/// All methods take `&self`; share it with an `Arc`. Methods ending in `_at`
+/// take the current time so limiters can be tested with a fixed clock; the
+/// others use `Utc::now()`.
+pub struct BoundedCache<K, V> {
+    inner: Arc<Inner<K, V>>,
+}
+
+impl<K, V> BoundedCache<K, V>
+where
+    K: Hash + Eq + Clone + Send + 'static,
+    V: Send + 'static,
+{
+    /// Create a cache of at most `max_entries`, sharded `DEFAULT_CACHE_SHARDS` ways
+    ///
+    /// # Arguments
+    /// * `name` - Shown in Diagnostics
+    /// * `ttl` - Entries not written for this long read as missing
+    pub fn new(name: &str, max_entries: usize, ttl: Option<Duration>) -> Self {
+        Self::with_shards(name, max_entries, ttl, DEFAULT_CACHE_SHARDS)
+    }
+
+    /// Create a cache split into `shards` (fewer if `max_entries` is smaller)
+    ///
+    /// Each shard holds `max_entries / shards`, so the capacity may round
+    /// down; one shard gives exact least-recently-used order.
+    pub fn with_shards(name: &str, max_entries: usize, ttl: Option<Duration>, shards: usize) -> Self {
+        let max_entries = max_entries.max(1);
+        let shards = shards.clamp(1, max_entries);
+        let inner = Arc::new(Inner {
+            name: name.to_string(),
+            shards: (0..shards).map(|_| Mutex::new(Shard::new())).collect(),
+            shard_capacity: max_entries / shards,
+            ttl,
+            hasher: Default::default(),
+            counters: Counters::default(),
+        });
+        let source: Arc<dyn StatsSource> = inner.clone();
+        registry().lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(Arc::downgrade(&source));
+        Self { inner }
+    }
+
+    fn shard(&self, key: &K) -> MutexGuard<'_, Shard<K, V>> {
+        let index = (self.inner.hasher.hash_one(key) % self.inner.shards.len() as u64) as usize;
+        Inner::lock(&self.inner.shards[index])
+    }
+
+    fn expired(&self, entry: &Entry<V>, now: DateTime<Utc>) -> bool {
+        self.inner.ttl.is_some_and(|ttl| now >= entry.written_at + ttl)
+    }
+
+    /// Live entry for `key` in `shard`, dropping it if it expired; counts the hit or miss
+    fn lookup<'a>(&self, shard: &'a mut Shard<K, V>, key: &K, now: DateTime<Utc>) -> Option<&'a mut Entry<V>> {
+        let expired = match shard.entries.get(key) {
+            None => {
+                Counters::bump(&self.inner.counters.misses);
+                return None;
+            }
+            Some(entry) => self.expired(entry, now),
+        };
+        if expired {
+            shard.remove(key);
+            Counters::bump(&self.inner.counters.expirations);
+            Counters::bump(&self.inner.counters.misses);
+            return None;
+        }
+        Counters::bump(&self.inner.counters.hits);
+        shard.touch(key);
+        shard.entries.get_mut(key)
+    }
+
+    /// Make room for one more entry in `shard`
+    fn make_room(&self, shard: &mut Shard<K, V>, now: DateTime<Utc>) {
+        while shard.entries.len() >= self.inner.shard_capacity {
+            let Some((_, oldest)) = shard.order.pop_first() else {
+                return;
+            };
+            if let Some(entry) = shard.entries.remove(&oldest) {
+                let counter = if self.expired(&entry, now) {
+                    &self.inner.counters.expirations
+                } else {
+                    &self.inner.counters.evictions
+                };
+                Counters::bump(counter);
+            }
+        }
+    }
+
+    /// Call `f` with the live value for `key` at `now`, if any
+    pub fn get_with_at<R>(&self, key: &K, now: DateTime<Utc>, f: impl FnOnce(&V) -> R) -> Option<R> {
+        let mut shard = self.shard(key);
+        self.lookup(&mut shard, key, now).map(|entry| f(&entry.value))
+    }
+
+    /// Clone of the live value for `key` at `now`
+    pub fn get_at(&self, key: &K, now: DateTime<Utc>) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.get_with_at(key, now, V::clone)
+    }
+
+    /// Clone of the live value for `key`
+    pub fn get(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.get_at(key, Utc::now())
+    }
+
+    /// Store `value` for `key`, written at `now`
+    ///
+    /// # Returns
+    /// The value it replaced, if that was still live
+    pub fn insert_at(&self, key: K, value: V, now: DateTime<Utc>) -> Option<V> {
+        let mut shard = self.shard(&key);
+        let previous = shard.remove(&key).filter(|entry| !self.expired(entry, now)).map(|entry| entry.value);
+        self.make_room(&mut shard, now);
+        let tick = shard.next_tick;
+        shard.next_tick += 1;
+        shard.order.insert(tick, key.clone());
+        shard.entries.insert(key, Entry { value, written_at: now, tick });
+        previous
+    }
+
+    /// Store `value` for `key`, written now
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.insert_at(key, value, Utc::now())
+    }
+
+    /// Update the value for `key` in place, starting from `default()` if it
+    /// is missing, evicted or expired
+    ///
+    /// The entry counts as written at `now`.
+    pub fn update_at<R>(&self, key: K, now: DateTime<Utc>, default: impl FnOnce() -> V, f: impl FnOnce(&mut V) -> R) -> R {
+        let mut shard = self.shard(&key);
+        if let Some(entry) = self.lookup(&mut shard, &key, now) {
+            entry.written_at = now;
+            return f(&mut entry.value);
+        }
+        self.make_room(&mut shard, now);
+        let mut value = default();
+        let result = f(&mut value);
+        let tick = shard.next_tick;
+        shard.next_tick += 1;
+        shard.order.insert(tick, key.clone());
+        shard.entries.insert(key, Entry { value, written_at: now, tick });
+        result
+    }
+
+    /// Remove the entry for `key`
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.shard(key).remove(key).map(|entry| entry.value)
+    }
+
+    /// Keep only the entries for which `keep` returns true
+    ///
+    /// Entries removed here are not counted as evictions.
+    pub fn retain(&self, mut keep: impl FnMut(&K, &mut V) -> bool) {
+        for shard in self.inner.shards.iter() {
+            let mut shard = Inner::lock(shard);
+            let Shard { entries, order, .. } = &mut *shard;
+            entries.retain(|key, entry| {
+                let kept = keep(key, &mut entry.value);
+                if !kept {
+                    order.remove(&entry.tick);
+                }
+                kept
+            });
+        }
+    }
+
+    /// Entries held, expired ones not yet dropped included
+    pub fn len(&self) -> usize {
+        self.inner.shards.iter().map(|shard| Inner::lock(shard).entries.len()).sum()
+    }
+
+    /// Whether the cache holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Most entries the cache holds
+    pub fn capacity(&self) -> usize {
+        self.inner.shard_capacity * self.inner.shards.len()
+    }
+
+    /// Size and counters of this cache
+    pub fn stats(&self) -> CacheStats {
+        self.inner.stats()
+    }
+}
+
+impl<K, V> fmt::Debug for BoundedCache<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoundedCache")
+            .field("name", &self.inner.name)
+            .field("shards", &self.inner.shards.len())
+            .field("shard_capacity", &self.inner.shard_capacity)
+            .field("ttl", &self.inner.ttl)
+            .finish()
+    }
+}
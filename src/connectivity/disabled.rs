@@ -0,0 +1,107 @@
+//! Stand-ins for port mapping protocols left out of the build
+//!
+//! Without the `connectivity-pcp-natpmp` or `connectivity-upnp` feature the
+//! matching module is replaced by one of these, with the same public
+//! functions. Every attempt fails with `MappingError::NotSupported`, so the
+//! orchestrator, the managers and diagnostics treat the protocol as one the
+//! gateway does not speak and move on to the next strategy.
+
+#[cfg(not(feature = "connectivity-pcp-natpmp"))]
+macro_rules! gateway_protocol {
+    ($name:literal, $mapping:ident, $with_protocol:ident, $with_report:ident, $probe:ident) => {
+        use crate::connectivity::types::{GatewayProbeReport, IpProtocol, MappingError, PortMappingResult};
+        use std::net::SocketAddr;
+        use std::time::Duration;
+
+        #[doc = concat!("Always fails: ", $name, " is not compiled in")]
+        pub async fn $mapping(local_port: u16, lifetime_secs: u32) -> Result<PortMappingResult, MappingError> {
+            $with_protocol(local_port, lifetime_secs, IpProtocol::TCP).await
+        }
+
+        #[doc = concat!("Always fails: ", $name, " is not compiled in")]
+        pub async fn $with_protocol(
+            local_port: u16,
+            lifetime_secs: u32,
+            protocol: IpProtocol,
+        ) -> Result<PortMappingResult, MappingError> {
+            $with_report(local_port, lifetime_secs, protocol).await.result
+        }
+
+        #[doc = concat!("Always fails: ", $name, " is not compiled in")]
+        pub async fn $with_report(_local_port: u16, _lifetime_secs: u32, _protocol: IpProtocol) -> GatewayProbeReport {
+            tracing::debug!("{} mapping skipped: not compiled in", $name);
+            GatewayProbeReport::failed(MappingError::NotSupported)
+        }
+
+        #[doc = concat!("Always fails without probing: ", $name, " is not compiled in")]
+        pub async fn $probe(
+            _servers: &[SocketAddr],
+            local_port: u16,
+            lifetime_secs: u32,
+            protocol: IpProtocol,
+            _timeout: Duration,
+        ) -> GatewayProbeReport {
+            $with_report(local_port, lifetime_secs, protocol).await
+        }
+    };
+}
+
+/// PCP stand-in (feature `connectivity-pcp-natpmp` off)
+#[cfg(not(feature = "connectivity-pcp-natpmp"))]
+pub mod pcp {
+    gateway_protocol!(
+        "PCP",
+        try_pcp_mapping,
+        try_pcp_mapping_with_protocol,
+        try_pcp_mapping_with_report,
+        probe_pcp_gateways
+    );
+}
+
+/// NAT-PMP stand-in (feature `connectivity-pcp-natpmp` off)
+#[cfg(not(feature = "connectivity-pcp-natpmp"))]
+pub mod natpmp {
+    gateway_protocol!(
+        "NAT-PMP",
+        try_natpmp_mapping,
+        try_natpmp_mapping_with_protocol,
+        try_natpmp_mapping_with_report,
+        probe_natpmp_gateways
+    );
+}
+
+/// UPnP stand-in (feature `connectivity-upnp` off)
+#[cfg(not(feature = "connectivity-upnp"))]
+pub mod upnp {
+    use crate::connectivity::types::{IpProtocol, MappingError, PortMappingResult};
+    use std::time::Duration;
+
+    /// Always fails: UPnP is not compiled in
+    pub async fn try_upnp_mapping(local_port: u16, lifetime_secs: u32) -> Result<PortMappingResult, MappingError> {
+        try_upnp_mapping_with_protocol(local_port, lifetime_secs, IpProtocol::TCP).await
+    }
+
+    /// Always fails: UPnP is not compiled in
+    pub async fn try_upnp_mapping_with_protocol(
+        _local_port: u16,
+        _lifetime_secs: u32,
+        _protocol: IpProtocol,
+    ) -> Result<PortMappingResult, MappingError> {
+        tracing::debug!("UPnP mapping skipped: not compiled in");
+        Err(MappingError::NotSupported)
+    }
+
+    /// Always fails: UPnP is not compiled in
+    pub async fn delete_upnp_mapping(_local_port: u16, _protocol: IpProtocol) -> Result<(), MappingError> {
+        Err(MappingError::NotSupported)
+    }
+
+    /// Nothing to remove: no UPnP mapping can exist
+    pub(crate) fn remove_mapping_blocking(
+        _local_port: u16,
+        _protocol: IpProtocol,
+        _timeout: Duration,
+    ) -> Result<(), MappingError> {
+        Err(MappingError::NotSupported)
+    }
+}
@@ -0,0 +1,312 @@
+//! Typed stream of connectivity changes
+//!
+//! Everything that learns something about our connectivity reports it to a
+//! `ConnectivityEvents` hub: the orchestrator (each protocol attempt and the
+//! final result), `PortMappingManager` renewals, the reachability health
+//! check and the transport port watchdog. The hub keeps the last known
+//! mapping, external IP, reachability and degradation, so producers report
+//! what they observed and only actual changes go out as events.
+//!
+//! Events are broadcast: every `subscribe()` gets its own receiver, so the
+//! TUI, the persisted connectivity history and any other consumer see the
+//! same events in the same order. Each event carries its timestamp and all
+//! it needs to be rendered.
+
+use super::types::{ConnectivityResult, MappingError, MappingProtocol, PortMappingResult, StrategyAttempt};
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Events a subscriber may fall behind by before it misses some
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// One change in connectivity
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectivityEvent {
+    /// A mapping strategy was tried
+    ProtocolAttempt {
+        /// Strategy tried (`Direct` for HTTP IP detection)
+        protocol: MappingProtocol,
+        /// Whether it produced a mapping
+        succeeded: bool,
+        /// The mapped address, or why it failed
+        detail: String,
+        /// When the attempt finished
+        at: DateTime<Utc>,
+    },
+    /// A mapping is in place where there was none (or a different one)
+    MappingEstablished {
+        /// The mapping
+        mapping: PortMappingResult,
+        /// Whether its external address is behind carrier-grade NAT
+        cgnat: bool,
+        /// When it was observed
+        at: DateTime<Utc>,
+    },
+    /// The mapping in place is gone (refresh found none, renewal failed)
+    MappingLost {
+        /// The mapping that was lost
+        previous: PortMappingResult,
+        /// Why
+        reason: String,
+        /// When it was observed
+        at: DateTime<Utc>,
+    },
+    /// Our external address differs from the last one seen
+    ExternalIpChanged {
+        /// The earlier address
+        from: IpAddr,
+        /// The new address
+        to: IpAddr,
+        /// When it was observed
+        at: DateTime<Utc>,
+    },
+    /// The health check reached a different verdict for a mapping
+    ReachabilityChanged {
+        /// The mapping checked
+        mapping: PortMappingResult,
+        /// Reachable from outside, or `None` if the check was inconclusive
+        reachable: Option<bool>,
+        /// When the check finished
+        at: DateTime<Utc>,
+    },
+    /// A full connectivity check finished
+    Checked {
+        /// All attempts and the final mapping, as the orchestrator left them
+        result: Box<ConnectivityResult>,
+        /// When it finished
+        at: DateTime<Utc>,
+    },
+    /// Connectivity got worse without the mapping being lost (e.g. our port was taken over)
+    Degraded {
+        /// What happened
+        reason: String,
+        /// When it was observed
+        at: DateTime<Utc>,
+    },
+    /// Recovered from the last `Degraded`
+    Restored {
+        /// How it recovered
+        detail: String,
+        /// When it was observed
+        at: DateTime<Utc>,
+    },
+}
+
+impl ConnectivityEvent {
+    /// When the event happened
+    pub fn at(&self) -> DateTime<Utc> {
+        match self {
+            ConnectivityEvent::ProtocolAttempt { at, .. }
+            | ConnectivityEvent::MappingEstablished { at, .. }
+            | ConnectivityEvent::MappingLost { at, .. }
+            | ConnectivityEvent::ExternalIpChanged { at, .. }
+            | ConnectivityEvent::ReachabilityChanged { at, .. }
+            | ConnectivityEvent::Checked { at, .. }
+            | ConnectivityEvent::Degraded { at, .. }
+            | ConnectivityEvent::Restored { at, .. } => *at,
+        }
+    }
+
+    /// Short name of the kind of event, e.g. "mapping_lost"
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConnectivityEvent::ProtocolAttempt { .. } => "protocol_attempt",
+            ConnectivityEvent::MappingEstablished { .. } => "mapping_established",
+            ConnectivityEvent::MappingLost { .. } => "mapping_lost",
+            ConnectivityEvent::ExternalIpChanged { .. } => "external_ip_changed",
+            ConnectivityEvent::ReachabilityChanged { .. } => "reachability_changed",
+            ConnectivityEvent::Checked { .. } => "checked",
+            ConnectivityEvent::Degraded { .. } => "degraded",
+            ConnectivityEvent::Restored { .. } => "restored",
+        }
+    }
+
+    /// One line for history and logs, e.g. "UPnP mapping 203.0.113.1:8080"
+    pub fn describe(&self) -> String {
+        match self {
+            ConnectivityEvent::ProtocolAttempt { protocol, succeeded, detail, .. } => {
+                let verdict = if *succeeded { "ok" } else { "failed" };
+                format!("{} {}: {}", protocol.name(), verdict, detail)
+            }
+            ConnectivityEvent::MappingEstablished { mapping, cgnat, .. } => format!(
+                "{} mapping {}:{}{}",
+                mapping.protocol.name(),
+                mapping.external_ip,
+                mapping.external_port,
+                if *cgnat { " (CGNAT)" } else { "" }
+            ),
+            ConnectivityEvent::MappingLost { previous, reason, .. } => format!(
+                "{} mapping {}:{} lost: {}",
+                previous.protocol.name(),
+                previous.external_ip,
+                previous.external_port,
+                reason
+            ),
+            ConnectivityEvent::ExternalIpChanged { from, to, .. } => format!("external IP {} → {}", from, to),
+            ConnectivityEvent::ReachabilityChanged { mapping, reachable, .. } => {
+                let verdict = match reachable {
+                    Some(true) => "reachable",
+                    Some(false) => "not reachable",
+                    None => "inconclusive",
+                };
+                format!("{}:{} {}", mapping.external_ip, mapping.external_port, verdict)
+            }
+            ConnectivityEvent::Checked { result, .. } => result.summary(),
+            ConnectivityEvent::Degraded { reason, .. } => reason.clone(),
+            ConnectivityEvent::Restored { detail, .. } => detail.clone(),
+        }
+    }
+}
+
+/// What the hub last knew, to turn observations into changes
+#[derive(Debug, Default)]
+struct KnownState {
+    mapping: Option<PortMappingResult>,
+    external_ip: Option<IpAddr>,
+    reachable: Option<bool>,
+    degraded: bool,
+}
+
+/// Whether two mappings are the same mapping (a renewal only moves `created_at_ms`)
+fn same_mapping(a: &PortMappingResult, b: &PortMappingResult) -> bool {
+    a.protocol == b.protocol && a.external_ip == b.external_ip && a.external_port == b.external_port
+}
+
+/// Broadcast hub for `ConnectivityEvent`s
+///
+/// Cheap to clone; clones share the channel and the known state, so a
+/// producer on another thread gets its own clone.
+#[derive(Debug, Clone)]
+pub struct ConnectivityEvents {
+    sender: broadcast::Sender<ConnectivityEvent>,
+    known: Arc<Mutex<KnownState>>,
+}
+
+impl Default for ConnectivityEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectivityEvents {
+    /// Create a hub nobody listens to yet
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self { sender, known: Arc::new(Mutex::new(KnownState::default())) }
+    }
+
+    /// Receive every event emitted from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<ConnectivityEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Send an event to all subscribers (dropped if there are none)
+    pub fn emit(&self, event: ConnectivityEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Mapping currently known to be in place
+    pub fn current_mapping(&self) -> Option<PortMappingResult> {
+        self.known.lock().unwrap().mapping.clone()
+    }
+
+    /// Report the outcome of one mapping strategy
+    pub fn observe_attempt(&self, protocol: MappingProtocol, outcome: &StrategyAttempt) {
+        let (succeeded, detail) = match outcome {
+            StrategyAttempt::NotAttempted => return,
+            StrategyAttempt::Success(mapping) => (true, format!("{}:{}", mapping.external_ip, mapping.external_port)),
+            StrategyAttempt::Failed(error) => (false, error.clone()),
+        };
+        self.emit(ConnectivityEvent::ProtocolAttempt { protocol, succeeded, detail, at: Utc::now() });
+    }
+
+    /// Report the mapping now in place (`None`: there is none, because of `reason`)
+    ///
+    /// Emits `MappingEstablished` for a new or different mapping,
+    /// `ExternalIpChanged` when its address differs from the last one seen
+    /// and `MappingLost` when a known mapping is gone. The same mapping
+    /// observed again (e.g. renewed) emits nothing.
+    pub fn observe_mapping(&self, mapping: Option<&PortMappingResult>, cgnat: bool, reason: &str) {
+        let at = Utc::now();
+        let mut events = Vec::new();
+        {
+            let mut known = self.known.lock().unwrap();
+            match mapping {
+                Some(mapping) => {
+                    if !known.mapping.as_ref().is_some_and(|m| same_mapping(m, mapping)) {
+                        events.push(ConnectivityEvent::MappingEstablished { mapping: mapping.clone(), cgnat, at });
+                    }
+                    if let Some(from) = known.external_ip
+                        && from != mapping.external_ip
+                    {
+                        events.push(ConnectivityEvent::ExternalIpChanged { from, to: mapping.external_ip, at });
+                    }
+                    known.mapping = Some(mapping.clone());
+                    known.external_ip = Some(mapping.external_ip);
+                }
+                None => {
+                    if let Some(previous) = known.mapping.take() {
+                        events.push(ConnectivityEvent::MappingLost { previous, reason: reason.to_string(), at });
+                    }
+                }
+            }
+        }
+        for event in events {
+            self.emit(event);
+        }
+    }
+
+    /// Report a finished connectivity check
+    ///
+    /// Emits the mapping changes, then `Checked` with the whole result. The
+    /// result's reachability becomes the known one, so the next health
+    /// check verdict is compared against it.
+    pub fn observe_result(&self, result: &ConnectivityResult) {
+        self.observe_mapping(result.mapping.as_ref(), result.cgnat_detected, "no strategy produced a mapping");
+        self.known.lock().unwrap().reachable = result.externally_reachable;
+        self.emit(ConnectivityEvent::Checked { result: Box::new(result.clone()), at: Utc::now() });
+    }
+
+    /// Report a renewal attempt of the mapping in place
+    ///
+    /// A failed renewal loses the mapping; the next successful one
+    /// establishes it again.
+    pub fn observe_renewal(&self, outcome: &Result<PortMappingResult, MappingError>) {
+        match outcome {
+            Ok(mapping) => self.observe_mapping(Some(mapping), super::cgnat::detect_cgnat(mapping.external_ip), ""),
+            Err(error) => self.observe_mapping(None, false, &format!("renewal failed: {}", error)),
+        }
+    }
+
+    /// Report a health check verdict for `mapping`
+    ///
+    /// Emits `ReachabilityChanged` unless the verdict is the one already known.
+    pub fn observe_reachability(&self, mapping: &PortMappingResult, reachable: Option<bool>) {
+        {
+            let mut known = self.known.lock().unwrap();
+            if known.reachable == reachable {
+                return;
+            }
+            known.reachable = reachable;
+        }
+        self.emit(ConnectivityEvent::ReachabilityChanged { mapping: mapping.clone(), reachable, at: Utc::now() });
+    }
+
+    /// Report that connectivity got worse; repeated reports emit once
+    pub fn observe_degraded(&self, reason: String) {
+        if std::mem::replace(&mut self.known.lock().unwrap().degraded, true) {
+            return;
+        }
+        self.emit(ConnectivityEvent::Degraded { reason, at: Utc::now() });
+    }
+
+    /// Report recovery; emits only after a `Degraded`
+    pub fn observe_restored(&self, detail: String) {
+        if !std::mem::replace(&mut self.known.lock().unwrap().degraded, false) {
+            return;
+        }
+        self.emit(ConnectivityEvent::Restored { detail, at: Utc::now() });
+    }
+}
@@ -1,8 +1,93 @@
 //! Gateway discovery for different platforms
+//!
+//! The concurrent prober PCP and NAT-PMP use on the candidates is in
+//! `gateway_probe`.
 
 use crate::connectivity::types::MappingError;
 use std::net::{IpAddr, Ipv4Addr};
 
+/// A gateway that may answer port mapping requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GatewayCandidate {
+    /// Gateway IP address
+    pub ip: IpAddr,
+    /// Interface the route goes through, if known
+    pub interface: Option<String>,
+}
+
+/// Find every gateway that may answer port mapping requests
+///
+/// On Linux this collects the gateways of all routes (default routes first),
+/// so VPN and secondary NIC gateways are included. Other platforms return the
+/// default gateway only.
+pub fn find_candidate_gateways() -> Result<Vec<GatewayCandidate>, MappingError> {
+    #[cfg(target_os = "linux")]
+    {
+        let route_table = std::fs::read_to_string("/proc/net/route")
+            .map_err(|e| MappingError::Internal(format!("Failed to read route table: {}", e)))?;
+        let candidates = parse_linux_route_table(&route_table);
+        if candidates.is_empty() {
+            return Err(MappingError::NoGateway);
+        }
+        Ok(candidates)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        Ok(vec![GatewayCandidate {
+            ip: find_default_gateway()?,
+            interface: None,
+        }])
+    }
+}
+
+/// Parse gateway candidates from the contents of `/proc/net/route`
+///
+/// Routes without a gateway (directly connected subnets) are skipped and each
+/// gateway is listed once. Default routes come first, then other routes in
+/// table order.
+pub(crate) fn parse_linux_route_table(route_table: &str) -> Vec<GatewayCandidate> {
+    /// RTF_GATEWAY flag: the route goes through a gateway
+    const RTF_GATEWAY: u32 = 0x2;
+
+    let mut default_routes = Vec::new();
+    let mut other_routes = Vec::new();
+
+    for line in route_table.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 {
+            continue;
+        }
+
+        let Ok(gateway_u32) = u32::from_str_radix(fields[2], 16) else {
+            continue;
+        };
+        let flags = u32::from_str_radix(fields[3], 16).unwrap_or(0);
+        if gateway_u32 == 0 || flags & RTF_GATEWAY == 0 {
+            continue;
+        }
+
+        let candidate = GatewayCandidate {
+            // Stored in little-endian hex
+            ip: IpAddr::V4(Ipv4Addr::from(gateway_u32.to_be())),
+            interface: Some(fields[0].to_string()),
+        };
+        if fields[1] == "00000000" {
+            default_routes.push(candidate);
+        } else {
+            other_routes.push(candidate);
+        }
+    }
+
+    let mut candidates: Vec<GatewayCandidate> = Vec::new();
+    for candidate in default_routes.into_iter().chain(other_routes) {
+        if !candidates.iter().any(|c| c.ip == candidate.ip) {
+            candidates.push(candidate);
+        }
+    }
+    candidates
+}
+
 /// Find the default gateway IP address
 ///
 /// This is a simple implementation that works on most platforms.
@@ -0,0 +1,127 @@
+//! Concurrent prober used by PCP and NAT-PMP
+//!
+//! Tries every candidate gateway at once (multi-homed machines often have
+//! several, and only one of them may answer). Built with the
+//! `connectivity-pcp-natpmp` feature.
+
+use super::gateway::GatewayCandidate;
+use super::types::{
+    GatewayProbe, GatewayProbeOutcome, GatewayProbeReport, MappingError, MappingProtocol,
+    PortMappingResult,
+};
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::task::JoinSet;
+
+/// Receive a datagram, giving up after `timeout`
+pub(crate) async fn recv_with_timeout(
+    socket: &UdpSocket,
+    buf: &mut [u8],
+    timeout: Duration,
+) -> Result<usize, MappingError> {
+    match tokio::time::timeout(timeout, socket.recv(buf)).await {
+        Ok(Ok(bytes)) => Ok(bytes),
+        Ok(Err(e)) => Err(MappingError::Io(e)),
+        Err(_) => Err(MappingError::Timeout),
+    }
+}
+
+/// Bind a UDP socket connected to `server`
+///
+/// Connecting lets the OS pick the local address on the interface that routes
+/// to the gateway, and filters out datagrams from other hosts.
+pub(crate) async fn connect_udp(server: SocketAddr) -> Result<UdpSocket, MappingError> {
+    let bind_addr: SocketAddr = if server.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(server).await?;
+    Ok(socket)
+}
+
+/// Probe several gateways concurrently and return the first mapping
+///
+/// Every gateway gets its own task; as soon as one creates a mapping the rest
+/// are cancelled. Each gateway's outcome is recorded in the report.
+pub(crate) async fn probe_gateways<F, Fut>(
+    protocol: MappingProtocol,
+    servers: &[SocketAddr],
+    probe: F,
+) -> GatewayProbeReport
+where
+    F: Fn(SocketAddr) -> Fut,
+    Fut: Future<Output = Result<PortMappingResult, MappingError>> + Send + 'static,
+{
+    if servers.is_empty() {
+        return GatewayProbeReport::failed(MappingError::NoGateway);
+    }
+
+    let started = Instant::now();
+    let mut tasks = JoinSet::new();
+    for (index, server) in servers.iter().enumerate() {
+        let request = probe(*server);
+        tasks.spawn(async move { (index, request.await) });
+    }
+
+    let mut outcomes: Vec<Option<(GatewayProbeOutcome, u64)>> = vec![None; servers.len()];
+    let mut mapping = None;
+    let mut error: Option<MappingError> = None;
+
+    while let Some(joined) = tasks.join_next().await {
+        let Ok((index, result)) = joined else {
+            continue;
+        };
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+
+        match result {
+            Ok(result) => {
+                outcomes[index] = Some((GatewayProbeOutcome::Mapped(result.clone()), elapsed_ms));
+                mapping = Some(result);
+                tasks.abort_all();
+                break;
+            }
+            Err(e) => {
+                outcomes[index] = Some((GatewayProbeOutcome::Failed(e.to_string()), elapsed_ms));
+                // Prefer an actual answer (gateway error) over a timeout
+                if error.as_ref().is_none_or(|kept| matches!(kept, MappingError::Timeout)) {
+                    error = Some(e);
+                }
+            }
+        }
+    }
+
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    let probes = servers
+        .iter()
+        .zip(outcomes)
+        .map(|(server, outcome)| {
+            let (outcome, elapsed_ms) = outcome.unwrap_or((GatewayProbeOutcome::Cancelled, elapsed_ms));
+            GatewayProbe {
+                protocol,
+                server: *server,
+                interface: None,
+                outcome,
+                elapsed_ms,
+            }
+        })
+        .collect();
+
+    GatewayProbeReport {
+        result: mapping.ok_or_else(|| error.unwrap_or(MappingError::Timeout)),
+        probes,
+    }
+}
+
+/// Fill in the interface of each probe from the discovered candidates
+pub(crate) fn label_probe_interfaces(probes: &mut [GatewayProbe], candidates: &[GatewayCandidate]) {
+    for probe in probes {
+        probe.interface = candidates
+            .iter()
+            .find(|c| c.ip == probe.server.ip())
+            .and_then(|c| c.interface.clone());
+    }
+}
@@ -4,9 +4,10 @@
 //! - `PortMappingManager` - PCP/NAT-PMP mapping with automatic renewal
 //! - `UpnpMappingManager` - UPnP mapping with automatic cleanup
 
+use super::events::ConnectivityEvents;
 use super::pcp::try_pcp_mapping_with_protocol;
 use super::types::{IpProtocol, MappingError, PortMappingResult};
-use super::upnp::{delete_upnp_mapping, try_upnp_mapping_with_protocol};
+use super::upnp::{delete_upnp_mapping, remove_mapping_blocking, try_upnp_mapping_with_protocol};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
@@ -16,13 +17,15 @@ use tracing::{debug, error, info};
 /// Automatic port mapping manager with renewal
 ///
 /// This manager creates a port mapping and automatically renews it
-/// before it expires (at 80% of lifetime).
+/// before it expires (at 80% of lifetime). Each renewal is reported to its
+/// `ConnectivityEvents`, so a failed one shows up as `MappingLost`.
 pub struct PortMappingManager {
     local_port: u16,
     lifetime_secs: u32,
     protocol: IpProtocol,
     current_mapping: Arc<Mutex<Option<PortMappingResult>>>,
     renewal_task: Arc<Mutex<Option<JoinHandle<()>>>>,
+    events: ConnectivityEvents,
 }
 
 impl PortMappingManager {
@@ -34,9 +37,16 @@ impl PortMappingManager {
             protocol,
             current_mapping: Arc::new(Mutex::new(None)),
             renewal_task: Arc::new(Mutex::new(None)),
+            events: ConnectivityEvents::new(),
         }
     }
 
+    /// Report the mapping and its renewals to `events`
+    pub fn with_events(mut self, events: ConnectivityEvents) -> Self {
+        self.events = events;
+        self
+    }
+
     /// Start the port mapping and automatic renewal
     ///
     /// This will create an initial mapping and spawn a background task
@@ -57,6 +67,7 @@ impl PortMappingManager {
 
         // Store mapping
         *self.current_mapping.lock().await = Some(mapping.clone());
+        self.events.observe_renewal(&Ok(mapping.clone()));
 
         // Start renewal task
         self.start_renewal_task().await;
@@ -78,6 +89,7 @@ impl PortMappingManager {
 
         // Clear current mapping
         *self.current_mapping.lock().await = None;
+        self.events.observe_mapping(None, false, "mapping manager stopped");
 
         Ok(())
     }
@@ -93,6 +105,7 @@ impl PortMappingManager {
         let lifetime_secs = self.lifetime_secs;
         let protocol = self.protocol;
         let current_mapping = self.current_mapping.clone();
+        let events = self.events.clone();
 
         // Cancel existing task if any
         if let Some(task) = self.renewal_task.lock().await.take() {
@@ -113,7 +126,9 @@ impl PortMappingManager {
 
                 // Attempt renewal
                 info!("Renewing port mapping for port {}", local_port);
-                match try_pcp_mapping_with_protocol(local_port, lifetime_secs, protocol).await {
+                let outcome = try_pcp_mapping_with_protocol(local_port, lifetime_secs, protocol).await;
+                events.observe_renewal(&outcome);
+                match outcome {
                     Ok(new_mapping) => {
                         info!(
                             "Port mapping renewed: {}:{} (lifetime: {}s)",
@@ -211,17 +226,7 @@ impl Drop for UpnpMappingManager {
 
         // Spawn blocking cleanup task
         std::thread::spawn(move || {
-            // Search for gateway
-            if let Ok(gateway) = igd_next::search_gateway(igd_next::SearchOptions {
-                timeout: Some(Duration::from_secs(2)),
-                ..Default::default()
-            }) {
-                let upnp_protocol = match protocol {
-                    IpProtocol::TCP => igd_next::PortMappingProtocol::TCP,
-                    IpProtocol::UDP => igd_next::PortMappingProtocol::UDP,
-                };
-
-                let _ = gateway.remove_port(upnp_protocol, local_port);
+            if remove_mapping_blocking(local_port, protocol, Duration::from_secs(2)).is_ok() {
                 debug!("UPnP mapping cleaned up on drop");
             }
         });
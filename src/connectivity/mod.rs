@@ -7,34 +7,65 @@
 //! - IPv6 support
 //!
 //! The module automatically attempts different protocols in priority order
-//! and manages mapping lifecycle including renewal.
+//! and manages mapping lifecycle including renewal. Changes are published as
+//! a `ConnectivityEvent` stream (see `events`).
+//!
+//! PCP/NAT-PMP and UPnP sit behind the `connectivity-pcp-natpmp` and
+//! `connectivity-upnp` features (both default). Without one, its module is a
+//! stand-in whose attempts fail with `MappingError::NotSupported`.
 
 // Submodules
 pub mod cgnat;
+#[cfg(not(all(feature = "connectivity-pcp-natpmp", feature = "connectivity-upnp")))]
+mod disabled;
+pub mod events;
 pub mod gateway;
+#[cfg(feature = "connectivity-pcp-natpmp")]
+mod gateway_probe;
 pub mod health_check;
 pub mod http_ip;
 pub mod ipv6;
 pub mod manager;
+#[cfg(feature = "connectivity-pcp-natpmp")]
 pub mod natpmp;
 pub mod orchestrator;
+#[cfg(feature = "connectivity-pcp-natpmp")]
 pub mod pcp;
 pub mod types;
+#[cfg(feature = "connectivity-upnp")]
 pub mod upnp;
 
+// Protocols left out of the build keep their functions, which always fail
+#[cfg(not(feature = "connectivity-pcp-natpmp"))]
+pub use disabled::{natpmp, pcp};
+#[cfg(not(feature = "connectivity-upnp"))]
+pub use disabled::upnp;
+
 // Re-export commonly used types
 pub use types::{
-    ConnectivityResult, IpProtocol, MappingError, MappingProtocol, PortMappingResult,
-    StrategyAttempt,
+    ConnectivityResult, GatewayProbe, GatewayProbeOutcome, GatewayProbeReport, IpProtocol,
+    MappingError, MappingProtocol, PortMappingResult, StrategyAttempt,
 };
 
 // Re-export main functions
 pub use cgnat::{detect_cgnat, is_private_ip};
+pub use events::{ConnectivityEvent, ConnectivityEvents, EVENT_CHANNEL_CAPACITY};
+pub use gateway::{find_candidate_gateways, find_default_gateway, GatewayCandidate};
 pub use health_check::{verify_external_reachability, ReachabilityStatus};
 pub use http_ip::detect_external_ip;
-pub use natpmp::{try_natpmp_mapping, try_natpmp_mapping_with_protocol};
-pub use orchestrator::{establish_connectivity, verify_connectivity_health};
-pub use pcp::{try_pcp_mapping, try_pcp_mapping_with_protocol};
+pub use natpmp::{
+    probe_natpmp_gateways, try_natpmp_mapping, try_natpmp_mapping_with_protocol,
+    try_natpmp_mapping_with_report,
+};
+pub use orchestrator::{
+    delete_port_mapping, establish_connectivity, establish_connectivity_with, try_single_protocol,
+    verify_connectivity_health, verify_connectivity_health_with, MappingStrategies, SystemStrategies,
+    DEFAULT_MAPPING_LIFETIME_SECS,
+};
+pub use pcp::{
+    probe_pcp_gateways, try_pcp_mapping, try_pcp_mapping_with_protocol,
+    try_pcp_mapping_with_report,
+};
 pub use upnp::{delete_upnp_mapping, try_upnp_mapping, try_upnp_mapping_with_protocol};
 
 // Re-export managers
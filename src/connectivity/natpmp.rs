@@ -23,9 +23,10 @@
 //! # }
 //! ```
 
-use super::gateway::find_default_gateway;
-use super::types::{IpProtocol, MappingError, MappingProtocol, PortMappingResult};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use super::gateway::find_candidate_gateways;
+use super::gateway_probe::{connect_udp, label_probe_interfaces, probe_gateways, recv_with_timeout};
+use super::types::{GatewayProbeReport, IpProtocol, MappingError, MappingProtocol, PortMappingResult};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::{debug, info};
 
@@ -91,7 +92,8 @@ impl NatPmpResultCode {
 /// Attempt to create a port mapping using NAT-PMP (NAT Port Mapping Protocol)
 ///
 /// NAT-PMP is a legacy protocol (RFC 6886) supported by older routers,
-/// particularly Apple AirPort devices and some Cisco routers.
+/// particularly Apple AirPort devices and some Cisco routers. Requests go to
+/// every candidate gateway at once and the first successful mapping wins.
 ///
 /// # Arguments
 ///
@@ -132,44 +134,98 @@ pub async fn try_natpmp_mapping_with_protocol(
     lifetime_secs: u32,
     protocol: IpProtocol,
 ) -> Result<PortMappingResult, MappingError> {
+    try_natpmp_mapping_with_report(local_port, lifetime_secs, protocol)
+        .await
+        .result
+}
+
+/// Attempt a NAT-PMP mapping and report the outcome of every gateway probed
+///
+/// Same as `try_natpmp_mapping_with_protocol`, but keeps the per-gateway
+/// outcomes for diagnostics.
+pub async fn try_natpmp_mapping_with_report(
+    local_port: u16,
+    lifetime_secs: u32,
+    protocol: IpProtocol,
+) -> GatewayProbeReport {
     info!(
         "Attempting NAT-PMP mapping for port {} (lifetime: {}s, protocol: {:?})",
         local_port, lifetime_secs, protocol
     );
 
-    // Find default gateway
-    let gateway = find_default_gateway()?;
-    debug!("Found default gateway: {}", gateway);
+    let candidates = match find_candidate_gateways() {
+        Ok(candidates) => candidates,
+        Err(e) => return GatewayProbeReport::failed(e),
+    };
+    debug!("Found {} candidate gateway(s): {:?}", candidates.len(), candidates);
+
+    let servers: Vec<SocketAddr> = candidates
+        .iter()
+        .map(|c| SocketAddr::new(c.ip, NATPMP_SERVER_PORT))
+        .collect();
+    let mut report =
+        probe_natpmp_gateways(&servers, local_port, lifetime_secs, protocol, NATPMP_TIMEOUT).await;
+    label_probe_interfaces(&mut report.probes, &candidates);
+    report
+}
 
-    // Create UDP socket for NAT-PMP communication
-    let socket = UdpSocket::bind("0.0.0.0:0")?;
-    socket.set_read_timeout(Some(NATPMP_TIMEOUT))?;
-    socket.set_write_timeout(Some(NATPMP_TIMEOUT))?;
+/// Send NAT-PMP requests to several NAT-PMP servers concurrently
+///
+/// Returns the first valid mapping; the other requests are cancelled. Each
+/// request waits at most `timeout` for an answer.
+///
+/// # Arguments
+///
+/// * `servers` - NAT-PMP server addresses (gateway IP and port 5351 in production)
+/// * `local_port` - The local port to map
+/// * `lifetime_secs` - Requested lifetime in seconds (0 = delete mapping)
+/// * `protocol` - IP protocol (TCP or UDP)
+/// * `timeout` - How long to wait for each response
+pub async fn probe_natpmp_gateways(
+    servers: &[SocketAddr],
+    local_port: u16,
+    lifetime_secs: u32,
+    protocol: IpProtocol,
+    timeout: Duration,
+) -> GatewayProbeReport {
+    probe_gateways(MappingProtocol::NATPMP, servers, |server| {
+        natpmp_map_request(server, local_port, lifetime_secs, protocol, timeout)
+    })
+    .await
+}
 
-    // Build NAT-PMP MAP request
+/// Ask one NAT-PMP server for its external address, then for a mapping
+async fn natpmp_map_request(
+    server: SocketAddr,
+    local_port: u16,
+    lifetime_secs: u32,
+    protocol: IpProtocol,
+    timeout: Duration,
+) -> Result<PortMappingResult, MappingError> {
+    let socket = connect_udp(server).await?;
+
+    // NAT-PMP doesn't return the external IP in the MAP response,
+    // so ask for it first
+    socket
+        .send(&[NATPMP_VERSION, NatPmpOpcode::ExternalAddress as u8])
+        .await?;
+    let mut address_buf = [0u8; 12]; // External address response is 12 bytes
+    let bytes_received = recv_with_timeout(&socket, &mut address_buf, timeout).await?;
+    let external_ip = parse_natpmp_external_address_response(&address_buf[..bytes_received])?;
+
+    // Build and send NAT-PMP MAP request
     let request = build_natpmp_map_request(local_port, local_port, lifetime_secs, protocol);
-
-    // Send request to gateway
-    let server_addr = SocketAddr::new(gateway, NATPMP_SERVER_PORT);
-    socket.send_to(&request, server_addr)?;
-    debug!("Sent NAT-PMP MAP request to {}", server_addr);
+    socket.send(&request).await?;
+    debug!("Sent NAT-PMP MAP request to {}", server);
 
     // Receive response
     let mut response_buf = [0u8; 16]; // NAT-PMP response is 16 bytes
-    let (bytes_received, _) = socket
-        .recv_from(&mut response_buf)
-        .map_err(|e| {
-            if e.kind() == std::io::ErrorKind::WouldBlock {
-                MappingError::Timeout
-            } else {
-                MappingError::Io(e)
-            }
-        })?;
-
-    debug!("Received {} bytes from NAT-PMP server", bytes_received);
+    let bytes_received = recv_with_timeout(&socket, &mut response_buf, timeout).await?;
+
+    debug!("Received {} bytes from NAT-PMP server {}", bytes_received, server);
 
     // Parse response
-    parse_natpmp_map_response(&response_buf[..bytes_received], gateway)
+    parse_natpmp_map_response(&response_buf[..bytes_received], external_ip)
 }
 
 /// Build a NAT-PMP MAP request packet
@@ -207,9 +263,12 @@ pub(crate) fn build_natpmp_map_request(
 }
 
 /// Parse a NAT-PMP MAP response packet
+///
+/// The MAP response carries no external IP; `external_ip` comes from the
+/// external address request sent before it.
 pub(crate) fn parse_natpmp_map_response(
     response: &[u8],
-    gateway_ip: IpAddr,
+    external_ip: IpAddr,
 ) -> Result<PortMappingResult, MappingError> {
     if response.len() < 16 {
         return Err(MappingError::InvalidResponse(format!(
@@ -259,10 +318,6 @@ pub(crate) fn parse_natpmp_map_response(
     // Parse lifetime (bytes 12-15, big-endian)
     let lifetime_secs = u32::from_be_bytes([response[12], response[13], response[14], response[15]]);
 
-    // NAT-PMP doesn't return external IP in the MAP response
-    // We need to make a separate request for external address
-    let external_ip = get_external_ip_natpmp(gateway_ip)?;
-
     let created_at_ms = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
@@ -284,41 +339,19 @@ pub(crate) fn parse_natpmp_map_response(
     Ok(result)
 }
 
-/// Get external IP address using NAT-PMP
-fn get_external_ip_natpmp(gateway: IpAddr) -> Result<IpAddr, MappingError> {
-    // Create UDP socket
-    let socket = UdpSocket::bind("0.0.0.0:0")?;
-    socket.set_read_timeout(Some(NATPMP_TIMEOUT))?;
-    socket.set_write_timeout(Some(NATPMP_TIMEOUT))?;
-
-    // Build external address request (2 bytes)
-    let request = vec![NATPMP_VERSION, NatPmpOpcode::ExternalAddress as u8];
-
-    // Send request
-    let server_addr = SocketAddr::new(gateway, NATPMP_SERVER_PORT);
-    socket.send_to(&request, server_addr)?;
-
-    // Receive response
-    let mut response_buf = [0u8; 12]; // External address response is 12 bytes
-    let (bytes_received, _) = socket.recv_from(&mut response_buf).map_err(|e| {
-        if e.kind() == std::io::ErrorKind::WouldBlock {
-            MappingError::Timeout
-        } else {
-            MappingError::Io(e)
-        }
-    })?;
-
-    if bytes_received < 12 {
+/// Parse a NAT-PMP external address response packet
+pub(crate) fn parse_natpmp_external_address_response(response: &[u8]) -> Result<IpAddr, MappingError> {
+    if response.len() < 12 {
         return Err(MappingError::InvalidResponse(format!(
             "External IP response too short: {} bytes",
-            bytes_received
+            response.len()
         )));
     }
 
     // Parse response
-    let version = response_buf[0];
-    let opcode = response_buf[1];
-    let result_code = u16::from_be_bytes([response_buf[2], response_buf[3]]);
+    let version = response[0];
+    let opcode = response[1];
+    let result_code = u16::from_be_bytes([response[2], response[3]]);
 
     if version != NATPMP_VERSION {
         return Err(MappingError::InvalidResponse(format!(
@@ -345,12 +378,7 @@ fn get_external_ip_natpmp(gateway: IpAddr) -> Result<IpAddr, MappingError> {
     }
 
     // Parse external IP (bytes 8-11)
-    let external_ip = Ipv4Addr::new(
-        response_buf[8],
-        response_buf[9],
-        response_buf[10],
-        response_buf[11],
-    );
+    let external_ip = Ipv4Addr::new(response[8], response[9], response[10], response[11]);
 
     Ok(IpAddr::V4(external_ip))
 }
@@ -4,13 +4,117 @@ use super::cgnat::detect_cgnat;
 use super::health_check::{verify_external_reachability, ReachabilityStatus};
 use super::http_ip::detect_external_ip;
 use super::ipv6::check_ipv6_connectivity;
-use super::natpmp::try_natpmp_mapping;
-use super::pcp::try_pcp_mapping;
-use super::upnp::try_upnp_mapping;
-use super::types::{ConnectivityResult, MappingProtocol, PortMappingResult, StrategyAttempt};
+use super::natpmp::{try_natpmp_mapping, try_natpmp_mapping_with_report};
+use super::pcp::{try_pcp_mapping, try_pcp_mapping_with_report};
+use super::upnp::{delete_upnp_mapping, try_upnp_mapping};
+use super::events::ConnectivityEvents;
+use super::types::{
+    ConnectivityResult, GatewayProbeReport, IpProtocol, MappingError, MappingProtocol, PortMappingResult,
+    StrategyAttempt,
+};
+use std::net::IpAddr;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::{debug, error, info, warn};
 
+/// Lifetime requested for NAT mappings (1 hour)
+pub const DEFAULT_MAPPING_LIFETIME_SECS: u32 = 3600;
+
+/// The protocol layer the orchestrator drives, one method per strategy
+///
+/// `SystemStrategies` talks to the real network; tests script outcomes.
+pub trait MappingStrategies: Sync {
+    /// Check direct IPv6 connectivity
+    fn ipv6(&self, port: u16) -> impl Future<Output = Result<PortMappingResult, MappingError>> + Send;
+    /// Request a PCP mapping from every candidate gateway
+    fn pcp(&self, port: u16, lifetime_secs: u32) -> impl Future<Output = GatewayProbeReport> + Send;
+    /// Request a NAT-PMP mapping from every candidate gateway
+    fn natpmp(&self, port: u16, lifetime_secs: u32) -> impl Future<Output = GatewayProbeReport> + Send;
+    /// Request a UPnP mapping
+    fn upnp(&self, port: u16, lifetime_secs: u32) -> impl Future<Output = Result<PortMappingResult, MappingError>> + Send;
+    /// Detect our external IP over HTTP
+    fn external_ip(&self) -> impl Future<Output = Result<IpAddr, MappingError>> + Send;
+    /// Check that `mapping` is reachable from outside
+    fn reachability(&self, mapping: &PortMappingResult) -> impl Future<Output = ReachabilityStatus> + Send;
+}
+
+/// Strategies backed by the real protocol implementations
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemStrategies;
+
+impl MappingStrategies for SystemStrategies {
+    async fn ipv6(&self, port: u16) -> Result<PortMappingResult, MappingError> {
+        check_ipv6_connectivity(port).await
+    }
+
+    async fn pcp(&self, port: u16, lifetime_secs: u32) -> GatewayProbeReport {
+        try_pcp_mapping_with_report(port, lifetime_secs, IpProtocol::TCP).await
+    }
+
+    async fn natpmp(&self, port: u16, lifetime_secs: u32) -> GatewayProbeReport {
+        try_natpmp_mapping_with_report(port, lifetime_secs, IpProtocol::TCP).await
+    }
+
+    async fn upnp(&self, port: u16, lifetime_secs: u32) -> Result<PortMappingResult, MappingError> {
+        try_upnp_mapping(port, lifetime_secs).await
+    }
+
+    async fn external_ip(&self) -> Result<IpAddr, MappingError> {
+        detect_external_ip().await
+    }
+
+    async fn reachability(&self, mapping: &PortMappingResult) -> ReachabilityStatus {
+        verify_external_reachability(mapping, 5).await
+    }
+}
+
+/// Try one mapping protocol on its own, without falling back to the others
+///
+/// Used by the Diagnostics screen to test a single protocol. Only PCP,
+/// NAT-PMP, UPnP and IPv6 can be tested this way.
+pub async fn try_single_protocol(
+    protocol: MappingProtocol,
+    port: u16,
+) -> Result<PortMappingResult, MappingError> {
+    info!("Testing {:?} alone for port {}", protocol, port);
+    match protocol {
+        MappingProtocol::PCP => try_pcp_mapping(port, DEFAULT_MAPPING_LIFETIME_SECS).await,
+        MappingProtocol::NATPMP => try_natpmp_mapping(port, DEFAULT_MAPPING_LIFETIME_SECS).await,
+        MappingProtocol::UPnP => try_upnp_mapping(port, DEFAULT_MAPPING_LIFETIME_SECS).await,
+        MappingProtocol::IPv6 => check_ipv6_connectivity(port).await,
+        MappingProtocol::Direct | MappingProtocol::Manual => Err(MappingError::NotSupported),
+    }
+}
+
+/// Delete a mapping through the protocol that created it
+///
+/// PCP and NAT-PMP mappings are deleted by requesting lifetime 0, UPnP ones
+/// with `DeletePortMapping`. IPv6, direct and manual connectivity have no
+/// router mapping to delete.
+///
+/// # Returns
+/// A line describing the router's answer
+pub async fn delete_port_mapping(
+    protocol: MappingProtocol,
+    port: u16,
+) -> Result<String, MappingError> {
+    info!("Deleting {:?} mapping for port {}", protocol, port);
+    match protocol {
+        MappingProtocol::PCP => {
+            let answer = try_pcp_mapping(port, 0).await?;
+            Ok(format!("PCP mapping for port {} deleted (router granted lifetime {}s)", port, answer.lifetime_secs))
+        }
+        MappingProtocol::NATPMP => {
+            let answer = try_natpmp_mapping(port, 0).await?;
+            Ok(format!("NAT-PMP mapping for port {} deleted (router granted lifetime {}s)", port, answer.lifetime_secs))
+        }
+        MappingProtocol::UPnP => {
+            delete_upnp_mapping(port, IpProtocol::TCP).await?;
+            Ok(format!("UPnP mapping for port {} deleted (DeletePortMapping accepted)", port))
+        }
+        MappingProtocol::IPv6 | MappingProtocol::Direct | MappingProtocol::Manual => Err(MappingError::NotSupported),
+    }
+}
+
 /// Verify connectivity after transport server is running
 ///
 /// This function should be called AFTER the transport server has started listening.
@@ -21,11 +125,23 @@ use tracing::{debug, error, info, warn};
 ///
 /// # Returns
 /// * Updated `ConnectivityResult` with externally_reachable field set
-pub async fn verify_connectivity_health(mut result: ConnectivityResult) -> ConnectivityResult {
+pub async fn verify_connectivity_health(result: ConnectivityResult) -> ConnectivityResult {
+    verify_connectivity_health_with(result, &SystemStrategies, &ConnectivityEvents::new()).await
+}
+
+/// Verify connectivity through `strategies`, reporting the verdict to `events`
+///
+/// Same as `verify_connectivity_health`; a verdict for the mapping that
+/// differs from the known one is emitted as `ReachabilityChanged`.
+pub async fn verify_connectivity_health_with<S: MappingStrategies>(
+    mut result: ConnectivityResult,
+    strategies: &S,
+    events: &ConnectivityEvents,
+) -> ConnectivityResult {
     if let Some(mapping) = &result.mapping {
         info!("Verifying external reachability of {}:{}", mapping.external_ip, mapping.external_port);
 
-        match verify_external_reachability(mapping, 5).await {
+        match strategies.reachability(mapping).await {
             ReachabilityStatus::Reachable => {
                 info!("✓ Port is confirmed reachable from external networks");
                 result.externally_reachable = Some(true);
@@ -44,6 +160,7 @@ pub async fn verify_connectivity_health(mut result: ConnectivityResult) -> Conne
                 result.externally_reachable = None;
             }
         }
+        events.observe_reachability(mapping, result.externally_reachable);
     } else {
         warn!("No mapping available to verify");
         result.externally_reachable = Some(false);
@@ -97,81 +214,118 @@ pub async fn verify_connectivity_health(mut result: ConnectivityResult) -> Conne
 /// # }
 /// ```
 pub async fn establish_connectivity(port: u16) -> ConnectivityResult {
+    establish_connectivity_with(port, &SystemStrategies, &ConnectivityEvents::new()).await
+}
+
+/// Establish connectivity through `strategies`, reporting to `events`
+///
+/// Same ladder as `establish_connectivity`. Each strategy tried is reported
+/// as it finishes, then the mapping changes and the whole result (see
+/// `ConnectivityEvents::observe_result`).
+pub async fn establish_connectivity_with<S: MappingStrategies>(
+    port: u16,
+    strategies: &S,
+    events: &ConnectivityEvents,
+) -> ConnectivityResult {
+    let result = run_strategies(port, strategies, events).await;
+    events.observe_result(&result);
+    result
+}
+
+/// Try each strategy in turn until one produces a mapping
+async fn run_strategies<S: MappingStrategies>(
+    port: u16,
+    strategies: &S,
+    events: &ConnectivityEvents,
+) -> ConnectivityResult {
     info!(
         "Establishing connectivity for port {} (trying IPv6 → PCP → NAT-PMP → UPnP → HTTP IP detection)",
         port
     );
 
     let mut result = ConnectivityResult::new();
-    let lifetime_secs = 3600; // 1 hour default lifetime for NAT mappings
+    let lifetime_secs = DEFAULT_MAPPING_LIFETIME_SECS;
 
     // Strategy 1: IPv6 direct connectivity
     info!("Attempting IPv6 direct connectivity...");
-    match check_ipv6_connectivity(port).await {
+    let outcome = strategies.ipv6(port).await;
+    match outcome {
         Ok(mapping) => {
             info!("IPv6 connectivity successful");
             result.cgnat_detected = detect_cgnat(mapping.external_ip);
             result.ipv6 = StrategyAttempt::Success(mapping.clone());
+            events.observe_attempt(MappingProtocol::IPv6, &result.ipv6);
             result.mapping = Some(mapping);
             return result; // Early return - IPv6 is best
         }
         Err(e) => {
             debug!("IPv6 not available: {}", e);
             result.ipv6 = StrategyAttempt::Failed(e.to_string());
+            events.observe_attempt(MappingProtocol::IPv6, &result.ipv6);
         }
     }
 
     // Strategy 2: PCP (Port Control Protocol)
     info!("Attempting PCP mapping...");
-    match try_pcp_mapping(port, lifetime_secs).await {
+    let report = strategies.pcp(port, lifetime_secs).await;
+    result.gateway_probes.extend(report.probes);
+    match report.result {
         Ok(mapping) => {
             info!("PCP mapping successful");
             result.cgnat_detected = detect_cgnat(mapping.external_ip);
             result.pcp = StrategyAttempt::Success(mapping.clone());
+            events.observe_attempt(MappingProtocol::PCP, &result.pcp);
             result.mapping = Some(mapping);
             return result; // Early return - found a working method
         }
         Err(e) => {
             debug!("PCP failed: {}", e);
             result.pcp = StrategyAttempt::Failed(e.to_string());
+            events.observe_attempt(MappingProtocol::PCP, &result.pcp);
         }
     }
 
     // Strategy 3: NAT-PMP (legacy)
     info!("Attempting NAT-PMP mapping...");
-    match try_natpmp_mapping(port, lifetime_secs).await {
+    let report = strategies.natpmp(port, lifetime_secs).await;
+    result.gateway_probes.extend(report.probes);
+    match report.result {
         Ok(mapping) => {
             info!("NAT-PMP mapping successful");
             result.cgnat_detected = detect_cgnat(mapping.external_ip);
             result.natpmp = StrategyAttempt::Success(mapping.clone());
+            events.observe_attempt(MappingProtocol::NATPMP, &result.natpmp);
             result.mapping = Some(mapping);
             return result; // Early return - found a working method
         }
         Err(e) => {
             debug!("NAT-PMP failed: {}", e);
             result.natpmp = StrategyAttempt::Failed(e.to_string());
+            events.observe_attempt(MappingProtocol::NATPMP, &result.natpmp);
         }
     }
 
     // Strategy 4: UPnP IGD (slowest but most universal)
     info!("Attempting UPnP mapping...");
-    match try_upnp_mapping(port, lifetime_secs).await {
+    match strategies.upnp(port, lifetime_secs).await {
         Ok(mapping) => {
             info!("UPnP mapping successful");
             result.cgnat_detected = detect_cgnat(mapping.external_ip);
             result.upnp = StrategyAttempt::Success(mapping.clone());
+            events.observe_attempt(MappingProtocol::UPnP, &result.upnp);
             result.mapping = Some(mapping);
             return result; // Success!
         }
         Err(e) => {
             warn!("UPnP failed: {}", e);
             result.upnp = StrategyAttempt::Failed(e.to_string());
+            events.observe_attempt(MappingProtocol::UPnP, &result.upnp);
         }
     }
 
     // All NAT traversal strategies failed - try HTTP-based IP detection as final fallback
     warn!("All NAT traversal protocols failed. Attempting HTTP-based IP detection...");
-    match detect_external_ip().await {
+    match strategies.external_ip().await {
         Ok(external_ip) => {
             info!("External IP detected via HTTP: {}", external_ip);
 
@@ -191,6 +345,7 @@ pub async fn establish_connectivity(port: u16) -> ConnectivityResult {
 
             result.cgnat_detected = detect_cgnat(external_ip);
             result.http = StrategyAttempt::Success(mapping.clone());
+            events.observe_attempt(MappingProtocol::Direct, &result.http);
             result.mapping = Some(mapping);
 
             info!("Connectivity established via HTTP IP detection (direct mode, no NAT mapping)");
@@ -199,6 +354,7 @@ pub async fn establish_connectivity(port: u16) -> ConnectivityResult {
         Err(e) => {
             error!("HTTP IP detection failed: {}", e);
             result.http = StrategyAttempt::Failed(e.to_string());
+            events.observe_attempt(MappingProtocol::Direct, &result.http);
         }
     }
 
@@ -28,9 +28,10 @@
 //! # }
 //! ```
 
-use super::gateway::find_default_gateway;
-use super::types::{IpProtocol, MappingError, MappingProtocol, PortMappingResult};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use super::gateway::find_candidate_gateways;
+use super::gateway_probe::{connect_udp, label_probe_interfaces, probe_gateways, recv_with_timeout};
+use super::types::{GatewayProbeReport, IpProtocol, MappingError, MappingProtocol, PortMappingResult};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::{debug, info, warn};
 
@@ -116,9 +117,8 @@ impl PcpResultCode {
 
 /// Attempt to create a port mapping using PCP (Port Control Protocol)
 ///
-/// This function sends a PCP MAP request to the default gateway and waits
-/// for a response. If successful, it returns the external IP, port, and
-/// lifetime of the mapping.
+/// This function sends a PCP MAP request to every candidate gateway at once
+/// and returns the first successful mapping (external IP, port, and lifetime).
 ///
 /// # Arguments
 ///
@@ -159,44 +159,89 @@ pub async fn try_pcp_mapping_with_protocol(
     lifetime_secs: u32,
     protocol: IpProtocol,
 ) -> Result<PortMappingResult, MappingError> {
+    try_pcp_mapping_with_report(local_port, lifetime_secs, protocol)
+        .await
+        .result
+}
+
+/// Attempt a PCP mapping and report the outcome of every gateway probed
+///
+/// Same as `try_pcp_mapping_with_protocol`, but keeps the per-gateway outcomes
+/// for diagnostics.
+pub async fn try_pcp_mapping_with_report(
+    local_port: u16,
+    lifetime_secs: u32,
+    protocol: IpProtocol,
+) -> GatewayProbeReport {
     info!(
         "Attempting PCP mapping for port {} (lifetime: {}s, protocol: {:?})",
         local_port, lifetime_secs, protocol
     );
 
-    // Find default gateway
-    let gateway = find_default_gateway()?;
-    debug!("Found default gateway: {}", gateway);
+    let candidates = match find_candidate_gateways() {
+        Ok(candidates) => candidates,
+        Err(e) => return GatewayProbeReport::failed(e),
+    };
+    debug!("Found {} candidate gateway(s): {:?}", candidates.len(), candidates);
+
+    let servers: Vec<SocketAddr> = candidates
+        .iter()
+        .map(|c| SocketAddr::new(c.ip, PCP_SERVER_PORT))
+        .collect();
+    let mut report = probe_pcp_gateways(&servers, local_port, lifetime_secs, protocol, PCP_TIMEOUT).await;
+    label_probe_interfaces(&mut report.probes, &candidates);
+    report
+}
 
-    // Create UDP socket for PCP communication
-    let socket = UdpSocket::bind("0.0.0.0:0")?;
-    socket.set_read_timeout(Some(PCP_TIMEOUT))?;
-    socket.set_write_timeout(Some(PCP_TIMEOUT))?;
+/// Send a PCP MAP request to several PCP servers concurrently
+///
+/// Returns the first valid mapping; the other requests are cancelled. Each
+/// server waits at most `timeout` for an answer.
+///
+/// # Arguments
+///
+/// * `servers` - PCP server addresses (gateway IP and port 5351 in production)
+/// * `local_port` - The local port to map
+/// * `lifetime_secs` - Requested lifetime in seconds (0 = delete mapping)
+/// * `protocol` - IP protocol (TCP or UDP)
+/// * `timeout` - How long to wait for each server
+pub async fn probe_pcp_gateways(
+    servers: &[SocketAddr],
+    local_port: u16,
+    lifetime_secs: u32,
+    protocol: IpProtocol,
+    timeout: Duration,
+) -> GatewayProbeReport {
+    probe_gateways(MappingProtocol::PCP, servers, |server| {
+        pcp_map_request(server, local_port, lifetime_secs, protocol, timeout)
+    })
+    .await
+}
 
-    // Get local IP address from the socket
+/// Send one PCP MAP request and wait for the response
+async fn pcp_map_request(
+    server: SocketAddr,
+    local_port: u16,
+    lifetime_secs: u32,
+    protocol: IpProtocol,
+    timeout: Duration,
+) -> Result<PortMappingResult, MappingError> {
+    let socket = connect_udp(server).await?;
+
+    // Local IP address on the interface that routes to this gateway
     let local_ip = socket.local_addr()?.ip();
 
     // Build PCP MAP request
     let request = build_pcp_map_request(local_ip, local_port, lifetime_secs, protocol);
 
-    // Send request to gateway
-    let server_addr = SocketAddr::new(gateway, PCP_SERVER_PORT);
-    socket.send_to(&request, server_addr)?;
-    debug!("Sent PCP MAP request to {}", server_addr);
+    socket.send(&request).await?;
+    debug!("Sent PCP MAP request to {}", server);
 
     // Receive response
     let mut response_buf = [0u8; 1100]; // PCP response can be up to 1100 bytes
-    let (bytes_received, _) = socket
-        .recv_from(&mut response_buf)
-        .map_err(|e| {
-            if e.kind() == std::io::ErrorKind::WouldBlock {
-                MappingError::Timeout
-            } else {
-                MappingError::Io(e)
-            }
-        })?;
-
-    debug!("Received {} bytes from PCP server", bytes_received);
+    let bytes_received = recv_with_timeout(&socket, &mut response_buf, timeout).await?;
+
+    debug!("Received {} bytes from PCP server {}", bytes_received, server);
 
     // Parse response
     parse_pcp_map_response(&response_buf[..bytes_received], local_port)
@@ -244,7 +289,7 @@ pub(crate) fn build_pcp_map_request(
 }
 
 /// Parse a PCP MAP response packet
-fn parse_pcp_map_response(
+pub(crate) fn parse_pcp_map_response(
     response: &[u8],
     expected_internal_port: u16,
 ) -> Result<PortMappingResult, MappingError> {
@@ -1,7 +1,7 @@
 //! Common types for connectivity module
 
 use serde::{Deserialize, Serialize};
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
 use thiserror::Error;
 
 /// Result of a port mapping operation
@@ -36,6 +36,20 @@ pub enum MappingProtocol {
     Manual,
 }
 
+impl MappingProtocol {
+    /// Display name, e.g. "NAT-PMP"
+    pub fn name(self) -> &'static str {
+        match self {
+            MappingProtocol::PCP => "PCP",
+            MappingProtocol::NATPMP => "NAT-PMP",
+            MappingProtocol::UPnP => "UPnP",
+            MappingProtocol::IPv6 => "IPv6",
+            MappingProtocol::Direct => "Direct",
+            MappingProtocol::Manual => "Manual",
+        }
+    }
+}
+
 /// Errors that can occur during port mapping
 #[derive(Debug, Error)]
 pub enum MappingError {
@@ -79,6 +93,51 @@ pub enum StrategyAttempt {
     Failed(String),
 }
 
+/// Outcome of probing a single gateway
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum GatewayProbeOutcome {
+    /// Gateway created a mapping
+    Mapped(PortMappingResult),
+    /// Gateway did not answer or returned an error
+    Failed(String),
+    /// Probe was stopped because another gateway answered first
+    Cancelled,
+}
+
+/// Record of one gateway probed while creating a port mapping
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GatewayProbe {
+    /// Protocol used for the probe (PCP or NAT-PMP)
+    pub protocol: MappingProtocol,
+    /// Gateway address and port the request was sent to
+    pub server: SocketAddr,
+    /// Network interface the gateway was discovered on, if known
+    pub interface: Option<String>,
+    /// What happened
+    pub outcome: GatewayProbeOutcome,
+    /// Time until the outcome was known, in milliseconds
+    pub elapsed_ms: u64,
+}
+
+/// Result of probing several gateways concurrently
+#[derive(Debug)]
+pub struct GatewayProbeReport {
+    /// First mapping created, or the most informative error if none answered
+    pub result: Result<PortMappingResult, MappingError>,
+    /// Per-gateway outcomes, in probe order
+    pub probes: Vec<GatewayProbe>,
+}
+
+impl GatewayProbeReport {
+    /// Report for a probe that could not start (e.g. no gateway found)
+    pub fn failed(error: MappingError) -> Self {
+        Self {
+            result: Err(error),
+            probes: Vec::new(),
+        }
+    }
+}
+
 /// Complete result of connectivity orchestration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ConnectivityResult {
@@ -98,6 +157,9 @@ pub struct ConnectivityResult {
     pub cgnat_detected: bool,
     /// Whether external reachability was verified (port is actually accessible)
     pub externally_reachable: Option<bool>,
+    /// Per-gateway outcomes of PCP and NAT-PMP probes
+    #[serde(default)]
+    pub gateway_probes: Vec<GatewayProbe>,
 }
 
 impl ConnectivityResult {
@@ -112,6 +174,7 @@ impl ConnectivityResult {
             mapping: None,
             cgnat_detected: false,
             externally_reachable: None,
+            gateway_probes: Vec::new(),
         }
     }
 
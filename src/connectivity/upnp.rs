@@ -193,29 +193,35 @@ pub async fn delete_upnp_mapping(
     );
 
     tokio::task::spawn_blocking(move || {
-        // Search for gateway
-        let gateway = igd_next::search_gateway(igd_next::SearchOptions {
-            timeout: Some(UPNP_TIMEOUT),
-            ..Default::default()
-        })
-        .map_err(|_| MappingError::NoGateway)?;
-
-        // Convert protocol
-        let upnp_protocol = match protocol {
-            IpProtocol::TCP => igd_next::PortMappingProtocol::TCP,
-            IpProtocol::UDP => igd_next::PortMappingProtocol::UDP,
-        };
-
-        // Remove port mapping
-        gateway
-            .remove_port(upnp_protocol, local_port)
-            .map_err(|e| {
-                MappingError::GatewayError(format!("DeletePortMapping failed: {}", e))
-            })?;
-
+        remove_mapping_blocking(local_port, protocol, UPNP_TIMEOUT)?;
         info!("UPnP mapping deleted successfully");
         Ok(())
     })
     .await
     .map_err(|e| MappingError::Internal(format!("Task join error: {}", e)))?
 }
+
+/// Blocking removal of a UPnP port mapping, searching `timeout` for the gateway
+pub(crate) fn remove_mapping_blocking(
+    local_port: u16,
+    protocol: IpProtocol,
+    timeout: Duration,
+) -> Result<(), MappingError> {
+    // Search for gateway
+    let gateway = igd_next::search_gateway(igd_next::SearchOptions {
+        timeout: Some(timeout),
+        ..Default::default()
+    })
+    .map_err(|_| MappingError::NoGateway)?;
+
+    // Convert protocol
+    let upnp_protocol = match protocol {
+        IpProtocol::TCP => igd_next::PortMappingProtocol::TCP,
+        IpProtocol::UDP => igd_next::PortMappingProtocol::UDP,
+    };
+
+    // Remove port mapping
+    gateway
+        .remove_port(upnp_protocol, local_port)
+        .map_err(|e| MappingError::GatewayError(format!("DeletePortMapping failed: {}", e)))
+}
@@ -13,9 +13,22 @@ use chacha20poly1305::{
 };
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use ring::digest::{Context, SHA256};
+use ring::hkdf;
 use serde::{Deserialize, Serialize};
 use x25519_dalek::PublicKey as X25519PublicKey;
 
+/// HKDF salt for the message queue content key
+const QUEUE_KEY_SALT: &[u8] = b"pure2p queue at rest v1";
+
+/// HKDF info for the message queue content key
+const QUEUE_KEY_INFO: &[u8] = b"message_queue.content";
+
+/// HKDF salt for the database snapshot key
+const SNAPSHOT_KEY_SALT: &[u8] = b"pure2p snapshot at rest v1";
+
+/// HKDF info for the database snapshot key
+const SNAPSHOT_KEY_INFO: &[u8] = b"pure2p.db.snapshot";
+
 /// Unique identifier derived from public key fingerprint
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct UID(String);
@@ -132,6 +145,36 @@ impl KeyPair {
         &self.uid
     }
 
+    /// Key that seals queued message content at rest
+    ///
+    /// HKDF-SHA256 over the Ed25519 seed: the same identity always gets the
+    /// same key, and a new identity gets a different one.
+    pub fn queue_content_key(&self) -> Result<[u8; 32]> {
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, QUEUE_KEY_SALT).extract(&self.private_key);
+        let okm = prk
+            .expand(&[QUEUE_KEY_INFO], hkdf::HKDF_SHA256)
+            .map_err(|_| Error::Crypto("Queue key derivation failed".to_string()))?;
+        let mut key = [0u8; 32];
+        okm.fill(&mut key)
+            .map_err(|_| Error::Crypto("Queue key derivation failed".to_string()))?;
+        Ok(key)
+    }
+
+    /// Key that seals database snapshots at rest
+    ///
+    /// Derived like `queue_content_key()` under its own salt, so a snapshot
+    /// opens only for the identity that took it.
+    pub fn snapshot_key(&self) -> Result<[u8; 32]> {
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, SNAPSHOT_KEY_SALT).extract(&self.private_key);
+        let okm = prk
+            .expand(&[SNAPSHOT_KEY_INFO], hkdf::HKDF_SHA256)
+            .map_err(|_| Error::Crypto("Snapshot key derivation failed".to_string()))?;
+        let mut key = [0u8; 32];
+        okm.fill(&mut key)
+            .map_err(|_| Error::Crypto("Snapshot key derivation failed".to_string()))?;
+        Ok(key)
+    }
+
     /// Derive a shared secret with a remote peer's X25519 public key
     pub fn derive_shared_secret(&self, remote_x25519_public: &[u8; 32]) -> Result<[u8; 32]> {
         let local_secret_bytes: [u8; 32] = self.x25519_secret.as_slice()
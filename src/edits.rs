@@ -0,0 +1,214 @@
+//! Editing sent messages
+//!
+//! An outgoing text message can be edited within `Settings::edit_window_minutes`
+//! of sending. How the edit travels depends on where the message is:
+//!
+//! - still in the queue: the queued row and the chat copy are changed in
+//!   place and nothing extra is sent (`EditRoute::LocalOnly`)
+//! - delivered to a peer that advertised edit support: an `edit` message
+//!   (`EditRequest`, sealed like text) names the original by the ID the
+//!   sender gave it (`MessageRequest::message_id`) (`EditRoute::Wire`)
+//! - delivered to an older peer: a normal message quoting the original and
+//!   carrying the correction (`EditRoute::Correction`)
+//!
+//! Both sides keep the replaced content in `Message::edit_history`; the chat
+//! view marks edited messages "(edited)" and the details popup shows each
+//! version as a word-level diff against the next.
+
+use crate::{
+    storage::{Chat, ContentKind, Message},
+    Error, Result,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Message type of an edit
+pub const EDIT_TYPE: &str = "edit";
+
+/// Default minutes after sending during which a message can be edited
+pub const DEFAULT_EDIT_WINDOW_MINUTES: u32 = 15;
+
+/// Word pairs compared at most by `word_diff`; longer texts are shown as
+/// one removal and one addition
+const MAX_DIFF_CELLS: usize = 250_000;
+
+/// Payload of an edit (CBOR)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditRequest {
+    /// Sender's ID of the edited message
+    pub message_id: String,
+    /// New content
+    pub content: Vec<u8>,
+    /// When the edit was made (Unix milliseconds)
+    pub edited_at: i64,
+}
+
+impl EditRequest {
+    /// Encode as a message payload
+    ///
+    /// # Errors
+    /// `Error::CborSerialization` if encoding fails
+    pub fn to_payload(&self) -> Result<Vec<u8>> {
+        serde_cbor::to_vec(self).map_err(|e| Error::CborSerialization(format!("Failed to serialize edit: {}", e)))
+    }
+
+    /// Decode from a message payload
+    ///
+    /// # Errors
+    /// `Error::CborSerialization` if the payload is not a valid edit
+    pub fn from_payload(payload: &[u8]) -> Result<Self> {
+        serde_cbor::from_slice(payload).map_err(|e| Error::CborSerialization(format!("Failed to deserialize edit: {}", e)))
+    }
+}
+
+/// How an edit reaches the peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditRoute {
+    /// The message is still queued: change it in place, send nothing extra
+    LocalOnly,
+    /// Send an `edit` message
+    Wire,
+    /// The peer does not understand edits: send a quoted correction
+    Correction,
+}
+
+impl EditRoute {
+    /// Route for a message that is (or is not) still queued to a contact that
+    /// does (or does not) support edits
+    pub fn choose(queued: bool, peer_supports_edits: bool) -> Self {
+        match (queued, peer_supports_edits) {
+            (true, _) => EditRoute::LocalOnly,
+            (false, true) => EditRoute::Wire,
+            (false, false) => EditRoute::Correction,
+        }
+    }
+}
+
+/// Check that `message` may be edited by `my_uid` at `now`
+///
+/// # Errors
+/// `Error::InvalidMessage` saying why not: someone else's message, not text,
+/// editing turned off (`window_minutes` 0) or the window has passed
+pub fn check_editable(message: &Message, my_uid: &str, window_minutes: u32, now: DateTime<Utc>) -> Result<()> {
+    if message.sender != my_uid {
+        return Err(Error::InvalidMessage("only your own messages can be edited".to_string()));
+    }
+    if message.content_kind() != ContentKind::Text {
+        return Err(Error::InvalidMessage("only text messages can be edited".to_string()));
+    }
+    if window_minutes == 0 {
+        return Err(Error::InvalidMessage("editing is turned off in Settings".to_string()));
+    }
+    let sent_at = DateTime::from_timestamp_millis(message.timestamp).unwrap_or(now);
+    if now - sent_at > Duration::minutes(window_minutes as i64) {
+        return Err(Error::InvalidMessage(format!(
+            "messages can only be edited within {} minutes of sending",
+            window_minutes
+        )));
+    }
+    Ok(())
+}
+
+/// Text of the correction sent to peers without edit support
+///
+/// The original is quoted line by line ("> "), followed by the new text.
+pub fn correction_text(original: &str, corrected: &str) -> String {
+    let mut text: String = original.lines().map(|line| format!("> {}\n", line)).collect();
+    text.push_str(corrected);
+    text
+}
+
+/// Apply an edit received from `from_uid` to its message in `chat`
+///
+/// Only the sender's own messages are matched (by `Message::remote_id`).
+/// An edit older than the current version is ignored, so edits arriving out
+/// of order settle on the latest.
+///
+/// # Returns
+/// Whether a message was changed
+pub fn apply_incoming_edit(chat: &mut Chat, from_uid: &str, edit: &EditRequest) -> bool {
+    let Some(message) = chat
+        .messages_mut()
+        .iter_mut()
+        .find(|m| m.sender == from_uid && m.remote_id.as_deref() == Some(edit.message_id.as_str()))
+    else {
+        return false;
+    };
+    let current_since = message.edit_history.last().map_or(message.timestamp, |e| e.replaced_at);
+    if edit.edited_at <= current_since {
+        return false;
+    }
+    message.replace_content(edit.content.clone(), edit.edited_at);
+    true
+}
+
+/// One run of words in a diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSpan {
+    /// Words in both versions
+    Same(String),
+    /// Words only in the older version
+    Removed(String),
+    /// Words only in the newer version
+    Added(String),
+}
+
+/// Word-level diff from `old` to `new` (longest common subsequence of words)
+///
+/// Words are split on whitespace and joined with single spaces; consecutive
+/// words of the same kind form one span.
+pub fn word_diff(old: &str, new: &str) -> Vec<DiffSpan> {
+    let old: Vec<&str> = old.split_whitespace().collect();
+    let new: Vec<&str> = new.split_whitespace().collect();
+
+    let mut ops: Vec<(u8, &str)> = Vec::new();
+    if old.len().saturating_mul(new.len()) > MAX_DIFF_CELLS {
+        ops.extend(old.iter().map(|w| (b'-', *w)));
+        ops.extend(new.iter().map(|w| (b'+', *w)));
+    } else {
+        // lcs[i][j]: common words of old[i..] and new[j..]
+        let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && old[i] == new[j] {
+                ops.push((b'=', old[i]));
+                i += 1;
+                j += 1;
+            } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push((b'-', old[i]));
+                i += 1;
+            } else {
+                ops.push((b'+', new[j]));
+                j += 1;
+            }
+        }
+    }
+
+    let mut spans: Vec<(u8, String)> = Vec::new();
+    for (kind, word) in ops {
+        match spans.last_mut() {
+            Some((last, text)) if *last == kind => {
+                text.push(' ');
+                text.push_str(word);
+            }
+            _ => spans.push((kind, word.to_string())),
+        }
+    }
+    spans
+        .into_iter()
+        .map(|(kind, text)| match kind {
+            b'=' => DiffSpan::Same(text),
+            b'-' => DiffSpan::Removed(text),
+            _ => DiffSpan::Added(text),
+        })
+        .collect()
+}
@@ -0,0 +1,473 @@
+//! Invite codes
+//!
+//! An invite is a short code that a peer redeems over `POST /invite` for our
+//! contact token, so it can be read out or typed instead of pasting a token.
+//! Codes are short enough to guess, so redemption is hardened:
+//!
+//! - only a SHA-256 hash of each code is kept, and lookups compare every
+//!   stored hash in constant time
+//! - failed lookups are rate-limited per source IP (`MAX_FAILURES_PER_WINDOW`
+//!   per minute, then an exponentially growing lockout)
+//! - an invite can be bound to the redeemer's public key, proven by a
+//!   signature over the code
+//! - every failed attempt is written to the request log with its source
+
+use crate::{cache::BoundedCache, crypto::verify_contact_token, storage::Storage, Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+
+/// HTTP path of the invite redemption endpoint
+pub const INVITE_PATH: &str = "/invite";
+
+/// Shortest allowed invite code
+pub const MIN_INVITE_CODE_LENGTH: usize = 8;
+
+/// Longest allowed invite code
+pub const MAX_INVITE_CODE_LENGTH: usize = 16;
+
+/// Invite code length used unless configured otherwise
+pub const DEFAULT_INVITE_CODE_LENGTH: usize = 10;
+
+/// Codes shorter than this get a warning when valid for longer than `WEAK_CODE_MAX_VALIDITY_HOURS`
+pub const STRONG_INVITE_CODE_LENGTH: usize = 12;
+
+/// Longest validity that is fine for short codes, in hours
+pub const WEAK_CODE_MAX_VALIDITY_HOURS: i64 = 24;
+
+/// Failed lookups per source IP allowed within `FAILURE_WINDOW_SECS`
+pub const MAX_FAILURES_PER_WINDOW: usize = 5;
+
+/// Window over which failed lookups are counted, in seconds
+pub const FAILURE_WINDOW_SECS: i64 = 60;
+
+/// First lockout after exceeding the failure limit, in seconds (doubles each time)
+pub const BASE_LOCKOUT_SECS: i64 = 60;
+
+/// Longest lockout, in seconds
+pub const MAX_LOCKOUT_SECS: i64 = 24 * 60 * 60;
+
+/// Most source IPs the limiter tracks; past that the least recent is forgotten
+pub const MAX_TRACKED_SOURCES: usize = 4096;
+
+/// Unambiguous code alphabet (no 0/O, 1/I): 32 symbols, 5 bits per character
+const INVITE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// Check that an invite code length is within the allowed range
+///
+/// # Errors
+/// `Error::Invite` if `length` is outside `MIN_INVITE_CODE_LENGTH..=MAX_INVITE_CODE_LENGTH`
+pub fn validate_code_length(length: usize) -> Result<()> {
+    if (MIN_INVITE_CODE_LENGTH..=MAX_INVITE_CODE_LENGTH).contains(&length) {
+        Ok(())
+    } else {
+        Err(Error::Invite(format!(
+            "Invite code length must be {}-{} characters, got {}",
+            MIN_INVITE_CODE_LENGTH, MAX_INVITE_CODE_LENGTH, length
+        )))
+    }
+}
+
+/// Entropy of a generated code of `length` characters, in bits
+pub fn code_entropy_bits(length: usize) -> u32 {
+    length as u32 * 5
+}
+
+/// Warning for the share screen when a short code stays valid for long
+pub fn weak_code_warning(length: usize, validity: Duration) -> Option<String> {
+    if length < STRONG_INVITE_CODE_LENGTH && validity > Duration::hours(WEAK_CODE_MAX_VALIDITY_HOURS) {
+        Some(format!(
+            "⚠ {}-character invite code ({} bits) valid for {} days: use {}+ characters or a validity under {} hours",
+            length,
+            code_entropy_bits(length),
+            validity.num_days(),
+            STRONG_INVITE_CODE_LENGTH,
+            WEAK_CODE_MAX_VALIDITY_HOURS
+        ))
+    } else {
+        None
+    }
+}
+
+/// Generate a random invite code of `length` characters
+///
+/// # Errors
+/// `Error::Invite` if the length is out of range
+pub fn generate_invite_code(length: usize) -> Result<String> {
+    validate_code_length(length)?;
+    let mut rng = rand::rngs::OsRng;
+    Ok((0..length)
+        .map(|_| INVITE_ALPHABET[rng.gen_range(0..INVITE_ALPHABET.len())] as char)
+        .collect())
+}
+
+/// Hash of an invite code as stored (case, spaces and dashes ignored)
+pub fn hash_invite_code(code: &str) -> [u8; 32] {
+    let normalized: String = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(digest(&SHA256, normalized.as_bytes()).as_ref());
+    hash
+}
+
+/// Compare two byte strings in time independent of where they differ
+///
+/// Only the length (public for fixed-size hashes) can end the comparison early.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
+/// Redemption request sent to `POST /invite` (CBOR-encoded)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteRedemption {
+    /// The invite code
+    pub code: String,
+    /// Redeemer's Ed25519 public key (required for key-bound invites)
+    #[serde(default)]
+    pub public_key: Option<Vec<u8>>,
+    /// Redeemer's signature over the normalized code hash
+    #[serde(default)]
+    pub signature: Option<Vec<u8>>,
+}
+
+impl InviteRedemption {
+    /// Redemption without a key (for unbound invites)
+    pub fn new(code: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            public_key: None,
+            signature: None,
+        }
+    }
+
+    /// Redemption signed with `keypair`, proving ownership for key-bound invites
+    ///
+    /// # Errors
+    /// Returns an error if signing fails
+    pub fn signed(code: impl Into<String>, keypair: &crate::crypto::KeyPair) -> Result<Self> {
+        let code = code.into();
+        let signature = keypair.sign(&hash_invite_code(&code))?;
+        Ok(Self {
+            code,
+            public_key: Some(keypair.public_key.clone()),
+            signature: Some(signature),
+        })
+    }
+
+    /// Whether the request carries a valid signature by `public_key`
+    fn proves_key(&self, bound_key: &[u8; 32]) -> bool {
+        let (Some(public_key), Some(signature)) = (&self.public_key, &self.signature) else {
+            return false;
+        };
+        let (Ok(public_key), Ok(signature)) = (
+            <[u8; 32]>::try_from(public_key.as_slice()),
+            <[u8; 64]>::try_from(signature.as_slice()),
+        ) else {
+            return false;
+        };
+        constant_time_eq(&public_key, bound_key)
+            && verify_contact_token(&public_key, &hash_invite_code(&self.code), &signature).unwrap_or(false)
+    }
+}
+
+/// Response of `POST /invite` (CBOR-encoded)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteResponse {
+    /// Contact token of the inviting peer
+    pub contact_token: String,
+}
+
+/// Why an invite redemption was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InviteRejection {
+    /// No invite matches the code
+    UnknownCode,
+    /// The invite has expired
+    Expired,
+    /// A single-use invite was already redeemed
+    AlreadyRedeemed,
+    /// The invite is bound to another key, or the key proof is missing or invalid
+    KeyMismatch,
+    /// Too many failed attempts from this source
+    RateLimited {
+        /// When the source may try again
+        retry_after: DateTime<Utc>,
+    },
+}
+
+impl InviteRejection {
+    /// HTTP status returned for this rejection
+    ///
+    /// All code failures share one status so the response does not reveal
+    /// whether a code exists.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Self::RateLimited { .. } => 429,
+            _ => 403,
+        }
+    }
+
+    /// Whether this rejection is written to the audit trail
+    ///
+    /// Attempts refused while the source is locked out are not: the failures
+    /// that caused the lockout are already there, and a locked-out guesser
+    /// should not be able to grow the request log.
+    pub fn is_audited(&self) -> bool {
+        !matches!(self, Self::RateLimited { .. })
+    }
+}
+
+impl fmt::Display for InviteRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCode => write!(f, "unknown invite code"),
+            Self::Expired => write!(f, "invite expired"),
+            Self::AlreadyRedeemed => write!(f, "invite already redeemed"),
+            Self::KeyMismatch => write!(f, "invite bound to another key"),
+            Self::RateLimited { retry_after } => {
+                write!(f, "too many failed attempts, locked until {}", retry_after.to_rfc3339())
+            }
+        }
+    }
+}
+
+/// Failed attempts and lockout state of one source
+#[derive(Debug, Clone, Default)]
+struct SourceFailures {
+    /// Times of failures within the current window
+    recent: Vec<DateTime<Utc>>,
+    /// Lockouts so far (each one doubles the next)
+    lockouts: u32,
+    /// End of the current lockout
+    locked_until: Option<DateTime<Utc>>,
+}
+
+/// Per-source-IP limiter for failed invite lookups
+///
+/// At most `MAX_TRACKED_SOURCES` sources are kept, so a scan from many
+/// addresses cannot grow it without bound. A source forgotten that way
+/// starts over with no failures.
+#[derive(Debug)]
+pub struct InviteRateLimiter {
+    sources: BoundedCache<IpAddr, SourceFailures>,
+    /// Last time stale sources were dropped
+    pruned_at: Option<DateTime<Utc>>,
+}
+
+impl Default for InviteRateLimiter {
+    fn default() -> Self {
+        Self { sources: BoundedCache::new("invite rate limiter", MAX_TRACKED_SOURCES, None), pruned_at: None }
+    }
+}
+
+impl InviteRateLimiter {
+    /// Create a limiter with no recorded failures
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check whether `source` may attempt a lookup at `now`
+    ///
+    /// # Errors
+    /// `InviteRejection::RateLimited` while the source is locked out
+    pub fn check(&self, source: IpAddr, now: DateTime<Utc>) -> std::result::Result<(), InviteRejection> {
+        match self.sources.get_with_at(&source, now, |s| s.locked_until).flatten() {
+            Some(until) if until > now => Err(InviteRejection::RateLimited { retry_after: until }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a failed lookup from `source`
+    ///
+    /// The `MAX_FAILURES_PER_WINDOW`-th failure within `FAILURE_WINDOW_SECS`
+    /// starts a lockout of `BASE_LOCKOUT_SECS`, doubled for every further
+    /// lockout of the same source (capped at `MAX_LOCKOUT_SECS`).
+    pub fn record_failure(&mut self, source: IpAddr, now: DateTime<Utc>) {
+        self.prune(now);
+        let window_start = now - Duration::seconds(FAILURE_WINDOW_SECS);
+        self.sources.update_at(source, now, SourceFailures::default, |entry| {
+            entry.recent.retain(|t| *t > window_start);
+            entry.recent.push(now);
+
+            if entry.recent.len() >= MAX_FAILURES_PER_WINDOW {
+                let factor = 1i64 << entry.lockouts.min(20);
+                let lockout = (BASE_LOCKOUT_SECS * factor).min(MAX_LOCKOUT_SECS);
+                entry.locked_until = Some(now + Duration::seconds(lockout));
+                entry.lockouts += 1;
+                entry.recent.clear();
+            }
+        });
+    }
+
+    /// Number of sources currently tracked
+    pub fn tracked_sources(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Forget sources with no recent failures and no lockout in effect
+    ///
+    /// Sources that were locked out are kept for a day so repeat offenders
+    /// keep their escalated lockout. Runs at most once per
+    /// `FAILURE_WINDOW_SECS`, as it walks every source.
+    fn prune(&mut self, now: DateTime<Utc>) {
+        if self.pruned_at.is_some_and(|at| now < at + Duration::seconds(FAILURE_WINDOW_SECS)) {
+            return;
+        }
+        self.pruned_at = Some(now);
+        let window_start = now - Duration::seconds(FAILURE_WINDOW_SECS);
+        let lockout_memory = now - Duration::seconds(MAX_LOCKOUT_SECS);
+        self.sources.retain(|_, s| {
+            s.recent.iter().any(|t| *t > window_start)
+                || s.locked_until.is_some_and(|until| until > lockout_memory)
+        });
+    }
+}
+
+/// An invite as stored: only the code's hash is kept
+#[derive(Debug, Clone)]
+struct Invite {
+    code_hash: [u8; 32],
+    contact_token: String,
+    expires_at: DateTime<Utc>,
+    bound_key: Option<[u8; 32]>,
+    single_use: bool,
+    redeemed: bool,
+}
+
+/// Outstanding invites and the limiter guarding their redemption
+#[derive(Debug, Default)]
+pub struct InviteBook {
+    invites: Vec<Invite>,
+    limiter: InviteRateLimiter,
+}
+
+impl InviteBook {
+    /// Create an empty invite book
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an invite for `contact_token`
+    ///
+    /// # Arguments
+    /// * `code_length` - Code length (`MIN_INVITE_CODE_LENGTH..=MAX_INVITE_CODE_LENGTH`)
+    /// * `validity` - How long the code can be redeemed
+    /// * `bound_key` - Ed25519 public key that alone may redeem the code; key-bound
+    ///   invites are single-use
+    ///
+    /// # Returns
+    /// The generated code (not stored; show it to the user once)
+    ///
+    /// # Errors
+    /// `Error::Invite` if the length is out of range or the key is not 32 bytes
+    pub fn create(
+        &mut self,
+        contact_token: String,
+        code_length: usize,
+        validity: Duration,
+        bound_key: Option<&[u8]>,
+        now: DateTime<Utc>,
+    ) -> Result<String> {
+        let bound_key = bound_key
+            .map(|key| {
+                <[u8; 32]>::try_from(key)
+                    .map_err(|_| Error::Invite(format!("Bound key must be 32 bytes, got {}", key.len())))
+            })
+            .transpose()?;
+        let code = generate_invite_code(code_length)?;
+
+        self.invites.retain(|i| i.expires_at > now && !i.redeemed);
+        self.invites.push(Invite {
+            code_hash: hash_invite_code(&code),
+            contact_token,
+            expires_at: now + validity,
+            single_use: bound_key.is_some(),
+            bound_key,
+            redeemed: false,
+        });
+        Ok(code)
+    }
+
+    /// Number of invites that can still be redeemed at `now`
+    pub fn active_count(&self, now: DateTime<Utc>) -> usize {
+        self.invites.iter().filter(|i| i.expires_at > now && !i.redeemed).count()
+    }
+
+    /// Redeem an invite code for its contact token
+    ///
+    /// Locked-out sources are refused before the code is looked at. Every
+    /// stored hash is compared, so lookup time does not depend on which (if
+    /// any) invite matches. Any failure counts against the source.
+    ///
+    /// # Errors
+    /// The reason the redemption was refused
+    pub fn redeem(
+        &mut self,
+        source: IpAddr,
+        request: &InviteRedemption,
+        now: DateTime<Utc>,
+    ) -> std::result::Result<String, InviteRejection> {
+        self.limiter.check(source, now)?;
+
+        let presented = hash_invite_code(&request.code);
+        let mut found = None;
+        for (index, invite) in self.invites.iter().enumerate() {
+            if constant_time_eq(&invite.code_hash, &presented) {
+                found = Some(index);
+            }
+        }
+
+        let result = match found.map(|index| &mut self.invites[index]) {
+            None => Err(InviteRejection::UnknownCode),
+            Some(invite) if invite.expires_at <= now => Err(InviteRejection::Expired),
+            Some(invite) if invite.redeemed => Err(InviteRejection::AlreadyRedeemed),
+            Some(invite) if invite.bound_key.is_some_and(|key| !request.proves_key(&key)) => {
+                Err(InviteRejection::KeyMismatch)
+            }
+            Some(invite) => {
+                if invite.single_use {
+                    invite.redeemed = true;
+                }
+                Ok(invite.contact_token.clone())
+            }
+        };
+
+        if result.is_err() {
+            self.limiter.record_failure(source, now);
+        }
+        result
+    }
+
+    /// The rate limiter guarding redemption
+    pub fn limiter(&self) -> &InviteRateLimiter {
+        &self.limiter
+    }
+}
+
+/// Write a failed redemption to the request log (the audit trail)
+///
+/// # Errors
+/// Returns an error if the log entry cannot be written
+pub fn audit_failed_redemption(storage: &Storage, source: IpAddr, rejection: &InviteRejection) -> Result<()> {
+    tracing::warn!("Rejected invite redemption from {}: {}", source, rejection);
+    storage.log_request(
+        "incoming",
+        "invite",
+        None,
+        Some(&source.to_string()),
+        Some(i32::from(rejection.status_code())),
+        false,
+        Some(&rejection.to_string()),
+        None,
+    )
+}
@@ -2,6 +2,24 @@
 //!
 //! This library provides the core functionality for Pure2P, a decentralized
 //! messaging system designed for cross-platform compatibility (Android, iOS, Desktop).
+//!
+//! # Cargo features
+//!
+//! The core (crypto, protocol, storage, queue, transport, messaging and the
+//! connectivity orchestrator) is always built. Everything else is optional,
+//! and the default set builds all of it but `onion`:
+//!
+//! | Feature                   | Enables                                        |
+//! |---------------------------|------------------------------------------------|
+//! | `tui`                     | The `tui` module (ratatui, crossterm)          |
+//! | `cli`                     | The `pure2p-tui` binary (implies `tui`)        |
+//! | `clipboard`               | System clipboard in the TUI (implies `tui`)    |
+//! | `connectivity-upnp`       | UPnP IGD port mapping (igd-next)               |
+//! | `connectivity-pcp-natpmp` | PCP and NAT-PMP port mapping                   |
+//! | `onion`                   | Skeleton Tor onion-service transport           |
+//!
+//! Embedders (mobile, FFI) can build with `--no-default-features`; port
+//! mapping protocols left out fail with `MappingError::NotSupported`.
 
 #![warn(missing_docs)]
 #![warn(clippy::all)]
@@ -11,8 +29,22 @@ pub mod protocol;
 pub mod transport;
 pub mod storage;
 pub mod queue;
+pub mod queue_migration;
+pub mod queue_dead_letters;
+pub mod retry_schedule;
 pub mod messaging;
+pub mod invite;
+pub mod auto_import;
+pub mod relay;
+pub mod sealing;
+pub mod signals;
+pub mod edits;
+pub mod probe;
 pub mod connectivity;
+pub mod update_check;
+pub mod logging;
+pub mod cache;
+#[cfg(feature = "tui")]
 pub mod tui;
 
 #[cfg(test)]
@@ -63,6 +95,42 @@ pub enum Error {
     /// Port mapping error
     #[error("Port mapping error: {0}")]
     PortMapping(#[from] connectivity::MappingError),
+
+    /// Message content failed validation (encoding or size)
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+
+    /// Message metadata failed validation
+    #[error("Invalid message metadata: {0}")]
+    InvalidMetadata(String),
+
+    /// Operation not supported by this transport or build
+    #[error("Not supported: {0}")]
+    NotSupported(String),
+
+    /// Invalid invite code configuration or request
+    #[error("Invite error: {0}")]
+    Invite(String),
+
+    /// A relay refused or could not take a message
+    #[error("Relay error: {0}")]
+    Relay(String),
+
+    /// A contact's UID does not match its public key
+    #[error("Identity mismatch: {0}")]
+    IdentityMismatch(String),
+
+    /// Refused by a rate cap (answered with HTTP 429)
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+
+    /// A contact address that cannot be dialled safely (see `storage::address`)
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+
+    /// A contact token expires too soon to be accepted (answered with HTTP 422)
+    #[error("Token too short-lived: {0}")]
+    TokenTooShortLived(String),
 }
 
 /// Initialize the Pure2P library with logging
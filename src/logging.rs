@@ -0,0 +1,294 @@
+//! Log file with size-based rotation, and panics captured into it
+//!
+//! `init_file_logging` sends every tracing event to a `RotatingLog` in
+//! `LOGS_DIR`. Each event is formatted into its own buffer and written under
+//! one lock, so lines from different threads never interleave. Before a
+//! write would take the file past `Settings::log_max_file_bytes`, the file
+//! is renamed to `pure2p.log.1` (older ones shift up) and a new one is
+//! started; files past `Settings::log_files_kept` are deleted, and the
+//! Diagnostics error log says older entries were rotated away.
+//!
+//! Writes are buffered. Warnings and errors are flushed as they are written,
+//! everything else at `RotatingLog::flush` (on shutdown) or when the buffer
+//! fills.
+//!
+//! `install_panic_hook` writes a panic's message, location and backtrace to
+//! the log and hands the message to a callback (the TUI reports it to the
+//! error banner) before the previous hook runs. Panics in background threads
+//! would otherwise only reach stderr, which the raw-mode terminal hides.
+
+use crate::{Error, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Folder the log files are written to
+pub const LOGS_DIR: &str = "./app_data/logs";
+
+/// Name of the current log file; rotated ones get `.1`, `.2`, ... appended
+pub const LOG_FILE_NAME: &str = "pure2p.log";
+
+/// Default size a log file may reach before it is rotated
+pub const DEFAULT_LOG_MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Default number of rotated files kept besides the current one
+pub const DEFAULT_LOG_FILES_KEPT: u32 = 5;
+
+/// Smallest accepted rotation size, so a bad setting cannot rotate per line
+pub const MIN_LOG_MAX_FILE_BYTES: u64 = 4 * 1024;
+
+/// Path of the `index`-th rotated file (0 is the current one)
+pub fn rotated_path(dir: &Path, index: u32) -> PathBuf {
+    match index {
+        0 => dir.join(LOG_FILE_NAME),
+        n => dir.join(format!("{}.{}", LOG_FILE_NAME, n)),
+    }
+}
+
+/// What rotation has done so far, for Diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RotationStatus {
+    /// Rotations since the log was opened
+    pub rotations: u64,
+    /// Rotated files on disk besides the current one
+    pub files_kept: u32,
+    /// Whether entries were deleted to stay within the retention count
+    pub rotated_away: bool,
+}
+
+impl RotationStatus {
+    /// Summary for the Diagnostics error log, e.g. "2 rotated files kept"
+    pub fn summary(&self) -> String {
+        let kept = match self.files_kept {
+            0 => "not rotated yet".to_string(),
+            1 => "1 rotated file kept".to_string(),
+            n => format!("{} rotated files kept", n),
+        };
+        if self.rotated_away {
+            format!("{}; older entries were rotated away", kept)
+        } else {
+            kept
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LogInner {
+    dir: PathBuf,
+    file: BufWriter<File>,
+    size: u64,
+    max_bytes: u64,
+    keep: u32,
+    status: RotationStatus,
+}
+
+impl LogInner {
+    fn write_event(&mut self, bytes: &[u8], flush: bool) -> std::io::Result<()> {
+        if self.size > 0 && self.size + bytes.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(bytes)?;
+        self.size += bytes.len() as u64;
+        if flush {
+            self.file.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Shift every file up one slot, dropping those past `keep`
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+        // With nothing kept, the oldest file is the current one
+        let oldest = rotated_path(&self.dir, self.keep);
+        if oldest.exists() {
+            std::fs::remove_file(oldest)?;
+            self.status.rotated_away = true;
+        }
+        for index in (0..self.keep).rev() {
+            let from = rotated_path(&self.dir, index);
+            if from.exists() {
+                std::fs::rename(&from, rotated_path(&self.dir, index + 1))?;
+            }
+        }
+        self.file = BufWriter::new(open_append(&rotated_path(&self.dir, 0))?);
+        self.size = 0;
+        self.status.rotations += 1;
+        self.status.files_kept = count_rotated(&self.dir, self.keep);
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn count_rotated(dir: &Path, keep: u32) -> u32 {
+    (1..=keep).filter(|index| rotated_path(dir, *index).exists()).count() as u32
+}
+
+/// Shared, size-rotated log file
+///
+/// Clones write to the same file; it can be handed to tracing as its writer.
+#[derive(Debug, Clone)]
+pub struct RotatingLog {
+    inner: Arc<Mutex<LogInner>>,
+}
+
+impl RotatingLog {
+    /// Open (or continue) the log in `dir`, creating the folder if needed
+    ///
+    /// Rotated files beyond `keep` left by an earlier, larger setting are
+    /// deleted at once.
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the folder or file cannot be created
+    pub fn open(dir: &Path, max_bytes: u64, keep: u32) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = rotated_path(dir, 0);
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+        let mut rotated_away = false;
+        let mut index = keep + 1;
+        while rotated_path(dir, index).exists() {
+            std::fs::remove_file(rotated_path(dir, index))?;
+            rotated_away = true;
+            index += 1;
+        }
+        Ok(Self {
+            inner: Arc::new(Mutex::new(LogInner {
+                dir: dir.to_path_buf(),
+                file: BufWriter::new(file),
+                size,
+                max_bytes: max_bytes.max(MIN_LOG_MAX_FILE_BYTES),
+                keep,
+                status: RotationStatus { rotations: 0, files_kept: count_rotated(dir, keep), rotated_away },
+            })),
+        })
+    }
+
+    // A panic while logging must not stop the rest of the log
+    fn lock(&self) -> MutexGuard<'_, LogInner> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Append `bytes` as one unit, rotating first if they would not fit
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if rotating or writing fails
+    pub fn write_event(&self, bytes: &[u8], flush: bool) -> Result<()> {
+        self.lock().write_event(bytes, flush).map_err(Error::Io)
+    }
+
+    /// Write buffered entries to disk
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the write fails
+    pub fn flush(&self) -> Result<()> {
+        self.lock().file.flush().map_err(Error::Io)
+    }
+
+    /// Path of the current log file
+    pub fn path(&self) -> PathBuf {
+        rotated_path(&self.lock().dir, 0)
+    }
+
+    /// What rotation has done so far
+    pub fn status(&self) -> RotationStatus {
+        self.lock().status
+    }
+}
+
+/// One event's output, written to the log in one piece when dropped
+pub struct EventWriter {
+    log: RotatingLog,
+    buffer: Vec<u8>,
+    flush: bool,
+}
+
+impl Write for EventWriter {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for EventWriter {
+    fn drop(&mut self) {
+        if !self.buffer.is_empty() {
+            // Nowhere left to report a failing log
+            let _ = self.log.write_event(&self.buffer, self.flush);
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for RotatingLog {
+    type Writer = EventWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        EventWriter { log: self.clone(), buffer: Vec::new(), flush: false }
+    }
+
+    fn make_writer_for(&'a self, meta: &tracing::Metadata<'_>) -> Self::Writer {
+        EventWriter { log: self.clone(), buffer: Vec::new(), flush: *meta.level() <= tracing::Level::WARN }
+    }
+}
+
+/// Send tracing events at info level and above to a `RotatingLog` in `dir`
+///
+/// # Returns
+/// The log, for flushing on shutdown and for the panic hook
+///
+/// # Errors
+/// Returns `Error::Io` if the log cannot be opened and `Error::Storage` if a
+/// global subscriber is already set
+pub fn init_file_logging(dir: &Path, max_bytes: u64, keep: u32) -> Result<RotatingLog> {
+    let log = RotatingLog::open(dir, max_bytes, keep)?;
+    tracing_subscriber::fmt()
+        .with_writer(log.clone())
+        .with_ansi(false)
+        .with_max_level(tracing::Level::INFO)
+        .try_init()
+        .map_err(|e| Error::Storage(format!("Logging already initialised: {}", e)))?;
+    Ok(log)
+}
+
+/// Log line for a panic: thread, location, message and backtrace
+pub fn panic_entry(info: &std::panic::PanicHookInfo<'_>, backtrace: &std::backtrace::Backtrace) -> String {
+    format!(
+        "{} PANIC {}\nbacktrace:\n{}\n",
+        chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+        panic_message(info),
+        backtrace
+    )
+}
+
+/// One-line description of a panic, e.g. "thread 'worker' panicked at src/x.rs:3:5: boom"
+pub fn panic_message(info: &std::panic::PanicHookInfo<'_>) -> String {
+    let payload = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string());
+    let location = info.location().map_or_else(|| "unknown location".to_string(), |l| l.to_string());
+    let thread = std::thread::current();
+    format!("thread '{}' panicked at {}: {}", thread.name().unwrap_or("<unnamed>"), location, payload)
+}
+
+/// Write every panic to `log`, then call `on_panic` with its message, then
+/// the hook that was installed before
+pub fn install_panic_hook(log: RotatingLog, on_panic: impl Fn(&str) + Send + Sync + 'static) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        let _ = log.write_event(panic_entry(info, &backtrace).as_bytes(), true);
+        on_panic(&panic_message(info));
+        previous(info);
+    }));
+}
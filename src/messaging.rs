@@ -4,20 +4,105 @@
 //! transport and queue operations for reliable message delivery.
 
 use crate::{
+    crypto::KeyPair,
     queue::{MessageQueue, Priority},
-    storage::{AppState, Contact, Message},
-    transport::Transport,
-    Result,
+    relay,
+    sealing::{seal_request, SendSecurity},
+    storage::{bound_message_timestamp, validate_metadata, AppState, Chat, Contact, Message, MAX_MESSAGE_BYTES},
+    transport::{MessageRequest, PeerTransport},
+    Error, Result,
 };
 use chrono::Utc;
 
+/// Build the wire request for a stored message
+fn message_request(message: &Message, message_type: &str) -> MessageRequest {
+    MessageRequest {
+        from_uid: message.sender.clone(),
+        message_type: message_type.to_string(),
+        payload: message.content.to_vec(),
+        metadata: message.metadata.clone(),
+        message_id: Some(message.id.clone()),
+    }
+}
+
+/// Size of a message as sent on the wire (the CBOR-encoded request)
+///
+/// # Errors
+/// Returns `Error::CborSerialization` if the request cannot be encoded
+pub fn encoded_size(message: &Message, message_type: &str) -> Result<usize> {
+    request_size(&message_request(message, message_type))
+}
+
+/// Size of a request as sent on the wire
+///
+/// # Errors
+/// Returns `Error::CborSerialization` if the request cannot be encoded
+pub fn request_size(request: &MessageRequest) -> Result<usize> {
+    serde_cbor::to_vec(request)
+        .map(|encoded| encoded.len())
+        .map_err(|e| Error::CborSerialization(format!("Failed to serialize message request: {}", e)))
+}
+
+/// The request `send_sealed_with_type` puts on the wire for `message`, and how it is protected
+///
+/// # Errors
+/// Returns an error if sealing fails
+pub fn outgoing_request(
+    message: &Message,
+    message_type: &str,
+    keypair: Option<&KeyPair>,
+    contact: &Contact,
+) -> Result<(MessageRequest, SendSecurity)> {
+    seal_request(message_request(message, message_type), keypair, contact)
+}
+
+/// Check a message before delivery or queueing
+///
+/// # Errors
+/// Returns `Error::InvalidMetadata` for invalid metadata and
+/// `Error::InvalidMessage` if the encoded request exceeds `MAX_MESSAGE_BYTES`
+pub fn validate_outgoing(message: &Message, message_type: &str) -> Result<()> {
+    validate_metadata(&message.metadata)?;
+
+    let size = encoded_size(message, message_type)?;
+    if size > MAX_MESSAGE_BYTES {
+        return Err(Error::InvalidMessage(format!(
+            "encoded size {} bytes exceeds the {} byte limit",
+            size, MAX_MESSAGE_BYTES
+        )));
+    }
+    Ok(())
+}
+
+/// Whether `message` repeats the previous outgoing message of `chat` within `window_secs`
+///
+/// Compares the (already sanitized) content with the last message `chat`
+/// holds from the same sender; system messages never count. A window of 0
+/// disables the check, and programmatic senders skip it with `allow_duplicate`.
+pub fn is_duplicate_send(chat: &Chat, message: &Message, window_secs: u32, allow_duplicate: bool) -> bool {
+    if allow_duplicate || window_secs == 0 || message.is_system() {
+        return false;
+    }
+    let Some(previous) = chat
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.sender == message.sender && !m.is_system())
+    else {
+        return false;
+    };
+
+    let elapsed_ms = message.timestamp - previous.timestamp;
+    previous.content == message.content && (0..=i64::from(window_secs) * 1000).contains(&elapsed_ms)
+}
+
 /// Send a message to a contact with automatic queueing on failure
 ///
 /// This function attempts to deliver a message immediately. If delivery fails,
 /// the message is automatically queued for retry with the specified priority.
 ///
 /// # Arguments
-/// * `transport` - The transport layer for sending messages
+/// * `transport` - The transport (or registry of transports) for sending messages
 /// * `queue` - The message queue for retry logic
 /// * `contact` - The contact to send the message to
 /// * `message` - The message to send
@@ -26,7 +111,7 @@ use chrono::Utc;
 /// # Returns
 /// * `Ok(true)` - Message delivered successfully
 /// * `Ok(false)` - Message queued for retry (delivery failed)
-/// * `Err(Error)` - Failed to queue message
+/// * `Err(Error)` - Invalid message metadata, message too large, or failed to queue message
 ///
 /// # Example
 /// ```rust,no_run
@@ -67,21 +152,18 @@ use chrono::Utc;
 /// # }
 /// ```
 pub async fn send_message(
-    transport: &Transport,
+    transport: &dyn PeerTransport,
     queue: &mut MessageQueue,
     contact: &Contact,
     message: &Message,
     priority: Priority,
 ) -> Result<bool> {
-    // Try to send via transport using /message endpoint
-    let result = transport
-        .send_message(
-            contact,
-            &message.sender,
-            "text", // Default message type
-            message.content.clone(),
-        )
-        .await;
+    // Reject invalid metadata or oversized messages before attempting delivery or queueing
+    validate_outgoing(message, "text")?;
+
+    // Try to send via transport
+    let request = message_request(message, "text"); // Default message type
+    let result = transport.send_message(contact, &request).await;
 
     match result {
         Ok(()) => {
@@ -103,13 +185,128 @@ pub async fn send_message(
     }
 }
 
+/// Send a text message, sealed when the contact's X25519 key is known
+///
+/// Like `send_message`, but the request is sealed for the contact (see
+/// `sealing`) and the chosen protection is returned. A message that cannot
+/// be delivered is queued as plaintext; the retry worker seals it again when
+/// it sends, so a key learned in the meantime is used. While older messages
+/// to the contact wait in the queue, a bulk-lane message is queued behind
+/// them without an attempt, so it cannot arrive first.
+///
+/// # Arguments
+/// * `transport` - The transport (or registry of transports) for sending messages
+/// * `queue` - The message queue for retry logic
+/// * `keypair` - Our identity (None sends plaintext)
+/// * `contact` - The contact to send the message to
+/// * `message` - The message to send
+/// * `priority` - Priority for queueing if delivery fails
+///
+/// # Returns
+/// Whether the message was delivered (false: queued for retry) and how it
+/// was protected
+///
+/// # Errors
+/// Returns an error for invalid metadata, an oversized message, a sealing
+/// failure or a failure to queue the message
+pub async fn send_sealed_message(
+    transport: &dyn PeerTransport,
+    queue: &mut MessageQueue,
+    keypair: Option<&KeyPair>,
+    contact: &Contact,
+    message: &Message,
+    priority: Priority,
+) -> Result<(bool, SendSecurity)> {
+    send_sealed_with_type(transport, queue, keypair, contact, message, "text", priority).await
+}
+
+/// Send a sealable message of `message_type` (text or an edit)
+///
+/// Same as `send_sealed_message`; a message that cannot be delivered is
+/// queued under `message_type` so the retry worker sends it as the same kind.
+///
+/// # Errors
+/// See `send_sealed_message`
+pub async fn send_sealed_with_type(
+    transport: &dyn PeerTransport,
+    queue: &mut MessageQueue,
+    keypair: Option<&KeyPair>,
+    contact: &Contact,
+    message: &Message,
+    message_type: &str,
+    priority: Priority,
+) -> Result<(bool, SendSecurity)> {
+    let (attempt, security) = attempt_sealed(transport, queue, keypair, contact, message, message_type, priority).await?;
+    if attempt.is_delivered() {
+        return Ok((true, security));
+    }
+    queue.enqueue_with_type(message.clone(), priority, message_type)?;
+    Ok((false, security))
+}
+
+/// Outcome of a sealed send's delivery attempt, before anything is queued
+#[derive(Debug)]
+pub enum SendAttempt {
+    /// The contact has the message
+    Delivered,
+    /// Older messages to the contact are waiting; no attempt was made
+    Waiting,
+    /// The attempt failed
+    Failed(Error),
+}
+
+impl SendAttempt {
+    /// Whether the contact has the message
+    pub fn is_delivered(&self) -> bool {
+        matches!(self, SendAttempt::Delivered)
+    }
+}
+
+/// Try to deliver a sealable message of `message_type`, queueing nothing
+///
+/// `send_sealed_with_type` without its fallback: a message that is not
+/// `Delivered` is the caller's to queue. Lets a caller bound the attempt
+/// (and drop it) without leaving a queued row behind.
+///
+/// # Errors
+/// Returns an error for invalid metadata, an oversized message, a sealing
+/// failure or a failure to read the queue
+pub async fn attempt_sealed(
+    transport: &dyn PeerTransport,
+    queue: &MessageQueue,
+    keypair: Option<&KeyPair>,
+    contact: &Contact,
+    message: &Message,
+    message_type: &str,
+    priority: Priority,
+) -> Result<(SendAttempt, SendSecurity)> {
+    validate_outgoing(message, message_type)?;
+
+    let (request, security) = outgoing_request(message, message_type, keypair, contact)?;
+    // Older messages to the contact still waiting go first
+    if !priority.is_interactive() && queue.has_waiting_for(&contact.uid)? {
+        tracing::info!("Message {} queued behind older messages to {}", message.id, contact.uid);
+        return Ok((SendAttempt::Waiting, security));
+    }
+    match transport.send_message(contact, &request).await {
+        Ok(()) => {
+            tracing::info!("Message {} delivered to {} ({:?})", message.id, contact.uid, security);
+            Ok((SendAttempt::Delivered, security))
+        }
+        Err(e) => {
+            tracing::warn!("Failed to deliver message {} to {}: {}. Queueing for retry.", message.id, contact.uid, e);
+            Ok((SendAttempt::Failed(e), security))
+        }
+    }
+}
+
 /// Send a message with a custom message type and automatic queueing on failure
 ///
 /// Similar to `send_message` but allows specifying a custom message type
 /// (e.g., "text", "delete", "typing", "file", etc.).
 ///
 /// # Arguments
-/// * `transport` - The transport layer for sending messages
+/// * `transport` - The transport (or registry of transports) for sending messages
 /// * `queue` - The message queue for retry logic
 /// * `contact` - The contact to send the message to
 /// * `message` - The message to send
@@ -119,19 +316,21 @@ pub async fn send_message(
 /// # Returns
 /// * `Ok(true)` - Message delivered successfully
 /// * `Ok(false)` - Message queued for retry (delivery failed)
-/// * `Err(Error)` - Failed to queue message
+/// * `Err(Error)` - Invalid message metadata, message too large, or failed to queue message
 pub async fn send_message_with_type(
-    transport: &Transport,
+    transport: &dyn PeerTransport,
     queue: &mut MessageQueue,
     contact: &Contact,
     message: &Message,
     message_type: &str,
     priority: Priority,
 ) -> Result<bool> {
-    // Try to send via transport using /message endpoint
-    let result = transport
-        .send_message(contact, &message.sender, message_type, message.content.clone())
-        .await;
+    // Reject invalid metadata or oversized messages before attempting delivery or queueing
+    validate_outgoing(message, message_type)?;
+
+    // Try to send via transport
+    let request = message_request(message, message_type);
+    let result = transport.send_message(contact, &request).await;
 
     match result {
         Ok(()) => {
@@ -159,6 +358,54 @@ pub async fn send_message_with_type(
     }
 }
 
+/// Deliver a queued message through a relay once direct delivery is exhausted
+///
+/// Consults `contacts` for relays that advertised reaching the recipient and
+/// hands the request to the first that accepts it. With `keypair`, text is
+/// sealed for the recipient first, so the relay only sees ciphertext. On
+/// success the message leaves the queue; otherwise it stays queued.
+///
+/// # Arguments
+/// * `transport` - The transport (or registry of transports) for reaching relays
+/// * `queue` - The message queue holding the message
+/// * `keypair` - Our identity (None relays the payload unchanged)
+/// * `contacts` - All contacts (relay candidates)
+/// * `message` - The queued message
+/// * `message_type` - Type the message was queued with
+///
+/// # Returns
+/// * `Ok(Some(relay_uid))` - A relay accepted the message
+/// * `Ok(None)` - No relay reaches the recipient or every relay refused
+/// * `Err(Error)` - Invalid message metadata, a sealing failure, or failed to update the queue
+pub async fn deliver_via_relay(
+    transport: &dyn PeerTransport,
+    queue: &mut MessageQueue,
+    keypair: Option<&KeyPair>,
+    contacts: &[Contact],
+    message: &Message,
+    message_type: &str,
+) -> Result<Option<String>> {
+    validate_metadata(&message.metadata)?;
+
+    let mut request = message_request(message, message_type);
+    if let Some(recipient) = contacts.iter().find(|c| c.uid == message.recipient) {
+        request = seal_request(request, keypair, recipient)?.0;
+    }
+    match relay::send_via_relay(transport, contacts, &message.recipient, &request).await {
+        Ok(relay_uid) => {
+            if queue.contains(&message.id)? {
+                queue.mark_delivered(&message.id)?;
+            }
+            tracing::info!("Message {} delivered to {} via relay {}", message.id, message.recipient, relay_uid);
+            Ok(Some(relay_uid))
+        }
+        Err(e) => {
+            tracing::debug!("No relay delivery for message {}: {}", message.id, e);
+            Ok(None)
+        }
+    }
+}
+
 /// Send a delete chat request to a contact
 ///
 /// This function sends a special DELETE-type message to a contact to notify them
@@ -166,7 +413,7 @@ pub async fn send_message_with_type(
 /// from their local AppState upon receiving this message.
 ///
 /// # Arguments
-/// * `transport` - The transport layer for sending messages
+/// * `transport` - The transport (or registry of transports) for sending messages
 /// * `queue` - The message queue for retry logic
 /// * `contact` - The contact to send the delete request to
 /// * `local_uid` - The UID of the local user (sender)
@@ -207,7 +454,7 @@ pub async fn send_message_with_type(
 /// # }
 /// ```
 pub async fn send_delete_chat(
-    transport: &Transport,
+    transport: &dyn PeerTransport,
     queue: &mut MessageQueue,
     contact: &Contact,
     local_uid: &str,
@@ -260,19 +507,17 @@ pub async fn send_delete_chat(
 /// assert!(removed); // Chat was removed
 /// ```
 pub fn handle_delete_chat(app_state: &mut AppState, sender_uid: &str) -> bool {
-    let initial_len = app_state.chats.len();
-    app_state.chats.retain(|chat| chat.contact_uid != sender_uid);
-    let new_len = app_state.chats.len();
-
-    // Return true if a chat was removed
-    initial_len > new_len
+    app_state.remove_chat(sender_uid).is_some()
 }
 
 /// Handle an incoming message
 ///
 /// This function processes incoming messages by:
 /// - Getting or creating a chat for the sender
-/// - Appending the message to the chat history
+/// - Recording the sender's timestamp against the receive time, from which the
+///   contact's UTC offset is inferred (see `storage::local_time`)
+/// - Replacing an implausible timestamp with the receive time (see `storage::bounds`)
+/// - Appending the message to the chat history (trimmed to the history limit)
 /// - Marking the chat as unread for TUI display
 ///
 /// # Arguments
@@ -314,7 +559,14 @@ pub fn handle_incoming_message(
     content: Vec<u8>,
     timestamp: i64,
 ) {
+    let now = Utc::now();
+    // The timestamp as sent, before bounding: a peer stamping local time may be hours ahead
+    if let Some(contact) = app_state.contact_by_uid_mut(sender_uid) {
+        contact.record_offset_sample(timestamp, now);
+    }
+
     // Get or create chat for this sender
+    let history_limit = app_state.settings.history_limit;
     let chat = app_state.get_or_create_chat(sender_uid);
 
     // Create message object
@@ -325,11 +577,13 @@ pub fn handle_incoming_message(
         content,
         timestamp,
     );
+    // A timestamp far in the past or future is replaced by the receive time
+    bound_message_timestamp(&mut message, now);
     // Incoming messages are already delivered to us
     message.mark_delivered();
 
-    // Append message to chat history
-    chat.append_message(message);
+    // Append message to chat history, trimming the oldest beyond the limit
+    chat.append_with_limit(message, history_limit);
 
     // Mark chat as unread for TUI display
     chat.mark_unread();
@@ -386,7 +640,7 @@ pub fn handle_incoming_message(
 /// # }
 /// ```
 pub async fn create_chat_from_ping(
-    transport: &Transport,
+    transport: &dyn PeerTransport,
     app_state: &mut AppState,
     contact: &Contact,
 ) -> Result<bool> {
@@ -527,7 +781,7 @@ pub fn create_inactive_chat(app_state: &mut AppState, contact_uid: &str) {
 /// # }
 /// ```
 pub async fn delete_chat(
-    transport: &Transport,
+    transport: &dyn PeerTransport,
     queue: &mut MessageQueue,
     app_state: &mut AppState,
     contact: &Contact,
@@ -674,7 +928,7 @@ pub fn delete_inactive_chat_immediate(
 /// # }
 /// ```
 pub async fn delete_active_chat_with_notification(
-    transport: &Transport,
+    transport: &dyn PeerTransport,
     queue: &mut MessageQueue,
     app_state: &mut AppState,
     contact: &Contact,
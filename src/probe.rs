@@ -0,0 +1,390 @@
+//! Peer-assisted reachability tests
+//!
+//! The automated check (`connectivity::verify_external_reachability`) can
+//! only tell whether our mapping answers from the gateway's side. To learn
+//! whether a real peer gets through, we ask a contact: a `probe_request`
+//! names one of our advertised addresses, the contact sends a small
+//! `probe_check` to exactly that address and reports back with a
+//! `probe_result` (reachable or not, latency, the address tried).
+//!
+//! The contact only serves probes for a stored contact it has marked
+//! verified, only towards an address that contact advertised to it (so a
+//! probe cannot be aimed at a third party), and at most
+//! `PROBE_LIMIT_PER_HOUR` times per contact in any sliding hour. Refusals
+//! are answered too, so the requester sees why nothing was tested.
+//!
+//! The check carries the random probe ID the requester chose, which is how
+//! the requester tells its own probe from anything else; results for IDs it
+//! never asked about are ignored. Finished probes are kept in the request
+//! log under `PEER_PROBE_AUDIT_TYPE` as connectivity history.
+
+use crate::{
+    cache::BoundedCache,
+    storage::{Contact, RequestLog, Storage},
+    transport::{MessageRequest, PeerTransport, TransportRegistry},
+    Error, Result,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Message type asking a contact to test one of our addresses
+pub const PROBE_REQUEST_TYPE: &str = "probe_request";
+
+/// Message type of the test request itself, sent to the probed address
+pub const PROBE_CHECK_TYPE: &str = "probe_check";
+
+/// Message type of the answer to a probe request
+pub const PROBE_RESULT_TYPE: &str = "probe_result";
+
+/// Probes served per contact in any sliding hour
+pub const PROBE_LIMIT_PER_HOUR: usize = 2;
+
+/// Length of the sliding window the limit applies to
+pub const PROBE_WINDOW_SECS: i64 = 60 * 60;
+
+/// Most requesting contacts the limiter tracks
+pub const MAX_TRACKED_PROBE_REQUESTERS: usize = 1024;
+
+/// How long the serving side waits for the probed address to answer
+pub const PROBE_CHECK_TIMEOUT_SECS: u64 = 10;
+
+/// Request type of finished probes in the request log
+pub const PEER_PROBE_AUDIT_TYPE: &str = "peer_probe";
+
+/// Whether `message_type` belongs to a reachability probe (consumed, not stored in the chat)
+pub fn is_probe_type(message_type: &str) -> bool {
+    [PROBE_REQUEST_TYPE, PROBE_CHECK_TYPE, PROBE_RESULT_TYPE].contains(&message_type)
+}
+
+/// Payload of a probe request (CBOR)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeRequest {
+    /// Random ID chosen by the requester, echoed in the check and the result
+    pub probe_id: String,
+    /// Requester's advertised address to test
+    pub address: String,
+}
+
+impl ProbeRequest {
+    /// Request with a fresh probe ID for `address`
+    pub fn new(address: impl Into<String>) -> Self {
+        Self { probe_id: uuid::Uuid::new_v4().to_string(), address: address.into() }
+    }
+
+    /// Encode as a message payload
+    ///
+    /// # Errors
+    /// `Error::CborSerialization` if encoding fails
+    pub fn to_payload(&self) -> Result<Vec<u8>> {
+        serde_cbor::to_vec(self).map_err(|e| Error::CborSerialization(format!("Failed to serialize probe request: {}", e)))
+    }
+
+    /// Decode from a message payload
+    ///
+    /// # Errors
+    /// `Error::CborSerialization` if the payload is not a valid probe request
+    pub fn from_payload(payload: &[u8]) -> Result<Self> {
+        serde_cbor::from_slice(payload)
+            .map_err(|e| Error::CborSerialization(format!("Failed to deserialize probe request: {}", e)))
+    }
+}
+
+/// Payload of a probe result (CBOR)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeResult {
+    /// ID of the request this answers
+    pub probe_id: String,
+    /// Address that was tested (or would have been)
+    pub address: String,
+    /// The address accepted the check
+    pub reachable: bool,
+    /// Round trip of the check in milliseconds (when it was answered)
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    /// Why the probe was not served (nothing was tested then)
+    #[serde(default)]
+    pub refused: Option<String>,
+}
+
+impl ProbeResult {
+    /// Answer to `request` that was not served because of `reason`
+    pub fn refused(request: &ProbeRequest, reason: impl Into<String>) -> Self {
+        Self {
+            probe_id: request.probe_id.clone(),
+            address: request.address.clone(),
+            reachable: false,
+            latency_ms: None,
+            refused: Some(reason.into()),
+        }
+    }
+
+    /// Encode as a message payload
+    ///
+    /// # Errors
+    /// `Error::CborSerialization` if encoding fails
+    pub fn to_payload(&self) -> Result<Vec<u8>> {
+        serde_cbor::to_vec(self).map_err(|e| Error::CborSerialization(format!("Failed to serialize probe result: {}", e)))
+    }
+
+    /// Decode from a message payload
+    ///
+    /// # Errors
+    /// `Error::CborSerialization` if the payload is not a valid probe result
+    pub fn from_payload(payload: &[u8]) -> Result<Self> {
+        serde_cbor::from_slice(payload)
+            .map_err(|e| Error::CborSerialization(format!("Failed to deserialize probe result: {}", e)))
+    }
+}
+
+/// Sliding-window limit on probes served, per requesting contact
+///
+/// A requester with nothing served for `PROBE_WINDOW_SECS` is forgotten.
+#[derive(Debug)]
+pub struct ProbeLimiter {
+    served: BoundedCache<String, VecDeque<DateTime<Utc>>>,
+}
+
+impl Default for ProbeLimiter {
+    fn default() -> Self {
+        Self {
+            served: BoundedCache::new(
+                "probe limiter",
+                MAX_TRACKED_PROBE_REQUESTERS,
+                Some(Duration::seconds(PROBE_WINDOW_SECS)),
+            ),
+        }
+    }
+}
+
+impl ProbeLimiter {
+    /// Create a limiter with nothing served yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a probe for `uid` at `now` unless the hourly limit is reached
+    ///
+    /// # Errors
+    /// `Error::RateLimited` if `uid` already had `PROBE_LIMIT_PER_HOUR`
+    /// probes served in the last hour (the refusal is not counted)
+    pub fn admit(&mut self, uid: &str, now: DateTime<Utc>) -> Result<()> {
+        let window_start = now - Duration::seconds(PROBE_WINDOW_SECS);
+        self.served.update_at(uid.to_string(), now, VecDeque::new, |served| {
+            while served.front().is_some_and(|at| *at <= window_start) {
+                served.pop_front();
+            }
+            if served.len() >= PROBE_LIMIT_PER_HOUR {
+                return Err(Error::RateLimited(format!("{} reachability probes per hour", PROBE_LIMIT_PER_HOUR)));
+            }
+            served.push_back(now);
+            Ok(())
+        })
+    }
+}
+
+/// Check whether a probe request from `from_uid` may be served
+///
+/// # Returns
+/// The requester's stored contact
+///
+/// # Errors
+/// - `Error::InvalidMessage` if the requester is not a stored contact, is
+///   not verified, or asked for an address it never advertised to us
+/// - `Error::RateLimited` if the requester is over the hourly limit
+pub fn admit_probe(
+    contacts: &[Contact],
+    from_uid: &str,
+    request: &ProbeRequest,
+    limiter: &mut ProbeLimiter,
+    now: DateTime<Utc>,
+) -> Result<Contact> {
+    let contact = contacts
+        .iter()
+        .find(|c| c.uid == from_uid)
+        .ok_or_else(|| Error::InvalidMessage(format!("Probe request from unknown sender {}", from_uid)))?;
+    if !contact.verified {
+        return Err(Error::InvalidMessage("Probes are only served for verified contacts".to_string()));
+    }
+    let advertised = contact.ip == request.address || contact.endpoints.iter().any(|e| e.address == request.address);
+    if !advertised {
+        return Err(Error::InvalidMessage(format!("{} is not an address advertised to us", request.address)));
+    }
+    limiter.admit(from_uid, now)?;
+    Ok(contact.clone())
+}
+
+/// Send the check for `request` to exactly the requested address
+///
+/// Other addresses of the requester are never tried, so the result says
+/// something about that one address only.
+pub async fn probe_address(
+    transports: &TransportRegistry,
+    local_uid: &str,
+    requester: &Contact,
+    request: &ProbeRequest,
+    timeout: std::time::Duration,
+) -> ProbeResult {
+    let mut target = requester.clone();
+    target.ip = request.address.clone();
+    target.endpoints.clear();
+    let check = MessageRequest {
+        from_uid: local_uid.to_string(),
+        message_type: PROBE_CHECK_TYPE.to_string(),
+        payload: request.probe_id.as_bytes().to_vec(),
+        metadata: Default::default(),
+        message_id: None,
+    };
+
+    let started = std::time::Instant::now();
+    let outcome = match transports.for_address(&request.address) {
+        Ok(transport) => tokio::time::timeout(timeout, transport.send_message(&target, &check))
+            .await
+            .unwrap_or_else(|_| Err(Error::Transport("Probe check timed out".to_string()))),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = &outcome {
+        tracing::debug!("Probe of {} for {} failed: {}", request.address, requester.uid, e);
+    }
+    ProbeResult {
+        probe_id: request.probe_id.clone(),
+        address: request.address.clone(),
+        reachable: outcome.is_ok(),
+        latency_ms: outcome.is_ok().then(|| started.elapsed().as_millis() as u64),
+        refused: None,
+    }
+}
+
+/// Build the message asking a contact to probe `request.address`
+///
+/// # Errors
+/// `Error::CborSerialization` if the payload cannot be encoded
+pub fn probe_request_message(local_uid: &str, request: &ProbeRequest) -> Result<MessageRequest> {
+    Ok(MessageRequest {
+        from_uid: local_uid.to_string(),
+        message_type: PROBE_REQUEST_TYPE.to_string(),
+        payload: request.to_payload()?,
+        metadata: Default::default(),
+        message_id: None,
+    })
+}
+
+/// Send `result` back to the requester (over any of its addresses)
+///
+/// # Errors
+/// Returns an error if the payload cannot be encoded or the transport fails
+pub async fn send_probe_result(
+    transport: &dyn PeerTransport,
+    local_uid: &str,
+    requester: &Contact,
+    result: &ProbeResult,
+) -> Result<()> {
+    let response = MessageRequest {
+        from_uid: local_uid.to_string(),
+        message_type: PROBE_RESULT_TYPE.to_string(),
+        payload: result.to_payload()?,
+        metadata: Default::default(),
+        message_id: None,
+    };
+    transport.send_message(requester, &response).await
+}
+
+/// A probe we asked a contact for and have no result of yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingProbe {
+    /// ID sent in the request
+    pub probe_id: String,
+    /// Contact asked to probe
+    pub contact_uid: String,
+    /// Our address to be tested
+    pub address: String,
+    /// When the request was sent
+    pub requested_at: DateTime<Utc>,
+}
+
+/// A finished probe, as kept in the connectivity history
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerProbeRecord {
+    /// When the result arrived
+    pub at: DateTime<Utc>,
+    /// Contact that probed
+    pub contact_uid: String,
+    /// Our address that was tested
+    pub address: String,
+    /// The address accepted the contact's check
+    pub reachable: bool,
+    /// Round trip of the check in milliseconds
+    pub latency_ms: Option<u64>,
+    /// Why the contact did not test (nothing was tested then)
+    pub refused: Option<String>,
+}
+
+impl PeerProbeRecord {
+    /// Record of `result` from `contact_uid`, arrived at `at`
+    pub fn from_result(contact_uid: &str, result: &ProbeResult, at: DateTime<Utc>) -> Self {
+        Self {
+            at,
+            contact_uid: contact_uid.to_string(),
+            address: result.address.clone(),
+            reachable: result.reachable,
+            latency_ms: result.latency_ms,
+            refused: result.refused.clone(),
+        }
+    }
+
+    /// Read back a record written by `record_probe_result`
+    pub fn from_log(log: &RequestLog) -> Self {
+        Self {
+            at: DateTime::from_timestamp_millis(log.timestamp).unwrap_or_default(),
+            contact_uid: log.target_uid.clone().unwrap_or_default(),
+            address: log.target_ip.clone().unwrap_or_default(),
+            reachable: log.success,
+            latency_ms: log.response_data.as_deref().and_then(|d| d.parse().ok()),
+            refused: log.error_message.clone(),
+        }
+    }
+
+    /// One line for Diagnostics, e.g. "✓ 1.2.3.4:8080 reachable (42ms) via 3f9a…"
+    pub fn summary(&self) -> String {
+        let via = &self.contact_uid[..8.min(self.contact_uid.len())];
+        match (&self.refused, self.reachable) {
+            (Some(reason), _) => format!("– {} not tested via {}: {}", self.address, via, reason),
+            (None, true) => format!(
+                "✓ {} reachable ({}ms) via {}",
+                self.address,
+                self.latency_ms.unwrap_or_default(),
+                via
+            ),
+            (None, false) => format!("✗ {} not reachable via {}", self.address, via),
+        }
+    }
+}
+
+/// Add a finished probe to the connectivity history in the request log
+///
+/// # Errors
+/// Returns an error if the log cannot be written
+pub fn record_probe_result(storage: &Storage, record: &PeerProbeRecord) -> Result<()> {
+    storage.log_request(
+        "outgoing",
+        PEER_PROBE_AUDIT_TYPE,
+        Some(&record.contact_uid),
+        Some(&record.address),
+        None,
+        record.reachable,
+        record.refused.as_deref(),
+        record.latency_ms.map(|ms| ms.to_string()).as_deref(),
+    )
+}
+
+/// The most recent `limit` finished probes, newest first
+///
+/// # Errors
+/// Returns an error if the log cannot be read
+pub fn probe_history(storage: &Storage, limit: usize) -> Result<Vec<PeerProbeRecord>> {
+    Ok(storage
+        .get_request_logs_of_type(PEER_PROBE_AUDIT_TYPE, limit)?
+        .iter()
+        .map(PeerProbeRecord::from_log)
+        .collect())
+}
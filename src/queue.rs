@@ -6,6 +6,8 @@
 //! - Priority handling
 //! - Queue persistence
 //! - Startup retry for pending messages
+//! - Redacted debug export/import for stuck deliveries
+//! - Message content sealed at rest
 //!
 //! ## Retry on Startup
 //!
@@ -51,12 +53,152 @@
 //! # Ok(())
 //! # }
 //! ```
-
-use crate::{storage::Message, Error, Result};
+//!
+//! ## Debugging Stuck Deliveries
+//!
+//! `export_debug()` writes a JSON report of every queued row: scheduling
+//! state, attempts and the last delivery error, with message content
+//! replaced by its length and SHA-256 and UIDs cut to 8 characters. A
+//! maintainer can load the report into a scratch queue with
+//! `import_debug()` (only after `set_debug_import(true)`) and replay the
+//! retry schedule with `fetch_pending_at()` / `mark_failed_at()`, which
+//! take the current time as an argument instead of reading the clock.
+//!
+//! ## Dormant Messages
+//!
+//! A message that uses up its retries is only dropped when the last error
+//! says the peer refused it (see `FailureKind::classify`). When the peer was
+//! merely unreachable, typically because its address changed and the
+//! `address_update` has not arrived yet, the message goes dormant instead:
+//! it stays queued but is no longer fetched. `resurrect_for()` brings a
+//! contact's dormant messages back with fresh attempts as soon as it is
+//! heard from again; `expire_dormant()` drops those still dormant after
+//! `DEFAULT_DORMANT_EXPIRY_MS`. Text messages that fail either way are kept
+//! as dead letters for review.
+//!
+//! ## Blocked Messages
+//!
+//! Messages to a contact whose token has expired cannot be delivered, so
+//! `block_for_at()` takes them out of every fetch instead of letting them
+//! use up their retries. They stay blocked, however long it takes, until a
+//! new token for the contact is imported; `unblock_for_at()` then makes them
+//! due at once with fresh attempts, dormant or not.
+//!
+//! ## Message Order
+//!
+//! Bulk-lane messages (text and edits) to one contact go out in the order
+//! they were written. `messaging::send_sealed_with_type` queues a new
+//! message behind older ones still waiting instead of sending it first,
+//! and the retry worker passes what it fetched through `order_per_contact`,
+//! which holds back anything newer than a contact's oldest waiting message
+//! and sorts the rest by timestamp. Failed text messages are kept for
+//! re-queueing (see `queue_dead_letters`).
+//!
+//! ## Content at Rest
+//!
+//! `message_queue.db` holds undelivered messages, so their content is sealed
+//! with XChaCha20-Poly1305 under `KeyPair::queue_content_key()`, derived
+//! from the identity (no passphrase). Every stored content starts with a
+//! marker byte: `CONTENT_SEALED`, or `CONTENT_PLAIN` for a queue without an
+//! identity. Rows from before the marker existed get `CONTENT_PLAIN` the
+//! first time the file is opened, and `set_identity()` seals every plain
+//! row. `rotate_identity()` re-seals the whole queue under a new identity's
+//! key. Fetching opens the content again, so callers only ever see
+//! plaintext.
+//!
+//! ## Schema Migrations
+//!
+//! Writes take a pass from the file's `QueueGate`, so a schema migration
+//! can run in bounded batches while the retry worker keeps going (see
+//! `queue_migration`).
+
+use crate::{
+    crypto::{decrypt_message, encrypt_message, EncryptedEnvelope, KeyPair},
+    storage::{
+        read_only::{open_read_only, WriteLog},
+        storage_db::{add_column_if_missing, decode_metadata, encode_metadata},
+        Message,
+    },
+    queue_dead_letters::DEAD_LETTERS_TABLE,
+    queue_migration::{QueueGate, QUEUE_DUE_INDEX, QUEUE_SCHEMA_VERSION},
+    Error, Result,
+};
 use chrono::Utc;
-use rusqlite::{params, Connection};
+use ring::digest::{digest, SHA256};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
+
+/// Version written to queue debug reports
+pub const QUEUE_DEBUG_VERSION: u32 = 1;
+
+/// Characters of a UID kept in a queue debug report
+pub const DEBUG_UID_CHARS: usize = 8;
+
+/// How long a dormant message waits for its contact before it fails (30 days)
+pub const DEFAULT_DORMANT_EXPIRY_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+
+/// Recorded error for a message whose contact is gone
+pub const CONTACT_NOT_FOUND_ERROR: &str = "contact not found";
+
+/// Marker byte: plaintext content follows
+pub const CONTENT_PLAIN: u8 = 0;
+
+/// Marker byte: nonce and XChaCha20-Poly1305 ciphertext follow
+pub const CONTENT_SEALED: u8 = 1;
+
+/// `user_version` of a queue file whose rows all carry a marker byte
+const CONTENT_FORMAT_VERSION: i64 = 1;
+
+/// Query behind `fetch_pending_at` (served by `idx_queue_due` once migrated)
+pub(crate) const FETCH_DUE_QUERY: &str =
+    "SELECT message_id, sender, recipient, content, timestamp, priority, retry_count, next_retry, metadata
+     FROM message_queue
+     WHERE next_retry <= ?1 AND dormant_since IS NULL AND suspended_since IS NULL AND blocked_since IS NULL
+     ORDER BY priority DESC, next_retry ASC, created_at ASC, rowid ASC";
+
+/// Store `plaintext` with its marker byte, sealed if there is a key
+fn seal_content(key: Option<&[u8; 32]>, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let Some(key) = key else {
+        let mut stored = Vec::with_capacity(plaintext.len() + 1);
+        stored.push(CONTENT_PLAIN);
+        stored.extend_from_slice(plaintext);
+        return Ok(stored);
+    };
+    let envelope = encrypt_message(key, plaintext)?;
+    let mut stored = Vec::with_capacity(1 + envelope.nonce.len() + envelope.ciphertext.len());
+    stored.push(CONTENT_SEALED);
+    stored.extend_from_slice(&envelope.nonce);
+    stored.extend_from_slice(&envelope.ciphertext);
+    Ok(stored)
+}
+
+/// Recover plaintext from stored content
+///
+/// # Errors
+/// `Error::Crypto` for sealed content without the right key, `Error::Queue`
+/// for an unknown marker
+fn open_content(key: Option<&[u8; 32]>, stored: &[u8]) -> Result<Vec<u8>> {
+    match stored.split_first() {
+        Some((&CONTENT_PLAIN, plaintext)) => Ok(plaintext.to_vec()),
+        Some((&CONTENT_SEALED, sealed)) => {
+            let key = key.ok_or_else(|| Error::Crypto("Queued content is sealed and no identity is set".to_string()))?;
+            if sealed.len() < 24 {
+                return Err(Error::Crypto("Sealed queue content is truncated".to_string()));
+            }
+            let (nonce, ciphertext) = sealed.split_at(24);
+            let envelope = EncryptedEnvelope {
+                nonce: nonce.try_into().map_err(|_| Error::Crypto("Invalid queue content nonce".to_string()))?,
+                ciphertext: ciphertext.to_vec(),
+            };
+            decrypt_message(key, &envelope)
+        }
+        Some((marker, _)) => Err(Error::Queue(format!("Unknown queue content marker {}", marker))),
+        None => Err(Error::Queue("Queued content has no marker byte".to_string())),
+    }
+}
 
 /// Message priority levels
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -82,6 +224,104 @@ impl Priority {
             _ => None,
         }
     }
+
+    /// Whether messages of this priority belong to the interactive lane
+    ///
+    /// High and Urgent messages (e.g., pings for newly imported contacts) are
+    /// interactive; Low and Normal messages form the bulk lane, which pauses
+    /// during quiet hours.
+    pub fn is_interactive(self) -> bool {
+        self >= Priority::High
+    }
+}
+
+/// Select the messages the retry worker should attempt now
+///
+/// When `bulk_paused` is true (e.g., during quiet hours) only interactive-lane
+/// messages are returned. Bulk messages stay queued and are picked up again
+/// once the pause ends.
+pub fn select_retry_lanes(messages: Vec<QueuedMessage>, bulk_paused: bool) -> Vec<QueuedMessage> {
+    if !bulk_paused {
+        return messages;
+    }
+    messages
+        .into_iter()
+        .filter(|m| m.priority.is_interactive())
+        .collect()
+}
+
+/// Keep each contact's bulk-lane messages in the order they were written
+///
+/// `waiting` maps contacts to the timestamp of their oldest bulk message
+/// that is queued but not due yet (see `MessageQueue::oldest_waiting_at`);
+/// later bulk messages to them are held back so nothing overtakes it. The
+/// bulk messages left for each contact are put in timestamp order, in the
+/// slots they had, so other contacts and the interactive lane keep their
+/// places.
+pub fn order_per_contact(messages: Vec<QueuedMessage>, waiting: &HashMap<String, i64>) -> Vec<QueuedMessage> {
+    let mut messages: Vec<QueuedMessage> = messages
+        .into_iter()
+        .filter(|m| {
+            m.priority.is_interactive()
+                || waiting.get(&m.message.recipient).is_none_or(|oldest| m.message.timestamp < *oldest)
+        })
+        .collect();
+
+    let mut slots: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, queued) in messages.iter().enumerate().filter(|(_, m)| !m.priority.is_interactive()) {
+        slots.entry(queued.message.recipient.clone()).or_default().push(index);
+    }
+    for indices in slots.values() {
+        let mut sorted = indices.clone();
+        sorted.sort_by_key(|&index| (messages[index].message.timestamp, index));
+        let in_order: Vec<QueuedMessage> = sorted.iter().map(|&index| messages[index].clone()).collect();
+        for (&slot, queued) in indices.iter().zip(in_order) {
+            messages[slot] = queued;
+        }
+    }
+    messages
+}
+
+/// 4xx answers that say "not now" rather than "never": a request timeout
+/// and a peer that is rate limiting (e.g. its auto-import cap)
+const RETRYABLE_CLIENT_STATUSES: [&str; 2] = ["408", "429"];
+
+/// Whether a failed delivery may succeed once the peer is reachable again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// No answer, a timeout or a server-side error; a working path may fix it
+    Connectivity,
+    /// The peer refused the message, or it can't be sent at all
+    Rejected,
+}
+
+impl FailureKind {
+    /// Classify a recorded delivery error
+    ///
+    /// A 4xx answer from the peer (other than 408 and 429), a missing
+    /// contact, an invalid contact address (see `storage::address`), and
+    /// crypto or encoding failures are `Rejected`; retrying would not change
+    /// them. No recorded error also counts as `Rejected`, so callers that
+    /// don't record one keep the drop-on-exhaustion behaviour. Everything
+    /// else is `Connectivity`.
+    pub fn classify(error: Option<&str>) -> Self {
+        let Some(error) = error else {
+            return FailureKind::Rejected;
+        };
+        let client_error = error.match_indices("status ").any(|(i, marker)| {
+            let code = &error[i + marker.len()..];
+            let code = code.get(..3).filter(|code| code.bytes().all(|b| b.is_ascii_digit()));
+            code.is_some_and(|code| code.starts_with('4') && !RETRYABLE_CLIENT_STATUSES.contains(&code))
+        });
+        let unsendable = ["Crypto error", "CBOR serialization error", "Identity mismatch", "Invalid address", CONTACT_NOT_FOUND_ERROR]
+            .iter()
+            .any(|marker| error.contains(marker));
+        if client_error || unsendable {
+            FailureKind::Rejected
+        } else {
+            FailureKind::Connectivity
+        }
+    }
 }
 
 /// Queued message with metadata
@@ -97,6 +337,105 @@ pub struct QueuedMessage {
     pub next_retry: i64,
 }
 
+/// Scheduling state of a queued row at the time of a debug export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueRowState {
+    /// Next retry time has passed; the worker picks it up on its next pass
+    Due,
+    /// Waiting for its next retry time
+    Waiting,
+    /// Out of retries after connectivity errors; waits for the contact
+    Dormant,
+    /// The contact's token expired; waits for a new one to be imported
+    Blocked,
+}
+
+/// One queued row in a debug report, without content or full UIDs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueDebugRow {
+    /// Message ID (random, not tied to the identity)
+    pub message_id: String,
+    /// Target UID, truncated
+    pub target: String,
+    /// Sender UID, truncated
+    pub sender: String,
+    /// Message type ("text", "ping", ...)
+    pub message_type: String,
+    /// Scheduling state at export time
+    pub state: QueueRowState,
+    /// Priority level
+    pub priority: Priority,
+    /// Delivery attempts so far
+    pub attempts: u32,
+    /// Next retry timestamp (Unix milliseconds)
+    pub next_retry: i64,
+    /// Last delivery attempt (Unix milliseconds)
+    pub last_attempt: Option<i64>,
+    /// Last delivery error, with UIDs truncated
+    pub last_error: Option<String>,
+    /// When the row was queued (Unix milliseconds)
+    pub created_at: i64,
+    /// When the row last changed (Unix milliseconds)
+    pub updated_at: i64,
+    /// When the row went dormant (Unix milliseconds)
+    #[serde(default)]
+    pub dormant_since: Option<i64>,
+    /// When the row was blocked on an expired contact (Unix milliseconds)
+    #[serde(default)]
+    pub blocked_since: Option<i64>,
+    /// Content length in bytes
+    pub content_len: usize,
+    /// Hex SHA-256 of the content
+    pub content_sha256: String,
+    /// Metadata keys (values are left out)
+    pub metadata_keys: Vec<String>,
+}
+
+/// Redacted snapshot of the whole queue for debugging stuck deliveries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueDebugReport {
+    /// Report format version
+    pub version: u32,
+    /// When the report was written (Unix milliseconds)
+    pub exported_at: i64,
+    /// Maximum retry attempts configured on the queue
+    pub max_retries: u32,
+    /// Base backoff delay configured on the queue (milliseconds)
+    pub base_delay_ms: i64,
+    /// Every queued row, in fetch order
+    pub rows: Vec<QueueDebugRow>,
+}
+
+/// Truncate a UID for a debug report
+fn redact_uid(uid: &str) -> String {
+    uid.chars().take(DEBUG_UID_CHARS).collect()
+}
+
+/// Truncate anything UID-like (long hex runs) inside free text
+fn redact_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut run = String::new();
+    let flush = |run: &mut String, out: &mut String| {
+        if run.len() > DEBUG_UID_CHARS * 2 {
+            out.push_str(&redact_uid(run));
+        } else {
+            out.push_str(run);
+        }
+        run.clear();
+    };
+    for c in text.chars() {
+        if c.is_ascii_hexdigit() {
+            run.push(c);
+        } else {
+            flush(&mut run, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut run, &mut out);
+    out
+}
+
 /// Message queue manager with SQLite persistence
 pub struct MessageQueue {
     /// SQLite connection
@@ -105,31 +444,184 @@ pub struct MessageQueue {
     pub(crate) max_retries: u32,
     /// Base delay for exponential backoff (milliseconds)
     pub(crate) base_delay_ms: i64,
+    /// Whether `import_debug` may write synthetic rows
+    debug_import: bool,
+    /// Key sealing content at rest (None writes plaintext with a marker)
+    content_key: Option<[u8; 32]>,
+    /// How long a message stays dormant before it fails (milliseconds)
+    pub(crate) dormant_expiry_ms: i64,
+    /// Shared with other connections to the same file; writes take a pass
+    pub(crate) gate: Arc<QueueGate>,
 }
 
 impl MessageQueue {
     /// Create a new message queue with in-memory database
     pub fn new() -> Result<Self> {
-        Self::new_with_connection(Connection::open_in_memory()?)
+        Self::new_with_connection(Connection::open_in_memory()?, QueueGate::new())
     }
 
     /// Create a new message queue with a file-based database
     pub fn new_with_path<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let conn = Connection::open(path)?;
-        Self::new_with_connection(conn)
+        let conn = Connection::open(&path)?;
+        Self::new_with_connection(conn, QueueGate::for_path(path.as_ref()))
+    }
+
+    /// Open a file-based queue whose content is sealed for `keypair`
+    ///
+    /// Plain rows (older versions, or written without an identity) are
+    /// sealed on open.
+    pub fn new_for_identity<P: AsRef<Path>>(path: P, keypair: &KeyPair) -> Result<Self> {
+        let mut queue = Self::new_with_path(path)?;
+        queue.set_identity(keypair)?;
+        Ok(queue)
+    }
+
+    /// Open the queue file at `path` strictly read-only (inspection mode)
+    ///
+    /// Content sealed for `keypair` can be read; nothing is created,
+    /// migrated or re-sealed, and writes fail and are recorded in `writes`.
+    /// A missing file reads as an empty queue.
+    pub fn open_read_only<P: AsRef<Path>>(path: P, keypair: &KeyPair, writes: &WriteLog) -> Result<Self> {
+        let content_key = Some(keypair.queue_content_key()?);
+        if !path.as_ref().exists() {
+            return Ok(Self { content_key, ..Self::new()? });
+        }
+        Ok(Self {
+            conn: open_read_only(path.as_ref(), writes)?,
+            max_retries: 5,
+            base_delay_ms: 1000,
+            debug_import: false,
+            content_key,
+            dormant_expiry_ms: DEFAULT_DORMANT_EXPIRY_MS,
+            gate: QueueGate::new(),
+        })
     }
 
     /// Create a new message queue with a provided connection
-    fn new_with_connection(conn: Connection) -> Result<Self> {
+    fn new_with_connection(conn: Connection, gate: Arc<QueueGate>) -> Result<Self> {
         let mut queue = Self {
             conn,
             max_retries: 5,
             base_delay_ms: 1000, // 1 second base delay
+            debug_import: false,
+            content_key: None,
+            dormant_expiry_ms: DEFAULT_DORMANT_EXPIRY_MS,
+            gate,
         };
         queue.init_schema()?;
+        queue.mark_legacy_content()?;
+        queue.migrate_small_schema()?;
         Ok(queue)
     }
 
+    /// Give rows written before marker bytes existed a `CONTENT_PLAIN` marker
+    ///
+    /// Runs once per file (tracked in `user_version`).
+    fn mark_legacy_content(&mut self) -> Result<()> {
+        let version: i64 = self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version >= CONTENT_FORMAT_VERSION {
+            return Ok(());
+        }
+
+        let _pass = self.gate.pass();
+        let tx = self.conn.transaction()?;
+        let rows = {
+            let mut stmt = tx.prepare("SELECT message_id, content FROM message_queue")?;
+            stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        for (message_id, content) in rows {
+            let stored = seal_content(None, &content)?;
+            tx.execute(
+                "UPDATE message_queue SET content = ?1, payload = ?1 WHERE message_id = ?2",
+                params![stored, message_id],
+            )?;
+        }
+        tx.pragma_update(None, "user_version", CONTENT_FORMAT_VERSION)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Seal content for `keypair` from now on, and seal every plain row
+    ///
+    /// # Returns
+    /// The number of rows sealed
+    pub fn set_identity(&mut self, keypair: &KeyPair) -> Result<usize> {
+        let key = keypair.queue_content_key()?;
+        let sealed = self.reseal_rows(Some(&key), &key, |stored| stored.first() == Some(&CONTENT_PLAIN))?;
+        self.content_key = Some(key);
+        Ok(sealed)
+    }
+
+    /// Re-seal every queued row for a new identity
+    ///
+    /// Opens each row with the current key and seals it with `new_keypair`'s,
+    /// all in one transaction; on error nothing changes.
+    ///
+    /// # Returns
+    /// The number of rows re-sealed
+    pub fn rotate_identity(&mut self, new_keypair: &KeyPair) -> Result<usize> {
+        let new_key = new_keypair.queue_content_key()?;
+        let old_key = self.content_key;
+        let resealed = self.reseal_rows(old_key.as_ref(), &new_key, |_| true)?;
+        self.content_key = Some(new_key);
+        Ok(resealed)
+    }
+
+    /// Open the rows whose stored content matches `filter` with `open_key`
+    /// and seal them with `seal_key`, in one transaction
+    fn reseal_rows(
+        &mut self,
+        open_key: Option<&[u8; 32]>,
+        seal_key: &[u8; 32],
+        filter: impl Fn(&[u8]) -> bool,
+    ) -> Result<usize> {
+        let _pass = self.gate.pass();
+        let tx = self.conn.transaction()?;
+        let rows = {
+            let mut stmt = tx.prepare("SELECT message_id, content FROM message_queue")?;
+            stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        let mut resealed = 0;
+        for (message_id, stored) in rows.iter().filter(|(_, stored)| filter(stored)) {
+            let plaintext = open_content(open_key, stored)?;
+            tx.execute(
+                "UPDATE message_queue SET content = ?1, payload = ?1 WHERE message_id = ?2",
+                params![seal_content(Some(seal_key), &plaintext)?, message_id],
+            )?;
+            resealed += 1;
+        }
+        // Dead letters are sealed the same way
+        let dead_letters = {
+            let mut stmt = tx.prepare(&format!("SELECT message_id, content FROM {DEAD_LETTERS_TABLE}"))?;
+            stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        for (message_id, stored) in dead_letters.iter().filter(|(_, stored)| filter(stored)) {
+            let plaintext = open_content(open_key, stored)?;
+            tx.execute(
+                &format!("UPDATE {DEAD_LETTERS_TABLE} SET content = ?1 WHERE message_id = ?2"),
+                params![seal_content(Some(seal_key), &plaintext)?, message_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(resealed)
+    }
+
+    /// Open stored content with the queue's key
+    pub(crate) fn open_stored(&self, stored: &[u8]) -> Result<Vec<u8>> {
+        open_content(self.content_key.as_ref(), stored)
+    }
+
+    /// Open the content of fetched rows
+    fn open_rows(&self, mut messages: Vec<QueuedMessage>) -> Result<Vec<QueuedMessage>> {
+        for queued in &mut messages {
+            queued.message.content = open_content(self.content_key.as_ref(), &queued.message.content)?.into();
+        }
+        Ok(messages)
+    }
+
     /// Initialize the database schema
     fn init_schema(&mut self) -> Result<()> {
         self.conn.execute(
@@ -146,42 +638,102 @@ impl MessageQueue {
                 timestamp INTEGER NOT NULL,
                 priority INTEGER NOT NULL,
                 next_retry INTEGER NOT NULL,
-                created_at INTEGER NOT NULL
+                created_at INTEGER NOT NULL,
+                metadata TEXT,
+                last_error TEXT,
+                updated_at INTEGER,
+                dormant_since INTEGER,
+                suspended_since INTEGER,
+                blocked_since INTEGER,
+                scheduled_at INTEGER,
+                claimed_by TEXT
             )",
             [],
         )?;
-
-        // Create index for efficient priority-based fetching
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_queue_priority_retry
-             ON message_queue(priority DESC, next_retry ASC)",
-            [],
-        )?;
+        add_column_if_missing(&self.conn, "message_queue", "metadata", "TEXT")?;
+        add_column_if_missing(&self.conn, "message_queue", "last_error", "TEXT")?;
+        add_column_if_missing(&self.conn, "message_queue", "updated_at", "INTEGER")?;
+        add_column_if_missing(&self.conn, "message_queue", "dormant_since", "INTEGER")?;
+        add_column_if_missing(&self.conn, "message_queue", "suspended_since", "INTEGER")?;
+        add_column_if_missing(&self.conn, "message_queue", "blocked_since", "INTEGER")?;
+        add_column_if_missing(&self.conn, "message_queue", "scheduled_at", "INTEGER")?;
+        add_column_if_missing(&self.conn, "message_queue", "claimed_by", "TEXT")?;
+
+        // Create index for efficient priority-based fetching (suspended rows
+        // are never fetched, so they stay out of it). Version 2 replaces it
+        // with `idx_queue_due`.
+        self.conn.execute("DROP INDEX IF EXISTS idx_queue_priority_retry", [])?;
+        self.init_dead_letters()?;
+        let version: i64 = self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version < QUEUE_SCHEMA_VERSION {
+            self.conn.execute(
+                "CREATE INDEX IF NOT EXISTS idx_queue_live_priority_retry
+                 ON message_queue(priority DESC, next_retry ASC) WHERE suspended_since IS NULL",
+                [],
+            )?;
+        } else {
+            self.conn.execute(
+                &format!(
+                    "CREATE INDEX IF NOT EXISTS {QUEUE_DUE_INDEX}
+                     ON message_queue(priority DESC, next_retry ASC, created_at ASC)
+                     WHERE dormant_since IS NULL AND suspended_since IS NULL"
+                ),
+                [],
+            )?;
+        }
 
         Ok(())
     }
 
+    /// Build a queued message from a row selected by the fetch/list queries
+    fn queued_message_from_row(row: &rusqlite::Row) -> rusqlite::Result<QueuedMessage> {
+        let priority_val: i64 = row.get(5)?;
+        let priority = Priority::from_i64(priority_val)
+            .unwrap_or(Priority::Normal);
+
+        let mut message = Message::new(
+            row.get(0)?,
+            row.get(1)?,
+            row.get(2)?,
+            row.get(3)?,
+            row.get(4)?,
+        );
+        let metadata: Option<String> = row.get(8)?;
+        message.metadata = decode_metadata(metadata.as_deref());
+
+        Ok(QueuedMessage {
+            message,
+            priority,
+            attempts: row.get(6)?,
+            next_retry: row.get(7)?,
+        })
+    }
+
     /// Add a message to the queue
     pub fn enqueue(&mut self, message: Message, priority: Priority) -> Result<()> {
         let now = Utc::now().timestamp_millis();
         let message_type = "text"; // Default message type
+        let stored = seal_content(self.content_key.as_ref(), &message.content)?;
+        let _pass = self.gate.pass();
 
         self.conn.execute(
             "INSERT INTO message_queue
              (message_id, target_uid, message_type, payload, last_attempt, retry_count,
-              sender, recipient, content, timestamp, priority, next_retry, created_at)
-             VALUES (?1, ?2, ?3, ?4, NULL, 0, ?5, ?6, ?7, ?8, ?9, ?10, ?10)",
+              sender, recipient, content, timestamp, priority, next_retry, created_at, metadata, updated_at,
+              scheduled_at)
+             VALUES (?1, ?2, ?3, ?4, NULL, 0, ?5, ?6, ?7, ?8, ?9, ?10, ?10, ?11, ?10, ?10)",
             params![
                 message.id,
                 message.recipient, // target_uid
                 message_type,
-                message.content, // payload
+                stored, // payload
                 message.sender,
                 message.recipient,
-                message.content,
+                stored,
                 message.timestamp,
                 priority as i64,
                 now,
+                encode_metadata(&message.metadata)?,
             ],
         )?;
 
@@ -196,23 +748,27 @@ impl MessageQueue {
         message_type: &str,
     ) -> Result<()> {
         let now = Utc::now().timestamp_millis();
+        let stored = seal_content(self.content_key.as_ref(), &message.content)?;
+        let _pass = self.gate.pass();
 
         self.conn.execute(
             "INSERT INTO message_queue
              (message_id, target_uid, message_type, payload, last_attempt, retry_count,
-              sender, recipient, content, timestamp, priority, next_retry, created_at)
-             VALUES (?1, ?2, ?3, ?4, NULL, 0, ?5, ?6, ?7, ?8, ?9, ?10, ?10)",
+              sender, recipient, content, timestamp, priority, next_retry, created_at, metadata, updated_at,
+              scheduled_at)
+             VALUES (?1, ?2, ?3, ?4, NULL, 0, ?5, ?6, ?7, ?8, ?9, ?10, ?10, ?11, ?10, ?10)",
             params![
                 message.id,
                 message.recipient, // target_uid
                 message_type,
-                message.content, // payload
+                stored, // payload
                 message.sender,
                 message.recipient,
-                message.content,
+                stored,
                 message.timestamp,
                 priority as i64,
                 now,
+                encode_metadata(&message.metadata)?,
             ],
         )?;
 
@@ -221,82 +777,66 @@ impl MessageQueue {
 
     /// Get pending messages ready for delivery (ordered by priority and retry time)
     pub fn fetch_pending(&self) -> Result<Vec<QueuedMessage>> {
-        let now = Utc::now().timestamp_millis();
+        self.fetch_pending_at(Utc::now().timestamp_millis())
+    }
 
-        let mut stmt = self.conn.prepare(
-            "SELECT message_id, sender, recipient, content, timestamp, priority, retry_count, next_retry
-             FROM message_queue
-             WHERE next_retry <= ?1
-             ORDER BY priority DESC, next_retry ASC",
-        )?;
-
-        let rows = stmt.query_map(params![now], |row| {
-            let priority_val: i64 = row.get(5)?;
-            let priority = Priority::from_i64(priority_val)
-                .unwrap_or(Priority::Normal);
-
-            Ok(QueuedMessage {
-                message: Message::new(
-                    row.get(0)?,
-                    row.get(1)?,
-                    row.get(2)?,
-                    row.get(3)?,
-                    row.get(4)?,
-                ),
-                priority,
-                attempts: row.get(6)?,
-                next_retry: row.get(7)?,
-            })
-        })?;
+    /// Get messages ready for delivery at `now` (Unix milliseconds)
+    ///
+    /// Same as `fetch_pending` with the clock supplied by the caller, so a
+    /// retry schedule can be replayed against a virtual clock.
+    pub fn fetch_pending_at(&self, now: i64) -> Result<Vec<QueuedMessage>> {
+        let mut stmt = self.conn.prepare(FETCH_DUE_QUERY)?;
+
+        let rows = stmt.query_map(params![now], Self::queued_message_from_row)?;
 
         let mut messages = Vec::new();
         for row in rows {
             messages.push(row?);
         }
 
-        Ok(messages)
+        self.open_rows(messages)
+    }
+
+    /// Earliest `next_retry` (Unix milliseconds) among messages that are not
+    /// dormant or blocked
+    ///
+    /// The retry worker plans its next cycle from this (see `retry_schedule`).
+    pub fn earliest_next_retry(&self) -> Result<Option<i64>> {
+        let earliest = self.conn.query_row(
+            "SELECT MIN(next_retry) FROM message_queue
+             WHERE dormant_since IS NULL AND suspended_since IS NULL AND blocked_since IS NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(earliest)
     }
 
     /// Get all pending messages for startup retry (ignores retry time)
     ///
     /// This is used on app startup to immediately retry all queued messages
-    /// regardless of their scheduled retry time.
+    /// regardless of their scheduled retry time. Dormant and blocked messages
+    /// keep waiting for their contact.
     pub fn fetch_all_pending(&self) -> Result<Vec<QueuedMessage>> {
         let mut stmt = self.conn.prepare(
-            "SELECT message_id, sender, recipient, content, timestamp, priority, retry_count, next_retry
+            "SELECT message_id, sender, recipient, content, timestamp, priority, retry_count, next_retry, metadata
              FROM message_queue
-             ORDER BY priority DESC, next_retry ASC",
+             WHERE dormant_since IS NULL AND suspended_since IS NULL AND blocked_since IS NULL
+             ORDER BY priority DESC, next_retry ASC, created_at ASC, rowid ASC",
         )?;
 
-        let rows = stmt.query_map([], |row| {
-            let priority_val: i64 = row.get(5)?;
-            let priority = Priority::from_i64(priority_val)
-                .unwrap_or(Priority::Normal);
-
-            Ok(QueuedMessage {
-                message: Message::new(
-                    row.get(0)?,
-                    row.get(1)?,
-                    row.get(2)?,
-                    row.get(3)?,
-                    row.get(4)?,
-                ),
-                priority,
-                attempts: row.get(6)?,
-                next_retry: row.get(7)?,
-            })
-        })?;
+        let rows = stmt.query_map([], Self::queued_message_from_row)?;
 
         let mut messages = Vec::new();
         for row in rows {
             messages.push(row?);
         }
 
-        Ok(messages)
+        self.open_rows(messages)
     }
 
     /// Dequeue: fetch the next pending message and remove it from the queue
     pub fn dequeue(&mut self) -> Result<Option<QueuedMessage>> {
+        let _pass = self.gate.pass();
         let pending = self.fetch_pending()?;
 
         if let Some(msg) = pending.first() {
@@ -313,6 +853,7 @@ impl MessageQueue {
 
     /// Mark a message as successfully delivered and remove from queue
     pub fn mark_delivered(&mut self, message_id: &str) -> Result<()> {
+        let _pass = self.gate.pass();
         let deleted = self.conn.execute(
             "DELETE FROM message_queue WHERE message_id = ?1",
             params![message_id],
@@ -335,24 +876,39 @@ impl MessageQueue {
 
     /// Mark a message as failed and schedule retry with exponential backoff
     pub fn mark_failed(&mut self, message_id: &str) -> Result<()> {
-        let now = Utc::now().timestamp_millis();
+        self.mark_failed_at(message_id, Utc::now().timestamp_millis())
+    }
 
+    /// Mark a message as failed at `now` (Unix milliseconds)
+    ///
+    /// Same as `mark_failed` with the clock supplied by the caller. Once the
+    /// retries are used up, the error recorded with `record_error` decides
+    /// between dropping the message and letting it go dormant.
+    pub fn mark_failed_at(&mut self, message_id: &str, now: i64) -> Result<()> {
+        let _pass = self.gate.pass();
         // Get current retry_count
-        let retry_count: u32 = self.conn.query_row(
-            "SELECT retry_count FROM message_queue WHERE message_id = ?1",
+        let (retry_count, last_error): (u32, Option<String>) = self.conn.query_row(
+            "SELECT retry_count, last_error FROM message_queue WHERE message_id = ?1",
             params![message_id],
-            |row| row.get(0),
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )?;
 
         let new_retry_count = retry_count + 1;
 
         // Check if we've exceeded max retries
         if new_retry_count >= self.max_retries {
-            // Remove from queue - too many failures
-            self.conn.execute(
-                "DELETE FROM message_queue WHERE message_id = ?1",
-                params![message_id],
-            )?;
+            if FailureKind::classify(last_error.as_deref()) == FailureKind::Connectivity {
+                // Unreachable, not refused: wait for the contact to show up again
+                self.conn.execute(
+                    "UPDATE message_queue
+                     SET retry_count = ?1, last_attempt = ?2, updated_at = ?2, dormant_since = ?2
+                     WHERE message_id = ?3",
+                    params![new_retry_count, now, message_id],
+                )?;
+                return Ok(());
+            }
+            // Out of the queue - too many failures (text is kept as a dead letter)
+            Self::bury_where(&self.conn, "message_id = ?1", &message_id, now)?;
             return Ok(());
         }
 
@@ -363,7 +919,7 @@ impl MessageQueue {
         // Update the message with new retry count, last attempt time, and next retry time
         self.conn.execute(
             "UPDATE message_queue
-             SET retry_count = ?1, last_attempt = ?2, next_retry = ?3
+             SET retry_count = ?1, last_attempt = ?2, next_retry = ?3, updated_at = ?2
              WHERE message_id = ?4",
             params![new_retry_count, now, next_retry, message_id],
         )?;
@@ -371,15 +927,92 @@ impl MessageQueue {
         Ok(())
     }
 
+    /// Bring back the dormant messages to `target_uid` for delivery now
+    ///
+    /// Call this when the contact is heard from (authenticated inbound
+    /// traffic proves a working path). See `resurrect_for_at`.
+    pub fn resurrect_for(&mut self, target_uid: &str) -> Result<usize> {
+        self.resurrect_for_at(target_uid, Utc::now().timestamp_millis())
+    }
+
+    /// Bring back the dormant messages to `target_uid` at `now` (Unix milliseconds)
+    ///
+    /// Their attempts start over and they are due at once, in the order
+    /// they were queued.
+    ///
+    /// # Returns
+    /// How many messages were brought back
+    pub fn resurrect_for_at(&mut self, target_uid: &str, now: i64) -> Result<usize> {
+        let _pass = self.gate.pass();
+        let resurrected = self.conn.execute(
+            "UPDATE message_queue
+             SET dormant_since = NULL, retry_count = 0, next_retry = ?1, updated_at = ?1
+             WHERE target_uid = ?2 AND dormant_since IS NOT NULL AND suspended_since IS NULL",
+            params![now, target_uid],
+        )?;
+        Ok(resurrected)
+    }
+
+    /// Drop messages that stayed dormant longer than the dormant expiry
+    pub fn expire_dormant(&mut self) -> Result<Vec<String>> {
+        self.expire_dormant_at(Utc::now().timestamp_millis())
+    }
+
+    /// Drop messages dormant since before `now - dormant expiry`
+    ///
+    /// Text messages are kept as dead letters (see `queue_dead_letters`).
+    ///
+    /// # Returns
+    /// The contacts that lost messages (sorted, each once), so their chats
+    /// can show the failure
+    pub fn expire_dormant_at(&mut self, now: i64) -> Result<Vec<String>> {
+        let _pass = self.gate.pass();
+        let cutoff = now - self.dormant_expiry_ms;
+        let tx = self.conn.transaction()?;
+        let mut targets = {
+            let mut stmt = tx.prepare(
+                "SELECT DISTINCT target_uid FROM message_queue
+                 WHERE dormant_since IS NOT NULL AND dormant_since <= ?1 AND suspended_since IS NULL",
+            )?;
+            let rows = stmt.query_map(params![cutoff], |row| row.get::<_, String>(0))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        Self::bury_where(
+            &tx,
+            "dormant_since IS NOT NULL AND dormant_since <= ?1 AND suspended_since IS NULL",
+            &cutoff,
+            now,
+        )?;
+        tx.commit()?;
+        targets.sort();
+        Ok(targets)
+    }
+
+    /// Number of dormant messages
+    pub fn count_dormant(&self) -> Result<usize> {
+        let count: usize = self.conn.query_row(
+            "SELECT COUNT(*) FROM message_queue WHERE dormant_since IS NOT NULL AND suspended_since IS NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    /// Set how long a message stays dormant before it fails (in milliseconds)
+    pub fn set_dormant_expiry_ms(&mut self, dormant_expiry_ms: i64) {
+        self.dormant_expiry_ms = dormant_expiry_ms;
+    }
+
     /// Schedule a retry for a message with custom delay
     pub fn schedule_retry(&mut self, message_id: &str, delay_ms: i64) -> Result<()> {
+        let _pass = self.gate.pass();
         let now = Utc::now().timestamp_millis();
         let next_retry = now + delay_ms;
 
         // Increment retry count and update last_attempt and next_retry
         self.conn.execute(
             "UPDATE message_queue
-             SET retry_count = retry_count + 1, last_attempt = ?1, next_retry = ?2
+             SET retry_count = retry_count + 1, last_attempt = ?1, next_retry = ?2, updated_at = ?1
              WHERE message_id = ?3",
             params![now, next_retry, message_id],
         )?;
@@ -392,13 +1025,14 @@ impl MessageQueue {
     /// This method uses the global retry interval instead of exponential backoff.
     /// Useful for periodic retry attempts at a fixed interval.
     pub fn schedule_retry_global(&mut self, message_id: &str, global_interval_ms: u64) -> Result<()> {
+        let _pass = self.gate.pass();
         let now = Utc::now().timestamp_millis();
         let next_retry = now + global_interval_ms as i64;
 
         // Increment retry count and update last_attempt and next_retry
         self.conn.execute(
             "UPDATE message_queue
-             SET retry_count = retry_count + 1, last_attempt = ?1, next_retry = ?2
+             SET retry_count = retry_count + 1, last_attempt = ?1, next_retry = ?2, updated_at = ?1
              WHERE message_id = ?3",
             params![now, next_retry, message_id],
         )?;
@@ -406,10 +1040,185 @@ impl MessageQueue {
         Ok(())
     }
 
-    /// Get the current queue size
+    /// Record why the last delivery attempt for a message failed
+    ///
+    /// Kept for `export_debug`; a message that is no longer queued is ignored.
+    pub fn record_error(&mut self, message_id: &str, error: &str) -> Result<()> {
+        let _pass = self.gate.pass();
+        self.conn.execute(
+            "UPDATE message_queue SET last_error = ?1, updated_at = ?2 WHERE message_id = ?3",
+            params![error, Utc::now().timestamp_millis(), message_id],
+        )?;
+        Ok(())
+    }
+
+    /// Last recorded delivery error for a message
+    pub fn last_error(&self, message_id: &str) -> Result<Option<String>> {
+        let error: Option<Option<String>> = self
+            .conn
+            .query_row(
+                "SELECT last_error FROM message_queue WHERE message_id = ?1",
+                params![message_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(error.flatten())
+    }
+
+    /// Build a redacted report of every queued row that is not suspended, with
+    /// states relative to `now`
+    ///
+    /// Content is reduced to its length and SHA-256, UIDs (also inside error
+    /// strings) are truncated to `DEBUG_UID_CHARS` and metadata values are
+    /// dropped. Rows are in fetch order.
+    pub fn debug_report(&self, now: i64) -> Result<QueueDebugReport> {
+        let mut stmt = self.conn.prepare(
+            "SELECT message_id, target_uid, sender, message_type, priority, retry_count, next_retry,
+                    last_attempt, last_error, created_at, COALESCE(updated_at, last_attempt, created_at),
+                    content, metadata, dormant_since, blocked_since
+             FROM message_queue
+             WHERE suspended_since IS NULL
+             ORDER BY priority DESC, next_retry ASC, created_at ASC, rowid ASC",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            let next_retry: i64 = row.get(6)?;
+            let last_error: Option<String> = row.get(8)?;
+            let stored: Vec<u8> = row.get(11)?;
+            // Length and hash describe the message, not its sealed form
+            let content = open_content(self.content_key.as_ref(), &stored).unwrap_or(stored);
+            let metadata: Option<String> = row.get(12)?;
+            let dormant_since: Option<i64> = row.get(13)?;
+            let blocked_since: Option<i64> = row.get(14)?;
+            let state = if blocked_since.is_some() {
+                QueueRowState::Blocked
+            } else if dormant_since.is_some() {
+                QueueRowState::Dormant
+            } else if next_retry <= now {
+                QueueRowState::Due
+            } else {
+                QueueRowState::Waiting
+            };
+            Ok(QueueDebugRow {
+                message_id: row.get(0)?,
+                target: redact_uid(&row.get::<_, String>(1)?),
+                sender: redact_uid(&row.get::<_, String>(2)?),
+                message_type: row.get(3)?,
+                state,
+                priority: Priority::from_i64(row.get(4)?).unwrap_or(Priority::Normal),
+                attempts: row.get(5)?,
+                next_retry,
+                last_attempt: row.get(7)?,
+                last_error: last_error.as_deref().map(redact_text),
+                created_at: row.get(9)?,
+                updated_at: row.get(10)?,
+                dormant_since,
+                blocked_since,
+                content_len: content.len(),
+                content_sha256: hex::encode(digest(&SHA256, &content)),
+                metadata_keys: decode_metadata(metadata.as_deref()).into_keys().collect(),
+            })
+        })?;
+
+        Ok(QueueDebugReport {
+            version: QUEUE_DEBUG_VERSION,
+            exported_at: now,
+            max_retries: self.max_retries,
+            base_delay_ms: self.base_delay_ms,
+            rows: rows.collect::<rusqlite::Result<Vec<_>>>()?,
+        })
+    }
+
+    /// Write a redacted debug report of the queue to `path` as JSON
+    ///
+    /// Returns the number of rows written. See `debug_report` for what is kept.
+    pub fn export_debug<P: AsRef<Path>>(&self, path: P) -> Result<usize> {
+        let report = self.debug_report(Utc::now().timestamp_millis())?;
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+        Ok(report.rows.len())
+    }
+
+    /// Allow or refuse `import_debug` on this queue
+    ///
+    /// Off by default so a report can't be loaded into a real profile by
+    /// accident; only turn it on for a scratch queue.
+    pub fn set_debug_import(&mut self, enabled: bool) {
+        self.debug_import = enabled;
+    }
+
+    /// Load a debug report written by `export_debug` into this queue
+    ///
+    /// See `import_debug_report`.
+    pub fn import_debug<P: AsRef<Path>>(&mut self, path: P) -> Result<usize> {
+        let json = std::fs::read_to_string(path)?;
+        let report: QueueDebugReport = serde_json::from_str(&json)?;
+        self.import_debug_report(&report)
+    }
+
+    /// Rebuild synthetic rows from a debug report
+    ///
+    /// Each row gets zeroed content of the reported length and the truncated
+    /// UIDs, with its priority, attempts and timestamps intact, and the
+    /// queue takes the report's retry settings. Replaying with
+    /// `fetch_pending_at` then yields the same order as the original queue.
+    /// Requires `set_debug_import(true)` and an empty queue.
+    pub fn import_debug_report(&mut self, report: &QueueDebugReport) -> Result<usize> {
+        if !self.debug_import {
+            return Err(Error::NotSupported(
+                "queue debug import is disabled (scratch queues only)".to_string(),
+            ));
+        }
+        if report.version != QUEUE_DEBUG_VERSION {
+            return Err(Error::Queue(format!(
+                "Unsupported queue debug report version {}",
+                report.version
+            )));
+        }
+        if self.size()? > 0 {
+            return Err(Error::Queue(
+                "Queue debug import needs an empty scratch queue".to_string(),
+            ));
+        }
+
+        let _pass = self.gate.pass();
+        let tx = self.conn.transaction()?;
+        for row in &report.rows {
+            let content = seal_content(self.content_key.as_ref(), &vec![0u8; row.content_len])?;
+            tx.execute(
+                "INSERT INTO message_queue
+                 (message_id, target_uid, message_type, payload, last_attempt, retry_count,
+                  sender, recipient, content, timestamp, priority, next_retry, created_at,
+                  metadata, last_error, updated_at, dormant_since, blocked_since, scheduled_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?2, ?4, ?8, ?9, ?10, ?8, NULL, ?11, ?12, ?13, ?14, ?8)",
+                params![
+                    row.message_id,
+                    row.target,
+                    row.message_type,
+                    content,
+                    row.last_attempt,
+                    row.attempts,
+                    row.sender,
+                    row.created_at,
+                    row.priority as i64,
+                    row.next_retry,
+                    row.last_error,
+                    row.updated_at,
+                    row.dormant_since,
+                    row.blocked_since,
+                ],
+            )?;
+        }
+        tx.commit()?;
+
+        self.max_retries = report.max_retries;
+        self.base_delay_ms = report.base_delay_ms;
+        Ok(report.rows.len())
+    }
+
+    /// Get the current queue size (suspended messages are not counted)
     pub fn size(&self) -> Result<usize> {
         let count: usize = self.conn.query_row(
-            "SELECT COUNT(*) FROM message_queue",
+            "SELECT COUNT(*) FROM message_queue WHERE suspended_since IS NULL",
             [],
             |row| row.get(0),
         )?;
@@ -421,45 +1230,31 @@ impl MessageQueue {
         self.size()
     }
 
-    /// Clear all messages from the queue
+    /// Clear all messages from the queue, dead letters included
     pub fn clear(&mut self) -> Result<()> {
+        let _pass = self.gate.pass();
         self.conn.execute("DELETE FROM message_queue", [])?;
+        self.conn.execute(&format!("DELETE FROM {DEAD_LETTERS_TABLE}"), [])?;
         Ok(())
     }
 
-    /// Get all messages in the queue (for inspection/debugging)
+    /// Get all messages in the queue (for inspection/debugging), suspended ones left out
     pub fn list(&self) -> Result<Vec<QueuedMessage>> {
         let mut stmt = self.conn.prepare(
-            "SELECT message_id, sender, recipient, content, timestamp, priority, retry_count, next_retry
+            "SELECT message_id, sender, recipient, content, timestamp, priority, retry_count, next_retry, metadata
              FROM message_queue
-             ORDER BY priority DESC, next_retry ASC",
+             WHERE suspended_since IS NULL
+             ORDER BY priority DESC, next_retry ASC, created_at ASC, rowid ASC",
         )?;
 
-        let rows = stmt.query_map([], |row| {
-            let priority_val: i64 = row.get(5)?;
-            let priority = Priority::from_i64(priority_val)
-                .unwrap_or(Priority::Normal);
-
-            Ok(QueuedMessage {
-                message: Message::new(
-                    row.get(0)?,
-                    row.get(1)?,
-                    row.get(2)?,
-                    row.get(3)?,
-                    row.get(4)?,
-                ),
-                priority,
-                attempts: row.get(6)?,
-                next_retry: row.get(7)?,
-            })
-        })?;
+        let rows = stmt.query_map([], Self::queued_message_from_row)?;
 
         let mut messages = Vec::new();
         for row in rows {
             messages.push(row?);
         }
 
-        Ok(messages)
+        self.open_rows(messages)
     }
 
     /// Get unique contact UIDs (target_uid) that have pending messages in the queue
@@ -485,7 +1280,7 @@ impl MessageQueue {
     /// ```
     pub fn get_pending_contact_uids(&self) -> Result<std::collections::HashSet<String>> {
         let mut stmt = self.conn.prepare(
-            "SELECT DISTINCT target_uid FROM message_queue",
+            "SELECT DISTINCT target_uid FROM message_queue WHERE suspended_since IS NULL",
         )?;
 
         let rows = stmt.query_map([], |row| {
@@ -501,6 +1296,214 @@ impl MessageQueue {
         Ok(uids)
     }
 
+    /// Check whether a message is still in the queue
+    ///
+    /// `mark_failed` drops a message once it exhausts its retries (unless it
+    /// goes dormant); this tells a rescheduled or dormant message from a
+    /// dropped one.
+    pub fn contains(&self, message_id: &str) -> Result<bool> {
+        let exists: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM message_queue WHERE message_id = ?1)",
+            params![message_id],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    /// Check whether any message to `target_uid` is still queued
+    pub fn has_pending_for(&self, target_uid: &str) -> Result<bool> {
+        let exists: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM message_queue WHERE target_uid = ?1 AND suspended_since IS NULL)",
+            params![target_uid],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    /// Drop every message to `target_uid`, dormant, suspended and failed ones included
+    ///
+    /// Used when a temporary contact ends or a deleted one is purged: nothing
+    /// should reach it afterwards.
+    ///
+    /// # Returns
+    /// The number of messages dropped
+    pub fn purge_for(&mut self, target_uid: &str) -> Result<usize> {
+        let _pass = self.gate.pass();
+        let deleted = self.conn.execute(
+            "DELETE FROM message_queue WHERE target_uid = ?1",
+            params![target_uid],
+        )?;
+        self.conn.execute(
+            &format!("DELETE FROM {DEAD_LETTERS_TABLE} WHERE target_uid = ?1"),
+            params![target_uid],
+        )?;
+        Ok(deleted)
+    }
+
+    /// Hold back every message to `target_uid` while its chat or contact is
+    /// soft-deleted (see `storage::soft_delete`)
+    ///
+    /// Suspended rows keep all their columns but are left out of every fetch,
+    /// count and listing; `unsuspend_for` brings them back unchanged.
+    ///
+    /// # Returns
+    /// The number of messages suspended
+    pub fn suspend_for(&mut self, target_uid: &str, now: i64) -> Result<usize> {
+        let _pass = self.gate.pass();
+        let suspended = self.conn.execute(
+            "UPDATE message_queue SET suspended_since = ?2 WHERE target_uid = ?1 AND suspended_since IS NULL",
+            params![target_uid, now],
+        )?;
+        Ok(suspended)
+    }
+
+    /// Undo `suspend_for`
+    ///
+    /// # Returns
+    /// The number of messages brought back
+    pub fn unsuspend_for(&mut self, target_uid: &str) -> Result<usize> {
+        let _pass = self.gate.pass();
+        let resumed = self.conn.execute(
+            "UPDATE message_queue SET suspended_since = NULL WHERE target_uid = ?1 AND suspended_since IS NOT NULL",
+            params![target_uid],
+        )?;
+        Ok(resumed)
+    }
+
+    /// Hold back the messages to `target_uid` from `now`: its token expired
+    ///
+    /// Blocked rows are left out of every fetch until `unblock_for_at`;
+    /// suspended and already blocked rows are left alone.
+    ///
+    /// # Returns
+    /// The number of messages newly blocked
+    pub fn block_for_at(&mut self, target_uid: &str, now: i64) -> Result<usize> {
+        let _pass = self.gate.pass();
+        let blocked = self.conn.execute(
+            "UPDATE message_queue SET blocked_since = ?2, updated_at = ?2
+             WHERE target_uid = ?1 AND blocked_since IS NULL AND suspended_since IS NULL",
+            params![target_uid, now],
+        )?;
+        Ok(blocked)
+    }
+
+    /// Undo `block_for_at` at `now`: a new token for `target_uid` was imported
+    ///
+    /// The messages are due at once with their attempts started over, like
+    /// `resurrect_for_at`, and dormant ones come back too.
+    ///
+    /// # Returns
+    /// The number of messages unblocked
+    pub fn unblock_for_at(&mut self, target_uid: &str, now: i64) -> Result<usize> {
+        let _pass = self.gate.pass();
+        let unblocked = self.conn.execute(
+            "UPDATE message_queue
+             SET blocked_since = NULL, dormant_since = NULL, retry_count = 0, next_retry = ?2, updated_at = ?2
+             WHERE target_uid = ?1 AND blocked_since IS NOT NULL AND suspended_since IS NULL",
+            params![target_uid, now],
+        )?;
+        Ok(unblocked)
+    }
+
+    /// Contacts with blocked messages
+    pub fn blocked_uids(&self) -> Result<std::collections::HashSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT target_uid FROM message_queue WHERE blocked_since IS NOT NULL AND suspended_since IS NULL",
+        )?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Number of blocked messages
+    pub fn count_blocked(&self) -> Result<usize> {
+        let count: usize = self.conn.query_row(
+            "SELECT COUNT(*) FROM message_queue WHERE blocked_since IS NOT NULL AND suspended_since IS NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    /// Contacts with suspended messages
+    pub fn suspended_uids(&self) -> Result<std::collections::HashSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT target_uid FROM message_queue WHERE suspended_since IS NOT NULL",
+        )?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// When each message to `target_uid` was queued
+    ///
+    /// # Returns
+    /// Map of message id to `created_at` (Unix milliseconds), dormant
+    /// messages included
+    pub fn queued_at_for(&self, target_uid: &str) -> Result<std::collections::HashMap<String, i64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT message_id, created_at FROM message_queue WHERE target_uid = ?1 AND suspended_since IS NULL",
+        )?;
+        let rows = stmt.query_map(params![target_uid], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Check whether a bulk-lane message to `target_uid` is queued and live
+    ///
+    /// Dormant, blocked and suspended messages don't count. A new message to a
+    /// contact with one queued goes in behind it rather than being sent
+    /// first (see `messaging::send_sealed_with_type`).
+    pub fn has_waiting_for(&self, target_uid: &str) -> Result<bool> {
+        let exists: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM message_queue
+             WHERE target_uid = ?1 AND priority < ?2 AND dormant_since IS NULL AND suspended_since IS NULL
+               AND blocked_since IS NULL)",
+            params![target_uid, Priority::High as i64],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    /// Timestamp of each contact's oldest live bulk-lane message not due at `now`
+    ///
+    /// Input for `order_per_contact`.
+    pub fn oldest_waiting_at(&self, now: i64) -> Result<HashMap<String, i64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT target_uid, MIN(timestamp) FROM message_queue
+             WHERE next_retry > ?1 AND priority < ?2 AND dormant_since IS NULL AND suspended_since IS NULL
+               AND blocked_since IS NULL
+             GROUP BY target_uid",
+        )?;
+        let rows = stmt.query_map(params![now, Priority::High as i64], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Check whether a message of `message_type` to `target_uid` is queued
+    pub fn has_queued_type_for(&self, target_uid: &str, message_type: &str) -> Result<bool> {
+        let exists: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM message_queue WHERE target_uid = ?1 AND message_type = ?2 AND suspended_since IS NULL)",
+            params![target_uid, message_type],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    /// Replace the content of a text message that is still queued
+    ///
+    /// Used by edits: an undelivered message is changed in place instead of
+    /// sending an edit after it.
+    ///
+    /// # Returns
+    /// Whether the message was in the queue
+    pub fn update_queued_content(&mut self, message_id: &str, content: &[u8]) -> Result<bool> {
+        let _pass = self.gate.pass();
+        let stored = seal_content(self.content_key.as_ref(), content)?;
+        let updated = self.conn.execute(
+            "UPDATE message_queue SET content = ?1, payload = ?1, updated_at = ?2
+             WHERE message_id = ?3 AND message_type = 'text'",
+            params![stored, Utc::now().timestamp_millis(), message_id],
+        )?;
+        Ok(updated > 0)
+    }
+
     /// Set maximum retry attempts
     pub fn set_max_retries(&mut self, max_retries: u32) {
         self.max_retries = max_retries;
@@ -561,6 +1564,7 @@ impl MessageQueue {
                 Err(e) => {
                     // Failure: mark as failed (will schedule retry)
                     tracing::warn!("Failed to deliver message {} on startup: {}", message_id, e);
+                    let _ = self.record_error(&message_id, &e.to_string());
                     if let Err(e) = self.mark_failed(&message_id) {
                         tracing::error!("Failed to update retry status for {}: {}", message_id, e);
                     }
@@ -0,0 +1,193 @@
+//! Failed messages kept for review and re-queueing
+//!
+//! A text message that uses up its retries and is refused (see
+//! `FailureKind`), or that stays dormant past the dormant expiry, leaves the
+//! queue for the `dead_letters` table instead of disappearing. Its content
+//! stays sealed at rest like a queued row, with the last delivery error as
+//! the reason it failed. Pings, edits and other control messages are still
+//! dropped; they mean nothing out of their moment.
+//!
+//! `failed_for` lists a contact's dead letters together with its dormant
+//! text messages in composition order (message timestamp, then queueing
+//! time). `requeue_failed` puts the chosen ones back with fresh attempts,
+//! due at once; the retry worker then sends each contact's messages oldest
+//! first (see `queue::order_per_contact`), ahead of anything composed later.
+
+use crate::queue::MessageQueue;
+use crate::storage::{storage_db::decode_metadata, Message};
+use crate::Result;
+use chrono::Utc;
+use rusqlite::{params, Connection, ToSql};
+use std::collections::HashSet;
+
+/// Table holding failed text messages
+pub const DEAD_LETTERS_TABLE: &str = "dead_letters";
+
+/// The only message type kept when it fails
+pub const DEAD_LETTER_TYPE: &str = "text";
+
+/// A text message that could not be delivered
+#[derive(Debug, Clone)]
+pub struct FailedMessage {
+    /// The message as it was queued
+    pub message: Message,
+    /// Delivery attempts made before it failed
+    pub attempts: u32,
+    /// Last delivery error (None if none was recorded)
+    pub reason: Option<String>,
+    /// When it failed or went dormant (Unix milliseconds)
+    pub since: i64,
+    /// Still queued, waiting for the contact, rather than failed for good
+    pub dormant: bool,
+}
+
+impl MessageQueue {
+    /// Create the `dead_letters` table
+    pub(crate) fn init_dead_letters(&mut self) -> Result<()> {
+        self.conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {DEAD_LETTERS_TABLE} (
+                    message_id TEXT PRIMARY KEY,
+                    target_uid TEXT NOT NULL,
+                    message_type TEXT NOT NULL,
+                    sender TEXT NOT NULL,
+                    recipient TEXT NOT NULL,
+                    content BLOB NOT NULL,
+                    timestamp INTEGER NOT NULL,
+                    priority INTEGER NOT NULL,
+                    metadata TEXT,
+                    attempts INTEGER NOT NULL,
+                    last_error TEXT,
+                    created_at INTEGER NOT NULL,
+                    failed_at INTEGER NOT NULL
+                )"
+            ),
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Move queued rows matching `condition` to the dead letters (text
+    /// messages) or drop them (everything else), failed at `now`
+    ///
+    /// `condition` is an SQL expression over `message_queue` taking `?1`.
+    /// The caller holds a pass.
+    ///
+    /// # Returns
+    /// How many rows left the queue
+    pub(crate) fn bury_where(conn: &Connection, condition: &str, arg: &dyn ToSql, now: i64) -> Result<usize> {
+        conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {DEAD_LETTERS_TABLE}
+                 (message_id, target_uid, message_type, sender, recipient, content, timestamp, priority,
+                  metadata, attempts, last_error, created_at, failed_at)
+                 SELECT message_id, target_uid, message_type, sender, recipient, content, timestamp, priority,
+                        metadata, retry_count, last_error, created_at, ?2
+                 FROM message_queue WHERE ({condition}) AND message_type = '{DEAD_LETTER_TYPE}'"
+            ),
+            params![arg, now],
+        )?;
+        Ok(conn.execute(&format!("DELETE FROM message_queue WHERE {condition}"), params![arg])?)
+    }
+
+    /// Failed and dormant text messages to `target_uid`, oldest first
+    ///
+    /// Ordered by when they were written, so a review shows them as the
+    /// conversation had them.
+    pub fn failed_for(&self, target_uid: &str) -> Result<Vec<FailedMessage>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT message_id, sender, recipient, content, timestamp, metadata, attempts, last_error,
+                    failed_at AS since, 0 AS dormant, created_at
+             FROM {DEAD_LETTERS_TABLE} WHERE target_uid = ?1
+             UNION ALL
+             SELECT message_id, sender, recipient, content, timestamp, metadata, retry_count, last_error,
+                    dormant_since, 1, created_at
+             FROM message_queue
+             WHERE target_uid = ?1 AND message_type = '{DEAD_LETTER_TYPE}'
+               AND dormant_since IS NOT NULL AND suspended_since IS NULL
+             ORDER BY timestamp ASC, created_at ASC, message_id ASC"
+        ))?;
+        let rows = stmt.query_map(params![target_uid], |row| {
+            let mut message = Message::new(row.get(0)?, row.get(1)?, row.get(2)?, row.get::<_, Vec<u8>>(3)?, row.get(4)?);
+            let metadata: Option<String> = row.get(5)?;
+            message.metadata = decode_metadata(metadata.as_deref());
+            Ok(FailedMessage {
+                message,
+                attempts: row.get(6)?,
+                reason: row.get(7)?,
+                since: row.get(8)?,
+                dormant: row.get(9)?,
+            })
+        })?;
+        let mut failed = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        for entry in &mut failed {
+            entry.message.content = self.open_stored(&entry.message.content)?.into();
+        }
+        Ok(failed)
+    }
+
+    /// IDs of the failed and dormant text messages to `target_uid`
+    ///
+    /// Same set as `failed_for`, without opening any content.
+    pub fn failed_ids_for(&self, target_uid: &str) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT message_id FROM {DEAD_LETTERS_TABLE} WHERE target_uid = ?1
+             UNION ALL
+             SELECT message_id FROM message_queue
+             WHERE target_uid = ?1 AND message_type = '{DEAD_LETTER_TYPE}'
+               AND dormant_since IS NOT NULL AND suspended_since IS NULL"
+        ))?;
+        let rows = stmt.query_map(params![target_uid], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Put failed messages to `target_uid` back in the queue
+    ///
+    /// See `requeue_failed_at`.
+    pub fn requeue_failed(&mut self, target_uid: &str, message_ids: &[String]) -> Result<usize> {
+        self.requeue_failed_at(target_uid, message_ids, Utc::now().timestamp_millis())
+    }
+
+    /// Put the failed and dormant messages in `message_ids` back in the
+    /// queue at `now` (Unix milliseconds), in one transaction
+    ///
+    /// Their attempts start over and they are due at once; each keeps its
+    /// original queueing time. IDs that are not failed messages to
+    /// `target_uid` are ignored.
+    ///
+    /// # Returns
+    /// How many messages were re-queued
+    pub fn requeue_failed_at(&mut self, target_uid: &str, message_ids: &[String], now: i64) -> Result<usize> {
+        let chosen: HashSet<&str> = message_ids.iter().map(String::as_str).collect();
+        let _pass = self.gate.pass();
+        let tx = self.conn.transaction()?;
+        let mut requeued = 0;
+        for message_id in chosen {
+            requeued += tx.execute(
+                "UPDATE message_queue
+                 SET dormant_since = NULL, retry_count = 0, last_error = NULL, next_retry = ?1, updated_at = ?1
+                 WHERE message_id = ?2 AND target_uid = ?3 AND dormant_since IS NOT NULL AND suspended_since IS NULL",
+                params![now, message_id, target_uid],
+            )?;
+            let restored = tx.execute(
+                &format!(
+                    "INSERT OR IGNORE INTO message_queue
+                     (message_id, target_uid, message_type, payload, last_attempt, retry_count,
+                      sender, recipient, content, timestamp, priority, next_retry, created_at, metadata, updated_at,
+                      scheduled_at)
+                     SELECT message_id, target_uid, message_type, content, NULL, 0,
+                            sender, recipient, content, timestamp, priority, ?1, created_at, metadata, ?1, created_at
+                     FROM {DEAD_LETTERS_TABLE} WHERE message_id = ?2 AND target_uid = ?3"
+                ),
+                params![now, message_id, target_uid],
+            )?;
+            tx.execute(
+                &format!("DELETE FROM {DEAD_LETTERS_TABLE} WHERE message_id = ?1 AND target_uid = ?2"),
+                params![message_id, target_uid],
+            )?;
+            requeued += restored;
+        }
+        tx.commit()?;
+        Ok(requeued)
+    }
+}
@@ -0,0 +1,260 @@
+//! Online schema migrations for the message queue
+//!
+//! The retry worker, send threads and the app each open their own
+//! connection to `message_queue.db`, so a migration must not hold the file
+//! for long. Writers go through a `QueueGate` shared by every `MessageQueue`
+//! on the same path: each write takes a pass, and a migration takes the gate
+//! exclusively for one bounded batch at a time. A writer that arrives during
+//! a batch waits for it to commit (its safe point), then carries on.
+//!
+//! Progress is kept in `queue_migration` inside the batch's transaction, so
+//! an interrupted migration resumes after the last committed batch. Queries
+//! work on both schemas, so nothing has to restart once the version changes.
+//!
+//! ## Version 2
+//!
+//! Adds `scheduled_at` (when the row was queued, backfilled from
+//! `created_at`) and `claimed_by` (the worker attempting a row; not written
+//! yet). Both columns are added on open, which is cheap; the migration fills
+//! `scheduled_at` for older rows and then replaces
+//! `idx_queue_live_priority_retry` with `idx_queue_due`, which covers the
+//! whole fetch order and skips dormant rows.
+//!
+//! Queues of at most `MIGRATION_BATCH_ROWS` rows migrate on open; larger
+//! ones are left to `MessageQueue::migrate_schema`, which the app runs in
+//! the background at startup.
+
+use crate::queue::MessageQueue;
+use crate::Result;
+use rusqlite::{params, OptionalExtension};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError, Weak};
+
+/// Queue schema version written to `user_version` once migrated
+pub const QUEUE_SCHEMA_VERSION: i64 = 2;
+
+/// Rows updated per migration batch (the longest a writer waits)
+pub const MIGRATION_BATCH_ROWS: usize = 1000;
+
+/// Index serving `fetch_pending_at` from version 2 on
+pub const QUEUE_DUE_INDEX: &str = "idx_queue_due";
+
+/// Coordinates queue writers with a running migration
+///
+/// Shared by every `MessageQueue` opened on the same file (see `for_path`).
+#[derive(Debug, Default)]
+pub struct QueueGate {
+    lock: RwLock<()>,
+    pauses: AtomicU64,
+}
+
+impl QueueGate {
+    /// Gate for a queue without a file (not shared)
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Gate shared by every queue opened on `path`
+    pub fn for_path(path: &Path) -> Arc<Self> {
+        static GATES: OnceLock<Mutex<HashMap<PathBuf, Weak<QueueGate>>>> = OnceLock::new();
+        let key = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut gates = GATES.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(gate) = gates.get(&key).and_then(Weak::upgrade) {
+            return gate;
+        }
+        gates.retain(|_, gate| gate.strong_count() > 0);
+        let gate = Self::new();
+        gates.insert(key, Arc::downgrade(&gate));
+        gate
+    }
+
+    /// Pass for one write, waiting while a migration batch runs
+    pub fn pass(&self) -> RwLockReadGuard<'_, ()> {
+        match self.lock.try_read() {
+            Ok(pass) => pass,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                self.pauses.fetch_add(1, Ordering::Relaxed);
+                self.lock.read().unwrap_or_else(|e| e.into_inner())
+            }
+        }
+    }
+
+    /// Hold off every writer until the guard is dropped
+    pub fn exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        self.lock.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// How many writes waited for a migration batch
+    pub fn pauses(&self) -> u64 {
+        self.pauses.load(Ordering::Relaxed)
+    }
+}
+
+/// Progress of a queue schema migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueMigration {
+    /// Rows migrated
+    pub done: usize,
+    /// Rows to migrate (rows queued meanwhile are written migrated)
+    pub total: usize,
+    /// Schema version the queue is at
+    pub version: i64,
+}
+
+impl QueueMigration {
+    /// Whether the queue is at `QUEUE_SCHEMA_VERSION`
+    pub fn is_complete(&self) -> bool {
+        self.version >= QUEUE_SCHEMA_VERSION
+    }
+}
+
+impl MessageQueue {
+    /// Schema version of the queue file
+    pub fn schema_version(&self) -> Result<i64> {
+        Ok(self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
+
+    /// Rows done by a migration that started and did not finish
+    ///
+    /// # Returns
+    /// None if no migration is under way (not started, or complete)
+    pub fn interrupted_migration(&self) -> Result<Option<usize>> {
+        if self.schema_version()? >= QUEUE_SCHEMA_VERSION {
+            return Ok(None);
+        }
+        let started: bool = self.conn.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'queue_migration'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !started {
+            return Ok(None);
+        }
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT rows_done FROM queue_migration WHERE version = ?1",
+                params![QUEUE_SCHEMA_VERSION],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Migrate the queue to `QUEUE_SCHEMA_VERSION` in batches
+    ///
+    /// Each batch of `MIGRATION_BATCH_ROWS` rows runs in its own
+    /// transaction under the exclusive gate, and `progress` is called after
+    /// it; returning `ControlFlow::Break` stops there. A stopped or
+    /// interrupted run resumes from the last committed batch. Does nothing
+    /// on a migrated queue.
+    ///
+    /// # Errors
+    /// Returns `Error::Database` if reading or writing fails
+    pub fn migrate_schema(
+        &mut self,
+        mut progress: impl FnMut(&QueueMigration) -> ControlFlow<()>,
+    ) -> Result<QueueMigration> {
+        let version = self.schema_version()?;
+        if version >= QUEUE_SCHEMA_VERSION {
+            return Ok(QueueMigration { done: 0, total: 0, version });
+        }
+        let gate = self.gate.clone();
+        // Writers keep the file busy between their passes, so even the setup
+        // waits for a gap rather than racing them for the lock
+        let setup = gate.exclusive();
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS queue_migration (
+                version INTEGER PRIMARY KEY,
+                last_rowid INTEGER NOT NULL,
+                rows_done INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        let (mut last_rowid, done) = self
+            .conn
+            .query_row(
+                "SELECT last_rowid, rows_done FROM queue_migration WHERE version = ?1",
+                params![QUEUE_SCHEMA_VERSION],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, usize>(1)?)),
+            )
+            .optional()?
+            .unwrap_or((0, 0));
+        let remaining: usize = self.conn.query_row(
+            "SELECT COUNT(*) FROM message_queue WHERE rowid > ?1",
+            params![last_rowid],
+            |row| row.get(0),
+        )?;
+        drop(setup);
+        let mut state = QueueMigration { done, total: done + remaining, version };
+
+        loop {
+            let batch = {
+                let _exclusive = gate.exclusive();
+                let tx = self.conn.transaction()?;
+                let (rows, batch_end): (usize, Option<i64>) = tx.query_row(
+                    "SELECT COUNT(*), MAX(rowid) FROM
+                     (SELECT rowid FROM message_queue WHERE rowid > ?1 ORDER BY rowid LIMIT ?2)",
+                    params![last_rowid, MIGRATION_BATCH_ROWS as i64],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )?;
+                match batch_end {
+                    Some(batch_end) => {
+                        tx.execute(
+                            "UPDATE message_queue SET scheduled_at = COALESCE(scheduled_at, created_at)
+                             WHERE rowid > ?1 AND rowid <= ?2",
+                            params![last_rowid, batch_end],
+                        )?;
+                        tx.execute(
+                            "INSERT OR REPLACE INTO queue_migration (version, last_rowid, rows_done)
+                             VALUES (?1, ?2, ?3)",
+                            params![QUEUE_SCHEMA_VERSION, batch_end, state.done + rows],
+                        )?;
+                        tx.commit()?;
+                        Some((rows, batch_end))
+                    }
+                    None => {
+                        // Last step: swap the index and record the version
+                        tx.execute_batch(&format!(
+                            "CREATE INDEX IF NOT EXISTS {QUEUE_DUE_INDEX}
+                             ON message_queue(priority DESC, next_retry ASC, created_at ASC)
+                             WHERE dormant_since IS NULL AND suspended_since IS NULL;
+                             DROP INDEX IF EXISTS idx_queue_live_priority_retry;"
+                        ))?;
+                        tx.execute("DELETE FROM queue_migration WHERE version = ?1", params![QUEUE_SCHEMA_VERSION])?;
+                        tx.pragma_update(None, "user_version", QUEUE_SCHEMA_VERSION)?;
+                        tx.commit()?;
+                        None
+                    }
+                }
+            };
+
+            match batch {
+                Some((rows, batch_end)) => {
+                    last_rowid = batch_end;
+                    state.done += rows;
+                    state.total = state.total.max(state.done);
+                }
+                None => state.version = QUEUE_SCHEMA_VERSION,
+            }
+            if progress(&state).is_break() || state.is_complete() {
+                return Ok(state);
+            }
+        }
+    }
+
+    /// Migrate right away if the queue fits in one batch
+    pub(crate) fn migrate_small_schema(&mut self) -> Result<()> {
+        if self.schema_version()? >= QUEUE_SCHEMA_VERSION {
+            return Ok(());
+        }
+        let rows: usize = self.conn.query_row("SELECT COUNT(*) FROM message_queue", [], |row| row.get(0))?;
+        if rows <= MIGRATION_BATCH_ROWS {
+            self.migrate_schema(|_| ControlFlow::Continue(()))?;
+        }
+        Ok(())
+    }
+}
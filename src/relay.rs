@@ -0,0 +1,464 @@
+//! Relay fallback through a mutually trusted contact
+//!
+//! Two peers behind CGNAT cannot reach each other, but both may reach a third
+//! contact with a public address. A peer that enables "act as relay for my
+//! contacts" accepts `RelayEnvelope`s over `POST /relay` from one of its
+//! contacts addressed to another, keeps them in a small per-pair queue and
+//! forwards them on its next delivery pass (store-and-forward).
+//!
+//! - the envelope wraps the sender's `MessageRequest` unchanged, so the relay
+//!   sees routing metadata (sender and recipient UIDs, size) but the payload
+//!   stays end-to-end encrypted
+//! - relays advertise which contacts they reach by sending a
+//!   `relay_capabilities` message to each contact; reachable UIDs are
+//!   hashed, so a contact only learns whether the relay reaches a UID it
+//!   already knows. A contact that never received them asks over
+//!   `POST /capabilities` (see `storage::capability_probe`) and gets the
+//!   same capabilities in the answer
+//! - each sender/recipient pair is capped (`MAX_RELAY_PAYLOAD_BYTES`,
+//!   `MAX_QUEUED_PER_PAIR`, `MAX_RELAYED_PER_WINDOW` per `RELAY_WINDOW_SECS`)
+//!
+//! Requests carry no sender signature, so a relay trusts `from_uid` the same
+//! way the `/message` endpoint does; it only refuses UIDs it does not know.
+//! Capability probes are answered on the same terms.
+
+use crate::{
+    retry_schedule::RetryWakeup,
+    storage::{CapabilityRecord, Contact, OutboundPolicy},
+    transport::{MessageRequest, PeerTransport},
+    Error, Result,
+};
+use chrono::{DateTime, Duration, Utc};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// HTTP path of the relay endpoint
+pub const RELAY_PATH: &str = "/relay";
+
+/// Message type carrying `RelayCapabilities` between contacts
+pub const RELAY_CAPABILITIES_TYPE: &str = "relay_capabilities";
+
+/// HTTP path answering capability probes with `RelayCapabilities`
+pub const CAPABILITIES_PATH: &str = "/capabilities";
+
+/// Largest relayed payload, in bytes
+pub const MAX_RELAY_PAYLOAD_BYTES: usize = 64 * 1024;
+
+/// Envelopes a relay holds per sender/recipient pair
+pub const MAX_QUEUED_PER_PAIR: usize = 16;
+
+/// Envelopes a relay accepts per sender/recipient pair within `RELAY_WINDOW_SECS`
+pub const MAX_RELAYED_PER_WINDOW: usize = 30;
+
+/// Window over which relayed envelopes are counted, in seconds
+pub const RELAY_WINDOW_SECS: i64 = 60;
+
+/// A message handed to a relay for another contact
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RelayEnvelope {
+    /// UID of the sender (must match `request.from_uid`)
+    pub from_uid: String,
+    /// UID of the recipient
+    pub to_uid: String,
+    /// The request as it would have been sent directly
+    pub request: MessageRequest,
+}
+
+impl RelayEnvelope {
+    /// Wrap `request` for delivery to `to_uid`
+    pub fn new(to_uid: impl Into<String>, request: MessageRequest) -> Self {
+        Self {
+            from_uid: request.from_uid.clone(),
+            to_uid: to_uid.into(),
+            request,
+        }
+    }
+
+    /// Sender/recipient pair the envelope is queued and capped under
+    fn pair(&self) -> (String, String) {
+        (self.from_uid.clone(), self.to_uid.clone())
+    }
+}
+
+/// Body of a `POST /capabilities` probe
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CapabilityProbe {
+    /// UID of the asking contact (the answer is tailored to it)
+    pub from_uid: String,
+}
+
+/// What a contact offers as a relay
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RelayCapabilities {
+    /// Whether the contact accepts envelopes for its other contacts
+    pub accepts_relay: bool,
+    /// `relay_uid_hash` of every UID the contact can forward to
+    pub reachable: Vec<String>,
+    /// Whether the contact understands `edit` messages (absent from older peers)
+    #[serde(default)]
+    pub edits: bool,
+}
+
+impl RelayCapabilities {
+    /// Capabilities a relay advertises to `recipient_uid`
+    ///
+    /// Lists every other contact when `enabled`; nothing otherwise.
+    /// Contacts the `OutboundPolicy` keeps out of introductions (temporary
+    /// and restricted ones) are never listed nor offered relaying. Edit
+    /// support is advertised either way, except to a restricted recipient,
+    /// which learns nothing.
+    pub fn for_contact(enabled: bool, contacts: &[Contact], recipient_uid: &str) -> Self {
+        let policy = contacts.iter().find(|c| c.uid == recipient_uid).map(OutboundPolicy::for_contact);
+        if policy.is_some_and(|p| !p.advertises_capabilities()) {
+            return Self::default();
+        }
+        if !enabled || policy.is_some_and(|p| !p.allows_introductions()) {
+            return Self { edits: true, ..Self::default() };
+        }
+        Self {
+            edits: true,
+            accepts_relay: true,
+            reachable: contacts
+                .iter()
+                .filter(|c| {
+                    c.uid != recipient_uid && !c.is_expired() && OutboundPolicy::for_contact(c).allows_introductions()
+                })
+                .map(|c| relay_uid_hash(&c.uid))
+                .collect(),
+        }
+    }
+
+    /// Encode as a message payload
+    ///
+    /// # Errors
+    /// `Error::CborSerialization` if encoding fails
+    pub fn to_payload(&self) -> Result<Vec<u8>> {
+        serde_cbor::to_vec(self)
+            .map_err(|e| Error::CborSerialization(format!("Failed to serialize relay capabilities: {}", e)))
+    }
+
+    /// Decode from a message payload
+    ///
+    /// # Errors
+    /// `Error::CborSerialization` if the payload is not valid capabilities
+    pub fn from_payload(payload: &[u8]) -> Result<Self> {
+        serde_cbor::from_slice(payload)
+            .map_err(|e| Error::CborSerialization(format!("Failed to deserialize relay capabilities: {}", e)))
+    }
+}
+
+/// Hash under which relays advertise a reachable UID (hex SHA-256)
+pub fn relay_uid_hash(uid: &str) -> String {
+    hex::encode(digest(&SHA256, uid.as_bytes()))
+}
+
+/// Record capabilities a contact advertised (or sent in a probe answer) at `now`
+///
+/// # Returns
+/// Whether the sender is a known contact (and was updated)
+pub fn apply_capabilities(
+    contacts: &mut [Contact],
+    from_uid: &str,
+    capabilities: RelayCapabilities,
+    now: DateTime<Utc>,
+) -> bool {
+    let Some(contact) = contacts.iter_mut().find(|c| c.uid == from_uid) else {
+        return false;
+    };
+    contact.capabilities = Some(CapabilityRecord::Fetched { fetched_at: now });
+    contact.is_relay = capabilities.accepts_relay;
+    contact.supports_edits = capabilities.edits;
+    contact.relay_reachable = if capabilities.accepts_relay {
+        capabilities.reachable
+    } else {
+        Vec::new()
+    };
+    true
+}
+
+/// Record the answer to a capability probe of `uid` at `now`
+///
+/// `None` (the peer has no capabilities endpoint) marks the contact legacy
+/// and leaves what it advertised before untouched.
+///
+/// # Returns
+/// Whether `uid` is a known contact (and was updated)
+pub fn apply_probe_answer(
+    contacts: &mut [Contact],
+    uid: &str,
+    answer: Option<RelayCapabilities>,
+    now: DateTime<Utc>,
+) -> bool {
+    match answer {
+        Some(capabilities) => apply_capabilities(contacts, uid, capabilities, now),
+        None => {
+            let Some(contact) = contacts.iter_mut().find(|c| c.uid == uid) else {
+                return false;
+            };
+            contact.capabilities = Some(CapabilityRecord::Legacy { probed_at: now });
+            true
+        }
+    }
+}
+
+/// Contacts that advertised relaying and reach `recipient_uid`
+pub fn relay_candidates<'a>(contacts: &'a [Contact], recipient_uid: &str) -> Vec<&'a Contact> {
+    let hash = relay_uid_hash(recipient_uid);
+    contacts
+        .iter()
+        .filter(|c| c.uid != recipient_uid && c.reaches_via_relay(&hash))
+        .collect()
+}
+
+/// Hand `request` to the first relay that accepts it
+///
+/// # Returns
+/// UID of the relay that accepted the envelope
+///
+/// # Errors
+/// `Error::Relay` if no contact relays to `recipient_uid`, otherwise the last
+/// relay's error
+pub async fn send_via_relay(
+    transport: &dyn PeerTransport,
+    contacts: &[Contact],
+    recipient_uid: &str,
+    request: &MessageRequest,
+) -> Result<String> {
+    let envelope = RelayEnvelope::new(recipient_uid, request.clone());
+
+    let mut last_error = None;
+    for relay in relay_candidates(contacts, recipient_uid) {
+        match transport.relay_message(relay, &envelope).await {
+            Ok(()) => {
+                tracing::info!("Message for {} handed to relay {}", recipient_uid, relay.uid);
+                return Ok(relay.uid.clone());
+            }
+            Err(e) => {
+                tracing::warn!("Relay {} refused message for {}: {}", relay.uid, recipient_uid, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| Error::Relay(format!("No relay reaches {}", recipient_uid))))
+}
+
+/// Why a relay refused an envelope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayRefusal {
+    /// Relaying is turned off
+    Disabled,
+    /// Sender or recipient is not one of the relay's contacts
+    UnknownPeer,
+    /// `from_uid` does not match the wrapped request, or sender equals recipient
+    Malformed,
+    /// Payload larger than `MAX_RELAY_PAYLOAD_BYTES`
+    TooLarge,
+    /// `MAX_QUEUED_PER_PAIR` envelopes already waiting for this pair
+    QueueFull,
+    /// `MAX_RELAYED_PER_WINDOW` reached for this pair
+    RateLimited,
+}
+
+impl RelayRefusal {
+    /// HTTP status returned for this refusal
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Self::Disabled | Self::UnknownPeer => 403,
+            Self::Malformed => 400,
+            Self::TooLarge => 413,
+            Self::QueueFull | Self::RateLimited => 429,
+        }
+    }
+}
+
+impl fmt::Display for RelayRefusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disabled => write!(f, "relaying is disabled"),
+            Self::UnknownPeer => write!(f, "sender or recipient is not a contact of the relay"),
+            Self::Malformed => write!(f, "malformed relay envelope"),
+            Self::TooLarge => write!(f, "payload exceeds {} bytes", MAX_RELAY_PAYLOAD_BYTES),
+            Self::QueueFull => write!(f, "relay queue for this pair is full"),
+            Self::RateLimited => write!(f, "too many relayed messages for this pair"),
+        }
+    }
+}
+
+impl From<RelayRefusal> for Error {
+    fn from(refusal: RelayRefusal) -> Self {
+        Error::Relay(refusal.to_string())
+    }
+}
+
+/// Relay-side state: switch, known contacts and the store-and-forward queue
+#[derive(Debug, Clone, Default)]
+pub struct Relay {
+    enabled: bool,
+    /// Contacts by UID (snapshot, refreshed by the owner)
+    contacts: HashMap<String, Contact>,
+    /// Every contact, temporary and restricted ones included (capability probes)
+    everyone: Vec<Contact>,
+    /// Envelopes waiting to be forwarded, per sender/recipient pair
+    queues: HashMap<(String, String), VecDeque<RelayEnvelope>>,
+    /// Acceptance times within the current window, per pair
+    accepted: HashMap<(String, String), Vec<DateTime<Utc>>>,
+    /// Woken when an envelope is accepted, so it is forwarded without waiting
+    wakeup: Option<RetryWakeup>,
+}
+
+impl Relay {
+    /// Create a disabled relay with no contacts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether envelopes are accepted
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turn relaying on or off
+    ///
+    /// Envelopes already queued are still forwarded after disabling.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Wake `wakeup` (the retry worker) whenever an envelope is accepted
+    pub fn set_wakeup(&mut self, wakeup: RetryWakeup) {
+        self.wakeup = Some(wakeup);
+    }
+
+    /// Replace the contacts the relay accepts from and forwards to
+    ///
+    /// Temporary and restricted contacts are left out: nothing is relayed
+    /// for or to them (their capability probes are still answered).
+    pub fn set_contacts(&mut self, contacts: &[Contact]) {
+        self.everyone = contacts.to_vec();
+        self.contacts = contacts
+            .iter()
+            .filter(|c| OutboundPolicy::for_contact(c).allows_introductions())
+            .map(|c| (c.uid.clone(), c.clone()))
+            .collect();
+    }
+
+    /// Answer to a capability probe from `from_uid`: what we advertise to it
+    ///
+    /// None if `from_uid` is not a current contact.
+    pub fn capabilities_for(&self, from_uid: &str) -> Option<RelayCapabilities> {
+        self.everyone.iter().find(|c| c.uid == from_uid && !c.is_expired())?;
+        Some(RelayCapabilities::for_contact(self.enabled, &self.everyone, from_uid))
+    }
+
+    /// Envelopes waiting for `from_uid` → `to_uid`
+    pub fn queued(&self, from_uid: &str, to_uid: &str) -> usize {
+        self.queues
+            .get(&(from_uid.to_string(), to_uid.to_string()))
+            .map_or(0, VecDeque::len)
+    }
+
+    /// Envelopes waiting in total
+    pub fn total_queued(&self) -> usize {
+        self.queues.values().map(VecDeque::len).sum()
+    }
+
+    /// Check an envelope against the relay's rules and queue it
+    ///
+    /// # Errors
+    /// The `RelayRefusal` explaining why the envelope was not accepted
+    pub fn accept(&mut self, envelope: RelayEnvelope, now: DateTime<Utc>) -> std::result::Result<(), RelayRefusal> {
+        if !self.enabled {
+            return Err(RelayRefusal::Disabled);
+        }
+        if envelope.from_uid != envelope.request.from_uid || envelope.from_uid == envelope.to_uid {
+            return Err(RelayRefusal::Malformed);
+        }
+        let known = |uid: &str| self.contacts.get(uid).is_some_and(|c| !c.is_expired());
+        if !known(&envelope.from_uid) || !known(&envelope.to_uid) {
+            return Err(RelayRefusal::UnknownPeer);
+        }
+        if envelope.request.payload.len() > MAX_RELAY_PAYLOAD_BYTES {
+            return Err(RelayRefusal::TooLarge);
+        }
+
+        let pair = envelope.pair();
+        if self.queues.get(&pair).is_some_and(|q| q.len() >= MAX_QUEUED_PER_PAIR) {
+            return Err(RelayRefusal::QueueFull);
+        }
+        let window_start = now - Duration::seconds(RELAY_WINDOW_SECS);
+        let accepted = self.accepted.entry(pair.clone()).or_default();
+        accepted.retain(|at| *at > window_start);
+        if accepted.len() >= MAX_RELAYED_PER_WINDOW {
+            return Err(RelayRefusal::RateLimited);
+        }
+
+        accepted.push(now);
+        self.queues.entry(pair).or_default().push_back(envelope);
+        if let Some(wakeup) = &self.wakeup {
+            wakeup.notify();
+        }
+        Ok(())
+    }
+
+    /// Take every queued envelope with its recipient, oldest first per pair
+    ///
+    /// Envelopes whose recipient is no longer a contact are dropped.
+    pub(crate) fn take_queued(&mut self) -> Vec<(Contact, RelayEnvelope)> {
+        let mut batch = Vec::new();
+        for (pair, queue) in self.queues.drain() {
+            match self.contacts.get(&pair.1) {
+                Some(recipient) => batch.extend(queue.into_iter().map(|e| (recipient.clone(), e))),
+                None => tracing::warn!("Dropping {} relayed message(s) for unknown contact {}", queue.len(), pair.1),
+            }
+        }
+        batch
+    }
+
+    /// Put undelivered envelopes back in front of their pair's queue
+    fn requeue(&mut self, envelopes: Vec<RelayEnvelope>) {
+        for envelope in envelopes.into_iter().rev() {
+            self.queues.entry(envelope.pair()).or_default().push_front(envelope);
+        }
+    }
+}
+
+/// Forward everything a relay holds
+///
+/// Delivery stops for a pair at its first failure so order is kept; the rest
+/// of that pair stays queued for the next pass.
+///
+/// # Returns
+/// Number of envelopes delivered
+pub async fn forward_queued(relay: &std::sync::Mutex<Relay>, transport: &dyn PeerTransport) -> usize {
+    let batch = relay.lock().unwrap().take_queued();
+
+    let mut delivered = 0;
+    let mut blocked: Vec<(String, String)> = Vec::new();
+    let mut undelivered = Vec::new();
+    for (recipient, envelope) in batch {
+        let pair = envelope.pair();
+        if blocked.contains(&pair) {
+            undelivered.push(envelope);
+            continue;
+        }
+        match transport.send_message(&recipient, &envelope.request).await {
+            Ok(()) => {
+                tracing::info!("Relayed message from {} to {}", envelope.from_uid, envelope.to_uid);
+                delivered += 1;
+            }
+            Err(e) => {
+                tracing::warn!("Relaying to {} failed, keeping it queued: {}", envelope.to_uid, e);
+                blocked.push(pair);
+                undelivered.push(envelope);
+            }
+        }
+    }
+
+    if !undelivered.is_empty() {
+        relay.lock().unwrap().requeue(undelivered);
+    }
+    delivered
+}
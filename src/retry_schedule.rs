@@ -0,0 +1,211 @@
+//! When the retry worker runs its next cycle
+//!
+//! Instead of sleeping a fixed interval between cycles, the worker asks
+//! `plan_next_cycle` after each one:
+//!
+//! - `Backlog`: the last cycle processed a full batch (`RETRY_BATCH_SIZE`),
+//!   so more messages are probably ready and the next cycle starts at once
+//! - `Scheduled`: messages are waiting; wake at the earliest `next_retry`,
+//!   but no later than the configured retry interval. Deferred work alone
+//!   (dormant messages that must still expire, relayed envelopes waiting for
+//!   their recipient) also keeps the worker on the interval
+//! - `Idle`: nothing is queued; the worker parks until `RetryWakeup::notify`
+//!   (a message was queued, the settings changed, the network changed, or a
+//!   relayed envelope arrived)
+//!
+//! The plan takes the clock as an argument, so schedules can be checked
+//! against a virtual clock.
+//!
+//! `retry_opportunities` replays the same schedule forward for one message,
+//! backoff and quiet hours included, so the chat view can tell whether a
+//! contact's token runs out before the message would be attempted (see
+//! `tui::expiry_warning`).
+
+use chrono::{DateTime, Duration, Utc};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Messages the worker handles per cycle; a full batch means a backlog
+pub const RETRY_BATCH_SIZE: usize = 50;
+
+/// What the retry worker is doing between cycles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RetryMode {
+    /// Nothing queued: parked until woken
+    #[default]
+    Idle,
+    /// Waiting for the next retry time or the interval
+    Scheduled,
+    /// Draining a backlog: cycles run back to back
+    Backlog,
+}
+
+impl fmt::Display for RetryMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryMode::Idle => write!(f, "idle"),
+            RetryMode::Scheduled => write!(f, "scheduled"),
+            RetryMode::Backlog => write!(f, "backlog"),
+        }
+    }
+}
+
+/// The worker's decision after a cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RetryPlan {
+    /// Mode until the next cycle
+    pub mode: RetryMode,
+    /// When the next cycle starts (None: when woken)
+    pub next_cycle: Option<DateTime<Utc>>,
+}
+
+impl RetryPlan {
+    /// How long to wait from `now` (None: until woken)
+    pub fn wait_from(&self, now: DateTime<Utc>) -> Option<std::time::Duration> {
+        self.next_cycle.map(|at| (at - now).to_std().unwrap_or_default())
+    }
+
+    /// Diagnostics text, e.g. "scheduled, next in 4m 10s"
+    pub fn describe(&self, now: DateTime<Utc>) -> String {
+        match (self.mode, self.next_cycle) {
+            (RetryMode::Scheduled, Some(at)) => {
+                let secs = (at - now).num_seconds().max(0);
+                if secs >= 60 {
+                    format!("scheduled, next in {}m {}s", secs / 60, secs % 60)
+                } else {
+                    format!("scheduled, next in {}s", secs)
+                }
+            }
+            (mode, _) => mode.to_string(),
+        }
+    }
+}
+
+/// Plan the worker's next cycle
+///
+/// # Arguments
+/// * `now` - Current time
+/// * `interval` - Configured retry interval (the longest wait while messages are queued)
+/// * `earliest_next_retry` - Earliest `next_retry` among queued, non-dormant messages
+/// * `has_deferred` - Whether dormant messages or relayed envelopes are held
+/// * `processed` - Messages the last cycle handled
+pub fn plan_next_cycle(
+    now: DateTime<Utc>,
+    interval: Duration,
+    earliest_next_retry: Option<DateTime<Utc>>,
+    has_deferred: bool,
+    processed: usize,
+) -> RetryPlan {
+    let Some(earliest) = earliest_next_retry else {
+        if has_deferred {
+            return RetryPlan { mode: RetryMode::Scheduled, next_cycle: Some(now + interval) };
+        }
+        return RetryPlan { mode: RetryMode::Idle, next_cycle: None };
+    };
+    if processed >= RETRY_BATCH_SIZE {
+        return RetryPlan { mode: RetryMode::Backlog, next_cycle: Some(now) };
+    }
+    // A message that was ready but not taken (e.g. bulk lane paused in quiet
+    // hours) waits for the interval instead of spinning the worker
+    let next_cycle = if earliest > now { earliest.min(now + interval) } else { now + interval };
+    RetryPlan { mode: RetryMode::Scheduled, next_cycle: Some(next_cycle) }
+}
+
+/// Longest quiet-hours pause `retry_opportunities` follows before giving up
+const MAX_PAUSE_DAYS: i64 = 8;
+
+/// When the retry worker attempts a bulk-lane message queued at `queued_at`,
+/// if every attempt fails
+///
+/// Follows the worker and `MessageQueue::mark_failed_at`: the message is due
+/// at once, then `base_delay * 2^n` after its n-th failed attempt, and goes
+/// dormant after `max_retries` failures (attempted again only once the
+/// contact is heard from). A message due while the bulk lane is `paused`
+/// (quiet hours) waits a retry `interval` at a time, as in `plan_next_cycle`,
+/// until it is not.
+///
+/// # Returns
+/// Up to `max_retries` attempt times, in order; fewer if the lane stays
+/// paused for over a week
+pub fn retry_opportunities(
+    queued_at: DateTime<Utc>,
+    base_delay: Duration,
+    max_retries: u32,
+    interval: Duration,
+    paused: impl Fn(DateTime<Utc>) -> bool,
+) -> Vec<DateTime<Utc>> {
+    let step = interval.max(Duration::seconds(1));
+    let mut attempts = Vec::new();
+    let mut due = queued_at;
+    for failures in 1..=max_retries {
+        let mut at = due;
+        while paused(at) {
+            at += step;
+            if at - due > Duration::days(MAX_PAUSE_DAYS) {
+                return attempts;
+            }
+        }
+        attempts.push(at);
+        due = at + base_delay * 2_i32.pow(failures.min(30));
+    }
+    attempts
+}
+
+/// Wakes a parked or sleeping retry worker
+///
+/// A wake-up sent while the worker is busy is kept, so the next wait ends
+/// at once and nothing queued meanwhile is missed.
+#[derive(Debug, Clone, Default)]
+pub struct RetryWakeup {
+    notify: Arc<Notify>,
+}
+
+impl RetryWakeup {
+    /// Create a wake-up handle
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wake the worker (or make its next wait end at once)
+    pub fn notify(&self) {
+        self.notify.notify_one();
+    }
+
+    /// Wait for the next `notify`, at most `timeout` (None: indefinitely)
+    ///
+    /// # Returns
+    /// Whether a wake-up arrived (false when the timeout ran out)
+    pub async fn wait(&self, timeout: Option<std::time::Duration>) -> bool {
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.notify.notified()).await.is_ok(),
+            None => {
+                self.notify.notified().await;
+                true
+            }
+        }
+    }
+}
+
+/// The retry worker's current plan, shared with the UI
+#[derive(Debug, Clone, Default)]
+pub struct RetryStatus {
+    plan: Arc<Mutex<RetryPlan>>,
+}
+
+impl RetryStatus {
+    /// Create a status reporting `Idle`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current plan
+    pub fn plan(&self) -> RetryPlan {
+        *self.plan.lock().unwrap()
+    }
+
+    /// Record the worker's new plan
+    pub fn set(&self, plan: RetryPlan) {
+        *self.plan.lock().unwrap() = plan;
+    }
+}
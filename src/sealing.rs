@@ -0,0 +1,263 @@
+//! End-to-end sealing of chat messages and the X25519 key upgrade
+//!
+//! Text messages to a contact whose X25519 key is known are sealed with the
+//! static ECDH secret between our key and theirs (XChaCha20-Poly1305) and
+//! sent as `ENCRYPTED_TEXT_TYPE`. Contacts stored before the key was part of
+//! every token have none, so messages to them go out in plaintext and
+//! `SendSecurity` says why.
+//!
+//! To close that gap a `key_upgrade_request` asks the peer for its key.
+//! Upgraded peers answer automatically with a `key_upgrade_response`
+//! carrying a freshly signed contact token; once the token verifies against
+//! the Ed25519 key we already hold, its X25519 key fills the contact and
+//! sealing starts with the next message.
+//!
+//! - only `text` and `edit` requests are sealed (an edit carries new text);
+//!   metadata and control messages are not
+//! - a sealed payload carries the sender's X25519 key, so a receiver that
+//!   lacks it can still open the message (the carried key is never stored,
+//!   and one that contradicts a stored key is refused)
+//! - upgrade responses only fill a missing key, never replace one
+
+use crate::{
+    crypto::{decrypt_message, encrypt_message, EncryptedEnvelope, KeyPair},
+    storage::{parse_contact_token, Contact, Protection},
+    transport::MessageRequest,
+    Error, Result,
+};
+use serde::{Deserialize, Serialize};
+
+/// Message type of a sealed text message
+pub const ENCRYPTED_TEXT_TYPE: &str = "text_e2e";
+
+/// Message type of a sealed edit
+pub const ENCRYPTED_EDIT_TYPE: &str = "edit_e2e";
+
+/// Sealed message type for a plaintext one, if that type is sealed
+fn sealed_type(message_type: &str) -> Option<&'static str> {
+    match message_type {
+        "text" => Some(ENCRYPTED_TEXT_TYPE),
+        crate::edits::EDIT_TYPE => Some(ENCRYPTED_EDIT_TYPE),
+        _ => None,
+    }
+}
+
+/// Plaintext message type of a sealed one
+fn opened_type(message_type: &str) -> Option<&'static str> {
+    match message_type {
+        ENCRYPTED_TEXT_TYPE => Some("text"),
+        ENCRYPTED_EDIT_TYPE => Some(crate::edits::EDIT_TYPE),
+        _ => None,
+    }
+}
+
+/// Message type asking a contact for its X25519 key
+pub const KEY_UPGRADE_REQUEST_TYPE: &str = "key_upgrade_request";
+
+/// Message type answering a key upgrade request with a signed contact token
+pub const KEY_UPGRADE_RESPONSE_TYPE: &str = "key_upgrade_response";
+
+/// How an outgoing message is (or would be) protected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendSecurity {
+    /// Sealed with the contact's X25519 key
+    Encrypted,
+    /// Plaintext: the contact has no X25519 key
+    MissingKey,
+    /// Plaintext: the stored X25519 key is not 32 bytes
+    InvalidKey,
+    /// Plaintext: there is no local identity to derive a secret from
+    NoIdentity,
+    /// Control message, never sealed
+    Control,
+}
+
+impl SendSecurity {
+    /// Whether messages are sealed
+    pub fn is_encrypted(self) -> bool {
+        self == SendSecurity::Encrypted
+    }
+
+    /// Line shown in the chat view's security strip
+    pub fn strip_text(self) -> &'static str {
+        match self {
+            SendSecurity::Encrypted => "🔒 End-to-end encrypted",
+            SendSecurity::MissingKey => "⚠ Not encrypted: contact has no encryption key",
+            SendSecurity::InvalidKey => "⚠ Not encrypted: contact's encryption key is malformed",
+            SendSecurity::NoIdentity => "⚠ Not encrypted: no local identity",
+            SendSecurity::Control => "Control message (not encrypted)",
+        }
+    }
+
+    /// Protection recorded for a message sent this way (None for control messages)
+    pub fn protection(self) -> Option<Protection> {
+        match self {
+            SendSecurity::Encrypted => Some(Protection::Sealed),
+            SendSecurity::MissingKey | SendSecurity::InvalidKey | SendSecurity::NoIdentity => Some(Protection::Plaintext),
+            SendSecurity::Control => None,
+        }
+    }
+}
+
+/// Choose how text messages to `contact` are sent
+pub fn send_security(keypair: Option<&KeyPair>, contact: &Contact) -> SendSecurity {
+    if keypair.is_none() {
+        return SendSecurity::NoIdentity;
+    }
+    match (&contact.x25519_pubkey, contact.x25519_key()) {
+        (None, _) => SendSecurity::MissingKey,
+        (Some(_), None) => SendSecurity::InvalidKey,
+        (Some(_), Some(_)) => SendSecurity::Encrypted,
+    }
+}
+
+/// Wire payload of `ENCRYPTED_TEXT_TYPE`
+#[derive(Debug, Serialize, Deserialize)]
+struct SealedPayload {
+    /// Sender's X25519 public key
+    sender_x25519: Vec<u8>,
+    /// Encrypted text
+    envelope: EncryptedEnvelope,
+}
+
+/// Static ECDH secret between `keypair` and a peer's X25519 key
+fn shared_secret(keypair: &KeyPair, peer_key: &[u8]) -> Result<[u8; 32]> {
+    let peer_key: &[u8; 32] = peer_key
+        .try_into()
+        .map_err(|_| Error::Crypto(format!("Invalid X25519 key length: expected 32 bytes, got {}", peer_key.len())))?;
+    keypair.derive_shared_secret(peer_key)
+}
+
+/// Seal a `text` or `edit` request for `contact` when its key allows it
+///
+/// Other message types pass through unchanged (`SendSecurity::Control`), as
+/// do text and edit requests to contacts without a usable key.
+///
+/// # Errors
+/// Returns `Error::Crypto` or `Error::CborSerialization` if sealing fails
+pub fn seal_request(
+    request: MessageRequest,
+    keypair: Option<&KeyPair>,
+    contact: &Contact,
+) -> Result<(MessageRequest, SendSecurity)> {
+    let Some(sealed_type) = sealed_type(&request.message_type) else {
+        return Ok((request, SendSecurity::Control));
+    };
+    let (Some(keypair), Some(peer_key)) = (keypair, contact.x25519_key()) else {
+        return Ok((request, send_security(keypair, contact)));
+    };
+
+    let secret = shared_secret(keypair, &peer_key)?;
+    let sealed = SealedPayload {
+        sender_x25519: keypair.x25519_public.clone(),
+        envelope: encrypt_message(&secret, &request.payload)?,
+    };
+    let payload = serde_cbor::to_vec(&sealed)
+        .map_err(|e| Error::CborSerialization(format!("Failed to serialize sealed message: {}", e)))?;
+
+    Ok((
+        MessageRequest {
+            message_type: sealed_type.to_string(),
+            payload,
+            ..request
+        },
+        SendSecurity::Encrypted,
+    ))
+}
+
+/// Protection a request arrived with, judged before `open_request`
+pub fn arrival_protection(message_type: &str) -> Protection {
+    if opened_type(message_type).is_some() {
+        Protection::Sealed
+    } else {
+        Protection::Plaintext
+    }
+}
+
+/// Open a sealed request back into a `text` or `edit` request
+///
+/// Requests of other types are returned unchanged.
+///
+/// # Errors
+/// Returns `Error::Crypto` if the carried key contradicts the one stored for
+/// the sender or decryption fails, and `Error::CborSerialization` for a
+/// malformed payload
+pub fn open_request(request: MessageRequest, keypair: &KeyPair, contacts: &[Contact]) -> Result<MessageRequest> {
+    let Some(opened_type) = opened_type(&request.message_type) else {
+        return Ok(request);
+    };
+    let sealed: SealedPayload = serde_cbor::from_slice(&request.payload)
+        .map_err(|e| Error::CborSerialization(format!("Invalid sealed message: {}", e)))?;
+
+    let stored_key = contacts
+        .iter()
+        .find(|c| c.uid == request.from_uid)
+        .and_then(|c| c.x25519_pubkey.as_deref());
+    if stored_key.is_some_and(|key| key != sealed.sender_x25519.as_slice()) {
+        return Err(Error::Crypto(format!(
+            "Sealed message from {} uses a different X25519 key than the stored one",
+            request.from_uid
+        )));
+    }
+
+    let secret = shared_secret(keypair, &sealed.sender_x25519)?;
+    let payload = decrypt_message(&secret, &sealed.envelope)?;
+    Ok(MessageRequest {
+        message_type: opened_type.to_string(),
+        payload,
+        ..request
+    })
+}
+
+/// Request asking a contact for its X25519 key
+pub fn key_upgrade_request(my_uid: &str) -> MessageRequest {
+    MessageRequest {
+        from_uid: my_uid.to_string(),
+        message_type: KEY_UPGRADE_REQUEST_TYPE.to_string(),
+        payload: Vec::new(),
+        metadata: Default::default(),
+        message_id: None,
+    }
+}
+
+/// Answer to a key upgrade request: our signed contact token
+pub fn key_upgrade_response(my_uid: &str, my_token: &str) -> MessageRequest {
+    MessageRequest {
+        from_uid: my_uid.to_string(),
+        message_type: KEY_UPGRADE_RESPONSE_TYPE.to_string(),
+        payload: my_token.as_bytes().to_vec(),
+        metadata: Default::default(),
+        message_id: None,
+    }
+}
+
+/// Fill the sender's missing X25519 key from a key upgrade response
+///
+/// The token must verify, belong to `from_uid` and carry the Ed25519 key
+/// already stored for that contact.
+///
+/// # Returns
+/// Whether a key was filled (false if the sender is unknown or already has one)
+///
+/// # Errors
+/// Returns an error if the token is invalid, belongs to someone else or has
+/// no usable X25519 key
+pub fn apply_key_upgrade(contacts: &mut [Contact], from_uid: &str, payload: &[u8]) -> Result<bool> {
+    let token = std::str::from_utf8(payload)
+        .map_err(|_| Error::Crypto("Key upgrade response is not a contact token".to_string()))?;
+    let refreshed = parse_contact_token(token)?;
+    if refreshed.uid != from_uid {
+        return Err(Error::Crypto(format!("Key upgrade response from {} carries another contact's token", from_uid)));
+    }
+
+    let Some(contact) = contacts.iter_mut().find(|c| c.uid == from_uid) else {
+        return Ok(false);
+    };
+    if contact.pubkey != refreshed.pubkey {
+        return Err(Error::Crypto(format!("Key upgrade response from {} is signed with another key", from_uid)));
+    }
+    let key = refreshed
+        .x25519_pubkey
+        .ok_or_else(|| Error::Crypto(format!("Key upgrade response from {} has no X25519 key", from_uid)))?;
+    contact.fill_x25519_key(&key)
+}
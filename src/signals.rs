@@ -0,0 +1,128 @@
+//! Read receipts, typing indicators and presence announcements
+//!
+//! Signals are best effort: sent once over the contact's transport, never
+//! queued or retried, and consumed by the receiver instead of landing in the
+//! chat. Every send path asks `storage::signal_enabled` first; when the
+//! contact's override or the global setting says no, nothing at all is sent,
+//! so the peer cannot tell a disabled signal from one that never happened.
+
+use crate::{
+    storage::{signal_enabled, Contact, PrivacySignal, Settings},
+    transport::{MessageRequest, PeerTransport},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Message type of a read receipt
+pub const READ_RECEIPT_TYPE: &str = "read_receipt";
+
+/// Message type of a typing indicator
+pub const TYPING_TYPE: &str = "typing";
+
+/// Message type of a presence announcement
+pub const PRESENCE_TYPE: &str = "presence";
+
+/// Typing indicators to one contact are sent at most this often
+pub const TYPING_RESEND_SECS: u64 = 5;
+
+/// Payload of a read receipt: the chat was read up to this time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadReceipt {
+    /// When the chat was read (Unix milliseconds)
+    pub read_at: i64,
+}
+
+/// Payload of a presence announcement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Presence {
+    /// Whether the sender is online
+    pub online: bool,
+}
+
+/// Whether `message_type` is a signal (consumed, not stored in the chat)
+pub fn is_signal_type(message_type: &str) -> bool {
+    [READ_RECEIPT_TYPE, TYPING_TYPE, PRESENCE_TYPE].contains(&message_type)
+}
+
+/// Tell `contact` its messages were read at `read_at`
+///
+/// # Returns
+/// `Ok(true)` if sent, `Ok(false)` if read receipts are off for the contact
+///
+/// # Errors
+/// Returns an error if the payload cannot be encoded or the transport fails
+pub async fn send_read_receipt(
+    transport: &dyn PeerTransport,
+    settings: &Settings,
+    contact: &Contact,
+    local_uid: &str,
+    read_at: DateTime<Utc>,
+) -> Result<bool> {
+    if !signal_enabled(settings, contact, PrivacySignal::ReadReceipts) {
+        return Ok(false);
+    }
+    let payload = serde_cbor::to_vec(&ReadReceipt { read_at: read_at.timestamp_millis() })
+        .map_err(|e| Error::CborSerialization(format!("Failed to serialize read receipt: {}", e)))?;
+    emit(transport, contact, local_uid, READ_RECEIPT_TYPE, payload).await
+}
+
+/// Tell `contact` we are typing a message to it
+///
+/// # Returns
+/// `Ok(true)` if sent, `Ok(false)` if typing indicators are off for the contact
+///
+/// # Errors
+/// Returns an error if the transport fails
+pub async fn send_typing(
+    transport: &dyn PeerTransport,
+    settings: &Settings,
+    contact: &Contact,
+    local_uid: &str,
+) -> Result<bool> {
+    if !signal_enabled(settings, contact, PrivacySignal::Typing) {
+        return Ok(false);
+    }
+    emit(transport, contact, local_uid, TYPING_TYPE, Vec::new()).await
+}
+
+/// Tell `contact` whether we are online
+///
+/// # Returns
+/// `Ok(true)` if sent, `Ok(false)` if presence is off for the contact
+///
+/// # Errors
+/// Returns an error if the payload cannot be encoded or the transport fails
+pub async fn send_presence(
+    transport: &dyn PeerTransport,
+    settings: &Settings,
+    contact: &Contact,
+    local_uid: &str,
+    online: bool,
+) -> Result<bool> {
+    if !signal_enabled(settings, contact, PrivacySignal::Presence) {
+        return Ok(false);
+    }
+    let payload = serde_cbor::to_vec(&Presence { online })
+        .map_err(|e| Error::CborSerialization(format!("Failed to serialize presence: {}", e)))?;
+    emit(transport, contact, local_uid, PRESENCE_TYPE, payload).await
+}
+
+/// Send a signal request (no queueing, no retries)
+async fn emit(
+    transport: &dyn PeerTransport,
+    contact: &Contact,
+    local_uid: &str,
+    message_type: &str,
+    payload: Vec<u8>,
+) -> Result<bool> {
+    let request = MessageRequest {
+        from_uid: local_uid.to_string(),
+        message_type: message_type.to_string(),
+        payload,
+        metadata: Default::default(),
+        message_id: None,
+    };
+    transport.send_message(contact, &request).await?;
+    Ok(true)
+}
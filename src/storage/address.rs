@@ -0,0 +1,162 @@
+//! Typed, validated `host:port` addresses of contacts
+//!
+//! Contact addresses come from signed tokens, but the signer chooses what
+//! they hold. Before anything is dialled, `PeerAddress::parse` checks that
+//! an HTTP(S) address is only an IP literal or DNS hostname and a port, with
+//! no path, query, fragment or user info, and URLs are built from the parsed
+//! parts (`PeerAddress::uri`) instead of by string interpolation.
+//! `validate_contact_addresses` applies the same check to every address of
+//! an imported contact.
+
+use super::contact::{split_address_scheme, Contact, DEFAULT_ADDRESS_SCHEME, TLS_ADDRESS_SCHEME};
+use crate::{Error, Result};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Status shown when a contact's address is rejected
+pub const INVALID_ADDRESS_STATUS: &str = "contact has an invalid address";
+
+/// Longest DNS hostname (RFC 1035)
+const MAX_HOSTNAME_LEN: usize = 253;
+
+/// Longest DNS label (RFC 1035)
+const MAX_LABEL_LEN: usize = 63;
+
+/// Host part of a peer address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerHost {
+    /// IPv4 literal
+    Ipv4(Ipv4Addr),
+    /// IPv6 literal (written in brackets)
+    Ipv6(Ipv6Addr),
+    /// DNS hostname (RFC 1123 letters, digits and hyphens)
+    Domain(String),
+}
+
+/// A contact address that is safe to dial: host and port only
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerAddress {
+    /// Host to connect to
+    pub host: PeerHost,
+    /// TCP port (never 0)
+    pub port: u16,
+}
+
+impl PeerAddress {
+    /// Parse `host:port` (the address with any `scheme://` already removed)
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidAddress` naming what is wrong
+    ///
+    /// # Example
+    /// ```
+    /// use pure2p::storage::{PeerAddress, PeerHost};
+    ///
+    /// let address = PeerAddress::parse("[::1]:8080").unwrap();
+    /// assert_eq!(address.host, PeerHost::Ipv6("::1".parse().unwrap()));
+    /// assert!(PeerAddress::parse("evil.com/steal?x=").is_err());
+    /// ```
+    pub fn parse(address: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidAddress(format!("{:?} {}", address, reason));
+
+        if address.is_empty() {
+            return Err(invalid("is empty"));
+        }
+        if !address.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(invalid("contains whitespace, control or non-ASCII characters"));
+        }
+        if address.contains('@') {
+            return Err(invalid("has user info"));
+        }
+        if address.contains(['/', '\\', '?', '#']) {
+            return Err(invalid("has a path, query or fragment"));
+        }
+
+        let (host, port) = if let Some(rest) = address.strip_prefix('[') {
+            let (literal, after) = rest.split_once(']').ok_or_else(|| invalid("has an unclosed '['"))?;
+            let ip: Ipv6Addr = literal.parse().map_err(|_| invalid("has an invalid IPv6 literal"))?;
+            let port = after.strip_prefix(':').ok_or_else(|| invalid("has no port"))?;
+            (PeerHost::Ipv6(ip), port)
+        } else {
+            let (host, port) = address.rsplit_once(':').ok_or_else(|| invalid("has no port"))?;
+            if host.contains(':') {
+                return Err(invalid("needs brackets around an IPv6 literal"));
+            }
+            (parse_host(host).ok_or_else(|| invalid("has a host that is neither an IP address nor a hostname"))?, port)
+        };
+
+        // `u16::from_str` would also take a leading '+'
+        let port = match port.parse::<u16>() {
+            Ok(number) if number != 0 && port.bytes().all(|b| b.is_ascii_digit()) => number,
+            _ => return Err(invalid("has a port outside 1-65535")),
+        };
+        Ok(Self { host, port })
+    }
+
+    /// `host:port` as dialled and sent in the Host header
+    pub fn authority(&self) -> String {
+        match &self.host {
+            PeerHost::Ipv4(ip) => format!("{}:{}", ip, self.port),
+            PeerHost::Ipv6(ip) => format!("[{}]:{}", ip, self.port),
+            PeerHost::Domain(name) => format!("{}:{}", name, self.port),
+        }
+    }
+
+    /// URL of `path` (e.g. "/message") at this address over `scheme`
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidAddress` for a scheme other than http/https or
+    /// a path that is not absolute
+    pub fn uri(&self, scheme: &str, path: &str) -> Result<hyper::Uri> {
+        if scheme != DEFAULT_ADDRESS_SCHEME && scheme != TLS_ADDRESS_SCHEME {
+            return Err(Error::InvalidAddress(format!("no URL for {:?} addresses", scheme)));
+        }
+        if !path.starts_with('/') {
+            return Err(Error::InvalidAddress(format!("path {:?} is not absolute", path)));
+        }
+        hyper::Uri::builder()
+            .scheme(scheme)
+            .authority(self.authority())
+            .path_and_query(path)
+            .build()
+            .map_err(|e| Error::InvalidAddress(format!("{}: {}", self.authority(), e)))
+    }
+}
+
+impl std::fmt::Display for PeerAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.authority())
+    }
+}
+
+/// IPv4 literal or RFC 1123 hostname
+fn parse_host(host: &str) -> Option<PeerHost> {
+    if let Ok(ip) = host.parse::<Ipv4Addr>() {
+        return Some(PeerHost::Ipv4(ip));
+    }
+    let labels_ok = host.split('.').all(|label| {
+        (1..=MAX_LABEL_LEN).contains(&label.len())
+            && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    });
+    // An all-numeric last label is a malformed IPv4 address, not a name
+    let numeric_tld = host.rsplit('.').next().is_some_and(|tld| tld.bytes().all(|b| b.is_ascii_digit()));
+    (host.len() <= MAX_HOSTNAME_LEN && labels_ok && !numeric_tld).then(|| PeerHost::Domain(host.to_string()))
+}
+
+/// Check the primary and every advertised HTTP(S) address of `contact`
+///
+/// Addresses of other schemes (loopback, onion) are left to their transports.
+///
+/// # Errors
+/// Returns `Error::InvalidAddress` for the first address that does not parse
+pub fn validate_contact_addresses(contact: &Contact) -> Result<()> {
+    let addresses = std::iter::once(contact.ip.as_str()).chain(contact.endpoints.iter().map(|e| e.address.as_str()));
+    for address in addresses {
+        let (scheme, rest) = split_address_scheme(address);
+        if scheme == DEFAULT_ADDRESS_SCHEME || scheme == TLS_ADDRESS_SCHEME {
+            PeerAddress::parse(rest)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Address changes staged for review (paranoid mode)
+//!
+//! Imported tokens and incoming pings rewrite a known contact's address
+//! automatically. With `Settings::address_review_enabled`, a change for a
+//! contact the user marked verified is staged instead: the contact details
+//! show it next to the current address until the user applies or ignores
+//! it. A staged change also applies itself once the current address has
+//! failed `Settings::address_auto_apply_failures` deliveries in a row, so a
+//! contact that really moved is not lost. Unverified contacts keep the
+//! automatic behaviour.
+
+use crate::storage::{Contact, ContactEndpoint};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Mechanism that reported a new address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressSource {
+    /// A contact token imported by the user
+    ImportedToken,
+    /// The token carried by an incoming ping
+    PingToken,
+}
+
+impl AddressSource {
+    /// Short description for the contact details
+    pub fn describe(self) -> &'static str {
+        match self {
+            AddressSource::ImportedToken => "imported token",
+            AddressSource::PingToken => "ping",
+        }
+    }
+}
+
+/// A new address claimed for a verified contact, waiting for review
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressChange {
+    /// Contact the change is for
+    pub uid: String,
+    /// Address in use when the change was reported
+    pub previous_ip: String,
+    /// Claimed address
+    pub ip: String,
+    /// Claimed endpoints, in preference order
+    #[serde(default)]
+    pub endpoints: Vec<ContactEndpoint>,
+    /// Which mechanism reported it
+    pub source: AddressSource,
+    /// Whether the claim came in a token whose signature verified
+    pub signature_verified: bool,
+    /// When it was reported
+    pub reported_at: DateTime<Utc>,
+    /// Failed deliveries to the current address in a row since it was reported
+    #[serde(default)]
+    pub failed_deliveries: u32,
+}
+
+impl AddressChange {
+    /// Stage the address and endpoints of `claimed` for `existing`
+    pub fn new(existing: &Contact, claimed: &Contact, source: AddressSource, reported_at: DateTime<Utc>) -> Self {
+        Self {
+            uid: existing.uid.clone(),
+            previous_ip: existing.ip.clone(),
+            ip: claimed.ip.clone(),
+            endpoints: claimed.endpoints.clone(),
+            source,
+            // Tokens are only accepted once their signature verifies
+            signature_verified: true,
+            reported_at,
+            failed_deliveries: 0,
+        }
+    }
+
+    /// Whether `claimed` asks for the same address as this change
+    pub fn same_address(&self, claimed: &Contact) -> bool {
+        self.ip == claimed.ip && self.endpoints == claimed.endpoints
+    }
+
+    /// Write the claimed address into `contact`
+    pub fn apply_to(&self, contact: &mut Contact) {
+        contact.ip = self.ip.clone();
+        contact.endpoints = self.endpoints.clone();
+    }
+}
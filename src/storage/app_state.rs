@@ -2,12 +2,45 @@
 
 use crate::{
     crypto::KeyPair,
-    storage::{chat::Chat, contact::Contact, settings::Settings, storage_db::Storage},
+    storage::{
+        address_change::{AddressChange, AddressSource},
+        bounds::clamp_token_expiry,
+        chat::Chat,
+        contact::Contact,
+        ephemeral::{ephemeral_warning_text, EphemeralSweep},
+        identity::{check_incoming_contact, ConflictKind, IdentityCheck, IdentityConflict},
+        message::Message,
+        migration,
+        settings::Settings,
+        storage_db::{IntegrityReport, Storage},
+        uid_index::UidIndex,
+    },
     Error, Result,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
+/// Name of the one-time startup identity scan in the `integrity_checks` table
+pub const IDENTITY_SCAN_CHECK: &str = "identity_scan_v1";
+
+/// Outcome of `AppState::ingest_contact`
+#[derive(Debug, Clone)]
+pub enum ContactIngest {
+    /// Stored as a new contact
+    Added,
+    /// Known contact whose address or endpoints were refreshed
+    AddressUpdated,
+    /// Known verified contact whose new address was staged for review
+    AddressStaged,
+    /// Known contact at the same address whose token now expires later
+    Renewed,
+    /// Known contact, nothing changed
+    Unchanged,
+    /// Held back for review; nothing was stored as a contact
+    Conflict(Box<IdentityConflict>),
+}
+
 /// Persistent application state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppState {
@@ -25,6 +58,18 @@ pub struct AppState {
     pub message_queue: Vec<String>, // Message IDs in queue
     /// Application settings
     pub settings: Settings,
+    /// Contacts held back because their identity conflicts with a stored one
+    #[serde(default)]
+    pub identity_conflicts: Vec<IdentityConflict>,
+    /// Address changes of verified contacts waiting for review
+    #[serde(default)]
+    pub address_changes: Vec<AddressChange>,
+    /// Position of each contact by UID (see `contact_by_uid`)
+    #[serde(skip)]
+    contact_index: UidIndex,
+    /// Position of each chat by contact UID (see `chat_by_uid`)
+    #[serde(skip)]
+    chat_index: UidIndex,
 }
 
 impl AppState {
@@ -50,6 +95,10 @@ impl AppState {
             chats: Vec::new(),
             message_queue: Vec::new(),
             settings: Settings::default(),
+            identity_conflicts: Vec::new(),
+            address_changes: Vec::new(),
+            contact_index: UidIndex::default(),
+            chat_index: UidIndex::default(),
         }
     }
 
@@ -89,7 +138,9 @@ impl AppState {
         let json = std::fs::read_to_string(path_ref)
             .map_err(|e| Error::Storage(format!("Failed to read state file: {}", e)))?;
 
-        let state: AppState = serde_json::from_str(&json)?;
+        let mut state: AppState = serde_json::from_str(&json)?;
+        state.settings.bound_limits();
+        state.reindex();
         Ok(state)
     }
 
@@ -121,8 +172,10 @@ impl AppState {
         let cbor = std::fs::read(path_ref)
             .map_err(|e| Error::Storage(format!("Failed to read state file: {}", e)))?;
 
-        let state: AppState = serde_cbor::from_slice(&cbor)
+        let mut state: AppState = serde_cbor::from_slice(&cbor)
             .map_err(|e| Error::CborSerialization(format!("Failed to deserialize state: {}", e)))?;
+        state.settings.bound_limits();
+        state.reindex();
         Ok(state)
     }
 
@@ -145,17 +198,63 @@ impl AppState {
     ///
     /// state.sync_pending_status(&pending_uids);
     /// ```
-    pub fn sync_pending_status(&mut self, pending_uids: &std::collections::HashSet<String>) {
+    ///
+    /// # Returns
+    /// Whether any chat's flag changed
+    pub fn sync_pending_status(&mut self, pending_uids: &std::collections::HashSet<String>) -> bool {
+        let mut changed = false;
         for chat in &mut self.chats {
-            if pending_uids.contains(&chat.contact_uid) {
+            let pending = pending_uids.contains(&chat.contact_uid);
+            changed |= chat.has_pending_messages != pending;
+            if pending {
                 chat.mark_has_pending();
             } else {
                 chat.mark_no_pending();
             }
         }
+        changed
     }
 
-    /// Get a mutable reference to a chat by contact UID
+    /// Rebuild the UID indexes of `contacts` and `chats`
+    ///
+    /// Needed only after editing either list directly; the methods of
+    /// `AppState` keep the indexes current.
+    pub fn reindex(&mut self) {
+        self.contact_index = UidIndex::build(&self.contacts, |c| &c.uid);
+        self.chat_index = UidIndex::build(&self.chats, |c| &c.contact_uid);
+    }
+
+    /// Position of a contact, rebuilding the index if the list was edited directly
+    fn contact_position(&mut self, uid: &str) -> Option<usize> {
+        self.contact_index.find_or_rebuild(&self.contacts, uid, |c| &c.uid)
+    }
+
+    /// Position of a chat, rebuilding the index if the list was edited directly
+    fn chat_position(&mut self, contact_uid: &str) -> Option<usize> {
+        self.chat_index.find_or_rebuild(&self.chats, contact_uid, |c| &c.contact_uid)
+    }
+
+    /// Contact with `uid`, if known
+    pub fn contact_by_uid(&self, uid: &str) -> Option<&Contact> {
+        self.contact_index.find(&self.contacts, uid, |c| &c.uid).map(|i| &self.contacts[i])
+    }
+
+    /// Mutable contact with `uid`, if known
+    pub fn contact_by_uid_mut(&mut self, uid: &str) -> Option<&mut Contact> {
+        self.contact_position(uid).map(|i| &mut self.contacts[i])
+    }
+
+    /// Chat with the contact `contact_uid`, if any
+    pub fn chat_by_uid(&self, contact_uid: &str) -> Option<&Chat> {
+        self.chat_index.find(&self.chats, contact_uid, |c| &c.contact_uid).map(|i| &self.chats[i])
+    }
+
+    /// Mutable chat with the contact `contact_uid`, if any
+    pub fn chat_by_uid_mut(&mut self, contact_uid: &str) -> Option<&mut Chat> {
+        self.chat_position(contact_uid).map(|i| &mut self.chats[i])
+    }
+
+    /// Get a mutable reference to a chat by contact UID (same as `chat_by_uid_mut`)
     ///
     /// # Arguments
     /// * `contact_uid` - The UID of the contact
@@ -163,10 +262,10 @@ impl AppState {
     /// # Returns
     /// A mutable reference to the chat if found, None otherwise
     pub fn get_chat_mut(&mut self, contact_uid: &str) -> Option<&mut Chat> {
-        self.chats.iter_mut().find(|c| c.contact_uid == contact_uid)
+        self.chat_by_uid_mut(contact_uid)
     }
 
-    /// Get a reference to a chat by contact UID
+    /// Get a reference to a chat by contact UID (same as `chat_by_uid`)
     ///
     /// # Arguments
     /// * `contact_uid` - The UID of the contact
@@ -174,7 +273,105 @@ impl AppState {
     /// # Returns
     /// A reference to the chat if found, None otherwise
     pub fn get_chat(&self, contact_uid: &str) -> Option<&Chat> {
-        self.chats.iter().find(|c| c.contact_uid == contact_uid)
+        self.chat_by_uid(contact_uid)
+    }
+
+    /// Add a contact without identity checks (see `ingest_contact` for those)
+    pub fn add_contact(&mut self, contact: Contact) {
+        self.contact_index.rebuild_if_resized(&self.contacts, |c| &c.uid);
+        self.contact_index.insert(&contact.uid, self.contacts.len());
+        self.contacts.push(contact);
+    }
+
+    /// Remove the contact with `uid` (its chat stays; see `remove_chat`)
+    ///
+    /// # Returns
+    /// The removed contact, if there was one
+    pub fn remove_contact(&mut self, uid: &str) -> Option<Contact> {
+        let position = self.contact_position(uid)?;
+        let contact = self.contacts.remove(position);
+        self.contact_index = UidIndex::build(&self.contacts, |c| &c.uid);
+        Some(contact)
+    }
+
+    /// Remove the chat with the contact `contact_uid`
+    ///
+    /// # Returns
+    /// The removed chat, if there was one
+    pub fn remove_chat(&mut self, contact_uid: &str) -> Option<Chat> {
+        let position = self.chat_position(contact_uid)?;
+        let chat = self.chats.remove(position);
+        self.chat_index = UidIndex::build(&self.chats, |c| &c.contact_uid);
+        Some(chat)
+    }
+
+    /// Move archived chats after the others, keeping the order within each group
+    pub fn sort_archived_last(&mut self) {
+        self.chats.sort_by_key(|chat| chat.archived);
+        self.chat_index = UidIndex::build(&self.chats, |c| &c.contact_uid);
+    }
+
+    /// Turn the temporary contact `uid` into a permanent one
+    ///
+    /// # Returns
+    /// Whether the contact was temporary
+    pub fn make_permanent(&mut self, uid: &str) -> bool {
+        self.contact_by_uid_mut(uid).is_some_and(|contact| contact.ephemeral.take().is_some())
+    }
+
+    /// Warn or remove temporary contacts whose lifetime is ending at `now`
+    ///
+    /// A chat gets one system message `EPHEMERAL_WARNING_HOURS` before its
+    /// contact ends; an ended contact is removed with its chat and staged
+    /// address change. Deleting their rows and queued messages is left to
+    /// the caller, which owns the database and the queue.
+    pub fn sweep_ephemeral(&mut self, now: DateTime<Utc>) -> EphemeralSweep {
+        let mut sweep = EphemeralSweep::default();
+        for contact in &self.contacts {
+            let Some(ephemeral) = contact.ephemeral else {
+                continue;
+            };
+            if ephemeral.is_expired_at(now) {
+                sweep.expired.push(contact.uid.clone());
+            } else if ephemeral.warning_due_at(now) {
+                sweep.warned.push(contact.uid.clone());
+            }
+        }
+
+        for uid in &sweep.warned {
+            let Some(until) = self.contact_by_uid_mut(uid).and_then(|c| c.ephemeral.as_mut()).map(|ephemeral| {
+                ephemeral.warned = true;
+                ephemeral.until
+            }) else {
+                continue;
+            };
+            self.get_or_create_chat(uid)
+                .append_message(Message::system(&ephemeral_warning_text(until), now.timestamp_millis()));
+        }
+        for uid in &sweep.expired {
+            self.remove_contact(uid);
+            self.remove_chat(uid);
+            self.ignore_address_change(uid);
+        }
+        sweep
+    }
+
+    /// Starred messages across all chats, newest first
+    ///
+    /// # Returns
+    /// Pairs of contact UID and message
+    pub fn starred_messages(&self) -> Vec<(&str, &Message)> {
+        let mut starred: Vec<(&str, &Message)> = self
+            .chats
+            .iter()
+            .flat_map(|chat| {
+                chat.starred_messages()
+                    .into_iter()
+                    .map(move |m| (chat.contact_uid.as_str(), m))
+            })
+            .collect();
+        starred.sort_by_key(|(_, m)| std::cmp::Reverse(m.timestamp));
+        starred
     }
 
     /// Add a new chat for a contact
@@ -185,8 +382,9 @@ impl AppState {
     /// # Returns
     /// A mutable reference to the newly created chat
     pub fn add_chat(&mut self, contact_uid: String) -> &mut Chat {
-        let chat = Chat::new(contact_uid);
-        self.chats.push(chat);
+        self.chat_index.rebuild_if_resized(&self.chats, |c| &c.contact_uid);
+        self.chat_index.insert(&contact_uid, self.chats.len());
+        self.chats.push(Chat::new(contact_uid));
         self.chats.last_mut().unwrap()
     }
 
@@ -198,42 +396,263 @@ impl AppState {
     /// # Returns
     /// A mutable reference to the chat (existing or newly created)
     pub fn get_or_create_chat(&mut self, contact_uid: &str) -> &mut Chat {
-        if self.get_chat(contact_uid).is_none() {
-            self.add_chat(contact_uid.to_string());
+        match self.chat_position(contact_uid) {
+            Some(i) => &mut self.chats[i],
+            None => self.add_chat(contact_uid.to_string()),
         }
-        self.get_chat_mut(contact_uid).unwrap()
     }
 
-    // ========== SQLite-based storage methods ==========
+    /// Add or refresh a contact received from a token or ping
+    ///
+    /// The contact's UID must derive from its key. A contact matching a stored
+    /// one by UID and key only refreshes its address, endpoints and expiry
+    /// (notes stay local). A contact that reuses a stored key under another
+    /// UID, or a stored UID with another key, is recorded in
+    /// `identity_conflicts` instead of being stored.
+    ///
+    /// # Errors
+    /// `Error::IdentityMismatch` if the contact's UID does not match its key
+    pub fn ingest_contact(&mut self, contact: Contact) -> Result<ContactIngest> {
+        self.ingest_contact_from(contact, AddressSource::ImportedToken, Utc::now())
+    }
 
-    /// Save the entire application state to SQLite database
+    /// Add or refresh a contact, recording where a new address came from
+    ///
+    /// The token's expiry is first clamped to `Settings::max_token_expiry_days`
+    /// (see `storage::bounds`). Like `ingest_contact`, except that with `Settings::address_review_enabled`
+    /// a new address for a verified contact is staged in `address_changes`
+    /// (replacing an older claim) instead of being applied. The expiry is
+    /// still extended.
+    ///
+    /// # Errors
+    /// `Error::IdentityMismatch` if the contact's UID does not match its key
+    pub fn ingest_contact_from(
+        &mut self,
+        mut contact: Contact,
+        source: AddressSource,
+        now: DateTime<Utc>,
+    ) -> Result<ContactIngest> {
+        clamp_token_expiry(&mut contact, now, self.settings.max_token_expiry_days);
+        match check_incoming_contact(&self.contacts, &contact)? {
+            IdentityCheck::New => {
+                self.add_contact(contact);
+                Ok(ContactIngest::Added)
+            }
+            IdentityCheck::Known(index) => {
+                let existing = &mut self.contacts[index];
+                // A renewed certificate is signed by the same identity key:
+                // taken over without review
+                let rotated = contact.cert_fingerprint.is_some() && existing.cert_fingerprint != contact.cert_fingerprint;
+                if rotated {
+                    existing.cert_fingerprint = contact.cert_fingerprint.clone();
+                }
+                if existing.ip == contact.ip && existing.endpoints == contact.endpoints {
+                    // A renewed token for the same address extends the expiry
+                    let renewed = contact.expiry > existing.expiry;
+                    existing.expiry = existing.expiry.max(contact.expiry);
+                    return Ok(if rotated {
+                        ContactIngest::AddressUpdated
+                    } else if renewed {
+                        ContactIngest::Renewed
+                    } else {
+                        ContactIngest::Unchanged
+                    });
+                }
+                if self.settings.address_review_enabled && existing.verified {
+                    existing.expiry = existing.expiry.max(contact.expiry);
+                    let staged = self.address_changes.iter().position(|c| c.uid == contact.uid);
+                    if staged.is_some_and(|i| self.address_changes[i].same_address(&contact)) {
+                        return Ok(ContactIngest::Unchanged);
+                    }
+                    let change = AddressChange::new(existing, &contact, source, now);
+                    match staged {
+                        Some(i) => self.address_changes[i] = change,
+                        None => self.address_changes.push(change),
+                    }
+                    return Ok(ContactIngest::AddressStaged);
+                }
+                existing.ip = contact.ip;
+                existing.endpoints = contact.endpoints;
+                existing.cert_fingerprint = contact.cert_fingerprint;
+                existing.expiry = existing.expiry.max(contact.expiry);
+                if contact.requested_expiry.is_some() {
+                    existing.requested_expiry = contact.requested_expiry;
+                }
+                Ok(ContactIngest::AddressUpdated)
+            }
+            IdentityCheck::Conflict(conflict) => {
+                self.record_identity_conflict((*conflict).clone());
+                Ok(ContactIngest::Conflict(conflict))
+            }
+        }
+    }
+
+    /// Address change staged for `uid`, if any
+    pub fn address_change(&self, uid: &str) -> Option<&AddressChange> {
+        self.address_changes.iter().find(|c| c.uid == uid)
+    }
+
+    /// Apply the address change staged for `uid`
+    ///
+    /// # Returns
+    /// Whether a change was staged (it is removed even if the contact is gone)
+    pub fn apply_address_change(&mut self, uid: &str) -> bool {
+        let Some(index) = self.address_changes.iter().position(|c| c.uid == uid) else {
+            return false;
+        };
+        let change = self.address_changes.remove(index);
+        if let Some(contact) = self.contact_by_uid_mut(uid) {
+            change.apply_to(contact);
+        }
+        true
+    }
+
+    /// Discard the address change staged for `uid`
+    ///
+    /// # Returns
+    /// Whether a change was staged
+    pub fn ignore_address_change(&mut self, uid: &str) -> bool {
+        let before = self.address_changes.len();
+        self.address_changes.retain(|c| c.uid != uid);
+        self.address_changes.len() != before
+    }
+
+    /// Count a delivery attempt to `uid` against its staged address change
+    ///
+    /// A delivery resets the count of failures in a row; a failure adds one,
+    /// and reaching `Settings::address_auto_apply_failures` (unless 0)
+    /// applies the change.
+    ///
+    /// # Returns
+    /// The change, if this failure applied it
+    pub fn record_delivery_outcome(&mut self, uid: &str, delivered: bool) -> Option<AddressChange> {
+        let threshold = self.settings.address_auto_apply_failures;
+        let change = self.address_changes.iter_mut().find(|c| c.uid == uid)?;
+        if delivered {
+            change.failed_deliveries = 0;
+            return None;
+        }
+        change.failed_deliveries += 1;
+        if threshold == 0 || change.failed_deliveries < threshold {
+            return None;
+        }
+
+        let change = change.clone();
+        self.apply_address_change(uid);
+        Some(change)
+    }
+
+    /// Add a conflict for review unless the same one is already recorded
+    ///
+    /// # Returns
+    /// Whether it was added
+    pub fn record_identity_conflict(&mut self, conflict: IdentityConflict) -> bool {
+        if self.identity_conflicts.iter().any(|c| c.same_as(&conflict)) {
+            return false;
+        }
+        self.identity_conflicts.push(conflict);
+        true
+    }
+
+    /// Run the one-time identity scan on a database written before
+    /// identities were checked
+    ///
+    /// Every conflict found is recorded for review. Contacts sharing a key
+    /// with their canonical owner (see `identity::canonical_contact_for_key`)
+    /// are merged into it, chat history included, so the chat list never
+    /// shows the same key twice. Does nothing once the scan has completed.
+    ///
+    /// # Returns
+    /// The report, or None if the scan already ran
     ///
     /// # Errors
     /// Returns an error if database operations fail
-    pub fn save_to_db(&self, db: &Storage) -> Result<()> {
-        // Save user identity
-        if let Some(ref keypair) = self.user_keypair {
-            db.save_user_identity(
-                keypair,
-                self.user_ip.as_deref(),
-                self.user_port,
-            )?;
+    pub fn run_identity_scan(&mut self, db: &Storage) -> Result<Option<IntegrityReport>> {
+        if db.integrity_check_completed(IDENTITY_SCAN_CHECK)? {
+            return Ok(None);
         }
 
-        // Save all contacts
-        for contact in &self.contacts {
-            db.save_contact(contact)?;
+        let report = db.verify_integrity()?;
+        for problem in &report.problems {
+            tracing::warn!("Database integrity check: {}", problem);
         }
 
-        // Save all chats (which includes messages)
-        for chat in &self.chats {
-            db.save_chat(chat)?;
+        for conflict in &report.conflicts {
+            tracing::warn!("Identity conflict for contact {}: {}", conflict.contact.uid, conflict.kind);
+            if conflict.kind == ConflictKind::DuplicateKey {
+                let duplicate = &conflict.contact.uid;
+                db.merge_duplicate_contact(duplicate, &conflict.existing_uid)?;
+                self.remove_contact(duplicate);
+                if let Some(chat) = self.remove_chat(duplicate) {
+                    let canonical = self.get_or_create_chat(&conflict.existing_uid);
+                    for message in chat.messages.iter() {
+                        canonical.append_message(message.clone());
+                    }
+                    canonical.messages_mut().sort_by_key(|m| m.timestamp);
+                }
+            }
+            self.record_identity_conflict(conflict.clone());
         }
 
-        // Save settings
-        db.save_settings(&self.settings)?;
+        db.save_identity_conflicts(&self.identity_conflicts)?;
+        db.mark_integrity_check_completed(IDENTITY_SCAN_CHECK)?;
+        Ok(Some(report))
+    }
 
-        Ok(())
+    /// Trim every chat to its history limit (chat override, else the setting)
+    ///
+    /// Normally limits are enforced lazily when a message is added; this
+    /// applies them at once. Returns the number of messages removed.
+    pub fn apply_history_limits(&mut self) -> usize {
+        let global_limit = self.settings.history_limit;
+        self.chats
+            .iter_mut()
+            .map(|chat| {
+                let limit = chat.effective_history_limit(global_limit);
+                chat.enforce_history_limit(limit)
+            })
+            .sum()
+    }
+
+    // ========== SQLite-based storage methods ==========
+
+    /// Save the entire application state to SQLite database
+    ///
+    /// All writes happen in one transaction, retried while another process
+    /// holds a lock on the database (see `Storage::with_write_retry`).
+    ///
+    /// # Errors
+    /// Returns an error if database operations fail
+    pub fn save_to_db(&self, db: &Storage) -> Result<()> {
+        db.with_write_retry(|| {
+            // Save user identity
+            if let Some(ref keypair) = self.user_keypair {
+                db.save_user_identity(
+                    keypair,
+                    self.user_ip.as_deref(),
+                    self.user_port,
+                )?;
+            }
+
+            // Save all contacts
+            for contact in &self.contacts {
+                db.save_contact(contact)?;
+            }
+
+            // Save all chats (which includes messages)
+            for chat in &self.chats {
+                db.save_chat(chat)?;
+            }
+
+            // Save settings
+            db.save_settings(&self.settings)?;
+
+            // Save identity conflicts and address changes awaiting review
+            db.save_identity_conflicts(&self.identity_conflicts)?;
+            db.save_address_changes(&self.address_changes)?;
+
+            Ok(())
+        })
     }
 
     /// Load the entire application state from SQLite database
@@ -244,6 +663,21 @@ impl AppState {
     /// # Errors
     /// Returns an error if database operations fail
     pub fn load_from_db(db: &Storage) -> Result<Self> {
+        Self::load_from_db_with(db, true)
+    }
+
+    /// Load application state from SQLite without reading message history
+    ///
+    /// Chats are loaded with empty message lists. Use `Storage::load_chat_messages`
+    /// to fill them in later.
+    ///
+    /// # Errors
+    /// Returns an error if database operations fail
+    pub fn load_headers_from_db(db: &Storage) -> Result<Self> {
+        Self::load_from_db_with(db, false)
+    }
+
+    fn load_from_db_with(db: &Storage, with_messages: bool) -> Result<Self> {
         // Load user identity
         let (user_keypair, user_ip, user_port) = if let Some((keypair, ip, port)) = db.load_user_identity()? {
             (Some(keypair), ip, port)
@@ -255,13 +689,20 @@ impl AppState {
         // Load contacts
         let contacts = db.load_contacts()?;
 
-        // Load chats
-        let chats = db.load_chats()?;
+        // Load chats (optionally without messages)
+        let chats = if with_messages {
+            db.load_chats()?
+        } else {
+            db.load_chat_headers()?
+        };
 
         // Load settings (or use defaults)
         let settings = db.load_settings()?.unwrap_or_default();
 
-        Ok(Self {
+        let identity_conflicts = db.load_identity_conflicts()?;
+        let address_changes = db.load_address_changes()?;
+
+        let mut state = Self {
             user_keypair,
             user_ip,
             user_port,
@@ -269,49 +710,33 @@ impl AppState {
             chats,
             message_queue: Vec::new(), // Queue is managed separately in message_queue.db
             settings,
-        })
+            identity_conflicts,
+            address_changes,
+            contact_index: UidIndex::default(),
+            chat_index: UidIndex::default(),
+        };
+        state.sort_archived_last();
+        state.reindex();
+        Ok(state)
     }
 
     /// Migrate from JSON file to SQLite database
     ///
+    /// The file is validated before anything is written and kept as
+    /// `app_state.json.bak`; see `storage::migration`.
+    ///
     /// # Arguments
     /// * `json_path` - Path to the old app_state.json file
     /// * `db` - SQLite storage instance
     ///
     /// # Returns
-    /// True if migration was performed, false if no JSON file exists or is corrupt
+    /// True if migration was performed, false if no JSON file exists
     ///
     /// # Errors
-    /// Returns an error if migration fails (but not if JSON is corrupt)
+    /// Returns an error if the JSON is malformed or inconsistent, or the import
+    /// fails; the database and the JSON file are then left unchanged
     pub fn migrate_from_json<P: AsRef<Path>>(json_path: P, db: &Storage) -> Result<bool> {
-        let path_ref = json_path.as_ref();
-
-        // Check if JSON file exists
-        if !path_ref.exists() {
-            return Ok(false);
-        }
-
-        // Try to load state from JSON (may fail if corrupt)
-        let state = match Self::load(path_ref) {
-            Ok(s) => s,
-            Err(_) => {
-                // JSON is corrupt, skip migration and remove bad file
-                tracing::warn!("Corrupt app_state.json found, skipping migration");
-                let backup_path = path_ref.with_extension("json.corrupt");
-                let _ = std::fs::rename(path_ref, &backup_path);
-                return Ok(false);
-            }
-        };
-
-        // Save to database
-        state.save_to_db(db)?;
-
-        // Rename the old JSON file to .bak
-        let backup_path = path_ref.with_extension("json.bak");
-        std::fs::rename(path_ref, &backup_path)
-            .map_err(|e| Error::Storage(format!("Failed to backup old state file: {}", e)))?;
-
-        Ok(true)
+        Ok(migration::migrate(json_path, db)?.is_some())
     }
 }
 
@@ -0,0 +1,137 @@
+//! Bounds on times that peers supply
+//!
+//! A token expiring in the year 9999 would keep its contact around forever,
+//! bypassing the expiry-based trust decay; a message timestamped at
+//! `i64::MAX` breaks chat ordering and duration arithmetic. Such values are
+//! bounded where they enter (`AppState::ingest_contact_from` for every token,
+//! incoming messages, incoming edits):
+//!
+//! - a token expiry more than `Settings::max_token_expiry_days` (default 180,
+//!   at most `MAX_TOKEN_EXPIRY_DAYS_CAP`) from now is clamped to that
+//!   horizon; the expiry the token asked for is kept in
+//!   `Contact::requested_expiry` and shown in the contact details
+//! - a token in a ping (introductions included) with less than
+//!   `Settings::min_token_validity_minutes` (default 60) left is refused, so
+//!   the sender is not imported only to expire moments later; its client
+//!   retries once with a fresh token. Pasted tokens only get a warning
+//! - a timestamp outside [now - 10 years, now + `MAX_CLOCK_SKEW_MINUTES`] is
+//!   replaced by the receive time; messages keep the original in their
+//!   metadata under `ORIGINAL_TIMESTAMP_KEY`
+//!
+//! The duration helpers that display these values saturate at
+//! `SATURATED_DURATION_TEXT` instead of overflowing.
+
+use super::{contact::Contact, message::{Message, MetadataValue}};
+use crate::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+
+/// Default for `Settings::max_token_expiry_days`
+pub const DEFAULT_MAX_TOKEN_EXPIRY_DAYS: u32 = 180;
+
+/// Largest `Settings::max_token_expiry_days` honoured (10 years); larger
+/// values from an edited or imported settings file are lowered to it
+pub const MAX_TOKEN_EXPIRY_DAYS_CAP: u32 = 3650;
+
+/// Default for `Settings::min_token_validity_minutes`
+pub const DEFAULT_MIN_TOKEN_VALIDITY_MINUTES: u32 = 60;
+
+/// Oldest accepted peer timestamp, in days before now (10 years)
+pub const MAX_TIMESTAMP_AGE_DAYS: i64 = 3650;
+
+/// How far a peer's clock may run ahead of ours
+pub const MAX_CLOCK_SKEW_MINUTES: i64 = 10;
+
+/// Metadata key holding a message's timestamp as sent, when it was replaced
+pub const ORIGINAL_TIMESTAMP_KEY: &str = "original_timestamp";
+
+/// Longest duration the display helpers spell out, in seconds (999 days)
+pub const MAX_DISPLAY_SECS: i64 = 999 * 24 * 3600;
+
+/// What the display helpers show for anything longer than `MAX_DISPLAY_SECS`
+pub const SATURATED_DURATION_TEXT: &str = "999+ days";
+
+/// Clamp `contact`'s expiry to `max_days` from `now`
+///
+/// The expiry the token asked for is kept in `Contact::requested_expiry`.
+/// A `max_days` of 0 counts as 1, so a token is never expired on arrival;
+/// more than `MAX_TOKEN_EXPIRY_DAYS_CAP` counts as the cap.
+///
+/// # Returns
+/// Whether the expiry was clamped
+pub fn clamp_token_expiry(contact: &mut Contact, now: DateTime<Utc>, max_days: u32) -> bool {
+    let max_days = max_days.clamp(1, MAX_TOKEN_EXPIRY_DAYS_CAP);
+    let horizon = now.checked_add_signed(Duration::days(i64::from(max_days))).unwrap_or(DateTime::<Utc>::MAX_UTC);
+    if contact.expiry <= horizon {
+        return false;
+    }
+    tracing::warn!(
+        "Token of {} expires {}, beyond the {}-day limit; clamped to {}",
+        contact.uid,
+        contact.expiry.format("%Y-%m-%d"),
+        max_days,
+        horizon.format("%Y-%m-%d")
+    );
+    contact.requested_expiry = Some(contact.expiry);
+    contact.expiry = horizon;
+    true
+}
+
+/// Whether `contact`'s token has less than `min_minutes` of validity left at `now`
+pub fn token_expires_soon(contact: &Contact, now: DateTime<Utc>, min_minutes: u32) -> bool {
+    contact.expiry < now + Duration::minutes(i64::from(min_minutes))
+}
+
+/// Refuse a token with less than `min_minutes` of validity left at `now`
+///
+/// # Errors
+/// `Error::TokenTooShortLived` naming the token's expiry
+pub fn require_min_validity(contact: &Contact, now: DateTime<Utc>, min_minutes: u32) -> Result<()> {
+    if !token_expires_soon(contact, now, min_minutes) {
+        return Ok(());
+    }
+    Err(Error::TokenTooShortLived(format!(
+        "token of {} expires {}, less than {} minutes from now",
+        contact.uid,
+        contact.expiry.format("%Y-%m-%d %H:%M:%S UTC"),
+        min_minutes
+    )))
+}
+
+/// Whether a peer-supplied timestamp (Unix milliseconds) is plausible at `now`
+pub fn timestamp_in_bounds(timestamp_ms: i64, now: DateTime<Utc>) -> bool {
+    let earliest = (now - Duration::days(MAX_TIMESTAMP_AGE_DAYS)).timestamp_millis();
+    let latest = (now + Duration::minutes(MAX_CLOCK_SKEW_MINUTES)).timestamp_millis();
+    (earliest..=latest).contains(&timestamp_ms)
+}
+
+/// `timestamp_ms` if it is plausible at `now`, otherwise `now`
+pub fn bounded_timestamp(timestamp_ms: i64, now: DateTime<Utc>) -> i64 {
+    if timestamp_in_bounds(timestamp_ms, now) {
+        timestamp_ms
+    } else {
+        now.timestamp_millis()
+    }
+}
+
+/// Replace an implausible `message.timestamp` with `received_at`
+///
+/// The timestamp as sent is kept in the metadata under `ORIGINAL_TIMESTAMP_KEY`.
+///
+/// # Returns
+/// Whether the timestamp was replaced
+pub fn bound_message_timestamp(message: &mut Message, received_at: DateTime<Utc>) -> bool {
+    if timestamp_in_bounds(message.timestamp, received_at) {
+        return false;
+    }
+    tracing::warn!(
+        "Message {} from {} has timestamp {}; using the receive time",
+        message.id,
+        message.sender,
+        message.timestamp
+    );
+    message
+        .metadata
+        .insert(ORIGINAL_TIMESTAMP_KEY.to_string(), MetadataValue::Integer(message.timestamp));
+    message.timestamp = received_at.timestamp_millis();
+    true
+}
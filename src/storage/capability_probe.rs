@@ -0,0 +1,117 @@
+//! Lazy discovery of what a contact's client supports
+//!
+//! Capabilities (edit support, relay offers) normally arrive as a pushed
+//! `relay_capabilities` message. Contacts imported before those existed, or
+//! whose client never pushed them, have none on record, so edits go out as
+//! corrections and they are never used as relays, even if the peer upgraded
+//! long ago. Such contacts are asked with `POST /capabilities` (see
+//! `PeerTransport::probe_capabilities`), and a `CapabilityRecord` keeps when
+//! the answer was fetched.
+//!
+//! A probe only ever follows a delivery to the contact that just succeeded:
+//! there is no probe on a schedule of its own, which would tell the peer we
+//! are online. `CapabilityProbes` allows one attempt per contact every
+//! `PROBE_MIN_INTERVAL_MINUTES`. A peer without the endpoint is recorded as
+//! legacy and only asked again after `LEGACY_REPROBE_DAYS`.
+
+use crate::cache::BoundedCache;
+use crate::storage::Contact;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Days after which fetched capabilities are asked for again
+pub const CAPABILITIES_STALE_DAYS: i64 = 7;
+
+/// Days after which a legacy peer is probed again (it may have upgraded)
+pub const LEGACY_REPROBE_DAYS: i64 = 30;
+
+/// Minutes between two probe attempts to the same contact
+pub const PROBE_MIN_INTERVAL_MINUTES: i64 = 60;
+
+/// Most contacts whose last probe attempt is remembered
+pub const MAX_TRACKED_PROBE_ATTEMPTS: usize = 1024;
+
+/// What is known about a contact's capabilities (local only)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum CapabilityRecord {
+    /// Capabilities were received (probe answer or advertised)
+    Fetched {
+        /// When they were received
+        fetched_at: DateTime<Utc>,
+    },
+    /// The peer does not answer capability probes (older client)
+    Legacy {
+        /// When it was probed
+        probed_at: DateTime<Utc>,
+    },
+}
+
+impl CapabilityRecord {
+    /// Short description for contact details, e.g. "legacy, probed 2024-05-01"
+    pub fn describe(&self) -> String {
+        match self {
+            CapabilityRecord::Fetched { fetched_at } => format!("fetched {}", fetched_at.format("%Y-%m-%d")),
+            CapabilityRecord::Legacy { probed_at } => format!("legacy, probed {}", probed_at.format("%Y-%m-%d")),
+        }
+    }
+}
+
+/// Whether a contact with `record` should be probed at `now`
+///
+/// Absent capabilities are always due, fetched ones after
+/// `CAPABILITIES_STALE_DAYS` and a legacy peer after `LEGACY_REPROBE_DAYS`.
+pub fn probe_due(record: Option<&CapabilityRecord>, now: DateTime<Utc>) -> bool {
+    match record {
+        None => true,
+        Some(CapabilityRecord::Fetched { fetched_at }) => now >= *fetched_at + Duration::days(CAPABILITIES_STALE_DAYS),
+        Some(CapabilityRecord::Legacy { probed_at }) => now >= *probed_at + Duration::days(LEGACY_REPROBE_DAYS),
+    }
+}
+
+/// Per-contact rate limit of capability probes (in memory, per session)
+///
+/// Attempts are forgotten once `PROBE_MIN_INTERVAL_MINUTES` have passed, or
+/// earlier if more than `MAX_TRACKED_PROBE_ATTEMPTS` contacts were probed
+/// since; a forgotten contact may be probed again.
+#[derive(Debug)]
+pub struct CapabilityProbes {
+    /// Last attempt by contact UID
+    attempts: BoundedCache<String, DateTime<Utc>>,
+}
+
+impl Default for CapabilityProbes {
+    fn default() -> Self {
+        Self {
+            attempts: BoundedCache::new(
+                "capability probes",
+                MAX_TRACKED_PROBE_ATTEMPTS,
+                Some(Duration::minutes(PROBE_MIN_INTERVAL_MINUTES)),
+            ),
+        }
+    }
+}
+
+impl CapabilityProbes {
+    /// Create a limiter with no attempts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to probe `contact` after an exchange at `now`
+    ///
+    /// The contact must be due (see `probe_due`) and not attempted within
+    /// `PROBE_MIN_INTERVAL_MINUTES`; if so, this attempt is counted, whether
+    /// or not the probe later gets an answer.
+    pub fn start(&mut self, contact: &Contact, now: DateTime<Utc>) -> bool {
+        if !probe_due(contact.capabilities.as_ref(), now) {
+            return false;
+        }
+        let interval = Duration::minutes(PROBE_MIN_INTERVAL_MINUTES);
+        if self.attempts.get_at(&contact.uid, now).is_some_and(|last| now < last + interval) {
+            return false;
+        }
+        self.attempts.insert_at(contact.uid.clone(), now, now);
+        true
+    }
+}
@@ -1,19 +1,52 @@
 //! Chat conversation management
 
+use crate::storage::chat_summary::MessageSummary;
 use crate::storage::message::Message;
+use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Maximum number of pinned messages per chat
+pub const MAX_PINNED_PER_CHAT: usize = 5;
+
+/// Per-chat history limits offered in the contact details popup (0 = unlimited)
+pub const HISTORY_LIMIT_PRESETS: [u32; 5] = [100, 500, 2000, 10_000, 0];
+
+/// Notice recorded the first time a chat's history is trimmed
+pub const HISTORY_TRIMMED_NOTICE: &str = "older messages were removed (history limit)";
 
 /// Represents a chat conversation with a contact
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chat {
     /// Contact UID this chat is with
     pub contact_uid: String,
-    /// Messages in this conversation
-    pub messages: Vec<Message>,
+    /// Messages in this conversation; shared, so cloning a chat is O(1)
+    /// (mutate through `messages_mut()`)
+    #[serde(serialize_with = "crate::storage::message::shared::serialize", deserialize_with = "crate::storage::message::shared::deserialize_vec")]
+    pub messages: Arc<Vec<Message>>,
     /// Whether this chat is active (unread messages present)
     pub is_active: bool,
     /// Whether there are pending (queued) messages for this contact
     pub has_pending_messages: bool,
+    /// Whether a message to this contact was dropped after exhausting its retries
+    #[serde(default)]
+    pub has_failed_messages: bool,
+    /// History limit for this chat, overriding the global setting (0 = unlimited)
+    #[serde(default)]
+    pub history_limit: Option<u32>,
+    /// Messages older than this (Unix milliseconds) were trimmed by the history limit
+    #[serde(default)]
+    pub trimmed_before: Option<i64>,
+    /// New messages in this chat raise no notification or alert
+    #[serde(default)]
+    pub muted: bool,
+    /// Archived: listed after the other chats until a new message arrives
+    #[serde(default)]
+    pub archived: bool,
+    /// Summary of the stored history, read with the chat header while
+    /// `messages` is not loaded yet (None if not built)
+    #[serde(skip)]
+    pub stored_summary: Option<MessageSummary>,
 }
 
 impl Chat {
@@ -21,15 +54,106 @@ impl Chat {
     pub fn new(contact_uid: String) -> Self {
         Self {
             contact_uid,
-            messages: Vec::new(),
+            messages: Arc::new(Vec::new()),
             is_active: false,
             has_pending_messages: false,
+            has_failed_messages: false,
+            history_limit: None,
+            trimmed_before: None,
+            muted: false,
+            archived: false,
+            stored_summary: None,
         }
     }
 
+    /// Messages for mutation, copied first if another clone shares them
+    pub fn messages_mut(&mut self) -> &mut Vec<Message> {
+        Arc::make_mut(&mut self.messages)
+    }
+
     /// Append a message to this chat
     pub fn append_message(&mut self, msg: Message) {
-        self.messages.push(msg);
+        self.messages_mut().push(msg);
+    }
+
+    /// Append a message and trim the oldest beyond the history limit
+    ///
+    /// `global_limit` is the setting used when the chat has no override.
+    /// Returns the number of messages removed (see `enforce_history_limit`).
+    pub fn append_with_limit(&mut self, msg: Message, global_limit: u32) -> usize {
+        self.messages_mut().push(msg);
+        self.enforce_history_limit(self.effective_history_limit(global_limit))
+    }
+
+    /// History limit in force: the chat's override, else `global_limit`
+    pub fn effective_history_limit(&self, global_limit: u32) -> u32 {
+        self.history_limit.unwrap_or(global_limit)
+    }
+
+    /// Keep only the newest `limit` messages (0 = unlimited)
+    ///
+    /// Pinned messages and local notices are never removed, and notices do
+    /// not count towards the limit. The first trim in a chat records a
+    /// `HISTORY_TRIMMED_NOTICE`. `trimmed_before` is advanced so that
+    /// `Storage::save_chat` deletes the same rows in the saving transaction.
+    ///
+    /// # Returns
+    /// The number of messages removed
+    pub fn enforce_history_limit(&mut self, limit: u32) -> usize {
+        let limit = limit as usize;
+        if limit == 0 {
+            return 0;
+        }
+        let mut timestamps: Vec<i64> = self
+            .messages
+            .iter()
+            .filter(|m| !m.is_system())
+            .map(|m| m.timestamp)
+            .collect();
+        if timestamps.len() <= limit {
+            return 0;
+        }
+        timestamps.sort_unstable_by(|a, b| b.cmp(a));
+        let cutoff = timestamps[limit - 1];
+
+        let before = self.messages.len();
+        self.messages_mut()
+            .retain(|m| m.timestamp >= cutoff || m.pinned || m.is_system());
+        let removed = before - self.messages.len();
+        if removed == 0 {
+            return 0;
+        }
+
+        if self.trimmed_before.is_none() {
+            let at = self
+                .messages
+                .iter()
+                .position(|m| m.timestamp >= cutoff)
+                .unwrap_or(0);
+            self.messages_mut()
+                .insert(at, Message::system(HISTORY_TRIMMED_NOTICE, cutoff - 1));
+        }
+        self.trimmed_before = Some(self.trimmed_before.map_or(cutoff, |t| t.max(cutoff)));
+        removed
+    }
+
+    /// Step the chat's override through `HISTORY_LIMIT_PRESETS`, then back to none
+    ///
+    /// Like the global setting, a new limit is enforced at the next message.
+    pub fn cycle_history_limit(&mut self) {
+        self.history_limit = match self.history_limit {
+            None => Some(HISTORY_LIMIT_PRESETS[0]),
+            Some(limit) => HISTORY_LIMIT_PRESETS
+                .iter()
+                .position(|&preset| preset == limit)
+                .and_then(|i| HISTORY_LIMIT_PRESETS.get(i + 1))
+                .copied(),
+        };
+    }
+
+    /// Whether messages were ever removed from this chat by the history limit
+    pub fn is_trimmed(&self) -> bool {
+        self.trimmed_before.is_some()
     }
 
     /// Mark chat as having unread messages (active)
@@ -56,4 +180,72 @@ impl Chat {
     pub fn has_pending(&self) -> bool {
         self.has_pending_messages
     }
+
+    /// Mark chat as having a message that could not be delivered
+    pub fn mark_has_failed(&mut self) {
+        self.has_failed_messages = true;
+    }
+
+    /// Clear the failed delivery flag
+    pub fn mark_no_failed(&mut self) {
+        self.has_failed_messages = false;
+    }
+
+    /// Check if a message to this contact failed permanently
+    pub fn has_failed(&self) -> bool {
+        self.has_failed_messages
+    }
+
+    /// Pin or unpin a message
+    ///
+    /// # Returns
+    /// Whether the message is pinned afterwards
+    ///
+    /// # Errors
+    /// Returns `Error::Storage` if the message is not in this chat, or if
+    /// pinning it would exceed `MAX_PINNED_PER_CHAT`
+    pub fn toggle_pin(&mut self, message_id: &str) -> Result<bool> {
+        let pinned_count = self.messages.iter().filter(|m| m.pinned).count();
+        let message = self
+            .messages_mut()
+            .iter_mut()
+            .find(|m| m.id == message_id)
+            .ok_or_else(|| Error::Storage(format!("Message {} not found", message_id)))?;
+
+        if !message.pinned && pinned_count >= MAX_PINNED_PER_CHAT {
+            return Err(Error::Storage(format!(
+                "At most {} pinned messages per chat",
+                MAX_PINNED_PER_CHAT
+            )));
+        }
+        message.pinned = !message.pinned;
+        Ok(message.pinned)
+    }
+
+    /// Star or unstar a message
+    ///
+    /// # Returns
+    /// Whether the message is starred afterwards
+    ///
+    /// # Errors
+    /// Returns `Error::Storage` if the message is not in this chat
+    pub fn toggle_star(&mut self, message_id: &str) -> Result<bool> {
+        let message = self
+            .messages_mut()
+            .iter_mut()
+            .find(|m| m.id == message_id)
+            .ok_or_else(|| Error::Storage(format!("Message {} not found", message_id)))?;
+        message.starred = !message.starred;
+        Ok(message.starred)
+    }
+
+    /// Pinned messages in chronological order
+    pub fn pinned_messages(&self) -> Vec<&Message> {
+        self.messages.iter().filter(|m| m.pinned).collect()
+    }
+
+    /// Starred messages in chronological order
+    pub fn starred_messages(&self) -> Vec<&Message> {
+        self.messages.iter().filter(|m| m.starred).collect()
+    }
 }
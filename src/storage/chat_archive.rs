@@ -0,0 +1,338 @@
+//! Verifiable chat export (signed archive) and its import-verify check
+//!
+//! A chat archive is a JSON Lines chat export the other participant can
+//! check was not doctored. `Storage::export_chat_archive` writes, in one file:
+//!
+//! - one `ExportedMessage` line per message, oldest first (inline content;
+//!   local notices are left out, the other side never had them)
+//! - an `ArchiveManifest` line: the chat's UID pair, date range, our identity
+//!   public key and, per message, the SHA-256 of its content and of its line
+//! - a trailer line with an Ed25519 signature of the manifest line by our
+//!   identity key
+//!
+//! `ChatArchive::parse` reads a file without trusting it; `verify` checks
+//! the signature against the key of the contact named in the manifest, then
+//! recomputes every line's hash and reports what was changed, dropped or
+//! added (`ArchiveProblem`). Nothing in the archive is ever imported.
+//!
+//! Holding the same chat, `cross_reference` (or
+//! `Storage::cross_reference_archive`) compares the signed manifest with our
+//! own messages. Each side stores a received message under a new ID and its
+//! time of arrival, so messages are matched by the ID their sender gave them
+//! (`wire_id`) and compared by content only.
+
+use crate::storage::{ContentMode, ExportedMessage, Message};
+use crate::{Error, Result};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Version written in every archive manifest
+pub const CHAT_ARCHIVE_VERSION: u32 = 1;
+
+/// One message as listed (and signed) in the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Message ID on the exporting side (the `id` of its line)
+    pub id: String,
+    /// ID the message's sender gave it, the same on both sides
+    pub wire_id: String,
+    /// Time the message was stored on the exporting side (ms since epoch)
+    pub sent_at: i64,
+    /// Hex SHA-256 of the message content
+    pub content_sha256: String,
+    /// Hex SHA-256 of the message's line, as written
+    pub line_sha256: String,
+}
+
+/// What an archive claims to hold, signed by its exporter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    /// Manifest schema version
+    pub version: u32,
+    /// UID of the exporter (the signer)
+    pub owner_uid: String,
+    /// UID of the other participant
+    pub contact_uid: String,
+    /// Earliest message time (None for an empty chat)
+    pub first_sent_at: Option<i64>,
+    /// Latest message time (None for an empty chat)
+    pub last_sent_at: Option<i64>,
+    /// Hex Ed25519 identity public key of the exporter
+    pub public_key: String,
+    /// Every message, in line order
+    pub messages: Vec<ManifestEntry>,
+}
+
+/// Trailer line of a chat archive
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchiveSignature {
+    signature: String,
+    public_key: String,
+}
+
+/// Something in the archive that does not match its signed manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveProblem {
+    /// The message's content differs from the signed hash
+    ContentChanged(String),
+    /// The message's time differs from the signed one
+    TimestampChanged(String),
+    /// Another field of the message's line was edited
+    LineChanged(String),
+    /// A signed message has no line
+    Dropped(String),
+    /// A line for a message the manifest does not list
+    Added(String),
+    /// A line that is not a message (line number, 1-based)
+    Unreadable(usize),
+}
+
+impl std::fmt::Display for ArchiveProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveProblem::ContentChanged(id) => write!(f, "{}: content changed", id),
+            ArchiveProblem::TimestampChanged(id) => write!(f, "{}: timestamp changed", id),
+            ArchiveProblem::LineChanged(id) => write!(f, "{}: line edited", id),
+            ArchiveProblem::Dropped(id) => write!(f, "{}: dropped", id),
+            ArchiveProblem::Added(id) => write!(f, "{}: not in the manifest", id),
+            ArchiveProblem::Unreadable(line) => write!(f, "line {}: not a message", line),
+        }
+    }
+}
+
+/// A chat archive read from a file, not checked yet
+#[derive(Debug, Clone)]
+pub struct ChatArchive {
+    /// The manifest, as written (unverified until `verify`)
+    pub manifest: ArchiveManifest,
+    manifest_line: String,
+    signature: [u8; 64],
+    lines: Vec<String>,
+}
+
+/// Result of checking an archive against its signature
+#[derive(Debug, Clone)]
+pub struct ArchiveVerification {
+    /// The manifest, whose signature verified
+    pub manifest: ArchiveManifest,
+    /// Messages whose lines match the manifest
+    pub intact: usize,
+    /// Differences between the lines and the manifest (empty: untouched)
+    pub problems: Vec<ArchiveProblem>,
+}
+
+impl ArchiveVerification {
+    /// Whether every line matches the signed manifest
+    pub fn is_intact(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Signed archive compared with our own copy of the chat
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrossReference {
+    /// Wire IDs held by both sides with the same content
+    pub matched: Vec<String>,
+    /// Wire IDs held by both sides with different content
+    pub mismatched: Vec<String>,
+    /// Wire IDs only in the archive
+    pub only_in_archive: Vec<String>,
+    /// Wire IDs only we hold, within the archive's date range
+    pub only_local: Vec<String>,
+}
+
+impl CrossReference {
+    /// Whether both sides hold exactly the same messages
+    pub fn agrees(&self) -> bool {
+        self.mismatched.is_empty() && self.only_in_archive.is_empty() && self.only_local.is_empty()
+    }
+}
+
+/// ID the sender gave `message`: ours for sent messages, the remote one for
+/// received messages
+pub fn wire_id(message: &Message) -> &str {
+    message.remote_id.as_deref().unwrap_or(&message.id)
+}
+
+/// Hex SHA-256 of a message's content
+pub fn content_sha256(message: &Message) -> String {
+    hex::encode(digest(&SHA256, &message.content))
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(digest(&SHA256, bytes))
+}
+
+/// Builds a chat archive line by line
+///
+/// Used by `Storage::export_chat_archive`, which feeds it a page at a time.
+pub(crate) struct ArchiveWriter {
+    manifest: ArchiveManifest,
+}
+
+impl ArchiveWriter {
+    pub(crate) fn new(owner_uid: &str, contact_uid: &str, public_key: &[u8]) -> Self {
+        Self {
+            manifest: ArchiveManifest {
+                version: CHAT_ARCHIVE_VERSION,
+                owner_uid: owner_uid.to_string(),
+                contact_uid: contact_uid.to_string(),
+                first_sent_at: None,
+                last_sent_at: None,
+                public_key: hex::encode(public_key),
+                messages: Vec::new(),
+            },
+        }
+    }
+
+    /// The line for `message`, recorded in the manifest
+    pub(crate) fn line(&mut self, message: &Message) -> Result<String> {
+        let line = serde_json::to_string(&ExportedMessage::new(message, &self.manifest.contact_uid, ContentMode::Inline))?;
+        let manifest = &mut self.manifest;
+        manifest.first_sent_at = Some(manifest.first_sent_at.map_or(message.timestamp, |t| t.min(message.timestamp)));
+        manifest.last_sent_at = Some(manifest.last_sent_at.map_or(message.timestamp, |t| t.max(message.timestamp)));
+        manifest.messages.push(ManifestEntry {
+            id: message.id.clone(),
+            wire_id: wire_id(message).to_string(),
+            sent_at: message.timestamp,
+            content_sha256: content_sha256(message),
+            line_sha256: sha256_hex(line.as_bytes()),
+        });
+        Ok(line)
+    }
+
+    /// The manifest and signature lines that end the archive
+    pub(crate) fn finish(self, keypair: &crate::crypto::KeyPair) -> Result<String> {
+        let manifest_line = serde_json::to_string(&self.manifest)?;
+        let signature = hex::encode(keypair.sign(manifest_line.as_bytes())?);
+        let trailer = serde_json::to_string(&ArchiveSignature { signature, public_key: self.manifest.public_key })?;
+        Ok(format!("{}\n{}\n", manifest_line, trailer))
+    }
+}
+
+impl ChatArchive {
+    /// Read an archive's lines without checking anything but their layout
+    ///
+    /// # Errors
+    /// Returns `Error::Crypto` if the manifest or signature line is missing
+    /// or malformed
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+        while lines.last().is_some_and(|l| l.trim().is_empty()) {
+            lines.pop();
+        }
+        let malformed = |what: &str| Error::Crypto(format!("Chat archive has no {} line", what));
+        let trailer = lines.pop().ok_or_else(|| malformed("signature"))?;
+        let manifest_line = lines.pop().ok_or_else(|| malformed("manifest"))?;
+
+        let signature = serde_json::from_str::<ArchiveSignature>(&trailer).map_err(|_| malformed("signature"))?.signature;
+        let signature: [u8; 64] = hex::decode(&signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| Error::Crypto("Chat archive signature is malformed".to_string()))?;
+        let manifest: ArchiveManifest = serde_json::from_str(&manifest_line).map_err(|_| malformed("manifest"))?;
+        if manifest.version != CHAT_ARCHIVE_VERSION {
+            return Err(Error::Crypto(format!("Chat archive version {} is not supported", manifest.version)));
+        }
+        Ok(Self { manifest, manifest_line, signature, lines })
+    }
+
+    /// Check the signature with the exporter's `public_key`, then every line
+    /// against the manifest
+    ///
+    /// # Errors
+    /// Returns `Error::Crypto` if the key is invalid or the signature does
+    /// not verify; tampered lines are reported, not errors
+    pub fn verify(&self, public_key: &[u8]) -> Result<ArchiveVerification> {
+        let key: [u8; 32] = public_key
+            .try_into()
+            .map_err(|_| Error::Crypto("Invalid public key length".to_string()))?;
+        if !crate::crypto::verify_contact_token(&key, self.manifest_line.as_bytes(), &self.signature)? {
+            return Err(Error::Crypto("Chat archive signature does not verify".to_string()));
+        }
+
+        let mut problems = Vec::new();
+        let mut lines: HashMap<String, (String, ExportedMessage)> = HashMap::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            match serde_json::from_str::<ExportedMessage>(line) {
+                Ok(message) => {
+                    lines.insert(message.id.clone(), (line.clone(), message));
+                }
+                Err(_) => problems.push(ArchiveProblem::Unreadable(i + 1)),
+            }
+        }
+
+        let mut intact = 0;
+        let listed: HashSet<&str> = self.manifest.messages.iter().map(|entry| entry.id.as_str()).collect();
+        for entry in &self.manifest.messages {
+            let Some((line, message)) = lines.get(&entry.id) else {
+                problems.push(ArchiveProblem::Dropped(entry.id.clone()));
+                continue;
+            };
+            if sha256_hex(line.as_bytes()) == entry.line_sha256 {
+                intact += 1;
+            } else if exported_content_sha256(message).as_deref() != Some(entry.content_sha256.as_str()) {
+                problems.push(ArchiveProblem::ContentChanged(entry.id.clone()));
+            } else if message.sent_at != entry.sent_at {
+                problems.push(ArchiveProblem::TimestampChanged(entry.id.clone()));
+            } else {
+                problems.push(ArchiveProblem::LineChanged(entry.id.clone()));
+            }
+        }
+        for line in &self.lines {
+            if let Ok(message) = serde_json::from_str::<ExportedMessage>(line)
+                && !listed.contains(message.id.as_str())
+            {
+                problems.push(ArchiveProblem::Added(message.id));
+            }
+        }
+
+        Ok(ArchiveVerification { manifest: self.manifest.clone(), intact, problems })
+    }
+}
+
+/// Content hash of an exported line: recomputed from inline text, or the
+/// reference hash of binary content
+fn exported_content_sha256(message: &ExportedMessage) -> Option<String> {
+    match &message.content {
+        Some(text) => Some(sha256_hex(text.as_bytes())),
+        None => message.content_sha256.clone(),
+    }
+}
+
+/// Compare a verified manifest with our own messages of the same chat
+///
+/// Only our messages within the manifest's date range count as missing from
+/// the archive; local notices are ignored.
+pub fn cross_reference<'a>(manifest: &ArchiveManifest, local: impl IntoIterator<Item = &'a Message>) -> CrossReference {
+    let in_range = |timestamp: i64| {
+        manifest.first_sent_at.is_some_and(|first| timestamp >= first)
+            && manifest.last_sent_at.is_some_and(|last| timestamp <= last)
+    };
+    let mut ours: HashMap<&str, (String, bool)> = HashMap::new();
+    for message in local.into_iter().filter(|m| !m.is_system()) {
+        ours.insert(wire_id(message), (content_sha256(message), in_range(message.timestamp)));
+    }
+
+    let mut report = CrossReference::default();
+    for entry in &manifest.messages {
+        match ours.remove(entry.wire_id.as_str()) {
+            Some((hash, _)) if hash == entry.content_sha256 => report.matched.push(entry.wire_id.clone()),
+            Some(_) => report.mismatched.push(entry.wire_id.clone()),
+            None => report.only_in_archive.push(entry.wire_id.clone()),
+        }
+    }
+    report.only_local = ours
+        .into_iter()
+        .filter(|(_, (_, in_range))| *in_range)
+        .map(|(id, _)| id.to_string())
+        .collect();
+    report.only_local.sort();
+    report
+}
+
+/// File name for a chat archive, before collision handling
+pub fn archive_file_name(contact_uid: &str) -> String {
+    crate::storage::export_file_name(contact_uid).replace(".jsonl", "-signed.jsonl")
+}
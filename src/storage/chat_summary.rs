@@ -0,0 +1,72 @@
+//! Per-chat summaries of stored message history
+//!
+//! The `chat_summaries` table keeps each chat's message count and latest
+//! message (ID, time, sender and a short preview), so the chat list is
+//! rendered without reading the messages table. `Storage::save_chat` rebuilds
+//! a chat's summary in the same transaction whenever its messages change, and
+//! `Storage::migrate_chat_summaries` builds the summaries of a database
+//! written before the table existed, one chat per transaction so it can be
+//! interrupted and resumed.
+
+use crate::storage::content::ContentKind;
+use crate::storage::message::Message;
+
+/// Characters of the latest message kept as its preview
+pub const SUMMARY_PREVIEW_CHARS: usize = 60;
+
+/// Stored history of one chat, as read by the chat list
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MessageSummary {
+    /// Number of stored messages
+    pub message_count: u64,
+    /// ID of the latest message (by timestamp, then ID)
+    pub latest_id: Option<String>,
+    /// Timestamp of the latest message (Unix milliseconds)
+    pub latest_timestamp: Option<i64>,
+    /// Sender UID of the latest message
+    pub latest_sender: Option<String>,
+    /// Start of the latest message (see `preview_text`)
+    pub latest_preview: Option<String>,
+}
+
+impl MessageSummary {
+    /// Replace the latest message (None when the chat has no messages left)
+    pub fn set_latest(&mut self, message: Option<&Message>) {
+        self.latest_id = message.map(|m| m.id.clone());
+        self.latest_timestamp = message.map(|m| m.timestamp);
+        self.latest_sender = message.map(|m| m.sender.clone());
+        self.latest_preview = message.map(|m| preview_text(&m.content));
+    }
+}
+
+/// Progress of `Storage::migrate_chat_summaries`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SummaryMigration {
+    /// Chats whose summary is built
+    pub done: usize,
+    /// All chats
+    pub total: usize,
+}
+
+impl SummaryMigration {
+    /// Whether every chat has its summary
+    pub fn is_complete(&self) -> bool {
+        self.done >= self.total
+    }
+}
+
+/// Single-line start of `content`, or a placeholder for non-text content
+pub fn preview_text(content: &[u8]) -> String {
+    match ContentKind::detect(content) {
+        ContentKind::Text => {
+            let text = String::from_utf8_lossy(content);
+            let mut preview: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if let Some((cut, _)) = preview.char_indices().nth(SUMMARY_PREVIEW_CHARS) {
+                preview.truncate(cut);
+                preview.push('…');
+            }
+            preview
+        }
+        kind => kind.placeholder(content.len()),
+    }
+}
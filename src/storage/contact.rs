@@ -3,13 +3,77 @@
 //! This module handles:
 //! - Contact struct representing peers in the P2P network
 //! - Signed contact token generation and verification
+//! - Advertised endpoints (several reachable addresses in one token)
 //! - Contact expiry and activation management
 
+use super::{capability_probe::CapabilityRecord, ephemeral::Ephemeral, local_time::{infer_utc_offset, record_offset_sample, OffsetSample, UtcOffset}, privacy::ContactPrivacy, settings::AlertStyle, trust::{OutboundPolicy, TrustTier}};
 use crate::{crypto::UID, Error, Result};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// Maximum size of a contact's notes in bytes (UTF-8 encoded)
+pub const MAX_CONTACT_NOTES_BYTES: usize = 4096;
+
+/// Transport scheme of addresses without an explicit `scheme://` prefix
+pub const DEFAULT_ADDRESS_SCHEME: &str = "http";
+
+/// Scheme of TLS endpoints of the HTTP transport (see `transport::tls`)
+pub const TLS_ADDRESS_SCHEME: &str = "https";
+
+/// Split an address into its transport scheme and the address without it
+///
+/// Plain `host:port` addresses use `DEFAULT_ADDRESS_SCHEME`, so tokens and
+/// contacts created before schemes existed keep working unchanged.
+///
+/// # Example
+/// ```
+/// use pure2p::storage::split_address_scheme;
+///
+/// assert_eq!(split_address_scheme("10.0.0.2:8080"), ("http", "10.0.0.2:8080"));
+/// assert_eq!(split_address_scheme("loopback://alice"), ("loopback", "alice"));
+/// ```
+pub fn split_address_scheme(address: &str) -> (&str, &str) {
+    match address.split_once("://") {
+        Some((scheme, rest)) => (scheme, rest),
+        None => (DEFAULT_ADDRESS_SCHEME, address),
+    }
+}
+
+/// A reachable address advertised in a contact token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContactEndpoint {
+    /// Address, optionally tagged with a transport scheme
+    /// (e.g., "10.8.0.2:8080" or "loopback://alice")
+    pub address: String,
+    /// Short label describing the endpoint (e.g., "vpn", "home")
+    pub label: String,
+    /// Time after which the endpoint should no longer be used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+impl ContactEndpoint {
+    /// Create an endpoint without an expiry
+    pub fn new(address: &str, label: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            label: label.to_string(),
+            valid_until: None,
+        }
+    }
+
+    /// Check whether the endpoint may still be used at `now`
+    pub fn is_valid_at(&self, now: DateTime<Utc>) -> bool {
+        self.valid_until.is_none_or(|until| now <= until)
+    }
+
+    /// Transport scheme used to reach this endpoint
+    pub fn scheme(&self) -> &str {
+        split_address_scheme(&self.address).0
+    }
+}
+
 /// Represents a contact/peer in the P2P network
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Contact {
@@ -20,15 +84,92 @@ pub struct Contact {
     /// Ed25519 public key bytes (for signature verification)
     pub pubkey: Vec<u8>,
     /// X25519 public key bytes (for key exchange)
-    pub x25519_pubkey: Vec<u8>,
+    ///
+    /// None for contacts stored before the key was part of every token.
+    /// Messages to them are sent in plaintext until a key upgrade fills it
+    /// (see `sealing`).
+    #[serde(default)]
+    pub x25519_pubkey: Option<Vec<u8>>,
     /// Expiration timestamp for this contact entry
     pub expiry: DateTime<Utc>,
     /// Whether this contact is currently active
     pub is_active: bool,
+    /// Free-text local notes about this contact
+    ///
+    /// Notes are stored locally only and are never included in contact tokens
+    /// or any other payload sent to peers.
+    #[serde(default)]
+    pub notes: String,
+    /// Endpoints advertised in the contact's token, in preference order
+    ///
+    /// Empty for contacts imported from single-endpoint tokens, in which case
+    /// `ip` is the only address.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub endpoints: Vec<ContactEndpoint>,
+    /// The contact advertised that it relays messages for its contacts
+    ///
+    /// Learned from the contact's relay capabilities, never from tokens.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_relay: bool,
+    /// `relay::relay_uid_hash` of the UIDs this contact relays to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relay_reachable: Vec<String>,
+    /// Marked verified by the user (local only, never sent to peers)
+    ///
+    /// With address review on, address changes learned for verified
+    /// contacts are staged for confirmation (see `storage::address_change`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub verified: bool,
+    /// Overrides for read receipts, typing and presence (local only)
+    #[serde(default, skip_serializing_if = "ContactPrivacy::is_default")]
+    pub privacy: ContactPrivacy,
+    /// The contact understands `edit` messages
+    ///
+    /// Learned from the contact's capabilities, never from tokens.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub supports_edits: bool,
+    /// Set for a temporary contact: when it and its chat are deleted
+    /// (local only, see `storage::ephemeral`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ephemeral: Option<Ephemeral>,
+    /// Trust tier deciding how much is sent to this contact (local only,
+    /// see `storage::trust`)
+    #[serde(default, skip_serializing_if = "TrustTier::is_normal")]
+    pub trust: TrustTier,
+    /// Expiry the contact's token asked for, when it was clamped to
+    /// `Settings::max_token_expiry_days` (local only, see `storage::bounds`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested_expiry: Option<DateTime<Utc>>,
+    /// SHA-256 fingerprint of the contact's TLS certificate, from its token
+    /// (see `transport::tls`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_fingerprint: Option<String>,
+    /// When the contact's capabilities were last fetched, or that it does not
+    /// answer probes; None if never learned (local only, see
+    /// `storage::capability_probe`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<CapabilityRecord>,
+    /// New-message alert style for this contact; None follows
+    /// `Settings::alert_style` (local only, see `tui::alerts`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alert_style: Option<AlertStyle>,
+    /// UTC offset in minutes set by hand, overriding the inferred one
+    /// (local only, see `storage::local_time`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utc_offset_override: Option<i32>,
+    /// Recent message timestamps against receive times, for inferring the
+    /// UTC offset (local only, see `storage::local_time`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub offset_samples: Vec<OffsetSample>,
+    /// When the contact last sent us authenticated traffic (local only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heard_at: Option<DateTime<Utc>>,
 }
 
 impl Contact {
     /// Create a new contact
+    ///
+    /// An empty `x25519_pubkey` is stored as missing.
     pub fn new(
         uid: String,
         ip: String,
@@ -36,21 +177,117 @@ impl Contact {
         x25519_pubkey: Vec<u8>,
         expiry: DateTime<Utc>,
     ) -> Self {
+        let mut contact = Self::without_x25519_key(uid, ip, pubkey, expiry);
+        if !x25519_pubkey.is_empty() {
+            contact.x25519_pubkey = Some(x25519_pubkey);
+        }
+        contact
+    }
+
+    /// Create a contact whose X25519 key is not known yet
+    pub fn without_x25519_key(uid: String, ip: String, pubkey: Vec<u8>, expiry: DateTime<Utc>) -> Self {
         Self {
             uid,
             ip,
             pubkey,
-            x25519_pubkey,
+            x25519_pubkey: None,
             expiry,
             is_active: true, // New contacts are active by default
+            notes: String::new(),
+            endpoints: Vec::new(),
+            is_relay: false,
+            relay_reachable: Vec::new(),
+            verified: false,
+            privacy: ContactPrivacy::default(),
+            supports_edits: false,
+            ephemeral: None,
+            trust: TrustTier::Normal,
+            requested_expiry: None,
+            cert_fingerprint: None,
+            capabilities: None,
+            alert_style: None,
+            utc_offset_override: None,
+            offset_samples: Vec::new(),
+            heard_at: None,
         }
     }
 
+    /// The X25519 key, if present and 32 bytes long
+    pub fn x25519_key(&self) -> Option<[u8; 32]> {
+        self.x25519_pubkey.as_deref().and_then(|key| key.try_into().ok())
+    }
+
+    /// Fill in a missing X25519 key
+    ///
+    /// A key that is already known is never replaced, so a peer cannot swap
+    /// keys through an upgrade response.
+    ///
+    /// # Returns
+    /// Whether the key was stored
+    ///
+    /// # Errors
+    /// Returns an error if `key` is not 32 bytes
+    pub fn fill_x25519_key(&mut self, key: &[u8]) -> Result<bool> {
+        if key.len() != 32 {
+            return Err(Error::Crypto(format!(
+                "Invalid X25519 key length: expected 32 bytes, got {}",
+                key.len()
+            )));
+        }
+        if self.x25519_key().is_some() {
+            return Ok(false);
+        }
+        self.x25519_pubkey = Some(key.to_vec());
+        Ok(true)
+    }
+
     /// Check if the contact has expired
     pub fn is_expired(&self) -> bool {
         Utc::now() > self.expiry
     }
 
+    /// Whether this is a temporary contact (see `storage::ephemeral`)
+    pub fn is_ephemeral(&self) -> bool {
+        self.ephemeral.is_some()
+    }
+
+    /// Record the timestamp the contact put on a message received at `now`
+    pub fn record_offset_sample(&mut self, sent_at_ms: i64, now: DateTime<Utc>) {
+        record_offset_sample(&mut self.offset_samples, OffsetSample::new(sent_at_ms, now), now);
+    }
+
+    /// The contact's UTC offset at `now`: set by hand, else inferred (see `storage::local_time`)
+    pub fn utc_offset(&self, now: DateTime<Utc>) -> Option<UtcOffset> {
+        UtcOffset::resolve(self.utc_offset_override, infer_utc_offset(&self.offset_samples, now))
+    }
+
+    /// Whether the contact demonstrably has our current token: it sent us
+    /// authenticated traffic after our token last changed at `token_changed_at`
+    pub fn has_current_token(&self, token_changed_at: DateTime<Utc>) -> bool {
+        self.heard_at.is_some_and(|heard_at| heard_at > token_changed_at)
+    }
+
+    /// Fingerprint of the Ed25519 key that signed the contact's token
+    ///
+    /// The first 16 bytes of the key in hex, in groups of four, e.g.
+    /// "1a2b 3c4d ...", for reading out when verifying.
+    pub fn signing_key_fingerprint(&self) -> String {
+        let hex = hex::encode(&self.pubkey[..self.pubkey.len().min(16)]);
+        hex.as_bytes()
+            .chunks(4)
+            .map(|group| String::from_utf8_lossy(group).into_owned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Whether the UID is the one derived from the signing key
+    ///
+    /// Always true for a contact from `parse_contact_token`; false when a
+    /// stored row was edited to pair a UID with someone else's key.
+    pub fn uid_matches_key(&self) -> bool {
+        UID::from_public_key(&self.pubkey).as_str() == self.uid
+    }
+
     /// Activate this contact
     pub fn activate(&mut self) {
         self.is_active = true;
@@ -61,19 +298,106 @@ impl Contact {
         self.is_active = false;
     }
 
+    /// Replace the notes for this contact
+    ///
+    /// # Errors
+    /// Returns an error if the notes exceed `MAX_CONTACT_NOTES_BYTES`
+    pub fn set_notes(&mut self, notes: &str) -> Result<()> {
+        if notes.len() > MAX_CONTACT_NOTES_BYTES {
+            return Err(Error::Storage(format!(
+                "Contact notes too large: {} bytes (max {})",
+                notes.len(),
+                MAX_CONTACT_NOTES_BYTES
+            )));
+        }
+        self.notes = notes.to_string();
+        Ok(())
+    }
+
+    /// Get the first `max_lines` lines of the notes
+    ///
+    /// # Returns
+    /// The preview text and whether any lines were cut off
+    pub fn notes_preview(&self, max_lines: usize) -> (String, bool) {
+        let total = self.notes.lines().count();
+        let preview = self.notes.lines().take(max_lines).collect::<Vec<_>>().join("\n");
+        (preview, total > max_lines)
+    }
+
+    /// Whether this contact relays to the UID hashed as `uid_hash`
+    ///
+    /// Never for a temporary or restricted contact: it is not told whom we
+    /// talk to.
+    pub fn reaches_via_relay(&self, uid_hash: &str) -> bool {
+        self.is_relay
+            && !self.is_expired()
+            && OutboundPolicy::for_contact(self).allows_introductions()
+            && self.relay_reachable.iter().any(|h| h == uid_hash)
+    }
+
+    /// Addresses to try when delivering to this contact, in order
+    ///
+    /// `preferred` (the address that last worked) goes first when it is one of
+    /// the advertised endpoints, followed by the remaining valid endpoints in
+    /// advertised order. Contacts without advertised endpoints use `ip`.
+    pub fn delivery_addresses(&self, preferred: Option<&str>) -> Vec<String> {
+        let now = Utc::now();
+        let mut addresses: Vec<String> = self
+            .endpoints
+            .iter()
+            .filter(|e| e.is_valid_at(now))
+            .map(|e| e.address.clone())
+            .collect();
+
+        if addresses.is_empty() {
+            return vec![self.ip.clone()];
+        }
+
+        if let Some(pos) = preferred.and_then(|p| addresses.iter().position(|a| a == p)) {
+            let address = addresses.remove(pos);
+            addresses.insert(0, address);
+        }
+        addresses
+    }
+
     /// Generate a signed token for this contact
     ///
+    /// Advertised endpoints are included when the contact has any, along with
+    /// the certificate fingerprint if set. A missing X25519 key is written as
+    /// empty, as older tokens did.
+    ///
     /// # Arguments
     /// * `keypair` - KeyPair to sign the token with
     ///
     /// # Returns
     /// A base64-encoded signed contact token string
     pub fn sign_token(&self, keypair: &crate::crypto::KeyPair) -> Result<String> {
+        if let Some(fingerprint) = &self.cert_fingerprint
+            && !self.endpoints.is_empty()
+        {
+            return generate_pinned_token(
+                &self.endpoints,
+                fingerprint,
+                &self.pubkey,
+                &keypair.private_key,
+                self.x25519_pubkey.as_deref().unwrap_or_default(),
+                self.expiry,
+            );
+        }
+        if !self.endpoints.is_empty() {
+            return generate_multi_endpoint_token(
+                &self.endpoints,
+                &self.pubkey,
+                &keypair.private_key,
+                self.x25519_pubkey.as_deref().unwrap_or_default(),
+                self.expiry,
+            );
+        }
         generate_contact_token(
             &self.ip,
             &self.pubkey,
             &keypair.private_key,
-            &self.x25519_pubkey,
+            self.x25519_pubkey.as_deref().unwrap_or_default(),
             self.expiry,
         )
     }
@@ -94,12 +418,20 @@ impl Contact {
 }
 
 /// Internal struct for contact token serialization (without signature)
+///
+/// `endpoints` is omitted when empty and `cert_fingerprint` when missing, so
+/// older tokens keep their original encoding (and their signatures still
+/// verify).
 #[derive(Debug, Serialize, Deserialize)]
 struct ContactTokenPayload {
     ip: String,
     pubkey: Vec<u8>,
     x25519_pubkey: Vec<u8>,
     expiry: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    endpoints: Vec<ContactEndpoint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cert_fingerprint: Option<String>,
 }
 
 /// Contact token with signature for integrity verification
@@ -138,8 +470,109 @@ pub fn generate_contact_token(
         pubkey: pubkey.to_vec(),
         x25519_pubkey: x25519_pubkey.to_vec(),
         expiry,
+        endpoints: Vec::new(),
+        cert_fingerprint: None,
     };
+    sign_token_payload(payload, privkey)
+}
 
+/// Generate a signed contact token advertising several endpoints
+///
+/// The signature covers the whole endpoint list. The first endpoint is also
+/// written as the token's `ip`, so clients that only understand
+/// single-endpoint tokens still get a usable address.
+///
+/// # Arguments
+/// * `endpoints` - Endpoints in preference order (at least one)
+/// * `pubkey` - Ed25519 public key bytes (for signature verification)
+/// * `privkey` - Ed25519 private key bytes (for signing the token)
+/// * `x25519_pubkey` - X25519 public key bytes (for key exchange)
+/// * `expiry` - Expiration timestamp
+///
+/// # Errors
+/// Returns an error if `endpoints` is empty or signing fails
+pub fn generate_multi_endpoint_token(
+    endpoints: &[ContactEndpoint],
+    pubkey: &[u8],
+    privkey: &[u8],
+    x25519_pubkey: &[u8],
+    expiry: DateTime<Utc>,
+) -> Result<String> {
+    let first = endpoints
+        .first()
+        .ok_or_else(|| Error::Storage("Contact token needs at least one endpoint".to_string()))?;
+
+    let payload = ContactTokenPayload {
+        ip: first.address.clone(),
+        pubkey: pubkey.to_vec(),
+        x25519_pubkey: x25519_pubkey.to_vec(),
+        expiry,
+        endpoints: endpoints.to_vec(),
+        cert_fingerprint: None,
+    };
+    sign_token_payload(payload, privkey)
+}
+
+/// Generate a signed contact token pinning our TLS certificate
+///
+/// Like `generate_multi_endpoint_token`, with the certificate's fingerprint
+/// signed along. The token's `ip` is the first endpoint that is not
+/// `https://`, so clients without TLS support ping an address they can reach.
+///
+/// # Arguments
+/// * `endpoints` - Endpoints in preference order (at least one)
+/// * `cert_fingerprint` - Hex SHA-256 fingerprint of our certificate
+/// * `pubkey` - Ed25519 public key bytes (for signature verification)
+/// * `privkey` - Ed25519 private key bytes (for signing the token)
+/// * `x25519_pubkey` - X25519 public key bytes (for key exchange)
+/// * `expiry` - Expiration timestamp
+///
+/// # Errors
+/// Returns an error if `endpoints` is empty or signing fails
+pub fn generate_pinned_token(
+    endpoints: &[ContactEndpoint],
+    cert_fingerprint: &str,
+    pubkey: &[u8],
+    privkey: &[u8],
+    x25519_pubkey: &[u8],
+    expiry: DateTime<Utc>,
+) -> Result<String> {
+    let ip = endpoints
+        .iter()
+        .find(|e| e.scheme() != TLS_ADDRESS_SCHEME)
+        .or_else(|| endpoints.first())
+        .ok_or_else(|| Error::Storage("Contact token needs at least one endpoint".to_string()))?;
+
+    let payload = ContactTokenPayload {
+        ip: ip.address.clone(),
+        pubkey: pubkey.to_vec(),
+        x25519_pubkey: x25519_pubkey.to_vec(),
+        expiry,
+        endpoints: endpoints.to_vec(),
+        cert_fingerprint: Some(cert_fingerprint.to_string()),
+    };
+    sign_token_payload(payload, privkey)
+}
+
+/// Endpoints for a token pinning our certificate
+///
+/// Each plain HTTP endpoint gets an `https://` twin (same host and port, the
+/// listener serves both); the twins come first, followed by `endpoints`
+/// unchanged as the fallback for peers without TLS.
+pub fn pinned_endpoints(endpoints: &[ContactEndpoint]) -> Vec<ContactEndpoint> {
+    let tls = endpoints
+        .iter()
+        .filter(|e| e.scheme() == DEFAULT_ADDRESS_SCHEME)
+        .map(|e| ContactEndpoint {
+            address: format!("{}://{}", TLS_ADDRESS_SCHEME, split_address_scheme(&e.address).1),
+            label: format!("{} (tls)", e.label),
+            valid_until: e.valid_until,
+        });
+    tls.chain(endpoints.iter().cloned()).collect()
+}
+
+/// Sign a token payload and encode the token
+fn sign_token_payload(payload: ContactTokenPayload, privkey: &[u8]) -> Result<String> {
     // Serialize payload to CBOR (this is what gets signed)
     let payload_cbor = serde_cbor::to_vec(&payload)
         .map_err(|e| Error::CborSerialization(format!("Failed to serialize token payload: {}", e)))?;
@@ -150,19 +583,114 @@ pub fn generate_contact_token(
     let signature = crate::crypto::sign_contact_token(&privkey_array, &payload_cbor)?;
 
     // Create token with signature
-    let token_data = ContactTokenData {
+    encode_token_data(&ContactTokenData {
         payload,
         signature: signature.to_vec(),
-    };
+    })
+}
 
-    // Serialize complete token (payload + signature) to CBOR
-    let cbor = serde_cbor::to_vec(&token_data)
+/// Serialize a signed token (payload + signature) to CBOR and encode it as base64 URL-safe
+fn encode_token_data(data: &ContactTokenData) -> Result<String> {
+    let cbor = serde_cbor::to_vec(data)
         .map_err(|e| Error::CborSerialization(format!("Failed to serialize contact token: {}", e)))?;
-
-    // Encode as base64 URL-safe
     Ok(URL_SAFE_NO_PAD.encode(cbor))
 }
 
+/// Decode a base64 token into its CBOR structure (not verified)
+fn decode_token_data(token: &str) -> Result<ContactTokenData> {
+    let cbor = URL_SAFE_NO_PAD
+        .decode(token)
+        .map_err(|e| Error::Storage(format!("Invalid base64 token: {}", e)))?;
+    serde_cbor::from_slice(&cbor).map_err(|e| Error::CborSerialization(format!("Invalid token data: {}", e)))
+}
+
+/// Version byte leading the compact token layout
+const COMPACT_TOKEN_VERSION: u8 = 1;
+
+/// Re-encode a token in the compact binary layout used by `token_armor`
+///
+/// CBOR spends two bytes on most key bytes, which would double the armored
+/// form. The compact layout is: version, the Ed25519 key, X25519 key and
+/// signature (each behind a length byte), expiry seconds (i64) and
+/// nanoseconds (u32), the address behind a u16 length, then endpoints and
+/// certificate fingerprint as CBOR if either is set. Integers are
+/// big-endian. The signature is carried, not checked.
+///
+/// # Errors
+/// Returns an error if the token does not decode
+pub(super) fn compact_token(token: &str) -> Result<Vec<u8>> {
+    let data = decode_token_data(token)?;
+    let payload = &data.payload;
+    let mut bytes = vec![COMPACT_TOKEN_VERSION];
+    for field in [&payload.pubkey, &payload.x25519_pubkey, &data.signature] {
+        let len = u8::try_from(field.len()).map_err(|_| Error::Storage("Token key or signature is too long".to_string()))?;
+        bytes.push(len);
+        bytes.extend_from_slice(field);
+    }
+    bytes.extend_from_slice(&payload.expiry.timestamp().to_be_bytes());
+    bytes.extend_from_slice(&payload.expiry.timestamp_subsec_nanos().to_be_bytes());
+    let ip_len = u16::try_from(payload.ip.len()).map_err(|_| Error::Storage("Token address is too long".to_string()))?;
+    bytes.extend_from_slice(&ip_len.to_be_bytes());
+    bytes.extend_from_slice(payload.ip.as_bytes());
+    if !payload.endpoints.is_empty() || payload.cert_fingerprint.is_some() {
+        let extras = serde_cbor::to_vec(&(&payload.endpoints, &payload.cert_fingerprint))
+            .map_err(|e| Error::CborSerialization(format!("Failed to serialize token endpoints: {}", e)))?;
+        bytes.extend_from_slice(&extras);
+    }
+    Ok(bytes)
+}
+
+/// Rebuild the base64 token from `compact_token` output
+///
+/// # Errors
+/// Returns `Error::Storage` if the bytes are truncated or of an unknown version
+pub(super) fn expand_compact_token(bytes: &[u8]) -> Result<String> {
+    fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+        if bytes.len() < n {
+            return Err(Error::Storage("Compact token is truncated".to_string()));
+        }
+        let (head, rest) = bytes.split_at(n);
+        *bytes = rest;
+        Ok(head)
+    }
+    fn take_array<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(take(bytes, N)?);
+        Ok(array)
+    }
+    fn take_prefixed(bytes: &mut &[u8]) -> Result<Vec<u8>> {
+        let len = take(bytes, 1)?[0] as usize;
+        Ok(take(bytes, len)?.to_vec())
+    }
+
+    let mut rest = bytes;
+    let version = take(&mut rest, 1)?[0];
+    if version != COMPACT_TOKEN_VERSION {
+        return Err(Error::Storage(format!("Unknown compact token version {}", version)));
+    }
+    let pubkey = take_prefixed(&mut rest)?;
+    let x25519_pubkey = take_prefixed(&mut rest)?;
+    let signature = take_prefixed(&mut rest)?;
+    let secs = i64::from_be_bytes(take_array(&mut rest)?);
+    let nanos = u32::from_be_bytes(take_array(&mut rest)?);
+    let expiry = DateTime::from_timestamp(secs, nanos)
+        .ok_or_else(|| Error::Storage("Compact token expiry is out of range".to_string()))?;
+    let ip_len = u16::from_be_bytes(take_array(&mut rest)?) as usize;
+    let ip = String::from_utf8(take(&mut rest, ip_len)?.to_vec())
+        .map_err(|_| Error::Storage("Compact token address is not UTF-8".to_string()))?;
+    let (endpoints, cert_fingerprint) = if rest.is_empty() {
+        (Vec::new(), None)
+    } else {
+        serde_cbor::from_slice(rest)
+            .map_err(|e| Error::CborSerialization(format!("Invalid token endpoints: {}", e)))?
+    };
+
+    encode_token_data(&ContactTokenData {
+        payload: ContactTokenPayload { ip, pubkey, x25519_pubkey, expiry, endpoints, cert_fingerprint },
+        signature,
+    })
+}
+
 /// Parse a contact token, verify signature, and validate expiry
 ///
 /// Decodes a base64 URL-safe token, deserializes CBOR data, verifies the Ed25519 signature,
@@ -171,6 +699,9 @@ pub fn generate_contact_token(
 /// # Arguments
 /// * `token` - Base64-encoded signed contact token string
 ///
+/// Both single-endpoint and multi-endpoint tokens are accepted; advertised
+/// endpoints end up in `Contact::endpoints`.
+///
 /// # Returns
 /// A `Contact` struct if the token is valid, signature is correct, and not expired
 ///
@@ -181,14 +712,24 @@ pub fn generate_contact_token(
 /// - Signature verification fails (invalid or tampered token)
 /// - Contact has expired
 pub fn parse_contact_token(token: &str) -> Result<Contact> {
-    // Decode from base64
-    let cbor = URL_SAFE_NO_PAD
-        .decode(token)
-        .map_err(|e| Error::Storage(format!("Invalid base64 token: {}", e)))?;
+    let contact = parse_contact_token_any_expiry(token)?;
+    if Utc::now() > contact.expiry {
+        return Err(Error::Storage("Contact token has expired".to_string()));
+    }
+    Ok(contact)
+}
 
-    // Deserialize from CBOR
-    let data: ContactTokenData = serde_cbor::from_slice(&cbor)
-        .map_err(|e| Error::CborSerialization(format!("Invalid token data: {}", e)))?;
+/// Parse and verify a signed contact token without checking its expiry
+///
+/// For callers that report an expired token differently from a broken one
+/// (e.g. the batch import review). The caller checks `Contact::expiry`.
+///
+/// # Errors
+/// Returns an error if decoding, deserialization or signature verification
+/// fails, or `Error::InvalidAddress` if an address is not a plain `host:port`
+pub fn parse_contact_token_any_expiry(token: &str) -> Result<Contact> {
+    // Decode from base64 and deserialize from CBOR
+    let data = decode_token_data(token)?;
 
     // Verify signature length
     if data.signature.len() != 64 {
@@ -213,20 +754,19 @@ pub fn parse_contact_token(token: &str) -> Result<Contact> {
         return Err(Error::Crypto("Contact token signature verification failed (token may be tampered with)".to_string()));
     }
 
-    // Validate expiry
-    if Utc::now() > data.payload.expiry {
-        return Err(Error::Storage("Contact token has expired".to_string()));
-    }
-
     // Generate UID from Ed25519 public key
     let uid = UID::from_public_key(&data.payload.pubkey);
 
-    // Create contact
-    Ok(Contact::new(
+    // Create contact (an empty X25519 key stays missing)
+    let mut contact = Contact::new(
         uid.to_string(),
         data.payload.ip,
         data.payload.pubkey,
         data.payload.x25519_pubkey,
         data.payload.expiry,
-    ))
+    );
+    contact.endpoints = data.payload.endpoints;
+    contact.cert_fingerprint = data.payload.cert_fingerprint;
+    super::address::validate_contact_addresses(&contact)?;
+    Ok(contact)
 }
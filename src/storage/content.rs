@@ -0,0 +1,147 @@
+//! Binary message content: type detection, size formatting and downloads
+//!
+//! Peers can send arbitrary bytes before there is any file transfer
+//! protocol. Known formats are recognised by their magic bytes so the chat
+//! view can show a typed placeholder, and any binary content can be saved
+//! to the downloads directory under a generated name.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory binary content is saved to (production)
+pub const DOWNLOADS_DIR: &str = "./app_data/downloads";
+
+/// Characters of the message ID kept in a generated file name
+const FILE_NAME_ID_CHARS: usize = 12;
+
+/// Kind of content carried by a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+    /// Valid UTF-8 with no known magic bytes
+    Text,
+    /// PNG image
+    Png,
+    /// JPEG image
+    Jpeg,
+    /// PDF document
+    Pdf,
+    /// ZIP archive
+    Zip,
+    /// Bytes of an unknown type
+    Binary,
+}
+
+impl ContentKind {
+    /// Detect the kind of `content`
+    ///
+    /// Magic bytes win over UTF-8 validity, so a PDF made only of ASCII is
+    /// still a PDF.
+    pub fn detect(content: &[u8]) -> Self {
+        const MAGIC: &[(&[u8], ContentKind)] = &[
+            (b"\x89PNG\r\n\x1a\n", ContentKind::Png),
+            (b"\xff\xd8\xff", ContentKind::Jpeg),
+            (b"%PDF-", ContentKind::Pdf),
+            (b"PK\x03\x04", ContentKind::Zip),
+            (b"PK\x05\x06", ContentKind::Zip),
+        ];
+        if let Some((_, kind)) = MAGIC.iter().find(|(magic, _)| content.starts_with(magic)) {
+            return *kind;
+        }
+        if std::str::from_utf8(content).is_ok() {
+            ContentKind::Text
+        } else {
+            ContentKind::Binary
+        }
+    }
+
+    /// MIME type, or None for text and unknown binary
+    pub fn mime(self) -> Option<&'static str> {
+        match self {
+            ContentKind::Png => Some("image/png"),
+            ContentKind::Jpeg => Some("image/jpeg"),
+            ContentKind::Pdf => Some("application/pdf"),
+            ContentKind::Zip => Some("application/zip"),
+            ContentKind::Text | ContentKind::Binary => None,
+        }
+    }
+
+    /// File extension used when saving
+    pub fn extension(self) -> &'static str {
+        match self {
+            ContentKind::Text => "txt",
+            ContentKind::Png => "png",
+            ContentKind::Jpeg => "jpg",
+            ContentKind::Pdf => "pdf",
+            ContentKind::Zip => "zip",
+            ContentKind::Binary => "bin",
+        }
+    }
+
+    /// Placeholder shown instead of non-text content, e.g. "[image/png, 340 KB]"
+    pub fn placeholder(self, len: usize) -> String {
+        match self.mime() {
+            Some(mime) => format!("[{}, {}]", mime, format_size(len)),
+            None => format!("[binary, {}]", format_size(len)),
+        }
+    }
+}
+
+/// Human-readable size: "512 bytes", "340 KB", "2.4 MB"
+pub fn format_size(bytes: usize) -> String {
+    const KB: usize = 1024;
+    const MB: usize = 1024 * KB;
+    if bytes == 1 {
+        "1 byte".to_string()
+    } else if bytes < KB {
+        format!("{} bytes", bytes)
+    } else if bytes < MB {
+        format!("{} KB", (bytes + KB / 2) / KB)
+    } else {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    }
+}
+
+/// Generated file name for a message's content, before collision handling
+///
+/// Built only from the message ID's ASCII letters, digits and dashes (IDs
+/// come from peers) plus the extension for `kind`.
+pub fn download_file_name(message_id: &str, kind: ContentKind) -> String {
+    let id: String = message_id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .take(FILE_NAME_ID_CHARS)
+        .collect();
+    let id = if id.is_empty() { "message".to_string() } else { id };
+    format!("pure2p-{}.{}", id, kind.extension())
+}
+
+/// Create a new file in `dir` named `name`, adding "-1", "-2", ... before the
+/// extension if it is taken
+///
+/// Never overwrites: each candidate is opened with `create_new`. Creates
+/// `dir` if needed.
+pub fn create_download_file(dir: &Path, name: &str) -> io::Result<(PathBuf, File)> {
+    std::fs::create_dir_all(dir)?;
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) => (stem, format!(".{}", extension)),
+        None => (name, String::new()),
+    };
+
+    for n in 0..1000 {
+        let candidate = if n == 0 {
+            dir.join(name)
+        } else {
+            dir.join(format!("{}-{}{}", stem, n, extension))
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&candidate) {
+            Ok(file) => return Ok((candidate, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("too many files named {} in {}", name, dir.display()),
+    ))
+}
@@ -0,0 +1,77 @@
+//! Deferred writes while the database is locked
+//!
+//! When a save still fails after `Storage::with_write_retry` gives up because
+//! another process holds a lock on the database, the in-memory state stays the
+//! source of truth and the change is counted here until a later flush succeeds.
+
+use crate::Error;
+use std::time::{Duration, Instant};
+
+/// Minimum time between flush attempts while writes are deferred
+pub const DEFERRED_FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Tracks changes that could not be written because the database was locked
+#[derive(Debug, Default)]
+pub struct DeferredWrites {
+    /// Number of changes waiting to be written
+    pending: usize,
+    /// Last storage error seen while writing
+    last_error: Option<String>,
+    /// When a flush was last attempted
+    last_attempt: Option<Instant>,
+}
+
+impl DeferredWrites {
+    /// Create an empty deferred writes buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a change that could not be written
+    pub fn record(&mut self, error: &Error) {
+        self.pending += 1;
+        self.last_error = Some(error.to_string());
+        self.last_attempt = Some(Instant::now());
+    }
+
+    /// Record a failed flush attempt (does not add a change)
+    pub fn flush_failed(&mut self, error: &Error) {
+        self.last_error = Some(error.to_string());
+    }
+
+    /// Clear the buffer after a successful write
+    pub fn flushed(&mut self) {
+        self.pending = 0;
+        self.last_error = None;
+        self.last_attempt = None;
+    }
+
+    /// Number of changes waiting to be written
+    pub fn pending_count(&self) -> usize {
+        self.pending
+    }
+
+    /// Check whether any changes are waiting to be written
+    pub fn is_pending(&self) -> bool {
+        self.pending > 0
+    }
+
+    /// Last storage error seen while writing
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Check whether a flush should be attempted now, and note the attempt if so
+    pub fn start_flush_attempt(&mut self, now: Instant) -> bool {
+        if !self.is_pending() {
+            return false;
+        }
+        let due = self
+            .last_attempt
+            .is_none_or(|last| now.duration_since(last) >= DEFERRED_FLUSH_INTERVAL);
+        if due {
+            self.last_attempt = Some(now);
+        }
+        due
+    }
+}
@@ -0,0 +1,102 @@
+//! Temporary (ephemeral) contacts for one-off conversations
+//!
+//! A contact imported as temporary carries an `Ephemeral` marker with the
+//! time it ends. Until then it works like any other contact, except that it
+//! is left out of broadcasts (presence, relay capabilities) and is never
+//! introduced to other contacts through the relay. When the lifetime ends,
+//! `AppState::sweep_ephemeral` removes the contact and its chat; the caller
+//! deletes the rows and purges the queue. Nothing is retained afterwards
+//! (no kept notes), so importing the contact again starts fresh.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Hours before the end of a temporary contact at which its chat is warned
+pub const EPHEMERAL_WARNING_HOURS: i64 = 24;
+
+/// Lifetimes offered on the Import screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EphemeralLifetime {
+    /// 24 hours
+    Day,
+    /// 7 days
+    Week,
+}
+
+impl EphemeralLifetime {
+    /// Length of the lifetime
+    pub fn duration(self) -> Duration {
+        match self {
+            EphemeralLifetime::Day => Duration::hours(24),
+            EphemeralLifetime::Week => Duration::days(7),
+        }
+    }
+
+    /// Short label, e.g. "24h"
+    pub fn label(self) -> &'static str {
+        match self {
+            EphemeralLifetime::Day => "24h",
+            EphemeralLifetime::Week => "7d",
+        }
+    }
+
+    /// Next choice of the Import screen toggle: permanent → 24h → 7d → permanent
+    pub fn cycle(choice: Option<Self>) -> Option<Self> {
+        match choice {
+            None => Some(EphemeralLifetime::Day),
+            Some(EphemeralLifetime::Day) => Some(EphemeralLifetime::Week),
+            Some(EphemeralLifetime::Week) => None,
+        }
+    }
+}
+
+/// Marker of a temporary contact (local only, never sent to peers)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ephemeral {
+    /// When the contact and its chat are deleted
+    pub until: DateTime<Utc>,
+    /// The pre-expiry warning was added to the chat
+    #[serde(default)]
+    pub warned: bool,
+}
+
+impl Ephemeral {
+    /// Marker for a contact imported at `now` with `lifetime`
+    pub fn new(lifetime: EphemeralLifetime, now: DateTime<Utc>) -> Self {
+        Self { until: now + lifetime.duration(), warned: false }
+    }
+
+    /// Whether the lifetime has ended at `now`
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        now >= self.until
+    }
+
+    /// Whether the chat should be warned at `now` (and has not been yet)
+    pub fn warning_due_at(&self, now: DateTime<Utc>) -> bool {
+        !self.warned && !self.is_expired_at(now) && self.until - now <= Duration::hours(EPHEMERAL_WARNING_HOURS)
+    }
+}
+
+/// Text of the system message added to a temporary contact's chat before it ends
+pub fn ephemeral_warning_text(until: DateTime<Utc>) -> String {
+    format!(
+        "This temporary contact and chat will be deleted at {}. Press k in the contact details to keep them.",
+        until.format("%Y-%m-%d %H:%M UTC")
+    )
+}
+
+/// What `AppState::sweep_ephemeral` did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EphemeralSweep {
+    /// Contacts whose chat received the pre-expiry warning
+    pub warned: Vec<String>,
+    /// Contacts removed (with their chats) because their lifetime ended
+    pub expired: Vec<String>,
+}
+
+impl EphemeralSweep {
+    /// Whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.warned.is_empty() && self.expired.is_empty()
+    }
+}
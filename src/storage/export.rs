@@ -0,0 +1,172 @@
+//! Machine-readable chat export (JSON Lines)
+//!
+//! `Storage::export_chat_jsonl` writes one `ExportedMessage` per line, oldest
+//! first, reading the chat a page at a time so memory stays flat however long
+//! the history is. Every line carries `version` (`JSONL_EXPORT_VERSION`);
+//! consumers should reject versions they do not know. Within a version,
+//! fields are only ever added, never renamed or removed.
+//!
+//! Example line (version 1):
+//!
+//! ```text
+//! {"version":1,"id":"m1","direction":"incoming","sent_at":1700000000000,"received_at":null,
+//!  "delivery_status":"sent","reply_to":null,"reactions":[],"metadata":{"lang":"en"},
+//!  "content_type":"text","content_bytes":2,"content":"hi","content_sha256":null,"protection":"sealed"}
+//! ```
+
+use crate::storage::{ContentKind, DeliveryStatus, Message, MessageMetadata, Protection};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+
+/// Version written in every exported line
+pub const JSONL_EXPORT_VERSION: u32 = 1;
+
+/// Messages read from the database per page while exporting
+pub const EXPORT_PAGE_SIZE: usize = 256;
+
+/// Who wrote an exported message, seen from this device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportDirection {
+    /// Sent by the contact
+    Incoming,
+    /// Sent by us
+    Outgoing,
+    /// Local notice (e.g. "older messages were removed")
+    System,
+}
+
+impl ExportDirection {
+    /// Direction of `message` in the chat with `contact_uid`
+    pub fn of(message: &Message, contact_uid: &str) -> Self {
+        if message.is_system() {
+            ExportDirection::System
+        } else if message.sender == contact_uid {
+            ExportDirection::Incoming
+        } else {
+            ExportDirection::Outgoing
+        }
+    }
+}
+
+/// How message content appears in the export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContentMode {
+    /// Text inline; binary content as a SHA-256 reference
+    #[default]
+    Inline,
+    /// Every message as a SHA-256 reference, no content at all
+    Reference,
+    /// Neither content nor hash (metadata only)
+    Omit,
+}
+
+/// Filters and content mode for `Storage::export_chat_jsonl`
+#[derive(Debug, Clone, Default)]
+pub struct JsonlExportOptions {
+    /// Only messages sent at or after this time (ms since epoch)
+    pub since: Option<i64>,
+    /// Only messages sent at or before this time (ms since epoch)
+    pub until: Option<i64>,
+    /// Only messages in this direction (None exports all)
+    pub direction: Option<ExportDirection>,
+    /// How content is written
+    pub content: ContentMode,
+}
+
+impl JsonlExportOptions {
+    /// Whether `message` passes the date range and direction filters
+    pub fn includes(&self, message: &Message, contact_uid: &str) -> bool {
+        self.since.is_none_or(|since| message.timestamp >= since)
+            && self.until.is_none_or(|until| message.timestamp <= until)
+            && self.direction.is_none_or(|direction| ExportDirection::of(message, contact_uid) == direction)
+    }
+}
+
+/// One line of a JSON Lines chat export (schema `JSONL_EXPORT_VERSION`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedMessage {
+    /// Schema version of this line
+    pub version: u32,
+    /// Message ID
+    pub id: String,
+    /// Who wrote the message
+    pub direction: ExportDirection,
+    /// Time the sender created the message (ms since epoch)
+    pub sent_at: i64,
+    /// Time this device received it (not recorded yet, always null in version 1)
+    pub received_at: Option<i64>,
+    /// "sent", "delivered", "pending" or "failed"
+    pub delivery_status: String,
+    /// Message this one replies to (replies do not exist yet, always null in version 1)
+    pub reply_to: Option<String>,
+    /// Reactions to the message (reactions do not exist yet, always empty in version 1)
+    pub reactions: Vec<String>,
+    /// Typed metadata attached by the sender
+    pub metadata: MessageMetadata,
+    /// "text", a MIME type, or "binary"
+    pub content_type: String,
+    /// Content length in bytes
+    pub content_bytes: usize,
+    /// UTF-8 content (text in `ContentMode::Inline` only)
+    pub content: Option<String>,
+    /// Hex SHA-256 of the content (binary in `ContentMode::Inline`, everything in `Reference`)
+    pub content_sha256: Option<String>,
+    /// "plaintext" or "sealed" as recorded on the wire (null if not recorded;
+    /// added in version 1, absent from older lines)
+    #[serde(default)]
+    pub protection: Option<Protection>,
+}
+
+impl ExportedMessage {
+    /// Export line for `message` in the chat with `contact_uid`
+    pub fn new(message: &Message, contact_uid: &str, mode: ContentMode) -> Self {
+        let kind = message.content_kind();
+        let inline = mode == ContentMode::Inline && kind == ContentKind::Text;
+        let reference = mode == ContentMode::Reference || (mode == ContentMode::Inline && !inline);
+
+        Self {
+            version: JSONL_EXPORT_VERSION,
+            id: message.id.clone(),
+            direction: ExportDirection::of(message, contact_uid),
+            sent_at: message.timestamp,
+            received_at: None,
+            delivery_status: delivery_status_name(message.delivery_status).to_string(),
+            reply_to: None,
+            reactions: Vec::new(),
+            metadata: message.metadata.clone(),
+            content_type: kind.mime().unwrap_or(content_type_name(kind)).to_string(),
+            content_bytes: message.content.len(),
+            content: inline.then(|| String::from_utf8_lossy(&message.content).into_owned()),
+            content_sha256: reference.then(|| hex::encode(digest(&SHA256, &message.content))),
+            protection: message.protection,
+        }
+    }
+}
+
+/// File name for a chat export, before collision handling
+///
+/// Built only from the contact UID's ASCII letters and digits.
+pub fn export_file_name(contact_uid: &str) -> String {
+    let uid: String = contact_uid.chars().filter(|c| c.is_ascii_alphanumeric()).take(16).collect();
+    let uid = if uid.is_empty() { "chat".to_string() } else { uid };
+    format!("pure2p-chat-{}.jsonl", uid)
+}
+
+/// Lowercase name of a delivery status, as exported
+fn delivery_status_name(status: DeliveryStatus) -> &'static str {
+    match status {
+        DeliveryStatus::Sent => "sent",
+        DeliveryStatus::Delivered => "delivered",
+        DeliveryStatus::Pending => "pending",
+        DeliveryStatus::Failed => "failed",
+    }
+}
+
+/// Exported type of content without a MIME type
+fn content_type_name(kind: ContentKind) -> &'static str {
+    match kind {
+        ContentKind::Text => "text",
+        _ => "binary",
+    }
+}
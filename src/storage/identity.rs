@@ -0,0 +1,180 @@
+//! Identity consistency checks for contacts
+//!
+//! A UID is the fingerprint of a contact's Ed25519 key, so every stored
+//! contact must satisfy `uid == UID::from_public_key(pubkey)`, and no key may
+//! appear under two UIDs. Contacts entering the app (import, ping) are checked
+//! with `check_incoming_contact`; rows that predate these checks are found by
+//! `scan_contacts` (run from `Storage::verify_integrity`). Anything that does
+//! not fit is kept as an `IdentityConflict` for the user to review instead of
+//! becoming a second contact.
+
+use crate::{crypto::UID, storage::contact::Contact, Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What is inconsistent about a contact's identity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConflictKind {
+    /// The stored UID is not derived from the stored key
+    UidKeyMismatch,
+    /// The key already belongs to a contact stored under another UID
+    DuplicateKey,
+    /// The UID is stored with a different key
+    KeyChanged,
+}
+
+impl ConflictKind {
+    /// Stable name used in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UidKeyMismatch => "uid_key_mismatch",
+            Self::DuplicateKey => "duplicate_key",
+            Self::KeyChanged => "key_changed",
+        }
+    }
+
+    /// Parse a name written by `as_str`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "uid_key_mismatch" => Some(Self::UidKeyMismatch),
+            "duplicate_key" => Some(Self::DuplicateKey),
+            "key_changed" => Some(Self::KeyChanged),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UidKeyMismatch => write!(f, "UID does not match its key"),
+            Self::DuplicateKey => write!(f, "key already known under another UID"),
+            Self::KeyChanged => write!(f, "UID already known with another key"),
+        }
+    }
+}
+
+/// A contact held back for review instead of being stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityConflict {
+    /// What is inconsistent
+    pub kind: ConflictKind,
+    /// UID of the stored contact it conflicts with
+    pub existing_uid: String,
+    /// The contact that was held back (or the inconsistent stored row)
+    pub contact: Contact,
+    /// When the conflict was found
+    pub detected_at: DateTime<Utc>,
+}
+
+impl IdentityConflict {
+    /// Create a conflict detected now
+    pub fn new(kind: ConflictKind, existing_uid: impl Into<String>, contact: Contact) -> Self {
+        Self {
+            kind,
+            existing_uid: existing_uid.into(),
+            contact,
+            detected_at: Utc::now(),
+        }
+    }
+
+    /// Whether two entries describe the same conflict (detection time aside)
+    pub fn same_as(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.existing_uid == other.existing_uid
+            && self.contact.uid == other.contact.uid
+            && self.contact.pubkey == other.contact.pubkey
+    }
+}
+
+/// How an incoming contact relates to the stored ones
+#[derive(Debug, Clone)]
+pub enum IdentityCheck {
+    /// Nobody with this UID or key is stored
+    New,
+    /// Same UID and key as the stored contact at this index (the address may differ)
+    Known(usize),
+    /// Conflicts with a stored contact
+    Conflict(Box<IdentityConflict>),
+}
+
+/// Check that a contact's UID is derived from its public key
+///
+/// # Errors
+/// `Error::IdentityMismatch` naming the claimed and derived UIDs
+pub fn verify_contact_uid(contact: &Contact) -> Result<()> {
+    let derived = UID::from_public_key(&contact.pubkey);
+    if derived.as_str() == contact.uid {
+        Ok(())
+    } else {
+        Err(Error::IdentityMismatch(format!(
+            "contact claims UID {} but its key belongs to {}",
+            contact.uid, derived
+        )))
+    }
+}
+
+/// Classify a contact about to be stored
+///
+/// # Errors
+/// `Error::IdentityMismatch` if the contact's own UID and key disagree
+pub fn check_incoming_contact(contacts: &[Contact], incoming: &Contact) -> Result<IdentityCheck> {
+    verify_contact_uid(incoming)?;
+
+    if let Some(existing) = contacts.iter().find(|c| c.pubkey == incoming.pubkey && c.uid != incoming.uid) {
+        return Ok(IdentityCheck::Conflict(Box::new(IdentityConflict::new(
+            ConflictKind::DuplicateKey,
+            existing.uid.clone(),
+            incoming.clone(),
+        ))));
+    }
+
+    match contacts.iter().position(|c| c.uid == incoming.uid) {
+        Some(index) if contacts[index].pubkey == incoming.pubkey => Ok(IdentityCheck::Known(index)),
+        Some(index) => Ok(IdentityCheck::Conflict(Box::new(IdentityConflict::new(
+            ConflictKind::KeyChanged,
+            contacts[index].uid.clone(),
+            incoming.clone(),
+        )))),
+        None => Ok(IdentityCheck::New),
+    }
+}
+
+/// Index of the contact that rightfully owns `pubkey`
+///
+/// That is the one whose UID derives from the key, or else the first one
+/// stored with it.
+pub fn canonical_contact_for_key(contacts: &[Contact], pubkey: &[u8]) -> Option<usize> {
+    let derived = UID::from_public_key(pubkey);
+    contacts
+        .iter()
+        .position(|c| c.pubkey == pubkey && c.uid == derived.as_str())
+        .or_else(|| contacts.iter().position(|c| c.pubkey == pubkey))
+}
+
+/// Find inconsistent identities among stored contacts
+///
+/// Reports every contact whose UID does not derive from its key, and every
+/// contact sharing a key with its canonical owner (see
+/// `canonical_contact_for_key`).
+pub fn scan_contacts(contacts: &[Contact]) -> Vec<IdentityConflict> {
+    let mut conflicts = Vec::new();
+    for (index, contact) in contacts.iter().enumerate() {
+        let canonical = canonical_contact_for_key(contacts, &contact.pubkey);
+        match canonical {
+            Some(owner) if owner != index => conflicts.push(IdentityConflict::new(
+                ConflictKind::DuplicateKey,
+                contacts[owner].uid.clone(),
+                contact.clone(),
+            )),
+            _ if verify_contact_uid(contact).is_err() => conflicts.push(IdentityConflict::new(
+                ConflictKind::UidKeyMismatch,
+                contact.uid.clone(),
+                contact.clone(),
+            )),
+            _ => {}
+        }
+    }
+    conflicts
+}
@@ -0,0 +1,670 @@
+//! Pending introductions: people expected to send a token, imported from
+//! another messenger's contact list
+//!
+//! A CSV or vCard export (names, emails, phone numbers) becomes a list of
+//! `PendingIntroduction`s, each a display name and free-form notes and
+//! nothing Pure2P can reach yet. The list is purely local: it is stored in
+//! the database and never sent to anyone.
+//!
+//! When a token is imported later, `best_match` picks the pending entry
+//! whose name matches the name typed before the token ("Alice: <token>"),
+//! and `PendingIntroduction::carry_over` copies its name and notes into the
+//! new contact's notes; the entry is then removed.
+//!
+//! Files are decoded leniently (`decode_text`: UTF-8 with or without a BOM,
+//! UTF-16 with a BOM, otherwise Latin-1), and every row or card that cannot
+//! be used is reported with its line instead of failing the whole file.
+
+use crate::storage::{Contact, MAX_CONTACT_NOTES_BYTES};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Longest accepted display name, in characters
+pub const MAX_INTRODUCTION_NAME_CHARS: usize = 100;
+
+/// Lowest `name_similarity` at which a pending entry is offered for linking
+pub const FUZZY_MATCH_THRESHOLD: f64 = 0.75;
+
+/// Someone expected to send a token, with what we know about them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingIntroduction {
+    /// Local ID
+    pub id: String,
+    /// Display name from the imported list
+    pub name: String,
+    /// Free-form notes (emails, phone numbers and notes from the list)
+    pub notes: String,
+    /// When the entry was imported
+    pub added_at: DateTime<Utc>,
+}
+
+impl PendingIntroduction {
+    /// New entry with a fresh ID
+    pub fn new(name: &str, notes: &str, added_at: DateTime<Utc>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            notes: notes.to_string(),
+            added_at,
+        }
+    }
+
+    /// Append the name and notes to `contact`'s notes
+    ///
+    /// Notes already on the contact come first; the result is cut at
+    /// `MAX_CONTACT_NOTES_BYTES`.
+    pub fn carry_over(&self, contact: &mut Contact) {
+        let mut carried = format!("Name: {}", self.name);
+        if !self.notes.is_empty() {
+            carried = format!("{}\n{}", carried, self.notes);
+        }
+        let notes = if contact.notes.is_empty() { carried } else { format!("{}\n\n{}", contact.notes, carried) };
+        contact.notes = truncate_to_bytes(&notes, MAX_CONTACT_NOTES_BYTES).to_string();
+    }
+}
+
+/// A row or card of an imported list that did not become an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntroductionRowError {
+    /// Line the row or card starts on (1-based)
+    pub line: usize,
+    /// What is wrong with it
+    pub reason: String,
+}
+
+impl fmt::Display for IntroductionRowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+/// Entries read from a list, and the rows that were skipped
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntroductionImport {
+    /// Entries in file order
+    pub entries: Vec<PendingIntroduction>,
+    /// One error per unusable row or card
+    pub errors: Vec<IntroductionRowError>,
+}
+
+impl IntroductionImport {
+    fn push(&mut self, line: usize, fields: ContactFields, added_at: DateTime<Utc>) {
+        match fields.into_entry(added_at) {
+            Ok(entry) => self.entries.push(entry),
+            Err(reason) => self.errors.push(IntroductionRowError { line, reason }),
+        }
+    }
+}
+
+/// Read a contact list file: vCard if it starts with `BEGIN:VCARD`, CSV otherwise
+pub fn parse_introduction_list(bytes: &[u8], added_at: DateTime<Utc>) -> IntroductionImport {
+    let text = decode_text(bytes);
+    let is_vcard = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .is_some_and(|line| line.eq_ignore_ascii_case("BEGIN:VCARD"));
+    if is_vcard {
+        parse_vcard_introductions(&text, added_at)
+    } else {
+        parse_csv_introductions(&text, added_at)
+    }
+}
+
+/// Decode file bytes as text, whatever the exporting program chose
+///
+/// A UTF-8 BOM is dropped, UTF-16 with a BOM is decoded, and bytes that
+/// are not valid UTF-8 are read as Latin-1.
+pub fn decode_text(bytes: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], decode: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| decode([pair[0], pair[1]])).collect();
+        String::from_utf16_lossy(&units)
+    };
+    match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        _ => match std::str::from_utf8(bytes) {
+            Ok(text) => text.to_string(),
+            Err(_) => latin1(bytes),
+        },
+    }
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// What one row or card says about a person
+#[derive(Debug, Default)]
+struct ContactFields {
+    name: String,
+    given: String,
+    family: String,
+    organization: String,
+    emails: Vec<String>,
+    phones: Vec<String>,
+    notes: Vec<String>,
+}
+
+impl ContactFields {
+    fn into_entry(self, added_at: DateTime<Utc>) -> std::result::Result<PendingIntroduction, String> {
+        let name = if self.name.trim().is_empty() {
+            [self.given.trim(), self.family.trim()].iter().filter(|part| !part.is_empty()).copied().collect::<Vec<_>>().join(" ")
+        } else {
+            self.name.trim().to_string()
+        };
+        let name: String = name.chars().filter(|c| !c.is_control()).collect();
+        if name.is_empty() {
+            let known = self.emails.iter().chain(&self.phones).find(|value| !value.is_empty());
+            return Err(match known {
+                Some(value) => format!("no name (for {})", value),
+                None => "no name".to_string(),
+            });
+        }
+        if name.chars().count() > MAX_INTRODUCTION_NAME_CHARS {
+            return Err(format!("name longer than {} characters", MAX_INTRODUCTION_NAME_CHARS));
+        }
+
+        let mut lines = Vec::new();
+        let nonempty = |values: &[String]| values.iter().filter(|v| !v.is_empty()).cloned().collect::<Vec<_>>();
+        if !self.organization.is_empty() {
+            lines.push(format!("Organization: {}", self.organization));
+        }
+        let emails = nonempty(&self.emails);
+        if !emails.is_empty() {
+            lines.push(format!("Email: {}", emails.join(", ")));
+        }
+        let phones = nonempty(&self.phones);
+        if !phones.is_empty() {
+            lines.push(format!("Phone: {}", phones.join(", ")));
+        }
+        lines.extend(nonempty(&self.notes));
+        let notes = lines.join("\n");
+        if notes.len() > MAX_CONTACT_NOTES_BYTES {
+            return Err(format!("notes larger than {} bytes", MAX_CONTACT_NOTES_BYTES));
+        }
+        Ok(PendingIntroduction::new(&name, &notes, added_at))
+    }
+}
+
+// ========== CSV ==========
+
+/// Which field of a person a CSV column holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CsvColumn {
+    Name,
+    Given,
+    Family,
+    Organization,
+    Email,
+    Phone,
+    Notes,
+    Other,
+}
+
+impl CsvColumn {
+    /// Column for a header cell, as the usual exports name them
+    fn from_header(cell: &str) -> Self {
+        let cell = cell.trim().to_lowercase();
+        match cell.as_str() {
+            "name" | "full name" | "display name" | "contact name" | "fn" => CsvColumn::Name,
+            "first name" | "given name" | "first" | "given" => CsvColumn::Given,
+            "last name" | "family name" | "surname" | "last" | "family" => CsvColumn::Family,
+            "organization" | "organisation" | "company" | "org" => CsvColumn::Organization,
+            "notes" | "note" | "comment" | "comments" => CsvColumn::Notes,
+            _ if cell.contains("e-mail") || cell.contains("email") => CsvColumn::Email,
+            _ if cell.contains("phone") || cell.contains("mobile") || cell == "tel" => CsvColumn::Phone,
+            _ => CsvColumn::Other,
+        }
+    }
+}
+
+/// One CSV record, or why it could not be read
+struct CsvRecord {
+    line: usize,
+    fields: std::result::Result<Vec<String>, String>,
+}
+
+/// Read pending introductions from CSV
+///
+/// The delimiter (`,`, `;` or tab) is the one most used on the first line.
+/// A first row naming known columns ("Name", "First Name", "E-mail
+/// Address", "Mobile Phone", ...) is a header; without one, the first column
+/// is the name and the others go to the notes. Quoted fields may hold
+/// delimiters, doubled quotes and line breaks.
+pub fn parse_csv_introductions(text: &str, added_at: DateTime<Utc>) -> IntroductionImport {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let delimiter = detect_delimiter(text);
+    let mut records = read_csv_records(text, delimiter).into_iter().filter(|record| {
+        !matches!(&record.fields, Ok(fields) if fields.iter().all(|field| field.trim().is_empty()))
+    });
+    let mut import = IntroductionImport::default();
+
+    let Some(first) = records.next() else {
+        return import;
+    };
+    let header = match &first.fields {
+        Ok(fields) => {
+            let columns: Vec<CsvColumn> = fields.iter().map(|cell| CsvColumn::from_header(cell)).collect();
+            let known = columns.iter().any(|column| !matches!(column, CsvColumn::Other | CsvColumn::Notes));
+            known.then_some(columns)
+        }
+        Err(_) => None,
+    };
+    let rows = match header {
+        Some(_) => Box::new(records) as Box<dyn Iterator<Item = CsvRecord>>,
+        None => Box::new(std::iter::once(first).chain(records)),
+    };
+
+    for record in rows {
+        let fields = match record.fields {
+            Ok(fields) => fields,
+            Err(reason) => {
+                import.errors.push(IntroductionRowError { line: record.line, reason });
+                continue;
+            }
+        };
+        let mut person = ContactFields::default();
+        match &header {
+            Some(columns) => {
+                let extra = fields.iter().skip(columns.len()).filter(|field| !field.trim().is_empty()).count();
+                if extra > 0 {
+                    import.errors.push(IntroductionRowError {
+                        line: record.line,
+                        reason: format!("{} fields, the header has {}", fields.len(), columns.len()),
+                    });
+                    continue;
+                }
+                for (column, value) in columns.iter().zip(&fields) {
+                    let value = value.trim().to_string();
+                    match column {
+                        CsvColumn::Name => person.name = value,
+                        CsvColumn::Given => person.given = value,
+                        CsvColumn::Family => person.family = value,
+                        CsvColumn::Organization => person.organization = value,
+                        CsvColumn::Email => person.emails.push(value),
+                        CsvColumn::Phone => person.phones.push(value),
+                        CsvColumn::Notes => person.notes.push(value),
+                        CsvColumn::Other => {}
+                    }
+                }
+            }
+            None => {
+                let mut fields = fields.into_iter().map(|field| field.trim().to_string());
+                person.name = fields.next().unwrap_or_default();
+                person.notes = fields.collect();
+            }
+        }
+        import.push(record.line, person, added_at);
+    }
+    import
+}
+
+/// Delimiter used most often outside quotes on the first non-empty line
+fn detect_delimiter(text: &str) -> char {
+    let first = text.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+    let mut counts = [(',', 0), (';', 0), ('\t', 0)];
+    let mut quoted = false;
+    for c in first.chars() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if !quoted && let Some(count) = counts.iter_mut().find(|(d, _)| *d == c) {
+            count.1 += 1;
+        }
+    }
+    // Ties go to the comma, listed first
+    counts.iter().rev().max_by_key(|(_, count)| *count).filter(|(_, count)| *count > 0).map_or(',', |(d, _)| *d)
+}
+
+/// Split `text` into records
+///
+/// A quoted field left open at the end of the file spoils only its own
+/// record: reading resumes on the line after the one it started on.
+fn read_csv_records(text: &str, delimiter: char) -> Vec<CsvRecord> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut records = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let start = index;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut was_quoted = false;
+        let mut error = None;
+        let mut finished = false;
+
+        while index < lines.len() && !finished {
+            let mut chars = lines[index].chars().peekable();
+            index += 1;
+            while let Some(c) = chars.next() {
+                if quoted {
+                    match c {
+                        '"' if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        '"' => quoted = false,
+                        '\r' if chars.peek() == Some(&'\n') => {}
+                        _ => field.push(c),
+                    }
+                    continue;
+                }
+                match c {
+                    '"' if field.trim().is_empty() && !was_quoted => {
+                        field.clear();
+                        quoted = true;
+                        was_quoted = true;
+                    }
+                    '\r' if chars.peek() == Some(&'\n') => {}
+                    '\n' => finished = true,
+                    c if c == delimiter => {
+                        fields.push(std::mem::take(&mut field));
+                        was_quoted = false;
+                    }
+                    c if was_quoted => {
+                        if !c.is_whitespace() && error.is_none() {
+                            error = Some(format!("text after the closing quote of field {}", fields.len() + 1));
+                        }
+                    }
+                    _ => field.push(c),
+                }
+            }
+            finished |= !quoted;
+        }
+
+        if quoted {
+            records.push(CsvRecord { line: start + 1, fields: Err("quoted field is never closed".to_string()) });
+            index = start + 1;
+            continue;
+        }
+        fields.push(field);
+        records.push(CsvRecord { line: start + 1, fields: error.map_or(Ok(fields), Err) });
+    }
+    records
+}
+
+// ========== vCard ==========
+
+/// One logical line of a vCard, after unfolding
+struct VcardLine {
+    line: usize,
+    text: String,
+}
+
+/// Read pending introductions from vCard (2.1, 3.0 and 4.0)
+///
+/// The name is `FN`, or else built from `N`; `EMAIL`, `TEL`, `ORG` and
+/// `NOTE` go to the notes. Folded lines, `QUOTED-PRINTABLE` values (with
+/// their soft line breaks and `CHARSET`) and escaped characters are decoded.
+/// A card is reported by the line of its `BEGIN:VCARD`.
+pub fn parse_vcard_introductions(text: &str, added_at: DateTime<Utc>) -> IntroductionImport {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut import = IntroductionImport::default();
+    // Start line of the open card, its fields, and its first problem
+    let mut card: Option<(usize, ContactFields, Option<String>)> = None;
+
+    for VcardLine { line, text } in unfold_vcard(text) {
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if trimmed.eq_ignore_ascii_case("BEGIN:VCARD") {
+            if let Some((start, _, _)) = card.take() {
+                import.errors.push(IntroductionRowError { line: start, reason: "card has no END:VCARD".to_string() });
+            }
+            card = Some((line, ContactFields::default(), None));
+            continue;
+        }
+        let Some((start, person, problem)) = &mut card else {
+            import.errors.push(IntroductionRowError { line, reason: "text outside BEGIN:VCARD/END:VCARD".to_string() });
+            continue;
+        };
+        if trimmed.eq_ignore_ascii_case("END:VCARD") {
+            let start = *start;
+            if let Some((_, person, problem)) = card.take() {
+                match problem {
+                    Some(reason) => import.errors.push(IntroductionRowError { line: start, reason }),
+                    None => import.push(start, person, added_at),
+                }
+            }
+            continue;
+        }
+        let Some((name_part, raw_value)) = split_property(&text) else {
+            problem.get_or_insert(format!("line {} is not a property", line));
+            continue;
+        };
+        let mut params = name_part.split(';');
+        let property = params.next().unwrap_or("");
+        let property = property.rsplit('.').next().unwrap_or(property).to_uppercase();
+        let (mut quoted_printable, mut charset) = (false, None);
+        for param in params {
+            let (key, value) = param.split_once('=').unwrap_or(("", param));
+            let value = value.trim_matches('"');
+            if value.eq_ignore_ascii_case("QUOTED-PRINTABLE") && (key.is_empty() || key.eq_ignore_ascii_case("ENCODING")) {
+                quoted_printable = true;
+            } else if key.eq_ignore_ascii_case("CHARSET") {
+                charset = Some(value.to_lowercase());
+            }
+        }
+        let value = if quoted_printable {
+            let bytes = decode_quoted_printable(raw_value);
+            match charset.as_deref() {
+                Some("iso-8859-1" | "latin1" | "windows-1252") => latin1(&bytes),
+                _ => decode_text(&bytes),
+            }
+        } else {
+            raw_value.to_string()
+        };
+
+        match property.as_str() {
+            "FN" => person.name = unescape_vcard(&value),
+            "N" => {
+                let parts: Vec<String> = split_unescaped(&value, ';').iter().map(|part| unescape_vcard(part)).collect();
+                let part = |index: usize| parts.get(index).map(|p| p.trim()).unwrap_or("");
+                person.family = part(0).to_string();
+                person.given = [part(1), part(2)].iter().filter(|p| !p.is_empty()).copied().collect::<Vec<_>>().join(" ");
+            }
+            "ORG" => {
+                let org = split_unescaped(&value, ';').first().map(|first| unescape_vcard(first)).unwrap_or_default();
+                person.organization = org.trim().to_string();
+            }
+            "EMAIL" => person.emails.push(unescape_vcard(&value).trim().to_string()),
+            "TEL" => {
+                let tel = unescape_vcard(&value);
+                let tel = tel.trim();
+                person.phones.push(tel.strip_prefix("tel:").unwrap_or(tel).to_string());
+            }
+            "NOTE" => person.notes.push(unescape_vcard(&value).trim().to_string()),
+            _ => {}
+        }
+    }
+    if let Some((start, _, _)) = card {
+        import.errors.push(IntroductionRowError { line: start, reason: "card has no END:VCARD".to_string() });
+    }
+    import
+}
+
+/// Join folded lines (starting with a space or tab) and quoted-printable
+/// soft line breaks (a line ending in `=`)
+fn unfold_vcard(text: &str) -> Vec<VcardLine> {
+    let mut lines: Vec<VcardLine> = Vec::new();
+    let mut soft_break = false;
+    for (index, raw) in text.lines().enumerate() {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        match lines.last_mut() {
+            Some(last) if soft_break => last.text.push_str(raw),
+            Some(last) if raw.starts_with([' ', '\t']) => last.text.push_str(&raw[1..]),
+            _ => lines.push(VcardLine { line: index + 1, text: raw.to_string() }),
+        }
+        let last = &mut lines.last_mut().expect("a line was just added").text;
+        soft_break = last.to_uppercase().contains("QUOTED-PRINTABLE") && last.ends_with('=');
+        if soft_break {
+            last.pop();
+        }
+    }
+    lines
+}
+
+/// Split a content line at the colon ending its name and parameters
+fn split_property(line: &str) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some((&line[..index], &line[index + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn decode_quoted_printable(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes.get(index + 1..index + 3).and_then(|pair| std::str::from_utf8(pair).ok());
+        match (bytes[index], hex.and_then(|pair| u8::from_str_radix(pair, 16).ok())) {
+            (b'=', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Split at `separator`s not escaped with a backslash (escapes are kept)
+fn split_unescaped(value: &str, separator: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut escaped = false;
+    for c in value.chars() {
+        let part = parts.last_mut().expect("parts is never empty");
+        if escaped {
+            part.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            part.push(c);
+            escaped = true;
+        } else if c == separator {
+            parts.push(String::new());
+        } else {
+            part.push(c);
+        }
+    }
+    parts
+}
+
+/// Undo vCard escapes: `\n`, `\,`, `\;` and `\\`
+fn unescape_vcard(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+// ========== Matching ==========
+
+/// Lowercase words of a name, accents dropped ("José Núñez" → ["jose", "nunez"])
+pub fn name_words(name: &str) -> Vec<String> {
+    name.chars()
+        .map(|c| fold_accent(c.to_lowercase().next().unwrap_or(c)))
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+fn fold_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ą' => 'a',
+        'ç' | 'ć' | 'č' => 'c',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => 'e',
+        'ì' | 'í' | 'î' | 'ï' | 'ī' => 'i',
+        'ñ' | 'ń' | 'ň' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ś' | 'š' => 's',
+        'ź' | 'ż' | 'ž' => 'z',
+        'ł' => 'l',
+        'ř' => 'r',
+        _ => c,
+    }
+}
+
+/// How alike two names are, from 0.0 to 1.0
+///
+/// Case, accents, punctuation and word order do not count ("Smith, Alice"
+/// is "Alice Smith"). A name whose words are all in the other scores 0.85
+/// ("Alice" and "Alice Smith"); otherwise the score is the edit distance of
+/// the sorted words relative to the longer name.
+pub fn name_similarity(a: &str, b: &str) -> f64 {
+    let (mut a, mut b) = (name_words(a), name_words(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    a.sort();
+    b.sort();
+    if a == b {
+        return 1.0;
+    }
+    let (shorter, longer) = if a.len() <= b.len() { (&a, &b) } else { (&b, &a) };
+    let subset = if shorter.iter().all(|word| longer.contains(word)) { 0.85 } else { 0.0 };
+    let (a, b): (Vec<char>, Vec<char>) = (a.join(" ").chars().collect(), b.join(" ").chars().collect());
+    let distance = edit_distance(&a, &b);
+    let edit = 1.0 - distance as f64 / a.len().max(b.len()) as f64;
+    edit.max(subset)
+}
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The pending entry whose name is most like `name`, if any reaches
+/// `FUZZY_MATCH_THRESHOLD` (the earliest wins a tie)
+pub fn best_match<'a>(name: &str, pending: &'a [PendingIntroduction]) -> Option<&'a PendingIntroduction> {
+    let mut best: Option<(&PendingIntroduction, f64)> = None;
+    for entry in pending {
+        let score = name_similarity(name, &entry.name);
+        if score >= FUZZY_MATCH_THRESHOLD && best.is_none_or(|(_, top)| score > top) {
+            best = Some((entry, score));
+        }
+    }
+    best.map(|(entry, _)| entry)
+}
+
+/// `text` cut to at most `max` bytes, on a character boundary
+fn truncate_to_bytes(text: &str, max: usize) -> &str {
+    if text.len() <= max {
+        return text;
+    }
+    let end = (0..=max).rev().find(|&index| text.is_char_boundary(index)).unwrap_or(0);
+    &text[..end]
+}
@@ -0,0 +1,306 @@
+//! Outbound delivery journal (opt-in, append-only)
+//!
+//! With `Settings::journal_enabled` on, every outgoing chat message that is
+//! delivered appends a `JournalRecord`: when, to whom, and the SHA-256 of
+//! the content (never the content itself). Each record carries the hash of
+//! the one before it, so editing, removing or reordering a record breaks
+//! the chain from that point on; `verify_chain` finds the first break.
+//! Switching the journal off appends a `JournalKind::Sealed` marker instead
+//! of deleting anything.
+//!
+//! `export_journal` writes a range of records as CSV or JSON Lines followed
+//! by one trailer line holding an Ed25519 signature, by our identity key,
+//! over every byte before it (`verify_journal_export` checks it).
+
+use crate::crypto::KeyPair;
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+
+/// `prev_hash` of the first record
+pub const JOURNAL_GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Header line of a CSV journal export
+pub const JOURNAL_CSV_HEADER: &str = "seq,at,kind,recipient_uid,content_sha256,ack_signature,prev_hash,hash";
+
+/// Start of the signature trailer of a CSV journal export
+const CSV_SIGNATURE_PREFIX: &str = "#signature,";
+
+/// What a journal record stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalKind {
+    /// An outgoing message was delivered
+    Delivered,
+    /// The journal was switched off; nothing was recorded until the next record
+    Sealed,
+}
+
+impl JournalKind {
+    /// Lowercase name, as stored and exported
+    pub fn name(self) -> &'static str {
+        match self {
+            JournalKind::Delivered => "delivered",
+            JournalKind::Sealed => "sealed",
+        }
+    }
+
+    /// Parse a name written by `name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "delivered" => Some(JournalKind::Delivered),
+            "sealed" => Some(JournalKind::Sealed),
+            _ => None,
+        }
+    }
+}
+
+/// One link of the journal chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalRecord {
+    /// Position in the chain, starting at 1
+    pub seq: u64,
+    /// When the record was appended (ms since epoch)
+    pub at: i64,
+    /// What the record stands for
+    pub kind: JournalKind,
+    /// Recipient of the delivered message (empty for a seal)
+    pub recipient_uid: String,
+    /// Hex SHA-256 of the delivered content (empty for a seal)
+    pub content_sha256: String,
+    /// Hex signature of the recipient's delivery acknowledgement, when it sent one
+    pub ack_signature: Option<String>,
+    /// `hash` of the previous record (`JOURNAL_GENESIS_HASH` for the first)
+    pub prev_hash: String,
+    /// Hex SHA-256 over every other field (see `JournalRecord::compute_hash`)
+    pub hash: String,
+}
+
+impl JournalRecord {
+    /// Record the delivery of `content` to `recipient_uid`, following `prev`
+    pub fn delivered(
+        prev: Option<&JournalRecord>,
+        at: DateTime<Utc>,
+        recipient_uid: &str,
+        content: &[u8],
+        ack_signature: Option<String>,
+    ) -> Self {
+        let content_sha256 = hex::encode(digest(&SHA256, content));
+        Self::next(prev, at, JournalKind::Delivered, recipient_uid.to_string(), content_sha256, ack_signature)
+    }
+
+    /// Seal marker closing the journal when it is switched off, following `prev`
+    pub fn seal(prev: Option<&JournalRecord>, at: DateTime<Utc>) -> Self {
+        Self::next(prev, at, JournalKind::Sealed, String::new(), String::new(), None)
+    }
+
+    fn next(
+        prev: Option<&JournalRecord>,
+        at: DateTime<Utc>,
+        kind: JournalKind,
+        recipient_uid: String,
+        content_sha256: String,
+        ack_signature: Option<String>,
+    ) -> Self {
+        let mut record = Self {
+            seq: prev.map_or(1, |p| p.seq + 1),
+            at: at.timestamp_millis(),
+            kind,
+            recipient_uid,
+            content_sha256,
+            ack_signature,
+            prev_hash: prev.map_or_else(|| JOURNAL_GENESIS_HASH.to_string(), |p| p.hash.clone()),
+            hash: String::new(),
+        };
+        record.hash = record.compute_hash();
+        record
+    }
+
+    /// Hex SHA-256 of the record's fields other than `hash`
+    ///
+    /// The fields are hashed as a JSON array, so no field can bleed into its
+    /// neighbour.
+    pub fn compute_hash(&self) -> String {
+        let fields = serde_json::json!([
+            self.seq,
+            self.at,
+            self.kind.name(),
+            self.recipient_uid,
+            self.content_sha256,
+            self.ack_signature,
+            self.prev_hash,
+        ]);
+        hex::encode(digest(&SHA256, fields.to_string().as_bytes()))
+    }
+}
+
+/// Why the chain breaks at a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalBreakReason {
+    /// `seq` does not follow the previous record's
+    SequenceGap,
+    /// `prev_hash` is not the previous record's hash
+    WrongPrevious,
+    /// `hash` does not match the record's contents
+    HashMismatch,
+}
+
+/// First record at which the chain no longer holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalBreak {
+    /// Sequence number of the record
+    pub seq: u64,
+    /// What is wrong with it
+    pub reason: JournalBreakReason,
+}
+
+impl std::fmt::Display for JournalBreak {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.reason {
+            JournalBreakReason::SequenceGap => "a record is missing before it",
+            JournalBreakReason::WrongPrevious => "it does not link to the previous record",
+            JournalBreakReason::HashMismatch => "its contents were changed",
+        };
+        write!(f, "journal breaks at record {}: {}", self.seq, reason)
+    }
+}
+
+/// Walk the whole chain from the genesis hash
+///
+/// # Returns
+/// The first break, or None if every record links to the one before it
+pub fn verify_chain(records: &[JournalRecord]) -> Option<JournalBreak> {
+    let mut expected_seq = 1;
+    let mut expected_prev = JOURNAL_GENESIS_HASH;
+    for record in records {
+        let reason = if record.seq != expected_seq {
+            Some(JournalBreakReason::SequenceGap)
+        } else if record.prev_hash != expected_prev {
+            Some(JournalBreakReason::WrongPrevious)
+        } else if record.hash != record.compute_hash() {
+            Some(JournalBreakReason::HashMismatch)
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            return Some(JournalBreak { seq: record.seq, reason });
+        }
+        expected_seq = record.seq + 1;
+        expected_prev = &record.hash;
+    }
+    None
+}
+
+/// Layout of a journal export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalExportFormat {
+    /// `JOURNAL_CSV_HEADER`, one row per record, `#signature,<sig>,<key>` trailer
+    Csv,
+    /// One JSON record per line, `{"signature":..,"public_key":..}` trailer
+    Jsonl,
+}
+
+impl JournalExportFormat {
+    /// CSV for a `.csv` path, JSON Lines otherwise
+    pub fn for_path(path: &std::path::Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => JournalExportFormat::Csv,
+            _ => JournalExportFormat::Jsonl,
+        }
+    }
+}
+
+/// Trailer line of a JSON Lines journal export
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportSignature {
+    signature: String,
+    public_key: String,
+}
+
+/// Write `records` in `format`, signed by `keypair`
+///
+/// # Errors
+/// Returns an error if a record cannot be serialized or signing fails
+pub fn export_journal(records: &[JournalRecord], format: JournalExportFormat, keypair: &KeyPair) -> Result<String> {
+    let mut body = String::new();
+    match format {
+        JournalExportFormat::Csv => {
+            body.push_str(JOURNAL_CSV_HEADER);
+            body.push('\n');
+            for r in records {
+                let row = [
+                    r.seq.to_string(),
+                    r.at.to_string(),
+                    r.kind.name().to_string(),
+                    csv_field(&r.recipient_uid),
+                    csv_field(&r.content_sha256),
+                    csv_field(r.ack_signature.as_deref().unwrap_or("")),
+                    csv_field(&r.prev_hash),
+                    csv_field(&r.hash),
+                ];
+                body.push_str(&row.join(","));
+                body.push('\n');
+            }
+        }
+        JournalExportFormat::Jsonl => {
+            for r in records {
+                body.push_str(&serde_json::to_string(r)?);
+                body.push('\n');
+            }
+        }
+    }
+
+    let signature = hex::encode(keypair.sign(body.as_bytes())?);
+    let public_key = hex::encode(&keypair.public_key);
+    let trailer = match format {
+        JournalExportFormat::Csv => format!("{}{},{}", CSV_SIGNATURE_PREFIX, signature, public_key),
+        JournalExportFormat::Jsonl => serde_json::to_string(&ExportSignature { signature, public_key })?,
+    };
+    body.push_str(&trailer);
+    body.push('\n');
+    Ok(body)
+}
+
+/// Check the signature trailer of an `export_journal` file against `public_key`
+///
+/// # Errors
+/// Returns `Error::Crypto` if the trailer is missing or malformed or the
+/// signature does not verify
+pub fn verify_journal_export(text: &str, public_key: &[u8]) -> Result<()> {
+    let trimmed = text.strip_suffix('\n').unwrap_or(text);
+    let split = trimmed.rfind('\n').map_or(0, |i| i + 1);
+    let (body, trailer) = trimmed.split_at(split);
+
+    let signature = match trailer.strip_prefix(CSV_SIGNATURE_PREFIX) {
+        Some(rest) => rest.split(',').next().unwrap_or_default().to_string(),
+        None => serde_json::from_str::<ExportSignature>(trailer)
+            .map_err(|_| Error::Crypto("Journal export has no signature line".to_string()))?
+            .signature,
+    };
+    let signature: [u8; 64] = hex::decode(&signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| Error::Crypto("Journal export signature is malformed".to_string()))?;
+    let public_key: [u8; 32] = public_key
+        .try_into()
+        .map_err(|_| Error::Crypto("Invalid public key length".to_string()))?;
+    if !crate::crypto::verify_contact_token(&public_key, body.as_bytes(), &signature)? {
+        return Err(Error::Crypto("Journal export signature does not verify".to_string()));
+    }
+    Ok(())
+}
+
+/// File name for a journal export made at `now`, before collision handling
+pub fn journal_export_file_name(now: DateTime<Utc>) -> String {
+    format!("pure2p-journal-{}.csv", now.format("%Y%m%d"))
+}
+
+/// Quote a CSV field if it holds a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
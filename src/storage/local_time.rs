@@ -0,0 +1,209 @@
+//! A contact's local time, from a UTC offset learned from their messages
+//!
+//! An incoming message that carries the sender's own timestamp gives an
+//! `OffsetSample`: that timestamp minus our receive time. A peer whose clock
+//! stamps messages in its local wall time is off by its UTC offset, plus
+//! network delay and clock skew of seconds to minutes. `infer_utc_offset()`
+//! takes the weighted median of the samples, where each weighs half as much
+//! per `OFFSET_SAMPLE_HALF_LIFE_DAYS` of age, and snaps it to the nearest
+//! `OFFSET_SNAP_MINUTES`, which absorbs the skew. Fewer than
+//! `MIN_OFFSET_SAMPLES` samples infer nothing, and samples older than
+//! `OFFSET_SAMPLE_MAX_AGE_DAYS` are dropped when the next one is recorded.
+//!
+//! Peers stamping in UTC infer an offset of zero; the offset set by hand in
+//! the contact details always wins (`UtcOffset::resolve()`). Samples and
+//! override stay on the `Contact` (local only): neither is put in tokens or
+//! any message.
+//!
+//! The chat view shows the resolved offset's local time in its header and,
+//! with `Settings::night_warning_enabled`, warns while composing during
+//! `NIGHT_START_MINUTES`-`NIGHT_END_MINUTES` their time (`is_night_at()`).
+
+use chrono::{DateTime, Duration, FixedOffset, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Samples needed before an offset is inferred
+pub const MIN_OFFSET_SAMPLES: usize = 5;
+
+/// Most samples kept per contact (the newest)
+pub const MAX_OFFSET_SAMPLES: usize = 50;
+
+/// Days after which a sample counts half as much
+pub const OFFSET_SAMPLE_HALF_LIFE_DAYS: i64 = 14;
+
+/// Days after which a sample is dropped
+pub const OFFSET_SAMPLE_MAX_AGE_DAYS: i64 = 60;
+
+/// Granularity offsets are snapped to, in minutes (every real zone is a multiple)
+pub const OFFSET_SNAP_MINUTES: i32 = 15;
+
+/// Westernmost UTC offset, in minutes (UTC-12:00)
+pub const MIN_UTC_OFFSET_MINUTES: i32 = -12 * 60;
+
+/// Easternmost UTC offset, in minutes (UTC+14:00)
+pub const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// Start of their night, in minutes after midnight their time (23:00)
+pub const NIGHT_START_MINUTES: u32 = 23 * 60;
+
+/// End of their night, in minutes after midnight their time (07:00)
+pub const NIGHT_END_MINUTES: u32 = 7 * 60;
+
+/// One message's sender timestamp against when we received it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OffsetSample {
+    /// Sender timestamp minus receive time, in milliseconds
+    pub offset_ms: i64,
+    /// When the message was received (Unix milliseconds)
+    pub received_at: i64,
+}
+
+impl OffsetSample {
+    /// Sample for a message the sender stamped `sent_at_ms` and we received at `received_at`
+    pub fn new(sent_at_ms: i64, received_at: DateTime<Utc>) -> Self {
+        let received_at = received_at.timestamp_millis();
+        Self {
+            offset_ms: sent_at_ms.saturating_sub(received_at),
+            received_at,
+        }
+    }
+
+    /// Weight at `now`: 1 when fresh, halving every `OFFSET_SAMPLE_HALF_LIFE_DAYS`
+    fn weight(&self, now: DateTime<Utc>) -> f64 {
+        let age_ms = now.timestamp_millis().saturating_sub(self.received_at).max(0) as f64;
+        let half_life_ms = Duration::days(OFFSET_SAMPLE_HALF_LIFE_DAYS).num_milliseconds() as f64;
+        0.5f64.powf(age_ms / half_life_ms)
+    }
+}
+
+/// Add `sample`, dropping samples past `OFFSET_SAMPLE_MAX_AGE_DAYS` and beyond `MAX_OFFSET_SAMPLES`
+pub fn record_offset_sample(samples: &mut Vec<OffsetSample>, sample: OffsetSample, now: DateTime<Utc>) {
+    let cutoff = (now - Duration::days(OFFSET_SAMPLE_MAX_AGE_DAYS)).timestamp_millis();
+    samples.retain(|s| s.received_at >= cutoff);
+    samples.push(sample);
+    if samples.len() > MAX_OFFSET_SAMPLES {
+        samples.drain(..samples.len() - MAX_OFFSET_SAMPLES);
+    }
+}
+
+/// Snap an offset in milliseconds to the nearest `OFFSET_SNAP_MINUTES`
+pub fn snap_offset_minutes(offset_ms: i64) -> i32 {
+    let step_ms = OFFSET_SNAP_MINUTES as i64 * 60_000;
+    let steps = (offset_ms as f64 / step_ms as f64).round() as i64;
+    (steps * OFFSET_SNAP_MINUTES as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+/// UTC offset in minutes inferred from `samples` at `now`
+///
+/// The weighted median of the samples within `OFFSET_SAMPLE_MAX_AGE_DAYS`,
+/// snapped to `OFFSET_SNAP_MINUTES`.
+///
+/// # Returns
+/// None with fewer than `MIN_OFFSET_SAMPLES` such samples, or when the median
+/// is outside UTC-12:00..UTC+14:00 (a clock that is simply wrong)
+pub fn infer_utc_offset(samples: &[OffsetSample], now: DateTime<Utc>) -> Option<i32> {
+    let cutoff = (now - Duration::days(OFFSET_SAMPLE_MAX_AGE_DAYS)).timestamp_millis();
+    let mut weighted: Vec<(i64, f64)> = samples
+        .iter()
+        .filter(|s| s.received_at >= cutoff)
+        .map(|s| (s.offset_ms, s.weight(now)))
+        .collect();
+    if weighted.len() < MIN_OFFSET_SAMPLES {
+        return None;
+    }
+
+    weighted.sort_by_key(|(offset, _)| *offset);
+    let half = weighted.iter().map(|(_, weight)| weight).sum::<f64>() / 2.0;
+    let mut running = 0.0;
+    let median = weighted
+        .iter()
+        .find(|(_, weight)| {
+            running += weight;
+            running >= half
+        })
+        .map(|(offset, _)| *offset)?;
+
+    let minutes = snap_offset_minutes(median);
+    (MIN_UTC_OFFSET_MINUTES..=MAX_UTC_OFFSET_MINUTES).contains(&minutes).then_some(minutes)
+}
+
+/// A contact's UTC offset and where it came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UtcOffset {
+    /// Set by hand in the contact details
+    Manual(i32),
+    /// Inferred from message timestamps
+    Inferred(i32),
+}
+
+impl UtcOffset {
+    /// The manual override if set, else the inferred offset
+    pub fn resolve(manual: Option<i32>, inferred: Option<i32>) -> Option<Self> {
+        manual.map(UtcOffset::Manual).or(inferred.map(UtcOffset::Inferred))
+    }
+
+    /// Offset in minutes east of UTC
+    pub fn minutes(self) -> i32 {
+        match self {
+            UtcOffset::Manual(minutes) | UtcOffset::Inferred(minutes) => minutes,
+        }
+    }
+
+    /// "UTC+05:30", with ", inferred" when not set by hand
+    pub fn describe(self) -> String {
+        match self {
+            UtcOffset::Manual(minutes) => format_utc_offset(minutes),
+            UtcOffset::Inferred(minutes) => format!("{}, inferred", format_utc_offset(minutes)),
+        }
+    }
+}
+
+/// Wall-clock time at `now` for a contact `offset_minutes` east of UTC
+pub fn local_time_at(now: DateTime<Utc>, offset_minutes: i32) -> DateTime<FixedOffset> {
+    let offset = FixedOffset::east_opt(offset_minutes * 60).unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    now.with_timezone(&offset)
+}
+
+/// Whether `now` falls in a contact's night (`NIGHT_START_MINUTES`-`NIGHT_END_MINUTES` their time)
+pub fn is_night_at(now: DateTime<Utc>, offset_minutes: i32) -> bool {
+    let local = local_time_at(now, offset_minutes);
+    let minute = local.hour() * 60 + local.minute();
+    // The window crosses midnight: night is everything outside the day
+    !(NIGHT_END_MINUTES..NIGHT_START_MINUTES).contains(&minute)
+}
+
+/// Format an offset as "UTC+05:30" / "UTC-03:00"
+pub fn format_utc_offset(minutes: i32) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let abs = minutes.unsigned_abs();
+    format!("UTC{}{:02}:{:02}", sign, abs / 60, abs % 60)
+}
+
+/// Parse an offset typed in the contact details
+///
+/// Accepts "+5", "-3:30", "5:45", "UTC+05:30" (the "UTC" prefix and the sign
+/// are optional).
+///
+/// # Returns
+/// Minutes east of UTC, or None unless the offset is within
+/// UTC-12:00..UTC+14:00 and a multiple of `OFFSET_SNAP_MINUTES`
+pub fn parse_utc_offset(input: &str) -> Option<i32> {
+    let input = input.trim();
+    let input = input.strip_prefix("UTC").or_else(|| input.strip_prefix("utc")).unwrap_or(input).trim();
+    let (negative, rest) = match input.chars().next()? {
+        '+' => (false, &input[1..]),
+        '-' => (true, &input[1..]),
+        _ => (false, input),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    if hours.is_empty() || !hours.chars().all(|c| c.is_ascii_digit()) || !minutes.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if minutes >= 60 || minutes % OFFSET_SNAP_MINUTES != 0 {
+        return None;
+    }
+    let total = (hours * 60 + minutes) * if negative { -1 } else { 1 };
+    (MIN_UTC_OFFSET_MINUTES..=MAX_UTC_OFFSET_MINUTES).contains(&total).then_some(total)
+}
@@ -1,6 +1,174 @@
 //! Message structures and delivery status tracking
 
+use super::content::ContentKind;
+use super::protection::Protection;
+use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Maximum encoded size of message metadata (CBOR, in bytes)
+pub const MAX_METADATA_BYTES: usize = 2048;
+
+/// Maximum encoded size of an outgoing message request (CBOR, in bytes)
+///
+/// Checked against the framed request, not the text: CBOR stores most payload
+/// bytes in two bytes, so text well under this limit can still exceed it.
+pub const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Sender of local notices (`Message::system`); never a real UID, which is hex
+pub const SYSTEM_SENDER: &str = "system";
+
+/// Metadata value attached to a message
+///
+/// Values are limited to strings, numbers and booleans so that metadata stays
+/// small and easy to consume from other applications.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MetadataValue {
+    /// Boolean value
+    Bool(bool),
+    /// Integer value
+    Integer(i64),
+    /// Floating-point value (must be finite)
+    Float(f64),
+    /// String value
+    Text(String),
+}
+
+impl fmt::Display for MetadataValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataValue::Bool(value) => write!(f, "{}", value),
+            MetadataValue::Integer(value) => write!(f, "{}", value),
+            MetadataValue::Float(value) => write!(f, "{}", value),
+            MetadataValue::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<bool> for MetadataValue {
+    fn from(value: bool) -> Self {
+        MetadataValue::Bool(value)
+    }
+}
+
+impl From<i64> for MetadataValue {
+    fn from(value: i64) -> Self {
+        MetadataValue::Integer(value)
+    }
+}
+
+impl From<f64> for MetadataValue {
+    fn from(value: f64) -> Self {
+        MetadataValue::Float(value)
+    }
+}
+
+impl From<&str> for MetadataValue {
+    fn from(value: &str) -> Self {
+        MetadataValue::Text(value.to_string())
+    }
+}
+
+impl From<String> for MetadataValue {
+    fn from(value: String) -> Self {
+        MetadataValue::Text(value)
+    }
+}
+
+/// Typed key-value metadata attached to a message
+pub type MessageMetadata = BTreeMap<String, MetadataValue>;
+
+/// Validate message metadata
+///
+/// # Errors
+/// Returns `Error::InvalidMetadata` if a key is empty, a number is not finite,
+/// or the encoded metadata exceeds `MAX_METADATA_BYTES`
+pub fn validate_metadata(metadata: &MessageMetadata) -> Result<()> {
+    if metadata.keys().any(|key| key.is_empty()) {
+        return Err(Error::InvalidMetadata("keys must not be empty".to_string()));
+    }
+    if let Some(key) = metadata
+        .iter()
+        .find(|(_, value)| matches!(value, MetadataValue::Float(f) if !f.is_finite()))
+        .map(|(key, _)| key)
+    {
+        return Err(Error::InvalidMetadata(format!("value for '{}' is not a finite number", key)));
+    }
+
+    let encoded = serde_cbor::to_vec(metadata)
+        .map_err(|e| Error::CborSerialization(format!("Failed to serialize metadata: {}", e)))?;
+    if encoded.len() > MAX_METADATA_BYTES {
+        return Err(Error::InvalidMetadata(format!(
+            "encoded size {} bytes exceeds the {} byte limit",
+            encoded.len(),
+            MAX_METADATA_BYTES
+        )));
+    }
+    Ok(())
+}
+
+/// Whether `c` may appear in message text
+///
+/// Newline and tab are kept; other C0/C1 controls and DEL are not, as they
+/// can drive the terminal (ESC starts an escape sequence).
+fn is_allowed_char(c: char) -> bool {
+    c == '\n' || c == '\t' || !c.is_control()
+}
+
+/// Remove characters that must not be sent or rendered
+///
+/// CRLF and lone CR become newlines; every other disallowed control character
+/// is dropped, so an escape sequence is left as harmless printable text.
+pub fn sanitize_text(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .chars()
+        .filter(|&c| is_allowed_char(c))
+        .collect()
+}
+
+/// Whether `c` extends the preceding character into one grapheme
+///
+/// Covers combining marks, variation selectors, emoji modifiers and tags,
+/// which is enough to keep common accented text and emoji sequences whole.
+fn is_grapheme_extender(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x0300..=0x036F
+            | 0x1AB0..=0x1AFF
+            | 0x1DC0..=0x1DFF
+            | 0x200C..=0x200D
+            | 0x20D0..=0x20FF
+            | 0xFE00..=0xFE0F
+            | 0xFE20..=0xFE2F
+            | 0x1F3FB..=0x1F3FF
+            | 0xE0020..=0xE007F
+    )
+}
+
+/// Split `text` into user-perceived characters (approximate graphemes)
+///
+/// A character followed by extenders, or joined to the next one by a
+/// zero-width joiner, stays in one piece.
+pub fn graphemes(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut joined = false;
+    for (index, c) in text.char_indices() {
+        if index > start && !joined && !is_grapheme_extender(c) {
+            pieces.push(&text[start..index]);
+            start = index;
+        }
+        joined = c == '\u{200D}';
+    }
+    if start < text.len() {
+        pieces.push(&text[start..]);
+    }
+    pieces
+}
 
 /// Message delivery status
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -21,6 +189,39 @@ impl Default for DeliveryStatus {
     }
 }
 
+/// Serde for `Arc`-backed fields, in the same format as the owned value
+///
+/// Keeps the JSON/CBOR encoding of `Arc<[u8]>` and `Arc<Vec<T>>` identical
+/// to `Vec<u8>` and `Vec<T>`.
+pub(crate) mod shared {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::sync::Arc;
+
+    /// Serialize the shared value as if it were owned
+    pub fn serialize<T: Serialize + ?Sized, S: Serializer>(value: &Arc<T>, serializer: S) -> Result<S::Ok, S::Error> {
+        T::serialize(value, serializer)
+    }
+
+    /// Deserialize bytes written as `Vec<u8>`
+    pub fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<[u8]>, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(Arc::from)
+    }
+
+    /// Deserialize a list written as `Vec<T>`
+    pub fn deserialize_vec<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<Vec<T>>, D::Error> {
+        Vec::<T>::deserialize(deserializer).map(Arc::new)
+    }
+}
+
+/// An earlier version of an edited message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageEdit {
+    /// Content before the edit
+    pub content: Vec<u8>,
+    /// When it was replaced (Unix milliseconds)
+    pub replaced_at: i64,
+}
+
 /// Represents a stored message
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -30,8 +231,9 @@ pub struct Message {
     pub sender: String,
     /// Recipient peer ID
     pub recipient: String,
-    /// Message content (encrypted)
-    pub content: Vec<u8>,
+    /// Message content (encrypted); shared, so cloning a message is cheap
+    #[serde(serialize_with = "shared::serialize", deserialize_with = "shared::deserialize_bytes")]
+    pub content: Arc<[u8]>,
     /// Timestamp
     pub timestamp: i64,
     /// Delivery status (legacy - kept for backward compatibility)
@@ -46,6 +248,30 @@ pub struct Message {
     /// Number of delivery attempts
     #[serde(default)]
     pub attempts: u32,
+    /// Application metadata (empty if none)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: MessageMetadata,
+    /// Pinned to the top of its chat (local annotation, never transmitted)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Starred for later reference (local annotation, never transmitted)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub starred: bool,
+    /// UID of the relay that delivered this message, if it did not go direct
+    /// (local annotation, never transmitted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relayed_via: Option<String>,
+    /// ID the sender gave a received message, which its edits refer to
+    /// (local annotation, never transmitted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_id: Option<String>,
+    /// Earlier versions, oldest first (empty unless edited)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub edit_history: Vec<MessageEdit>,
+    /// Protection the message had on the wire, once sent or received
+    /// (local annotation, never transmitted; see `storage::protection`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protection: Option<Protection>,
 }
 
 impl Message {
@@ -61,15 +287,108 @@ impl Message {
             id,
             sender,
             recipient,
-            content,
+            content: content.into(),
             timestamp,
             delivered: false,
             delivery_status: DeliveryStatus::Sent,
             next_retry_at: None,
             attempts: 0,
+            metadata: MessageMetadata::new(),
+            pinned: false,
+            starred: false,
+            relayed_via: None,
+            remote_id: None,
+            edit_history: Vec::new(),
+            protection: None,
         }
     }
 
+    /// Attach metadata to the message
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidMetadata` if the metadata does not pass
+    /// `validate_metadata`
+    pub fn with_metadata(mut self, metadata: MessageMetadata) -> Result<Self> {
+        validate_metadata(&metadata)?;
+        self.metadata = metadata;
+        Ok(self)
+    }
+
+    /// Check whether the message carries metadata
+    pub fn has_metadata(&self) -> bool {
+        !self.metadata.is_empty()
+    }
+
+    /// Kind of content, detected from its magic bytes
+    pub fn content_kind(&self) -> ContentKind {
+        ContentKind::detect(&self.content)
+    }
+
+    /// Content as text safe to render, passed through `sanitize_text`
+    ///
+    /// Anything that is not text is shown as a placeholder with its type and
+    /// size, e.g. "[image/png, 340 KB]" or "[binary, 12 bytes]".
+    pub fn display_text(&self) -> String {
+        match self.content_kind() {
+            ContentKind::Text => sanitize_text(&String::from_utf8_lossy(&self.content)),
+            kind => kind.placeholder(self.content.len()),
+        }
+    }
+
+    /// Single-line text preview, truncated to `max_chars` characters
+    ///
+    /// Never cuts a character from its combining marks (see `graphemes`).
+    pub fn preview(&self, max_chars: usize) -> String {
+        let text = self.display_text();
+        let line = text.lines().next().unwrap_or("");
+        let pieces = graphemes(line);
+        if pieces.len() > max_chars {
+            format!("{}…", pieces[..max_chars.saturating_sub(1)].concat())
+        } else {
+            line.to_string()
+        }
+    }
+
+    /// Whether the content was edited after it was sent
+    pub fn is_edited(&self) -> bool {
+        !self.edit_history.is_empty()
+    }
+
+    /// Replace the content, keeping the current one in `edit_history`
+    pub fn replace_content(&mut self, content: Vec<u8>, replaced_at: i64) {
+        let previous = std::mem::replace(&mut self.content, content.into());
+        self.edit_history.push(MessageEdit {
+            content: previous.to_vec(),
+            replaced_at,
+        });
+    }
+
+    /// Format metadata as "key: value" lines for display
+    pub fn metadata_lines(&self) -> Vec<String> {
+        self.metadata
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect()
+    }
+
+    /// Local notice shown in a chat, never sent to the peer
+    pub fn system(text: &str, timestamp: i64) -> Self {
+        let mut message = Self::new(
+            uuid::Uuid::new_v4().to_string(),
+            SYSTEM_SENDER.to_string(),
+            SYSTEM_SENDER.to_string(),
+            text.as_bytes().to_vec(),
+            timestamp,
+        );
+        message.mark_delivered();
+        message
+    }
+
+    /// Whether this is a local notice created by `Message::system`
+    pub fn is_system(&self) -> bool {
+        self.sender == SYSTEM_SENDER
+    }
+
     /// Mark message as delivered
     pub fn mark_delivered(&mut self) {
         self.delivered = true;
@@ -77,6 +396,12 @@ impl Message {
         self.next_retry_at = None;
     }
 
+    /// Mark message as delivered through the relay `relay_uid`
+    pub fn mark_delivered_via_relay(&mut self, relay_uid: &str) {
+        self.mark_delivered();
+        self.relayed_via = Some(relay_uid.to_string());
+    }
+
     /// Mark message as pending retry
     pub fn mark_pending(&mut self, next_retry_at: i64) {
         self.delivery_status = DeliveryStatus::Pending;
@@ -94,7 +419,7 @@ impl Message {
     pub fn time_until_retry(&self) -> Option<i64> {
         self.next_retry_at.map(|retry_at| {
             let now = chrono::Utc::now().timestamp_millis();
-            let seconds = (retry_at - now) / 1000;
+            let seconds = retry_at.saturating_sub(now) / 1000;
             seconds.max(0)
         })
     }
@@ -113,7 +438,10 @@ impl Message {
     pub fn status_text(&self) -> String {
         match self.delivery_status {
             DeliveryStatus::Sent => "sent".to_string(),
-            DeliveryStatus::Delivered => "delivered".to_string(),
+            DeliveryStatus::Delivered => match &self.relayed_via {
+                Some(relay) => format!("delivered via relay {}", relay),
+                None => "delivered".to_string(),
+            },
             DeliveryStatus::Pending => {
                 if let Some(seconds) = self.time_until_retry() {
                     format!("retry in {}", format_retry_time(seconds))
@@ -126,9 +454,11 @@ impl Message {
     }
 }
 
-/// Format retry time as human-readable string
+/// Format retry time as human-readable string (saturates at "999+ days")
 fn format_retry_time(seconds: i64) -> String {
-    if seconds >= 3600 {
+    if seconds > super::bounds::MAX_DISPLAY_SECS {
+        super::bounds::SATURATED_DURATION_TEXT.to_string()
+    } else if seconds >= 3600 {
         format!("{}h {}m", seconds / 3600, (seconds % 3600) / 60)
     } else if seconds >= 60 {
         format!("{}m", seconds / 60)
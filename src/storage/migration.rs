@@ -0,0 +1,178 @@
+//! Import of the legacy `app_state.json` into SQLite
+//!
+//! The whole file is parsed and checked before anything is written, a copy
+//! is kept as `app_state.json.bak`, and all inserts run in one transaction.
+//! A failed migration leaves both the JSON file and the database untouched.
+
+use crate::{
+    storage::{app_state::AppState, message::validate_metadata, storage_db::Storage},
+    Error, Result,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Default location of the legacy JSON state file
+pub const LEGACY_STATE_FILE: &str = "app_state.json";
+
+/// What a migration imports (or would import, for a dry run)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Whether the file carries a user identity
+    pub has_identity: bool,
+    /// Number of contacts
+    pub contacts: usize,
+    /// Number of chats
+    pub chats: usize,
+    /// Number of messages across all chats
+    pub messages: usize,
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{} contacts, {} chats, {} messages",
+            if self.has_identity { "identity, " } else { "" },
+            self.contacts,
+            self.chats,
+            self.messages
+        )
+    }
+}
+
+/// Path of the backup copy made before migrating `json_path`
+pub fn backup_path(json_path: &Path) -> PathBuf {
+    json_path.with_extension("json.bak")
+}
+
+/// Parse a legacy state file
+///
+/// # Errors
+/// `Error::Storage` naming the line and column when the JSON is malformed
+/// or does not match the state structure
+pub fn read_legacy_state(json_path: &Path) -> Result<AppState> {
+    let json = std::fs::read_to_string(json_path)
+        .map_err(|e| Error::Storage(format!("Failed to read {}: {}", json_path.display(), e)))?;
+
+    let mut state: AppState = serde_json::from_str(&json).map_err(|e| {
+        let problem = match e.classify() {
+            serde_json::error::Category::Eof => "file ends unexpectedly (truncated?)".to_string(),
+            serde_json::error::Category::Syntax => "malformed JSON".to_string(),
+            _ => e.to_string(),
+        };
+        Error::Storage(format!(
+            "Cannot parse {} at line {}, column {}: {}",
+            json_path.display(),
+            e.line(),
+            e.column(),
+            problem
+        ))
+    })?;
+    state.settings.bound_limits();
+    Ok(state)
+}
+
+/// Check a parsed legacy state for consistency before importing it
+///
+/// Every chat must belong to a known contact, and contact UIDs, chats and
+/// message IDs must be unique (the database would otherwise silently merge
+/// or reject them half-way through).
+///
+/// # Errors
+/// `Error::Storage` naming the first entity that failed validation
+pub fn validate_legacy_state(state: &AppState) -> Result<MigrationReport> {
+    let invalid = |what: String| Err(Error::Storage(format!("Invalid legacy state: {}", what)));
+
+    let mut contact_uids = HashSet::new();
+    for contact in &state.contacts {
+        if contact.uid.is_empty() {
+            return invalid("contact with empty UID".to_string());
+        }
+        if !contact_uids.insert(contact.uid.as_str()) {
+            return invalid(format!("contact {} appears more than once", contact.uid));
+        }
+    }
+
+    let mut chat_uids = HashSet::new();
+    let mut message_ids = HashSet::new();
+    for chat in &state.chats {
+        if !contact_uids.contains(chat.contact_uid.as_str()) {
+            return invalid(format!("chat {} has no matching contact", chat.contact_uid));
+        }
+        if !chat_uids.insert(chat.contact_uid.as_str()) {
+            return invalid(format!("chat {} appears more than once", chat.contact_uid));
+        }
+        for message in chat.messages.iter() {
+            if !message_ids.insert(message.id.as_str()) {
+                return invalid(format!("message {} appears more than once", message.id));
+            }
+            if let Err(e) = validate_metadata(&message.metadata) {
+                return invalid(format!("message {} in chat {}: {}", message.id, chat.contact_uid, e));
+            }
+        }
+    }
+
+    Ok(MigrationReport {
+        has_identity: state.user_keypair.is_some(),
+        contacts: state.contacts.len(),
+        chats: state.chats.len(),
+        messages: message_ids.len(),
+    })
+}
+
+/// Report what migrating `json_path` would import, without writing anything
+///
+/// # Returns
+/// `None` if there is no legacy file
+///
+/// # Errors
+/// The same parse and validation errors a real migration would stop on
+pub fn dry_run<P: AsRef<Path>>(json_path: P) -> Result<Option<MigrationReport>> {
+    let json_path = json_path.as_ref();
+    if !json_path.exists() {
+        return Ok(None);
+    }
+    let state = read_legacy_state(json_path)?;
+    validate_legacy_state(&state).map(Some)
+}
+
+/// Import `json_path` into `db`
+///
+/// On success the original file is removed and its copy stays at
+/// `backup_path()`. On failure the backup is removed again, the transaction
+/// is rolled back and the original file is left in place.
+///
+/// # Returns
+/// `None` if there is no legacy file
+///
+/// # Errors
+/// Parse, validation, backup or database errors
+pub fn migrate<P: AsRef<Path>>(json_path: P, db: &Storage) -> Result<Option<MigrationReport>> {
+    let json_path = json_path.as_ref();
+    if !json_path.exists() {
+        return Ok(None);
+    }
+
+    let state = read_legacy_state(json_path)?;
+    let report = validate_legacy_state(&state)?;
+
+    let backup = backup_path(json_path);
+    std::fs::copy(json_path, &backup)
+        .map_err(|e| Error::Storage(format!("Failed to back up {}: {}", json_path.display(), e)))?;
+
+    // save_to_db writes everything in a single transaction
+    if let Err(e) = state.save_to_db(db) {
+        let _ = std::fs::remove_file(&backup);
+        return Err(Error::Storage(format!(
+            "Migration of {} rolled back: {}",
+            json_path.display(),
+            e
+        )));
+    }
+
+    std::fs::remove_file(json_path)
+        .map_err(|e| Error::Storage(format!("Failed to remove migrated {}: {}", json_path.display(), e)))?;
+
+    Ok(Some(report))
+}
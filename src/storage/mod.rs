@@ -8,30 +8,153 @@
 //!
 //! The module is organized into submodules for better maintainability:
 //! - `contact` - Contact/peer management and token generation/verification
+//! - `address` - Typed, validated contact addresses and the URLs built from them
+//! - `bounds` - Bounds on token expiries and timestamps supplied by peers
+//! - `address_change` - Address changes of verified contacts staged for review
 //! - `message` - Message structures and delivery status
+//! - `content` - Binary content detection, size formatting and downloads
+//! - `export` - JSON Lines chat export schema and options
+//! - `chat_archive` - Signed chat archive the other participant can verify and cross-reference
+//! - `journal` - Opt-in hash-chained journal of delivered outgoing messages
 //! - `chat` - Chat conversation management
+//! - `local_time` - A contact's UTC offset, learned from message timestamps or set by hand
+//! - `chat_summary` - Per-chat message counts and latest message, read by the chat list
 //! - `settings` - Application settings and configuration
 //! - `settings_manager` - Thread-safe settings management
+//! - `privacy` - Per-contact overrides for read receipts, typing and presence
+//! - `trust` - Per-contact trust tier and the outbound policy every send path asks
+//! - `capability_probe` - When to ask a contact for capabilities it never advertised
+//! - `ephemeral` - Temporary contacts deleted with their chat after a chosen lifetime
+//! - `soft_delete` - Soft-deleted chats and contacts, the undo window and purge tombstones
+//! - `template` - Message templates (canned responses)
+//! - `token_armor` - Armored contact tokens with per-line checksums, for manual transcription
+//! - `identity` - UID/key consistency checks and identity conflicts
+//! - `introductions` - Pending introductions imported from other messengers' contact lists (CSV/vCard)
 //! - `app_state` - Persistent application state
+//! - `uid_index` - UID → position index behind `AppState`'s contact and chat lookups
+//! - `runtime_info` - Atomically written pid/port file for finding the running instance
+//! - `session_marker` - File present while a session runs, left behind by an unclean exit
+//! - `read_only` - Strictly read-only connections to a copied app_data directory (inspection mode)
+//! - `snapshot` - Sealed database snapshots and the streaming snapshot diff
+//! - `migration` - Validated import of the legacy `app_state.json`
 //! - `storage_db` - Low-level SQLite database (unimplemented)
+//! - `deferred_writes` - Changes held in memory while the database is locked
 
 // Submodules
+pub mod address;
+pub mod address_change;
 pub mod app_state;
+pub mod bounds;
+pub mod capability_probe;
 pub mod chat;
+pub mod chat_archive;
+pub mod chat_summary;
 pub mod contact;
+pub mod content;
+pub mod deferred_writes;
+pub mod ephemeral;
+pub mod export;
+pub mod identity;
+pub mod introductions;
+pub mod journal;
+pub mod local_time;
 pub mod message;
+pub mod migration;
+pub mod privacy;
+pub mod protection;
+pub mod read_only;
+pub mod runtime_info;
+pub mod session_marker;
 pub mod settings;
 pub mod settings_manager;
+pub mod snapshot;
+pub mod soft_delete;
 pub mod storage_db;
+pub mod template;
+pub mod token_armor;
+pub mod trust;
+pub mod uid_index;
 
 // Re-export commonly used types
-pub use app_state::AppState;
-pub use chat::Chat;
-pub use contact::Contact;
-pub use message::{DeliveryStatus, Message};
-pub use settings::Settings;
+pub use address::{validate_contact_addresses, PeerAddress, PeerHost, INVALID_ADDRESS_STATUS};
+pub use address_change::{AddressChange, AddressSource};
+pub use app_state::{AppState, ContactIngest, IDENTITY_SCAN_CHECK};
+pub use bounds::{
+    bound_message_timestamp, bounded_timestamp, clamp_token_expiry, require_min_validity, timestamp_in_bounds,
+    token_expires_soon, DEFAULT_MAX_TOKEN_EXPIRY_DAYS, DEFAULT_MIN_TOKEN_VALIDITY_MINUTES, MAX_CLOCK_SKEW_MINUTES, MAX_TOKEN_EXPIRY_DAYS_CAP, MAX_DISPLAY_SECS, MAX_TIMESTAMP_AGE_DAYS, ORIGINAL_TIMESTAMP_KEY, SATURATED_DURATION_TEXT,
+};
+pub use capability_probe::{
+    probe_due, CapabilityProbes, CapabilityRecord, CAPABILITIES_STALE_DAYS, LEGACY_REPROBE_DAYS, PROBE_MIN_INTERVAL_MINUTES,
+};
+pub use chat_archive::{
+    archive_file_name, cross_reference, ArchiveManifest, ArchiveProblem, ArchiveVerification, ChatArchive, CrossReference,
+    ManifestEntry, CHAT_ARCHIVE_VERSION,
+};
+pub use chat::{Chat, HISTORY_LIMIT_PRESETS, HISTORY_TRIMMED_NOTICE, MAX_PINNED_PER_CHAT};
+pub use chat_summary::{preview_text, MessageSummary, SummaryMigration, SUMMARY_PREVIEW_CHARS};
+pub use contact::{
+    pinned_endpoints, split_address_scheme, Contact, ContactEndpoint, DEFAULT_ADDRESS_SCHEME, MAX_CONTACT_NOTES_BYTES,
+    TLS_ADDRESS_SCHEME,
+};
+pub use content::{create_download_file, download_file_name, format_size, ContentKind, DOWNLOADS_DIR};
+pub use deferred_writes::DeferredWrites;
+pub use ephemeral::{ephemeral_warning_text, Ephemeral, EphemeralLifetime, EphemeralSweep, EPHEMERAL_WARNING_HOURS};
+pub use export::{
+    export_file_name, ContentMode, ExportDirection, ExportedMessage, JsonlExportOptions, EXPORT_PAGE_SIZE,
+    JSONL_EXPORT_VERSION,
+};
+pub use identity::{
+    check_incoming_contact, scan_contacts, verify_contact_uid, ConflictKind, IdentityCheck,
+    IdentityConflict,
+};
+pub use introductions::{
+    best_match, decode_text, name_similarity, name_words, parse_csv_introductions, parse_introduction_list,
+    parse_vcard_introductions, IntroductionImport, IntroductionRowError, PendingIntroduction, FUZZY_MATCH_THRESHOLD,
+    MAX_INTRODUCTION_NAME_CHARS,
+};
+pub use journal::{
+    export_journal, journal_export_file_name, verify_chain, verify_journal_export, JournalBreak, JournalBreakReason,
+    JournalExportFormat, JournalKind, JournalRecord, JOURNAL_CSV_HEADER, JOURNAL_GENESIS_HASH,
+};
+pub use message::{
+    graphemes, sanitize_text, validate_metadata, DeliveryStatus, Message, MessageEdit, MessageMetadata,
+    MetadataValue, MAX_MESSAGE_BYTES, MAX_METADATA_BYTES, SYSTEM_SENDER,
+};
+pub use local_time::{
+    format_utc_offset, infer_utc_offset, is_night_at, local_time_at, parse_utc_offset, record_offset_sample,
+    snap_offset_minutes, OffsetSample, UtcOffset, MAX_OFFSET_SAMPLES, MIN_OFFSET_SAMPLES, NIGHT_END_MINUTES,
+    NIGHT_START_MINUTES, OFFSET_SAMPLE_HALF_LIFE_DAYS, OFFSET_SAMPLE_MAX_AGE_DAYS,
+};
+pub use migration::{MigrationReport, LEGACY_STATE_FILE};
+pub use privacy::{signal_enabled, ContactPrivacy, PrivacySignal, SignalOverride};
+pub use protection::Protection;
+pub use read_only::{check_not_live, open_read_only, WriteLog, DATABASE_FILE_NAME, QUEUE_FILE_NAME};
+pub use runtime_info::{
+    live_instance, process_alive, read_runtime_info, remove_runtime_info, write_runtime_info, RuntimeInfo,
+    RUNTIME_INFO_FILE,
+};
+pub use session_marker::{begin_session, end_session, SessionMarker, SESSION_MARKER_FILE};
+pub use settings::{
+    format_time_of_day, is_quiet, parse_time_of_day, AccentColor, AlertMode, AlertStyle, ErrorSeverity, Settings, ALL_DAYS_MASK, DEFAULT_ADDRESS_AUTO_APPLY_FAILURES,
+    DEFAULT_HISTORY_LIMIT, MAX_PROFILE_LABEL_CHARS,
+};
 pub use settings_manager::SettingsManager;
-pub use storage_db::{RequestLog, Storage};
+pub use snapshot::{
+    diff_databases, diff_snapshot_files, list_snapshots, snapshot_file_name, ChangeList, ContactChange, FieldChange,
+    MessageCountChange, SnapshotComparison, SnapshotDiff, SnapshotFile, SNAPSHOTS_DIR, SNAPSHOT_DIFF_LIST_LIMIT,
+    SNAPSHOT_DIFF_VERSION,
+};
+pub use soft_delete::{purge_cutoff, DeletedKind, Tombstone, DEFAULT_SOFT_DELETE_WINDOW_HOURS};
+pub use storage_db::{
+    is_busy_error, BusyPolicy, ConnectivityHistoryEntry, IntegrityReport, RequestLog, Storage,
+    CONNECTIVITY_HISTORY_LIMIT,
+};
+pub use template::{MessageTemplate, MAX_TEMPLATES};
+pub use token_armor::{armor_token, dearmor_token, has_armor_footer, is_armored, token_from_input, ARMOR_FOOTER, ARMOR_HEADER};
+pub use trust::{is_lan_address, own_token_for, own_token_inputs, OutboundPolicy, TrustTier};
 
 // Re-export main functions
-pub use contact::{generate_contact_token, parse_contact_token};
+pub use contact::{
+    generate_contact_token, generate_multi_endpoint_token, generate_pinned_token, parse_contact_token,
+    parse_contact_token_any_expiry,
+};
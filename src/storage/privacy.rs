@@ -0,0 +1,137 @@
+//! Per-contact privacy of read receipts, typing indicators and presence
+//!
+//! Each signal has a global default in `Settings` and a three-state override
+//! per contact (`SignalOverride`). An override stays as chosen when the
+//! global default changes; only contacts left at `Default` follow it.
+//! `signal_enabled` is the one place the two are combined, through
+//! `OutboundPolicy`, which also turns every signal off for restricted
+//! contacts (see `storage::trust`).
+
+use super::{contact::Contact, settings::Settings, trust::OutboundPolicy};
+use serde::{Deserialize, Serialize};
+
+/// Signals a peer may send about its user's activity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivacySignal {
+    /// "Read" notices for received messages
+    ReadReceipts,
+    /// "Typing…" indicators
+    Typing,
+    /// Online announcements
+    Presence,
+}
+
+impl PrivacySignal {
+    /// All signals, in display order
+    pub const ALL: [PrivacySignal; 3] = [PrivacySignal::ReadReceipts, PrivacySignal::Typing, PrivacySignal::Presence];
+
+    /// Short name for the contact details popup
+    pub fn label(self) -> &'static str {
+        match self {
+            PrivacySignal::ReadReceipts => "receipts",
+            PrivacySignal::Typing => "typing",
+            PrivacySignal::Presence => "presence",
+        }
+    }
+}
+
+/// Per-contact choice for one signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignalOverride {
+    /// Follow the global setting
+    #[default]
+    Default,
+    /// Always send to this contact
+    On,
+    /// Never send to this contact
+    Off,
+}
+
+impl SignalOverride {
+    /// Next state in the popup cycle: default → on → off → default
+    pub fn cycle(self) -> Self {
+        match self {
+            SignalOverride::Default => SignalOverride::On,
+            SignalOverride::On => SignalOverride::Off,
+            SignalOverride::Off => SignalOverride::Default,
+        }
+    }
+
+    /// Effective value given the global setting
+    pub fn resolve(self, global: bool) -> bool {
+        match self {
+            SignalOverride::Default => global,
+            SignalOverride::On => true,
+            SignalOverride::Off => false,
+        }
+    }
+
+    /// Popup text, e.g. "default (on)" or "off"
+    pub fn describe(self, global: bool) -> String {
+        let on_off = |value: bool| if value { "on" } else { "off" };
+        match self {
+            SignalOverride::Default => format!("default ({})", on_off(global)),
+            explicit => on_off(explicit.resolve(global)).to_string(),
+        }
+    }
+}
+
+/// A contact's overrides for all signals (local only, never sent to peers)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ContactPrivacy {
+    /// Read receipts override
+    #[serde(default)]
+    pub read_receipts: SignalOverride,
+    /// Typing indicator override
+    #[serde(default)]
+    pub typing: SignalOverride,
+    /// Presence override
+    #[serde(default)]
+    pub presence: SignalOverride,
+}
+
+impl ContactPrivacy {
+    /// Override for `signal`
+    pub fn get(&self, signal: PrivacySignal) -> SignalOverride {
+        match signal {
+            PrivacySignal::ReadReceipts => self.read_receipts,
+            PrivacySignal::Typing => self.typing,
+            PrivacySignal::Presence => self.presence,
+        }
+    }
+
+    /// Mutable override for `signal`
+    pub fn get_mut(&mut self, signal: PrivacySignal) -> &mut SignalOverride {
+        match signal {
+            PrivacySignal::ReadReceipts => &mut self.read_receipts,
+            PrivacySignal::Typing => &mut self.typing,
+            PrivacySignal::Presence => &mut self.presence,
+        }
+    }
+
+    /// Whether every signal follows the global settings
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Settings {
+    /// Global default for `signal`
+    pub fn sends(&self, signal: PrivacySignal) -> bool {
+        match signal {
+            PrivacySignal::ReadReceipts => self.send_read_receipts,
+            PrivacySignal::Typing => self.send_typing,
+            PrivacySignal::Presence => self.send_presence,
+        }
+    }
+}
+
+/// Whether `signal` may be sent to `contact`
+///
+/// Never for a restricted contact. Otherwise the contact's override wins;
+/// `Default` falls back to the global setting. Every send path asks this
+/// before building anything for the wire.
+pub fn signal_enabled(settings: &Settings, contact: &Contact, signal: PrivacySignal) -> bool {
+    OutboundPolicy::for_contact(contact).allows_signal(settings, signal)
+}
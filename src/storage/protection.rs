@@ -0,0 +1,101 @@
+//! Protection each message actually had on the wire
+//!
+//! The send path records the level `seal_request` applied once a message is
+//! delivered (the retry worker does the same for queued messages), and the
+//! message handler records whether an incoming text arrived sealed. Nothing
+//! is inferred from the contact's current key: a message sent in plaintext
+//! stays marked plaintext after the contact's key arrives.
+//!
+//! Levels are compared per direction. When a message's level differs from
+//! the previous recorded one in the same direction, `Chat::record_protection`
+//! inserts a notice before it, so a chat shows where sealing started or
+//! stopped without flapping when the two sides differ.
+//!
+//! Only two levels exist: plaintext and sealed under the static X25519 secret
+//! (see `sealing`); there is no ratchet. Whether TLS carried a request is not
+//! recorded, as transports do not report which connection they used.
+
+use super::chat::Chat;
+use super::message::Message;
+use serde::{Deserialize, Serialize};
+
+/// Protection a message had on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protection {
+    /// Sent or received as plaintext
+    Plaintext,
+    /// Sealed end-to-end with the contact's X25519 key
+    Sealed,
+}
+
+impl Protection {
+    /// Stable name, as stored and exported
+    pub fn name(self) -> &'static str {
+        match self {
+            Protection::Plaintext => "plaintext",
+            Protection::Sealed => "sealed",
+        }
+    }
+
+    /// Parse a stored name (None if unknown)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "plaintext" => Some(Protection::Plaintext),
+            "sealed" => Some(Protection::Sealed),
+            _ => None,
+        }
+    }
+
+    /// Marker shown next to a message in the chat view
+    pub fn marker(self) -> &'static str {
+        match self {
+            Protection::Plaintext => "○",
+            Protection::Sealed => "🔒",
+        }
+    }
+
+    /// Notice inserted where messages in one direction change to this level
+    pub fn transition_notice(self, incoming: bool) -> &'static str {
+        match (self, incoming) {
+            (Protection::Sealed, false) => "Messages you send are now end-to-end encrypted",
+            (Protection::Plaintext, false) => "Messages you send are no longer encrypted",
+            (Protection::Sealed, true) => "Messages you receive are now end-to-end encrypted",
+            (Protection::Plaintext, true) => "Messages you receive are no longer encrypted",
+        }
+    }
+}
+
+impl Chat {
+    /// Record the protection a message had on the wire
+    ///
+    /// The level is compared with the previous message in the same direction
+    /// that has one; if it differs, a `Message::system` notice is inserted
+    /// right before the message. Recording the same level again does nothing,
+    /// so each change is announced once.
+    ///
+    /// # Returns
+    /// Whether the message's level changed (false if it is not in the chat)
+    pub fn record_protection(&mut self, message_id: &str, protection: Protection) -> bool {
+        let Some(index) = self.messages.iter().position(|m| m.id == message_id) else {
+            return false;
+        };
+        if self.messages[index].protection == Some(protection) {
+            return false;
+        }
+        let incoming = self.messages[index].sender == self.contact_uid;
+        let previous = self.messages[..index]
+            .iter()
+            .rev()
+            .filter(|m| !m.is_system() && (m.sender == self.contact_uid) == incoming)
+            .find_map(|m| m.protection);
+
+        let messages = self.messages_mut();
+        messages[index].protection = Some(protection);
+        if previous.is_some_and(|previous| previous != protection) {
+            let timestamp = messages[index].timestamp;
+            messages.insert(index, Message::system(protection.transition_notice(incoming), timestamp - 1));
+        }
+        true
+    }
+}
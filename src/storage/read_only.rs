@@ -0,0 +1,208 @@
+//! Strictly read-only access to a copied app_data directory
+//!
+//! Inspection mode (`--inspect <dir>`) opens another installation's files to
+//! browse them. Nothing in that directory may change, so each SQLite file is
+//! opened three ways at once:
+//! - as an immutable, read-only URI, so SQLite takes no locks and never
+//!   writes the file, its journal or a WAL;
+//! - with `PRAGMA query_only`, so the connection refuses writing statements;
+//! - behind an authorizer that denies every write action and records it in a
+//!   `WriteLog`, so a code path that still tries to write is found (and tests
+//!   can assert that none did).
+//!
+//! `check_not_live` refuses a directory some running instance still uses:
+//! inspecting a live database could read it mid-write. An immutable open also
+//! ignores a hot journal or WAL, so a copy taken after a crash would show the
+//! database without its last transactions (or a half-written one); such a file
+//! is refused while a non-empty `-journal` or `-wal` lies next to it.
+
+use crate::storage::runtime_info::read_json;
+use crate::storage::session_marker::SessionMarker;
+use crate::storage::RuntimeInfo;
+use crate::{Error, Result};
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+use rusqlite::{Connection, OpenFlags};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Database file inside an app_data directory
+pub const DATABASE_FILE_NAME: &str = "pure2p.db";
+
+/// Queue database file inside an app_data directory
+pub const QUEUE_FILE_NAME: &str = "message_queue.db";
+
+/// Pragmas that take an argument without changing anything
+const READ_PRAGMAS: &[&str] = &[
+    "table_info",
+    "table_xinfo",
+    "index_list",
+    "index_info",
+    "index_xinfo",
+    "foreign_key_list",
+    "quick_check",
+    "integrity_check",
+];
+
+/// Write attempts denied on read-only connections, shared between them
+#[derive(Debug, Clone, Default)]
+pub struct WriteLog {
+    attempts: Arc<Mutex<Vec<String>>>,
+}
+
+impl WriteLog {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a denied write
+    pub fn record(&self, attempt: String) {
+        tracing::warn!("Denied write in read-only mode: {}", attempt);
+        self.attempts.lock().unwrap().push(attempt);
+    }
+
+    /// Denied writes, oldest first
+    pub fn attempts(&self) -> Vec<String> {
+        self.attempts.lock().unwrap().clone()
+    }
+
+    /// Number of denied writes
+    pub fn len(&self) -> usize {
+        self.attempts.lock().unwrap().len()
+    }
+
+    /// Whether nothing tried to write
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The write `action` would make, as one line (None for reads)
+fn describe_write(action: &AuthAction<'_>) -> Option<String> {
+    let line = match action {
+        AuthAction::Insert { table_name } => format!("insert into {}", table_name),
+        AuthAction::Update { table_name, column_name } => format!("update {}.{}", table_name, column_name),
+        AuthAction::Delete { table_name } => format!("delete from {}", table_name),
+        AuthAction::CreateTable { table_name } | AuthAction::CreateTempTable { table_name } => {
+            format!("create table {}", table_name)
+        }
+        AuthAction::CreateIndex { index_name, .. } | AuthAction::CreateTempIndex { index_name, .. } => {
+            format!("create index {}", index_name)
+        }
+        AuthAction::CreateTrigger { trigger_name, .. } | AuthAction::CreateTempTrigger { trigger_name, .. } => {
+            format!("create trigger {}", trigger_name)
+        }
+        AuthAction::CreateView { view_name } | AuthAction::CreateTempView { view_name } => {
+            format!("create view {}", view_name)
+        }
+        AuthAction::DropTable { table_name } | AuthAction::DropTempTable { table_name } => {
+            format!("drop table {}", table_name)
+        }
+        AuthAction::DropIndex { index_name, .. } | AuthAction::DropTempIndex { index_name, .. } => {
+            format!("drop index {}", index_name)
+        }
+        AuthAction::DropTrigger { trigger_name, .. } | AuthAction::DropTempTrigger { trigger_name, .. } => {
+            format!("drop trigger {}", trigger_name)
+        }
+        AuthAction::DropView { view_name } | AuthAction::DropTempView { view_name } => {
+            format!("drop view {}", view_name)
+        }
+        AuthAction::AlterTable { table_name, .. } => format!("alter table {}", table_name),
+        AuthAction::Reindex { index_name } => format!("reindex {}", index_name),
+        AuthAction::Analyze { table_name } => format!("analyze {}", table_name),
+        AuthAction::CreateVtable { table_name, .. } => format!("create virtual table {}", table_name),
+        AuthAction::DropVtable { table_name, .. } => format!("drop virtual table {}", table_name),
+        AuthAction::Attach { filename } => format!("attach {}", filename),
+        AuthAction::Pragma { pragma_name, pragma_value: Some(value) }
+            if !READ_PRAGMAS.contains(&pragma_name.to_ascii_lowercase().as_str()) =>
+        {
+            format!("pragma {} = {}", pragma_name, value)
+        }
+        _ => return None,
+    };
+    Some(line)
+}
+
+/// Sidecar files SQLite recovers from on a normal open
+const SIDECAR_SUFFIXES: &[&str] = &["-journal", "-wal"];
+
+/// A non-empty journal or WAL left next to the database at `path`
+fn unfinished_sidecar(path: &Path) -> Option<std::path::PathBuf> {
+    SIDECAR_SUFFIXES
+        .iter()
+        .map(|suffix| {
+            let mut name = path.as_os_str().to_os_string();
+            name.push(suffix);
+            std::path::PathBuf::from(name)
+        })
+        .find(|sidecar| std::fs::metadata(sidecar).is_ok_and(|meta| meta.len() > 0))
+}
+
+/// SQLite URI opening `path` as an immutable file
+fn immutable_uri(path: &Path) -> String {
+    let escaped = path
+        .to_string_lossy()
+        .replace('%', "%25")
+        .replace('?', "%3f")
+        .replace('#', "%23");
+    format!("file:{}?immutable=1", escaped)
+}
+
+/// Open the SQLite file at `path` strictly read-only
+///
+/// Write attempts fail and are recorded in `writes`.
+///
+/// # Errors
+/// Returns an error if the file is missing, has an unfinished journal or WAL
+/// next to it, or cannot be opened
+pub fn open_read_only(path: &Path, writes: &WriteLog) -> Result<Connection> {
+    if !path.is_file() {
+        return Err(Error::Storage(format!("No database at {}", path.display())));
+    }
+    if let Some(sidecar) = unfinished_sidecar(path) {
+        return Err(Error::Storage(format!(
+            "{} has an unfinished transaction in {} (copied after a crash or mid-write); \
+             open a copy once normally to recover it, then inspect that",
+            path.display(),
+            sidecar.display()
+        )));
+    }
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    let conn = Connection::open_with_flags(immutable_uri(path), flags)
+        .map_err(|e| Error::Storage(format!("Failed to open {} read-only: {}", path.display(), e)))?;
+    conn.pragma_update(None, "query_only", true)?;
+
+    let writes = writes.clone();
+    conn.authorizer(Some(move |context: AuthContext<'_>| match describe_write(&context.action) {
+        Some(attempt) => {
+            writes.record(attempt);
+            Authorization::Deny
+        }
+        None => Authorization::Allow,
+    }));
+    Ok(conn)
+}
+
+/// Refuse to inspect `dir` if a running instance uses it
+///
+/// Its runtime info file or session marker naming a process `is_alive`
+/// accepts means the directory is live. Neither file is touched, unlike
+/// `live_instance` which removes stale ones.
+///
+/// # Errors
+/// Returns an error naming the running pid, or if `dir` holds no database
+pub fn check_not_live(dir: &Path, is_alive: impl Fn(u32) -> bool) -> Result<()> {
+    if !dir.join(DATABASE_FILE_NAME).is_file() {
+        return Err(Error::Storage(format!("{} is not an app_data directory (no {})", dir.display(), DATABASE_FILE_NAME)));
+    }
+    let runtime_pid = read_json::<RuntimeInfo>(&dir.join("runtime.json")).ok().flatten().map(|info| info.pid);
+    let marker_pid = read_json::<SessionMarker>(&dir.join("session.marker")).ok().flatten().map(|marker| marker.pid);
+    match [runtime_pid, marker_pid].into_iter().flatten().find(|&pid| pid != 0 && is_alive(pid)) {
+        Some(pid) => Err(Error::Storage(format!(
+            "{} is in use by a running instance (pid {}); inspect a copy of it instead",
+            dir.display(),
+            pid
+        ))),
+        None => Ok(()),
+    }
+}
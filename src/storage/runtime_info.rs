@@ -0,0 +1,159 @@
+//! Runtime info file for finding the running instance
+//!
+//! The listening port changes whenever the startup bind loop has to move off
+//! the preferred one, so tooling cannot rely on the port saved in the
+//! database (which may also be mid-write). Instead the transport thread writes
+//! `RUNTIME_INFO_FILE` once the server is running, and the port watchdog
+//! rewrites it after every rebind. Each write goes to a temporary file next to
+//! it that is then renamed over it, so a reader sees the old file or the new
+//! one, never half of one.
+//!
+//! The file is removed on clean shutdown, and only by the process it names. A
+//! crash leaves it behind: `live_instance()` ignores and removes a file whose
+//! pid is no longer running, or that cannot be parsed.
+//!
+//! There is no local control API yet, so the file names no socket or token;
+//! it is only a way to find the live instance's pid and port.
+
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Runtime info file of the running instance (production)
+pub const RUNTIME_INFO_FILE: &str = "./app_data/runtime.json";
+
+/// What a running instance publishes about itself
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuntimeInfo {
+    /// Process id of the instance
+    pub pid: u32,
+    /// Port the transport server is listening on
+    pub port: u16,
+    /// When the instance started (Unix milliseconds)
+    pub started_at: i64,
+}
+
+impl RuntimeInfo {
+    /// Info for this process listening on `port`
+    pub fn for_this_process(port: u16, started_at: DateTime<Utc>) -> Self {
+        Self {
+            pid: std::process::id(),
+            port,
+            started_at: started_at.timestamp_millis(),
+        }
+    }
+}
+
+/// Temporary file a new runtime info file is written to before the rename
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Write `value` as JSON to `path` atomically (temporary file, then rename)
+pub(crate) fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temp = temp_path(path);
+    std::fs::write(&temp, serde_json::to_vec_pretty(value)?)?;
+    std::fs::rename(&temp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        Error::Storage(format!("Failed to replace {}: {}", path.display(), e))
+    })
+}
+
+/// Write `info` to `path` atomically (temporary file, then rename)
+pub fn write_runtime_info(path: &Path, info: &RuntimeInfo) -> Result<()> {
+    write_json_atomic(path, info)
+}
+
+/// Read the runtime info file as written, without checking the pid
+///
+/// # Returns
+/// None if there is no file
+pub fn read_runtime_info(path: &Path) -> Result<Option<RuntimeInfo>> {
+    read_json(path)
+}
+
+/// Read a JSON file written by `write_json_atomic` (None if there is none)
+pub(crate) fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The running instance named by `path`, if its process is alive
+///
+/// A file naming a process `is_alive` rejects, or one that cannot be parsed,
+/// was left by a crash: it is removed.
+pub fn live_instance(path: &Path, is_alive: impl Fn(u32) -> bool) -> Option<RuntimeInfo> {
+    match read_runtime_info(path) {
+        Ok(Some(info)) if is_alive(info.pid) => Some(info),
+        Ok(Some(info)) => {
+            tracing::info!("Removing stale runtime info of pid {}", info.pid);
+            let _ = std::fs::remove_file(path);
+            None
+        }
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!("Removing unreadable runtime info {}: {}", path.display(), e);
+            let _ = std::fs::remove_file(path);
+            None
+        }
+    }
+}
+
+/// Remove the runtime info file if it names `pid`
+///
+/// # Returns
+/// Whether a file was removed (another instance's file is left alone)
+pub fn remove_runtime_info(path: &Path, pid: u32) -> Result<bool> {
+    match read_runtime_info(path) {
+        Ok(Some(info)) if info.pid == pid => {}
+        Ok(_) => return Ok(false),
+        // Nobody can use an unreadable file
+        Err(_) => {}
+    }
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether a process with `pid` is running
+///
+/// Platforms without a check treat every pid as alive.
+pub fn process_alive(pid: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        Path::new("/proc").join(pid.to_string()).exists()
+    }
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        std::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(true)
+    }
+    #[cfg(windows)]
+    {
+        std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stdout).contains(&pid.to_string()))
+            .unwrap_or(true)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = pid;
+        true
+    }
+}
@@ -0,0 +1,97 @@
+//! Session marker for noticing that the previous session ended uncleanly
+//!
+//! `begin_session` writes `SESSION_MARKER_FILE` at startup and `end_session`
+//! removes it once the app has saved its state and is about to exit. A
+//! marker found at startup was left by a session that crashed, was killed,
+//! or lost power, so the app runs its recovery steps eagerly and says what
+//! they did (see `tui::recovery`).
+//!
+//! Unlike the runtime info file, the marker is not removed on unwinding: a
+//! panic in the main thread still counts as an unclean end.
+
+use crate::storage::runtime_info::{read_json, write_json_atomic};
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Session marker of the running instance (production)
+pub const SESSION_MARKER_FILE: &str = "./app_data/session.marker";
+
+/// What a session writes about itself when it starts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionMarker {
+    /// Process id of the session (0 if the marker could not be read)
+    pub pid: u32,
+    /// When the session started (Unix milliseconds, 0 if unknown)
+    pub started_at: i64,
+}
+
+impl SessionMarker {
+    /// Marker for this process, started at `started_at`
+    pub fn for_this_process(started_at: DateTime<Utc>) -> Self {
+        Self {
+            pid: std::process::id(),
+            started_at: started_at.timestamp_millis(),
+        }
+    }
+
+    /// Stand-in for a marker that was left behind but cannot be read
+    pub fn unreadable() -> Self {
+        Self { pid: 0, started_at: 0 }
+    }
+
+    /// When the session started, if known
+    pub fn started(&self) -> Option<DateTime<Utc>> {
+        (self.started_at > 0).then(|| DateTime::from_timestamp_millis(self.started_at)).flatten()
+    }
+}
+
+/// Write `marker` to `path`, reporting the session that left one behind
+///
+/// A marker naming another process that `is_alive` accepts belongs to an
+/// instance still running, not to a crash. One naming this process was left
+/// by an earlier session whose pid was reused.
+///
+/// # Returns
+/// The marker of the previous session if it ended uncleanly
+/// (`SessionMarker::unreadable` if its file could not be parsed)
+///
+/// # Errors
+/// Returns an error if the new marker cannot be written
+pub fn begin_session(path: &Path, marker: &SessionMarker, is_alive: impl Fn(u32) -> bool) -> Result<Option<SessionMarker>> {
+    let previous = match read_json::<SessionMarker>(path) {
+        Ok(Some(previous)) if previous.pid != marker.pid && is_alive(previous.pid) => {
+            tracing::warn!("Session marker names pid {}, which is still running", previous.pid);
+            None
+        }
+        Ok(previous) => previous,
+        Err(e) => {
+            tracing::warn!("Unreadable session marker {}: {}", path.display(), e);
+            Some(SessionMarker::unreadable())
+        }
+    };
+    write_json_atomic(path, marker)?;
+    Ok(previous)
+}
+
+/// Remove the marker at `path` if it names `pid` (a clean exit)
+///
+/// # Returns
+/// Whether a marker was removed (another instance's is left alone)
+///
+/// # Errors
+/// Returns an error if the file exists but cannot be removed
+pub fn end_session(path: &Path, pid: u32) -> Result<bool> {
+    match read_json::<SessionMarker>(path) {
+        Ok(Some(marker)) if marker.pid == pid => {}
+        Ok(_) => return Ok(false),
+        // Nobody can use an unreadable marker
+        Err(_) => {}
+    }
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -1,8 +1,322 @@
 //! Application settings and configuration
 
-use crate::{Error, Result};
+use crate::{
+    storage::{
+        bounds::MAX_TOKEN_EXPIRY_DAYS_CAP,
+        template::{MessageTemplate, MAX_TEMPLATES},
+    },
+    Error, Result,
+};
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike};
 use serde::{Deserialize, Serialize};
 
+/// Minutes in a day (quiet hours are stored as minutes after local midnight)
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Days-of-week mask with every day enabled (bit 0 = Monday ... bit 6 = Sunday)
+pub const ALL_DAYS_MASK: u8 = 0b111_1111;
+
+fn default_quiet_hours_start() -> u32 {
+    22 * 60 // 22:00
+}
+
+fn default_quiet_hours_end() -> u32 {
+    7 * 60 // 07:00
+}
+
+fn default_quiet_hours_days() -> u8 {
+    ALL_DAYS_MASK
+}
+
+fn default_invite_code_length() -> usize {
+    crate::invite::DEFAULT_INVITE_CODE_LENGTH
+}
+
+/// Messages kept per chat unless configured otherwise
+pub const DEFAULT_HISTORY_LIMIT: u32 = 2000;
+
+fn default_history_limit() -> u32 {
+    DEFAULT_HISTORY_LIMIT
+}
+
+/// Seconds within which re-sending the previous text asks for confirmation
+pub const DEFAULT_DUPLICATE_WINDOW_SECS: u32 = 3;
+
+fn default_duplicate_window_secs() -> u32 {
+    DEFAULT_DUPLICATE_WINDOW_SECS
+}
+
+/// Failed deliveries to a contact's old address before a staged address change applies itself
+pub const DEFAULT_ADDRESS_AUTO_APPLY_FAILURES: u32 = 3;
+
+fn default_address_auto_apply_failures() -> u32 {
+    DEFAULT_ADDRESS_AUTO_APPLY_FAILURES
+}
+
+fn default_send_signal() -> bool {
+    true
+}
+
+fn default_auto_send_preview() -> bool {
+    true
+}
+
+fn default_night_warning_enabled() -> bool {
+    true
+}
+
+fn default_edit_window_minutes() -> u32 {
+    crate::edits::DEFAULT_EDIT_WINDOW_MINUTES
+}
+
+fn default_max_token_expiry_days() -> u32 {
+    super::bounds::DEFAULT_MAX_TOKEN_EXPIRY_DAYS
+}
+
+fn default_min_token_validity_minutes() -> u32 {
+    super::bounds::DEFAULT_MIN_TOKEN_VALIDITY_MINUTES
+}
+
+fn default_soft_delete_window_hours() -> u32 {
+    super::soft_delete::DEFAULT_SOFT_DELETE_WINDOW_HOURS
+}
+
+fn default_log_max_file_bytes() -> u64 {
+    crate::logging::DEFAULT_LOG_MAX_FILE_BYTES
+}
+
+fn default_log_files_kept() -> u32 {
+    crate::logging::DEFAULT_LOG_FILES_KEPT
+}
+
+fn default_update_manifest_url() -> String {
+    crate::update_check::DEFAULT_UPDATE_MANIFEST_URL.to_string()
+}
+
+fn default_auto_import_contacts_per_hour() -> u32 {
+    crate::auto_import::DEFAULT_AUTO_IMPORT_CONTACTS_PER_HOUR
+}
+
+fn default_auto_import_chats_per_hour() -> u32 {
+    crate::auto_import::DEFAULT_AUTO_IMPORT_CHATS_PER_HOUR
+}
+
+/// Longest profile label, in characters
+pub const MAX_PROFILE_LABEL_CHARS: usize = 16;
+
+/// Accent colour that tells profiles apart
+///
+/// Kept free of terminal types; the TUI maps it onto its theme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccentColor {
+    /// Cyan
+    Cyan,
+    /// Green
+    Green,
+    /// Yellow
+    Yellow,
+    /// Magenta
+    Magenta,
+    /// Blue
+    Blue,
+    /// Red
+    Red,
+}
+
+impl AccentColor {
+    /// Every accent, in the order the settings screen cycles through them
+    pub const ALL: [AccentColor; 6] = [
+        AccentColor::Cyan,
+        AccentColor::Green,
+        AccentColor::Yellow,
+        AccentColor::Magenta,
+        AccentColor::Blue,
+        AccentColor::Red,
+    ];
+
+    /// Lowercase name, as stored in the database
+    pub fn name(&self) -> &'static str {
+        match self {
+            AccentColor::Cyan => "cyan",
+            AccentColor::Green => "green",
+            AccentColor::Yellow => "yellow",
+            AccentColor::Magenta => "magenta",
+            AccentColor::Blue => "blue",
+            AccentColor::Red => "red",
+        }
+    }
+
+    /// Parse a name written by `name()`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|accent| accent.name() == name)
+    }
+
+    /// Next accent for cycling: `None` (theme default), then each of `ALL`
+    pub fn cycle(current: Option<Self>) -> Option<Self> {
+        match current {
+            None => Some(Self::ALL[0]),
+            Some(accent) => {
+                let index = Self::ALL.iter().position(|a| *a == accent).unwrap_or(0);
+                Self::ALL.get(index + 1).copied()
+            }
+        }
+    }
+}
+
+/// How new messages in other chats are signalled while the app is open
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertMode {
+    /// No in-app alert
+    #[default]
+    None,
+    /// Terminal bell (BEL)
+    Bell,
+    /// Briefly invert the header line
+    Flash,
+    /// Bell and flash
+    Both,
+}
+
+impl AlertMode {
+    /// Every mode, in the order the settings screen cycles through them
+    pub const ALL: [AlertMode; 4] = [AlertMode::None, AlertMode::Bell, AlertMode::Flash, AlertMode::Both];
+
+    /// Lowercase name, as stored in the database
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlertMode::None => "none",
+            AlertMode::Bell => "bell",
+            AlertMode::Flash => "flash",
+            AlertMode::Both => "both",
+        }
+    }
+
+    /// Parse a name written by `name()`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    /// Next mode for cycling (wraps around)
+    pub fn cycle(self) -> Self {
+        let index = Self::ALL.iter().position(|m| *m == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Whether this mode rings the terminal bell
+    pub fn rings_bell(self) -> bool {
+        matches!(self, AlertMode::Bell | AlertMode::Both)
+    }
+
+    /// Whether this mode flashes the header line
+    pub fn flashes(self) -> bool {
+        matches!(self, AlertMode::Flash | AlertMode::Both)
+    }
+}
+
+/// How strongly a new message is signalled: a contact's choice, or
+/// `Settings::alert_style` for contacts without one
+///
+/// `AlertMode` picks the channels (bell, flash); the style picks the
+/// pattern on them, as mapped by the `NotificationSink` in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStyle {
+    /// One bell and the normal header flash
+    #[default]
+    Default,
+    /// No bell, a faint flash
+    Subtle,
+    /// Repeated bells and a longer, bolder flash
+    Urgent,
+    /// Nothing (the chat still counts as unread)
+    None,
+}
+
+impl AlertStyle {
+    /// Every style, in the order the contact details popup cycles through them
+    pub const ALL: [AlertStyle; 4] = [AlertStyle::Default, AlertStyle::Subtle, AlertStyle::Urgent, AlertStyle::None];
+
+    /// Lowercase name, as stored in the database
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlertStyle::Default => "default",
+            AlertStyle::Subtle => "subtle",
+            AlertStyle::Urgent => "urgent",
+            AlertStyle::None => "none",
+        }
+    }
+
+    /// Parse a name written by `name()`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|style| style.name() == name)
+    }
+
+    /// Next style for cycling (wraps around)
+    pub fn cycle(self) -> Self {
+        let index = Self::ALL.iter().position(|s| *s == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Next per-contact choice: the global default, then each style
+    pub fn cycle_override(style: Option<Self>) -> Option<Self> {
+        match style {
+            None => Some(Self::ALL[0]),
+            Some(style) if style == Self::ALL[Self::ALL.len() - 1] => None,
+            Some(style) => Some(style.cycle()),
+        }
+    }
+
+    /// Loudness rank: when several chats alert at once, the loudest style plays
+    pub fn loudness(self) -> u8 {
+        match self {
+            AlertStyle::None => 0,
+            AlertStyle::Subtle => 1,
+            AlertStyle::Default => 2,
+            AlertStyle::Urgent => 3,
+        }
+    }
+}
+
+/// Severity of a background failure report (ordered, lowest first)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorSeverity {
+    /// Worth keeping in the log, nothing the user must act on
+    Info,
+    /// Something was not saved or sent, but the app carries on
+    #[default]
+    Warning,
+    /// Data may be lost or a component stopped working
+    Error,
+}
+
+impl ErrorSeverity {
+    /// Every severity, in the order the settings screen cycles through them
+    pub const ALL: [ErrorSeverity; 3] = [ErrorSeverity::Info, ErrorSeverity::Warning, ErrorSeverity::Error];
+
+    /// Lowercase name, as stored in the database
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorSeverity::Info => "info",
+            ErrorSeverity::Warning => "warning",
+            ErrorSeverity::Error => "error",
+        }
+    }
+
+    /// Parse a name written by `name()`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|severity| severity.name() == name)
+    }
+
+    /// Next severity for cycling (wraps around)
+    pub fn cycle(self) -> Self {
+        let index = Self::ALL.iter().position(|s| *s == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
 /// Application settings
 ///
 /// Persistent configuration for the Pure2P application.
@@ -40,6 +354,109 @@ pub struct Settings {
     pub retry_interval_minutes: u32,
     /// Storage path for application data
     pub storage_path: String,
+    /// Whether Do Not Disturb quiet hours are enabled
+    #[serde(default)]
+    pub quiet_hours_enabled: bool,
+    /// Quiet period start, in minutes after local midnight
+    #[serde(default = "default_quiet_hours_start")]
+    pub quiet_hours_start_minutes: u32,
+    /// Quiet period end, in minutes after local midnight (may be before start to cross midnight)
+    #[serde(default = "default_quiet_hours_end")]
+    pub quiet_hours_end_minutes: u32,
+    /// Days on which a quiet period starts (bit 0 = Monday ... bit 6 = Sunday)
+    #[serde(default = "default_quiet_hours_days")]
+    pub quiet_hours_days: u8,
+    /// Length of newly generated invite codes
+    #[serde(default = "default_invite_code_length")]
+    pub invite_code_length: usize,
+    /// Act as a relay for my contacts (forward messages between them)
+    #[serde(default)]
+    pub relay_enabled: bool,
+    /// Short label naming this profile (e.g. "work"); empty when unset
+    #[serde(default)]
+    pub profile_label: String,
+    /// Accent colour for this profile (None keeps the theme's colours)
+    #[serde(default)]
+    pub accent_color: Option<AccentColor>,
+    /// Messages kept per chat, oldest trimmed first (0 = unlimited; chats may override)
+    #[serde(default = "default_history_limit")]
+    pub history_limit: u32,
+    /// In-app alert for new messages in chats other than the open one
+    #[serde(default)]
+    pub alert_mode: AlertMode,
+    /// Alert style of contacts without their own (see `Contact::alert_style`)
+    #[serde(default)]
+    pub alert_style: AlertStyle,
+    /// Lowest severity of background failure shown as a banner
+    #[serde(default)]
+    pub error_banner_severity: ErrorSeverity,
+    /// Seconds within which sending the same text again asks "send duplicate?" (0 = never)
+    #[serde(default = "default_duplicate_window_secs")]
+    pub duplicate_window_secs: u32,
+    /// Stage address changes of verified contacts for review instead of applying them
+    #[serde(default)]
+    pub address_review_enabled: bool,
+    /// Consecutive failed deliveries to the old address after which a staged
+    /// change is applied without review (0 = never)
+    #[serde(default = "default_address_auto_apply_failures")]
+    pub address_auto_apply_failures: u32,
+    /// Contacts that incoming pings may import per hour (0 = unlimited)
+    #[serde(default = "default_auto_import_contacts_per_hour")]
+    pub auto_import_contacts_per_hour: u32,
+    /// Chats that incoming pings and messages may create per hour (0 = unlimited)
+    #[serde(default = "default_auto_import_chats_per_hour")]
+    pub auto_import_chats_per_hour: u32,
+    /// Send read receipts to contacts without an override
+    #[serde(default = "default_send_signal")]
+    pub send_read_receipts: bool,
+    /// Send typing indicators to contacts without an override
+    #[serde(default = "default_send_signal")]
+    pub send_typing: bool,
+    /// Announce presence to contacts without an override
+    #[serde(default = "default_send_signal")]
+    pub send_presence: bool,
+    /// Minutes after sending during which a message can be edited (0 = never)
+    #[serde(default = "default_edit_window_minutes")]
+    pub edit_window_minutes: u32,
+    /// Longest token validity accepted from peers, in days; later expiries are clamped
+    #[serde(default = "default_max_token_expiry_days")]
+    pub max_token_expiry_days: u32,
+    /// Shortest validity left, in minutes, for a token arriving in a ping; shorter ones are refused
+    #[serde(default = "default_min_token_validity_minutes")]
+    pub min_token_validity_minutes: u32,
+    /// Preview a send that is near the size limit, plaintext or to an unreachable contact
+    #[serde(default = "default_auto_send_preview")]
+    pub auto_send_preview: bool,
+    /// Warn while composing during a contact's likely night (see `storage::local_time`)
+    #[serde(default = "default_night_warning_enabled")]
+    pub night_warning_enabled: bool,
+    /// Check the release manifest for updates once a day (opt-in)
+    #[serde(default)]
+    pub update_check_enabled: bool,
+    /// Where the signed release manifest is fetched from
+    #[serde(default = "default_update_manifest_url")]
+    pub update_manifest_url: String,
+    /// Also serve TLS on the listener port, pinned through our contact token
+    #[serde(default)]
+    pub tls_enabled: bool,
+    /// Refuse plain HTTP to non-LAN addresses of contacts that pinned a certificate
+    #[serde(default)]
+    pub require_tls_external: bool,
+    /// Journal every delivered outgoing message (hashes only, see `storage::journal`)
+    #[serde(default)]
+    pub journal_enabled: bool,
+    /// Hours a deleted chat or contact can be undone before it is purged
+    #[serde(default = "default_soft_delete_window_hours")]
+    pub soft_delete_window_hours: u32,
+    /// Size the log file may reach before it is rotated (see `logging`)
+    #[serde(default = "default_log_max_file_bytes")]
+    pub log_max_file_bytes: u64,
+    /// Rotated log files kept besides the current one; older ones are deleted
+    #[serde(default = "default_log_files_kept")]
+    pub log_files_kept: u32,
+    /// Message templates, in the order they were added
+    #[serde(default)]
+    pub templates: Vec<MessageTemplate>,
 }
 
 impl Settings {
@@ -71,6 +488,7 @@ impl Settings {
 
         // Ensure milliseconds and minutes are in sync
         settings.sync_retry_interval();
+        settings.bound_limits();
 
         Ok(settings)
     }
@@ -130,6 +548,135 @@ impl Settings {
         self.retry_interval_minutes
     }
 
+    /// How long the tokens we send in pings and key upgrade answers stay valid
+    ///
+    /// `default_contact_expiry_days`, at least a day.
+    pub fn own_token_validity(&self) -> Duration {
+        Duration::days(self.default_contact_expiry_days.max(1) as i64)
+    }
+
+    /// Validate the quiet hours configuration
+    ///
+    /// # Errors
+    /// Returns an error if a time is out of range, or if start equals end while
+    /// quiet hours are enabled
+    pub fn validate_quiet_hours(&self) -> Result<()> {
+        if self.quiet_hours_start_minutes >= MINUTES_PER_DAY || self.quiet_hours_end_minutes >= MINUTES_PER_DAY {
+            return Err(Error::Storage("Quiet hours times must be between 00:00 and 23:59".to_string()));
+        }
+        if self.quiet_hours_days > ALL_DAYS_MASK {
+            return Err(Error::Storage("Invalid quiet hours days mask".to_string()));
+        }
+        if self.quiet_hours_enabled && self.quiet_hours_start_minutes == self.quiet_hours_end_minutes {
+            return Err(Error::Storage("Quiet hours start and end must differ".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Set the length of newly generated invite codes
+    ///
+    /// # Errors
+    /// Returns an error if the length is outside the supported range
+    pub fn set_invite_code_length(&mut self, length: usize) -> Result<()> {
+        crate::invite::validate_code_length(length)?;
+        self.invite_code_length = length;
+        Ok(())
+    }
+
+    /// Set the profile label (trimmed; empty clears it)
+    ///
+    /// # Errors
+    /// Returns an error if the label is longer than `MAX_PROFILE_LABEL_CHARS`
+    /// or contains control characters
+    pub fn set_profile_label(&mut self, label: &str) -> Result<()> {
+        let label = label.trim();
+        if label.chars().count() > MAX_PROFILE_LABEL_CHARS {
+            return Err(Error::Storage(format!(
+                "Profile label is limited to {} characters",
+                MAX_PROFILE_LABEL_CHARS
+            )));
+        }
+        if label.chars().any(char::is_control) {
+            return Err(Error::Storage("Profile label cannot contain control characters".to_string()));
+        }
+        self.profile_label = label.to_string();
+        Ok(())
+    }
+
+    /// Add a message template
+    ///
+    /// # Errors
+    /// Returns an error if `MAX_TEMPLATES` are already stored, or if the
+    /// template is invalid (see `update_template`)
+    pub fn add_template(&mut self, name: &str, body: &str) -> Result<()> {
+        if self.templates.len() >= MAX_TEMPLATES {
+            return Err(Error::Storage(format!("At most {} templates can be stored", MAX_TEMPLATES)));
+        }
+        self.check_template(None, name, body)?;
+        self.templates.push(MessageTemplate::new(name.trim(), body));
+        Ok(())
+    }
+
+    /// Replace the template at `index`
+    ///
+    /// # Errors
+    /// Returns an error if the index is out of range, the name is empty or
+    /// already used by another template, or the body is empty
+    pub fn update_template(&mut self, index: usize, name: &str, body: &str) -> Result<()> {
+        if index >= self.templates.len() {
+            return Err(Error::Storage(format!("No template at position {}", index)));
+        }
+        self.check_template(Some(index), name, body)?;
+        self.templates[index] = MessageTemplate::new(name.trim(), body);
+        Ok(())
+    }
+
+    /// Remove the template at `index`
+    pub fn remove_template(&mut self, index: usize) -> Option<MessageTemplate> {
+        (index < self.templates.len()).then(|| self.templates.remove(index))
+    }
+
+    /// Validate a template name and body, ignoring the template at `skip`
+    fn check_template(&self, skip: Option<usize>, name: &str, body: &str) -> Result<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::Storage("Template name cannot be empty".to_string()));
+        }
+        if body.trim().is_empty() {
+            return Err(Error::Storage("Template text cannot be empty".to_string()));
+        }
+        let duplicate = self
+            .templates
+            .iter()
+            .enumerate()
+            .any(|(i, t)| Some(i) != skip && t.name.eq_ignore_ascii_case(name));
+        if duplicate {
+            return Err(Error::Storage(format!("A template named '{}' already exists", name)));
+        }
+        Ok(())
+    }
+
+    /// Lower limits a hand-edited or imported file set beyond what is honoured
+    ///
+    /// `max_token_expiry_days` is capped at `MAX_TOKEN_EXPIRY_DAYS_CAP`.
+    /// Called wherever settings are loaded or imported.
+    ///
+    /// # Returns
+    /// Whether anything was lowered
+    pub fn bound_limits(&mut self) -> bool {
+        if self.max_token_expiry_days <= MAX_TOKEN_EXPIRY_DAYS_CAP {
+            return false;
+        }
+        tracing::warn!(
+            "max_token_expiry_days {} is beyond {}; using {}",
+            self.max_token_expiry_days,
+            MAX_TOKEN_EXPIRY_DAYS_CAP,
+            MAX_TOKEN_EXPIRY_DAYS_CAP
+        );
+        self.max_token_expiry_days = MAX_TOKEN_EXPIRY_DAYS_CAP;
+        true
+    }
+
     /// Synchronize retry interval values (ensure minutes and milliseconds match)
     fn sync_retry_interval(&mut self) {
         // Prefer milliseconds value as source of truth
@@ -148,6 +695,94 @@ impl Default for Settings {
             global_retry_interval_ms: 60_000, // 1 minute = 60,000 ms
             retry_interval_minutes: 1, // 1 minute
             storage_path: "./data".to_string(), // Default storage path
+            quiet_hours_enabled: false,
+            quiet_hours_start_minutes: default_quiet_hours_start(),
+            quiet_hours_end_minutes: default_quiet_hours_end(),
+            quiet_hours_days: default_quiet_hours_days(),
+            invite_code_length: default_invite_code_length(),
+            relay_enabled: false,
+            profile_label: String::new(),
+            accent_color: None,
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            alert_mode: AlertMode::None,
+            alert_style: AlertStyle::Default,
+            error_banner_severity: ErrorSeverity::Warning,
+            duplicate_window_secs: DEFAULT_DUPLICATE_WINDOW_SECS,
+            address_review_enabled: false,
+            address_auto_apply_failures: DEFAULT_ADDRESS_AUTO_APPLY_FAILURES,
+            auto_import_contacts_per_hour: default_auto_import_contacts_per_hour(),
+            auto_import_chats_per_hour: default_auto_import_chats_per_hour(),
+            send_read_receipts: true,
+            send_typing: true,
+            send_presence: true,
+            edit_window_minutes: default_edit_window_minutes(),
+            max_token_expiry_days: default_max_token_expiry_days(),
+            min_token_validity_minutes: default_min_token_validity_minutes(),
+            auto_send_preview: true,
+            night_warning_enabled: true,
+            update_check_enabled: false,
+            update_manifest_url: default_update_manifest_url(),
+            tls_enabled: false,
+            require_tls_external: false,
+            journal_enabled: false,
+            soft_delete_window_hours: default_soft_delete_window_hours(),
+            log_max_file_bytes: default_log_max_file_bytes(),
+            log_files_kept: default_log_files_kept(),
+            templates: Vec::new(),
         }
     }
 }
+
+/// Check whether `now` falls inside the configured quiet hours
+///
+/// Evaluation uses the wall-clock time of `now` in its own time zone, so a
+/// period like 22:00-07:00 keeps its local meaning across DST changes. Periods
+/// where end is before start cross midnight and belong to the day they start
+/// on (a Friday 22:00-07:00 period covers early Saturday morning).
+///
+/// # Example
+/// ```rust
+/// use pure2p::storage::{is_quiet, Settings};
+///
+/// let mut settings = Settings::default();
+/// settings.quiet_hours_enabled = true;
+///
+/// let late = chrono::DateTime::parse_from_rfc3339("2024-03-01T23:30:00+01:00").unwrap();
+/// assert!(is_quiet(&late, &settings));
+/// ```
+pub fn is_quiet<Tz: TimeZone>(now: &DateTime<Tz>, settings: &Settings) -> bool {
+    let start = settings.quiet_hours_start_minutes;
+    let end = settings.quiet_hours_end_minutes;
+    if !settings.quiet_hours_enabled || start == end {
+        return false;
+    }
+
+    let local = now.naive_local();
+    let minute = local.hour() * 60 + local.minute();
+    let today = local.weekday().num_days_from_monday();
+    let starts_on = |day: u32| settings.quiet_hours_days & (1 << day) != 0;
+
+    if start < end {
+        starts_on(today) && minute >= start && minute < end
+    } else {
+        // Crosses midnight: late part belongs to today, early part to yesterday
+        (minute >= start && starts_on(today)) || (minute < end && starts_on((today + 6) % 7))
+    }
+}
+
+/// Parse an "HH:MM" time into minutes after midnight
+pub fn parse_time_of_day(input: &str) -> Option<u32> {
+    let (hours, minutes) = input.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    if hours < 24 && minutes < 60 {
+        Some(hours * 60 + minutes)
+    } else {
+        None
+    }
+}
+
+/// Format minutes after midnight as "HH:MM"
+pub fn format_time_of_day(minutes: u32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
@@ -0,0 +1,629 @@
+//! Database snapshots and a read-only diff between two of them
+//!
+//! A snapshot is a copy of `pure2p.db` written with `VACUUM INTO`
+//! (`Storage::write_snapshot`). Snapshots taken with an identity are sealed at
+//! rest: the file starts with `SNAPSHOT_SEALED_MAGIC`, followed by the database
+//! in `SNAPSHOT_CHUNK_BYTES` slices, each sealed with XChaCha20-Poly1305 under
+//! `KeyPair::snapshot_key()` and prefixed with its length. A plain copy of the
+//! database (e.g. from a backup tool) is a valid snapshot too.
+//!
+//! `diff_databases` compares two databases without changing either. Each table
+//! is read in primary-key order from both sides at once and the two streams
+//! are merged, so only the current row of each side is held in memory; beyond
+//! `SNAPSHOT_DIFF_LIST_LIMIT` entries per change class, changes are counted but
+//! not listed. A sealed snapshot is opened into a temporary plain copy that is
+//! removed once compared; one the current identity cannot open is reported as
+//! `SnapshotComparison::Encrypted` rather than as an error.
+
+use crate::crypto::{decrypt_message, encrypt_message, EncryptedEnvelope, KeyPair};
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::types::Value;
+use rusqlite::{Connection, OpenFlags, Rows};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Directory snapshots are written to (production)
+pub const SNAPSHOTS_DIR: &str = "./app_data/snapshots";
+
+/// First bytes of a sealed snapshot
+pub const SNAPSHOT_SEALED_MAGIC: &[u8; 8] = b"P2PSNAP1";
+
+/// Plaintext bytes sealed per chunk of a sealed snapshot
+pub const SNAPSHOT_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Changes listed per change class; the rest are only counted
+pub const SNAPSHOT_DIFF_LIST_LIMIT: usize = 200;
+
+/// Version written in an exported diff
+pub const SNAPSHOT_DIFF_VERSION: u32 = 1;
+
+/// First bytes of every SQLite database file
+const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// Characters of a text value shown in a field change
+const VALUE_SHOWN_CHARS: usize = 64;
+
+/// Nonce + Poly1305 tag added to each sealed chunk
+const CHUNK_OVERHEAD: usize = 24 + 16;
+
+/// File name of a snapshot taken at `now`
+pub fn snapshot_file_name(now: DateTime<Utc>) -> String {
+    format!("pure2p_{}.snapshot", now.format("%Y%m%d_%H%M%S_%3f"))
+}
+
+/// A snapshot file found in the snapshots directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotFile {
+    /// Where the file is
+    pub path: PathBuf,
+    /// File name shown in the list
+    pub name: String,
+    /// Last modification time, if the filesystem reports one
+    pub modified: Option<DateTime<Utc>>,
+    /// Whether the file is sealed (rather than a plain database copy)
+    pub sealed: bool,
+}
+
+/// Snapshots in `dir`, newest first (none if the directory does not exist)
+pub fn list_snapshots(dir: &Path) -> Vec<SnapshotFile> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<SnapshotFile> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|entry| {
+            let path = entry.path();
+            let format = read_format(&path).ok()?;
+            if format == SnapshotFormat::Unknown {
+                return None;
+            }
+            Some(SnapshotFile {
+                name: entry.file_name().to_string_lossy().to_string(),
+                modified: entry.metadata().and_then(|m| m.modified()).ok().map(DateTime::<Utc>::from),
+                sealed: format == SnapshotFormat::Sealed,
+                path,
+            })
+        })
+        .collect();
+    snapshots.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| b.name.cmp(&a.name)));
+    snapshots
+}
+
+/// What a file holds, judged by its first bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SnapshotFormat {
+    Plain,
+    Sealed,
+    Unknown,
+}
+
+fn read_format(path: &Path) -> Result<SnapshotFormat> {
+    let mut header = [0u8; 16];
+    let mut file = File::open(path)?;
+    let read = read_full(&mut file, &mut header)?;
+    Ok(if header[..read].starts_with(SNAPSHOT_SEALED_MAGIC) {
+        SnapshotFormat::Sealed
+    } else if read == header.len() && &header == SQLITE_MAGIC {
+        SnapshotFormat::Plain
+    } else {
+        SnapshotFormat::Unknown
+    })
+}
+
+/// Read until `buf` is full or the reader ends; returns the bytes read
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// Write the database behind `conn` to `path`, sealed under `key` if given
+///
+/// The file must not exist yet; its folder is created if needed.
+pub(crate) fn write_snapshot(conn: &Connection, path: &Path, key: Option<&[u8; 32]>) -> Result<()> {
+    if path.exists() {
+        return Err(Error::Storage(format!("Snapshot {} already exists", path.display())));
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let Some(key) = key else {
+        conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+        return Ok(());
+    };
+
+    let plain = path.with_extension("partial");
+    let _ = std::fs::remove_file(&plain);
+    conn.execute("VACUUM INTO ?1", [plain.to_string_lossy()])?;
+    let result = seal_file(&plain, path, key);
+    let _ = std::fs::remove_file(&plain);
+    if result.is_err() {
+        let _ = std::fs::remove_file(path);
+    }
+    result
+}
+
+fn seal_file(plain: &Path, sealed: &Path, key: &[u8; 32]) -> Result<()> {
+    let mut reader = BufReader::new(File::open(plain)?);
+    let mut writer = BufWriter::new(File::create_new(sealed)?);
+    writer.write_all(SNAPSHOT_SEALED_MAGIC)?;
+    let mut chunk = vec![0u8; SNAPSHOT_CHUNK_BYTES];
+    loop {
+        let read = read_full(&mut reader, &mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        let envelope = encrypt_message(key, &chunk[..read])?;
+        let length = (envelope.nonce.len() + envelope.ciphertext.len()) as u32;
+        writer.write_all(&length.to_le_bytes())?;
+        writer.write_all(&envelope.nonce)?;
+        writer.write_all(&envelope.ciphertext)?;
+    }
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    Ok(())
+}
+
+/// Decrypt the sealed snapshot at `sealed` into `plain`, a chunk at a time
+///
+/// # Errors
+/// `Error::Crypto` when a chunk does not open under `key` (another identity's
+/// snapshot) or the file is truncated
+fn open_sealed(sealed: &Path, plain: &Path, key: &[u8; 32]) -> Result<()> {
+    let mut reader = BufReader::new(File::open(sealed)?);
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    let mut writer = BufWriter::new(File::create_new(plain)?);
+    let mut chunk = Vec::with_capacity(SNAPSHOT_CHUNK_BYTES + CHUNK_OVERHEAD);
+    loop {
+        let mut length = [0u8; 4];
+        match read_full(&mut reader, &mut length)? {
+            0 => break,
+            4 => {}
+            _ => return Err(Error::Crypto("Sealed snapshot is truncated".to_string())),
+        }
+        let length = u32::from_le_bytes(length) as usize;
+        if !(CHUNK_OVERHEAD..=SNAPSHOT_CHUNK_BYTES + CHUNK_OVERHEAD).contains(&length) {
+            return Err(Error::Crypto("Sealed snapshot has an invalid chunk".to_string()));
+        }
+        chunk.resize(length, 0);
+        if read_full(&mut reader, &mut chunk)? != length {
+            return Err(Error::Crypto("Sealed snapshot is truncated".to_string()));
+        }
+        let (nonce, ciphertext) = chunk.split_at(24);
+        let envelope = EncryptedEnvelope {
+            nonce: nonce.try_into().map_err(|_| Error::Crypto("Invalid snapshot nonce".to_string()))?,
+            ciphertext: ciphertext.to_vec(),
+        };
+        writer.write_all(&decrypt_message(key, &envelope)?)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Temporary plain copy of a sealed snapshot, removed when dropped
+struct PlainCopy(PathBuf);
+
+impl Drop for PlainCopy {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// A snapshot opened read-only for comparison
+struct OpenedSnapshot {
+    // Declared first so the connection closes before the copy is removed
+    conn: Connection,
+    _plain_copy: Option<PlainCopy>,
+}
+
+/// Open the snapshot at `path` read-only
+///
+/// # Returns
+/// `None` for a sealed snapshot that does not open under `key` (or when there
+/// is no key)
+///
+/// # Errors
+/// `Error::Storage` for a file that is neither a database nor a snapshot, I/O
+/// and database errors
+fn open_snapshot(path: &Path, key: Option<&[u8; 32]>) -> Result<Option<OpenedSnapshot>> {
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    match read_format(path)? {
+        SnapshotFormat::Plain => Ok(Some(OpenedSnapshot {
+            conn: Connection::open_with_flags(path, flags)?,
+            _plain_copy: None,
+        })),
+        SnapshotFormat::Sealed => {
+            let Some(key) = key else {
+                return Ok(None);
+            };
+            let copy = PlainCopy(std::env::temp_dir().join(format!("pure2p-snapshot-{}.db", uuid::Uuid::new_v4())));
+            match open_sealed(path, &copy.0, key) {
+                Ok(()) => Ok(Some(OpenedSnapshot {
+                    conn: Connection::open_with_flags(&copy.0, flags)?,
+                    _plain_copy: Some(copy),
+                })),
+                Err(Error::Crypto(_)) => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+        SnapshotFormat::Unknown => Err(Error::Storage(format!(
+            "{} is not a pure2p database or snapshot",
+            path.display()
+        ))),
+    }
+}
+
+/// One field that differs between two versions of a row
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    /// Column name
+    pub field: String,
+    /// Value before (None for NULL or a missing column)
+    pub old: Option<String>,
+    /// Value after
+    pub new: Option<String>,
+}
+
+/// A contact present on both sides with different fields
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ContactChange {
+    /// Contact UID
+    pub uid: String,
+    /// Fields that differ
+    pub fields: Vec<FieldChange>,
+}
+
+/// A chat whose number of stored messages changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MessageCountChange {
+    /// Contact UID of the chat
+    pub chat_uid: String,
+    /// Messages before
+    pub old: u64,
+    /// Messages after
+    pub new: u64,
+}
+
+impl MessageCountChange {
+    /// Messages gained (negative when messages disappeared)
+    pub fn delta(&self) -> i64 {
+        self.new as i64 - self.old as i64
+    }
+}
+
+/// Changes of one class: all are counted, the first `SNAPSHOT_DIFF_LIST_LIMIT` listed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangeList<T> {
+    /// Number of changes, listed or not
+    pub total: usize,
+    /// The changes kept for display and export
+    pub listed: Vec<T>,
+}
+
+impl<T> Default for ChangeList<T> {
+    fn default() -> Self {
+        Self { total: 0, listed: Vec::new() }
+    }
+}
+
+impl<T> ChangeList<T> {
+    fn push(&mut self, change: T) {
+        self.total += 1;
+        if self.listed.len() < SNAPSHOT_DIFF_LIST_LIMIT {
+            self.listed.push(change);
+        }
+    }
+
+    /// Changes counted but not listed
+    pub fn unlisted(&self) -> usize {
+        self.total - self.listed.len()
+    }
+}
+
+/// Everything that differs between two databases
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnapshotDiff {
+    /// `SNAPSHOT_DIFF_VERSION`
+    pub version: u32,
+    /// Label of the older side (file name or "live database")
+    pub old: String,
+    /// Label of the newer side
+    pub new: String,
+    /// UIDs of contacts only in the newer side
+    pub contacts_added: ChangeList<String>,
+    /// UIDs of contacts only in the older side
+    pub contacts_removed: ChangeList<String>,
+    /// Contacts on both sides with field-level changes
+    pub contacts_modified: ChangeList<ContactChange>,
+    /// Contact UIDs of chats only in the newer side
+    pub chats_added: ChangeList<String>,
+    /// Contact UIDs of chats only in the older side
+    pub chats_removed: ChangeList<String>,
+    /// Chats whose message count changed
+    pub message_counts: ChangeList<MessageCountChange>,
+    /// Changed settings fields
+    pub settings: Vec<FieldChange>,
+    /// Rows (or per-chat counts) read from both sides together
+    pub rows_compared: u64,
+}
+
+impl SnapshotDiff {
+    fn new(old: &str, new: &str) -> Self {
+        Self {
+            version: SNAPSHOT_DIFF_VERSION,
+            old: old.to_string(),
+            new: new.to_string(),
+            contacts_added: ChangeList::default(),
+            contacts_removed: ChangeList::default(),
+            contacts_modified: ChangeList::default(),
+            chats_added: ChangeList::default(),
+            chats_removed: ChangeList::default(),
+            message_counts: ChangeList::default(),
+            settings: Vec::new(),
+            rows_compared: 0,
+        }
+    }
+
+    /// Whether the two sides hold the same contacts, chats, counts and settings
+    pub fn is_empty(&self) -> bool {
+        self.contacts_added.total == 0
+            && self.contacts_removed.total == 0
+            && self.contacts_modified.total == 0
+            && self.chats_added.total == 0
+            && self.chats_removed.total == 0
+            && self.message_counts.total == 0
+            && self.settings.is_empty()
+    }
+
+    /// One-line summary, e.g. "contacts +1 −0 ~2 | chats +1 −0 | 3 chats with other message counts | 1 setting changed"
+    pub fn summary(&self) -> String {
+        if self.is_empty() {
+            return "No differences".to_string();
+        }
+        format!(
+            "contacts +{} −{} ~{} | chats +{} −{} | {} chat(s) with other message counts | {} setting(s) changed",
+            self.contacts_added.total,
+            self.contacts_removed.total,
+            self.contacts_modified.total,
+            self.chats_added.total,
+            self.chats_removed.total,
+            self.message_counts.total,
+            self.settings.len()
+        )
+    }
+
+    /// The diff as pretty-printed JSON, for export
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Outcome of comparing two databases
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotComparison {
+    /// Both sides could be read
+    Compared(Box<SnapshotDiff>),
+    /// This sealed snapshot does not open with the current identity
+    Encrypted(PathBuf),
+}
+
+/// Label of a snapshot file in a diff
+fn file_label(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| path.display().to_string())
+}
+
+/// Compare two snapshot files (or plain database copies), `old` first
+///
+/// Sealed snapshots are opened with `keypair`'s snapshot key.
+pub fn diff_snapshot_files(old: &Path, new: &Path, keypair: Option<&KeyPair>) -> Result<SnapshotComparison> {
+    let key = keypair.map(KeyPair::snapshot_key).transpose()?;
+    let Some(old_db) = open_snapshot(old, key.as_ref())? else {
+        return Ok(SnapshotComparison::Encrypted(old.to_path_buf()));
+    };
+    let Some(new_db) = open_snapshot(new, key.as_ref())? else {
+        return Ok(SnapshotComparison::Encrypted(new.to_path_buf()));
+    };
+    let diff = diff_databases(&old_db.conn, &new_db.conn, &file_label(old), &file_label(new))?;
+    Ok(SnapshotComparison::Compared(Box::new(diff)))
+}
+
+/// Compare the snapshot at `snapshot` (older side) with the live database
+pub(crate) fn diff_snapshot_with_live(
+    snapshot: &Path,
+    live: &Connection,
+    keypair: Option<&KeyPair>,
+) -> Result<SnapshotComparison> {
+    let key = keypair.map(KeyPair::snapshot_key).transpose()?;
+    let Some(old_db) = open_snapshot(snapshot, key.as_ref())? else {
+        return Ok(SnapshotComparison::Encrypted(snapshot.to_path_buf()));
+    };
+    let diff = diff_databases(&old_db.conn, live, &file_label(snapshot), "live database")?;
+    Ok(SnapshotComparison::Compared(Box::new(diff)))
+}
+
+/// Compare two databases table by table without loading either
+///
+/// Only reads; `old` and `new` may be the same live connection.
+pub fn diff_databases(old: &Connection, new: &Connection, old_label: &str, new_label: &str) -> Result<SnapshotDiff> {
+    let mut diff = SnapshotDiff::new(old_label, new_label);
+
+    let mut rows = merge_table(old, new, "SELECT uid, * FROM contacts ORDER BY uid", |merged| match merged {
+        Merged::OnlyOld(row) => diff.contacts_removed.push(row.key),
+        Merged::OnlyNew(row) => diff.contacts_added.push(row.key),
+        Merged::Both(old_row, new_row) => {
+            let fields = field_changes(&old_row, &new_row);
+            if !fields.is_empty() {
+                diff.contacts_modified.push(ContactChange { uid: old_row.key, fields });
+            }
+        }
+    })?;
+
+    rows += merge_table(old, new, "SELECT contact_uid FROM chats ORDER BY contact_uid", |merged| match merged {
+        Merged::OnlyOld(row) => diff.chats_removed.push(row.key),
+        Merged::OnlyNew(row) => diff.chats_added.push(row.key),
+        Merged::Both(..) => {}
+    })?;
+
+    let counts = "SELECT chat_uid, COUNT(*) FROM messages GROUP BY chat_uid ORDER BY chat_uid";
+    rows += merge_table(old, new, counts, |merged| {
+        let (old_count, new_count, chat_uid) = match merged {
+            Merged::OnlyOld(row) => (row.count(), 0, row.key),
+            Merged::OnlyNew(row) => (0, row.count(), row.key),
+            Merged::Both(old_row, new_row) => (old_row.count(), new_row.count(), old_row.key),
+        };
+        if old_count != new_count {
+            diff.message_counts.push(MessageCountChange { chat_uid, old: old_count, new: new_count });
+        }
+    })?;
+
+    let empty = |key: &str| KeyedRow { key: key.to_string(), values: Vec::new() };
+    rows += merge_table(old, new, "SELECT id, * FROM settings ORDER BY id", |merged| {
+        let (old_row, new_row) = match merged {
+            Merged::OnlyOld(row) => (empty(&row.key), row),
+            Merged::OnlyNew(row) => (empty(&row.key), row),
+            Merged::Both(old_row, new_row) => (old_row, new_row),
+        };
+        diff.settings.extend(field_changes(&old_row, &new_row));
+    })?;
+
+    diff.rows_compared = rows;
+    Ok(diff)
+}
+
+/// A row read from one side of a table: the key (first column) and the rest
+struct KeyedRow {
+    key: String,
+    values: Vec<(String, Value)>,
+}
+
+impl KeyedRow {
+    fn get(&self, field: &str) -> Option<&Value> {
+        self.values.iter().find(|(name, _)| name == field).map(|(_, value)| value)
+    }
+
+    /// The first value as a count (message count rows)
+    fn count(&self) -> u64 {
+        match self.values.first() {
+            Some((_, Value::Integer(n))) => (*n).max(0) as u64,
+            _ => 0,
+        }
+    }
+}
+
+/// How a key appears across the two sides
+enum Merged {
+    OnlyOld(KeyedRow),
+    OnlyNew(KeyedRow),
+    Both(KeyedRow, KeyedRow),
+}
+
+/// Run `sql` (key first, ordered by key) on both sides and merge the results
+///
+/// # Returns
+/// The number of merged rows
+fn merge_table(old: &Connection, new: &Connection, sql: &str, mut visit: impl FnMut(Merged)) -> Result<u64> {
+    let mut old_stmt = old.prepare(sql)?;
+    let mut new_stmt = new.prepare(sql)?;
+    let old_columns: Vec<String> = old_stmt.column_names().into_iter().map(String::from).collect();
+    let new_columns: Vec<String> = new_stmt.column_names().into_iter().map(String::from).collect();
+    let mut old_rows = old_stmt.query([])?;
+    let mut new_rows = new_stmt.query([])?;
+
+    let mut old_row = next_row(&mut old_rows, &old_columns)?;
+    let mut new_row = next_row(&mut new_rows, &new_columns)?;
+    let mut merged_rows = 0;
+    loop {
+        let merged = match (old_row.take(), new_row.take()) {
+            (None, None) => break,
+            (Some(o), None) => {
+                old_row = next_row(&mut old_rows, &old_columns)?;
+                Merged::OnlyOld(o)
+            }
+            (None, Some(n)) => {
+                new_row = next_row(&mut new_rows, &new_columns)?;
+                Merged::OnlyNew(n)
+            }
+            (Some(o), Some(n)) => match o.key.cmp(&n.key) {
+                Ordering::Less => {
+                    new_row = Some(n);
+                    old_row = next_row(&mut old_rows, &old_columns)?;
+                    Merged::OnlyOld(o)
+                }
+                Ordering::Greater => {
+                    old_row = Some(o);
+                    new_row = next_row(&mut new_rows, &new_columns)?;
+                    Merged::OnlyNew(n)
+                }
+                Ordering::Equal => {
+                    old_row = next_row(&mut old_rows, &old_columns)?;
+                    new_row = next_row(&mut new_rows, &new_columns)?;
+                    Merged::Both(o, n)
+                }
+            },
+        };
+        merged_rows += 1;
+        visit(merged);
+    }
+    Ok(merged_rows)
+}
+
+fn next_row(rows: &mut Rows<'_>, columns: &[String]) -> Result<Option<KeyedRow>> {
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+    let key = match Value::from(row.get_ref(0)?) {
+        Value::Text(text) => text,
+        Value::Integer(n) => n.to_string(),
+        other => render_value(&other).unwrap_or_default(),
+    };
+    let mut values = Vec::with_capacity(columns.len().saturating_sub(1));
+    for (index, name) in columns.iter().enumerate().skip(1) {
+        values.push((name.clone(), Value::from(row.get_ref(index)?)));
+    }
+    Ok(Some(KeyedRow { key, values }))
+}
+
+/// Fields that differ between two versions of a row (NULL and missing are equal)
+fn field_changes(old: &KeyedRow, new: &KeyedRow) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    for (field, old_value) in &old.values {
+        let new_value = new.get(field).unwrap_or(&Value::Null);
+        if new_value != old_value {
+            changes.push(FieldChange {
+                field: field.clone(),
+                old: render_value(old_value),
+                new: render_value(new_value),
+            });
+        }
+    }
+    for (field, new_value) in &new.values {
+        if old.get(field).is_none() && *new_value != Value::Null {
+            changes.push(FieldChange { field: field.clone(), old: None, new: render_value(new_value) });
+        }
+    }
+    changes
+}
+
+/// A value as shown in a field change: text shortened, blobs by size and first bytes
+fn render_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Integer(n) => Some(n.to_string()),
+        Value::Real(r) => Some(r.to_string()),
+        Value::Text(text) if text.chars().count() > VALUE_SHOWN_CHARS => {
+            Some(format!("{}…", text.chars().take(VALUE_SHOWN_CHARS).collect::<String>()))
+        }
+        Value::Text(text) => Some(text.clone()),
+        Value::Blob(bytes) => Some(format!("<{} bytes {}>", bytes.len(), hex::encode(&bytes[..bytes.len().min(4)]))),
+    }
+}
@@ -0,0 +1,64 @@
+//! Soft delete of chats and contacts, with an undo window
+//!
+//! Deleting a chat or contact only stamps its row with `deleted_at`; every
+//! load query leaves such rows out, so the item disappears at once while its
+//! messages, summary and flags stay on disk untouched. Queued messages to it
+//! are suspended (see `MessageQueue::suspend_for`) rather than dropped.
+//! Undo clears the stamp and lifts the suspension, which gives back exactly
+//! the state before the delete.
+//!
+//! The maintenance pass purges rows soft-deleted longer than
+//! `Settings::soft_delete_window_hours` ago (`Storage::purge_soft_deleted`):
+//! the rows go for good and a `Tombstone` records what was removed. A live
+//! save over a soft-deleted row (the contact imported again, or writing to
+//! us) purges it first, so the new chat starts fresh as it did before.
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Default for `Settings::soft_delete_window_hours`
+pub const DEFAULT_SOFT_DELETE_WINDOW_HOURS: u32 = 24;
+
+/// What was deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeletedKind {
+    /// A chat (the contact stays)
+    Chat,
+    /// A contact and its chat
+    Contact,
+}
+
+impl DeletedKind {
+    /// Name stored in the `tombstones` table
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeletedKind::Chat => "chat",
+            DeletedKind::Contact => "contact",
+        }
+    }
+
+    /// Parse a stored name (unknown names read as a chat)
+    pub fn parse(name: &str) -> Self {
+        match name {
+            "contact" => DeletedKind::Contact,
+            _ => DeletedKind::Chat,
+        }
+    }
+}
+
+/// Record of a purged chat or contact
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tombstone {
+    /// Contact UID of the chat or contact
+    pub uid: String,
+    /// What was purged
+    pub kind: DeletedKind,
+    /// When it was soft-deleted (Unix milliseconds)
+    pub deleted_at: i64,
+    /// When its rows were removed (Unix milliseconds)
+    pub purged_at: i64,
+}
+
+/// Items soft-deleted at or before this time are due for purging at `now`
+pub fn purge_cutoff(now: DateTime<Utc>, window_hours: u32) -> DateTime<Utc> {
+    now - Duration::hours(i64::from(window_hours))
+}
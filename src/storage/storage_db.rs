@@ -5,11 +5,74 @@
 
 use crate::{
     crypto::KeyPair,
-    storage::{chat::Chat, contact::Contact, message::Message, settings::Settings},
+    storage::{
+        address_change::AddressChange,
+        chat::Chat,
+        chat_summary::{MessageSummary, SummaryMigration},
+        contact::{Contact, ContactEndpoint},
+        capability_probe::CapabilityRecord,
+        ephemeral::Ephemeral,
+        local_time::OffsetSample,
+        privacy::ContactPrivacy,
+        protection::Protection,
+        read_only::{open_read_only, WriteLog},
+        trust::TrustTier,
+        snapshot::{self, SnapshotComparison},
+        soft_delete::{DeletedKind, Tombstone},
+        export::{ExportedMessage, JsonlExportOptions, EXPORT_PAGE_SIZE},
+        chat_archive::{cross_reference, ArchiveManifest, ArchiveWriter, CrossReference},
+        journal::{JournalKind, JournalRecord},
+        identity::{scan_contacts, ConflictKind, IdentityConflict},
+        introductions::PendingIntroduction,
+        message::{Message, MessageEdit, MessageMetadata, SYSTEM_SENDER},
+        settings::{AccentColor, AlertMode, AlertStyle, ErrorSeverity, Settings},
+        template::MessageTemplate,
+    },
     Error, Result,
 };
+use chrono::{DateTime, Utc};
 use rusqlite::{params, Connection, OptionalExtension};
+use std::ops::ControlFlow;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Slice size used by `Storage::copy_message_content` (bytes)
+pub const CONTENT_CHUNK_BYTES: usize = 64 * 1024;
+
+/// How SQLite writes behave while another process holds a lock on the database
+/// (e.g. a backup tool)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusyPolicy {
+    /// How long SQLite itself waits for a lock before reporting busy
+    pub busy_timeout: Duration,
+    /// Extra attempts made by `Storage::with_write_retry` after a busy failure
+    pub max_retries: u32,
+    /// Delay before the first retry (doubled on each further retry)
+    pub base_delay: Duration,
+}
+
+impl Default for BusyPolicy {
+    fn default() -> Self {
+        Self {
+            busy_timeout: Duration::from_millis(1000),
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Check whether an error means the database is locked by another connection
+pub fn is_busy_error(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Database(rusqlite::Error::SqliteFailure(e, _))
+            if matches!(e.code, rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    )
+}
 
 /// Request log entry for debugging network issues
 #[derive(Debug, Clone)]
@@ -36,11 +99,58 @@ pub struct RequestLog {
     pub response_data: Option<String>,
 }
 
+/// Rows kept in the connectivity history; older ones are dropped on insert
+pub const CONNECTIVITY_HISTORY_LIMIT: usize = 500;
+
+/// One recorded connectivity event (see `connectivity::ConnectivityEvent`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectivityHistoryEntry {
+    /// When the event happened
+    pub at: DateTime<Utc>,
+    /// Kind of event, e.g. "mapping_lost"
+    pub kind: String,
+    /// What happened, as one line
+    pub detail: String,
+}
+
+/// Result of `Storage::verify_integrity`
+#[derive(Debug, Clone, Default)]
+pub struct IntegrityReport {
+    /// Problems reported by SQLite's `quick_check` (empty when the file is sound)
+    pub problems: Vec<String>,
+    /// Stored contacts whose identity is inconsistent
+    pub conflicts: Vec<IdentityConflict>,
+}
+
+impl IntegrityReport {
+    /// Whether nothing was found
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty() && self.conflicts.is_empty()
+    }
+}
+
 /// SQLite-based storage manager
 pub struct Storage {
     conn: Connection,
     /// Path to database file (for creating new connections on clone)
     path: Option<String>,
+    /// Number of queries run against the messages table (for startup profiling)
+    message_queries: AtomicU64,
+    /// Number of message rows inserted or changed
+    message_writes: AtomicU64,
+    /// Fingerprints of each chat's messages as last written by this
+    /// connection, in chat order, so `save_chat` skips unchanged ones
+    saved_messages: Mutex<HashMap<String, Vec<u64>>>,
+    /// Fingerprints written by the open transaction, kept once it commits
+    staged_messages: Mutex<Vec<(String, Vec<u64>)>>,
+    /// Lock handling for writes
+    busy_policy: BusyPolicy,
+    /// Number of write retries caused by a locked database
+    busy_retries: AtomicU64,
+    /// Number of write transactions committed
+    commits: AtomicU64,
+    /// Denied writes, when opened read-only (clones stay read-only)
+    read_only: Option<WriteLog>,
 }
 
 impl Storage {
@@ -49,21 +159,62 @@ impl Storage {
         let path_str = path.as_ref().to_string_lossy().to_string();
         let conn = Connection::open(path)
             .map_err(|e| Error::Storage(format!("Failed to open database: {}", e)))?;
+        let busy_policy = BusyPolicy::default();
+        conn.busy_timeout(busy_policy.busy_timeout)?;
 
         let mut storage = Self {
             conn,
             path: Some(path_str),
+            message_queries: AtomicU64::new(0),
+            message_writes: AtomicU64::new(0),
+            saved_messages: Mutex::new(HashMap::new()),
+            staged_messages: Mutex::new(Vec::new()),
+            busy_policy,
+            busy_retries: AtomicU64::new(0),
+            commits: AtomicU64::new(0),
+            read_only: None,
         };
         storage.init_schema()?;
         Ok(storage)
     }
 
+    /// Open the database file at `path` strictly read-only (inspection mode)
+    ///
+    /// The schema is neither created nor migrated; writes fail and are
+    /// recorded in `writes` (see `storage::read_only`).
+    pub fn open_read_only<P: AsRef<Path>>(path: P, writes: &WriteLog) -> Result<Self> {
+        let conn = open_read_only(path.as_ref(), writes)?;
+        Ok(Self {
+            conn,
+            path: Some(path.as_ref().to_string_lossy().to_string()),
+            message_queries: AtomicU64::new(0),
+            message_writes: AtomicU64::new(0),
+            saved_messages: Mutex::new(HashMap::new()),
+            staged_messages: Mutex::new(Vec::new()),
+            busy_policy: BusyPolicy::default(),
+            busy_retries: AtomicU64::new(0),
+            commits: AtomicU64::new(0),
+            read_only: Some(writes.clone()),
+        })
+    }
+
     /// Create an in-memory storage instance (for testing)
     pub fn new_in_memory() -> Result<Self> {
         let conn = Connection::open_in_memory()
             .map_err(|e| Error::Storage(format!("Failed to create in-memory database: {}", e)))?;
 
-        let mut storage = Self { conn, path: None };
+        let mut storage = Self {
+            conn,
+            path: None,
+            message_queries: AtomicU64::new(0),
+            message_writes: AtomicU64::new(0),
+            saved_messages: Mutex::new(HashMap::new()),
+            staged_messages: Mutex::new(Vec::new()),
+            busy_policy: BusyPolicy::default(),
+            busy_retries: AtomicU64::new(0),
+            commits: AtomicU64::new(0),
+            read_only: None,
+        };
         storage.init_schema()?;
         Ok(storage)
     }
@@ -91,9 +242,76 @@ impl Storage {
                 uid TEXT PRIMARY KEY,
                 ip TEXT NOT NULL,
                 pubkey BLOB NOT NULL,
-                x25519_pubkey BLOB NOT NULL,
+                x25519_pubkey BLOB,
                 expiry INTEGER NOT NULL,
-                is_active INTEGER NOT NULL
+                is_active INTEGER NOT NULL,
+                notes TEXT NOT NULL DEFAULT '',
+                endpoints TEXT,
+                is_relay INTEGER NOT NULL DEFAULT 0,
+                relay_reachable TEXT,
+                verified INTEGER NOT NULL DEFAULT 0,
+                privacy TEXT,
+                supports_edits INTEGER NOT NULL DEFAULT 0,
+                ephemeral TEXT,
+                trust TEXT NOT NULL DEFAULT 'normal',
+                requested_expiry INTEGER,
+                cert_fingerprint TEXT,
+                capabilities TEXT,
+                deleted_at INTEGER,
+                alert_style TEXT,
+                utc_offset_override INTEGER,
+                offset_samples TEXT,
+                heard_at INTEGER
+            )",
+            [],
+        )?;
+
+        // Databases created before contact notes existed lack the column
+        add_column_if_missing(&self.conn, "contacts", "notes", "TEXT NOT NULL DEFAULT ''")?;
+        add_column_if_missing(&self.conn, "contacts", "endpoints", "TEXT")?;
+        add_column_if_missing(&self.conn, "contacts", "is_relay", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "contacts", "relay_reachable", "TEXT")?;
+        make_contact_x25519_nullable(&self.conn)?;
+        add_column_if_missing(&self.conn, "contacts", "verified", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "contacts", "privacy", "TEXT")?;
+        add_column_if_missing(&self.conn, "contacts", "supports_edits", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "contacts", "ephemeral", "TEXT")?;
+        add_column_if_missing(&self.conn, "contacts", "trust", "TEXT NOT NULL DEFAULT 'normal'")?;
+        add_column_if_missing(&self.conn, "contacts", "requested_expiry", "INTEGER")?;
+        add_column_if_missing(&self.conn, "contacts", "cert_fingerprint", "TEXT")?;
+        add_column_if_missing(&self.conn, "contacts", "capabilities", "TEXT")?;
+        add_column_if_missing(&self.conn, "contacts", "deleted_at", "INTEGER")?;
+        add_column_if_missing(&self.conn, "contacts", "alert_style", "TEXT")?;
+        add_column_if_missing(&self.conn, "contacts", "utc_offset_override", "INTEGER")?;
+        add_column_if_missing(&self.conn, "contacts", "offset_samples", "TEXT")?;
+        add_column_if_missing(&self.conn, "contacts", "heard_at", "INTEGER")?;
+
+        // Notes kept after their contact was deleted (restored on re-import)
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS retained_contact_notes (
+                uid TEXT PRIMARY KEY,
+                notes TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // People expected to send a token, from an imported contact list
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS pending_introductions (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                notes TEXT NOT NULL,
+                added_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // Chat sends in flight, so a crash does not lose them (see `tui::send_leases`)
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS send_leases (
+                message_id TEXT PRIMARY KEY,
+                contact_uid TEXT NOT NULL,
+                started_at INTEGER NOT NULL
             )",
             [],
         )?;
@@ -104,10 +322,22 @@ impl Storage {
                 contact_uid TEXT PRIMARY KEY,
                 is_active INTEGER NOT NULL,
                 has_pending_messages INTEGER NOT NULL,
+                has_failed_messages INTEGER NOT NULL DEFAULT 0,
+                history_limit INTEGER,
+                trimmed_before INTEGER,
+                muted INTEGER NOT NULL DEFAULT 0,
+                deleted_at INTEGER,
+                archived INTEGER NOT NULL DEFAULT 0,
                 FOREIGN KEY (contact_uid) REFERENCES contacts(uid) ON DELETE CASCADE
             )",
             [],
         )?;
+        add_column_if_missing(&self.conn, "chats", "has_failed_messages", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "chats", "history_limit", "INTEGER")?;
+        add_column_if_missing(&self.conn, "chats", "trimmed_before", "INTEGER")?;
+        add_column_if_missing(&self.conn, "chats", "muted", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "chats", "archived", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "chats", "deleted_at", "INTEGER")?;
 
         // Messages table
         self.conn.execute(
@@ -118,11 +348,52 @@ impl Storage {
                 content BLOB NOT NULL,
                 timestamp INTEGER NOT NULL,
                 chat_uid TEXT NOT NULL,
+                metadata TEXT,
+                pinned INTEGER NOT NULL DEFAULT 0,
+                starred INTEGER NOT NULL DEFAULT 0,
+                relayed_via TEXT,
+                remote_id TEXT,
+                edit_history BLOB,
+                protection TEXT,
+                FOREIGN KEY (chat_uid) REFERENCES chats(contact_uid) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+        add_column_if_missing(&self.conn, "messages", "metadata", "TEXT")?;
+        add_column_if_missing(&self.conn, "messages", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "messages", "starred", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "messages", "relayed_via", "TEXT")?;
+        add_column_if_missing(&self.conn, "messages", "remote_id", "TEXT")?;
+        add_column_if_missing(&self.conn, "messages", "edit_history", "BLOB")?;
+        add_column_if_missing(&self.conn, "messages", "protection", "TEXT")?;
+
+        // Chat summaries, so the chat list never reads the messages table
+        // (see storage::chat_summary)
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS chat_summaries (
+                chat_uid TEXT PRIMARY KEY,
+                message_count INTEGER NOT NULL,
+                latest_id TEXT,
+                latest_timestamp INTEGER,
+                latest_sender TEXT,
+                latest_preview TEXT,
                 FOREIGN KEY (chat_uid) REFERENCES chats(contact_uid) ON DELETE CASCADE
             )",
             [],
         )?;
 
+        // Chats and contacts purged after their undo window (see storage::soft_delete)
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS tombstones (
+                uid TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                deleted_at INTEGER NOT NULL,
+                purged_at INTEGER NOT NULL,
+                PRIMARY KEY (uid, kind)
+            )",
+            [],
+        )?;
+
         // Settings table (single row)
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS settings (
@@ -134,7 +405,176 @@ impl Storage {
                 enable_notifications INTEGER NOT NULL,
                 global_retry_interval_ms INTEGER NOT NULL,
                 retry_interval_minutes INTEGER NOT NULL,
-                storage_path TEXT NOT NULL
+                storage_path TEXT NOT NULL,
+                quiet_hours_enabled INTEGER NOT NULL DEFAULT 0,
+                quiet_hours_start_minutes INTEGER NOT NULL DEFAULT 1320,
+                quiet_hours_end_minutes INTEGER NOT NULL DEFAULT 420,
+                quiet_hours_days INTEGER NOT NULL DEFAULT 127,
+                invite_code_length INTEGER NOT NULL DEFAULT 10,
+                relay_enabled INTEGER NOT NULL DEFAULT 0,
+                profile_label TEXT NOT NULL DEFAULT '',
+                accent_color TEXT,
+                history_limit INTEGER NOT NULL DEFAULT 2000,
+                alert_mode TEXT NOT NULL DEFAULT 'none',
+                error_banner_severity TEXT NOT NULL DEFAULT 'warning',
+                duplicate_window_secs INTEGER NOT NULL DEFAULT 3,
+                address_review_enabled INTEGER NOT NULL DEFAULT 0,
+                address_auto_apply_failures INTEGER NOT NULL DEFAULT 3,
+                auto_import_contacts_per_hour INTEGER NOT NULL DEFAULT 10,
+                auto_import_chats_per_hour INTEGER NOT NULL DEFAULT 10,
+                send_read_receipts INTEGER NOT NULL DEFAULT 1,
+                send_typing INTEGER NOT NULL DEFAULT 1,
+                send_presence INTEGER NOT NULL DEFAULT 1,
+                edit_window_minutes INTEGER NOT NULL DEFAULT 15,
+                max_token_expiry_days INTEGER NOT NULL DEFAULT 180,
+                update_check_enabled INTEGER NOT NULL DEFAULT 0,
+                update_manifest_url TEXT NOT NULL DEFAULT '',
+                tls_enabled INTEGER NOT NULL DEFAULT 0,
+                require_tls_external INTEGER NOT NULL DEFAULT 0,
+                journal_enabled INTEGER NOT NULL DEFAULT 0,
+                soft_delete_window_hours INTEGER NOT NULL DEFAULT 24,
+                min_token_validity_minutes INTEGER NOT NULL DEFAULT 60,
+                auto_send_preview INTEGER NOT NULL DEFAULT 1,
+                alert_style TEXT NOT NULL DEFAULT 'default',
+                night_warning_enabled INTEGER NOT NULL DEFAULT 1,
+                log_max_file_bytes INTEGER NOT NULL DEFAULT 5242880,
+                log_files_kept INTEGER NOT NULL DEFAULT 5
+            )",
+            [],
+        )?;
+
+        // Databases created before quiet hours existed lack these columns
+        add_column_if_missing(&self.conn, "settings", "quiet_hours_enabled", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "settings", "quiet_hours_start_minutes", "INTEGER NOT NULL DEFAULT 1320")?;
+        add_column_if_missing(&self.conn, "settings", "quiet_hours_end_minutes", "INTEGER NOT NULL DEFAULT 420")?;
+        add_column_if_missing(&self.conn, "settings", "quiet_hours_days", "INTEGER NOT NULL DEFAULT 127")?;
+        add_column_if_missing(&self.conn, "settings", "invite_code_length", "INTEGER NOT NULL DEFAULT 10")?;
+        add_column_if_missing(&self.conn, "settings", "relay_enabled", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "settings", "profile_label", "TEXT NOT NULL DEFAULT ''")?;
+        add_column_if_missing(&self.conn, "settings", "accent_color", "TEXT")?;
+        add_column_if_missing(&self.conn, "settings", "history_limit", "INTEGER NOT NULL DEFAULT 2000")?;
+        add_column_if_missing(&self.conn, "settings", "alert_mode", "TEXT NOT NULL DEFAULT 'none'")?;
+        add_column_if_missing(&self.conn, "settings", "alert_style", "TEXT NOT NULL DEFAULT 'default'")?;
+        add_column_if_missing(&self.conn, "settings", "duplicate_window_secs", "INTEGER NOT NULL DEFAULT 3")?;
+        add_column_if_missing(&self.conn, "settings", "address_review_enabled", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "settings", "address_auto_apply_failures", "INTEGER NOT NULL DEFAULT 3")?;
+        add_column_if_missing(&self.conn, "settings", "auto_import_contacts_per_hour", "INTEGER NOT NULL DEFAULT 10")?;
+        add_column_if_missing(&self.conn, "settings", "auto_import_chats_per_hour", "INTEGER NOT NULL DEFAULT 10")?;
+        add_column_if_missing(&self.conn, "settings", "send_read_receipts", "INTEGER NOT NULL DEFAULT 1")?;
+        add_column_if_missing(&self.conn, "settings", "send_typing", "INTEGER NOT NULL DEFAULT 1")?;
+        add_column_if_missing(&self.conn, "settings", "send_presence", "INTEGER NOT NULL DEFAULT 1")?;
+        add_column_if_missing(&self.conn, "settings", "edit_window_minutes", "INTEGER NOT NULL DEFAULT 15")?;
+        add_column_if_missing(&self.conn, "settings", "error_banner_severity", "TEXT NOT NULL DEFAULT 'warning'")?;
+        add_column_if_missing(&self.conn, "settings", "max_token_expiry_days", "INTEGER NOT NULL DEFAULT 180")?;
+        add_column_if_missing(&self.conn, "settings", "update_check_enabled", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "settings", "update_manifest_url", "TEXT NOT NULL DEFAULT ''")?;
+        add_column_if_missing(&self.conn, "settings", "tls_enabled", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "settings", "require_tls_external", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "settings", "journal_enabled", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&self.conn, "settings", "soft_delete_window_hours", "INTEGER NOT NULL DEFAULT 24")?;
+        add_column_if_missing(&self.conn, "settings", "min_token_validity_minutes", "INTEGER NOT NULL DEFAULT 60")?;
+        add_column_if_missing(&self.conn, "settings", "auto_send_preview", "INTEGER NOT NULL DEFAULT 1")?;
+        add_column_if_missing(&self.conn, "settings", "night_warning_enabled", "INTEGER NOT NULL DEFAULT 1")?;
+        add_column_if_missing(&self.conn, "settings", "log_max_file_bytes", "INTEGER NOT NULL DEFAULT 5242880")?;
+        add_column_if_missing(&self.conn, "settings", "log_files_kept", "INTEGER NOT NULL DEFAULT 5")?;
+
+        // Message templates, stored with the settings they belong to
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS message_templates (
+                position INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                body TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Address changes of verified contacts staged for review (one per contact)
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS address_changes (
+                uid TEXT PRIMARY KEY,
+                change TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Contacts held back because their identity conflicts with a stored one
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS identity_conflicts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                existing_uid TEXT NOT NULL,
+                contact TEXT NOT NULL,
+                detected_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // One-time integrity checks that have already run on this database
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS integrity_checks (
+                name TEXT PRIMARY KEY,
+                completed_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // When the release manifest was last fetched (single row)
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS update_check (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                checked_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // What our token was last made from, and when that changed
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS own_token (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                inputs TEXT NOT NULL,
+                changed_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // Our TLS certificate and key, PEM (single row, see transport::tls)
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS tls_identity (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                cert_pem TEXT NOT NULL,
+                key_pem TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Outbound delivery journal (see storage::journal); rows are never changed or removed
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS journal (
+                seq INTEGER PRIMARY KEY,
+                at INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                recipient_uid TEXT NOT NULL,
+                content_sha256 TEXT NOT NULL,
+                ack_signature TEXT,
+                prev_hash TEXT NOT NULL,
+                hash TEXT NOT NULL
+            )",
+            [],
+        )?;
+        self.conn.execute_batch(
+            "CREATE TRIGGER IF NOT EXISTS journal_no_update BEFORE UPDATE ON journal
+                 BEGIN SELECT RAISE(ABORT, 'journal is append-only'); END;
+             CREATE TRIGGER IF NOT EXISTS journal_no_delete BEFORE DELETE ON journal
+                 BEGIN SELECT RAISE(ABORT, 'journal is append-only'); END;",
+        )?;
+
+        // Connectivity events, newest last (at most CONNECTIVITY_HISTORY_LIMIT rows)
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS connectivity_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                at INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                detail TEXT NOT NULL
             )",
             [],
         )?;
@@ -156,17 +596,30 @@ impl Storage {
             [],
         )?;
 
-        // Create indexes for better query performance
+        // Create indexes for better query performance. Message indexes lead
+        // with the chat, so a write only touches that chat's part of each
         self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_messages_chat ON messages(chat_uid)",
+            "CREATE INDEX IF NOT EXISTS idx_messages_chat_id ON messages(chat_uid, id)",
             [],
         )?;
 
         self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_messages_timestamp ON messages(timestamp)",
+            "CREATE INDEX IF NOT EXISTS idx_messages_chat_time ON messages(chat_uid, timestamp DESC)",
             [],
         )?;
 
+        // Replaced by the composite indexes above
+        self.conn.execute_batch(
+            "DROP INDEX IF EXISTS idx_messages_chat;
+             DROP INDEX IF EXISTS idx_messages_timestamp;",
+        )?;
+
+        // Soft-deleted rows only: live queries never use these, the purge scans them
+        self.conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_chats_deleted ON chats(deleted_at) WHERE deleted_at IS NOT NULL;
+             CREATE INDEX IF NOT EXISTS idx_contacts_deleted ON contacts(deleted_at) WHERE deleted_at IS NOT NULL;",
+        )?;
+
         self.conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_request_logs_timestamp ON request_logs(timestamp)",
             [],
@@ -180,6 +633,73 @@ impl Storage {
         Ok(())
     }
 
+    /// Set the lock handling policy for writes
+    pub fn set_busy_policy(&mut self, policy: BusyPolicy) -> Result<()> {
+        self.conn.busy_timeout(policy.busy_timeout)?;
+        self.busy_policy = policy;
+        Ok(())
+    }
+
+    /// Get the lock handling policy for writes
+    pub fn busy_policy(&self) -> BusyPolicy {
+        self.busy_policy
+    }
+
+    /// Number of write retries caused by a locked database
+    pub fn busy_retry_count(&self) -> u64 {
+        self.busy_retries.load(Ordering::Relaxed)
+    }
+
+    /// Run a group of writes in one transaction, retrying while the database is locked
+    ///
+    /// The transaction is started with `BEGIN IMMEDIATE` so a lock held by
+    /// another process is detected before anything is written. Busy failures
+    /// are retried up to `BusyPolicy::max_retries` times with exponential
+    /// backoff; any other error rolls back and is returned immediately.
+    ///
+    /// # Errors
+    /// Returns the last error if the writes still fail after all retries.
+    /// Use `is_busy_error` to tell a locked database from other failures.
+    pub fn with_write_retry<T>(&self, mut op: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut delay = self.busy_policy.base_delay;
+        let mut attempt = 0;
+        loop {
+            match self.run_in_transaction(&mut op) {
+                Err(e) if is_busy_error(&e) && attempt < self.busy_policy.max_retries => {
+                    attempt += 1;
+                    self.busy_retries.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(
+                        "Database locked, retrying write in {:?} (attempt {}/{})",
+                        delay,
+                        attempt,
+                        self.busy_policy.max_retries
+                    );
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn run_in_transaction<T>(&self, op: &mut impl FnMut() -> Result<T>) -> Result<T> {
+        self.conn.execute_batch("BEGIN IMMEDIATE")?;
+        let result = op().and_then(|value| {
+            self.conn.execute_batch("COMMIT")?;
+            self.commits.fetch_add(1, Ordering::Relaxed);
+            Ok(value)
+        });
+        if result.is_err() && !self.conn.is_autocommit() {
+            let _ = self.conn.execute_batch("ROLLBACK");
+        }
+
+        let staged = std::mem::take(&mut *self.staged_messages.lock().unwrap());
+        if result.is_ok() {
+            self.saved_messages.lock().unwrap().extend(staged);
+        }
+        result
+    }
+
     // ========== User Identity ==========
 
     /// Save user identity (keypair, IP, port)
@@ -232,10 +752,24 @@ impl Storage {
     // ========== Contacts ==========
 
     /// Save or update a contact
+    ///
+    /// Updates in place: replacing the row would delete the contact's chat
+    /// and messages through the foreign keys. A soft-deleted contact with
+    /// the same UID is purged first, so the saved one starts fresh.
     pub fn save_contact(&self, contact: &Contact) -> Result<()> {
+        self.purge_deleted(DeletedKind::Contact, &contact.uid, Utc::now())?;
         self.conn.execute(
-            "INSERT OR REPLACE INTO contacts (uid, ip, pubkey, x25519_pubkey, expiry, is_active)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO contacts (uid, ip, pubkey, x25519_pubkey, expiry, is_active, notes, endpoints, is_relay, relay_reachable, verified, privacy, supports_edits, ephemeral, trust, requested_expiry, cert_fingerprint, capabilities, alert_style, utc_offset_override, offset_samples, heard_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)
+             ON CONFLICT(uid) DO UPDATE SET
+                 ip = excluded.ip, pubkey = excluded.pubkey, x25519_pubkey = excluded.x25519_pubkey,
+                 expiry = excluded.expiry, is_active = excluded.is_active, notes = excluded.notes,
+                 endpoints = excluded.endpoints, is_relay = excluded.is_relay, relay_reachable = excluded.relay_reachable,
+                 verified = excluded.verified, privacy = excluded.privacy, supports_edits = excluded.supports_edits,
+                 ephemeral = excluded.ephemeral, trust = excluded.trust, requested_expiry = excluded.requested_expiry,
+                 cert_fingerprint = excluded.cert_fingerprint, capabilities = excluded.capabilities,
+                 alert_style = excluded.alert_style, utc_offset_override = excluded.utc_offset_override,
+                 offset_samples = excluded.offset_samples, heard_at = excluded.heard_at",
             params![
                 &contact.uid,
                 &contact.ip,
@@ -243,6 +777,22 @@ impl Storage {
                 &contact.x25519_pubkey,
                 contact.expiry.timestamp(),
                 contact.is_active as i32,
+                &contact.notes,
+                encode_endpoints(&contact.endpoints)?,
+                contact.is_relay as i32,
+                encode_relay_reachable(&contact.relay_reachable)?,
+                contact.verified as i32,
+                encode_privacy(&contact.privacy)?,
+                contact.supports_edits as i32,
+                encode_ephemeral(contact.ephemeral.as_ref())?,
+                contact.trust.as_str(),
+                contact.requested_expiry.map(|expiry| expiry.timestamp()),
+                &contact.cert_fingerprint,
+                encode_capabilities(contact.capabilities.as_ref())?,
+                contact.alert_style.map(|style| style.name()),
+                contact.utc_offset_override,
+                encode_offset_samples(&contact.offset_samples)?,
+                contact.heard_at.map(|at| at.timestamp_millis()),
             ],
         )?;
         Ok(())
@@ -251,16 +801,33 @@ impl Storage {
     /// Load all contacts
     pub fn load_contacts(&self) -> Result<Vec<Contact>> {
         let mut stmt = self.conn.prepare(
-            "SELECT uid, ip, pubkey, x25519_pubkey, expiry, is_active FROM contacts"
+            "SELECT uid, ip, pubkey, x25519_pubkey, expiry, is_active, notes, endpoints, is_relay, relay_reachable, verified, privacy, supports_edits, ephemeral, trust, requested_expiry, cert_fingerprint, capabilities, alert_style, utc_offset_override, offset_samples, heard_at FROM contacts
+             WHERE deleted_at IS NULL"
         )?;
 
         let contacts = stmt.query_map([], |row| {
             let uid: String = row.get(0)?;
             let ip: String = row.get(1)?;
             let pubkey: Vec<u8> = row.get(2)?;
-            let x25519_pubkey: Vec<u8> = row.get(3)?;
+            let x25519_pubkey: Option<Vec<u8>> = row.get(3)?;
             let expiry_timestamp: i64 = row.get(4)?;
             let is_active: i32 = row.get(5)?;
+            let notes: String = row.get(6)?;
+            let endpoints: Option<String> = row.get(7)?;
+            let is_relay: i32 = row.get(8)?;
+            let relay_reachable: Option<String> = row.get(9)?;
+            let verified: i32 = row.get(10)?;
+            let privacy: Option<String> = row.get(11)?;
+            let supports_edits: i32 = row.get(12)?;
+            let ephemeral: Option<String> = row.get(13)?;
+            let trust: String = row.get(14)?;
+            let requested_expiry: Option<i64> = row.get(15)?;
+            let cert_fingerprint: Option<String> = row.get(16)?;
+            let capabilities: Option<String> = row.get(17)?;
+            let alert_style: Option<String> = row.get(18)?;
+            let utc_offset_override: Option<i32> = row.get(19)?;
+            let offset_samples: Option<String> = row.get(20)?;
+            let heard_at: Option<i64> = row.get(21)?;
 
             let expiry = chrono::DateTime::from_timestamp(expiry_timestamp, 0)
                 .unwrap_or_else(chrono::Utc::now);
@@ -269,9 +836,25 @@ impl Storage {
                 uid,
                 ip,
                 pubkey,
-                x25519_pubkey,
+                x25519_pubkey: x25519_pubkey.filter(|key| !key.is_empty()),
                 expiry,
                 is_active: is_active != 0,
+                notes,
+                endpoints: decode_endpoints(endpoints.as_deref()),
+                is_relay: is_relay != 0,
+                relay_reachable: decode_relay_reachable(relay_reachable.as_deref()),
+                verified: verified != 0,
+                privacy: decode_privacy(privacy.as_deref()),
+                supports_edits: supports_edits != 0,
+                ephemeral: ephemeral.as_deref().and_then(|json| serde_json::from_str(json).ok()),
+                trust: TrustTier::parse(&trust),
+                requested_expiry: requested_expiry.and_then(|secs| chrono::DateTime::from_timestamp(secs, 0)),
+                cert_fingerprint,
+                capabilities: capabilities.as_deref().and_then(|json| serde_json::from_str(json).ok()),
+                alert_style: alert_style.as_deref().and_then(AlertStyle::from_name),
+                utc_offset_override,
+                offset_samples: offset_samples.as_deref().and_then(|json| serde_json::from_str(json).ok()).unwrap_or_default(),
+                heard_at: heard_at.and_then(chrono::DateTime::from_timestamp_millis),
             })
         })?
         .collect::<std::result::Result<Vec<_>, _>>()?;
@@ -285,103 +868,941 @@ impl Storage {
         Ok(())
     }
 
+    /// Keep a deleted contact's notes so they can be restored on re-import
+    pub fn retain_contact_notes(&self, uid: &str, notes: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO retained_contact_notes (uid, notes) VALUES (?1, ?2)",
+            params![uid, notes],
+        )?;
+        Ok(())
+    }
+
+    /// Remove and return retained notes for a contact, if any
+    pub fn take_retained_contact_notes(&self, uid: &str) -> Result<Option<String>> {
+        let notes = self.conn.query_row(
+            "SELECT notes FROM retained_contact_notes WHERE uid = ?1",
+            params![uid],
+            |row| row.get(0),
+        ).optional()?;
+
+        if notes.is_some() {
+            self.conn.execute("DELETE FROM retained_contact_notes WHERE uid = ?1", params![uid])?;
+        }
+        Ok(notes)
+    }
+
+    // ========== Pending Introductions ==========
+
+    /// Add or replace pending introductions
+    pub fn save_pending_introductions(&self, entries: &[PendingIntroduction]) -> Result<()> {
+        for entry in entries {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO pending_introductions (id, name, notes, added_at) VALUES (?1, ?2, ?3, ?4)",
+                params![&entry.id, &entry.name, &entry.notes, entry.added_at.timestamp_millis()],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Load pending introductions, oldest first
+    pub fn load_pending_introductions(&self) -> Result<Vec<PendingIntroduction>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, notes, added_at FROM pending_introductions ORDER BY added_at, rowid"
+        )?;
+        let entries = stmt
+            .query_map([], |row| {
+                Ok(PendingIntroduction {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    notes: row.get(2)?,
+                    added_at: DateTime::from_timestamp_millis(row.get(3)?).unwrap_or_default(),
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    /// Delete a pending introduction (linked or dismissed)
+    ///
+    /// # Returns
+    /// Whether it existed
+    pub fn delete_pending_introduction(&self, id: &str) -> Result<bool> {
+        let deleted = self.conn.execute("DELETE FROM pending_introductions WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    // ========== Send Leases ==========
+
+    /// Replace the stored send leases with `leases` (message ID, contact UID, start)
+    pub fn replace_send_leases(&self, leases: &[(String, String, DateTime<Utc>)]) -> Result<()> {
+        self.with_write_retry(|| {
+            self.conn.execute("DELETE FROM send_leases", [])?;
+            for (message_id, contact_uid, started_at) in leases {
+                self.conn.execute(
+                    "INSERT INTO send_leases (message_id, contact_uid, started_at) VALUES (?1, ?2, ?3)",
+                    params![message_id, contact_uid, started_at.timestamp_millis()],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Load the stored send leases, oldest first
+    pub fn load_send_leases(&self) -> Result<Vec<(String, String, DateTime<Utc>)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT message_id, contact_uid, started_at FROM send_leases ORDER BY started_at, rowid"
+        )?;
+        let leases = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, DateTime::from_timestamp_millis(row.get(2)?).unwrap_or_default()))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(leases)
+    }
+
+    // ========== Identity ==========
+
+    /// Replace the stored identity conflicts
+    pub fn save_identity_conflicts(&self, conflicts: &[IdentityConflict]) -> Result<()> {
+        self.conn.execute("DELETE FROM identity_conflicts", [])?;
+        for conflict in conflicts {
+            self.conn.execute(
+                "INSERT INTO identity_conflicts (kind, existing_uid, contact, detected_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    conflict.kind.as_str(),
+                    &conflict.existing_uid,
+                    serde_json::to_string(&conflict.contact)?,
+                    conflict.detected_at.timestamp_millis(),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Replace the stored address changes awaiting review
+    pub fn save_address_changes(&self, changes: &[AddressChange]) -> Result<()> {
+        self.conn.execute("DELETE FROM address_changes", [])?;
+        for change in changes {
+            self.conn.execute(
+                "INSERT INTO address_changes (uid, change) VALUES (?1, ?2)",
+                params![&change.uid, serde_json::to_string(change)?],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Load stored address changes awaiting review
+    ///
+    /// Rows that cannot be decoded are skipped.
+    pub fn load_address_changes(&self) -> Result<Vec<AddressChange>> {
+        let mut stmt = self.conn.prepare("SELECT change FROM address_changes ORDER BY uid")?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows.iter().filter_map(|change| serde_json::from_str(change).ok()).collect())
+    }
+
+    /// Load stored identity conflicts, oldest first
+    ///
+    /// Rows that cannot be decoded are skipped.
+    pub fn load_identity_conflicts(&self) -> Result<Vec<IdentityConflict>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT kind, existing_uid, contact, detected_at FROM identity_conflicts ORDER BY id"
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+            ))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(kind, existing_uid, contact, detected_at)| {
+                Some(IdentityConflict {
+                    kind: ConflictKind::parse(&kind)?,
+                    existing_uid,
+                    contact: serde_json::from_str(&contact).ok()?,
+                    detected_at: DateTime::from_timestamp_millis(detected_at)?,
+                })
+            })
+            .collect())
+    }
+
+    /// Move a duplicate contact's chat history to its canonical contact and
+    /// delete the duplicate's contact and chat rows
+    pub fn merge_duplicate_contact(&self, duplicate_uid: &str, canonical_uid: &str) -> Result<()> {
+        self.with_write_retry(|| {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO chats (contact_uid, is_active, has_pending_messages, has_failed_messages)
+                 SELECT ?2, is_active, has_pending_messages, has_failed_messages FROM chats WHERE contact_uid = ?1",
+                params![duplicate_uid, canonical_uid],
+            )?;
+            self.conn.execute(
+                "UPDATE messages SET chat_uid = ?2 WHERE chat_uid = ?1",
+                params![duplicate_uid, canonical_uid],
+            )?;
+            self.conn.execute("DELETE FROM chats WHERE contact_uid = ?1", params![duplicate_uid])?;
+            // Stale now; rebuilt by the next save or `migrate_chat_summaries`
+            self.conn.execute("DELETE FROM chat_summaries WHERE chat_uid = ?1", params![canonical_uid])?;
+            self.forget_saved_messages(duplicate_uid);
+            self.forget_saved_messages(canonical_uid);
+            self.conn.execute("DELETE FROM contacts WHERE uid = ?1", params![duplicate_uid])?;
+            Ok(())
+        })
+    }
+
+    /// Check the database file and the stored contacts' identities
+    ///
+    /// Runs SQLite's `PRAGMA quick_check` and `identity::scan_contacts`.
+    /// Nothing is modified.
+    pub fn verify_integrity(&self) -> Result<IntegrityReport> {
+        let mut stmt = self.conn.prepare("PRAGMA quick_check")?;
+        let problems = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|line| line != "ok")
+            .collect();
+
+        Ok(IntegrityReport {
+            problems,
+            conflicts: scan_contacts(&self.load_contacts()?),
+        })
+    }
+
+    /// Whether the named one-time integrity check has already run
+    pub fn integrity_check_completed(&self, name: &str) -> Result<bool> {
+        let found = self.conn.query_row(
+            "SELECT 1 FROM integrity_checks WHERE name = ?1",
+            params![name],
+            |_| Ok(()),
+        ).optional()?;
+        Ok(found.is_some())
+    }
+
+    /// Record that the named one-time integrity check has run
+    pub fn mark_integrity_check_completed(&self, name: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO integrity_checks (name, completed_at) VALUES (?1, ?2)",
+            params![name, Utc::now().timestamp_millis()],
+        )?;
+        Ok(())
+    }
+
+    /// When the release manifest was last fetched
+    pub fn last_update_check(&self) -> Result<Option<DateTime<Utc>>> {
+        let checked_at = self.conn.query_row(
+            "SELECT checked_at FROM update_check WHERE id = 1",
+            [],
+            |row| row.get::<_, i64>(0),
+        ).optional()?;
+        Ok(checked_at.and_then(DateTime::from_timestamp_millis))
+    }
+
+    /// Record a release manifest fetch at `at`
+    pub fn record_update_check(&self, at: DateTime<Utc>) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO update_check (id, checked_at) VALUES (1, ?1)",
+            params![at.timestamp_millis()],
+        )?;
+        Ok(())
+    }
+
+    /// When our token last changed, noting `inputs` (see `own_token_inputs`) as current at `now`
+    ///
+    /// The first call, and any call with different inputs, records `now` as
+    /// the change: a change is noticed when the next token is made, never
+    /// earlier than it happened.
+    pub fn own_token_changed_at(&self, inputs: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        let stored = self.conn.query_row(
+            "SELECT inputs, changed_at FROM own_token WHERE id = 1",
+            [],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+        ).optional()?;
+        if let Some((stored_inputs, changed_at)) = stored
+            && stored_inputs == inputs
+            && let Some(changed_at) = DateTime::from_timestamp_millis(changed_at)
+        {
+            return Ok(changed_at);
+        }
+        self.conn.execute(
+            "INSERT OR REPLACE INTO own_token (id, inputs, changed_at) VALUES (1, ?1, ?2)",
+            params![inputs, now.timestamp_millis()],
+        )?;
+        Ok(now)
+    }
+
+    /// Our TLS certificate and private key (PEM), if one was generated
+    pub fn load_tls_identity(&self) -> Result<Option<(String, String)>> {
+        Ok(self.conn.query_row(
+            "SELECT cert_pem, key_pem FROM tls_identity WHERE id = 1",
+            [],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        ).optional()?)
+    }
+
+    /// Store our TLS certificate and private key (PEM), replacing the old pair
+    pub fn save_tls_identity(&self, cert_pem: &str, key_pem: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO tls_identity (id, cert_pem, key_pem) VALUES (1, ?1, ?2)",
+            params![cert_pem, key_pem],
+        )?;
+        Ok(())
+    }
+
+    // ========== Journal ==========
+
+    /// Append `record` to the outbound delivery journal
+    ///
+    /// Fails if a record with the same sequence number exists.
+    pub fn append_journal_record(&self, record: &JournalRecord) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO journal (seq, at, kind, recipient_uid, content_sha256, ack_signature, prev_hash, hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                record.seq as i64,
+                record.at,
+                record.kind.name(),
+                &record.recipient_uid,
+                &record.content_sha256,
+                &record.ack_signature,
+                &record.prev_hash,
+                &record.hash,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Last record of the journal (the one the next record links to)
+    pub fn journal_tail(&self) -> Result<Option<JournalRecord>> {
+        let mut stmt = self.conn.prepare(&format!("{} ORDER BY seq DESC LIMIT 1", JOURNAL_SELECT))?;
+        Ok(stmt.query_row([], journal_record_from_row).optional()?)
+    }
+
+    /// Journal records appended between `since` and `until` (ms since epoch, inclusive), oldest first
+    pub fn load_journal(&self, since: Option<i64>, until: Option<i64>) -> Result<Vec<JournalRecord>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "{} WHERE at >= ?1 AND at <= ?2 ORDER BY seq ASC",
+            JOURNAL_SELECT
+        ))?;
+        let records = stmt
+            .query_map(params![since.unwrap_or(i64::MIN), until.unwrap_or(i64::MAX)], journal_record_from_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(records)
+    }
+
     // ========== Chats ==========
 
     /// Save or update a chat
+    ///
+    /// The chat row is updated in place (replacing it would delete its
+    /// messages through the foreign key) and messages already stored
+    /// unchanged are not rewritten, so saving a chat after adding a message
+    /// writes that message and the chat's summary. Messages this connection
+    /// already wrote unchanged at the same position are not even compared.
+    /// The summary is rebuilt from the chat's indexes only when its messages
+    /// changed.
+    /// Also deletes the messages trimmed by the history limit (unpinned,
+    /// older than `trimmed_before`), so run it inside the transaction that
+    /// stores the message which caused the trim (see `AppState::save_to_db`).
     pub fn save_chat(&self, chat: &Chat) -> Result<()> {
+        self.purge_deleted(DeletedKind::Chat, &chat.contact_uid, Utc::now())?;
         self.conn.execute(
-            "INSERT OR REPLACE INTO chats (contact_uid, is_active, has_pending_messages)
-             VALUES (?1, ?2, ?3)",
+            "INSERT INTO chats (contact_uid, is_active, has_pending_messages, has_failed_messages,
+                                history_limit, trimmed_before, muted, archived)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT(contact_uid) DO UPDATE SET
+                 is_active = excluded.is_active, has_pending_messages = excluded.has_pending_messages,
+                 has_failed_messages = excluded.has_failed_messages, history_limit = excluded.history_limit,
+                 trimmed_before = excluded.trimmed_before, muted = excluded.muted, archived = excluded.archived",
             params![
                 &chat.contact_uid,
                 chat.is_active as i32,
                 chat.has_pending_messages as i32,
+                chat.has_failed_messages as i32,
+                chat.history_limit,
+                chat.trimmed_before,
+                chat.muted as i32,
+                chat.archived as i32,
             ],
         )?;
 
-        // Save all messages in the chat
-        for message in &chat.messages {
-            self.save_message(message, &chat.contact_uid)?;
+        // Save the messages not written unchanged at the same position before
+        let saved = self.saved_messages.lock().unwrap().get(&chat.contact_uid).cloned().unwrap_or_default();
+        let mut fingerprints = Vec::with_capacity(chat.messages.len());
+        let mut changed = false;
+        for (i, message) in chat.messages.iter().enumerate() {
+            let fingerprint = message_fingerprint(message, &chat.contact_uid)?;
+            if saved.get(i) != Some(&fingerprint) {
+                changed |= self.save_message(message, &chat.contact_uid)?;
+            }
+            fingerprints.push(fingerprint);
         }
 
+        if let Some(trimmed_before) = chat.trimmed_before {
+            changed |= self.conn.execute(
+                "DELETE FROM messages
+                 WHERE chat_uid = ?1 AND timestamp < ?2 AND pinned = 0 AND sender != ?3",
+                params![&chat.contact_uid, trimmed_before, SYSTEM_SENDER],
+            )? > 0;
+        }
+
+        if changed || self.load_summary(&chat.contact_uid)?.is_none() {
+            self.build_summary(&chat.contact_uid)?;
+        }
+
+        if self.conn.is_autocommit() {
+            self.saved_messages.lock().unwrap().insert(chat.contact_uid.clone(), fingerprints);
+        } else {
+            self.staged_messages.lock().unwrap().push((chat.contact_uid.clone(), fingerprints));
+        }
         Ok(())
     }
 
+    /// Forget which messages of `chat_uid` this connection wrote, so the
+    /// next `save_chat` writes them all
+    fn forget_saved_messages(&self, chat_uid: &str) {
+        self.saved_messages.lock().unwrap().remove(chat_uid);
+    }
+
     /// Load all chats with their messages
     pub fn load_chats(&self) -> Result<Vec<Chat>> {
+        let mut chats = self.load_chat_headers()?;
+        for chat in &mut chats {
+            chat.messages = Arc::new(self.load_messages_for_chat(&chat.contact_uid)?);
+        }
+        Ok(chats)
+    }
+
+    /// Load all chats without their messages
+    ///
+    /// Used at startup so the UI can render before message history is read.
+    /// Each chat carries its `stored_summary`; the messages table is not read.
+    pub fn load_chat_headers(&self) -> Result<Vec<Chat>> {
         let mut stmt = self.conn.prepare(
-            "SELECT contact_uid, is_active, has_pending_messages FROM chats"
+            "SELECT c.contact_uid, c.is_active, c.has_pending_messages, c.has_failed_messages, c.history_limit,
+                    c.trimmed_before, c.muted, c.archived,
+                    s.message_count, s.latest_id, s.latest_timestamp, s.latest_sender, s.latest_preview
+             FROM chats c LEFT JOIN chat_summaries s ON s.chat_uid = c.contact_uid
+             WHERE c.deleted_at IS NULL"
         )?;
 
-        let mut chats = Vec::new();
-
-        for row in stmt.query_map([], |row| {
+        let chats = stmt.query_map([], |row| {
             let contact_uid: String = row.get(0)?;
             let is_active: i32 = row.get(1)?;
             let has_pending_messages: i32 = row.get(2)?;
+            let has_failed_messages: i32 = row.get(3)?;
 
-            Ok((contact_uid, is_active != 0, has_pending_messages != 0))
-        })? {
-            let (contact_uid, is_active, has_pending_messages) = row?;
-            let messages = self.load_messages_for_chat(&contact_uid)?;
+            Ok(Chat {
+                contact_uid,
+                messages: Arc::new(Vec::new()),
+                is_active: is_active != 0,
+                has_pending_messages: has_pending_messages != 0,
+                has_failed_messages: has_failed_messages != 0,
+                history_limit: row.get(4)?,
+                trimmed_before: row.get(5)?,
+                muted: row.get::<_, i32>(6)? != 0,
+                archived: row.get::<_, i32>(7)? != 0,
+                stored_summary: summary_from_row(row, 8)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(chats)
+    }
 
-            chats.push(Chat {
+    /// Load messages for a chat
+    pub fn load_chat_messages(&self, chat_uid: &str) -> Result<Vec<Message>> {
+        self.load_messages_for_chat(chat_uid)
+    }
+
+    /// Load one page of a chat's messages, oldest first
+    ///
+    /// Pages are keyed by the last message of the previous page (`after`, as
+    /// its timestamp and ID), so paging stays correct while messages are
+    /// added and costs the same at any depth.
+    pub fn load_chat_messages_page(&self, chat_uid: &str, after: Option<(i64, &str)>, limit: usize) -> Result<Vec<Message>> {
+        self.message_queries.fetch_add(1, Ordering::Relaxed);
+        let (after_timestamp, after_id) = after.unwrap_or((i64::MIN, ""));
+        let mut stmt = self.conn.prepare(
+            "SELECT id, sender, receiver, content, timestamp, metadata, pinned, starred, relayed_via, remote_id, edit_history, protection FROM messages
+             WHERE chat_uid = ?1 AND (timestamp > ?2 OR (timestamp = ?2 AND id > ?3))
+               AND chat_uid IN (SELECT contact_uid FROM chats WHERE deleted_at IS NULL)
+             ORDER BY timestamp ASC, id ASC
+             LIMIT ?4"
+        )?;
+
+        let messages = stmt
+            .query_map(params![chat_uid, after_timestamp, after_id, limit as i64], message_from_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(messages)
+    }
+
+    /// Write a snapshot of the whole database to `path`
+    ///
+    /// Sealed under `keypair`'s snapshot key when one is given (see
+    /// `storage::snapshot`); the file must not exist yet.
+    ///
+    /// # Errors
+    /// Returns `Error::Storage` if the file exists, `Error::Database`,
+    /// `Error::Io` or `Error::Crypto` if copying or sealing fails
+    pub fn write_snapshot(&self, path: &Path, keypair: Option<&KeyPair>) -> Result<()> {
+        let key = keypair.map(KeyPair::snapshot_key).transpose()?;
+        snapshot::write_snapshot(&self.conn, path, key.as_ref())
+    }
+
+    /// Compare the snapshot at `snapshot` (older side) with this database
+    ///
+    /// Read-only. A sealed snapshot `keypair` cannot open is reported as
+    /// `SnapshotComparison::Encrypted`.
+    pub fn diff_against_snapshot(&self, snapshot: &Path, keypair: Option<&KeyPair>) -> Result<SnapshotComparison> {
+        snapshot::diff_snapshot_with_live(snapshot, &self.conn, keypair)
+    }
+
+    /// Write a chat as JSON Lines, one `ExportedMessage` per line
+    ///
+    /// Reads `EXPORT_PAGE_SIZE` messages at a time, so memory does not grow
+    /// with the length of the chat. Returns the number of lines written.
+    ///
+    /// # Errors
+    /// Returns `Error::Database` if reading fails and `Error::Io` or
+    /// `Error::JsonSerialization` if writing fails
+    pub fn export_chat_jsonl<W: std::io::Write>(
+        &self,
+        contact_uid: &str,
+        writer: &mut W,
+        options: &JsonlExportOptions,
+    ) -> Result<usize> {
+        let mut written = 0;
+        let mut cursor: Option<(i64, String)> = None;
+        loop {
+            let page = self.load_chat_messages_page(
                 contact_uid,
-                messages,
-                is_active,
-                has_pending_messages,
-            });
+                cursor.as_ref().map(|(timestamp, id)| (*timestamp, id.as_str())),
+                EXPORT_PAGE_SIZE,
+            )?;
+            for message in page.iter().filter(|m| options.includes(m, contact_uid)) {
+                serde_json::to_writer(&mut *writer, &ExportedMessage::new(message, contact_uid, options.content))?;
+                writer.write_all(b"\n")?;
+                written += 1;
+            }
+
+            match page.last() {
+                Some(last) if page.len() == EXPORT_PAGE_SIZE && options.until.is_none_or(|until| last.timestamp <= until) => {
+                    cursor = Some((last.timestamp, last.id.clone()));
+                }
+                _ => break,
+            }
         }
+        writer.flush()?;
+        Ok(written)
+    }
 
-        Ok(chats)
+    /// Stream a message's content into `writer` without loading it whole
+    ///
+    /// Reads the stored blob in `CONTENT_CHUNK_BYTES` slices. Returns the
+    /// number of bytes written, or `None` if the message is not stored.
+    pub fn copy_message_content<W: std::io::Write>(&self, message_id: &str, writer: &mut W) -> Result<Option<u64>> {
+        let len: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT length(content) FROM messages
+                 WHERE id = ?1 AND chat_uid IN (SELECT contact_uid FROM chats WHERE deleted_at IS NULL)",
+                params![message_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(len) = len else {
+            return Ok(None);
+        };
+
+        let mut stmt = self.conn.prepare("SELECT substr(content, ?1, ?2) FROM messages WHERE id = ?3")?;
+        let mut offset: i64 = 0;
+        while offset < len {
+            // substr() on a blob is 1-based and returns a blob
+            let chunk: Vec<u8> = stmt.query_row(
+                params![offset + 1, CONTENT_CHUNK_BYTES as i64, message_id],
+                |row| row.get(0),
+            )?;
+            if chunk.is_empty() {
+                break;
+            }
+            writer.write_all(&chunk)?;
+            offset += chunk.len() as i64;
+        }
+        writer.flush()?;
+        Ok(Some(offset as u64))
+    }
+
+    /// Write a chat as a signed archive the other participant can verify
+    ///
+    /// Same paging as `export_chat_jsonl`; local notices are left out. The
+    /// manifest and the signature by `keypair` follow the message lines (see
+    /// `storage::chat_archive`). Returns the number of messages written.
+    ///
+    /// # Errors
+    /// Returns `Error::Database` if reading fails, `Error::Crypto` if signing
+    /// fails and `Error::Io` or `Error::JsonSerialization` if writing fails
+    pub fn export_chat_archive<W: std::io::Write>(&self, contact_uid: &str, keypair: &KeyPair, writer: &mut W) -> Result<usize> {
+        let mut archive = ArchiveWriter::new(&keypair.uid.to_string(), contact_uid, &keypair.public_key);
+        let mut written = 0;
+        let mut cursor: Option<(i64, String)> = None;
+        loop {
+            let page = self.load_chat_messages_page(
+                contact_uid,
+                cursor.as_ref().map(|(timestamp, id)| (*timestamp, id.as_str())),
+                EXPORT_PAGE_SIZE,
+            )?;
+            for message in page.iter().filter(|m| !m.is_system()) {
+                writer.write_all(archive.line(message)?.as_bytes())?;
+                writer.write_all(b"\n")?;
+                written += 1;
+            }
+            match page.last() {
+                Some(last) if page.len() == EXPORT_PAGE_SIZE => cursor = Some((last.timestamp, last.id.clone())),
+                _ => break,
+            }
+        }
+        writer.write_all(archive.finish(keypair)?.as_bytes())?;
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Compare a verified archive's manifest with our chat with its exporter
+    ///
+    /// Read-only; see `chat_archive::cross_reference`. A chat we do not have
+    /// reports every archived message as only in the archive.
+    pub fn cross_reference_archive(&self, manifest: &ArchiveManifest) -> Result<CrossReference> {
+        let messages = self.load_chat_messages(&manifest.owner_uid)?;
+        Ok(cross_reference(manifest, &messages))
     }
 
     /// Delete a chat and all its messages
     pub fn delete_chat(&self, contact_uid: &str) -> Result<()> {
         self.conn.execute("DELETE FROM chats WHERE contact_uid = ?1", params![contact_uid])?;
-        // Messages will be deleted automatically due to CASCADE
+        // Messages and the summary will be deleted automatically due to CASCADE
+        self.forget_saved_messages(contact_uid);
         Ok(())
     }
 
-    // ========== Messages ==========
+    // ========== Soft Delete ==========
+
+    /// Soft-delete a chat: every load leaves it out until it is restored or
+    /// purged (see `storage::soft_delete`)
+    ///
+    /// # Returns
+    /// Whether a chat that was not deleted yet was marked
+    pub fn soft_delete_chat(&self, contact_uid: &str, at: DateTime<Utc>) -> Result<bool> {
+        let marked = self.conn.execute(
+            "UPDATE chats SET deleted_at = ?2 WHERE contact_uid = ?1 AND deleted_at IS NULL",
+            params![contact_uid, at.timestamp_millis()],
+        )?;
+        Ok(marked > 0)
+    }
+
+    /// Soft-delete a contact (its chat is marked separately)
+    ///
+    /// # Returns
+    /// Whether a contact that was not deleted yet was marked
+    pub fn soft_delete_contact(&self, uid: &str, at: DateTime<Utc>) -> Result<bool> {
+        let marked = self.conn.execute(
+            "UPDATE contacts SET deleted_at = ?2 WHERE uid = ?1 AND deleted_at IS NULL",
+            params![uid, at.timestamp_millis()],
+        )?;
+        Ok(marked > 0)
+    }
+
+    /// Undo `soft_delete_chat`
+    ///
+    /// # Returns
+    /// Whether the chat was still soft-deleted (false once purged)
+    pub fn restore_chat(&self, contact_uid: &str) -> Result<bool> {
+        let restored = self.conn.execute(
+            "UPDATE chats SET deleted_at = NULL WHERE contact_uid = ?1 AND deleted_at IS NOT NULL",
+            params![contact_uid],
+        )?;
+        Ok(restored > 0)
+    }
+
+    /// Undo `soft_delete_contact`
+    ///
+    /// # Returns
+    /// Whether the contact was still soft-deleted (false once purged)
+    pub fn restore_contact(&self, uid: &str) -> Result<bool> {
+        let restored = self.conn.execute(
+            "UPDATE contacts SET deleted_at = NULL WHERE uid = ?1 AND deleted_at IS NOT NULL",
+            params![uid],
+        )?;
+        Ok(restored > 0)
+    }
+
+    /// UIDs of the chats and contacts that are soft-deleted
+    pub fn soft_deleted_uids(&self) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT contact_uid FROM chats WHERE deleted_at IS NOT NULL
+             UNION SELECT uid FROM contacts WHERE deleted_at IS NOT NULL"
+        )?;
+        let uids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<HashSet<String>, _>>()?;
+        Ok(uids)
+    }
 
-    /// Save a message
-    fn save_message(&self, message: &Message, chat_uid: &str) -> Result<()> {
+    /// Remove the chats and contacts soft-deleted at or before `cutoff`
+    ///
+    /// Their messages and summaries go with them (foreign keys), and a
+    /// tombstone stamped `now` is written for each in the same transaction.
+    /// Queued messages live in the queue database; the caller purges those.
+    ///
+    /// # Returns
+    /// The tombstones written, chats before contacts
+    ///
+    /// # Errors
+    /// Returns `Error::Database` if reading or writing fails
+    pub fn purge_soft_deleted(&self, cutoff: DateTime<Utc>, now: DateTime<Utc>) -> Result<Vec<Tombstone>> {
+        self.with_write_retry(|| {
+            let due = {
+                let mut stmt = self.conn.prepare(
+                    "SELECT 'chat', contact_uid FROM chats WHERE deleted_at IS NOT NULL AND deleted_at <= ?1
+                     UNION ALL
+                     SELECT 'contact', uid FROM contacts WHERE deleted_at IS NOT NULL AND deleted_at <= ?1"
+                )?;
+                stmt.query_map(params![cutoff.timestamp_millis()], |row| {
+                    Ok((DeletedKind::parse(&row.get::<_, String>(0)?), row.get::<_, String>(1)?))
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?
+            };
+
+            let mut tombstones = Vec::new();
+            for (kind, uid) in due {
+                tombstones.extend(self.purge_deleted(kind, &uid, now)?);
+            }
+            Ok(tombstones)
+        })
+    }
+
+    /// All tombstones, oldest purge first
+    pub fn load_tombstones(&self) -> Result<Vec<Tombstone>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT uid, kind, deleted_at, purged_at FROM tombstones ORDER BY purged_at, uid"
+        )?;
+        let tombstones = stmt
+            .query_map([], |row| {
+                Ok(Tombstone {
+                    uid: row.get(0)?,
+                    kind: DeletedKind::parse(&row.get::<_, String>(1)?),
+                    deleted_at: row.get(2)?,
+                    purged_at: row.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(tombstones)
+    }
+
+    /// Remove a soft-deleted chat or contact and write its tombstone
+    ///
+    /// A live row is left alone. A contact takes its soft-deleted chat
+    /// with it (tombstoned first).
+    fn purge_deleted(&self, kind: DeletedKind, uid: &str, now: DateTime<Utc>) -> Result<Vec<Tombstone>> {
+        let (select, delete) = match kind {
+            DeletedKind::Chat => (
+                "SELECT deleted_at FROM chats WHERE contact_uid = ?1 AND deleted_at IS NOT NULL",
+                "DELETE FROM chats WHERE contact_uid = ?1",
+            ),
+            DeletedKind::Contact => (
+                "SELECT deleted_at FROM contacts WHERE uid = ?1 AND deleted_at IS NOT NULL",
+                "DELETE FROM contacts WHERE uid = ?1",
+            ),
+        };
+        let deleted_at: Option<i64> = self.conn.prepare_cached(select)?.query_row(params![uid], |row| row.get(0)).optional()?;
+        let Some(deleted_at) = deleted_at else {
+            return Ok(Vec::new());
+        };
+
+        // A deleted contact takes its (also deleted) chat along, chat first
+        let mut tombstones = Vec::new();
+        if kind == DeletedKind::Contact {
+            tombstones.extend(self.purge_deleted(DeletedKind::Chat, uid, now)?);
+        }
+        self.conn.execute(delete, params![uid])?;
+        self.forget_saved_messages(uid);
+        let tombstone = Tombstone { uid: uid.to_string(), kind, deleted_at, purged_at: now.timestamp_millis() };
         self.conn.execute(
-            "INSERT OR REPLACE INTO messages (id, sender, receiver, content, timestamp, chat_uid)
+            "INSERT OR REPLACE INTO tombstones (uid, kind, deleted_at, purged_at) VALUES (?1, ?2, ?3, ?4)",
+            params![&tombstone.uid, kind.as_str(), tombstone.deleted_at, tombstone.purged_at],
+        )?;
+        tombstones.push(tombstone);
+        Ok(tombstones)
+    }
+
+    // ========== Chat Summaries ==========
+
+    /// Summaries of all chats that have one, keyed by contact UID
+    ///
+    /// Reads only `chat_summaries` and `chats` (soft-deleted chats are left
+    /// out), never the messages table.
+    pub fn load_chat_summaries(&self) -> Result<Vec<(String, MessageSummary)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT s.chat_uid, s.message_count, s.latest_id, s.latest_timestamp, s.latest_sender, s.latest_preview
+             FROM chat_summaries s JOIN chats c ON c.contact_uid = s.chat_uid
+             WHERE c.deleted_at IS NULL"
+        )?;
+        let summaries = stmt
+            .query_map([], |row| Ok((row.get(0)?, summary_from_row(row, 1)?.unwrap_or_default())))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(summaries)
+    }
+
+    /// Build the summary of every chat that has none yet
+    ///
+    /// Needed once for a database written before `chat_summaries` existed.
+    /// Each chat is built in its own transaction and `progress` is called
+    /// after each one; returning `ControlFlow::Break` stops after that chat.
+    /// A stopped or interrupted run resumes where it left off, since built
+    /// summaries are skipped.
+    ///
+    /// # Errors
+    /// Returns `Error::Database` if reading or writing fails
+    pub fn migrate_chat_summaries(
+        &self,
+        mut progress: impl FnMut(&SummaryMigration) -> ControlFlow<()>,
+    ) -> Result<SummaryMigration> {
+        let total: i64 = self.conn.query_row("SELECT COUNT(*) FROM chats", [], |row| row.get(0))?;
+        let pending = {
+            let mut stmt = self.conn.prepare(
+                "SELECT contact_uid FROM chats
+                 WHERE contact_uid NOT IN (SELECT chat_uid FROM chat_summaries)
+                 ORDER BY contact_uid"
+            )?;
+            stmt.query_map([], |row| row.get::<_, String>(0))?
+                .collect::<std::result::Result<Vec<_>, _>>()?
+        };
+
+        let mut state = SummaryMigration { done: total as usize - pending.len(), total: total as usize };
+        for chat_uid in pending {
+            self.with_write_retry(|| self.build_summary(&chat_uid))?;
+            state.done += 1;
+            if progress(&state).is_break() {
+                break;
+            }
+        }
+        Ok(state)
+    }
+
+    /// Stored summary of a chat, if built
+    fn load_summary(&self, chat_uid: &str) -> Result<Option<MessageSummary>> {
+        let summary = self.conn.query_row(
+            "SELECT message_count, latest_id, latest_timestamp, latest_sender, latest_preview
+             FROM chat_summaries WHERE chat_uid = ?1",
+            params![chat_uid],
+            |row| summary_from_row(row, 0),
+        ).optional()?;
+        Ok(summary.flatten())
+    }
+
+    /// Compute a chat's summary from its stored messages and store it
+    ///
+    /// Both reads are served by the `(chat_uid, ...)` indexes.
+    fn build_summary(&self, chat_uid: &str) -> Result<()> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM messages WHERE chat_uid = ?1",
+            params![chat_uid],
+            |row| row.get(0),
+        )?;
+        let mut summary = MessageSummary { message_count: count as u64, ..Default::default() };
+        summary.set_latest(self.load_latest_message(chat_uid)?.as_ref());
+        self.store_summary(chat_uid, &summary)
+    }
+
+    /// Latest stored message of a chat (by timestamp, then ID)
+    fn load_latest_message(&self, chat_uid: &str) -> Result<Option<Message>> {
+        let message = self.conn.query_row(
+            "SELECT id, sender, receiver, content, timestamp, metadata, pinned, starred, relayed_via, remote_id, edit_history, protection FROM messages
+             WHERE chat_uid = ?1 ORDER BY timestamp DESC, id DESC LIMIT 1",
+            params![chat_uid],
+            message_from_row,
+        ).optional()?;
+        Ok(message)
+    }
+
+    fn store_summary(&self, chat_uid: &str, summary: &MessageSummary) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO chat_summaries
+                 (chat_uid, message_count, latest_id, latest_timestamp, latest_sender, latest_preview)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![
-                &message.id,
-                &message.sender,
-                &message.recipient,
-                &message.content,
-                message.timestamp,
                 chat_uid,
+                summary.message_count as i64,
+                &summary.latest_id,
+                summary.latest_timestamp,
+                &summary.latest_sender,
+                &summary.latest_preview,
             ],
         )?;
         Ok(())
     }
 
+    // ========== Messages ==========
+
+    /// Save a message, leaving an identical stored copy untouched
+    ///
+    /// Returns whether the row was inserted or changed.
+    fn save_message(&self, message: &Message, chat_uid: &str) -> Result<bool> {
+        let metadata = encode_metadata(&message.metadata)?;
+        let edit_history = encode_edit_history(&message.edit_history)?;
+        let values = params![
+            &message.id,
+            &message.sender,
+            &message.recipient,
+            &message.content,
+            message.timestamp,
+            chat_uid,
+            metadata,
+            message.pinned as i32,
+            message.starred as i32,
+            &message.relayed_via,
+            &message.remote_id,
+            edit_history,
+            message.protection.map(|protection| protection.name()),
+        ];
+
+        // Cached: `save_chat` runs it for every message of the chat
+        let written = self.conn.prepare_cached(
+            "INSERT INTO messages (id, sender, receiver, content, timestamp, chat_uid, metadata, pinned, starred, relayed_via, remote_id, edit_history, protection)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+             ON CONFLICT(id) DO UPDATE SET
+                 sender = excluded.sender, receiver = excluded.receiver, content = excluded.content,
+                 timestamp = excluded.timestamp, chat_uid = excluded.chat_uid, metadata = excluded.metadata,
+                 pinned = excluded.pinned, starred = excluded.starred, relayed_via = excluded.relayed_via,
+                 remote_id = excluded.remote_id, edit_history = excluded.edit_history, protection = excluded.protection
+             WHERE sender IS NOT excluded.sender OR receiver IS NOT excluded.receiver OR content IS NOT excluded.content
+                OR timestamp IS NOT excluded.timestamp OR chat_uid IS NOT excluded.chat_uid
+                OR metadata IS NOT excluded.metadata OR pinned IS NOT excluded.pinned OR starred IS NOT excluded.starred
+                OR relayed_via IS NOT excluded.relayed_via OR remote_id IS NOT excluded.remote_id
+                OR edit_history IS NOT excluded.edit_history OR protection IS NOT excluded.protection",
+        )?.execute(values)? > 0;
+        if written {
+            self.message_writes.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(written)
+    }
+
     /// Load all messages for a specific chat
     fn load_messages_for_chat(&self, chat_uid: &str) -> Result<Vec<Message>> {
+        self.message_queries.fetch_add(1, Ordering::Relaxed);
         let mut stmt = self.conn.prepare(
-            "SELECT id, sender, receiver, content, timestamp FROM messages
-             WHERE chat_uid = ?1 ORDER BY timestamp ASC"
+            "SELECT id, sender, receiver, content, timestamp, metadata, pinned, starred, relayed_via, remote_id, edit_history, protection FROM messages
+             WHERE chat_uid = ?1 AND chat_uid IN (SELECT contact_uid FROM chats WHERE deleted_at IS NULL)
+             ORDER BY timestamp ASC"
         )?;
 
-        let messages = stmt.query_map(params![chat_uid], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                sender: row.get(1)?,
-                recipient: row.get(2)?,
-                content: row.get(3)?,
-                timestamp: row.get(4)?,
-                delivered: false,
-                delivery_status: crate::storage::message::DeliveryStatus::Sent,
-                next_retry_at: None,
-                attempts: 0,
-            })
-        })?
+        let messages = stmt.query_map(params![chat_uid], message_from_row)?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(messages)
@@ -395,8 +1816,16 @@ impl Storage {
             "INSERT OR REPLACE INTO settings (
                 id, default_contact_expiry_days, auto_accept_contacts,
                 max_message_retries, retry_base_delay_ms, enable_notifications,
-                global_retry_interval_ms, retry_interval_minutes, storage_path
-            ) VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                global_retry_interval_ms, retry_interval_minutes, storage_path,
+                quiet_hours_enabled, quiet_hours_start_minutes, quiet_hours_end_minutes,
+                quiet_hours_days, invite_code_length, relay_enabled, profile_label, accent_color,
+                history_limit, alert_mode, duplicate_window_secs, address_review_enabled,
+                address_auto_apply_failures, auto_import_contacts_per_hour, auto_import_chats_per_hour,
+                send_read_receipts, send_typing, send_presence, edit_window_minutes, error_banner_severity,
+                max_token_expiry_days, update_check_enabled, update_manifest_url, tls_enabled,
+                require_tls_external, journal_enabled, soft_delete_window_hours, min_token_validity_minutes,
+                auto_send_preview, alert_style, night_warning_enabled, log_max_file_bytes, log_files_kept
+            ) VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34, ?35, ?36, ?37, ?38, ?39, ?40, ?41)",
             params![
                 settings.default_contact_expiry_days,
                 settings.auto_accept_contacts as i32,
@@ -406,8 +1835,49 @@ impl Storage {
                 settings.global_retry_interval_ms as i64,
                 settings.retry_interval_minutes,
                 &settings.storage_path,
+                settings.quiet_hours_enabled as i32,
+                settings.quiet_hours_start_minutes,
+                settings.quiet_hours_end_minutes,
+                settings.quiet_hours_days,
+                settings.invite_code_length as i64,
+                settings.relay_enabled as i32,
+                &settings.profile_label,
+                settings.accent_color.map(|accent| accent.name()),
+                settings.history_limit,
+                settings.alert_mode.name(),
+                settings.duplicate_window_secs,
+                settings.address_review_enabled as i32,
+                settings.address_auto_apply_failures,
+                settings.auto_import_contacts_per_hour,
+                settings.auto_import_chats_per_hour,
+                settings.send_read_receipts as i32,
+                settings.send_typing as i32,
+                settings.send_presence as i32,
+                settings.edit_window_minutes,
+                settings.error_banner_severity.name(),
+                settings.max_token_expiry_days,
+                settings.update_check_enabled as i32,
+                &settings.update_manifest_url,
+                settings.tls_enabled as i32,
+                settings.require_tls_external as i32,
+                settings.journal_enabled as i32,
+                settings.soft_delete_window_hours,
+                settings.min_token_validity_minutes,
+                settings.auto_send_preview as i32,
+                settings.alert_style.name(),
+                settings.night_warning_enabled as i32,
+                settings.log_max_file_bytes as i64,
+                settings.log_files_kept,
             ],
         )?;
+
+        self.conn.execute("DELETE FROM message_templates", [])?;
+        for (position, template) in settings.templates.iter().enumerate() {
+            self.conn.execute(
+                "INSERT INTO message_templates (position, name, body) VALUES (?1, ?2, ?3)",
+                params![position as i64, &template.name, &template.body],
+            )?;
+        }
         Ok(())
     }
 
@@ -416,7 +1886,16 @@ impl Storage {
         let result = self.conn.query_row(
             "SELECT default_contact_expiry_days, auto_accept_contacts, max_message_retries,
                     retry_base_delay_ms, enable_notifications, global_retry_interval_ms,
-                    retry_interval_minutes, storage_path
+                    retry_interval_minutes, storage_path, quiet_hours_enabled,
+                    quiet_hours_start_minutes, quiet_hours_end_minutes, quiet_hours_days,
+                    invite_code_length, relay_enabled, profile_label, accent_color, history_limit,
+                    alert_mode, duplicate_window_secs, address_review_enabled, address_auto_apply_failures,
+                    auto_import_contacts_per_hour, auto_import_chats_per_hour,
+                    send_read_receipts, send_typing, send_presence, edit_window_minutes,
+                    error_banner_severity, max_token_expiry_days, update_check_enabled,
+                    update_manifest_url, tls_enabled, require_tls_external, journal_enabled,
+                    soft_delete_window_hours, min_token_validity_minutes, auto_send_preview, alert_style,
+                    night_warning_enabled, log_max_file_bytes, log_files_kept
              FROM settings WHERE id = 1",
             [],
             |row| {
@@ -429,11 +1908,58 @@ impl Storage {
                     global_retry_interval_ms: row.get::<_, i64>(5)? as u64,
                     retry_interval_minutes: row.get(6)?,
                     storage_path: row.get(7)?,
+                    quiet_hours_enabled: row.get::<_, i32>(8)? != 0,
+                    quiet_hours_start_minutes: row.get(9)?,
+                    quiet_hours_end_minutes: row.get(10)?,
+                    quiet_hours_days: row.get(11)?,
+                    invite_code_length: row.get::<_, i64>(12)? as usize,
+                    relay_enabled: row.get::<_, i32>(13)? != 0,
+                    profile_label: row.get(14)?,
+                    accent_color: row
+                        .get::<_, Option<String>>(15)?
+                        .and_then(|name| AccentColor::from_name(&name)),
+                    history_limit: row.get(16)?,
+                    alert_mode: AlertMode::from_name(&row.get::<_, String>(17)?).unwrap_or_default(),
+                    duplicate_window_secs: row.get(18)?,
+                    address_review_enabled: row.get::<_, i32>(19)? != 0,
+                    address_auto_apply_failures: row.get(20)?,
+                    auto_import_contacts_per_hour: row.get(21)?,
+                    auto_import_chats_per_hour: row.get(22)?,
+                    send_read_receipts: row.get::<_, i32>(23)? != 0,
+                    send_typing: row.get::<_, i32>(24)? != 0,
+                    send_presence: row.get::<_, i32>(25)? != 0,
+                    edit_window_minutes: row.get(26)?,
+                    error_banner_severity: ErrorSeverity::from_name(&row.get::<_, String>(27)?).unwrap_or_default(),
+                    max_token_expiry_days: row.get(28)?,
+                    update_check_enabled: row.get::<_, i32>(29)? != 0,
+                    update_manifest_url: Some(row.get::<_, String>(30)?)
+                        .filter(|url| !url.is_empty())
+                        .unwrap_or_else(|| crate::update_check::DEFAULT_UPDATE_MANIFEST_URL.to_string()),
+                    tls_enabled: row.get::<_, i32>(31)? != 0,
+                    require_tls_external: row.get::<_, i32>(32)? != 0,
+                    journal_enabled: row.get::<_, i32>(33)? != 0,
+                    soft_delete_window_hours: row.get(34)?,
+                    min_token_validity_minutes: row.get(35)?,
+                    auto_send_preview: row.get::<_, i32>(36)? != 0,
+                    alert_style: AlertStyle::from_name(&row.get::<_, String>(37)?).unwrap_or_default(),
+                    night_warning_enabled: row.get::<_, i32>(38)? != 0,
+                    log_max_file_bytes: row.get::<_, i64>(39)? as u64,
+                    log_files_kept: row.get(40)?,
+                    templates: Vec::new(),
                 })
             },
         ).optional()?;
 
-        Ok(result)
+        let Some(mut settings) = result else {
+            return Ok(None);
+        };
+        settings.bound_limits();
+        let mut stmt = self.conn.prepare("SELECT name, body FROM message_templates ORDER BY position")?;
+        settings.templates = stmt
+            .query_map([], |row| Ok(MessageTemplate::new(row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(Some(settings))
     }
 
     // ========== Request Logs ==========
@@ -497,6 +2023,34 @@ impl Storage {
         Ok(logs)
     }
 
+    /// Get recent request logs of one request type (e.g. an audit entry)
+    pub fn get_request_logs_of_type(&self, request_type: &str, limit: usize) -> Result<Vec<RequestLog>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, timestamp, direction, request_type, target_uid, target_ip, status_code, success, error_message, response_data
+             FROM request_logs
+             WHERE request_type = ?1
+             ORDER BY timestamp DESC, id DESC
+             LIMIT ?2",
+        )?;
+
+        let logs = stmt.query_map(params![request_type, limit], |row| {
+            Ok(RequestLog {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                direction: row.get(2)?,
+                request_type: row.get(3)?,
+                target_uid: row.get(4)?,
+                target_ip: row.get(5)?,
+                status_code: row.get(6)?,
+                success: row.get::<_, i32>(7)? != 0,
+                error_message: row.get(8)?,
+                response_data: row.get(9)?,
+            })
+        })?.collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(logs)
+    }
+
     /// Get request logs for a specific contact
     pub fn get_request_logs_for_contact(&self, uid: &str, limit: usize) -> Result<Vec<RequestLog>> {
         let mut stmt = self.conn.prepare(
@@ -525,6 +2079,18 @@ impl Storage {
         Ok(logs)
     }
 
+    /// Delete request logs of `request_type` logged at or after `since_ms`
+    ///
+    /// # Returns
+    /// Number of entries deleted
+    pub fn delete_request_logs_since(&self, request_type: &str, since_ms: i64) -> Result<usize> {
+        let deleted = self.conn.execute(
+            "DELETE FROM request_logs WHERE request_type = ?1 AND timestamp >= ?2",
+            params![request_type, since_ms],
+        )?;
+        Ok(deleted)
+    }
+
     /// Clear old request logs (keep last N days)
     pub fn clear_old_request_logs(&self, keep_days: i64) -> Result<()> {
         let cutoff = chrono::Utc::now().timestamp_millis() - (keep_days * 24 * 60 * 60 * 1000);
@@ -535,20 +2101,324 @@ impl Storage {
         Ok(())
     }
 
+    // ========== Connectivity History ==========
+
+    /// Record a connectivity event, dropping the oldest beyond `CONNECTIVITY_HISTORY_LIMIT`
+    pub fn record_connectivity_event(&self, at: DateTime<Utc>, kind: &str, detail: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO connectivity_history (at, kind, detail) VALUES (?1, ?2, ?3)",
+            params![at.timestamp_millis(), kind, detail],
+        )?;
+        self.conn.execute(
+            "DELETE FROM connectivity_history WHERE id <= (SELECT MAX(id) FROM connectivity_history) - ?1",
+            params![CONNECTIVITY_HISTORY_LIMIT as i64],
+        )?;
+        Ok(())
+    }
+
+    /// The latest `limit` connectivity events, oldest first
+    pub fn load_connectivity_history(&self, limit: usize) -> Result<Vec<ConnectivityHistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT at, kind, detail FROM (
+                 SELECT id, at, kind, detail FROM connectivity_history ORDER BY id DESC LIMIT ?1
+             ) ORDER BY id",
+        )?;
+        let entries = stmt
+            .query_map(params![limit], |row| {
+                Ok(ConnectivityHistoryEntry {
+                    at: DateTime::from_timestamp_millis(row.get(0)?).unwrap_or_default(),
+                    kind: row.get(1)?,
+                    detail: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
     // ========== Utility ==========
 
+    /// Number of queries run against the messages table since this connection was opened
+    pub fn message_query_count(&self) -> u64 {
+        self.message_queries.load(Ordering::Relaxed)
+    }
+
+    /// Number of message rows inserted or changed since this connection was opened
+    pub fn message_write_count(&self) -> u64 {
+        self.message_writes.load(Ordering::Relaxed)
+    }
+
+    /// Number of write transactions committed since this connection was opened
+    pub fn commit_count(&self) -> u64 {
+        self.commits.load(Ordering::Relaxed)
+    }
+
     /// Clear all data (for testing)
     pub fn clear_all(&self) -> Result<()> {
         self.conn.execute("DELETE FROM messages", [])?;
+        self.saved_messages.lock().unwrap().clear();
         self.conn.execute("DELETE FROM chats", [])?;
         self.conn.execute("DELETE FROM contacts", [])?;
+        self.conn.execute("DELETE FROM retained_contact_notes", [])?;
         self.conn.execute("DELETE FROM user_identity", [])?;
         self.conn.execute("DELETE FROM settings", [])?;
         self.conn.execute("DELETE FROM request_logs", [])?;
+        self.conn.execute("DELETE FROM connectivity_history", [])?;
+        self.conn.execute("DELETE FROM identity_conflicts", [])?;
+        self.conn.execute("DELETE FROM address_changes", [])?;
+        self.conn.execute("DELETE FROM tombstones", [])?;
         Ok(())
     }
 }
 
+/// Add a column to an existing table unless it is already present
+pub(crate) fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<std::result::Result<Vec<_>, _>>()?
+        .iter()
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// Allow contacts without an X25519 key
+///
+/// Databases created while the key was `NOT NULL` get the contacts table
+/// rebuilt with a nullable column. Empty keys become NULL; no key is ever
+/// made up for an old row.
+fn make_contact_x25519_nullable(conn: &Connection) -> Result<()> {
+    let not_null = conn
+        .prepare("PRAGMA table_info(contacts)")?
+        .query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, i64>(3)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?
+        .iter()
+        .any(|(name, not_null)| name == "x25519_pubkey" && *not_null != 0);
+    if !not_null {
+        return Ok(());
+    }
+
+    conn.execute_batch(
+        "BEGIN;
+         CREATE TABLE contacts_x25519_nullable (
+             uid TEXT PRIMARY KEY,
+             ip TEXT NOT NULL,
+             pubkey BLOB NOT NULL,
+             x25519_pubkey BLOB,
+             expiry INTEGER NOT NULL,
+             is_active INTEGER NOT NULL,
+             notes TEXT NOT NULL DEFAULT '',
+             endpoints TEXT,
+             is_relay INTEGER NOT NULL DEFAULT 0,
+             relay_reachable TEXT
+         );
+         INSERT INTO contacts_x25519_nullable
+             SELECT uid, ip, pubkey,
+                    CASE WHEN length(x25519_pubkey) = 0 THEN NULL ELSE x25519_pubkey END,
+                    expiry, is_active, notes, endpoints, is_relay, relay_reachable
+             FROM contacts;
+         DROP TABLE contacts;
+         ALTER TABLE contacts_x25519_nullable RENAME TO contacts;
+         COMMIT;",
+    )?;
+    Ok(())
+}
+
+/// Encode message metadata as JSON for a TEXT column (NULL when empty)
+pub(crate) fn encode_metadata(metadata: &MessageMetadata) -> Result<Option<String>> {
+    if metadata.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(metadata)?))
+}
+
+/// Decode message metadata from a TEXT column
+///
+/// Unreadable metadata is dropped rather than failing the whole load.
+pub(crate) fn decode_metadata(encoded: Option<&str>) -> MessageMetadata {
+    encoded
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+/// Encode a message's earlier versions for a BLOB column (CBOR, NULL when never edited)
+fn encode_edit_history(history: &[MessageEdit]) -> Result<Option<Vec<u8>>> {
+    if history.is_empty() {
+        return Ok(None);
+    }
+    serde_cbor::to_vec(&history)
+        .map(Some)
+        .map_err(|e| Error::CborSerialization(format!("Failed to serialize edit history: {}", e)))
+}
+
+/// Decode a message's earlier versions; unreadable history is dropped
+fn decode_edit_history(encoded: Option<&[u8]>) -> Vec<MessageEdit> {
+    encoded
+        .and_then(|cbor| serde_cbor::from_slice(cbor).ok())
+        .unwrap_or_default()
+}
+
+/// Hash of everything `save_message` stores for `message` in `chat_uid`
+fn message_fingerprint(message: &Message, chat_uid: &str) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    chat_uid.hash(&mut hasher);
+    message.id.hash(&mut hasher);
+    message.sender.hash(&mut hasher);
+    message.recipient.hash(&mut hasher);
+    message.content.hash(&mut hasher);
+    message.timestamp.hash(&mut hasher);
+    encode_metadata(&message.metadata)?.hash(&mut hasher);
+    message.pinned.hash(&mut hasher);
+    message.starred.hash(&mut hasher);
+    message.relayed_via.hash(&mut hasher);
+    message.remote_id.hash(&mut hasher);
+    encode_edit_history(&message.edit_history)?.hash(&mut hasher);
+    message.protection.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// Build a `MessageSummary` from `message_count, latest_id, latest_timestamp,
+/// latest_sender, latest_preview` starting at column `first` (None if NULL)
+fn summary_from_row(row: &rusqlite::Row<'_>, first: usize) -> rusqlite::Result<Option<MessageSummary>> {
+    let Some(message_count) = row.get::<_, Option<i64>>(first)? else {
+        return Ok(None);
+    };
+    Ok(Some(MessageSummary {
+        message_count: message_count as u64,
+        latest_id: row.get(first + 1)?,
+        latest_timestamp: row.get(first + 2)?,
+        latest_sender: row.get(first + 3)?,
+        latest_preview: row.get(first + 4)?,
+    }))
+}
+
+/// Build a `Message` from a row of `id, sender, receiver, content, timestamp, metadata, pinned, starred, relayed_via, remote_id, edit_history, protection`
+fn message_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Message> {
+    let metadata: Option<String> = row.get(5)?;
+    let relayed_via: Option<String> = row.get(8)?;
+    let edit_history: Option<Vec<u8>> = row.get(10)?;
+    Ok(Message {
+        id: row.get(0)?,
+        sender: row.get(1)?,
+        recipient: row.get(2)?,
+        content: row.get::<_, Vec<u8>>(3)?.into(),
+        timestamp: row.get(4)?,
+        // Only relayed deliveries are recorded; other statuses are not persisted
+        delivered: relayed_via.is_some(),
+        delivery_status: if relayed_via.is_some() {
+            crate::storage::message::DeliveryStatus::Delivered
+        } else {
+            crate::storage::message::DeliveryStatus::Sent
+        },
+        next_retry_at: None,
+        attempts: 0,
+        metadata: decode_metadata(metadata.as_deref()),
+        pinned: row.get::<_, i32>(6)? != 0,
+        starred: row.get::<_, i32>(7)? != 0,
+        relayed_via,
+        remote_id: row.get(9)?,
+        edit_history: decode_edit_history(edit_history.as_deref()),
+        protection: row.get::<_, Option<String>>(11)?.as_deref().and_then(Protection::from_name),
+    })
+}
+
+/// Columns read by `journal_record_from_row`
+const JOURNAL_SELECT: &str =
+    "SELECT seq, at, kind, recipient_uid, content_sha256, ack_signature, prev_hash, hash FROM journal";
+
+/// Build a `JournalRecord` from a row of `JOURNAL_SELECT`
+///
+/// An unknown kind reads as delivered; its hash then no longer matches.
+fn journal_record_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<JournalRecord> {
+    Ok(JournalRecord {
+        seq: row.get::<_, i64>(0)? as u64,
+        at: row.get(1)?,
+        kind: JournalKind::from_name(&row.get::<_, String>(2)?).unwrap_or(JournalKind::Delivered),
+        recipient_uid: row.get(3)?,
+        content_sha256: row.get(4)?,
+        ack_signature: row.get(5)?,
+        prev_hash: row.get(6)?,
+        hash: row.get(7)?,
+    })
+}
+
+/// Encode a contact's advertised endpoints for a TEXT column (NULL when empty)
+fn encode_endpoints(endpoints: &[ContactEndpoint]) -> Result<Option<String>> {
+    if endpoints.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(endpoints)?))
+}
+
+/// Decode a contact's advertised endpoints from a TEXT column
+///
+/// Unreadable endpoint lists are dropped; the contact falls back to its `ip`.
+fn decode_endpoints(encoded: Option<&str>) -> Vec<ContactEndpoint> {
+    encoded
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+/// Encode the UID hashes a relay contact reaches for a TEXT column (NULL when empty)
+fn encode_relay_reachable(hashes: &[String]) -> Result<Option<String>> {
+    if hashes.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(hashes)?))
+}
+
+/// Decode the UID hashes a relay contact reaches from a TEXT column
+fn decode_relay_reachable(encoded: Option<&str>) -> Vec<String> {
+    encoded
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+/// Encode a contact's privacy overrides for a TEXT column (NULL when all default)
+fn encode_privacy(privacy: &ContactPrivacy) -> Result<Option<String>> {
+    if privacy.is_default() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(privacy)?))
+}
+
+/// Decode a contact's privacy overrides from a TEXT column
+fn decode_privacy(encoded: Option<&str>) -> ContactPrivacy {
+    encoded
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+/// Encode a temporary contact's marker for a TEXT column (NULL for permanent contacts)
+fn encode_ephemeral(ephemeral: Option<&Ephemeral>) -> Result<Option<String>> {
+    let Some(ephemeral) = ephemeral else {
+        return Ok(None);
+    };
+    Ok(Some(serde_json::to_string(ephemeral)?))
+}
+
+/// Encode a contact's capability record for a TEXT column (NULL if never learned)
+fn encode_capabilities(record: Option<&CapabilityRecord>) -> Result<Option<String>> {
+    let Some(record) = record else {
+        return Ok(None);
+    };
+    Ok(Some(serde_json::to_string(record)?))
+}
+
+/// Encode a contact's offset samples for a TEXT column (NULL if none)
+fn encode_offset_samples(samples: &[OffsetSample]) -> Result<Option<String>> {
+    if samples.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(samples)?))
+}
+
 impl Default for Storage {
     fn default() -> Self {
         Self::new_with_default_path().expect("Failed to create default storage")
@@ -558,10 +2428,15 @@ impl Default for Storage {
 impl Clone for Storage {
     fn clone(&self) -> Self {
         // Create a new connection to the same database
-        match &self.path {
-            Some(path) => Self::new(path).expect("Failed to clone storage connection"),
-            None => Self::new_in_memory().expect("Failed to clone in-memory storage"),
-        }
+        let mut storage = match (&self.path, &self.read_only) {
+            (Some(path), Some(writes)) => Self::open_read_only(path, writes).expect("Failed to clone read-only storage"),
+            (Some(path), None) => Self::new(path).expect("Failed to clone storage connection"),
+            (None, _) => Self::new_in_memory().expect("Failed to clone in-memory storage"),
+        };
+        storage
+            .set_busy_policy(self.busy_policy)
+            .expect("Failed to apply busy policy to cloned storage");
+        storage
     }
 }
 
@@ -0,0 +1,43 @@
+//! Message templates (canned responses)
+//!
+//! Templates are part of `Settings`, so they are saved and exported with the
+//! rest of the profile configuration. They are only ever inserted into the
+//! chat input; sending stays a separate, explicit step.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of stored templates
+pub const MAX_TEMPLATES: usize = 50;
+
+/// Placeholder replaced with the contact's display name
+pub const NAME_PLACEHOLDER: &str = "{name}";
+
+/// Placeholder replaced with the current local date (YYYY-MM-DD)
+pub const DATE_PLACEHOLDER: &str = "{date}";
+
+/// A named message body that can be inserted into the chat input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageTemplate {
+    /// Name shown in the picker
+    pub name: String,
+    /// Text inserted into the input (may span several lines)
+    pub body: String,
+}
+
+impl MessageTemplate {
+    /// Create a template
+    pub fn new(name: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            body: body.into(),
+        }
+    }
+
+    /// Body with `{name}` and `{date}` substituted
+    pub fn render(&self, contact_name: &str, date: NaiveDate) -> String {
+        self.body
+            .replace(NAME_PLACEHOLDER, contact_name)
+            .replace(DATE_PLACEHOLDER, &date.format("%Y-%m-%d").to_string())
+    }
+}
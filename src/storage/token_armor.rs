@@ -0,0 +1,283 @@
+//! Armored contact tokens for reading aloud or copying by hand
+//!
+//! A base64 token fails as a whole on one mistyped character. The armored
+//! form re-encodes the token compactly (see `contact::compact_token`) in
+//! Crockford base32, which has no 0/O or 1/I/L pairs to confuse, and lays it
+//! out in short lines between a header and a footer:
+//!
+//! ```text
+//! -----PURE2P CONTACT-----
+//! 0R8ZK 3M7QA ... 4 groups of 5 characters, then a check character
+//! ...
+//! SUM 9XKD2
+//! -----END PURE2P CONTACT-----
+//! ```
+//!
+//! Each line's check character covers its characters and its position, so a
+//! wrong, swapped or misplaced line is reported by number ("line 3 checksum
+//! mismatch"); data lines count from 1 after the header. The `SUM` line
+//! covers the whole token and catches a missing line. Decoding ignores case
+//! and spacing and reads O as 0 and I or L as 1.
+
+use super::contact::{compact_token, expand_compact_token};
+use crate::{Error, Result};
+use ring::digest::{digest, SHA256};
+
+/// First line of an armored token
+pub const ARMOR_HEADER: &str = "-----PURE2P CONTACT-----";
+
+/// Last line of an armored token
+pub const ARMOR_FOOTER: &str = "-----END PURE2P CONTACT-----";
+
+/// Characters of the armored data (Crockford base32: no I, L, O or U)
+pub const ARMOR_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Characters per group
+pub const ARMOR_GROUP_CHARS: usize = 5;
+
+/// Groups per line
+pub const ARMOR_LINE_GROUPS: usize = 4;
+
+/// Data characters per full line
+const LINE_CHARS: usize = ARMOR_GROUP_CHARS * ARMOR_LINE_GROUPS;
+
+/// Extra check characters (checks are mod 37, a prime, so any single
+/// substitution or adjacent swap changes the check)
+const CHECK_SYMBOLS: &[u8; 5] = b"*~$=U";
+
+/// Label of the overall checksum line
+const SUM_LABEL: &str = "SUM";
+
+/// Whether `input` is an armored token rather than a base64 one
+///
+/// Only the header is looked at, ignoring case and surrounding whitespace.
+pub fn is_armored(input: &str) -> bool {
+    input.lines().map(str::trim).find(|line| !line.is_empty()).is_some_and(|line| marker_is(line, ARMOR_HEADER))
+}
+
+/// Whether armored input has reached its footer line
+pub fn has_armor_footer(input: &str) -> bool {
+    input.lines().any(|line| marker_is(line, ARMOR_FOOTER))
+}
+
+/// Armor a base64 contact token
+///
+/// # Errors
+/// Returns an error if the token does not decode
+pub fn armor_token(token: &str) -> Result<String> {
+    Ok(armor_bytes(&compact_token(token.trim())?))
+}
+
+/// Recover the base64 contact token from its armored form
+///
+/// The token is not verified; pass it to `parse_contact_token` as usual.
+///
+/// # Errors
+/// Returns `Error::Storage` naming the line to re-check, or if the text is
+/// not a complete armored token
+pub fn dearmor_token(text: &str) -> Result<String> {
+    expand_compact_token(&dearmor_bytes(text)?)
+}
+
+/// The base64 token in pasted or typed input, armored or not
+///
+/// # Errors
+/// Returns the `dearmor_token` error for broken armored input
+pub fn token_from_input(input: &str) -> Result<String> {
+    if is_armored(input) {
+        dearmor_token(input)
+    } else {
+        Ok(input.trim().to_string())
+    }
+}
+
+/// Armor arbitrary bytes (the layout of `armor_token`)
+pub fn armor_bytes(bytes: &[u8]) -> String {
+    let digits = to_base32(bytes);
+    let mut lines = vec![ARMOR_HEADER.to_string()];
+    for (index, chunk) in digits.chunks(LINE_CHARS).enumerate() {
+        let groups: Vec<String> = chunk.chunks(ARMOR_GROUP_CHARS).map(digits_to_string).collect();
+        let check = check_symbol(line_check(index + 1, chunk));
+        lines.push(format!("{} {}", groups.join(" "), check));
+    }
+    lines.push(format!("{} {}", SUM_LABEL, digits_to_string(&overall_check(bytes))));
+    lines.push(ARMOR_FOOTER.to_string());
+    lines.join("\n")
+}
+
+/// Decode armored text back into bytes, checking every line
+///
+/// # Errors
+/// Returns `Error::Storage` with the data line (counted from 1 after the
+/// header) that fails its check, or if the header, `SUM` line or footer is
+/// missing or the overall checksum does not match
+pub fn dearmor_bytes(text: &str) -> Result<Vec<u8>> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    if !lines.next().is_some_and(|line| marker_is(line, ARMOR_HEADER)) {
+        return Err(Error::Storage(format!("Armored token must start with {}", ARMOR_HEADER)));
+    }
+
+    let mut digits = Vec::new();
+    let mut sum = None;
+    let mut footer = false;
+    let mut short_line = None;
+    for (index, line) in lines.enumerate() {
+        let number = index + 1;
+        if marker_is(line, ARMOR_FOOTER) {
+            footer = true;
+            break;
+        }
+        if sum.is_some() {
+            return Err(Error::Storage(format!("Unexpected line after the {} line: {}", SUM_LABEL, line)));
+        }
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if words[0].eq_ignore_ascii_case(SUM_LABEL) {
+            sum = Some(parse_digits(&words[1..].concat(), SUM_LABEL)?);
+            continue;
+        }
+        if let Some(short) = short_line {
+            return Err(Error::Storage(format!(
+                "line {} is short — a character may be missing, please re-check that line",
+                short
+            )));
+        }
+
+        let check = words.pop().filter(|word| word.chars().count() == 1 && !words.is_empty());
+        let Some(check) = check else {
+            return Err(Error::Storage(format!(
+                "line {} has no check character — please re-check that line",
+                number
+            )));
+        };
+        let data = parse_digits(&words.concat(), &format!("line {}", number))?;
+        if data.len() > LINE_CHARS {
+            return Err(Error::Storage(format!(
+                "line {} has {} characters, expected {} — please re-check that line",
+                number,
+                data.len(),
+                LINE_CHARS
+            )));
+        }
+        if parse_check(check) != Some(line_check(number, &data)) {
+            return Err(Error::Storage(format!("line {} checksum mismatch — please re-check that line", number)));
+        }
+        if data.len() < LINE_CHARS {
+            short_line = Some(number);
+        }
+        digits.extend(data);
+    }
+
+    if !footer {
+        return Err(Error::Storage(format!("Armored token must end with {}", ARMOR_FOOTER)));
+    }
+    let Some(sum) = sum else {
+        return Err(Error::Storage(format!("Armored token has no {} line", SUM_LABEL)));
+    };
+    let bytes = from_base32(&digits).ok_or_else(|| {
+        Error::Storage("Armored token has the wrong number of characters — a line may be missing".to_string())
+    })?;
+    if sum != overall_check(&bytes) {
+        return Err(Error::Storage(format!(
+            "overall checksum mismatch — please re-check the {} line and that no line is missing",
+            SUM_LABEL
+        )));
+    }
+    Ok(bytes)
+}
+
+/// Whether `line` is the header or footer `marker`, ignoring case and spacing
+fn marker_is(line: &str, marker: &str) -> bool {
+    let core = |text: &str| text.trim_matches(|c: char| c == '-' || c.is_whitespace()).to_ascii_uppercase();
+    core(line) == core(marker)
+}
+
+/// Values of armored characters; `what` names the line in errors
+fn parse_digits(text: &str, what: &str) -> Result<Vec<u8>> {
+    text.chars()
+        .map(|c| {
+            digit_value(c).ok_or_else(|| {
+                Error::Storage(format!("{}: '{}' is not a valid character — please re-check that line", what, c))
+            })
+        })
+        .collect()
+}
+
+/// Value of one data character, reading O as 0 and I or L as 1
+fn digit_value(c: char) -> Option<u8> {
+    let c = match c.to_ascii_uppercase() {
+        'O' => '0',
+        'I' | 'L' => '1',
+        c => c,
+    };
+    ARMOR_ALPHABET.iter().position(|&a| a as char == c).map(|v| v as u8)
+}
+
+/// Value of a check character (data characters, then `CHECK_SYMBOLS`)
+fn parse_check(check: &str) -> Option<u8> {
+    let c = check.chars().next()?;
+    digit_value(c).or_else(|| {
+        CHECK_SYMBOLS
+            .iter()
+            .position(|&s| s as char == c.to_ascii_uppercase())
+            .map(|v| (ARMOR_ALPHABET.len() + v) as u8)
+    })
+}
+
+fn check_symbol(value: u8) -> char {
+    ARMOR_ALPHABET.iter().chain(CHECK_SYMBOLS).nth(value as usize).map(|&c| c as char).unwrap_or('?')
+}
+
+/// Weighted sum of a line's values plus its number, mod 37
+fn line_check(number: usize, digits: &[u8]) -> u8 {
+    let sum = digits.iter().enumerate().fold(number, |sum, (i, &d)| sum + (i + 1) * d as usize);
+    (sum % 37) as u8
+}
+
+/// First 25 bits of the SHA-256 of the bytes, as five values
+fn overall_check(bytes: &[u8]) -> Vec<u8> {
+    let mut check = to_base32(&digest(&SHA256, bytes).as_ref()[..4]);
+    check.truncate(5);
+    check
+}
+
+fn digits_to_string(digits: &[u8]) -> String {
+    digits.iter().map(|&d| ARMOR_ALPHABET[d as usize] as char).collect()
+}
+
+/// Bytes to 5-bit values, most significant bits first, zero-padded
+fn to_base32(bytes: &[u8]) -> Vec<u8> {
+    let mut digits = Vec::with_capacity(bytes.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            digits.push(((buffer >> bits) & 31) as u8);
+        }
+    }
+    if bits > 0 {
+        digits.push(((buffer << (5 - bits)) & 31) as u8);
+    }
+    digits
+}
+
+/// Inverse of `to_base32`; None if the count or padding cannot come from it
+fn from_base32(digits: &[u8]) -> Option<Vec<u8>> {
+    let len = digits.len() * 5 / 8;
+    if (len * 8).div_ceil(5) != digits.len() {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(len);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &digit in digits {
+        buffer = (buffer << 5) | digit as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    // Padding bits must be zero
+    (buffer & ((1 << bits) - 1) == 0).then_some(bytes)
+}
@@ -0,0 +1,172 @@
+//! Per-contact trust tier and what is sent to each contact
+//!
+//! Contacts are `Normal` unless marked `Restricted`, at import or later in
+//! the contact details popup. A restricted contact gets a minimized profile:
+//!
+//! - tokens sent to it (introduction pings, key upgrade answers) never carry
+//!   a LAN address; when that is the only address we know, no token is sent
+//! - capability messages advertise nothing, not even edit support
+//! - read receipts, typing and presence are off whatever the settings and
+//!   per-contact overrides say
+//! - no introductions through it: we neither relay for it, route through it
+//!   nor list it to other contacts (as for temporary contacts)
+//!
+//! Pings carry only the token, with no version header, in either tier.
+//! Send paths ask `OutboundPolicy` instead of checking the tier themselves.
+
+use super::{
+    contact::{pinned_endpoints, split_address_scheme, Contact, ContactEndpoint},
+    privacy::{ContactPrivacy, PrivacySignal},
+    settings::Settings,
+};
+use crate::{connectivity::is_private_ip, crypto::KeyPair, Result};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+
+/// How far a contact is trusted with our details (local only, never sent to peers)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrustTier {
+    /// Full profile
+    #[default]
+    Normal,
+    /// Minimized profile (see the module docs)
+    Restricted,
+}
+
+impl TrustTier {
+    /// Stored form ("normal" / "restricted")
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TrustTier::Normal => "normal",
+            TrustTier::Restricted => "restricted",
+        }
+    }
+
+    /// Parse the stored form (anything unknown is `Normal`)
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "restricted" => TrustTier::Restricted,
+            _ => TrustTier::Normal,
+        }
+    }
+
+    /// The other tier
+    pub fn toggled(self) -> Self {
+        match self {
+            TrustTier::Normal => TrustTier::Restricted,
+            TrustTier::Restricted => TrustTier::Normal,
+        }
+    }
+
+    /// Whether this is the default tier
+    pub fn is_normal(&self) -> bool {
+        *self == TrustTier::Normal
+    }
+}
+
+/// Whether `address` is a private, loopback or link-local IP (with or without port)
+///
+/// Addresses that are not IPs (host names, other transports) never are.
+pub fn is_lan_address(address: &str) -> bool {
+    let (_, rest) = split_address_scheme(address);
+    let ip = rest
+        .parse::<SocketAddr>()
+        .map(|socket| socket.ip())
+        .or_else(|_| rest.parse::<IpAddr>());
+    ip.is_ok_and(is_private_ip)
+}
+
+/// What may be sent to one contact
+///
+/// Resolved from the contact's trust tier, signal overrides and temporary
+/// marker; every send path that builds something for a contact asks it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutboundPolicy {
+    tier: TrustTier,
+    privacy: ContactPrivacy,
+    temporary: bool,
+}
+
+impl OutboundPolicy {
+    /// Policy for `contact`
+    pub fn for_contact(contact: &Contact) -> Self {
+        Self {
+            tier: contact.trust,
+            privacy: contact.privacy,
+            temporary: contact.is_ephemeral(),
+        }
+    }
+
+    /// Whether the contact gets the minimized profile
+    pub fn is_minimized(&self) -> bool {
+        self.tier == TrustTier::Restricted
+    }
+
+    /// Whether `signal` may be sent, given the global settings
+    ///
+    /// Always false when minimized; otherwise the contact's override wins
+    /// and `Default` falls back to the global setting.
+    pub fn allows_signal(&self, settings: &Settings, signal: PrivacySignal) -> bool {
+        !self.is_minimized() && self.privacy.get(signal).resolve(settings.sends(signal))
+    }
+
+    /// Address to put in a token for this contact, if any
+    ///
+    /// `None` when minimized and `address` is a LAN address.
+    pub fn token_address<'a>(&self, address: &'a str) -> Option<&'a str> {
+        (!self.is_minimized() || !is_lan_address(address)).then_some(address)
+    }
+
+    /// Whether optional capabilities (edit support, relay offers) are advertised
+    pub fn advertises_capabilities(&self) -> bool {
+        !self.is_minimized()
+    }
+
+    /// Whether the contact takes part in introductions: relaying for it,
+    /// routing through it, or listing it to others
+    pub fn allows_introductions(&self) -> bool {
+        !self.is_minimized() && !self.temporary
+    }
+}
+
+/// What our token is made from, to notice when it changes (see `Storage::own_token_changed_at`)
+pub fn own_token_inputs(address: &str, cert_fingerprint: Option<&str>, validity: Duration) -> String {
+    format!("{}|{}|{}", address, cert_fingerprint.unwrap_or(""), validity.num_seconds())
+}
+
+/// Our signed contact token at `address`, as sent to `recipient`
+///
+/// The token is valid for `validity` (`Settings::own_token_validity()`). With
+/// `cert_fingerprint` (our TLS certificate), the token pins it and
+/// advertises `address` over TLS first (see `pinned_endpoints`).
+///
+/// # Returns
+/// `None` when `recipient`'s `OutboundPolicy` withholds `address`
+///
+/// # Errors
+/// Returns an error if signing fails
+pub fn own_token_for(
+    keypair: &KeyPair,
+    address: &str,
+    cert_fingerprint: Option<&str>,
+    validity: Duration,
+    recipient: &Contact,
+) -> Result<Option<String>> {
+    let Some(address) = OutboundPolicy::for_contact(recipient).token_address(address) else {
+        return Ok(None);
+    };
+    let mut contact = Contact::new(
+        keypair.uid.to_string(),
+        address.to_string(),
+        keypair.public_key.clone(),
+        keypair.x25519_public.clone(),
+        Utc::now() + validity,
+    );
+    if let Some(fingerprint) = cert_fingerprint {
+        contact.endpoints = pinned_endpoints(&[ContactEndpoint::new(address, "detected")]);
+        contact.cert_fingerprint = Some(fingerprint.to_string());
+    }
+    contact.sign_token(keypair).map(Some)
+}
@@ -0,0 +1,77 @@
+//! Position index over the contact and chat lists
+//!
+//! `AppState` keeps one `UidIndex` per list so lookups by UID do not scan.
+//! The lists stay plain `Vec`s: the `AppState` mutation API keeps the index
+//! current, and code that edits a list directly calls `AppState::reindex()`
+//! afterwards. A lookup never trusts a stale index: a hit is checked against
+//! the list, and anything else (a miss, a position now holding another UID,
+//! a list whose length no longer matches) falls back to a scan. So only a
+//! found entry is fast; looking up an unknown UID costs a scan.
+
+use std::collections::HashMap;
+
+/// Map of UID to position in a list
+#[derive(Debug, Clone, Default)]
+pub struct UidIndex {
+    positions: HashMap<String, usize>,
+    /// Length of the list when indexed (duplicate UIDs make this more than `positions.len()`)
+    len: usize,
+}
+
+impl UidIndex {
+    /// Index `items` by `key`
+    pub fn build<T>(items: &[T], key: impl Fn(&T) -> &str) -> Self {
+        let mut positions = HashMap::with_capacity(items.len());
+        for (i, item) in items.iter().enumerate() {
+            // The first entry wins, like a scan would find it
+            positions.entry(key(item).to_string()).or_insert(i);
+        }
+        Self { positions, len: items.len() }
+    }
+
+    /// Record that `uid` was appended at `position`
+    pub fn insert(&mut self, uid: &str, position: usize) {
+        self.positions.entry(uid.to_string()).or_insert(position);
+        self.len = position + 1;
+    }
+
+    /// Whether the index covers every entry of a list of `len` items
+    pub fn matches_len(&self, len: usize) -> bool {
+        self.len == len
+    }
+
+    /// Rebuild the index if `items` grew or shrank behind its back
+    pub fn rebuild_if_resized<T>(&mut self, items: &[T], key: impl Fn(&T) -> &str) {
+        if !self.matches_len(items.len()) {
+            *self = Self::build(items, key);
+        }
+    }
+
+    /// Position of `uid` in `items` according to the index, checked against the list
+    fn hit<T>(&self, items: &[T], uid: &str, key: impl Fn(&T) -> &str) -> Option<usize> {
+        let i = *self.positions.get(uid)?;
+        (self.matches_len(items.len()) && items.get(i).is_some_and(|item| key(item) == uid)).then_some(i)
+    }
+
+    /// Position of `uid` in `items`
+    ///
+    /// Uses the index when its entry checks out; otherwise (the list was
+    /// edited directly, or `uid` is not in it) scans.
+    pub fn find<T>(&self, items: &[T], uid: &str, key: impl Fn(&T) -> &str) -> Option<usize> {
+        self.hit(items, uid, &key).or_else(|| items.iter().position(|item| key(item) == uid))
+    }
+
+    /// Like `find`, but rebuilds the index when the scan finds what it missed
+    pub fn find_or_rebuild<T>(&mut self, items: &[T], uid: &str, key: impl Fn(&T) -> &str) -> Option<usize> {
+        if let Some(i) = self.hit(items, uid, &key) {
+            return Some(i);
+        }
+        let found = items.iter().position(|item| key(item) == uid);
+        if found.is_some() {
+            *self = Self::build(items, &key);
+        } else {
+            self.rebuild_if_resized(items, &key);
+        }
+        found
+    }
+}
@@ -0,0 +1,167 @@
+// Address tests - adversarial address matrix rejected at import and at send, permanent failure classification, well-formed IPv4/IPv6/hostname endpoints still parsed and dialled
+
+use crate::crypto::KeyPair;
+use crate::protocol::MessageEnvelope;
+use crate::queue::FailureKind;
+use crate::storage::{
+    generate_contact_token, generate_multi_endpoint_token, Chat, Contact, ContactEndpoint, PeerAddress, PeerHost,
+    INVALID_ADDRESS_STATUS,
+};
+use crate::transport::Transport;
+use crate::tui::{App, ChatViewScreen, ImportContactScreen};
+use crate::Error;
+use chrono::{Duration, Utc};
+use tempfile::TempDir;
+
+/// Addresses that must never be dialled
+const MALFORMED: &[&str] = &[
+    "",
+    "evil.com/steal?x=",
+    "127.0.0.1:80#@real.com",
+    "127.0.0.1:80/message",
+    "127.0.0.1:80?q=1",
+    "user:pass@10.0.0.1:80",
+    "@10.0.0.1:80",
+    "10.0.0.1 :80",
+    "10.0.0.1:80\r\nHost: evil.com",
+    "10.0.0.1:80\t",
+    "10.0.0.1:\u{0}80",
+    "exämple.com:80",
+    "10.0.0.1",
+    "10.0.0.1:",
+    "10.0.0.1:0",
+    "10.0.0.1:65536",
+    "10.0.0.1:+80",
+    "10.0.0.1:80:80",
+    "::1:8080",
+    "[::1:8080",
+    "[not-ipv6]:8080",
+    "[::1]8080",
+    "[fe80::1%eth0]:8080",
+    "999.1.1.1:80",
+    "1.2.3:80",
+    "-bad.example.com:80",
+    "bad-.example.com:80",
+    "a..b:80",
+    "under_score.example:80",
+    "http://evil.com:80/steal",
+    "10.0.0.1:80\\..\\x",
+];
+
+fn keypair_token(ip: &str, keypair: &KeyPair) -> String {
+    generate_contact_token(ip, &keypair.public_key, &keypair.private_key, &keypair.x25519_public, Utc::now() + Duration::days(30))
+        .unwrap()
+}
+
+fn contact(ip: &str) -> Contact {
+    Contact::new("bob_uid".to_string(), ip.to_string(), vec![1, 2, 3], vec![7u8; 32], Utc::now() + Duration::days(30))
+}
+
+#[test]
+fn test_malformed_addresses_are_rejected_at_parse_and_import() {
+    let keypair = KeyPair::generate().unwrap();
+    for address in MALFORMED {
+        assert!(matches!(PeerAddress::parse(address), Err(Error::InvalidAddress(_))), "{:?}", address);
+
+        // The signature is fine; the address is not
+        let token = keypair_token(address, &keypair);
+        let mut screen = ImportContactScreen::new();
+        screen.input = token;
+        screen.parse_token();
+        assert!(screen.parsed_contact.is_none(), "{:?}", address);
+        assert!(screen.is_error);
+        assert!(screen.status_message.as_ref().unwrap().contains("Invalid address"), "{:?}", screen.status_message);
+    }
+
+    // A bad advertised endpoint behind a good primary address is caught too
+    let endpoints = [ContactEndpoint::new("10.0.0.1:8080", "lan"), ContactEndpoint::new("https://evil.com/x", "tls")];
+    let token = generate_multi_endpoint_token(
+        &endpoints,
+        &keypair.public_key,
+        &keypair.private_key,
+        &keypair.x25519_public,
+        Utc::now() + Duration::days(30),
+    )
+    .unwrap();
+    assert!(matches!(crate::storage::parse_contact_token(&token), Err(Error::InvalidAddress(_))));
+
+    // Addresses of other transports are theirs to check
+    assert!(crate::storage::parse_contact_token(&keypair_token("loopback://bob", &keypair)).is_ok());
+}
+
+#[test]
+fn test_well_formed_addresses_parse_into_typed_parts() {
+    let ipv4 = PeerAddress::parse("192.168.1.100:8080").unwrap();
+    assert_eq!(ipv4.host, PeerHost::Ipv4("192.168.1.100".parse().unwrap()));
+    assert_eq!(ipv4.port, 8080);
+    assert_eq!(ipv4.uri("http", "/message").unwrap().to_string(), "http://192.168.1.100:8080/message");
+
+    let ipv6 = PeerAddress::parse("[2001:db8::1]:443").unwrap();
+    assert_eq!(ipv6.host, PeerHost::Ipv6("2001:db8::1".parse().unwrap()));
+    assert_eq!(ipv6.uri("https", "/ping").unwrap().to_string(), "https://[2001:db8::1]:443/ping");
+
+    let host = PeerAddress::parse("peer-1.example.com:65535").unwrap();
+    assert_eq!(host.host, PeerHost::Domain("peer-1.example.com".to_string()));
+    assert_eq!(host.to_string(), "peer-1.example.com:65535");
+    assert!(PeerAddress::parse("localhost:1").is_ok());
+
+    // URLs are only built for the HTTP transport, and only for absolute paths
+    assert!(ipv4.uri("loopback", "/message").is_err());
+    assert!(ipv4.uri("http", "@evil.com/").is_err());
+
+    let keypair = KeyPair::generate().unwrap();
+    for address in ["10.0.0.1:80", "[::1]:8080", "https://[::1]:8443", "peer.example.org:9000"] {
+        assert!(crate::storage::parse_contact_token(&keypair_token(address, &keypair)).is_ok(), "{}", address);
+    }
+}
+
+#[tokio::test]
+async fn test_send_refuses_invalid_addresses_as_permanent_failures() {
+    let transport = Transport::new();
+    let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    for address in ["evil.com/steal?x=", "127.0.0.1:80#@real.com", "10.0.0.1:80\r\nX: y"] {
+        let error = transport.send_message(&contact(address), "alice_uid", "text", b"hi".to_vec()).await.unwrap_err();
+        assert!(error.to_string().contains("Invalid address"), "{}", error);
+        assert_eq!(FailureKind::classify(Some(&error.to_string())), FailureKind::Rejected);
+
+        let error = transport.send_ping(&contact(address), "").await.unwrap_err();
+        assert_eq!(FailureKind::classify(Some(&error.to_string())), FailureKind::Rejected);
+
+        let envelope = MessageEnvelope::new_text(&alice.uid, &bob.uid, b"hi".to_vec());
+        assert!(matches!(transport.send(address, &envelope).await, Err(Error::InvalidAddress(_))));
+    }
+
+    // Unreachable is still a connectivity failure
+    assert_eq!(FailureKind::classify(Some("Transport error: Message send failed: client error (Connect)")), FailureKind::Connectivity);
+}
+
+#[tokio::test]
+async fn test_hostname_and_ip_endpoints_still_deliver() {
+    let mut receiver = Transport::new();
+    receiver.set_new_message_handler(|_| Ok(())).await;
+    receiver.start("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let port = receiver.local_addr().unwrap().port();
+
+    let sender = Transport::new();
+    for address in [format!("127.0.0.1:{}", port), format!("localhost:{}", port)] {
+        sender.send_message(&contact(&address), "alice_uid", "text", b"hi".to_vec()).await.unwrap();
+    }
+}
+
+#[test]
+fn test_chat_view_does_not_send_to_an_invalid_address() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.contacts.push(contact("evil.com/steal?x="));
+    app.app_state.chats.push(Chat::new("bob_uid".to_string()));
+
+    let mut screen = ChatViewScreen::new("bob_uid".to_string());
+    screen.input = "hello".to_string();
+    app.chat_view_screen = Some(screen);
+    app.send_message_in_chat();
+
+    let screen = app.chat_view_screen.as_ref().unwrap();
+    assert_eq!(screen.status_message.as_deref(), Some(format!("Not sent: {}", INVALID_ADDRESS_STATUS).as_str()));
+    assert_eq!(screen.input, "hello");
+    assert!(app.app_state.chat_by_uid("bob_uid").unwrap().messages.is_empty());
+}
@@ -0,0 +1,204 @@
+// Auto-import tests - hourly caps on contacts and chats created by incoming pings, aggregated audit entry, sliding window, temporary lift
+
+use crate::auto_import::*;
+use crate::crypto::KeyPair;
+use crate::storage::{generate_contact_token, AppState, ContactIngest};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, PeerTransport};
+use crate::tui::App;
+use crate::Error;
+use chrono::{Duration, TimeZone, Utc};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use super::helpers::storage_with_identity;
+
+/// Token of a fresh sender at `address`
+fn sender_token(sender: &KeyPair, address: &str) -> String {
+    generate_contact_token(
+        address,
+        &sender.public_key,
+        &sender.private_key,
+        &sender.x25519_public,
+        Utc::now() + Duration::days(1),
+    )
+    .expect("Failed to generate token")
+}
+
+#[tokio::test]
+async fn test_pings_under_and_over_cap() {
+    let storage = storage_with_identity();
+    let limiter = Mutex::new(AutoImportLimiter::new(2, 2));
+    let now = Utc::now();
+
+    // Two unknown senders fit the cap
+    let known = KeyPair::generate().unwrap();
+    let token = sender_token(&known, "10.0.0.1:8080");
+    assert!(matches!(App::apply_incoming_ping(&storage, &token, None, &limiter, now).unwrap(), ContactIngest::Added));
+    let token = sender_token(&KeyPair::generate().unwrap(), "10.0.0.2:8080");
+    assert!(matches!(App::apply_incoming_ping(&storage, &token, None, &limiter, now).unwrap(), ContactIngest::Added));
+
+    // The third is refused and nothing is stored for it
+    let token = sender_token(&KeyPair::generate().unwrap(), "10.0.0.3:8080");
+    let result = App::apply_incoming_ping(&storage, &token, None, &limiter, now);
+    assert!(matches!(result, Err(Error::RateLimited(_))));
+    let state = AppState::load_from_db(&storage).unwrap();
+    assert_eq!(state.contacts.len(), 2);
+    assert_eq!(state.chats.len(), 2);
+
+    // Known contacts are never limited
+    let token = sender_token(&known, "10.0.0.9:8080");
+    let outcome = App::apply_incoming_ping(&storage, &token, None, &limiter, now).unwrap();
+    assert!(matches!(outcome, ContactIngest::AddressUpdated));
+
+    // Over the wire the refusal is a 429
+    let storage = Arc::new(Mutex::new(storage));
+    let limiter = Arc::new(limiter);
+    let network = LoopbackNetwork::new();
+    let receiver = LoopbackTransport::new(&network);
+    receiver
+        .set_ping_handler(move |token| {
+            let storage = storage.lock().unwrap();
+            App::apply_incoming_ping(&storage, &token, None, &limiter, Utc::now()).map(|_| ())
+        })
+        .await;
+    receiver.start_listener("bob").await.unwrap();
+    let sender = LoopbackTransport::new(&network);
+    let bob = crate::storage::Contact::new(
+        "bob_uid".to_string(),
+        "loopback://bob".to_string(),
+        vec![0; 32],
+        vec![0; 32],
+        Utc::now() + Duration::days(1),
+    );
+    let token = sender_token(&KeyPair::generate().unwrap(), "loopback://alice");
+    let error = sender.send_ping(&bob, &token).await.unwrap_err().to_string();
+    assert!(error.contains("429"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_http_handlers_get_socket_address() {
+    let mut transport = crate::transport::Transport::new();
+    let sources = Arc::new(Mutex::new(Vec::new()));
+    let recorded = sources.clone();
+    transport
+        .set_ping_handler_with_source(move |_token, source| {
+            recorded.lock().unwrap().push(source);
+            Ok(())
+        })
+        .await;
+    transport.start("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let address = transport.local_addr().unwrap().to_string();
+
+    // The token advertises another host; the handler sees where the ping came from
+    let contact = crate::storage::Contact::new(
+        "bob_uid".to_string(),
+        address,
+        vec![0; 32],
+        vec![0; 32],
+        Utc::now() + Duration::days(1),
+    );
+    let token = sender_token(&KeyPair::generate().unwrap(), "203.0.113.7:8080");
+    transport.send_ping(&contact, &token).await.unwrap();
+    transport.stop();
+
+    let localhost: IpAddr = "127.0.0.1".parse().unwrap();
+    assert_eq!(*sources.lock().unwrap(), vec![Some(localhost)]);
+}
+
+#[test]
+fn test_refusals_aggregated_in_one_audit_entry() {
+    let storage = storage_with_identity();
+    let limiter = Mutex::new(AutoImportLimiter::new(1, 10));
+    let now = Utc::now();
+
+    let token = sender_token(&KeyPair::generate().unwrap(), "10.0.0.1:8080");
+    App::apply_incoming_ping(&storage, &token, None, &limiter, now).unwrap();
+
+    // 37 refusals from 3 socket addresses; the addresses the tokens advertise don't count
+    let sources: [IpAddr; 3] = ["203.0.113.7".parse().unwrap(), "203.0.113.8".parse().unwrap(), "2001:db8::1".parse().unwrap()];
+    for i in 0..37 {
+        let token = sender_token(&KeyPair::generate().unwrap(), &format!("10.1.0.{}:8080", i));
+        let result = App::apply_incoming_ping(&storage, &token, Some(sources[i % 3]), &limiter, now);
+        assert!(matches!(result, Err(Error::RateLimited(_))));
+    }
+
+    let audit: Vec<_> = storage
+        .get_request_logs(100)
+        .unwrap()
+        .into_iter()
+        .filter(|log| log.request_type == AUTO_IMPORT_AUDIT_TYPE)
+        .collect();
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0].status_code, Some(429));
+    assert_eq!(
+        audit[0].error_message.as_deref(),
+        Some("suppressed 37 auto-imports from 3 source IPs in the last hour")
+    );
+}
+
+#[test]
+fn test_sliding_window_resets() {
+    let start = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+    let mut limiter = AutoImportLimiter::new(2, 1);
+
+    limiter.admit(true, true, Some("203.0.113.7"), start).unwrap();
+    limiter.admit(true, false, Some("203.0.113.7"), start + Duration::minutes(30)).unwrap();
+    assert!(limiter.admit(true, false, None, start + Duration::minutes(59)).is_err());
+    assert!(limiter.admit(false, true, None, start + Duration::minutes(59)).is_err());
+    assert_eq!(limiter.suppression_summary(start + Duration::minutes(59)).unwrap().count, 2);
+
+    // The first creation leaves the window after an hour, the second does not yet
+    let hour_later = start + Duration::minutes(60);
+    limiter.admit(true, true, None, hour_later).unwrap();
+    assert!(limiter.admit(true, false, None, hour_later).is_err());
+
+    // Refusals leave the window too
+    let much_later = start + Duration::minutes(119);
+    assert_eq!(limiter.suppression_summary(much_later).map(|s| s.count), Some(1));
+    limiter.admit(true, false, None, much_later).unwrap();
+    assert_eq!(limiter.suppression_summary(start + Duration::minutes(120)), None);
+
+    // 0 means unlimited
+    let mut unlimited = AutoImportLimiter::new(0, 0);
+    for _ in 0..100 {
+        unlimited.admit(true, true, None, start).unwrap();
+    }
+}
+
+#[test]
+fn test_temporary_lift_from_settings() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    let now = Utc::now();
+
+    // Caps come from the settings
+    app.app_state.settings.auto_import_contacts_per_hour = 1;
+    app.sync_auto_import_caps();
+    app.auto_import_limiter.lock().unwrap().admit(true, false, None, now).unwrap();
+    assert!(app.auto_import_limiter.lock().unwrap().admit(true, false, None, now).is_err());
+
+    // Toggled on the settings screen and saved: lifted for an hour
+    app.show_settings_screen();
+    let screen = app.settings_screen.as_mut().unwrap();
+    assert!(!screen.auto_import_lifted);
+    screen.selected_field = crate::tui::SettingsScreen::FIELD_AUTO_IMPORT_LIFT;
+    screen.add_char(' ');
+    assert!(screen.auto_import_lifted);
+    app.save_settings();
+
+    let until = app.auto_import_limiter.lock().unwrap().lifted_until(Utc::now()).expect("caps lifted");
+    assert!(until > now + Duration::minutes(AUTO_IMPORT_LIFT_MINUTES - 1));
+    app.auto_import_limiter.lock().unwrap().admit(true, false, None, Utc::now()).unwrap();
+
+    // The lift runs out on its own
+    assert!(app.auto_import_limiter.lock().unwrap().admit(true, false, None, until).is_err());
+
+    // Or is undone from Settings
+    app.show_settings_screen();
+    let screen = app.settings_screen.as_mut().unwrap();
+    assert!(screen.auto_import_lifted);
+    screen.selected_field = crate::tui::SettingsScreen::FIELD_AUTO_IMPORT_LIFT;
+    screen.add_char(' ');
+    app.save_settings();
+    assert_eq!(app.auto_import_limiter.lock().unwrap().lifted_until(Utc::now()), None);
+}
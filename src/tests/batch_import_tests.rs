@@ -0,0 +1,215 @@
+// Batch import tests - mixed-validity batch parsing and classification, deselection at import, per-entry ping results, reading a token file, importing the same batch twice
+
+use crate::crypto::KeyPair;
+use crate::storage::{generate_contact_token, parse_contact_token, AppState};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, PeerTransport, TransportRegistry};
+use crate::tui::{parse_token_batch, App, BatchEntryResult, BatchEntryStatus, PingDispatch, SaveTarget};
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn token_at(keypair: &KeyPair, address: &str, expires_in: Duration) -> String {
+    generate_contact_token(
+        address,
+        &keypair.public_key,
+        &keypair.private_key,
+        &keypair.x25519_public,
+        Utc::now() + expires_in,
+    )
+    .unwrap()
+}
+
+fn token(keypair: &KeyPair) -> String {
+    token_at(keypair, "192.168.1.50:8080", Duration::days(30))
+}
+
+/// Batch-mode Import screen holding `text`
+fn app_with_batch(temp_dir: &TempDir, text: &str) -> App {
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.show_import_contact_screen();
+    app.toggle_batch_import();
+    app.import_contact_screen.as_mut().unwrap().input = text.to_string();
+    app
+}
+
+/// Wait until every imported entry's ping has a result
+fn wait_for_pings(app: &mut App) {
+    for _ in 0..50 {
+        app.poll_batch_import();
+        let batch = app.import_contact_screen.as_ref().unwrap().batch.as_ref().unwrap();
+        if batch.report.as_ref().unwrap().iter().all(|e| e.ping != Some(PingDispatch::Pending)) {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    panic!("Ping results did not arrive");
+}
+
+#[test]
+fn test_mixed_batch_classification() {
+    let me = KeyPair::generate().unwrap();
+    let (alice, bob, carol, dave) = (
+        KeyPair::generate().unwrap(),
+        KeyPair::generate().unwrap(),
+        KeyPair::generate().unwrap(),
+        KeyPair::generate().unwrap(),
+    );
+    let mut state = AppState::new();
+    state.add_contact(parse_contact_token(&token(&carol)).unwrap());
+
+    let text = format!(
+        "# team tokens\n\nAlice: {}\n{}\n\nAlice again - {}\nCarol {}\n  Dave = {}\nnot-a-token\n{}\n",
+        token(&alice),
+        token(&bob),
+        token(&alice),
+        token(&carol),
+        token_at(&dave, "10.0.0.4:8080", Duration::days(-2)),
+        token(&me),
+    );
+    let entries = parse_token_batch(&text, &state, &me.uid.to_string(), Utc::now());
+
+    let summary: Vec<(usize, Option<&str>, &str, bool)> = entries
+        .iter()
+        .map(|e| (e.line, e.name.as_deref(), e.status.label(), e.selected))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (3, Some("Alice"), "ok", true),
+            (4, None, "ok", true),
+            (6, Some("Alice again"), "duplicate", false),
+            (7, Some("Carol"), "duplicate", false),
+            (8, Some("Dave"), "expired", false),
+            (9, None, "invalid", false),
+            (10, None, "self", false),
+        ]
+    );
+    assert_eq!(entries[1].display_name(), &bob.uid.to_string()[..16]);
+    assert_eq!(entries[5].display_name(), "line 9");
+    assert!(matches!(&entries[5].status, BatchEntryStatus::Invalid(reason) if !reason.is_empty()));
+    assert!(entries[4].selectable() && !entries[5].selectable() && !entries[6].selectable());
+
+    // The screen shows the table; nothing to review is an error
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_batch(&temp_dir, "\n# nothing here\n");
+    app.review_token_batch();
+    let screen = app.import_contact_screen.as_ref().unwrap();
+    assert!(screen.is_error && !screen.batch.as_ref().unwrap().in_review());
+    app.import_contact_screen.as_mut().unwrap().input = text;
+    app.review_token_batch();
+    let screen = app.import_contact_screen.as_ref().unwrap();
+    assert_eq!(screen.batch.as_ref().unwrap().entries.len(), 7);
+    // Carol and `me` are strangers to this app
+    assert!(screen.status_message.as_deref().unwrap().starts_with("7 token(s), 4 new"));
+}
+
+#[test]
+fn test_deselected_entries_are_not_imported() {
+    let keypairs: Vec<KeyPair> = (0..3).map(|_| KeyPair::generate().unwrap()).collect();
+    let text: String = keypairs.iter().map(|k| format!("{}\n", token(k))).collect::<String>() + "garbage\n";
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_batch(&temp_dir, &text);
+    app.review_token_batch();
+
+    {
+        let batch = app.import_contact_screen.as_mut().unwrap().batch.as_mut().unwrap();
+        batch.move_selection(true);
+        assert!(batch.toggle_selected(), "Deselect the second token");
+        batch.move_selection(true);
+        batch.move_selection(true);
+        assert!(!batch.toggle_selected(), "Invalid tokens cannot be selected");
+        batch.move_selection(true);
+        assert_eq!(batch.selected_row, 3, "The highlight stops at the last row");
+        assert_eq!(batch.selected_count(), 2);
+    }
+
+    assert_eq!(app.confirm_batch_import(), 2);
+    let uids: Vec<String> = keypairs.iter().map(|k| k.uid.to_string()).collect();
+    assert!(app.app_state.contact_by_uid(&uids[0]).is_some());
+    assert!(app.app_state.contact_by_uid(&uids[1]).is_none());
+    assert!(app.app_state.contact_by_uid(&uids[2]).is_some());
+    assert!(app.app_state.chat_by_uid(&uids[2]).unwrap().has_pending_messages);
+
+    // Saved in one pass
+    let stored = AppState::load_from_db(&app.storage).unwrap();
+    assert_eq!(stored.contacts.len(), 2);
+    let report = app.import_contact_screen.as_ref().unwrap().batch.as_ref().unwrap().report.clone().unwrap();
+    assert_eq!(report.len(), 2);
+    assert!(report.iter().all(|e| e.result == BatchEntryResult::Imported));
+}
+
+#[test]
+fn test_ping_results_reported_per_entry() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let peer = LoopbackTransport::new(&network);
+    rt.block_on(peer.set_ping_handler(|_token| Ok(())));
+    rt.block_on(peer.start_listener("bob")).unwrap();
+
+    let (bob, nobody) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let text = format!(
+        "Bob: {}\nNobody: {}\n",
+        token_at(&bob, "loopback://bob", Duration::days(30)),
+        token_at(&nobody, "loopback://nobody", Duration::days(30)),
+    );
+
+    // The batch is read from a file through the path overlay
+    let temp_dir = TempDir::new().unwrap();
+    let file = temp_dir.path().join("team.txt");
+    std::fs::write(&file, text).unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(&network)));
+    app.show_import_contact_screen();
+    app.open_path_picker(SaveTarget::TokenBatch);
+    app.path_picker.as_mut().unwrap().input = temp_dir.path().join("missing.txt").display().to_string();
+    app.submit_path_picker();
+    assert!(app.path_picker.as_ref().unwrap().error.as_deref().unwrap().contains("does not exist"));
+    app.path_picker.as_mut().unwrap().input = file.display().to_string();
+    app.submit_path_picker();
+    assert!(app.path_picker.is_none());
+    assert!(app.last_saved_path.is_none(), "Reading is not saving");
+
+    assert_eq!(app.confirm_batch_import(), 2);
+    wait_for_pings(&mut app);
+    let batch = app.import_contact_screen.as_ref().unwrap().batch.as_ref().unwrap();
+    let report = batch.report.as_ref().unwrap();
+    assert_eq!(report[0].line(), "Bob: imported, ping answered");
+    assert_eq!((report[1].name.as_str(), report[1].ping.clone()), ("Nobody", Some(PingDispatch::Queued)));
+    assert_eq!(
+        batch.summary().unwrap(),
+        "2 imported, 0 skipped, 0 failed; pings: 1 answered, 1 queued, 0 pending"
+    );
+    assert_eq!(app.import_contact_screen.as_ref().unwrap().status_message, batch.summary());
+}
+
+#[test]
+fn test_same_batch_twice_is_idempotent() {
+    let keypairs: Vec<KeyPair> = (0..2).map(|_| KeyPair::generate().unwrap()).collect();
+    let text: String = keypairs.iter().map(|k| format!("{}\n", token(k))).collect();
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_batch(&temp_dir, &text);
+    app.review_token_batch();
+    assert_eq!(app.confirm_batch_import(), 2);
+
+    // Second time round every entry is a duplicate, and none is selected
+    app.import_contact_screen.as_mut().unwrap().back_to_batch_input();
+    app.review_token_batch();
+    let batch = app.import_contact_screen.as_ref().unwrap().batch.as_ref().unwrap();
+    assert!(batch.entries.iter().all(|e| e.status == BatchEntryStatus::Duplicate));
+    assert_eq!(batch.selected_count(), 0);
+    assert_eq!(app.confirm_batch_import(), 0);
+
+    // Selecting the duplicates anyway changes nothing
+    app.import_contact_screen.as_mut().unwrap().back_to_batch_input();
+    app.review_token_batch();
+    let batch = app.import_contact_screen.as_mut().unwrap().batch.as_mut().unwrap();
+    assert!(batch.toggle_selected());
+    batch.move_selection(true);
+    assert!(batch.toggle_selected());
+    assert_eq!(app.confirm_batch_import(), 0);
+    let report = app.import_contact_screen.as_ref().unwrap().batch.as_ref().unwrap().report.clone().unwrap();
+    assert!(report.iter().all(|e| e.result == BatchEntryResult::Skipped("Contact already exists".to_string())));
+    assert!(report.iter().all(|e| e.ping.is_none()));
+    assert_eq!(app.app_state.contacts.len(), 2);
+    assert_eq!(app.app_state.chats.len(), 2);
+}
@@ -0,0 +1,180 @@
+// Cache tests - LRU and TTL eviction, hit/miss/eviction counters, the registry behind Diagnostics, concurrent access, the rate limiter staying bounded under a scan
+
+use crate::cache::{cache_stats, BoundedCache, CacheStats};
+use crate::invite::{InviteRateLimiter, MAX_FAILURES_PER_WINDOW, MAX_TRACKED_SOURCES};
+use chrono::{Duration, Utc};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+
+#[test]
+fn test_least_recently_used_is_evicted() {
+    let cache = BoundedCache::with_shards("lru test", 3, None, 1);
+    let now = Utc::now();
+    cache.insert_at("a", 1, now);
+    cache.insert_at("b", 2, now);
+    cache.insert_at("c", 3, now);
+
+    // Reading "a" makes "b" the least recently used
+    assert_eq!(cache.get_at(&"a", now), Some(1));
+    assert_eq!(cache.insert_at("d", 4, now), None);
+    assert_eq!(cache.get_at(&"b", now), None);
+    assert_eq!((cache.len(), cache.capacity()), (3, 3));
+
+    // Replacing a value makes room for nothing and returns the old one
+    assert_eq!(cache.insert_at("c", 30, now), Some(3));
+    assert_eq!(cache.len(), 3);
+    cache.insert_at("e", 5, now);
+    assert_eq!(cache.get_at(&"a", now), None);
+    assert_eq!(cache.get_at(&"c", now), Some(30));
+
+    // Updating an evicted key starts over from the default
+    let count = cache.update_at("a", now, || 100, |value| {
+        *value += 1;
+        *value
+    });
+    assert_eq!(count, 101);
+
+    let stats = cache.stats();
+    assert_eq!(stats.name, "lru test");
+    assert_eq!((stats.hits, stats.misses, stats.evictions, stats.expirations), (2, 3, 3, 0));
+
+    // Removed and retained-away entries are not evictions
+    assert_eq!(cache.remove(&"c"), Some(30));
+    cache.retain(|key, _| *key != "e");
+    assert_eq!(cache.len(), 1);
+    assert_eq!(cache.stats().evictions, 3);
+}
+
+#[test]
+fn test_entries_expire_after_last_write() {
+    let ttl = Duration::seconds(60);
+    let cache = BoundedCache::with_shards("ttl test", 10, Some(ttl), 1);
+    let start = Utc::now();
+    cache.insert_at("a", 1, start);
+    cache.insert_at("b", 2, start);
+
+    // Reads do not extend the lifetime; writes do
+    assert_eq!(cache.get_at(&"a", start + Duration::seconds(59)), Some(1));
+    cache.update_at("b", start + Duration::seconds(30), || 0, |value| *value += 1);
+    assert_eq!(cache.get_at(&"a", start + ttl), None);
+    assert_eq!(cache.get_at(&"b", start + ttl), Some(3));
+    assert_eq!(cache.get_at(&"b", start + Duration::seconds(90)), None);
+
+    // An expired value is not returned as the one replaced
+    cache.insert_at("c", 1, start);
+    assert_eq!(cache.insert_at("c", 2, start + ttl), None);
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.expirations, stats.evictions), (3, 2, 2, 0));
+    assert_eq!(stats.entries, 1);
+
+    // Expired entries make room before live ones when the cache is full
+    let small = BoundedCache::with_shards("ttl room test", 2, Some(ttl), 1);
+    small.insert_at("old", 1, start);
+    small.insert_at("new", 2, start + ttl);
+    small.insert_at("newer", 3, start + ttl);
+    let stats = small.stats();
+    assert_eq!((stats.expirations, stats.evictions), (1, 0));
+    assert_eq!(small.get_at(&"new", start + ttl), Some(2));
+}
+
+#[test]
+fn test_stats_registry_and_summary() {
+    let name = format!("registry test {}", uuid::Uuid::new_v4());
+    let cache: BoundedCache<u32, u32> = BoundedCache::new(&name, 100, None);
+    cache.insert(1, 1);
+    cache.get(&1);
+    cache.get(&2);
+
+    let listed = cache_stats().into_iter().find(|stats| stats.name == name).unwrap();
+    assert_eq!(listed, cache.stats());
+    assert_eq!(listed.summary(), "1/96 entries, 50% hits, 0 evicted, 0 expired");
+    assert_eq!(CacheStats::default().summary(), "0/0 entries, no lookups, 0 evicted, 0 expired");
+
+    // Capacity rounds down to whole shards; tiny caches use fewer shards
+    assert_eq!(cache.capacity(), 96);
+    assert_eq!(BoundedCache::<u32, u32>::new("tiny test", 3, None).capacity(), 3);
+
+    let total = CacheStats::total(&[listed.clone(), listed]);
+    assert_eq!((total.name.as_str(), total.entries, total.capacity, total.hits), ("all caches", 2, 192, 2));
+
+    // Dropped caches leave the list
+    drop(cache);
+    assert!(cache_stats().iter().all(|stats| stats.name != name));
+}
+
+#[test]
+fn test_concurrent_access_from_many_threads() {
+    const THREADS: usize = 8;
+    const ROUNDS: usize = 2000;
+
+    // Shared counters: every increment lands, none is lost between threads
+    let counters = Arc::new(BoundedCache::new("hammer counters test", 1024, None));
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let counters = counters.clone();
+            std::thread::spawn(move || {
+                for i in 0..ROUNDS {
+                    counters.update_at(i % 32, Utc::now(), || 0u64, |count| *count += 1);
+                    assert!(counters.get(&(i % 32)).is_some());
+                }
+            })
+        })
+        .collect();
+    for handle in threads {
+        handle.join().unwrap();
+    }
+    let total: u64 = (0..32).map(|key| counters.get(&key).unwrap()).sum();
+    assert_eq!(total, (THREADS * ROUNDS) as u64);
+    let stats = counters.stats();
+    assert_eq!(stats.hits + stats.misses, (THREADS * ROUNDS * 2 + 32) as u64);
+    assert_eq!((stats.misses, stats.evictions), (32, 0));
+
+    // Distinct keys from every thread: the bound holds and every drop is counted
+    let small = Arc::new(BoundedCache::new("hammer eviction test", 256, None));
+    let threads: Vec<_> = (0..THREADS)
+        .map(|thread| {
+            let small = small.clone();
+            std::thread::spawn(move || {
+                for i in 0..ROUNDS {
+                    small.insert((thread, i), i);
+                    if let Some(value) = small.get(&(thread, i / 2)) {
+                        assert_eq!(value, i / 2);
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in threads {
+        handle.join().unwrap();
+    }
+    let stats = small.stats();
+    assert!(stats.entries <= stats.capacity);
+    assert_eq!(stats.entries as u64 + stats.evictions, (THREADS * ROUNDS) as u64);
+}
+
+#[test]
+fn test_rate_limiter_stays_bounded_under_scan() {
+    let mut limiter = InviteRateLimiter::new();
+    let start = Utc::now();
+
+    // 100k distinct sources failing once each, faster than pruning drops them
+    for i in 0..100_000u32 {
+        let source = IpAddr::V4(Ipv4Addr::from(0x0a00_0000 + i));
+        limiter.record_failure(source, start + Duration::milliseconds(i as i64 / 10));
+        assert!(limiter.tracked_sources() <= MAX_TRACKED_SOURCES);
+    }
+    assert!(limiter.tracked_sources() > MAX_TRACKED_SOURCES / 2);
+
+    // A source locked out in the middle of the scan is still locked out
+    let now = start + Duration::seconds(10);
+    let offender = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    for _ in 0..MAX_FAILURES_PER_WINDOW {
+        limiter.record_failure(offender, now);
+    }
+    for i in 0..100u32 {
+        limiter.record_failure(IpAddr::V4(Ipv4Addr::from(0x0b00_0000 + i)), now);
+    }
+    assert!(limiter.check(offender, now).is_err());
+    assert!(limiter.tracked_sources() <= MAX_TRACKED_SOURCES);
+}
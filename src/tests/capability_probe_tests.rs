@@ -0,0 +1,214 @@
+// Capability probe tests - due rules and rate limit, probe answers persisted and changing the edit route, legacy peers and piggybacking on deliveries
+
+use crate::crypto::KeyPair;
+use crate::edits::EditRoute;
+use crate::relay::{Relay, RelayCapabilities, RelayEnvelope};
+use crate::storage::{
+    probe_due, AppState, CapabilityProbes, CapabilityRecord, Contact, CAPABILITIES_STALE_DAYS, LEGACY_REPROBE_DAYS,
+    PROBE_MIN_INTERVAL_MINUTES,
+};
+use crate::transport::{
+    LoopbackNetwork, LoopbackTransport, MessageRequest, PeerTransport, PingResponse, TransportCapabilities,
+    TransportFuture,
+};
+use crate::tui::App;
+use chrono::{Duration, Utc};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use super::helpers::{app_chatting_through, contact_at, settle};
+
+/// Loopback transport that logs every message and probe it carries, in order
+struct CountingTransport {
+    inner: LoopbackTransport,
+    log: Arc<Mutex<Vec<&'static str>>>,
+}
+
+impl PeerTransport for CountingTransport {
+    fn scheme(&self) -> &str {
+        self.inner.scheme()
+    }
+
+    fn capabilities(&self) -> TransportCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn send_message<'a>(&'a self, contact: &'a Contact, request: &'a MessageRequest) -> TransportFuture<'a, ()> {
+        self.log.lock().unwrap().push("message");
+        self.inner.send_message(contact, request)
+    }
+
+    fn send_ping<'a>(&'a self, contact: &'a Contact, my_contact_token: &'a str) -> TransportFuture<'a, PingResponse> {
+        self.inner.send_ping(contact, my_contact_token)
+    }
+
+    fn relay_message<'a>(&'a self, relay: &'a Contact, envelope: &'a RelayEnvelope) -> TransportFuture<'a, ()> {
+        self.inner.relay_message(relay, envelope)
+    }
+
+    fn probe_capabilities<'a>(
+        &'a self,
+        contact: &'a Contact,
+        from_uid: &'a str,
+    ) -> TransportFuture<'a, Option<RelayCapabilities>> {
+        self.log.lock().unwrap().push("probe");
+        self.inner.probe_capabilities(contact, from_uid)
+    }
+
+    fn start_listener<'a>(&'a self, address: &'a str) -> TransportFuture<'a, String> {
+        self.inner.start_listener(address)
+    }
+
+    fn stop_listener(&self) -> TransportFuture<'_, ()> {
+        self.inner.stop_listener()
+    }
+}
+
+/// Start a loopback peer at `name`, with relay state knowing `contacts` if given
+async fn peer(network: &LoopbackNetwork, name: &str, contacts: Option<&[Contact]>) -> LoopbackTransport {
+    let peer = LoopbackTransport::new(network);
+    peer.set_new_message_handler(|_| Ok(())).await;
+    if let Some(contacts) = contacts {
+        let mut relay = Relay::new();
+        relay.set_contacts(contacts);
+        peer.set_relay(Arc::new(Mutex::new(relay))).await;
+    }
+    peer.start_listener(name).await.expect("Failed to start loopback listener");
+    peer
+}
+
+/// App with `contact`'s chat open, sending through a logging loopback transport
+fn app_chatting_with(
+    temp_dir: &TempDir,
+    network: &LoopbackNetwork,
+    contact: Contact,
+) -> (App, Arc<Mutex<Vec<&'static str>>>) {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let transport = CountingTransport { inner: LoopbackTransport::new(network), log: log.clone() };
+    (app_chatting_through(temp_dir, transport, contact), log)
+}
+
+/// Type `text` into the open chat, send it and record whatever came back
+fn send(app: &mut App, text: &str) {
+    let screen = app.chat_view_screen.as_mut().unwrap();
+    screen.clear_input();
+    screen.input = text.to_string();
+    app.send_message_in_chat();
+    settle();
+    app.process_incoming_updates();
+}
+
+#[test]
+fn test_probe_due_rules_and_rate_limit() {
+    let now = Utc::now();
+    assert!(probe_due(None, now));
+
+    let fresh = CapabilityRecord::Fetched { fetched_at: now - Duration::days(1) };
+    let stale = CapabilityRecord::Fetched { fetched_at: now - Duration::days(CAPABILITIES_STALE_DAYS) };
+    assert!(!probe_due(Some(&fresh), now));
+    assert!(probe_due(Some(&stale), now));
+
+    let legacy = CapabilityRecord::Legacy { probed_at: now - Duration::days(CAPABILITIES_STALE_DAYS) };
+    let old_legacy = CapabilityRecord::Legacy { probed_at: now - Duration::days(LEGACY_REPROBE_DAYS) };
+    assert!(!probe_due(Some(&legacy), now));
+    assert!(probe_due(Some(&old_legacy), now));
+    assert!(legacy.describe().starts_with("legacy, probed "));
+
+    // One attempt per contact per interval, answered or not
+    let contact = contact_at(&KeyPair::generate().unwrap(), "loopback://bob");
+    let other = contact_at(&KeyPair::generate().unwrap(), "loopback://carol");
+    let mut probes = CapabilityProbes::new();
+    assert!(probes.start(&contact, now));
+    assert!(!probes.start(&contact, now + Duration::minutes(PROBE_MIN_INTERVAL_MINUTES - 1)));
+    assert!(probes.start(&other, now));
+    assert!(probes.start(&contact, now + Duration::minutes(PROBE_MIN_INTERVAL_MINUTES)));
+
+    // A contact that is not due is never counted
+    let mut known = contact.clone();
+    known.capabilities = Some(fresh);
+    assert!(!CapabilityProbes::new().start(&known, now));
+}
+
+#[test]
+fn test_probe_answer_persisted_and_enables_edits() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let temp_dir = TempDir::new().unwrap();
+    let carol = KeyPair::generate().unwrap();
+    let contact = contact_at(&carol, "loopback://carol");
+    assert!(contact.capabilities.is_none());
+    assert_eq!(EditRoute::choose(false, contact.supports_edits), EditRoute::Correction);
+
+    let (mut app, log) = app_chatting_with(&temp_dir, &network, contact);
+    let me = contact_at(&app.keypair, "loopback://alice");
+    let _carol_peer = rt.block_on(peer(&network, "carol", Some(&[me])));
+
+    send(&mut app, "hello carol");
+    assert_eq!(*log.lock().unwrap(), vec!["message", "probe"]);
+
+    let uid = carol.uid.to_string();
+    let updated = app.app_state.contact_by_uid(&uid).unwrap();
+    assert!(matches!(updated.capabilities, Some(CapabilityRecord::Fetched { .. })));
+    assert!(updated.supports_edits);
+    assert_eq!(EditRoute::choose(false, updated.supports_edits), EditRoute::Wire);
+
+    // Survives a restart
+    let reloaded = AppState::load_from_db(&app.storage).unwrap();
+    let stored = reloaded.contact_by_uid(&uid).unwrap();
+    assert_eq!(stored.capabilities, updated.capabilities);
+    assert!(stored.supports_edits);
+}
+
+#[test]
+fn test_legacy_peer_recorded_and_not_probed_again() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let temp_dir = TempDir::new().unwrap();
+    let dave = KeyPair::generate().unwrap();
+    let _dave_peer = rt.block_on(peer(&network, "dave", None));
+
+    let (mut app, log) = app_chatting_with(&temp_dir, &network, contact_at(&dave, "loopback://dave"));
+    send(&mut app, "hello dave");
+    assert_eq!(*log.lock().unwrap(), vec!["message", "probe"]);
+
+    let uid = dave.uid.to_string();
+    let record = app.app_state.contact_by_uid(&uid).unwrap().capabilities;
+    assert!(matches!(record, Some(CapabilityRecord::Legacy { .. })));
+    let stored = AppState::load_from_db(&app.storage).unwrap();
+    assert_eq!(stored.contact_by_uid(&uid).unwrap().capabilities, record);
+
+    // Not asked again, even once the session's rate limit is gone
+    app.capability_probes = CapabilityProbes::new();
+    send(&mut app, "still there?");
+    assert_eq!(*log.lock().unwrap(), vec!["message", "probe", "message"]);
+}
+
+#[test]
+fn test_probe_only_follows_a_delivery() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let temp_dir = TempDir::new().unwrap();
+    let erin = KeyPair::generate().unwrap();
+
+    // Nobody listens yet: the message is queued and nothing is probed
+    let (mut app, log) = app_chatting_with(&temp_dir, &network, contact_at(&erin, "loopback://erin"));
+    send(&mut app, "are you there?");
+    assert_eq!(*log.lock().unwrap(), vec!["message"]);
+    assert!(app.app_state.contact_by_uid(&erin.uid.to_string()).unwrap().capabilities.is_none());
+
+    // An idle main loop never probes on its own
+    for _ in 0..3 {
+        app.process_incoming_updates();
+    }
+    assert_eq!(*log.lock().unwrap(), vec!["message"]);
+
+    // Once a delivery goes through, exactly one probe follows it
+    let me = contact_at(&app.keypair, "loopback://alice");
+    let _erin_peer = rt.block_on(peer(&network, "erin", Some(&[me])));
+    app.capability_probes = CapabilityProbes::new();
+    // New messages wait behind older queued ones; say the worker sent the first meanwhile
+    app.queue.clear().unwrap();
+    send(&mut app, "there you are");
+    assert_eq!(*log.lock().unwrap(), vec!["message", "message", "probe"]);
+    send(&mut app, "and again");
+    assert_eq!(*log.lock().unwrap(), vec!["message", "message", "probe", "message"]);
+}
@@ -0,0 +1,170 @@
+// Capture tests - scoping to one contact amid other traffic, expiry by time and exchange count, redacted vs included bodies, chat badge and consent prompt, bundle inclusion only on request
+
+use crate::queue::MessageQueue;
+use crate::storage::{Chat, Contact};
+use crate::transport::{
+    list_captures, write_diagnostics_bundle, CaptureDirection, CaptureSlot, CapturedExchange, Transport,
+    CAPTURE_MAX_EXCHANGES, CAPTURE_MAX_MINUTES,
+};
+use crate::tui::{App, CapturePrompt};
+use chrono::{Duration, Utc};
+use tempfile::TempDir;
+
+/// Exchanges recorded in a capture file (the header line skipped)
+fn exchanges(path: &std::path::Path) -> Vec<CapturedExchange> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn contact(uid: &str, addr: std::net::SocketAddr) -> Contact {
+    Contact::new(uid.to_string(), addr.to_string(), vec![1, 2, 3], vec![7u8; 32], Utc::now() + Duration::days(30))
+}
+
+#[tokio::test]
+async fn test_capture_records_only_the_captured_contact() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut receiver = Transport::new();
+    receiver.set_new_message_handler(|_| Ok(())).await;
+    receiver.start("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let receiver_addr = receiver.local_addr().unwrap();
+    let sender = Transport::new();
+
+    // The receiver captures what alice sends; the sender captures what goes to carol
+    let incoming = receiver.capture().start("alice_uid", false, &temp_dir.path().join("in"), Utc::now()).unwrap();
+    let outgoing = sender.capture().start("carol_uid", false, &temp_dir.path().join("out"), Utc::now()).unwrap();
+
+    let carol = contact("carol_uid", receiver_addr);
+    let dave = contact("dave_uid", receiver_addr);
+    sender.send_message(&carol, "alice_uid", "text", b"to carol".to_vec()).await.unwrap();
+    sender.send_message(&dave, "alice_uid", "text", b"to dave".to_vec()).await.unwrap();
+    sender.send_message(&dave, "bob_uid", "text", b"from bob".to_vec()).await.unwrap();
+    sender.send_message(&carol, "bob_uid", "text", b"bob to carol".to_vec()).await.unwrap();
+
+    let sent = exchanges(&outgoing);
+    assert_eq!(sent.len(), 2);
+    assert!(sent.iter().all(|e| e.direction == CaptureDirection::Outgoing && e.path == "/message"));
+    assert!(sent.iter().all(|e| e.status == Some(200)));
+    assert_eq!(sent[0].address, Some(format!("http://{}", receiver_addr)));
+
+    let received = exchanges(&incoming);
+    assert_eq!(received.len(), 2);
+    assert!(received.iter().all(|e| e.direction == CaptureDirection::Incoming && e.status == Some(200)));
+
+    // Clones share the slot; stopping ends it for every clone
+    assert!(receiver.clone().capture().is_capturing("alice_uid", Utc::now()));
+    assert_eq!(receiver.capture().stop(), Some(incoming.clone()));
+    sender.send_message(&dave, "alice_uid", "text", b"after".to_vec()).await.unwrap();
+    assert_eq!(exchanges(&incoming).len(), 2);
+    assert!(receiver.capture().start("", false, temp_dir.path(), Utc::now()).is_err());
+}
+
+#[test]
+fn test_capture_expires_by_time_and_by_count() {
+    let temp_dir = TempDir::new().unwrap();
+    let slot = CaptureSlot::default();
+    let start = Utc::now();
+    let exchange = |bodies| CapturedExchange::incoming("/ping", b"token", 200, None, bodies, start);
+
+    let path = slot.start("alice_uid", false, temp_dir.path(), start).unwrap();
+    slot.record("alice_uid", start + Duration::minutes(CAPTURE_MAX_MINUTES - 1), exchange);
+    let late = start + Duration::minutes(CAPTURE_MAX_MINUTES + 1);
+    slot.record("alice_uid", late, exchange);
+    assert_eq!(exchanges(&path).len(), 1);
+    assert_eq!(slot.status(late), None);
+
+    let path = slot.start("alice_uid", false, temp_dir.path(), late).unwrap();
+    for _ in 0..CAPTURE_MAX_EXCHANGES + 5 {
+        slot.record("alice_uid", late, exchange);
+    }
+    assert_eq!(exchanges(&path).len(), CAPTURE_MAX_EXCHANGES);
+    assert!(!slot.is_capturing("alice_uid", late));
+    assert_eq!(list_captures(temp_dir.path()).first(), Some(&path));
+}
+
+#[test]
+fn test_bodies_are_redacted_unless_included() {
+    let temp_dir = TempDir::new().unwrap();
+    let slot = CaptureSlot::default();
+    let now = Utc::now();
+    let body = b"secret message body";
+    let outcome: Result<(u16, &[u8]), String> = Ok((200, b"ok"));
+
+    let redacted = slot.start("bob_uid", false, &temp_dir.path().join("redacted"), now).unwrap();
+    slot.record("bob_uid", now, |bodies| CapturedExchange::outgoing("http://peer", "/message", body, &outcome, bodies, now));
+    let exchange = &exchanges(&redacted)[0];
+    assert_eq!(exchange.request.bytes, body.len());
+    assert_eq!(exchange.request.sha256.len(), 64);
+    assert_eq!(exchange.request.hex, None);
+    assert!(!std::fs::read_to_string(&redacted).unwrap().contains(&hex::encode(body)));
+
+    let included = slot.start("bob_uid", true, &temp_dir.path().join("included"), now).unwrap();
+    slot.record("bob_uid", now, |bodies| CapturedExchange::outgoing("http://peer", "/message", body, &outcome, bodies, now));
+    let exchange = &exchanges(&included)[0];
+    assert_eq!(exchange.request.hex, Some(hex::encode(body)));
+    assert_eq!(exchange.response.as_ref().unwrap().hex, Some(hex::encode(b"ok")));
+    assert_eq!(exchange.request.sha256, exchanges(&redacted)[0].request.sha256);
+}
+
+#[test]
+fn test_badge_follows_the_consented_capture() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.chats.push(Chat::new("bob_uid".to_string()));
+    app.app_state.contacts.push(contact("bob_uid", "127.0.0.1:9".parse().unwrap()));
+    app.show_chat_list_screen();
+    app.show_contact_details();
+    assert!(!app.capture_badge("bob_uid"));
+
+    // Nothing starts until the prompt is answered; Esc cancels
+    app.toggle_contact_capture();
+    let popup = |app: &App| app.chat_list_screen.as_ref().unwrap().contact_details.as_ref().unwrap().capture_prompt;
+    assert_eq!(popup(&app), Some(CapturePrompt::Confirm));
+    app.answer_capture_prompt('\u{1b}');
+    assert_eq!(popup(&app), None);
+    assert!(!app.capture_badge("bob_uid"));
+
+    // Bodies need the second confirmation
+    app.toggle_contact_capture();
+    app.answer_capture_prompt('b');
+    assert_eq!(popup(&app), Some(CapturePrompt::ConfirmBodies));
+    app.answer_capture_prompt('y');
+    assert!(app.capture_badge("bob_uid"));
+    assert!(!app.capture_badge("carol_uid"));
+    assert!(app.transport.capture().status(Utc::now()).unwrap().include_bodies);
+    assert!(app.chat_title("bob_uid").contains("[capturing]"));
+    assert_eq!(list_captures(&app.captures_dir).len(), 1);
+
+    app.show_capture_screen();
+    assert!(app.capture_screen.as_ref().unwrap().lines[0].contains("bob_uid"));
+    app.close_capture_screen();
+
+    app.toggle_contact_capture();
+    assert!(!app.capture_badge("bob_uid"));
+    assert!(!app.chat_title("bob_uid").contains("[capturing]"));
+}
+
+#[test]
+fn test_bundle_includes_captures_only_on_request() {
+    let temp_dir = TempDir::new().unwrap();
+    let queue = MessageQueue::new_with_path(temp_dir.path().join("queue.db")).unwrap();
+    let slot = CaptureSlot::default();
+    let capture = slot.start("alice_uid", false, &temp_dir.path().join("captures"), Utc::now()).unwrap();
+
+    let plain = temp_dir.path().join("bundle");
+    let files = write_diagnostics_bundle(&plain, &queue, &[]).unwrap();
+    assert_eq!(files, [plain.join("queue.json")]);
+    assert!(!plain.join("captures").exists());
+
+    let with_captures = temp_dir.path().join("bundle-with-captures");
+    let files = write_diagnostics_bundle(&with_captures, &queue, std::slice::from_ref(&capture)).unwrap();
+    assert_eq!(files.len(), 2);
+    let copied = with_captures.join("captures").join(capture.file_name().unwrap());
+    assert_eq!(std::fs::read(copied).unwrap(), std::fs::read(&capture).unwrap());
+
+    // An existing folder is not written into
+    assert!(write_diagnostics_bundle(&plain, &queue, &[]).is_err());
+}
@@ -0,0 +1,242 @@
+// Connectivity events tests - events of the orchestrator, health check, renewals and port watchdog over scripted protocols, multi-subscriber delivery, the App adapters and lost-then-restored ordering
+
+use crate::connectivity::{
+    establish_connectivity_with, verify_connectivity_health_with, ConnectivityEvent, ConnectivityEvents,
+    ConnectivityResult, GatewayProbeReport, MappingError, MappingProtocol, MappingStrategies, PortMappingResult,
+    ReachabilityStatus,
+};
+use crate::crypto::KeyPair;
+use crate::storage::{storage_db::Storage, AppState, CONNECTIVITY_HISTORY_LIMIT};
+use crate::transport::watchdog::MAX_BIND_ATTEMPTS;
+use crate::transport::{bind_verified, Transport};
+use crate::tui::{App, ErrorReporter, PortWatchdog, TransportServerStatus};
+use chrono::Utc;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use tempfile::TempDir;
+use tokio::sync::broadcast::Receiver;
+
+/// Protocol layer answering from a script: `None` fails the strategy
+#[derive(Default)]
+struct ScriptedStrategies {
+    ipv6: Option<PortMappingResult>,
+    pcp: Option<PortMappingResult>,
+    natpmp: Option<PortMappingResult>,
+    upnp: Option<PortMappingResult>,
+    external_ip: Option<IpAddr>,
+    reachability: Option<ReachabilityStatus>,
+}
+
+fn scripted(outcome: &Option<PortMappingResult>) -> Result<PortMappingResult, MappingError> {
+    outcome.clone().ok_or(MappingError::NoGateway)
+}
+
+impl MappingStrategies for ScriptedStrategies {
+    async fn ipv6(&self, _port: u16) -> Result<PortMappingResult, MappingError> {
+        scripted(&self.ipv6)
+    }
+
+    async fn pcp(&self, _port: u16, _lifetime_secs: u32) -> GatewayProbeReport {
+        GatewayProbeReport { result: scripted(&self.pcp), probes: Vec::new() }
+    }
+
+    async fn natpmp(&self, _port: u16, _lifetime_secs: u32) -> GatewayProbeReport {
+        GatewayProbeReport { result: scripted(&self.natpmp), probes: Vec::new() }
+    }
+
+    async fn upnp(&self, _port: u16, _lifetime_secs: u32) -> Result<PortMappingResult, MappingError> {
+        scripted(&self.upnp)
+    }
+
+    async fn external_ip(&self) -> Result<IpAddr, MappingError> {
+        self.external_ip.ok_or(MappingError::NotSupported)
+    }
+
+    async fn reachability(&self, _mapping: &PortMappingResult) -> ReachabilityStatus {
+        self.reachability.clone().unwrap_or(ReachabilityStatus::TestFailed("not scripted".to_string()))
+    }
+}
+
+fn mapping(protocol: MappingProtocol, ip: &str, port: u16) -> PortMappingResult {
+    PortMappingResult {
+        external_ip: ip.parse().unwrap(),
+        external_port: port,
+        lifetime_secs: 3600,
+        protocol,
+        created_at_ms: Utc::now().timestamp_millis(),
+    }
+}
+
+/// Everything received so far
+fn drain(rx: &mut Receiver<ConnectivityEvent>) -> Vec<ConnectivityEvent> {
+    let mut events = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        events.push(event);
+    }
+    events
+}
+
+fn kinds(events: &[ConnectivityEvent]) -> Vec<&'static str> {
+    events.iter().map(|event| event.kind()).collect()
+}
+
+#[tokio::test]
+async fn test_orchestrator_and_health_check_events() {
+    let events = ConnectivityEvents::new();
+    let mut tui = events.subscribe();
+    let mut history = events.subscribe();
+
+    // IPv6 and PCP fail, NAT-PMP maps
+    let natpmp = mapping(MappingProtocol::NATPMP, "203.0.113.7", 40000);
+    let strategies = ScriptedStrategies { natpmp: Some(natpmp.clone()), ..Default::default() };
+    let result = establish_connectivity_with(40000, &strategies, &events).await;
+    assert_eq!(result.mapping.as_ref(), Some(&natpmp));
+
+    let received = drain(&mut tui);
+    assert_eq!(kinds(&received), vec!["protocol_attempt", "protocol_attempt", "protocol_attempt", "mapping_established", "checked"]);
+    let attempts: Vec<_> = received
+        .iter()
+        .filter_map(|event| match event {
+            ConnectivityEvent::ProtocolAttempt { protocol, succeeded, .. } => Some((*protocol, *succeeded)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(attempts, vec![(MappingProtocol::IPv6, false), (MappingProtocol::PCP, false), (MappingProtocol::NATPMP, true)]);
+    assert!(matches!(&received[3], ConnectivityEvent::MappingEstablished { mapping, cgnat: false, .. } if *mapping == natpmp));
+    assert!(matches!(&received[4], ConnectivityEvent::Checked { result: checked, .. } if **checked == result));
+    assert!(received.windows(2).all(|pair| pair[0].at() <= pair[1].at()));
+
+    // Every subscriber gets the same events
+    assert_eq!(drain(&mut history), received);
+
+    // The health check reports a verdict once, and again when it changes
+    let reachable = ScriptedStrategies { reachability: Some(ReachabilityStatus::Reachable), ..Default::default() };
+    let verified = verify_connectivity_health_with(result.clone(), &reachable, &events).await;
+    assert_eq!(verified.externally_reachable, Some(true));
+    verify_connectivity_health_with(result.clone(), &reachable, &events).await;
+    let unreachable = ScriptedStrategies { reachability: Some(ReachabilityStatus::Unreachable), ..Default::default() };
+    verify_connectivity_health_with(result.clone(), &unreachable, &events).await;
+    let received = drain(&mut tui);
+    assert_eq!(kinds(&received), vec!["reachability_changed", "reachability_changed"]);
+    assert!(matches!(&received[0], ConnectivityEvent::ReachabilityChanged { reachable: Some(true), mapping, .. } if *mapping == natpmp));
+    assert!(matches!(&received[1], ConnectivityEvent::ReachabilityChanged { reachable: Some(false), .. }));
+
+    // Nothing works any more: five failed attempts and the mapping is lost
+    let result = establish_connectivity_with(40000, &ScriptedStrategies::default(), &events).await;
+    assert!(result.mapping.is_none());
+    let received = drain(&mut tui);
+    assert_eq!(kinds(&received)[..5], ["protocol_attempt"; 5]);
+    assert_eq!(kinds(&received)[5..], ["mapping_lost", "checked"]);
+    assert!(matches!(&received[4], ConnectivityEvent::ProtocolAttempt { protocol: MappingProtocol::Direct, succeeded: false, .. }));
+    assert!(matches!(&received[5], ConnectivityEvent::MappingLost { previous, .. } if *previous == natpmp));
+    assert!(events.current_mapping().is_none());
+}
+
+#[test]
+fn test_renewal_lost_then_restored_ordering() {
+    let events = ConnectivityEvents::new();
+    let mut rx = events.subscribe();
+    let first = mapping(MappingProtocol::PCP, "203.0.113.7", 40000);
+
+    events.observe_renewal(&Ok(first.clone()));
+    // A renewal of the same mapping is no change
+    let mut renewed = first.clone();
+    renewed.created_at_ms += 2_880_000;
+    events.observe_renewal(&Ok(renewed.clone()));
+    // The next renewal fails, then one succeeds on a new address
+    events.observe_renewal(&Err(MappingError::Timeout));
+    events.observe_renewal(&Err(MappingError::Timeout));
+    let second = mapping(MappingProtocol::PCP, "198.51.100.4", 40000);
+    events.observe_renewal(&Ok(second.clone()));
+
+    let received = drain(&mut rx);
+    assert_eq!(kinds(&received), vec!["mapping_established", "mapping_lost", "mapping_established", "external_ip_changed"]);
+    assert!(matches!(&received[1], ConnectivityEvent::MappingLost { previous, reason, .. }
+        if *previous == renewed && reason.contains("renewal failed")));
+    assert!(matches!(&received[2], ConnectivityEvent::MappingEstablished { mapping, .. } if *mapping == second));
+    assert!(matches!(&received[3], ConnectivityEvent::ExternalIpChanged { from, to, .. }
+        if *from == first.external_ip && *to == second.external_ip));
+    assert!(received.windows(2).all(|pair| pair[0].at() <= pair[1].at()));
+    assert_eq!(events.current_mapping(), Some(second));
+
+    // Degradation is reported once until it is restored
+    events.observe_restored("nothing to restore".to_string());
+    events.observe_degraded("Port 40000 is no longer answering".to_string());
+    events.observe_degraded("Port 40000 is no longer answering".to_string());
+    events.observe_restored("listening again".to_string());
+    assert_eq!(kinds(&drain(&mut rx)), vec!["degraded", "restored"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_port_watchdog_emits_degraded_then_restored() {
+    let transport = Transport::new();
+    let free = std::net::TcpListener::bind("0.0.0.0:0").unwrap().local_addr().unwrap().port();
+    let port = bind_verified(&transport, free, MAX_BIND_ATTEMPTS).await.unwrap();
+
+    let storage = Storage::new_in_memory().unwrap();
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().unwrap());
+    state.save_to_db(&storage).unwrap();
+    let status = Mutex::new(TransportServerStatus::Running(port));
+    let alert = Mutex::new(None);
+    let reports = ErrorReporter::new();
+    let events = ConnectivityEvents::new();
+    let mut rx = events.subscribe();
+    let mut watchdog = PortWatchdog::new(port).with_events(events);
+
+    // Lost after two unanswered probes, then bound again
+    transport.stop();
+    watchdog.tick(&transport, &status, &storage, &alert, &reports).await;
+    assert!(drain(&mut rx).is_empty());
+    watchdog.tick(&transport, &status, &storage, &alert, &reports).await;
+    let received = drain(&mut rx);
+    assert_eq!(kinds(&received), vec!["degraded", "restored"]);
+    assert!(received[0].describe().contains("no longer answering"));
+    assert!(received[1].describe().ends_with("listening again"));
+    transport.stop();
+}
+
+#[test]
+fn test_app_adapters_follow_the_stream() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    assert!(!app.poll_startup_connectivity());
+    assert!(!app.poll_connectivity_events());
+
+    // A finished check is applied by the startup poll, exactly once
+    let upnp = mapping(MappingProtocol::UPnP, "203.0.113.9", 41000);
+    let mut result = ConnectivityResult::new();
+    result.mapping = Some(upnp.clone());
+    app.connectivity_events.observe_result(&result);
+    assert!(app.poll_startup_connectivity());
+    assert!(!app.poll_startup_connectivity());
+    assert_eq!(app.local_ip, "203.0.113.9:41000");
+    assert_eq!(app.app_state.user_port, 41000);
+    assert_eq!(app.connectivity_result.as_ref().unwrap().mapping, Some(upnp.clone()));
+    assert_eq!(AppState::load_from_db(&app.storage).unwrap().user_ip.as_deref(), Some("203.0.113.9:41000"));
+
+    // A verdict for the current mapping is merged, one for another is ignored
+    let other = mapping(MappingProtocol::UPnP, "198.51.100.1", 41000);
+    app.connectivity_events.observe_reachability(&other, Some(true));
+    app.poll_health_check();
+    assert_eq!(app.connectivity_result.as_ref().unwrap().externally_reachable, None);
+    app.connectivity_events.observe_reachability(&upnp, Some(false));
+    app.poll_health_check();
+    assert_eq!(app.connectivity_result.as_ref().unwrap().externally_reachable, Some(false));
+
+    // A Diagnostics refresh that finds nothing replaces the result but keeps the address
+    app.show_diagnostics_screen();
+    app.connectivity_events.observe_result(&ConnectivityResult::new());
+    assert!(app.poll_diagnostics_result());
+    assert!(app.connectivity_result.as_ref().unwrap().mapping.is_none());
+    assert_eq!(app.local_ip, "203.0.113.9:41000");
+
+    // Everything went to the persisted history, in order
+    let history = app.storage.load_connectivity_history(CONNECTIVITY_HISTORY_LIMIT).unwrap();
+    let recorded: Vec<_> = history.iter().map(|entry| entry.kind.as_str()).collect();
+    assert_eq!(
+        recorded,
+        vec!["mapping_established", "checked", "reachability_changed", "reachability_changed", "mapping_lost", "checked"]
+    );
+    assert!(history[0].detail.contains("UPnP mapping 203.0.113.9:41000"));
+}
@@ -1,10 +1,13 @@
 use crate::connectivity::*;
+#[cfg(feature = "connectivity-pcp-natpmp")]
 use crate::connectivity::pcp::{PcpResultCode, PcpOpcode, build_pcp_map_request, parse_pcp_ip_address, PCP_VERSION};
+#[cfg(feature = "connectivity-pcp-natpmp")]
 use crate::connectivity::natpmp::{NatPmpResultCode, NatPmpOpcode, build_natpmp_map_request, parse_natpmp_map_response, NATPMP_VERSION};
 use crate::connectivity::ipv6::is_ipv6_link_local;
 use chrono::Utc;
 use std::net::{IpAddr, Ipv4Addr};
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_pcp_result_code_conversion() {
     assert_eq!(PcpResultCode::from_u8(0), Some(PcpResultCode::Success));
@@ -15,6 +18,7 @@ fn test_pcp_result_code_conversion() {
     assert_eq!(PcpResultCode::from_u8(255), None);
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_pcp_result_code_error_message() {
     assert_eq!(PcpResultCode::Success.to_error_message(), "Success");
@@ -24,6 +28,7 @@ fn test_pcp_result_code_error_message() {
     );
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_build_pcp_map_request_ipv4() {
     let local_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 100));
@@ -58,6 +63,7 @@ fn test_build_pcp_map_request_ipv4() {
     assert_eq!(internal_port, 8080, "Internal port should match");
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_parse_pcp_ip_address_ipv4_mapped() {
     let bytes = [
@@ -67,6 +73,7 @@ fn test_parse_pcp_ip_address_ipv4_mapped() {
     assert_eq!(ip, IpAddr::V4(Ipv4Addr::new(203, 0, 113, 10)));
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_parse_pcp_ip_address_ipv6() {
     let bytes = [
@@ -77,6 +84,7 @@ fn test_parse_pcp_ip_address_ipv6() {
     assert!(matches!(ip, IpAddr::V6(_)));
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_parse_pcp_ip_address_invalid_length() {
     let bytes = [0u8; 8]; // Too short
@@ -114,6 +122,7 @@ async fn test_port_mapping_manager_creation() {
 // NAT-PMP Tests
 // ========================================================================
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_natpmp_result_code_conversion() {
     assert_eq!(NatPmpResultCode::from_u16(0), Some(NatPmpResultCode::Success));
@@ -128,6 +137,7 @@ fn test_natpmp_result_code_conversion() {
     assert_eq!(NatPmpResultCode::from_u16(999), None);
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_natpmp_result_code_error_message() {
     assert_eq!(NatPmpResultCode::Success.to_error_message(), "Success");
@@ -141,6 +151,7 @@ fn test_natpmp_result_code_error_message() {
     );
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_build_natpmp_map_request_tcp() {
     let request = build_natpmp_map_request(8080, 8080, 3600, IpProtocol::TCP);
@@ -168,6 +179,7 @@ fn test_build_natpmp_map_request_tcp() {
     assert_eq!(lifetime, 3600, "Lifetime should match requested value");
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_build_natpmp_map_request_udp() {
     let request = build_natpmp_map_request(5060, 5060, 1800, IpProtocol::UDP);
@@ -186,6 +198,7 @@ fn test_build_natpmp_map_request_udp() {
     assert_eq!(lifetime, 1800);
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_natpmp_map_response_parsing() {
     // Simulate a successful NAT-PMP MAP response
@@ -199,16 +212,17 @@ fn test_natpmp_map_response_parsing() {
     response.extend_from_slice(&50123u16.to_be_bytes()); // external port
     response.extend_from_slice(&3600u32.to_be_bytes()); // lifetime
 
-    // Note: This test would need a mock for get_external_ip_natpmp
-    // For now, we just test the response parsing would fail gracefully
-    let gateway_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
-    let result = parse_natpmp_map_response(&response, gateway_ip);
+    // External IP comes from the separate external address request
+    let external_ip = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1));
+    let mapping = parse_natpmp_map_response(&response, external_ip).expect("Failed to parse response");
 
-    // Will fail because get_external_ip_natpmp tries real network call
-    // In production, this should be mocked
-    assert!(result.is_err() || result.is_ok());
+    assert_eq!(mapping.external_ip, external_ip);
+    assert_eq!(mapping.external_port, 50123);
+    assert_eq!(mapping.lifetime_secs, 3600);
+    assert_eq!(mapping.protocol, MappingProtocol::NATPMP);
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_natpmp_response_invalid_version() {
     let mut response = vec![0u8; 16];
@@ -224,6 +238,7 @@ fn test_natpmp_response_invalid_version() {
     }
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_natpmp_response_too_short() {
     let response = vec![0u8; 10]; // Too short (should be 16)
@@ -237,6 +252,7 @@ fn test_natpmp_response_too_short() {
     }
 }
 
+#[cfg(feature = "connectivity-pcp-natpmp")]
 #[test]
 fn test_natpmp_response_error_code() {
     let mut response = Vec::with_capacity(16);
@@ -831,3 +847,260 @@ async fn test_health_check_timeout() {
         "Non-routable IP should not be reachable"
     );
 }
+
+// ========== Gateway discovery and concurrent probing ==========
+
+#[test]
+fn test_parse_linux_route_table_candidates() {
+    use crate::connectivity::gateway::parse_linux_route_table;
+
+    let table = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+wg0\t00000080\t0100080A\t0003\t0\t0\t0\t00000080\t0\t0\t0
+eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0
+docker0\t000011AC\t00000000\t0001\t0\t0\t0\t0000FFFF\t0\t0\t0
+eth1\t00000000\t01010A0A\t0003\t0\t0\t200\t00000000\t0\t0\t0
+eth0\t0000A8C0\t0101A8C0\t0003\t0\t0\t100\t0000FFFF\t0\t0\t0
+";
+
+    let candidates = parse_linux_route_table(table);
+    let found: Vec<(String, Option<&str>)> = candidates
+        .iter()
+        .map(|c| (c.ip.to_string(), c.interface.as_deref()))
+        .collect();
+
+    // Default routes first, then other gateway routes; directly connected
+    // subnets skipped; each gateway listed once
+    assert_eq!(
+        found,
+        vec![
+            ("192.168.1.1".to_string(), Some("eth0")),
+            ("10.10.1.1".to_string(), Some("eth1")),
+            ("10.8.0.1".to_string(), Some("wg0")),
+        ]
+    );
+
+    assert!(parse_linux_route_table("Iface\tDestination\tGateway\n").is_empty());
+}
+
+#[cfg(feature = "connectivity-pcp-natpmp")]
+#[test]
+fn test_parse_natpmp_external_address_response() {
+    use crate::connectivity::natpmp::parse_natpmp_external_address_response;
+
+    let response = [NATPMP_VERSION, 128, 0, 0, 0, 0, 0, 1, 203, 0, 113, 9];
+    assert_eq!(
+        parse_natpmp_external_address_response(&response).unwrap(),
+        IpAddr::V4(Ipv4Addr::new(203, 0, 113, 9))
+    );
+
+    // Error result code
+    let mut refused = response;
+    refused[3] = 2;
+    assert!(matches!(
+        parse_natpmp_external_address_response(&refused),
+        Err(MappingError::GatewayError(_))
+    ));
+
+    assert!(parse_natpmp_external_address_response(&response[..8]).is_err());
+}
+
+#[cfg(feature = "connectivity-pcp-natpmp")]
+/// Build a successful PCP MAP response for `internal_port`
+fn mock_pcp_response(internal_port: u16, external_ip: Ipv4Addr, external_port: u16) -> Vec<u8> {
+    let mut response = vec![0u8; 60];
+    response[0] = PCP_VERSION;
+    response[1] = 0x80 | PcpOpcode::Map as u8;
+    response[3] = PcpResultCode::Success as u8;
+    response[4..8].copy_from_slice(&3600u32.to_be_bytes());
+    response[36] = IpProtocol::TCP as u8;
+    response[40..42].copy_from_slice(&internal_port.to_be_bytes());
+    response[42..44].copy_from_slice(&external_port.to_be_bytes());
+    response[54..56].copy_from_slice(&[0xff, 0xff]);
+    response[56..60].copy_from_slice(&external_ip.octets());
+    response
+}
+
+#[cfg(feature = "connectivity-pcp-natpmp")]
+/// Mock gateway on a localhost port
+///
+/// `reply` builds the answer to each request; `None` keeps the gateway silent.
+async fn mock_gateway<F>(reply: F) -> std::net::SocketAddr
+where
+    F: Fn(&[u8]) -> Option<Vec<u8>> + Send + 'static,
+{
+    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 1100];
+        while let Ok((len, from)) = socket.recv_from(&mut buf).await {
+            if let Some(response) = reply(&buf[..len]) {
+                let _ = socket.send_to(&response, from).await;
+            }
+        }
+    });
+    addr
+}
+
+#[cfg(feature = "connectivity-pcp-natpmp")]
+#[tokio::test]
+async fn test_pcp_probe_multiple_gateways_one_answers() {
+    use crate::connectivity::{probe_pcp_gateways, GatewayProbeOutcome};
+
+    let silent_a = mock_gateway(|_| None).await;
+    let silent_b = mock_gateway(|_| None).await;
+    let answering = mock_gateway(|request| {
+        let internal_port = u16::from_be_bytes([request[40], request[41]]);
+        Some(mock_pcp_response(internal_port, Ipv4Addr::new(203, 0, 113, 5), 40000))
+    }).await;
+
+    let started = std::time::Instant::now();
+    let report = probe_pcp_gateways(
+        &[silent_a, silent_b, answering],
+        8080,
+        3600,
+        IpProtocol::TCP,
+        std::time::Duration::from_secs(2),
+    ).await;
+
+    // Winner is returned without waiting for the silent gateways to time out
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    let mapping = report.result.expect("Answering gateway should win");
+    assert_eq!(mapping.external_ip, IpAddr::V4(Ipv4Addr::new(203, 0, 113, 5)));
+    assert_eq!(mapping.external_port, 40000);
+    assert_eq!(mapping.protocol, MappingProtocol::PCP);
+
+    // One record per gateway, in probe order
+    assert_eq!(report.probes.len(), 3);
+    assert_eq!(report.probes[0].server, silent_a);
+    assert_eq!(report.probes[0].outcome, GatewayProbeOutcome::Cancelled);
+    assert_eq!(report.probes[1].outcome, GatewayProbeOutcome::Cancelled);
+    assert_eq!(report.probes[2].server, answering);
+    assert!(matches!(report.probes[2].outcome, GatewayProbeOutcome::Mapped(_)));
+    assert!(report.probes.iter().all(|p| p.protocol == MappingProtocol::PCP));
+}
+
+#[cfg(feature = "connectivity-pcp-natpmp")]
+#[tokio::test]
+async fn test_pcp_probe_times_out_when_no_gateway_answers() {
+    use crate::connectivity::{probe_pcp_gateways, GatewayProbeOutcome};
+
+    let gateways = [
+        mock_gateway(|_| None).await,
+        mock_gateway(|_| None).await,
+        mock_gateway(|_| None).await,
+    ];
+
+    let timeout = std::time::Duration::from_millis(200);
+    let started = std::time::Instant::now();
+    let report = probe_pcp_gateways(&gateways, 8080, 3600, IpProtocol::TCP, timeout).await;
+    let elapsed = started.elapsed();
+
+    assert!(matches!(report.result, Err(MappingError::Timeout)));
+    // Probes run concurrently: one timeout, not three
+    assert!(elapsed >= timeout);
+    assert!(elapsed < timeout * 3, "Probes should not run one after another ({:?})", elapsed);
+    assert_eq!(report.probes.len(), 3);
+    for probe in &report.probes {
+        assert_eq!(probe.outcome, GatewayProbeOutcome::Failed(MappingError::Timeout.to_string()));
+    }
+}
+
+#[cfg(feature = "connectivity-pcp-natpmp")]
+#[tokio::test]
+async fn test_pcp_probe_prefers_gateway_error_over_timeout() {
+    use crate::connectivity::probe_pcp_gateways;
+
+    let silent = mock_gateway(|_| None).await;
+    let refusing = mock_gateway(|request| {
+        let internal_port = u16::from_be_bytes([request[40], request[41]]);
+        let mut response = mock_pcp_response(internal_port, Ipv4Addr::LOCALHOST, 0);
+        response[3] = PcpResultCode::NotAuthorized as u8;
+        Some(response)
+    }).await;
+
+    let report = probe_pcp_gateways(
+        &[silent, refusing],
+        8080,
+        3600,
+        IpProtocol::TCP,
+        std::time::Duration::from_millis(200),
+    ).await;
+
+    match report.result {
+        Err(MappingError::GatewayError(msg)) => assert!(msg.contains("Not authorized")),
+        other => panic!("Expected gateway error, got {:?}", other),
+    }
+}
+
+#[cfg(feature = "connectivity-pcp-natpmp")]
+#[tokio::test]
+async fn test_pcp_probe_does_not_stall_runtime() {
+    use crate::connectivity::probe_pcp_gateways;
+
+    // Single-threaded runtime: a blocking read would freeze the ticker below
+    let gateways = [mock_gateway(|_| None).await, mock_gateway(|_| None).await];
+
+    let ticker = tokio::spawn(async {
+        let mut max_gap = std::time::Duration::ZERO;
+        let mut last = std::time::Instant::now();
+        for _ in 0..20 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            max_gap = max_gap.max(last.elapsed());
+            last = std::time::Instant::now();
+        }
+        max_gap
+    });
+
+    let report = probe_pcp_gateways(
+        &gateways,
+        8080,
+        3600,
+        IpProtocol::TCP,
+        std::time::Duration::from_millis(300),
+    ).await;
+    assert!(report.result.is_err());
+
+    let max_gap = ticker.await.unwrap();
+    assert!(
+        max_gap < std::time::Duration::from_millis(150),
+        "Concurrent task was stalled for {:?}",
+        max_gap
+    );
+}
+
+#[cfg(feature = "connectivity-pcp-natpmp")]
+#[tokio::test]
+async fn test_natpmp_probe_multiple_gateways_one_answers() {
+    use crate::connectivity::{probe_natpmp_gateways, GatewayProbeOutcome};
+
+    let silent = mock_gateway(|_| None).await;
+    let answering = mock_gateway(|request| match request[1] {
+        // External address request
+        0 => Some(vec![NATPMP_VERSION, 128, 0, 0, 0, 0, 0, 1, 198, 51, 100, 7]),
+        // MAP TCP request: echo ports, grant one hour
+        op => {
+            let mut response = vec![NATPMP_VERSION, 128 + op, 0, 0, 0, 0, 0, 1];
+            response.extend_from_slice(&request[4..6]);
+            response.extend_from_slice(&request[6..8]);
+            response.extend_from_slice(&3600u32.to_be_bytes());
+            Some(response)
+        }
+    }).await;
+
+    let report = probe_natpmp_gateways(
+        &[silent, answering],
+        8080,
+        3600,
+        IpProtocol::TCP,
+        std::time::Duration::from_secs(2),
+    ).await;
+
+    let mapping = report.result.expect("Answering gateway should win");
+    assert_eq!(mapping.external_ip, IpAddr::V4(Ipv4Addr::new(198, 51, 100, 7)));
+    assert_eq!(mapping.external_port, 8080);
+    assert_eq!(mapping.protocol, MappingProtocol::NATPMP);
+    assert_eq!(report.probes[0].outcome, GatewayProbeOutcome::Cancelled);
+    assert!(matches!(report.probes[1].outcome, GatewayProbeOutcome::Mapped(_)));
+    assert!(report.probes.iter().all(|p| p.protocol == MappingProtocol::NATPMP));
+}
@@ -0,0 +1,210 @@
+// Dead letter tests - failed messages kept for review, bulk and selective re-queue after an address fix, delivery in original order ahead of newer messages, and the review in the chat view
+
+use crate::messaging::{outgoing_request, send_sealed_message};
+use crate::queue::{order_per_contact, MessageQueue, Priority};
+use crate::storage::{Contact, Message, SYSTEM_SENDER};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, MessageRequest, PeerTransport};
+use crate::tui::App;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use super::helpers::recording_peer;
+
+const REFUSED: &str = "Server returned status 404 Not Found";
+const UNREACHABLE: &str = "Connection refused";
+
+fn contact(uid: &str, address: &str) -> Contact {
+    let mut contact = Contact::new(
+        uid.to_string(),
+        address.to_string(),
+        vec![1; 32],
+        vec![2; 32],
+        Utc::now() + chrono::Duration::days(30),
+    );
+    contact.x25519_pubkey = None;
+    contact
+}
+
+fn text(id: &str, to: &str, timestamp: i64) -> Message {
+    Message::new(id.to_string(), "me".to_string(), to.to_string(), format!("text {}", id).into_bytes(), timestamp)
+}
+
+/// Queue for bob with m1..m4 failed for good: m1 and m3 refused (dead
+/// letters), m2 and m4 unreachable (dormant)
+fn queue_with_failures(now: i64) -> MessageQueue {
+    let mut queue = MessageQueue::new().unwrap();
+    queue.set_max_retries(1);
+    for (i, id) in ["m1", "m2", "m3", "m4"].iter().enumerate() {
+        queue.enqueue(text(id, "bob", 1000 + i as i64), Priority::Normal).unwrap();
+        let error = if i % 2 == 0 { REFUSED } else { UNREACHABLE };
+        queue.record_error(id, error).unwrap();
+        queue.mark_failed_at(id, now).unwrap();
+    }
+    queue
+}
+
+/// Deliver everything due, per contact oldest first, as the retry worker does
+async fn deliver_due(queue: &mut MessageQueue, transport: &dyn PeerTransport, contact: &Contact, now: i64) {
+    let due = order_per_contact(queue.fetch_pending_at(now).unwrap(), &HashMap::new());
+    for queued in due {
+        let (request, _) = outgoing_request(&queued.message, "text", None, contact).unwrap();
+        transport.send_message(contact, &request).await.unwrap();
+        queue.mark_success(&queued.message.id).unwrap();
+    }
+}
+
+fn received_ids(received: &Arc<Mutex<Vec<MessageRequest>>>) -> Vec<String> {
+    received.lock().unwrap().iter().filter_map(|request| request.message_id.clone()).collect()
+}
+
+#[test]
+fn test_failures_accumulate_and_requeue_in_order_after_address_fix() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let now = Utc::now().timestamp_millis();
+    let mut queue = queue_with_failures(now);
+
+    // Nothing left to retry, but all four are listed with why they failed
+    assert!(queue.fetch_pending_at(now + 3_600_000).unwrap().is_empty());
+    let failed = queue.failed_for("bob").unwrap();
+    let ids: Vec<&str> = failed.iter().map(|f| f.message.id.as_str()).collect();
+    assert_eq!(ids, ["m1", "m2", "m3", "m4"]);
+    assert_eq!(failed[0].reason.as_deref(), Some(REFUSED));
+    assert!(!failed[0].dormant && failed[1].dormant);
+    assert_eq!(&failed[2].message.content[..], b"text m3");
+    assert_eq!(failed[3].attempts, 1);
+    assert_eq!(queue.failed_ids_for("bob").unwrap().len(), 4);
+    assert!(queue.failed_for("carol").unwrap().is_empty());
+
+    // The address is fixed: everything goes back and arrives in its original order
+    let (_bob_peer, received) = rt.block_on(recording_peer(&network, "bob"));
+    let bob = contact("bob", "loopback://bob");
+    let all: Vec<String> = failed.iter().map(|f| f.message.id.clone()).collect();
+    let later = now + 60_000;
+    assert_eq!(queue.requeue_failed_at("bob", &all, later).unwrap(), 4);
+    assert!(queue.failed_for("bob").unwrap().is_empty());
+    let requeued = queue.fetch_pending_at(later).unwrap();
+    assert!(requeued.iter().all(|q| q.attempts == 0));
+
+    let transport = LoopbackTransport::new(&network);
+    rt.block_on(deliver_due(&mut queue, &transport, &bob, later));
+    assert_eq!(received_ids(&received), ["m1", "m2", "m3", "m4"]);
+    assert!(queue.fetch_all_pending().unwrap().is_empty());
+}
+
+#[test]
+fn test_selective_requeue_leaves_the_rest_failed() {
+    let now = Utc::now().timestamp_millis();
+    let mut queue = queue_with_failures(now);
+
+    // Unknown IDs and other contacts' messages are ignored
+    let chosen = ["m3".to_string(), "m2".to_string(), "nope".to_string()];
+    assert_eq!(queue.requeue_failed_at("bob", &chosen, now).unwrap(), 2);
+    assert_eq!(queue.requeue_failed_at("carol", &["m1".to_string()], now).unwrap(), 0);
+
+    let due: Vec<String> = order_per_contact(queue.fetch_pending_at(now).unwrap(), &HashMap::new())
+        .into_iter()
+        .map(|q| q.message.id)
+        .collect();
+    assert_eq!(due, ["m2", "m3"]);
+    let left: Vec<String> = queue.failed_for("bob").unwrap().into_iter().map(|f| f.message.id).collect();
+    assert_eq!(left, ["m1", "m4"]);
+
+    // Re-queueing one twice only counts it once
+    assert_eq!(queue.requeue_failed_at("bob", &chosen, now).unwrap(), 0);
+}
+
+#[test]
+fn test_requeued_messages_go_before_newly_composed_ones() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (_bob_peer, received) = rt.block_on(recording_peer(&network, "bob"));
+    let bob = contact("bob", "loopback://bob");
+    let transport = LoopbackTransport::new(&network);
+    let now = Utc::now().timestamp_millis();
+    let mut queue = queue_with_failures(now);
+    let all = ["m1", "m2", "m3", "m4"].map(String::from);
+    queue.requeue_failed_at("bob", &all, now).unwrap();
+
+    // A new message while the old ones wait is queued behind them, not sent past them
+    let fresh = text("m5", "bob", now);
+    let (delivered, _) =
+        rt.block_on(send_sealed_message(&transport, &mut queue, None, &bob, &fresh, Priority::Normal)).unwrap();
+    assert!(!delivered);
+    assert!(received_ids(&received).is_empty());
+
+    // Interactive messages are not held back
+    let ping = text("p1", "bob", now);
+    let (delivered, _) =
+        rt.block_on(send_sealed_message(&transport, &mut queue, None, &bob, &ping, Priority::Urgent)).unwrap();
+    assert!(delivered);
+
+    // The worker still keeps composition order when the new one comes due first
+    let due_at = now + 1000;
+    let mut due = queue.fetch_pending_at(due_at).unwrap();
+    due.rotate_right(1);
+    let ordered: Vec<String> = order_per_contact(due, &HashMap::new()).into_iter().map(|q| q.message.id).collect();
+    assert_eq!(ordered, ["m1", "m2", "m3", "m4", "m5"]);
+
+    // And a later message is held while an older one still waits for its retry
+    let waiting = HashMap::from([("bob".to_string(), 1000)]);
+    let due = queue.fetch_pending_at(due_at).unwrap();
+    assert!(order_per_contact(due, &waiting).is_empty());
+
+    rt.block_on(deliver_due(&mut queue, &transport, &bob, due_at));
+    assert_eq!(received_ids(&received), ["p1", "m1", "m2", "m3", "m4", "m5"]);
+    assert!(!queue.has_waiting_for("bob").unwrap());
+}
+
+#[test]
+fn test_review_in_chat_view_requeues_with_a_notice() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.contacts.push(contact("bob", "loopback://bob"));
+    app.app_state.get_or_create_chat("bob");
+    let now = Utc::now().timestamp_millis();
+    app.queue.set_max_retries(1);
+    for (i, id) in ["m1", "m2", "m3"].iter().enumerate() {
+        app.queue.enqueue(text(id, "bob", 1000 + i as i64), Priority::Normal).unwrap();
+        app.queue.record_error(id, REFUSED).unwrap();
+        app.queue.mark_failed_at(id, now).unwrap();
+    }
+    app.save_state().unwrap();
+    app.open_chat("bob");
+    assert_eq!(app.chat_view_screen.as_ref().unwrap().failed_ids.len(), 3);
+
+    // Mark the last one and re-queue only it
+    app.open_failed_review();
+    let review = app.chat_view_screen.as_mut().unwrap().failed_review.as_mut().unwrap();
+    assert_eq!(review.messages.len(), 3);
+    review.move_selection(true);
+    review.move_selection(true);
+    review.toggle_marked();
+    app.requeue_failed(false);
+    let notices = |app: &App| -> Vec<String> {
+        let chat = app.app_state.get_chat("bob").unwrap();
+        chat.messages
+            .iter()
+            .filter(|m| m.sender == SYSTEM_SENDER)
+            .map(|m| String::from_utf8_lossy(&m.content).into_owned())
+            .collect()
+    };
+    assert_eq!(notices(&app), ["Re-queued 1 failed message(s) in their original order"]);
+    let screen = app.chat_view_screen.as_ref().unwrap();
+    assert!(screen.failed_review.is_none());
+    assert_eq!(screen.failed_ids.len(), 2);
+    assert!(screen.queued_since.contains_key("m3"));
+
+    // Then the rest at once
+    app.open_failed_review();
+    app.requeue_failed(true);
+    assert_eq!(notices(&app).last().unwrap(), "Re-queued 2 failed message(s) in their original order");
+    assert!(app.chat_view_screen.as_ref().unwrap().failed_ids.is_empty());
+
+    // Nothing left to review
+    app.open_failed_review();
+    let screen = app.chat_view_screen.as_ref().unwrap();
+    assert!(screen.failed_review.is_none());
+    assert_eq!(screen.status_message.as_deref(), Some("No failed messages"));
+}
@@ -0,0 +1,244 @@
+// Edits tests - edit round trip with history, queued local-only edits, the time window, correction fallback and the "(edited)" marker
+
+use crate::crypto::KeyPair;
+use crate::edits::*;
+use crate::queue::Priority;
+use crate::sealing::{open_request, ENCRYPTED_EDIT_TYPE};
+use crate::storage::{Contact, Message, Storage};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, MessageRequest, TransportRegistry};
+use crate::tui::ui::ui;
+use crate::tui::App;
+use chrono::{Duration, Utc};
+use ratatui::{backend::TestBackend, Terminal};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use super::helpers::{recording_peer, settle};
+
+/// App with bob's chat open, holding our message "m1" sent `sent_ago` ago
+fn app_with_sent_message(temp_dir: &TempDir, network: &LoopbackNetwork, bob: Contact, sent_ago: Duration) -> App {
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(network)));
+    let (me, bob_uid) = (app.keypair.uid.to_string(), bob.uid.clone());
+    app.app_state.contacts.push(bob);
+    let sent_at = (Utc::now() - sent_ago).timestamp_millis();
+    app.app_state
+        .get_or_create_chat(&bob_uid)
+        .append_message(Message::new("m1".to_string(), me, bob_uid, b"see you at 5".to_vec(), sent_at));
+    app.save_state().unwrap();
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+    app
+}
+
+/// Select the most recent message and start editing it
+fn edit_last(app: &mut App) {
+    let uid = app.chat_view_screen.as_ref().unwrap().contact_uid.clone();
+    let chat = app.app_state.get_chat(&uid).unwrap().clone();
+    app.chat_view_screen.as_mut().unwrap().start_selection(&chat);
+    app.start_edit_selected();
+}
+
+fn typed(received: &Arc<Mutex<Vec<MessageRequest>>>, message_type: &str) -> Vec<MessageRequest> {
+    received.lock().unwrap().iter().filter(|r| r.message_type == message_type).cloned().collect()
+}
+
+#[test]
+fn test_edit_round_trip_keeps_history() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (_receiver, received) = rt.block_on(recording_peer(&network, "bob"));
+    let bob = KeyPair::generate().unwrap();
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut contact = Contact::new(
+        bob.uid.to_string(),
+        "loopback://bob".to_string(),
+        bob.public_key.clone(),
+        bob.x25519_public.clone(),
+        Utc::now() + Duration::days(30),
+    );
+    contact.supports_edits = true;
+    let mut app = app_with_sent_message(&temp_dir, &network, contact, Duration::minutes(1));
+
+    edit_last(&mut app);
+    let screen = app.chat_view_screen.as_mut().unwrap();
+    assert_eq!(screen.editing.as_deref(), Some("m1"));
+    assert_eq!(screen.input, "see you at 5");
+    screen.clear_input();
+    screen.input = "see you at 6\u{7}".to_string();
+    app.send_message_in_chat();
+    settle();
+
+    // Edited in place on our side, the old text kept
+    let chat = app.app_state.get_chat(&bob.uid.to_string()).unwrap();
+    assert_eq!(chat.messages.len(), 1);
+    assert_eq!(&*chat.messages[0].content, b"see you at 6");
+    assert_eq!(chat.messages[0].edit_history.len(), 1);
+    assert_eq!(chat.messages[0].edit_history[0].content, b"see you at 5");
+    assert!(app.chat_view_screen.as_ref().unwrap().editing.is_none());
+
+    // One sealed edit on the wire, naming the message by the id we sent it with
+    let requests = typed(&received, ENCRYPTED_EDIT_TYPE);
+    assert_eq!(requests.len(), 1);
+    let alice = Contact::new(
+        app.keypair.uid.to_string(),
+        "loopback://alice".to_string(),
+        app.keypair.public_key.clone(),
+        app.keypair.x25519_public.clone(),
+        Utc::now() + Duration::days(30),
+    );
+    let opened = open_request(requests[0].clone(), &bob, std::slice::from_ref(&alice)).unwrap();
+    assert_eq!(opened.message_type, EDIT_TYPE);
+    let edit = EditRequest::from_payload(&opened.payload).unwrap();
+    assert_eq!(edit.message_id, "m1");
+    assert_eq!(edit.content, b"see you at 6");
+
+    // Bob applies it to the copy he received as "m1", once
+    let mut bob_chat = crate::storage::Chat::new(alice.uid.clone());
+    let mut original = Message::new("local".to_string(), alice.uid.clone(), bob.uid.to_string(), b"see you at 5".to_vec(), edit.edited_at - 60_000);
+    original.remote_id = Some("m1".to_string());
+    bob_chat.append_message(original);
+    assert!(!apply_incoming_edit(&mut bob_chat, "mallory_uid", &edit));
+    assert!(apply_incoming_edit(&mut bob_chat, &alice.uid, &edit));
+    assert!(!apply_incoming_edit(&mut bob_chat, &alice.uid, &edit));
+
+    // The history survives storage
+    let storage = Storage::new_in_memory().unwrap();
+    storage.save_contact(&alice).unwrap();
+    storage.save_chat(&bob_chat).unwrap();
+    let loaded = &storage.load_chats().unwrap()[0].messages[0];
+    assert_eq!(&*loaded.content, b"see you at 6");
+    assert_eq!(loaded.remote_id.as_deref(), Some("m1"));
+    assert_eq!(loaded.edit_history, bob_chat.messages[0].edit_history);
+    assert!(loaded.is_edited());
+}
+
+#[test]
+fn test_queued_edit_stays_local() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (_receiver, received) = rt.block_on(recording_peer(&network, "bob"));
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut contact = Contact::new("bob_uid".to_string(), "loopback://bob".to_string(), vec![1; 32], vec![], Utc::now() + Duration::days(30));
+    contact.supports_edits = true;
+    let mut app = app_with_sent_message(&temp_dir, &network, contact, Duration::minutes(1));
+    let queued = app.app_state.get_chat("bob_uid").unwrap().messages[0].clone();
+    app.queue.enqueue(queued, Priority::Normal).unwrap();
+    received.lock().unwrap().clear();
+
+    edit_last(&mut app);
+    app.chat_view_screen.as_mut().unwrap().input = "see you at 7".to_string();
+    app.send_message_in_chat();
+    settle();
+
+    // The queued row carries the new text; nothing else went out
+    let pending = app.queue.fetch_all_pending().unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(&*pending[0].message.content, b"see you at 7");
+    assert!(received.lock().unwrap().is_empty());
+    let message = &app.app_state.get_chat("bob_uid").unwrap().messages[0];
+    assert_eq!(&*message.content, b"see you at 7");
+    assert_eq!(message.edit_history.len(), 1);
+    assert_eq!(app.chat_view_screen.as_ref().unwrap().status_message.as_deref(), Some("Queued message edited"));
+}
+
+#[test]
+fn test_edit_window_guard() {
+    let now = Utc::now();
+    let sent = |minutes_ago: i64| {
+        Message::new("m1".to_string(), "me".to_string(), "bob_uid".to_string(), b"hi".to_vec(), (now - Duration::minutes(minutes_ago)).timestamp_millis())
+    };
+    assert!(check_editable(&sent(14), "me", DEFAULT_EDIT_WINDOW_MINUTES, now).is_ok());
+    assert!(check_editable(&sent(16), "me", DEFAULT_EDIT_WINDOW_MINUTES, now).is_err());
+    assert!(check_editable(&sent(16), "me", 30, now).is_ok());
+    assert!(check_editable(&sent(0), "me", 0, now).is_err());
+    assert!(check_editable(&sent(0), "someone_else", DEFAULT_EDIT_WINDOW_MINUTES, now).is_err());
+
+    // In the app the old message never reaches the input
+    let network = LoopbackNetwork::new();
+    let temp_dir = TempDir::new().unwrap();
+    let contact = Contact::new("bob_uid".to_string(), "loopback://bob".to_string(), vec![1; 32], vec![], now + Duration::days(30));
+    let mut app = app_with_sent_message(&temp_dir, &network, contact, Duration::minutes(20));
+    edit_last(&mut app);
+    let screen = app.chat_view_screen.as_ref().unwrap();
+    assert!(screen.editing.is_none());
+    assert!(screen.input.is_empty());
+    assert!(screen.status_message.as_deref().unwrap().contains("within 15 minutes"));
+}
+
+#[test]
+fn test_correction_fallback_without_capability() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (_receiver, received) = rt.block_on(recording_peer(&network, "bob"));
+
+    let temp_dir = TempDir::new().unwrap();
+    let contact = Contact::new("bob_uid".to_string(), "loopback://bob".to_string(), vec![1; 32], vec![], Utc::now() + Duration::days(30));
+    let mut app = app_with_sent_message(&temp_dir, &network, contact, Duration::minutes(1));
+
+    edit_last(&mut app);
+    app.chat_view_screen.as_mut().unwrap().input = "see you at 6".to_string();
+    app.send_message_in_chat();
+    // Bob has no encryption key, so the correction is previewed first
+    assert!(app.chat_view_screen.as_ref().unwrap().send_preview.is_some());
+    app.answer_send_preview(true);
+    settle();
+
+    // An ordinary message quoting the original; no edit on the wire
+    assert!(typed(&received, EDIT_TYPE).is_empty());
+    let texts = typed(&received, "text");
+    assert_eq!(texts.len(), 1);
+    assert_eq!(texts[0].payload, b"> see you at 5\nsee you at 6");
+    assert_eq!(correction_text("a\nb", "c"), "> a\n> b\nc");
+
+    // The original stays as sent; the correction is a new message
+    let chat = app.app_state.get_chat("bob_uid").unwrap();
+    assert_eq!(chat.messages.len(), 2);
+    assert!(!chat.messages[0].is_edited());
+    assert_eq!(&*chat.messages[0].content, b"see you at 5");
+    assert!(app.chat_view_screen.as_ref().unwrap().editing.is_none());
+    assert_eq!(EditRoute::choose(false, false), EditRoute::Correction);
+    assert_eq!(EditRoute::choose(true, false), EditRoute::LocalOnly);
+}
+
+#[test]
+fn test_edited_marker_and_diff_render() {
+    assert_eq!(
+        word_diff("see you at 5 then", "see you at 6 then"),
+        vec![
+            DiffSpan::Same("see you at".to_string()),
+            DiffSpan::Removed("5".to_string()),
+            DiffSpan::Added("6".to_string()),
+            DiffSpan::Same("then".to_string()),
+        ]
+    );
+    assert_eq!(word_diff("", "hi there"), vec![DiffSpan::Added("hi there".to_string())]);
+
+    let network = LoopbackNetwork::new();
+    let temp_dir = TempDir::new().unwrap();
+    let contact = Contact::new("bob_uid".to_string(), "loopback://bob".to_string(), vec![1; 32], vec![], Utc::now() + Duration::days(30));
+    let mut app = app_with_sent_message(&temp_dir, &network, contact, Duration::minutes(1));
+    let render = |app: &App| {
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|f| ui(f, app)).unwrap();
+        terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect::<String>()
+    };
+    assert!(!render(&app).contains("(edited)"));
+
+    let edited_at = Utc::now().timestamp_millis();
+    app.app_state.get_chat_mut("bob_uid").unwrap().messages_mut()[0].replace_content(b"see you at 6".to_vec(), edited_at);
+    let rendered = render(&app);
+    assert!(rendered.contains("see you at 6 (edited)"));
+
+    // The details popup of the selected message shows the change word by word
+    edit_last(&mut app);
+    let screen = app.chat_view_screen.as_mut().unwrap();
+    screen.cancel_edit();
+    let chat = app.app_state.get_chat("bob_uid").unwrap().clone();
+    let screen = app.chat_view_screen.as_mut().unwrap();
+    screen.start_selection(&chat);
+    screen.toggle_message_details();
+    let rendered = render(&app);
+    assert!(rendered.contains("see you at 5 6"), "{}", rendered);
+}
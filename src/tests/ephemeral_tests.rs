@@ -0,0 +1,244 @@
+// Ephemeral tests - temporary contact import, exclusion from broadcasts and introductions, expiry with queue purge, pre-expiry warning and keeping permanently
+
+use crate::crypto::KeyPair;
+use crate::queue::Priority;
+use crate::relay::{relay_uid_hash, Relay, RelayCapabilities, RelayEnvelope, RelayRefusal, RELAY_CAPABILITIES_TYPE};
+use crate::storage::{
+    ephemeral_warning_text, generate_contact_token, parse_contact_token, AppState, ContactIngest, Ephemeral,
+    EphemeralLifetime, Message, EPHEMERAL_WARNING_HOURS,
+};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, MessageRequest};
+use crate::tui::ui::{chat_list_rows, temporary_badge};
+use crate::tui::{App, ContactDetailsPopup};
+use chrono::{Duration, Utc};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use super::helpers::{app_through, contact_at, recording_peer, settle};
+
+/// App holding bob as a temporary contact ending at `until`, with a chat and
+/// one queued message to him
+fn app_with_temporary_contact(temp_dir: &TempDir, network: &LoopbackNetwork, bob: &KeyPair, until: chrono::DateTime<Utc>) -> App {
+    let mut app = app_through(temp_dir, LoopbackTransport::new(network));
+    let mut contact = contact_at(bob, "loopback://bob");
+    contact.ephemeral = Some(Ephemeral { until, warned: false });
+    contact.notes = "sold me a bike".to_string();
+    let bob_uid = contact.uid.clone();
+    app.app_state.add_contact(contact);
+    let message = Message::new("m1".to_string(), app.keypair.uid.to_string(), bob_uid.clone(), b"still for sale?".to_vec(), 1000);
+    app.app_state.get_or_create_chat(&bob_uid).append_message(message.clone());
+    app.queue.enqueue(message, Priority::Normal).unwrap();
+    app.save_state().unwrap();
+    app
+}
+
+#[test]
+fn test_temporary_import_persists_metadata() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (peer, _) = rt.block_on(recording_peer(&network, "bob"));
+    let pings = Arc::new(Mutex::new(0));
+    let pings_clone = pings.clone();
+    rt.block_on(peer.set_ping_handler(move |_token| {
+        *pings_clone.lock().unwrap() += 1;
+        Ok(())
+    }));
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_through(&temp_dir, LoopbackTransport::new(&network));
+    let bob = KeyPair::generate().unwrap();
+    let token = generate_contact_token("loopback://bob", &bob.public_key, &bob.private_key, &bob.x25519_public, Utc::now() + Duration::days(30)).unwrap();
+
+    // The toggle steps permanent → 24h → 7d → permanent
+    app.show_import_contact_screen();
+    let screen = app.import_contact_screen.as_mut().unwrap();
+    assert_eq!(screen.temporary, None);
+    screen.cycle_temporary();
+    assert_eq!(screen.temporary, Some(EphemeralLifetime::Day));
+    screen.cycle_temporary();
+    assert_eq!(screen.temporary, Some(EphemeralLifetime::Week));
+    screen.cycle_temporary();
+    assert_eq!(screen.temporary, None);
+    screen.cycle_temporary();
+
+    let before = Utc::now();
+    app.import_contact(parse_contact_token(&token).unwrap());
+    let status = app.import_contact_screen.as_ref().unwrap().status_message.clone().unwrap();
+    assert!(status.contains("Temporary contact imported") && status.contains("24h"), "{}", status);
+
+    // The marker is stored with the contact and survives a reload
+    let bob_uid = bob.uid.to_string();
+    let until = app.app_state.contact_by_uid(&bob_uid).unwrap().ephemeral.unwrap().until;
+    assert!(until >= before + Duration::hours(24) && until <= Utc::now() + Duration::hours(24));
+    let reloaded = AppState::load_from_db(&app.storage).unwrap();
+    assert_eq!(reloaded.contact_by_uid(&bob_uid).unwrap().ephemeral, Some(Ephemeral { until, warned: false }));
+    assert!(reloaded.get_chat(&bob_uid).is_some());
+
+    // It works normally otherwise: the introduction ping went out
+    settle();
+    assert_eq!(*pings.lock().unwrap(), 1);
+    assert!(app.chat_title(&bob_uid).contains("[temporary]"));
+}
+
+#[test]
+fn test_temporary_contacts_left_out_of_broadcasts_and_introductions() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (_alice_peer, alice_received) = rt.block_on(recording_peer(&network, "alice"));
+    let (_bob_peer, bob_received) = rt.block_on(recording_peer(&network, "bob"));
+    let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let (alice_uid, bob_uid) = (alice.uid.to_string(), bob.uid.to_string());
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_temporary_contact(&temp_dir, &network, &bob, Utc::now() + Duration::days(7));
+    app.app_state.add_contact(contact_at(&alice, "loopback://alice"));
+    app.app_state.settings.relay_enabled = true;
+
+    // Presence goes to alice only; bob learns edit support but no relay offer
+    app.announce_presence();
+    app.advertise_relay_capabilities();
+    settle();
+    let alice_requests = alice_received.lock().unwrap().clone();
+    let bob_requests = bob_received.lock().unwrap().clone();
+    assert!(alice_requests.iter().any(|r| r.message_type != RELAY_CAPABILITIES_TYPE));
+    assert!(bob_requests.iter().all(|r| r.message_type == RELAY_CAPABILITIES_TYPE));
+    let capabilities = |requests: &[MessageRequest]| {
+        let request = requests.iter().find(|r| r.message_type == RELAY_CAPABILITIES_TYPE).unwrap();
+        RelayCapabilities::from_payload(&request.payload).unwrap()
+    };
+    let to_bob = capabilities(&bob_requests);
+    assert!(to_bob.edits && !to_bob.accepts_relay && to_bob.reachable.is_empty());
+
+    // Bob is never introduced to alice as reachable through us
+    let to_alice = capabilities(&alice_requests);
+    assert!(to_alice.accepts_relay);
+    assert!(!to_alice.reachable.contains(&relay_uid_hash(&bob_uid)));
+
+    // Our relay does not carry anything from or to him
+    let mut relay = Relay::new();
+    relay.set_enabled(true);
+    relay.set_contacts(&app.app_state.contacts);
+    let request = |from: &str| MessageRequest {
+        from_uid: from.to_string(),
+        message_type: "text".to_string(),
+        payload: vec![],
+        metadata: Default::default(),
+        message_id: None,
+    };
+    let now = Utc::now();
+    assert_eq!(relay.accept(RelayEnvelope::new(&alice_uid, request(&bob_uid)), now), Err(RelayRefusal::UnknownPeer));
+    assert_eq!(relay.accept(RelayEnvelope::new(&bob_uid, request(&alice_uid)), now), Err(RelayRefusal::UnknownPeer));
+
+    // And he is never picked as a relay himself
+    let bob_contact = app.app_state.contact_by_uid_mut(&bob_uid).unwrap();
+    bob_contact.is_relay = true;
+    bob_contact.relay_reachable = vec![relay_uid_hash(&alice_uid)];
+    assert!(!bob_contact.reaches_via_relay(&relay_uid_hash(&alice_uid)));
+}
+
+#[test]
+fn test_expiry_deletes_contact_chat_and_queued_messages() {
+    let network = LoopbackNetwork::new();
+    let bob = KeyPair::generate().unwrap();
+    let bob_uid = bob.uid.to_string();
+    let start = Utc::now();
+    let until = start + Duration::days(7);
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_temporary_contact(&temp_dir, &network, &bob, until);
+    let carol = KeyPair::generate().unwrap();
+    let carol_uid = carol.uid.to_string();
+    app.app_state.add_contact(contact_at(&carol, "loopback://carol"));
+    let to_carol = Message::new("m2".to_string(), app.keypair.uid.to_string(), carol_uid.clone(), b"hi".to_vec(), 2000);
+    app.queue.enqueue(to_carol, Priority::Normal).unwrap();
+    app.save_state().unwrap();
+
+    // Nothing happens while the lifetime runs
+    assert!(!app.run_ephemeral_maintenance(start));
+    assert!(!app.run_ephemeral_maintenance(until - Duration::hours(EPHEMERAL_WARNING_HOURS + 1)));
+    assert!(app.queue.has_pending_for(&bob_uid).unwrap());
+
+    // Once it ends, the contact, chat, rows and queued messages are gone
+    assert!(app.run_ephemeral_maintenance(until + Duration::seconds(1)));
+    assert!(app.app_state.contact_by_uid(&bob_uid).is_none());
+    assert!(app.app_state.chat_by_uid(&bob_uid).is_none());
+    assert!(!app.queue.has_pending_for(&bob_uid).unwrap());
+    assert!(app.queue.has_pending_for(&carol_uid).unwrap());
+    let reloaded = AppState::load_from_db(&app.storage).unwrap();
+    assert!(reloaded.contact_by_uid(&bob_uid).is_none());
+    assert!(reloaded.get_chat(&bob_uid).is_none());
+    assert!(reloaded.contact_by_uid(&carol_uid).is_some());
+    assert!(!app.run_ephemeral_maintenance(until + Duration::seconds(2)));
+
+    // No tombstone: nothing is kept, and importing him again is a plain import
+    assert_eq!(app.storage.take_retained_contact_notes(&bob_uid).unwrap(), None);
+    assert!(matches!(app.app_state.ingest_contact(contact_at(&bob, "loopback://bob")), Ok(ContactIngest::Added)));
+    let contact = app.app_state.contact_by_uid(&bob_uid).unwrap();
+    assert!(!contact.is_ephemeral());
+    assert!(contact.notes.is_empty());
+}
+
+#[test]
+fn test_warning_added_once_before_expiry() {
+    let network = LoopbackNetwork::new();
+    let bob = KeyPair::generate().unwrap();
+    let bob_uid = bob.uid.to_string();
+    let until = Utc::now() + Duration::days(7);
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_temporary_contact(&temp_dir, &network, &bob, until);
+
+    let warning_at = until - Duration::hours(EPHEMERAL_WARNING_HOURS);
+    assert!(!app.run_ephemeral_maintenance(warning_at - Duration::minutes(1)));
+    assert!(app.run_ephemeral_maintenance(warning_at));
+    assert!(!app.run_ephemeral_maintenance(warning_at + Duration::hours(1)));
+
+    let notices: Vec<&Message> = app.app_state.chat_by_uid(&bob_uid).unwrap().messages.iter().filter(|m| m.is_system()).collect();
+    assert_eq!(notices.len(), 1);
+    assert_eq!(notices[0].display_text(), ephemeral_warning_text(until));
+    assert_eq!(notices[0].timestamp, warning_at.timestamp_millis());
+
+    // The warning and the flag are stored, so a restart does not warn again
+    let mut reloaded = AppState::load_from_db(&app.storage).unwrap();
+    assert!(reloaded.contact_by_uid(&bob_uid).unwrap().ephemeral.unwrap().warned);
+    assert!(reloaded.sweep_ephemeral(warning_at + Duration::hours(2)).is_empty());
+    assert_eq!(reloaded.get_chat(&bob_uid).unwrap().messages.iter().filter(|m| m.is_system()).count(), 1);
+
+    // A 24h contact is warned on the first pass
+    let mut state = AppState::new();
+    let mut contact = contact_at(&KeyPair::generate().unwrap(), "loopback://dave");
+    let now = Utc::now();
+    contact.ephemeral = Some(Ephemeral::new(EphemeralLifetime::Day, now));
+    let dave_uid = contact.uid.clone();
+    state.add_contact(contact);
+    assert_eq!(state.sweep_ephemeral(now).warned, vec![dave_uid]);
+}
+
+#[test]
+fn test_keep_permanently_clears_marker() {
+    let network = LoopbackNetwork::new();
+    let bob = KeyPair::generate().unwrap();
+    let bob_uid = bob.uid.to_string();
+    let now = Utc::now();
+    let until = now + Duration::hours(30);
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_temporary_contact(&temp_dir, &network, &bob, until);
+
+    // The chat list shows the badge until the contact is kept
+    let row_until = chat_list_rows(&app.app_state, now)[0].temporary_until;
+    assert_eq!(row_until, Some(until));
+    assert_eq!(temporary_badge(until, now), " [temp 30h]");
+    assert_eq!(temporary_badge(now + Duration::days(7), now), " [temp 7d]");
+
+    app.show_chat_list_screen();
+    app.chat_list_screen.as_mut().unwrap().contact_details = Some(ContactDetailsPopup::new(bob_uid.clone()));
+    app.make_contact_permanent();
+    assert!(!app.app_state.contact_by_uid(&bob_uid).unwrap().is_ephemeral());
+    assert_eq!(chat_list_rows(&app.app_state, now)[0].temporary_until, None);
+    assert!(!app.chat_title(&bob_uid).contains("[temporary]"));
+
+    // Stored that way, and the lifetime no longer applies
+    let reloaded = AppState::load_from_db(&app.storage).unwrap();
+    assert_eq!(reloaded.contact_by_uid(&bob_uid).unwrap().ephemeral, None);
+    assert!(!app.run_ephemeral_maintenance(until + Duration::days(1)));
+    assert!(app.app_state.chat_by_uid(&bob_uid).is_some());
+    assert!(app.queue.has_pending_for(&bob_uid).unwrap());
+    assert!(!app.app_state.make_permanent(&bob_uid));
+}
@@ -0,0 +1,164 @@
+// Error reports tests - ring buffer bounds and suppression counting, banner threshold and dismissal, background-thread and storage failures reaching the error log
+
+use crate::crypto::KeyPair;
+use crate::storage::{AppState, Contact, Ephemeral, ErrorSeverity, Storage};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, PeerTransport, TransportRegistry};
+use crate::tui::ui::error_banner_text;
+use crate::tui::{App, ErrorReporter, StartupTimings, ERROR_REPORT_CAPACITY};
+use chrono::{Duration, Utc};
+use rusqlite::Connection;
+use std::sync::Arc;
+use tempfile::TempDir;
+use super::helpers::contact_at;
+
+/// File-backed app holding `contacts` (each with a chat), using loopback transports
+fn file_backed_app(temp_dir: &TempDir, network: &LoopbackNetwork, contacts: Vec<Contact>) -> App {
+    let storage = Storage::new(temp_dir.path().join("pure2p.db")).unwrap();
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().unwrap());
+    for contact in contacts {
+        state.add_chat(contact.uid.clone());
+        state.add_contact(contact);
+    }
+    state.save_to_db(&storage).unwrap();
+
+    let state_path = temp_dir.path().join("app_state.json").to_string_lossy().to_string();
+    let mut app = App::new_with_storage(storage, state_path, StartupTimings::new()).unwrap();
+    app.transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(network)));
+    app
+}
+
+/// Make every save and every contact/chat deletion in the app's database fail
+fn break_storage(temp_dir: &TempDir) {
+    let conn = Connection::open(temp_dir.path().join("pure2p.db")).unwrap();
+    conn.execute_batch(
+        "CREATE TRIGGER fail_settings BEFORE INSERT ON settings BEGIN SELECT RAISE(ABORT, 'disk full'); END;
+         CREATE TRIGGER fail_contacts BEFORE DELETE ON contacts BEGIN SELECT RAISE(ABORT, 'disk full'); END;
+         CREATE TRIGGER fail_chats BEFORE DELETE ON chats BEGIN SELECT RAISE(ABORT, 'disk full'); END;
+         CREATE TRIGGER fail_soft_delete BEFORE UPDATE OF deleted_at ON chats BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_ring_buffer_bounds_and_suppression() {
+    let reports = ErrorReporter::new();
+    assert!(reports.banner(ErrorSeverity::Info).is_none());
+
+    for i in 0..ERROR_REPORT_CAPACITY + 5 {
+        let severity = if i % 2 == 0 { ErrorSeverity::Warning } else { ErrorSeverity::Info };
+        reports.report(severity, "storage", format!("failure {}", i));
+    }
+
+    // The oldest reports are dropped once the log is full
+    let kept = reports.reports();
+    assert_eq!(kept.len(), ERROR_REPORT_CAPACITY);
+    assert_eq!(kept[0].message, "failure 5");
+    assert_eq!(kept.last().unwrap().message, format!("failure {}", ERROR_REPORT_CAPACITY + 4));
+
+    // The banner shows the newest report at the threshold and counts the rest
+    let banner = reports.banner(ErrorSeverity::Warning).unwrap();
+    assert_eq!(banner.report.message, "failure 104");
+    assert_eq!(banner.suppressed, 49);
+    assert_eq!(reports.banner(ErrorSeverity::Info).unwrap().suppressed, ERROR_REPORT_CAPACITY - 1);
+    assert!(reports.banner(ErrorSeverity::Error).is_none());
+    assert!(error_banner_text(&banner).contains("storage: failure 104 (+49 more)"));
+
+    reports.report(ErrorSeverity::Error, "message queue", "queue locked");
+    let banner = reports.banner(ErrorSeverity::Error).unwrap();
+    assert_eq!((banner.report.source.as_str(), banner.suppressed), ("message queue", 0));
+    assert!(!error_banner_text(&banner).contains("more"));
+
+    // Results are passed through, errors reported
+    assert_eq!(reports.check(ErrorSeverity::Warning, "storage", Ok::<_, String>(7)), Some(7));
+    assert_eq!(reports.check::<u8, _>(ErrorSeverity::Warning, "storage", Err("row corrupt")), None);
+    assert_eq!(reports.reports().last().unwrap().message, "row corrupt");
+}
+
+#[test]
+fn test_banner_dismissal_and_threshold() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    assert!(!app.dismiss_error_banner(), "Nothing to dismiss");
+
+    app.error_reports.report(ErrorSeverity::Info, "storage", "vacuum skipped");
+    assert!(app.error_banner().is_none(), "Info is below the default threshold");
+    app.error_reports.report(ErrorSeverity::Warning, "storage", "save failed");
+    app.error_reports.report(ErrorSeverity::Warning, "storage", "save failed again");
+    assert_eq!(app.error_banner().unwrap().suppressed, 1);
+
+    // Dismissing hides the banner but keeps the log
+    assert!(app.dismiss_error_banner());
+    assert!(app.error_banner().is_none());
+    assert_eq!(app.error_reports.len(), 3);
+
+    // A new report brings it back without the dismissed ones
+    app.error_reports.report(ErrorSeverity::Warning, "message queue", "mark failed");
+    let banner = app.error_banner().unwrap();
+    assert_eq!((banner.report.message.as_str(), banner.suppressed), ("mark failed", 0));
+
+    // The threshold is a setting that survives a reload
+    app.app_state.settings.error_banner_severity = ErrorSeverity::Error;
+    assert!(app.error_banner().is_none());
+    app.save_state().unwrap();
+    let settings = app.storage.load_settings().unwrap().unwrap();
+    assert_eq!(settings.error_banner_severity, ErrorSeverity::Error);
+    app.app_state.settings.error_banner_severity = ErrorSeverity::Info;
+    assert_eq!(app.error_banner().unwrap().report.message, "mark failed");
+}
+
+#[test]
+fn test_import_thread_failure_raises_banner() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let peer = LoopbackTransport::new(&network);
+    rt.block_on(peer.set_ping_handler(|_token| Ok(())));
+    rt.block_on(peer.start_listener("bob")).unwrap();
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = file_backed_app(&temp_dir, &network, Vec::new());
+    break_storage(&temp_dir);
+
+    // The answered ping is recorded by the import thread, whose save fails too
+    let bob = KeyPair::generate().unwrap();
+    app.import_contact(contact_at(&bob, "loopback://bob"));
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    let banner = app.error_banner().expect("Import thread failure should raise the banner");
+    assert_eq!(banner.report.source, "contact import");
+    assert_eq!(banner.report.severity, ErrorSeverity::Error);
+    assert!(banner.report.message.contains("disk full"));
+    assert_eq!(banner.suppressed, 1, "The import's own save failed first");
+    assert_eq!(app.error_reports.reports()[0].source, "storage");
+}
+
+#[test]
+fn test_known_call_sites_report_failures() {
+    let network = LoopbackNetwork::new();
+    let alice = KeyPair::generate().unwrap();
+    let bob = KeyPair::generate().unwrap();
+    let mut temporary = contact_at(&bob, "loopback://bob");
+    temporary.ephemeral = Some(Ephemeral { until: Utc::now() - Duration::hours(1), warned: true });
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = file_backed_app(&temp_dir, &network, vec![contact_at(&alice, "loopback://alice"), temporary]);
+    break_storage(&temp_dir);
+
+    // Deleting a contact: the soft delete of its rows, then the save
+    app.show_chat_list_screen();
+    app.show_contact_details();
+    app.request_delete_contact();
+    app.confirm_delete_contact(false);
+    let sources: Vec<String> = app.error_reports.reports().into_iter().map(|r| r.source).collect();
+    assert_eq!(sources, ["storage", "storage"]);
+    assert!(app.error_reports.reports()[1].message.starts_with("Failed to save"));
+
+    // Expiry of a temporary contact reports the same way
+    assert!(app.run_ephemeral_maintenance(Utc::now()));
+    assert!(app.error_reports.len() >= 5);
+    assert!(app.error_reports.reports().iter().all(|r| r.severity == ErrorSeverity::Error));
+
+    // The log lines shown in Diagnostics name the source
+    let line = app.error_reports.reports()[0].log_line();
+    assert!(line.contains("error storage: ") && line.contains("disk full"), "{}", line);
+}
@@ -0,0 +1,198 @@
+// Expiry warning tests - trigger matrix against the retry schedule (backoff, quiet hours), each answer's side effects, blocking on expiry and unblocking after a new token import
+
+use crate::crypto::KeyPair;
+use crate::queue::{Priority, QueueRowState};
+use crate::retry_schedule::retry_opportunities;
+use crate::storage::{Contact, Message};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, TransportRegistry};
+use crate::tui::{expiry_warning, App, ExpiryChoice, EXPIRY_LOOKAHEAD_ATTEMPTS, TOKEN_REFRESH_REQUEST_TYPE};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 3, 2, hour, minute, 0).unwrap()
+}
+
+/// App with the chat with a contact expiring at `expiry` open; nothing reaches the contact
+fn app_chatting_with(temp_dir: &TempDir, expiry: DateTime<Utc>) -> (App, String) {
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(&LoopbackNetwork::new())));
+    app.app_state.settings.auto_send_preview = false;
+    let bob = KeyPair::generate().unwrap();
+    let contact = Contact::new(bob.uid.to_string(), "loopback://bob".to_string(), bob.public_key.clone(), bob.x25519_public.clone(), expiry);
+    let uid = contact.uid.clone();
+    app.app_state.contacts.push(contact);
+    app.app_state.get_or_create_chat(&uid);
+    app.save_state().unwrap();
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+    (app, uid)
+}
+
+/// Queue an earlier message to `uid`, as left by a failed send
+fn queue_earlier(app: &mut App, uid: &str) -> String {
+    let message = Message::new(uuid::Uuid::new_v4().to_string(), app.keypair.uid.to_string(), uid.to_string(), b"earlier".to_vec(), Utc::now().timestamp_millis());
+    let id = message.id.clone();
+    app.queue.enqueue_with_type(message, Priority::Normal, "text").unwrap();
+    id
+}
+
+fn type_and_send(app: &mut App, text: &str) {
+    app.chat_view_screen.as_mut().unwrap().input = text.to_string();
+    app.send_message_in_chat();
+}
+
+fn message_count(app: &App, uid: &str) -> usize {
+    app.app_state.get_chat(uid).map_or(0, |chat| chat.messages.len())
+}
+
+#[test]
+fn test_retry_opportunities_follow_backoff_and_quiet_hours() {
+    let start = at(12, 0);
+    let minute = Duration::minutes(1);
+
+    // Due at once, then 2, 4, 8, 16 base delays apart, dormant after five failures
+    let attempts = retry_opportunities(start, minute, 5, Duration::hours(1), |_| false);
+    let offsets: Vec<i64> = attempts.iter().map(|t| (*t - start).num_minutes()).collect();
+    assert_eq!(offsets, [0, 2, 6, 14, 30]);
+
+    // Quiet 12:05-20:00: the attempt due at 12:06 waits for the first cycle after
+    let quiet = |t: DateTime<Utc>| t >= at(12, 5) && t < at(20, 0);
+    let attempts = retry_opportunities(start, minute, 5, Duration::minutes(15), quiet);
+    assert_eq!(attempts[..3], [start, at(12, 2), at(20, 6)]);
+    assert_eq!(attempts[3], at(20, 6) + Duration::minutes(8));
+
+    // A lane that never reopens leaves no attempts to count on
+    assert!(retry_opportunities(start, minute, 5, Duration::hours(1), |_| true).is_empty());
+}
+
+#[test]
+fn test_warning_trigger_matrix() {
+    let now = at(12, 0);
+    let quiet = |t: DateTime<Utc>| t >= at(12, 5) && t < at(20, 0);
+    let paused = retry_opportunities(now, Duration::minutes(1), 5, Duration::minutes(15), quiet);
+    let running = retry_opportunities(now, Duration::minutes(1), 5, Duration::minutes(15), |_| false);
+    let horizon = running[EXPIRY_LOOKAHEAD_ATTEMPTS - 1];
+    assert_eq!(horizon, at(12, 6));
+
+    // (expiry, reachable, attempts, warned)
+    let cases = [
+        // Reachable contacts are never warned about
+        (now + Duration::hours(2), true, &paused, false),
+        // Quiet hours push the third attempt past the expiry
+        (now + Duration::hours(2), false, &paused, true),
+        (now + Duration::hours(2), false, &running, false),
+        // Right at the horizon counts as outlasting it; just before does not
+        (horizon, false, &running, false),
+        (horizon - Duration::seconds(1), false, &running, true),
+        // Already expired: the delivery banner covers it
+        (now, false, &running, false),
+        (now - Duration::hours(1), false, &paused, false),
+    ];
+    for (expiry, reachable, attempts, warned) in cases {
+        let warning = expiry_warning(expiry, reachable, attempts, now);
+        assert_eq!(warning.is_some(), warned, "expiry {} reachable {} attempts {:?}", expiry, reachable, attempts);
+    }
+
+    // Fewer attempts than the lookahead: all of them must be outlasted
+    assert!(expiry_warning(now + Duration::minutes(3), false, &running[..2], now).is_none());
+    assert!(expiry_warning(now + Duration::minutes(1), false, &running[..2], now).is_some());
+    assert!(expiry_warning(now + Duration::days(30), false, &[], now).is_some());
+
+    let warning = expiry_warning(now + Duration::hours(2), false, &paused, now).unwrap();
+    assert_eq!(warning.expires_in(), Duration::hours(2));
+    assert!(warning.text().starts_with("Contact's token expires in 2 hours - message may never be delivered"));
+    let soon = expiry_warning(now + Duration::seconds(30), false, &running, now).unwrap();
+    assert!(soon.text().contains("expires in under a minute"));
+}
+
+#[test]
+fn test_each_answer_to_the_warning() {
+    for choice in [ExpiryChoice::Cancel, ExpiryChoice::SendAnyway, ExpiryChoice::SendAndRequestRefresh] {
+        let temp_dir = TempDir::new().unwrap();
+        // With hour-long backoff the third attempt is six hours out
+        let (mut app, uid) = app_chatting_with(&temp_dir, Utc::now() + Duration::hours(3));
+        app.queue.set_base_delay_ms(60 * 60 * 1000);
+
+        // Reachable as far as we know: no warning
+        assert!(app.send_expiry_warning(&uid, Utc::now()).is_none());
+        queue_earlier(&mut app, &uid);
+        app.refresh_queued_since();
+
+        type_and_send(&mut app, "see you tomorrow");
+        let screen = app.chat_view_screen.as_ref().unwrap();
+        assert!(screen.expiry_warning.is_some(), "{:?}: not warned", choice);
+        assert!(screen.status_message.as_deref().unwrap().starts_with("Contact's token expires in 2 hours"));
+        assert_eq!(message_count(&app, &uid), 0);
+
+        app.answer_expiry_warning(choice);
+        let screen = app.chat_view_screen.as_ref().unwrap();
+        assert!(screen.expiry_warning.is_none());
+        let refresh_queued = app.queue.has_queued_type_for(&uid, TOKEN_REFRESH_REQUEST_TYPE).unwrap();
+        match choice {
+            ExpiryChoice::Cancel => {
+                assert_eq!(screen.input, "see you tomorrow");
+                assert_eq!(message_count(&app, &uid), 0);
+                assert!(!refresh_queued);
+            }
+            ExpiryChoice::SendAnyway => {
+                assert!(screen.input.is_empty());
+                assert_eq!(message_count(&app, &uid), 1);
+                assert!(!refresh_queued);
+            }
+            ExpiryChoice::SendAndRequestRefresh => {
+                assert!(screen.input.is_empty());
+                assert_eq!(message_count(&app, &uid), 1);
+                assert_eq!(screen.status_message.as_deref(), Some("Sent; token refresh requested"));
+                // Urgent, so quiet hours never hold it back
+                let pending = app.queue.fetch_all_pending().unwrap();
+                let request = pending.iter().find(|m| m.message.content.is_empty()).unwrap();
+                assert_eq!((request.priority, request.message.recipient.as_str()), (Priority::Urgent, uid.as_str()));
+                // Asked once, however often the warning is answered this way
+                assert!(!app.request_token_refresh(&uid, Utc::now()).unwrap());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_expired_contact_blocks_then_unblocks_after_import() {
+    let temp_dir = TempDir::new().unwrap();
+    let now = Utc::now();
+    let (mut app, uid) = app_chatting_with(&temp_dir, now + Duration::hours(1));
+    let earlier = queue_earlier(&mut app, &uid);
+
+    // Still valid: nothing changes
+    assert_eq!(app.sync_expired_blocks(now), (0, 0));
+    assert_eq!(app.queue.fetch_all_pending().unwrap().len(), 1);
+
+    // Expiry passes: blocked instead of retried, once
+    let later = now + Duration::hours(2);
+    assert_eq!(app.sync_expired_blocks(later), (1, 0));
+    assert_eq!(app.sync_expired_blocks(later + Duration::minutes(1)), (0, 0));
+    assert!(app.queue.fetch_pending_at(later.timestamp_millis()).unwrap().is_empty());
+    assert!(app.queue.fetch_all_pending().unwrap().is_empty());
+    assert_eq!(app.queue.earliest_next_retry().unwrap(), None);
+    assert!(app.queue.blocked_uids().unwrap().contains(&uid));
+    assert_eq!(app.queue.count_blocked().unwrap(), 1);
+    let report = app.queue.debug_report(later.timestamp_millis()).unwrap();
+    assert_eq!(report.rows[0].state, QueueRowState::Blocked);
+
+    // Used-up attempts do not matter once unblocked
+    for _ in 0..3 {
+        app.queue.mark_failed_at(&earlier, later.timestamp_millis()).unwrap();
+    }
+
+    // A new token for the contact lets the message go at once with fresh attempts
+    let contact = app.app_state.contact_by_uid(&uid).unwrap().clone();
+    let mut renewed = contact.clone();
+    renewed.expiry = Utc::now() + Duration::days(30);
+    app.show_import_contact_screen();
+    app.import_contact(renewed);
+    assert!(app.app_state.contact_by_uid(&uid).unwrap().expiry > Utc::now() + Duration::days(29));
+    assert!(app.queue.blocked_uids().unwrap().is_empty());
+    let pending = app.queue.fetch_pending_at(Utc::now().timestamp_millis()).unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!((pending[0].message.id.as_str(), pending[0].attempts), (earlier.as_str(), 0));
+}
@@ -0,0 +1,74 @@
+// Feature tests - the core API (identity, storage, queue, transport client, messaging) built and working without the TUI, and port mapping protocols left out of the build failing as unsupported
+
+use crate::crypto::KeyPair;
+use crate::messaging::send_sealed_message;
+use crate::queue::{MessageQueue, Priority, QueuedMessage};
+use crate::storage::{AppState, Contact, Message, Settings, Storage};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, PeerTransport, TransportRegistry};
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+
+#[test]
+fn test_core_api_without_tui() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let me = KeyPair::generate().unwrap();
+    let bob = KeyPair::generate().unwrap();
+
+    // Storage: identity, contacts and settings round trip
+    let storage = Storage::new_in_memory().unwrap();
+    let contact = Contact::new(
+        bob.uid.to_string(),
+        "loopback://bob".to_string(),
+        bob.public_key.clone(),
+        bob.x25519_public.clone(),
+        Utc::now() + Duration::days(30),
+    );
+    storage.save_contact(&contact).unwrap();
+    storage.save_settings(&Settings::default()).unwrap();
+    assert_eq!(storage.load_contacts().unwrap().len(), 1);
+    let mut state = AppState::new();
+    state.contacts.push(contact.clone());
+    state.get_or_create_chat(&contact.uid);
+
+    // Client: send through a transport registry, queueing while nobody listens
+    let client = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(&network)));
+    let mut queue = MessageQueue::new().unwrap();
+    let first = Message::new("m1".to_string(), me.uid.to_string(), contact.uid.clone(), b"hello".to_vec(), 1);
+    let (delivered, _) =
+        rt.block_on(send_sealed_message(&client, &mut queue, Some(&me), &contact, &first, Priority::Normal)).unwrap();
+    assert!(!delivered);
+    let pending: Vec<QueuedMessage> = queue.fetch_all_pending().unwrap();
+    assert_eq!(pending.len(), 1);
+
+    // Once bob listens, the queued message goes out
+    let peer = LoopbackTransport::new(&network);
+    rt.block_on(async {
+        peer.set_new_message_handler(|_| Ok(())).await;
+        peer.start_listener("bob").await.unwrap();
+    });
+    let (request, _) = crate::messaging::outgoing_request(&pending[0].message, "text", Some(&me), &contact).unwrap();
+    rt.block_on(client.send_message(&contact, &request)).unwrap();
+    queue.mark_success("m1").unwrap();
+    assert!(queue.fetch_all_pending().unwrap().is_empty());
+}
+
+#[cfg(not(feature = "connectivity-upnp"))]
+#[test]
+fn test_upnp_left_out_fails_as_unsupported() {
+    use crate::connectivity::{delete_upnp_mapping, try_upnp_mapping, IpProtocol, MappingError};
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    assert!(matches!(rt.block_on(try_upnp_mapping(8080, 3600)), Err(MappingError::NotSupported)));
+    assert!(matches!(rt.block_on(delete_upnp_mapping(8080, IpProtocol::TCP)), Err(MappingError::NotSupported)));
+}
+
+#[cfg(not(feature = "connectivity-pcp-natpmp"))]
+#[test]
+fn test_pcp_natpmp_left_out_fail_as_unsupported() {
+    use crate::connectivity::{try_natpmp_mapping_with_report, try_pcp_mapping, IpProtocol, MappingError};
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    assert!(matches!(rt.block_on(try_pcp_mapping(8080, 3600)), Err(MappingError::NotSupported)));
+    let report = rt.block_on(try_natpmp_mapping_with_report(8080, 3600, IpProtocol::TCP));
+    assert!(matches!(report.result, Err(MappingError::NotSupported)));
+    assert!(report.probes.is_empty());
+}
@@ -0,0 +1,95 @@
+//! Shared test helpers for peers on the loopback transport
+
+use crate::crypto::KeyPair;
+use crate::storage::Contact;
+use crate::transport::{LoopbackNetwork, LoopbackTransport, MessageRequest, PeerTransport};
+#[cfg(feature = "tui")]
+use crate::{
+    storage::{AppState, Storage},
+    transport::TransportRegistry,
+    tui::App,
+};
+use chrono::{Duration, Utc};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "tui")]
+use tempfile::TempDir;
+
+/// Contact for `keypair` at `address`, valid for 30 days
+pub fn contact_at(keypair: &KeyPair, address: &str) -> Contact {
+    Contact::new(
+        keypair.uid.to_string(),
+        address.to_string(),
+        keypair.public_key.clone(),
+        keypair.x25519_public.clone(),
+        Utc::now() + Duration::days(30),
+    )
+}
+
+/// Start a loopback peer at `name` that records every message it receives
+pub async fn recording_peer(
+    network: &LoopbackNetwork,
+    name: &str,
+) -> (LoopbackTransport, Arc<Mutex<Vec<MessageRequest>>>) {
+    let peer = LoopbackTransport::new(network);
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let received_clone = received.clone();
+    peer.set_new_message_handler(move |msg| {
+        received_clone.lock().unwrap().push(msg);
+        Ok(())
+    })
+    .await;
+    peer.start_listener(name).await.expect("Failed to start loopback listener");
+    (peer, received)
+}
+
+/// In-memory storage holding only our identity
+#[cfg(feature = "tui")]
+pub fn storage_with_identity() -> Storage {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    state.save_to_db(&storage).expect("Failed to save state");
+    storage
+}
+
+/// Pid of a process that has exited
+#[cfg(feature = "tui")]
+pub fn dead_pid() -> u32 {
+    let mut child = std::process::Command::new("true").spawn().unwrap();
+    let pid = child.id();
+    child.wait().unwrap();
+    pid
+}
+
+/// Wait for background sender threads to hand their requests over
+#[cfg(feature = "tui")]
+pub fn settle() {
+    std::thread::sleep(std::time::Duration::from_millis(300));
+}
+
+/// App keeping its state in `temp_dir`
+#[cfg(feature = "tui")]
+pub fn new_app(temp_dir: &TempDir) -> App {
+    App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).expect("Failed to create app")
+}
+
+/// App sending through `transport` only
+#[cfg(feature = "tui")]
+pub fn app_through(temp_dir: &TempDir, transport: impl PeerTransport + 'static) -> App {
+    let mut app = new_app(temp_dir);
+    app.transports = TransportRegistry::new().with(Arc::new(transport));
+    app
+}
+
+/// App with `contact`'s chat open, sending through `transport` only
+#[cfg(feature = "tui")]
+pub fn app_chatting_through(temp_dir: &TempDir, transport: impl PeerTransport + 'static, contact: Contact) -> App {
+    let mut app = app_through(temp_dir, transport);
+    let uid = contact.uid.clone();
+    app.app_state.contacts.push(contact);
+    app.app_state.get_or_create_chat(&uid);
+    app.save_state().unwrap();
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+    app
+}
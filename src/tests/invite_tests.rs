@@ -0,0 +1,202 @@
+use crate::crypto::KeyPair;
+use crate::invite::*;
+use crate::storage::{Settings, Storage};
+use crate::transport::Transport;
+use chrono::{Duration, Utc};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+const TOKEN: &str = "contact-token";
+
+fn source(last: u8) -> IpAddr {
+    IpAddr::V4(Ipv4Addr::new(192, 0, 2, last))
+}
+
+#[test]
+fn test_code_length_validation() {
+    assert!(validate_code_length(MIN_INVITE_CODE_LENGTH).is_ok());
+    assert!(validate_code_length(MAX_INVITE_CODE_LENGTH).is_ok());
+    assert!(validate_code_length(MIN_INVITE_CODE_LENGTH - 1).is_err());
+    assert!(validate_code_length(MAX_INVITE_CODE_LENGTH + 1).is_err());
+
+    let code = generate_invite_code(12).unwrap();
+    assert_eq!(code.len(), 12);
+    assert_eq!(code_entropy_bits(12), 60);
+    assert!(generate_invite_code(4).is_err());
+}
+
+#[test]
+fn test_settings_invite_code_length() {
+    let mut settings = Settings::default();
+    assert_eq!(settings.invite_code_length, DEFAULT_INVITE_CODE_LENGTH);
+
+    settings.set_invite_code_length(14).unwrap();
+    assert_eq!(settings.invite_code_length, 14);
+    assert!(settings.set_invite_code_length(6).is_err());
+    assert_eq!(settings.invite_code_length, 14);
+}
+
+#[test]
+fn test_weak_code_warning() {
+    assert!(weak_code_warning(8, Duration::days(7)).is_some());
+    assert!(weak_code_warning(8, Duration::hours(12)).is_none());
+    assert!(weak_code_warning(STRONG_INVITE_CODE_LENGTH, Duration::days(7)).is_none());
+}
+
+#[test]
+fn test_hash_ignores_case_and_separators() {
+    assert_eq!(hash_invite_code("abcd-efgh"), hash_invite_code("ABCD EFGH"));
+    assert_ne!(hash_invite_code("ABCDEFGH"), hash_invite_code("ABCDEFGJ"));
+}
+
+#[test]
+fn test_constant_time_eq() {
+    assert!(constant_time_eq(b"same", b"same"));
+    assert!(!constant_time_eq(b"same", b"sane"));
+    assert!(!constant_time_eq(b"short", b"longer"));
+}
+
+#[test]
+fn test_redeem_valid_code() {
+    let mut book = InviteBook::new();
+    let now = Utc::now();
+    let code = book.create(TOKEN.to_string(), 10, Duration::hours(1), None, now).unwrap();
+
+    // Codes can be typed in lower case with separators
+    let typed = format!("{}-{}", &code[..5], &code[5..]).to_lowercase();
+    assert_eq!(book.redeem(source(1), &InviteRedemption::new(typed), now).unwrap(), TOKEN);
+    // Unbound invites stay redeemable until they expire
+    assert!(book.redeem(source(2), &InviteRedemption::new(&code), now).is_ok());
+}
+
+#[test]
+fn test_redeem_rejections() {
+    let mut book = InviteBook::new();
+    let now = Utc::now();
+    let code = book.create(TOKEN.to_string(), 10, Duration::hours(1), None, now).unwrap();
+
+    assert_eq!(
+        book.redeem(source(1), &InviteRedemption::new("WRONGCODE1"), now),
+        Err(InviteRejection::UnknownCode)
+    );
+    assert_eq!(
+        book.redeem(source(1), &InviteRedemption::new(&code), now + Duration::hours(2)),
+        Err(InviteRejection::Expired)
+    );
+    assert_eq!(InviteRejection::UnknownCode.status_code(), InviteRejection::Expired.status_code());
+}
+
+#[test]
+fn test_rate_limiter_locks_out_after_failures() {
+    let mut book = InviteBook::new();
+    let now = Utc::now();
+    let code = book.create(TOKEN.to_string(), 10, Duration::hours(1), None, now).unwrap();
+
+    for _ in 0..MAX_FAILURES_PER_WINDOW {
+        let rejection = book.redeem(source(1), &InviteRedemption::new("WRONGCODE1"), now).unwrap_err();
+        assert!(rejection.is_audited());
+    }
+
+    // Even the right code is refused while locked out, and the refusal is not audited
+    let result = book.redeem(source(1), &InviteRedemption::new(&code), now);
+    assert!(matches!(result, Err(InviteRejection::RateLimited { .. })));
+    let rejection = result.unwrap_err();
+    assert_eq!(rejection.status_code(), 429);
+    assert!(!rejection.is_audited());
+
+    // Other sources are unaffected, and the lockout ends
+    assert!(book.redeem(source(2), &InviteRedemption::new(&code), now).is_ok());
+    let later = now + Duration::seconds(BASE_LOCKOUT_SECS + 1);
+    assert!(book.redeem(source(1), &InviteRedemption::new(&code), later).is_ok());
+}
+
+#[test]
+fn test_rate_limiter_lockout_grows() {
+    let mut limiter = InviteRateLimiter::new();
+    let ip = source(1);
+    let mut now = Utc::now();
+
+    for round in 0..3 {
+        for _ in 0..MAX_FAILURES_PER_WINDOW {
+            limiter.record_failure(ip, now);
+        }
+        let lockout = BASE_LOCKOUT_SECS << round;
+        assert!(limiter.check(ip, now + Duration::seconds(lockout - 1)).is_err());
+        assert!(limiter.check(ip, now + Duration::seconds(lockout)).is_ok());
+        now += Duration::seconds(lockout);
+    }
+
+    // Failures spread beyond the window never trigger a lockout
+    let other = source(2);
+    for i in 0..(MAX_FAILURES_PER_WINDOW as i64 * 2) {
+        limiter.record_failure(other, now + Duration::seconds(i * FAILURE_WINDOW_SECS));
+    }
+    assert!(limiter.check(other, now + Duration::seconds(20 * FAILURE_WINDOW_SECS)).is_ok());
+}
+
+#[test]
+fn test_key_bound_invite() {
+    let invitee = KeyPair::generate().unwrap();
+    let stranger = KeyPair::generate().unwrap();
+    let mut book = InviteBook::new();
+    let now = Utc::now();
+    let code = book
+        .create(TOKEN.to_string(), 10, Duration::hours(1), Some(&invitee.public_key), now)
+        .unwrap();
+
+    // A plain code or someone else's key is not enough
+    assert_eq!(
+        book.redeem(source(1), &InviteRedemption::new(&code), now),
+        Err(InviteRejection::KeyMismatch)
+    );
+    let forged = InviteRedemption::signed(&code, &stranger).unwrap();
+    assert_eq!(book.redeem(source(1), &forged, now), Err(InviteRejection::KeyMismatch));
+
+    // The bound key redeems it once
+    let redemption = InviteRedemption::signed(&code, &invitee).unwrap();
+    assert_eq!(book.redeem(source(1), &redemption, now).unwrap(), TOKEN);
+    assert_eq!(book.redeem(source(1), &redemption, now), Err(InviteRejection::AlreadyRedeemed));
+    assert_eq!(book.active_count(now), 0);
+
+    assert!(book.create(TOKEN.to_string(), 10, Duration::hours(1), Some(&[0u8; 16]), now).is_err());
+}
+
+#[test]
+fn test_failed_redemption_audit_log() {
+    let storage = Storage::new_in_memory().unwrap();
+    audit_failed_redemption(&storage, source(7), &InviteRejection::UnknownCode).unwrap();
+
+    let logs = storage.get_request_logs(10).unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].request_type, "invite");
+    assert_eq!(logs[0].target_ip.as_deref(), Some("192.0.2.7"));
+    assert_eq!(logs[0].status_code, Some(403));
+    assert!(!logs[0].success);
+    assert_eq!(logs[0].error_message.as_deref(), Some("unknown invite code"));
+}
+
+#[tokio::test]
+async fn test_invite_endpoint_roundtrip() {
+    let mut transport = Transport::new();
+    let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    transport.start(addr).await.expect("Failed to start transport");
+    let address = transport.local_addr().unwrap().to_string();
+
+    let code = transport
+        .invites()
+        .lock()
+        .unwrap()
+        .create(TOKEN.to_string(), 10, Duration::hours(1), None, Utc::now())
+        .unwrap();
+
+    let client = Transport::new();
+    let token = client.redeem_invite(&address, &InviteRedemption::new(&code)).await.unwrap();
+    assert_eq!(token, TOKEN);
+
+    let err = client
+        .redeem_invite(&address, &InviteRedemption::new("WRONGCODE1"))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("403"));
+
+    transport.stop();
+}
@@ -0,0 +1,163 @@
+// Journal tests - hash chain construction and verification (tampered middle record), delivered-only journaling, signed range export, seal on disable
+
+use crate::crypto::KeyPair;
+use crate::storage::{
+    export_journal, verify_chain, verify_journal_export, Chat, JournalBreak, JournalBreakReason, JournalExportFormat,
+    JournalKind, JournalRecord, Message, JOURNAL_CSV_HEADER, JOURNAL_GENESIS_HASH,
+};
+use crate::tui::{App, DeliveryEvent, DeliveryUpdate, SettingsScreen};
+use chrono::{Duration, TimeZone, Utc};
+use tempfile::TempDir;
+
+/// Three delivered records, one minute apart
+fn chain() -> Vec<JournalRecord> {
+    let start = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+    let mut records: Vec<JournalRecord> = Vec::new();
+    for (i, content) in [b"one".as_slice(), b"two", b"three"].iter().enumerate() {
+        let at = start + Duration::minutes(i as i64);
+        records.push(JournalRecord::delivered(records.last(), at, "bob_uid", content, None));
+    }
+    records
+}
+
+/// App with the journal on and a chat holding one outgoing message per id
+fn journal_app(message_ids: &[&str]) -> (App, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.settings.journal_enabled = true;
+    let own_uid = app.keypair.uid.to_string();
+    let mut chat = Chat::new("bob_uid".to_string());
+    for id in message_ids {
+        chat.append_message(Message::new(id.to_string(), own_uid.clone(), "bob_uid".to_string(), b"hello".to_vec(), 0));
+    }
+    app.app_state.chats.push(chat);
+    (app, temp_dir)
+}
+
+#[test]
+fn test_chain_links_and_tampered_middle_record_is_found() {
+    let records = chain();
+    assert_eq!(records.iter().map(|r| r.seq).collect::<Vec<_>>(), [1, 2, 3]);
+    assert_eq!(records[0].prev_hash, JOURNAL_GENESIS_HASH);
+    assert_eq!(records[1].prev_hash, records[0].hash);
+    assert_eq!(records[2].prev_hash, records[1].hash);
+    // Only the hash of the content is kept
+    assert_eq!(records[1].content_sha256.len(), 64);
+    assert_eq!(verify_chain(&records), None);
+
+    // Changing a middle record is reported there, even if its hash is recomputed
+    let mut tampered = records.clone();
+    tampered[1].recipient_uid = "mallory_uid".to_string();
+    assert_eq!(verify_chain(&tampered), Some(JournalBreak { seq: 2, reason: JournalBreakReason::HashMismatch }));
+    tampered[1].hash = tampered[1].compute_hash();
+    assert_eq!(verify_chain(&tampered), Some(JournalBreak { seq: 3, reason: JournalBreakReason::WrongPrevious }));
+
+    // Removing one leaves a gap
+    let mut removed = records.clone();
+    removed.remove(1);
+    assert_eq!(verify_chain(&removed), Some(JournalBreak { seq: 3, reason: JournalBreakReason::SequenceGap }));
+
+    // The stored chain reads back intact and cannot be edited in place
+    let (app, _temp_dir) = journal_app(&[]);
+    for record in &records {
+        app.storage.append_journal_record(record).unwrap();
+    }
+    assert!(app.storage.append_journal_record(&records[2]).is_err());
+    let stored = app.storage.load_journal(None, None).unwrap();
+    assert_eq!(stored, records);
+    assert_eq!(app.storage.journal_tail().unwrap().as_ref(), records.last());
+}
+
+#[test]
+fn test_only_delivered_messages_are_journaled() {
+    let (mut app, _temp_dir) = journal_app(&["m1", "m2", "m3"]);
+    let events = app.delivery_event_sender();
+
+    events.send(DeliveryEvent::new("bob_uid", DeliveryUpdate::Queued, true).with_message_id("m1")).unwrap();
+    events.send(DeliveryEvent::new("bob_uid", DeliveryUpdate::Failed, false).with_message_id("m2")).unwrap();
+    events.send(DeliveryEvent::new("bob_uid", DeliveryUpdate::PingAnswered, false)).unwrap();
+    // A delivery with no chat message behind it (e.g. a control message)
+    events.send(DeliveryEvent::new("bob_uid", DeliveryUpdate::Delivered, false).with_message_id("gone")).unwrap();
+    app.process_delivery_events();
+    assert!(app.storage.load_journal(None, None).unwrap().is_empty());
+
+    events.send(DeliveryEvent::new("bob_uid", DeliveryUpdate::Delivered, false).with_message_id("m3")).unwrap();
+    app.process_delivery_events();
+    let records = app.storage.load_journal(None, None).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].kind, JournalKind::Delivered);
+    assert_eq!(records[0].recipient_uid, "bob_uid");
+    assert_eq!(records[0].ack_signature, None);
+
+    // Nothing is journaled while the journal is off
+    app.app_state.settings.journal_enabled = false;
+    events.send(DeliveryEvent::new("bob_uid", DeliveryUpdate::Delivered, false).with_message_id("m1")).unwrap();
+    app.process_delivery_events();
+    assert_eq!(app.storage.load_journal(None, None).unwrap().len(), 1);
+}
+
+#[test]
+fn test_range_export_is_signed_by_the_identity_key() {
+    let keypair = KeyPair::generate().unwrap();
+    let records = chain();
+    let (app, _temp_dir) = journal_app(&[]);
+    for record in &records {
+        app.storage.append_journal_record(record).unwrap();
+    }
+    let range = app.storage.load_journal(Some(records[1].at), Some(records[2].at)).unwrap();
+    assert_eq!(range, records[1..]);
+
+    let csv = export_journal(&range, JournalExportFormat::Csv, &keypair).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], JOURNAL_CSV_HEADER);
+    assert_eq!(lines.len(), 4);
+    assert!(lines[1].starts_with("2,"));
+    assert!(lines[3].starts_with("#signature,"));
+    verify_journal_export(&csv, &keypair.public_key).unwrap();
+
+    let jsonl = export_journal(&range, JournalExportFormat::Jsonl, &keypair).unwrap();
+    let first: JournalRecord = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+    assert_eq!(first, records[1]);
+    verify_journal_export(&jsonl, &keypair.public_key).unwrap();
+
+    // Another key or an edited line does not verify
+    let other = KeyPair::generate().unwrap();
+    assert!(verify_journal_export(&jsonl, &other.public_key).is_err());
+    let edited = csv.replacen("bob_uid", "eve_uid", 1);
+    assert!(verify_journal_export(&edited, &keypair.public_key).is_err());
+    assert!(verify_journal_export("2,3\n", &keypair.public_key).is_err());
+}
+
+#[test]
+fn test_switching_off_seals_the_chain() {
+    let (mut app, _temp_dir) = journal_app(&["m1"]);
+    app.journal_delivery("bob_uid", "m1", Utc::now());
+
+    app.show_settings_screen();
+    let screen = app.settings_screen.as_mut().unwrap();
+    assert!(screen.journal_enabled);
+    screen.selected_field = SettingsScreen::FIELD_JOURNAL;
+    screen.add_char(' ');
+    app.save_settings();
+    assert!(!app.app_state.settings.journal_enabled);
+
+    // Nothing is deleted: the seal follows the last delivery
+    let records = app.storage.load_journal(None, None).unwrap();
+    assert_eq!(records.iter().map(|r| r.kind).collect::<Vec<_>>(), [JournalKind::Delivered, JournalKind::Sealed]);
+    assert_eq!(records[1].prev_hash, records[0].hash);
+    assert_eq!(verify_chain(&records), None);
+
+    // Saving again while off does not seal twice; switching back on continues the chain
+    app.save_settings();
+    assert_eq!(app.storage.load_journal(None, None).unwrap().len(), 2);
+    app.app_state.settings.journal_enabled = true;
+    app.journal_delivery("bob_uid", "m1", Utc::now());
+    let records = app.storage.load_journal(None, None).unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(verify_chain(&records), None);
+
+    app.show_diagnostics_screen();
+    app.verify_journal();
+    let status = app.diagnostics_screen.as_ref().unwrap().status_message.clone().unwrap();
+    assert!(status.contains("intact (3 records)"), "{}", status);
+}
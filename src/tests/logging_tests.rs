@@ -0,0 +1,154 @@
+// Logging tests - size rotation with retention pruning, line integrity under 8 concurrent writers, panics from spawned threads reaching the log and the error log, flush on shutdown
+
+use crate::logging::{install_panic_hook, rotated_path, RotatingLog, LOG_FILE_NAME, MIN_LOG_MAX_FILE_BYTES};
+use crate::storage::{ErrorSeverity, Settings, Storage};
+use crate::tui::ErrorReporter;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Everything written so far, oldest file first
+fn read_all(dir: &Path, keep: u32) -> String {
+    (0..=keep).rev().filter_map(|index| std::fs::read_to_string(rotated_path(dir, index)).ok()).collect()
+}
+
+/// Tracing dispatch writing to `log`, as `init_file_logging` sets it up
+fn dispatch_to(log: &RotatingLog) -> tracing::Dispatch {
+    tracing::Dispatch::new(tracing_subscriber::fmt().with_writer(log.clone()).with_ansi(false).finish())
+}
+
+#[test]
+fn test_rotation_at_threshold_prunes_past_retention() {
+    let temp_dir = TempDir::new().unwrap();
+    let line = format!("{}\n", "x".repeat(99));
+    let log = RotatingLog::open(temp_dir.path(), MIN_LOG_MAX_FILE_BYTES, 2).unwrap();
+
+    // 40 lines of 100 bytes fit in 4096; the 41st rotates
+    for _ in 0..40 {
+        log.write_event(line.as_bytes(), false).unwrap();
+    }
+    assert_eq!(log.status().rotations, 0);
+    log.write_event(line.as_bytes(), false).unwrap();
+    assert_eq!(log.status().rotations, 1);
+    log.flush().unwrap();
+    assert_eq!(std::fs::metadata(rotated_path(temp_dir.path(), 1)).unwrap().len(), 4000);
+    assert!(!log.status().rotated_away);
+
+    // Two more rotations: the first file is deleted, two are kept
+    for _ in 0..80 {
+        log.write_event(line.as_bytes(), false).unwrap();
+    }
+    log.flush().unwrap();
+    let status = log.status();
+    assert_eq!((status.rotations, status.files_kept, status.rotated_away), (3, 2, true));
+    assert!(!rotated_path(temp_dir.path(), 3).exists());
+    assert_eq!(status.summary(), "2 rotated files kept; older entries were rotated away");
+    for index in 0..=2 {
+        assert!(std::fs::metadata(rotated_path(temp_dir.path(), index)).unwrap().len() <= MIN_LOG_MAX_FILE_BYTES);
+    }
+
+    // Reopening with a lower retention prunes at once
+    let reopened = RotatingLog::open(temp_dir.path(), MIN_LOG_MAX_FILE_BYTES, 1).unwrap();
+    assert!(!rotated_path(temp_dir.path(), 2).exists());
+    assert_eq!(reopened.path(), temp_dir.path().join(LOG_FILE_NAME));
+    assert!(reopened.status().rotated_away);
+
+    // Both limits are settings, kept across restarts
+    let storage = Storage::new_in_memory().unwrap();
+    let settings = Settings { log_max_file_bytes: 1 << 20, log_files_kept: 9, ..Settings::default() };
+    storage.save_settings(&settings).unwrap();
+    let loaded = storage.load_settings().unwrap().unwrap();
+    assert_eq!((loaded.log_max_file_bytes, loaded.log_files_kept), (1 << 20, 9));
+}
+
+#[test]
+fn test_concurrent_writers_never_interleave_lines() {
+    let temp_dir = TempDir::new().unwrap();
+    let keep = 100;
+    let log = RotatingLog::open(temp_dir.path(), 64 * 1024, keep).unwrap();
+    let dispatch = dispatch_to(&log);
+
+    let threads: Vec<_> = (0..8)
+        .map(|thread| {
+            let dispatch = dispatch.clone();
+            std::thread::spawn(move || {
+                tracing::dispatcher::with_default(&dispatch, || {
+                    for i in 0..500 {
+                        let payload = format!("{}-{}", thread, i).repeat(10);
+                        tracing::info!(thread, i, len = payload.len(), "payload={}", payload);
+                    }
+                });
+            })
+        })
+        .collect();
+    for handle in threads {
+        handle.join().unwrap();
+    }
+    log.flush().unwrap();
+    assert!(log.status().rotations > 0, "the test should rotate while writing");
+
+    // Every line is whole: its payload matches the fields it was logged with
+    let text = read_all(temp_dir.path(), keep);
+    let mut seen = std::collections::HashSet::new();
+    for line in text.lines() {
+        let field = |name: &str| {
+            let rest = line.split(&format!("{}=", name)).nth(1).unwrap_or_else(|| panic!("broken line: {}", line));
+            rest.split(' ').next().unwrap()
+        };
+        let payload = field("payload");
+        let (thread, i) = (field("thread"), field("i"));
+        assert_eq!(payload, format!("{}-{}", thread, i).repeat(10), "broken line: {}", line);
+        assert_eq!(payload.len().to_string(), field("len"));
+        assert!(seen.insert((thread.to_string(), i.to_string())), "duplicate line: {}", line);
+    }
+    assert_eq!(seen.len(), 8 * 500);
+}
+
+#[test]
+fn test_panic_in_spawned_thread_reaches_log_and_error_log() {
+    let temp_dir = TempDir::new().unwrap();
+    let log = RotatingLog::open(temp_dir.path(), 1 << 20, 1).unwrap();
+    let reporter = ErrorReporter::new();
+    let hook_reporter = reporter.clone();
+    install_panic_hook(log.clone(), move |message| hook_reporter.report(ErrorSeverity::Error, "panic", message));
+
+    let result = std::thread::Builder::new()
+        .name("delivery-worker".to_string())
+        .spawn(|| panic!("deliberate panic for the log"))
+        .unwrap()
+        .join();
+    // Back to the default hook for the other tests
+    let _ = std::panic::take_hook();
+    assert!(result.is_err());
+
+    // Written and flushed by the hook, not at shutdown
+    let text = std::fs::read_to_string(log.path()).unwrap();
+    assert!(text.contains("PANIC thread 'delivery-worker' panicked at"), "{}", text);
+    assert!(text.contains("deliberate panic for the log"));
+    assert!(text.contains("backtrace:"));
+
+    let reports = reporter.reports();
+    let report = reports.iter().find(|r| r.message.contains("deliberate panic for the log")).unwrap();
+    assert_eq!((report.severity, report.source.as_str()), (ErrorSeverity::Error, "panic"));
+    assert!(report.message.starts_with("thread 'delivery-worker' panicked at"));
+}
+
+#[test]
+fn test_buffered_entries_reach_disk_on_flush() {
+    let temp_dir = TempDir::new().unwrap();
+    let log = RotatingLog::open(temp_dir.path(), 1 << 20, 1).unwrap();
+    let dispatch = dispatch_to(&log);
+
+    // Info stays buffered until shutdown flushes it
+    tracing::dispatcher::with_default(&dispatch, || tracing::info!("routine entry"));
+    assert!(!std::fs::read_to_string(log.path()).unwrap().contains("routine entry"));
+
+    // Warnings and errors go to disk at once, with everything before them
+    tracing::dispatcher::with_default(&dispatch, || tracing::warn!("something is off"));
+    let text = std::fs::read_to_string(log.path()).unwrap();
+    assert!(text.contains("routine entry") && text.contains("something is off"));
+
+    tracing::dispatcher::with_default(&dispatch, || tracing::info!("last words"));
+    drop(dispatch);
+    log.flush().unwrap();
+    assert!(std::fs::read_to_string(log.path()).unwrap().contains("last words"));
+}
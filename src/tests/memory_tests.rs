@@ -0,0 +1,127 @@
+// Memory tests - state clones share message content instead of copying it
+
+use crate::storage::{Chat, Contact, Message};
+use crate::tui::ui::ui;
+use crate::tui::App;
+use chrono::{Duration, Utc};
+use ratatui::{backend::TestBackend, Terminal};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use tempfile::TempDir;
+
+/// System allocator that tracks the live bytes of threads that opted in
+struct CountingAllocator;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+thread_local! {
+    static TRACKING: Cell<bool> = const { Cell::new(false) };
+    static LIVE: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+fn record(delta: isize) {
+    let _ = TRACKING.try_with(|tracking| {
+        if tracking.get() {
+            let live = LIVE.get() + delta;
+            LIVE.set(live);
+            PEAK.set(PEAK.get().max(live));
+        }
+    });
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        record(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            record(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
+
+/// Peak bytes allocated by this thread while running `f`, above where it started
+fn peak_allocation(f: impl FnOnce()) -> usize {
+    LIVE.set(0);
+    PEAK.set(0);
+    TRACKING.set(true);
+    f();
+    TRACKING.set(false);
+    PEAK.get().max(0) as usize
+}
+
+#[test]
+fn test_reload_and_render_share_message_content() {
+    const MESSAGES: usize = 20_000;
+    const CONTENT_BYTES: usize = 512;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.contacts.push(Contact::new(
+        "alice_uid".to_string(),
+        "192.168.1.100:8080".to_string(),
+        vec![0; 32],
+        vec![0; 32],
+        Utc::now() + Duration::days(30),
+    ));
+    let mut chat = Chat::new("alice_uid".to_string());
+    for i in 0..MESSAGES {
+        let sender = if i % 2 == 0 { "alice_uid" } else { "me" };
+        let content = vec![b'a' + (i % 26) as u8; CONTENT_BYTES];
+        chat.append_message(Message::new(format!("m{}", i), sender.to_string(), "alice_uid".to_string(), content, i as i64));
+    }
+    app.app_state.chats.push(chat);
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+    let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+
+    // A deep copy of the history alone is over 10 MB
+    let deep_copy = MESSAGES * CONTENT_BYTES;
+    let peak = peak_allocation(|| {
+        for _ in 0..100 {
+            let reloaded = app.app_state.clone();
+            app.app_state = reloaded;
+            terminal.draw(|f| ui(f, &app)).unwrap();
+        }
+    });
+    assert!(peak < deep_copy / 4, "peak {} bytes, budget {}", peak, deep_copy / 4);
+
+    // Mutating a clone copies the message list, never another clone's content
+    let snapshot = app.app_state.clone();
+    app.app_state.chats[0].messages_mut()[0].pinned = true;
+    assert!(!snapshot.chats[0].messages[0].pinned);
+    assert!(std::sync::Arc::ptr_eq(&snapshot.chats[0].messages[1].content, &app.app_state.chats[0].messages[1].content));
+
+    // The serialized form is that of the owned Vec<u8> and Vec<Message>
+    let message = Message::new("m".to_string(), "a".to_string(), "b".to_string(), vec![1, 2, 3], 0);
+    let json = serde_json::to_value(&message).unwrap();
+    assert_eq!(json["content"], serde_json::json!([1, 2, 3]));
+    let mut chat = Chat::new("b".to_string());
+    chat.append_message(message);
+    let cbor = serde_cbor::to_vec(&chat).unwrap();
+    let decoded: Chat = serde_cbor::from_slice(&cbor).unwrap();
+    assert_eq!(decoded.messages[0].content.as_ref(), &[1, 2, 3]);
+    assert_eq!(serde_json::to_value(&chat).unwrap()["messages"][0]["content"], serde_json::json!([1, 2, 3]));
+}
@@ -83,6 +83,70 @@ async fn test_send_message_with_type_failure_queues() {
     assert_eq!(queued_messages[0].priority, Priority::High);
 }
 
+#[tokio::test]
+async fn test_send_message_rejects_invalid_metadata() {
+    use crate::storage::MetadataValue;
+
+    let transport = Transport::new();
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+    let contact = create_test_contact();
+
+    let mut message = create_test_message("msg_meta", "sender_uid", "test_uid");
+    message.metadata.insert("blob".to_string(), MetadataValue::from("x".repeat(4096)));
+
+    let result = send_message(&transport, &mut queue, &contact, &message, Priority::Normal).await;
+
+    // Rejected at send time, never queued
+    assert!(matches!(result, Err(crate::Error::InvalidMetadata(_))));
+    assert_eq!(queue.size().expect("Failed to get queue size"), 0);
+}
+
+#[tokio::test]
+async fn test_send_message_rejects_framed_size_over_limit() {
+    use crate::storage::MAX_MESSAGE_BYTES;
+
+    let transport = Transport::new();
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+    let contact = create_test_contact();
+
+    // Under the limit by raw length, but CBOR spends two bytes on most payload bytes
+    let mut message = create_test_message("msg_big", "sender_uid", "test_uid");
+    message.content = "a".repeat(MAX_MESSAGE_BYTES * 3 / 4).into_bytes().into();
+    assert!(message.content.len() < MAX_MESSAGE_BYTES);
+    assert!(encoded_size(&message, "text").unwrap() > MAX_MESSAGE_BYTES);
+
+    let result = send_message(&transport, &mut queue, &contact, &message, Priority::Normal).await;
+    assert!(matches!(result, Err(crate::Error::InvalidMessage(_))));
+    assert_eq!(queue.size().expect("Failed to get queue size"), 0);
+
+    // Shrinking it below the framed limit is accepted (and queued, as the peer is offline)
+    message.content = message.content[..MAX_MESSAGE_BYTES / 3].into();
+    assert!(validate_outgoing(&message, "text").is_ok());
+}
+
+#[tokio::test]
+async fn test_send_message_failure_queues_metadata() {
+    use crate::storage::{MessageMetadata, MetadataValue};
+
+    let transport = Transport::new();
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+    let contact = create_test_contact();
+
+    let mut metadata = MessageMetadata::new();
+    metadata.insert("ticket".to_string(), MetadataValue::from("T-1"));
+    let message = create_test_message("msg_meta", "sender_uid", "test_uid")
+        .with_metadata(metadata.clone())
+        .unwrap();
+
+    let delivered = send_message(&transport, &mut queue, &contact, &message, Priority::Normal)
+        .await
+        .expect("Failed to send message");
+
+    assert!(!delivered);
+    let queued = queue.list().expect("Failed to list queue");
+    assert_eq!(queued[0].message.metadata, metadata);
+}
+
 #[tokio::test]
 async fn test_send_message_success() {
     // Start a test server to receive the message
@@ -96,6 +160,7 @@ async fn test_send_message_success() {
             tokio::spawn(async move {
                 *r.lock().await = true;
             });
+            Ok(())
         })
         .await;
 
@@ -146,7 +211,7 @@ async fn test_send_multiple_messages_mixed_results() {
     // Start a test server
     let mut receiver = Transport::new();
     receiver
-        .set_new_message_handler(|_msg| {})
+        .set_new_message_handler(|_msg| Ok(()))
         .await;
 
     receiver
@@ -271,6 +336,7 @@ async fn test_send_delete_chat_success() {
             tokio::spawn(async move {
                 *r.lock().await = Some(msg);
             });
+            Ok(())
         })
         .await;
 
@@ -418,7 +484,7 @@ fn test_handle_incoming_message_new_chat() {
     assert_eq!(msg.id, message_id);
     assert_eq!(msg.sender, sender_uid);
     assert_eq!(msg.recipient, recipient_uid);
-    assert_eq!(msg.content, content);
+    assert_eq!(msg.content.to_vec(), content);
     assert_eq!(msg.timestamp, timestamp);
     assert!(msg.delivered);
 }
@@ -460,7 +526,7 @@ fn test_handle_incoming_message_existing_chat() {
     assert_eq!(chat.messages.len(), 2);
     let msg = &chat.messages[1];
     assert_eq!(msg.id, message_id);
-    assert_eq!(msg.content, content);
+    assert_eq!(msg.content.to_vec(), content);
 }
 
 #[test]
@@ -538,6 +604,7 @@ async fn test_delete_chat_roundtrip() {
                     handle_delete_chat(&mut s, &msg.from_uid);
                 }
             });
+            Ok(())
         })
         .await;
 
@@ -775,3 +842,24 @@ async fn test_delete_active_chat_sends_notification() {
     let queued = queue.list().expect("Failed to list queue");
     assert_eq!(queued[0].priority, Priority::Urgent);
 }
+
+#[test]
+fn test_duplicate_send_allow_flag_and_system_messages() {
+    let mut chat = crate::storage::Chat::new("test_uid".to_string());
+    let now = Utc::now().timestamp_millis();
+    let mut first = create_test_message("m1", "me", "test_uid");
+    first.timestamp = now;
+    chat.append_message(first.clone());
+
+    let mut again = create_test_message("m2", "me", "test_uid");
+    again.content = first.content.clone();
+    again.timestamp = now + 500;
+    assert!(is_duplicate_send(&chat, &again, 3, false));
+    assert!(!is_duplicate_send(&chat, &again, 3, true), "the API flag bypasses the guard");
+
+    // System notices are neither compared against nor guarded
+    chat.append_message(Message::system("older messages were removed", now + 100));
+    assert!(is_duplicate_send(&chat, &again, 3, false));
+    let notice = Message::system("older messages were removed", now + 200);
+    assert!(!is_duplicate_send(&chat, &notice, 3, false));
+}
@@ -1,12 +1,80 @@
 // Test modules for Pure2P
 // Each module contains extracted unit tests from the corresponding source file
+// Modules that drive the TUI (App, screens) only build with the "tui" feature
 
+#[cfg(feature = "tui")]
+mod address_tests;
+#[cfg(feature = "tui")]
+mod auto_import_tests;
+#[cfg(feature = "tui")]
+mod batch_import_tests;
+mod cache_tests;
+#[cfg(feature = "tui")]
+mod capability_probe_tests;
+#[cfg(feature = "tui")]
+mod capture_tests;
+#[cfg(feature = "tui")]
+mod connectivity_events_tests;
 mod connectivity_tests;
 mod crypto_tests;
+#[cfg(feature = "tui")]
+mod dead_letter_tests;
+#[cfg(feature = "tui")]
+mod edits_tests;
+#[cfg(feature = "tui")]
+mod ephemeral_tests;
+#[cfg(feature = "tui")]
+mod error_reports_tests;
+#[cfg(feature = "tui")]
+mod expiry_warning_tests;
+mod feature_tests;
+mod helpers;
+mod invite_tests;
+#[cfg(feature = "tui")]
+mod journal_tests;
 mod lib_tests;
+#[cfg(feature = "tui")]
+mod logging_tests;
+#[cfg(feature = "tui")]
+mod memory_tests;
 mod messaging_tests;
+mod peer_transport_tests;
+#[cfg(feature = "tui")]
+mod pending_introductions_tests;
+#[cfg(feature = "tui")]
+mod port_watchdog_tests;
+#[cfg(feature = "tui")]
+mod probe_tests;
+#[cfg(feature = "tui")]
+mod protection_tests;
 mod protocol_tests;
+#[cfg(feature = "tui")]
+mod queued_ping_tests;
+mod queue_migration_tests;
 mod queue_tests;
+mod relay_tests;
+#[cfg(feature = "tui")]
+mod runtime_info_tests;
+#[cfg(feature = "tui")]
+mod retry_schedule_tests;
+mod sealing_tests;
+#[cfg(feature = "tui")]
+mod send_preview_tests;
+#[cfg(feature = "tui")]
+mod signals_tests;
+#[cfg(feature = "tui")]
+mod soft_delete_tests;
 mod storage_tests;
+#[cfg(feature = "tui")]
+mod tls_tests;
+#[cfg(feature = "tui")]
+mod token_armor_tests;
+#[cfg(feature = "tui")]
+mod token_validity_tests;
 mod transport_tests;
+#[cfg(feature = "tui")]
+mod trust_tier_tests;
+#[cfg(feature = "tui")]
 mod tui_tests;
+#[cfg(feature = "tui")]
+mod update_check_tests;
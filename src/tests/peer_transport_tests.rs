@@ -0,0 +1,301 @@
+// Peer transport tests - PeerTransport trait, registry dispatch and the loopback carrier
+//
+// The two-peer messaging scenarios from messaging_tests run here over the
+// in-process loopback transport instead of HTTP.
+
+use crate::messaging::*;
+use crate::queue::{MessageQueue, Priority};
+use crate::storage::{split_address_scheme, AppState, Contact, ContactEndpoint, Message};
+use crate::transport::{
+    LoopbackNetwork, LoopbackTransport, MessageRequest, PeerTransport, Transport, TransportRegistry,
+};
+use crate::Error;
+use chrono::{Duration, Utc};
+use std::sync::{Arc, Mutex};
+use super::helpers::recording_peer;
+
+fn contact_at(uid: &str, address: &str) -> Contact {
+    Contact::new(
+        uid.to_string(),
+        address.to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32], // x25519_pubkey placeholder
+        Utc::now() + Duration::days(30),
+    )
+}
+
+fn create_test_message(id: &str, sender: &str, recipient: &str) -> Message {
+    Message::new(
+        id.to_string(),
+        sender.to_string(),
+        recipient.to_string(),
+        b"Test message content".to_vec(),
+        Utc::now().timestamp_millis(),
+    )
+}
+
+#[test]
+fn test_split_address_scheme() {
+    assert_eq!(split_address_scheme("192.168.1.5:8080"), ("http", "192.168.1.5:8080"));
+    assert_eq!(split_address_scheme("http://192.168.1.5:8080"), ("http", "192.168.1.5:8080"));
+    assert_eq!(split_address_scheme("loopback://bob"), ("loopback", "bob"));
+    assert_eq!(ContactEndpoint::new("onion://abc.onion:80", "tor").scheme(), "onion");
+    assert_eq!(ContactEndpoint::new("10.8.0.2:8080", "vpn").scheme(), "http");
+}
+
+#[tokio::test]
+async fn test_loopback_send_message_success() {
+    let network = LoopbackNetwork::new();
+    let (_receiver, received) = recording_peer(&network, "bob").await;
+    let sender = LoopbackTransport::new(&network);
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+
+    let contact = contact_at("bob_uid", "loopback://bob");
+    let mut message = create_test_message("msg_success", "alice_uid", "bob_uid");
+    message.metadata.insert("reply_to".to_string(), "msg_0".into());
+
+    let delivered = send_message(&sender, &mut queue, &contact, &message, Priority::Normal)
+        .await
+        .expect("Failed to send message");
+
+    assert!(delivered, "Message should be delivered");
+    assert_eq!(queue.size().expect("Failed to get queue size"), 0);
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].from_uid, "alice_uid");
+    assert_eq!(received[0].message_type, "text");
+    assert_eq!(received[0].payload, b"Test message content".to_vec());
+    assert_eq!(received[0].metadata, message.metadata);
+}
+
+#[tokio::test]
+async fn test_loopback_mixed_results_queue_failures() {
+    let network = LoopbackNetwork::new();
+    let (_receiver, _received) = recording_peer(&network, "bob").await;
+    let sender = LoopbackTransport::new(&network);
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+
+    let online = contact_at("bob_uid", "loopback://bob");
+    let offline = contact_at("carol_uid", "loopback://carol");
+
+    let msg1 = create_test_message("msg1", "alice_uid", "bob_uid");
+    let msg2 = create_test_message("msg2", "alice_uid", "carol_uid");
+
+    assert!(send_message(&sender, &mut queue, &online, &msg1, Priority::Normal).await.unwrap());
+    assert!(!send_message(&sender, &mut queue, &offline, &msg2, Priority::High).await.unwrap());
+
+    let queued = queue.list().expect("Failed to list queue");
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].message.id, "msg2");
+    assert_eq!(queued[0].priority, Priority::High);
+}
+
+#[tokio::test]
+async fn test_loopback_handler_error_keeps_message_queued() {
+    let network = LoopbackNetwork::new();
+    let receiver = LoopbackTransport::new(&network);
+    receiver
+        .set_new_message_handler(|_msg| Err(Error::Storage("database is locked".to_string())))
+        .await;
+    receiver.start_listener("bob").await.unwrap();
+
+    let sender = LoopbackTransport::new(&network);
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+    let contact = contact_at("bob_uid", "loopback://bob");
+    let message = create_test_message("msg1", "alice_uid", "bob_uid");
+
+    let delivered = send_message(&sender, &mut queue, &contact, &message, Priority::Normal)
+        .await
+        .expect("Failed to send message");
+    assert!(!delivered, "Unstored message must not count as delivered");
+    assert_eq!(queue.size().unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_loopback_delete_chat_roundtrip() {
+    let network = LoopbackNetwork::new();
+    let receiver = LoopbackTransport::new(&network);
+    let receiver_state = Arc::new(Mutex::new(AppState::new()));
+    receiver_state.lock().unwrap().add_chat("alice_uid".to_string());
+
+    let state = receiver_state.clone();
+    receiver
+        .set_new_message_handler(move |msg| {
+            if msg.message_type == "delete_chat" {
+                handle_delete_chat(&mut state.lock().unwrap(), &msg.from_uid);
+            }
+            Ok(())
+        })
+        .await;
+    receiver.start_listener("bob").await.unwrap();
+
+    let sender = LoopbackTransport::new(&network);
+    let mut sender_state = AppState::new();
+    let contact = contact_at("bob_uid", "loopback://bob");
+    sender_state.contacts.push(contact.clone());
+    create_active_chat(&mut sender_state, "bob_uid");
+
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+    let was_active = delete_chat(&sender, &mut queue, &mut sender_state, &contact, "alice_uid")
+        .await
+        .expect("Failed to delete chat");
+
+    assert!(was_active);
+    assert!(sender_state.chats.is_empty());
+    assert_eq!(queue.size().unwrap(), 0, "Delete request should be delivered, not queued");
+    assert!(receiver_state.lock().unwrap().chats.is_empty(), "Receiver should have deleted the chat");
+}
+
+#[tokio::test]
+async fn test_loopback_create_chat_from_ping() {
+    let network = LoopbackNetwork::new();
+    let receiver = LoopbackTransport::new(&network);
+    receiver.set_local_uid("bob_uid".to_string()).await;
+    let pinged = Arc::new(Mutex::new(None));
+    let pinged_clone = pinged.clone();
+    receiver
+        .set_ping_handler(move |token| {
+            *pinged_clone.lock().unwrap() = Some(token);
+            Ok(())
+        })
+        .await;
+    receiver.start_listener("bob").await.unwrap();
+
+    let sender = LoopbackTransport::new(&network);
+    let response = sender
+        .send_ping(&contact_at("bob_uid", "loopback://bob"), "alice_token")
+        .await
+        .expect("Ping should succeed");
+    assert_eq!(response.uid, "bob_uid");
+    assert_eq!(response.status, "ok");
+    assert_eq!(pinged.lock().unwrap().as_deref(), Some("alice_token"));
+
+    // Online contact gets an active chat, offline one an inactive chat
+    let mut app_state = AppState::new();
+    assert!(create_chat_from_ping(&sender, &mut app_state, &contact_at("bob_uid", "loopback://bob")).await.unwrap());
+    assert!(!create_chat_from_ping(&sender, &mut app_state, &contact_at("carol_uid", "loopback://carol")).await.unwrap());
+    assert!(app_state.get_chat("bob_uid").unwrap().is_active);
+    assert!(!app_state.get_chat("carol_uid").unwrap().is_active);
+}
+
+#[tokio::test]
+async fn test_loopback_listener_lifecycle() {
+    let network = LoopbackNetwork::new();
+    let (receiver, _received) = recording_peer(&network, "bob").await;
+    assert!(network.is_listening("loopback://bob").await);
+    assert_eq!(receiver.local_address().await.as_deref(), Some("loopback://bob"));
+
+    // Address is taken while bob listens
+    let other = LoopbackTransport::new(&network);
+    assert!(other.start_listener("bob").await.is_err());
+
+    receiver.stop_listener().await.unwrap();
+    assert!(!network.is_listening("bob").await);
+
+    let sender = LoopbackTransport::new(&network);
+    let request = MessageRequest {
+        from_uid: "alice_uid".to_string(),
+        message_type: "text".to_string(),
+        payload: b"hi".to_vec(),
+        metadata: Default::default(),
+        message_id: None,
+    };
+    assert!(sender.send_message(&contact_at("bob_uid", "loopback://bob"), &request).await.is_err());
+
+    // Freed address can be reused
+    assert_eq!(other.start_listener("bob").await.unwrap(), "bob");
+}
+
+#[tokio::test]
+async fn test_registry_dispatches_by_scheme() {
+    let network = LoopbackNetwork::new();
+    let (_receiver, received) = recording_peer(&network, "bob").await;
+
+    let registry = TransportRegistry::new()
+        .with(Arc::new(Transport::new()))
+        .with(Arc::new(LoopbackTransport::new(&network)));
+    assert_eq!(registry.schemes(), vec!["http".to_string(), "loopback".to_string()]);
+
+    // Unreachable HTTP endpoint first, loopback endpoint second
+    let mut contact = contact_at("bob_uid", "127.0.0.1:9");
+    contact.endpoints = vec![
+        ContactEndpoint::new("127.0.0.1:9", "lan"),
+        ContactEndpoint::new("loopback://bob", "local"),
+    ];
+
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+    let message = create_test_message("msg1", "alice_uid", "bob_uid");
+    assert!(send_message(&registry, &mut queue, &contact, &message, Priority::Normal).await.unwrap());
+    assert_eq!(received.lock().unwrap().len(), 1);
+
+    // The HTTP transport alone cannot reach a loopback-only contact
+    let http_only = TransportRegistry::new().with(Arc::new(Transport::new()));
+    let loopback_only = contact_at("bob_uid", "loopback://bob");
+    let err = http_only.send_ping(&loopback_only, "").await.unwrap_err();
+    assert!(matches!(err, Error::NotSupported(_)), "got {:?}", err);
+}
+
+#[tokio::test]
+async fn test_registry_unknown_scheme_not_supported() {
+    let registry = TransportRegistry::new().with(Arc::new(Transport::new()));
+    let contact = contact_at("bob_uid", "pigeon://bob");
+
+    assert!(matches!(registry.for_address("pigeon://bob"), Err(Error::NotSupported(_))));
+    assert!(matches!(registry.send_ping(&contact, "").await, Err(Error::NotSupported(_))));
+
+    let request = MessageRequest {
+        from_uid: "alice_uid".to_string(),
+        message_type: "text".to_string(),
+        payload: vec![],
+        metadata: Default::default(),
+        message_id: None,
+    };
+    assert!(matches!(registry.send_message(&contact, &request).await, Err(Error::NotSupported(_))));
+}
+
+#[tokio::test]
+async fn test_http_transport_through_trait() {
+    let receiver = Transport::new();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let received_clone = received.clone();
+    receiver
+        .set_new_message_handler(move |msg| {
+            received_clone.lock().unwrap().push(msg);
+            Ok(())
+        })
+        .await;
+
+    let registry = TransportRegistry::new().with(Arc::new(receiver.clone()));
+    assert!(registry.capabilities().can_listen);
+
+    let bound = registry.start_listener("127.0.0.1:0").await.expect("Failed to start listener");
+    assert_eq!(receiver.local_addr().map(|a| a.to_string()), Some(bound.clone()));
+
+    let sender: &dyn PeerTransport = &Transport::new();
+    let request = MessageRequest {
+        from_uid: "alice_uid".to_string(),
+        message_type: "text".to_string(),
+        payload: b"over http".to_vec(),
+        metadata: Default::default(),
+        message_id: None,
+    };
+    sender.send_message(&contact_at("bob_uid", &bound), &request).await.expect("HTTP delivery failed");
+    assert_eq!(received.lock().unwrap()[0].payload, b"over http".to_vec());
+
+    registry.stop_listener().await.unwrap();
+    assert!(receiver.local_addr().is_none());
+}
+
+#[cfg(feature = "onion")]
+#[tokio::test]
+async fn test_onion_transport_not_supported() {
+    use crate::transport::OnionTransport;
+
+    let registry = TransportRegistry::new().with(Arc::new(OnionTransport::new()));
+    let contact = contact_at("bob_uid", "onion://bobexample.onion:80");
+
+    assert!(registry.capabilities().anonymous);
+    assert!(matches!(registry.send_ping(&contact, "").await, Err(Error::NotSupported(_))));
+    assert!(matches!(registry.start_listener("onion://me.onion:80").await, Err(Error::NotSupported(_))));
+}
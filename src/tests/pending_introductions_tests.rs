@@ -0,0 +1,167 @@
+// Pending introductions tests - reading a contact list into the Import screen, dismissing entries, linking a pasted token by fuzzy name match, linking named batch entries, carry-over of name and notes
+
+use crate::crypto::KeyPair;
+use crate::storage::{generate_contact_token, PendingIntroduction};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, TransportRegistry};
+use crate::tui::{App, SaveTarget};
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Token for `keypair` at a loopback address nobody listens on
+fn token(keypair: &KeyPair) -> String {
+    generate_contact_token(
+        &format!("loopback://{}", &keypair.uid.as_str()[..8]),
+        &keypair.public_key,
+        &keypair.private_key,
+        &keypair.x25519_public,
+        Utc::now() + Duration::days(30),
+    )
+    .unwrap()
+}
+
+/// App on the Import screen with `pending` stored; pings fail at once
+fn app_with_pending(temp_dir: &TempDir, pending: &[PendingIntroduction]) -> App {
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(&LoopbackNetwork::new())));
+    app.storage.save_pending_introductions(pending).unwrap();
+    app.show_import_contact_screen();
+    app
+}
+
+fn read_list(app: &mut App, path: &std::path::Path) {
+    app.open_path_picker(SaveTarget::IntroductionList);
+    app.path_picker.as_mut().unwrap().input = path.display().to_string();
+    app.submit_path_picker();
+    assert!(app.path_picker.is_none());
+}
+
+#[test]
+fn test_contact_list_read_into_awaiting_section() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_pending(&temp_dir, &[]);
+    let file = temp_dir.path().join("contacts.csv");
+    std::fs::write(&file, "Name,Email\nAlice Smith,alice@example.com\n,nobody@example.com\nBob,\n").unwrap();
+
+    read_list(&mut app, &file);
+    let screen = app.import_contact_screen.as_ref().unwrap();
+    let names: Vec<&str> = screen.pending.iter().map(|intro| intro.name.as_str()).collect();
+    assert_eq!(names, ["Alice Smith", "Bob"]);
+    assert_eq!(screen.list_errors.len(), 1);
+    assert_eq!(screen.list_errors[0].line, 3);
+    assert_eq!(screen.status_message.as_deref(), Some("2 pending introduction(s) added, 0 already pending, 1 row(s) skipped"));
+    assert!(!screen.is_error);
+    assert!(app.last_saved_path.is_none(), "Reading is not saving");
+
+    // The same list again adds nothing
+    read_list(&mut app, &file);
+    let screen = app.import_contact_screen.as_ref().unwrap();
+    assert_eq!(screen.pending.len(), 2);
+    assert_eq!(screen.status_message.as_deref(), Some("0 pending introduction(s) added, 2 already pending, 1 row(s) skipped"));
+
+    // Dismissing the selected entry removes it for good
+    let screen = app.import_contact_screen.as_mut().unwrap();
+    screen.cycle_link();
+    screen.cycle_link();
+    assert_eq!(screen.linked_introduction().unwrap().name, "Bob");
+    app.dismiss_linked_introduction();
+    app.show_import_contact_screen();
+    let screen = app.import_contact_screen.as_ref().unwrap();
+    assert_eq!(screen.pending.len(), 1);
+    assert_eq!(screen.pending[0].name, "Alice Smith");
+    assert!(screen.link.is_none());
+}
+
+#[test]
+fn test_pasted_token_links_fuzzy_match() {
+    let temp_dir = TempDir::new().unwrap();
+    let alice_smith = PendingIntroduction::new("Alice Smith", "Email: alice@example.com\nFrom the chess club", Utc::now());
+    let bob = PendingIntroduction::new("Bob Baker", "", Utc::now());
+    let mut app = app_with_pending(&temp_dir, &[alice_smith.clone(), bob.clone()]);
+    let alice = KeyPair::generate().unwrap();
+
+    // The name typed before the token picks the match as it is typed
+    let screen = app.import_contact_screen.as_mut().unwrap();
+    for c in format!("alise smith: {}", token(&alice)).chars() {
+        screen.add_char(c);
+    }
+    assert_eq!(screen.link.as_deref(), Some(alice_smith.id.as_str()));
+
+    // Ctrl+L overrides the match, and typing no longer changes it
+    screen.cycle_link();
+    assert_eq!(screen.link.as_deref(), Some(bob.id.as_str()));
+    screen.cycle_link();
+    assert!(screen.link.is_none());
+    screen.cycle_link();
+    assert_eq!(screen.link.as_deref(), Some(alice_smith.id.as_str()));
+    screen.add_char(' ');
+    assert_eq!(screen.link.as_deref(), Some(alice_smith.id.as_str()));
+
+    // Enter: the token is parsed without the name and the contact linked
+    screen.parse_token();
+    let contact = screen.get_contact().cloned().expect("the name prefix is not part of the token");
+    app.import_contact(contact);
+    let screen = app.import_contact_screen.as_ref().unwrap();
+    assert!(!screen.is_error);
+    assert!(screen.status_message.as_deref().unwrap().contains("Linked to Alice Smith"));
+    let stored = app.app_state.contact_by_uid(alice.uid.as_str()).unwrap();
+    assert_eq!(stored.notes, "Name: Alice Smith\nEmail: alice@example.com\nFrom the chess club");
+
+    // Linked entries leave the list, in the database too
+    assert_eq!(screen.pending.iter().map(|intro| intro.name.as_str()).collect::<Vec<_>>(), ["Bob Baker"]);
+    assert!(screen.link.is_none());
+    let stored: Vec<String> = app.storage.load_pending_introductions().unwrap().into_iter().map(|intro| intro.id).collect();
+    assert_eq!(stored, [bob.id]);
+
+    // No name, no link; a name matching nothing links nothing
+    let carol = KeyPair::generate().unwrap();
+    let screen = app.import_contact_screen.as_mut().unwrap();
+    screen.clear();
+    screen.input = token(&carol);
+    screen.suggest_link();
+    assert!(screen.link.is_none());
+    screen.input = format!("Carol: {}", token(&carol));
+    screen.suggest_link();
+    assert!(screen.link.is_none());
+}
+
+#[test]
+fn test_named_batch_entries_link_on_import() {
+    let temp_dir = TempDir::new().unwrap();
+    let alice_smith = PendingIntroduction::new("Alice Smith", "Phone: +1 555 0100", Utc::now());
+    let bob = PendingIntroduction::new("Bob Baker", "Email: bob@example.com", Utc::now());
+    let mut app = app_with_pending(&temp_dir, &[alice_smith.clone(), bob.clone()]);
+    let (alice, bobby, alice_again, zed) = (
+        KeyPair::generate().unwrap(),
+        KeyPair::generate().unwrap(),
+        KeyPair::generate().unwrap(),
+        KeyPair::generate().unwrap(),
+    );
+
+    app.toggle_batch_import();
+    app.import_contact_screen.as_mut().unwrap().input = format!(
+        "Smith, Alice: {}\nbobby baker - {}\nAlice Smith {}\nZed: {}\n",
+        token(&alice),
+        token(&bobby),
+        token(&alice_again),
+        token(&zed)
+    );
+    app.review_token_batch();
+
+    // Each introduction goes to the first entry matching it
+    let batch = app.import_contact_screen.as_ref().unwrap().batch.as_ref().unwrap();
+    let links: Vec<Option<&str>> = batch.entries.iter().map(|e| e.link.as_ref().map(|intro| intro.name.as_str())).collect();
+    assert_eq!(links, [Some("Alice Smith"), Some("Bob Baker"), None, None]);
+
+    assert_eq!(app.confirm_batch_import(), 4);
+    let notes = |keypair: &KeyPair| app.app_state.contact_by_uid(keypair.uid.as_str()).unwrap().notes.clone();
+    assert_eq!(notes(&alice), "Name: Alice Smith\nPhone: +1 555 0100");
+    assert_eq!(notes(&bobby), "Name: Bob Baker\nEmail: bob@example.com");
+    assert_eq!(notes(&alice_again), "");
+    assert_eq!(notes(&zed), "");
+
+    let report = app.import_contact_screen.as_ref().unwrap().batch.as_ref().unwrap().report.clone().unwrap();
+    assert_eq!(report[0].name, "Smith, Alice (linked to Alice Smith)");
+    assert!(app.storage.load_pending_introductions().unwrap().is_empty());
+    assert!(app.import_contact_screen.as_ref().unwrap().pending.is_empty());
+}
@@ -0,0 +1,192 @@
+// Port watchdog tests - boot nonce on /health, takeover detection, rebind recovery, status transitions and audit entry
+
+use crate::storage::{storage_db::Storage, AppState};
+use crate::transport::watchdog::{classify, generate_boot_nonce, MAX_BIND_ATTEMPTS};
+use crate::transport::{bind_verified, probe_self, ProbeOutcome, Transport, BOOT_NONCE_HEADER};
+use crate::tui::{ErrorReporter, PortAlert, PortWatchdog, TransportServerStatus, PORT_TAKEOVER_AUDIT_TYPE};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use super::helpers::storage_with_identity;
+
+/// A port nothing is listening on right now
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("0.0.0.0:0").unwrap().local_addr().unwrap().port()
+}
+
+/// Answer every request on a fresh port with a plain healthy "ok", like a
+/// stranger's server would
+async fn foreign_server() -> u16 {
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await;
+        }
+    });
+    port
+}
+
+/// Record every distinct status the server goes through
+fn watch_status(status: Arc<Mutex<TransportServerStatus>>) -> Arc<Mutex<Vec<TransportServerStatus>>> {
+    let seen = Arc::new(Mutex::new(vec![status.lock().unwrap().clone()]));
+    let seen_clone = seen.clone();
+    tokio::spawn(async move {
+        loop {
+            let current = status.lock().unwrap().clone();
+            {
+                let mut seen = seen_clone.lock().unwrap();
+                if seen.last() != Some(&current) {
+                    seen.push(current);
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    });
+    seen
+}
+
+fn takeover_audit(storage: &Storage) -> Vec<crate::storage::RequestLog> {
+    storage
+        .get_request_logs(100)
+        .unwrap()
+        .into_iter()
+        .filter(|log| log.request_type == PORT_TAKEOVER_AUDIT_TYPE)
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_foreign_answer_detected_and_rebound() {
+    let stolen_port = foreign_server().await;
+    let transport = Transport::new();
+    transport.clone().start("0.0.0.0:0".parse().unwrap()).await.unwrap();
+    assert_eq!(probe_self(stolen_port, &transport.boot_nonce()).await, ProbeOutcome::Foreign);
+
+    let storage = storage_with_identity();
+    let status = Arc::new(Mutex::new(TransportServerStatus::Running(stolen_port)));
+    let alert = Mutex::new(None);
+    let reports = ErrorReporter::new();
+    let seen = watch_status(status.clone());
+
+    let mut watchdog = PortWatchdog::new(stolen_port);
+    let outcome = watchdog.tick(&transport, &status, &storage, &alert, &reports).await;
+    assert_eq!(outcome, ProbeOutcome::Foreign);
+
+    // The stranger keeps the port, so we move to another one and verify it is ours
+    let new_port = watchdog.port();
+    assert_ne!(new_port, stolen_port);
+    assert_eq!(probe_self(new_port, &transport.boot_nonce()).await, ProbeOutcome::Ours);
+    assert_eq!(AppState::load_from_db(&storage).unwrap().user_port, new_port);
+    assert!(reports.is_empty());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            TransportServerStatus::Running(stolen_port),
+            TransportServerStatus::Hijacked(stolen_port),
+            TransportServerStatus::Running(new_port),
+        ]
+    );
+
+    // One audit entry and a banner naming both ports
+    let audit = takeover_audit(&storage);
+    assert_eq!(audit.len(), 1);
+    assert!(!audit[0].success);
+    assert_eq!(
+        audit[0].error_message.as_deref(),
+        Some(format!("port {} answered by another process", stolen_port).as_str())
+    );
+    let text = alert.lock().unwrap().as_ref().unwrap().text.clone();
+    assert!(text.contains(&stolen_port.to_string()) && text.contains(&new_port.to_string()), "{}", text);
+    transport.stop();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lost_listener_rebinds_same_port() {
+    let transport = Transport::new();
+    let port = bind_verified(&transport, free_port(), MAX_BIND_ATTEMPTS).await.unwrap();
+    let first_nonce = transport.boot_nonce();
+
+    let storage = storage_with_identity();
+    let status = Arc::new(Mutex::new(TransportServerStatus::Running(port)));
+    let alert = Mutex::new(None);
+    let reports = ErrorReporter::new();
+    let mut watchdog = PortWatchdog::new(port);
+
+    // One unanswered probe is not enough to call the port lost
+    transport.stop();
+    let outcome = watchdog.tick(&transport, &status, &storage, &alert, &reports).await;
+    assert_eq!(outcome, ProbeOutcome::Unreachable);
+    assert_eq!(*status.lock().unwrap(), TransportServerStatus::Running(port));
+    assert!(takeover_audit(&storage).is_empty());
+
+    // The second is: the same port is free again, so it is bound again
+    let seen = watch_status(status.clone());
+    watchdog.tick(&transport, &status, &storage, &alert, &reports).await;
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(watchdog.port(), port);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![TransportServerStatus::Running(port), TransportServerStatus::Lost(port), TransportServerStatus::Running(port)]
+    );
+    assert_eq!(takeover_audit(&storage)[0].error_message.as_deref(), Some(format!("port {} no longer answering", port).as_str()));
+    assert!(alert.lock().unwrap().as_ref().unwrap().text.ends_with("listening again"));
+    assert_eq!(AppState::load_from_db(&storage).unwrap().user_port, port);
+
+    // The new listener has a new nonce, and the old one is no longer accepted
+    assert_ne!(transport.boot_nonce(), first_nonce);
+    assert_eq!(probe_self(port, &first_nonce).await, ProbeOutcome::Foreign);
+    transport.stop();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_own_answer_is_not_a_takeover() {
+    let transport = Transport::new();
+    transport.set_local_uid("alice_uid".to_string()).await;
+    let port = bind_verified(&transport, free_port(), MAX_BIND_ATTEMPTS).await.unwrap();
+
+    let storage = Storage::new_in_memory().unwrap();
+    let status = Arc::new(Mutex::new(TransportServerStatus::Running(port)));
+    let alert = Mutex::new(None);
+    let reports = ErrorReporter::new();
+    let mut watchdog = PortWatchdog::new(port);
+    for _ in 0..3 {
+        assert_eq!(watchdog.tick(&transport, &status, &storage, &alert, &reports).await, ProbeOutcome::Ours);
+    }
+    assert_eq!(*status.lock().unwrap(), TransportServerStatus::Running(port));
+    assert!(takeover_audit(&storage).is_empty());
+    assert!(alert.lock().unwrap().is_none());
+
+    // /health still says "ok"; the nonce is random hex, not our identity
+    let response = reqwest::get(format!("http://127.0.0.1:{}/health", port)).await.unwrap();
+    let nonce = response.headers().get(BOOT_NONCE_HEADER).unwrap().to_str().unwrap().to_string();
+    assert_eq!(response.text().await.unwrap(), "ok");
+    assert_eq!(nonce, transport.boot_nonce());
+    assert_eq!(nonce.len(), 32);
+    assert!(nonce.chars().all(|c| c.is_ascii_hexdigit()));
+    assert!(!nonce.contains("alice"));
+    transport.stop();
+}
+
+#[test]
+fn test_classify_and_banner_lifetime() {
+    let nonce = generate_boot_nonce();
+    assert_ne!(nonce, generate_boot_nonce());
+    assert_eq!(classify(true, Some(&nonce), &nonce), ProbeOutcome::Ours);
+    assert_eq!(classify(true, None, &nonce), ProbeOutcome::Foreign);
+    assert_eq!(classify(true, Some("0123"), &nonce), ProbeOutcome::Foreign);
+    assert_eq!(classify(false, Some(&nonce), &nonce), ProbeOutcome::Foreign);
+
+    // Up while rebinding, then for a minute after
+    let now = Instant::now();
+    let old = PortAlert { text: "Port 8080 was …".to_string(), raised_at: now - Duration::from_secs(120) };
+    assert!(old.is_visible(&TransportServerStatus::Hijacked(8080), now));
+    assert!(old.is_visible(&TransportServerStatus::Lost(8080), now));
+    assert!(!old.is_visible(&TransportServerStatus::Running(9090), now));
+    let fresh = PortAlert { raised_at: now - Duration::from_secs(5), ..old };
+    assert!(fresh.is_visible(&TransportServerStatus::Running(9090), now));
+}
@@ -0,0 +1,238 @@
+// Probe tests - peer-assisted reachability round trip (reachable and unreachable), the verification, address and rate-limit gates, and the connectivity history
+
+use crate::crypto::KeyPair;
+use crate::probe::*;
+use crate::storage::{Contact, ContactEndpoint};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, MessageRequest, TransportRegistry};
+use crate::tui::App;
+use crate::Error;
+use chrono::{Duration, Utc};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use super::helpers::{recording_peer, settle};
+
+fn typed(received: &Arc<Mutex<Vec<MessageRequest>>>, message_type: &str) -> Vec<MessageRequest> {
+    received.lock().unwrap().iter().filter(|r| r.message_type == message_type).cloned().collect()
+}
+
+/// Alice as stored by her contacts: a dead address first, then the live one
+fn alice_contact(alice: &KeyPair, verified: bool) -> Contact {
+    let mut contact = Contact::new(
+        alice.uid.to_string(),
+        "loopback://alice-nat".to_string(),
+        alice.public_key.clone(),
+        alice.x25519_public.clone(),
+        Utc::now() + Duration::days(30),
+    );
+    contact.endpoints = vec![
+        ContactEndpoint::new("loopback://alice-nat", "home"),
+        ContactEndpoint::new("loopback://alice", "vpn"),
+    ];
+    contact.verified = verified;
+    contact
+}
+
+/// In-memory app holding `contacts`, sending over the loopback network
+fn app_with(temp_dir: &TempDir, network: &LoopbackNetwork, contacts: Vec<Contact>) -> App {
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(network)));
+    for contact in contacts {
+        app.app_state.add_contact(contact);
+    }
+    app
+}
+
+#[test]
+fn test_probe_round_trip_both_outcomes() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (_alice_peer, alice_inbox) = rt.block_on(recording_peer(&network, "alice"));
+    let (_bob_peer, bob_inbox) = rt.block_on(recording_peer(&network, "bob"));
+
+    let alice_dir = TempDir::new().unwrap();
+    let bob_dir = TempDir::new().unwrap();
+    let mut alice = app_with(&alice_dir, &network, Vec::new());
+    let mut bob = app_with(&bob_dir, &network, vec![alice_contact(&alice.keypair, true)]);
+    let bob_contact = Contact::new(
+        bob.keypair.uid.to_string(),
+        "loopback://bob".to_string(),
+        bob.keypair.public_key.clone(),
+        bob.keypair.x25519_public.clone(),
+        Utc::now() + Duration::days(30),
+    );
+    let (alice_uid, bob_uid) = (alice.keypair.uid.to_string(), bob_contact.uid.clone());
+    alice.app_state.add_contact(bob_contact);
+    alice.show_diagnostics_screen();
+
+    for (address, reachable) in [("loopback://alice", true), ("loopback://alice-nat", false)] {
+        alice_inbox.lock().unwrap().clear();
+        bob_inbox.lock().unwrap().clear();
+
+        // Alice asks Bob to test the address she advertises
+        alice.local_ip = address.to_string();
+        assert!(alice.request_peer_probe(&bob_uid));
+        settle();
+        let requests = typed(&bob_inbox, PROBE_REQUEST_TYPE);
+        assert_eq!(requests.len(), 1);
+        let request = ProbeRequest::from_payload(&requests[0].payload).unwrap();
+        assert_eq!(request.address, address);
+
+        // Bob sends the check to exactly that address, then the result
+        bob.serve_probe_request(&alice_uid, request.clone(), Utc::now()).unwrap();
+        settle();
+        let checks = typed(&alice_inbox, PROBE_CHECK_TYPE);
+        assert_eq!(checks.len(), usize::from(reachable), "The check only arrives when the address works");
+        if reachable {
+            assert_eq!(checks[0].payload, request.probe_id.as_bytes());
+        }
+        let results = typed(&alice_inbox, PROBE_RESULT_TYPE);
+        assert_eq!(results.len(), 1, "The result falls back to Alice's working address");
+        let result = ProbeResult::from_payload(&results[0].payload).unwrap();
+        assert_eq!((result.address.as_str(), result.reachable, result.refused.clone()), (address, reachable, None));
+        assert_eq!(result.latency_ms.is_some(), reachable);
+
+        // Alice applies it: history and Diagnostics
+        assert!(alice.apply_probe_result(&bob_uid, &result, Utc::now()));
+        assert!(alice.pending_probes.is_empty());
+        let screen = alice.diagnostics_screen.as_ref().unwrap();
+        assert_eq!(screen.peer_probes[0].reachable, reachable);
+        let expected = if reachable { "reachable (" } else { "not reachable" };
+        assert!(screen.status_message.as_deref().unwrap().contains(expected));
+    }
+}
+
+#[test]
+fn test_verification_address_and_rate_limit_gates() {
+    let alice = KeyPair::generate().unwrap();
+    let alice_uid = alice.uid.to_string();
+    let mut carol = alice_contact(&KeyPair::generate().unwrap(), true);
+    carol.uid = "carol".to_string();
+    let now = Utc::now();
+    let mut limiter = ProbeLimiter::new();
+
+    // Unknown and unverified requesters are refused without counting
+    let request = ProbeRequest::new("loopback://alice");
+    assert!(matches!(
+        admit_probe(&[], &alice_uid, &request, &mut limiter, now),
+        Err(Error::InvalidMessage(_))
+    ));
+    let unverified = [alice_contact(&alice, false)];
+    assert!(matches!(
+        admit_probe(&unverified, &alice_uid, &request, &mut limiter, now),
+        Err(Error::InvalidMessage(_))
+    ));
+
+    // Only addresses the requester advertised to us are probed
+    let contacts = [alice_contact(&alice, true), carol];
+    let elsewhere = ProbeRequest::new("loopback://bank");
+    assert!(matches!(
+        admit_probe(&contacts, &alice_uid, &elsewhere, &mut limiter, now),
+        Err(Error::InvalidMessage(_))
+    ));
+
+    // Two probes per contact per hour
+    for minutes in [0, 10] {
+        admit_probe(&contacts, &alice_uid, &request, &mut limiter, now + Duration::minutes(minutes)).unwrap();
+    }
+    assert!(matches!(
+        admit_probe(&contacts, &alice_uid, &request, &mut limiter, now + Duration::minutes(20)),
+        Err(Error::RateLimited(_))
+    ));
+    admit_probe(&contacts, "carol", &request, &mut limiter, now + Duration::minutes(20)).unwrap();
+    admit_probe(&contacts, &alice_uid, &request, &mut limiter, now + Duration::minutes(61)).unwrap();
+}
+
+#[test]
+fn test_refusals_are_answered() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (_alice_peer, alice_inbox) = rt.block_on(recording_peer(&network, "alice"));
+
+    let temp_dir = TempDir::new().unwrap();
+    let alice = KeyPair::generate().unwrap();
+    let alice_uid = alice.uid.to_string();
+    let mut bob = app_with(&temp_dir, &network, vec![alice_contact(&alice, false)]);
+
+    // Unverified: told why, nothing tested
+    let request = ProbeRequest::new("loopback://alice");
+    assert!(bob.serve_probe_request(&alice_uid, request.clone(), Utc::now()).is_err());
+    settle();
+    assert!(typed(&alice_inbox, PROBE_CHECK_TYPE).is_empty());
+    let results = typed(&alice_inbox, PROBE_RESULT_TYPE);
+    let result = ProbeResult::from_payload(&results[0].payload).unwrap();
+    assert_eq!(result.probe_id, request.probe_id);
+    assert!(result.refused.unwrap().contains("verified"));
+
+    // Over the limit: the third request gets a refusal, not a check
+    bob.app_state.contact_by_uid_mut(&alice_uid).unwrap().verified = true;
+    alice_inbox.lock().unwrap().clear();
+    for _ in 0..PROBE_LIMIT_PER_HOUR {
+        bob.serve_probe_request(&alice_uid, ProbeRequest::new("loopback://alice"), Utc::now()).unwrap();
+    }
+    let third = bob.serve_probe_request(&alice_uid, ProbeRequest::new("loopback://alice"), Utc::now());
+    assert!(matches!(third, Err(Error::RateLimited(_))));
+    settle();
+    assert_eq!(typed(&alice_inbox, PROBE_CHECK_TYPE).len(), PROBE_LIMIT_PER_HOUR);
+    let refused = typed(&alice_inbox, PROBE_RESULT_TYPE)
+        .iter()
+        .filter(|r| ProbeResult::from_payload(&r.payload).unwrap().refused.is_some())
+        .count();
+    assert_eq!(refused, 1);
+
+    // Strangers get no answer at all
+    alice_inbox.lock().unwrap().clear();
+    assert!(bob.serve_probe_request("mallory", ProbeRequest::new("loopback://alice"), Utc::now()).is_err());
+    settle();
+    assert!(alice_inbox.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_results_feed_connectivity_history() {
+    let network = LoopbackNetwork::new();
+    let temp_dir = TempDir::new().unwrap();
+    let bob = KeyPair::generate().unwrap();
+    let mut bob_contact = alice_contact(&bob, true);
+    bob_contact.endpoints.clear();
+    let bob_uid = bob_contact.uid.clone();
+    let mut app = app_with(&temp_dir, &network, vec![bob_contact]);
+    app.local_ip = "loopback://me".to_string();
+
+    // Bob is not listening: the request comes back as not tested
+    assert!(app.request_peer_probe(&bob_uid));
+    settle();
+    assert!(app.process_incoming_updates());
+    assert!(app.pending_probes.is_empty());
+    let history = probe_history(&app.storage, 10).unwrap();
+    assert_eq!(history.len(), 1);
+    assert!(history[0].refused.as_deref().unwrap().starts_with("request not delivered"));
+
+    // Results nobody asked for are ignored
+    assert!(app.request_peer_probe(&bob_uid));
+    let pending = app.pending_probes.last().unwrap().clone();
+    let result = ProbeResult {
+        probe_id: pending.probe_id.clone(),
+        address: "loopback://elsewhere".to_string(),
+        reachable: true,
+        latency_ms: Some(42),
+        refused: None,
+    };
+    assert!(!app.apply_probe_result("mallory", &result, Utc::now()));
+    let unknown = ProbeResult { probe_id: "made-up".to_string(), ..result.clone() };
+    assert!(!app.apply_probe_result(&bob_uid, &unknown, Utc::now()));
+    assert!(app.apply_probe_result(&bob_uid, &result, Utc::now()));
+    assert!(!app.apply_probe_result(&bob_uid, &result, Utc::now()), "A result is applied once");
+
+    // Kept in the request log, newest first, under the address we asked about
+    let history = probe_history(&app.storage, 10).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(
+        (history[0].contact_uid.as_str(), history[0].address.as_str(), history[0].reachable, history[0].latency_ms),
+        (bob_uid.as_str(), "loopback://me", true, Some(42))
+    );
+    assert!(history[0].summary().starts_with("✓ loopback://me reachable (42ms)"));
+    assert!(history[1].summary().contains("not tested"));
+
+    // Diagnostics shows the history when opened
+    app.show_diagnostics_screen();
+    assert_eq!(app.diagnostics_screen.as_ref().unwrap().peer_probes, history);
+}
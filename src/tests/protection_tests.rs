@@ -0,0 +1,228 @@
+// Protection tests - levels recorded by both peers across a key upgrade, transition notices firing once, the marker toggle and export
+
+use crate::crypto::KeyPair;
+use crate::sealing::{apply_key_upgrade, arrival_protection, open_request};
+use crate::storage::{AppState, Chat, Contact, ExportedMessage, JsonlExportOptions, Message, Protection, Storage};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, MessageRequest, PeerTransport, TransportRegistry};
+use crate::tui::ui::ui;
+use crate::tui::App;
+use chrono::{Duration, Utc};
+use ratatui::{backend::TestBackend, Terminal};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use super::helpers::{contact_at, settle};
+
+const SENT_NOW_SEALED: &str = "Messages you send are now end-to-end encrypted";
+const RECEIVED_NOW_SEALED: &str = "Messages you receive are now end-to-end encrypted";
+
+fn bob() -> Contact {
+    Contact::new("bob".to_string(), "loopback://bob".to_string(), vec![1; 32], vec![2; 32], Utc::now() + Duration::days(30))
+}
+
+fn message(id: &str, sender: &str, recipient: &str, timestamp: i64) -> Message {
+    Message::new(id.to_string(), sender.to_string(), recipient.to_string(), id.as_bytes().to_vec(), timestamp)
+}
+
+/// Each message of `chat` as (text, protection)
+fn levels(chat: &Chat) -> Vec<(String, Option<Protection>)> {
+    chat.messages.iter().map(|m| (m.display_text(), m.protection)).collect()
+}
+
+fn notices(chat: &Chat) -> Vec<String> {
+    chat.messages.iter().filter(|m| m.is_system()).map(|m| m.display_text()).collect()
+}
+
+/// Type `text` into the open chat, send it and apply the delivery event
+fn send_and_settle(app: &mut App, text: &str) {
+    app.chat_view_screen.as_mut().unwrap().input = text.to_string();
+    app.send_message_in_chat();
+    settle();
+    app.process_delivery_events();
+}
+
+/// Store the texts Bob received the way the message handler does
+fn bob_stores(received: &Mutex<Vec<MessageRequest>>, bob: &KeyPair, bob_contacts: &[Contact], chat: &mut Chat) {
+    for request in received.lock().unwrap().drain(..) {
+        if !matches!(request.message_type.as_str(), "text" | "text_e2e") {
+            continue;
+        }
+        let protection = arrival_protection(&request.message_type);
+        let request = open_request(request, bob, bob_contacts).unwrap();
+        let stored = Message::new(
+            uuid::Uuid::new_v4().to_string(),
+            request.from_uid,
+            bob.uid.to_string(),
+            request.payload,
+            Utc::now().timestamp_millis(),
+        );
+        let id = stored.id.clone();
+        chat.append_message(stored);
+        assert!(chat.record_protection(&id, protection));
+    }
+}
+
+#[test]
+fn test_levels_recorded_across_key_upgrade() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let temp_dir = TempDir::new().unwrap();
+    let bob = KeyPair::generate().unwrap();
+
+    // Bob records every request; his contact for Alice carries her key
+    let bob_transport = LoopbackTransport::new(&network);
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let received_clone = received.clone();
+    rt.block_on(async {
+        bob_transport
+            .set_new_message_handler(move |request| {
+                received_clone.lock().unwrap().push(request);
+                Ok(())
+            })
+            .await;
+        bob_transport.start_listener("bob").await.unwrap();
+    });
+
+    // Alice's contact for Bob predates his X25519 key
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.load_chat_messages().unwrap();
+    app.transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(&network)));
+    app.app_state.settings.auto_send_preview = false;
+    let mut keyless = contact_at(&bob, "loopback://bob");
+    keyless.x25519_pubkey = None;
+    app.app_state.contacts.push(keyless);
+    app.app_state.get_or_create_chat(&bob.uid.to_string());
+    app.save_state().unwrap();
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+    let bob_contacts = vec![contact_at(&app.keypair, "loopback://alice")];
+    let mut bob_chat = Chat::new(app.keypair.uid.to_string());
+
+    send_and_settle(&mut app, "in the clear");
+    bob_stores(&received, &bob, &bob_contacts, &mut bob_chat);
+
+    // Bob's key arrives through an upgrade response; later messages are sealed
+    let bob_token = contact_at(&bob, "loopback://bob").sign_token(&bob).unwrap();
+    assert!(apply_key_upgrade(&mut app.app_state.contacts, &bob.uid.to_string(), bob_token.as_bytes()).unwrap());
+    send_and_settle(&mut app, "now sealed");
+    send_and_settle(&mut app, "still sealed");
+    bob_stores(&received, &bob, &bob_contacts, &mut bob_chat);
+
+    let alice_chat = app.app_state.get_chat(&bob.uid.to_string()).unwrap();
+    let expected = |notice: &str| {
+        vec![
+            ("in the clear".to_string(), Some(Protection::Plaintext)),
+            (notice.to_string(), None),
+            ("now sealed".to_string(), Some(Protection::Sealed)),
+            ("still sealed".to_string(), Some(Protection::Sealed)),
+        ]
+    };
+    assert_eq!(levels(alice_chat), expected(SENT_NOW_SEALED));
+    assert_eq!(levels(&bob_chat), expected(RECEIVED_NOW_SEALED));
+
+    // The plaintext message stays marked plaintext once stored
+    let reloaded = AppState::load_from_db(&app.storage).unwrap();
+    assert_eq!(levels(reloaded.get_chat(&bob.uid.to_string()).unwrap()), expected(SENT_NOW_SEALED));
+}
+
+#[test]
+fn test_transition_notices_fire_once_per_change() {
+    let mut chat = Chat::new("bob".to_string());
+    for (i, (id, sender)) in [("o1", "me"), ("i1", "bob"), ("o2", "me"), ("o3", "me"), ("i2", "bob"), ("o4", "me"), ("i3", "bob")]
+        .into_iter()
+        .enumerate()
+    {
+        let recipient = if sender == "me" { "bob" } else { "me" };
+        chat.append_message(message(id, sender, recipient, 1_000 * (i as i64 + 1)));
+    }
+
+    // The first level in each direction is no transition, and directions
+    // are compared separately, so differing sides do not flap
+    assert!(chat.record_protection("o1", Protection::Plaintext));
+    assert!(chat.record_protection("i1", Protection::Sealed));
+    assert!(chat.record_protection("o2", Protection::Plaintext));
+    assert!(notices(&chat).is_empty());
+
+    assert!(chat.record_protection("o3", Protection::Sealed));
+    assert_eq!(notices(&chat), [SENT_NOW_SEALED]);
+    // Recording the same level again changes nothing
+    assert!(!chat.record_protection("o3", Protection::Sealed));
+    assert!(chat.record_protection("o4", Protection::Sealed));
+    assert_eq!(notices(&chat).len(), 1);
+
+    assert!(chat.record_protection("i2", Protection::Plaintext));
+    assert!(chat.record_protection("i3", Protection::Plaintext));
+    assert_eq!(notices(&chat), [SENT_NOW_SEALED, "Messages you receive are no longer encrypted"]);
+    assert!(!chat.record_protection("unknown", Protection::Sealed));
+
+    // Each notice sits right before the message that changed
+    let ids: Vec<&str> = chat.messages.iter().map(|m| if m.is_system() { "notice" } else { m.id.as_str() }).collect();
+    assert_eq!(ids, ["o1", "i1", "o2", "notice", "o3", "notice", "i2", "o4", "i3"]);
+    assert!(chat.messages[3].timestamp < chat.messages[4].timestamp);
+}
+
+#[test]
+fn test_protection_markers_toggle() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    let me = app.keypair.uid.to_string();
+    app.app_state.contacts.push(bob());
+    let chat = app.app_state.get_or_create_chat("bob");
+    let now = Utc::now().timestamp_millis();
+    chat.append_message(message("plain", &me, "bob", now - 2));
+    chat.append_message(message("sealed", &me, "bob", now - 1));
+    chat.append_message(message("unrecorded", &me, "bob", now));
+    chat.record_protection("plain", Protection::Plaintext);
+    chat.record_protection("sealed", Protection::Sealed);
+    app.save_state().unwrap();
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+
+    let render = |app: &App| {
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|f| ui(f, app)).unwrap();
+        terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect::<String>()
+    };
+    let rendered = render(&app);
+    assert!(rendered.contains("] ○ You: plain"), "{}", rendered);
+    assert!(rendered.contains("🔒") && !rendered.contains("] You: sealed"));
+    assert!(rendered.contains("] You: unrecorded"));
+
+    let screen = app.chat_view_screen.as_mut().unwrap();
+    screen.toggle_protection_markers();
+    assert_eq!(screen.status_message.as_deref(), Some("Protection markers hidden"));
+    let rendered = render(&app);
+    assert!(rendered.contains("] You: plain") && rendered.contains("] You: sealed"));
+    assert!(!rendered.contains("○"));
+}
+
+#[test]
+fn test_export_includes_protection() {
+    let storage = Storage::new_in_memory().unwrap();
+    storage.save_contact(&bob()).unwrap();
+    let mut chat = Chat::new("bob".to_string());
+    chat.append_message(message("m1", "bob", "me", 1_000));
+    chat.append_message(message("m2", "bob", "me", 2_000));
+    chat.append_message(message("m3", "bob", "me", 3_000));
+    chat.record_protection("m1", Protection::Plaintext);
+    chat.record_protection("m2", Protection::Sealed);
+    storage.save_chat(&chat).unwrap();
+
+    let mut out = Vec::new();
+    assert_eq!(storage.export_chat_jsonl("bob", &mut out, &JsonlExportOptions::default()).unwrap(), 4);
+    let text = String::from_utf8(out).unwrap();
+    let lines: Vec<ExportedMessage> = text.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    let exported: Vec<(&str, Option<Protection>)> = lines
+        .iter()
+        .filter(|line| line.id.starts_with('m'))
+        .map(|line| (line.id.as_str(), line.protection))
+        .collect();
+    assert_eq!(exported, [("m1", Some(Protection::Plaintext)), ("m2", Some(Protection::Sealed)), ("m3", None)]);
+    assert!(text.contains(r#""protection":"sealed""#));
+    assert!(text.contains(r#""protection":null"#));
+
+    // Lines written before the field existed still read
+    let mut older: serde_json::Value = serde_json::from_str(text.lines().next().unwrap()).unwrap();
+    older.as_object_mut().unwrap().remove("protection");
+    let older: ExportedMessage = serde_json::from_value(older).unwrap();
+    assert_eq!(older.protection, None);
+}
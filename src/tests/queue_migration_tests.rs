@@ -0,0 +1,256 @@
+// Queue migration tests - online v2 migration with a concurrent writer, interrupt and resume, the writer gate and the new index
+
+use crate::queue::{MessageQueue, Priority, FETCH_DUE_QUERY};
+use crate::queue_migration::{QueueMigration, MIGRATION_BATCH_ROWS, QUEUE_DUE_INDEX, QUEUE_SCHEMA_VERSION};
+use crate::storage::Message;
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Write a version 1 queue file (before `scheduled_at`) holding `rows`
+/// plain rows named "seed-<n>"
+fn seed_v1_queue(path: &Path, rows: usize) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE message_queue (
+            message_id TEXT PRIMARY KEY,
+            target_uid TEXT NOT NULL,
+            message_type TEXT NOT NULL,
+            payload BLOB NOT NULL,
+            last_attempt INTEGER,
+            retry_count INTEGER NOT NULL DEFAULT 0,
+            sender TEXT NOT NULL,
+            recipient TEXT NOT NULL,
+            content BLOB NOT NULL,
+            timestamp INTEGER NOT NULL,
+            priority INTEGER NOT NULL,
+            next_retry INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            metadata TEXT,
+            last_error TEXT,
+            updated_at INTEGER,
+            dormant_since INTEGER,
+            suspended_since INTEGER
+        );
+        CREATE INDEX idx_queue_live_priority_retry
+        ON message_queue(priority DESC, next_retry ASC) WHERE suspended_since IS NULL;
+        PRAGMA user_version = 1;",
+    )
+    .unwrap();
+    conn.execute(
+        "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i + 1 < ?1)
+         INSERT INTO message_queue
+         (message_id, target_uid, message_type, payload, sender, recipient, content, timestamp,
+          priority, next_retry, created_at)
+         SELECT 'seed-' || i, 'bob', 'text', X'0001', 'me', 'bob', X'0001', 1000 + i,
+                i % 3, 1000 + i, 1000 + i
+         FROM n",
+        params![rows as i64],
+    )
+    .unwrap();
+}
+
+fn message(id: &str) -> Message {
+    Message::new(id.to_string(), "me".to_string(), "bob".to_string(), vec![1], Utc::now().timestamp_millis())
+}
+
+fn message_ids(queue: &MessageQueue) -> Vec<String> {
+    let mut stmt = queue.conn.prepare("SELECT message_id FROM message_queue").unwrap();
+    stmt.query_map([], |row| row.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap()
+}
+
+fn unscheduled(queue: &MessageQueue) -> usize {
+    queue
+        .conn
+        .query_row("SELECT COUNT(*) FROM message_queue WHERE scheduled_at IS NULL", [], |row| row.get(0))
+        .unwrap()
+}
+
+#[test]
+fn test_migration_with_concurrent_writer_keeps_every_row() {
+    const SEEDED: usize = 50_000;
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("message_queue.db");
+    seed_v1_queue(&path, SEEDED);
+
+    // Too large to migrate on open
+    let mut queue = MessageQueue::new_with_path(&path).unwrap();
+    assert_eq!(queue.schema_version().unwrap(), 1);
+    assert_eq!(unscheduled(&queue), SEEDED);
+
+    // A worker on its own connection queues new messages and delivers seeded ones
+    let stop = Arc::new(AtomicBool::new(false));
+    let writes = Arc::new(AtomicUsize::new(0));
+    let (started, wait_started) = mpsc::channel();
+    let worker = {
+        let (path, stop, writes) = (path.clone(), stop.clone(), writes.clone());
+        std::thread::spawn(move || {
+            let mut queue = MessageQueue::new_with_path(&path).unwrap();
+            let (mut added, mut delivered) = (Vec::new(), Vec::new());
+            let mut i = 0;
+            while !stop.load(Ordering::Relaxed) {
+                let id = format!("new-{}", i);
+                queue.enqueue(message(&id), Priority::Normal).unwrap();
+                added.push(id);
+                if i * 7 < SEEDED {
+                    let seeded = format!("seed-{}", i * 7);
+                    queue.mark_delivered(&seeded).unwrap();
+                    delivered.push(seeded);
+                }
+                writes.fetch_add(1, Ordering::Relaxed);
+                if i == 0 {
+                    started.send(()).unwrap();
+                }
+                i += 1;
+            }
+            (added, delivered)
+        })
+    };
+    wait_started.recv().unwrap();
+
+    let writes_before = writes.load(Ordering::Relaxed);
+    let mut reports = Vec::new();
+    let migration = queue
+        .migrate_schema(|progress| {
+            reports.push(*progress);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+    let writes_during = writes.load(Ordering::Relaxed) - writes_before;
+    stop.store(true, Ordering::Relaxed);
+    let (added, delivered) = worker.join().unwrap();
+
+    // The worker kept writing, waiting at batch boundaries
+    assert!(writes_during > 0);
+    assert!(queue.gate.pauses() > 0);
+    assert!(migration.is_complete());
+    // Rows queued meanwhile are migrated too, so there may be extra batches
+    assert!(reports.len() > SEEDED.div_ceil(MIGRATION_BATCH_ROWS));
+    assert!(reports.iter().rev().skip(1).all(|progress| !progress.is_complete()));
+    assert!(reports.windows(2).all(|pair| pair[0].done < pair[1].done || pair[1].is_complete()));
+
+    // No row lost or duplicated
+    let ids = message_ids(&queue);
+    let unique: HashSet<&String> = ids.iter().collect();
+    assert_eq!(unique.len(), ids.len());
+    let delivered: HashSet<String> = delivered.into_iter().collect();
+    let expected: HashSet<String> = (0..SEEDED)
+        .map(|i| format!("seed-{}", i))
+        .filter(|id| !delivered.contains(id))
+        .chain(added)
+        .collect();
+    assert_eq!(unique, expected.iter().collect());
+
+    assert_eq!(unscheduled(&queue), 0);
+    assert_eq!(queue.schema_version().unwrap(), QUEUE_SCHEMA_VERSION);
+    // Both connections keep working on the new schema
+    queue.enqueue(message("after"), Priority::High).unwrap();
+    assert!(queue.fetch_pending().unwrap().iter().any(|queued| queued.message.id == "after"));
+}
+
+#[test]
+fn test_interrupted_migration_resumes_from_last_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("message_queue.db");
+    seed_v1_queue(&path, 5 * MIGRATION_BATCH_ROWS);
+
+    let mut queue = MessageQueue::new_with_path(&path).unwrap();
+    let mut batches = 0;
+    let stopped = queue
+        .migrate_schema(|_| {
+            batches += 1;
+            if batches == 2 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        })
+        .unwrap();
+    assert_eq!(
+        stopped,
+        QueueMigration { done: 2 * MIGRATION_BATCH_ROWS, total: 5 * MIGRATION_BATCH_ROWS, version: 1 }
+    );
+    assert!(!stopped.is_complete());
+    assert_eq!(unscheduled(&queue), 3 * MIGRATION_BATCH_ROWS);
+    assert_eq!(queue.interrupted_migration().unwrap(), Some(2 * MIGRATION_BATCH_ROWS));
+    drop(queue);
+
+    // A new run (e.g. after a restart) starts after the committed batches
+    let mut queue = MessageQueue::new_with_path(&path).unwrap();
+    let mut reports = Vec::new();
+    let finished = queue
+        .migrate_schema(|progress| {
+            reports.push(progress.done);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+    assert_eq!(reports[0], 3 * MIGRATION_BATCH_ROWS);
+    assert!(finished.is_complete());
+    assert_eq!(finished.done, 5 * MIGRATION_BATCH_ROWS);
+    assert_eq!(unscheduled(&queue), 0);
+    let progress_rows: usize = queue.conn.query_row("SELECT COUNT(*) FROM queue_migration", [], |row| row.get(0)).unwrap();
+    assert_eq!(progress_rows, 0);
+    assert_eq!(queue.interrupted_migration().unwrap(), None);
+
+    // Migrating again does nothing
+    assert_eq!(queue.migrate_schema(|_| ControlFlow::Continue(())).unwrap().done, 0);
+}
+
+#[test]
+fn test_writers_wait_for_a_running_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("message_queue.db");
+    let queue = MessageQueue::new_with_path(&path).unwrap();
+
+    let exclusive = queue.gate.exclusive();
+    let writer = {
+        let path = path.clone();
+        std::thread::spawn(move || {
+            let mut queue = MessageQueue::new_with_path(&path).unwrap();
+            queue.enqueue(message("waiting"), Priority::Normal).unwrap();
+        })
+    };
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(!writer.is_finished());
+    assert_eq!(queue.size().unwrap(), 0);
+
+    drop(exclusive);
+    writer.join().unwrap();
+    assert_eq!(queue.size().unwrap(), 1);
+    assert_eq!(queue.gate.pauses(), 1);
+}
+
+#[test]
+fn test_migrated_queue_fetches_through_due_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("message_queue.db");
+    seed_v1_queue(&path, 10);
+
+    // Small queues migrate on open
+    let mut queue = MessageQueue::new_with_path(&path).unwrap();
+    assert_eq!(queue.schema_version().unwrap(), QUEUE_SCHEMA_VERSION);
+    assert_eq!(unscheduled(&queue), 0);
+    queue.enqueue(message("fresh"), Priority::Normal).unwrap();
+    assert_eq!(unscheduled(&queue), 0);
+
+    let plan: Vec<String> = {
+        let mut stmt = queue.conn.prepare(&format!("EXPLAIN QUERY PLAN {}", FETCH_DUE_QUERY)).unwrap();
+        stmt.query_map(params![0], |row| row.get(3)).unwrap().collect::<rusqlite::Result<_>>().unwrap()
+    };
+    assert!(plan.iter().any(|detail| detail.contains(QUEUE_DUE_INDEX)), "{:?}", plan);
+    assert!(!plan.iter().any(|detail| detail.contains("TEMP B-TREE")), "{:?}", plan);
+
+    // Reopening keeps the new index and does not bring the old one back
+    drop(queue);
+    let queue = MessageQueue::new_with_path(&path).unwrap();
+    let indexes: Vec<String> = {
+        let mut stmt = queue
+            .conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'index' AND tbl_name = 'message_queue' AND sql IS NOT NULL")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap()
+    };
+    assert_eq!(indexes, [QUEUE_DUE_INDEX]);
+}
@@ -204,6 +204,26 @@ fn test_mark_failed_removes_after_max_retries() {
     assert_eq!(queue.size().expect("Failed to get size"), 0);
 }
 
+#[test]
+fn test_contains_and_has_pending_for() {
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+    queue.set_max_retries(2);
+    assert!(!queue.contains("msg1").unwrap());
+    assert!(!queue.has_pending_for("bob").unwrap());
+
+    queue.enqueue(create_test_message("msg1", "alice", "bob"), Priority::Normal).unwrap();
+    assert!(queue.contains("msg1").unwrap());
+    assert!(queue.has_pending_for("bob").unwrap());
+    assert!(!queue.has_pending_for("carol").unwrap());
+
+    // A rescheduled message is still there; a dropped one is not
+    queue.mark_failed("msg1").unwrap();
+    assert!(queue.contains("msg1").unwrap());
+    queue.mark_failed("msg1").unwrap();
+    assert!(!queue.contains("msg1").unwrap());
+    assert!(!queue.has_pending_for("bob").unwrap());
+}
+
 #[test]
 fn test_fetch_pending_respects_retry_time() {
     let mut queue = MessageQueue::new().expect("Failed to create queue");
@@ -835,3 +855,491 @@ fn test_integration_appstate_queue_sync() {
     assert!(!app_state.get_chat("bob").unwrap().has_pending_messages);
     assert!(app_state.get_chat("charlie").unwrap().has_pending_messages);
 }
+
+#[test]
+fn test_select_retry_lanes_pauses_bulk() {
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+
+    queue.enqueue(create_test_message("bulk_low", "me", "alice"), Priority::Low).expect("Failed to enqueue");
+    queue.enqueue(create_test_message("bulk_normal", "me", "alice"), Priority::Normal).expect("Failed to enqueue");
+    queue.enqueue(create_test_message("chat_high", "me", "bob"), Priority::High).expect("Failed to enqueue");
+    queue.enqueue(create_test_message("chat_urgent", "me", "bob"), Priority::Urgent).expect("Failed to enqueue");
+
+    // Paused: only the interactive lane goes out
+    let paused = select_retry_lanes(queue.list().expect("Failed to list"), true);
+    let mut ids: Vec<_> = paused.iter().map(|m| m.message.id.as_str()).collect();
+    ids.sort();
+    assert_eq!(ids, vec!["chat_high", "chat_urgent"]);
+
+    // Bulk messages stay queued and resume once the pause ends
+    assert_eq!(queue.list().expect("Failed to list").len(), 4);
+    let resumed = select_retry_lanes(queue.list().expect("Failed to list"), false);
+    assert_eq!(resumed.len(), 4);
+}
+
+#[test]
+fn test_queue_preserves_metadata_through_retries() {
+    use crate::storage::{MessageMetadata, MetadataValue};
+
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+    let mut metadata = MessageMetadata::new();
+    metadata.insert("ticket".to_string(), MetadataValue::from("T-9"));
+    metadata.insert("attempt_budget".to_string(), MetadataValue::from(3i64));
+
+    let msg = create_test_message("meta", "me", "alice").with_metadata(metadata.clone()).unwrap();
+    queue.enqueue(msg, Priority::Normal).expect("Failed to enqueue");
+    queue.enqueue_with_type(create_test_message("plain", "me", "alice"), Priority::Normal, "text").expect("Failed to enqueue");
+
+    // Metadata survives a failed attempt and is available to the retry
+    queue.mark_failed("meta").expect("Failed to mark failed");
+    let pending = queue.fetch_all_pending().expect("Failed to fetch");
+    let meta = pending.iter().find(|m| m.message.id == "meta").unwrap();
+    let plain = pending.iter().find(|m| m.message.id == "plain").unwrap();
+    assert_eq!(meta.attempts, 1);
+    assert_eq!(meta.message.metadata, metadata);
+    assert!(plain.message.metadata.is_empty());
+}
+
+#[test]
+fn test_queue_adds_metadata_column_to_existing_database() {
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let db_path = temp_dir.path().join("legacy_queue.db");
+
+    // Simulate a queue created before metadata existed
+    {
+        let conn = rusqlite::Connection::open(&db_path).expect("Failed to open database");
+        conn.execute(
+            "CREATE TABLE message_queue (
+                message_id TEXT PRIMARY KEY,
+                target_uid TEXT NOT NULL,
+                message_type TEXT NOT NULL,
+                payload BLOB NOT NULL,
+                last_attempt INTEGER,
+                retry_count INTEGER NOT NULL DEFAULT 0,
+                sender TEXT NOT NULL,
+                recipient TEXT NOT NULL,
+                content BLOB NOT NULL,
+                timestamp INTEGER NOT NULL,
+                priority INTEGER NOT NULL,
+                next_retry INTEGER NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO message_queue VALUES ('old', 'bob', 'text', X'01', NULL, 0, 'me', 'bob', X'01', 1, 1, 0, 0)",
+            params![],
+        ).unwrap();
+    }
+
+    let queue = MessageQueue::new_with_path(&db_path).expect("Failed to open legacy queue");
+    let messages = queue.list().expect("Failed to list");
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].message.metadata.is_empty());
+}
+
+/// Queue with a mix of priorities, types, retries and errors
+fn seeded_queue(alice: &str, bob: &str) -> MessageQueue {
+    let mut queue = MessageQueue::new().unwrap();
+    let mut text = Message::new("m-text".to_string(), alice.to_string(), bob.to_string(), b"secret plans".to_vec(), 1);
+    text.metadata.insert("reply_to".to_string(), crate::storage::MetadataValue::Text("m-0".to_string()));
+    queue.enqueue(text, Priority::Normal).unwrap();
+    queue
+        .enqueue_with_type(create_test_message("m-ping", alice, bob), Priority::Urgent, "ping")
+        .unwrap();
+    queue.enqueue(create_test_message("m-low", alice, bob), Priority::Low).unwrap();
+    queue.enqueue(create_test_message("m-high", alice, bob), Priority::High).unwrap();
+
+    queue.record_error("m-text", &format!("connection refused by {}", bob)).unwrap();
+    queue.mark_failed("m-text").unwrap();
+    queue.schedule_retry("m-low", -60_000).unwrap();
+    queue
+}
+
+#[test]
+fn test_debug_export_redacts_content_and_uids() {
+    let alice = "a1".repeat(16);
+    let bob = "b2".repeat(16);
+    let queue = seeded_queue(&alice, &bob);
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("queue_report.json");
+
+    assert_eq!(queue.export_debug(&path).unwrap(), 4);
+    let json = std::fs::read_to_string(&path).unwrap();
+
+    assert!(!json.contains(&alice));
+    assert!(!json.contains(&bob));
+    assert!(!json.contains(&alice[..DEBUG_UID_CHARS + 1]));
+    assert!(!json.contains(&bob[..DEBUG_UID_CHARS + 1]));
+    assert!(!json.contains("secret plans"));
+    assert!(!json.contains(&hex::encode(b"secret plans")));
+    assert!(!json.contains("m-0"), "metadata values are left out");
+    assert!(json.contains(&format!("connection refused by {}", &bob[..DEBUG_UID_CHARS])));
+}
+
+#[test]
+fn test_debug_report_complete() {
+    let (alice, bob) = ("a1".repeat(16), "b2".repeat(16));
+    let queue = seeded_queue(&alice, &bob);
+    let now = Utc::now().timestamp_millis();
+    let report = queue.debug_report(now).unwrap();
+
+    assert_eq!(report.version, QUEUE_DEBUG_VERSION);
+    assert_eq!(report.max_retries, 5);
+    let ids: Vec<&str> = report.rows.iter().map(|r| r.message_id.as_str()).collect();
+    let listed: Vec<String> = queue.list().unwrap().into_iter().map(|q| q.message.id).collect();
+    assert_eq!(ids, listed);
+
+    let text = report.rows.iter().find(|r| r.message_id == "m-text").unwrap();
+    assert_eq!(text.target, &bob[..DEBUG_UID_CHARS]);
+    assert_eq!(text.sender, &alice[..DEBUG_UID_CHARS]);
+    assert_eq!(text.message_type, "text");
+    assert_eq!(text.priority, Priority::Normal);
+    assert_eq!(text.attempts, 1);
+    assert_eq!(text.state, QueueRowState::Waiting);
+    assert!(text.last_attempt.is_some());
+    assert_eq!(text.last_error.as_deref(), Some(format!("connection refused by {}", &bob[..DEBUG_UID_CHARS]).as_str()));
+    assert!(text.updated_at >= text.created_at);
+    assert_eq!(text.content_len, 12);
+    assert_eq!(text.content_sha256, hex::encode(ring::digest::digest(&ring::digest::SHA256, b"secret plans")));
+    assert_eq!(text.metadata_keys, vec!["reply_to".to_string()]);
+
+    let ping = report.rows.iter().find(|r| r.message_id == "m-ping").unwrap();
+    assert_eq!((ping.message_type.as_str(), ping.state, ping.attempts), ("ping", QueueRowState::Due, 0));
+    assert!(ping.last_error.is_none());
+}
+
+#[test]
+fn test_debug_import_replays_schedule() {
+    let (alice, bob) = ("a1".repeat(16), "b2".repeat(16));
+    let mut original = seeded_queue(&alice, &bob);
+    let start = Utc::now().timestamp_millis();
+    let report = original.debug_report(start).unwrap();
+
+    let mut scratch = MessageQueue::new().unwrap();
+    scratch.set_debug_import(true);
+    assert_eq!(scratch.import_debug_report(&report).unwrap(), 4);
+    assert_eq!(scratch.debug_report(start).unwrap().rows.len(), 4);
+
+    // Step both queues through the same virtual clock, failing every delivery
+    let fetch_order = |queue: &MessageQueue, now: i64| -> Vec<String> {
+        queue.fetch_pending_at(now).unwrap().into_iter().map(|q| q.message.id).collect()
+    };
+    for step in 0..8 {
+        let now = start + step * 5_000;
+        let expected = fetch_order(&original, now);
+        assert_eq!(fetch_order(&scratch, now), expected, "diverged at step {}", step);
+        for id in expected {
+            original.mark_failed_at(&id, now).unwrap();
+            scratch.mark_failed_at(&id, now).unwrap();
+        }
+    }
+    assert_eq!(scratch.size().unwrap(), original.size().unwrap());
+}
+
+#[test]
+fn test_debug_import_gated() {
+    let (alice, bob) = ("a1".repeat(16), "b2".repeat(16));
+    let original = seeded_queue(&alice, &bob);
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("queue_report.json");
+    original.export_debug(&path).unwrap();
+
+    let mut scratch = MessageQueue::new().unwrap();
+    assert!(matches!(scratch.import_debug(&path), Err(Error::NotSupported(_))));
+    assert_eq!(scratch.size().unwrap(), 0);
+
+    // Only into an empty queue, even with the flag on
+    scratch.set_debug_import(true);
+    assert_eq!(scratch.import_debug(&path).unwrap(), 4);
+    assert!(matches!(scratch.import_debug(&path), Err(Error::Queue(_))));
+}
+
+/// Message with recognisable text content
+fn secret_message(id: &str, text: &str) -> Message {
+    Message::new(id.to_string(), "alice".to_string(), "bob".to_string(), text.as_bytes().to_vec(), Utc::now().timestamp_millis())
+}
+
+/// Raw `content` and `payload` columns of every queued row
+fn raw_content(path: &std::path::Path) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    let mut stmt = conn.prepare("SELECT content, payload FROM message_queue ORDER BY message_id").unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .unwrap()
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[test]
+fn test_queue_content_sealed_at_rest() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let path = temp_dir.path().join("queue.db");
+    let keypair = crate::crypto::KeyPair::generate().unwrap();
+    let mut queue = MessageQueue::new_for_identity(&path, &keypair).unwrap();
+
+    queue.enqueue(secret_message("m1", "meet at the old bridge"), Priority::Normal).unwrap();
+    queue.enqueue_with_type(secret_message("m2", "ping token text"), Priority::Urgent, "ping").unwrap();
+
+    // Nothing readable in the file, in either column
+    for (content, payload) in raw_content(&path) {
+        assert_eq!(content[0], CONTENT_SEALED);
+        assert_eq!(content, payload);
+        assert!(!contains(&content, b"old bridge") && !contains(&content, b"token text"));
+    }
+
+    // Fetching returns the plaintext
+    let pending = queue.fetch_pending().unwrap();
+    let all = queue.fetch_all_pending().unwrap();
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[0].message.content.as_ref(), b"ping token text");
+    assert_eq!(all[1].message.content.as_ref(), b"meet at the old bridge");
+
+    // The key is the same every time for one identity
+    assert_eq!(keypair.queue_content_key().unwrap(), keypair.queue_content_key().unwrap());
+    let report = queue.debug_report(Utc::now().timestamp_millis()).unwrap();
+    assert_eq!(report.rows[1].content_len, "meet at the old bridge".len());
+}
+
+#[test]
+fn test_queue_legacy_plaintext_rows_migrated() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let path = temp_dir.path().join("queue.db");
+
+    // A queue file written before content markers existed
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE message_queue (
+                 message_id TEXT PRIMARY KEY, target_uid TEXT NOT NULL, message_type TEXT NOT NULL,
+                 payload BLOB NOT NULL, last_attempt INTEGER, retry_count INTEGER NOT NULL DEFAULT 0,
+                 sender TEXT NOT NULL, recipient TEXT NOT NULL, content BLOB NOT NULL,
+                 timestamp INTEGER NOT NULL, priority INTEGER NOT NULL, next_retry INTEGER NOT NULL,
+                 created_at INTEGER NOT NULL
+             );",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO message_queue VALUES ('old', 'bob', 'text', ?1, NULL, 0, 'alice', 'bob', ?1, 1, 1, 0, 0)",
+            params![b"legacy plaintext".to_vec()],
+        )
+        .unwrap();
+    }
+
+    // Readable without an identity; the row now carries the plain marker
+    let queue = MessageQueue::new_with_path(&path).unwrap();
+    assert_eq!(queue.fetch_all_pending().unwrap()[0].message.content.as_ref(), b"legacy plaintext");
+    assert_eq!(raw_content(&path)[0].0, [&[CONTENT_PLAIN][..], b"legacy plaintext"].concat());
+    drop(queue);
+
+    // The first open with an identity seals it
+    let keypair = crate::crypto::KeyPair::generate().unwrap();
+    let mut queue = MessageQueue::new_for_identity(&path, &keypair).unwrap();
+    let (content, _) = &raw_content(&path)[0];
+    assert_eq!(content[0], CONTENT_SEALED);
+    assert!(!contains(content, b"legacy plaintext"));
+    assert_eq!(queue.fetch_all_pending().unwrap()[0].message.content.as_ref(), b"legacy plaintext");
+    assert_eq!(queue.set_identity(&keypair).unwrap(), 0);
+}
+
+#[test]
+fn test_queue_content_not_readable_by_other_identity() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let path = temp_dir.path().join("queue.db");
+    let alice = crate::crypto::KeyPair::generate().unwrap();
+    let bob = crate::crypto::KeyPair::generate().unwrap();
+    assert_ne!(alice.queue_content_key().unwrap(), bob.queue_content_key().unwrap());
+
+    let mut queue = MessageQueue::new_for_identity(&path, &alice).unwrap();
+    queue.enqueue(secret_message("m1", "for alice's queue only"), Priority::Normal).unwrap();
+    drop(queue);
+
+    let other = MessageQueue::new_for_identity(&path, &bob).unwrap();
+    assert!(matches!(other.fetch_all_pending(), Err(Error::Crypto(_))));
+    let keyless = MessageQueue::new_with_path(&path).unwrap();
+    assert!(matches!(keyless.fetch_all_pending(), Err(Error::Crypto(_))));
+
+    let queue = MessageQueue::new_for_identity(&path, &alice).unwrap();
+    assert_eq!(queue.fetch_all_pending().unwrap()[0].message.content.as_ref(), b"for alice's queue only");
+}
+
+#[test]
+fn test_queue_content_resealed_on_identity_rotation() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let path = temp_dir.path().join("queue.db");
+    let old_identity = crate::crypto::KeyPair::generate().unwrap();
+    let new_identity = crate::crypto::KeyPair::generate().unwrap();
+
+    let mut queue = MessageQueue::new_for_identity(&path, &old_identity).unwrap();
+    queue.enqueue(secret_message("m1", "first"), Priority::Normal).unwrap();
+    queue.enqueue(secret_message("m2", "second"), Priority::Low).unwrap();
+    let before = raw_content(&path);
+
+    assert_eq!(queue.rotate_identity(&new_identity).unwrap(), 2);
+    let after = raw_content(&path);
+    assert!(before.iter().zip(&after).all(|(old, new)| old.0 != new.0 && new.1 == new.0));
+    let contents: Vec<_> = queue.fetch_all_pending().unwrap().into_iter().map(|q| q.message.content.to_vec()).collect();
+    assert_eq!(contents, vec![b"first".to_vec(), b"second".to_vec()]);
+
+    // New rows use the new key; the old identity can no longer read anything
+    queue.enqueue(secret_message("m3", "third"), Priority::Low).unwrap();
+    drop(queue);
+    assert!(MessageQueue::new_for_identity(&path, &old_identity).unwrap().fetch_all_pending().is_err());
+    assert_eq!(MessageQueue::new_for_identity(&path, &new_identity).unwrap().fetch_all_pending().unwrap().len(), 3);
+}
+
+/// Queue with one retry, a message per id to `target` and the given error recorded
+fn exhausted_queue(ids: &[&str], target: &str, error: &str, now: i64) -> MessageQueue {
+    let mut queue = MessageQueue::new().unwrap();
+    queue.set_max_retries(1);
+    for id in ids {
+        queue.enqueue(create_test_message(id, "alice", target), Priority::Normal).unwrap();
+        queue.record_error(id, error).unwrap();
+        queue.mark_failed_at(id, now).unwrap();
+    }
+    queue
+}
+
+const UNREACHABLE: &str = "Transport error: Message send failed: client error (Connect)";
+
+#[test]
+fn test_exhausted_connectivity_errors_go_dormant() {
+    let now = 1_700_000_000_000;
+
+    // No answer from the peer: kept, but no longer fetched
+    let queue = exhausted_queue(&["m1"], "bob", UNREACHABLE, now);
+    assert!(queue.contains("m1").unwrap());
+    assert_eq!(queue.count_dormant().unwrap(), 1);
+    assert!(queue.fetch_pending_at(now + 1_000_000).unwrap().is_empty());
+    assert!(queue.fetch_all_pending().unwrap().is_empty());
+    assert!(queue.has_pending_for("bob").unwrap());
+
+    // The peer refused it, or no error was recorded: dropped as before
+    let rejected = "Transport error: Message send failed with status 403 Forbidden";
+    for error in [rejected, CONTACT_NOT_FOUND_ERROR, "Crypto error: bad key"] {
+        let queue = exhausted_queue(&["m1"], "bob", error, now);
+        assert!(!queue.contains("m1").unwrap(), "{}", error);
+    }
+    let mut queue = MessageQueue::new().unwrap();
+    queue.set_max_retries(1);
+    queue.enqueue(create_test_message("m1", "alice", "bob"), Priority::Normal).unwrap();
+    queue.mark_failed_at("m1", now).unwrap();
+    assert!(!queue.contains("m1").unwrap());
+
+    assert_eq!(FailureKind::classify(Some(UNREACHABLE)), FailureKind::Connectivity);
+    assert_eq!(FailureKind::classify(Some("Delivery failed with status 503 Service Unavailable")), FailureKind::Connectivity);
+    assert_eq!(FailureKind::classify(Some(rejected)), FailureKind::Rejected);
+    assert_eq!(FailureKind::classify(None), FailureKind::Rejected);
+}
+
+#[test]
+fn test_rate_limited_and_timed_out_answers_are_connectivity() {
+    // As the transport and the loopback carrier format them
+    let rate_limited = [
+        "Transport error: Message send failed with status 429 Too Many Requests",
+        "Transport error: Delivery failed with status 429 Too Many Requests",
+        "Transport error: Ping failed with status 429 Too Many Requests: auto-import cap reached",
+        "Transport error: Message send failed with status 408 Request Timeout",
+    ];
+    for error in rate_limited {
+        assert_eq!(FailureKind::classify(Some(error)), FailureKind::Connectivity, "{}", error);
+    }
+    assert_eq!(
+        FailureKind::classify(Some("Transport error: Ping failed with status 422 Unprocessable Entity: token expires too soon")),
+        FailureKind::Rejected
+    );
+    assert_eq!(FailureKind::classify(Some("Transport error: Message send failed with status 4")), FailureKind::Connectivity);
+
+    // A peer that is only rate limiting keeps the message dormant instead of dropping it
+    let now = 1_700_000_000_000;
+    let queue = exhausted_queue(&["m1"], "bob", rate_limited[0], now);
+    assert!(queue.contains("m1").unwrap());
+    assert_eq!(queue.count_dormant().unwrap(), 1);
+}
+
+#[test]
+fn test_dormant_messages_resurrect_in_queue_order() {
+    let now = 1_700_000_000_000;
+    let mut queue = exhausted_queue(&["b1", "b2", "b3"], "bob", UNREACHABLE, now);
+    queue.enqueue(create_test_message("c1", "alice", "carol"), Priority::Normal).unwrap();
+    queue.record_error("c1", UNREACHABLE).unwrap();
+    queue.mark_failed_at("c1", now).unwrap();
+    assert_eq!(queue.count_dormant().unwrap(), 4);
+
+    // Hearing from bob brings back only bob's messages, due at once with fresh attempts
+    let later = now + 60_000;
+    assert_eq!(queue.resurrect_for_at("bob", later).unwrap(), 3);
+    let due = queue.fetch_pending_at(later).unwrap();
+    let ids: Vec<&str> = due.iter().map(|q| q.message.id.as_str()).collect();
+    assert_eq!(ids, ["b1", "b2", "b3"]);
+    assert!(due.iter().all(|q| q.attempts == 0 && q.next_retry == later));
+    assert_eq!(queue.count_dormant().unwrap(), 1);
+    assert_eq!(queue.resurrect_for_at("bob", later).unwrap(), 0);
+
+    // They get the full retry budget again before going dormant
+    queue.set_max_retries(2);
+    queue.record_error("b1", UNREACHABLE).unwrap();
+    queue.mark_failed_at("b1", later).unwrap();
+    assert_eq!(queue.count_dormant().unwrap(), 1);
+}
+
+#[test]
+fn test_dormant_messages_expire_after_30_days() {
+    let day = 24 * 60 * 60 * 1000;
+    let start = 1_700_000_000_000;
+    let mut queue = exhausted_queue(&["b1"], "bob", UNREACHABLE, start);
+    queue.enqueue(create_test_message("c1", "alice", "carol"), Priority::Normal).unwrap();
+    queue.record_error("c1", UNREACHABLE).unwrap();
+    queue.mark_failed_at("c1", start + 10 * day).unwrap();
+    assert_eq!(DEFAULT_DORMANT_EXPIRY_MS, 30 * day);
+
+    assert!(queue.expire_dormant_at(start + 30 * day - 1).unwrap().is_empty());
+    assert_eq!(queue.expire_dormant_at(start + 30 * day).unwrap(), ["bob"]);
+    assert!(!queue.contains("b1").unwrap());
+    assert!(queue.contains("c1").unwrap());
+    assert_eq!(queue.expire_dormant_at(start + 40 * day).unwrap(), ["carol"]);
+    assert_eq!(queue.size().unwrap(), 0);
+
+    // Resurrected messages no longer count towards the expiry
+    let mut queue = exhausted_queue(&["b1"], "bob", UNREACHABLE, start);
+    queue.resurrect_for_at("bob", start + day).unwrap();
+    assert!(queue.expire_dormant_at(start + 60 * day).unwrap().is_empty());
+    assert!(queue.contains("b1").unwrap());
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn test_dormant_state_exposed_and_resumed_by_app() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let mut app = crate::tui::App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    let now = Utc::now().timestamp_millis();
+    app.queue.set_max_retries(1);
+    app.queue.enqueue(create_test_message("b1", "alice", "bob"), Priority::Normal).unwrap();
+    app.queue.record_error("b1", UNREACHABLE).unwrap();
+    app.queue.mark_failed_at("b1", now).unwrap();
+
+    // The debug report and the Diagnostics queue line show it as dormant
+    let report = app.queue.debug_report(now).unwrap();
+    assert_eq!(report.rows[0].state, QueueRowState::Dormant);
+    assert_eq!(report.rows[0].dormant_since, Some(now));
+    app.show_diagnostics_screen();
+    let screen = app.diagnostics_screen.as_ref().unwrap();
+    assert_eq!((screen.queue_size, screen.dormant_count), (1, 1));
+
+    // Authenticated traffic from someone else changes nothing
+    app.record_heard_from("carol");
+    assert_eq!(app.resume_dormant_messages(), 0);
+
+    // Hearing from bob (twice in one batch) resumes his message once
+    app.record_heard_from("bob");
+    app.record_heard_from("bob");
+    assert_eq!(app.resume_dormant_messages(), 1);
+    assert_eq!(app.queue.count_dormant().unwrap(), 0);
+    assert_eq!(app.queue.fetch_pending().unwrap().len(), 1);
+    let notice = app.notifications.visible(std::time::Instant::now()).unwrap();
+    assert!(notice.contains("resending 1 message(s)"));
+    assert_eq!(app.resume_dormant_messages(), 0);
+}
@@ -0,0 +1,204 @@
+// Queued ping tests - token made at send time with the current address, pings without a token to contacts that have the current one, token validity from the settings, queue rows holding a literal token from older versions
+
+use crate::crypto::KeyPair;
+use crate::queue::{Priority, QueuedMessage};
+use crate::storage::{parse_contact_token, AppState, Contact, Message, Storage};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, PeerTransport, TransportRegistry};
+use crate::tui::{App, OwnTokenSource, FRESH_TOKEN_MARKER};
+use chrono::{DateTime, Duration, Utc};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+
+/// Storage holding our identity at `address`
+fn storage_at(keypair: &KeyPair, address: &str) -> Storage {
+    let storage = Storage::new_in_memory().unwrap();
+    let mut state = AppState::new();
+    state.user_keypair = Some(keypair.clone());
+    state.user_ip = Some(address.to_string());
+    state.save_to_db(&storage).unwrap();
+    storage
+}
+
+/// Move our stored address to `address`, like a new connectivity result does
+fn move_to(storage: &Storage, address: &str) {
+    let mut state = AppState::load_from_db(storage).unwrap();
+    state.user_ip = Some(address.to_string());
+    state.save_to_db(storage).unwrap();
+}
+
+fn bob() -> Contact {
+    Contact::new(
+        "bob_uid".to_string(),
+        "loopback://bob".to_string(),
+        vec![0; 32],
+        vec![0; 32],
+        Utc::now() + Duration::days(30),
+    )
+}
+
+fn queued_ping(keypair: &KeyPair, content: &[u8]) -> QueuedMessage {
+    QueuedMessage {
+        message: Message::new(
+            "ping-1".to_string(),
+            keypair.uid.to_string(),
+            "bob_uid".to_string(),
+            content.to_vec(),
+            Utc::now().timestamp_millis(),
+        ),
+        priority: Priority::Urgent,
+        attempts: 3,
+        next_retry: 0,
+    }
+}
+
+/// Loopback receiver at "bob" recording the tokens handed to its ping handler
+async fn recording_receiver(network: &LoopbackNetwork) -> (LoopbackTransport, Arc<Mutex<Vec<String>>>) {
+    let tokens = Arc::new(Mutex::new(Vec::new()));
+    let tokens_clone = tokens.clone();
+    let receiver = LoopbackTransport::new(network);
+    receiver
+        .set_ping_handler(move |token| {
+            tokens_clone.lock().unwrap().push(token);
+            Ok(())
+        })
+        .await;
+    receiver.start_listener("bob").await.unwrap();
+    (receiver, tokens)
+}
+
+/// Send `queued` to bob through `storage` like the retry worker does
+async fn send(transports: &TransportRegistry, storage: &Storage, keypair: &KeyPair, contact: &Contact, queued: &QueuedMessage) {
+    App::send_queued_ping(transports, storage, keypair, "127.0.0.1:8080", None, contact, queued)
+        .await
+        .expect("ping answered");
+}
+
+fn expiry_close_to(expiry: DateTime<Utc>, expected: DateTime<Utc>) -> bool {
+    (expiry - expected).num_seconds().abs() < 60
+}
+
+#[tokio::test]
+async fn test_queued_ping_carries_address_at_send_time() {
+    let network = LoopbackNetwork::new();
+    let (_receiver, tokens) = recording_receiver(&network).await;
+    let transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(&network)));
+    let keypair = KeyPair::generate().unwrap();
+    let storage = storage_at(&keypair, "203.0.113.5:4000");
+
+    // Queued while we were at the old address, delivered after the move
+    let queued = queued_ping(&keypair, FRESH_TOKEN_MARKER);
+    move_to(&storage, "198.51.100.7:5000");
+    send(&transports, &storage, &keypair, &bob(), &queued).await;
+
+    let sent = tokens.lock().unwrap().clone();
+    assert_eq!(sent.len(), 1);
+    let token = parse_contact_token(&sent[0]).unwrap();
+    assert_eq!(token.ip, "198.51.100.7:5000");
+    assert_eq!(token.uid, keypair.uid.to_string());
+
+    // Without a detected address the worker's own address is used
+    let fresh = Storage::new_in_memory().unwrap();
+    let source = OwnTokenSource::load(&fresh, &keypair, "192.0.2.1:4000", None).unwrap();
+    assert_eq!(source.address, "192.0.2.1:4000");
+}
+
+#[tokio::test]
+async fn test_token_left_out_while_contact_has_the_current_one() {
+    let network = LoopbackNetwork::new();
+    let (_receiver, tokens) = recording_receiver(&network).await;
+    let transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(&network)));
+    let keypair = KeyPair::generate().unwrap();
+    let storage = storage_at(&keypair, "203.0.113.5:4000");
+    let queued = queued_ping(&keypair, FRESH_TOKEN_MARKER);
+
+    // Never heard from: the token goes along
+    let mut contact = bob();
+    send(&transports, &storage, &keypair, &contact, &queued).await;
+    assert_eq!(tokens.lock().unwrap().len(), 1);
+
+    // Heard from before our token last changed: still sent
+    contact.heard_at = Some(Utc::now() - Duration::hours(1));
+    send(&transports, &storage, &keypair, &contact, &queued).await;
+    assert_eq!(tokens.lock().unwrap().len(), 2);
+
+    // Heard from since: the ping is answered without handing over a token
+    contact.heard_at = Some(Utc::now() + Duration::seconds(1));
+    assert!(contact.has_current_token(Utc::now()));
+    send(&transports, &storage, &keypair, &contact, &queued).await;
+    assert_eq!(tokens.lock().unwrap().len(), 2);
+
+    // Our address changes after that: the new token is sent again
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    move_to(&storage, "198.51.100.7:5000");
+    send(&transports, &storage, &keypair, &contact, &queued).await;
+    let sent = tokens.lock().unwrap().clone();
+    assert_eq!(sent.len(), 3);
+    assert_eq!(parse_contact_token(&sent[2]).unwrap().ip, "198.51.100.7:5000");
+
+    // Authenticated traffic stamps the contact in the app
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.contacts.push(bob());
+    app.app_state.reindex();
+    app.record_heard_from("bob_uid");
+    app.resume_dormant_messages();
+    assert!(app.app_state.contact_by_uid("bob_uid").unwrap().heard_at.is_some());
+}
+
+#[test]
+fn test_token_validity_follows_setting() {
+    let keypair = KeyPair::generate().unwrap();
+    let storage = storage_at(&keypair, "203.0.113.5:4000");
+    let mut state = AppState::load_from_db(&storage).unwrap();
+    state.settings.default_contact_expiry_days = 7;
+    state.save_to_db(&storage).unwrap();
+
+    let source = OwnTokenSource::load(&storage, &keypair, "127.0.0.1:8080", None).unwrap();
+    assert_eq!(source.validity, Duration::days(7));
+    let token = parse_contact_token(&source.token_for(&bob()).unwrap().unwrap()).unwrap();
+    assert!(expiry_close_to(token.expiry, Utc::now() + Duration::days(7)), "{}", token.expiry);
+
+    // The default is the contact expiry default, and never under a day
+    let mut state = AppState::load_from_db(&storage).unwrap();
+    state.settings.default_contact_expiry_days = 0;
+    state.save_to_db(&storage).unwrap();
+    assert_eq!(OwnTokenSource::load(&storage, &keypair, "", None).unwrap().validity, Duration::days(1));
+    assert_eq!(AppState::new().settings.own_token_validity(), Duration::days(30));
+}
+
+#[tokio::test]
+async fn test_old_queue_row_with_literal_token_sends_a_fresh_one() {
+    let network = LoopbackNetwork::new();
+    let (_receiver, tokens) = recording_receiver(&network).await;
+    let transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(&network)));
+    let keypair = KeyPair::generate().unwrap();
+    let storage = storage_at(&keypair, "203.0.113.5:4000");
+
+    // Older versions queued the token itself: a day's validity at the old address
+    let old_token = OwnTokenSource {
+        keypair: keypair.clone(),
+        address: "192.0.2.44:4000".to_string(),
+        cert_fingerprint: None,
+        validity: Duration::days(1),
+    }
+    .token_for(&bob())
+    .unwrap()
+    .unwrap();
+    let queued = queued_ping(&keypair, old_token.as_bytes());
+    send(&transports, &storage, &keypair, &bob(), &queued).await;
+
+    let sent = tokens.lock().unwrap().clone();
+    assert_eq!(sent.len(), 1);
+    assert_ne!(sent[0], old_token);
+    let token = parse_contact_token(&sent[0]).unwrap();
+    assert_eq!(token.ip, "203.0.113.5:4000");
+    assert!(expiry_close_to(token.expiry, Utc::now() + Duration::days(30)));
+
+    // A restricted contact still gets no token while only a LAN address is known
+    move_to(&storage, "192.168.1.20:4000");
+    let mut restricted = bob();
+    restricted.trust = crate::storage::TrustTier::Restricted;
+    let result = App::send_queued_ping(&transports, &storage, &keypair, "", None, &restricted, &queued).await;
+    assert!(result.is_err());
+    assert_eq!(tokens.lock().unwrap().len(), 1);
+}
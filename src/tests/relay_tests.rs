@@ -0,0 +1,188 @@
+// Relay tests - store-and-forward through a mutual contact over the loopback transport
+//
+// Peers A and B cannot reach each other; both reach C, which relays.
+
+use crate::crypto::{decrypt_message, encrypt_message, EncryptedEnvelope, KeyPair};
+use crate::messaging::{deliver_via_relay, send_message};
+use crate::queue::{MessageQueue, Priority};
+use crate::relay::*;
+use crate::storage::{Contact, Message, Storage};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, MessageRequest, PeerTransport};
+use chrono::{Duration, Utc};
+use std::sync::{Arc, Mutex};
+use super::helpers::{contact_at, recording_peer};
+
+fn request(from_uid: &str, payload: Vec<u8>) -> MessageRequest {
+    MessageRequest {
+        from_uid: from_uid.to_string(),
+        message_type: "text".to_string(),
+        payload,
+        metadata: Default::default(),
+        message_id: None,
+    }
+}
+
+/// A relay that knows `contacts`
+fn enabled_relay(contacts: &[Contact]) -> Relay {
+    let mut relay = Relay::new();
+    relay.set_enabled(true);
+    relay.set_contacts(contacts);
+    relay
+}
+
+#[tokio::test]
+async fn test_message_relayed_between_unreachable_peers() {
+    let (alice, bob, carol) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let network = LoopbackNetwork::new();
+
+    // Bob listens, but Alice only knows an address behind his NAT
+    let (_bob_transport, received) = recording_peer(&network, "bob").await;
+
+    // Carol relays and reaches both of them
+    let carol_transport = LoopbackTransport::new(&network);
+    let carol_contacts = vec![contact_at(&alice, "loopback://alice"), contact_at(&bob, "loopback://bob")];
+    let relay = Arc::new(Mutex::new(enabled_relay(&carol_contacts)));
+    carol_transport.set_relay(relay.clone()).await;
+    carol_transport.start_listener("carol").await.unwrap();
+
+    // Alice learned Carol's capabilities
+    let mut alice_contacts = vec![contact_at(&bob, "loopback://bob-behind-cgnat"), contact_at(&carol, "loopback://carol")];
+    let capabilities = RelayCapabilities::for_contact(true, &carol_contacts, &alice.uid.to_string());
+    assert!(apply_capabilities(&mut alice_contacts, &carol.uid.to_string(), capabilities, Utc::now()));
+    assert_eq!(relay_candidates(&alice_contacts, &bob.uid.to_string()).len(), 1);
+
+    // End-to-end encrypted payload
+    let secret = alice.derive_shared_secret(bob.x25519_public.as_slice().try_into().unwrap()).unwrap();
+    let sealed = serde_cbor::to_vec(&encrypt_message(&secret, b"hello via carol").unwrap()).unwrap();
+    let message = Message::new(
+        "msg_relayed".to_string(),
+        alice.uid.to_string(),
+        bob.uid.to_string(),
+        sealed.clone(),
+        Utc::now().timestamp_millis(),
+    );
+
+    // Direct delivery fails and queues the message
+    let alice_transport = LoopbackTransport::new(&network);
+    let mut queue = MessageQueue::new().unwrap();
+    assert!(!send_message(&alice_transport, &mut queue, &alice_contacts[0], &message, Priority::Normal).await.unwrap());
+    assert_eq!(queue.size().unwrap(), 1);
+
+    // The relay takes it and the queue empties
+    let relay_uid = deliver_via_relay(&alice_transport, &mut queue, None, &alice_contacts, &message, "text").await.unwrap();
+    assert_eq!(relay_uid, Some(carol.uid.to_string()));
+    assert_eq!(queue.size().unwrap(), 0);
+    assert_eq!(relay.lock().unwrap().queued(&alice.uid.to_string(), &bob.uid.to_string()), 1);
+    assert!(received.lock().unwrap().is_empty());
+
+    // Carol forwards on her next pass; the relay only ever held ciphertext
+    assert_eq!(forward_queued(&relay, &carol_transport).await, 1);
+    assert_eq!(relay.lock().unwrap().total_queued(), 0);
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].from_uid, alice.uid.to_string());
+    assert_eq!(received[0].payload, sealed);
+    let envelope: EncryptedEnvelope = serde_cbor::from_slice(&received[0].payload).unwrap();
+    let bob_secret = bob.derive_shared_secret(alice.x25519_public.as_slice().try_into().unwrap()).unwrap();
+    assert_eq!(decrypt_message(&bob_secret, &envelope).unwrap(), b"hello via carol");
+}
+
+#[test]
+fn test_relay_caps_enforced() {
+    let (alice, bob, mallory) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let (alice_uid, bob_uid) = (alice.uid.to_string(), bob.uid.to_string());
+    let contacts = vec![contact_at(&alice, "loopback://alice"), contact_at(&bob, "loopback://bob")];
+    let now = Utc::now();
+
+    let mut relay = Relay::new();
+    relay.set_contacts(&contacts);
+    let envelope = RelayEnvelope::new(&bob_uid, request(&alice_uid, b"sealed".to_vec()));
+    assert_eq!(relay.accept(envelope.clone(), now), Err(RelayRefusal::Disabled));
+    relay.set_enabled(true);
+
+    // Only between the relay's own contacts, with a consistent sender
+    let stranger = RelayEnvelope::new(mallory.uid.to_string(), request(&alice_uid, vec![]));
+    assert_eq!(relay.accept(stranger, now), Err(RelayRefusal::UnknownPeer));
+    let mut spoofed = envelope.clone();
+    spoofed.from_uid = bob_uid.clone();
+    assert_eq!(relay.accept(spoofed, now), Err(RelayRefusal::Malformed));
+    let too_large = RelayEnvelope::new(&bob_uid, request(&alice_uid, vec![0; MAX_RELAY_PAYLOAD_BYTES + 1]));
+    assert_eq!(relay.accept(too_large, now).unwrap_err().status_code(), 413);
+
+    // Queue cap per pair; the reverse direction has its own queue
+    for _ in 0..MAX_QUEUED_PER_PAIR {
+        relay.accept(envelope.clone(), now).unwrap();
+    }
+    assert_eq!(relay.accept(envelope.clone(), now), Err(RelayRefusal::QueueFull));
+    relay.accept(RelayEnvelope::new(&alice_uid, request(&bob_uid, vec![])), now).unwrap();
+    assert_eq!(relay.queued(&alice_uid, &bob_uid), MAX_QUEUED_PER_PAIR);
+
+    // Rate cap per pair, even once the queue has drained
+    let mut relay = enabled_relay(&contacts);
+    for _ in 0..MAX_RELAYED_PER_WINDOW {
+        relay.accept(envelope.clone(), now).unwrap();
+        relay.take_queued();
+    }
+    assert_eq!(relay.accept(envelope.clone(), now), Err(RelayRefusal::RateLimited));
+    assert!(relay.accept(envelope, now + Duration::seconds(RELAY_WINDOW_SECS + 1)).is_ok());
+}
+
+#[tokio::test]
+async fn test_no_relay_leaves_message_queued() {
+    let (alice, bob, carol) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let network = LoopbackNetwork::new();
+    let transport = LoopbackTransport::new(&network);
+    let mut queue = MessageQueue::new().unwrap();
+
+    let message = Message::new(
+        "msg_stuck".to_string(),
+        alice.uid.to_string(),
+        bob.uid.to_string(),
+        b"sealed".to_vec(),
+        Utc::now().timestamp_millis(),
+    );
+    queue.enqueue(message.clone(), Priority::Normal).unwrap();
+
+    // Carol is a contact but never advertised relaying
+    let mut contacts = vec![contact_at(&bob, "loopback://bob"), contact_at(&carol, "loopback://carol")];
+    assert_eq!(deliver_via_relay(&transport, &mut queue, None, &contacts, &message, "text").await.unwrap(), None);
+    assert!(queue.contains("msg_stuck").unwrap());
+
+    // Carol advertises relaying but is offline
+    let capabilities = RelayCapabilities {
+        accepts_relay: true,
+        reachable: vec![relay_uid_hash(&bob.uid.to_string())],
+        edits: true,
+    };
+    apply_capabilities(&mut contacts, &carol.uid.to_string(), capabilities, Utc::now());
+    assert_eq!(deliver_via_relay(&transport, &mut queue, None, &contacts, &message, "text").await.unwrap(), None);
+    assert!(queue.contains("msg_stuck").unwrap());
+
+    // Withdrawing relaying clears what the contact reaches
+    apply_capabilities(&mut contacts, &carol.uid.to_string(), RelayCapabilities::default(), Utc::now());
+    assert!(!contacts[1].is_relay);
+    assert!(contacts[1].relay_reachable.is_empty());
+}
+
+#[test]
+fn test_relay_state_persisted() {
+    let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let storage = Storage::new_in_memory().unwrap();
+
+    let capabilities = RelayCapabilities::for_contact(true, &[contact_at(&alice, "a:1"), contact_at(&bob, "b:1")], &bob.uid.to_string());
+    let decoded = RelayCapabilities::from_payload(&capabilities.to_payload().unwrap()).unwrap();
+    assert_eq!(decoded.reachable, vec![relay_uid_hash(&alice.uid.to_string())]);
+    assert!(!decoded.reachable.contains(&alice.uid.to_string()));
+
+    let mut relay_contact = contact_at(&bob, "b:1");
+    apply_capabilities(std::slice::from_mut(&mut relay_contact), &bob.uid.to_string(), decoded, Utc::now());
+    storage.save_contact(&relay_contact).unwrap();
+    let loaded = storage.load_contacts().unwrap();
+    assert!(loaded[0].is_relay);
+    assert!(loaded[0].reaches_via_relay(&relay_uid_hash(&alice.uid.to_string())));
+
+    let mut message = Message::new("m1".to_string(), "me".to_string(), bob.uid.to_string(), b"hi".to_vec(), 1);
+    message.mark_delivered_via_relay("carol_uid");
+    assert_eq!(message.status_text(), "delivered via relay carol_uid");
+}
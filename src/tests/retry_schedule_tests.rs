@@ -0,0 +1,140 @@
+// Retry schedule tests - parking on an empty queue, alignment to the earliest next_retry, backlog chaining (virtual clock), wake-ups, worker mode transitions
+
+use crate::queue::{MessageQueue, Priority};
+use crate::retry_schedule::{plan_next_cycle, RetryMode, RetryPlan, RetryWakeup, RETRY_BATCH_SIZE};
+use crate::storage::Message;
+use crate::tui::App;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use tempfile::TempDir;
+
+fn t0() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap()
+}
+
+fn queued(id: &str) -> Message {
+    Message::new(id.to_string(), "me".to_string(), "peer".to_string(), b"hi".to_vec(), 0)
+}
+
+/// Plan from the queue's state at `now`, as the worker does
+fn plan_from(queue: &MessageQueue, now: DateTime<Utc>, interval: Duration, processed: usize) -> RetryPlan {
+    let earliest = queue.earliest_next_retry().unwrap().and_then(DateTime::from_timestamp_millis);
+    let has_deferred = queue.count_dormant().unwrap() > 0;
+    plan_next_cycle(now, interval, earliest, has_deferred, processed)
+}
+
+#[test]
+fn test_plan_parks_on_empty_queue_and_aligns_to_earliest_retry() {
+    let interval = Duration::minutes(10);
+
+    // Nothing queued: park until woken
+    let plan = plan_next_cycle(t0(), interval, None, false, 0);
+    assert_eq!(plan, RetryPlan { mode: RetryMode::Idle, next_cycle: None });
+    assert_eq!(plan.wait_from(t0()), None);
+
+    // Dormant messages alone still come back on the interval
+    let plan = plan_next_cycle(t0(), interval, None, true, 0);
+    assert_eq!(plan.mode, RetryMode::Scheduled);
+    assert_eq!(plan.next_cycle, Some(t0() + interval));
+
+    // Earliest retry before the interval ends: wake exactly then
+    let plan = plan_next_cycle(t0(), interval, Some(t0() + Duration::seconds(250)), false, 3);
+    assert_eq!(plan.next_cycle, Some(t0() + Duration::seconds(250)));
+    assert_eq!(plan.wait_from(t0()), Some(std::time::Duration::from_secs(250)));
+    assert_eq!(plan.describe(t0()), "scheduled, next in 4m 10s");
+
+    // Never later than the interval
+    let plan = plan_next_cycle(t0(), interval, Some(t0() + Duration::hours(2)), false, 3);
+    assert_eq!(plan.next_cycle, Some(t0() + interval));
+
+    // Due but not taken (e.g. bulk lane paused): the interval, not a spin
+    let plan = plan_next_cycle(t0(), interval, Some(t0() - Duration::seconds(5)), false, 0);
+    assert_eq!(plan.next_cycle, Some(t0() + interval));
+}
+
+#[test]
+fn test_backlog_chains_full_batches_on_virtual_clock() {
+    let mut queue = MessageQueue::new().unwrap();
+    for i in 0..(RETRY_BATCH_SIZE * 2 + 20) {
+        queue.enqueue(queued(&format!("m{}", i)), Priority::Normal).unwrap();
+    }
+    let interval = Duration::minutes(10);
+    let mut now = Utc::now();
+
+    // Each cycle takes one batch, then plans the next (as the worker does);
+    // every 7th message fails and backs off
+    let mut modes = Vec::new();
+    loop {
+        let batch: Vec<_> = queue.fetch_pending_at(now.timestamp_millis()).unwrap().into_iter().take(RETRY_BATCH_SIZE).collect();
+        for (i, msg) in batch.iter().enumerate() {
+            if i % 7 == 0 {
+                queue.mark_failed_at(&msg.message.id, now.timestamp_millis()).unwrap();
+            } else {
+                queue.mark_success(&msg.message.id).unwrap();
+            }
+        }
+        let plan = plan_from(&queue, now, interval, batch.len());
+        modes.push(plan.mode);
+        if plan.mode != RetryMode::Backlog {
+            // Next wake is the first failure's backoff (2s after one failure), not the interval
+            let wait = plan.wait_from(now).unwrap();
+            assert!(wait <= std::time::Duration::from_secs(2), "waits {:?}", wait);
+            break;
+        }
+        // Back-to-back cycles: the virtual clock barely moves
+        now += Duration::milliseconds(10);
+    }
+    // 120 ready messages: two full batches chain, the partial third ends the backlog
+    assert_eq!(modes, vec![RetryMode::Backlog, RetryMode::Backlog, RetryMode::Scheduled]);
+}
+
+#[tokio::test]
+async fn test_wakeup_ends_park_and_is_kept_while_busy() {
+    let wakeup = RetryWakeup::new();
+
+    // No wake-up: a bounded wait runs out
+    assert!(!wakeup.wait(Some(std::time::Duration::from_millis(20))).await);
+
+    // Parked indefinitely until notified from elsewhere
+    let waker = wakeup.clone();
+    let parked = tokio::spawn(async move { wakeup.wait(None).await });
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    waker.notify();
+    let woke = tokio::time::timeout(std::time::Duration::from_secs(1), parked).await;
+    assert!(woke.expect("worker stayed parked").unwrap());
+
+    // Sent while the worker was busy: the next wait ends at once
+    waker.notify();
+    assert!(waker.wait(None).await);
+}
+
+#[test]
+fn test_worker_goes_idle_then_schedules_after_enqueue() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.start_retry_worker().unwrap();
+
+    let wait_for = |app: &App, mode: RetryMode| {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while app.retry_status.plan().mode != mode {
+            assert!(std::time::Instant::now() < deadline, "worker never became {}", mode);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    };
+
+    // Empty queue: parked
+    wait_for(&app, RetryMode::Idle);
+
+    // Queued for an unknown contact: the attempt fails and is retried well
+    // before the 10-minute interval, at the backoff time
+    app.queue.enqueue(queued("m1"), Priority::Normal).unwrap();
+    app.retry_wakeup.notify();
+    wait_for(&app, RetryMode::Scheduled);
+    let plan = app.retry_status.plan();
+    let wait = plan.wait_from(Utc::now()).unwrap();
+    assert!(wait <= std::time::Duration::from_secs(2), "waits {:?}", wait);
+
+    // Stopping wakes the worker instead of waiting out its schedule
+    let started = std::time::Instant::now();
+    app.stop_retry_worker();
+    assert!(started.elapsed() < std::time::Duration::from_secs(2));
+}
@@ -0,0 +1,139 @@
+// Runtime info tests - atomic write/rename, stale files of dead processes, removal only by the owner, rewrite on rebind, publish on start and removal on shutdown
+
+use crate::crypto::KeyPair;
+use crate::storage::{
+    live_instance, process_alive, read_runtime_info, remove_runtime_info, storage_db::Storage, write_runtime_info,
+    AppState, RuntimeInfo,
+};
+use crate::transport::{ProbeOutcome, Transport};
+use crate::tui::{App, ErrorReporter, PortWatchdog, TransportServerStatus};
+use chrono::Utc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use super::helpers::dead_pid;
+
+#[test]
+fn test_write_replaces_atomically() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("app_data").join("runtime.json");
+    let first = RuntimeInfo::for_this_process(4100, Utc::now());
+    write_runtime_info(&path, &first).unwrap();
+    assert_eq!(read_runtime_info(&path).unwrap(), Some(first.clone()));
+
+    // A temporary file left by a crash mid-write is simply overwritten
+    let temp = temp_dir.path().join("app_data").join("runtime.json.tmp");
+    std::fs::write(&temp, b"{\"pid\":").unwrap();
+    let second = RuntimeInfo { port: 4200, ..first };
+    write_runtime_info(&path, &second).unwrap();
+
+    // The rename leaves only the finished file
+    assert_eq!(read_runtime_info(&path).unwrap(), Some(second));
+    assert!(!temp.exists());
+    let names: Vec<_> = std::fs::read_dir(temp_dir.path().join("app_data")).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(names, vec!["runtime.json"]);
+}
+
+#[test]
+fn test_stale_file_of_dead_process_is_removed() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("runtime.json");
+
+    let ours = RuntimeInfo::for_this_process(4100, Utc::now());
+    assert!(process_alive(ours.pid));
+    write_runtime_info(&path, &ours).unwrap();
+    assert_eq!(live_instance(&path, process_alive), Some(ours.clone()));
+    assert!(path.exists());
+
+    // Left behind by a crash: ignored and cleaned up
+    let pid = dead_pid();
+    assert!(!process_alive(pid));
+    write_runtime_info(&path, &RuntimeInfo { pid, ..ours }).unwrap();
+    assert_eq!(live_instance(&path, process_alive), None);
+    assert!(!path.exists());
+
+    // So is a file nobody can parse
+    std::fs::write(&path, b"not json").unwrap();
+    assert_eq!(live_instance(&path, |_| true), None);
+    assert!(!path.exists());
+    assert_eq!(live_instance(&path, |_| true), None);
+}
+
+#[test]
+fn test_only_the_owner_removes_the_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("runtime.json");
+    let other = RuntimeInfo { pid: std::process::id().wrapping_add(1), port: 4100, started_at: 0 };
+    write_runtime_info(&path, &other).unwrap();
+
+    // Another instance's file stays
+    assert!(!remove_runtime_info(&path, std::process::id()).unwrap());
+    assert_eq!(read_runtime_info(&path).unwrap(), Some(other.clone()));
+
+    assert!(remove_runtime_info(&path, other.pid).unwrap());
+    assert!(!path.exists());
+    assert!(!remove_runtime_info(&path, other.pid).unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rebind_rewrites_the_port() {
+    // A stranger answering on our port forces a move to another one
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:0").await.unwrap();
+    let stolen_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok").await;
+        }
+    });
+    let transport = Transport::new();
+    transport.clone().start("0.0.0.0:0".parse().unwrap()).await.unwrap();
+
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("runtime.json");
+    let info = RuntimeInfo::for_this_process(stolen_port, Utc::now());
+    write_runtime_info(&path, &info).unwrap();
+
+    let storage = Storage::new_in_memory().unwrap();
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().unwrap());
+    state.save_to_db(&storage).unwrap();
+    let status = Arc::new(Mutex::new(TransportServerStatus::Running(stolen_port)));
+    let alert = Mutex::new(None);
+    let reports = ErrorReporter::new();
+
+    let mut watchdog = PortWatchdog::new(stolen_port).with_runtime_info(path.clone(), info.clone());
+    let outcome = watchdog.tick(&transport, &status, &storage, &alert, &reports).await;
+    assert_eq!(outcome, ProbeOutcome::Foreign);
+    assert_ne!(watchdog.port(), stolen_port);
+    assert_eq!(read_runtime_info(&path).unwrap(), Some(RuntimeInfo { port: watchdog.port(), ..info }));
+    assert!(reports.is_empty());
+    transport.stop();
+}
+
+#[test]
+fn test_app_publishes_port_and_removes_it_on_shutdown() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(temp_dir.path().join("settings.json"))).unwrap();
+    let path = app.runtime_info_path.clone();
+    assert_eq!(path, temp_dir.path().join("runtime.json"));
+    app.start_transport().unwrap();
+
+    let mut published = None;
+    for _ in 0..200 {
+        if let Some(info) = read_runtime_info(&path).unwrap() {
+            published = Some(info);
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let published = published.expect("runtime info written once the server is running");
+    assert_eq!(*app.transport_server_status.lock().unwrap(), TransportServerStatus::Running(published.port));
+    assert_eq!(published.pid, std::process::id());
+    assert_eq!(published.started_at, app.started_at.timestamp_millis());
+
+    drop(app);
+    assert!(!path.exists());
+}
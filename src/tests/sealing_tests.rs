@@ -0,0 +1,197 @@
+// Sealing tests - send path selection, the key upgrade flow and the contacts migration
+
+use crate::crypto::KeyPair;
+use crate::messaging::send_sealed_message;
+use crate::queue::{MessageQueue, Priority};
+use crate::sealing::*;
+use crate::storage::{Contact, Message, Storage};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, MessageRequest, PeerTransport};
+use chrono::{Duration, Utc};
+use rusqlite::{params, Connection};
+use std::sync::{Arc, Mutex};
+use super::helpers::contact_at;
+
+fn text_message(from: &KeyPair, to: &KeyPair, text: &str) -> Message {
+    Message::new(
+        uuid::Uuid::new_v4().to_string(),
+        from.uid.to_string(),
+        to.uid.to_string(),
+        text.as_bytes().to_vec(),
+        Utc::now().timestamp_millis(),
+    )
+}
+
+/// A loopback listener that records every request it receives
+async fn recording_listener(network: &LoopbackNetwork, name: &str) -> (LoopbackTransport, Arc<Mutex<Vec<MessageRequest>>>) {
+    let transport = LoopbackTransport::new(network);
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let received_clone = received.clone();
+    transport
+        .set_new_message_handler(move |request| {
+            received_clone.lock().unwrap().push(request);
+            Ok(())
+        })
+        .await;
+    transport.start_listener(name).await.unwrap();
+    (transport, received)
+}
+
+#[tokio::test]
+async fn test_missing_key_sends_plaintext_with_reason() {
+    let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let network = LoopbackNetwork::new();
+    let (_bob_transport, received) = recording_listener(&network, "bob").await;
+
+    let keyless = Contact::without_x25519_key(
+        bob.uid.to_string(),
+        "loopback://bob".to_string(),
+        bob.public_key.clone(),
+        Utc::now() + Duration::days(30),
+    );
+    assert_eq!(keyless.x25519_pubkey, None);
+    assert_eq!(send_security(Some(&alice), &keyless), SendSecurity::MissingKey);
+    assert_eq!(SendSecurity::MissingKey.strip_text(), "⚠ Not encrypted: contact has no encryption key");
+    assert!(!SendSecurity::MissingKey.is_encrypted());
+
+    let mut malformed = contact_at(&bob, "loopback://bob");
+    malformed.x25519_pubkey = Some(vec![1, 2, 3]);
+    assert_eq!(send_security(Some(&alice), &malformed), SendSecurity::InvalidKey);
+    assert_eq!(send_security(None, &contact_at(&bob, "loopback://bob")), SendSecurity::NoIdentity);
+
+    // The plaintext path delivers the text unchanged
+    let alice_transport = LoopbackTransport::new(&network);
+    let mut queue = MessageQueue::new().unwrap();
+    let message = text_message(&alice, &bob, "hello in the clear");
+    let (delivered, security) = send_sealed_message(&alice_transport, &mut queue, Some(&alice), &keyless, &message, Priority::Normal)
+        .await
+        .unwrap();
+    assert!(delivered);
+    assert_eq!(security, SendSecurity::MissingKey);
+
+    let received = received.lock().unwrap();
+    assert_eq!(received[0].message_type, "text");
+    assert_eq!(received[0].payload, b"hello in the clear");
+}
+
+#[tokio::test]
+async fn test_key_upgrade_fills_key_and_enables_sealing() {
+    let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let network = LoopbackNetwork::new();
+    let (alice_transport, alice_received) = recording_listener(&network, "alice").await;
+    let (bob_transport, bob_received) = recording_listener(&network, "bob").await;
+
+    // Alice's stored contact for Bob predates the X25519 key
+    let mut alice_contacts = vec![contact_at(&bob, "loopback://bob")];
+    alice_contacts[0].x25519_pubkey = None;
+    let bob_contacts = vec![contact_at(&alice, "loopback://alice")];
+
+    // Alice asks, Bob answers with a fresh token
+    alice_transport.send_message(&alice_contacts[0], &key_upgrade_request(&alice.uid.to_string())).await.unwrap();
+    assert_eq!(bob_received.lock().unwrap()[0].message_type, KEY_UPGRADE_REQUEST_TYPE);
+    let bob_token = contact_at(&bob, "loopback://bob").sign_token(&bob).unwrap();
+    bob_transport.send_message(&bob_contacts[0], &key_upgrade_response(&bob.uid.to_string(), &bob_token)).await.unwrap();
+
+    let response = alice_received.lock().unwrap().remove(0);
+    assert_eq!(response.message_type, KEY_UPGRADE_RESPONSE_TYPE);
+
+    // Another contact's token is refused; Bob's fills the key once
+    let mallory_token = contact_at(&alice, "loopback://alice").sign_token(&alice).unwrap();
+    assert!(apply_key_upgrade(&mut alice_contacts, &bob.uid.to_string(), mallory_token.as_bytes()).is_err());
+    assert!(apply_key_upgrade(&mut alice_contacts, &bob.uid.to_string(), &response.payload).unwrap());
+    assert_eq!(alice_contacts[0].x25519_pubkey, Some(bob.x25519_public.clone()));
+    assert!(!apply_key_upgrade(&mut alice_contacts, &bob.uid.to_string(), &response.payload).unwrap());
+
+    // The next message is sealed and only Bob can open it
+    let mut queue = MessageQueue::new().unwrap();
+    let message = text_message(&alice, &bob, "now sealed");
+    let (delivered, security) = send_sealed_message(&alice_transport, &mut queue, Some(&alice), &alice_contacts[0], &message, Priority::Normal)
+        .await
+        .unwrap();
+    assert!(delivered);
+    assert_eq!(security, SendSecurity::Encrypted);
+
+    let sealed = bob_received.lock().unwrap().pop().unwrap();
+    assert_eq!(sealed.message_type, ENCRYPTED_TEXT_TYPE);
+    assert!(!sealed.payload.windows(10).any(|w| w == b"now sealed"));
+    let opened = open_request(sealed.clone(), &bob, &bob_contacts).unwrap();
+    assert_eq!(opened.message_type, "text");
+    assert_eq!(opened.payload, b"now sealed");
+
+    // A carried key contradicting the stored one is refused
+    let mut wrong_contacts = bob_contacts.clone();
+    wrong_contacts[0].x25519_pubkey = Some(vec![7; 32]);
+    assert!(open_request(sealed, &bob, &wrong_contacts).is_err());
+}
+
+#[test]
+fn test_migration_leaves_missing_keys_empty() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let path = temp_dir.path().join("old.db");
+    let (with_key, without_key) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+
+    // Contacts table as created while the key was NOT NULL
+    {
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE contacts (
+                 uid TEXT PRIMARY KEY,
+                 ip TEXT NOT NULL,
+                 pubkey BLOB NOT NULL,
+                 x25519_pubkey BLOB NOT NULL,
+                 expiry INTEGER NOT NULL,
+                 is_active INTEGER NOT NULL
+             );",
+        )
+        .unwrap();
+        let expiry = (Utc::now() + Duration::days(30)).timestamp();
+        for (keypair, x25519) in [(&with_key, with_key.x25519_public.clone()), (&without_key, Vec::new())] {
+            conn.execute(
+                "INSERT INTO contacts (uid, ip, pubkey, x25519_pubkey, expiry, is_active) VALUES (?1, 'a:1', ?2, ?3, ?4, 1)",
+                params![keypair.uid.to_string(), keypair.public_key, x25519, expiry],
+            )
+            .unwrap();
+        }
+    }
+
+    let storage = Storage::new(&path).unwrap();
+    let contacts = storage.load_contacts().unwrap();
+    let find = |uid: String| contacts.iter().find(|c| c.uid == uid).unwrap();
+    assert_eq!(find(with_key.uid.to_string()).x25519_pubkey, Some(with_key.x25519_public.clone()));
+    assert_eq!(find(without_key.uid.to_string()).x25519_pubkey, None);
+
+    // The column is nullable now and a missing key survives a round trip
+    let mut keyless = find(without_key.uid.to_string()).clone();
+    keyless.notes = "kept".to_string();
+    storage.save_contact(&keyless).unwrap();
+    drop(storage);
+    let conn = Connection::open(&path).unwrap();
+    let stored: Option<Vec<u8>> = conn
+        .query_row("SELECT x25519_pubkey FROM contacts WHERE uid = ?1", params![without_key.uid.to_string()], |row| row.get(0))
+        .unwrap();
+    assert_eq!(stored, None);
+    let reopened = Storage::new(&path).unwrap().load_contacts().unwrap();
+    assert_eq!(reopened.len(), 2);
+}
+
+#[test]
+fn test_no_unwraps_on_x25519_key_in_send_path() {
+    let sources = [
+        ("sealing.rs", include_str!("../sealing.rs")),
+        ("messaging.rs", include_str!("../messaging.rs")),
+        ("storage/contact.rs", include_str!("../storage/contact.rs")),
+        ("tui/app.rs", include_str!("../tui/app.rs")),
+    ];
+    for (file, source) in sources {
+        for (number, line) in source.lines().enumerate() {
+            if line.contains("x25519") {
+                assert!(
+                    !line.contains(".unwrap()") && !line.contains(".expect("),
+                    "{}:{} unwraps the X25519 key: {}",
+                    file,
+                    number + 1,
+                    line.trim()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+// Send preview tests - reason matrix, previews matching what the transport actually carries, cancel leaving no trace, Ctrl+O and the settings toggle
+
+use crate::crypto::KeyPair;
+use crate::messaging::request_size;
+use crate::queue::Priority;
+use crate::relay::{RelayCapabilities, RelayEnvelope};
+use crate::sealing::SendSecurity;
+use crate::storage::{AppState, Contact, Message, MAX_MESSAGE_BYTES};
+use crate::transport::{
+    LoopbackNetwork, LoopbackTransport, MessageRequest, PeerTransport, PingResponse, TransportCapabilities,
+    TransportFuture,
+};
+use crate::tui::{preview_reasons, App, DeliveryHint, InputCounter, PreviewReason, SendPreview};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use super::helpers::{app_chatting_through, contact_at, settle};
+
+/// What the transport was asked to carry: message type, wire size and whether it was delivered
+type SentLog = Arc<Mutex<Vec<(String, usize, bool)>>>;
+
+/// Loopback transport that logs every message it carries
+struct LoggingTransport {
+    inner: LoopbackTransport,
+    log: SentLog,
+}
+
+impl PeerTransport for LoggingTransport {
+    fn scheme(&self) -> &str {
+        self.inner.scheme()
+    }
+
+    fn capabilities(&self) -> TransportCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn send_message<'a>(&'a self, contact: &'a Contact, request: &'a MessageRequest) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            let result = self.inner.send_message(contact, request).await;
+            let size = request_size(request).unwrap();
+            self.log.lock().unwrap().push((request.message_type.clone(), size, result.is_ok()));
+            result
+        })
+    }
+
+    fn send_ping<'a>(&'a self, contact: &'a Contact, my_contact_token: &'a str) -> TransportFuture<'a, PingResponse> {
+        self.inner.send_ping(contact, my_contact_token)
+    }
+
+    fn relay_message<'a>(&'a self, relay: &'a Contact, envelope: &'a RelayEnvelope) -> TransportFuture<'a, ()> {
+        self.inner.relay_message(relay, envelope)
+    }
+
+    fn probe_capabilities<'a>(
+        &'a self,
+        contact: &'a Contact,
+        from_uid: &'a str,
+    ) -> TransportFuture<'a, Option<RelayCapabilities>> {
+        self.inner.probe_capabilities(contact, from_uid)
+    }
+
+    fn start_listener<'a>(&'a self, address: &'a str) -> TransportFuture<'a, String> {
+        self.inner.start_listener(address)
+    }
+
+    fn stop_listener(&self) -> TransportFuture<'_, ()> {
+        self.inner.stop_listener()
+    }
+}
+
+/// Start a loopback peer accepting every message at `name`
+async fn peer(network: &LoopbackNetwork, name: &str) -> LoopbackTransport {
+    let peer = LoopbackTransport::new(network);
+    peer.set_new_message_handler(|_| Ok(())).await;
+    peer.start_listener(name).await.expect("Failed to start loopback listener");
+    peer
+}
+
+/// `contact` without an encryption key
+fn unencrypted(mut contact: Contact) -> Contact {
+    contact.x25519_pubkey = None;
+    contact
+}
+
+/// App with `contact`'s chat open, sending through a logging loopback transport
+fn app_chatting_with(temp_dir: &TempDir, network: &LoopbackNetwork, contact: Contact) -> (App, SentLog) {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let transport = LoggingTransport { inner: LoopbackTransport::new(network), log: log.clone() };
+    (app_chatting_through(temp_dir, transport, contact), log)
+}
+
+/// Type `text` into the open chat and press Enter
+fn type_and_send(app: &mut App, text: &str) {
+    let screen = app.chat_view_screen.as_mut().unwrap();
+    screen.clear_input();
+    screen.input = text.to_string();
+    app.send_message_in_chat();
+}
+
+/// Wait for the send thread and collect what came back
+fn collect(app: &mut App) {
+    settle();
+    app.process_incoming_updates();
+}
+
+fn shown_preview(app: &App) -> Option<SendPreview> {
+    app.chat_view_screen.as_ref().unwrap().send_preview.clone()
+}
+
+fn message_count(app: &App, uid: &str) -> usize {
+    app.app_state.get_chat(uid).map_or(0, |chat| chat.messages.len())
+}
+
+#[test]
+fn test_preview_reason_matrix() {
+    let near = MAX_MESSAGE_BYTES * 9 / 10;
+    let short = InputCounter::Chars(5);
+    let long = InputCounter::Bytes(near);
+
+    assert!(preview_reasons(short, SendSecurity::Encrypted, None).is_empty());
+    assert_eq!(preview_reasons(long, SendSecurity::Encrypted, None), vec![PreviewReason::NearSizeLimit]);
+    for plaintext in [SendSecurity::MissingKey, SendSecurity::InvalidKey, SendSecurity::NoIdentity] {
+        assert_eq!(preview_reasons(short, plaintext, None), vec![PreviewReason::Plaintext]);
+    }
+    for hint in [DeliveryHint::Waiting, DeliveryHint::StaleAddress, DeliveryHint::Undeliverable] {
+        assert_eq!(preview_reasons(short, SendSecurity::Encrypted, Some(hint)), vec![PreviewReason::Queued]);
+    }
+    assert_eq!(
+        preview_reasons(long, SendSecurity::MissingKey, Some(DeliveryHint::Waiting)),
+        vec![PreviewReason::NearSizeLimit, PreviewReason::Plaintext, PreviewReason::Queued]
+    );
+    assert_eq!(InputCounter::new("x", near), long);
+}
+
+#[test]
+fn test_preview_matches_what_is_sent() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+
+    // Encrypted and reachable: sent right away, as the on-demand preview said
+    let temp_dir = TempDir::new().unwrap();
+    let bob = KeyPair::generate().unwrap();
+    let _bob_peer = rt.block_on(peer(&network, "bob"));
+    let (mut app, log) = app_chatting_with(&temp_dir, &network, contact_at(&bob, "loopback://bob"));
+    app.chat_view_screen.as_mut().unwrap().input = "hello bob".to_string();
+    app.preview_chat_input();
+    let preview = shown_preview(&app).unwrap();
+    assert!(preview.reasons.is_empty());
+    assert_eq!((preview.parts, preview.destinations.len()), (1, 1));
+    app.answer_send_preview(true);
+    collect(&mut app);
+    let sent = log.lock().unwrap()[0].clone();
+    assert_eq!((sent.0.as_str(), sent.2), (preview.message_type.as_str(), true));
+    assert_eq!(preview.message_type, "text_e2e");
+    assert_eq!(preview.security, SendSecurity::Encrypted);
+
+    // Near the size limit: previewed, then sent sealed on top of the previewed size
+    // Each byte of content encodes to two
+    let long_text = "a".repeat(MAX_MESSAGE_BYTES * 9 / 20);
+    type_and_send(&mut app, &long_text);
+    let preview = shown_preview(&app).unwrap();
+    assert_eq!(preview.reasons, vec![PreviewReason::NearSizeLimit]);
+    assert!(preview.bytes <= MAX_MESSAGE_BYTES);
+    assert_eq!(log.lock().unwrap().len(), 1);
+    app.answer_send_preview(true);
+    collect(&mut app);
+    let (message_type, size, delivered) = log.lock().unwrap()[1].clone();
+    assert_eq!((message_type.as_str(), delivered), ("text_e2e", true));
+    assert!(size > preview.bytes);
+
+    // Plaintext: previewed as such and sent as plain text
+    let temp_dir = TempDir::new().unwrap();
+    let carol = KeyPair::generate().unwrap();
+    let _carol_peer = rt.block_on(peer(&network, "carol"));
+    let (mut app, log) = app_chatting_with(&temp_dir, &network, unencrypted(contact_at(&carol, "loopback://carol")));
+    type_and_send(&mut app, "hello carol");
+    let preview = shown_preview(&app).unwrap();
+    assert_eq!(preview.reasons, vec![PreviewReason::Plaintext]);
+    assert_eq!(preview.security, SendSecurity::MissingKey);
+    assert!(preview.lines().iter().any(|line| line.contains("Not encrypted")));
+    app.answer_send_preview(true);
+    collect(&mut app);
+    assert_eq!(log.lock().unwrap()[0], ("text".to_string(), preview.bytes, true));
+
+    // Unreachable with a message already queued: previewed as queued, and queued behind it
+    let temp_dir = TempDir::new().unwrap();
+    let dave = KeyPair::generate().unwrap();
+    let dave_uid = dave.uid.to_string();
+    let (mut app, log) = app_chatting_with(&temp_dir, &network, contact_at(&dave, "loopback://dave"));
+    let earlier = Message::new("m1".to_string(), app.keypair.uid.to_string(), dave_uid.clone(), b"hi".to_vec(), 1000);
+    app.queue.enqueue(earlier, Priority::Normal).unwrap();
+    type_and_send(&mut app, "are you there?");
+    let preview = shown_preview(&app).unwrap();
+    assert_eq!(preview.reasons, vec![PreviewReason::Queued]);
+    assert_eq!((preview.queued, preview.queue_hint), (1, Some(DeliveryHint::Waiting)));
+    assert!(preview.queue_forecast().contains("likely queued"));
+    app.answer_send_preview(true);
+    collect(&mut app);
+    assert!(log.lock().unwrap().is_empty());
+    assert_eq!(app.queue.fetch_all_pending().unwrap().len(), 2);
+}
+
+#[test]
+fn test_cancel_leaves_no_trace() {
+    let network = LoopbackNetwork::new();
+    let temp_dir = TempDir::new().unwrap();
+    let erin = KeyPair::generate().unwrap();
+    let erin_uid = erin.uid.to_string();
+    let (mut app, log) = app_chatting_with(&temp_dir, &network, unencrypted(contact_at(&erin, "loopback://erin")));
+
+    type_and_send(&mut app, "plain words");
+    assert!(shown_preview(&app).is_some());
+    app.answer_send_preview(false);
+    collect(&mut app);
+
+    let screen = app.chat_view_screen.as_ref().unwrap();
+    assert!(screen.send_preview.is_none());
+    assert_eq!(screen.input, "plain words");
+    assert!(screen.status_message.as_deref().unwrap().starts_with("Not sent"));
+    assert_eq!(message_count(&app, &erin_uid), 0);
+    assert_eq!(AppState::load_from_db(&app.storage).unwrap().get_chat(&erin_uid).unwrap().messages.len(), 0);
+    assert!(!app.queue.has_pending_for(&erin_uid).unwrap());
+    assert!(log.lock().unwrap().is_empty());
+
+    // Answering again without a preview does nothing
+    app.answer_send_preview(true);
+    collect(&mut app);
+    assert!(log.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_auto_preview_toggle() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let temp_dir = TempDir::new().unwrap();
+    let frank = KeyPair::generate().unwrap();
+    let frank_uid = frank.uid.to_string();
+    let _frank_peer = rt.block_on(peer(&network, "frank"));
+    let (mut app, log) = app_chatting_with(&temp_dir, &network, unencrypted(contact_at(&frank, "loopback://frank")));
+    assert!(app.app_state.settings.auto_send_preview);
+
+    // Switched off in Settings and saved
+    app.show_settings_screen();
+    let screen = app.settings_screen.as_mut().unwrap();
+    screen.selected_field = crate::tui::SettingsScreen::FIELD_SEND_PREVIEW;
+    screen.add_char(' ');
+    app.save_settings();
+    assert!(!app.app_state.settings.auto_send_preview);
+    assert!(!AppState::load_from_db(&app.storage).unwrap().settings.auto_send_preview);
+
+    // Plaintext now goes out without asking
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+    type_and_send(&mut app, "no questions");
+    assert!(shown_preview(&app).is_none());
+    collect(&mut app);
+    assert_eq!(message_count(&app, &frank_uid), 1);
+    assert_eq!(log.lock().unwrap()[0].0, "text");
+
+    // Ctrl+O still previews on demand
+    app.chat_view_screen.as_mut().unwrap().input = "show me".to_string();
+    app.preview_chat_input();
+    assert_eq!(shown_preview(&app).unwrap().reasons, vec![PreviewReason::Plaintext]);
+}
@@ -0,0 +1,164 @@
+// Signals tests - per-contact privacy of read receipts, typing and presence, send paths and their absence on the wire
+
+use crate::signals::*;
+use crate::storage::{signal_enabled, AppState, Contact, Message, PrivacySignal, Settings, SignalOverride, Storage};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, TransportRegistry};
+use crate::tui::App;
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tempfile::TempDir;
+use super::helpers::{recording_peer, settle};
+
+fn contact_at(uid: &str, address: &str) -> Contact {
+    Contact::new(uid.to_string(), address.to_string(), vec![1, 2, 3], vec![9; 32], Utc::now() + Duration::days(30))
+}
+
+#[test]
+fn test_resolution_precedence() {
+    let mut settings = Settings::default();
+    let mut contact = contact_at("bob_uid", "loopback://bob");
+
+    for signal in PrivacySignal::ALL {
+        // Default follows the global setting both ways
+        assert!(signal_enabled(&settings, &contact, signal));
+        *contact.privacy.get_mut(signal) = SignalOverride::Default;
+        set_global(&mut settings, signal, false);
+        assert!(!signal_enabled(&settings, &contact, signal));
+
+        // Explicit choices win over the global setting, whichever it is
+        *contact.privacy.get_mut(signal) = SignalOverride::On;
+        assert!(signal_enabled(&settings, &contact, signal));
+        set_global(&mut settings, signal, true);
+        *contact.privacy.get_mut(signal) = SignalOverride::Off;
+        assert!(!signal_enabled(&settings, &contact, signal));
+
+        // Flipping the global default does not touch explicit choices
+        set_global(&mut settings, signal, false);
+        assert_eq!(contact.privacy.get(signal), SignalOverride::Off);
+        set_global(&mut settings, signal, true);
+        assert!(!signal_enabled(&settings, &contact, signal));
+        *contact.privacy.get_mut(signal) = SignalOverride::Default;
+    }
+
+    assert_eq!(SignalOverride::Default.cycle(), SignalOverride::On);
+    assert_eq!(SignalOverride::On.cycle(), SignalOverride::Off);
+    assert_eq!(SignalOverride::Off.cycle(), SignalOverride::Default);
+    assert_eq!(SignalOverride::Default.describe(false), "default (off)");
+    assert_eq!(SignalOverride::On.describe(false), "on");
+}
+
+fn set_global(settings: &mut Settings, signal: PrivacySignal, value: bool) {
+    match signal {
+        PrivacySignal::ReadReceipts => settings.send_read_receipts = value,
+        PrivacySignal::Typing => settings.send_typing = value,
+        PrivacySignal::Presence => settings.send_presence = value,
+    }
+}
+
+#[tokio::test]
+async fn test_send_paths_consult_helper() {
+    let network = LoopbackNetwork::new();
+    let (_receiver, received) = recording_peer(&network, "bob").await;
+    let sender = LoopbackTransport::new(&network);
+    let mut settings = Settings::default();
+    let mut contact = contact_at("bob_uid", "loopback://bob");
+
+    assert!(send_read_receipt(&sender, &settings, &contact, "alice_uid", Utc::now()).await.unwrap());
+    assert!(send_typing(&sender, &settings, &contact, "alice_uid").await.unwrap());
+    assert!(send_presence(&sender, &settings, &contact, "alice_uid", true).await.unwrap());
+    let types: Vec<_> = received.lock().unwrap().iter().map(|r| r.message_type.clone()).collect();
+    assert_eq!(types, vec![READ_RECEIPT_TYPE, TYPING_TYPE, PRESENCE_TYPE]);
+    assert!(types.iter().all(|t| is_signal_type(t)));
+    received.lock().unwrap().clear();
+
+    // Each path refuses when its own signal is off for the contact
+    contact.privacy.read_receipts = SignalOverride::Off;
+    assert!(!send_read_receipt(&sender, &settings, &contact, "alice_uid", Utc::now()).await.unwrap());
+    assert!(send_typing(&sender, &settings, &contact, "alice_uid").await.unwrap());
+    contact.privacy.typing = SignalOverride::Off;
+    assert!(!send_typing(&sender, &settings, &contact, "alice_uid").await.unwrap());
+    settings.send_presence = false;
+    assert!(!send_presence(&sender, &settings, &contact, "alice_uid", true).await.unwrap());
+    contact.privacy.presence = SignalOverride::On;
+    assert!(send_presence(&sender, &settings, &contact, "alice_uid", false).await.unwrap());
+
+    let types: Vec<_> = received.lock().unwrap().iter().map(|r| r.message_type.clone()).collect();
+    assert_eq!(types, vec![TYPING_TYPE, PRESENCE_TYPE]);
+    let presence: Presence = serde_cbor::from_slice(&received.lock().unwrap()[1].payload).unwrap();
+    assert!(!presence.online);
+}
+
+#[test]
+fn test_popup_editing_persists() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.contacts.push(contact_at("bob_uid", "192.168.1.100:8080"));
+    app.app_state.add_chat("bob_uid".to_string());
+    app.save_state().unwrap();
+    app.show_chat_list_screen();
+    app.show_contact_details();
+
+    app.cycle_contact_signal(PrivacySignal::ReadReceipts);
+    app.cycle_contact_signal(PrivacySignal::ReadReceipts);
+    app.cycle_contact_signal(PrivacySignal::Typing);
+
+    let loaded = AppState::load_from_db(&app.storage).unwrap();
+    let privacy = loaded.contacts[0].privacy;
+    assert_eq!(privacy.read_receipts, SignalOverride::Off);
+    assert_eq!(privacy.typing, SignalOverride::On);
+    assert_eq!(privacy.presence, SignalOverride::Default);
+
+    // Back to default for every signal stores nothing
+    let storage = Storage::new_in_memory().unwrap();
+    let mut contact = loaded.contacts[0].clone();
+    storage.save_contact(&contact).unwrap();
+    assert_eq!(storage.load_contacts().unwrap()[0].privacy, privacy);
+    contact.privacy = Default::default();
+    storage.save_contact(&contact).unwrap();
+    assert!(storage.load_contacts().unwrap()[0].privacy.is_default());
+
+    // Global defaults are settings like any other
+    let settings = Settings { send_typing: false, ..Settings::default() };
+    storage.save_settings(&settings).unwrap();
+    let loaded = storage.load_settings().unwrap().unwrap();
+    assert!(loaded.send_read_receipts && !loaded.send_typing && loaded.send_presence);
+}
+
+#[test]
+fn test_disabled_receipt_absent_on_wire() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (_receiver, received) = rt.block_on(recording_peer(&network, "bob"));
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(&network)));
+    let mut contact = contact_at("bob_uid", "loopback://bob");
+    contact.privacy.read_receipts = SignalOverride::Off;
+    contact.privacy.typing = SignalOverride::Off;
+    app.app_state.contacts.push(contact);
+    let me = app.keypair.uid.to_string();
+    let chat = app.app_state.get_or_create_chat("bob_uid");
+    chat.append_message(Message::new("m1".to_string(), "bob_uid".to_string(), me, b"hi".to_vec(), 1));
+    app.show_chat_list_screen();
+
+    // Opening the chat and typing emit nothing: no receipt, no "disabled" notice
+    app.open_selected_chat();
+    app.note_typing();
+    settle();
+    assert!(received.lock().unwrap().is_empty());
+
+    // Once enabled, the same chat sends one receipt carrying only the read time
+    app.app_state.contacts[0].privacy.read_receipts = SignalOverride::Default;
+    app.back_to_chat_list();
+    app.open_selected_chat();
+    app.back_to_chat_list();
+    app.open_selected_chat();
+    settle();
+    let requests = received.lock().unwrap().clone();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].message_type, READ_RECEIPT_TYPE);
+    assert!(requests[0].metadata.is_empty());
+    let receipt: ReadReceipt = serde_cbor::from_slice(&requests[0].payload).unwrap();
+    assert!(receipt.read_at > 0);
+}
@@ -0,0 +1,280 @@
+// Soft delete tests - delete/undo round trip with queue rows and flags, exclusion from every view, purge after the window with tombstones, undo status and Maintenance entry lifecycle
+
+use crate::crypto::KeyPair;
+use crate::queue::Priority;
+use crate::storage::{AppState, Chat, Contact, DeletedKind, JsonlExportOptions, Message, Storage};
+use crate::tui::ui::chat_list_rows;
+use crate::tui::{App, ContactDetailsPopup, UndoState, UNDO_STATUS_SECS};
+use chrono::{Duration, Utc};
+use tempfile::TempDir;
+
+fn contact(uid: &str) -> Contact {
+    Contact::new(uid.to_string(), "192.168.1.100:8080".to_string(), vec![1, 2, 3], vec![7u8; 32], Utc::now() + Duration::days(30))
+}
+
+fn message(id: &str, sender: &str, receiver: &str, timestamp: i64) -> Message {
+    Message::new(id.to_string(), sender.to_string(), receiver.to_string(), format!("text of {}", id).into_bytes(), timestamp)
+}
+
+/// Storage holding alice, bob and carol, each with a chat of two messages
+fn storage_with_chats() -> Storage {
+    let storage = Storage::new_in_memory().unwrap();
+    let keypair = KeyPair::generate().unwrap();
+    storage
+        .with_write_retry(|| {
+            storage.save_user_identity(&keypair, None, 8080)?;
+            for uid in ["alice", "bob", "carol"] {
+                storage.save_contact(&contact(uid))?;
+                let mut chat = Chat::new(uid.to_string());
+                chat.append_message(message(&format!("{}_1", uid), uid, "me", 1000));
+                chat.append_message(message(&format!("{}_2", uid), "me", uid, 2000));
+                chat.messages_mut()[0].starred = true;
+                storage.save_chat(&chat)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+    storage
+}
+
+/// App holding bob with a muted chat, two messages and two queued messages
+/// (one already retried), plus alice with a chat
+fn app_with_bob(temp_dir: &TempDir) -> (App, String) {
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    let bob_uid = KeyPair::generate().unwrap().uid.to_string();
+    let me = app.keypair.uid.to_string();
+    app.app_state.add_contact(contact("alice"));
+    app.app_state.get_or_create_chat("alice").append_message(message("a1", "alice", &me, 500));
+    let mut bob = contact(&bob_uid);
+    bob.notes = "met at the market".to_string();
+    app.app_state.add_contact(bob);
+
+    let chat = app.app_state.get_or_create_chat(&bob_uid);
+    chat.append_message(message("b1", &bob_uid, &me, 1000));
+    chat.append_message(message("b2", &me, &bob_uid, 2000));
+    chat.muted = true;
+    chat.history_limit = Some(500);
+    chat.mark_has_pending();
+    for id in ["q1", "q2"] {
+        app.queue.enqueue(message(id, &me, &bob_uid, 3000), Priority::Normal).unwrap();
+    }
+    app.queue.mark_failed_at("q1", Utc::now().timestamp_millis()).unwrap();
+    app.save_state().unwrap();
+    app.show_chat_list_screen();
+    (app, bob_uid)
+}
+
+fn chat_index(app: &App, uid: &str) -> usize {
+    app.app_state.chats.iter().position(|chat| chat.contact_uid == uid).unwrap()
+}
+
+fn delete_chat(app: &mut App, uid: &str) {
+    let index = chat_index(app, uid);
+    app.chat_list_screen.as_mut().unwrap().show_delete_popup(index);
+    app.confirm_delete_chat();
+}
+
+fn status(app: &App) -> Option<String> {
+    app.chat_list_screen.as_ref().unwrap().status_message.clone()
+}
+
+#[test]
+fn test_delete_and_undo_restore_queue_rows_and_flags() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut app, bob_uid) = app_with_bob(&temp_dir);
+    let now = Utc::now().timestamp_millis();
+    let queue_before = app.queue.debug_report(now).unwrap().rows;
+    let chat_before = serde_json::to_value(app.app_state.chat_by_uid(&bob_uid).unwrap()).unwrap();
+    let position = chat_index(&app, &bob_uid);
+    assert_eq!(queue_before.len(), 2);
+
+    // Deleting hides the chat and holds its queued messages back
+    delete_chat(&mut app, &bob_uid);
+    assert!(app.app_state.chat_by_uid(&bob_uid).is_none());
+    assert!(app.storage.load_chat_headers().unwrap().iter().all(|chat| chat.contact_uid != bob_uid));
+    assert!(app.app_state.contact_by_uid(&bob_uid).is_some());
+    assert!(!app.queue.has_pending_for(&bob_uid).unwrap());
+    assert!(app.queue.fetch_all_pending().unwrap().is_empty());
+    assert_eq!(app.queue.earliest_next_retry().unwrap(), None);
+    assert!(!app.queue.get_pending_contact_uids().unwrap().contains(&bob_uid));
+    assert_eq!(app.queue.suspended_uids().unwrap().into_iter().collect::<Vec<_>>(), std::slice::from_ref(&bob_uid));
+
+    // Undo gives back the chat, its position and flags, and the queue rows unchanged
+    assert!(app.undo_last_delete(Utc::now()));
+    assert_eq!(chat_index(&app, &bob_uid), position);
+    assert_eq!(serde_json::to_value(app.app_state.chat_by_uid(&bob_uid).unwrap()).unwrap(), chat_before);
+    assert_eq!(app.queue.debug_report(now).unwrap().rows, queue_before);
+    assert!(app.queue.suspended_uids().unwrap().is_empty());
+    let reloaded = AppState::load_from_db(&app.storage).unwrap();
+    let stored = reloaded.get_chat(&bob_uid).unwrap();
+    assert!(stored.muted && stored.has_pending_messages);
+    assert_eq!(stored.history_limit, Some(500));
+    assert_eq!(stored.messages.len(), 2);
+    assert_eq!(status(&app).as_deref(), Some(format!("Restored chat with {}", &bob_uid[..16]).as_str()));
+
+    // A contact delete takes the chat too; undo also drops the notes it kept
+    let contact_before = serde_json::to_value(app.app_state.contact_by_uid(&bob_uid).unwrap()).unwrap();
+    app.chat_list_screen.as_mut().unwrap().contact_details = Some(ContactDetailsPopup::new(bob_uid.clone()));
+    app.confirm_delete_contact(true);
+    assert!(app.app_state.contact_by_uid(&bob_uid).is_none());
+    assert!(app.app_state.chat_by_uid(&bob_uid).is_none());
+    assert!(AppState::load_from_db(&app.storage).unwrap().contact_by_uid(&bob_uid).is_none());
+    assert!(!app.queue.has_pending_for(&bob_uid).unwrap());
+
+    assert!(app.undo_last_delete(Utc::now()));
+    assert_eq!(serde_json::to_value(app.app_state.contact_by_uid(&bob_uid).unwrap()).unwrap(), contact_before);
+    assert_eq!(serde_json::to_value(app.app_state.chat_by_uid(&bob_uid).unwrap()).unwrap(), chat_before);
+    assert_eq!(app.queue.debug_report(now).unwrap().rows, queue_before);
+    assert_eq!(app.storage.take_retained_contact_notes(&bob_uid).unwrap(), None);
+    let reloaded = AppState::load_from_db(&app.storage).unwrap();
+    assert_eq!(reloaded.contact_by_uid(&bob_uid).unwrap().notes, "met at the market");
+    assert_eq!(reloaded.get_chat(&bob_uid).unwrap().messages.len(), 2);
+}
+
+#[test]
+fn test_soft_deleted_items_are_left_out_of_every_view() {
+    let storage = storage_with_chats();
+    let at = Utc::now();
+    storage
+        .with_write_retry(|| {
+            storage.soft_delete_chat("bob", at)?;
+            storage.soft_delete_chat("carol", at)?;
+            storage.soft_delete_contact("carol", at)
+        })
+        .unwrap();
+
+    // Chat list (headers and summaries)
+    let mut state = AppState::load_headers_from_db(&storage).unwrap();
+    let rows: Vec<&str> = chat_list_rows(&state, Utc::now()).iter().map(|row| row.contact_uid).collect();
+    assert_eq!(rows, ["alice"]);
+    let summaries: Vec<String> = storage.load_chat_summaries().unwrap().into_iter().map(|(uid, _)| uid).collect();
+    assert_eq!(summaries, ["alice"]);
+    let contacts: Vec<String> = storage.load_contacts().unwrap().into_iter().map(|c| c.uid).collect();
+    assert_eq!(contacts, ["alice", "bob"]);
+
+    // Message reads: full history, pages, content and export
+    state = AppState::load_from_db(&storage).unwrap();
+    assert_eq!(state.chats.len(), 1);
+    assert!(storage.load_chat_messages("bob").unwrap().is_empty());
+    assert!(storage.load_chat_messages_page("bob", None, 10).unwrap().is_empty());
+    assert_eq!(storage.copy_message_content("bob_1", &mut Vec::new()).unwrap(), None);
+    assert_eq!(storage.copy_message_content("alice_1", &mut Vec::new()).unwrap(), Some(15));
+    let mut out = Vec::new();
+    assert_eq!(storage.export_chat_jsonl("bob", &mut out, &JsonlExportOptions::default()).unwrap(), 0);
+    assert!(out.is_empty());
+    assert_eq!(storage.export_chat_jsonl("alice", &mut Vec::new(), &JsonlExportOptions::default()).unwrap(), 2);
+
+    // Cross-chat lookups (the tree has no message search; starred messages
+    // are the one view across all chats)
+    let starred: Vec<&str> = state.starred_messages().into_iter().map(|(uid, _)| uid).collect();
+    assert_eq!(starred, ["alice"]);
+
+    // Restoring brings everything back as it was
+    storage.with_write_retry(|| storage.restore_chat("bob")).unwrap();
+    let state = AppState::load_from_db(&storage).unwrap();
+    assert_eq!(state.get_chat("bob").unwrap().messages.len(), 2);
+    assert_eq!(storage.load_chat_summaries().unwrap().len(), 2);
+    assert_eq!(storage.soft_deleted_uids().unwrap().into_iter().collect::<Vec<_>>(), ["carol"]);
+}
+
+#[test]
+fn test_purge_after_window_writes_tombstones() {
+    let storage = storage_with_chats();
+    let deleted_at = Utc::now() - Duration::hours(30);
+    storage
+        .with_write_retry(|| {
+            storage.soft_delete_chat("bob", deleted_at)?;
+            storage.soft_delete_chat("carol", deleted_at)?;
+            storage.soft_delete_contact("carol", deleted_at)
+        })
+        .unwrap();
+
+    // Nothing is purged (or tombstoned) inside the window
+    let now = Utc::now();
+    assert!(storage.purge_soft_deleted(deleted_at - Duration::seconds(1), now).unwrap().is_empty());
+    assert!(storage.load_tombstones().unwrap().is_empty());
+
+    let tombstones = storage.purge_soft_deleted(now - Duration::hours(24), now).unwrap();
+    let purged: Vec<(&str, DeletedKind)> = tombstones.iter().map(|t| (t.uid.as_str(), t.kind)).collect();
+    assert_eq!(purged, [("bob", DeletedKind::Chat), ("carol", DeletedKind::Chat), ("carol", DeletedKind::Contact)]);
+    assert!(tombstones.iter().all(|t| t.deleted_at == deleted_at.timestamp_millis() && t.purged_at == now.timestamp_millis()));
+    assert_eq!(storage.load_tombstones().unwrap().len(), 3);
+
+    // The rows are gone for good
+    assert!(!storage.restore_chat("bob").unwrap());
+    assert!(!storage.restore_contact("carol").unwrap());
+    assert!(storage.soft_deleted_uids().unwrap().is_empty());
+    let migration = storage.migrate_chat_summaries(|_| std::ops::ControlFlow::Continue(())).unwrap();
+    assert_eq!(migration.total, 1);
+    assert_eq!(AppState::load_from_db(&storage).unwrap().contacts.len(), 2);
+    assert!(storage.purge_soft_deleted(now, now).unwrap().is_empty());
+
+    // Saving a soft-deleted item again purges the old one: the new chat starts empty
+    storage.with_write_retry(|| storage.soft_delete_chat("alice", now)).unwrap();
+    storage.with_write_retry(|| storage.save_chat(&Chat::new("alice".to_string()))).unwrap();
+    assert!(storage.load_chat_messages("alice").unwrap().is_empty());
+    assert_eq!(storage.load_chat_headers().unwrap().len(), 1);
+    assert_eq!(storage.load_tombstones().unwrap().last().map(|t| (t.uid.as_str(), t.kind)), Some(("alice", DeletedKind::Chat)));
+
+    // In the app, the queued messages go with the purge
+    let temp_dir = TempDir::new().unwrap();
+    let (mut app, bob_uid) = app_with_bob(&temp_dir);
+    delete_chat(&mut app, &bob_uid);
+    let later = Utc::now() + Duration::hours(24) + Duration::seconds(UNDO_STATUS_SECS);
+    app.run_soft_delete_maintenance(later);
+    assert_eq!(app.undo.entries()[0].state, UndoState::Purged);
+    assert!(app.queue.suspended_uids().unwrap().is_empty());
+    assert_eq!(app.queue.size().unwrap(), 0);
+    assert!(app.storage.load_tombstones().unwrap().iter().any(|t| t.uid == bob_uid));
+    assert!(!app.undo_last_delete(later));
+}
+
+#[test]
+fn test_undo_status_and_maintenance_entry_lifecycle() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut app, bob_uid) = app_with_bob(&temp_dir);
+    app.app_state.settings.soft_delete_window_hours = 2;
+    let deleted = Utc::now();
+    delete_chat(&mut app, "alice");
+    delete_chat(&mut app, &bob_uid);
+    assert!(app.app_state.chats.is_empty());
+    let bob_status = format!("Deleted chat with {} — press U to undo", &bob_uid[..16]);
+    assert_eq!(status(&app).as_deref(), Some(bob_status.as_str()));
+
+    // The offer stays for UNDO_STATUS_SECS, then the status clears and U does nothing
+    app.run_soft_delete_maintenance(deleted + Duration::seconds(UNDO_STATUS_SECS - 5));
+    assert_eq!(status(&app).as_deref(), Some(bob_status.as_str()));
+    let expired = deleted + Duration::seconds(UNDO_STATUS_SECS + 1);
+    app.run_soft_delete_maintenance(expired);
+    assert_eq!(status(&app), None);
+    assert!(!app.undo_last_delete(expired));
+    assert!(app.app_state.chats.is_empty());
+
+    // Both entries stay on the Maintenance screen for the session
+    app.show_maintenance_screen();
+    assert_eq!(app.undo.entries().len(), 2);
+    assert!(app.undo.entries().iter().all(|entry| entry.state == UndoState::Pending));
+    app.maintenance_screen.as_mut().unwrap().move_selection(true, 2);
+    app.undo_selected_delete();
+    assert_eq!(app.undo.entries()[1].state, UndoState::Restored);
+    assert!(app.app_state.chat_by_uid(&bob_uid).is_some());
+    assert!(app.queue.has_pending_for(&bob_uid).unwrap());
+    let screen = app.maintenance_screen.as_ref().unwrap();
+    assert!(!screen.is_error);
+    assert_eq!(screen.status_message.as_deref(), Some(format!("Restored chat with {}", &bob_uid[..16]).as_str()));
+
+    app.undo_selected_delete();
+    assert!(app.maintenance_screen.as_ref().unwrap().is_error);
+
+    // Past the configured window the other entry is purged and can no longer be undone
+    app.run_soft_delete_maintenance(deleted + Duration::hours(2) + Duration::minutes(5));
+    assert_eq!(app.undo.entries()[0].state, UndoState::Purged);
+    assert_eq!(app.undo.entries()[1].state, UndoState::Restored);
+    app.maintenance_screen.as_mut().unwrap().move_selection(false, 2);
+    app.undo_selected_delete();
+    let screen = app.maintenance_screen.as_ref().unwrap();
+    assert!(screen.is_error);
+    assert!(screen.status_message.as_deref().unwrap().contains("purged"));
+    assert!(app.app_state.chat_by_uid("alice").is_none());
+    assert!(app.app_state.chat_by_uid(&bob_uid).is_some());
+}
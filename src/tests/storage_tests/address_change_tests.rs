@@ -0,0 +1,142 @@
+// Address Change Tests - Testing staged address changes of verified contacts (review, auto-apply, provenance)
+
+use crate::crypto::KeyPair;
+use crate::storage::{AddressSource, AppState, Contact, ContactIngest, Storage};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+fn contact_for(keypair: &KeyPair, ip: &str) -> Contact {
+    Contact::new(
+        keypair.uid.to_string(),
+        ip.to_string(),
+        keypair.public_key.clone(),
+        keypair.x25519_public.clone(),
+        Utc::now() + Duration::days(30),
+    )
+}
+
+fn reported_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap()
+}
+
+/// State with address review on and one stored contact at 10.0.0.1:8080
+fn reviewing_state(keypair: &KeyPair, verified: bool) -> AppState {
+    let mut state = AppState::new();
+    state.settings.address_review_enabled = true;
+    let mut contact = contact_for(keypair, "10.0.0.1:8080");
+    contact.verified = verified;
+    state.contacts.push(contact);
+    state
+}
+
+#[test]
+fn test_address_change_staged_only_for_verified_contacts() {
+    let keypair = KeyPair::generate().unwrap();
+
+    // Verified contact: staged, the current address stays
+    let mut state = reviewing_state(&keypair, true);
+    let claimed = contact_for(&keypair, "10.0.0.2:8080");
+    let outcome = state.ingest_contact_from(claimed.clone(), AddressSource::PingToken, reported_at()).unwrap();
+    assert!(matches!(outcome, ContactIngest::AddressStaged));
+    assert_eq!(state.contacts[0].ip, "10.0.0.1:8080");
+    assert_eq!(state.address_change(&claimed.uid).unwrap().ip, "10.0.0.2:8080");
+
+    // The same claim again is not a new change; a different one replaces it
+    let outcome = state.ingest_contact_from(claimed.clone(), AddressSource::PingToken, reported_at()).unwrap();
+    assert!(matches!(outcome, ContactIngest::Unchanged));
+    let newer = contact_for(&keypair, "10.0.0.3:8080");
+    state.ingest_contact_from(newer, AddressSource::ImportedToken, reported_at()).unwrap();
+    assert_eq!(state.address_changes.len(), 1);
+    assert_eq!(state.address_change(&claimed.uid).unwrap().ip, "10.0.0.3:8080");
+
+    // Unverified contact: applied right away
+    let mut state = reviewing_state(&keypair, false);
+    let outcome = state.ingest_contact_from(claimed.clone(), AddressSource::PingToken, reported_at()).unwrap();
+    assert!(matches!(outcome, ContactIngest::AddressUpdated));
+    assert_eq!(state.contacts[0].ip, "10.0.0.2:8080");
+    assert!(state.address_changes.is_empty());
+
+    // Review disabled: verified contacts are updated as before
+    let mut state = reviewing_state(&keypair, true);
+    state.settings.address_review_enabled = false;
+    let outcome = state.ingest_contact(claimed).unwrap();
+    assert!(matches!(outcome, ContactIngest::AddressUpdated));
+    assert_eq!(state.contacts[0].ip, "10.0.0.2:8080");
+}
+
+#[test]
+fn test_address_change_auto_applies_after_failed_deliveries() {
+    let keypair = KeyPair::generate().unwrap();
+    let uid = keypair.uid.to_string();
+    let mut state = reviewing_state(&keypair, true);
+    state.settings.address_auto_apply_failures = 3;
+    state
+        .ingest_contact_from(contact_for(&keypair, "10.0.0.2:8080"), AddressSource::PingToken, reported_at())
+        .unwrap();
+
+    // A delivery to the current address resets the count
+    assert!(state.record_delivery_outcome(&uid, false).is_none());
+    assert!(state.record_delivery_outcome(&uid, false).is_none());
+    assert!(state.record_delivery_outcome(&uid, true).is_none());
+    assert_eq!(state.address_change(&uid).unwrap().failed_deliveries, 0);
+
+    assert!(state.record_delivery_outcome(&uid, false).is_none());
+    assert!(state.record_delivery_outcome(&uid, false).is_none());
+    let applied = state.record_delivery_outcome(&uid, false).unwrap();
+    assert_eq!(applied.failed_deliveries, 3);
+    assert_eq!(state.contacts[0].ip, "10.0.0.2:8080");
+    assert!(state.address_changes.is_empty());
+
+    // A threshold of 0 never applies on its own
+    let mut state = reviewing_state(&keypair, true);
+    state.settings.address_auto_apply_failures = 0;
+    state
+        .ingest_contact_from(contact_for(&keypair, "10.0.0.2:8080"), AddressSource::PingToken, reported_at())
+        .unwrap();
+    for _ in 0..10 {
+        assert!(state.record_delivery_outcome(&uid, false).is_none());
+    }
+    assert_eq!(state.contacts[0].ip, "10.0.0.1:8080");
+}
+
+#[test]
+fn test_address_change_records_provenance_and_persists() {
+    let keypair = KeyPair::generate().unwrap();
+    let mut state = reviewing_state(&keypair, true);
+    state
+        .ingest_contact_from(contact_for(&keypair, "10.0.0.2:8080"), AddressSource::ImportedToken, reported_at())
+        .unwrap();
+
+    let change = state.address_change(&keypair.uid.to_string()).unwrap().clone();
+    assert_eq!(change.previous_ip, "10.0.0.1:8080");
+    assert_eq!(change.source, AddressSource::ImportedToken);
+    assert!(change.signature_verified);
+    assert_eq!(change.reported_at, reported_at());
+
+    state.user_keypair = Some(KeyPair::generate().unwrap());
+    let storage = Storage::new_in_memory().unwrap();
+    state.save_to_db(&storage).unwrap();
+    let loaded = AppState::load_from_db(&storage).unwrap();
+    assert_eq!(loaded.address_changes, vec![change]);
+    assert!(loaded.contacts[0].verified);
+    assert!(loaded.settings.address_review_enabled);
+}
+
+#[test]
+fn test_address_change_apply_and_ignore() {
+    let keypair = KeyPair::generate().unwrap();
+    let uid = keypair.uid.to_string();
+    let claimed = contact_for(&keypair, "10.0.0.2:8080");
+
+    let mut state = reviewing_state(&keypair, true);
+    state.ingest_contact_from(claimed.clone(), AddressSource::PingToken, reported_at()).unwrap();
+    assert!(state.apply_address_change(&uid));
+    assert_eq!(state.contacts[0].ip, "10.0.0.2:8080");
+    assert!(!state.apply_address_change(&uid));
+
+    let mut state = reviewing_state(&keypair, true);
+    state.ingest_contact_from(claimed, AddressSource::PingToken, reported_at()).unwrap();
+    assert!(state.ignore_address_change(&uid));
+    assert_eq!(state.contacts[0].ip, "10.0.0.1:8080");
+    assert!(state.address_change(&uid).is_none());
+    assert!(!state.ignore_address_change(&uid));
+}
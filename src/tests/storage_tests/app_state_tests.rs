@@ -520,3 +520,199 @@ fn test_app_state_sqlite_settings_persistence() {
     assert_eq!(loaded.settings.max_message_retries, 15);
     assert!(!loaded.settings.enable_notifications);
 }
+
+#[test]
+fn test_app_state_load_headers_skips_messages() {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    state.contacts.push(Contact::new(
+        "alice_uid".to_string(),
+        "127.0.0.1:8080".to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        Utc::now() + Duration::days(30),
+    ));
+    let chat = state.add_chat("alice_uid".to_string());
+    chat.mark_has_pending();
+    chat.append_message(Message::new(
+        "msg1".to_string(),
+        "alice_uid".to_string(),
+        "me".to_string(),
+        b"hello".to_vec(),
+        1000,
+    ));
+    state.save_to_db(&storage).expect("Failed to save");
+
+    let loaded = AppState::load_headers_from_db(&storage).expect("Failed to load headers");
+    assert_eq!(loaded.chats.len(), 1);
+    assert!(loaded.chats[0].messages.is_empty());
+    assert!(loaded.chats[0].has_pending_messages);
+    assert_eq!(storage.message_query_count(), 0);
+
+    let messages = storage.load_chat_messages("alice_uid").expect("Failed to load messages");
+    assert_eq!(messages.len(), 1);
+    assert_eq!(storage.message_query_count(), 1);
+}
+
+#[test]
+fn test_app_state_sqlite_message_metadata() {
+    use crate::storage::{MessageMetadata, MetadataValue};
+
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    state.contacts.push(Contact::new(
+        "alice".to_string(),
+        "10.0.0.1:8080".to_string(),
+        vec![1; 32],
+        vec![2; 32],
+        Utc::now() + Duration::days(30),
+    ));
+
+    let mut metadata = MessageMetadata::new();
+    metadata.insert("ticket".to_string(), MetadataValue::from("T-7"));
+    metadata.insert("open".to_string(), MetadataValue::from(true));
+
+    let mut chat = Chat::new("alice".to_string());
+    chat.append_message(
+        Message::new("with".to_string(), "alice".to_string(), "self".to_string(), vec![1], 1)
+            .with_metadata(metadata.clone())
+            .unwrap(),
+    );
+    chat.append_message(Message::new("without".to_string(), "alice".to_string(), "self".to_string(), vec![2], 2));
+    state.chats.push(chat);
+
+    state.save_to_db(&storage).expect("Failed to save");
+    let loaded = AppState::load_from_db(&storage).expect("Failed to load");
+
+    assert_eq!(loaded.chats[0].messages[0].metadata, metadata);
+    assert!(!loaded.chats[0].messages[1].has_metadata());
+}
+
+#[test]
+fn test_app_state_sqlite_message_annotations() {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    state.contacts.push(Contact::new(
+        "alice".to_string(),
+        "10.0.0.1:8080".to_string(),
+        vec![1; 32],
+        vec![2; 32],
+        Utc::now() + Duration::days(30),
+    ));
+
+    let mut chat = Chat::new("alice".to_string());
+    chat.append_message(Message::new("pinned".to_string(), "alice".to_string(), "self".to_string(), vec![1], 1));
+    chat.append_message(Message::new("starred".to_string(), "alice".to_string(), "self".to_string(), vec![2], 2));
+    chat.append_message(Message::new("plain".to_string(), "alice".to_string(), "self".to_string(), vec![3], 3));
+    chat.toggle_pin("pinned").unwrap();
+    chat.toggle_star("starred").unwrap();
+    state.chats.push(chat);
+
+    state.save_to_db(&storage).expect("Failed to save");
+    let loaded = AppState::load_from_db(&storage).expect("Failed to load");
+
+    let messages = &loaded.chats[0].messages;
+    assert!(messages[0].pinned && !messages[0].starred);
+    assert!(!messages[1].pinned && messages[1].starred);
+    assert!(!messages[2].pinned && !messages[2].starred);
+}
+
+#[test]
+fn test_app_state_starred_messages_across_chats() {
+    let mut state = AppState::new();
+
+    let mut alice = Chat::new("alice".to_string());
+    alice.append_message(Message::new("a1".to_string(), "alice".to_string(), "self".to_string(), vec![], 10));
+    alice.append_message(Message::new("a2".to_string(), "alice".to_string(), "self".to_string(), vec![], 30));
+    alice.toggle_star("a1").unwrap();
+    alice.toggle_star("a2").unwrap();
+
+    let mut bob = Chat::new("bob".to_string());
+    bob.append_message(Message::new("b1".to_string(), "bob".to_string(), "self".to_string(), vec![], 20));
+    bob.append_message(Message::new("b2".to_string(), "bob".to_string(), "self".to_string(), vec![], 40));
+    bob.toggle_star("b1").unwrap();
+
+    state.chats.push(alice);
+    state.chats.push(bob);
+
+    // Newest first, each tagged with its chat
+    let starred: Vec<(&str, &str)> = state
+        .starred_messages()
+        .into_iter()
+        .map(|(uid, m)| (uid, m.id.as_str()))
+        .collect();
+    assert_eq!(starred, vec![("alice", "a2"), ("bob", "b1"), ("alice", "a1")]);
+}
+
+#[test]
+fn test_app_state_sqlite_failed_delivery_flag() {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    for uid in ["alice", "bob"] {
+        state.contacts.push(Contact::new(
+            uid.to_string(),
+            "10.0.0.1:8080".to_string(),
+            vec![1; 32],
+            vec![2; 32],
+            Utc::now() + Duration::days(30),
+        ));
+        state.chats.push(Chat::new(uid.to_string()));
+    }
+    state.chats[0].mark_has_failed();
+
+    state.save_to_db(&storage).expect("Failed to save");
+    let loaded = AppState::load_headers_from_db(&storage).expect("Failed to load");
+
+    assert!(loaded.get_chat("alice").unwrap().has_failed());
+    assert!(!loaded.get_chat("bob").unwrap().has_failed());
+}
+
+#[test]
+fn test_app_state_sqlite_history_trim_persisted() {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    state.settings.history_limit = 2;
+    state.contacts.push(Contact::new(
+        "alice".to_string(),
+        "10.0.0.1:8080".to_string(),
+        vec![1; 32],
+        vec![2; 32],
+        Utc::now() + Duration::days(30),
+    ));
+    let mut chat = Chat::new("alice".to_string());
+    for n in 1..=3 {
+        chat.append_message(Message::new(format!("m{}", n), "alice".to_string(), "me".to_string(), b"hi".to_vec(), n));
+    }
+    chat.toggle_pin("m1").unwrap();
+    state.chats.push(chat);
+    state.save_to_db(&storage).expect("Failed to save");
+
+    // A stale copy still holding m2 does not bring it back once the trim is saved
+    let stale = state.clone();
+    let limit = state.settings.history_limit;
+    state.chats[0].append_with_limit(
+        Message::new("m4".to_string(), "alice".to_string(), "me".to_string(), b"hi".to_vec(), 4),
+        limit,
+    );
+    state.save_to_db(&storage).expect("Failed to save");
+    stale.save_to_db(&storage).expect("Failed to save");
+    state.save_to_db(&storage).expect("Failed to save");
+
+    let loaded = AppState::load_from_db(&storage).expect("Failed to load");
+    assert_eq!(loaded.settings.history_limit, 2);
+    let chat = loaded.get_chat("alice").unwrap();
+    assert!(chat.is_trimmed());
+    let ids: Vec<&str> = chat.messages.iter().filter(|m| !m.is_system()).map(|m| m.id.as_str()).collect();
+    assert_eq!(ids, vec!["m1", "m3", "m4"]);
+    assert_eq!(chat.messages.iter().filter(|m| m.is_system()).count(), 1);
+}
@@ -0,0 +1,182 @@
+// Bounds Tests - Testing clamped token expiries and peer timestamps (boundaries, recorded warning, display saturation, ordering)
+
+use crate::crypto::KeyPair;
+use crate::messaging::handle_incoming_message;
+use crate::storage::{
+    bound_message_timestamp, bounded_timestamp, generate_contact_token, parse_contact_token, timestamp_in_bounds,
+    clamp_token_expiry, migration::read_legacy_state, AddressSource, AppState, Contact, Message, MetadataValue, Settings, Storage,
+    DEFAULT_MAX_TOKEN_EXPIRY_DAYS, MAX_CLOCK_SKEW_MINUTES, MAX_TIMESTAMP_AGE_DAYS, MAX_TOKEN_EXPIRY_DAYS_CAP,
+    ORIGINAL_TIMESTAMP_KEY,
+};
+use crate::tui::ui::format_duration_until_at;
+use crate::tui::DiagnosticsScreen;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+fn now() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap()
+}
+
+fn contact_expiring(keypair: &KeyPair, expiry: DateTime<Utc>) -> Contact {
+    Contact::new(
+        keypair.uid.to_string(),
+        "203.0.113.7:8080".to_string(),
+        keypair.public_key.clone(),
+        keypair.x25519_public.clone(),
+        expiry,
+    )
+}
+
+#[test]
+fn test_token_expiry_clamped_beyond_horizon_with_warning_recorded() {
+    let horizon = now() + Duration::days(i64::from(DEFAULT_MAX_TOKEN_EXPIRY_DAYS));
+    let mut state = AppState::new();
+
+    // Exactly at the horizon: kept as is
+    let at_limit = KeyPair::generate().unwrap();
+    state
+        .ingest_contact_from(contact_expiring(&at_limit, horizon), AddressSource::ImportedToken, now())
+        .unwrap();
+    let stored = state.contact_by_uid(&at_limit.uid.to_string()).unwrap();
+    assert_eq!(stored.expiry, horizon);
+    assert_eq!(stored.requested_expiry, None);
+
+    // One second later: clamped, the requested expiry recorded on the contact
+    let over = KeyPair::generate().unwrap();
+    let asked = horizon + Duration::seconds(1);
+    state
+        .ingest_contact_from(contact_expiring(&over, asked), AddressSource::ImportedToken, now())
+        .unwrap();
+    let stored = state.contact_by_uid(&over.uid.to_string()).unwrap();
+    assert_eq!(stored.expiry, horizon);
+    assert_eq!(stored.requested_expiry, Some(asked));
+
+    // A signed token for the year 9999, under a shorter configured horizon
+    let giant = KeyPair::generate().unwrap();
+    let year_9999 = Utc.with_ymd_and_hms(9999, 12, 31, 0, 0, 0).unwrap();
+    let token = generate_contact_token("203.0.113.9:8080", &giant.public_key, &giant.private_key, &giant.x25519_public, year_9999)
+        .unwrap();
+    state.settings.max_token_expiry_days = 30;
+    state
+        .ingest_contact_from(parse_contact_token(&token).unwrap(), AddressSource::PingToken, now())
+        .unwrap();
+    let stored = state.contact_by_uid(&giant.uid.to_string()).unwrap().clone();
+    assert_eq!(stored.expiry, now() + Duration::days(30));
+    assert_eq!(stored.requested_expiry, Some(year_9999));
+
+    // The warning survives a save and reload
+    let storage = Storage::new_in_memory().unwrap();
+    storage.save_contact(&stored).unwrap();
+    let loaded = storage.load_contacts().unwrap();
+    assert_eq!(loaded[0].requested_expiry, Some(year_9999));
+    assert_eq!(loaded[0].expiry, stored.expiry);
+}
+
+#[test]
+fn test_huge_configured_horizon_is_capped_not_overflowed() {
+    let cap = now() + Duration::days(i64::from(MAX_TOKEN_EXPIRY_DAYS_CAP));
+    let year_9999 = Utc.with_ymd_and_hms(9999, 12, 31, 0, 0, 0).unwrap();
+
+    // The clamp itself never overflows, whatever it is given
+    for max_days in [MAX_TOKEN_EXPIRY_DAYS_CAP + 1, 100_000_000, u32::MAX] {
+        let mut contact = contact_expiring(&KeyPair::generate().unwrap(), year_9999);
+        assert!(clamp_token_expiry(&mut contact, now(), max_days));
+        assert_eq!(contact.expiry, cap);
+    }
+    // Nor at the end of time, where the horizon saturates
+    let mut contact = contact_expiring(&KeyPair::generate().unwrap(), DateTime::<Utc>::MAX_UTC);
+    assert!(!clamp_token_expiry(&mut contact, DateTime::<Utc>::MAX_UTC - Duration::days(1), 30));
+    assert_eq!(contact.expiry, DateTime::<Utc>::MAX_UTC);
+
+    // Settings loaded from a file, the database or an imported state are capped
+    let dir = tempfile::TempDir::new().unwrap();
+    let settings_path = dir.path().join("settings.json");
+    let edited = Settings { max_token_expiry_days: u32::MAX, ..Settings::default() };
+    edited.save(&settings_path).unwrap();
+    assert_eq!(Settings::load(&settings_path).unwrap().max_token_expiry_days, MAX_TOKEN_EXPIRY_DAYS_CAP);
+
+    let storage = Storage::new_in_memory().unwrap();
+    storage.save_settings(&edited).unwrap();
+    assert_eq!(storage.load_settings().unwrap().unwrap().max_token_expiry_days, MAX_TOKEN_EXPIRY_DAYS_CAP);
+
+    let state_path = dir.path().join("app_state.json");
+    let mut state = AppState::new();
+    state.settings = edited.clone();
+    state.save(&state_path).unwrap();
+    assert_eq!(AppState::load(&state_path).unwrap().settings.max_token_expiry_days, MAX_TOKEN_EXPIRY_DAYS_CAP);
+    assert_eq!(read_legacy_state(&state_path).unwrap().settings.max_token_expiry_days, MAX_TOKEN_EXPIRY_DAYS_CAP);
+
+    // Values within the cap are left alone
+    let mut settings = Settings { max_token_expiry_days: MAX_TOKEN_EXPIRY_DAYS_CAP, ..Settings::default() };
+    assert!(!settings.bound_limits());
+    assert_eq!(settings.max_token_expiry_days, MAX_TOKEN_EXPIRY_DAYS_CAP);
+}
+
+#[test]
+fn test_message_timestamps_clamped_outside_window() {
+    let earliest = (now() - Duration::days(MAX_TIMESTAMP_AGE_DAYS)).timestamp_millis();
+    let latest = (now() + Duration::minutes(MAX_CLOCK_SKEW_MINUTES)).timestamp_millis();
+
+    // Both ends are inclusive
+    assert!(timestamp_in_bounds(earliest, now()));
+    assert!(timestamp_in_bounds(latest, now()));
+    assert!(!timestamp_in_bounds(earliest - 1, now()));
+    assert!(!timestamp_in_bounds(latest + 1, now()));
+    assert_eq!(bounded_timestamp(latest, now()), latest);
+    assert_eq!(bounded_timestamp(latest + 1, now()), now().timestamp_millis());
+
+    for absurd in [i64::MAX, i64::MIN, latest + 1, earliest - 1] {
+        let mut message = Message::new("m".to_string(), "peer".to_string(), "me".to_string(), b"hi".to_vec(), absurd);
+        assert!(bound_message_timestamp(&mut message, now()));
+        assert_eq!(message.timestamp, now().timestamp_millis());
+        assert_eq!(message.metadata.get(ORIGINAL_TIMESTAMP_KEY), Some(&MetadataValue::Integer(absurd)));
+    }
+
+    let mut message = Message::new("m".to_string(), "peer".to_string(), "me".to_string(), b"hi".to_vec(), earliest);
+    assert!(!bound_message_timestamp(&mut message, now()));
+    assert_eq!(message.timestamp, earliest);
+    assert!(message.metadata.is_empty());
+}
+
+#[test]
+fn test_duration_helpers_saturate_and_never_panic() {
+    // Far future, including the largest representable time
+    assert_eq!(format_duration_until_at(now() + Duration::days(999), now()), "999 days");
+    assert_eq!(format_duration_until_at(now() + Duration::days(999) + Duration::seconds(1), now()), "999+ days");
+    assert_eq!(format_duration_until_at(DateTime::<Utc>::MAX_UTC, now()), "999+ days");
+
+    // Past and near-overflow past
+    assert_eq!(format_duration_until_at(now() - Duration::days(3), now()), "expired");
+    assert_eq!(format_duration_until_at(DateTime::<Utc>::MIN_UTC, now()), "expired");
+    assert_eq!(format_duration_until_at(now(), DateTime::<Utc>::MAX_UTC), "expired");
+
+    assert_eq!(DiagnosticsScreen::format_time_remaining(i64::MAX), "999+ days");
+    assert_eq!(DiagnosticsScreen::format_time_remaining(-5), "0s");
+    assert_eq!(DiagnosticsScreen::format_time_remaining(i64::MIN), "0s");
+    assert_eq!(DiagnosticsScreen::format_time_remaining(90), "1m 30s");
+
+    // Retry countdown from a queue row with an absurd next_retry
+    let mut message = Message::new("m".to_string(), "me".to_string(), "peer".to_string(), b"hi".to_vec(), 0);
+    message.mark_pending(i64::MAX);
+    assert_eq!(message.status_text(), "retry in 999+ days");
+    message.mark_pending(i64::MIN);
+    assert_eq!(message.status_text(), "retry in 0s");
+}
+
+#[test]
+fn test_clamped_timestamps_keep_chat_order() {
+    let mut state = AppState::new();
+    let received = Utc::now();
+
+    // A message "from the future" arrives first, then ordinary ones
+    handle_incoming_message(&mut state, "peer", "me", "future", b"a".to_vec(), i64::MAX);
+    handle_incoming_message(&mut state, "peer", "me", "ancient", b"b".to_vec(), 0);
+    handle_incoming_message(&mut state, "peer", "me", "normal", b"c".to_vec(), Utc::now().timestamp_millis());
+
+    let chat = state.get_chat("peer").unwrap();
+    let ids: Vec<&str> = chat.messages.iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(ids, vec!["future", "ancient", "normal"]);
+    // Timestamps follow arrival order, so sorting by them changes nothing
+    assert!(chat.messages.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+    assert!(chat.messages[0].timestamp >= received.timestamp_millis());
+    assert_eq!(chat.messages[0].metadata.get(ORIGINAL_TIMESTAMP_KEY), Some(&MetadataValue::Integer(i64::MAX)));
+}
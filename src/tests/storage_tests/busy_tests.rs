@@ -0,0 +1,139 @@
+// Busy Tests - Testing write retries and deferred writes while the database is locked
+
+use crate::crypto::KeyPair;
+use crate::storage::{
+    deferred_writes::DEFERRED_FLUSH_INTERVAL, is_busy_error, AppState, BusyPolicy, Contact,
+    DeferredWrites, Storage,
+};
+use chrono::{Duration, Utc};
+use rusqlite::Connection;
+use std::time::{Duration as StdDuration, Instant};
+use tempfile::TempDir;
+
+/// Policy with short waits so locked-database tests run quickly
+fn fast_policy(max_retries: u32) -> BusyPolicy {
+    BusyPolicy {
+        busy_timeout: StdDuration::from_millis(10),
+        max_retries,
+        base_delay: StdDuration::from_millis(5),
+    }
+}
+
+fn open_storage(temp_dir: &TempDir, max_retries: u32) -> Storage {
+    let mut storage = Storage::new(temp_dir.path().join("pure2p.db")).expect("Failed to open storage");
+    storage.set_busy_policy(fast_policy(max_retries)).expect("Failed to set busy policy");
+    storage
+}
+
+/// Hold an exclusive lock from a second connection, as a backup tool would
+fn lock_database(temp_dir: &TempDir) -> Connection {
+    let conn = Connection::open(temp_dir.path().join("pure2p.db")).expect("Failed to open lock connection");
+    conn.execute_batch("BEGIN EXCLUSIVE").expect("Failed to lock database");
+    conn
+}
+
+fn test_state(contact_uid: &str) -> AppState {
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    state.contacts.push(Contact::new(
+        contact_uid.to_string(),
+        "127.0.0.1:8080".to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        Utc::now() + Duration::days(30),
+    ));
+    state
+}
+
+#[test]
+fn test_busy_policy_default() {
+    let policy = BusyPolicy::default();
+    assert!(policy.busy_timeout > StdDuration::ZERO);
+    assert!(policy.max_retries > 0);
+
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+    assert_eq!(storage.busy_policy(), policy);
+}
+
+#[test]
+fn test_save_retries_then_fails_while_locked() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let storage = open_storage(&temp_dir, 2);
+    let lock = lock_database(&temp_dir);
+
+    let err = test_state("alice").save_to_db(&storage).expect_err("Save should fail while locked");
+    assert!(is_busy_error(&err));
+    assert_eq!(storage.busy_retry_count(), 2);
+
+    // Nothing was written
+    lock.execute_batch("COMMIT").expect("Failed to release lock");
+    assert!(storage.load_contacts().expect("Failed to load contacts").is_empty());
+}
+
+#[test]
+fn test_save_succeeds_when_lock_released_during_retries() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut storage = open_storage(&temp_dir, 6);
+    storage.set_busy_policy(BusyPolicy {
+        base_delay: StdDuration::from_millis(20),
+        ..fast_policy(6)
+    }).expect("Failed to set busy policy");
+
+    let lock = lock_database(&temp_dir);
+    let releaser = std::thread::spawn(move || {
+        std::thread::sleep(StdDuration::from_millis(60));
+        lock.execute_batch("COMMIT").expect("Failed to release lock");
+    });
+
+    test_state("alice").save_to_db(&storage).expect("Save should succeed after the lock is released");
+    releaser.join().unwrap();
+
+    assert!(storage.busy_retry_count() >= 1);
+    assert_eq!(storage.load_contacts().expect("Failed to load contacts").len(), 1);
+}
+
+#[test]
+fn test_with_write_retry_rolls_back_on_error() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let storage = open_storage(&temp_dir, 2);
+    let state = test_state("alice");
+
+    let result: crate::Result<()> = storage.with_write_retry(|| {
+        storage.save_contact(&state.contacts[0])?;
+        Err(crate::Error::Storage("simulated failure".to_string()))
+    });
+
+    assert!(result.is_err());
+    assert!(!is_busy_error(&result.unwrap_err()));
+    assert_eq!(storage.busy_retry_count(), 0);
+    assert!(storage.load_contacts().expect("Failed to load contacts").is_empty());
+}
+
+#[test]
+fn test_deferred_writes_lifecycle() {
+    let mut deferred = DeferredWrites::new();
+    assert!(!deferred.is_pending());
+    assert!(!deferred.start_flush_attempt(Instant::now()));
+
+    let error = crate::Error::Storage("locked".to_string());
+    deferred.record(&error);
+    deferred.record(&error);
+    assert!(deferred.is_pending());
+    assert_eq!(deferred.pending_count(), 2);
+    assert!(deferred.last_error().unwrap().contains("locked"));
+
+    // Flushes are throttled after a failed write
+    let now = Instant::now();
+    assert!(!deferred.start_flush_attempt(now));
+    let later = now + DEFERRED_FLUSH_INTERVAL;
+    assert!(deferred.start_flush_attempt(later));
+    assert!(!deferred.start_flush_attempt(later));
+
+    // A failed flush keeps the pending count
+    deferred.flush_failed(&error);
+    assert_eq!(deferred.pending_count(), 2);
+
+    deferred.flushed();
+    assert!(!deferred.is_pending());
+    assert!(deferred.last_error().is_none());
+}
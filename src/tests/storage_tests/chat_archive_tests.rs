@@ -0,0 +1,176 @@
+// Chat Archive Tests - Signed chat archive: export/verify round trip, tamper detection, cross-reference with the peer's copy, wrong key
+
+use crate::crypto::KeyPair;
+use crate::storage::{ArchiveProblem, Chat, ChatArchive, Contact, Message, Storage, CHAT_ARCHIVE_VERSION};
+use chrono::{Duration, Utc};
+
+/// Alice's chat with bob: received, sent, binary received, then a local notice
+fn alice_chat(alice: &KeyPair, bob: &KeyPair) -> Chat {
+    let (alice_uid, bob_uid) = (alice.uid.to_string(), bob.uid.to_string());
+    let mut chat = Chat::new(bob_uid.clone());
+    let mut hi = Message::new("a1".to_string(), bob_uid.clone(), alice_uid.clone(), b"hi".to_vec(), 1000);
+    hi.remote_id = Some("b1".to_string());
+    chat.append_message(hi);
+    chat.append_message(Message::new("a2".to_string(), alice_uid.clone(), bob_uid.clone(), b"hello".to_vec(), 2000));
+    let mut photo = Message::new("a3".to_string(), bob_uid, alice_uid, vec![0, 255, 254], 3000);
+    photo.remote_id = Some("b3".to_string());
+    chat.append_message(photo);
+    chat.append_message(Message::system("older messages were removed", 4000));
+    chat
+}
+
+/// Storage holding `chat` and its contact
+fn storage_with(chat: &Chat) -> Storage {
+    let storage = Storage::new_in_memory().unwrap();
+    storage
+        .save_contact(&Contact::new(
+            chat.contact_uid.clone(),
+            "192.168.1.100:8080".to_string(),
+            vec![0; 32],
+            vec![0; 32],
+            Utc::now() + Duration::days(30),
+        ))
+        .unwrap();
+    storage.save_chat(chat).unwrap();
+    storage
+}
+
+fn archive_of(alice: &KeyPair, chat: &Chat) -> (String, usize) {
+    let storage = storage_with(chat);
+    let mut out = Vec::new();
+    let written = storage.export_chat_archive(&chat.contact_uid, alice, &mut out).unwrap();
+    (String::from_utf8(out).unwrap(), written)
+}
+
+/// Whether `line` is the message line for `id` (the manifest lists IDs too)
+fn is_line_of(line: &str, id: &str) -> bool {
+    line.starts_with(&format!("{{\"version\":1,\"id\":\"{}\"", id))
+}
+
+/// Apply `edit` to the message line for `id`
+fn edit_line(text: &str, id: &str, edit: impl Fn(&str) -> String) -> String {
+    text.lines()
+        .map(|line| if is_line_of(line, id) { edit(line) } else { line.to_string() })
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[test]
+fn test_archive_round_trip_verifies() {
+    let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let (text, written) = archive_of(&alice, &alice_chat(&alice, &bob));
+
+    // Three messages, a manifest and a signature; the notice stays out
+    assert_eq!(written, 3);
+    assert_eq!(text.lines().count(), 5);
+    assert!(!text.contains("older messages were removed"));
+
+    let archive = ChatArchive::parse(&text).unwrap();
+    let manifest = &archive.manifest;
+    assert_eq!(manifest.version, CHAT_ARCHIVE_VERSION);
+    assert_eq!((manifest.owner_uid.as_str(), manifest.contact_uid.as_str()), (alice.uid.as_str(), bob.uid.as_str()));
+    assert_eq!((manifest.first_sent_at, manifest.last_sent_at), (Some(1000), Some(3000)));
+    assert_eq!(manifest.public_key, hex::encode(&alice.public_key));
+    let wire_ids: Vec<&str> = manifest.messages.iter().map(|entry| entry.wire_id.as_str()).collect();
+    assert_eq!(wire_ids, ["b1", "a2", "b3"]);
+
+    let verification = archive.verify(&alice.public_key).unwrap();
+    assert!(verification.is_intact());
+    assert_eq!(verification.intact, 3);
+
+    // An empty chat still makes a verifiable archive
+    let (empty, written) = archive_of(&alice, &Chat::new(bob.uid.to_string()));
+    assert_eq!(written, 0);
+    let verification = ChatArchive::parse(&empty).unwrap().verify(&alice.public_key).unwrap();
+    assert!(verification.is_intact());
+    assert_eq!(verification.manifest.first_sent_at, None);
+}
+
+#[test]
+fn test_tampered_lines_are_reported() {
+    let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let (text, _) = archive_of(&alice, &alice_chat(&alice, &bob));
+    let problems = |text: &str| ChatArchive::parse(text).unwrap().verify(&alice.public_key).unwrap().problems;
+
+    // Modified content
+    let changed = edit_line(&text, "a2", |line| line.replace("\"hello\"", "\"goodbye\""));
+    assert_eq!(problems(&changed), [ArchiveProblem::ContentChanged("a2".to_string())]);
+
+    // Altered timestamp
+    let moved = edit_line(&text, "a1", |line| line.replace("\"sent_at\":1000", "\"sent_at\":1500"));
+    assert_eq!(problems(&moved), [ArchiveProblem::TimestampChanged("a1".to_string())]);
+
+    // Another field
+    let status = edit_line(&text, "a2", |line| line.replace("\"delivery_status\":\"sent\"", "\"delivery_status\":\"failed\""));
+    assert_eq!(problems(&status), [ArchiveProblem::LineChanged("a2".to_string())]);
+
+    // Dropped message
+    let dropped = edit_line(&text, "a3", |_| String::new());
+    assert_eq!(
+        problems(&dropped),
+        [ArchiveProblem::Unreadable(3), ArchiveProblem::Dropped("a3".to_string())]
+    );
+    let dropped: String = text.lines().filter(|line| !is_line_of(line, "a3")).map(|line| format!("{}\n", line)).collect();
+    assert_eq!(problems(&dropped), [ArchiveProblem::Dropped("a3".to_string())]);
+
+    // A message slipped in, and the intact ones still count
+    let forged = edit_line(&text, "a2", |line| format!("{}\n{}", line, line.replace("\"a2\"", "\"a9\"")));
+    let verification = ChatArchive::parse(&forged).unwrap().verify(&alice.public_key).unwrap();
+    assert_eq!(verification.problems, [ArchiveProblem::Added("a9".to_string())]);
+    assert_eq!(verification.intact, 3);
+}
+
+#[test]
+fn test_cross_reference_against_peer_copy() {
+    let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let (alice_uid, bob_uid) = (alice.uid.to_string(), bob.uid.to_string());
+    let (text, _) = archive_of(&alice, &alice_chat(&alice, &bob));
+    let verification = ChatArchive::parse(&text).unwrap().verify(&alice.public_key).unwrap();
+
+    // Bob's copy: own IDs for what he sent, arrival times, and deliberate differences
+    let mut chat = Chat::new(alice_uid.clone());
+    chat.append_message(Message::new("b1".to_string(), bob_uid.clone(), alice_uid.clone(), b"hi".to_vec(), 999));
+    let mut hello = Message::new("x2".to_string(), alice_uid.clone(), bob_uid.clone(), b"hello!".to_vec(), 2010);
+    hello.remote_id = Some("a2".to_string());
+    chat.append_message(hello);
+    // b3 is missing from bob's copy; b4 only he has, within the archive's range
+    chat.append_message(Message::new("b4".to_string(), bob_uid.clone(), alice_uid.clone(), b"you there?".to_vec(), 2500));
+    // After the archive was made: not counted against it
+    chat.append_message(Message::new("b5".to_string(), bob_uid.clone(), alice_uid.clone(), b"later".to_vec(), 9000));
+    chat.append_message(Message::system("older messages were removed", 2600));
+    let storage = storage_with(&chat);
+
+    let report = storage.cross_reference_archive(&verification.manifest).unwrap();
+    assert_eq!(report.matched, ["b1"]);
+    assert_eq!(report.mismatched, ["a2"]);
+    assert_eq!(report.only_in_archive, ["b3"]);
+    assert_eq!(report.only_local, ["b4"]);
+    assert!(!report.agrees());
+
+    // Without the chat, everything is only in the archive
+    let report = Storage::new_in_memory().unwrap().cross_reference_archive(&verification.manifest).unwrap();
+    assert_eq!(report.only_in_archive.len(), 3);
+}
+
+#[test]
+fn test_wrong_key_or_edited_manifest_rejected() {
+    let (alice, bob, mallory) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let (text, _) = archive_of(&alice, &alice_chat(&alice, &bob));
+    let archive = ChatArchive::parse(&text).unwrap();
+
+    assert!(archive.verify(&mallory.public_key).is_err());
+    assert!(archive.verify(&[0u8; 5]).is_err());
+
+    // Re-signing a forged manifest with another key does not help
+    let (forged, _) = archive_of(&mallory, &alice_chat(&alice, &bob));
+    let forged = forged.replace(mallory.uid.as_str(), alice.uid.as_str());
+    assert!(ChatArchive::parse(&forged).unwrap().verify(&alice.public_key).is_err());
+
+    // Editing the manifest breaks the signature
+    let edited = text.replace("\"first_sent_at\":1000", "\"first_sent_at\":1");
+    assert!(ChatArchive::parse(&edited).unwrap().verify(&alice.public_key).is_err());
+
+    // No trailer, no archive
+    let plain: String = text.lines().take(3).map(|line| format!("{}\n", line)).collect();
+    assert!(ChatArchive::parse(&plain).is_err());
+}
@@ -0,0 +1,260 @@
+// Chat summary tests - summary maintenance across insert/edit/trim/delete, chat list without message reads, resumable migration of a 100k-row database, insert cost against the old full rewrite
+
+use crate::storage::{preview_text, AppState, Chat, Contact, Message, MessageSummary, Storage, SUMMARY_PREVIEW_CHARS};
+use crate::tui::ui::chat_list_rows;
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+fn message(id: &str, sender: &str, text: &str, timestamp: i64) -> Message {
+    Message::new(id.to_string(), sender.to_string(), "me".to_string(), text.as_bytes().to_vec(), timestamp)
+}
+
+/// Save `chat` and its contact
+fn save(storage: &Storage, chat: &Chat) {
+    let contact = Contact::new(
+        chat.contact_uid.clone(),
+        "192.168.1.100:8080".to_string(),
+        vec![1, 2, 3],
+        vec![7u8; 32],
+        Utc::now() + chrono::Duration::days(30),
+    );
+    storage.with_write_retry(|| storage.save_contact(&contact).and_then(|_| storage.save_chat(chat))).unwrap();
+}
+
+fn summary_of(storage: &Storage, uid: &str) -> Option<MessageSummary> {
+    storage.load_chat_summaries().unwrap().into_iter().find(|(chat_uid, _)| chat_uid == uid).map(|(_, s)| s)
+}
+
+/// The summary a full read of the chat's stored messages gives
+fn expected_summary(storage: &Storage, uid: &str) -> MessageSummary {
+    let messages = storage.load_chat_messages(uid).unwrap();
+    let mut summary = MessageSummary { message_count: messages.len() as u64, ..Default::default() };
+    summary.set_latest(messages.iter().max_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id))));
+    summary
+}
+
+#[test]
+fn test_summary_follows_insert_edit_trim_and_delete() {
+    let storage = Storage::new_in_memory().unwrap();
+    let mut chat = Chat::new("alice".to_string());
+    save(&storage, &chat);
+    assert_eq!(summary_of(&storage, "alice"), Some(MessageSummary::default()));
+
+    // Inserts, including one older than the latest
+    chat.append_message(message("m2", "alice", "second", 2000));
+    chat.append_message(message("m1", "me", "first", 1000));
+    save(&storage, &chat);
+    let summary = summary_of(&storage, "alice").unwrap();
+    assert_eq!(summary, expected_summary(&storage, "alice"));
+    assert_eq!(summary.message_count, 2);
+    assert_eq!(summary.latest_id.as_deref(), Some("m2"));
+    assert_eq!(summary.latest_preview.as_deref(), Some("second"));
+
+    // Editing the latest message updates the preview; editing an older one does not
+    chat.messages_mut()[0].content = b"second, edited".to_vec().into();
+    chat.messages_mut()[1].starred = true;
+    save(&storage, &chat);
+    assert_eq!(summary_of(&storage, "alice").unwrap().latest_preview.as_deref(), Some("second, edited"));
+    assert_eq!(summary_of(&storage, "alice"), Some(expected_summary(&storage, "alice")));
+
+    // Trimming by the history limit
+    for i in 3..10 {
+        chat.append_with_limit(message(&format!("m{}", i), "alice", &format!("text {}", i), i * 1000), 5);
+    }
+    save(&storage, &chat);
+    assert_eq!(summary_of(&storage, "alice"), Some(expected_summary(&storage, "alice")));
+    assert_eq!(summary_of(&storage, "alice").unwrap().latest_id.as_deref(), Some("m9"));
+
+    // Deleting the chat removes its summary; other chats keep theirs
+    let mut bob = Chat::new("bob".to_string());
+    bob.append_message(message("b1", "bob", "hey", 500));
+    save(&storage, &bob);
+    storage.with_write_retry(|| storage.delete_chat("alice")).unwrap();
+    assert_eq!(summary_of(&storage, "alice"), None);
+    assert_eq!(summary_of(&storage, "bob").unwrap().message_count, 1);
+
+    // Previews stay on one line and short
+    let long = "word\n".repeat(100);
+    assert_eq!(preview_text(long.as_bytes()).chars().count(), SUMMARY_PREVIEW_CHARS + 1);
+    assert!(!preview_text(long.as_bytes()).contains('\n'));
+    assert_eq!(preview_text(&[0, 255, 254]), "[binary, 3 bytes]");
+}
+
+#[test]
+fn test_chat_list_reads_no_messages() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("pure2p.db");
+    {
+        let storage = Storage::new(&path).unwrap();
+        for (uid, count) in [("alice", 3), ("bob", 0), ("carol", 12)] {
+            let mut chat = Chat::new(uid.to_string());
+            for i in 0..count {
+                chat.append_message(message(&format!("{}_{}", uid, i), uid, "hi", i));
+            }
+            save(&storage, &chat);
+        }
+    }
+
+    let storage = Storage::new(&path).unwrap();
+    let mut state = AppState::new();
+    state.chats = storage.load_chat_headers().unwrap();
+    state.reindex();
+    let rows = chat_list_rows(&state, Utc::now());
+    let counts: Vec<(&str, usize)> = rows.iter().map(|row| (row.contact_uid, row.message_count)).collect();
+    assert_eq!(counts, [("alice", 3), ("bob", 0), ("carol", 12)]);
+    assert_eq!(storage.load_chat_summaries().unwrap().len(), 3);
+    assert_eq!(storage.message_query_count(), 0);
+}
+
+#[test]
+fn test_migration_of_100k_rows_resumes_after_interruption() {
+    const CHATS: usize = 200;
+    const PER_CHAT: usize = 500;
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("pure2p.db");
+    drop(Storage::new(&path).unwrap());
+
+    // A database written before chat summaries: no summaries, the old indexes
+    {
+        let mut conn = Connection::open(&path).unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = OFF").unwrap();
+        let tx = conn.transaction().unwrap();
+        tx.execute_batch(
+            "DROP TABLE chat_summaries;
+             DROP INDEX idx_messages_chat_id;
+             DROP INDEX idx_messages_chat_time;
+             CREATE INDEX idx_messages_chat ON messages(chat_uid);
+             CREATE INDEX idx_messages_timestamp ON messages(timestamp);",
+        )
+        .unwrap();
+        {
+            let mut chat = tx.prepare("INSERT INTO chats (contact_uid, is_active, has_pending_messages) VALUES (?1, 0, 0)").unwrap();
+            let mut msg = tx
+                .prepare("INSERT INTO messages (id, sender, receiver, content, timestamp, chat_uid) VALUES (?1, ?2, 'me', ?3, ?4, ?2)")
+                .unwrap();
+            for c in 0..CHATS {
+                let uid = format!("chat_{:03}", c);
+                chat.execute(params![uid]).unwrap();
+                for m in 0..PER_CHAT {
+                    msg.execute(params![format!("{}_{}", uid, m), uid, format!("message {}", m).into_bytes(), (m * 7 % PER_CHAT) as i64])
+                        .unwrap();
+                }
+            }
+        }
+        tx.commit().unwrap();
+    }
+
+    // Opening converts the indexes in place
+    let storage = Storage::new(&path).unwrap();
+    let conn = Connection::open(&path).unwrap();
+    let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'index' AND tbl_name = 'messages' AND sql IS NOT NULL ORDER BY name").unwrap();
+    let indexes: Vec<String> = stmt.query_map([], |row| row.get(0)).unwrap().map(|r| r.unwrap()).collect();
+    assert_eq!(indexes, ["idx_messages_chat_id", "idx_messages_chat_time"]);
+
+    // Interrupted after 50 chats
+    let mut reported = Vec::new();
+    let stopped = storage
+        .migrate_chat_summaries(|progress| {
+            reported.push(progress.done);
+            if progress.done == 50 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        })
+        .unwrap();
+    assert_eq!((stopped.done, stopped.total), (50, CHATS));
+    assert!(!stopped.is_complete());
+    assert_eq!(reported, (1..=50).collect::<Vec<_>>());
+    drop(storage);
+
+    // Resumed on the next open
+    let storage = Storage::new(&path).unwrap();
+    let mut reported = Vec::new();
+    let finished = storage
+        .migrate_chat_summaries(|progress| {
+            reported.push(progress.done);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+    assert!(finished.is_complete());
+    assert_eq!(reported.first(), Some(&51));
+    assert_eq!(reported.len(), CHATS - 50);
+
+    let summaries = storage.load_chat_summaries().unwrap();
+    assert_eq!(summaries.len(), CHATS);
+    assert_eq!(summaries.iter().map(|(_, s)| s.message_count).sum::<u64>(), (CHATS * PER_CHAT) as u64);
+    for uid in ["chat_000", "chat_049", "chat_050", "chat_199"] {
+        assert_eq!(summary_of(&storage, uid), Some(expected_summary(&storage, uid)));
+    }
+
+    // Nothing left to do
+    let again = storage.migrate_chat_summaries(|_| panic!("no chat left to build")).unwrap();
+    assert!(again.is_complete());
+}
+
+#[test]
+fn test_saving_a_new_message_no_longer_rewrites_history() {
+    const HISTORY: i64 = 5_000;
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("pure2p.db");
+    let storage = Storage::new(&path).unwrap();
+    let mut chat = Chat::new("alice".to_string());
+    for i in 0..HISTORY {
+        chat.append_message(message(&format!("m{}", i), "alice", "some history", i));
+    }
+    save(&storage, &chat);
+    // The same history under another chat, saved the old way below
+    let mut other = chat.clone();
+    other.contact_uid = "bob".to_string();
+    for m in other.messages_mut() {
+        m.id = format!("bob_{}", m.id);
+    }
+    save(&storage, &other);
+
+    // Before: every save replaced the chat row, which deleted its messages
+    // through the foreign key, and wrote them all again
+    let conn = Connection::open(&path).unwrap();
+    let old_save = |chat: &Chat| {
+        let start = Instant::now();
+        conn.execute_batch("BEGIN IMMEDIATE").unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO chats (contact_uid, is_active, has_pending_messages) VALUES (?1, 0, 0)",
+            params![chat.contact_uid],
+        )
+        .unwrap();
+        for m in chat.messages.iter() {
+            conn.execute(
+                "INSERT OR REPLACE INTO messages (id, sender, receiver, content, timestamp, chat_uid) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![m.id, m.sender, m.recipient, m.content.to_vec(), m.timestamp, chat.contact_uid],
+            )
+            .unwrap();
+        }
+        conn.execute_batch("COMMIT").unwrap();
+        start.elapsed()
+    };
+
+    // After: only the new message and the summary are written
+    let mut before = Duration::MAX;
+    let mut after = Duration::MAX;
+    for round in 0..3 {
+        let mut old_chat = other.clone();
+        old_chat.append_message(message(&format!("old_{}", round), "me", "new", HISTORY + round));
+        before = before.min(old_save(&old_chat));
+
+        chat.append_message(message(&format!("new_{}", round), "me", "new", HISTORY + round));
+        let writes = storage.message_write_count();
+        let start = Instant::now();
+        save(&storage, &chat);
+        after = after.min(start.elapsed());
+        assert_eq!(storage.message_write_count(), writes + 1);
+    }
+    assert!(after * 4 < before, "save with one new message took {:?}, the full rewrite {:?}", after, before);
+    assert_eq!(summary_of(&storage, "alice"), Some(expected_summary(&storage, "alice")));
+
+    // Another connection compares instead of rewriting
+    let fresh = Storage::new(&path).unwrap();
+    chat.append_message(message("fresh", "me", "new", HISTORY + 10));
+    save(&fresh, &chat);
+    assert_eq!(fresh.message_write_count(), 1);
+    assert_eq!(summary_of(&fresh, "alice").unwrap().latest_id.as_deref(), Some("fresh"));
+}
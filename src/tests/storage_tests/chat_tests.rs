@@ -1,6 +1,9 @@
 // Chat Tests - Testing Chat and Message structs
 
-use crate::storage::{Chat, DeliveryStatus, Message};
+use crate::storage::{
+    graphemes, sanitize_text, validate_metadata, Chat, DeliveryStatus, Message, MessageMetadata, MetadataValue,
+    HISTORY_LIMIT_PRESETS, HISTORY_TRIMMED_NOTICE, MAX_METADATA_BYTES, MAX_PINNED_PER_CHAT,
+};
 
 #[test]
 fn test_chat_creation() {
@@ -82,7 +85,7 @@ fn test_message_serialization() {
     assert_eq!(loaded.id, "test_msg_123");
     assert_eq!(loaded.sender, "sender_uid");
     assert_eq!(loaded.recipient, "recipient_uid");
-    assert_eq!(loaded.content, vec![10, 20, 30, 40, 50]);
+    assert_eq!(loaded.content.to_vec(), vec![10, 20, 30, 40, 50]);
     assert_eq!(loaded.timestamp, 1234567890);
     assert!(loaded.delivered);
 }
@@ -207,7 +210,7 @@ fn test_message_new() {
     assert_eq!(msg.id, "msg_id");
     assert_eq!(msg.sender, "sender_uid");
     assert_eq!(msg.recipient, "recipient_uid");
-    assert_eq!(msg.content, vec![1, 2, 3]);
+    assert_eq!(msg.content.to_vec(), vec![1, 2, 3]);
     assert_eq!(msg.timestamp, 1000);
     assert!(!msg.delivered);
     assert_eq!(msg.delivery_status, DeliveryStatus::Sent);
@@ -370,3 +373,262 @@ fn test_message_backward_compatibility() {
     assert_eq!(msg.next_retry_at, None);
     assert_eq!(msg.attempts, 0);
 }
+
+// Message Metadata Tests
+
+fn test_metadata() -> MessageMetadata {
+    let mut metadata = MessageMetadata::new();
+    metadata.insert("ticket".to_string(), MetadataValue::from("T-42"));
+    metadata.insert("count".to_string(), MetadataValue::from(7i64));
+    metadata.insert("ratio".to_string(), MetadataValue::from(0.5));
+    metadata.insert("done".to_string(), MetadataValue::from(false));
+    metadata
+}
+
+#[test]
+fn test_message_with_metadata() {
+    let message = Message::new("id".to_string(), "a".to_string(), "b".to_string(), vec![], 0);
+    assert!(!message.has_metadata());
+
+    let message = message.with_metadata(test_metadata()).expect("Valid metadata rejected");
+    assert!(message.has_metadata());
+    assert_eq!(
+        message.metadata_lines(),
+        vec!["count: 7", "done: false", "ratio: 0.5", "ticket: T-42"]
+    );
+}
+
+#[test]
+fn test_metadata_validation_rejects_nonconforming() {
+    let mut metadata = MessageMetadata::new();
+    metadata.insert(String::new(), MetadataValue::from(1i64));
+    assert!(matches!(validate_metadata(&metadata), Err(crate::Error::InvalidMetadata(_))));
+
+    let mut metadata = MessageMetadata::new();
+    metadata.insert("nan".to_string(), MetadataValue::from(f64::NAN));
+    assert!(matches!(validate_metadata(&metadata), Err(crate::Error::InvalidMetadata(_))));
+
+    // Nested values are not representable
+    let nested = r#"{"location": {"lat": 1.0}}"#;
+    assert!(serde_json::from_str::<MessageMetadata>(nested).is_err());
+    let list = r#"{"tags": ["a", "b"]}"#;
+    assert!(serde_json::from_str::<MessageMetadata>(list).is_err());
+}
+
+#[test]
+fn test_metadata_validation_size_limit() {
+    let mut metadata = MessageMetadata::new();
+    metadata.insert("blob".to_string(), MetadataValue::from("x".repeat(MAX_METADATA_BYTES - 16)));
+    assert!(validate_metadata(&metadata).is_ok());
+
+    metadata.insert("blob".to_string(), MetadataValue::from("x".repeat(MAX_METADATA_BYTES)));
+    let err = validate_metadata(&metadata).unwrap_err();
+    assert!(err.to_string().contains("byte limit"));
+
+    let message = Message::new("id".to_string(), "a".to_string(), "b".to_string(), vec![], 0);
+    assert!(message.with_metadata(metadata).is_err());
+}
+
+#[test]
+fn test_message_metadata_serialization() {
+    // Empty metadata is omitted, so messages without it serialize as before
+    let message = Message::new("id".to_string(), "a".to_string(), "b".to_string(), vec![], 0);
+    let json = serde_json::to_string(&message).unwrap();
+    assert!(!json.contains("metadata"));
+
+    let message = message.with_metadata(test_metadata()).unwrap();
+    let json = serde_json::to_string(&message).unwrap();
+    let restored: Message = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.metadata, test_metadata());
+
+    let cbor = serde_cbor::to_vec(&message).unwrap();
+    let restored: Message = serde_cbor::from_slice(&cbor).unwrap();
+    assert_eq!(restored.metadata, test_metadata());
+}
+
+fn chat_with_messages(count: usize) -> Chat {
+    let mut chat = Chat::new("alice".to_string());
+    for i in 0..count {
+        chat.append_message(Message::new(
+            format!("msg_{}", i),
+            "alice".to_string(),
+            "self".to_string(),
+            format!("message {}", i).into_bytes(),
+            i as i64,
+        ));
+    }
+    chat
+}
+
+#[test]
+fn test_chat_toggle_pin_cap() {
+    let mut chat = chat_with_messages(MAX_PINNED_PER_CHAT + 1);
+
+    for i in 0..MAX_PINNED_PER_CHAT {
+        assert!(chat.toggle_pin(&format!("msg_{}", i)).unwrap());
+    }
+    assert_eq!(chat.pinned_messages().len(), MAX_PINNED_PER_CHAT);
+
+    // One over the cap is refused and leaves the message unpinned
+    let last = format!("msg_{}", MAX_PINNED_PER_CHAT);
+    let err = chat.toggle_pin(&last).unwrap_err();
+    assert!(err.to_string().contains("pinned messages per chat"));
+    assert!(!chat.messages[MAX_PINNED_PER_CHAT].pinned);
+
+    // Unpinning frees a slot
+    assert!(!chat.toggle_pin("msg_0").unwrap());
+    assert!(chat.toggle_pin(&last).unwrap());
+    assert_eq!(chat.pinned_messages()[0].id, "msg_1");
+}
+
+#[test]
+fn test_chat_toggle_star() {
+    let mut chat = chat_with_messages(3);
+    assert!(chat.starred_messages().is_empty());
+
+    assert!(chat.toggle_star("msg_2").unwrap());
+    assert!(chat.toggle_star("msg_0").unwrap());
+    let starred: Vec<&str> = chat.starred_messages().iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(starred, vec!["msg_0", "msg_2"]);
+
+    // Stars are not capped and do not affect pins
+    assert!(!chat.toggle_star("msg_0").unwrap());
+    assert!(chat.pinned_messages().is_empty());
+}
+
+#[test]
+fn test_chat_annotations_unknown_message() {
+    let mut chat = chat_with_messages(1);
+    assert!(chat.toggle_pin("missing").is_err());
+    assert!(chat.toggle_star("missing").is_err());
+}
+
+#[test]
+fn test_message_preview_and_annotation_serialization() {
+    let message = Message::new("id".to_string(), "a".to_string(), "b".to_string(), b"first line\nsecond".to_vec(), 0);
+    assert_eq!(message.preview(50), "first line");
+    assert_eq!(message.preview(6), "first…");
+
+    let binary = Message::new("id".to_string(), "a".to_string(), "b".to_string(), vec![0xff, 0xfe], 0);
+    assert_eq!(binary.preview(50), "[binary, 2 bytes]");
+
+    // Unset flags are omitted, so older snapshots still deserialize
+    let json = serde_json::to_string(&message).unwrap();
+    assert!(!json.contains("pinned") && !json.contains("starred"));
+
+    let mut annotated = message.clone();
+    annotated.pinned = true;
+    annotated.starred = true;
+    let restored: Message = serde_json::from_str(&serde_json::to_string(&annotated).unwrap()).unwrap();
+    assert!(restored.pinned && restored.starred);
+}
+
+#[test]
+fn test_sanitize_text_strips_control_characters() {
+    // Newline and tab survive; CR becomes a newline
+    assert_eq!(sanitize_text("a\tb\nc"), "a\tb\nc");
+    assert_eq!(sanitize_text("one\r\ntwo\rthree"), "one\ntwo\nthree");
+
+    // ESC, BEL, NUL, DEL and C1 controls are dropped; the rest of a sequence stays as text
+    assert_eq!(sanitize_text("\u{1b}[31mred\u{1b}[0m"), "[31mred[0m");
+    assert_eq!(sanitize_text("\u{1b}]0;title\u{7}x"), "]0;titlex");
+    assert_eq!(sanitize_text("a\u{0}b\u{7f}c\u{9b}d"), "abcd");
+
+    // Pasted replacement characters and other text are untouched
+    assert_eq!(sanitize_text("caf\u{e9} \u{fffd} \u{1f44b}"), "caf\u{e9} \u{fffd} \u{1f44b}");
+}
+
+#[test]
+fn test_preview_keeps_graphemes_whole() {
+    // e + combining acute, a family emoji joined with ZWJs, a thumbs up with a skin tone
+    let text = "e\u{301}\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\u{1f44d}\u{1f3fd}x";
+    assert_eq!(
+        graphemes(text),
+        vec!["e\u{301}", "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}", "\u{1f44d}\u{1f3fd}", "x"]
+    );
+
+    let message = Message::new("m".to_string(), "a".to_string(), "b".to_string(), text.as_bytes().to_vec(), 1);
+    assert_eq!(message.preview(3), "e\u{301}\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}…");
+    assert_eq!(message.preview(4), text);
+
+    let escaped = Message::new("m".to_string(), "a".to_string(), "b".to_string(), b"\x1b[2Jhi".to_vec(), 1);
+    assert_eq!(escaped.display_text(), "[2Jhi");
+}
+
+/// Message `n` of a chat, timestamped `n`
+fn numbered(n: i64) -> Message {
+    Message::new(format!("m{}", n), "peer".to_string(), "me".to_string(), b"hi".to_vec(), n)
+}
+
+/// IDs of the non-notice messages, oldest first
+fn kept_ids(chat: &Chat) -> Vec<String> {
+    chat.messages.iter().filter(|m| !m.is_system()).map(|m| m.id.clone()).collect()
+}
+
+#[test]
+fn test_history_limit_trims_on_insert_at_boundary() {
+    let mut chat = Chat::new("peer".to_string());
+    for n in 1..=3 {
+        assert_eq!(chat.append_with_limit(numbered(n), 3), 0);
+    }
+    assert!(!chat.is_trimmed());
+
+    // One over the limit drops exactly the oldest
+    assert_eq!(chat.append_with_limit(numbered(4), 3), 1);
+    assert_eq!(kept_ids(&chat), vec!["m2", "m3", "m4"]);
+    assert_eq!(chat.trimmed_before, Some(2));
+
+    // 0 means unlimited
+    for n in 5..=10 {
+        assert_eq!(chat.append_with_limit(numbered(n), 0), 0);
+    }
+    assert_eq!(kept_ids(&chat).len(), 9);
+}
+
+#[test]
+fn test_history_limit_keeps_pinned_and_notes_once() {
+    let mut chat = Chat::new("peer".to_string());
+    for n in 1..=3 {
+        chat.append_message(numbered(n));
+    }
+    chat.toggle_pin("m1").unwrap();
+
+    chat.append_with_limit(numbered(4), 2);
+    assert_eq!(kept_ids(&chat), vec!["m1", "m3", "m4"]);
+
+    // The notice is recorded on the first trim only, ahead of what is kept
+    chat.append_with_limit(numbered(5), 2);
+    chat.append_with_limit(numbered(6), 2);
+    assert_eq!(kept_ids(&chat), vec!["m1", "m5", "m6"]);
+    let notices: Vec<&Message> = chat.messages.iter().filter(|m| m.is_system()).collect();
+    assert_eq!(notices.len(), 1);
+    assert_eq!(notices[0].display_text(), HISTORY_TRIMMED_NOTICE);
+    assert!(notices[0].timestamp < 3);
+    assert_eq!(chat.trimmed_before, Some(5));
+}
+
+#[test]
+fn test_history_limit_chat_override_wins() {
+    let mut chat = Chat::new("peer".to_string());
+    assert_eq!(chat.effective_history_limit(2000), 2000);
+    chat.history_limit = Some(2);
+    assert_eq!(chat.effective_history_limit(2000), 2);
+    for n in 1..=3 {
+        chat.append_with_limit(numbered(n), 2000);
+    }
+    assert_eq!(kept_ids(&chat), vec!["m2", "m3"]);
+
+    // An unlimited override beats a small global limit
+    chat.history_limit = Some(0);
+    chat.append_with_limit(numbered(4), 1);
+    assert_eq!(kept_ids(&chat), vec!["m2", "m3", "m4"]);
+
+    // Cycling walks the presets and back to the global setting
+    chat.history_limit = None;
+    for preset in HISTORY_LIMIT_PRESETS {
+        chat.cycle_history_limit();
+        assert_eq!(chat.history_limit, Some(preset));
+    }
+    chat.cycle_history_limit();
+    assert_eq!(chat.history_limit, None);
+}
@@ -189,3 +189,240 @@ fn test_contact_multiple_activate_deactivate() {
     contact.deactivate(); // Double deactivate should be idempotent
     assert!(!contact.is_active);
 }
+
+#[test]
+fn test_contact_notes_default_empty() {
+    let expiry = Utc::now() + Duration::days(30);
+    let contact = Contact::new(
+        "test_uid".to_string(),
+        "127.0.0.1:8080".to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        expiry,
+    );
+
+    assert!(contact.notes.is_empty());
+}
+
+#[test]
+fn test_contact_set_notes_size_cap() {
+    let expiry = Utc::now() + Duration::days(30);
+    let mut contact = Contact::new(
+        "test_uid".to_string(),
+        "127.0.0.1:8080".to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        expiry,
+    );
+
+    // Exactly at the limit is accepted
+    let max_notes = "a".repeat(MAX_CONTACT_NOTES_BYTES);
+    contact.set_notes(&max_notes).expect("Notes at the limit should be accepted");
+    assert_eq!(contact.notes.len(), MAX_CONTACT_NOTES_BYTES);
+
+    // One byte over is rejected and leaves existing notes untouched
+    let too_large = "b".repeat(MAX_CONTACT_NOTES_BYTES + 1);
+    assert!(contact.set_notes(&too_large).is_err());
+    assert_eq!(contact.notes, max_notes);
+
+    // Limit is in bytes, not characters
+    let multibyte = "é".repeat(MAX_CONTACT_NOTES_BYTES / 2 + 1);
+    assert!(contact.set_notes(&multibyte).is_err());
+}
+
+#[test]
+fn test_contact_notes_preview() {
+    let expiry = Utc::now() + Duration::days(30);
+    let mut contact = Contact::new(
+        "test_uid".to_string(),
+        "127.0.0.1:8080".to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        expiry,
+    );
+
+    contact.set_notes("line one\nline two").unwrap();
+    assert_eq!(contact.notes_preview(2), ("line one\nline two".to_string(), false));
+
+    contact.set_notes("line one\nline two\nline three").unwrap();
+    assert_eq!(contact.notes_preview(2), ("line one\nline two".to_string(), true));
+}
+
+#[test]
+fn test_contact_notes_included_in_serialization() {
+    let expiry = Utc::now() + Duration::days(30);
+    let mut contact = Contact::new(
+        "test_uid".to_string(),
+        "127.0.0.1:8080".to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        expiry,
+    );
+    contact.set_notes("met at conference").unwrap();
+
+    let json = serde_json::to_string(&contact).expect("Failed to serialize");
+    assert!(json.contains("met at conference"));
+
+    let restored: Contact = serde_json::from_str(&json).expect("Failed to deserialize");
+    assert_eq!(restored.notes, "met at conference");
+
+    // Older serialized contacts without notes still load
+    let legacy = json.replace(",\"notes\":\"met at conference\"", "");
+    let restored: Contact = serde_json::from_str(&legacy).expect("Failed to deserialize legacy contact");
+    assert!(restored.notes.is_empty());
+}
+
+#[test]
+fn test_contact_notes_excluded_from_token() {
+    let keypair = crate::crypto::KeyPair::generate().expect("Failed to generate keypair");
+    let expiry = Utc::now() + Duration::days(30);
+    let mut contact = Contact::new(
+        keypair.uid.to_string(),
+        "127.0.0.1:8080".to_string(),
+        keypair.public_key.clone(),
+        keypair.x25519_public.clone(),
+        expiry,
+    );
+    contact.set_notes("private annotation").unwrap();
+
+    let token = contact.sign_token(&keypair).expect("Failed to sign token");
+    let parsed = parse_contact_token(&token).expect("Failed to parse token");
+
+    assert!(parsed.notes.is_empty(), "Notes must never be transmitted in tokens");
+}
+
+#[test]
+fn test_contact_notes_persistence_roundtrip() {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+    let expiry = Utc::now() + Duration::days(30);
+    let mut contact = Contact::new(
+        "notes_uid".to_string(),
+        "127.0.0.1:8080".to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        expiry,
+    );
+
+    // Create
+    contact.set_notes("first line\nsecond line").unwrap();
+    storage.save_contact(&contact).expect("Failed to save contact");
+    let loaded = storage.load_contacts().expect("Failed to load contacts");
+    assert_eq!(loaded[0].notes, "first line\nsecond line");
+
+    // Update
+    contact.set_notes("updated").unwrap();
+    storage.save_contact(&contact).expect("Failed to save contact");
+    let loaded = storage.load_contacts().expect("Failed to load contacts");
+    assert_eq!(loaded[0].notes, "updated");
+
+    // Clear
+    contact.set_notes("").unwrap();
+    storage.save_contact(&contact).expect("Failed to save contact");
+    let loaded = storage.load_contacts().expect("Failed to load contacts");
+    assert!(loaded[0].notes.is_empty());
+}
+
+#[test]
+fn test_contact_notes_column_added_to_existing_database() {
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let db_path = temp_dir.path().join("legacy.db");
+
+    // Simulate a database created before contact notes existed
+    {
+        let conn = rusqlite::Connection::open(&db_path).expect("Failed to open database");
+        conn.execute(
+            "CREATE TABLE contacts (
+                uid TEXT PRIMARY KEY,
+                ip TEXT NOT NULL,
+                pubkey BLOB NOT NULL,
+                x25519_pubkey BLOB NOT NULL,
+                expiry INTEGER NOT NULL,
+                is_active INTEGER NOT NULL
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO contacts VALUES ('legacy_uid', '127.0.0.1:8080', X'01', X'02', 4102444800, 1)",
+            [],
+        ).unwrap();
+    }
+
+    let storage = Storage::new(&db_path).expect("Failed to open legacy database");
+    let contacts = storage.load_contacts().expect("Failed to load contacts");
+    assert_eq!(contacts.len(), 1);
+    assert!(contacts[0].notes.is_empty());
+}
+
+#[test]
+fn test_retained_contact_notes() {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+
+    assert!(storage.take_retained_contact_notes("uid").unwrap().is_none());
+
+    storage.retain_contact_notes("uid", "kept note").unwrap();
+    assert_eq!(storage.take_retained_contact_notes("uid").unwrap(), Some("kept note".to_string()));
+
+    // Taking removes the retained notes
+    assert!(storage.take_retained_contact_notes("uid").unwrap().is_none());
+}
+
+#[test]
+fn test_contact_delivery_addresses() {
+    let mut contact = Contact::new(
+        "uid".to_string(),
+        "10.8.0.2:8080".to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        Utc::now() + Duration::days(1),
+    );
+
+    // Single-endpoint contacts use their ip
+    assert_eq!(contact.delivery_addresses(None), vec!["10.8.0.2:8080".to_string()]);
+
+    let mut expired = ContactEndpoint::new("192.0.2.1:8080", "old");
+    expired.valid_until = Some(Utc::now() - Duration::hours(1));
+    contact.endpoints = vec![
+        ContactEndpoint::new("10.8.0.2:8080", "vpn"),
+        expired,
+        ContactEndpoint::new("203.0.113.7:8080", "home"),
+    ];
+
+    // Advertised order, expired endpoints skipped
+    assert_eq!(
+        contact.delivery_addresses(None),
+        vec!["10.8.0.2:8080".to_string(), "203.0.113.7:8080".to_string()]
+    );
+
+    // The endpoint that last worked goes first
+    assert_eq!(
+        contact.delivery_addresses(Some("203.0.113.7:8080")),
+        vec!["203.0.113.7:8080".to_string(), "10.8.0.2:8080".to_string()]
+    );
+
+    // Unknown or expired preferences are ignored
+    assert_eq!(contact.delivery_addresses(Some("192.0.2.1:8080"))[0], "10.8.0.2:8080");
+}
+
+#[test]
+fn test_contact_endpoints_persistence_roundtrip() {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+    let mut contact = Contact::new(
+        "endpoints_uid".to_string(),
+        "10.8.0.2:8080".to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        Utc::now() + Duration::days(30),
+    );
+
+    // Single-endpoint contacts store no list
+    storage.save_contact(&contact).expect("Failed to save contact");
+    assert!(storage.load_contacts().unwrap()[0].endpoints.is_empty());
+
+    let mut home = ContactEndpoint::new("203.0.113.7:8080", "home");
+    home.valid_until = Some(chrono::DateTime::from_timestamp(Utc::now().timestamp() + 3600, 0).unwrap());
+    contact.endpoints = vec![ContactEndpoint::new("10.8.0.2:8080", "vpn"), home];
+    storage.save_contact(&contact).expect("Failed to save contact");
+
+    let loaded = storage.load_contacts().expect("Failed to load contacts");
+    assert_eq!(loaded[0].endpoints, contact.endpoints);
+}
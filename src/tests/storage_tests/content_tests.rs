@@ -0,0 +1,62 @@
+// Content Tests - Testing binary content detection, placeholders and downloads
+
+use crate::storage::{create_download_file, download_file_name, format_size, ContentKind, Message};
+
+#[test]
+fn test_content_kind_magic_bytes() {
+    let cases: &[(&[u8], ContentKind, Option<&str>)] = &[
+        (b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR", ContentKind::Png, Some("image/png")),
+        (b"\xff\xd8\xff\xe0\x00\x10JFIF", ContentKind::Jpeg, Some("image/jpeg")),
+        (b"%PDF-1.7\n%\xe2\xe3\xcf\xd3", ContentKind::Pdf, Some("application/pdf")),
+        (b"%PDF-1.4 plain ascii", ContentKind::Pdf, Some("application/pdf")),
+        (b"PK\x03\x04\x14\x00", ContentKind::Zip, Some("application/zip")),
+        (b"PK\x05\x06", ContentKind::Zip, Some("application/zip")),
+        (b"hello", ContentKind::Text, None),
+        (b"", ContentKind::Text, None),
+        (b"\x89PN", ContentKind::Binary, None),
+        (b"\x00\xff\xfe", ContentKind::Binary, None),
+    ];
+    for (content, kind, mime) in cases {
+        assert_eq!(ContentKind::detect(content), *kind, "{:?}", content);
+        assert_eq!(kind.mime(), *mime);
+    }
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png.resize(340 * 1024, 0);
+    let message = Message::new("m".to_string(), "a".to_string(), "b".to_string(), png, 0);
+    assert_eq!(message.display_text(), "[image/png, 340 KB]");
+}
+
+#[test]
+fn test_unknown_binary_fallback_and_sizes() {
+    let message = Message::new("m".to_string(), "a".to_string(), "b".to_string(), vec![0xff; 12], 0);
+    assert_eq!(message.content_kind(), ContentKind::Binary);
+    assert_eq!(message.display_text(), "[binary, 12 bytes]");
+    assert_eq!(download_file_name(&message.id, message.content_kind()), "pure2p-m.bin");
+
+    assert_eq!(format_size(1), "1 byte");
+    assert_eq!(format_size(1023), "1023 bytes");
+    assert_eq!(format_size(1024), "1 KB");
+    assert_eq!(format_size(1536), "2 KB");
+    assert_eq!(format_size(5 * 1024 * 1024 / 2), "2.5 MB");
+}
+
+#[test]
+fn test_download_file_names_and_collisions() {
+    // Peer-chosen IDs cannot escape the downloads directory
+    assert_eq!(download_file_name("../../etc/passwd", ContentKind::Pdf), "pure2p-etcpasswd.pdf");
+    assert_eq!(download_file_name("/\\:*?", ContentKind::Png), "pure2p-message.png");
+    assert_eq!(
+        download_file_name("5f0c2e7a-91b4-4c1e-8d2a-0b6f3c9e1d77", ContentKind::Jpeg),
+        "pure2p-5f0c2e7a-91b.jpg"
+    );
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().join("downloads");
+    let (first, _) = create_download_file(&dir, "pure2p-abc.png").unwrap();
+    let (second, _) = create_download_file(&dir, "pure2p-abc.png").unwrap();
+    let (third, _) = create_download_file(&dir, "pure2p-abc.png").unwrap();
+    assert_eq!(first, dir.join("pure2p-abc.png"));
+    assert_eq!(second, dir.join("pure2p-abc-1.png"));
+    assert_eq!(third, dir.join("pure2p-abc-2.png"));
+}
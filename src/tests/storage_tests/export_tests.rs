@@ -0,0 +1,160 @@
+// Export Tests - JSON Lines chat export: golden output, streaming, schema version
+
+use crate::storage::{
+    Chat, Contact, ContentMode, ExportDirection, ExportedMessage, JsonlExportOptions, Message, MetadataValue, Protection,
+    Storage, EXPORT_PAGE_SIZE, JSONL_EXPORT_VERSION,
+};
+use chrono::{Duration, Utc};
+use std::io::{self, Write};
+
+/// Chat with alice: incoming sealed text, outgoing text, incoming binary, a system notice
+fn seeded_chat() -> Chat {
+    let mut chat = Chat::new("alice".to_string());
+    let mut hi = Message::new("m1".to_string(), "alice".to_string(), "me".to_string(), b"hi".to_vec(), 1000);
+    hi.metadata.insert("lang".to_string(), MetadataValue::Text("en".to_string()));
+    hi.protection = Some(Protection::Sealed);
+    chat.append_message(hi);
+    chat.append_message(Message::new("m2".to_string(), "me".to_string(), "alice".to_string(), b"hello".to_vec(), 2000));
+    chat.append_message(Message::new("m3".to_string(), "alice".to_string(), "me".to_string(), vec![0, 255, 254], 3000));
+    let mut notice = Message::system("older messages were removed", 4000);
+    notice.id = "m4".to_string();
+    chat.append_message(notice);
+    chat
+}
+
+/// Storage holding `chat` with alice
+fn storage_with(chat: &Chat) -> Storage {
+    let storage = Storage::new_in_memory().unwrap();
+    storage
+        .save_contact(&Contact::new(
+            "alice".to_string(),
+            "192.168.1.100:8080".to_string(),
+            vec![0; 32],
+            vec![0; 32],
+            Utc::now() + Duration::days(30),
+        ))
+        .unwrap();
+    storage.save_chat(chat).unwrap();
+    storage
+}
+
+fn export(storage: &Storage, options: &JsonlExportOptions) -> String {
+    let mut out = Vec::new();
+    storage.export_chat_jsonl("alice", &mut out, options).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+const HI_INLINE: &str = r#"{"version":1,"id":"m1","direction":"incoming","sent_at":1000,"received_at":null,"delivery_status":"sent","reply_to":null,"reactions":[],"metadata":{"lang":"en"},"content_type":"text","content_bytes":2,"content":"hi","content_sha256":null,"protection":"sealed"}"#;
+const HELLO_INLINE: &str = r#"{"version":1,"id":"m2","direction":"outgoing","sent_at":2000,"received_at":null,"delivery_status":"sent","reply_to":null,"reactions":[],"metadata":{},"content_type":"text","content_bytes":5,"content":"hello","content_sha256":null,"protection":null}"#;
+const BINARY_REFERENCE: &str = r#"{"version":1,"id":"m3","direction":"incoming","sent_at":3000,"received_at":null,"delivery_status":"sent","reply_to":null,"reactions":[],"metadata":{},"content_type":"binary","content_bytes":3,"content":null,"content_sha256":"d590f90f7944340fb253f0c59cb89fd41d4ec255ff246f524f8f7c94f0a233e5","protection":null}"#;
+const NOTICE_INLINE: &str = r#"{"version":1,"id":"m4","direction":"system","sent_at":4000,"received_at":null,"delivery_status":"sent","reply_to":null,"reactions":[],"metadata":{},"content_type":"text","content_bytes":27,"content":"older messages were removed","content_sha256":null,"protection":null}"#;
+
+#[test]
+fn test_jsonl_export_golden_output() {
+    let storage = storage_with(&seeded_chat());
+    let lines = |lines: &[&str]| lines.iter().map(|line| format!("{}\n", line)).collect::<String>();
+
+    // Defaults: everything, text inline, binary by hash
+    assert_eq!(
+        export(&storage, &JsonlExportOptions::default()),
+        lines(&[HI_INLINE, HELLO_INLINE, BINARY_REFERENCE, NOTICE_INLINE])
+    );
+
+    // Reference mode hashes text as well
+    let hello_reference = HELLO_INLINE.replace(
+        r#""content":"hello","content_sha256":null"#,
+        r#""content":null,"content_sha256":"2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824""#,
+    );
+    let options = JsonlExportOptions {
+        direction: Some(ExportDirection::Outgoing),
+        content: ContentMode::Reference,
+        ..Default::default()
+    };
+    assert_eq!(export(&storage, &options), lines(&[&hello_reference]));
+
+    // Omit mode keeps only the metadata, for incoming messages in range
+    let options = JsonlExportOptions {
+        since: Some(1000),
+        until: Some(3000),
+        direction: Some(ExportDirection::Incoming),
+        content: ContentMode::Omit,
+    };
+    let omitted = |line: &str, content: &str| line.replace(content, r#""content":null,"content_sha256":null"#);
+    assert_eq!(
+        export(&storage, &options),
+        lines(&[
+            &omitted(HI_INLINE, r#""content":"hi","content_sha256":null"#),
+            &omitted(
+                BINARY_REFERENCE,
+                r#""content":null,"content_sha256":"d590f90f7944340fb253f0c59cb89fd41d4ec255ff246f524f8f7c94f0a233e5""#
+            ),
+        ])
+    );
+
+    // Date range bounds are inclusive
+    let options = JsonlExportOptions { since: Some(2000), until: Some(2000), ..Default::default() };
+    assert_eq!(export(&storage, &options), lines(&[HELLO_INLINE]));
+    let options = JsonlExportOptions { since: Some(5000), ..Default::default() };
+    assert_eq!(export(&storage, &options), "");
+}
+
+/// Writer that counts bytes and remembers its largest single write
+#[derive(Default)]
+struct CountingWriter {
+    bytes: usize,
+    lines: usize,
+    largest_write: usize,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes += buf.len();
+        self.lines += buf.iter().filter(|b| **b == b'\n').count();
+        self.largest_write = self.largest_write.max(buf.len());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_jsonl_export_streams_long_chat() {
+    let mut chat = seeded_chat();
+    for n in 0..10_000 {
+        chat.append_message(Message::new(
+            format!("bulk-{:05}", n),
+            "me".to_string(),
+            "alice".to_string(),
+            format!("message number {}", n).into_bytes(),
+            10_000 + n,
+        ));
+    }
+    let storage = storage_with(&chat);
+    let total = 10_004;
+
+    let queries_before = storage.message_query_count();
+    let mut writer = CountingWriter::default();
+    let written = storage.export_chat_jsonl("alice", &mut writer, &JsonlExportOptions::default()).unwrap();
+
+    assert_eq!(written, total);
+    assert_eq!(writer.lines, total);
+    // Read a page at a time, and written a line (or less) at a time
+    assert_eq!(storage.message_query_count() - queries_before, total.div_ceil(EXPORT_PAGE_SIZE) as u64);
+    assert!(writer.largest_write < 512, "largest write was {} bytes", writer.largest_write);
+    assert!(writer.bytes > total * 100);
+}
+
+#[test]
+fn test_jsonl_export_schema_version_on_every_line() {
+    let storage = storage_with(&seeded_chat());
+    let output = export(&storage, &JsonlExportOptions::default());
+
+    for line in output.lines() {
+        assert!(line.starts_with(&format!("{{\"version\":{},", JSONL_EXPORT_VERSION)), "{}", line);
+        let parsed: ExportedMessage = serde_json::from_str(line).unwrap();
+        assert_eq!(parsed.version, JSONL_EXPORT_VERSION);
+    }
+    assert_eq!(output.lines().count(), 4);
+}
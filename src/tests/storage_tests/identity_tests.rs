@@ -0,0 +1,184 @@
+// Identity Tests - Testing UID/key verification, conflict detection and the startup scan
+
+use crate::crypto::KeyPair;
+use crate::storage::{
+    check_incoming_contact, verify_contact_uid, AppState, Chat, ConflictKind, Contact, ContactIngest,
+    IdentityCheck, Message, Storage,
+};
+use chrono::{Duration, Utc};
+
+/// Contact whose UID is derived from its key, as a parsed token would be
+fn contact_for(keypair: &KeyPair, ip: &str) -> Contact {
+    Contact::new(
+        keypair.uid.to_string(),
+        ip.to_string(),
+        keypair.public_key.clone(),
+        keypair.x25519_public.clone(),
+        Utc::now() + Duration::days(30),
+    )
+}
+
+fn chat_with_message(contact_uid: &str, id: &str, timestamp: i64) -> Chat {
+    let mut chat = Chat::new(contact_uid.to_string());
+    chat.append_message(Message::new(
+        id.to_string(),
+        contact_uid.to_string(),
+        "me".to_string(),
+        id.as_bytes().to_vec(),
+        timestamp,
+    ));
+    chat
+}
+
+#[test]
+fn test_verify_contact_uid() {
+    let keypair = KeyPair::generate().unwrap();
+    let mut contact = contact_for(&keypair, "192.168.1.10:8080");
+    assert!(verify_contact_uid(&contact).is_ok());
+
+    contact.uid = "f".repeat(32);
+    let err = verify_contact_uid(&contact).unwrap_err();
+    assert!(matches!(err, crate::Error::IdentityMismatch(_)));
+    assert!(err.to_string().contains(&keypair.uid.to_string()));
+}
+
+#[test]
+fn test_check_incoming_contact() {
+    let alice = KeyPair::generate().unwrap();
+    let bob = KeyPair::generate().unwrap();
+    let stored = vec![contact_for(&alice, "192.168.1.10:8080")];
+
+    // New and known identities
+    let incoming = contact_for(&bob, "192.168.1.20:8080");
+    assert!(matches!(check_incoming_contact(&stored, &incoming).unwrap(), IdentityCheck::New));
+    let moved = contact_for(&alice, "10.0.0.5:9000");
+    assert!(matches!(check_incoming_contact(&stored, &moved).unwrap(), IdentityCheck::Known(0)));
+
+    // The key stored under another UID, or the UID stored with another key
+    let mut legacy = stored.clone();
+    legacy[0].uid = "legacy_uid".to_string();
+    match check_incoming_contact(&legacy, &moved).unwrap() {
+        IdentityCheck::Conflict(conflict) => {
+            assert_eq!(conflict.kind, ConflictKind::DuplicateKey);
+            assert_eq!(conflict.existing_uid, "legacy_uid");
+        }
+        other => panic!("expected conflict, got {:?}", other),
+    }
+    let mut renamed = stored.clone();
+    renamed[0].pubkey = vec![7u8; 32];
+    match check_incoming_contact(&renamed, &moved).unwrap() {
+        IdentityCheck::Conflict(conflict) => {
+            assert_eq!(conflict.kind, ConflictKind::KeyChanged);
+            assert_eq!(conflict.existing_uid, alice.uid.to_string());
+        }
+        other => panic!("expected conflict, got {:?}", other),
+    }
+
+    // The incoming contact itself must be consistent
+    let mut forged = incoming.clone();
+    forged.uid = alice.uid.to_string();
+    assert!(check_incoming_contact(&stored, &forged).is_err());
+}
+
+#[test]
+fn test_ingest_contact_updates_address_only() {
+    let alice = KeyPair::generate().unwrap();
+    let mut state = AppState::new();
+
+    let first = contact_for(&alice, "192.168.1.10:8080");
+    assert!(matches!(state.ingest_contact(first.clone()).unwrap(), ContactIngest::Added));
+    state.contacts[0].notes = "met at conference".to_string();
+
+    assert!(matches!(state.ingest_contact(first.clone()).unwrap(), ContactIngest::Unchanged));
+    // A later token for the same address only extends the expiry
+    let mut renewed = first.clone();
+    renewed.expiry = first.expiry + Duration::days(30);
+    assert!(matches!(state.ingest_contact(renewed.clone()).unwrap(), ContactIngest::Renewed));
+    assert_eq!(state.contacts[0].expiry, renewed.expiry);
+    assert!(matches!(
+        state.ingest_contact(contact_for(&alice, "10.0.0.5:9000")).unwrap(),
+        ContactIngest::AddressUpdated
+    ));
+
+    assert_eq!(state.contacts.len(), 1);
+    assert_eq!(state.contacts[0].ip, "10.0.0.5:9000");
+    assert_eq!(state.contacts[0].notes, "met at conference");
+    assert!(state.identity_conflicts.is_empty());
+}
+
+#[test]
+fn test_ingest_conflict_is_recorded_and_persisted() {
+    let alice = KeyPair::generate().unwrap();
+    let storage = Storage::new_in_memory().unwrap();
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().unwrap());
+
+    // A legacy row stores Alice's key under a made-up UID
+    let mut legacy = contact_for(&alice, "192.168.1.10:8080");
+    legacy.uid = "legacy_uid".to_string();
+    state.contacts.push(legacy);
+
+    for _ in 0..2 {
+        let outcome = state.ingest_contact(contact_for(&alice, "10.0.0.5:9000")).unwrap();
+        assert!(matches!(outcome, ContactIngest::Conflict(_)));
+    }
+    assert_eq!(state.contacts.len(), 1);
+    assert_eq!(state.identity_conflicts.len(), 1, "same conflict is recorded once");
+
+    state.save_to_db(&storage).unwrap();
+    let loaded = AppState::load_from_db(&storage).unwrap();
+    assert_eq!(loaded.identity_conflicts.len(), 1);
+    let conflict = &loaded.identity_conflicts[0];
+    assert_eq!(conflict.kind, ConflictKind::DuplicateKey);
+    assert_eq!(conflict.existing_uid, "legacy_uid");
+    assert_eq!(conflict.contact.uid, alice.uid.to_string());
+    assert_eq!(conflict.contact.ip, "10.0.0.5:9000");
+}
+
+#[test]
+fn test_identity_scan_merges_duplicate_keys() {
+    let alice = KeyPair::generate().unwrap();
+    let bob = KeyPair::generate().unwrap();
+    let storage = Storage::new_in_memory().unwrap();
+
+    // Seed a database written before identities were checked: Alice is
+    // stored twice, and Bob's row carries a UID that is not his
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().unwrap());
+    let alice_uid = alice.uid.to_string();
+    let mut alice_copy = contact_for(&alice, "10.0.0.5:9000");
+    alice_copy.uid = "alice_copy".to_string();
+    let mut bob_wrong = contact_for(&bob, "192.168.1.20:8080");
+    bob_wrong.uid = "bob_wrong".to_string();
+    state.contacts = vec![alice_copy, contact_for(&alice, "192.168.1.10:8080"), bob_wrong];
+    state.chats = vec![
+        chat_with_message("alice_copy", "m2", 2000),
+        chat_with_message(&alice_uid, "m1", 1000),
+        chat_with_message("bob_wrong", "m3", 3000),
+    ];
+    state.save_to_db(&storage).unwrap();
+
+    let report = storage.verify_integrity().unwrap();
+    assert!(report.problems.is_empty());
+    assert_eq!(report.conflicts.len(), 2);
+    assert!(!report.is_clean());
+
+    // The scan collapses the duplicate into the contact owning the key
+    let mut state = AppState::load_from_db(&storage).unwrap();
+    assert!(state.run_identity_scan(&storage).unwrap().is_some());
+    assert_eq!(state.contacts.len(), 2);
+    assert_eq!(state.chats.len(), 2);
+    assert_eq!(state.identity_conflicts.len(), 2);
+    let alice_chat = state.get_chat(&alice_uid).unwrap();
+    let ids: Vec<&str> = alice_chat.messages.iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(ids, ["m1", "m2"]);
+
+    // The database matches, and the scan does not run again
+    let reloaded = AppState::load_from_db(&storage).unwrap();
+    assert!(reloaded.contacts.iter().all(|c| c.uid != "alice_copy"));
+    assert!(reloaded.get_chat("alice_copy").is_none());
+    assert_eq!(reloaded.get_chat(&alice_uid).unwrap().messages.len(), 2);
+    assert_eq!(reloaded.identity_conflicts.len(), 2);
+    let mut state = reloaded;
+    assert!(state.run_identity_scan(&storage).unwrap().is_none());
+}
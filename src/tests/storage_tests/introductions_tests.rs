@@ -0,0 +1,206 @@
+// Introductions Tests - Pending introductions: CSV and vCard parsing with malformed rows and encoding quirks, fuzzy name matching, carry-over into contact notes, persistence
+
+use crate::storage::{
+    best_match, decode_text, name_similarity, parse_csv_introductions, parse_introduction_list, parse_vcard_introductions,
+    Contact, IntroductionImport, PendingIntroduction, Storage, FUZZY_MATCH_THRESHOLD, MAX_CONTACT_NOTES_BYTES,
+};
+use chrono::{Duration, TimeZone, Utc};
+use tempfile::TempDir;
+
+fn names(import: &IntroductionImport) -> Vec<&str> {
+    import.entries.iter().map(|entry| entry.name.as_str()).collect()
+}
+
+fn errors(import: &IntroductionImport) -> Vec<String> {
+    import.errors.iter().map(|error| error.to_string()).collect()
+}
+
+#[test]
+fn test_csv_with_header_and_malformed_rows() {
+    let csv = "Name,Given Name,Family Name,E-mail 1 - Value,Phone 1 - Value,Notes\n\
+               Alice Smith,,,alice@example.com,+1 555 0100,Met at the meetup\n\
+               \"Baker, Bob\",,,bob@example.com,,\"Says \"\"hi\"\"\nTwo lines\"\n\
+               ,Carol,Jones,carol@example.com,,\n\
+               ,,,nobody@example.com,,\n\
+               Dave,,,dave@example.com,,,extra\n\
+               \"Eve\" Online,,,,,\n\
+               \n\
+               \"Frank,,,frank@example.com,,\n\
+               Grace,,,grace@example.com,,\n";
+    let import = parse_csv_introductions(csv, Utc::now());
+
+    assert_eq!(names(&import), ["Alice Smith", "Baker, Bob", "Carol Jones", "Grace"]);
+    assert_eq!(import.entries[0].notes, "Email: alice@example.com\nPhone: +1 555 0100\nMet at the meetup");
+    assert_eq!(import.entries[1].notes, "Email: bob@example.com\nSays \"hi\"\nTwo lines");
+    assert_eq!(import.entries[2].notes, "Email: carol@example.com");
+
+    // Every unusable row is reported with its line; the unclosed quote spoils only its own
+    assert_eq!(
+        errors(&import),
+        [
+            "line 6: no name (for nobody@example.com)",
+            "line 7: 7 fields, the header has 6",
+            "line 8: text after the closing quote of field 1",
+            "line 10: quoted field is never closed",
+        ]
+    );
+}
+
+#[test]
+fn test_csv_encoding_quirks() {
+    // UTF-8 BOM, CRLF line ends, semicolons, accents
+    let bom = "\u{feff}Full Name;Email\r\nJosé Núñez;jose@example.com\r\nZoë;\r\n";
+    let import = parse_introduction_list(bom.as_bytes(), Utc::now());
+    assert_eq!(names(&import), ["José Núñez", "Zoë"]);
+    assert_eq!(import.entries[0].notes, "Email: jose@example.com");
+    assert!(import.errors.is_empty());
+
+    // Latin-1 bytes that are not UTF-8
+    let latin1 = b"Name\tPhone\nRen\xe9e M\xfcller\t030 1234\n";
+    let import = parse_introduction_list(latin1, Utc::now());
+    assert_eq!(names(&import), ["Renée Müller"]);
+    assert_eq!(import.entries[0].notes, "Phone: 030 1234");
+
+    // UTF-16 little endian with a BOM, as some address books export
+    let mut utf16 = vec![0xFF, 0xFE];
+    utf16.extend("Name,E-mail Address\r\nÅsa,asa@example.com\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+    let import = parse_introduction_list(&utf16, Utc::now());
+    assert_eq!(names(&import), ["Åsa"]);
+
+    let mut utf16_be = vec![0xFE, 0xFF];
+    utf16_be.extend("Åsa".encode_utf16().flat_map(u16::to_be_bytes));
+    assert_eq!(decode_text(&utf16_be), "Åsa");
+
+    // No header: the first column is the name, the rest are notes
+    let import = parse_csv_introductions("Alice,alice@example.com,friend\n\"Bob\"\n", Utc::now());
+    assert_eq!(names(&import), ["Alice", "Bob"]);
+    assert_eq!(import.entries[0].notes, "alice@example.com\nfriend");
+
+    // Empty files and blank lines give nothing
+    assert_eq!(parse_introduction_list(b"", Utc::now()), IntroductionImport::default());
+    assert_eq!(parse_introduction_list(b"\n\n,,\n", Utc::now()), IntroductionImport::default());
+}
+
+#[test]
+fn test_vcard_parsing_and_malformed_cards() {
+    let vcf = "BEGIN:VCARD\r\n\
+               VERSION:3.0\r\n\
+               FN:Alice\r\n  Smith\r\n\
+               N:Smith;Alice;;;\r\n\
+               ORG:Example\\, Inc.;Research\r\n\
+               item1.EMAIL;TYPE=INTERNET:alice@example.com\r\n\
+               TEL;TYPE=\"cell,voice\":tel:+15550100\r\n\
+               NOTE:Met at the meetup\\nbring cake\r\n\
+               END:VCARD\r\n\
+               BEGIN:VCARD\r\n\
+               VERSION:2.1\r\n\
+               N;CHARSET=UTF-8;ENCODING=QUOTED-PRINTABLE:M=C3=BCller;J=C3=\r\n\
+               =BCrgen\r\n\
+               NOTE;QUOTED-PRINTABLE;CHARSET=ISO-8859-1:Gr=FC=DFe\r\n\
+               END:VCARD\r\n\
+               BEGIN:VCARD\r\n\
+               FN:Never Ended\r\n\
+               BEGIN:VCARD\r\n\
+               EMAIL:only@example.com\r\n\
+               END:VCARD\r\n\
+               stray text\r\n\
+               BEGIN:VCARD\r\n\
+               FN:Broken\r\n\
+               this is not a property\r\n\
+               END:VCARD\r\n\
+               begin:vcard\r\n\
+               fn:Lower Case\r\n\
+               end:vcard\r\n\
+               BEGIN:VCARD\r\n\
+               FN:Cut Off\r\n";
+    let import = parse_introduction_list(vcf.as_bytes(), Utc::now());
+
+    assert_eq!(names(&import), ["Alice Smith", "Jürgen Müller", "Lower Case"]);
+    assert_eq!(
+        import.entries[0].notes,
+        "Organization: Example, Inc.\nEmail: alice@example.com\nPhone: +15550100\nMet at the meetup\nbring cake"
+    );
+    assert_eq!(import.entries[1].notes, "Grüße");
+    assert_eq!(
+        errors(&import),
+        [
+            "line 17: card has no END:VCARD",
+            "line 19: no name (for only@example.com)",
+            "line 22: text outside BEGIN:VCARD/END:VCARD",
+            "line 23: line 25 is not a property",
+            "line 30: card has no END:VCARD",
+        ]
+    );
+
+    // Not a vCard at all: read as CSV
+    assert!(parse_vcard_introductions("Name\nAlice\n", Utc::now()).entries.is_empty());
+}
+
+#[test]
+fn test_fuzzy_name_matching() {
+    assert_eq!(name_similarity("Alice Smith", "alice smith"), 1.0);
+    assert_eq!(name_similarity("Smith, Alice", "Alice Smith"), 1.0);
+    assert_eq!(name_similarity("Jose Nunez", "José Núñez"), 1.0);
+    assert_eq!(name_similarity("Alice", "Alice Smith"), 0.85);
+    assert!(name_similarity("Alise Smith", "Alice Smith") >= FUZZY_MATCH_THRESHOLD);
+    assert!(name_similarity("Bob", "Alice Smith") < FUZZY_MATCH_THRESHOLD);
+    assert_eq!(name_similarity("", "Alice"), 0.0);
+    assert_eq!(name_similarity("!!", "Alice"), 0.0);
+
+    let now = Utc::now();
+    let pending = [
+        PendingIntroduction::new("Alice Smith", "", now),
+        PendingIntroduction::new("Alicia Smithers", "", now),
+        PendingIntroduction::new("Bob Baker", "", now),
+    ];
+    assert_eq!(best_match("alice smith", &pending).map(|p| p.name.as_str()), Some("Alice Smith"));
+    assert_eq!(best_match("Smith Alice", &pending).map(|p| p.name.as_str()), Some("Alice Smith"));
+    assert_eq!(best_match("Alicia Smithers", &pending).map(|p| p.name.as_str()), Some("Alicia Smithers"));
+    assert_eq!(best_match("Bobby Baker", &pending).map(|p| p.name.as_str()), Some("Bob Baker"));
+    assert_eq!(best_match("Carol", &pending), None);
+    assert_eq!(best_match("Alice", &[]), None);
+}
+
+#[test]
+fn test_carry_over_into_contact_notes() {
+    let intro = PendingIntroduction::new("Alice Smith", "Email: alice@example.com", Utc::now());
+    let mut contact = Contact::new("uid".to_string(), "10.0.0.1:8080".to_string(), vec![0; 32], vec![0; 32], Utc::now());
+    intro.carry_over(&mut contact);
+    assert_eq!(contact.notes, "Name: Alice Smith\nEmail: alice@example.com");
+
+    // Notes already there stay first
+    contact.notes = "Restored notes".to_string();
+    PendingIntroduction::new("Alice", "", Utc::now()).carry_over(&mut contact);
+    assert_eq!(contact.notes, "Restored notes\n\nName: Alice");
+
+    // The result stays within the notes limit, on a character boundary
+    contact.notes = "é".repeat(MAX_CONTACT_NOTES_BYTES / 2);
+    intro.carry_over(&mut contact);
+    assert!(contact.notes.len() <= MAX_CONTACT_NOTES_BYTES);
+    assert!(contact.set_notes(&contact.notes.clone()).is_ok());
+}
+
+#[test]
+fn test_pending_introductions_persist() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("pure2p.db");
+    let earlier = Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap();
+    let alice = PendingIntroduction::new("Alice Smith", "Email: alice@example.com\nfriend", earlier + Duration::minutes(1));
+    let bob = PendingIntroduction::new("Bob", "", earlier);
+
+    let storage = Storage::new(&path).unwrap();
+    assert!(storage.load_pending_introductions().unwrap().is_empty());
+    storage.save_pending_introductions(&[alice.clone(), bob.clone()]).unwrap();
+    drop(storage);
+
+    // Oldest first, every field kept across a restart
+    let storage = Storage::new(&path).unwrap();
+    assert_eq!(storage.load_pending_introductions().unwrap(), [bob.clone(), alice.clone()]);
+
+    // Saving again replaces; deleting reports whether it existed
+    let renamed = PendingIntroduction { name: "Robert".to_string(), ..bob.clone() };
+    storage.save_pending_introductions(std::slice::from_ref(&renamed)).unwrap();
+    assert!(storage.delete_pending_introduction(&alice.id).unwrap());
+    assert!(!storage.delete_pending_introduction(&alice.id).unwrap());
+    assert_eq!(storage.load_pending_introductions().unwrap(), [renamed]);
+}
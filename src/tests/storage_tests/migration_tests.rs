@@ -0,0 +1,150 @@
+// Migration Tests - Testing the validated legacy app_state.json import
+
+use crate::crypto::KeyPair;
+use crate::storage::{migration, AppState, Chat, Contact, Message, MigrationReport, storage_db::Storage};
+use chrono::{Duration, Utc};
+use std::path::Path;
+use tempfile::TempDir;
+
+fn contact(uid: &str) -> Contact {
+    Contact::new(
+        uid.to_string(),
+        "10.0.0.1:8080".to_string(),
+        vec![1; 32],
+        vec![2; 32],
+        Utc::now() + Duration::days(30),
+    )
+}
+
+fn legacy_state() -> AppState {
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    state.contacts.push(contact("alice"));
+    state.contacts.push(contact("bob"));
+
+    let mut chat = Chat::new("alice".to_string());
+    for i in 0..3 {
+        chat.append_message(Message::new(format!("a{}", i), "alice".to_string(), "self".to_string(), vec![i], i as i64));
+    }
+    state.chats.push(chat);
+    state
+}
+
+/// Database already holding one contact, to show failed migrations leave it alone
+fn existing_db() -> Storage {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    state.contacts.push(contact("existing"));
+    state.save_to_db(&storage).expect("Failed to save");
+    storage
+}
+
+fn row_counts(storage: &Storage) -> (usize, usize, usize) {
+    let state = AppState::load_from_db(storage).expect("Failed to load");
+    let messages = state.chats.iter().map(|c| c.messages.len()).sum();
+    (state.contacts.len(), state.chats.len(), messages)
+}
+
+fn write_fixture(dir: &TempDir, contents: &str) -> std::path::PathBuf {
+    let path = dir.path().join("app_state.json");
+    std::fs::write(&path, contents).expect("Failed to write fixture");
+    path
+}
+
+fn assert_untouched(path: &Path, contents: &str) {
+    assert_eq!(std::fs::read_to_string(path).unwrap(), contents);
+    assert!(!migration::backup_path(path).exists(), "backup should be removed on failure");
+}
+
+#[test]
+fn test_migration_imports_valid_fixture() {
+    let dir = TempDir::new().unwrap();
+    let json = serde_json::to_string_pretty(&legacy_state()).unwrap();
+    let path = write_fixture(&dir, &json);
+    let storage = Storage::new_in_memory().unwrap();
+
+    let report = migration::migrate(&path, &storage).unwrap().expect("file exists");
+    assert_eq!(
+        report,
+        MigrationReport { has_identity: true, contacts: 2, chats: 1, messages: 3 }
+    );
+    assert_eq!(row_counts(&storage), (2, 1, 3));
+    assert!(AppState::load_from_db(&storage).unwrap().user_keypair.is_some());
+
+    // Original consumed, byte-identical copy kept
+    assert!(!path.exists());
+    assert_eq!(std::fs::read_to_string(migration::backup_path(&path)).unwrap(), json);
+}
+
+#[test]
+fn test_migration_truncated_fixture_rolls_back() {
+    let dir = TempDir::new().unwrap();
+    let json = serde_json::to_string_pretty(&legacy_state()).unwrap();
+    let truncated = &json[..json.len() / 2];
+    let path = write_fixture(&dir, truncated);
+    let storage = existing_db();
+
+    let err = migration::migrate(&path, &storage).unwrap_err().to_string();
+    assert!(err.contains("line"), "parse error should name the line: {}", err);
+    assert!(err.contains("truncated"), "unexpected error: {}", err);
+    assert_eq!(row_counts(&storage), (1, 0, 0));
+    assert_untouched(&path, truncated);
+
+    // Malformed (not just short) JSON reports its position too
+    let corrupt = "{\n  \"user_port\": 80,\n  oops\n}";
+    let path = write_fixture(&dir, corrupt);
+    let err = migration::migrate(&path, &storage).unwrap_err().to_string();
+    assert!(err.contains("line 3"), "unexpected error: {}", err);
+    assert_eq!(row_counts(&storage), (1, 0, 0));
+    assert_untouched(&path, corrupt);
+}
+
+#[test]
+fn test_migration_inconsistent_fixture_rolls_back() {
+    let dir = TempDir::new().unwrap();
+    let storage = existing_db();
+
+    // Chat without a contact
+    let mut state = legacy_state();
+    state.chats.push(Chat::new("mallory".to_string()));
+    let json = serde_json::to_string(&state).unwrap();
+    let path = write_fixture(&dir, &json);
+    let err = migration::migrate(&path, &storage).unwrap_err().to_string();
+    assert!(err.contains("chat mallory has no matching contact"), "unexpected error: {}", err);
+    assert_eq!(row_counts(&storage), (1, 0, 0));
+    assert_untouched(&path, &json);
+
+    // Duplicate message IDs would silently overwrite each other
+    let mut state = legacy_state();
+    let mut chat = Chat::new("bob".to_string());
+    chat.append_message(Message::new("a0".to_string(), "bob".to_string(), "self".to_string(), vec![], 0));
+    state.chats.push(chat);
+    let json = serde_json::to_string(&state).unwrap();
+    let path = write_fixture(&dir, &json);
+    let err = migration::migrate(&path, &storage).unwrap_err().to_string();
+    assert!(err.contains("message a0 appears more than once"), "unexpected error: {}", err);
+    assert_eq!(row_counts(&storage), (1, 0, 0));
+    assert_untouched(&path, &json);
+}
+
+#[test]
+fn test_migration_dry_run_reports_counts() {
+    let dir = TempDir::new().unwrap();
+    assert_eq!(migration::dry_run(dir.path().join("app_state.json")).unwrap(), None);
+
+    let json = serde_json::to_string(&legacy_state()).unwrap();
+    let path = write_fixture(&dir, &json);
+    let report = migration::dry_run(&path).unwrap().expect("file exists");
+    assert_eq!(report.contacts, 2);
+    assert_eq!(report.chats, 1);
+    assert_eq!(report.messages, 3);
+    assert_eq!(report.to_string(), "identity, 2 contacts, 1 chats, 3 messages");
+
+    // Nothing written, nothing backed up
+    assert_untouched(&path, &json);
+
+    // Dry runs stop on the same problems as real migrations
+    let path = write_fixture(&dir, "{");
+    assert!(migration::dry_run(&path).is_err());
+}
@@ -1,11 +1,23 @@
 // Storage Tests Module - Testing the storage module
 // Tests organized by storage module functionality:
-// - contact_tests: Contact struct and methods (creation, expiry, activation, serialization)
+// - contact_tests: Contact struct and methods (creation, expiry, activation, serialization, notes)
 // - token_tests: Token generation and parsing (roundtrip, validation, crypto integration)
-// - chat_tests: Chat and Message structs (append, active management, pending flags)
+// - chat_tests: Chat and Message structs (append, active management, pending flags, metadata, pin/star, text sanitization, history limit)
 // - app_state_tests: AppState struct (save/load, sync, chat management)
-// - settings_tests: Settings and SettingsManager (defaults, persistence, concurrent access)
+// - settings_tests: Settings and SettingsManager (defaults, persistence, concurrent access, quiet hours, templates, profile label and accent)
 // - request_log_tests: Request logging for network debugging (log CRUD, filtering, cleanup)
+// - busy_tests: Locked database handling (write retries, rollback, deferred writes)
+// - migration_tests: Legacy JSON import (validation, backup, rollback, dry run)
+// - identity_tests: UID/key verification, identity conflicts, startup identity scan
+// - content_tests: Binary content detection, size placeholders, download file names
+// - export_tests: JSON Lines chat export (golden output, streaming, schema version)
+// - chat_archive_tests: Signed chat archive (round trip, tamper detection, cross-reference, wrong key)
+// - introductions_tests: Pending introductions (CSV/vCard parsing, encoding quirks, fuzzy matching, carry-over, persistence)
+// - address_change_tests: Address changes of verified contacts (staging, auto-apply, provenance)
+// - uid_index_tests: Indexed contact/chat lookups (consistency across add, remove, direct edits and in-place renames, duplicate UIDs, scaling)
+// - snapshot_tests: Database snapshots (diff per change class, bounded output, sealed snapshots, JSON export)
+// - bounds_tests: Clamped token expiries and peer timestamps, duration display saturation
+// - chat_summary_tests: Chat summaries (maintenance, chat list without message reads, resumable migration, insert cost)
 
 mod contact_tests;
 mod token_tests;
@@ -13,3 +25,19 @@ mod chat_tests;
 mod app_state_tests;
 mod settings_tests;
 mod request_log_tests;
+mod busy_tests;
+mod migration_tests;
+mod identity_tests;
+mod content_tests;
+mod export_tests;
+mod chat_archive_tests;
+mod introductions_tests;
+mod address_change_tests;
+#[cfg(feature = "tui")]
+mod uid_index_tests;
+#[cfg(feature = "tui")]
+mod snapshot_tests;
+#[cfg(feature = "tui")]
+mod bounds_tests;
+#[cfg(feature = "tui")]
+mod chat_summary_tests;
@@ -1,6 +1,9 @@
-// Settings Tests - Testing Settings and SettingsManager
+// Settings Tests - Testing Settings and SettingsManager (including message templates)
 
-use crate::storage::{Settings, SettingsManager};
+use crate::storage::{
+    format_time_of_day, is_quiet, parse_time_of_day, AccentColor, MessageTemplate, Settings, SettingsManager,
+    Storage, ALL_DAYS_MASK, MAX_PROFILE_LABEL_CHARS, MAX_TEMPLATES,
+};
 use tempfile::NamedTempFile;
 
 // Settings Tests
@@ -438,3 +441,307 @@ async fn test_settings_manager_clone() {
 
 // Mapping Consent Tests
 
+
+// Quiet Hours Tests
+
+fn quiet_settings(start: &str, end: &str) -> Settings {
+    Settings {
+        quiet_hours_enabled: true,
+        quiet_hours_start_minutes: parse_time_of_day(start).unwrap(),
+        quiet_hours_end_minutes: parse_time_of_day(end).unwrap(),
+        ..Settings::default()
+    }
+}
+
+fn at(rfc3339: &str) -> chrono::DateTime<chrono::FixedOffset> {
+    chrono::DateTime::parse_from_rfc3339(rfc3339).unwrap()
+}
+
+#[test]
+fn test_quiet_hours_defaults() {
+    let settings = Settings::default();
+
+    assert!(!settings.quiet_hours_enabled);
+    assert_eq!(settings.quiet_hours_start_minutes, 22 * 60);
+    assert_eq!(settings.quiet_hours_end_minutes, 7 * 60);
+    assert_eq!(settings.quiet_hours_days, ALL_DAYS_MASK);
+
+    // Disabled quiet hours never apply
+    assert!(!is_quiet(&at("2024-03-01T23:30:00+00:00"), &settings));
+}
+
+#[test]
+fn test_is_quiet_same_day_range() {
+    let settings = quiet_settings("13:00", "14:30");
+
+    assert!(!is_quiet(&at("2024-03-01T12:59:00+00:00"), &settings));
+    assert!(is_quiet(&at("2024-03-01T13:00:00+00:00"), &settings));
+    assert!(is_quiet(&at("2024-03-01T14:29:00+00:00"), &settings));
+    assert!(!is_quiet(&at("2024-03-01T14:30:00+00:00"), &settings));
+}
+
+#[test]
+fn test_is_quiet_crosses_midnight() {
+    let settings = quiet_settings("22:00", "07:00");
+
+    assert!(!is_quiet(&at("2024-03-01T21:59:00+00:00"), &settings));
+    assert!(is_quiet(&at("2024-03-01T22:00:00+00:00"), &settings));
+    assert!(is_quiet(&at("2024-03-01T23:59:00+00:00"), &settings));
+    assert!(is_quiet(&at("2024-03-02T00:00:00+00:00"), &settings));
+    assert!(is_quiet(&at("2024-03-02T06:59:00+00:00"), &settings));
+    assert!(!is_quiet(&at("2024-03-02T07:00:00+00:00"), &settings));
+}
+
+#[test]
+fn test_is_quiet_day_mask_uses_start_day() {
+    // Friday nights only (bit 4 = Friday); 2024-03-01 is a Friday
+    let mut settings = quiet_settings("22:00", "07:00");
+    settings.quiet_hours_days = 1 << 4;
+
+    assert!(is_quiet(&at("2024-03-01T23:00:00+00:00"), &settings));
+    // Early Saturday morning still belongs to Friday's period
+    assert!(is_quiet(&at("2024-03-02T03:00:00+00:00"), &settings));
+    // Saturday night does not start a new period
+    assert!(!is_quiet(&at("2024-03-02T23:00:00+00:00"), &settings));
+    // Early Friday morning belongs to Thursday's period
+    assert!(!is_quiet(&at("2024-03-01T03:00:00+00:00"), &settings));
+}
+
+#[test]
+fn test_is_quiet_uses_wall_clock_across_dst() {
+    // Quiet hours follow local wall-clock time, whatever the UTC offset
+    let settings = quiet_settings("22:00", "07:00");
+
+    // 2024-03-31 in Central Europe: 06:30 CEST (+02:00) is 05:30 CET (+01:00)
+    assert!(!is_quiet(&at("2024-03-31T07:30:00+02:00"), &settings));
+    assert!(is_quiet(&at("2024-03-31T06:30:00+01:00"), &settings));
+
+    // 2024-10-27: the period starts at 22:00 local time after falling back
+    assert!(is_quiet(&at("2024-10-27T22:30:00+01:00"), &settings));
+    assert!(!is_quiet(&at("2024-10-27T21:30:00+01:00"), &settings));
+}
+
+#[test]
+fn test_validate_quiet_hours() {
+    let mut settings = Settings::default();
+    assert!(settings.validate_quiet_hours().is_ok());
+
+    // Start equal to end is only an error while enabled
+    settings.quiet_hours_start_minutes = 60;
+    settings.quiet_hours_end_minutes = 60;
+    assert!(settings.validate_quiet_hours().is_ok());
+    settings.quiet_hours_enabled = true;
+    assert!(settings.validate_quiet_hours().is_err());
+
+    settings.quiet_hours_end_minutes = 24 * 60;
+    assert!(settings.validate_quiet_hours().is_err());
+
+    settings.quiet_hours_end_minutes = 120;
+    settings.quiet_hours_days = 0b1000_0000;
+    assert!(settings.validate_quiet_hours().is_err());
+}
+
+#[test]
+fn test_parse_and_format_time_of_day() {
+    assert_eq!(parse_time_of_day("00:00"), Some(0));
+    assert_eq!(parse_time_of_day("7:05"), Some(425));
+    assert_eq!(parse_time_of_day("23:59"), Some(1439));
+    assert_eq!(parse_time_of_day("24:00"), None);
+    assert_eq!(parse_time_of_day("12:60"), None);
+    assert_eq!(parse_time_of_day("1200"), None);
+    assert_eq!(parse_time_of_day(""), None);
+
+    assert_eq!(format_time_of_day(0), "00:00");
+    assert_eq!(format_time_of_day(425), "07:05");
+    assert_eq!(format_time_of_day(1439), "23:59");
+}
+
+#[test]
+fn test_quiet_hours_db_persistence() {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+    let mut settings = quiet_settings("23:15", "06:45");
+    settings.quiet_hours_days = 0b001_1111;
+
+    storage.save_settings(&settings).expect("Failed to save settings");
+    let loaded = storage.load_settings().expect("Failed to load settings").unwrap();
+
+    assert!(loaded.quiet_hours_enabled);
+    assert_eq!(loaded.quiet_hours_start_minutes, 23 * 60 + 15);
+    assert_eq!(loaded.quiet_hours_end_minutes, 6 * 60 + 45);
+    assert_eq!(loaded.quiet_hours_days, 0b001_1111);
+}
+
+#[test]
+fn test_quiet_hours_json_backward_compatible() {
+    // Settings files written before quiet hours existed still load
+    let json = r#"{
+        "default_contact_expiry_days": 30,
+        "auto_accept_contacts": false,
+        "max_message_retries": 5,
+        "retry_base_delay_ms": 1000,
+        "enable_notifications": true,
+        "global_retry_interval_ms": 60000,
+        "retry_interval_minutes": 1,
+        "storage_path": "./data"
+    }"#;
+    let settings: Settings = serde_json::from_str(json).unwrap();
+
+    assert!(!settings.quiet_hours_enabled);
+    assert_eq!(settings.quiet_hours_days, ALL_DAYS_MASK);
+}
+
+#[test]
+fn test_quiet_hours_columns_added_to_existing_database() {
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let db_path = temp_dir.path().join("legacy.db");
+
+    // Simulate a database created before quiet hours existed
+    {
+        let conn = rusqlite::Connection::open(&db_path).expect("Failed to open database");
+        conn.execute(
+            "CREATE TABLE settings (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                default_contact_expiry_days INTEGER NOT NULL,
+                auto_accept_contacts INTEGER NOT NULL,
+                max_message_retries INTEGER NOT NULL,
+                retry_base_delay_ms INTEGER NOT NULL,
+                enable_notifications INTEGER NOT NULL,
+                global_retry_interval_ms INTEGER NOT NULL,
+                retry_interval_minutes INTEGER NOT NULL,
+                storage_path TEXT NOT NULL
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO settings VALUES (1, 30, 0, 5, 1000, 1, 120000, 2, './data')",
+            [],
+        ).unwrap();
+    }
+
+    let storage = Storage::new(&db_path).expect("Failed to open legacy database");
+    let settings = storage.load_settings().expect("Failed to load settings").unwrap();
+    assert_eq!(settings.retry_interval_minutes, 2);
+    assert!(!settings.quiet_hours_enabled);
+    assert_eq!(settings.quiet_hours_start_minutes, 22 * 60);
+    assert_eq!(settings.quiet_hours_end_minutes, 7 * 60);
+    assert_eq!(settings.quiet_hours_days, ALL_DAYS_MASK);
+}
+
+// Message Template Tests
+
+#[test]
+fn test_template_crud() {
+    let mut settings = Settings::default();
+    assert!(settings.templates.is_empty());
+
+    settings.add_template("  Meeting ", "Join at https://meet.example/abc").unwrap();
+    settings.add_template("Payment", "IBAN: DE00 1234").unwrap();
+    assert_eq!(settings.templates[0].name, "Meeting");
+
+    // Names are unique (ignoring case), and neither field may be empty
+    assert!(settings.add_template("meeting", "other").is_err());
+    assert!(settings.add_template(" ", "text").is_err());
+    assert!(settings.add_template("Empty", "  ").is_err());
+
+    settings.update_template(1, "Payment", "IBAN: DE99 5678").unwrap();
+    assert_eq!(settings.templates[1].body, "IBAN: DE99 5678");
+    // Keeping its own name is fine, taking another template's is not
+    assert!(settings.update_template(1, "MEETING", "x").is_err());
+    assert!(settings.update_template(5, "New", "x").is_err());
+
+    let removed = settings.remove_template(0).unwrap();
+    assert_eq!(removed.name, "Meeting");
+    assert_eq!(settings.templates.len(), 1);
+    assert!(settings.remove_template(3).is_none());
+}
+
+#[test]
+fn test_template_cap() {
+    let mut settings = Settings::default();
+    for i in 0..MAX_TEMPLATES {
+        settings.add_template(&format!("t{}", i), "body").unwrap();
+    }
+
+    let err = settings.add_template("one too many", "body").unwrap_err();
+    assert!(err.to_string().contains(&MAX_TEMPLATES.to_string()));
+    assert_eq!(settings.templates.len(), MAX_TEMPLATES);
+
+    // Editing still works at the cap
+    settings.update_template(0, "first", "new body").unwrap();
+}
+
+#[test]
+fn test_template_placeholders() {
+    let template = MessageTemplate::new("Hi", "Hello {name},\nsee you on {date}. Bye {name}!");
+    let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+
+    assert_eq!(
+        template.render("alice", date),
+        "Hello alice,\nsee you on 2024-03-09. Bye alice!"
+    );
+    // Bodies without placeholders are inserted as written
+    assert_eq!(MessageTemplate::new("Plain", "{other}").render("alice", date), "{other}");
+}
+
+#[test]
+fn test_templates_persist_in_db() {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+    let mut settings = Settings::default();
+    settings.add_template("b", "second\nline").unwrap();
+    settings.add_template("a", "first").unwrap();
+
+    storage.save_settings(&settings).expect("Failed to save settings");
+    let loaded = storage.load_settings().expect("Failed to load settings").unwrap();
+    assert_eq!(loaded.templates, settings.templates);
+
+    // Removed templates are removed from the database too
+    settings.remove_template(0);
+    storage.save_settings(&settings).expect("Failed to save settings");
+    let loaded = storage.load_settings().expect("Failed to load settings").unwrap();
+    assert_eq!(loaded.templates, vec![MessageTemplate::new("a", "first")]);
+}
+
+#[test]
+fn test_templates_export_import_with_settings() {
+    let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+    let mut settings = Settings { quiet_hours_enabled: true, ..Settings::default() };
+    settings.add_template("Link", "https://meet.example/{name}").unwrap();
+
+    settings.save(temp_file.path()).expect("Failed to export settings");
+    let imported = Settings::load(temp_file.path()).expect("Failed to import settings");
+
+    assert!(imported.quiet_hours_enabled);
+    assert_eq!(imported.templates, settings.templates);
+}
+
+#[test]
+fn test_profile_label_and_accent_persist() {
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+    let mut settings = Settings::default();
+    assert!(settings.profile_label.is_empty());
+    assert_eq!(settings.accent_color, None);
+
+    settings.set_profile_label("  work  ").unwrap();
+    settings.accent_color = Some(AccentColor::Magenta);
+    storage.save_settings(&settings).expect("Failed to save settings");
+    let loaded = storage.load_settings().expect("Failed to load settings").unwrap();
+    assert_eq!(loaded.profile_label, "work");
+    assert_eq!(loaded.accent_color, Some(AccentColor::Magenta));
+
+    // Labels are short and printable
+    assert!(settings.set_profile_label(&"x".repeat(MAX_PROFILE_LABEL_CHARS + 1)).is_err());
+    assert!(settings.set_profile_label("wo\u{1b}rk").is_err());
+    assert_eq!(settings.profile_label, "work");
+
+    // Cycling goes through every accent and back to the theme default
+    let mut accent = None;
+    let mut seen = Vec::new();
+    loop {
+        accent = AccentColor::cycle(accent);
+        match accent {
+            Some(a) => seen.push(a),
+            None => break,
+        }
+    }
+    assert_eq!(seen, AccentColor::ALL.to_vec());
+}
@@ -0,0 +1,237 @@
+// Snapshot Tests - snapshot diff per change class, bounded output on large fixtures, sealed snapshots, JSON export shape
+
+use crate::crypto::KeyPair;
+use crate::storage::{
+    diff_snapshot_files, list_snapshots, Chat, Contact, Message, Settings, SnapshotComparison, SnapshotDiff, Storage,
+    SNAPSHOT_DIFF_LIST_LIMIT, SNAPSHOT_DIFF_VERSION,
+};
+use crate::tui::{App, SaveTarget};
+use chrono::DateTime;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Contact with a fixed expiry, so saving it again changes nothing else
+fn contact(uid: &str, ip: &str) -> Contact {
+    let expiry = DateTime::from_timestamp(4_000_000_000, 0).unwrap();
+    Contact::new(uid.to_string(), ip.to_string(), vec![1; 32], vec![2; 32], expiry)
+}
+
+fn chat_with(uid: &str, messages: usize) -> Chat {
+    let mut chat = Chat::new(uid.to_string());
+    for i in 0..messages {
+        chat.append_message(Message::new(
+            format!("{}-{}", uid, i),
+            uid.to_string(),
+            "me".to_string(),
+            b"hi".to_vec(),
+            1000 + i as i64,
+        ));
+    }
+    chat
+}
+
+/// Storage holding alice (2 messages) and bob (1 message), default settings
+fn seeded_storage() -> Storage {
+    let storage = Storage::new_in_memory().unwrap();
+    for (uid, messages) in [("alice", 2), ("bob", 1)] {
+        storage.save_contact(&contact(uid, "192.168.1.10:8080")).unwrap();
+        storage.save_chat(&chat_with(uid, messages)).unwrap();
+    }
+    storage.save_settings(&Settings::default()).unwrap();
+    storage
+}
+
+fn compared(comparison: SnapshotComparison) -> SnapshotDiff {
+    match comparison {
+        SnapshotComparison::Compared(diff) => *diff,
+        SnapshotComparison::Encrypted(path) => panic!("{} should open", path.display()),
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[test]
+fn test_diff_covers_each_change_class() {
+    let temp_dir = TempDir::new().unwrap();
+    let (old_path, new_path) = (temp_dir.path().join("old.snapshot"), temp_dir.path().join("new.snapshot"));
+    let storage = seeded_storage();
+    storage.write_snapshot(&old_path, None).unwrap();
+
+    // Nothing changed yet
+    let same = compared(storage.diff_against_snapshot(&old_path, None).unwrap());
+    assert!(same.is_empty(), "{:?}", same);
+    assert_eq!(same.summary(), "No differences");
+
+    // carol added, bob removed, alice moved with new notes, messages and a setting changed
+    storage.save_contact(&contact("carol", "10.0.0.3:8080")).unwrap();
+    storage.save_chat(&chat_with("carol", 3)).unwrap();
+    storage.delete_chat("bob").unwrap();
+    storage.delete_contact("bob").unwrap();
+    let mut alice = contact("alice", "203.0.113.5:9000");
+    alice.notes = "met at the conference".to_string();
+    storage.save_contact(&alice).unwrap();
+    storage.save_chat(&chat_with("alice", 5)).unwrap();
+    let settings = Settings { retry_interval_minutes: 42, ..Settings::default() };
+    storage.save_settings(&settings).unwrap();
+    storage.write_snapshot(&new_path, None).unwrap();
+
+    let diff = compared(diff_snapshot_files(&old_path, &new_path, None).unwrap());
+    assert_eq!((diff.old.as_str(), diff.new.as_str()), ("old.snapshot", "new.snapshot"));
+    assert_eq!(diff.contacts_added.listed, ["carol"]);
+    assert_eq!(diff.contacts_removed.listed, ["bob"]);
+    assert_eq!(diff.chats_added.listed, ["carol"]);
+    assert_eq!(diff.chats_removed.listed, ["bob"]);
+
+    // Field-level: only the fields that changed
+    assert_eq!(diff.contacts_modified.total, 1);
+    let modified = &diff.contacts_modified.listed[0];
+    assert_eq!(modified.uid, "alice");
+    let fields: Vec<(&str, Option<&str>, Option<&str>)> = modified
+        .fields
+        .iter()
+        .map(|f| (f.field.as_str(), f.old.as_deref(), f.new.as_deref()))
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            ("ip", Some("192.168.1.10:8080"), Some("203.0.113.5:9000")),
+            ("notes", Some(""), Some("met at the conference")),
+        ]
+    );
+
+    let counts: Vec<(&str, u64, u64, i64)> =
+        diff.message_counts.listed.iter().map(|c| (c.chat_uid.as_str(), c.old, c.new, c.delta())).collect();
+    assert_eq!(counts, [("alice", 2, 5, 3), ("bob", 1, 0, -1), ("carol", 0, 3, 3)]);
+    let retry: Vec<_> = diff.settings.iter().filter(|f| f.field == "retry_interval_minutes").collect();
+    assert_eq!((retry[0].old.as_deref(), retry[0].new.as_deref()), (Some("1"), Some("42")));
+
+    // The live database compares the same way as its snapshot
+    let live = compared(storage.diff_against_snapshot(&old_path, None).unwrap());
+    assert_eq!(live.new, "live database");
+    assert_eq!(live.contacts_modified, diff.contacts_modified);
+    assert_eq!(live.message_counts, diff.message_counts);
+    assert!(diff.summary().starts_with("contacts +1 −1 ~1 | chats +1 −1 | 3 chat(s)"));
+}
+
+#[test]
+fn test_large_fixtures_keep_output_bounded() {
+    /// Diff of `n` contacts whose address changed, plus `n / 2` new ones
+    fn diff_of(temp_dir: &TempDir, n: usize) -> SnapshotDiff {
+        let storage = Storage::new_in_memory().unwrap();
+        for i in 0..n {
+            storage.save_contact(&contact(&format!("uid-{:06}", i), "10.0.0.1:8080")).unwrap();
+        }
+        let old_path = temp_dir.path().join(format!("old-{}.snapshot", n));
+        storage.write_snapshot(&old_path, None).unwrap();
+        for i in 0..n {
+            storage.save_contact(&contact(&format!("uid-{:06}", i), "10.0.0.2:8080")).unwrap();
+        }
+        for i in n..n + n / 2 {
+            storage.save_contact(&contact(&format!("uid-{:06}", i), "10.0.0.2:8080")).unwrap();
+        }
+        compared(storage.diff_against_snapshot(&old_path, None).unwrap())
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let small = diff_of(&temp_dir, SNAPSHOT_DIFF_LIST_LIMIT * 2);
+    let large = diff_of(&temp_dir, SNAPSHOT_DIFF_LIST_LIMIT * 10);
+
+    // Everything is counted; only the first changes per class are kept
+    assert_eq!(large.contacts_modified.total, SNAPSHOT_DIFF_LIST_LIMIT * 10);
+    assert_eq!(large.contacts_added.total, SNAPSHOT_DIFF_LIST_LIMIT * 5);
+    assert_eq!(large.contacts_modified.listed.len(), SNAPSHOT_DIFF_LIST_LIMIT);
+    assert_eq!(large.contacts_added.listed.len(), SNAPSHOT_DIFF_LIST_LIMIT);
+    assert_eq!(large.contacts_modified.unlisted(), SNAPSHOT_DIFF_LIST_LIMIT * 9);
+    assert_eq!(large.contacts_modified.listed[0].uid, "uid-000000", "Rows are merged in key order");
+
+    // Every row was read, yet what is held does not grow with the fixture
+    assert!(large.rows_compared >= (SNAPSHOT_DIFF_LIST_LIMIT * 15) as u64);
+    let (small_json, large_json) = (small.to_json().unwrap(), large.to_json().unwrap());
+    assert!(large_json.len().abs_diff(small_json.len()) < 64, "{} vs {}", small_json.len(), large_json.len());
+}
+
+#[test]
+fn test_sealed_snapshots_and_other_identities() {
+    let temp_dir = TempDir::new().unwrap();
+    let (me, someone_else) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let storage = seeded_storage();
+    let sealed = temp_dir.path().join("snapshots/sealed.snapshot");
+    let plain = temp_dir.path().join("snapshots/plain.snapshot");
+    storage.write_snapshot(&sealed, Some(&me)).unwrap();
+    storage.write_snapshot(&plain, None).unwrap();
+    assert!(storage.write_snapshot(&plain, None).is_err(), "Snapshots are never overwritten");
+
+    // Nothing readable at rest
+    let bytes = std::fs::read(&sealed).unwrap();
+    assert!(!contains(&bytes, b"SQLite format 3") && !contains(&bytes, b"192.168.1.10"));
+    let listed = list_snapshots(&temp_dir.path().join("snapshots"));
+    let mut flags: Vec<(&str, bool)> = listed.iter().map(|s| (s.name.as_str(), s.sealed)).collect();
+    flags.sort();
+    assert_eq!(flags, [("plain.snapshot", false), ("sealed.snapshot", true)]);
+
+    // Opened with the current key, including against a plain copy
+    assert!(compared(storage.diff_against_snapshot(&sealed, Some(&me)).unwrap()).is_empty());
+    assert!(compared(diff_snapshot_files(&sealed, &plain, Some(&me)).unwrap()).is_empty());
+
+    // Another identity, or none, cannot compare
+    for keypair in [Some(&someone_else), None] {
+        assert_eq!(
+            storage.diff_against_snapshot(&sealed, keypair).unwrap(),
+            SnapshotComparison::Encrypted(sealed.clone())
+        );
+        assert_eq!(
+            diff_snapshot_files(&plain, &sealed, keypair).unwrap(),
+            SnapshotComparison::Encrypted(sealed.clone())
+        );
+    }
+
+    // A file that is neither is an error, not "encrypted"
+    let garbage = temp_dir.path().join("notes.txt");
+    std::fs::write(&garbage, "not a database").unwrap();
+    assert!(matches!(diff_snapshot_files(&garbage, &plain, Some(&me)), Err(crate::Error::Storage(_))));
+}
+
+#[test]
+fn test_json_export_shape_from_the_screen() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.show_snapshots_screen();
+    let snapshot = app.take_snapshot().unwrap();
+    assert!(snapshot.starts_with(&app.snapshots_dir));
+
+    // A new chat after the snapshot
+    app.app_state.add_contact(contact("dave", "10.0.0.4:8080"));
+    app.app_state.add_chat("dave".to_string());
+    app.show_snapshots_screen();
+    let screen = app.snapshots_screen.as_mut().unwrap();
+    assert_eq!(screen.snapshots.len(), 1);
+    assert!(screen.snapshots[0].sealed);
+    app.compare_snapshots();
+    assert!(app.snapshots_screen.as_ref().unwrap().is_error, "The live row alone is not a comparison");
+    app.snapshots_screen.as_mut().unwrap().move_selection(true);
+    app.compare_snapshots();
+    let screen = app.snapshots_screen.as_ref().unwrap();
+    assert_eq!(screen.diff().unwrap().contacts_added.listed, ["dave"]);
+    assert!(screen.status_message.as_deref().unwrap().starts_with("contacts +1"));
+
+    // Exported through the path overlay
+    let export = temp_dir.path().join("diff.json");
+    app.open_path_picker(SaveTarget::SnapshotDiff);
+    app.path_picker.as_mut().unwrap().input = export.display().to_string();
+    app.submit_path_picker();
+    assert!(app.path_picker.is_none());
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&export).unwrap()).unwrap();
+    assert_eq!(json["version"], SNAPSHOT_DIFF_VERSION);
+    assert_eq!(json["old"], snapshot.file_name().unwrap().to_str().unwrap());
+    assert_eq!(json["new"], "live database");
+    assert_eq!(json["contacts_added"], serde_json::json!({ "total": 1, "listed": ["dave"] }));
+    assert_eq!(json["chats_added"]["listed"][0], "dave");
+    assert_eq!(json["message_counts"]["total"], 0);
+    assert!(json["contacts_modified"]["listed"].as_array().unwrap().is_empty());
+    assert!(json["settings"].is_array() && json["rows_compared"].is_u64());
+    let keys: Vec<&String> = json.as_object().unwrap().keys().collect();
+    assert_eq!(keys.len(), 11);
+    assert!(Path::new(&export).exists());
+}
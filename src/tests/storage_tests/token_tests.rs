@@ -1,7 +1,9 @@
 // Token Tests - Testing contact token generation and parsing (with signature verification)
 
 use crate::crypto::KeyPair;
-use crate::storage::{generate_contact_token, parse_contact_token};
+use crate::storage::{
+    generate_contact_token, generate_multi_endpoint_token, parse_contact_token, ContactEndpoint,
+};
 use crate::Error;
 use chrono::{Duration, Utc};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
@@ -48,7 +50,7 @@ fn test_parse_contact_token_roundtrip() {
     // Verify fields
     assert_eq!(contact.ip, ip);
     assert_eq!(contact.pubkey, keypair.public_key);
-    assert_eq!(contact.x25519_pubkey, keypair.x25519_public);
+    assert_eq!(contact.x25519_pubkey, Some(keypair.x25519_public));
     assert_eq!(contact.expiry, expiry);
     assert!(contact.is_active); // Should be active by default
 
@@ -134,7 +136,7 @@ fn test_contact_token_with_real_crypto_keys() {
     // Verify fields
     assert_eq!(contact.ip, ip);
     assert_eq!(contact.pubkey, keypair.public_key);
-    assert_eq!(contact.x25519_pubkey, keypair.x25519_public);
+    assert_eq!(contact.x25519_pubkey, Some(keypair.x25519_public));
     assert_eq!(contact.uid, keypair.uid.to_string());
     assert!(contact.is_active);
 }
@@ -304,3 +306,148 @@ fn test_contact_token_wrong_signer() {
         panic!("Expected Crypto error for mismatched signer");
     }
 }
+
+fn multi_endpoints() -> Vec<ContactEndpoint> {
+    let mut home = ContactEndpoint::new("203.0.113.7:8080", "home");
+    home.valid_until = Some(Utc::now() + Duration::days(2));
+    vec![ContactEndpoint::new("10.8.0.2:8080", "vpn"), home]
+}
+
+#[test]
+fn test_multi_endpoint_token_roundtrip() {
+    let keypair = KeyPair::generate().expect("Failed to generate keypair");
+    let endpoints = multi_endpoints();
+
+    let token = generate_multi_endpoint_token(
+        &endpoints,
+        &keypair.public_key,
+        &keypair.private_key,
+        &keypair.x25519_public,
+        Utc::now() + Duration::days(7),
+    ).expect("Failed to generate token");
+
+    let contact = parse_contact_token(&token).expect("Failed to parse multi-endpoint token");
+    assert_eq!(contact.endpoints, endpoints);
+    // First endpoint doubles as the legacy address
+    assert_eq!(contact.ip, "10.8.0.2:8080");
+    assert_eq!(contact.uid, keypair.uid.to_string());
+
+    // Re-signing from the parsed contact keeps the endpoint list
+    let resigned = contact.sign_token(&keypair).expect("Failed to re-sign token");
+    assert_eq!(parse_contact_token(&resigned).unwrap().endpoints, endpoints);
+}
+
+#[test]
+fn test_multi_endpoint_token_requires_endpoint() {
+    let keypair = KeyPair::generate().expect("Failed to generate keypair");
+    let result = generate_multi_endpoint_token(
+        &[],
+        &keypair.public_key,
+        &keypair.private_key,
+        &keypair.x25519_public,
+        Utc::now() + Duration::days(7),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_multi_endpoint_token_signature_covers_endpoints() {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Payload {
+        ip: String,
+        pubkey: Vec<u8>,
+        x25519_pubkey: Vec<u8>,
+        expiry: chrono::DateTime<Utc>,
+        endpoints: Vec<ContactEndpoint>,
+    }
+    #[derive(Serialize, Deserialize)]
+    struct TokenData {
+        payload: Payload,
+        signature: Vec<u8>,
+    }
+
+    let keypair = KeyPair::generate().expect("Failed to generate keypair");
+    let token = generate_multi_endpoint_token(
+        &multi_endpoints(),
+        &keypair.public_key,
+        &keypair.private_key,
+        &keypair.x25519_public,
+        Utc::now() + Duration::days(7),
+    ).expect("Failed to generate token");
+
+    let cbor = URL_SAFE_NO_PAD.decode(&token).expect("Failed to decode token");
+    let original: TokenData = serde_cbor::from_slice(&cbor).expect("Failed to decode token data");
+
+    // Redirecting, reordering or dropping an endpoint invalidates the signature
+    let tampers: Vec<fn(&mut Vec<ContactEndpoint>)> = vec![
+        |e| e[1].address = "198.51.100.66:8080".to_string(),
+        |e| e.swap(0, 1),
+        |e| { e.pop(); },
+        |e| e[1].valid_until = None,
+    ];
+    for tamper in tampers {
+        let mut data: TokenData = serde_cbor::from_slice(&cbor).unwrap();
+        tamper(&mut data.payload.endpoints);
+        let tampered = URL_SAFE_NO_PAD.encode(serde_cbor::to_vec(&data).unwrap());
+        assert!(
+            matches!(parse_contact_token(&tampered), Err(Error::Crypto(_))),
+            "Tampered endpoint list must fail verification"
+        );
+    }
+
+    // Untouched token still verifies
+    let reencoded = URL_SAFE_NO_PAD.encode(serde_cbor::to_vec(&original).unwrap());
+    assert!(parse_contact_token(&reencoded).is_ok());
+}
+
+#[test]
+fn test_legacy_single_endpoint_token_parses() {
+    use serde::Serialize;
+
+    // Token layout used before endpoint lists existed
+    #[derive(Serialize)]
+    struct LegacyPayload {
+        ip: String,
+        pubkey: Vec<u8>,
+        x25519_pubkey: Vec<u8>,
+        expiry: chrono::DateTime<Utc>,
+    }
+    #[derive(Serialize)]
+    struct LegacyToken {
+        payload: LegacyPayload,
+        signature: Vec<u8>,
+    }
+
+    let keypair = KeyPair::generate().expect("Failed to generate keypair");
+    let payload = LegacyPayload {
+        ip: "192.168.1.50:8080".to_string(),
+        pubkey: keypair.public_key.clone(),
+        x25519_pubkey: keypair.x25519_public.clone(),
+        expiry: Utc::now() + Duration::days(1),
+    };
+    let payload_cbor = serde_cbor::to_vec(&payload).unwrap();
+    let privkey: [u8; 32] = keypair.private_key.as_slice().try_into().unwrap();
+    let signature = crate::crypto::sign_contact_token(&privkey, &payload_cbor).unwrap();
+    let legacy = URL_SAFE_NO_PAD.encode(
+        serde_cbor::to_vec(&LegacyToken { payload, signature: signature.to_vec() }).unwrap(),
+    );
+
+    let contact = parse_contact_token(&legacy).expect("Legacy token should parse");
+    assert_eq!(contact.ip, "192.168.1.50:8080");
+    assert!(contact.endpoints.is_empty());
+    assert_eq!(contact.delivery_addresses(None), vec!["192.168.1.50:8080".to_string()]);
+
+    // Single-endpoint tokens are still generated in the legacy layout
+    let current = generate_contact_token(
+        "192.168.1.50:8080",
+        &keypair.public_key,
+        &keypair.private_key,
+        &keypair.x25519_public,
+        contact.expiry,
+    ).unwrap();
+    let decoded = URL_SAFE_NO_PAD.decode(&current).unwrap();
+    let value: serde_cbor::Value = serde_cbor::from_slice(&decoded).unwrap();
+    assert!(!format!("{:?}", value).contains("endpoints"));
+}
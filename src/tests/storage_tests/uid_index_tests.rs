@@ -0,0 +1,228 @@
+// UID Index Tests - Testing indexed contact/chat lookups (consistency, direct edits, scaling)
+
+use crate::crypto::KeyPair;
+use crate::storage::{uid_index::UidIndex, AppState, Chat, Contact, Message, Storage};
+use crate::tui::ui::{chat_list_rows, ChatRowStatus};
+use chrono::{Duration, Utc};
+use std::time::Instant;
+
+fn contact(uid: &str) -> Contact {
+    Contact::new(
+        uid.to_string(),
+        "127.0.0.1:8080".to_string(),
+        vec![1, 2, 3],
+        vec![],
+        Utc::now() + Duration::days(30),
+    )
+}
+
+fn message(id: &str, sender: &str) -> Message {
+    Message::new(id.to_string(), sender.to_string(), "me".to_string(), id.as_bytes().to_vec(), 1000)
+}
+
+/// Every contact and chat is found at its own position
+fn assert_consistent(state: &AppState) {
+    for contact in &state.contacts {
+        assert!(std::ptr::eq(state.contact_by_uid(&contact.uid).unwrap(), contact));
+    }
+    for chat in &state.chats {
+        assert!(std::ptr::eq(state.chat_by_uid(&chat.contact_uid).unwrap(), chat));
+    }
+}
+
+#[test]
+fn test_index_follows_add_and_remove() {
+    let mut state = AppState::new();
+    for uid in ["alice", "bob", "carol"] {
+        state.add_contact(contact(uid));
+        state.add_chat(uid.to_string()).append_message(message(&format!("m_{}", uid), uid));
+    }
+    assert_consistent(&state);
+
+    // Removing from the middle shifts the later entries
+    assert_eq!(state.remove_contact("bob").unwrap().uid, "bob");
+    assert_eq!(state.remove_chat("bob").unwrap().contact_uid, "bob");
+    assert!(state.remove_contact("bob").is_none());
+    assert!(state.remove_chat("bob").is_none());
+    assert!(state.contact_by_uid("bob").is_none());
+    assert!(state.chat_by_uid("bob").is_none());
+    assert_eq!(state.chat_by_uid("carol").unwrap().messages[0].id, "m_carol");
+    assert_consistent(&state);
+
+    // get_or_create_chat finds the existing chat instead of adding another
+    state.get_or_create_chat("carol").append_message(message("m_carol_2", "carol"));
+    assert_eq!(state.chats.len(), 2);
+    assert_eq!(state.chat_by_uid("carol").unwrap().messages.len(), 2);
+    state.get_or_create_chat("dave");
+    assert_eq!(state.chats.len(), 3);
+    assert_consistent(&state);
+
+    // Mutable lookups reach the stored entry
+    state.contact_by_uid_mut("alice").unwrap().verified = true;
+    assert!(state.contacts[0].verified);
+}
+
+#[test]
+fn test_index_survives_direct_edits() {
+    let mut state = AppState::new();
+    state.add_contact(contact("alice"));
+    state.add_chat("alice".to_string());
+
+    // Lists that grew or shrank behind the index's back are scanned
+    state.contacts.push(contact("bob"));
+    state.chats.push(Chat::new("bob".to_string()));
+    assert_eq!(state.contact_by_uid("bob").unwrap().uid, "bob");
+    assert_eq!(state.chat_by_uid("bob").unwrap().contact_uid, "bob");
+    state.chats[0].contact_uid = "alice_renamed".to_string();
+    assert!(state.chat_by_uid("alice").is_none());
+    assert_eq!(state.chat_by_uid("alice_renamed").unwrap().contact_uid, "alice_renamed");
+
+    // Mutable lookups rebuild the index, after which a stale position is
+    // never returned for another UID
+    assert_eq!(state.contact_by_uid_mut("bob").unwrap().uid, "bob");
+    state.contacts.swap(0, 1);
+    assert_eq!(state.contact_by_uid("alice").unwrap().uid, "alice");
+    assert_eq!(state.contact_by_uid("bob").unwrap().uid, "bob");
+
+    // Replacing entries without changing the length is found too; reindex()
+    // just saves the scan
+    state.contacts = vec![contact("carol"), contact("dave")];
+    assert_eq!(state.contact_by_uid("dave").unwrap().uid, "dave");
+    state.reindex();
+    assert!(state.contact_by_uid("alice").is_none());
+    assert_eq!(state.contact_by_uid_mut("carol").unwrap().uid, "carol");
+    assert_consistent(&state);
+}
+
+#[test]
+fn test_uid_renamed_in_place_is_found() {
+    let mut state = AppState::new();
+    for uid in ["alice", "bob", "carol"] {
+        state.add_contact(contact(uid));
+        state.add_chat(uid.to_string());
+    }
+
+    // Same length, so the index still looks current, but the UID it has at
+    // position 1 is gone and the new one is unknown to it
+    state.contacts[1].uid = "bob_v2".to_string();
+    state.chats[1].contact_uid = "bob_v2".to_string();
+    assert_eq!(state.contact_by_uid("bob_v2").unwrap().uid, "bob_v2");
+    assert_eq!(state.chat_by_uid("bob_v2").unwrap().contact_uid, "bob_v2");
+    assert!(state.contact_by_uid("bob").is_none());
+    assert!(state.chat_by_uid("bob").is_none());
+
+    // A mutable lookup finds it and rebuilds the index for the next ones
+    state.contact_by_uid_mut("bob_v2").unwrap().verified = true;
+    assert!(state.contacts[1].verified);
+    state.get_or_create_chat("bob_v2");
+    assert_eq!(state.chats.len(), 3);
+    state.add_contact(contact("bob"));
+    assert_eq!(state.contact_by_uid("bob").unwrap().uid, "bob");
+    assert_consistent(&state);
+
+    // Two UIDs swapped in place
+    state.contacts[0].uid = "carol".to_string();
+    state.contacts[2].uid = "alice".to_string();
+    assert!(std::ptr::eq(state.contact_by_uid("carol").unwrap(), &state.contacts[0]));
+    assert!(std::ptr::eq(state.contact_by_uid("alice").unwrap(), &state.contacts[2]));
+}
+
+#[test]
+fn test_duplicate_uids_keep_index_current() {
+    let uids = ["alice", "bob", "alice", "carol"];
+    let mut index = UidIndex::build(&uids, |uid| uid);
+    assert!(index.matches_len(uids.len()));
+    assert_eq!(index.find(&uids, "alice", |uid| uid), Some(0));
+
+    // An append after the duplicate keeps the index matching, so hits need no rebuild
+    index.insert("dave", uids.len());
+    let uids = ["alice", "bob", "alice", "carol", "dave"];
+    assert!(index.matches_len(uids.len()));
+    assert_eq!(index.find_or_rebuild(&uids, "dave", |uid| uid), Some(4));
+    assert!(!index.matches_len(uids.len() + 1));
+}
+
+#[test]
+fn test_index_after_load_and_identity_merge() {
+    let alice = KeyPair::generate().unwrap();
+    let storage = Storage::new_in_memory().unwrap();
+    let alice_uid = alice.uid.to_string();
+    let alice_contact = |uid: &str| {
+        let mut c = Contact::new(
+            alice_uid.clone(),
+            "192.168.1.10:8080".to_string(),
+            alice.public_key.clone(),
+            alice.x25519_public.clone(),
+            Utc::now() + Duration::days(30),
+        );
+        c.uid = uid.to_string();
+        c
+    };
+
+    // A copy of Alice under another UID is merged into her by the scan
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().unwrap());
+    state.contacts = vec![alice_contact("alice_copy"), alice_contact(&alice_uid)];
+    let mut copy_chat = Chat::new("alice_copy".to_string());
+    copy_chat.append_message(message("m2", "alice_copy"));
+    state.chats = vec![copy_chat, Chat::new(alice_uid.clone())];
+    state.save_to_db(&storage).unwrap();
+
+    let mut state = AppState::load_from_db(&storage).unwrap();
+    assert_consistent(&state);
+    assert!(state.run_identity_scan(&storage).unwrap().is_some());
+    assert!(state.contact_by_uid("alice_copy").is_none());
+    assert!(state.chat_by_uid("alice_copy").is_none());
+    assert_eq!(state.chat_by_uid(&alice_uid).unwrap().messages.len(), 1);
+    assert_consistent(&state);
+
+    let reloaded = AppState::load_from_db(&storage).unwrap();
+    assert_eq!(reloaded.contact_by_uid(&alice_uid).unwrap().uid, alice_uid);
+    assert_consistent(&reloaded);
+}
+
+#[test]
+fn test_chat_list_and_inserts_scale() {
+    const SIZE: usize = 2_000;
+    let now = Utc::now();
+    let mut state = AppState::new();
+    for i in 0..SIZE {
+        let uid = format!("contact_{:05}", i);
+        let mut c = contact(&uid);
+        if i % 10 == 0 {
+            c.expiry = now - Duration::days(1);
+        }
+        c.verified = i % 3 == 0;
+        state.add_contact(c);
+    }
+    // Chats in the opposite order, so a scan would walk far for each
+    for i in (0..SIZE).rev() {
+        state.add_chat(format!("contact_{:05}", i));
+    }
+
+    // A second of redraws (20 chat list models) and 100 messages arriving the
+    // way the handler stores them: look the sender up, then find (or start)
+    // the chat. With a scan per lookup this takes about half a second in a
+    // debug build.
+    let started = Instant::now();
+    for _ in 0..19 {
+        assert_eq!(chat_list_rows(&state, now).len(), SIZE);
+    }
+    let rows = chat_list_rows(&state, now);
+    let row_count = rows.len();
+    let last_uid = rows[0].contact_uid.to_string();
+    let oldest = rows.iter().find(|r| r.contact_uid == "contact_00000").map(|r| (r.status, r.verified));
+    for i in 0..100 {
+        let uid = format!("contact_{:05}", (i * 37) % SIZE);
+        assert!(state.contact_by_uid(&uid).is_some());
+        state.get_or_create_chat(&uid).append_message(message(&format!("in_{}", i), &uid));
+    }
+    let elapsed = started.elapsed();
+    assert!(elapsed < std::time::Duration::from_millis(150), "took {:?}", elapsed);
+
+    assert_eq!(row_count, SIZE);
+    assert_eq!(last_uid, format!("contact_{:05}", SIZE - 1));
+    assert_eq!(oldest, Some((ChatRowStatus::Expired, true)));
+    assert_eq!(state.chats.len(), SIZE);
+    assert_eq!(state.chat_by_uid("contact_00037").unwrap().messages.len(), 1);
+}
@@ -0,0 +1,216 @@
+// TLS tests - pinned handshake success and fingerprint mismatch between in-process peers, token round trip with the certificate fingerprint, plain and TLS on one listener, strict mode for LAN vs external destinations, certificate renewal
+
+use crate::crypto::KeyPair;
+use crate::storage::{
+    generate_contact_token, generate_pinned_token, own_token_for, parse_contact_token, pinned_endpoints, AddressSource,
+    Contact, ContactEndpoint, ContactIngest,
+};
+use crate::transport::{
+    connect_pinned, plain_http_allowed, PeerTransport, TlsIdentity, TlsTransport, Transport, TransportRegistry,
+    TLS_CERT_RENEW_DAYS, TLS_CERT_VALIDITY_DAYS,
+};
+use crate::tui::App;
+use crate::Error;
+use chrono::{Duration, Utc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// A transport listening on a random local port, counting pings
+async fn tls_peer(identity: Option<&TlsIdentity>) -> (Transport, String, Arc<AtomicUsize>) {
+    let mut transport = Transport::new();
+    transport.set_local_uid("server_uid".to_string()).await;
+    transport.set_tls_identity(identity).unwrap();
+    let pings = Arc::new(AtomicUsize::new(0));
+    let counter = pings.clone();
+    transport
+        .set_ping_handler(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .await;
+    transport.start("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let address = transport.local_addr().unwrap().to_string();
+    (transport, address, pings)
+}
+
+/// The server as a contact: plain `ip`, TLS endpoint first, pinned fingerprint
+fn pinned_contact(address: &str, fingerprint: &str) -> Contact {
+    let mut contact = Contact::new(
+        "server_uid".to_string(),
+        address.to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        Utc::now() + Duration::days(30),
+    );
+    contact.endpoints = pinned_endpoints(&[ContactEndpoint::new(address, "detected")]);
+    contact.cert_fingerprint = Some(fingerprint.to_string());
+    contact
+}
+
+#[tokio::test]
+async fn test_pinned_handshake_succeeds_and_mismatch_is_rejected() {
+    let identity = TlsIdentity::generate(Utc::now()).unwrap();
+    let other = TlsIdentity::generate(Utc::now()).unwrap();
+    assert_ne!(identity.fingerprint(), other.fingerprint());
+    let (_server, address, pings) = tls_peer(Some(&identity)).await;
+
+    assert!(connect_pinned(&address, identity.fingerprint()).await.is_ok());
+    assert!(matches!(connect_pinned(&address, other.fingerprint()).await, Err(Error::Transport(_))));
+
+    let tls = TlsTransport::new(Transport::new());
+    let response = tls.send_ping(&pinned_contact(&address, identity.fingerprint()), "token").await.unwrap();
+    assert_eq!(response.uid, "server_uid");
+    assert_eq!(pings.load(Ordering::SeqCst), 1);
+
+    // A certificate other than the pinned one gets no request at all
+    let error = tls.send_ping(&pinned_contact(&address, other.fingerprint()), "token").await.unwrap_err();
+    assert!(error.to_string().contains("fingerprint"), "{}", error);
+    assert_eq!(pings.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_token_round_trip_with_fingerprint() {
+    let keypair = KeyPair::generate().unwrap();
+    let identity = TlsIdentity::generate(Utc::now()).unwrap();
+    let expiry = Utc::now() + Duration::days(1);
+    let endpoints = pinned_endpoints(&[ContactEndpoint::new("203.0.113.5:4000", "detected")]);
+    assert_eq!(
+        endpoints.iter().map(|e| e.address.as_str()).collect::<Vec<_>>(),
+        ["https://203.0.113.5:4000", "203.0.113.5:4000"]
+    );
+
+    let token = generate_pinned_token(
+        &endpoints,
+        identity.fingerprint(),
+        &keypair.public_key,
+        &keypair.private_key,
+        &keypair.x25519_public,
+        expiry,
+    )
+    .unwrap();
+    let contact = parse_contact_token(&token).unwrap();
+    assert_eq!(contact.cert_fingerprint.as_deref(), Some(identity.fingerprint()));
+    assert_eq!(contact.endpoints, endpoints);
+    // Clients without TLS support ping the plain address
+    assert_eq!(contact.ip, "203.0.113.5:4000");
+
+    // Re-signing keeps the fingerprint; tokens without one parse as before
+    let resigned = parse_contact_token(&contact.sign_token(&keypair).unwrap()).unwrap();
+    assert_eq!(resigned.cert_fingerprint, contact.cert_fingerprint);
+    let plain =
+        generate_contact_token("203.0.113.5:4000", &keypair.public_key, &keypair.private_key, &keypair.x25519_public, expiry)
+            .unwrap();
+    assert_eq!(parse_contact_token(&plain).unwrap().cert_fingerprint, None);
+
+    // Pings carry the fingerprint too
+    let ping_token = own_token_for(&keypair, "203.0.113.5:4000", Some(identity.fingerprint()), Duration::days(30), &contact).unwrap().unwrap();
+    let pinged = parse_contact_token(&ping_token).unwrap();
+    assert_eq!(pinged.cert_fingerprint.as_deref(), Some(identity.fingerprint()));
+    assert_eq!(pinged.endpoints[0].address, "https://203.0.113.5:4000");
+}
+
+#[tokio::test]
+async fn test_plain_and_tls_share_the_listener_during_transition() {
+    let identity = TlsIdentity::generate(Utc::now()).unwrap();
+    let (server, address, pings) = tls_peer(Some(&identity)).await;
+    assert!(server.serves_tls());
+
+    // Peers without our certificate keep using plain HTTP on the same port
+    let mut plain_contact = pinned_contact(&address, identity.fingerprint());
+    plain_contact.endpoints.clear();
+    plain_contact.cert_fingerprint = None;
+    assert_eq!(Transport::new().send_ping(&plain_contact, "token").await.unwrap().uid, "server_uid");
+
+    // Peers that pinned it go over TLS first
+    let client = Transport::new();
+    let registry = TransportRegistry::new()
+        .with(Arc::new(client.clone()))
+        .with(Arc::new(TlsTransport::new(client.clone())));
+    let contact = pinned_contact(&address, identity.fingerprint());
+    let tls_only = TlsTransport::new(client.clone());
+    assert!(registry.send_ping(&contact, "token").await.is_ok());
+    assert!(tls_only.send_ping(&contact, "token").await.is_ok());
+    assert_eq!(pings.load(Ordering::SeqCst), 3);
+
+    // With TLS switched off the TLS endpoint fails and the registry falls back
+    server.set_tls_identity(None).unwrap();
+    assert!(!server.serves_tls());
+    assert!(tls_only.send_ping(&contact, "token").await.is_err());
+    assert!(registry.send_ping(&contact, "token").await.is_ok());
+    assert_eq!(pings.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn test_require_tls_refuses_plain_http_beyond_the_lan() {
+    let identity = TlsIdentity::generate(Utc::now()).unwrap();
+    let external = pinned_contact("203.0.113.5:4000", identity.fingerprint());
+    let mut unpinned = external.clone();
+    unpinned.cert_fingerprint = None;
+
+    assert!(plain_http_allowed(false, &external, "203.0.113.5:4000"));
+    assert!(!plain_http_allowed(true, &external, "203.0.113.5:4000"));
+    assert!(plain_http_allowed(true, &external, "192.168.1.20:4000"));
+    assert!(plain_http_allowed(true, &external, "127.0.0.1:4000"));
+    // Contacts that never pinned a certificate have no TLS to require
+    assert!(plain_http_allowed(true, &unpinned, "203.0.113.5:4000"));
+
+    // Strict mode refuses before connecting
+    let client = Transport::new();
+    client.set_require_tls_external(true);
+    let error = client.send_ping(&external, "token").await.unwrap_err();
+    assert!(error.to_string().contains("TLS required"), "{}", error);
+    let error = client.send_message(&external, "me", "text", b"hi".to_vec()).await.unwrap_err();
+    assert!(error.to_string().contains("TLS required"), "{}", error);
+
+    // LAN peers are still reached over plain HTTP
+    let (_server, address, pings) = tls_peer(None).await;
+    let lan = pinned_contact(&address, identity.fingerprint());
+    assert_eq!(client.send_ping(&lan, "token").await.unwrap().uid, "server_uid");
+    assert_eq!(pings.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_certificate_renewal_and_rotation() {
+    let now = Utc::now();
+    let old = TlsIdentity::generate(now - Duration::days(TLS_CERT_VALIDITY_DAYS - TLS_CERT_RENEW_DAYS + 1)).unwrap();
+    assert!(old.needs_renewal(now));
+    assert!(!TlsIdentity::generate(now).unwrap().needs_renewal(now));
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    assert_eq!(app.tls_fingerprint, None);
+    app.storage.save_tls_identity(old.cert_pem(), old.key_pem()).unwrap();
+    app.app_state.settings.tls_enabled = true;
+    app.apply_tls_settings(now);
+
+    // Renewed: a new certificate is served, stored and kept on the next start
+    let renewed = app.tls_fingerprint.clone().unwrap();
+    assert_ne!(renewed, old.fingerprint());
+    assert!(app.transport.serves_tls());
+    app.apply_tls_settings(now);
+    assert_eq!(app.tls_fingerprint.as_deref(), Some(renewed.as_str()));
+
+    // A known contact's new fingerprint is taken over from its token
+    let keypair = KeyPair::generate().unwrap();
+    let mut contact = Contact::new(
+        keypair.uid.to_string(),
+        "203.0.113.5:4000".to_string(),
+        keypair.public_key.clone(),
+        keypair.x25519_public.clone(),
+        now + Duration::days(1),
+    );
+    contact.cert_fingerprint = Some(old.fingerprint().to_string());
+    app.app_state.ingest_contact(contact.clone()).unwrap();
+    contact.cert_fingerprint = Some(renewed.clone());
+    assert!(matches!(
+        app.app_state.ingest_contact_from(contact, AddressSource::ImportedToken, now).unwrap(),
+        ContactIngest::AddressUpdated
+    ));
+    assert_eq!(app.app_state.contacts[0].cert_fingerprint.as_deref(), Some(renewed.as_str()));
+
+    app.app_state.settings.tls_enabled = false;
+    app.apply_tls_settings(now);
+    assert_eq!(app.tls_fingerprint, None);
+    assert!(!app.transport.serves_tls());
+}
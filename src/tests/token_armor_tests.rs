@@ -0,0 +1,232 @@
+// Token armor tests - round trips over random tokens and bytes, single-character corruption reported on its line, the transcription alphabet, and armored vs base64 input on the Import and Share screens
+
+use crate::crypto::KeyPair;
+use crate::storage::token_armor::{armor_bytes, dearmor_bytes, ARMOR_ALPHABET, ARMOR_GROUP_CHARS, ARMOR_LINE_GROUPS};
+use crate::storage::{
+    armor_token, dearmor_token, generate_contact_token, generate_multi_endpoint_token, generate_pinned_token,
+    is_armored, parse_contact_token, token_from_input, ContactEndpoint, ARMOR_FOOTER, ARMOR_HEADER,
+};
+use crate::tui::{ImportContactScreen, ShareContactScreen};
+use chrono::{Duration, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+fn token(keypair: &KeyPair, ip: &str) -> String {
+    generate_contact_token(
+        ip,
+        &keypair.public_key,
+        &keypair.private_key,
+        &keypair.x25519_public,
+        Utc::now() + Duration::days(1),
+    )
+    .unwrap()
+}
+
+/// A token of a random shape: single, multi-endpoint or pinned, with random
+/// addresses, labels and expiries
+fn random_token(rng: &mut StdRng, round: usize) -> String {
+    let keypair = KeyPair::generate().unwrap();
+    let expiry = Utc::now() + Duration::seconds(rng.gen_range(60..90 * 86_400)) + Duration::nanoseconds(rng.gen_range(0..1_000_000_000));
+    let endpoints: Vec<ContactEndpoint> = (0..rng.gen_range(1..4))
+        .map(|i| {
+            let address = format!("10.{}.{}.{}:{}", rng.r#gen::<u8>(), rng.r#gen::<u8>(), rng.r#gen::<u8>(), rng.gen_range(1024..65535));
+            let mut endpoint = ContactEndpoint::new(&address, &format!("site-{}", i));
+            if rng.gen_bool(0.5) {
+                endpoint.valid_until = Some(expiry - Duration::hours(rng.gen_range(1..24)));
+            }
+            endpoint
+        })
+        .collect();
+    let (public, private, x25519) = (&keypair.public_key, &keypair.private_key, &keypair.x25519_public);
+    match round % 3 {
+        0 => generate_contact_token(&endpoints[0].address, public, private, x25519, expiry),
+        1 => generate_multi_endpoint_token(&endpoints, public, private, x25519, expiry),
+        _ => generate_pinned_token(&endpoints, &format!("{:064x}", rng.r#gen::<u128>()), public, private, x25519, expiry),
+    }
+    .unwrap()
+}
+
+/// Data lines of an armored token (without header, `SUM` line and footer)
+fn data_lines(armored: &str) -> Vec<&str> {
+    let lines: Vec<&str> = armored.lines().collect();
+    lines[1..lines.len() - 2].to_vec()
+}
+
+#[test]
+fn test_round_trip_random_tokens_and_bytes() {
+    let mut rng = StdRng::seed_from_u64(2481);
+    for round in 0..60 {
+        let token = random_token(&mut rng, round);
+        let armored = armor_token(&token).unwrap();
+        let restored = dearmor_token(&armored).unwrap();
+        assert_eq!(restored, token, "round {}", round);
+        assert_eq!(parse_contact_token(&restored).unwrap().uid, parse_contact_token(&token).unwrap().uid);
+    }
+
+    // Every length, including the ones ending in a partial group or line
+    for len in 0..200 {
+        let bytes: Vec<u8> = (0..len).map(|_| rng.r#gen()).collect();
+        assert_eq!(dearmor_bytes(&armor_bytes(&bytes)).unwrap(), bytes, "{} bytes", len);
+    }
+}
+
+#[test]
+fn test_typical_token_layout_fits_in_twenty_lines() {
+    let keypair = KeyPair::generate().unwrap();
+    let armored = armor_token(&token(&keypair, "192.168.100.200:51820")).unwrap();
+    let lines: Vec<&str> = armored.lines().collect();
+    assert!(lines.len() <= 20, "{} lines:\n{}", lines.len(), armored);
+    assert_eq!(lines[0], ARMOR_HEADER);
+    assert_eq!(lines[lines.len() - 1], ARMOR_FOOTER);
+    assert!(lines[lines.len() - 2].starts_with("SUM "));
+
+    // Full lines are four groups of five and one check character
+    let data = data_lines(&armored);
+    for line in &data[..data.len() - 1] {
+        let words: Vec<&str> = line.split(' ').collect();
+        assert_eq!(words.len(), ARMOR_LINE_GROUPS + 1, "{}", line);
+        assert!(words[..ARMOR_LINE_GROUPS].iter().all(|group| group.len() == ARMOR_GROUP_CHARS));
+        assert_eq!(words[ARMOR_LINE_GROUPS].len(), 1);
+    }
+}
+
+#[test]
+fn test_single_character_corruption_names_its_line() {
+    let keypair = KeyPair::generate().unwrap();
+    let armored = armor_token(&token(&keypair, "10.0.0.2:8080")).unwrap();
+    let lines: Vec<&str> = armored.lines().collect();
+
+    for (index, line) in lines.iter().enumerate().take(lines.len() - 2).skip(1) {
+        let number = index;
+        for (position, original) in line.char_indices().filter(|(_, c)| *c != ' ') {
+            // Another data character, so only the checksum can notice
+            let value = ARMOR_ALPHABET.iter().position(|&a| a as char == original).unwrap_or(0);
+            let replacement = ARMOR_ALPHABET[(value + 1 + position) % ARMOR_ALPHABET.len()] as char;
+            if replacement == original {
+                continue;
+            }
+            let mut corrupted = lines.clone();
+            let changed = format!("{}{}{}", &line[..position], replacement, &line[position + 1..]);
+            corrupted[index] = &changed;
+            let err = dearmor_token(&corrupted.join("\n")).unwrap_err().to_string();
+            assert!(
+                err.contains(&format!("line {} checksum mismatch — please re-check that line", number)),
+                "changing {:?} to {:?} on line {}: {}",
+                original,
+                replacement,
+                number,
+                err
+            );
+        }
+    }
+
+    // A character outside the alphabet is named with its line
+    let mut corrupted = lines.clone();
+    let changed = lines[2].replacen(|c: char| c.is_ascii_alphanumeric(), "U", 1);
+    corrupted[2] = &changed;
+    let err = dearmor_token(&corrupted.join("\n")).unwrap_err().to_string();
+    assert!(err.contains("line 2: 'U' is not a valid character"), "{}", err);
+
+    // A dropped line shifts the numbering of the next one
+    let mut dropped = lines.clone();
+    dropped.remove(3);
+    let err = dearmor_token(&dropped.join("\n")).unwrap_err().to_string();
+    assert!(err.contains("line 3 checksum mismatch"), "{}", err);
+
+    // A wrong SUM line is caught by the overall checksum
+    let mut corrupted = lines.clone();
+    let sum_index = lines.len() - 2;
+    let changed = if lines[sum_index].ends_with('0') {
+        format!("{}1", lines[sum_index].trim_end_matches('0'))
+    } else {
+        format!("{}0", &lines[sum_index][..lines[sum_index].len() - 1])
+    };
+    corrupted[sum_index] = &changed;
+    let err = dearmor_token(&corrupted.join("\n")).unwrap_err().to_string();
+    assert!(err.contains("overall checksum mismatch"), "{}", err);
+}
+
+#[test]
+fn test_alphabet_excludes_ambiguous_characters() {
+    let alphabet: String = ARMOR_ALPHABET.iter().map(|&c| c as char).collect();
+    assert_eq!(alphabet.len(), 32);
+    for ambiguous in ['O', 'I', 'L', 'U', 'o', 'i', 'l', 'u'] {
+        assert!(!alphabet.contains(ambiguous), "{}", ambiguous);
+    }
+
+    // Data groups only ever use the alphabet
+    let keypair = KeyPair::generate().unwrap();
+    let armored = armor_token(&token(&keypair, "10.0.0.2:8080")).unwrap();
+    for line in data_lines(&armored) {
+        let (groups, _check) = line.rsplit_once(' ').unwrap();
+        assert!(groups.chars().all(|c| c == ' ' || alphabet.contains(c)), "{}", line);
+    }
+
+    // Transcription slips between look-alikes and letter case still decode
+    let sloppy = armored.to_lowercase().replace('0', "o").replace('1', "l");
+    assert_eq!(dearmor_token(&sloppy).unwrap(), dearmor_token(&armored).unwrap());
+}
+
+#[test]
+fn test_import_detects_armored_and_base64_input() {
+    let keypair = KeyPair::generate().unwrap();
+    let token = token(&keypair, "10.0.0.2:8080");
+    let armored = armor_token(&token).unwrap();
+
+    assert!(is_armored(&armored));
+    assert!(is_armored(&format!("\n  {}\n", armored.to_lowercase())));
+    assert!(!is_armored(&token));
+    assert_eq!(token_from_input(&armored).unwrap(), token);
+    assert_eq!(token_from_input(&format!(" {}\n", token)).unwrap(), token);
+
+    // Both forms import the same contact
+    for input in [&armored, &token] {
+        let mut screen = ImportContactScreen::new();
+        screen.input = input.clone();
+        screen.parse_token();
+        assert_eq!(screen.get_contact().unwrap().uid, keypair.uid.to_string(), "{:?}", screen.status_message);
+    }
+
+    // Armored errors name the line instead of failing as base64
+    let mut lines: Vec<String> = armored.lines().map(str::to_string).collect();
+    let first = lines[2].remove(0);
+    lines[2].insert(0, if first == 'A' { 'B' } else { 'A' });
+    let mut screen = ImportContactScreen::new();
+    screen.input = lines.join("\n");
+    screen.parse_token();
+    assert!(screen.get_contact().is_none());
+    let status = screen.status_message.unwrap();
+    assert!(status.contains("line 2 checksum mismatch — please re-check that line"), "{}", status);
+    assert!(!status.contains("base64"));
+
+    // Typing it by hand: Enter continues until the footer line
+    let mut screen = ImportContactScreen::new();
+    for line in armored.lines() {
+        screen.input.push_str(line);
+        if screen.awaits_armor_lines() {
+            screen.add_char('\n');
+        }
+    }
+    assert!(!screen.awaits_armor_lines());
+    screen.parse_token();
+    assert!(screen.get_contact().is_some());
+}
+
+#[test]
+fn test_share_screen_armored_view() {
+    let keypair = KeyPair::generate().unwrap();
+    let mut screen = ShareContactScreen::new(&keypair, "10.0.0.2:8080");
+    assert_eq!(screen.shown_token(), screen.token);
+
+    screen.toggle_armored();
+    assert!(screen.shown_token().starts_with(ARMOR_HEADER));
+    assert_eq!(dearmor_token(screen.shown_token()).unwrap(), screen.token);
+
+    // Regenerating keeps the armored view in step with the token
+    screen.expiry = Utc::now() + Duration::days(3);
+    assert!(screen.regenerate_token(&keypair));
+    assert_eq!(dearmor_token(screen.shown_token()).unwrap(), screen.token);
+
+    screen.toggle_armored();
+    assert_eq!(screen.shown_token(), screen.token);
+}
@@ -0,0 +1,202 @@
+// Token validity tests - pings with short-lived tokens refused below the minimum and accepted above, the 422 mapping over HTTP and loopback, one retry with a fresh token, and the Import screen warning
+
+use crate::auto_import::AutoImportLimiter;
+use crate::crypto::KeyPair;
+use crate::storage::{generate_contact_token, parse_contact_token, AppState, Contact, ContactIngest, Storage};
+use crate::transport::{
+    ping_status_error, LoopbackNetwork, LoopbackTransport, PeerTransport, Transport, TransportRegistry,
+    TOKEN_TOO_SHORT_LIVED_STATUS,
+};
+use crate::tui::contact_import::ping_renewing_token;
+use crate::tui::App;
+use crate::Error;
+use chrono::{Duration, Utc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use super::helpers::storage_with_identity;
+
+/// Token of `sender` at `address`, valid for `valid_for`
+fn token_valid_for(sender: &KeyPair, address: &str, valid_for: Duration) -> String {
+    generate_contact_token(
+        address,
+        &sender.public_key,
+        &sender.private_key,
+        &sender.x25519_public,
+        Utc::now() + valid_for,
+    )
+    .expect("Failed to generate token")
+}
+
+fn bob() -> Contact {
+    Contact::new(
+        "bob_uid".to_string(),
+        "loopback://bob".to_string(),
+        vec![0; 32],
+        vec![0; 32],
+        Utc::now() + Duration::days(1),
+    )
+}
+
+/// Loopback receiver at "bob" applying pings to `storage`, counting them
+async fn counting_receiver(network: &LoopbackNetwork, storage: Storage) -> (LoopbackTransport, Arc<AtomicUsize>) {
+    let storage = Arc::new(Mutex::new(storage));
+    let limiter = Arc::new(Mutex::new(AutoImportLimiter::new(0, 0)));
+    let pings = Arc::new(AtomicUsize::new(0));
+    let pings_clone = pings.clone();
+    let receiver = LoopbackTransport::new(network);
+    receiver
+        .set_ping_handler(move |token| {
+            pings_clone.fetch_add(1, Ordering::SeqCst);
+            let storage = storage.lock().unwrap();
+            App::apply_incoming_ping(&storage, &token, None, &limiter, Utc::now()).map(|_| ())
+        })
+        .await;
+    receiver.start_listener("bob").await.unwrap();
+    (receiver, pings)
+}
+
+#[test]
+fn test_ping_refused_below_minimum_validity() {
+    let storage = storage_with_identity();
+    let limiter = Mutex::new(AutoImportLimiter::new(0, 0));
+    let now = Utc::now();
+
+    // Thirty seconds left: refused, nothing stored
+    let sender = KeyPair::generate().unwrap();
+    let token = token_valid_for(&sender, "10.0.0.1:8080", Duration::seconds(30));
+    let result = App::apply_incoming_ping(&storage, &token, None, &limiter, now);
+    assert!(matches!(result, Err(Error::TokenTooShortLived(_))), "{:?}", result);
+    let state = AppState::load_from_db(&storage).unwrap();
+    assert!(state.contacts.is_empty());
+    assert!(state.chats.is_empty());
+
+    // Two hours left clears the default hour
+    let token = token_valid_for(&sender, "10.0.0.1:8080", Duration::hours(2));
+    assert!(matches!(App::apply_incoming_ping(&storage, &token, None, &limiter, now).unwrap(), ContactIngest::Added));
+
+    // The minimum is a setting
+    let mut state = AppState::load_from_db(&storage).unwrap();
+    assert_eq!(state.settings.min_token_validity_minutes, 60);
+    state.settings.min_token_validity_minutes = 180;
+    state.save_to_db(&storage).unwrap();
+    let other = KeyPair::generate().unwrap();
+    let token = token_valid_for(&other, "10.0.0.2:8080", Duration::hours(2));
+    assert!(matches!(App::apply_incoming_ping(&storage, &token, None, &limiter, now), Err(Error::TokenTooShortLived(_))));
+    assert_eq!(AppState::load_from_db(&storage).unwrap().contacts.len(), 1);
+}
+
+#[tokio::test]
+async fn test_short_lived_refusal_maps_to_422() {
+    assert_eq!(TOKEN_TOO_SHORT_LIVED_STATUS, 422);
+    assert!(matches!(ping_status_error(422, "x".to_string()), Error::TokenTooShortLived(_)));
+    assert!(matches!(ping_status_error(429, "x".to_string()), Error::Transport(_)));
+    assert!(matches!(ping_status_error(500, "x".to_string()), Error::Transport(_)));
+
+    // Over HTTP, the handler's refusal reaches the sender as the same error
+    let mut receiver = Transport::new();
+    receiver.set_ping_handler(|_| Err(Error::TokenTooShortLived("too soon".to_string()))).await;
+    receiver.start("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let addr = receiver.local_addr().unwrap();
+    let contact = Contact::new("bob_uid".to_string(), addr.to_string(), vec![0; 32], vec![0; 32], Utc::now() + Duration::days(1));
+    let error = Transport::new().send_ping(&contact, "token").await.unwrap_err();
+    assert!(matches!(error, Error::TokenTooShortLived(_)), "{:?}", error);
+    assert!(error.to_string().contains("422"), "{}", error);
+
+    // Other handler errors still answer the ping
+    receiver.set_ping_handler(|_| Err(Error::Storage("disk full".to_string()))).await;
+    assert!(Transport::new().send_ping(&contact, "token").await.is_ok());
+
+    // And over loopback
+    let network = LoopbackNetwork::new();
+    let (_receiver, _) = counting_receiver(&network, storage_with_identity()).await;
+    let short = token_valid_for(&KeyPair::generate().unwrap(), "loopback://alice", Duration::minutes(5));
+    let error = LoopbackTransport::new(&network).send_ping(&bob(), &short).await.unwrap_err();
+    assert!(matches!(error, Error::TokenTooShortLived(_)), "{:?}", error);
+}
+
+#[tokio::test]
+async fn test_sender_renews_token_exactly_once() {
+    let network = LoopbackNetwork::new();
+    let (_receiver, pings) = counting_receiver(&network, storage_with_identity()).await;
+    let transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(&network)));
+    let alice = KeyPair::generate().unwrap();
+    let short = token_valid_for(&alice, "loopback://alice", Duration::minutes(5));
+    let long = token_valid_for(&alice, "loopback://alice", Duration::hours(24));
+
+    // A refused short-lived token is replaced once and the fresh one is answered
+    let renewals = AtomicUsize::new(0);
+    let fresh = || {
+        renewals.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(long.clone()))
+    };
+    let (result, sent) = ping_renewing_token(&transports, &bob(), short.clone(), fresh).await;
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(sent, long);
+    assert_eq!(renewals.load(Ordering::SeqCst), 1);
+    assert_eq!(pings.load(Ordering::SeqCst), 2);
+
+    // A fresh token refused too is not renewed again
+    let renewals = AtomicUsize::new(0);
+    let still_short = token_valid_for(&alice, "loopback://alice", Duration::minutes(10));
+    let fresh = || {
+        renewals.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(still_short.clone()))
+    };
+    let (result, sent) = ping_renewing_token(&transports, &bob(), short.clone(), fresh).await;
+    assert!(matches!(result, Err(Error::TokenTooShortLived(_))));
+    assert_eq!(sent, still_short);
+    assert_eq!(renewals.load(Ordering::SeqCst), 1);
+    assert_eq!(pings.load(Ordering::SeqCst), 4);
+
+    // An accepted token, or any other failure, is never renewed
+    let renewals = AtomicUsize::new(0);
+    let fresh = || {
+        renewals.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(long.clone()))
+    };
+    let (result, _) = ping_renewing_token(&transports, &bob(), long.clone(), fresh).await;
+    assert!(result.is_ok());
+    let mut nobody = bob();
+    nobody.ip = "loopback://nobody".to_string();
+    let (result, sent) = ping_renewing_token(&transports, &nobody, short.clone(), || Ok(Some(long.clone()))).await;
+    assert!(result.is_err());
+    assert_eq!(sent, short);
+    assert_eq!(renewals.load(Ordering::SeqCst), 0);
+    assert_eq!(pings.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn test_import_screen_warns_about_short_lived_token() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    let network = LoopbackNetwork::new();
+    app.transports = TransportRegistry::new().with(Arc::new(LoopbackTransport::new(&network)));
+
+    // Twenty minutes left: imported anyway, with a warning
+    let carol = KeyPair::generate().unwrap();
+    app.show_import_contact_screen();
+    let screen = app.import_contact_screen.as_mut().unwrap();
+    screen.input = token_valid_for(&carol, "loopback://carol", Duration::minutes(20));
+    screen.parse_token();
+    let contact = screen.get_contact().cloned().unwrap();
+    app.import_contact(contact);
+    let screen = app.import_contact_screen.as_ref().unwrap();
+    let warning = screen.expiry_warning.as_deref().unwrap();
+    assert!(warning.starts_with("Token expires in 19m") || warning.starts_with("Token expires in 20m"), "{}", warning);
+    assert!(!screen.is_error);
+    assert!(screen.status_message.as_deref().unwrap().contains("Warning: Token expires in"));
+    assert!(app.app_state.contact_by_uid(&carol.uid.to_string()).is_some());
+
+    // A day left: no warning, and parsing another token clears it
+    let dave = KeyPair::generate().unwrap();
+    let screen = app.import_contact_screen.as_mut().unwrap();
+    screen.input = token_valid_for(&dave, "loopback://dave", Duration::days(1));
+    screen.parse_token();
+    assert!(screen.expiry_warning.is_none());
+    let contact = parse_contact_token(&screen.input).unwrap();
+    app.import_contact(contact);
+    let screen = app.import_contact_screen.as_ref().unwrap();
+    assert!(screen.expiry_warning.is_none());
+    assert!(!screen.status_message.as_deref().unwrap().contains("Warning"));
+}
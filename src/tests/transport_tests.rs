@@ -9,6 +9,7 @@ use hyper::{Method, Request, StatusCode};
 use hyper_util::client::legacy::Client;
 use tokio::sync::Mutex;
 use std::sync::Arc;
+use crate::storage::{MessageMetadata, MetadataValue};
 
 #[test]
 fn test_transport_creation() {
@@ -516,6 +517,8 @@ fn test_message_request_creation() {
         from_uid: "sender_uid".to_string(),
         message_type: "text".to_string(),
         payload: b"Hello, world!".to_vec(),
+        metadata: MessageMetadata::new(),
+        message_id: None,
     };
 
     assert_eq!(msg_req.from_uid, "sender_uid");
@@ -529,6 +532,8 @@ fn test_message_request_serialization() {
         from_uid: "alice_uid".to_string(),
         message_type: "text".to_string(),
         payload: vec![1, 2, 3, 4, 5],
+        metadata: MessageMetadata::new(),
+        message_id: None,
     };
 
     // Serialize to CBOR
@@ -552,6 +557,7 @@ async fn test_set_new_message_handler() {
 
     transport.set_new_message_handler(move |_msg| {
         called_clone.store(true, Ordering::SeqCst);
+        Ok(())
     }).await;
 
     // Verify handler is set
@@ -564,8 +570,10 @@ async fn test_set_new_message_handler() {
             from_uid: "test".to_string(),
             message_type: "text".to_string(),
             payload: vec![],
+            metadata: MessageMetadata::new(),
+            message_id: None,
         };
-        handler(test_msg);
+        handler(test_msg, None).expect("Handler failed");
     }
 
     assert!(called.load(Ordering::SeqCst));
@@ -586,6 +594,7 @@ async fn test_message_endpoint() {
         assert_eq!(msg.message_type, "text");
         assert_eq!(msg.payload, b"Test message");
         received_clone.store(true, Ordering::SeqCst);
+        Ok(())
     }).await;
 
     // Start transport
@@ -630,6 +639,7 @@ async fn test_message_endpoint_different_types() {
 
     transport.set_new_message_handler(move |msg| {
         received_clone.lock().unwrap().push((msg.message_type.clone(), msg.payload.clone()));
+        Ok(())
     }).await;
 
     // Start transport
@@ -701,10 +711,12 @@ async fn test_message_bidirectional() {
 
     transport1.set_new_message_handler(move |msg| {
         m1.lock().unwrap().push(msg);
+        Ok(())
     }).await;
 
     transport2.set_new_message_handler(move |msg| {
         m2.lock().unwrap().push(msg);
+        Ok(())
     }).await;
 
     transport1.start("127.0.0.1:0".parse().unwrap()).await.expect("Failed to start t1");
@@ -739,6 +751,138 @@ async fn test_message_bidirectional() {
     assert_eq!(msgs2[0].payload, b"Hello from 1");
 }
 
+#[tokio::test]
+async fn test_message_metadata_roundtrip_between_peers() {
+    use std::sync::{Arc, Mutex as StdMutex};
+    use crate::storage::Contact;
+    use chrono::{Utc, Duration as ChronoDuration};
+
+    let transport1 = Transport::new();
+    let mut transport2 = Transport::new();
+
+    let received = Arc::new(StdMutex::new(Vec::new()));
+    let received_clone = received.clone();
+    transport2.set_new_message_handler(move |msg| {
+        received_clone.lock().unwrap().push(msg);
+        Ok(())
+    }).await;
+
+    transport2.start("127.0.0.1:0".parse().unwrap()).await.expect("Failed to start t2");
+    sleep(Duration::from_millis(100)).await;
+
+    let addr2 = transport2.local_addr().unwrap();
+    let contact2 = Contact::new("peer2".to_string(), addr2.to_string(), vec![2], vec![99u8; 32], Utc::now() + ChronoDuration::days(30));
+
+    let mut metadata = MessageMetadata::new();
+    metadata.insert("ticket".to_string(), MetadataValue::from("T-1234"));
+    metadata.insert("priority".to_string(), MetadataValue::from(3i64));
+    metadata.insert("lat".to_string(), MetadataValue::from(52.52));
+    metadata.insert("urgent".to_string(), MetadataValue::from(true));
+
+    transport1
+        .send_message_with_metadata(&contact2, "peer1", "text", b"Ticket update".to_vec(), metadata.clone())
+        .await
+        .expect("Send with metadata failed");
+
+    sleep(Duration::from_millis(200)).await;
+
+    let msgs = received.lock().unwrap();
+    assert_eq!(msgs.len(), 1);
+    assert_eq!(msgs[0].payload, b"Ticket update");
+    assert_eq!(msgs[0].metadata, metadata);
+}
+
+#[tokio::test]
+async fn test_send_message_with_invalid_metadata_rejected() {
+    use crate::storage::Contact;
+    use chrono::{Utc, Duration as ChronoDuration};
+
+    let transport = Transport::new();
+    let contact = Contact::new("peer".to_string(), "127.0.0.1:9".to_string(), vec![1], vec![99u8; 32], Utc::now() + ChronoDuration::days(30));
+
+    let mut metadata = MessageMetadata::new();
+    metadata.insert("blob".to_string(), MetadataValue::from("x".repeat(3000)));
+
+    let result = transport
+        .send_message_with_metadata(&contact, "me", "text", b"hi".to_vec(), metadata)
+        .await;
+    assert!(matches!(result, Err(crate::Error::InvalidMetadata(_))));
+}
+
+#[tokio::test]
+async fn test_message_endpoint_rejects_invalid_metadata() {
+    let mut transport = Transport::new();
+    transport.set_new_message_handler(|_msg| {
+        panic!("Handler must not be called for invalid metadata");
+    }).await;
+    transport.start("127.0.0.1:0".parse().unwrap()).await.expect("Failed to start transport");
+    let local_addr = transport.local_addr().unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    // Bypass sender-side validation by encoding the request directly
+    let mut metadata = MessageMetadata::new();
+    metadata.insert(String::new(), MetadataValue::from(1i64));
+    let msg_req = MessageRequest {
+        from_uid: "sender".to_string(),
+        message_type: "text".to_string(),
+        payload: vec![1],
+        metadata,
+        message_id: None,
+    };
+    let cbor = serde_cbor::to_vec(&msg_req).expect("Failed to serialize");
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build_http();
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("http://{}/message", local_addr))
+        .body(Full::new(Bytes::from(cbor)))
+        .expect("Failed to build request");
+
+    let response = client.request(req).await.expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_message_request_metadata_wire_compatibility() {
+    use serde::{Deserialize, Serialize};
+
+    // Request shape used by peers that predate metadata
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct LegacyMessageRequest {
+        from_uid: String,
+        message_type: String,
+        payload: Vec<u8>,
+    }
+
+    let legacy = LegacyMessageRequest {
+        from_uid: "old_peer".to_string(),
+        message_type: "text".to_string(),
+        payload: vec![1, 2, 3],
+    };
+
+    // New peers accept requests from old peers
+    let cbor = serde_cbor::to_vec(&legacy).unwrap();
+    let decoded: MessageRequest = serde_cbor::from_slice(&cbor).unwrap();
+    assert!(decoded.metadata.is_empty());
+
+    // Without metadata, new peers send exactly what old peers expect
+    let plain = MessageRequest {
+        from_uid: "old_peer".to_string(),
+        message_type: "text".to_string(),
+        payload: vec![1, 2, 3],
+        metadata: MessageMetadata::new(),
+        message_id: None,
+    };
+    assert_eq!(serde_cbor::to_vec(&plain).unwrap(), cbor);
+
+    // Old peers ignore metadata they don't understand
+    let mut with_metadata = plain.clone();
+    with_metadata.metadata.insert("ticket".to_string(), MetadataValue::from("T-1"));
+    let cbor = serde_cbor::to_vec(&with_metadata).unwrap();
+    let decoded: LegacyMessageRequest = serde_cbor::from_slice(&cbor).unwrap();
+    assert_eq!(decoded, legacy);
+}
+
 // ============================================================================
 // Health Endpoint Tests
 // ============================================================================
@@ -898,6 +1042,7 @@ async fn test_health_endpoint_with_other_endpoints() {
             let mut msgs = messages.lock().await;
             msgs.push(msg);
         });
+        Ok(())
     }).await;
 
     // Give server time to start
@@ -922,6 +1067,8 @@ async fn test_health_endpoint_with_other_endpoints() {
         from_uid: "sender".to_string(),
         message_type: "text".to_string(),
         payload: b"test message".to_vec(),
+        metadata: MessageMetadata::new(),
+        message_id: None,
     };
 
     let cbor_data = serde_cbor::to_vec(&msg_req).unwrap();
@@ -1017,9 +1164,25 @@ async fn test_send_ping_logs_successful_request() {
         uid: keypair.uid.to_string(),
         ip: format!("127.0.0.1:{}", port),
         pubkey: keypair.public_key.clone(),
-        x25519_pubkey: keypair.x25519_public.clone(),
+        x25519_pubkey: Some(keypair.x25519_public.clone()),
         expiry,
         is_active: true,
+        notes: String::new(),
+        endpoints: Vec::new(),
+        is_relay: false,
+        relay_reachable: Vec::new(),
+        verified: false,
+        privacy: Default::default(),
+        supports_edits: false,
+        ephemeral: None,
+        trust: Default::default(),
+        requested_expiry: None,
+        cert_fingerprint: None,
+        capabilities: None,
+        alert_style: None,
+        utc_offset_override: None,
+        offset_samples: Vec::new(),
+        heard_at: None,
     };
 
     // Send ping (this should log to database)
@@ -1046,9 +1209,25 @@ async fn test_send_ping_logs_failed_request() {
         uid: keypair.uid.to_string(),
         ip: "127.0.0.1:9999".to_string(), // No server listening here
         pubkey: keypair.public_key.clone(),
-        x25519_pubkey: keypair.x25519_public.clone(),
+        x25519_pubkey: Some(keypair.x25519_public.clone()),
         expiry,
         is_active: true,
+        notes: String::new(),
+        endpoints: Vec::new(),
+        is_relay: false,
+        relay_reachable: Vec::new(),
+        verified: false,
+        privacy: Default::default(),
+        supports_edits: false,
+        ephemeral: None,
+        trust: Default::default(),
+        requested_expiry: None,
+        cert_fingerprint: None,
+        capabilities: None,
+        alert_style: None,
+        utc_offset_override: None,
+        offset_samples: Vec::new(),
+        heard_at: None,
     };
 
     // Send ping to unreachable address (this should log failure)
@@ -1106,9 +1285,25 @@ async fn test_send_message_logs_successful_request() {
         uid: keypair.uid.to_string(),
         ip: format!("127.0.0.1:{}", port),
         pubkey: keypair.public_key.clone(),
-        x25519_pubkey: keypair.x25519_public.clone(),
+        x25519_pubkey: Some(keypair.x25519_public.clone()),
         expiry,
         is_active: true,
+        notes: String::new(),
+        endpoints: Vec::new(),
+        is_relay: false,
+        relay_reachable: Vec::new(),
+        verified: false,
+        privacy: Default::default(),
+        supports_edits: false,
+        ephemeral: None,
+        trust: Default::default(),
+        requested_expiry: None,
+        cert_fingerprint: None,
+        capabilities: None,
+        alert_style: None,
+        utc_offset_override: None,
+        offset_samples: Vec::new(),
+        heard_at: None,
     };
 
     // Send message (this should log to database)
@@ -1131,9 +1326,25 @@ async fn test_send_message_logs_failed_request() {
         uid: keypair.uid.to_string(),
         ip: "127.0.0.1:9998".to_string(), // No server listening
         pubkey: keypair.public_key.clone(),
-        x25519_pubkey: keypair.x25519_public.clone(),
+        x25519_pubkey: Some(keypair.x25519_public.clone()),
         expiry,
         is_active: true,
+        notes: String::new(),
+        endpoints: Vec::new(),
+        is_relay: false,
+        relay_reachable: Vec::new(),
+        verified: false,
+        privacy: Default::default(),
+        supports_edits: false,
+        ephemeral: None,
+        trust: Default::default(),
+        requested_expiry: None,
+        cert_fingerprint: None,
+        capabilities: None,
+        alert_style: None,
+        utc_offset_override: None,
+        offset_samples: Vec::new(),
+        heard_at: None,
     };
 
     // Send message to unreachable address (this should log failure)
@@ -1162,6 +1373,7 @@ async fn test_incoming_ping_logs_request() {
         tokio::spawn(async move {
             tokens.lock().await.push(token);
         });
+        Ok(())
     }).await;
 
     // Start transport server using the existing start method
@@ -1172,18 +1384,19 @@ async fn test_incoming_ping_logs_request() {
     // Clone handlers for the server task
     let msg_handler = Arc::new(Mutex::new(None::<MessageHandler>));
     let new_msg_handler = Arc::new(Mutex::new(None::<NewMessageHandler>));
-    let ping_h = Arc::new(Mutex::new(Some(Arc::new(move |token: String| {
+    let ping_h = Arc::new(Mutex::new(Some(Arc::new(move |token: String, _source| {
         let tokens = received_tokens.clone();
         tokio::spawn(async move {
             tokens.lock().await.push(token);
         });
-    }) as Arc<dyn Fn(String) + Send + Sync>)));
+        Ok(())
+    }) as PingHandler)));
     let uid = Arc::new(Mutex::new(Some("test_uid".to_string())));
 
     // Spawn server
     tokio::spawn(async move {
         loop {
-            if let Ok((stream, _)) = listener.accept().await {
+            if let Ok((stream, remote_addr)) = listener.accept().await {
                 let io = hyper_util::rt::TokioIo::new(stream);
                 let msg_h = msg_handler.clone();
                 let new_msg_h = new_msg_handler.clone();
@@ -1198,7 +1411,7 @@ async fn test_incoming_ping_logs_request() {
                         let u = u.clone();
 
                         async move {
-                            crate::transport::handle_request(req, msg_h, new_msg_h, p_h, u).await
+                            crate::transport::handle_request(req, msg_h, new_msg_h, p_h, u, remote_addr.ip()).await
                         }
                     });
 
@@ -1244,3 +1457,124 @@ async fn test_incoming_ping_logs_request() {
     // Note: Incoming ping is also logged to ./app_data/pure2p.db
     // We verify the code compiles and executes without error
 }
+
+#[tokio::test]
+async fn test_message_endpoint_returns_503_when_database_locked() {
+    use crate::storage::{AppState, BusyPolicy, Storage};
+
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let db_path = temp_dir.path().join("pure2p.db");
+    Storage::new(&db_path).expect("Failed to create database");
+
+    // Handler stores the message the way the TUI does
+    let handler_db_path = db_path.clone();
+    let mut transport = Transport::new();
+    transport.set_new_message_handler(move |msg| {
+        let mut storage = Storage::new(&handler_db_path)?;
+        storage.set_busy_policy(BusyPolicy {
+            busy_timeout: std::time::Duration::from_millis(10),
+            max_retries: 1,
+            base_delay: std::time::Duration::from_millis(5),
+        })?;
+        let mut state = AppState::load_from_db(&storage)?;
+        state.contacts.retain(|c| c.uid != msg.from_uid);
+        state.save_to_db(&storage)
+    }).await;
+    transport.start("127.0.0.1:0".parse().unwrap()).await.expect("Failed to start transport");
+    let local_addr = transport.local_addr().unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    let msg_req = MessageRequest {
+        from_uid: "sender".to_string(),
+        message_type: "text".to_string(),
+        payload: vec![1],
+        metadata: MessageMetadata::new(),
+        message_id: None,
+    };
+    let cbor = serde_cbor::to_vec(&msg_req).expect("Failed to serialize");
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build_http();
+    let post = |body: Vec<u8>| {
+        Request::builder()
+            .method(Method::POST)
+            .uri(format!("http://{}/message", local_addr))
+            .body(Full::new(Bytes::from(body)))
+            .expect("Failed to build request")
+    };
+
+    // Another process holds the database lock: the sender must retry later
+    let lock = rusqlite::Connection::open(&db_path).expect("Failed to open lock connection");
+    lock.execute_batch("BEGIN EXCLUSIVE").expect("Failed to lock database");
+    let response = client.request(post(cbor.clone())).await.expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // After the lock is released the same message is accepted
+    lock.execute_batch("COMMIT").expect("Failed to release lock");
+    let response = client.request(post(cbor)).await.expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_send_message_tries_endpoints_in_order_and_learns() {
+    use crate::storage::{Contact, ContactEndpoint};
+
+    // Stub listener that refuses messages (503) and one that accepts them
+    async fn stub(accept: bool, calls: Arc<AtomicUsize>) -> Transport {
+        let mut transport = Transport::new();
+        transport.set_new_message_handler(move |_msg| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            if accept {
+                Ok(())
+            } else {
+                Err(crate::Error::Storage("unavailable".to_string()))
+            }
+        }).await;
+        transport.start("127.0.0.1:0".parse().unwrap()).await.expect("Failed to start transport");
+        transport
+    }
+
+    let refused_calls = Arc::new(AtomicUsize::new(0));
+    let accepted_calls = Arc::new(AtomicUsize::new(0));
+    let refusing = stub(false, refused_calls.clone()).await;
+    let accepting = stub(true, accepted_calls.clone()).await;
+    sleep(Duration::from_millis(100)).await;
+
+    // Nothing listens on the first endpoint
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+    let refusing_addr = refusing.local_addr().unwrap().to_string();
+    let accepting_addr = accepting.local_addr().unwrap().to_string();
+
+    let mut contact = Contact::new(
+        "multi_uid".to_string(),
+        closed.clone(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        chrono::Utc::now() + chrono::Duration::days(1),
+    );
+    contact.endpoints = vec![
+        ContactEndpoint::new(&closed, "vpn"),
+        ContactEndpoint::new(&refusing_addr, "office"),
+        ContactEndpoint::new(&accepting_addr, "home"),
+    ];
+
+    let sender = Transport::new();
+    assert!(sender.learned_endpoint("multi_uid").await.is_none());
+
+    // First send walks the list in advertised order
+    sender.send_message(&contact, "me", "text", b"one".to_vec()).await
+        .expect("Send should succeed on the last endpoint");
+    assert_eq!(refused_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(accepted_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(sender.learned_endpoint("multi_uid").await, Some(accepting_addr.clone()));
+
+    // Second send goes straight to the learned endpoint
+    sender.send_message(&contact, "me", "text", b"two".to_vec()).await
+        .expect("Send should succeed on the learned endpoint");
+    assert_eq!(refused_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(accepted_calls.load(Ordering::SeqCst), 2);
+
+    // Without the working endpoint every attempt fails and the error surfaces
+    contact.endpoints.pop();
+    let result = sender.send_message(&contact, "me", "text", b"three".to_vec()).await;
+    assert!(result.is_err());
+    assert_eq!(refused_calls.load(Ordering::SeqCst), 2);
+}
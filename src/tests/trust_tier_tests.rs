@@ -0,0 +1,218 @@
+// Trust tier tests - LAN address withheld from ping tokens and capabilities minimized for restricted contacts (captured on loopback peers), forced-off signals, refused introductions, tier persistence, normal contacts unaffected
+
+use crate::crypto::KeyPair;
+use crate::relay::{relay_candidates, relay_uid_hash, Relay, RelayCapabilities, RelayEnvelope, RelayRefusal, RELAY_CAPABILITIES_TYPE};
+use crate::signals::send_read_receipt;
+use crate::storage::{
+    generate_contact_token, is_lan_address, parse_contact_token, signal_enabled, AppState, Contact, OutboundPolicy,
+    PrivacySignal, Settings, SignalOverride, Storage, TrustTier,
+};
+use crate::transport::{LoopbackNetwork, LoopbackTransport, MessageRequest};
+use crate::tui::{App, ContactDetailsPopup, PingDispatch};
+use chrono::{Duration, Utc};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use super::helpers::{app_through, contact_at, recording_peer, settle};
+
+/// Like `recording_peer`, also recording the tokens of the pings it receives
+async fn recording_peer_with_pings(
+    network: &LoopbackNetwork,
+    name: &str,
+) -> (LoopbackTransport, Arc<Mutex<Vec<String>>>, Arc<Mutex<Vec<MessageRequest>>>) {
+    let (peer, messages) = recording_peer(network, name).await;
+    let tokens = Arc::new(Mutex::new(Vec::new()));
+    let tokens_clone = tokens.clone();
+    peer.set_ping_handler(move |token| {
+        tokens_clone.lock().unwrap().push(token);
+        Ok(())
+    })
+    .await;
+    (peer, tokens, messages)
+}
+
+/// `contact` moved to the restricted tier
+fn restricted(mut contact: Contact) -> Contact {
+    contact.trust = TrustTier::Restricted;
+    contact
+}
+
+fn token(keypair: &KeyPair, address: &str) -> String {
+    generate_contact_token(address, &keypair.public_key, &keypair.private_key, &keypair.x25519_public, Utc::now() + Duration::days(30))
+        .unwrap()
+}
+
+/// Import the contacts in `text` through the batch screen with the trust toggle at `trust`
+/// and wait for their ping results
+fn import_batch(app: &mut App, text: &str, trust: TrustTier) -> Vec<Option<PingDispatch>> {
+    app.show_import_contact_screen();
+    app.toggle_batch_import();
+    let screen = app.import_contact_screen.as_mut().unwrap();
+    screen.input = text.to_string();
+    if screen.trust != trust {
+        screen.toggle_trust();
+    }
+    app.review_token_batch();
+    app.confirm_batch_import();
+    for _ in 0..50 {
+        app.poll_batch_import();
+        let batch = app.import_contact_screen.as_ref().unwrap().batch.as_ref().unwrap();
+        let pings: Vec<_> = batch.report.as_ref().unwrap().iter().map(|e| e.ping.clone()).collect();
+        if pings.iter().all(|p| *p != Some(PingDispatch::Pending)) {
+            return pings;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    panic!("Ping results did not arrive");
+}
+
+#[test]
+fn test_restricted_ping_tokens_omit_lan_address() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (_bob_peer, bob_tokens, _) = rt.block_on(recording_peer_with_pings(&network, "bob"));
+    let (_carol_peer, carol_tokens, _) = rt.block_on(recording_peer_with_pings(&network, "carol"));
+    let (_dave_peer, dave_tokens, _) = rt.block_on(recording_peer_with_pings(&network, "dave"));
+    let (bob, carol, dave) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    assert!(is_lan_address("192.168.1.20:8080") && is_lan_address("[fe80::1]:8080") && is_lan_address("10.0.0.1"));
+    assert!(!is_lan_address("203.0.113.7:4000") && !is_lan_address("loopback://bob"));
+
+    // Only a LAN address known: the restricted contact gets no token at all
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_through(&temp_dir, LoopbackTransport::new(&network));
+    app.local_ip = "192.168.1.20:8080".to_string();
+    let pings = import_batch(&mut app, &token(&bob, "loopback://bob"), TrustTier::Restricted);
+    assert!(matches!(&pings[0], Some(PingDispatch::Failed(reason)) if reason.contains("restricted")));
+    assert_eq!(app.app_state.contact_by_uid(&bob.uid.to_string()).unwrap().trust, TrustTier::Restricted);
+    settle();
+    assert!(bob_tokens.lock().unwrap().is_empty());
+
+    // A normal contact still gets the LAN address
+    let pings = import_batch(&mut app, &token(&dave, "loopback://dave"), TrustTier::Normal);
+    assert_eq!(pings, vec![Some(PingDispatch::Answered)]);
+    let sent = parse_contact_token(&dave_tokens.lock().unwrap()[0]).unwrap();
+    assert_eq!(sent.ip, "192.168.1.20:8080");
+
+    // With a public address, the restricted contact's token carries only that
+    app.local_ip = "203.0.113.7:4000".to_string();
+    let pings = import_batch(&mut app, &token(&carol, "loopback://carol"), TrustTier::Restricted);
+    assert_eq!(pings, vec![Some(PingDispatch::Answered)]);
+    let sent = parse_contact_token(&carol_tokens.lock().unwrap()[0]).unwrap();
+    assert_eq!((sent.uid.as_str(), sent.ip.as_str()), (app.keypair.uid.to_string().as_str(), "203.0.113.7:4000"));
+    assert!(sent.endpoints.is_empty());
+    assert_eq!(sent.x25519_pubkey.as_deref(), Some(app.keypair.x25519_public.as_slice()));
+    assert_eq!(sent.trust, TrustTier::Normal, "The tier is local and never on the wire");
+}
+
+#[test]
+fn test_restricted_contacts_get_no_capabilities_and_no_introductions() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (_alice_peer, _, alice_received) = rt.block_on(recording_peer_with_pings(&network, "alice"));
+    let (_bob_peer, _, bob_received) = rt.block_on(recording_peer_with_pings(&network, "bob"));
+    let (alice, bob) = (KeyPair::generate().unwrap(), KeyPair::generate().unwrap());
+    let (alice_uid, bob_uid) = (alice.uid.to_string(), bob.uid.to_string());
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_through(&temp_dir, LoopbackTransport::new(&network));
+    app.local_ip = "203.0.113.7:4000".to_string();
+    app.app_state.add_contact(contact_at(&alice, "loopback://alice"));
+    app.app_state.add_contact(restricted(contact_at(&bob, "loopback://bob")));
+    app.app_state.settings.relay_enabled = true;
+
+    // Bob learns nothing, not even edit support; alice gets the full offer without bob
+    app.advertise_relay_capabilities();
+    settle();
+    let capabilities = |received: &Arc<Mutex<Vec<MessageRequest>>>| {
+        let received = received.lock().unwrap();
+        let request = received.iter().find(|r| r.message_type == RELAY_CAPABILITIES_TYPE).unwrap();
+        RelayCapabilities::from_payload(&request.payload).unwrap()
+    };
+    assert_eq!(capabilities(&bob_received), RelayCapabilities::default());
+    let to_alice = capabilities(&alice_received);
+    assert!(to_alice.edits && to_alice.accepts_relay);
+    assert!(!to_alice.reachable.contains(&relay_uid_hash(&bob_uid)));
+
+    // Our relay carries nothing from or to him
+    let mut relay = Relay::new();
+    relay.set_enabled(true);
+    relay.set_contacts(&app.app_state.contacts);
+    let request = |from: &str| MessageRequest {
+        from_uid: from.to_string(),
+        message_type: "text".to_string(),
+        payload: vec![],
+        metadata: Default::default(),
+        message_id: None,
+    };
+    let now = Utc::now();
+    assert_eq!(relay.accept(RelayEnvelope::new(&alice_uid, request(&bob_uid)), now), Err(RelayRefusal::UnknownPeer));
+    assert_eq!(relay.accept(RelayEnvelope::new(&bob_uid, request(&alice_uid)), now), Err(RelayRefusal::UnknownPeer));
+
+    // And we never route through him, even when he offers
+    let bob_contact = app.app_state.contact_by_uid_mut(&bob_uid).unwrap();
+    bob_contact.is_relay = true;
+    bob_contact.relay_reachable = vec![relay_uid_hash(&alice_uid)];
+    assert!(relay_candidates(&app.app_state.contacts, &alice_uid).is_empty());
+
+    // Back to normal, he is a relay candidate again
+    app.app_state.contact_by_uid_mut(&bob_uid).unwrap().trust = TrustTier::Normal;
+    assert_eq!(relay_candidates(&app.app_state.contacts, &alice_uid).len(), 1);
+}
+
+#[test]
+fn test_restricted_signals_forced_off() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let network = LoopbackNetwork::new();
+    let (_peer, _, received) = rt.block_on(recording_peer_with_pings(&network, "bob"));
+    let bob = KeyPair::generate().unwrap();
+    let settings = Settings { send_read_receipts: true, send_typing: true, send_presence: true, ..Settings::default() };
+
+    let mut restricted = restricted(contact_at(&bob, "loopback://bob"));
+    for signal in PrivacySignal::ALL {
+        *restricted.privacy.get_mut(signal) = SignalOverride::On;
+    }
+    let mut normal = restricted.clone();
+    normal.trust = TrustTier::Normal;
+    for signal in PrivacySignal::ALL {
+        assert!(!signal_enabled(&settings, &restricted, signal), "{:?}", signal);
+        assert!(signal_enabled(&settings, &normal, signal), "{:?}", signal);
+    }
+    assert!(OutboundPolicy::for_contact(&restricted).is_minimized());
+
+    // Nothing reaches the wire
+    let transport = LoopbackTransport::new(&network);
+    assert!(!rt.block_on(send_read_receipt(&transport, &settings, &restricted, "me", Utc::now())).unwrap());
+    assert!(received.lock().unwrap().is_empty());
+    assert!(rt.block_on(send_read_receipt(&transport, &settings, &normal, "me", Utc::now())).unwrap());
+    assert_eq!(received.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn test_trust_tier_edit_persists() {
+    let network = LoopbackNetwork::new();
+    let bob = KeyPair::generate().unwrap();
+    let bob_uid = bob.uid.to_string();
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_through(&temp_dir, LoopbackTransport::new(&network));
+    app.local_ip = "192.168.1.20:8080".to_string();
+    app.app_state.add_contact(contact_at(&bob, "loopback://bob"));
+    app.app_state.get_or_create_chat(&bob_uid);
+    app.save_state().unwrap();
+
+    app.show_chat_list_screen();
+    app.chat_list_screen.as_mut().unwrap().contact_details = Some(ContactDetailsPopup::new(bob_uid.clone()));
+    app.toggle_contact_trust();
+    let reloaded = AppState::load_from_db(&app.storage).unwrap();
+    assert_eq!(reloaded.contact_by_uid(&bob_uid).unwrap().trust, TrustTier::Restricted);
+    assert!(app.chat_list_screen.as_ref().unwrap().status_message.as_deref().unwrap().contains("restricted"));
+
+    app.toggle_contact_trust();
+    let reloaded = AppState::load_from_db(&app.storage).unwrap();
+    assert_eq!(reloaded.contact_by_uid(&bob_uid).unwrap().trust, TrustTier::Normal);
+
+    // Stored as a plain column
+    let storage = Storage::new_in_memory().unwrap();
+    storage.save_contact(&restricted(contact_at(&bob, "10.0.0.2:8080"))).unwrap();
+    assert_eq!(storage.load_contacts().unwrap()[0].trust, TrustTier::Restricted);
+    assert_eq!(TrustTier::parse("restricted"), TrustTier::Restricted);
+    assert_eq!(TrustTier::parse("something else"), TrustTier::Normal);
+}
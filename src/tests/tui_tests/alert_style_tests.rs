@@ -0,0 +1,158 @@
+// Alert Style Tests - Per-contact alert styles over the global default, the notification sink, test-play and persistence
+
+use crate::storage::{AlertMode, AlertStyle, Contact, Settings, Storage};
+use crate::tui::alerts::{resolve_alert_style, AlertSignal, NotificationSink, TerminalSink, URGENT_BELLS, URGENT_BELL_GAP};
+use crate::tui::{App, UiEffect};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Alerts played so far, in order
+type Played = Arc<Mutex<Vec<(AlertStyle, AlertMode)>>>;
+
+/// Sink that records what it was asked to play
+#[derive(Debug, Default)]
+struct RecordingSink {
+    played: Played,
+}
+
+impl NotificationSink for RecordingSink {
+    fn signal(&mut self, style: AlertStyle, mode: AlertMode) -> AlertSignal {
+        self.played.lock().unwrap().push((style, mode));
+        AlertSignal::default()
+    }
+}
+
+fn contact(uid: &str) -> Contact {
+    Contact::new(
+        uid.to_string(),
+        "192.168.1.100:8080".to_string(),
+        vec![1; 32],
+        vec![2; 32],
+        chrono::Utc::now() + chrono::Duration::days(30),
+    )
+}
+
+/// App with Bob's chat selected on the chat list and his details popup open
+fn app_with_details(temp_dir: &TempDir) -> (App, Played) {
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.contacts.push(contact("bob"));
+    app.app_state.get_or_create_chat("bob");
+    let sink = RecordingSink::default();
+    let played = sink.played.clone();
+    app.notification_sink = Box::new(sink);
+    app.show_chat_list_screen();
+    app.show_contact_details();
+    (app, played)
+}
+
+#[test]
+fn test_style_resolution_precedence() {
+    // The contact's style wins over the global default
+    assert_eq!(resolve_alert_style(None, AlertStyle::Subtle, false, false), AlertStyle::Subtle);
+    assert_eq!(resolve_alert_style(Some(AlertStyle::Urgent), AlertStyle::Subtle, false, false), AlertStyle::Urgent);
+    assert_eq!(resolve_alert_style(Some(AlertStyle::None), AlertStyle::Urgent, false, false), AlertStyle::None);
+
+    // Mute and quiet hours win over both
+    assert_eq!(resolve_alert_style(Some(AlertStyle::Urgent), AlertStyle::Urgent, true, false), AlertStyle::None);
+    assert_eq!(resolve_alert_style(Some(AlertStyle::Urgent), AlertStyle::Default, false, true), AlertStyle::None);
+
+    // Cycling a contact's override ends back on the global default
+    let mut style = None;
+    let mut seen = Vec::new();
+    for _ in 0..AlertStyle::ALL.len() + 1 {
+        style = AlertStyle::cycle_override(style);
+        seen.push(style);
+    }
+    assert_eq!(seen.last(), Some(&None));
+    assert!(AlertStyle::ALL.iter().all(|s| seen.contains(&Some(*s))));
+}
+
+#[test]
+fn test_terminal_sink_signals() {
+    let mut sink = TerminalSink;
+    let urgent = sink.signal(AlertStyle::Urgent, AlertMode::Both);
+    assert_eq!(urgent.bells.len(), URGENT_BELLS as usize);
+    assert_eq!(urgent.bells[1] - urgent.bells[0], URGENT_BELL_GAP);
+    assert_eq!(urgent.flash, Some(UiEffect::UrgentFlash));
+
+    assert_eq!(sink.signal(AlertStyle::Default, AlertMode::Both).bells, vec![Duration::ZERO]);
+    let subtle = sink.signal(AlertStyle::Subtle, AlertMode::Both);
+    assert!(subtle.bells.is_empty());
+    assert_eq!(subtle.flash, Some(UiEffect::SubtleFlash));
+    assert_eq!(sink.signal(AlertStyle::None, AlertMode::Both), AlertSignal::default());
+
+    // The global mode still picks the channels
+    assert!(sink.signal(AlertStyle::Urgent, AlertMode::Flash).bells.is_empty());
+    assert_eq!(sink.signal(AlertStyle::Urgent, AlertMode::Bell).flash, None);
+
+    // The app rings the urgent bells one by one as they fall due
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.settings.alert_mode = AlertMode::Both;
+    let earlier = Instant::now() - URGENT_BELL_GAP * URGENT_BELLS;
+    app.play_alert(AlertStyle::Urgent, earlier);
+    assert!(app.effects.is_active(UiEffect::UrgentFlash, earlier));
+    for _ in 0..URGENT_BELLS {
+        assert!(app.take_bell());
+    }
+    assert!(!app.take_bell());
+}
+
+#[test]
+fn test_test_play_uses_the_contact_style() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut app, played) = app_with_details(&temp_dir);
+    app.app_state.settings.alert_mode = AlertMode::Both;
+    app.app_state.settings.alert_style = AlertStyle::Subtle;
+
+    // Following the global default
+    app.test_contact_alert_style();
+    assert_eq!(played.lock().unwrap().last(), Some(&(AlertStyle::Subtle, AlertMode::Both)));
+    assert_eq!(
+        app.chat_list_screen.as_ref().unwrap().status_message.as_deref(),
+        Some("Playing the 'subtle' alert")
+    );
+
+    // Overridden, and still played for a muted chat
+    app.cycle_contact_alert_style();
+    app.cycle_contact_alert_style();
+    app.cycle_contact_alert_style();
+    assert_eq!(app.app_state.contact_by_uid("bob").unwrap().alert_style, Some(AlertStyle::Urgent));
+    app.app_state.chat_by_uid_mut("bob").unwrap().muted = true;
+    app.test_contact_alert_style();
+    assert_eq!(played.lock().unwrap().last(), Some(&(AlertStyle::Urgent, AlertMode::Both)));
+
+    // With alerts off the sink is asked anyway, and the status says why nothing happens
+    app.app_state.settings.alert_mode = AlertMode::None;
+    app.test_contact_alert_style();
+    assert_eq!(played.lock().unwrap().last(), Some(&(AlertStyle::Urgent, AlertMode::None)));
+    assert_eq!(
+        app.chat_list_screen.as_ref().unwrap().status_message.as_deref(),
+        Some("In-app alerts are off (Settings: Alert)")
+    );
+
+    // Without the popup nothing plays
+    let count = played.lock().unwrap().len();
+    app.close_contact_details();
+    app.test_contact_alert_style();
+    assert_eq!(played.lock().unwrap().len(), count);
+}
+
+#[test]
+fn test_contact_and_global_styles_persist() {
+    let storage = Storage::new_in_memory().unwrap();
+    let settings = Settings { alert_style: AlertStyle::Urgent, ..Settings::default() };
+    storage.save_settings(&settings).unwrap();
+    assert_eq!(storage.load_settings().unwrap().unwrap().alert_style, AlertStyle::Urgent);
+    assert_eq!(Settings::default().alert_style, AlertStyle::Default);
+
+    let mut quiet = contact("carol");
+    quiet.alert_style = Some(AlertStyle::None);
+    storage.save_contact(&quiet).unwrap();
+    storage.save_contact(&contact("dave")).unwrap();
+    let contacts = storage.load_contacts().unwrap();
+    let style = |uid: &str| contacts.iter().find(|c| c.uid == uid).unwrap().alert_style;
+    assert_eq!(style("carol"), Some(AlertStyle::None));
+    assert_eq!(style("dave"), None);
+}
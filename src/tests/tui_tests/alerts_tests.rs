@@ -0,0 +1,109 @@
+// Alerts Tests - Terminal bell and header flash for new messages
+
+use crate::storage::{AlertMode, Chat, Contact, Settings, Storage};
+use crate::tui::alerts::{ring_bell, should_alert, BELL};
+use crate::tui::effects::HEADER_FLASH_DURATION;
+use crate::tui::{App, EffectQueue, SettingsScreen, UiEffect};
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_alert_trigger_matrix() {
+    // Another chat, not muted, outside quiet hours: alert
+    assert!(should_alert("alice", Some("bob"), false, false));
+    assert!(should_alert("alice", None, false, false));
+
+    // The open chat, a muted chat or quiet hours: no alert
+    assert!(!should_alert("alice", Some("alice"), false, false));
+    assert!(!should_alert("alice", Some("bob"), true, false));
+    assert!(!should_alert("alice", None, false, true));
+    assert!(!should_alert("alice", None, true, true));
+}
+
+#[test]
+fn test_effect_queue_expiry() {
+    let start = Instant::now();
+    let mut effects = EffectQueue::new();
+    assert!(!effects.is_active(UiEffect::HeaderFlash, start));
+    assert_eq!(effects.next_change(start), None);
+
+    effects.push(UiEffect::HeaderFlash, start);
+    assert!(effects.is_active(UiEffect::HeaderFlash, start));
+    assert_eq!(effects.next_change(start), Some(HEADER_FLASH_DURATION));
+
+    // Restarting extends the flash instead of stacking it
+    let halfway = start + HEADER_FLASH_DURATION / 2;
+    effects.push(UiEffect::HeaderFlash, halfway);
+    assert!(effects.is_active(UiEffect::HeaderFlash, start + HEADER_FLASH_DURATION));
+    assert_eq!(effects.next_change(halfway), Some(HEADER_FLASH_DURATION));
+
+    let ended = halfway + HEADER_FLASH_DURATION;
+    assert!(!effects.is_active(UiEffect::HeaderFlash, ended));
+    assert_eq!(effects.next_change(ended), Some(Duration::ZERO));
+    effects.prune(ended);
+    assert_eq!(effects.next_change(ended), None);
+}
+
+#[test]
+fn test_alert_mode_from_settings() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    let now = Instant::now();
+
+    // Off by default
+    app.raise_alert(now);
+    assert!(!app.take_bell());
+    assert!(!app.effects.is_active(UiEffect::HeaderFlash, now));
+
+    // Chosen on the settings screen
+    app.show_settings_screen();
+    let screen = app.settings_screen.as_mut().unwrap();
+    screen.selected_field = SettingsScreen::FIELD_ALERT_MODE;
+    screen.add_char(' ');
+    assert_eq!(screen.alert_mode, AlertMode::Bell);
+    app.save_settings();
+    assert_eq!(app.app_state.settings.alert_mode, AlertMode::Bell);
+
+    app.raise_alert(now);
+    assert!(app.take_bell());
+    assert!(!app.take_bell(), "the bell is taken once");
+    assert!(!app.effects.is_active(UiEffect::HeaderFlash, now));
+
+    app.app_state.settings.alert_mode = AlertMode::Both;
+    app.raise_alert(now);
+    assert!(app.take_bell());
+    assert!(app.effects.is_active(UiEffect::HeaderFlash, now));
+
+    // Mode and per-chat mute persist
+    let storage = Storage::new_in_memory().unwrap();
+    let settings = Settings {
+        alert_mode: AlertMode::Flash,
+        ..Settings::default()
+    };
+    storage.save_settings(&settings).unwrap();
+    assert_eq!(storage.load_settings().unwrap().unwrap().alert_mode, AlertMode::Flash);
+
+    storage
+        .save_contact(&Contact::new(
+            "alice_uid".to_string(),
+            "192.168.1.100:8080".to_string(),
+            vec![0; 32],
+            vec![0; 32],
+            chrono::Utc::now() + chrono::Duration::days(30),
+        ))
+        .unwrap();
+    let mut chat = Chat::new("alice_uid".to_string());
+    chat.muted = true;
+    storage.save_chat(&chat).unwrap();
+    assert!(storage.load_chats().unwrap()[0].muted);
+}
+
+#[test]
+fn test_bell_only_written_to_a_terminal() {
+    let mut redirected = Vec::new();
+    assert!(!ring_bell(&mut redirected, false).unwrap());
+    assert!(redirected.is_empty());
+
+    let mut terminal = Vec::new();
+    assert!(ring_bell(&mut terminal, true).unwrap());
+    assert_eq!(terminal, BELL);
+}
@@ -180,10 +180,10 @@ fn test_app_confirm_delete_inactive_chat() {
     // Popup should be hidden
     assert!(!app.chat_list_screen.as_ref().unwrap().show_delete_confirmation);
 
-    // Status should indicate inactive chat deletion
+    // Status should offer the undo
     let status = app.chat_list_screen.as_ref().unwrap().status_message.as_ref();
     assert!(status.is_some());
-    assert!(status.unwrap().contains("inactive"));
+    assert!(status.unwrap().contains("press U to undo"));
 }
 
 #[test]
@@ -207,10 +207,10 @@ fn test_app_confirm_delete_active_chat() {
     // Popup should be hidden
     assert!(!app.chat_list_screen.as_ref().unwrap().show_delete_confirmation);
 
-    // Status should indicate delete request was sent
+    // Status should offer the undo
     let status = app.chat_list_screen.as_ref().unwrap().status_message.as_ref();
     assert!(status.is_some());
-    assert!(status.unwrap().contains("delete request"));
+    assert_eq!(status.unwrap(), "Deleted chat with alice_uid — press U to undo");
 }
 
 #[test]
@@ -322,3 +322,332 @@ fn test_confirm_delete_with_invalid_index() {
     // Chat should not be deleted
     assert_eq!(app.app_state.chats.len(), initial_count);
 }
+
+/// Import a freshly generated contact into the app and return its UID
+fn import_test_contact(app: &mut crate::tui::App) -> (String, crate::storage::Contact) {
+    let keypair = crate::crypto::KeyPair::generate().expect("Failed to generate keypair");
+    let token = crate::storage::generate_contact_token(
+        "127.0.0.1:9",
+        &keypair.public_key,
+        &keypair.private_key,
+        &keypair.x25519_public,
+        chrono::Utc::now() + chrono::Duration::days(30),
+    ).expect("Failed to generate token");
+    let contact = crate::storage::parse_contact_token(&token).expect("Failed to parse token");
+    let uid = contact.uid.clone();
+    app.import_contact(contact.clone());
+    (uid, contact)
+}
+
+#[test]
+fn test_app_edit_contact_notes() {
+    let (mut app, _temp_dir) = create_test_app();
+    let (uid, _) = import_test_contact(&mut app);
+
+    app.show_chat_list_screen();
+    app.show_contact_details();
+    app.start_editing_notes();
+
+    {
+        let popup = app.chat_list_screen.as_mut().unwrap().contact_details.as_mut().unwrap();
+        let editor = popup.notes_editor.as_mut().expect("Editor should be open");
+        for c in "line one".chars() {
+            editor.add_char(c);
+        }
+        editor.newline();
+        for c in "line two".chars() {
+            editor.add_char(c);
+        }
+    }
+    app.save_contact_notes();
+
+    let contact = app.app_state.contacts.iter().find(|c| c.uid == uid).unwrap();
+    assert_eq!(contact.notes, "line one\nline two");
+    let popup = app.chat_list_screen.as_ref().unwrap().contact_details.as_ref().unwrap();
+    assert!(popup.notes_editor.is_none(), "Editor should close after saving");
+}
+
+#[test]
+fn test_app_delete_contact_keep_notes() {
+    let (mut app, _temp_dir) = create_test_app();
+    let (uid, contact) = import_test_contact(&mut app);
+    app.app_state.contacts[0].set_notes("remember me").unwrap();
+
+    app.show_chat_list_screen();
+    app.show_contact_details();
+    app.request_delete_contact();
+    assert!(app.chat_list_screen.as_ref().unwrap().contact_details.as_ref().unwrap().confirm_delete);
+    app.confirm_delete_contact(true);
+
+    assert!(app.app_state.contacts.iter().all(|c| c.uid != uid));
+    assert!(app.app_state.chats.iter().all(|c| c.contact_uid != uid));
+    assert!(app.chat_list_screen.as_ref().unwrap().contact_details.is_none());
+
+    // Re-importing restores the kept notes
+    app.import_contact(contact);
+    let restored = app.app_state.contacts.iter().find(|c| c.uid == uid).unwrap();
+    assert_eq!(restored.notes, "remember me");
+}
+
+#[test]
+fn test_app_delete_contact_discard_notes() {
+    let (mut app, _temp_dir) = create_test_app();
+    let (uid, contact) = import_test_contact(&mut app);
+    app.app_state.contacts[0].set_notes("forget me").unwrap();
+
+    app.show_chat_list_screen();
+    app.show_contact_details();
+    app.request_delete_contact();
+    app.confirm_delete_contact(false);
+
+    app.import_contact(contact);
+    let restored = app.app_state.contacts.iter().find(|c| c.uid == uid).unwrap();
+    assert!(restored.notes.is_empty());
+}
+
+#[test]
+fn test_app_toggle_pin_selected_respects_cap() {
+    use crate::storage::{Message, MAX_PINNED_PER_CHAT};
+
+    let (mut app, _temp_dir) = create_test_app();
+    let chat = app.app_state.get_or_create_chat("alice_uid");
+    for i in 0..=MAX_PINNED_PER_CHAT {
+        chat.append_message(Message::new(
+            format!("msg_{}", i),
+            "alice_uid".to_string(),
+            "me".to_string(),
+            b"hello".to_vec(),
+            i as i64,
+        ));
+    }
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+
+    // Pin all but the newest message
+    for i in 0..MAX_PINNED_PER_CHAT {
+        if let Some(screen) = app.chat_view_screen.as_mut() {
+            screen.selected_message_id = Some(format!("msg_{}", i));
+        }
+        app.toggle_pin_selected();
+    }
+    assert_eq!(app.app_state.chats[0].pinned_messages().len(), MAX_PINNED_PER_CHAT);
+
+    // The newest one exceeds the cap: refused with a status message
+    app.chat_view_screen.as_mut().unwrap().start_selection(&app.app_state.chats[0]);
+    app.toggle_pin_selected();
+    assert_eq!(app.app_state.chats[0].pinned_messages().len(), MAX_PINNED_PER_CHAT);
+    let status = app.chat_view_screen.as_ref().unwrap().status_message.clone().unwrap();
+    assert!(status.starts_with("Error:"), "unexpected status: {}", status);
+
+    app.toggle_star_selected();
+    assert!(app.app_state.chats[0].messages[MAX_PINNED_PER_CHAT].starred);
+}
+
+#[test]
+fn test_app_save_selected_binary_content() {
+    use crate::storage::{Contact, Message};
+
+    let (mut app, _temp_dir) = create_test_app();
+    app.app_state.contacts.push(Contact::new(
+        "alice_uid".to_string(),
+        "192.168.1.100:8080".to_string(),
+        vec![0; 32],
+        vec![0; 32],
+        chrono::Utc::now() + chrono::Duration::days(30),
+    ));
+    let mut blob = b"\x89PNG\r\n\x1a\n".to_vec();
+    blob.extend((0..400 * 1024).map(|i| (i % 251) as u8));
+    let chat = app.app_state.get_or_create_chat("alice_uid");
+    chat.append_message(Message::new("photo".to_string(), "alice_uid".to_string(), "me".to_string(), blob.clone(), 1));
+    chat.append_message(Message::new("photo2".to_string(), "alice_uid".to_string(), "me".to_string(), vec![0xff; 3], 2));
+    app.save_state().unwrap();
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+
+    // Streamed from storage in chunks into a typed file
+    app.chat_view_screen.as_mut().unwrap().selected_message_id = Some("photo".to_string());
+    app.save_selected_content();
+    let path = app.downloads_dir.join("pure2p-photo.png");
+    assert_eq!(std::fs::read(&path).unwrap(), blob);
+    let status = app.chat_view_screen.as_ref().unwrap().status_message.clone().unwrap();
+    assert!(status.contains(&path.display().to_string()), "unexpected status: {}", status);
+    assert!(status.contains("KB"));
+
+    // Saving again never overwrites
+    app.save_selected_content();
+    assert_eq!(std::fs::read(app.downloads_dir.join("pure2p-photo-1.png")).unwrap(), blob);
+
+    // Unknown binary is still saveable
+    app.chat_view_screen.as_mut().unwrap().selected_message_id = Some("photo2".to_string());
+    app.save_selected_content();
+    assert_eq!(std::fs::read(app.downloads_dir.join("pure2p-photo2.bin")).unwrap(), vec![0xff; 3]);
+}
+
+#[test]
+fn test_app_history_limit_lazy_until_applied() {
+    use crate::messaging::handle_incoming_message;
+    use crate::storage::{Contact, Message};
+
+    let (mut app, _temp_dir) = create_test_app();
+    for uid in ["alice_uid", "bob_uid"] {
+        app.app_state.contacts.push(Contact::new(
+            uid.to_string(),
+            "192.168.1.100:8080".to_string(),
+            vec![0; 32],
+            vec![0; 32],
+            chrono::Utc::now() + chrono::Duration::days(30),
+        ));
+        let chat = app.app_state.get_or_create_chat(uid);
+        for n in 1..=5 {
+            chat.append_message(Message::new(format!("{}_{}", uid, n), uid.to_string(), "me".to_string(), b"hi".to_vec(), n));
+        }
+    }
+
+    // Lowering the limit deletes nothing by itself
+    app.show_settings_screen();
+    app.settings_screen.as_mut().unwrap().history_limit_input = "3".to_string();
+    app.save_settings();
+    assert_eq!(app.app_state.settings.history_limit, 3);
+    assert!(app.app_state.chats.iter().all(|c| c.messages.len() == 5 && !c.is_trimmed()));
+
+    // The next message in a chat trims that chat only
+    handle_incoming_message(&mut app.app_state, "alice_uid", "me", "alice_6", b"hi".to_vec(), 6);
+    let alice = app.app_state.get_chat("alice_uid").unwrap();
+    assert_eq!(alice.messages.iter().filter(|m| !m.is_system()).count(), 3);
+    assert!(alice.is_trimmed());
+    assert_eq!(app.app_state.get_chat("bob_uid").unwrap().messages.len(), 5);
+
+    // "Apply now" trims the rest
+    app.apply_history_limit_now();
+    let bob = app.app_state.get_chat("bob_uid").unwrap();
+    assert_eq!(bob.messages.iter().filter(|m| !m.is_system()).count(), 3);
+    let status = app.settings_screen.as_ref().unwrap().status_message.clone().unwrap();
+    assert!(status.contains("2 old messages removed"), "unexpected status: {}", status);
+}
+
+/// App on the chat list with chats for alice, bob and charlie (in that order)
+fn app_with_three_chats() -> (crate::tui::App, tempfile::TempDir) {
+    let (mut app, temp_dir) = create_test_app();
+    for uid in ["alice_uid", "bob_uid", "charlie_uid"] {
+        app.app_state.add_chat(uid.to_string());
+    }
+    app.show_chat_list_screen();
+    (app, temp_dir)
+}
+
+fn open_uid(app: &crate::tui::App) -> Option<String> {
+    app.chat_view_screen.as_ref().map(|screen| screen.contact_uid.clone())
+}
+
+#[test]
+fn test_open_chat_by_number_follows_list_order() {
+    let (mut app, _temp_dir) = app_with_three_chats();
+    let order: Vec<String> = app.app_state.chats.iter().map(|chat| chat.contact_uid.clone()).collect();
+
+    assert!(app.open_chat_number(3));
+    assert_eq!(app.current_screen, Screen::ChatView);
+    assert_eq!(open_uid(&app).as_ref(), Some(&order[2]));
+    assert_eq!(app.chat_list_screen.as_ref().unwrap().selected_index, 2);
+
+    app.back_to_chat_list();
+    assert!(app.open_chat_number(1));
+    assert_eq!(open_uid(&app).as_ref(), Some(&order[0]));
+
+    // Past the end of the list (or 0): nothing opens
+    app.back_to_chat_list();
+    assert!(!app.open_chat_number(4));
+    assert!(!app.open_chat_number(0));
+    assert_eq!(app.current_screen, Screen::ChatList);
+    assert_eq!(app.chat_list_screen.as_ref().unwrap().status_message.as_deref(), Some("No chat 0"));
+}
+
+#[test]
+fn test_recent_chats_follow_every_open_path() {
+    let (mut app, _temp_dir) = app_with_three_chats();
+    let order: Vec<String> = app.app_state.chats.iter().map(|chat| chat.contact_uid.clone()).collect();
+    assert!(app.recent_chats.is_empty());
+
+    // From the list
+    app.chat_list_screen.as_mut().unwrap().selected_index = 1;
+    app.open_selected_chat();
+    assert_eq!(app.recent_chats, [order[1].clone()]);
+
+    // By number
+    app.back_to_chat_list();
+    app.open_chat_number(3);
+    assert_eq!(app.recent_chats, [order[2].clone(), order[1].clone()]);
+
+    // By UID (any other flow), reopening moves to the front; only two are kept
+    app.open_chat(&order[0]);
+    assert_eq!(app.recent_chats, [order[0].clone(), order[2].clone()]);
+    app.open_chat(&order[2]);
+    assert_eq!(app.recent_chats, [order[2].clone(), order[0].clone()]);
+
+    // Kept across screens
+    app.back_to_main_menu();
+    app.show_chat_list_screen();
+    assert_eq!(app.recent_chats, [order[2].clone(), order[0].clone()]);
+
+    // Tab jumps to the most recent chat other than the selection, then back
+    let screen = app.chat_list_screen.as_mut().unwrap();
+    screen.selected_index = 1;
+    app.toggle_recent_chat_selection();
+    assert_eq!(app.chat_list_screen.as_ref().unwrap().selected_index, 2);
+    app.toggle_recent_chat_selection();
+    assert_eq!(app.chat_list_screen.as_ref().unwrap().selected_index, 1);
+    app.chat_list_screen.as_mut().unwrap().selected_index = 2;
+    app.toggle_recent_chat_selection();
+    assert_eq!(app.chat_list_screen.as_ref().unwrap().selected_index, 0);
+}
+
+#[test]
+fn test_backtick_switches_to_previous_chat() {
+    let (mut app, _temp_dir) = app_with_three_chats();
+    let order: Vec<String> = app.app_state.chats.iter().map(|chat| chat.contact_uid.clone()).collect();
+
+    // Nothing to switch to with a single chat opened
+    app.open_chat_number(1);
+    assert!(!app.switch_to_previous_chat());
+
+    app.back_to_chat_list();
+    app.open_chat_number(2);
+    assert!(app.switch_to_previous_chat());
+    assert_eq!(app.current_screen, Screen::ChatView);
+    assert_eq!(open_uid(&app).as_ref(), Some(&order[0]));
+    assert!(app.switch_to_previous_chat());
+    assert_eq!(open_uid(&app).as_ref(), Some(&order[1]));
+    assert_eq!(app.recent_chats, [order[1].clone(), order[0].clone()]);
+
+    // A deleted chat is skipped
+    app.app_state.chats.retain(|chat| chat.contact_uid != order[0]);
+    assert!(!app.switch_to_previous_chat());
+}
+
+#[test]
+fn test_chat_shortcuts_leave_other_inputs_alone() {
+    let (mut app, _temp_dir) = app_with_three_chats();
+    app.open_chat_number(1);
+    app.back_to_chat_list();
+    app.open_chat_number(2);
+
+    // Typed text keeps its digits and backticks
+    let screen = app.chat_view_screen.as_mut().unwrap();
+    screen.add_char('3');
+    assert!(!app.switch_to_previous_chat());
+    let screen = app.chat_view_screen.as_mut().unwrap();
+    screen.add_char('`');
+    assert_eq!(screen.input, "3`");
+    let open = open_uid(&app);
+
+    // Digits in the settings' numeric fields never open chats
+    app.show_settings_screen();
+    let settings = app.settings_screen.as_mut().unwrap();
+    settings.retry_interval_input.clear();
+    for c in ['1', '2'] {
+        settings.add_char(c);
+    }
+    assert_eq!(settings.retry_interval_input, "12");
+    assert_eq!(app.current_screen, Screen::Settings);
+    assert_eq!(app.recent_chats.len(), 2);
+    assert_eq!(app.recent_chats[0], open.unwrap());
+}
@@ -155,3 +155,116 @@ fn test_app_import_duplicate_contact() {
         "Should show duplicate contact error message"
     );
 }
+
+#[test]
+fn test_app_import_mismatched_uid_rejected() {
+    let (mut app, _temp_dir) = create_test_app();
+    app.show_import_contact_screen();
+
+    let other_keypair = KeyPair::generate().expect("Failed to generate keypair");
+    let mut contact = crate::storage::Contact::new(
+        "0".repeat(32),
+        "192.168.1.200:8080".to_string(),
+        other_keypair.public_key.clone(),
+        other_keypair.x25519_public.clone(),
+        Utc::now() + Duration::days(30),
+    );
+    app.import_contact(contact.clone());
+
+    assert!(app.app_state.contacts.is_empty(), "Contact with a forged UID should not be added");
+    assert!(app.app_state.chats.is_empty());
+    let screen = app.import_contact_screen.as_ref().unwrap();
+    assert!(screen.is_error);
+    assert!(screen.status_message.as_ref().unwrap().contains("Identity mismatch"));
+
+    // With the UID derived from the key the same contact is accepted
+    contact.uid = other_keypair.uid.to_string();
+    app.import_contact(contact);
+    assert_eq!(app.app_state.contacts.len(), 1);
+}
+
+#[test]
+fn test_app_import_key_conflict_held_for_review() {
+    let (mut app, _temp_dir) = create_test_app();
+    app.show_import_contact_screen();
+
+    // A contact stored before identities were checked, under a made-up UID
+    let other_keypair = KeyPair::generate().expect("Failed to generate keypair");
+    app.app_state.contacts.push(crate::storage::Contact::new(
+        "legacy_uid".to_string(),
+        "192.168.1.200:8080".to_string(),
+        other_keypair.public_key.clone(),
+        other_keypair.x25519_public.clone(),
+        Utc::now() + Duration::days(30),
+    ));
+
+    let token = generate_contact_token(
+        "192.168.1.201:8080",
+        &other_keypair.public_key,
+        &other_keypair.private_key,
+        &other_keypair.x25519_public,
+        Utc::now() + Duration::days(30),
+    ).expect("Failed to generate token");
+    app.import_contact(parse_contact_token(&token).expect("Failed to parse token"));
+
+    // Nothing new in the chat list; the conflict waits for review
+    assert_eq!(app.app_state.contacts.len(), 1);
+    assert!(app.app_state.chats.is_empty());
+    assert_eq!(app.app_state.identity_conflicts.len(), 1);
+    let screen = app.import_contact_screen.as_ref().unwrap();
+    assert!(screen.is_error);
+    assert!(screen.status_message.as_ref().unwrap().contains("held for review"));
+
+    app.show_chat_list_screen();
+    app.open_conflict_review();
+    assert_eq!(app.chat_list_screen.as_ref().unwrap().conflict_review, Some(0));
+    app.dismiss_selected_conflict();
+    assert!(app.app_state.identity_conflicts.is_empty());
+    assert_eq!(app.chat_list_screen.as_ref().unwrap().conflict_review, None);
+}
+
+#[test]
+fn test_incoming_ping_ingests_sender() {
+    use crate::storage::{AppState, ContactIngest, Storage};
+    use crate::tui::App;
+
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    state.save_to_db(&storage).expect("Failed to save state");
+
+    let sender = KeyPair::generate().expect("Failed to generate keypair");
+    let token_at = |ip: &str| {
+        generate_contact_token(
+            ip,
+            &sender.public_key,
+            &sender.private_key,
+            &sender.x25519_public,
+            Utc::now() + Duration::days(1),
+        ).expect("Failed to generate token")
+    };
+
+    // First ping auto-imports the sender, a later one only refreshes the address
+    let limiter = std::sync::Mutex::new(crate::auto_import::AutoImportLimiter::default());
+    let outcome = App::apply_incoming_ping(&storage, &token_at("192.168.1.50:8080"), None, &limiter, Utc::now()).unwrap();
+    assert!(matches!(outcome, ContactIngest::Added));
+    let outcome = App::apply_incoming_ping(&storage, &token_at("10.0.0.50:9000"), None, &limiter, Utc::now()).unwrap();
+    assert!(matches!(outcome, ContactIngest::AddressUpdated));
+
+    let loaded = AppState::load_from_db(&storage).unwrap();
+    assert_eq!(loaded.contacts.len(), 1);
+    assert_eq!(loaded.contacts[0].ip, "10.0.0.50:9000");
+    assert!(loaded.get_chat(&sender.uid.to_string()).is_some_and(|c| c.is_active));
+
+    // A sender whose key is stored under another UID gets no chat
+    let mut state = loaded;
+    state.contacts[0].uid = "legacy_uid".to_string();
+    state.chats.clear();
+    storage.clear_all().unwrap();
+    state.save_to_db(&storage).unwrap();
+    let outcome = App::apply_incoming_ping(&storage, &token_at("10.0.0.50:9000"), None, &limiter, Utc::now()).unwrap();
+    assert!(matches!(outcome, ContactIngest::Conflict(_)));
+    let loaded = AppState::load_from_db(&storage).unwrap();
+    assert!(loaded.chats.is_empty());
+    assert_eq!(loaded.identity_conflicts.len(), 1);
+}
@@ -0,0 +1,177 @@
+//! Diagnostics screen single-protocol actions (tests, deletion, alternate port)
+
+use crate::connectivity::{MappingProtocol, PortMappingResult};
+use crate::tui::{App, DiagnosticsAction, MappingActions};
+use super::helpers::create_test_app;
+use std::sync::{Arc, Mutex};
+
+/// Records every router call and answers without touching the network
+#[derive(Default)]
+struct StubActions {
+    calls: Mutex<Vec<DiagnosticsAction>>,
+}
+
+impl MappingActions for StubActions {
+    fn map(&self, protocol: MappingProtocol, port: u16) -> Result<PortMappingResult, String> {
+        self.calls.lock().unwrap().push(DiagnosticsAction::Test { protocol, port });
+        Ok(mapping(protocol, port + 1))
+    }
+
+    fn delete(&self, protocol: MappingProtocol, port: u16) -> Result<String, String> {
+        self.calls.lock().unwrap().push(DiagnosticsAction::Delete { protocol, port });
+        Ok(format!("stub router removed port {}", port))
+    }
+}
+
+fn mapping(protocol: MappingProtocol, external_port: u16) -> PortMappingResult {
+    PortMappingResult {
+        external_ip: "203.0.113.7".parse().unwrap(),
+        external_port,
+        lifetime_secs: 3600,
+        protocol,
+        created_at_ms: chrono::Utc::now().timestamp_millis(),
+    }
+}
+
+fn app_with_stub() -> (App, Arc<StubActions>, tempfile::TempDir) {
+    let (mut app, temp_dir) = create_test_app();
+    let stub = Arc::new(StubActions::default());
+    app.mapping_actions = stub.clone();
+    app.show_diagnostics_screen();
+    (app, stub, temp_dir)
+}
+
+fn wait_for_action(app: &mut App) {
+    for _ in 0..200 {
+        if app.poll_diagnostics_action() {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+    panic!("diagnostics action did not finish");
+}
+
+#[test]
+fn test_single_protocol_test_updates_only_its_panel() {
+    let (mut app, stub, _temp_dir) = app_with_stub();
+    let screen = app.diagnostics_screen.as_mut().unwrap();
+    screen.set_pcp_status(Err("no PCP server".to_string()));
+    screen.set_upnp_status(Ok(mapping(MappingProtocol::UPnP, 40000)));
+    let port = screen.local_port;
+
+    assert!(app.run_single_protocol_test(MappingProtocol::NATPMP));
+    wait_for_action(&mut app);
+
+    assert_eq!(
+        *stub.calls.lock().unwrap(),
+        vec![DiagnosticsAction::Test { protocol: MappingProtocol::NATPMP, port }]
+    );
+    let screen = app.diagnostics_screen.as_ref().unwrap();
+    let natpmp = screen.natpmp_status.as_ref().unwrap().as_ref().unwrap();
+    assert_eq!(natpmp.protocol, MappingProtocol::NATPMP);
+    assert_eq!(natpmp.external_port, port + 1);
+    assert_eq!(screen.pcp_status, Some(Err("no PCP server".to_string())));
+    assert_eq!(screen.upnp_status.as_ref().unwrap().as_ref().unwrap().external_port, 40000);
+    assert!(screen.pending_action.is_none());
+    assert!(!app.diagnostics_busy());
+}
+
+#[test]
+fn test_delete_dispatches_to_active_protocol() {
+    let (mut app, stub, _temp_dir) = app_with_stub();
+    let screen = app.diagnostics_screen.as_mut().unwrap();
+    screen.set_pcp_status(Err("no PCP server".to_string()));
+    screen.set_upnp_status(Ok(mapping(MappingProtocol::UPnP, 40000)));
+    let port = screen.local_port;
+
+    // Deleting needs confirmation
+    app.request_delete_mapping();
+    assert!(app.diagnostics_screen.as_ref().unwrap().confirm_delete);
+    app.cancel_delete_mapping();
+    assert!(stub.calls.lock().unwrap().is_empty());
+
+    app.request_delete_mapping();
+    assert!(app.confirm_delete_mapping());
+    wait_for_action(&mut app);
+
+    assert_eq!(
+        *stub.calls.lock().unwrap(),
+        vec![DiagnosticsAction::Delete { protocol: MappingProtocol::UPnP, port }]
+    );
+    let screen = app.diagnostics_screen.as_ref().unwrap();
+    assert!(screen.upnp_status.is_none());
+    assert!(screen.status_message.as_ref().unwrap().contains("stub router removed"));
+
+    // Nothing left to delete
+    app.request_delete_mapping();
+    assert!(!app.diagnostics_screen.as_ref().unwrap().confirm_delete);
+}
+
+#[test]
+fn test_alternate_port_leaves_app_port_alone() {
+    let (mut app, stub, _temp_dir) = app_with_stub();
+    let local_port = app.local_port;
+    let user_port = app.app_state.user_port;
+
+    let screen = app.diagnostics_screen.as_mut().unwrap();
+    screen.start_port_prompt();
+    for c in "5x0000".chars() {
+        screen.port_prompt_push(c);
+    }
+    screen.submit_port_prompt().unwrap();
+    assert_eq!(screen.alternate_port, Some(50000));
+
+    assert!(app.run_single_protocol_test(MappingProtocol::PCP));
+    wait_for_action(&mut app);
+
+    assert_eq!(
+        *stub.calls.lock().unwrap(),
+        vec![DiagnosticsAction::Test { protocol: MappingProtocol::PCP, port: 50000 }]
+    );
+    assert_eq!(app.local_port, local_port);
+    assert_eq!(app.app_state.user_port, user_port);
+    let screen = app.diagnostics_screen.as_mut().unwrap();
+    assert_eq!(screen.local_port, local_port);
+    assert_eq!(screen.tested_port(MappingProtocol::PCP), 50000);
+
+    // Invalid ports keep the prompt open; an empty one resets
+    screen.start_port_prompt();
+    while screen.port_prompt.as_ref().is_some_and(|p| !p.is_empty()) {
+        screen.port_prompt_pop();
+    }
+    screen.port_prompt_push('0');
+    assert!(screen.submit_port_prompt().is_err());
+    screen.port_prompt_pop();
+    screen.submit_port_prompt().unwrap();
+    assert_eq!(screen.test_port(), local_port);
+}
+
+#[test]
+fn test_actions_excluded_while_refresh_runs() {
+    let (mut app, stub, _temp_dir) = app_with_stub();
+
+    // A full refresh in flight blocks single-protocol actions
+    let (release, wait) = std::sync::mpsc::channel::<()>();
+    app.diagnostics_refresh_handle = Some(std::thread::spawn(move || {
+        let _ = wait.recv();
+    }));
+    app.diagnostics_screen.as_mut().unwrap().set_upnp_status(Ok(mapping(MappingProtocol::UPnP, 40000)));
+
+    assert!(app.diagnostics_busy());
+    assert!(!app.run_single_protocol_test(MappingProtocol::PCP));
+    app.request_delete_mapping();
+    assert!(!app.diagnostics_screen.as_ref().unwrap().confirm_delete);
+    assert!(stub.calls.lock().unwrap().is_empty());
+    assert!(app.diagnostics_action_handle.is_none());
+
+    release.send(()).unwrap();
+    let handle = app.diagnostics_refresh_handle.take().unwrap();
+    handle.join().unwrap();
+
+    // A running action blocks the full refresh
+    assert!(app.run_single_protocol_test(MappingProtocol::PCP));
+    app.trigger_diagnostics_refresh();
+    assert!(app.diagnostics_refresh_handle.is_none());
+    wait_for_action(&mut app);
+    assert!(!app.diagnostics_busy());
+}
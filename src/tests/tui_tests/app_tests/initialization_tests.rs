@@ -79,27 +79,23 @@ fn test_app_loads_existing_settings() {
 }
 
 #[test]
-fn test_app_falls_back_to_defaults_on_corrupt_state() {
+fn test_app_refuses_corrupt_legacy_state() {
     let temp_dir = TempDir::new().expect("Failed to create temp dir");
     let state_path = temp_dir.path().join("app_state.json");
 
-    // Note: With SQLite storage, corrupt JSON files are ignored (migration is optional)
-    // This test now verifies that creating a fresh app gives defaults
-    // Create a corrupted JSON file (it will be ignored during migration)
+    // A corrupt legacy file stops startup instead of being silently dropped
     std::fs::write(&state_path, "{ invalid json }")
         .expect("Failed to write corrupt state");
 
-    // Create app (uses in-memory SQLite for tests, ignores corrupt JSON)
-    let app = App::new_with_settings(Some(&state_path))
-        .expect("Failed to create app");
+    let err = App::new_with_settings(Some(&state_path))
+        .err()
+        .expect("Corrupt legacy state should fail startup")
+        .to_string();
+    assert!(err.contains("line 1, column 3"), "Error should locate the problem: {}", err);
 
-    // Verify default state is used (fresh database)
-    assert_eq!(app.app_state.settings.retry_interval_minutes, 1,
-        "Should use default retry interval");
-    assert_eq!(app.app_state.settings.default_contact_expiry_days, 30,
-        "Should use default contact expiry");
-    assert!(app.app_state.contacts.is_empty(), "Should have no contacts");
-    assert!(app.app_state.chats.is_empty(), "Should have no chats");
+    // The file is left for the user to fix or remove
+    assert_eq!(std::fs::read_to_string(&state_path).unwrap(), "{ invalid json }");
+    assert!(!temp_dir.path().join("app_state.json.bak").exists());
 }
 
 #[test]
@@ -31,7 +31,7 @@ fn test_app_send_message() {
     assert_eq!(msg.sender, app.keypair.uid.to_string());
     assert_eq!(msg.recipient, "alice_uid");
     assert_eq!(
-        String::from_utf8(msg.content.clone()).unwrap(),
+        String::from_utf8(msg.content.to_vec()).unwrap(),
         "Hello Alice!"
     );
 
@@ -88,8 +88,205 @@ fn test_app_multiple_messages() {
     for (i, msg) in chat.messages.iter().enumerate() {
         let expected = format!("Message {}", i + 1);
         assert_eq!(
-            String::from_utf8(msg.content.clone()).unwrap(),
+            String::from_utf8(msg.content.to_vec()).unwrap(),
             expected
         );
     }
 }
+
+#[test]
+fn test_app_insert_template_from_picker() {
+    let (mut app, _temp_dir) = create_test_app();
+    let uid = "a1b2c3d4e5f6a7b8c9d0e1f2";
+    app.app_state.add_chat(uid.to_string());
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+
+    // Without templates '%' is just a character
+    assert!(!app.open_template_picker());
+
+    app.app_state.settings.add_template("Meeting", "Hi {name}, join here").unwrap();
+    app.app_state.settings.add_template("Bank", "Paid on {date}").unwrap();
+
+    let screen = app.chat_view_screen.as_mut().unwrap();
+    for c in "50% off".chars() {
+        screen.add_char(c);
+    }
+    // Mid-word '%' does not open the picker
+    screen.cursor_left();
+    screen.cursor_left();
+    screen.cursor_left();
+    screen.cursor_left();
+    assert!(!app.open_template_picker());
+
+    // At the start of a word it does; filtering narrows the list
+    app.chat_view_screen.as_mut().unwrap().cursor_right();
+    assert!(app.open_template_picker());
+    assert_eq!(app.template_picker_matches(), vec![0, 1]);
+    let picker = app.chat_view_screen.as_mut().unwrap().template_picker.as_mut().unwrap();
+    picker.push_char('m');
+    picker.push_char('t');
+    assert_eq!(app.template_picker_matches(), vec![0]);
+
+    // Contacts have no display name: {name} is the short UID shown in the chat list
+    app.insert_selected_template();
+    let screen = app.chat_view_screen.as_ref().unwrap();
+    assert!(screen.template_picker.is_none());
+    assert_eq!(screen.input, "50% Hi a1b2c3d4e5f6a7b8, join hereoff");
+    assert!(app.app_state.chats[0].messages.is_empty(), "Templates never auto-send");
+
+    // Cancelling types the '%' instead
+    app.chat_view_screen.as_mut().unwrap().clear_input();
+    assert!(app.open_template_picker());
+    app.cancel_template_picker();
+    assert_eq!(app.chat_view_screen.as_ref().unwrap().input, "%");
+}
+
+#[test]
+fn test_app_send_sanitizes_and_refuses_oversized() {
+    use crate::storage::MAX_MESSAGE_BYTES;
+
+    let (mut app, _temp_dir) = create_test_app();
+    app.app_state.add_chat("alice_uid".to_string());
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+
+    // Escape sequences are stripped before the message is stored or sent
+    app.chat_view_screen.as_mut().unwrap().input = "\u{1b}[2Jhi\r\nthere".to_string();
+    app.send_message_in_chat();
+    let chat = app.app_state.chats.iter().find(|c| c.contact_uid == "alice_uid").unwrap();
+    assert_eq!(chat.messages[0].content.as_ref(), b"[2Jhi\nthere");
+
+    // Control characters alone are nothing to send
+    app.chat_view_screen.as_mut().unwrap().input = "\u{1b}\u{7}".to_string();
+    app.send_message_in_chat();
+
+    // Over the framed limit: refused, input kept for editing
+    let big = "a".repeat(MAX_MESSAGE_BYTES * 3 / 4);
+    app.chat_view_screen.as_mut().unwrap().input = big.clone();
+    assert!(app.chat_input_counter().unwrap().is_over_limit());
+    app.send_message_in_chat();
+
+    let chat = app.app_state.chats.iter().find(|c| c.contact_uid == "alice_uid").unwrap();
+    assert_eq!(chat.messages.len(), 1);
+    let screen = app.chat_view_screen.as_ref().unwrap();
+    assert_eq!(screen.input, big);
+    assert!(screen.status_message.as_ref().unwrap().starts_with("Not sent"));
+}
+
+#[test]
+fn test_sending_as_confirmed_once_per_profile() {
+    let (mut app, _temp_dir) = create_test_app();
+    app.app_state.add_chat("alice_uid".to_string());
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+    let fingerprint = app.keypair.uid.to_string()[..8].to_string();
+
+    // Unlabelled profile: no confirmation, title shows the fingerprint
+    assert_eq!(app.chat_title("alice_uid"), format!("Chat with alice_uid  (as {})", fingerprint));
+    app.chat_view_screen.as_mut().unwrap().input = "one".to_string();
+    app.send_message_in_chat();
+    assert_eq!(app.app_state.chats[0].messages.len(), 1);
+
+    // Labelling the profile in settings asks once before the next send
+    app.show_settings_screen();
+    app.settings_screen.as_mut().unwrap().profile_label_input = "work".to_string();
+    app.save_settings();
+    assert_eq!(app.identity_label(), format!("work · {}", fingerprint));
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+    assert!(app.chat_title("alice_uid").ends_with(&format!("(as work · {})", fingerprint)));
+
+    app.chat_view_screen.as_mut().unwrap().input = "two".to_string();
+    app.send_message_in_chat();
+    assert_eq!(app.app_state.chats[0].messages.len(), 1);
+    let screen = app.chat_view_screen.as_ref().unwrap();
+    assert_eq!(screen.input, "two");
+    assert!(screen.status_message.as_ref().unwrap().starts_with("Sending as work"));
+
+    // Enter again sends, and later sends go straight out
+    app.send_message_in_chat();
+    app.chat_view_screen.as_mut().unwrap().input = "three".to_string();
+    app.send_message_in_chat();
+    assert_eq!(app.app_state.chats[0].messages.len(), 3);
+
+    // Saving settings without changing the profile keeps the confirmation
+    app.show_settings_screen();
+    app.save_settings();
+    assert!(app.sending_as_confirmed);
+}
+
+/// Open a chat with alice and type `text`
+fn type_in_alice_chat(app: &mut crate::tui::App, text: &str) {
+    if app.chat_view_screen.is_none() {
+        app.app_state.add_chat("alice_uid".to_string());
+        app.show_chat_list_screen();
+        app.open_selected_chat();
+    }
+    app.chat_view_screen.as_mut().unwrap().input = text.to_string();
+}
+
+fn alice_message_count(app: &crate::tui::App) -> usize {
+    app.app_state.get_chat("alice_uid").unwrap().messages.len()
+}
+
+#[test]
+fn test_app_double_enter_prompts_for_duplicate() {
+    let (mut app, _temp_dir) = create_test_app();
+    type_in_alice_chat(&mut app, "on my way");
+    app.send_message_in_chat();
+
+    // The same text again, differing only in stripped control characters
+    type_in_alice_chat(&mut app, "on my\u{7} way");
+    app.send_message_in_chat();
+    let screen = app.chat_view_screen.as_ref().unwrap();
+    assert!(screen.duplicate_prompt);
+    assert!(screen.status_message.as_ref().unwrap().contains("send duplicate? [y/N]"));
+    assert_eq!(alice_message_count(&app), 1);
+
+    // Anything but 'y' keeps it unsent, with the input intact
+    app.answer_duplicate_prompt(false);
+    let screen = app.chat_view_screen.as_ref().unwrap();
+    assert!(!screen.duplicate_prompt);
+    assert_eq!(screen.input, "on my\u{7} way");
+    assert_eq!(alice_message_count(&app), 1);
+}
+
+#[test]
+fn test_app_confirmed_duplicate_sends_once() {
+    let (mut app, _temp_dir) = create_test_app();
+    type_in_alice_chat(&mut app, "ok");
+    app.send_message_in_chat();
+    type_in_alice_chat(&mut app, "ok");
+    app.send_message_in_chat();
+
+    app.answer_duplicate_prompt(true);
+    assert_eq!(alice_message_count(&app), 2);
+    assert!(!app.chat_view_screen.as_ref().unwrap().duplicate_prompt);
+    assert!(app.chat_view_screen.as_ref().unwrap().input.is_empty());
+
+    // No prompt left to answer
+    app.answer_duplicate_prompt(true);
+    assert_eq!(alice_message_count(&app), 2);
+}
+
+#[test]
+fn test_app_repeat_outside_window_sends() {
+    let (mut app, _temp_dir) = create_test_app();
+    type_in_alice_chat(&mut app, "ping");
+    app.send_message_in_chat();
+
+    // Sent longer ago than the window
+    let window_ms = i64::from(app.app_state.settings.duplicate_window_secs) * 1000;
+    app.app_state.chats[0].messages_mut()[0].timestamp -= window_ms + 1;
+    type_in_alice_chat(&mut app, "ping");
+    app.send_message_in_chat();
+    assert!(!app.chat_view_screen.as_ref().unwrap().duplicate_prompt);
+    assert_eq!(alice_message_count(&app), 2);
+
+    // A window of 0 turns the guard off
+    app.app_state.settings.duplicate_window_secs = 0;
+    type_in_alice_chat(&mut app, "ping");
+    app.send_message_in_chat();
+    assert_eq!(alice_message_count(&app), 3);
+}
@@ -3,13 +3,15 @@
 //! This module contains tests for the App struct's business logic organized by feature area:
 //! - `helpers` - Shared test utilities
 //! - `initialization` - App creation, state loading, settings (6 tests)
-//! - `navigation` - Screen transitions, menu navigation (14 tests)
-//! - `contact_import` - Import validation, duplicate detection (3 tests)
-//! - `chat_management` - Chat creation, deletion, selection (14 tests)
-//! - `messaging` - Message sending (3 tests)
-//! - `startup` - Startup sync, connectivity (4 tests)
+//! - `navigation` - Screen transitions, menu navigation, templates in settings (15 tests)
+//! - `contact_import` - Import validation, duplicate detection, identity conflicts, incoming pings (6 tests)
+//! - `chat_management` - Chat creation, deletion, selection, contact notes, pin/star, saving content, history limit (20 tests)
+//! - `messaging` - Message sending, sanitization and size refusal, sending-as confirmation, duplicate send prompt, template picker (9 tests)
+//! - `startup` - Startup sync, connectivity, deferred loading (6 tests)
+//! - `storage_lock` - Deferred writes while the database is locked (1 test)
+//! - `diagnostics_actions` - Single-protocol tests, mapping deletion, alternate port, exclusion with refresh (4 tests)
 //!
-//! Total: 44 tests
+//! Total: 67 tests
 
 mod helpers;
 mod initialization_tests;
@@ -18,3 +20,5 @@ mod contact_import_tests;
 mod chat_management_tests;
 mod messaging_tests;
 mod startup_tests;
+mod storage_lock_tests;
+mod diagnostics_actions_tests;
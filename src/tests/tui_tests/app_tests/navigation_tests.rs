@@ -265,3 +265,44 @@ fn test_app_select_settings() {
     assert_eq!(app.current_screen, Screen::Settings);
     assert!(app.settings_screen.is_some());
 }
+
+#[test]
+fn test_app_manage_templates_in_settings() {
+    let (mut app, _temp_dir) = create_test_app();
+    app.show_settings_screen();
+    app.open_template_manager();
+
+    // Add a template through the editor
+    app.new_template();
+    let editor = app.settings_screen.as_mut().unwrap().template_editor.as_mut().unwrap();
+    "Hello".chars().for_each(|c| editor.add_char(c));
+    editor.newline();
+    "Hi {name}".chars().for_each(|c| editor.add_char(c));
+    editor.newline();
+    "bye".chars().for_each(|c| editor.add_char(c));
+    app.save_template_editor();
+    assert_eq!(app.app_state.settings.templates[0].body, "Hi {name}\nbye");
+    assert!(app.settings_screen.as_ref().unwrap().template_editor.is_none());
+
+    // A duplicate name keeps the editor open with an error
+    app.new_template();
+    let editor = app.settings_screen.as_mut().unwrap().template_editor.as_mut().unwrap();
+    "hello".chars().for_each(|c| editor.add_char(c));
+    editor.toggle_field();
+    editor.add_char('x');
+    app.save_template_editor();
+    let editor = app.settings_screen.as_ref().unwrap().template_editor.as_ref().unwrap();
+    assert!(editor.status_message.as_ref().unwrap().starts_with("Error:"));
+    app.cancel_template_editor();
+
+    // Edit and delete
+    app.edit_selected_template();
+    app.settings_screen.as_mut().unwrap().template_editor.as_mut().unwrap().add_char('!');
+    app.save_template_editor();
+    assert_eq!(app.app_state.settings.templates[0].name, "Hello!");
+
+    app.delete_selected_template();
+    assert!(app.app_state.settings.templates.is_empty());
+    app.close_template_manager();
+    assert!(app.settings_screen.as_ref().unwrap().template_selected.is_none());
+}
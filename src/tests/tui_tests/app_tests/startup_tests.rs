@@ -31,3 +31,86 @@ fn test_app_trigger_startup_connectivity() {
     assert!(app.diagnostics_refresh_handle.is_some(),
         "Should not create duplicate handles");
 }
+
+#[test]
+fn test_app_minimal_startup_skips_message_history() {
+    use crate::crypto::KeyPair;
+    use crate::storage::{AppState, Chat, Contact, Message, Storage};
+    use crate::tui::{App, StartupTimings};
+
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+
+    // Seed a database with 10k messages in a single chat
+    let storage = Storage::new_in_memory().expect("Failed to create storage");
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    state.contacts.push(Contact::new(
+        "seeded_uid".to_string(),
+        "127.0.0.1:8080".to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        chrono::Utc::now() + chrono::Duration::days(30),
+    ));
+    let mut chat = Chat::new("seeded_uid".to_string());
+    for i in 0..10_000 {
+        chat.append_message(Message::new(
+            format!("msg-{}", i),
+            "seeded_uid".to_string(),
+            "me".to_string(),
+            b"hello".to_vec(),
+            i,
+        ));
+    }
+    state.chats.push(chat);
+    state.save_to_db(&storage).expect("Failed to seed database");
+
+    let state_path = temp_dir.path().join("app_state.json").to_string_lossy().to_string();
+    let mut app = App::new_with_storage(storage, state_path, StartupTimings::new())
+        .expect("Failed to create app");
+
+    // Minimal path must not read the messages table
+    assert_eq!(app.storage.message_query_count(), 0);
+    assert!(!app.messages_loaded);
+    assert_eq!(app.app_state.chats.len(), 1);
+    assert!(app.app_state.chats[0].messages.is_empty());
+
+    // Pre-TUI phases stay within budget
+    assert!(app.startup_timings.get("state load").is_some());
+    assert!(
+        app.startup_timings.total() < std::time::Duration::from_millis(500),
+        "Minimal startup took {:?}",
+        app.startup_timings.total()
+    );
+
+    // Saving before history is loaded must not drop it
+    app.save_state().expect("Failed to save state");
+    assert!(app.app_state.chats[0].messages.is_empty());
+
+    // Deferred load fills in the history
+    app.load_chat_messages().expect("Failed to load messages");
+    assert!(app.messages_loaded);
+    assert_eq!(app.app_state.chats[0].messages.len(), 10_000);
+    assert_eq!(app.storage.message_query_count(), 2);
+    assert!(app.startup_timings.get("message load").is_some());
+}
+
+#[test]
+fn test_app_deferred_message_load_keeps_new_messages() {
+    let (mut app, _temp_dir) = create_test_app();
+
+    // Message added before history finished loading
+    let chat = app.app_state.add_chat("alice_uid".to_string());
+    chat.append_message(crate::storage::Message::new(
+        "early".to_string(),
+        "alice_uid".to_string(),
+        "me".to_string(),
+        b"hi".to_vec(),
+        1,
+    ));
+
+    app.load_chat_messages().expect("Failed to load messages");
+
+    let chat = app.app_state.get_chat("alice_uid").unwrap();
+    assert_eq!(chat.messages.len(), 1);
+    assert_eq!(chat.messages[0].id, "early");
+}
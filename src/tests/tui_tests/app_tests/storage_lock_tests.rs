@@ -0,0 +1,66 @@
+//! Locked database tests (deferred writes and recovery)
+
+use crate::crypto::KeyPair;
+use crate::storage::{AppState, BusyPolicy, Contact, Storage};
+use crate::tui::{App, StartupTimings};
+use rusqlite::Connection;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Create a file-backed app whose storage gives up quickly on locks
+fn create_file_backed_app(temp_dir: &TempDir) -> App {
+    let db_path = temp_dir.path().join("pure2p.db");
+    let mut storage = Storage::new(&db_path).expect("Failed to open storage");
+    storage.set_busy_policy(BusyPolicy {
+        busy_timeout: Duration::from_millis(10),
+        max_retries: 1,
+        base_delay: Duration::from_millis(5),
+    }).expect("Failed to set busy policy");
+
+    let mut state = AppState::new();
+    state.user_keypair = Some(KeyPair::generate().expect("Failed to generate keypair"));
+    state.save_to_db(&storage).expect("Failed to seed database");
+
+    let state_path = temp_dir.path().join("app_state.json").to_string_lossy().to_string();
+    App::new_with_storage(storage, state_path, StartupTimings::new())
+        .expect("Failed to create app")
+}
+
+fn test_contact(uid: &str) -> Contact {
+    Contact::new(
+        uid.to_string(),
+        "127.0.0.1:8080".to_string(),
+        vec![1, 2, 3],
+        vec![99u8; 32],
+        chrono::Utc::now() + chrono::Duration::days(30),
+    )
+}
+
+#[test]
+fn test_app_defers_writes_while_database_locked() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut app = create_file_backed_app(&temp_dir);
+
+    let lock = Connection::open(temp_dir.path().join("pure2p.db")).expect("Failed to open lock connection");
+    lock.execute_batch("BEGIN EXCLUSIVE").expect("Failed to lock database");
+
+    // Saves succeed from the UI's point of view but are held in memory
+    app.app_state.contacts.push(test_contact("alice"));
+    app.save_state().expect("Locked save should be deferred, not fail");
+    assert!(app.deferred_writes.is_pending());
+    assert_eq!(app.deferred_writes.pending_count(), 1);
+
+    app.app_state.contacts.push(test_contact("bob"));
+    app.save_state().expect("Locked save should be deferred, not fail");
+    assert_eq!(app.deferred_writes.pending_count(), 2);
+
+    // Flushes are throttled right after a failed write
+    app.flush_deferred_writes();
+    assert_eq!(app.deferred_writes.pending_count(), 2);
+
+    // Once the lock is released, the next write persists everything
+    lock.execute_batch("COMMIT").expect("Failed to release lock");
+    app.save_state().expect("Save should succeed after unlock");
+    assert!(!app.deferred_writes.is_pending());
+    assert_eq!(app.storage.load_contacts().expect("Failed to load contacts").len(), 2);
+}
@@ -0,0 +1,140 @@
+// Badges Tests - Testing unread/pending aggregation and terminal title updates
+
+use crate::storage::Chat;
+use crate::tui::badges::{title_escape, TITLE_UPDATE_INTERVAL};
+use crate::tui::{App, ChatSummary, TerminalTitle};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+fn create_test_app() -> (App, TempDir) {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let app = App::new_with_settings(Some(temp_dir.path().join("settings.json")))
+        .expect("Failed to create app");
+    (app, temp_dir)
+}
+
+fn chat(uid: &str, unread: bool, pending: bool) -> Chat {
+    let mut chat = Chat::new(uid.to_string());
+    if unread {
+        chat.mark_unread();
+    }
+    if pending {
+        chat.mark_has_pending();
+    }
+    chat
+}
+
+#[test]
+fn test_chat_summary_aggregation() {
+    assert_eq!(ChatSummary::from_chats(&[]), ChatSummary::default());
+
+    let chats = vec![
+        chat("alice", true, false),
+        chat("bob", true, true),
+        chat("carol", false, true),
+        chat("dave", false, false),
+        chat("erin", true, false),
+    ];
+    let summary = ChatSummary::from_chats(&chats);
+    assert_eq!(summary.unread, 3);
+    assert_eq!(summary.pending, 2);
+
+    // Reading a chat drops it from the unread count only
+    let mut chats = chats;
+    chats[1].mark_read();
+    let summary = ChatSummary::from_chats(&chats);
+    assert_eq!(summary.unread, 2);
+    assert_eq!(summary.pending, 2);
+}
+
+#[test]
+fn test_chat_summary_labels() {
+    let none = ChatSummary::default();
+    assert_eq!(none.badge(), None);
+    assert_eq!(none.menu_label(), "Chat List");
+    assert_eq!(none.terminal_title(), "Pure2P");
+
+    let both = ChatSummary { unread: 3, pending: 1 };
+    assert_eq!(both.menu_label(), "Chat List (3 unread, 1 pending)");
+    assert_eq!(both.terminal_title(), "Pure2P — 3 unread, 1 pending");
+
+    let unread = ChatSummary { unread: 3, pending: 0 };
+    assert_eq!(unread.terminal_title(), "Pure2P — 3 unread");
+
+    let pending = ChatSummary { unread: 0, pending: 2 };
+    assert_eq!(pending.menu_label(), "Chat List (2 pending)");
+}
+
+#[test]
+fn test_title_escape_format() {
+    assert_eq!(title_escape("Pure2P — 3 unread"), "\x1b]0;Pure2P — 3 unread\x07");
+    // Control characters cannot end the sequence early
+    assert_eq!(title_escape("evil\x07\x1b]0;x"), "\x1b]0;evil]0;x\x07");
+    assert_eq!(title_escape(""), "\x1b]0;\x07");
+}
+
+#[test]
+fn test_terminal_title_disabled_without_tty() {
+    let mut title = TerminalTitle::new(false);
+    assert!(!title.is_enabled());
+    assert!(title.update("Pure2P — 1 unread", Instant::now()).is_none());
+    assert!(title.clear().is_none());
+}
+
+#[test]
+fn test_terminal_title_rate_limited() {
+    let start = Instant::now();
+    let mut title = TerminalTitle::new(true);
+
+    assert_eq!(title.update("Pure2P", start), Some(title_escape("Pure2P")));
+    // Unchanged title is never re-emitted
+    assert!(title.update("Pure2P", start + Duration::from_secs(5)).is_none());
+
+    // A change within the interval is held back...
+    let soon = start + Duration::from_millis(300);
+    assert!(title.update("Pure2P — 1 unread", soon).is_none());
+    assert!(title.update("Pure2P — 2 unread", soon + Duration::from_millis(300)).is_none());
+
+    // ...and the latest requested title goes out once it elapses
+    let later = start + TITLE_UPDATE_INTERVAL;
+    assert_eq!(
+        title.update("Pure2P — 2 unread", later),
+        Some(title_escape("Pure2P — 2 unread"))
+    );
+    assert!(title.update("Pure2P — 3 unread", later + Duration::from_millis(999)).is_none());
+}
+
+#[test]
+fn test_terminal_title_cleared_on_exit() {
+    let mut title = TerminalTitle::new(true);
+    // Nothing to clear if no title was ever set
+    assert!(title.clear().is_none());
+
+    title.update("Pure2P — 1 unread", Instant::now());
+    assert_eq!(title.clear(), Some(title_escape("")));
+    assert!(title.clear().is_none());
+}
+
+#[test]
+fn test_incoming_update_flag_consumed() {
+    let (mut app, _temp_dir) = create_test_app();
+    assert!(!app.process_incoming_updates());
+
+    // Background handlers raise the flag after storing a message
+    app.incoming_updates.store(true, Ordering::SeqCst);
+    assert!(app.process_incoming_updates());
+    assert!(!app.incoming_updates.load(Ordering::SeqCst));
+    assert!(!app.process_incoming_updates());
+}
+
+#[test]
+fn test_app_chat_summary_follows_state() {
+    let (mut app, _temp_dir) = create_test_app();
+    assert_eq!(app.chat_summary(), ChatSummary::default());
+
+    app.app_state.chats.push(chat("alice", true, false));
+    app.app_state.chats.push(chat("bob", false, true));
+    assert_eq!(app.chat_summary(), ChatSummary { unread: 1, pending: 1 });
+    assert_eq!(app.chat_summary().menu_label(), "Chat List (1 unread, 1 pending)");
+}
@@ -0,0 +1,151 @@
+// Chat List Bulk Tests - Marks kept by UID across re-sorts, bulk archive/read/mute/delete in one write each, mark all, delete confirmation text
+
+use crate::storage::{AppState, Chat, Contact, Message};
+use crate::tui::ui::bulk_delete_text;
+use crate::tui::{App, ChatListScreen, Screen};
+use chrono::{Duration, Utc};
+use tempfile::TempDir;
+
+/// App on the chat list with unread chats with alice, bob and carol, in that order
+fn app_on_chat_list(temp_dir: &TempDir) -> App {
+    let mut app = App::new_with_settings(Some(temp_dir.path().join("settings.json"))).expect("Failed to create app");
+    for (i, uid) in ["alice", "bob", "carol"].into_iter().enumerate() {
+        let contact = Contact::new(uid.to_string(), "192.168.1.100:8080".to_string(), vec![1, 2, 3], vec![7u8; 32], Utc::now() + Duration::days(30));
+        app.app_state.add_contact(contact);
+        let message = Message::new(format!("{}_1", uid), uid.to_string(), "me".to_string(), b"hi".to_vec(), 1000 + i as i64);
+        let chat = app.app_state.get_or_create_chat(uid);
+        chat.append_message(message);
+        chat.mark_unread();
+    }
+    app.save_or_report();
+    app.chat_list_screen = Some(ChatListScreen::new());
+    app.current_screen = Screen::ChatList;
+    app
+}
+
+/// Highlight the chat with `uid` and mark it
+fn mark(app: &mut App, uid: &str) {
+    let index = app.app_state.chats.iter().position(|chat| chat.contact_uid == uid).unwrap();
+    app.chat_list_screen.as_mut().unwrap().selected_index = index;
+    app.toggle_chat_marked();
+}
+
+fn chat<'a>(app: &'a App, uid: &str) -> &'a Chat {
+    app.app_state.get_chat(uid).unwrap()
+}
+
+#[test]
+fn test_marks_follow_chats_across_a_resort() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut app = app_on_chat_list(&temp_dir);
+    mark(&mut app, "alice");
+    mark(&mut app, "carol");
+
+    // A new message re-sorts the list: carol moves to the top
+    let carol = app.app_state.remove_chat("carol").unwrap();
+    app.app_state.chats.insert(0, carol);
+    app.app_state.reindex();
+
+    let screen = app.chat_list_screen.as_ref().unwrap();
+    let marked: Vec<&str> = screen.marked_in(&app.app_state.chats).iter().map(|chat| chat.contact_uid.as_str()).collect();
+    assert_eq!(marked, ["carol", "alice"]);
+    assert!(!screen.is_marked("bob"));
+
+    // A marked chat that went away is left out
+    app.app_state.remove_chat("alice");
+    let screen = app.chat_list_screen.as_ref().unwrap();
+    assert_eq!(screen.marked_in(&app.app_state.chats).len(), 1);
+
+    // Esc drops the marks before leaving the list
+    assert!(app.clear_chat_marks());
+    assert!(!app.clear_chat_marks());
+}
+
+#[test]
+fn test_bulk_actions_apply_to_marked_chats_in_one_write() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut app = app_on_chat_list(&temp_dir);
+
+    // Mark read
+    mark(&mut app, "alice");
+    mark(&mut app, "bob");
+    let commits = app.storage.commit_count();
+    app.mark_marked_chats_read();
+    assert_eq!(app.storage.commit_count(), commits + 1);
+    assert!(!chat(&app, "alice").is_active && !chat(&app, "bob").is_active);
+    assert!(chat(&app, "carol").is_active);
+    assert!(app.chat_list_screen.as_ref().unwrap().marked.is_empty());
+
+    // Mute, then unmute once all are muted
+    mark(&mut app, "bob");
+    mark(&mut app, "carol");
+    let commits = app.storage.commit_count();
+    app.mute_marked_chats();
+    assert_eq!(app.storage.commit_count(), commits + 1);
+    assert!(chat(&app, "bob").muted && chat(&app, "carol").muted);
+    assert!(!chat(&app, "alice").muted);
+    mark(&mut app, "bob");
+    app.mute_marked_chats();
+    assert!(!chat(&app, "bob").muted);
+
+    // Archive moves the chats last, and the flag survives a reload
+    mark(&mut app, "alice");
+    mark(&mut app, "bob");
+    let commits = app.storage.commit_count();
+    app.archive_marked_chats();
+    assert_eq!(app.storage.commit_count(), commits + 1);
+    let order: Vec<&str> = app.app_state.chats.iter().map(|chat| chat.contact_uid.as_str()).collect();
+    assert_eq!(order, ["carol", "alice", "bob"]);
+    let reloaded = AppState::load_from_db(&app.storage).unwrap();
+    assert!(reloaded.get_chat("alice").unwrap().archived && !reloaded.get_chat("carol").unwrap().archived);
+    assert!(reloaded.get_chat("carol").unwrap().muted);
+
+    // Delete: one confirmation, one write, an undo entry per chat
+    mark(&mut app, "carol");
+    mark(&mut app, "bob");
+    app.request_bulk_delete();
+    assert!(app.chat_list_screen.as_ref().unwrap().show_bulk_delete_confirmation);
+    let commits = app.storage.commit_count();
+    app.answer_bulk_delete(true);
+    assert_eq!(app.storage.commit_count(), commits + 1);
+    let order: Vec<&str> = app.app_state.chats.iter().map(|chat| chat.contact_uid.as_str()).collect();
+    assert_eq!(order, ["alice"]);
+    assert_eq!(AppState::load_from_db(&app.storage).unwrap().chats.len(), 1);
+    assert!(app.undo_last_delete(Utc::now()));
+    assert_eq!(app.app_state.chats.len(), 2);
+}
+
+#[test]
+fn test_mark_all_and_declined_delete() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut app = app_on_chat_list(&temp_dir);
+    app.app_state.get_chat_mut("bob").unwrap().archived = true;
+    app.app_state.sort_archived_last();
+
+    // Every listed chat is marked, archived ones included
+    app.mark_all_chats();
+    let screen = app.chat_list_screen.as_ref().unwrap();
+    assert_eq!(screen.marked_in(&app.app_state.chats).len(), 3);
+
+    app.request_bulk_delete();
+    let commits = app.storage.commit_count();
+    app.answer_bulk_delete(false);
+    assert_eq!(app.storage.commit_count(), commits);
+    assert_eq!(app.app_state.chats.len(), 3);
+    assert!(!app.chat_list_screen.as_ref().unwrap().show_bulk_delete_confirmation);
+
+    // Opening a chat drops the marks
+    app.open_chat("alice");
+    assert!(app.chat_list_screen.as_ref().unwrap().marked.is_empty());
+}
+
+#[test]
+fn test_bulk_delete_text_breaks_down_active_and_inactive() {
+    let mut active = Chat::new("alice".to_string());
+    active.mark_unread();
+    let read = Chat::new("bob".to_string());
+    let other = Chat::new("carol".to_string());
+
+    assert_eq!(bulk_delete_text(&[&active, &read, &other]), "Delete 3 chats (1 active, 2 inactive)?");
+    assert_eq!(bulk_delete_text(&[&active]), "Delete 1 chat (1 active, 0 inactive)?");
+}
@@ -0,0 +1,190 @@
+// Connectivity Indicator Tests - Footer connectivity segment (derivation, recomputation, jump key, 80-column fit)
+
+use crate::connectivity::{ConnectivityResult, MappingProtocol, PortMappingResult};
+use crate::tui::ui::{connectivity_segment, display_width, footer_fits, ui, MIN_FOOTER_WIDTH};
+use crate::tui::{App, ConnectivityIndicator, LimitReason, Screen, TransportServerStatus};
+use ratatui::{backend::TestBackend, Terminal};
+use tempfile::TempDir;
+use crate::tests::helpers::new_app;
+
+fn result_with(protocol: Option<MappingProtocol>, cgnat: bool, reachable: Option<bool>) -> ConnectivityResult {
+    let mut result = ConnectivityResult::new();
+    result.mapping = protocol.map(|protocol| PortMappingResult {
+        external_ip: "203.0.113.7".parse().unwrap(),
+        external_port: 8080,
+        lifetime_secs: 3600,
+        protocol,
+        created_at_ms: 0,
+    });
+    result.cgnat_detected = cgnat;
+    result.externally_reachable = reachable;
+    result
+}
+
+#[test]
+fn test_indicator_derivation_matrix() {
+    use ConnectivityIndicator::*;
+    let running = TransportServerStatus::Running(8080);
+    let upnp = Some(MappingProtocol::UPnP);
+
+    // Without a running server the result does not matter
+    for result in [None, Some(result_with(upnp, false, Some(true)))] {
+        let result = result.as_ref();
+        assert_eq!(ConnectivityIndicator::derive(&TransportServerStatus::NotStarted, result), Starting);
+        assert_eq!(ConnectivityIndicator::derive(&TransportServerStatus::Starting, result), Starting);
+        assert_eq!(ConnectivityIndicator::derive(&TransportServerStatus::Failed("bind".into()), result), Offline);
+    }
+    assert_eq!(ConnectivityIndicator::derive(&running, None), Checking);
+
+    // Running: mapping x CGNAT x health check verdict
+    let cases = [
+        (upnp, false, None, Online(MappingProtocol::UPnP)),
+        (upnp, false, Some(true), Online(MappingProtocol::UPnP)),
+        (upnp, false, Some(false), Limited(LimitReason::NoInbound)),
+        (upnp, true, None, Limited(LimitReason::Cgnat)),
+        (upnp, true, Some(true), Online(MappingProtocol::UPnP)),
+        (upnp, true, Some(false), Limited(LimitReason::NoInbound)),
+        (None, false, None, Limited(LimitReason::NoMapping)),
+        (None, false, Some(true), Limited(LimitReason::NoMapping)),
+        (None, false, Some(false), Limited(LimitReason::NoInbound)),
+        (None, true, None, Limited(LimitReason::Cgnat)),
+        (None, true, Some(true), Limited(LimitReason::Cgnat)),
+        (None, true, Some(false), Limited(LimitReason::NoInbound)),
+    ];
+    for (protocol, cgnat, reachable, expected) in cases {
+        let result = result_with(protocol, cgnat, reachable);
+        assert_eq!(
+            ConnectivityIndicator::derive(&running, Some(&result)),
+            expected,
+            "mapping {:?}, cgnat {}, reachable {:?}",
+            protocol,
+            cgnat,
+            reachable
+        );
+    }
+
+    assert_eq!(Online(MappingProtocol::UPnP).label(), "● online (UPnP)");
+    assert_eq!(Online(MappingProtocol::NATPMP).label(), "● online (NAT-PMP)");
+    assert_eq!(Limited(LimitReason::NoInbound).label(), "◐ limited (no inbound)");
+    assert_eq!(Offline.label(), "○ offline");
+    assert_eq!(Checking.label(), "◌ checking");
+}
+
+#[test]
+fn test_indicator_recomputed_on_events_not_frames() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = new_app(&temp_dir);
+    assert_eq!(app.connectivity_indicator, ConnectivityIndicator::Starting);
+
+    // No change in the server status: nothing is re-derived
+    assert!(!app.poll_transport_status());
+    *app.transport_server_status.lock().unwrap() = TransportServerStatus::Running(8080);
+    assert!(app.poll_transport_status());
+    assert_eq!(app.connectivity_indicator, ConnectivityIndicator::Checking);
+    assert!(!app.poll_transport_status());
+
+    // Drawing frames only reads the stored indicator
+    app.connectivity_result = Some(result_with(Some(MappingProtocol::PCP), false, None));
+    let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+    for _ in 0..3 {
+        terminal.draw(|f| ui(f, &app)).unwrap();
+    }
+    assert_eq!(app.connectivity_indicator, ConnectivityIndicator::Checking);
+
+    // Applying a connectivity result is the event that updates it
+    let result = app.connectivity_result.clone().unwrap();
+    app.apply_connectivity_result(result);
+    assert_eq!(app.connectivity_indicator, ConnectivityIndicator::Online(MappingProtocol::PCP));
+    terminal.draw(|f| ui(f, &app)).unwrap();
+    let rendered: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+    assert!(rendered.contains("● online (PCP)"));
+
+    // Losing the server takes it offline
+    *app.transport_server_status.lock().unwrap() = TransportServerStatus::Failed("crashed".to_string());
+    assert!(app.poll_transport_status());
+    assert_eq!(app.connectivity_indicator, ConnectivityIndicator::Offline);
+}
+
+#[test]
+fn test_jump_to_diagnostics_from_several_screens() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = new_app(&temp_dir);
+
+    // Screens without text entry: 'D'
+    for open in [App::show_chat_list_screen, App::show_share_contact_screen] {
+        app.back_to_main_menu();
+        open(&mut app);
+        assert_eq!(app.diagnostics_jump_hint(), "D");
+        assert!(!app.handle_diagnostics_jump('d', false));
+        assert!(app.handle_diagnostics_jump('D', false));
+        assert_eq!(app.current_screen, Screen::Diagnostics);
+        assert!(app.diagnostics_screen.is_some());
+    }
+    app.back_to_main_menu();
+    assert!(app.handle_diagnostics_jump('D', false));
+    assert_eq!(app.current_screen, Screen::Diagnostics);
+
+    // Typing screens keep 'D' as text; Ctrl+D jumps instead
+    for open in [App::show_import_contact_screen, App::show_settings_screen] {
+        app.back_to_main_menu();
+        open(&mut app);
+        assert_eq!(app.diagnostics_jump_hint(), "Ctrl+D");
+        assert!(!app.handle_diagnostics_jump('D', false));
+        assert_ne!(app.current_screen, Screen::Diagnostics);
+        assert!(app.handle_diagnostics_jump('d', true));
+        assert_eq!(app.current_screen, Screen::Diagnostics);
+    }
+
+    // Already there: consumed without resetting the screen
+    app.diagnostics_screen.as_mut().unwrap().set_status_message("kept".to_string());
+    assert!(app.handle_diagnostics_jump('D', false));
+    assert!(app.diagnostics_screen.as_ref().unwrap().status_message.is_some());
+}
+
+/// Opens one screen of the app
+type ScreenOpener = fn(&mut App);
+
+#[test]
+fn test_footer_segment_fits_80_columns() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = new_app(&temp_dir);
+    app.connectivity_indicator = ConnectivityIndicator::Limited(LimitReason::NoInbound);
+
+    let screens: [(ScreenOpener, &str); 5] = [
+        (App::show_chat_list_screen, "Enter: Open"),
+        (App::show_import_contact_screen, "Enter: Parse | Ctrl+V: Paste | Delete: Clear | Esc: Back"),
+        (App::show_settings_screen, "Space: Toggle"),
+        (App::show_share_contact_screen, "c: Copy to Clipboard"),
+        (App::show_diagnostics_screen, "r/F5: Refresh"),
+    ];
+    for (open, hint) in screens {
+        app.back_to_main_menu();
+        open(&mut app);
+        let segment = connectivity_segment(&app);
+        assert!(footer_fits(MIN_FOOTER_WIDTH, &segment), "{:?}: {}", app.current_screen, segment);
+
+        let mut terminal = Terminal::new(TestBackend::new(MIN_FOOTER_WIDTH, 40)).unwrap();
+        terminal.draw(|f| ui(f, &app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows: Vec<String> = (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer.get(x, y).symbol()).collect())
+            .collect();
+
+        // The segment sits whole in a border row; the hint row is untouched
+        let border_row = rows.iter().position(|row| row.contains(segment.as_str()));
+        let border_row = border_row.unwrap_or_else(|| panic!("{:?}: segment missing", app.current_screen));
+        assert!(rows[border_row + 1].contains(hint), "{:?}: hint cut", app.current_screen);
+    }
+
+    // Every state with either key hint fits
+    let widest = [
+        ConnectivityIndicator::Online(MappingProtocol::NATPMP),
+        ConnectivityIndicator::Limited(LimitReason::NoInbound),
+        ConnectivityIndicator::Offline,
+    ];
+    for indicator in widest {
+        let segment = format!(" {} · Ctrl+D: Diagnostics ", indicator.label());
+        assert!(footer_fits(MIN_FOOTER_WIDTH, &segment));
+        assert!(display_width(&segment) < 50);
+    }
+}
@@ -0,0 +1,125 @@
+// Delivery Events Tests - Testing in-place chat list updates from background senders
+
+use crate::queue::{MessageQueue, Priority};
+use crate::storage::{Chat, Message};
+use crate::tui::{App, ChatSummary, DeliveryEvent, DeliveryUpdate};
+use tempfile::TempDir;
+
+fn create_test_app() -> (App, TempDir) {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let app = App::new_with_settings(Some(temp_dir.path().join("settings.json")))
+        .expect("Failed to create app");
+    (app, temp_dir)
+}
+
+fn pending_chat(uid: &str) -> Chat {
+    let mut chat = Chat::new(uid.to_string());
+    chat.mark_has_pending();
+    chat
+}
+
+#[test]
+fn test_worker_delivery_clears_pending_without_reload() {
+    let (mut app, _temp_dir) = create_test_app();
+    app.app_state.chats.push(pending_chat("alice"));
+    app.app_state.chats.push(pending_chat("bob"));
+    app.show_chat_list_screen();
+    assert_eq!(app.chat_summary().pending, 2);
+
+    // Nothing published yet
+    assert!(!app.process_delivery_events());
+
+    // The retry worker delivers alice's backlog from its own thread
+    let events = app.delivery_event_sender();
+    std::thread::spawn(move || {
+        events
+            .send(DeliveryEvent::new("alice", DeliveryUpdate::Delivered, false))
+            .unwrap();
+    })
+    .join()
+    .unwrap();
+
+    assert!(app.process_delivery_events());
+    assert!(!app.app_state.chats[0].has_pending());
+    assert!(app.app_state.chats[1].has_pending());
+    assert_eq!(app.chat_summary(), ChatSummary { unread: 0, pending: 1 });
+
+    // A delivery that leaves more queued keeps the indicator
+    app.delivery_event_sender()
+        .send(DeliveryEvent::new("bob", DeliveryUpdate::Delivered, true))
+        .unwrap();
+    assert!(!app.process_delivery_events());
+    assert!(app.app_state.chats[1].has_pending());
+}
+
+#[test]
+fn test_dropped_message_adds_failed_badge() {
+    let (mut app, _temp_dir) = create_test_app();
+    app.app_state.chats.push(pending_chat("alice"));
+
+    app.delivery_event_sender()
+        .send(DeliveryEvent::new("alice", DeliveryUpdate::Failed, false))
+        .unwrap();
+    assert!(app.process_delivery_events());
+    let chat = &app.app_state.chats[0];
+    assert!(chat.has_failed());
+    assert!(!chat.has_pending());
+
+    // A later successful delivery clears it
+    app.delivery_event_sender()
+        .send(DeliveryEvent::new("alice", DeliveryUpdate::Delivered, false))
+        .unwrap();
+    assert!(app.process_delivery_events());
+    assert!(!app.app_state.chats[0].has_failed());
+}
+
+#[test]
+fn test_event_updates_and_unknown_contacts() {
+    let mut chats = vec![Chat::new("alice".to_string())];
+
+    assert!(DeliveryEvent::new("alice", DeliveryUpdate::Queued, true).apply(&mut chats));
+    assert!(chats[0].has_pending());
+
+    // An answered ping marks the chat active and clears pending
+    assert!(DeliveryEvent::new("alice", DeliveryUpdate::PingAnswered, false).apply(&mut chats));
+    assert!(chats[0].is_active);
+    assert!(!chats[0].has_pending());
+
+    // Repeated or unknown events change nothing
+    assert!(!DeliveryEvent::new("alice", DeliveryUpdate::PingAnswered, false).apply(&mut chats));
+    assert!(!DeliveryEvent::new("mallory", DeliveryUpdate::Failed, false).apply(&mut chats));
+}
+
+#[test]
+fn test_event_reads_remaining_queue() {
+    let mut queue = MessageQueue::new().expect("Failed to create queue");
+    let message = Message::new("m1".to_string(), "me".to_string(), "alice".to_string(), vec![], 0);
+    queue.enqueue(message, Priority::Normal).unwrap();
+
+    let event = DeliveryEvent::from_queue(&queue, "alice", DeliveryUpdate::Delivered);
+    assert!(event.still_pending);
+    let event = DeliveryEvent::from_queue(&queue, "bob", DeliveryUpdate::Delivered);
+    assert!(!event.still_pending);
+    // Queued is pending by definition
+    let event = DeliveryEvent::from_queue(&queue, "bob", DeliveryUpdate::Queued);
+    assert!(event.still_pending);
+}
+
+#[test]
+fn test_reconciliation_corrects_missed_event() {
+    let (mut app, _temp_dir) = create_test_app();
+    app.queue.clear().unwrap();
+    app.app_state.chats.push(pending_chat("alice"));
+    app.app_state.chats.push(Chat::new("bob".to_string()));
+
+    // alice's delivery event was lost; bob's message was queued without one
+    let message = Message::new("m1".to_string(), "me".to_string(), "bob".to_string(), vec![], 0);
+    app.queue.enqueue(message, Priority::Normal).unwrap();
+
+    assert!(app.reconcile_delivery_status());
+    assert!(!app.app_state.chats[0].has_pending());
+    assert!(app.app_state.chats[1].has_pending());
+
+    // Already consistent
+    assert!(!app.reconcile_delivery_status());
+}
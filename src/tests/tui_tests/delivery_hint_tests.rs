@@ -0,0 +1,136 @@
+// Delivery Hint Tests - Queue age annotations, stale-address and expired-token hints, banner actions
+
+use crate::queue::Priority;
+use crate::storage::{Contact, Message};
+use crate::tui::ui::ui;
+use crate::tui::{delivery_hint, queued_annotation, App, DeliveryHint, Screen};
+use chrono::{Duration, TimeZone, Utc};
+use ratatui::{backend::TestBackend, Terminal};
+use tempfile::TempDir;
+
+/// App with a chat open to a contact whose token expires in 30 days
+fn app_with_open_chat(temp_dir: &TempDir) -> App {
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.contacts.push(Contact::new(
+        "alice_uid".to_string(),
+        "192.168.1.100:8080".to_string(),
+        vec![0; 32],
+        vec![0; 32],
+        Utc::now() + Duration::days(30),
+    ));
+    app.app_state.add_chat("alice_uid".to_string());
+    app.save_state().unwrap();
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+    app
+}
+
+#[test]
+fn test_queued_annotation_buckets() {
+    assert_eq!(queued_annotation(Duration::seconds(20)), "queued — just now");
+    assert_eq!(queued_annotation(Duration::minutes(1)), "queued — waiting 1 min");
+    assert_eq!(queued_annotation(Duration::minutes(59)), "queued — waiting 59 min");
+    assert_eq!(queued_annotation(Duration::minutes(60)), "queued — waiting 1 hour");
+    assert_eq!(queued_annotation(Duration::hours(23) + Duration::minutes(59)), "queued — waiting 23 hours");
+    assert_eq!(queued_annotation(Duration::hours(24)), "queued — waiting 1 day");
+    assert_eq!(queued_annotation(Duration::days(3) + Duration::hours(20)), "queued — waiting 3 days");
+
+    // The chat view annotates the queued message and shows the banner
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_open_chat(&temp_dir);
+    let me = app.keypair.uid.to_string();
+    let chat = app.app_state.get_chat_mut("alice_uid").unwrap();
+    chat.append_message(Message::new("m1".to_string(), me, "alice_uid".to_string(), b"hi".to_vec(), 1));
+    let three_days_ago = (Utc::now() - Duration::days(3)).timestamp_millis();
+    app.chat_view_screen.as_mut().unwrap().queued_since.insert("m1".to_string(), three_days_ago);
+
+    let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+    terminal.draw(|f| ui(f, &app)).unwrap();
+    let rendered: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+    assert!(rendered.contains("↻ queued — waiting 3 days"));
+    assert!(rendered.contains("address may be stale"));
+}
+
+#[test]
+fn test_hint_threshold_transitions() {
+    let queued_at = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+    let expiry = Some(queued_at + Duration::days(10));
+    let at = |hours: i64| queued_at + Duration::hours(hours);
+
+    // Under a day: still waiting
+    assert_eq!(delivery_hint(queued_at, expiry, None, at(0)), DeliveryHint::Waiting);
+    assert_eq!(delivery_hint(queued_at, expiry, None, at(23)), DeliveryHint::Waiting);
+
+    // A day without word from the contact: its address may be stale
+    assert_eq!(delivery_hint(queued_at, expiry, None, at(24)), DeliveryHint::StaleAddress);
+    assert_eq!(delivery_hint(queued_at, expiry, Some(at(-5)), at(48)), DeliveryHint::StaleAddress);
+
+    // Heard from since queueing: the path works, no stale hint
+    assert_eq!(delivery_hint(queued_at, expiry, Some(at(2)), at(48)), DeliveryHint::Waiting);
+
+    assert_eq!(DeliveryHint::Waiting.banner(), None);
+    assert!(DeliveryHint::StaleAddress.banner().unwrap().contains("Ctrl+R: Resend introduction ping"));
+}
+
+#[test]
+fn test_undeliverable_after_token_expiry() {
+    let queued_at = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+    let expiry = queued_at + Duration::days(10);
+
+    assert_eq!(
+        delivery_hint(queued_at, Some(expiry), None, expiry - Duration::seconds(1)),
+        DeliveryHint::StaleAddress
+    );
+    assert_eq!(delivery_hint(queued_at, Some(expiry), None, expiry), DeliveryHint::Undeliverable);
+
+    // Hearing from the contact does not make an expired token usable
+    let heard = Some(expiry - Duration::hours(1));
+    assert_eq!(delivery_hint(queued_at, Some(expiry), heard, expiry + Duration::days(1)), DeliveryHint::Undeliverable);
+
+    // Neither is there anything to deliver to without a contact
+    assert_eq!(delivery_hint(queued_at, None, None, queued_at), DeliveryHint::Undeliverable);
+    assert!(DeliveryHint::Undeliverable.banner().unwrap().contains("new token is imported"));
+
+    // Banners fit the chat view at 80 columns (2-column margins)
+    for hint in [DeliveryHint::StaleAddress, DeliveryHint::Undeliverable] {
+        assert!(hint.banner().unwrap().chars().count() <= 76);
+    }
+}
+
+#[test]
+fn test_banner_actions() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_open_chat(&temp_dir);
+    let now = Utc::now();
+
+    // Nothing queued, no hint
+    assert_eq!(app.chat_delivery_hint(now), None);
+
+    let me = app.keypair.uid.to_string();
+    let message = Message::new("m1".to_string(), me, "alice_uid".to_string(), b"hi".to_vec(), 1);
+    app.queue.enqueue(message, Priority::Normal).unwrap();
+    app.refresh_queued_since();
+    assert_eq!(app.chat_delivery_hint(now), Some(DeliveryHint::Waiting));
+    app.delivery_banner_action(now);
+    assert!(!app.queue.has_queued_type_for("alice_uid", "ping").unwrap());
+
+    // Stale address: Ctrl+R queues one introduction ping
+    let later = now + Duration::hours(25);
+    assert_eq!(app.chat_delivery_hint(later), Some(DeliveryHint::StaleAddress));
+    app.delivery_banner_action(later);
+    assert!(app.queue.has_queued_type_for("alice_uid", "ping").unwrap());
+    assert_eq!(app.chat_view_screen.as_ref().unwrap().queued_since.len(), 2);
+    assert!(app.process_delivery_events());
+    assert!(app.app_state.get_chat("alice_uid").unwrap().has_pending_messages);
+
+    app.delivery_banner_action(later);
+    assert_eq!(app.queue.queued_at_for("alice_uid").unwrap().len(), 2);
+    let status = app.chat_view_screen.as_ref().unwrap().status_message.clone().unwrap();
+    assert_eq!(status, "Introduction ping already queued");
+
+    // Expired token: Ctrl+R opens the import screen
+    let expired = now + Duration::days(31);
+    assert_eq!(app.chat_delivery_hint(expired), Some(DeliveryHint::Undeliverable));
+    app.delivery_banner_action(expired);
+    assert_eq!(app.current_screen, Screen::ImportContact);
+}
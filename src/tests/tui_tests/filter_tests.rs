@@ -0,0 +1,49 @@
+// Filter Tests - Fuzzy filtering and selection used by list overlays
+
+use crate::tui::filter::{fuzzy_score, FilterList};
+
+#[test]
+fn test_fuzzy_score_matching() {
+    assert_eq!(fuzzy_score("", "anything"), Some(0));
+    assert!(fuzzy_score("mtg", "Meeting link").is_some());
+    assert!(fuzzy_score("MEET", "meeting").is_some());
+    assert!(fuzzy_score("gtm", "Meeting").is_none(), "Characters must appear in order");
+    assert!(fuzzy_score("meetings", "meeting").is_none());
+
+    // Consecutive and word-start matches rank higher
+    assert!(fuzzy_score("pay", "Payment") > fuzzy_score("pay", "Replay any"));
+}
+
+#[test]
+fn test_filter_list_matches_and_selection() {
+    let names = ["Meeting link", "Bank account", "Address", "Meet later"];
+    let mut filter = FilterList::new();
+
+    // Empty query lists everything in order
+    assert_eq!(filter.matches(names), vec![0, 1, 2, 3]);
+
+    filter.push_char('m');
+    filter.push_char('e');
+    filter.push_char('e');
+    assert_eq!(filter.matches(names), vec![0, 3]);
+    assert_eq!(filter.selected_index(names), Some(0));
+
+    filter.next(2);
+    assert_eq!(filter.selected_index(names), Some(3));
+    filter.next(2);
+    assert_eq!(filter.selected_index(names), Some(0), "Selection wraps");
+    filter.previous(2);
+    assert_eq!(filter.selected_index(names), Some(3));
+
+    // Changing the query goes back to the best match
+    filter.push_char('t');
+    assert_eq!(filter.selected, 0);
+
+    filter.push_char('x');
+    assert!(filter.matches(names).is_empty());
+    assert_eq!(filter.selected_index(names), None);
+
+    while filter.pop_char() {}
+    assert!(filter.query.is_empty());
+    assert!(!filter.pop_char());
+}
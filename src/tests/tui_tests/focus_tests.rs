@@ -0,0 +1,137 @@
+// Focus Tests - Read-marking gate, background tick rate, buffered new-message flashes and terminals without focus events
+
+use crate::storage::{AlertMode, Contact, Message};
+use crate::tui::{App, FocusState, UiEffect, CHAT_VIEW_PAGE_SIZE, UNFOCUSED_POLL_INTERVAL};
+use chrono::Utc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const NORMAL_TICK: Duration = Duration::from_millis(100);
+
+/// App with a chat from Bob holding `incoming` of his messages, none read yet
+fn app_with_chat(temp_dir: &TempDir, incoming: usize) -> App {
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    let me = app.keypair.uid.to_string();
+    app.app_state.contacts.push(Contact::new(
+        "bob".to_string(),
+        "192.168.1.100:8080".to_string(),
+        vec![1; 32],
+        vec![2; 32],
+        Utc::now() + chrono::Duration::days(30),
+    ));
+    let chat = app.app_state.get_or_create_chat("bob");
+    for i in 0..incoming {
+        chat.append_message(Message::new(format!("m{}", i), "bob".to_string(), me.clone(), b"hi".to_vec(), i as i64));
+    }
+    app.save_state().unwrap();
+    app
+}
+
+fn acknowledged(app: &App) -> Option<&str> {
+    app.read_receipts_sent.get("bob").map(String::as_str)
+}
+
+#[test]
+fn test_read_marking_follows_focus() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_chat(&temp_dir, 3);
+
+    // Opened in the background: nothing is marked read until focus returns
+    app.focus_lost();
+    app.open_chat("bob");
+    assert_eq!(acknowledged(&app), None);
+    app.focus_gained(Instant::now());
+    assert_eq!(acknowledged(&app), Some("m2"));
+
+    // A message arriving while away is marked read on return, with the chat at the bottom
+    app.focus_lost();
+    let me = app.keypair.uid.to_string();
+    app.app_state.get_chat_mut("bob").unwrap().append_message(Message::new(
+        "m3".to_string(),
+        "bob".to_string(),
+        me,
+        b"back?".to_vec(),
+        10,
+    ));
+    app.focus_gained(Instant::now());
+    assert_eq!(acknowledged(&app), Some("m3"));
+}
+
+#[test]
+fn test_focus_gain_skips_read_marking_when_scrolled_up() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_chat(&temp_dir, CHAT_VIEW_PAGE_SIZE * 2);
+    app.focus_lost();
+    app.open_chat("bob");
+
+    // The view opens at the oldest page, so the newest message is not on screen
+    let chat = app.app_state.get_chat("bob").unwrap();
+    assert!(!app.chat_view_screen.as_ref().unwrap().is_at_bottom(chat));
+    app.focus_gained(Instant::now());
+    assert_eq!(acknowledged(&app), None);
+
+    // Scrolled to the bottom, the next return marks it read
+    app.chat_view_screen.as_mut().unwrap().scroll_offset = CHAT_VIEW_PAGE_SIZE;
+    app.focus_lost();
+    app.focus_gained(Instant::now());
+    assert_eq!(acknowledged(&app), Some(format!("m{}", CHAT_VIEW_PAGE_SIZE * 2 - 1).as_str()));
+}
+
+#[test]
+fn test_tick_rate_and_buffered_alerts() {
+    let mut focus = FocusState::new();
+    assert_eq!(focus.poll_interval(NORMAL_TICK), NORMAL_TICK);
+    focus.lost();
+    assert_eq!(focus.poll_interval(NORMAL_TICK), UNFOCUSED_POLL_INTERVAL);
+    // A slower normal tick is never sped up
+    assert_eq!(focus.poll_interval(Duration::from_secs(5)), Duration::from_secs(5));
+    focus.gained();
+    assert_eq!(focus.poll_interval(NORMAL_TICK), NORMAL_TICK);
+
+    // Flashes are held back in the background; the bell still rings
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_chat(&temp_dir, 0);
+    app.app_state.settings.alert_mode = AlertMode::Both;
+    let now = Instant::now();
+    app.focus_lost();
+    for _ in 0..3 {
+        app.raise_alert(now);
+    }
+    assert!(app.take_bell());
+    assert!(!app.effects.is_active(UiEffect::HeaderFlash, now));
+    assert_eq!(app.focus.missed_alerts(), 3);
+
+    // Replayed as one flash and one notification
+    app.focus_gained(now);
+    assert!(app.effects.is_active(UiEffect::HeaderFlash, now));
+    assert_eq!(app.notifications.visible(Instant::now()), Some("3 new message alert(s) while away"));
+    assert_eq!(app.focus.missed_alerts(), 0);
+
+    // Nothing to replay on the next return
+    app.notifications.current = None;
+    app.effects.prune(now + Duration::from_secs(1));
+    app.focus_lost();
+    app.focus_gained(now + Duration::from_secs(1));
+    assert!(!app.effects.is_active(UiEffect::HeaderFlash, now + Duration::from_secs(1)));
+    assert_eq!(app.notifications.current, None);
+}
+
+#[test]
+fn test_terminal_without_focus_events_behaves_as_before() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = app_with_chat(&temp_dir, 2);
+
+    // No focus event ever arrives: always focused
+    assert!(app.focus.is_focused());
+    assert!(!app.focus.reports_focus());
+    assert_eq!(app.focus.poll_interval(NORMAL_TICK), NORMAL_TICK);
+
+    app.app_state.settings.alert_mode = AlertMode::Flash;
+    let now = Instant::now();
+    app.raise_alert(now);
+    assert!(app.effects.is_active(UiEffect::HeaderFlash, now));
+    assert_eq!(app.focus.missed_alerts(), 0);
+
+    app.open_chat("bob");
+    assert_eq!(acknowledged(&app), Some("m1"));
+}
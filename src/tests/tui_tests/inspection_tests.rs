@@ -0,0 +1,365 @@
+// Inspection Tests - Read-only enforcement, no transport or workers, refusal statuses, live-directory refusal, leftover journal or WAL refusal, read screens on a foreign fixture
+
+use crate::crypto::KeyPair;
+use crate::queue::{MessageQueue, Priority};
+use crate::storage::{
+    open_read_only, AppState, Contact, Message, RuntimeInfo, SessionMarker, Storage, WriteLog, DATABASE_FILE_NAME,
+    QUEUE_FILE_NAME,
+};
+use crate::tui::ui::ui;
+use crate::tui::{App, Inspection, SaveTarget, Screen, StageState, StartupStage, TransportServerStatus};
+use chrono::{Duration, Utc};
+use ratatui::{backend::TestBackend, Terminal};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use crate::tests::helpers::dead_pid;
+
+/// Another installation's app_data: identity, one contact with a chat,
+/// one queued message and a log file
+fn seed_foreign_dir() -> (TempDir, KeyPair, Contact) {
+    let dir = TempDir::new().unwrap();
+    let owner = KeyPair::generate().unwrap();
+    let bob_keys = KeyPair::generate().unwrap();
+    let bob = Contact::new(
+        bob_keys.uid.to_string(),
+        "127.0.0.1:9".to_string(),
+        bob_keys.public_key.clone(),
+        bob_keys.x25519_public.clone(),
+        Utc::now() + Duration::days(30),
+    );
+    let owner_uid = owner.uid.to_string();
+
+    let storage = Storage::new(dir.path().join(DATABASE_FILE_NAME)).unwrap();
+    let mut state = AppState::new();
+    state.user_keypair = Some(owner.clone());
+    state.add_contact(bob.clone());
+    let chat = state.get_or_create_chat(&bob.uid);
+    chat.append_message(Message::new("m1".to_string(), bob.uid.clone(), owner_uid.clone(), b"hello from the past".to_vec(), 1000));
+    chat.append_message(Message::new("m2".to_string(), owner_uid.clone(), bob.uid.clone(), b"still queued".to_vec(), 2000));
+    state.save_to_db(&storage).unwrap();
+    drop(storage);
+
+    let mut queue = MessageQueue::new_for_identity(dir.path().join(QUEUE_FILE_NAME), &owner).unwrap();
+    queue
+        .enqueue(Message::new("m2".to_string(), owner_uid.clone(), bob.uid.clone(), b"still queued".to_vec(), 2000), Priority::Normal)
+        .unwrap();
+    drop(queue);
+
+    std::fs::create_dir_all(dir.path().join("logs")).unwrap();
+    std::fs::write(dir.path().join("logs").join("pure2p.log"), "INFO started\nWARN delivery failed\n").unwrap();
+    (dir, owner, bob)
+}
+
+/// Every file under `dir` with its contents
+fn files(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    let mut found = BTreeMap::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            found.extend(files(&path));
+        } else {
+            found.insert(path.clone(), std::fs::read(&path).unwrap());
+        }
+    }
+    found
+}
+
+/// Rows of the whole UI rendered for the current screen
+fn render(app: &App) -> Vec<String> {
+    let mut terminal = Terminal::new(TestBackend::new(160, 40)).unwrap();
+    terminal.draw(|f| ui(f, app)).unwrap();
+    let buffer = terminal.backend().buffer();
+    buffer
+        .content()
+        .chunks(buffer.area.width as usize)
+        .map(|row| row.iter().map(|cell| cell.symbol()).collect())
+        .collect()
+}
+
+fn fingerprint(keypair: &KeyPair) -> String {
+    keypair.uid.to_string()[..8].to_string()
+}
+
+#[test]
+fn test_read_only_connections_deny_and_record_writes() {
+    let (dir, _, bob) = seed_foreign_dir();
+    let before = files(dir.path());
+
+    let writes = WriteLog::new();
+    let conn = open_read_only(&dir.path().join(DATABASE_FILE_NAME), &writes).unwrap();
+
+    // Reads, including pragmas that take an argument, go through unrecorded
+    let contacts: i64 = conn.query_row("SELECT COUNT(*) FROM contacts", [], |row| row.get(0)).unwrap();
+    assert_eq!(contacts, 1);
+    conn.query_row("PRAGMA user_version", [], |_| Ok(())).unwrap();
+    conn.prepare("PRAGMA table_info(contacts)").unwrap();
+    conn.query_row("PRAGMA quick_check", [], |_| Ok(())).unwrap();
+    assert!(writes.is_empty(), "{:?}", writes.attempts());
+
+    // Every kind of write is denied and recorded
+    assert!(conn.execute("DELETE FROM contacts", []).is_err());
+    assert!(conn.execute("UPDATE contacts SET ip = 'x'", []).is_err());
+    assert!(conn.execute_batch("CREATE TABLE scratch (a INTEGER)").is_err());
+    assert!(conn.execute_batch("PRAGMA user_version = 7").is_err());
+    assert!(conn.execute_batch("PRAGMA query_only = OFF").is_err());
+    // Creating a table is refused at its schema row, before the table itself
+    assert_eq!(
+        writes.attempts(),
+        ["delete from contacts", "update contacts.ip", "insert into sqlite_master", "pragma user_version = 7", "pragma query_only = OFF"]
+    );
+    drop(conn);
+
+    // The same through the app's storage and queue, which share the log
+    let mut app = App::new_for_inspection(dir.path()).unwrap();
+    let writes = app.inspection.as_ref().unwrap().writes.clone();
+    assert!(writes.is_empty(), "{:?}", writes.attempts());
+    assert!(app.storage.save_contact(&bob).is_err());
+    let message = Message::new("m3".to_string(), "me".to_string(), bob.uid.clone(), b"no".to_vec(), 3000);
+    assert!(app.queue.enqueue(message, Priority::Normal).is_err());
+    assert!(app.storage.clone().delete_contact(&bob.uid).is_err());
+    let attempts = writes.attempts();
+    assert!(attempts.iter().any(|a| a.starts_with("insert into contacts")), "{:?}", attempts);
+    assert!(attempts.iter().any(|a| a == "insert into message_queue"), "{:?}", attempts);
+    assert!(attempts.iter().any(|a| a.starts_with("delete from")), "{:?}", attempts);
+    drop(app);
+
+    assert_eq!(files(dir.path()), before);
+}
+
+#[test]
+fn test_transport_workers_and_connectivity_never_start() {
+    let (dir, _, _) = seed_foreign_dir();
+    let mut app = App::new_for_inspection(dir.path()).unwrap();
+
+    app.complete_deferred_startup().unwrap();
+    for _ in 0..3 {
+        app.poll_startup(std::time::Instant::now());
+        app.poll_startup_connectivity();
+        app.poll_transport_status();
+        app.process_delivery_events();
+        assert!(!app.maybe_check_for_updates(Utc::now()));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+
+    assert_eq!(*app.transport_server_status.lock().unwrap(), TransportServerStatus::NotStarted);
+    for stage in StartupStage::ALL {
+        assert!(matches!(app.startup.state(stage), StageState::Waiting), "{:?} is {:?}", stage, app.startup.state(stage));
+    }
+    assert!(app.diagnostics_refresh_handle.is_none());
+    assert!(app.connectivity_result.is_none());
+    assert!(app.tls_fingerprint.is_none());
+
+    // Started directly, they still refuse
+    assert!(app.start_transport().is_err());
+    assert!(app.start_retry_worker().is_err());
+    assert_eq!(*app.transport_server_status.lock().unwrap(), TransportServerStatus::NotStarted);
+    assert!(app.recovery_screen.is_none());
+    assert!(!dir.path().join("session.marker").exists());
+}
+
+/// An action run on the app
+type AppAction = fn(&mut App);
+
+#[test]
+fn test_mutating_actions_are_refused_with_a_status() {
+    let (dir, owner, bob) = seed_foreign_dir();
+    let before = files(dir.path());
+    let mut app = App::new_for_inspection(dir.path()).unwrap();
+    app.complete_deferred_startup().unwrap();
+    let status = |app: &App| app.inspection.as_ref().unwrap().status(std::time::Instant::now()).map(str::to_string);
+
+    app.show_share_contact_screen();
+    assert_eq!(app.current_screen, Screen::MainMenu);
+    assert_eq!(status(&app).as_deref(), Some("Read only: cannot share a contact token in inspection mode"));
+    app.show_import_contact_screen();
+    assert_eq!(app.current_screen, Screen::MainMenu);
+    assert_eq!(status(&app).as_deref(), Some("Read only: cannot import contacts in inspection mode"));
+
+    // Chat list and chat view
+    app.show_chat_list_screen();
+    app.show_delete_confirmation();
+    assert!(!app.chat_list_screen.as_ref().unwrap().show_delete_confirmation);
+    assert_eq!(status(&app).as_deref(), Some("Read only: cannot delete chats in inspection mode"));
+    app.open_selected_chat();
+    app.chat_view_screen.as_mut().unwrap().input = "a new message".to_string();
+    app.send_message_in_chat();
+    assert_eq!(app.app_state.get_chat(&bob.uid).unwrap().messages.len(), 2);
+    assert_eq!(app.queue.size().unwrap(), 1);
+    assert_eq!(status(&app).as_deref(), Some("Read only: cannot send messages in inspection mode"));
+
+    let refused: [(&str, AppAction); 10] = [
+        ("pin messages", |app| app.toggle_pin_selected()),
+        ("star messages", |app| app.toggle_star_selected()),
+        ("edit messages", |app| app.start_edit_selected()),
+        ("save files", |app| app.export_chat_jsonl()),
+        ("save settings", |app| app.save_settings()),
+        ("take snapshots", |app| assert!(app.take_snapshot().is_none())),
+        ("run connectivity checks", |app| app.trigger_diagnostics_refresh()),
+        ("retry deliveries", |app| app.requeue_failed(true)),
+        ("undo deletes", |app| assert!(!app.undo_last_delete(Utc::now()))),
+        ("probe contacts", |app| assert!(!app.request_peer_probe(&app.app_state.contacts[0].uid.clone()))),
+    ];
+    for (action, run) in refused {
+        run(&mut app);
+        assert_eq!(status(&app), Some(format!("Read only: cannot {} in inspection mode", action)));
+    }
+    assert!(app.path_picker.is_none());
+    assert!(app.diagnostics_refresh_handle.is_none());
+
+    // Reading a file is not refused
+    app.open_path_picker(SaveTarget::ArchiveVerify);
+    assert!(app.path_picker.is_some());
+
+    // The banner carries the refusal for a while, then only the mode
+    let banner = render(&app)[0].clone();
+    assert!(banner.contains(&format!("INSPECTION MODE — read only — identity: {}", fingerprint(&owner))), "{}", banner);
+    assert!(banner.contains("Read only: cannot probe contacts in inspection mode"), "{}", banner);
+    let mut inspection = Inspection::new(dir.path().to_path_buf(), WriteLog::new());
+    let at = std::time::Instant::now();
+    inspection.refuse("send messages", at);
+    assert!(inspection.status(at + std::time::Duration::from_secs(4)).is_some());
+    assert!(inspection.status(at + std::time::Duration::from_secs(5)).is_none());
+
+    assert!(app.inspection.as_ref().unwrap().writes.is_empty());
+    drop(app);
+    assert_eq!(files(dir.path()), before);
+}
+
+#[test]
+fn test_live_directory_is_refused() {
+    let (dir, _, _) = seed_foreign_dir();
+    let runtime_path = dir.path().join("runtime.json");
+    let marker_path = dir.path().join("session.marker");
+
+    // A running instance's runtime file or session marker
+    let live = RuntimeInfo { pid: std::process::id(), port: 4100, started_at: 1 };
+    std::fs::write(&runtime_path, serde_json::to_vec(&live).unwrap()).unwrap();
+    let error = App::new_for_inspection(dir.path()).err().unwrap().to_string();
+    assert!(error.contains(&format!("in use by a running instance (pid {})", std::process::id())), "{}", error);
+    assert!(error.contains("inspect a copy"), "{}", error);
+    std::fs::remove_file(&runtime_path).unwrap();
+
+    let marker = SessionMarker { pid: std::process::id(), started_at: 1 };
+    std::fs::write(&marker_path, serde_json::to_vec(&marker).unwrap()).unwrap();
+    assert!(App::new_for_inspection(dir.path()).is_err());
+
+    // Left behind by dead processes: inspected, and left as they were
+    let stale = RuntimeInfo { pid: dead_pid(), port: 4100, started_at: 1 };
+    std::fs::write(&runtime_path, serde_json::to_vec(&stale).unwrap()).unwrap();
+    std::fs::write(&marker_path, serde_json::to_vec(&SessionMarker { pid: stale.pid, started_at: 1 }).unwrap()).unwrap();
+    let before = files(dir.path());
+    let mut app = App::new_for_inspection(dir.path()).unwrap();
+    app.complete_deferred_startup().unwrap();
+    app.end_session();
+    drop(app);
+    assert_eq!(files(dir.path()), before);
+
+    // Not an app_data directory at all
+    let empty = TempDir::new().unwrap();
+    let error = App::new_for_inspection(empty.path()).err().unwrap().to_string();
+    assert!(error.contains("not an app_data directory"), "{}", error);
+}
+
+#[test]
+fn test_leftover_journal_or_wal_is_refused() {
+    let (dir, _, _) = seed_foreign_dir();
+    let journal_path = dir.path().join(format!("{}-journal", DATABASE_FILE_NAME));
+    let wal_path = dir.path().join(format!("{}-wal", QUEUE_FILE_NAME));
+
+    // A hot journal copied along after a crash: refused, and left as it was
+    std::fs::write(&journal_path, b"rollback pages of an unfinished transaction").unwrap();
+    let before = files(dir.path());
+    let error = App::new_for_inspection(dir.path()).err().unwrap().to_string();
+    assert!(error.contains("unfinished transaction"), "{}", error);
+    assert!(error.contains(&journal_path.display().to_string()), "{}", error);
+    assert_eq!(files(dir.path()), before);
+
+    // An empty journal holds nothing to recover
+    std::fs::write(&journal_path, b"").unwrap();
+    let mut app = App::new_for_inspection(dir.path()).unwrap();
+    app.complete_deferred_startup().unwrap();
+    drop(app);
+    std::fs::remove_file(&journal_path).unwrap();
+
+    // The same for a WAL next to the queue database
+    std::fs::write(&wal_path, b"frames not yet checkpointed").unwrap();
+    let error = open_read_only(&dir.path().join(QUEUE_FILE_NAME), &WriteLog::new()).err().unwrap().to_string();
+    assert!(error.contains("unfinished transaction"), "{}", error);
+    assert!(error.contains(&wal_path.display().to_string()), "{}", error);
+}
+
+#[test]
+fn test_read_screens_on_foreign_fixture() {
+    let (dir, owner, bob) = seed_foreign_dir();
+    let before = files(dir.path());
+    let mut app = App::new_for_inspection(dir.path()).unwrap();
+    let writes = app.inspection.as_ref().unwrap().writes.clone();
+    assert_eq!(app.keypair.uid, owner.uid);
+    app.complete_deferred_startup().unwrap();
+    assert!(app.messages_loaded);
+
+    let banner = format!("INSPECTION MODE — read only — identity: {}", fingerprint(&owner));
+    let check = |app: &App, expected: &str| {
+        let rows = render(app);
+        assert!(rows[0].contains(&banner), "{:?}: {}", app.current_screen, rows[0]);
+        assert!(rows.concat().contains(expected), "{:?} lacks {:?}", app.current_screen, expected);
+    };
+    check(&app, "Chat");
+
+    // Chats and contacts
+    app.show_chat_list_screen();
+    check(&app, &bob.uid[..8]);
+    app.show_contact_details();
+    assert!(app.chat_list_screen.as_ref().unwrap().contact_details.is_some());
+    check(&app, "127.0.0.1:9");
+    app.close_contact_details();
+    app.open_selected_chat();
+    assert_eq!(app.current_screen, Screen::ChatView);
+    check(&app, "hello from the past");
+
+    // Settings
+    app.show_settings_screen();
+    assert_eq!(app.current_screen, Screen::Settings);
+    check(&app, "Settings");
+
+    // Diagnostics: integrity report, queue contents and the log file
+    app.show_diagnostics_screen();
+    assert_eq!(app.diagnostics_screen.as_ref().unwrap().queue_size, 1);
+    app.run_integrity_report();
+    let integrity = app.diagnostics_screen.as_ref().unwrap().status_message.clone().unwrap();
+    assert!(integrity.starts_with("✓ Database intact"), "{}", integrity);
+    app.toggle_queue_view();
+    let rows = app.diagnostics_screen.as_ref().unwrap().queue_rows.clone().unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].message_id, "m2");
+    assert_eq!(rows[0].content_len, b"still queued".len());
+    check(&app, "Queue (1)");
+    app.toggle_queue_view();
+    assert!(app.diagnostics_screen.as_ref().unwrap().queue_rows.is_none());
+
+    app.open_log_viewer();
+    assert_eq!(app.current_screen, Screen::Capture);
+    assert_eq!(app.capture_screen.as_ref().unwrap().lines, ["INFO started", "WARN delivery failed"]);
+    check(&app, "Log pure2p.log");
+    app.close_capture_screen();
+    assert_eq!(app.current_screen, Screen::Diagnostics);
+
+    app.show_snapshots_screen();
+    check(&app, "Snapshot");
+    app.show_maintenance_screen();
+    check(&app, "INSPECTION MODE");
+
+    // Main loop work that would write is skipped
+    app.save_state().unwrap();
+    app.flush_deferred_writes();
+    app.run_ephemeral_maintenance(Utc::now());
+    app.run_soft_delete_maintenance(Utc::now() + Duration::days(60));
+    app.maybe_sync_expired_blocks(Utc::now());
+    app.reconcile_delivery_status();
+    app.back_to_main_menu();
+    drop(app);
+
+    assert!(writes.is_empty(), "{:?}", writes.attempts());
+    assert_eq!(files(dir.path()), before);
+}
@@ -0,0 +1,246 @@
+// Local Time Tests - UTC offset inference from message timestamps, snapping and sample rules, manual override, the night window and persistence
+
+use crate::messaging::handle_incoming_message;
+use crate::storage::{
+    format_utc_offset, infer_utc_offset, is_night_at, parse_utc_offset, record_offset_sample, snap_offset_minutes, AppState,
+    Contact, OffsetSample, Settings, Storage, UtcOffset, MAX_OFFSET_SAMPLES, MIN_OFFSET_SAMPLES, OFFSET_SAMPLE_MAX_AGE_DAYS,
+};
+use crate::tui::App;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use tempfile::TempDir;
+
+fn contact(uid: &str) -> Contact {
+    Contact::new(uid.to_string(), "192.168.1.100:8080".to_string(), vec![1; 32], vec![2; 32], Utc::now() + Duration::days(30))
+}
+
+fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 3, 1, hour, minute, 0).unwrap()
+}
+
+/// Samples of a peer `offset_minutes` ahead, received hourly up to `now`, each off by one of `skews_secs`
+fn samples(now: DateTime<Utc>, offset_minutes: i64, skews_secs: &[i64]) -> Vec<OffsetSample> {
+    skews_secs
+        .iter()
+        .enumerate()
+        .map(|(i, skew)| {
+            let received = now - Duration::hours(i as i64);
+            let sent = received + Duration::minutes(offset_minutes) + Duration::seconds(*skew);
+            OffsetSample::new(sent.timestamp_millis(), received)
+        })
+        .collect()
+}
+
+#[test]
+fn test_offset_inferred_from_skewed_clocks() {
+    let now = at(12, 0);
+
+    // India: clock a little off in both directions, plus delivery delay
+    assert_eq!(infer_utc_offset(&samples(now, 330, &[-40, 25, 90, -5, 12, 61]), now), Some(330));
+
+    // A peer stamping in UTC whose clock runs three minutes fast
+    assert_eq!(infer_utc_offset(&samples(now, 0, &[180, 175, 190, 182, 170]), now), Some(0));
+
+    // West of UTC, with one message that sat in a queue for hours
+    let mut skewed = samples(now, -300, &[10, -20, 30, 5, -15]);
+    skewed.push(OffsetSample::new((now - Duration::hours(9)).timestamp_millis(), now));
+    assert_eq!(infer_utc_offset(&skewed, now), Some(-300));
+
+    // Nepal's quarter hour survives the snapping
+    assert_eq!(infer_utc_offset(&samples(now, 345, &[3, -7, 20, 0, 9]), now), Some(345));
+}
+
+#[test]
+fn test_snapping_and_minimum_samples() {
+    // Nearest 15 minutes, either side of zero
+    assert_eq!(snap_offset_minutes(7 * 60_000), 0);
+    assert_eq!(snap_offset_minutes(8 * 60_000), 15);
+    assert_eq!(snap_offset_minutes(-8 * 60_000), -15);
+    assert_eq!(snap_offset_minutes((5 * 60 + 29) * 60_000), 5 * 60 + 30);
+
+    // One sample short of the minimum infers nothing
+    let now = at(12, 0);
+    let all = samples(now, 120, &[0; MIN_OFFSET_SAMPLES]);
+    assert_eq!(infer_utc_offset(&all[..MIN_OFFSET_SAMPLES - 1], now), None);
+    assert_eq!(infer_utc_offset(&all, now), Some(120));
+
+    // A clock off by more than any time zone is not a time zone
+    assert_eq!(infer_utc_offset(&samples(now, 20 * 60, &[0; MIN_OFFSET_SAMPLES]), now), None);
+
+    // Samples past the maximum age neither count nor stay
+    let old_now = now + Duration::days(OFFSET_SAMPLE_MAX_AGE_DAYS + 1);
+    assert_eq!(infer_utc_offset(&all, old_now), None);
+    let mut kept = all.clone();
+    record_offset_sample(&mut kept, OffsetSample::new(old_now.timestamp_millis(), old_now), old_now);
+    assert_eq!(kept.len(), 1);
+
+    // Only the newest samples are kept
+    let mut many = Vec::new();
+    for i in 0..MAX_OFFSET_SAMPLES + 10 {
+        let received = now + Duration::minutes(i as i64);
+        record_offset_sample(&mut many, OffsetSample::new(received.timestamp_millis(), received), received);
+    }
+    assert_eq!(many.len(), MAX_OFFSET_SAMPLES);
+    assert_eq!(many[0].received_at, (now + Duration::minutes(10)).timestamp_millis());
+}
+
+#[test]
+fn test_old_samples_decay() {
+    // The contact moved from UTC-3 to UTC+2 a month and a half ago: more
+    // samples from before, but they weigh far less than the recent ones
+    let now = at(12, 0);
+    let mut all = samples(now - Duration::days(45), -180, &[0; 8]);
+    all.extend(samples(now, 120, &[0; MIN_OFFSET_SAMPLES]));
+    assert_eq!(infer_utc_offset(&all, now), Some(120));
+
+    // Just as many old samples, all fresh, would have won
+    let mut fresh = samples(now, -180, &[0; 8]);
+    fresh.extend(samples(now, 120, &[0; MIN_OFFSET_SAMPLES]));
+    assert_eq!(infer_utc_offset(&fresh, now), Some(-180));
+}
+
+#[test]
+fn test_manual_override_wins() {
+    let now = Utc::now();
+    let mut bob = contact("bob");
+    for i in 0..MIN_OFFSET_SAMPLES as i64 {
+        let received = now - Duration::minutes(i);
+        bob.record_offset_sample((received + Duration::hours(2)).timestamp_millis(), received);
+    }
+    assert_eq!(bob.utc_offset(now), Some(UtcOffset::Inferred(120)));
+
+    bob.utc_offset_override = Some(-300);
+    assert_eq!(bob.utc_offset(now), Some(UtcOffset::Manual(-300)));
+    assert_eq!(bob.utc_offset(now).unwrap().describe(), "UTC-05:00");
+
+    bob.utc_offset_override = None;
+    assert_eq!(bob.utc_offset(now).unwrap().describe(), "UTC+02:00, inferred");
+    assert_eq!(UtcOffset::resolve(Some(0), None), Some(UtcOffset::Manual(0)));
+    assert_eq!(UtcOffset::resolve(None, None), None);
+
+    // Typed offsets
+    assert_eq!(parse_utc_offset("+5:30"), Some(330));
+    assert_eq!(parse_utc_offset("UTC-03:00"), Some(-180));
+    assert_eq!(parse_utc_offset("5:45"), Some(345));
+    assert_eq!(parse_utc_offset("-3"), Some(-180));
+    assert_eq!(parse_utc_offset("+14"), Some(14 * 60));
+    assert_eq!(parse_utc_offset("+15"), None);
+    assert_eq!(parse_utc_offset("-12:15"), None);
+    assert_eq!(parse_utc_offset("+5:20"), None);
+    assert_eq!(parse_utc_offset("+5:"), None);
+    assert_eq!(parse_utc_offset("abc"), None);
+    assert_eq!(format_utc_offset(-570), "UTC-09:30");
+}
+
+#[test]
+fn test_night_window_across_day_boundaries() {
+    // 22:30 UTC: 23:30 in UTC+1 (night), 07:30 next morning in UTC+9 (day)
+    assert!(is_night_at(at(22, 30), 60));
+    assert!(!is_night_at(at(22, 30), 9 * 60));
+    // 01:00 UTC is the previous evening in UTC-8 (17:00)
+    assert!(!is_night_at(at(1, 0), -8 * 60));
+    // 08:00 UTC is 03:00 in UTC-5, the night before
+    assert!(is_night_at(at(8, 0), -5 * 60));
+
+    // Edges: 23:00 starts the night, 07:00 ends it
+    assert!(is_night_at(at(23, 0), 0));
+    assert!(!is_night_at(at(22, 59), 0));
+    assert!(is_night_at(at(6, 59), 0));
+    assert!(!is_night_at(at(7, 0), 0));
+    // Half-hour zones shift the edges with them
+    assert!(is_night_at(at(1, 29), 330));
+    assert!(!is_night_at(at(1, 29), 345));
+}
+
+#[test]
+fn test_override_samples_and_night_warning_persist() {
+    let storage = Storage::new_in_memory().unwrap();
+    let now = Utc::now();
+    let mut bob = contact("bob");
+    bob.utc_offset_override = Some(330);
+    bob.record_offset_sample((now + Duration::hours(5)).timestamp_millis(), now);
+    storage.save_contact(&bob).unwrap();
+    storage.save_contact(&contact("carol")).unwrap();
+
+    let contacts = storage.load_contacts().unwrap();
+    let find = |uid: &str| contacts.iter().find(|c| c.uid == uid).unwrap();
+    assert_eq!(find("bob").utc_offset_override, Some(330));
+    assert_eq!(find("bob").offset_samples, bob.offset_samples);
+    assert_eq!(find("carol").utc_offset_override, None);
+    assert!(find("carol").offset_samples.is_empty());
+
+    assert!(Settings::default().night_warning_enabled);
+    storage.save_settings(&Settings { night_warning_enabled: false, ..Settings::default() }).unwrap();
+    assert!(!storage.load_settings().unwrap().unwrap().night_warning_enabled);
+}
+
+#[test]
+fn test_incoming_messages_record_the_sent_timestamp() {
+    let mut state = AppState::new();
+    state.contacts.push(contact("bob"));
+    // Three hours ahead: the stored message gets the receive time, the sample keeps the offset
+    let sent = (Utc::now() + Duration::hours(3)).timestamp_millis();
+    for i in 0..MIN_OFFSET_SAMPLES {
+        handle_incoming_message(&mut state, "bob", "me", &format!("m{}", i), b"hi".to_vec(), sent);
+    }
+    let bob = state.contact_by_uid("bob").unwrap();
+    assert_eq!(bob.offset_samples.len(), MIN_OFFSET_SAMPLES);
+    assert_eq!(bob.utc_offset(Utc::now()), Some(UtcOffset::Inferred(180)));
+    assert!(state.get_chat("bob").unwrap().messages[0].timestamp < sent);
+
+    // Strangers have no contact to learn about
+    handle_incoming_message(&mut state, "eve", "me", "e1", b"hi".to_vec(), sent);
+    assert!(state.contact_by_uid("eve").is_none());
+}
+
+/// What is typed in the details popup's UTC offset input, if open
+fn app_popup_input(app: &App) -> Option<String> {
+    app.chat_list_screen.as_ref()?.contact_details.as_ref()?.offset_input.clone()
+}
+
+#[test]
+fn test_offset_editing_and_night_warning_in_app() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.contacts.push(contact("bob"));
+    app.app_state.get_or_create_chat("bob");
+    app.show_chat_list_screen();
+    app.show_contact_details();
+
+    // An invalid offset keeps the input open
+    app.start_editing_utc_offset();
+    assert_eq!(app_popup_input(&app).as_deref(), Some(""));
+    for c in "+25x".chars() {
+        app.chat_list_screen.as_mut().unwrap().contact_details.as_mut().unwrap().offset_add_char(c);
+    }
+    assert_eq!(app_popup_input(&app).as_deref(), Some("+25"));
+    app.save_contact_utc_offset();
+    assert!(app_popup_input(&app).is_some());
+    assert_eq!(app.app_state.contact_by_uid("bob").unwrap().utc_offset_override, None);
+
+    // A valid one is stored; the input reopens pre-filled with it
+    app.chat_list_screen.as_mut().unwrap().contact_details.as_mut().unwrap().offset_input = Some("-5".to_string());
+    app.save_contact_utc_offset();
+    assert_eq!(app.app_state.contact_by_uid("bob").unwrap().utc_offset_override, Some(-300));
+    assert_eq!(
+        app.chat_list_screen.as_ref().unwrap().status_message.as_deref(),
+        Some("UTC offset set to UTC-05:00")
+    );
+    app.start_editing_utc_offset();
+    assert_eq!(app_popup_input(&app).as_deref(), Some("UTC-05:00"));
+
+    // The chat input warns during their night, only with something typed and the setting on
+    app.open_chat("bob");
+    let night = at(8, 0); // 03:00 their time
+    assert_eq!(app.night_warning(night), None);
+    app.chat_view_screen.as_mut().unwrap().input = "still up?".to_string();
+    assert_eq!(app.night_warning(night).as_deref(), Some("Likely night for them (03:00 their time)"));
+    assert_eq!(app.night_warning(at(15, 0)), None);
+    app.app_state.settings.night_warning_enabled = false;
+    assert_eq!(app.night_warning(night), None);
+
+    // Clearing the override leaves no offset until enough messages arrive
+    app.app_state.settings.night_warning_enabled = true;
+    app.app_state.contact_by_uid_mut("bob").unwrap().utc_offset_override = None;
+    assert_eq!(app.contact_local_time("bob", night), None);
+    assert_eq!(app.night_warning(night), None);
+}
@@ -1,25 +1,60 @@
 // TUI Tests Module - Testing the public tui module
 // Tests organized by TUI module structure:
-// - app_tests: App struct and business logic, modularized (44 tests)
+// - app_tests: App struct and business logic, modularized (67 tests)
 //   - initialization: App creation, state loading (6 tests)
-//   - navigation: Screen transitions, menu navigation (14 tests)
-//   - contact_import: Import validation, duplicate detection (3 tests)
-//   - chat_management: Chat creation, deletion, selection (14 tests)
-//   - messaging: Message sending (3 tests)
-//   - startup: Startup sync, connectivity (4 tests)
-// - screen_tests: All screen structs, modularized by screen type (76 tests)
-//   - share_contact_tests: ShareContactScreen (5 tests)
-//   - import_contact_tests: ImportContactScreen (10 tests)
-//   - chat_list_tests: ChatListScreen (5 tests)
-//   - chat_view_tests: ChatViewScreen (3 tests)
-//   - settings_tests: SettingsScreen (10 tests)
+//   - navigation: Screen transitions, menu navigation, templates in settings (15 tests)
+//   - contact_import: Import validation, duplicate detection, identity conflicts, incoming pings (6 tests)
+//   - chat_management: Chat creation, deletion, selection, contact notes, pin/star, saving content, history limit (20 tests)
+//   - messaging: Message sending, sanitization, sending-as confirmation, duplicate guard, template picker (9 tests)
+//   - startup: Startup sync, connectivity, deferred loading (6 tests)
+//   - storage_lock: Deferred writes while the database is locked (1 test)
+// - screen_tests: All screen structs, modularized by screen type (91 tests)
+//   - share_contact_tests: ShareContactScreen, endpoint editor (5 tests)
+//   - import_contact_tests: ImportContactScreen, signature preview (11 tests)
+//   - chat_list_tests: ChatListScreen, contact details popup (7 tests)
+//   - chat_view_tests: ChatViewScreen, message details, pin/star, input cursor, input counter (10 tests)
+//   - settings_tests: SettingsScreen, quiet hours fields, profile fields, error banner severity (16 tests)
 //   - startup_sync_tests: StartupSyncScreen (10 tests)
-//   - diagnostics_tests: DiagnosticsScreen (20 tests)
+//   - diagnostics_tests: DiagnosticsScreen, gateway probes (21 tests)
 //   - status_indicators_tests: Status badges and expiry (10 tests)
-// - types_tests: MenuItem enum and related types (3 tests)
-// - ui_tests: UI helper functions (4 tests)
+// - types_tests: MenuItem enum, StartupTimings and related types (4 tests)
+// - notifications_tests: In-app notifications, quiet hours suppression (5 tests)
+// - alerts_tests: Bell/flash triggers, effect expiry, alert mode, TTY gate (4 tests)
+// - alert_style_tests: Per-contact alert styles, notification sink, test-play, persistence (4 tests)
+// - local_time_tests: UTC offset inference, snapping, decay, manual override, night window, persistence (8 tests)
+// - path_picker_tests: Save-path overlay, ~ expansion, overwrite prompt, default folder, failure messages (4 tests)
+// - connectivity_indicator_tests: Footer connectivity segment, event-driven recompute, jump key, 80-column fit (4 tests)
+// - delivery_hint_tests: Queue age annotations, stale-address/expired-token hints, banner actions (4 tests)
+// - badges_tests: Unread/pending badges, terminal title updates (8 tests)
+// - delivery_events_tests: In-place pending/failed updates, reconciliation (5 tests)
+// - chat_list_bulk_tests: Marks by UID across re-sorts, bulk archive/read/mute/delete in one write, mark all, delete text (4 tests)
+// - startup_graph_tests: Startup stage order under fast and slow reports, per-stage timeouts, final port for connectivity (4 tests)
+// - send_leases_tests: Panicking sends, orphaned leases re-queued once, late outcomes dropped, normal sends untouched (4 tests)
+// - recovery_tests: Session marker lifecycle, eager reconciliation order, summary per recovery class, unfixable problems (4 tests)
+// - inspection_tests: Read-only enforcement, no transport or workers, refusal statuses, live-directory refusal, leftover journal or WAL refusal, read screens (6 tests)
+// - filter_tests: Fuzzy filter list used by the template picker (2 tests)
+// - focus_tests: Read-marking gate, background tick rate, buffered flashes, terminals without focus events (4 tests)
+// - theme_tests: Profile accent over the theme (1 test)
+// - ui_tests: UI helper functions, escape sequence rendering (5 tests)
 
+mod alert_style_tests;
+mod alerts_tests;
 mod app_tests;
+mod badges_tests;
+mod chat_list_bulk_tests;
+mod local_time_tests;
+mod path_picker_tests;
+mod recovery_tests;
+mod connectivity_indicator_tests;
+mod delivery_events_tests;
+mod delivery_hint_tests;
+mod filter_tests;
+mod focus_tests;
+mod inspection_tests;
+mod notifications_tests;
 mod screen_tests;
+mod send_leases_tests;
+mod startup_graph_tests;
+mod theme_tests;
 mod types_tests;
 mod ui_tests;
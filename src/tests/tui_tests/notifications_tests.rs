@@ -0,0 +1,69 @@
+// Notifications Tests - Testing in-app notifications and quiet hours suppression
+
+use crate::tui::notifications::NOTIFICATION_DISPLAY_DURATION;
+use crate::tui::Notifications;
+use std::time::Instant;
+
+#[test]
+fn test_notification_shown_outside_quiet_hours() {
+    let mut notifications = Notifications::new();
+
+    notifications.notify("New message from alice".to_string());
+
+    assert_eq!(notifications.visible(Instant::now()), Some("New message from alice"));
+    assert!(notifications.suppressed.is_empty());
+}
+
+#[test]
+fn test_notification_expires() {
+    let mut notifications = Notifications::new();
+    notifications.notify("hello".to_string());
+
+    let later = Instant::now() + NOTIFICATION_DISPLAY_DURATION;
+    assert!(notifications.visible(later).is_none());
+}
+
+#[test]
+fn test_notifications_suppressed_during_quiet_hours() {
+    let mut notifications = Notifications::new();
+    assert!(notifications.set_quiet(true).is_none());
+
+    notifications.notify("first".to_string());
+    notifications.notify("second".to_string());
+
+    assert!(notifications.visible(Instant::now()).is_none());
+    assert_eq!(notifications.suppressed.len(), 2);
+
+    // Staying quiet does not release anything
+    assert!(notifications.set_quiet(true).is_none());
+    assert_eq!(notifications.suppressed.len(), 2);
+}
+
+#[test]
+fn test_summary_when_quiet_period_ends() {
+    let mut notifications = Notifications::new();
+    notifications.set_quiet(true);
+    notifications.notify("first".to_string());
+    notifications.notify("second".to_string());
+
+    let summary = notifications.set_quiet(false).expect("Expected a summary");
+
+    assert!(summary.contains("2 notifications"));
+    assert!(summary.contains("second"));
+    assert_eq!(notifications.visible(Instant::now()), Some(summary.as_str()));
+    assert!(notifications.suppressed.is_empty());
+
+    // Nothing left to summarize next time
+    notifications.set_quiet(true);
+    assert!(notifications.set_quiet(false).is_none());
+}
+
+#[test]
+fn test_summary_single_notification() {
+    let mut notifications = Notifications::new();
+    notifications.set_quiet(true);
+    notifications.notify("only one".to_string());
+
+    let summary = notifications.set_quiet(false).expect("Expected a summary");
+    assert_eq!(summary, "During quiet hours: only one");
+}
@@ -0,0 +1,164 @@
+// Path Picker Tests - Save-path overlay (expansion, validation, overwrite flow, default folder, error messages)
+
+use crate::tui::clipboard::mock::MockClipboard;
+use crate::tui::path_picker::{open_for_save, validate_save_path};
+use crate::tui::{default_save_dir, expand_tilde, App, PathEnv, PathPicker, Platform, SavePathError, SaveTarget};
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn test_path_expansion_and_validation() {
+    let home = Path::new("/home/alice");
+    assert_eq!(expand_tilde("~", Some(home)), Some(PathBuf::from("/home/alice")));
+    assert_eq!(expand_tilde("~/notes/t.txt", Some(home)), Some(PathBuf::from("/home/alice/notes/t.txt")));
+    assert_eq!(expand_tilde("~bob/t.txt", Some(home)), Some(PathBuf::from("~bob/t.txt")));
+    assert_eq!(expand_tilde("/tmp/t.txt", None), Some(PathBuf::from("/tmp/t.txt")));
+    assert_eq!(expand_tilde("~/t.txt", None), None);
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    std::fs::write(dir.join("file.txt"), "x").unwrap();
+
+    // Relative paths and ~ resolve to absolute paths
+    assert_eq!(validate_save_path("  out.txt ", None, dir), Ok(dir.join("out.txt")));
+    assert_eq!(validate_save_path("~/out.txt", Some(dir), Path::new("/")), Ok(dir.join("out.txt")));
+
+    assert_eq!(validate_save_path("", None, dir), Err(SavePathError::Empty));
+    assert_eq!(validate_save_path("~/out.txt", None, dir), Err(SavePathError::NoHome));
+    assert_eq!(validate_save_path("new/", None, dir), Err(SavePathError::NoFileName));
+    assert_eq!(validate_save_path(".", None, dir), Err(SavePathError::IsDirectory(dir.join("."))));
+    assert_eq!(
+        validate_save_path("missing/out.txt", None, dir),
+        Err(SavePathError::ParentMissing(dir.join("missing")))
+    );
+    assert_eq!(
+        validate_save_path("file.txt/out.txt", None, dir),
+        Err(SavePathError::ParentNotDirectory(dir.join("file.txt")))
+    );
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let locked = dir.join("locked");
+        std::fs::create_dir(&locked).unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o555)).unwrap();
+        assert_eq!(
+            validate_save_path("locked/out.txt", None, dir),
+            Err(SavePathError::ReadOnly(locked.clone()))
+        );
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
+
+#[test]
+fn test_overwrite_confirmation_flow() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    app.show_share_contact_screen();
+    let token = app.share_contact_screen.as_ref().unwrap().token.clone();
+    let target = temp_dir.path().join("token.txt");
+
+    // The suggestion is a fresh file in the save folder
+    app.open_path_picker(SaveTarget::ContactToken);
+    let picker = app.path_picker.as_mut().unwrap();
+    assert!(picker.input.starts_with(&app.save_dir.display().to_string()));
+    assert!(picker.input.ends_with(".txt"));
+
+    // A free path saves at once and reports the absolute path
+    picker.input = target.display().to_string();
+    app.submit_path_picker();
+    assert!(app.path_picker.is_none());
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), token);
+    assert_eq!(app.last_saved_path.as_deref(), Some(target.as_path()));
+    let status = app.share_contact_screen.as_ref().unwrap().status_message.clone().unwrap();
+    assert!(status.contains(&target.display().to_string()));
+
+    // An existing file asks first; declining keeps it
+    std::fs::write(&target, "keep me").unwrap();
+    app.open_path_picker(SaveTarget::ContactToken);
+    app.path_picker.as_mut().unwrap().input = target.display().to_string();
+    app.submit_path_picker();
+    assert_eq!(app.path_picker.as_ref().unwrap().pending_overwrite.as_deref(), Some(target.as_path()));
+    app.answer_overwrite_prompt(false);
+    assert!(app.path_picker.is_some());
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "keep me");
+
+    // Confirming replaces it
+    app.submit_path_picker();
+    app.answer_overwrite_prompt(true);
+    assert!(app.path_picker.is_none());
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), token);
+
+    // The saved path can be copied
+    let clipboard = MockClipboard::new();
+    app.current_screen = crate::tui::Screen::ShareContact;
+    app.copy_saved_path_with_provider(&mut Ok(clipboard.clone()));
+    assert_eq!(clipboard.get_content(), Some(target.display().to_string()));
+
+    // A refused path keeps the overlay open with the reason
+    app.open_path_picker(SaveTarget::ContactToken);
+    app.path_picker.as_mut().unwrap().input = temp_dir.path().join("nope/token.txt").display().to_string();
+    app.submit_path_picker();
+    assert!(app.path_picker.as_ref().unwrap().error.as_ref().unwrap().contains("does not exist"));
+}
+
+#[test]
+fn test_default_save_dir_per_platform() {
+    let env = PathEnv {
+        home: Some(PathBuf::from("/home/alice")),
+        xdg_download_dir: Some(PathBuf::from("/data/dl")),
+    };
+    let existing = |dirs: &'static [&'static str]| move |p: &Path| dirs.iter().any(|d| p == Path::new(d));
+
+    // Linux honours XDG_DOWNLOAD_DIR when it exists
+    let all = existing(&["/data/dl", "/home/alice/Downloads", "/home/alice/Documents"]);
+    assert_eq!(default_save_dir(Platform::Linux, &env, all), PathBuf::from("/data/dl"));
+    assert_eq!(default_save_dir(Platform::MacOs, &env, all), PathBuf::from("/home/alice/Downloads"));
+    assert_eq!(default_save_dir(Platform::Windows, &env, all), PathBuf::from("/home/alice/Downloads"));
+
+    // Then Downloads, Documents, the home folder, the current folder
+    let documents = existing(&["/home/alice/Documents"]);
+    assert_eq!(default_save_dir(Platform::Linux, &env, documents), PathBuf::from("/home/alice/Documents"));
+    assert_eq!(default_save_dir(Platform::Linux, &env, existing(&[])), PathBuf::from("/home/alice"));
+    assert_eq!(default_save_dir(Platform::Windows, &PathEnv::default(), existing(&[])), PathBuf::from("."));
+}
+
+#[test]
+fn test_save_failure_messages() {
+    let path = Path::new("/data/out/chat.jsonl");
+    let classify = |kind: io::ErrorKind| SavePathError::from_io(&io::Error::from(kind), path);
+
+    let read_only = classify(io::ErrorKind::ReadOnlyFilesystem);
+    assert_eq!(read_only, SavePathError::ReadOnly(PathBuf::from("/data/out")));
+    assert_eq!(classify(io::ErrorKind::PermissionDenied), read_only);
+    assert_eq!(
+        read_only.to_string(),
+        "Cannot write to /data/out (read-only or permission denied)"
+    );
+
+    let full = classify(io::ErrorKind::StorageFull);
+    assert_eq!(full, SavePathError::DiskFull(path.to_path_buf()));
+    assert_eq!(classify(io::ErrorKind::QuotaExceeded), full);
+    assert_eq!(full.to_string(), "Disk full: could not write /data/out/chat.jsonl");
+
+    assert_eq!(classify(io::ErrorKind::NotFound).to_string(), "Folder /data/out does not exist");
+    assert_eq!(SavePathError::Empty.to_string(), "Enter a file path");
+    assert_eq!(SavePathError::NoFileName.to_string(), "Path must end with a file name");
+    assert!(classify(io::ErrorKind::Interrupted).to_string().starts_with("Could not write /data/out/chat.jsonl: "));
+
+    // Without overwrite an existing file is never truncated
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let existing = temp_dir.path().join("a.txt");
+    std::fs::write(&existing, "data").unwrap();
+    assert!(open_for_save(&existing, false).is_err());
+    assert_eq!(std::fs::read_to_string(&existing).unwrap(), "data");
+    assert!(open_for_save(&existing, true).is_ok());
+    assert_eq!(
+        open_for_save(&temp_dir.path().join("missing/a.txt"), false).unwrap_err(),
+        SavePathError::ParentMissing(temp_dir.path().join("missing"))
+    );
+
+    let mut picker = PathPicker::new(SaveTarget::ContactToken, Path::new("/tmp/t.txt"));
+    picker.set_error(&full);
+    assert_eq!(picker.error.as_deref(), Some("Disk full: could not write /data/out/chat.jsonl"));
+}
@@ -0,0 +1,187 @@
+// Recovery Tests - Session marker across clean and crashed exits, eager reconciliation in order, summary per recovery class, unfixable problems surfaced
+
+use crate::crypto::KeyPair;
+use crate::queue_migration::QUEUE_SCHEMA_VERSION;
+use crate::storage::{
+    begin_session, end_session, read_runtime_info, write_runtime_info, Contact, ErrorSeverity, Message, RuntimeInfo,
+    SessionMarker,
+};
+use crate::tui::{App, RecoveryReport, RecoveryScreen, RecoveryStep, Screen, StepOutcome};
+use chrono::{Duration, Utc};
+use tempfile::TempDir;
+use crate::tests::helpers::{dead_pid, new_app};
+
+/// Leave the marker of a session (of a dead process) that never ended
+fn leave_crashed_marker(app: &App) {
+    let marker = SessionMarker { pid: dead_pid(), started_at: (Utc::now() - Duration::hours(1)).timestamp_millis() };
+    begin_session(&app.session_marker_path, &marker, |_| false).unwrap();
+}
+
+#[test]
+fn test_marker_lifecycle_across_clean_and_crashed_exits() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // First start: nothing to recover; a clean exit removes the marker
+    let mut app = new_app(&temp_dir);
+    let marker_path = app.session_marker_path.clone();
+    app.start_session(Utc::now());
+    assert!(marker_path.exists());
+    assert!(app.recovery_screen.is_none());
+    assert_eq!(app.current_screen, Screen::MainMenu);
+    app.end_session();
+    assert!(!marker_path.exists());
+    drop(app);
+
+    // Dropping (as unwinding from a panic does) leaves the marker behind
+    let mut app = new_app(&temp_dir);
+    app.start_session(Utc::now());
+    drop(app);
+    assert!(marker_path.exists());
+
+    let mut app = new_app(&temp_dir);
+    app.start_session(Utc::now());
+    assert_eq!(app.current_screen, Screen::Recovery);
+    let report = &app.recovery_screen.as_ref().unwrap().report;
+    assert_eq!(report.previous.pid, std::process::id());
+    assert!(!report.has_problems());
+    app.dismiss_recovery_screen();
+    assert_eq!(app.current_screen, Screen::MainMenu);
+    assert!(app.recovery_screen.is_none());
+
+    // A marker of an instance still running is not a crash, and is not removed by us
+    let other = SessionMarker { pid: std::process::id() + 1, started_at: 1 };
+    std::fs::write(&marker_path, serde_json::to_vec(&other).unwrap()).unwrap();
+    assert_eq!(begin_session(&marker_path, &SessionMarker::for_this_process(Utc::now()), |_| true).unwrap(), None);
+    begin_session(&marker_path, &other, |_| true).unwrap();
+    assert!(!end_session(&marker_path, std::process::id()).unwrap());
+    assert!(marker_path.exists());
+
+    // An unreadable marker still counts as a crash
+    std::fs::write(&marker_path, b"{\"pid\":").unwrap();
+    let previous = begin_session(&marker_path, &SessionMarker::for_this_process(Utc::now()), |_| true).unwrap();
+    assert_eq!(previous, Some(SessionMarker::unreadable()));
+    assert!(end_session(&marker_path, std::process::id()).unwrap());
+}
+
+#[test]
+fn test_recovery_runs_every_step_in_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = new_app(&temp_dir);
+    let message = Message::new("m1".to_string(), "me".to_string(), "bob".to_string(), b"hello".to_vec(), 1000);
+    app.app_state.get_or_create_chat("bob").append_message(message);
+
+    // What a crash mid-send, mid-migration and before shutdown leaves behind
+    app.storage.replace_send_leases(&[("m1".to_string(), "bob".to_string(), Utc::now())]).unwrap();
+    app.queue.conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS queue_migration (version INTEGER PRIMARY KEY, last_rowid INTEGER NOT NULL, rows_done INTEGER NOT NULL);
+         INSERT INTO queue_migration VALUES ({QUEUE_SCHEMA_VERSION}, 0, 0);
+         PRAGMA user_version = 1;"
+    )).unwrap();
+    let stale = RuntimeInfo { pid: dead_pid(), port: 4100, started_at: 1 };
+    write_runtime_info(&app.runtime_info_path, &stale).unwrap();
+    leave_crashed_marker(&app);
+
+    app.start_session(Utc::now());
+    let report = app.recovery_screen.as_ref().unwrap().report.clone();
+    let order: Vec<RecoveryStep> = report.steps.iter().map(|outcome| outcome.step).collect();
+    assert_eq!(order, RecoveryStep::ALL);
+    assert_eq!(
+        report.headline(),
+        "Previous session ended unexpectedly — recovered 1 unsent message, verified database integrity, resumed 1 migration, removed a stale runtime file"
+    );
+
+    // Every step did its work before the screen was shown
+    assert!(app.queue.contains("m1").unwrap());
+    assert!(app.storage.load_send_leases().unwrap().is_empty());
+    assert_eq!(app.queue.interrupted_migration().unwrap(), None);
+    assert_eq!(app.queue.schema_version().unwrap(), QUEUE_SCHEMA_VERSION);
+    assert_eq!(read_runtime_info(&app.runtime_info_path).unwrap(), None);
+    assert!(app.error_reports.is_empty());
+    assert!(app.process_delivery_events());
+    assert!(app.app_state.get_chat("bob").unwrap().has_pending_messages);
+
+    // Leases of sends started now are stored until they finish
+    app.send_leases.begin("m2", "bob", Utc::now());
+    app.persist_send_leases();
+    assert_eq!(app.storage.load_send_leases().unwrap().len(), 1);
+    app.send_leases.finish("m2");
+    app.process_delivery_events();
+    assert!(app.storage.load_send_leases().unwrap().is_empty());
+}
+
+#[test]
+fn test_summary_for_each_recovery_class() {
+    let previous = SessionMarker { pid: 4242, started_at: Utc::now().timestamp_millis() };
+    let report = |steps: Vec<StepOutcome>| RecoveryReport { previous: previous.clone(), steps };
+
+    // Nothing to do anywhere
+    let idle = report(RecoveryStep::ALL.into_iter().map(StepOutcome::new).collect());
+    assert_eq!(idle.headline(), "Previous session ended unexpectedly — nothing needed recovering");
+    let lines = idle.detail_lines();
+    assert!(lines[0].starts_with("Previous session: pid 4242, started "));
+    assert_eq!(lines[1..], ["Delivery journal: nothing to do", "Unsent messages: nothing to do", "Database integrity: nothing to do", "Queue migration: nothing to do", "Runtime file: nothing to do"]);
+
+    // One class at a time, each naming what it did
+    let classes = [
+        (StepOutcome::new(RecoveryStep::Journal).done("verified the delivery journal").detail("3 records intact"), "verified the delivery journal", "Delivery journal: 3 records intact"),
+        (StepOutcome::new(RecoveryStep::SendLeases).done("recovered 2 unsent messages").detail("queued m1 to bob for retry"), "recovered 2 unsent messages", "Unsent messages: queued m1 to bob for retry"),
+        (StepOutcome::new(RecoveryStep::Integrity).done("verified database integrity").detail("quick check passed"), "verified database integrity", "Database integrity: quick check passed"),
+        (StepOutcome::new(RecoveryStep::Migration).done("resumed 1 migration").detail("continued from row 0 of 5"), "resumed 1 migration", "Queue migration: continued from row 0 of 5"),
+        (StepOutcome::new(RecoveryStep::RuntimeFile).done("removed a stale runtime file").detail("removed the file of pid 7"), "removed a stale runtime file", "Runtime file: removed the file of pid 7"),
+    ];
+    for (outcome, done, detail) in classes {
+        let single = report(vec![outcome]);
+        assert_eq!(single.headline(), format!("Previous session ended unexpectedly — {}", done));
+        assert_eq!(single.detail_lines()[1], detail);
+        assert!(!single.has_problems());
+    }
+
+    // The marker could not be read, and problems are counted in the headline
+    let mut broken = report(vec![
+        StepOutcome::new(RecoveryStep::Journal).problem("record 4 does not follow record 3"),
+        StepOutcome::new(RecoveryStep::Integrity).done("verified database integrity").problem("quick check: page 7 is never used"),
+    ]);
+    broken.previous = SessionMarker::unreadable();
+    assert_eq!(
+        broken.headline(),
+        "Previous session ended unexpectedly — verified database integrity. 2 problems could not be fixed"
+    );
+    assert_eq!(broken.problems(), ["Delivery journal: record 4 does not follow record 3", "Database integrity: quick check: page 7 is never used"]);
+    assert_eq!(broken.detail_lines()[0], "Previous session: marker unreadable");
+
+    // Details start collapsed
+    let mut screen = RecoveryScreen::new(broken);
+    assert!(!screen.show_details);
+    screen.toggle_details();
+    assert!(screen.show_details);
+}
+
+#[test]
+fn test_unfixable_problem_is_surfaced() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut app = new_app(&temp_dir);
+    let keypair = KeyPair::generate().unwrap();
+    let contact = Contact::new("bad-uid".to_string(), "127.0.0.1:9000".to_string(), keypair.public_key, vec![], Utc::now() + Duration::days(30));
+    app.storage.save_contact(&contact).unwrap();
+    leave_crashed_marker(&app);
+
+    app.start_session(Utc::now());
+    assert_eq!(app.current_screen, Screen::Recovery);
+    let report = &app.recovery_screen.as_ref().unwrap().report;
+    assert!(report.has_problems());
+    assert!(report.headline().ends_with(". 1 problem could not be fixed"), "{}", report.headline());
+    assert_eq!(report.problems(), ["Database integrity: contact bad-uid: UID does not match its key, held for review"]);
+
+    // The problem is in the error log and the conflict held for review
+    let logged = app.error_reports.reports();
+    assert_eq!(logged.len(), 1);
+    assert_eq!((logged[0].severity, logged[0].source.as_str()), (ErrorSeverity::Error, "recovery"));
+    assert!(logged[0].message.contains("UID does not match its key"));
+    assert_eq!(app.app_state.identity_conflicts.len(), 1);
+
+    // 'l' opens the error log in Diagnostics
+    app.open_recovery_logs();
+    assert_eq!(app.current_screen, Screen::Diagnostics);
+    assert!(app.diagnostics_screen.as_ref().unwrap().show_error_log);
+    assert!(app.recovery_screen.is_none());
+}
@@ -1,6 +1,7 @@
 // ChatListScreen Tests - Testing chat list navigation and management
 
-use crate::tui::screens::ChatListScreen;
+use crate::storage::MAX_CONTACT_NOTES_BYTES;
+use crate::tui::screens::{ChatListScreen, ContactDetailsPopup, NotesEditor};
 
 #[test]
 fn test_chat_list_screen_creation() {
@@ -84,3 +85,33 @@ fn test_chat_list_screen_popup_state_independent() {
     assert!(screen.show_delete_confirmation);
     assert_eq!(screen.pending_delete_index, Some(1));
 }
+
+#[test]
+fn test_contact_details_popup_toggle_notes() {
+    let mut popup = ContactDetailsPopup::new("uid".to_string());
+    assert!(!popup.notes_expanded);
+
+    popup.toggle_notes_expanded();
+    assert!(popup.notes_expanded);
+
+    popup.toggle_notes_expanded();
+    assert!(!popup.notes_expanded);
+}
+
+#[test]
+fn test_notes_editor_size_cap() {
+    let mut editor = NotesEditor::new(&"a".repeat(MAX_CONTACT_NOTES_BYTES - 1));
+    assert_eq!(editor.remaining_bytes(), 1);
+
+    // Multi-byte character does not fit in the last byte
+    editor.add_char('é');
+    assert_eq!(editor.buffer.len(), MAX_CONTACT_NOTES_BYTES - 1);
+    assert!(editor.status_message.is_some());
+
+    editor.add_char('b');
+    assert_eq!(editor.remaining_bytes(), 0);
+
+    editor.backspace();
+    assert_eq!(editor.remaining_bytes(), 1);
+    assert!(editor.status_message.is_none());
+}
@@ -1,6 +1,7 @@
 // ChatViewScreen Tests - Testing individual chat conversation view
 
-use crate::tui::screens::ChatViewScreen;
+use crate::storage::MAX_MESSAGE_BYTES;
+use crate::tui::screens::{ChatViewScreen, InputCounter, BYTE_COUNTER_PERCENT};
 
 #[test]
 fn test_chat_view_screen_creation() {
@@ -55,3 +56,197 @@ fn test_chat_view_screen_scroll() {
     screen.scroll_down(10);
     assert_eq!(screen.scroll_offset, 10, "Should stay at max offset");
 }
+
+#[test]
+fn test_chat_view_message_details_toggle() {
+    use crate::storage::{Message, MetadataValue};
+
+    let mut screen = ChatViewScreen::new("alice_uid".to_string());
+    assert!(!screen.show_message_details);
+
+    screen.toggle_message_details();
+    assert!(screen.show_message_details);
+    screen.toggle_message_details();
+    assert!(!screen.show_message_details);
+
+    // Only messages carrying metadata get the "⋯" indicator
+    let mut message = Message::new("id".to_string(), "alice_uid".to_string(), "me".to_string(), b"hi".to_vec(), 0);
+    assert!(!message.has_metadata());
+    message.metadata.insert("ticket".to_string(), MetadataValue::from("T-3"));
+    assert!(message.has_metadata());
+    assert_eq!(message.metadata_lines(), vec!["ticket: T-3"]);
+}
+
+fn annotated_chat(count: usize) -> crate::storage::Chat {
+    use crate::storage::{Chat, Message};
+
+    let mut chat = Chat::new("alice_uid".to_string());
+    for i in 0..count {
+        chat.append_message(Message::new(
+            format!("msg_{}", i),
+            "alice_uid".to_string(),
+            "me".to_string(),
+            format!("message {}", i).into_bytes(),
+            i as i64,
+        ));
+    }
+    chat
+}
+
+#[test]
+fn test_chat_view_starred_filter() {
+    let mut chat = annotated_chat(4);
+    chat.toggle_star("msg_1").unwrap();
+    chat.toggle_star("msg_3").unwrap();
+
+    let mut screen = ChatViewScreen::new("alice_uid".to_string());
+    assert_eq!(screen.visible_messages(&chat).len(), 4);
+
+    screen.scroll_offset = 2;
+    screen.toggle_starred_only(&chat);
+    let ids: Vec<&str> = screen.visible_messages(&chat).iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(ids, vec!["msg_1", "msg_3"]);
+    assert_eq!(screen.scroll_offset, 0);
+
+    // A selection hidden by the filter is dropped
+    screen.toggle_starred_only(&chat);
+    screen.start_selection(&chat);
+    screen.select_previous(&chat);
+    assert_eq!(screen.selected_message_id.as_deref(), Some("msg_2"));
+    screen.toggle_starred_only(&chat);
+    assert!(!screen.is_selecting());
+}
+
+#[test]
+fn test_chat_view_selection_movement() {
+    use crate::tui::screens::CHAT_VIEW_PAGE_SIZE;
+
+    let chat = annotated_chat(CHAT_VIEW_PAGE_SIZE + 5);
+    let mut screen = ChatViewScreen::new("alice_uid".to_string());
+
+    // Selection starts at the newest message and scrolls it into view
+    screen.start_selection(&chat);
+    let last = CHAT_VIEW_PAGE_SIZE + 4;
+    assert_eq!(screen.selected_message(&chat).unwrap().id, format!("msg_{}", last));
+    assert_eq!(screen.scroll_offset, 5);
+
+    // Cannot move past the newest message
+    screen.select_next(&chat);
+    assert_eq!(screen.selected_message(&chat).unwrap().id, format!("msg_{}", last));
+
+    // Moving above the page scrolls up with the selection
+    for _ in 0..CHAT_VIEW_PAGE_SIZE {
+        screen.select_previous(&chat);
+    }
+    assert_eq!(screen.selected_message(&chat).unwrap().id, "msg_4");
+    assert_eq!(screen.scroll_offset, 4);
+
+    screen.end_selection();
+    assert!(!screen.is_selecting());
+}
+
+#[test]
+fn test_chat_view_pinned_strip_focus() {
+    let mut chat = annotated_chat(5);
+    let mut screen = ChatViewScreen::new("alice_uid".to_string());
+
+    // Nothing pinned: the strip cannot take focus
+    screen.focus_pinned_strip(&chat);
+    assert!(screen.pinned_focus.is_none());
+
+    chat.toggle_pin("msg_1").unwrap();
+    chat.toggle_pin("msg_3").unwrap();
+    screen.focus_pinned_strip(&chat);
+    assert_eq!(screen.focused_pinned(&chat).unwrap().id, "msg_1");
+
+    screen.next_pinned(&chat);
+    screen.next_pinned(&chat);
+    assert_eq!(screen.focused_pinned(&chat).unwrap().id, "msg_3");
+    screen.previous_pinned();
+    assert_eq!(screen.focused_pinned(&chat).unwrap().id, "msg_1");
+
+    // Unpinning the last entries keeps focus in range, then releases it
+    screen.next_pinned(&chat);
+    chat.toggle_pin("msg_3").unwrap();
+    screen.clamp_pinned_focus(&chat);
+    assert_eq!(screen.focused_pinned(&chat).unwrap().id, "msg_1");
+    chat.toggle_pin("msg_1").unwrap();
+    screen.clamp_pinned_focus(&chat);
+    assert!(screen.pinned_focus.is_none());
+}
+
+#[test]
+fn test_chat_view_jump_to_pinned() {
+    use crate::tui::screens::CHAT_VIEW_PAGE_SIZE;
+
+    let mut chat = annotated_chat(CHAT_VIEW_PAGE_SIZE * 3);
+    chat.toggle_pin("msg_2").unwrap();
+    chat.toggle_star("msg_25").unwrap();
+
+    let mut screen = ChatViewScreen::new("alice_uid".to_string());
+    screen.toggle_starred_only(&chat);
+    screen.scroll_offset = CHAT_VIEW_PAGE_SIZE * 2;
+
+    // Jumping to an older page selects the message and clears the filter hiding it
+    screen.focus_pinned_strip(&chat);
+    screen.jump_to_focused_pinned(&chat);
+    assert!(!screen.starred_only);
+    assert!(screen.pinned_focus.is_none());
+    assert_eq!(screen.selected_message(&chat).unwrap().id, "msg_2");
+    assert_eq!(screen.scroll_offset, 2);
+}
+
+#[test]
+fn test_chat_view_insert_at_cursor() {
+    let mut screen = ChatViewScreen::new("alice_uid".to_string());
+    for c in "Hi  there".chars() {
+        screen.add_char(c);
+    }
+
+    // Move back to between the two spaces and insert a multi-line text
+    for _ in 0..6 {
+        screen.cursor_left();
+    }
+    assert!(screen.cursor_at_word_start());
+    screen.insert_text("Bob,\nsee you ");
+    assert_eq!(screen.input, "Hi Bob,\nsee you  there");
+    assert_eq!(screen.cursor_line_column(), (1, 8));
+
+    // Typing continues at the cursor
+    screen.add_char('!');
+    assert_eq!(screen.input, "Hi Bob,\nsee you ! there");
+    screen.backspace();
+    screen.backspace();
+    assert_eq!(screen.input, "Hi Bob,\nsee you there");
+
+    screen.cursor_home();
+    screen.backspace();
+    assert_eq!(screen.input, "Hi Bob,\nsee you there", "Backspace at the start does nothing");
+    screen.cursor_end();
+    assert!(!screen.cursor_at_word_start());
+
+    screen.clear_input();
+    assert_eq!(screen.cursor, 0);
+}
+
+#[test]
+fn test_input_counter_thresholds() {
+    let near = MAX_MESSAGE_BYTES * BYTE_COUNTER_PERCENT / 100;
+
+    // Characters (graphemes) while well under the limit
+    let counter = InputCounter::new("he\u{301}llo", 120);
+    assert_eq!(counter, InputCounter::Chars(5));
+    assert_eq!(counter.label(), "5 chars");
+
+    // Bytes from the threshold up to the limit
+    assert_eq!(InputCounter::new("x", near - 1), InputCounter::Chars(1));
+    assert_eq!(InputCounter::new("x", near), InputCounter::Bytes(near));
+    let at_limit = InputCounter::new("x", MAX_MESSAGE_BYTES);
+    assert_eq!(at_limit.label(), format!("{} / {} bytes", MAX_MESSAGE_BYTES, MAX_MESSAGE_BYTES));
+    assert!(!at_limit.is_over_limit());
+
+    // Over the limit
+    let over = InputCounter::new("x", MAX_MESSAGE_BYTES + 1);
+    assert!(over.is_over_limit());
+    assert!(over.label().ends_with("too large"));
+}
@@ -409,3 +409,55 @@ fn test_diagnostics_screen_http_fallback_from_connectivity_result() {
     assert!(!screen.cgnat_detected);
     assert!(!screen.is_refreshing);
 }
+
+#[test]
+fn test_diagnostics_screen_gateway_probe_lines() {
+    use crate::connectivity::{GatewayProbe, GatewayProbeOutcome};
+
+    let mapping = PortMappingResult {
+        external_ip: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 5)),
+        external_port: 40000,
+        lifetime_secs: 3600,
+        protocol: MappingProtocol::PCP,
+        created_at_ms: Utc::now().timestamp_millis(),
+    };
+    let mut result = ConnectivityResult::new();
+    result.pcp = StrategyAttempt::Success(mapping.clone());
+    result.mapping = Some(mapping.clone());
+    result.gateway_probes = vec![
+        GatewayProbe {
+            protocol: MappingProtocol::PCP,
+            server: "10.8.0.1:5351".parse().unwrap(),
+            interface: Some("wg0".to_string()),
+            outcome: GatewayProbeOutcome::Mapped(mapping),
+            elapsed_ms: 12,
+        },
+        GatewayProbe {
+            protocol: MappingProtocol::PCP,
+            server: "192.168.1.1:5351".parse().unwrap(),
+            interface: None,
+            outcome: GatewayProbeOutcome::Cancelled,
+            elapsed_ms: 12,
+        },
+        GatewayProbe {
+            protocol: MappingProtocol::NATPMP,
+            server: "172.17.0.1:5351".parse().unwrap(),
+            interface: Some("docker0".to_string()),
+            outcome: GatewayProbeOutcome::Failed("Mapping request timed out".to_string()),
+            elapsed_ms: 2000,
+        },
+    ];
+
+    let mut screen = DiagnosticsScreen::new(8080);
+    assert!(screen.gateway_probe_lines().is_empty());
+
+    screen.update_from_connectivity_result(&result);
+    assert_eq!(
+        screen.gateway_probe_lines(),
+        vec![
+            "PCP 10.8.0.1 (wg0): mapped in 12ms".to_string(),
+            "PCP 192.168.1.1: not needed".to_string(),
+            "NAT-PMP 172.17.0.1 (docker0): Mapping request timed out".to_string(),
+        ]
+    );
+}
@@ -245,3 +245,39 @@ fn test_paste_from_clipboard_success() {
         "Should have paste success message"
     );
 }
+
+#[test]
+fn test_import_preview_shows_signature_and_key_fingerprint() {
+    let keypair = KeyPair::generate().expect("Failed to generate keypair");
+    let expiry = Utc::now() + Duration::days(30);
+    let token = generate_contact_token("192.168.1.100:8080", &keypair.public_key, &keypair.private_key, &keypair.x25519_public, expiry)
+        .expect("Failed to generate token");
+
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let mut app = crate::tui::App::new_with_settings(Some(temp_dir.path().join("settings.json"))).unwrap();
+    app.show_import_contact_screen();
+    let screen = app.import_contact_screen.as_mut().unwrap();
+    screen.input = token;
+    screen.parse_token();
+
+    let contact = screen.get_contact().unwrap();
+    assert!(contact.uid_matches_key());
+    let fingerprint = contact.signing_key_fingerprint();
+    assert_eq!(fingerprint.split(' ').count(), 8);
+    assert!(fingerprint.replace(' ', "").starts_with(&hex::encode(&keypair.public_key[..4])));
+
+    let render = |app: &crate::tui::App| -> String {
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|f| crate::tui::ui::render_import_contact(f, app)).unwrap();
+        terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect()
+    };
+    let rendered = render(&app);
+    assert!(rendered.contains("Signature: valid, key"), "{}", rendered);
+    assert!(rendered.contains(&fingerprint));
+
+    // A UID paired with someone else's key is called out
+    let other = KeyPair::generate().unwrap();
+    app.import_contact_screen.as_mut().unwrap().parsed_contact.as_mut().unwrap().uid = other.uid.to_string();
+    assert!(!app.import_contact_screen.as_ref().unwrap().get_contact().unwrap().uid_matches_key());
+    assert!(render(&app).contains("the UID is not this key's"));
+}
@@ -1,11 +1,11 @@
 // Screen Tests Module - Testing individual TUI screen structs
 // Organized by screen type for maintainability
 
-mod share_contact_tests;      // ShareContactScreen (5 tests)
-mod import_contact_tests;     // ImportContactScreen (10 tests)
-mod chat_list_tests;          // ChatListScreen (5 tests)
-mod chat_view_tests;          // ChatViewScreen (3 tests)
-mod settings_tests;           // SettingsScreen (9 tests)
+mod share_contact_tests;      // ShareContactScreen, endpoint editor (6 tests)
+mod import_contact_tests;     // ImportContactScreen, signature preview (11 tests)
+mod chat_list_tests;          // ChatListScreen, contact details popup (7 tests)
+mod chat_view_tests;          // ChatViewScreen, message details, pin/star, input cursor, input counter (10 tests)
+mod settings_tests;           // SettingsScreen, quiet hours fields, profile fields, error banner severity (16 tests)
 mod startup_sync_tests;       // StartupSyncScreen (10 tests)
-mod diagnostics_tests;        // DiagnosticsScreen (20 tests)
+mod diagnostics_tests;        // DiagnosticsScreen, gateway probes (21 tests)
 mod status_indicators_tests;  // Status badges and contact expiry (10 tests)
@@ -1,5 +1,6 @@
 // SettingsScreen Tests - Testing settings configuration screen
 
+use crate::storage::{AccentColor, ErrorSeverity, Settings, MAX_PROFILE_LABEL_CHARS};
 use crate::tui::screens::SettingsScreen;
 
 #[test]
@@ -148,3 +149,128 @@ fn test_settings_screen_set_saved_message() {
     assert!(screen.status_message.as_ref().unwrap().contains("✓"));
     assert!(screen.status_message.as_ref().unwrap().contains("45"));
 }
+
+#[test]
+fn test_settings_screen_field_navigation() {
+    let mut screen = SettingsScreen::new(10);
+    assert_eq!(screen.selected_field, SettingsScreen::FIELD_RETRY_INTERVAL);
+
+    screen.next_field();
+    assert_eq!(screen.selected_field, SettingsScreen::FIELD_QUIET_ENABLED);
+
+    // Wraps in both directions
+    screen.previous_field();
+    screen.previous_field();
+    assert_eq!(screen.selected_field, SettingsScreen::FIELD_TEMPLATES);
+    screen.next_field();
+    assert_eq!(screen.selected_field, SettingsScreen::FIELD_RETRY_INTERVAL);
+}
+
+#[test]
+fn test_settings_screen_quiet_hours_input() {
+    let mut screen = SettingsScreen::new(10);
+
+    screen.selected_field = SettingsScreen::FIELD_QUIET_ENABLED;
+    screen.add_char(' ');
+    assert!(screen.quiet_hours_enabled);
+
+    screen.selected_field = SettingsScreen::FIELD_QUIET_START;
+    screen.clear_input();
+    for c in "23:3x0".chars() {
+        screen.add_char(c);
+    }
+    assert_eq!(screen.quiet_start_input, "23:30");
+    screen.backspace();
+    assert_eq!(screen.quiet_start_input, "23:3");
+
+    // Day keys toggle Monday (1) through Sunday (7)
+    screen.selected_field = SettingsScreen::FIELD_QUIET_DAYS;
+    screen.add_char('1');
+    screen.add_char('7');
+    assert_eq!(screen.quiet_days, 0b011_1110);
+    screen.add_char('1');
+    assert_eq!(screen.quiet_days, 0b011_1111);
+
+    // Retry interval input is untouched
+    assert_eq!(screen.retry_interval_input, "10");
+}
+
+#[test]
+fn test_settings_screen_apply_quiet_hours() {
+    let mut settings = Settings::default();
+    let mut screen = SettingsScreen::new(10);
+    screen.load_quiet_hours(&settings);
+    screen.quiet_hours_enabled = true;
+    screen.quiet_start_input = "21:00".to_string();
+    screen.quiet_end_input = "06:30".to_string();
+
+    assert!(screen.apply_quiet_hours(&mut settings));
+    assert!(settings.quiet_hours_enabled);
+    assert_eq!(settings.quiet_hours_start_minutes, 21 * 60);
+    assert_eq!(settings.quiet_hours_end_minutes, 6 * 60 + 30);
+}
+
+#[test]
+fn test_settings_screen_apply_quiet_hours_rejects_invalid() {
+    let mut settings = Settings::default();
+    let mut screen = SettingsScreen::new(10);
+    screen.quiet_hours_enabled = true;
+
+    // Start equal to end
+    screen.quiet_start_input = "22:00".to_string();
+    screen.quiet_end_input = "22:00".to_string();
+    assert!(!screen.apply_quiet_hours(&mut settings));
+    assert!(screen.is_error);
+    assert!(!settings.quiet_hours_enabled);
+
+    // Malformed time
+    screen.quiet_end_input = "25:00".to_string();
+    assert!(!screen.apply_quiet_hours(&mut settings));
+    assert!(screen.is_error);
+}
+
+#[test]
+fn test_settings_screen_profile_fields() {
+    let mut screen = SettingsScreen::new(1);
+    screen.selected_field = SettingsScreen::FIELD_PROFILE_LABEL;
+
+    // Any printable character, spaces included, up to the limit
+    for c in "my work\u{7}".chars() {
+        screen.add_char(c);
+    }
+    assert_eq!(screen.profile_label_input, "my work");
+    for _ in 0..MAX_PROFILE_LABEL_CHARS {
+        screen.add_char('x');
+    }
+    assert_eq!(screen.profile_label_input.chars().count(), MAX_PROFILE_LABEL_CHARS);
+    screen.backspace();
+    assert_eq!(screen.profile_label_input.chars().count(), MAX_PROFILE_LABEL_CHARS - 1);
+
+    // Space cycles the accent
+    screen.next_field();
+    assert_eq!(screen.selected_field, SettingsScreen::FIELD_ACCENT);
+    assert_eq!(screen.accent_color, None);
+    screen.add_char(' ');
+    assert_eq!(screen.accent_color, Some(AccentColor::Cyan));
+    screen.add_char(' ');
+    assert_eq!(screen.accent_color, Some(AccentColor::Green));
+}
+
+#[test]
+fn test_settings_screen_error_banner_severity() {
+    let mut screen = SettingsScreen::new(1);
+    assert_eq!(screen.error_banner_severity, ErrorSeverity::Warning);
+
+    // Space cycles warning → error → info → warning; other keys are ignored
+    screen.selected_field = SettingsScreen::FIELD_ERROR_BANNER;
+    screen.add_char('x');
+    assert_eq!(screen.error_banner_severity, ErrorSeverity::Warning);
+    screen.add_char(' ');
+    assert_eq!(screen.error_banner_severity, ErrorSeverity::Error);
+    screen.add_char(' ');
+    assert_eq!(screen.error_banner_severity, ErrorSeverity::Info);
+    screen.add_char(' ');
+    assert_eq!(screen.error_banner_severity, ErrorSeverity::Warning);
+    assert_eq!(ErrorSeverity::from_name("error"), Some(ErrorSeverity::Error));
+    assert_eq!(ErrorSeverity::from_name("fatal"), None);
+}
@@ -1,9 +1,12 @@
 // ShareContactScreen Tests - Testing contact token generation and sharing
 
 use crate::crypto::KeyPair;
+use crate::invite::InviteRedemption;
 use crate::storage::{generate_contact_token, parse_contact_token};
+use crate::tui::App;
 use crate::tui::screens::ShareContactScreen;
 use chrono::{Duration, Utc};
+use std::net::{IpAddr, Ipv4Addr};
 
 #[test]
 fn test_share_contact_screen_creation() {
@@ -68,67 +71,6 @@ fn test_share_contact_screen_token_valid() {
     );
 }
 
-#[test]
-fn test_share_contact_screen_save_to_file() {
-    use tempfile::TempDir;
-
-    // Create a temporary directory for testing
-    let temp_dir = TempDir::new().expect("Failed to create temp dir");
-    let original_dir = std::env::current_dir().expect("Failed to get current dir");
-
-    // Change to temp directory for the test
-    std::env::set_current_dir(temp_dir.path()).expect("Failed to change dir");
-
-    let keypair = KeyPair::generate().expect("Failed to generate keypair");
-    let local_ip = "192.168.1.100:8080";
-
-    let mut screen = ShareContactScreen::new(&keypair, local_ip);
-    let original_token = screen.token.clone();
-
-    // Save to file
-    screen.save_to_file();
-
-    // Verify status message indicates success
-    assert!(
-        screen
-            .status_message
-            .as_ref()
-            .expect("Status message should be set")
-            .starts_with("Saved to"),
-        "Status message should indicate successful save"
-    );
-
-    // Find the generated file
-    let entries = std::fs::read_dir(temp_dir.path()).expect("Failed to read temp dir");
-    let mut found_file = false;
-
-    for entry in entries {
-        let entry = entry.expect("Failed to read entry");
-        let filename = entry.file_name();
-        let filename_str = filename.to_string_lossy();
-
-        if filename_str.starts_with("contact_token_") && filename_str.ends_with(".txt") {
-            // Read the file contents
-            let contents = std::fs::read_to_string(entry.path())
-                .expect("Failed to read saved token file");
-
-            // Verify contents match the token
-            assert_eq!(
-                contents.trim(),
-                original_token,
-                "Saved token should match original"
-            );
-            found_file = true;
-            break;
-        }
-    }
-
-    assert!(found_file, "Token file should have been created");
-
-    // Restore original directory
-    std::env::set_current_dir(original_dir).expect("Failed to restore dir");
-}
-
 #[test]
 fn test_token_consistency() {
     // Same keypair and IP should generate same token
@@ -243,3 +185,122 @@ fn test_copy_to_clipboard_success() {
     // Verify token was actually copied
     assert_eq!(mock_clipboard.get_content(), Some(token));
 }
+
+#[test]
+fn test_share_contact_endpoint_editor_state_machine() {
+    let keypair = KeyPair::generate().expect("Failed to generate keypair");
+    let mut screen = ShareContactScreen::new(&keypair, "192.168.1.100:8080");
+
+    // Starts with the detected endpoint and a single-endpoint token
+    assert_eq!(screen.endpoints.len(), 1);
+    assert!(screen.endpoints[0].detected);
+    assert!(!screen.editing_endpoints);
+    assert!(parse_contact_token(&screen.token).unwrap().endpoints.is_empty());
+
+    screen.open_endpoint_editor();
+    assert!(screen.editing_endpoints);
+
+    // Add a manual endpoint with a label and validity
+    screen.start_adding_endpoint();
+    for c in "10.8.0.2:8080 vpn 3".chars() {
+        screen.add_char(c);
+    }
+    assert!(screen.confirm_endpoint());
+    assert!(screen.new_endpoint_input.is_none());
+    assert_eq!(screen.endpoints.len(), 2);
+    assert_eq!(screen.endpoints[1].endpoint.label, "vpn");
+    assert!(screen.endpoints[1].endpoint.valid_until.is_some());
+    assert_eq!(screen.selected_endpoint, 1);
+
+    // Invalid and duplicate input is rejected and the input kept for editing
+    screen.start_adding_endpoint();
+    for c in "no-port".chars() {
+        screen.add_char(c);
+    }
+    assert!(!screen.confirm_endpoint());
+    assert!(screen.new_endpoint_input.is_some());
+    screen.cancel_adding_endpoint();
+    screen.start_adding_endpoint();
+    for c in "10.8.0.2:8080".chars() {
+        screen.add_char(c);
+    }
+    assert!(!screen.confirm_endpoint());
+    screen.cancel_adding_endpoint();
+    assert_eq!(screen.endpoints.len(), 2);
+
+    // Reorder: VPN first
+    screen.move_endpoint_up();
+    assert_eq!(screen.selected_endpoint, 0);
+    assert_eq!(screen.endpoints[0].endpoint.address, "10.8.0.2:8080");
+    screen.move_endpoint_up(); // Already first
+    assert_eq!(screen.endpoints[0].endpoint.address, "10.8.0.2:8080");
+
+    // Closing regenerates a multi-endpoint token in the chosen order
+    screen.close_endpoint_editor(&keypair);
+    assert!(!screen.editing_endpoints);
+    let contact = parse_contact_token(&screen.token).expect("Token should parse");
+    let addresses: Vec<_> = contact.endpoints.iter().map(|e| e.address.as_str()).collect();
+    assert_eq!(addresses, vec!["10.8.0.2:8080", "192.168.1.100:8080"]);
+
+    // Excluding the detected endpoint leaves only the VPN address
+    screen.open_endpoint_editor();
+    screen.next_endpoint();
+    screen.toggle_endpoint();
+    assert!(!screen.endpoints[1].included);
+    screen.close_endpoint_editor(&keypair);
+    let contact = parse_contact_token(&screen.token).unwrap();
+    assert_eq!(contact.ip, "10.8.0.2:8080");
+    assert_eq!(contact.endpoints.len(), 1);
+
+    // Detected endpoints cannot be removed, manual ones can
+    screen.open_endpoint_editor();
+    screen.remove_endpoint();
+    assert_eq!(screen.endpoints.len(), 2);
+    screen.previous_endpoint();
+    screen.remove_endpoint();
+    assert_eq!(screen.endpoints.len(), 1);
+    assert_eq!(screen.selected_endpoint, 0);
+
+    // Editor stays open while nothing is included
+    let token_before = screen.token.clone();
+    screen.close_endpoint_editor(&keypair);
+    assert!(screen.editing_endpoints);
+    assert_eq!(screen.token, token_before);
+    screen.toggle_endpoint();
+    screen.close_endpoint_editor(&keypair);
+    assert!(!screen.editing_endpoints);
+    assert!(parse_contact_token(&screen.token).unwrap().endpoints.is_empty());
+}
+
+#[test]
+fn test_share_contact_create_invite() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut app = App::new_with_settings(Some(temp_dir.path().join("settings.json"))).unwrap();
+    app.app_state.settings.invite_code_length = 8;
+    app.show_share_contact_screen();
+
+    // Valid for the token's default day: no warning for an 8-character code
+    app.create_invite();
+    let screen = app.share_contact_screen.as_ref().unwrap();
+    let status = screen.status_message.clone().unwrap();
+    assert!(status.starts_with("Invite code: "), "got {}", status);
+    assert!(!status.contains('⚠'));
+
+    let code = status.trim_start_matches("Invite code: ").to_string();
+    assert_eq!(code.len(), 8);
+    let source = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let token = app
+        .transport
+        .invites()
+        .lock()
+        .unwrap()
+        .redeem(source, &InviteRedemption::new(&code), Utc::now())
+        .unwrap();
+    assert_eq!(token, screen.token);
+
+    // A week-long token makes the short code weak
+    app.share_contact_screen.as_mut().unwrap().expiry = Utc::now() + Duration::days(7);
+    app.create_invite();
+    let status = app.share_contact_screen.as_ref().unwrap().status_message.clone().unwrap();
+    assert!(status.contains('⚠'), "got {}", status);
+}
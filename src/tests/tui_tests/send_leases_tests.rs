@@ -0,0 +1,122 @@
+// Send Leases Tests - Panicking send threads, orphaned leases swept into the retry queue, late outcomes dropped, normal sends left alone
+
+use crate::storage::{DeliveryStatus, Message};
+use crate::tui::{run_send, App, ErrorReporter, SendLeases, LEASE_TIMEOUT_SECS};
+use chrono::{Duration, Utc};
+use tempfile::TempDir;
+
+/// App with a chat with bob holding the sent message "m1"
+fn app_with_sent_message() -> (App, TempDir) {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut app = App::new_with_settings(Some(temp_dir.path().join("settings.json"))).expect("Failed to create app");
+    let message = Message::new("m1".to_string(), "me".to_string(), "bob".to_string(), b"hello".to_vec(), 1000);
+    app.app_state.get_or_create_chat("bob").append_message(message);
+    (app, temp_dir)
+}
+
+#[test]
+fn test_panicking_send_becomes_a_failure() {
+    let (mut app, _temp_dir) = app_with_sent_message();
+    let reports = ErrorReporter::new();
+    app.send_leases.begin("m1", "bob", Utc::now());
+
+    let outcome: Option<()> = run_send(&app.send_leases, "bob", "m1", &app.delivery_event_sender(), &reports, || {
+        panic!("transport exploded");
+    });
+
+    assert!(outcome.is_none());
+    assert!(app.send_leases.is_empty());
+    assert_eq!(reports.len(), 1);
+    assert!(reports.reports()[0].message.contains("transport exploded"));
+
+    // The main loop marks the message and the chat failed
+    assert!(app.process_delivery_events());
+    let chat = app.app_state.get_chat("bob").unwrap();
+    assert!(chat.has_failed_messages);
+    assert_eq!(chat.messages[0].delivery_status, DeliveryStatus::Failed);
+}
+
+#[test]
+fn test_orphaned_lease_is_requeued_exactly_once() {
+    let (mut app, _temp_dir) = app_with_sent_message();
+    let now = Utc::now();
+    app.send_leases.begin("m1", "bob", now - Duration::seconds(LEASE_TIMEOUT_SECS + 1));
+
+    assert_eq!(app.sweep_send_leases(now), 1);
+    assert!(app.queue.contains("m1").unwrap());
+    assert!(app.send_leases.is_empty());
+    assert!(app.process_delivery_events());
+    assert!(app.app_state.get_chat("bob").unwrap().has_pending_messages);
+
+    // Neither a second sweep nor the thread finishing late queues it again
+    assert_eq!(app.sweep_send_leases(now + Duration::seconds(LEASE_TIMEOUT_SECS)), 0);
+    assert!(!app.send_leases.finish("m1"));
+    assert_eq!(app.queue.size().unwrap(), 1);
+
+    // A message that made it into the queue before its thread died stays as it is
+    app.send_leases.begin("m1", "bob", now - Duration::seconds(LEASE_TIMEOUT_SECS + 1));
+    assert_eq!(app.sweep_send_leases(now), 0);
+    assert_eq!(app.queue.size().unwrap(), 1);
+}
+
+#[test]
+fn test_send_finishing_after_its_sweep_is_dropped() {
+    let (mut app, _temp_dir) = app_with_sent_message();
+    let reports = ErrorReporter::new();
+    let now = Utc::now();
+    let leases = app.send_leases.clone();
+    let events = app.delivery_event_sender();
+    leases.begin("m1", "bob", now - Duration::seconds(LEASE_TIMEOUT_SECS + 1));
+
+    // The sweep queues the message while the send is still running
+    let outcome = run_send(&leases, "bob", "m1", &events, &reports, || {
+        assert_eq!(app.sweep_send_leases(now), 1);
+        "delivered"
+    });
+    assert_eq!(outcome, None);
+    assert_eq!(app.queue.size().unwrap(), 1);
+    assert!(app.send_leases.is_empty());
+
+    // The chat shows the sweep's Queued update
+    assert!(app.process_delivery_events());
+    assert!(app.app_state.get_chat("bob").unwrap().has_pending_messages);
+
+    // Nor does a late crash mark it failed
+    leases.begin("m1", "bob", now - Duration::seconds(LEASE_TIMEOUT_SECS + 1));
+    let outcome: Option<()> = run_send(&leases, "bob", "m1", &events, &reports, || {
+        assert_eq!(app.sweep_send_leases(now), 0);
+        panic!("transport exploded");
+    });
+    assert!(outcome.is_none());
+    assert_eq!(reports.len(), 1);
+    app.process_delivery_events();
+    assert!(!app.app_state.get_chat("bob").unwrap().has_failed_messages);
+    assert_eq!(app.queue.size().unwrap(), 1);
+}
+
+#[test]
+fn test_normal_sends_never_trip_the_sweep() {
+    let (mut app, _temp_dir) = app_with_sent_message();
+    let reports = ErrorReporter::new();
+    let now = Utc::now();
+
+    // A send that finishes gives its lease back
+    app.send_leases.begin("m1", "bob", now);
+    let events = app.delivery_event_sender();
+    assert_eq!(run_send(&app.send_leases, "bob", "m1", &events, &reports, || "delivered"), Some("delivered"));
+    assert!(app.send_leases.is_empty());
+    assert_eq!(app.sweep_send_leases(now + Duration::seconds(LEASE_TIMEOUT_SECS * 10)), 0);
+
+    // A slow send still within the timeout is left running
+    let leases = SendLeases::new();
+    leases.begin("m1", "bob", now - Duration::seconds(LEASE_TIMEOUT_SECS - 1));
+    assert!(leases.take_expired(now).is_empty());
+    assert_eq!(leases.len(), 1);
+
+    assert!(reports.is_empty());
+    assert_eq!(app.queue.size().unwrap(), 0);
+    app.process_delivery_events();
+    let chat = app.app_state.get_chat("bob").unwrap();
+    assert!(!chat.has_failed_messages && !chat.has_pending_messages);
+    assert_eq!(chat.messages[0].delivery_status, DeliveryStatus::Sent);
+}
@@ -0,0 +1,172 @@
+// Startup Graph Tests - Dependency order under fast and slow reports (virtual clock), per-stage timeouts, final port for connectivity
+
+use crate::tui::{StageState, StartupEvent, StartupGraph, StartupStage};
+use std::time::{Duration, Instant};
+
+/// Every stage's success report, in the order the work finishes
+fn reports() -> Vec<StartupEvent> {
+    vec![
+        StartupEvent::done(StartupStage::Storage, "3 chats"),
+        StartupEvent::done(StartupStage::Identity, "me"),
+        StartupEvent::done(StartupStage::HandlersRegistered, ""),
+        StartupEvent::listening(8080),
+        StartupEvent::done(StartupStage::Connectivity, "203.0.113.7:8080"),
+        StartupEvent::done(StartupStage::ReachabilityCheck, "reachable"),
+        StartupEvent::done(StartupStage::RetryWorker, ""),
+    ]
+}
+
+/// Advance at `now`, checking each started stage's dependencies are done
+fn advance_checked(graph: &mut StartupGraph, now: Instant, started: &mut Vec<StartupStage>) {
+    for stage in graph.advance(now) {
+        for dependency in stage.dependencies() {
+            assert!(
+                started.contains(dependency) && matches!(graph.state(*dependency), StageState::Done { .. }),
+                "{:?} started before {:?} was done",
+                stage,
+                dependency
+            );
+        }
+        started.push(stage);
+    }
+}
+
+#[test]
+fn test_fast_reports_start_stages_in_dependency_order() {
+    let now = Instant::now();
+    let mut graph = StartupGraph::new();
+    let mut started = Vec::new();
+
+    // Everything reports at once, even before its stage started
+    for event in reports().into_iter().rev() {
+        assert!(!graph.record(event, now));
+    }
+    advance_checked(&mut graph, now, &mut started);
+
+    assert_eq!(started, StartupStage::ALL);
+    assert!(graph.is_settled());
+    assert!(StartupStage::ALL.iter().all(|stage| matches!(graph.state(*stage), StageState::Done { .. })));
+    assert_eq!(graph.listening_port(), Some(8080));
+}
+
+#[test]
+fn test_slow_reports_wait_for_each_stage() {
+    let start = Instant::now();
+    let mut graph = StartupGraph::new();
+    let mut started = Vec::new();
+    advance_checked(&mut graph, start, &mut started);
+    assert_eq!(started, [StartupStage::Storage]);
+
+    // Each report comes a few seconds after the last, within every timeout
+    for (i, event) in reports().into_iter().enumerate() {
+        let now = start + Duration::from_secs(4 * (i as u64 + 1));
+        let stage = event.stage;
+        assert!(matches!(graph.state(stage), StageState::Running { .. }), "{:?} not running", stage);
+        assert!(graph.record(event, now));
+        // The retry worker started with the reachability check, one report earlier
+        let took = if stage == StartupStage::RetryWorker { 8 } else { 4 };
+        assert_eq!(*graph.state(stage), StageState::Done {
+            took: Duration::from_secs(took),
+            detail: match stage {
+                StartupStage::Storage => "3 chats".to_string(),
+                StartupStage::Identity => "me".to_string(),
+                StartupStage::TransportListening => "port 8080".to_string(),
+                StartupStage::Connectivity => "203.0.113.7:8080".to_string(),
+                StartupStage::ReachabilityCheck => "reachable".to_string(),
+                _ => String::new(),
+            },
+        });
+        advance_checked(&mut graph, now, &mut started);
+        // The retry worker and the reachability check both wait on connectivity only
+        if stage == StartupStage::Connectivity {
+            assert_eq!(started[started.len() - 2..], [StartupStage::ReachabilityCheck, StartupStage::RetryWorker]);
+        }
+    }
+    assert!(graph.is_settled());
+    assert_eq!(graph.describe(start)[3], "Listening: done in 4000ms (port 8080)");
+}
+
+#[test]
+fn test_each_timeout_degrades_the_stage_and_its_dependents() {
+    for stalled in StartupStage::ALL {
+        let mut now = Instant::now();
+        let mut graph = StartupGraph::new();
+        let mut started = Vec::new();
+        advance_checked(&mut graph, now, &mut started);
+
+        // Everything before the stalled stage reports in time
+        for event in reports().into_iter().take_while(|event| event.stage != stalled) {
+            graph.record(event, now);
+            advance_checked(&mut graph, now, &mut started);
+        }
+        assert!(matches!(graph.state(stalled), StageState::Running { .. }));
+
+        // Not degraded at the timeout, only past it
+        now += stalled.timeout();
+        advance_checked(&mut graph, now, &mut started);
+        assert!(matches!(graph.state(stalled), StageState::Running { .. }));
+        now += Duration::from_secs(1);
+        advance_checked(&mut graph, now, &mut started);
+        assert_eq!(*graph.state(stalled), StageState::Degraded {
+            reason: format!("timed out after {}s", stalled.timeout().as_secs()),
+        });
+
+        // Stages needing it are degraded without starting; the others finish
+        let blocked: Vec<StartupStage> = StartupStage::ALL
+            .into_iter()
+            .filter(|stage| stage.dependencies().iter().any(|d| matches!(graph.state(*d), StageState::Degraded { .. })))
+            .collect();
+        for stage in &blocked {
+            assert!(!started.contains(stage), "{:?} started although {:?} stalled", stage, stalled);
+            assert!(matches!(graph.state(*stage), StageState::Degraded { reason } if reason.ends_with("degraded")));
+        }
+        for event in reports().into_iter().filter(|event| started.contains(&event.stage) && event.stage != stalled) {
+            graph.record(event, now);
+        }
+        advance_checked(&mut graph, now, &mut started);
+        assert!(graph.is_settled(), "{:?} stalled: {:?}", stalled, graph.describe(now));
+
+        // A report after the timeout marks the stage done; its dependents stay degraded
+        let late = reports().into_iter().find(|event| event.stage == stalled).unwrap();
+        assert!(graph.record(late, now));
+        assert!(matches!(graph.state(stalled), StageState::Done { detail, .. } if detail.ends_with("late")));
+        for stage in &blocked {
+            assert!(matches!(graph.state(*stage), StageState::Degraded { .. }));
+        }
+    }
+}
+
+#[test]
+fn test_connectivity_only_starts_with_the_final_port() {
+    let now = Instant::now();
+    let mut graph = StartupGraph::new();
+    let mut started = Vec::new();
+    for event in reports().into_iter().take(3) {
+        graph.record(event, now);
+        advance_checked(&mut graph, now, &mut started);
+    }
+
+    // The server is running, but the port is not in the database yet
+    let later = now + Duration::from_secs(20);
+    advance_checked(&mut graph, later, &mut started);
+    assert!(!started.contains(&StartupStage::Connectivity));
+    assert_eq!(graph.listening_port(), None);
+
+    // Bound on another port after the preferred one was taken
+    graph.record(StartupEvent::listening(40123), later);
+    advance_checked(&mut graph, later, &mut started);
+    assert_eq!(started.last(), Some(&StartupStage::Connectivity));
+    assert_eq!(graph.listening_port(), Some(40123));
+
+    // A failed bind degrades connectivity and what follows it
+    let mut graph = StartupGraph::new();
+    for event in reports().into_iter().take(3) {
+        graph.record(event, now);
+        graph.advance(now);
+    }
+    graph.record(StartupEvent::degraded(StartupStage::TransportListening, "no free port"), now);
+    assert!(graph.advance(now).is_empty());
+    assert!(graph.is_settled());
+    assert_eq!(*graph.state(StartupStage::Connectivity), StageState::Degraded { reason: "listening degraded".to_string() });
+    assert_eq!(*graph.state(StartupStage::RetryWorker), StageState::Degraded { reason: "connectivity degraded".to_string() });
+}
@@ -0,0 +1,27 @@
+// Theme Tests - Profile accent applied on top of the theme
+
+use crate::storage::AccentColor;
+use crate::tui::theme::{accent_color, Theme};
+use crate::tui::App;
+use ratatui::style::Color;
+use tempfile::TempDir;
+
+#[test]
+fn test_accent_overrides_theme_slots() {
+    let theme = Theme::default();
+    assert_eq!(theme.with_accent(None), theme);
+
+    let accented = theme.with_accent(Some(AccentColor::Magenta));
+    assert_eq!(accented.title, Color::Magenta);
+    assert_eq!(accented.selection, Color::Magenta);
+    assert_eq!(accented.identity, Color::Magenta);
+    assert_eq!(accent_color(AccentColor::Blue), Color::LightBlue);
+
+    // The app resolves its theme from the profile settings
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    assert_eq!(app.theme(), Theme::default());
+    app.app_state.settings.accent_color = Some(AccentColor::Green);
+    assert_eq!(app.theme().title, Color::Green);
+    assert_eq!(app.theme().selection, Color::Green);
+}
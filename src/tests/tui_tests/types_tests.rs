@@ -46,3 +46,21 @@ fn test_menu_items_updated() {
     assert_eq!(items[4], MenuItem::Settings);
     assert_eq!(items[5], MenuItem::Exit);
 }
+
+#[test]
+fn test_startup_timings_record() {
+    use crate::tui::StartupTimings;
+    use std::time::Duration;
+
+    let mut timings = StartupTimings::new();
+    assert_eq!(timings.total(), Duration::ZERO);
+
+    timings.record("storage open", Duration::from_millis(5));
+    timings.record("state load", Duration::from_millis(10));
+
+    assert_eq!(timings.phases.len(), 2);
+    assert_eq!(timings.phases[0].0, "storage open");
+    assert_eq!(timings.get("state load"), Some(Duration::from_millis(10)));
+    assert_eq!(timings.get("missing"), None);
+    assert_eq!(timings.total(), Duration::from_millis(15));
+}
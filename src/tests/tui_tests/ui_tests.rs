@@ -79,6 +79,7 @@ fn test_main_menu_hides_warning_after_connectivity() {
         http: crate::connectivity::StrategyAttempt::NotAttempted,
         cgnat_detected: false,
         externally_reachable: None,
+        gateway_probes: Vec::new(),
     };
     app.connectivity_result = Some(mock_result);
 
@@ -111,6 +112,7 @@ fn test_main_menu_warning_logic_with_successful_mapping() {
         http: crate::connectivity::StrategyAttempt::NotAttempted,
         cgnat_detected: false,
         externally_reachable: Some(true),
+        gateway_probes: Vec::new(),
     };
 
     app.connectivity_result = Some(result_with_mapping);
@@ -120,3 +122,59 @@ fn test_main_menu_warning_logic_with_successful_mapping() {
     assert!(app.connectivity_result.as_ref().unwrap().mapping.is_some(),
         "Should have successful mapping");
 }
+
+#[test]
+fn test_escape_sequence_payload_rendered_harmlessly() {
+    use crate::storage::Message;
+    use crate::transport::{LoopbackNetwork, LoopbackTransport, MessageRequest, PeerTransport};
+    use ratatui::{backend::TestBackend, Terminal};
+
+    // A peer sends raw escape sequences (clear screen, set title, bell)
+    let payload = b"\x1b[2J\x1b]0;pwned\x07hello".to_vec();
+    let received = tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let network = LoopbackNetwork::new();
+        let (_receiver, received) = crate::tests::helpers::recording_peer(&network, "me").await;
+
+        let sender = LoopbackTransport::new(&network);
+        let request = MessageRequest {
+            from_uid: "mallory_uid".to_string(),
+            message_type: "text".to_string(),
+            payload: payload.clone(),
+            metadata: Default::default(),
+            message_id: None,
+        };
+        let contact = crate::storage::Contact::new(
+            "me".to_string(),
+            "loopback://me".to_string(),
+            vec![1, 2, 3],
+            vec![99u8; 32],
+            Utc::now() + Duration::days(1),
+        );
+        sender.send_message(&contact, &request).await.unwrap();
+        received
+    });
+
+    // Stored as received, then shown in the chat view
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    let request = received.lock().unwrap().remove(0);
+    assert_eq!(request.payload, payload);
+    let message = Message::new(
+        "incoming".to_string(),
+        request.from_uid.clone(),
+        app.keypair.uid.to_string(),
+        request.payload,
+        Utc::now().timestamp_millis(),
+    );
+    app.app_state.add_chat("mallory_uid".to_string());
+    app.app_state.chats[0].append_message(message);
+    app.show_chat_list_screen();
+    app.open_selected_chat();
+
+    let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+    terminal.draw(|f| crate::tui::ui::render_chat_view(f, &app)).unwrap();
+
+    let rendered: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+    assert!(!rendered.chars().any(|c| c.is_control()));
+    assert!(rendered.contains("[2J]0;pwnedhello"));
+}
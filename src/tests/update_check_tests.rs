@@ -0,0 +1,170 @@
+// Update check tests - signed manifest parsing and verification, daily throttle (virtual clock), semver ordering with pre-releases, notice severities, no fetch while disabled
+
+use crate::crypto::KeyPair;
+use crate::update_check::{
+    evaluate_manifest, parse_signed_manifest, sign_manifest, update_check_due, ManifestFetcher, ReleaseManifest,
+    UpdateNotice, Version, UPDATE_SIGNING_KEY,
+};
+use crate::tui::App;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn t0() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 5, 1, 9, 0, 0).unwrap()
+}
+
+fn manifest(latest: &str, min_protocol_version: u8) -> ReleaseManifest {
+    ReleaseManifest {
+        latest_version: latest.to_string(),
+        min_protocol_version,
+        release_notes_url: "https://example.org/notes".to_string(),
+    }
+}
+
+fn key_pair() -> ([u8; 32], [u8; 32]) {
+    let keypair = KeyPair::generate().unwrap();
+    (
+        keypair.private_key.clone().try_into().unwrap(),
+        keypair.public_key.clone().try_into().unwrap(),
+    )
+}
+
+/// Counts fetches and answers with a fixed body
+struct CountingFetcher {
+    calls: AtomicUsize,
+    body: String,
+}
+
+impl ManifestFetcher for CountingFetcher {
+    fn fetch(&self, _url: &str) -> Result<String, String> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(self.body.clone())
+    }
+}
+
+fn app_with_fetcher(temp_dir: &TempDir, body: String) -> (App, Arc<CountingFetcher>) {
+    let mut app = App::new_with_settings(Some(&temp_dir.path().join("settings.json"))).unwrap();
+    let fetcher = Arc::new(CountingFetcher { calls: AtomicUsize::new(0), body });
+    app.update_fetcher = fetcher.clone();
+    (app, fetcher)
+}
+
+fn wait_for_check(app: &mut App) {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while !app.poll_update_check() {
+        assert!(std::time::Instant::now() < deadline, "update check never finished");
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn test_signed_manifest_parses_and_bad_signatures_are_rejected() {
+    let (private_key, public_key) = key_pair();
+    let signed = sign_manifest(&manifest("0.9.0", 1), &private_key).unwrap();
+    assert_eq!(parse_signed_manifest(&signed, &public_key).unwrap(), manifest("0.9.0", 1));
+
+    // Another key (including the baked-in release key) does not verify it
+    let (_, other_public) = key_pair();
+    assert!(matches!(parse_signed_manifest(&signed, &other_public), Err(crate::Error::Crypto(_))));
+    assert!(matches!(parse_signed_manifest(&signed, &UPDATE_SIGNING_KEY), Err(crate::Error::Crypto(_))));
+
+    // Tampered manifest under the original signature
+    let tampered = signed.replace("0.9.0", "9.9.9");
+    assert!(matches!(parse_signed_manifest(&tampered, &public_key), Err(crate::Error::Crypto(_))));
+
+    // Malformed signatures and bodies
+    let mut file: serde_json::Value = serde_json::from_str(&signed).unwrap();
+    file["signature"] = serde_json::Value::String("abcd".to_string());
+    assert!(matches!(parse_signed_manifest(&file.to_string(), &public_key), Err(crate::Error::Crypto(_))));
+    assert!(parse_signed_manifest("not json", &public_key).is_err());
+}
+
+#[test]
+fn test_version_ordering_with_prerelease_tags() {
+    let v = |text: &str| Version::parse(text).unwrap();
+    assert!(v("0.2.0") > v("0.1.9"));
+    assert!(v("0.10.0") > v("0.9.0"));
+    assert!(v("1.0.0") > v("1.0.0-rc.1"));
+    assert!(v("1.0.0-rc.1") > v("1.0.0-beta.11"));
+    assert!(v("1.0.0-beta.11") > v("1.0.0-beta.2"));
+    assert!(v("1.0.0-beta") > v("1.0.0-alpha.1"));
+    assert!(v("1.0.0-alpha.1") > v("1.0.0-alpha"));
+    assert!(v("1.0.0-alpha.beta") > v("1.0.0-alpha.1"));
+    assert_eq!(v("v1.2.3+build.5"), v("1.2.3"));
+
+    for bad in ["", "1.2", "1.2.3.4", "1.x.3", "1.2.3-"] {
+        assert!(Version::parse(bad).is_none(), "{:?} parsed", bad);
+    }
+
+    // Pre-releases are only offered to pre-release builds
+    assert_eq!(evaluate_manifest(&manifest("0.3.0-beta.1", 1), "0.2.0", 1), None);
+    assert!(evaluate_manifest(&manifest("0.3.0-beta.2", 1), "0.3.0-beta.1", 1).is_some());
+    assert_eq!(evaluate_manifest(&manifest("0.2.0", 1), "0.2.0", 1), None);
+    assert_eq!(evaluate_manifest(&manifest("0.1.0", 1), "0.2.0", 1), None);
+}
+
+#[test]
+fn test_notice_severities() {
+    let available = evaluate_manifest(&manifest("0.3.0", 1), "0.2.0", 1).unwrap();
+    assert!(matches!(available, UpdateNotice::Available { .. }));
+    assert!(!available.is_urgent());
+    assert!(available.to_string().starts_with("Update available: 0.3.0"));
+
+    // Our protocol is below the minimum: the stronger warning, even if the
+    // version looks current
+    for current in ["0.2.0", "0.3.0"] {
+        let outdated = evaluate_manifest(&manifest("0.3.0", 2), current, 1).unwrap();
+        assert!(matches!(outdated, UpdateNotice::ProtocolOutdated { min_protocol_version: 2, .. }));
+        assert!(outdated.is_urgent());
+        assert!(outdated.to_string().contains("426"));
+    }
+}
+
+#[test]
+fn test_check_runs_at_most_daily_on_virtual_clock() {
+    assert!(update_check_due(None, t0()));
+    assert!(!update_check_due(Some(t0()), t0() + Duration::hours(23)));
+    assert!(update_check_due(Some(t0()), t0() + Duration::hours(24)));
+    // A clock that went backwards does not block checks for good
+    assert!(update_check_due(Some(t0()), t0() - Duration::hours(1)));
+
+    let temp_dir = TempDir::new().unwrap();
+    let (private_key, _) = key_pair();
+    let body = sign_manifest(&manifest("99.0.0", 1), &private_key).unwrap();
+    let (mut app, fetcher) = app_with_fetcher(&temp_dir, body);
+    app.app_state.settings.update_check_enabled = true;
+
+    assert!(app.maybe_check_for_updates(t0()));
+    wait_for_check(&mut app);
+    assert_eq!(fetcher.calls.load(Ordering::SeqCst), 1);
+    // Signed by a key other than the release key: reported, no notice
+    assert_eq!(app.update_notice, None);
+
+    assert!(!app.maybe_check_for_updates(t0() + Duration::hours(12)));
+    assert!(!app.maybe_check_for_updates(t0() + Duration::hours(23) + Duration::minutes(59)));
+    assert_eq!(fetcher.calls.load(Ordering::SeqCst), 1);
+
+    assert!(app.maybe_check_for_updates(t0() + Duration::hours(24)));
+    wait_for_check(&mut app);
+    assert_eq!(fetcher.calls.load(Ordering::SeqCst), 2);
+
+    // The last check is stored, so a restart does not check again
+    assert_eq!(app.storage.last_update_check().unwrap(), Some(t0() + Duration::hours(24)));
+}
+
+#[test]
+fn test_no_fetch_while_disabled() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut app, fetcher) = app_with_fetcher(&temp_dir, String::new());
+    assert!(!app.app_state.settings.update_check_enabled);
+
+    for day in 0..5 {
+        assert!(!app.maybe_check_for_updates(t0() + Duration::days(day)));
+        assert!(!app.poll_update_check());
+    }
+    assert_eq!(fetcher.calls.load(Ordering::SeqCst), 0);
+    assert_eq!(app.storage.last_update_check().unwrap(), None);
+    assert_eq!(app.update_notice, None);
+}
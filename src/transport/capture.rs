@@ -0,0 +1,345 @@
+//! Debug capture of the exchanges with one contact
+//!
+//! When a single contact cannot be reached, `CaptureSlot::start` records
+//! every request to and from that contact, with its response metadata, into
+//! a JSON Lines file under `CAPTURES_DIR`. Only one contact is captured at a
+//! time and nothing else is ever written; the capture ends by itself after
+//! `CAPTURE_MAX_MINUTES` or `CAPTURE_MAX_EXCHANGES` exchanges, whichever
+//! comes first.
+//!
+//! Bodies are reduced to their length and SHA-256 unless the capture was
+//! started with `include_bodies` (a second confirmation in the TUI). Capture
+//! files stay on this device; `write_diagnostics_bundle` copies them into a
+//! bundle only when asked to.
+
+use crate::queue::MessageQueue;
+use crate::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Longest a capture runs, in minutes
+pub const CAPTURE_MAX_MINUTES: i64 = 15;
+
+/// Most exchanges recorded by one capture
+pub const CAPTURE_MAX_EXCHANGES: usize = 50;
+
+/// Folder capture files are written to
+pub const CAPTURES_DIR: &str = "./app_data/captures";
+
+/// Who started an exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureDirection {
+    /// We sent the request
+    Outgoing,
+    /// The contact sent the request
+    Incoming,
+}
+
+/// A request or response body as captured
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedBody {
+    /// Length in bytes
+    pub bytes: usize,
+    /// Hex SHA-256
+    pub sha256: String,
+    /// Hex of the body itself (only with `include_bodies`)
+    pub hex: Option<String>,
+}
+
+impl CapturedBody {
+    /// Capture `body`, keeping its bytes only when `include` is set
+    pub fn new(body: &[u8], include: bool) -> Self {
+        Self {
+            bytes: body.len(),
+            sha256: hex::encode(digest(&SHA256, body)),
+            hex: include.then(|| hex::encode(body)),
+        }
+    }
+}
+
+/// One request and its outcome (one line of a capture file)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedExchange {
+    /// When the exchange finished (ms since epoch)
+    pub at: i64,
+    /// Who sent the request
+    pub direction: CaptureDirection,
+    /// Endpoint path (e.g. "/message")
+    pub path: String,
+    /// Address the request went to (outgoing only)
+    pub address: Option<String>,
+    /// Request body
+    pub request: CapturedBody,
+    /// HTTP status of the response (None if no response arrived)
+    pub status: Option<u16>,
+    /// Why the exchange failed, if it did
+    pub error: Option<String>,
+    /// Response body (outgoing only)
+    pub response: Option<CapturedBody>,
+}
+
+/// First line of a capture file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CaptureHeader {
+    capture: String,
+    started_at: i64,
+    expires_at: i64,
+    max_exchanges: usize,
+    include_bodies: bool,
+}
+
+/// A running capture
+#[derive(Debug)]
+pub struct CaptureSession {
+    /// The only contact whose traffic is recorded
+    pub contact_uid: String,
+    /// When the capture started
+    pub started_at: DateTime<Utc>,
+    /// Whether bodies are kept instead of length and hash
+    pub include_bodies: bool,
+    /// Exchanges recorded so far
+    pub exchanges: usize,
+    /// The capture file
+    pub path: PathBuf,
+}
+
+impl CaptureSession {
+    /// Start capturing `contact_uid` into a new file in `dir`
+    ///
+    /// # Errors
+    /// Returns an I/O error if the folder or file cannot be created
+    pub fn start(contact_uid: &str, include_bodies: bool, dir: &Path, now: DateTime<Utc>) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let session = Self {
+            contact_uid: contact_uid.to_string(),
+            started_at: now,
+            include_bodies,
+            exchanges: 0,
+            path: dir.join(capture_file_name(contact_uid, now)),
+        };
+        let header = CaptureHeader {
+            capture: session.contact_uid.clone(),
+            started_at: now.timestamp_millis(),
+            expires_at: session.expires_at().timestamp_millis(),
+            max_exchanges: CAPTURE_MAX_EXCHANGES,
+            include_bodies,
+        };
+        let mut file = std::fs::OpenOptions::new().write(true).create_new(true).open(&session.path)?;
+        writeln!(file, "{}", serde_json::to_string(&header)?)?;
+        Ok(session)
+    }
+
+    /// When the capture ends at the latest
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.started_at + Duration::minutes(CAPTURE_MAX_MINUTES)
+    }
+
+    /// Whether the time or exchange limit has been reached
+    pub fn is_finished(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at() || self.exchanges >= CAPTURE_MAX_EXCHANGES
+    }
+
+    /// Append `exchange` to the capture file
+    fn append(&mut self, exchange: &CapturedExchange) -> Result<()> {
+        let mut file = std::fs::OpenOptions::new().append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(exchange)?)?;
+        self.exchanges += 1;
+        Ok(())
+    }
+}
+
+/// What the TUI shows about the running capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureStatus {
+    /// Contact being captured
+    pub contact_uid: String,
+    /// The capture file
+    pub path: PathBuf,
+    /// Exchanges recorded so far
+    pub exchanges: usize,
+    /// When the capture ends at the latest
+    pub expires_at: DateTime<Utc>,
+    /// Whether bodies are kept
+    pub include_bodies: bool,
+}
+
+/// The capture slot of a `Transport`: empty, or one contact's capture
+///
+/// Clones share the slot, so every sender using the same transport records
+/// into the same capture.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureSlot {
+    session: Arc<Mutex<Option<CaptureSession>>>,
+}
+
+impl CaptureSlot {
+    /// Capture `contact_uid`, replacing any running capture
+    ///
+    /// # Returns
+    /// The capture file
+    pub fn start(&self, contact_uid: &str, include_bodies: bool, dir: &Path, now: DateTime<Utc>) -> Result<PathBuf> {
+        if contact_uid.is_empty() {
+            return Err(Error::Transport("A capture needs a contact".to_string()));
+        }
+        let session = CaptureSession::start(contact_uid, include_bodies, dir, now)?;
+        let path = session.path.clone();
+        *self.session.lock().unwrap() = Some(session);
+        Ok(path)
+    }
+
+    /// End the running capture
+    ///
+    /// # Returns
+    /// The file of the capture that was running
+    pub fn stop(&self) -> Option<PathBuf> {
+        self.session.lock().unwrap().take().map(|s| s.path)
+    }
+
+    /// The running capture, ending it first if a limit was reached
+    pub fn status(&self, now: DateTime<Utc>) -> Option<CaptureStatus> {
+        let mut guard = self.session.lock().unwrap();
+        if guard.as_ref().is_some_and(|s| s.is_finished(now)) {
+            *guard = None;
+        }
+        guard.as_ref().map(|s| CaptureStatus {
+            contact_uid: s.contact_uid.clone(),
+            path: s.path.clone(),
+            exchanges: s.exchanges,
+            expires_at: s.expires_at(),
+            include_bodies: s.include_bodies,
+        })
+    }
+
+    /// Whether `contact_uid`'s traffic is being captured
+    pub fn is_capturing(&self, contact_uid: &str, now: DateTime<Utc>) -> bool {
+        self.status(now).is_some_and(|s| s.contact_uid == contact_uid)
+    }
+
+    /// Record an exchange with `contact_uid` if it is the captured contact
+    ///
+    /// `build` gets whether bodies are included. Write failures are logged
+    /// and end the capture.
+    pub fn record(&self, contact_uid: &str, now: DateTime<Utc>, build: impl FnOnce(bool) -> CapturedExchange) {
+        let mut guard = self.session.lock().unwrap();
+        let Some(session) = guard.as_mut() else {
+            return;
+        };
+        if session.is_finished(now) {
+            *guard = None;
+            return;
+        }
+        if session.contact_uid != contact_uid {
+            return;
+        }
+        let exchange = build(session.include_bodies);
+        if let Err(e) = session.append(&exchange) {
+            tracing::warn!("Capture of {} stopped: {}", session.contact_uid, e);
+            *guard = None;
+        } else if session.is_finished(now) {
+            *guard = None;
+        }
+    }
+}
+
+impl CapturedExchange {
+    /// An exchange we started: `request` sent to `address` + `path`
+    pub fn outgoing(
+        address: &str,
+        path: &str,
+        request: &[u8],
+        outcome: &std::result::Result<(u16, &[u8]), String>,
+        include_bodies: bool,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            at: now.timestamp_millis(),
+            direction: CaptureDirection::Outgoing,
+            path: path.to_string(),
+            address: Some(address.to_string()),
+            request: CapturedBody::new(request, include_bodies),
+            status: outcome.as_ref().ok().map(|(status, _)| *status),
+            error: outcome.as_ref().err().cloned(),
+            response: outcome.as_ref().ok().map(|(_, body)| CapturedBody::new(body, include_bodies)),
+        }
+    }
+
+    /// A request the contact sent to `path`, answered with `status`
+    pub fn incoming(
+        path: &str,
+        request: &[u8],
+        status: u16,
+        error: Option<String>,
+        include_bodies: bool,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            at: now.timestamp_millis(),
+            direction: CaptureDirection::Incoming,
+            path: path.to_string(),
+            address: None,
+            request: CapturedBody::new(request, include_bodies),
+            status: Some(status),
+            error,
+            response: None,
+        }
+    }
+}
+
+/// File name of a capture of `contact_uid` started at `now`
+///
+/// Built only from the UID's ASCII letters and digits.
+pub fn capture_file_name(contact_uid: &str, now: DateTime<Utc>) -> String {
+    let uid: String = contact_uid.chars().filter(|c| c.is_ascii_alphanumeric()).take(16).collect();
+    format!("capture-{}-{}.jsonl", uid, now.format("%Y%m%d_%H%M%S%3f"))
+}
+
+/// Capture files in `dir`, newest first
+pub fn list_captures(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    files.sort();
+    files.reverse();
+    files
+}
+
+/// Write a diagnostics bundle into the new folder `dir`
+///
+/// Always holds the redacted queue report (`queue.json`, see
+/// `MessageQueue::export_debug`); the files in `captures` are copied into
+/// `captures/` only when listed, never by default.
+///
+/// # Returns
+/// Paths of the files written
+///
+/// # Errors
+/// Returns an error if `dir` exists or a file cannot be written
+pub fn write_diagnostics_bundle(dir: &Path, queue: &MessageQueue, captures: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir(dir)?;
+    let mut written = Vec::new();
+    let report = dir.join("queue.json");
+    queue.export_debug(&report)?;
+    written.push(report);
+    if !captures.is_empty() {
+        let capture_dir = dir.join("captures");
+        std::fs::create_dir(&capture_dir)?;
+        for capture in captures {
+            let name = capture
+                .file_name()
+                .ok_or_else(|| Error::Storage(format!("Not a capture file: {}", capture.display())))?;
+            let target = capture_dir.join(name);
+            std::fs::copy(capture, &target)?;
+            written.push(target);
+        }
+    }
+    Ok(written)
+}
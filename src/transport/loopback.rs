@@ -0,0 +1,323 @@
+//! In-process loopback transport
+//!
+//! Peers attached to the same `LoopbackNetwork` reach each other at
+//! `loopback://<name>` addresses through direct handler calls instead of
+//! sockets. Requests still go through CBOR encoding and the same checks as the
+//! HTTP endpoints, so it doubles as the transport for two-peer tests.
+
+use super::{
+    peer::addresses_for_scheme, ping_status_error, MessageRequest, NewMessageHandler, PeerTransport, PingHandler,
+    PingResponse, TransportCapabilities, TransportFuture, TOKEN_TOO_SHORT_LIVED_STATUS,
+};
+use crate::{
+    relay::{CapabilityProbe, Relay, RelayCapabilities, RelayEnvelope},
+    storage::{split_address_scheme, validate_metadata, Contact},
+    Error, Result,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Address scheme of the loopback transport
+pub const LOOPBACK_SCHEME: &str = "loopback";
+
+/// Handlers and identity of one loopback peer
+#[derive(Default)]
+struct LoopbackHandlers {
+    local_uid: Option<String>,
+    new_message_handler: Option<NewMessageHandler>,
+    ping_handler: Option<PingHandler>,
+    relay: Option<Arc<std::sync::Mutex<Relay>>>,
+}
+
+/// Shared namespace in which loopback peers find each other
+#[derive(Clone, Default)]
+pub struct LoopbackNetwork {
+    /// Listening peers by address (without scheme)
+    listeners: Arc<Mutex<HashMap<String, Arc<Mutex<LoopbackHandlers>>>>>,
+}
+
+impl LoopbackNetwork {
+    /// Create an empty network
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check whether a peer is listening at `address` (with or without scheme)
+    pub async fn is_listening(&self, address: &str) -> bool {
+        let (_, name) = split_address_scheme(address);
+        self.listeners.lock().await.contains_key(name)
+    }
+
+    /// Handlers of the peer listening at `name`
+    async fn listener(&self, name: &str) -> Result<Arc<Mutex<LoopbackHandlers>>> {
+        self.listeners
+            .lock()
+            .await
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::Transport(format!("No loopback peer listening at {}", name)))
+    }
+}
+
+/// Transport delivering to peers on a `LoopbackNetwork`
+#[derive(Clone)]
+pub struct LoopbackTransport {
+    /// Network this peer is attached to
+    network: LoopbackNetwork,
+    /// This peer's handlers (shared with the network while listening)
+    handlers: Arc<Mutex<LoopbackHandlers>>,
+    /// Name this peer listens on
+    address: Arc<Mutex<Option<String>>>,
+}
+
+impl LoopbackTransport {
+    /// Create a transport attached to `network`
+    pub fn new(network: &LoopbackNetwork) -> Self {
+        Self {
+            network: network.clone(),
+            handlers: Arc::new(Mutex::new(LoopbackHandlers::default())),
+            address: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Set the local UID returned in ping responses
+    pub async fn set_local_uid(&self, uid: String) {
+        self.handlers.lock().await.local_uid = Some(uid);
+    }
+
+    /// Set the handler for incoming messages
+    ///
+    /// Returning an error makes delivery fail for the sender, like a 503 from
+    /// the HTTP endpoint.
+    pub async fn set_new_message_handler<F>(&self, handler: F)
+    where
+        F: Fn(MessageRequest) -> Result<()> + Send + Sync + 'static,
+    {
+        self.handlers.lock().await.new_message_handler = Some(Arc::new(move |request, _| handler(request)));
+    }
+
+    /// Set the handler for incoming pings (receives the sender's contact token)
+    ///
+    /// Returning `Error::RateLimited` or `Error::TokenTooShortLived` fails the
+    /// ping for the sender, like a 429 or 422 from the HTTP endpoint.
+    pub async fn set_ping_handler<F>(&self, handler: F)
+    where
+        F: Fn(String) -> Result<()> + Send + Sync + 'static,
+    {
+        self.handlers.lock().await.ping_handler = Some(Arc::new(move |token, _| handler(token)));
+    }
+
+    /// Accept relay envelopes into `relay`, like the HTTP `/relay` endpoint
+    pub async fn set_relay(&self, relay: Arc<std::sync::Mutex<Relay>>) {
+        self.handlers.lock().await.relay = Some(relay);
+    }
+
+    /// Full address (`loopback://<name>`) this peer listens on
+    pub async fn local_address(&self) -> Option<String> {
+        self.address
+            .lock()
+            .await
+            .as_ref()
+            .map(|name| format!("{}://{}", LOOPBACK_SCHEME, name))
+    }
+
+    /// Hand an encoded message request to the peer listening at `name`
+    async fn deliver_to(&self, name: &str, cbor_data: &[u8]) -> Result<()> {
+        let peer = self.network.listener(name).await?;
+
+        let msg_req: MessageRequest = serde_cbor::from_slice(cbor_data)
+            .map_err(|e| Error::Transport(format!("Invalid message format: {}", e)))?;
+        if validate_metadata(&msg_req.metadata).is_err() {
+            return Err(Error::Transport("Invalid message metadata".to_string()));
+        }
+
+        let handler = peer.lock().await.new_message_handler.clone();
+        match handler {
+            Some(handler) => handler(msg_req, None)
+                .map_err(|e| Error::Transport(format!("Message not stored, retry later: {}", e))),
+            None => {
+                warn!("No message handler at loopback peer {}, message dropped", name);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl PeerTransport for LoopbackTransport {
+    fn scheme(&self) -> &str {
+        LOOPBACK_SCHEME
+    }
+
+    fn capabilities(&self) -> TransportCapabilities {
+        TransportCapabilities {
+            can_send: true,
+            can_listen: true,
+            anonymous: false,
+        }
+    }
+
+    fn send_message<'a>(
+        &'a self,
+        contact: &'a Contact,
+        request: &'a MessageRequest,
+    ) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            validate_metadata(&request.metadata)?;
+            let cbor_data = serde_cbor::to_vec(request)
+                .map_err(|e| Error::CborSerialization(format!("Failed to serialize message request: {}", e)))?;
+
+            let mut last_error = None;
+            for (_, name) in addresses_for_scheme(contact, None, LOOPBACK_SCHEME) {
+                match self.deliver_to(&name, &cbor_data).await {
+                    Ok(()) => {
+                        info!("Loopback message delivered to {} at {}", contact.uid, name);
+                        return Ok(());
+                    }
+                    Err(e) => last_error = Some(e),
+                }
+            }
+
+            Err(last_error.unwrap_or_else(|| {
+                Error::Transport(format!("No loopback address for contact {}", contact.uid))
+            }))
+        })
+    }
+
+    fn relay_message<'a>(
+        &'a self,
+        relay: &'a Contact,
+        envelope: &'a RelayEnvelope,
+    ) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            // Round-trip through CBOR like the HTTP endpoint
+            let cbor_data = serde_cbor::to_vec(envelope)
+                .map_err(|e| Error::CborSerialization(format!("Failed to serialize relay envelope: {}", e)))?;
+
+            let mut last_error = None;
+            for (_, name) in addresses_for_scheme(relay, None, LOOPBACK_SCHEME) {
+                let peer = match self.network.listener(&name).await {
+                    Ok(peer) => peer,
+                    Err(e) => {
+                        last_error = Some(e);
+                        continue;
+                    }
+                };
+                let Some(relay_state) = peer.lock().await.relay.clone() else {
+                    last_error = Some(Error::Relay(format!("Loopback peer {} does not relay", name)));
+                    continue;
+                };
+                let envelope: RelayEnvelope = serde_cbor::from_slice(&cbor_data)
+                    .map_err(|e| Error::Transport(format!("Invalid relay envelope: {}", e)))?;
+                relay_state.lock().unwrap().accept(envelope, chrono::Utc::now())?;
+                return Ok(());
+            }
+
+            Err(last_error.unwrap_or_else(|| {
+                Error::Transport(format!("No loopback address for relay {}", relay.uid))
+            }))
+        })
+    }
+
+    fn probe_capabilities<'a>(
+        &'a self,
+        contact: &'a Contact,
+        from_uid: &'a str,
+    ) -> TransportFuture<'a, Option<RelayCapabilities>> {
+        Box::pin(async move {
+            // Round-trip through CBOR like the HTTP endpoint
+            let cbor_data = serde_cbor::to_vec(&CapabilityProbe { from_uid: from_uid.to_string() })
+                .map_err(|e| Error::CborSerialization(format!("Failed to serialize capability probe: {}", e)))?;
+
+            let mut last_error = None;
+            for (_, name) in addresses_for_scheme(contact, None, LOOPBACK_SCHEME) {
+                let peer = match self.network.listener(&name).await {
+                    Ok(peer) => peer,
+                    Err(e) => {
+                        last_error = Some(e);
+                        continue;
+                    }
+                };
+                // A peer without relay state stands in for a client without the endpoint
+                let Some(relay_state) = peer.lock().await.relay.clone() else {
+                    return Ok(None);
+                };
+                let probe: CapabilityProbe = serde_cbor::from_slice(&cbor_data)
+                    .map_err(|e| Error::Transport(format!("Invalid capability probe: {}", e)))?;
+                let answer = relay_state.lock().unwrap().capabilities_for(&probe.from_uid);
+                return match answer {
+                    Some(capabilities) => Ok(Some(capabilities)),
+                    None => Err(Error::Transport("Capability probe refused with status 403 Forbidden".to_string())),
+                };
+            }
+
+            Err(last_error.unwrap_or_else(|| {
+                Error::Transport(format!("No loopback address for {}", contact.uid))
+            }))
+        })
+    }
+
+    fn send_ping<'a>(
+        &'a self,
+        contact: &'a Contact,
+        my_contact_token: &'a str,
+    ) -> TransportFuture<'a, PingResponse> {
+        Box::pin(async move {
+            let (scheme, name) = split_address_scheme(&contact.ip);
+            if scheme != LOOPBACK_SCHEME {
+                return Err(Error::NotSupported(format!(
+                    "Loopback transport cannot reach '{}' addresses",
+                    scheme
+                )));
+            }
+
+            let peer = self.network.listener(name).await?;
+            let (handler, uid) = {
+                let guard = peer.lock().await;
+                (guard.ping_handler.clone(), guard.local_uid.clone())
+            };
+            // A ping without a token has nothing to import
+            if let Some(handler) = handler.filter(|_| !my_contact_token.is_empty()) {
+                match handler(my_contact_token.to_string(), None) {
+                    Err(Error::RateLimited(reason)) => {
+                        return Err(Error::Transport(format!("Ping failed with status 429 Too Many Requests: {}", reason)));
+                    }
+                    Err(Error::TokenTooShortLived(reason)) => {
+                        let message = format!("Ping failed with status 422 Unprocessable Entity: {}", reason);
+                        return Err(ping_status_error(TOKEN_TOO_SHORT_LIVED_STATUS, message));
+                    }
+                    Err(e) => warn!("Ping handler at loopback peer {} failed: {}", name, e),
+                    Ok(()) => {}
+                }
+            }
+
+            Ok(PingResponse {
+                uid: uid.unwrap_or_else(|| "unknown".to_string()),
+                status: "ok".to_string(),
+            })
+        })
+    }
+
+    fn start_listener<'a>(&'a self, address: &'a str) -> TransportFuture<'a, String> {
+        Box::pin(async move {
+            let (_, name) = split_address_scheme(address);
+            let mut listeners = self.network.listeners.lock().await;
+            if listeners.contains_key(name) {
+                return Err(Error::Transport(format!("Loopback address {} already in use", name)));
+            }
+            listeners.insert(name.to_string(), self.handlers.clone());
+            *self.address.lock().await = Some(name.to_string());
+            Ok(name.to_string())
+        })
+    }
+
+    fn stop_listener(&self) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(name) = self.address.lock().await.take() {
+                self.network.listeners.lock().await.remove(&name);
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,1682 @@
+//! Network transport module
+//!
+//! This module handles peer-to-peer networking including:
+//! - HTTP/2 POST /output endpoint for receiving messages
+//! - Direct peer-to-peer message sending
+//! - Delivery state tracking and logging
+//! - Integration with message queue for retry logic
+//! - Pluggable carriers behind the `PeerTransport` trait (HTTP, loopback, onion)
+//! - Optional TLS on the same port, pinned by certificate fingerprint (see `tls`)
+//! - Debug capture of the exchanges with one contact (see `capture`)
+
+pub mod capture;
+pub mod loopback;
+#[cfg(feature = "onion")]
+pub mod onion;
+pub mod peer;
+pub mod tls;
+pub mod watchdog;
+
+pub use capture::{
+    capture_file_name, list_captures, write_diagnostics_bundle, CaptureDirection, CaptureSession, CaptureSlot,
+    CaptureStatus, CapturedBody, CapturedExchange, CAPTURES_DIR, CAPTURE_MAX_EXCHANGES, CAPTURE_MAX_MINUTES,
+};
+pub use loopback::{LoopbackNetwork, LoopbackTransport};
+#[cfg(feature = "onion")]
+pub use onion::OnionTransport;
+pub use peer::{PeerTransport, TransportCapabilities, TransportFuture, TransportRegistry, MAX_LEARNED_CONTACTS};
+pub use tls::{
+    cert_fingerprint, connect_pinned, plain_http_allowed, TlsIdentity, TlsTransport, TLS_CERT_RENEW_DAYS,
+    TLS_CERT_VALIDITY_DAYS, TLS_SCHEME,
+};
+pub use watchdog::{bind_verified, probe_self, ProbeOutcome, BOOT_NONCE_HEADER};
+
+use crate::{
+    cache::BoundedCache,
+    invite::{audit_failed_redemption, InviteBook, InviteRedemption, InviteResponse, INVITE_PATH},
+    protocol::MessageEnvelope,
+    relay::{CapabilityProbe, Relay, RelayCapabilities, RelayEnvelope, CAPABILITIES_PATH, RELAY_PATH},
+    storage::{split_address_scheme, validate_metadata, MessageMetadata, PeerAddress, DEFAULT_ADDRESS_SCHEME},
+    Error, Result,
+};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tracing::{debug, error, info, warn};
+
+/// Represents a peer in the network
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Peer {
+    /// Unique peer identifier (UID)
+    pub id: String,
+    /// Network address (e.g., "192.168.1.100:8080" or "example.com:8080")
+    pub addr: String,
+    /// Public key for encryption
+    pub public_key: Vec<u8>,
+}
+
+/// Delivery state for logging
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeliveryState {
+    /// Message successfully delivered
+    Success,
+    /// Message queued for later delivery
+    Queued,
+    /// Message failed, will retry
+    Retry {
+        /// Current attempt number
+        attempt: u32,
+        /// Next retry timestamp in milliseconds
+        next_retry_ms: i64,
+    },
+    /// Message failed permanently (max retries exceeded)
+    Failed,
+}
+
+/// Status answering a ping whose token expires too soon (see `storage::bounds`)
+pub const TOKEN_TOO_SHORT_LIVED_STATUS: u16 = 422;
+
+/// Error for a ping answered with the non-success `status`
+///
+/// `TOKEN_TOO_SHORT_LIVED_STATUS` becomes `Error::TokenTooShortLived`, so the
+/// sender can retry once with a fresh token; anything else is a transport error.
+pub fn ping_status_error(status: u16, message: String) -> Error {
+    if status == TOKEN_TOO_SHORT_LIVED_STATUS {
+        Error::TokenTooShortLived(message)
+    } else {
+        Error::Transport(message)
+    }
+}
+
+/// Ping request structure
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PingRequest {
+    /// Contact token of the sender (base64 CBOR with signature)
+    /// This allows the receiver to automatically import the sender. Empty
+    /// when the receiver already has the sender's current token: the ping
+    /// only checks that it is reachable.
+    pub contact_token: String,
+}
+
+/// Ping response structure
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PingResponse {
+    /// UID of the responding peer
+    pub uid: String,
+    /// Status message
+    pub status: String,
+}
+
+/// Message request structure for /message endpoint
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MessageRequest {
+    /// UID of the sender
+    pub from_uid: String,
+    /// Type of message (e.g., "text", "delete", "typing", etc.)
+    pub message_type: String,
+    /// Message payload (arbitrary bytes)
+    pub payload: Vec<u8>,
+    /// Application metadata (omitted on the wire when empty, so older peers
+    /// never see the field)
+    #[serde(default, skip_serializing_if = "MessageMetadata::is_empty")]
+    pub metadata: MessageMetadata,
+    /// Sender's ID for the message, which later edits refer to (omitted on
+    /// the wire when absent, so older peers never see the field)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+}
+
+/// Callback type for handling received messages (legacy - for /output endpoint)
+pub type MessageHandler = Arc<dyn Fn(MessageEnvelope) + Send + Sync>;
+
+/// Callback type for handling received messages from /message endpoint
+///
+/// Also receives the socket address the request came from (None over
+/// carriers without one). Returning an error (e.g. the message could not be
+/// stored) makes the endpoint answer 503 so the sender keeps the message
+/// queued and retries.
+pub type NewMessageHandler = Arc<dyn Fn(MessageRequest, Option<IpAddr>) -> Result<()> + Send + Sync>;
+
+/// Callback type for handling received ping requests
+///
+/// Also receives the socket address the request came from (None over
+/// carriers without one). Returning `Error::RateLimited` makes the endpoint
+/// answer 429 and `Error::TokenTooShortLived` 422
+/// (`TOKEN_TOO_SHORT_LIVED_STATUS`); other errors are logged and the ping is
+/// still answered.
+pub type PingHandler = Arc<dyn Fn(String, Option<IpAddr>) -> Result<()> + Send + Sync>;
+
+/// Network transport layer
+#[derive(Clone)]
+pub struct Transport {
+    /// Local binding address
+    local_addr: Arc<std::sync::Mutex<Option<SocketAddr>>>,
+    /// Accept loop of the running listener
+    listener_task: Arc<std::sync::Mutex<Option<tokio::task::AbortHandle>>>,
+    /// Known peers
+    peers: Arc<Mutex<Vec<Peer>>>,
+    /// Message handler callback (legacy - for /output endpoint)
+    message_handler: Arc<Mutex<Option<MessageHandler>>>,
+    /// New message handler callback (for /message endpoint)
+    pub(crate) new_message_handler: Arc<Mutex<Option<NewMessageHandler>>>,
+    /// Ping handler callback (for /ping endpoint)
+    pub(crate) ping_handler: Arc<Mutex<Option<PingHandler>>>,
+    /// HTTP client for sending messages
+    client: Client<HttpConnector, Full<Bytes>>,
+    /// Local UID for ping responses
+    pub(crate) local_uid: Arc<Mutex<Option<String>>>,
+    /// Address that last accepted a message, by contact UID
+    learned_endpoints: Arc<BoundedCache<String, String>>,
+    /// Invite codes redeemable over `POST /invite`
+    invites: Arc<std::sync::Mutex<InviteBook>>,
+    /// Envelopes accepted over `POST /relay` for other contacts
+    relay: Arc<std::sync::Mutex<Relay>>,
+    /// Random nonce of the running listener, returned by `GET /health`
+    boot_nonce: Arc<std::sync::Mutex<String>>,
+    /// Serves TLS connections on the listener's port when set
+    tls_acceptor: Arc<std::sync::Mutex<Option<tokio_native_tls::TlsAcceptor>>>,
+    /// Refuse plain HTTP to non-LAN addresses of contacts with a pinned certificate
+    require_tls_external: Arc<AtomicBool>,
+    /// Debug capture of one contact's exchanges (empty unless started)
+    capture: CaptureSlot,
+}
+
+impl Transport {
+    /// Create a new transport instance
+    pub fn new() -> Self {
+        let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build_http();
+
+        Self {
+            local_addr: Arc::new(std::sync::Mutex::new(None)),
+            listener_task: Arc::new(std::sync::Mutex::new(None)),
+            peers: Arc::new(Mutex::new(Vec::new())),
+            message_handler: Arc::new(Mutex::new(None)),
+            new_message_handler: Arc::new(Mutex::new(None)),
+            ping_handler: Arc::new(Mutex::new(None)),
+            client,
+            local_uid: Arc::new(Mutex::new(None)),
+            learned_endpoints: Arc::new(BoundedCache::new("learned endpoints", MAX_LEARNED_CONTACTS, None)),
+            invites: Arc::new(std::sync::Mutex::new(InviteBook::new())),
+            relay: Arc::new(std::sync::Mutex::new(Relay::new())),
+            boot_nonce: Arc::new(std::sync::Mutex::new(String::new())),
+            tls_acceptor: Arc::new(std::sync::Mutex::new(None)),
+            require_tls_external: Arc::new(AtomicBool::new(false)),
+            capture: CaptureSlot::default(),
+        }
+    }
+
+    /// Set the local UID for this transport instance
+    pub async fn set_local_uid(&self, uid: String) {
+        let mut guard = self.local_uid.lock().await;
+        *guard = Some(uid);
+    }
+
+    /// Set the message handler callback (legacy - for /output endpoint)
+    pub async fn set_message_handler<F>(&self, handler: F)
+    where
+        F: Fn(MessageEnvelope) + Send + Sync + 'static,
+    {
+        let mut guard = self.message_handler.lock().await;
+        *guard = Some(Arc::new(handler));
+    }
+
+    /// Set the new message handler callback (for /message endpoint)
+    ///
+    /// This handler receives MessageRequest objects from the /message endpoint.
+    /// The handler is responsible for storing messages in AppState via Chat,
+    /// and should return an error if the message could not be stored.
+    pub async fn set_new_message_handler<F>(&self, handler: F)
+    where
+        F: Fn(MessageRequest) -> Result<()> + Send + Sync + 'static,
+    {
+        self.set_new_message_handler_with_source(move |request, _| handler(request)).await;
+    }
+
+    /// Set the new message handler callback, also given the socket address of the sender
+    pub async fn set_new_message_handler_with_source<F>(&self, handler: F)
+    where
+        F: Fn(MessageRequest, Option<IpAddr>) -> Result<()> + Send + Sync + 'static,
+    {
+        let capture = self.capture.clone();
+        let handler = move |request: MessageRequest, source: Option<IpAddr>| {
+            // Re-encoded only while the sender is being captured
+            let captured = capture
+                .is_capturing(&request.from_uid, chrono::Utc::now())
+                .then(|| (request.from_uid.clone(), serde_cbor::to_vec(&request).unwrap_or_default()));
+            let result = handler(request, source);
+            if let Some((uid, body)) = captured {
+                let status = match &result {
+                    Ok(()) => StatusCode::OK,
+                    Err(Error::RateLimited(_)) => StatusCode::TOO_MANY_REQUESTS,
+                    Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+                };
+                let error = result.as_ref().err().map(|e| e.to_string());
+                let now = chrono::Utc::now();
+                capture.record(&uid, now, |bodies| {
+                    CapturedExchange::incoming("/message", &body, status.as_u16(), error, bodies, now)
+                });
+            }
+            result
+        };
+        let mut guard = self.new_message_handler.lock().await;
+        *guard = Some(Arc::new(handler));
+    }
+
+    /// Set the ping handler callback (for /ping endpoint)
+    ///
+    /// This handler receives the sender's UID when a ping request is received.
+    /// The handler can create a chat or perform other actions based on the ping.
+    pub async fn set_ping_handler<F>(&self, handler: F)
+    where
+        F: Fn(String) -> Result<()> + Send + Sync + 'static,
+    {
+        self.set_ping_handler_with_source(move |token, _| handler(token)).await;
+    }
+
+    /// Set the ping handler callback, also given the socket address of the sender
+    pub async fn set_ping_handler_with_source<F>(&self, handler: F)
+    where
+        F: Fn(String, Option<IpAddr>) -> Result<()> + Send + Sync + 'static,
+    {
+        let capture = self.capture.clone();
+        let handler = move |token: String, source: Option<IpAddr>| {
+            // The token is only parsed while some contact is being captured
+            let captured = capture.status(chrono::Utc::now()).and_then(|status| {
+                crate::storage::parse_contact_token(&token)
+                    .ok()
+                    .filter(|contact| contact.uid == status.contact_uid)
+                    .map(|contact| contact.uid)
+            });
+            let result = handler(token.clone(), source);
+            if let Some(uid) = captured {
+                let status = match &result {
+                    Err(Error::RateLimited(_)) => StatusCode::TOO_MANY_REQUESTS,
+                    Err(Error::TokenTooShortLived(_)) => StatusCode::UNPROCESSABLE_ENTITY,
+                    _ => StatusCode::OK,
+                };
+                let error = result.as_ref().err().map(|e| e.to_string());
+                let body = serde_cbor::to_vec(&PingRequest { contact_token: token }).unwrap_or_default();
+                let now = chrono::Utc::now();
+                capture.record(&uid, now, |bodies| {
+                    CapturedExchange::incoming("/ping", &body, status.as_u16(), error, bodies, now)
+                });
+            }
+            result
+        };
+        let mut guard = self.ping_handler.lock().await;
+        *guard = Some(Arc::new(handler));
+    }
+
+    /// Debug capture slot (shared by every clone of this transport)
+    pub fn capture(&self) -> &CaptureSlot {
+        &self.capture
+    }
+
+    /// Invite codes served by this transport's listener
+    pub fn invites(&self) -> Arc<std::sync::Mutex<InviteBook>> {
+        self.invites.clone()
+    }
+
+    /// Relay state served by this transport's listener (disabled until enabled)
+    pub fn relay(&self) -> Arc<std::sync::Mutex<Relay>> {
+        self.relay.clone()
+    }
+
+    /// Boot nonce of the running listener (empty before the first start)
+    pub fn boot_nonce(&self) -> String {
+        self.boot_nonce.lock().unwrap().clone()
+    }
+
+    /// Serve TLS with `identity` on the listener's port, next to plain HTTP
+    /// (None: plain HTTP only)
+    ///
+    /// Applies to connections accepted from now on, without a restart.
+    ///
+    /// # Errors
+    /// Returns `Error::Crypto` if the identity cannot be loaded
+    pub fn set_tls_identity(&self, identity: Option<&TlsIdentity>) -> Result<()> {
+        let acceptor = identity.map(TlsIdentity::acceptor).transpose()?;
+        *self.tls_acceptor.lock().unwrap() = acceptor;
+        Ok(())
+    }
+
+    /// Whether the listener serves TLS
+    pub fn serves_tls(&self) -> bool {
+        self.tls_acceptor.lock().unwrap().is_some()
+    }
+
+    /// Refuse (or allow again) plain HTTP to non-LAN addresses of contacts
+    /// whose token carries a certificate fingerprint
+    pub fn set_require_tls_external(&self, required: bool) {
+        self.require_tls_external.store(required, Ordering::Relaxed);
+    }
+
+    /// Start the transport layer and listen for incoming connections
+    pub async fn start(&mut self, addr: SocketAddr) -> Result<()> {
+        self.listen(addr).await.map(|_| ())
+    }
+
+    /// Bind `addr` and spawn the accept loop, returning the bound address
+    async fn listen(&self, addr: SocketAddr) -> Result<SocketAddr> {
+        info!("Starting transport on {}", addr);
+
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| Error::Transport(format!("Failed to bind to {}: {}", addr, e)))?;
+
+        // Get the actual bound address (important when using port 0 for auto-assignment)
+        let actual_addr = listener.local_addr()
+            .map_err(|e| Error::Transport(format!("Failed to get local address: {}", e)))?;
+
+        *self.local_addr.lock().unwrap() = Some(actual_addr);
+
+        // Each start answers /health with a new nonce
+        let nonce = watchdog::generate_boot_nonce();
+        *self.boot_nonce.lock().unwrap() = nonce.clone();
+
+        let connection = ConnectionContext {
+            message_handler: self.message_handler.clone(),
+            new_message_handler: self.new_message_handler.clone(),
+            ping_handler: self.ping_handler.clone(),
+            local_uid: self.local_uid.clone(),
+            invites: self.invites.clone(),
+            relay: self.relay.clone(),
+            nonce,
+        };
+        let tls_acceptor = self.tls_acceptor.clone();
+
+        // Spawn listener task
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, remote_addr)) => {
+                        debug!("Accepted connection from {}", remote_addr);
+
+                        let connection = connection.clone();
+                        let acceptor = tls_acceptor.lock().unwrap().clone();
+                        tokio::spawn(async move {
+                            // TLS and plain HTTP share the port: the first byte tells them apart
+                            match acceptor {
+                                Some(acceptor) if tls::starts_with_tls_handshake(&stream).await => {
+                                    match acceptor.accept(stream).await {
+                                        Ok(stream) => connection.serve(stream, remote_addr).await,
+                                        Err(e) => debug!("TLS handshake with {} failed: {}", remote_addr, e),
+                                    }
+                                }
+                                _ => connection.serve(stream, remote_addr).await,
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("Failed to accept connection: {}", e);
+                    }
+                }
+            }
+        });
+
+        if let Some(previous) = self.listener_task.lock().unwrap().replace(task.abort_handle()) {
+            previous.abort();
+        }
+
+        info!("Transport listening on {}", actual_addr);
+        Ok(actual_addr)
+    }
+
+    /// Stop accepting connections (connections already accepted finish normally)
+    pub fn stop(&self) {
+        if let Some(task) = self.listener_task.lock().unwrap().take() {
+            task.abort();
+            info!("Transport listener stopped");
+        }
+        *self.local_addr.lock().unwrap() = None;
+    }
+
+    /// Add or update a peer
+    pub async fn add_peer(&self, peer: Peer) -> Result<()> {
+        let mut peers = self.peers.lock().await;
+
+        // Check if peer already exists and update, otherwise add
+        if let Some(existing) = peers.iter_mut().find(|p| p.id == peer.id) {
+            *existing = peer;
+            debug!("Updated existing peer");
+        } else {
+            peers.push(peer);
+            debug!("Added new peer");
+        }
+
+        Ok(())
+    }
+
+    /// Remove a peer
+    pub async fn remove_peer(&self, peer_id: &str) -> Result<()> {
+        let mut peers = self.peers.lock().await;
+        peers.retain(|p| p.id != peer_id);
+        Ok(())
+    }
+
+    /// Get a peer by ID
+    pub async fn get_peer(&self, peer_id: &str) -> Option<Peer> {
+        let peers = self.peers.lock().await;
+        peers.iter().find(|p| p.id == peer_id).cloned()
+    }
+
+    /// Send a message to a peer via HTTP POST to their /output endpoint
+    ///
+    /// Returns the delivery state:
+    /// - Success: Message delivered immediately
+    /// - Error: Message failed to deliver (caller should queue for retry)
+    pub async fn send(&self, peer_addr: &str, envelope: &MessageEnvelope) -> Result<DeliveryState> {
+        info!("Sending message to peer at {}", peer_addr);
+
+        // Serialize the envelope to CBOR
+        let payload = envelope.to_cbor()?;
+
+        // Construct the POST request
+        let url = PeerAddress::parse(peer_addr)?.uri(DEFAULT_ADDRESS_SCHEME, "/output")?;
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(url)
+            .header("Content-Type", "application/cbor")
+            .body(Full::new(Bytes::from(payload)))
+            .map_err(|e| Error::Transport(format!("Failed to build request: {}", e)))?;
+
+        // Send the request
+        match self.client.request(req).await {
+            Ok(response) => {
+                if response.status().is_success() {
+                    info!("Message delivered successfully to {}", peer_addr);
+                    Ok(DeliveryState::Success)
+                } else {
+                    warn!(
+                        "Message delivery failed with status {}: {}",
+                        response.status(),
+                        peer_addr
+                    );
+                    Err(Error::Transport(format!(
+                        "Delivery failed with status {}",
+                        response.status()
+                    )))
+                }
+            }
+            Err(e) => {
+                error!("Failed to send message to {}: {}", peer_addr, e);
+                Err(Error::Transport(format!("Send failed: {}", e)))
+            }
+        }
+    }
+
+    /// Send a ping to a contact to test connectivity
+    ///
+    /// # Arguments
+    /// * `contact` - The contact to ping (uses the Contact struct from storage module)
+    /// * `my_contact_token` - Signed contact token to send (allows receiver to auto-import sender)
+    ///
+    /// # Returns
+    /// * `Ok(PingResponse)` - Ping successful, contains UID and status
+    /// * `Err(Error)` - Ping failed (network error, timeout, etc.)
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use pure2p::transport::Transport;
+    /// use pure2p::storage::Contact;
+    /// use chrono::{Utc, Duration};
+    ///
+    /// # async fn example() -> pure2p::Result<()> {
+    /// let transport = Transport::new();
+    /// let contact = Contact::new(
+    ///     "alice_uid".to_string(),
+    ///     "192.168.1.100:8080".to_string(),
+    ///     vec![1, 2, 3],
+    ///     vec![99u8; 32], // x25519_pubkey
+    ///     Utc::now() + Duration::days(30),
+    /// );
+    ///
+    /// // Send ping with your contact token (empty string for legacy/test usage)
+    /// match transport.send_ping(&contact, "").await {
+    ///     Ok(response) => println!("Ping OK: {} - {}", response.uid, response.status),
+    ///     Err(e) => println!("Ping failed: {}", e),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_ping(&self, contact: &crate::storage::Contact, my_contact_token: &str) -> Result<PingResponse> {
+        self.ping(contact, my_contact_token, DEFAULT_ADDRESS_SCHEME).await
+    }
+
+    /// Send a ping to the contact's address over `scheme` (plain or TLS)
+    pub(crate) async fn ping(
+        &self,
+        contact: &crate::storage::Contact,
+        my_contact_token: &str,
+        over: &str,
+    ) -> Result<PingResponse> {
+        info!("Sending ping to {} at {}", contact.uid, contact.ip);
+
+        // TLS pings go to the first advertised https:// endpoint, plain ones
+        // to the contact's address
+        let tls_host = peer::addresses_for_scheme(contact, None, over).into_iter().next().map(|(_, host)| host);
+        let (scheme, address) = match (over == TLS_SCHEME, tls_host.as_deref()) {
+            (true, Some(host)) => (TLS_SCHEME, host),
+            (true, None) => return Err(Error::Transport(format!("No {} address for contact {}", over, contact.uid))),
+            (false, _) => split_address_scheme(&contact.ip),
+        };
+        if scheme != over {
+            return Err(Error::NotSupported(format!("{} transport cannot reach '{}' addresses", over, scheme)));
+        }
+
+        // Create ping request with sender's contact token (allows receiver to auto-import)
+        let ping_request = PingRequest {
+            contact_token: my_contact_token.to_string(),
+        };
+
+        // Serialize to CBOR
+        let ping_body = serde_cbor::to_vec(&ping_request)
+            .map_err(|e| Error::CborSerialization(format!("Failed to serialize ping request: {}", e)))?;
+
+        // Send the request to /ping
+        match self.post_cbor(contact, scheme, address, "/ping", ping_body).await {
+            Ok((status, body)) => {
+                let status_code = status.as_u16() as i32;
+                if status.is_success() {
+                    // Deserialize the ping response from CBOR
+                    let ping_response: PingResponse = serde_cbor::from_slice(&body)
+                        .map_err(|e| Error::CborSerialization(format!("Failed to deserialize ping response: {}", e)))?;
+
+                    info!("Ping successful: {} - {}", ping_response.uid, ping_response.status);
+
+                    // Log successful request
+                    Self::log_request_to_db(
+                        "outgoing",
+                        "ping",
+                        Some(&contact.uid),
+                        Some(&contact.ip),
+                        Some(status_code),
+                        true,
+                        None,
+                        Some(&ping_response.status),
+                    );
+
+                    Ok(ping_response)
+                } else {
+                    let error_msg = format!("Ping failed with status {}", status);
+                    warn!("{}: {}", error_msg, contact.ip);
+
+                    // Log failed request
+                    Self::log_request_to_db(
+                        "outgoing",
+                        "ping",
+                        Some(&contact.uid),
+                        Some(&contact.ip),
+                        Some(status_code),
+                        false,
+                        Some(&error_msg),
+                        None,
+                    );
+
+                    Err(ping_status_error(status.as_u16(), error_msg))
+                }
+            }
+            Err(e) => {
+                let error_msg = format!("Ping send failed: {}", e);
+                error!("Failed to send ping to {}: {}", contact.ip, e);
+
+                // Log failed request
+                Self::log_request_to_db(
+                    "outgoing",
+                    "ping",
+                    Some(&contact.uid),
+                    Some(&contact.ip),
+                    None,
+                    false,
+                    Some(&error_msg),
+                    None,
+                );
+
+                Err(Error::Transport(error_msg))
+            }
+        }
+    }
+
+    /// POST a CBOR body to `path` at `host`, recording the exchange if the
+    /// contact is being captured (see `send_cbor`)
+    async fn post_cbor(
+        &self,
+        contact: &crate::storage::Contact,
+        scheme: &str,
+        host: &str,
+        path: &str,
+        body: Vec<u8>,
+    ) -> std::result::Result<(StatusCode, Bytes), String> {
+        if !self.capture.is_capturing(&contact.uid, chrono::Utc::now()) {
+            return self.send_cbor(contact, scheme, host, path, body).await;
+        }
+        let request = body.clone();
+        let result = self.send_cbor(contact, scheme, host, path, body).await;
+        let outcome = match &result {
+            Ok((status, response)) => Ok((status.as_u16(), response.as_ref())),
+            Err(e) => Err(e.clone()),
+        };
+        let address = format!("{}://{}", scheme, host);
+        let now = chrono::Utc::now();
+        self.capture.record(&contact.uid, now, |bodies| {
+            CapturedExchange::outgoing(&address, path, &request, &outcome, bodies, now)
+        });
+        result
+    }
+
+    /// POST a CBOR body to `path` at `host`, over plain HTTP or pinned TLS
+    ///
+    /// `host` must parse as a `PeerAddress`; the URL is built from its parts.
+    /// Plain HTTP is refused when `plain_http_allowed` says so; TLS needs
+    /// the certificate fingerprint from the contact's token.
+    ///
+    /// # Returns
+    /// The response status and body, or why no response was received
+    async fn send_cbor(
+        &self,
+        contact: &crate::storage::Contact,
+        scheme: &str,
+        host: &str,
+        path: &str,
+        body: Vec<u8>,
+    ) -> std::result::Result<(StatusCode, Bytes), String> {
+        let peer = PeerAddress::parse(host).map_err(|e| e.to_string())?;
+        if scheme == TLS_SCHEME {
+            let fingerprint = contact
+                .cert_fingerprint
+                .as_deref()
+                .ok_or_else(|| format!("no pinned certificate for {}", contact.uid))?;
+            return tls::pinned_post(&peer.authority(), fingerprint, path, body).await.map_err(|e| match e {
+                Error::Transport(reason) => reason,
+                other => other.to_string(),
+            });
+        }
+        if !plain_http_allowed(self.require_tls_external.load(Ordering::Relaxed), contact, host) {
+            return Err(format!("plain HTTP to external address {} refused (TLS required)", host));
+        }
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(peer.uri(DEFAULT_ADDRESS_SCHEME, path).map_err(|e| e.to_string())?)
+            .header("Content-Type", "application/cbor")
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|e| format!("failed to build request: {}", e))?;
+        let response = self.client.request(req).await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let body = response
+            .collect()
+            .await
+            .map_err(|e| format!("failed to read response: {}", e))?
+            .to_bytes();
+        Ok((status, body))
+    }
+
+    /// Send a message to a contact via the /message endpoint
+    ///
+    /// # Arguments
+    /// * `contact` - The contact to send the message to
+    /// * `from_uid` - The sender's UID
+    /// * `message_type` - Type of message (e.g., "text", "delete", "typing")
+    /// * `payload` - Message payload as bytes
+    ///
+    /// # Returns
+    /// * `Ok(())` - Message sent successfully
+    /// * `Err(Error)` - Failed to send message (caller should queue for retry)
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use pure2p::transport::Transport;
+    /// use pure2p::storage::Contact;
+    /// use chrono::{Utc, Duration};
+    ///
+    /// # async fn example() -> pure2p::Result<()> {
+    /// let transport = Transport::new();
+    /// let contact = Contact::new(
+    ///     "alice_uid".to_string(),
+    ///     "192.168.1.100:8080".to_string(),
+    ///     vec![1, 2, 3],
+    ///     vec![99u8; 32], // x25519_pubkey
+    ///     Utc::now() + Duration::days(30),
+    /// );
+    ///
+    /// let payload = b"Hello, Alice!".to_vec();
+    /// transport.send_message(&contact, "my_uid", "text", payload).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_message(
+        &self,
+        contact: &crate::storage::Contact,
+        from_uid: &str,
+        message_type: &str,
+        payload: Vec<u8>,
+    ) -> Result<()> {
+        self.send_message_with_metadata(contact, from_uid, message_type, payload, MessageMetadata::new())
+            .await
+    }
+
+    /// Send a message with application metadata to a contact via the /message endpoint
+    ///
+    /// Same as `send_message`, but attaches `metadata` to the request.
+    ///
+    /// Contacts advertising several endpoints are tried in advertised order,
+    /// starting with the address that last accepted a message; the first
+    /// success wins and is remembered for the next send.
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidMetadata` without sending if the metadata does not
+    /// pass `validate_metadata`
+    pub async fn send_message_with_metadata(
+        &self,
+        contact: &crate::storage::Contact,
+        from_uid: &str,
+        message_type: &str,
+        payload: Vec<u8>,
+        metadata: MessageMetadata,
+    ) -> Result<()> {
+        validate_metadata(&metadata)?;
+
+        // Create message request
+        let msg_req = MessageRequest {
+            from_uid: from_uid.to_string(),
+            message_type: message_type.to_string(),
+            payload,
+            metadata,
+            message_id: None,
+        };
+
+        self.deliver(contact, &msg_req, DEFAULT_ADDRESS_SCHEME).await
+    }
+
+    /// Deliver a message request to the contact's addresses of `scheme`
+    /// (plain HTTP or TLS)
+    ///
+    /// Addresses are tried in advertised order, starting with the one that
+    /// last worked; addresses of other schemes are left to their transports.
+    pub(crate) async fn deliver(
+        &self,
+        contact: &crate::storage::Contact,
+        msg_req: &MessageRequest,
+        scheme: &str,
+    ) -> Result<()> {
+        // Serialize to CBOR
+        let cbor_data = serde_cbor::to_vec(msg_req)
+            .map_err(|e| Error::CborSerialization(format!("Failed to serialize message request: {}", e)))?;
+
+        let preferred = self.learned_endpoint(&contact.uid).await;
+        let addresses = peer::addresses_for_scheme(contact, preferred.as_deref(), scheme);
+
+        let mut last_error = None;
+        for (address, host) in &addresses {
+            match self.post_message(contact, scheme, host, &msg_req.message_type, cbor_data.clone()).await {
+                Ok(()) => {
+                    if addresses.len() > 1 {
+                        self.learned_endpoints.insert(contact.uid.clone(), address.clone());
+                    }
+                    return Ok(());
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| Error::Transport(format!("No {} address for contact {}", scheme, contact.uid))))
+    }
+
+    /// Redeem an invite code at a peer's `POST /invite` endpoint
+    ///
+    /// # Arguments
+    /// * `address` - Peer address (`host:port`)
+    /// * `redemption` - Code, plus a key proof for key-bound invites
+    ///
+    /// # Returns
+    /// The inviting peer's contact token
+    ///
+    /// # Errors
+    /// `Error::Invite` if the peer refused the code, or a transport error
+    pub async fn redeem_invite(&self, address: &str, redemption: &InviteRedemption) -> Result<String> {
+        let body = serde_cbor::to_vec(redemption)
+            .map_err(|e| Error::CborSerialization(format!("Failed to serialize invite redemption: {}", e)))?;
+
+        let url = PeerAddress::parse(address)?.uri(DEFAULT_ADDRESS_SCHEME, INVITE_PATH)?;
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(url)
+            .header("Content-Type", "application/cbor")
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|e| Error::Transport(format!("Failed to build invite request: {}", e)))?;
+
+        let response = self.client.request(req).await
+            .map_err(|e| Error::Transport(format!("Invite request failed: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Invite(format!("Invite rejected with status {}", status)));
+        }
+
+        let body = response.collect().await
+            .map_err(|e| Error::Transport(format!("Failed to read invite response: {}", e)))?
+            .to_bytes();
+        let invite_response: InviteResponse = serde_cbor::from_slice(&body)
+            .map_err(|e| Error::CborSerialization(format!("Failed to deserialize invite response: {}", e)))?;
+
+        Ok(invite_response.contact_token)
+    }
+
+    /// POST a relay envelope to the relay contact's addresses of `scheme`
+    pub(crate) async fn post_relay(
+        &self,
+        relay: &crate::storage::Contact,
+        envelope: &RelayEnvelope,
+        scheme: &str,
+    ) -> Result<()> {
+        let body = serde_cbor::to_vec(envelope)
+            .map_err(|e| Error::CborSerialization(format!("Failed to serialize relay envelope: {}", e)))?;
+
+        let mut last_error = None;
+        for (_, host) in peer::addresses_for_scheme(relay, None, scheme) {
+            match self.post_cbor(relay, scheme, &host, RELAY_PATH, body.clone()).await {
+                Ok((status, _)) if status.is_success() => {
+                    info!("Relay {} accepted message for {}", relay.uid, envelope.to_uid);
+                    return Ok(());
+                }
+                Ok((status, _)) => {
+                    last_error = Some(Error::Relay(format!("Relay refused with status {}", status)));
+                }
+                Err(e) => last_error = Some(Error::Transport(format!("Relay request failed: {}", e))),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| Error::Transport(format!("No {} address for relay {}", scheme, relay.uid))))
+    }
+
+    /// POST a capability probe to the contact's addresses of `scheme`
+    ///
+    /// A 404 means the peer predates the endpoint and maps to `Ok(None)`.
+    pub(crate) async fn post_capability_probe(
+        &self,
+        contact: &crate::storage::Contact,
+        from_uid: &str,
+        scheme: &str,
+    ) -> Result<Option<RelayCapabilities>> {
+        let probe = CapabilityProbe { from_uid: from_uid.to_string() };
+        let body = serde_cbor::to_vec(&probe)
+            .map_err(|e| Error::CborSerialization(format!("Failed to serialize capability probe: {}", e)))?;
+
+        let preferred = self.learned_endpoint(&contact.uid).await;
+        let mut last_error = None;
+        for (_, host) in peer::addresses_for_scheme(contact, preferred.as_deref(), scheme) {
+            match self.post_cbor(contact, scheme, &host, CAPABILITIES_PATH, body.clone()).await {
+                Ok((status, body)) if status.is_success() => {
+                    let capabilities = serde_cbor::from_slice::<RelayCapabilities>(&body).map_err(|e| {
+                        Error::CborSerialization(format!("Failed to deserialize capabilities: {}", e))
+                    })?;
+                    return Ok(Some(capabilities));
+                }
+                Ok((StatusCode::NOT_FOUND, _)) => {
+                    debug!("{} has no capabilities endpoint", contact.uid);
+                    return Ok(None);
+                }
+                Ok((status, _)) => {
+                    last_error = Some(Error::Transport(format!("Capability probe refused with status {}", status)));
+                }
+                Err(e) => last_error = Some(Error::Transport(format!("Capability probe failed: {}", e))),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| Error::Transport(format!("No {} address for {}", scheme, contact.uid))))
+    }
+
+    /// Get the address that last accepted a message for a contact
+    ///
+    /// Only recorded for contacts advertising more than one endpoint, and
+    /// only for the `MAX_LEARNED_CONTACTS` most recently used ones.
+    pub async fn learned_endpoint(&self, contact_uid: &str) -> Option<String> {
+        self.learned_endpoints.get(&contact_uid.to_string())
+    }
+
+    /// POST an encoded message request to one address of a contact
+    async fn post_message(
+        &self,
+        contact: &crate::storage::Contact,
+        scheme: &str,
+        address: &str,
+        message_type: &str,
+        cbor_data: Vec<u8>,
+    ) -> Result<()> {
+        info!("Sending {} message to {} at {}", message_type, contact.uid, address);
+
+        // POST to /message
+        match self.post_cbor(contact, scheme, address, "/message", cbor_data).await {
+            Ok((status, _)) => {
+                let status_code = status.as_u16() as i32;
+                if status.is_success() {
+                    info!("Message sent successfully to {}", address);
+
+                    // Log successful request
+                    Self::log_request_to_db(
+                        "outgoing",
+                        message_type,
+                        Some(&contact.uid),
+                        Some(address),
+                        Some(status_code),
+                        true,
+                        None,
+                        Some("Message delivered"),
+                    );
+
+                    Ok(())
+                } else {
+                    let error_msg = format!("Message send failed with status {}", status);
+                    warn!("{}: {}", error_msg, address);
+
+                    // Log failed request
+                    Self::log_request_to_db(
+                        "outgoing",
+                        message_type,
+                        Some(&contact.uid),
+                        Some(address),
+                        Some(status_code),
+                        false,
+                        Some(&error_msg),
+                        None,
+                    );
+
+                    Err(Error::Transport(error_msg))
+                }
+            }
+            Err(e) => {
+                let error_msg = format!("Message send failed: {}", e);
+                error!("Failed to send message to {}: {}", address, e);
+
+                // Log failed request
+                Self::log_request_to_db(
+                    "outgoing",
+                    message_type,
+                    Some(&contact.uid),
+                    Some(address),
+                    None,
+                    false,
+                    Some(&error_msg),
+                    None,
+                );
+
+                Err(Error::Transport(error_msg))
+            }
+        }
+    }
+
+    /// Get list of known peers
+    pub async fn peers(&self) -> Vec<Peer> {
+        let peers = self.peers.lock().await;
+        peers.clone()
+    }
+
+    /// Get the local listening address
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        *self.local_addr.lock().unwrap()
+    }
+
+    /// Helper method to log requests to database
+    /// This is a best-effort operation - errors are logged but don't affect the request
+    fn log_request_to_db(
+        direction: &str,
+        request_type: &str,
+        target_uid: Option<&str>,
+        target_ip: Option<&str>,
+        status_code: Option<i32>,
+        success: bool,
+        error_message: Option<&str>,
+        response_data: Option<&str>,
+    ) {
+        // Create storage connection (file-based production DB)
+        let storage = match crate::storage::Storage::new_with_default_path() {
+            Ok(s) => s,
+            Err(e) => {
+                debug!("Failed to create storage for request logging: {}", e);
+                return;
+            }
+        };
+
+        // Log the request
+        if let Err(e) = storage.log_request(
+            direction,
+            request_type,
+            target_uid,
+            target_ip,
+            status_code,
+            success,
+            error_message,
+            response_data,
+        ) {
+            debug!("Failed to log request to database: {}", e);
+        }
+    }
+}
+
+/// What each accepted connection needs to answer requests
+#[derive(Clone)]
+struct ConnectionContext {
+    message_handler: Arc<Mutex<Option<MessageHandler>>>,
+    new_message_handler: Arc<Mutex<Option<NewMessageHandler>>>,
+    ping_handler: Arc<Mutex<Option<PingHandler>>>,
+    local_uid: Arc<Mutex<Option<String>>>,
+    invites: Arc<std::sync::Mutex<InviteBook>>,
+    relay: Arc<std::sync::Mutex<Relay>>,
+    nonce: String,
+}
+
+impl ConnectionContext {
+    /// Serve HTTP/1 requests on an accepted (plain or TLS) connection
+    async fn serve<I>(self, io: I, remote_addr: SocketAddr)
+    where
+        I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let service = service_fn(move |req: Request<Incoming>| {
+            let context = self.clone();
+            async move {
+                let handler = context.message_handler;
+                let new_handler = context.new_message_handler;
+                let ping_h = context.ping_handler;
+                let uid = context.local_uid;
+                // Invites and auto-import caps are keyed on the socket address, which a client cannot spoof
+                if req.method() == Method::POST && req.uri().path() == INVITE_PATH {
+                    handle_invite_request(req, context.invites, remote_addr.ip()).await
+                } else if req.method() == Method::POST && req.uri().path() == RELAY_PATH {
+                    handle_relay_request(req, context.relay).await
+                } else if req.method() == Method::POST && req.uri().path() == CAPABILITIES_PATH {
+                    handle_capabilities_request(req, context.relay).await
+                } else if req.method() == Method::GET && req.uri().path() == "/health" {
+                    // Our own watchdog tells us from another process by the nonce
+                    let mut response = handle_request(req, handler, new_handler, ping_h, uid, remote_addr.ip()).await;
+                    if let Ok(response) = response.as_mut() {
+                        response.headers_mut().insert(
+                            BOOT_NONCE_HEADER,
+                            hyper::header::HeaderValue::from_str(&context.nonce).expect("hex nonce"),
+                        );
+                    }
+                    response
+                } else {
+                    handle_request(req, handler, new_handler, ping_h, uid, remote_addr.ip()).await
+                }
+            }
+        });
+
+        if let Err(e) = http1::Builder::new()
+            .serve_connection(TokioIo::new(io), service)
+            .await
+        {
+            error!("Error serving connection: {}", e);
+        }
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PeerTransport for Transport {
+    fn scheme(&self) -> &str {
+        DEFAULT_ADDRESS_SCHEME
+    }
+
+    fn capabilities(&self) -> TransportCapabilities {
+        TransportCapabilities {
+            can_send: true,
+            can_listen: true,
+            anonymous: false,
+        }
+    }
+
+    fn send_message<'a>(
+        &'a self,
+        contact: &'a crate::storage::Contact,
+        request: &'a MessageRequest,
+    ) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            validate_metadata(&request.metadata)?;
+            self.deliver(contact, request, DEFAULT_ADDRESS_SCHEME).await
+        })
+    }
+
+    fn send_ping<'a>(
+        &'a self,
+        contact: &'a crate::storage::Contact,
+        my_contact_token: &'a str,
+    ) -> TransportFuture<'a, PingResponse> {
+        Box::pin(Transport::send_ping(self, contact, my_contact_token))
+    }
+
+    fn relay_message<'a>(
+        &'a self,
+        relay: &'a crate::storage::Contact,
+        envelope: &'a RelayEnvelope,
+    ) -> TransportFuture<'a, ()> {
+        Box::pin(self.post_relay(relay, envelope, DEFAULT_ADDRESS_SCHEME))
+    }
+
+    fn probe_capabilities<'a>(
+        &'a self,
+        contact: &'a crate::storage::Contact,
+        from_uid: &'a str,
+    ) -> TransportFuture<'a, Option<RelayCapabilities>> {
+        Box::pin(self.post_capability_probe(contact, from_uid, DEFAULT_ADDRESS_SCHEME))
+    }
+
+    fn start_listener<'a>(&'a self, address: &'a str) -> TransportFuture<'a, String> {
+        Box::pin(async move {
+            let addr: SocketAddr = address
+                .parse()
+                .map_err(|e| Error::Transport(format!("Invalid listen address {}: {}", address, e)))?;
+            Ok(self.listen(addr).await?.to_string())
+        })
+    }
+
+    fn stop_listener(&self) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            self.stop();
+            Ok(())
+        })
+    }
+}
+
+/// Helper function to log incoming requests to database
+fn log_incoming_request(
+    request_type: &str,
+    sender_uid: Option<&str>,
+    sender_ip: Option<&str>,
+    status_code: i32,
+    success: bool,
+    error_message: Option<&str>,
+) {
+    // Create storage connection (file-based production DB)
+    let storage = match crate::storage::Storage::new_with_default_path() {
+        Ok(s) => s,
+        Err(e) => {
+            debug!("Failed to create storage for request logging: {}", e);
+            return;
+        }
+    };
+
+    // Log the request
+    if let Err(e) = storage.log_request(
+        "incoming",
+        request_type,
+        sender_uid,
+        sender_ip,
+        Some(status_code),
+        success,
+        error_message,
+        None,
+    ) {
+        debug!("Failed to log incoming request to database: {}", e);
+    }
+}
+
+/// Handle incoming HTTP requests
+///
+/// `source` is the socket address of the connection, handed to the ping and
+/// message handlers.
+pub(crate) async fn handle_request(
+    req: Request<Incoming>,
+    message_handler: Arc<Mutex<Option<MessageHandler>>>,
+    new_message_handler: Arc<Mutex<Option<NewMessageHandler>>>,
+    ping_handler: Arc<Mutex<Option<PingHandler>>>,
+    local_uid: Arc<Mutex<Option<String>>>,
+    source: IpAddr,
+) -> std::result::Result<Response<Full<Bytes>>, hyper::Error> {
+    // Extract peer address from request headers if available
+    let peer_addr = req.headers()
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+
+    match (req.method(), req.uri().path()) {
+        (&Method::POST, "/output") => {
+            debug!("Received POST /output request");
+
+            // Read the body
+            let body = req.collect().await?.to_bytes();
+
+            // Deserialize the message envelope from CBOR
+            match MessageEnvelope::from_cbor(&body) {
+                Ok(envelope) => {
+                    info!(
+                        "Received message from {} to {}",
+                        envelope.from_uid, envelope.to_uid
+                    );
+
+                    // Call the message handler if set
+                    let handler_guard = message_handler.lock().await;
+                    if let Some(handler) = handler_guard.as_ref() {
+                        handler(envelope);
+                    } else {
+                        warn!("No message handler set, message dropped");
+                    }
+
+                    Ok(Response::builder()
+                        .status(StatusCode::OK)
+                        .body(Full::new(Bytes::from("Message received")))
+                        .unwrap())
+                }
+                Err(e) => {
+                    error!("Failed to deserialize message: {}", e);
+                    Ok(Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .body(Full::new(Bytes::from(format!(
+                            "Invalid message format: {}",
+                            e
+                        ))))
+                        .unwrap())
+                }
+            }
+        }
+        (&Method::POST, "/ping") => {
+            debug!("Received POST /ping request");
+
+            // Read the body to get sender's UID
+            let body = req.collect().await?.to_bytes();
+
+            // Deserialize the ping request from CBOR
+            match serde_cbor::from_slice::<PingRequest>(&body) {
+                Ok(ping_req) => {
+                    info!("Received ping with contact token");
+
+                    // Parse the contact token to get sender UID (for logging)
+                    let sender_uid = crate::storage::parse_contact_token(&ping_req.contact_token)
+                        .ok()
+                        .map(|contact| contact.uid);
+
+                    // Call the ping handler if set (to auto-import sender and create chat);
+                    // a ping without a token has nothing to import
+                    let handler_guard = ping_handler.lock().await;
+                    let handled = match handler_guard.as_ref() {
+                        _ if ping_req.contact_token.is_empty() => Ok(()),
+                        Some(handler) => handler(ping_req.contact_token.clone(), Some(source)),
+                        None => {
+                            warn!("No ping handler set");
+                            Ok(())
+                        }
+                    };
+                    drop(handler_guard);
+
+                    match handled {
+                        Err(Error::RateLimited(reason)) => {
+                            warn!("Ping refused: {}", reason);
+                            log_incoming_request("ping", sender_uid.as_deref(), peer_addr.as_deref(), 429, false, Some(&reason));
+                            return Ok(Response::builder()
+                                .status(StatusCode::TOO_MANY_REQUESTS)
+                                .body(Full::new(Bytes::from("Too many new contacts, retry later")))
+                                .unwrap());
+                        }
+                        Err(Error::TokenTooShortLived(reason)) => {
+                            warn!("Ping refused: {}", reason);
+                            log_incoming_request(
+                                "ping",
+                                sender_uid.as_deref(),
+                                peer_addr.as_deref(),
+                                TOKEN_TOO_SHORT_LIVED_STATUS as i32,
+                                false,
+                                Some(&reason),
+                            );
+                            return Ok(Response::builder()
+                                .status(TOKEN_TOO_SHORT_LIVED_STATUS)
+                                .body(Full::new(Bytes::from("Token expires too soon, send a fresh one")))
+                                .unwrap());
+                        }
+                        Err(e) => error!("Ping handler failed: {}", e),
+                        Ok(()) => {}
+                    }
+
+                    // Get local UID for response
+                    let uid_guard = local_uid.lock().await;
+                    let uid = uid_guard.as_ref().map(|s| s.clone()).unwrap_or_else(|| "unknown".to_string());
+                    drop(uid_guard);
+
+                    // Create ping response
+                    let response = PingResponse {
+                        uid,
+                        status: "ok".to_string(),
+                    };
+
+                    // Serialize to CBOR
+                    match serde_cbor::to_vec(&response) {
+                        Ok(cbor_data) => {
+                            info!("Responding to ping");
+
+                            // Log successful incoming ping
+                            log_incoming_request(
+                                "ping",
+                                sender_uid.as_deref(),
+                                peer_addr.as_deref(),
+                                200,
+                                true,
+                                None,
+                            );
+
+                            Ok(Response::builder()
+                                .status(StatusCode::OK)
+                                .header("Content-Type", "application/cbor")
+                                .body(Full::new(Bytes::from(cbor_data)))
+                                .unwrap())
+                        }
+                        Err(e) => {
+                            let error_msg = format!("Failed to serialize response: {}", e);
+                            error!("{}", error_msg);
+
+                            // Log failed ping response
+                            log_incoming_request(
+                                "ping",
+                                sender_uid.as_deref(),
+                                peer_addr.as_deref(),
+                                500,
+                                false,
+                                Some(&error_msg),
+                            );
+
+                            Ok(Response::builder()
+                                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                                .body(Full::new(Bytes::from(error_msg)))
+                                .unwrap())
+                        }
+                    }
+                }
+                Err(e) => {
+                    let error_msg = format!("Invalid ping format: {}", e);
+                    error!("{}", error_msg);
+
+                    // Log failed ping (invalid format)
+                    log_incoming_request(
+                        "ping",
+                        None,
+                        peer_addr.as_deref(),
+                        400,
+                        false,
+                        Some(&error_msg),
+                    );
+
+                    Ok(Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .body(Full::new(Bytes::from(error_msg)))
+                        .unwrap())
+                }
+            }
+        }
+        (&Method::POST, "/message") => {
+            debug!("Received POST /message request");
+
+            // Read the body
+            let body = req.collect().await?.to_bytes();
+
+            // Deserialize the message request from CBOR
+            match serde_cbor::from_slice::<MessageRequest>(&body) {
+                Ok(msg_req) if validate_metadata(&msg_req.metadata).is_err() => {
+                    warn!("Rejected message from {} with invalid metadata", msg_req.from_uid);
+                    Ok(Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .body(Full::new(Bytes::from("Invalid message metadata")))
+                        .unwrap())
+                }
+                Ok(msg_req) => {
+                    info!(
+                        "Received message from {} (type: {})",
+                        msg_req.from_uid, msg_req.message_type
+                    );
+
+                    // Call the new message handler if set
+                    let handler_guard = new_message_handler.lock().await;
+                    let handled = match handler_guard.as_ref() {
+                        Some(handler) => handler(msg_req, Some(source)),
+                        None => {
+                            warn!("No message handler set for /message endpoint, message dropped");
+                            Ok(())
+                        }
+                    };
+
+                    match handled {
+                        Ok(()) => Ok(Response::builder()
+                            .status(StatusCode::OK)
+                            .body(Full::new(Bytes::from("Message received")))
+                            .unwrap()),
+                        Err(Error::RateLimited(reason)) => {
+                            warn!("Message refused: {}", reason);
+                            Ok(Response::builder()
+                                .status(StatusCode::TOO_MANY_REQUESTS)
+                                .body(Full::new(Bytes::from("Too many new chats, retry later")))
+                                .unwrap())
+                        }
+                        Err(e) => {
+                            // Not stored: don't ack, so the sender retries later
+                            warn!("Message handler failed, asking sender to retry: {}", e);
+                            Ok(Response::builder()
+                                .status(StatusCode::SERVICE_UNAVAILABLE)
+                                .body(Full::new(Bytes::from("Message not stored, retry later")))
+                                .unwrap())
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to deserialize message request: {}", e);
+                    Ok(Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .body(Full::new(Bytes::from(format!(
+                            "Invalid message format: {}",
+                            e
+                        ))))
+                        .unwrap())
+                }
+            }
+        }
+        (&Method::GET, "/health") => {
+            debug!("Received GET /health request");
+
+            // Simple health check endpoint for external connectivity verification
+            Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "text/plain")
+                .body(Full::new(Bytes::from("ok")))
+                .unwrap())
+        }
+        _ => {
+            debug!("Received unsupported request: {} {}", req.method(), req.uri().path());
+            Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Full::new(Bytes::from("Not Found")))
+                .unwrap())
+        }
+    }
+}
+
+/// Handle `POST /invite`: exchange an invite code for our contact token
+pub(crate) async fn handle_invite_request(
+    req: Request<Incoming>,
+    invites: Arc<std::sync::Mutex<InviteBook>>,
+    source: IpAddr,
+) -> std::result::Result<Response<Full<Bytes>>, hyper::Error> {
+    debug!("Received POST {} request from {}", INVITE_PATH, source);
+
+    let body = req.collect().await?.to_bytes();
+    let redemption = match serde_cbor::from_slice::<InviteRedemption>(&body) {
+        Ok(redemption) => redemption,
+        Err(e) => {
+            error!("Failed to deserialize invite redemption: {}", e);
+            return Ok(Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Full::new(Bytes::from(format!("Invalid invite format: {}", e))))
+                .unwrap());
+        }
+    };
+
+    let result = invites.lock().unwrap().redeem(source, &redemption, chrono::Utc::now());
+    match result {
+        Ok(contact_token) => {
+            info!("Invite redeemed from {}", source);
+            log_incoming_request("invite", None, Some(&source.to_string()), 200, true, None);
+
+            match serde_cbor::to_vec(&InviteResponse { contact_token }) {
+                Ok(response_body) => Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header("Content-Type", "application/cbor")
+                    .body(Full::new(Bytes::from(response_body)))
+                    .unwrap()),
+                Err(e) => {
+                    error!("Failed to serialize invite response: {}", e);
+                    Ok(Response::builder()
+                        .status(StatusCode::INTERNAL_SERVER_ERROR)
+                        .body(Full::new(Bytes::from("Internal server error")))
+                        .unwrap())
+                }
+            }
+        }
+        Err(rejection) => {
+            if rejection.is_audited() {
+                match crate::storage::Storage::new_with_default_path() {
+                    Ok(storage) => {
+                        if let Err(e) = audit_failed_redemption(&storage, source, &rejection) {
+                            debug!("Failed to audit invite redemption: {}", e);
+                        }
+                    }
+                    Err(e) => debug!("Failed to create storage for invite audit: {}", e),
+                }
+            }
+
+            // The body does not say why, so a guesser learns nothing about which codes exist
+            let status = StatusCode::from_u16(rejection.status_code()).unwrap_or(StatusCode::FORBIDDEN);
+            Ok(Response::builder()
+                .status(status)
+                .body(Full::new(Bytes::from(if status == StatusCode::TOO_MANY_REQUESTS {
+                    "Too many attempts"
+                } else {
+                    "Invite rejected"
+                })))
+                .unwrap())
+        }
+    }
+}
+
+/// Log delivery state
+pub fn log_delivery_state(peer_addr: &str, state: &DeliveryState) {
+    match state {
+        DeliveryState::Success => {
+            info!("✓ Message delivered successfully to {}", peer_addr);
+        }
+        DeliveryState::Queued => {
+            info!("⊙ Message queued for delivery to {}", peer_addr);
+        }
+        DeliveryState::Retry { attempt, next_retry_ms } => {
+            warn!(
+                "⟲ Message delivery failed to {} (attempt {}), will retry at timestamp {}",
+                peer_addr, attempt, next_retry_ms
+            );
+        }
+        DeliveryState::Failed => {
+            error!("✗ Message delivery failed permanently to {}", peer_addr);
+        }
+    }
+}
+
+/// Handle `POST /relay`: queue an envelope for another contact
+pub(crate) async fn handle_relay_request(
+    req: Request<Incoming>,
+    relay: Arc<std::sync::Mutex<Relay>>,
+) -> std::result::Result<Response<Full<Bytes>>, hyper::Error> {
+    debug!("Received POST {} request", RELAY_PATH);
+
+    let body = req.collect().await?.to_bytes();
+    let envelope = match serde_cbor::from_slice::<RelayEnvelope>(&body) {
+        Ok(envelope) => envelope,
+        Err(e) => {
+            error!("Failed to deserialize relay envelope: {}", e);
+            return Ok(Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Full::new(Bytes::from(format!("Invalid relay envelope: {}", e))))
+                .unwrap());
+        }
+    };
+
+    let (from_uid, to_uid) = (envelope.from_uid.clone(), envelope.to_uid.clone());
+    let result = relay.lock().unwrap().accept(envelope, chrono::Utc::now());
+    match result {
+        Ok(()) => {
+            info!("Queued relayed message from {} to {}", from_uid, to_uid);
+            log_incoming_request("relay", Some(&from_uid), None, 202, true, None);
+            Ok(Response::builder()
+                .status(StatusCode::ACCEPTED)
+                .body(Full::new(Bytes::from("Queued for relay")))
+                .unwrap())
+        }
+        Err(refusal) => {
+            warn!("Refused relayed message from {} to {}: {}", from_uid, to_uid, refusal);
+            let status = StatusCode::from_u16(refusal.status_code()).unwrap_or(StatusCode::FORBIDDEN);
+            log_incoming_request("relay", Some(&from_uid), None, status.as_u16() as i32, false, Some(&refusal.to_string()));
+            Ok(Response::builder()
+                .status(status)
+                .body(Full::new(Bytes::from(refusal.to_string())))
+                .unwrap())
+        }
+    }
+}
+
+/// Handle `POST /capabilities`: answer a contact's capability probe
+///
+/// Only current contacts get an answer; anyone else is refused with 403.
+pub(crate) async fn handle_capabilities_request(
+    req: Request<Incoming>,
+    relay: Arc<std::sync::Mutex<Relay>>,
+) -> std::result::Result<Response<Full<Bytes>>, hyper::Error> {
+    debug!("Received POST {} request", CAPABILITIES_PATH);
+
+    let body = req.collect().await?.to_bytes();
+    let probe = match serde_cbor::from_slice::<CapabilityProbe>(&body) {
+        Ok(probe) => probe,
+        Err(e) => {
+            error!("Failed to deserialize capability probe: {}", e);
+            return Ok(Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Full::new(Bytes::from(format!("Invalid capability probe: {}", e))))
+                .unwrap());
+        }
+    };
+
+    let answer = relay.lock().unwrap().capabilities_for(&probe.from_uid);
+    let Some(capabilities) = answer else {
+        warn!("Refused capability probe from unknown {}", probe.from_uid);
+        log_incoming_request("capabilities", Some(&probe.from_uid), None, 403, false, Some("Not a contact"));
+        return Ok(Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .body(Full::new(Bytes::from("Not a contact")))
+            .unwrap());
+    };
+
+    match serde_cbor::to_vec(&capabilities) {
+        Ok(payload) => {
+            log_incoming_request("capabilities", Some(&probe.from_uid), None, 200, true, None);
+            Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "application/cbor")
+                .body(Full::new(Bytes::from(payload)))
+                .unwrap())
+        }
+        Err(e) => {
+            error!("Failed to serialize capabilities: {}", e);
+            Ok(Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Full::new(Bytes::from("Failed to serialize capabilities")))
+                .unwrap())
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Onion-service transport (skeleton)
+//!
+//! Reserves the `onion` scheme so contacts can advertise `onion://` endpoints.
+//! No Tor client is bundled yet: every operation fails with
+//! `Error::NotSupported`, and the registry falls through to the contact's
+//! other addresses.
+
+use super::{MessageRequest, PeerTransport, PingResponse, TransportCapabilities, TransportFuture};
+use crate::{storage::Contact, Error};
+
+/// Address scheme of the onion transport
+pub const ONION_SCHEME: &str = "onion";
+
+/// Placeholder transport for Tor onion services
+#[derive(Debug, Clone, Default)]
+pub struct OnionTransport;
+
+impl OnionTransport {
+    /// Create the placeholder transport
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn not_supported<T>(operation: &str) -> crate::Result<T> {
+        Err(Error::NotSupported(format!("Onion transport cannot {} yet", operation)))
+    }
+}
+
+impl PeerTransport for OnionTransport {
+    fn scheme(&self) -> &str {
+        ONION_SCHEME
+    }
+
+    fn capabilities(&self) -> TransportCapabilities {
+        TransportCapabilities {
+            can_send: false,
+            can_listen: false,
+            anonymous: true,
+        }
+    }
+
+    fn send_message<'a>(
+        &'a self,
+        _contact: &'a Contact,
+        _request: &'a MessageRequest,
+    ) -> TransportFuture<'a, ()> {
+        Box::pin(async { Self::not_supported("send messages") })
+    }
+
+    fn send_ping<'a>(
+        &'a self,
+        _contact: &'a Contact,
+        _my_contact_token: &'a str,
+    ) -> TransportFuture<'a, PingResponse> {
+        Box::pin(async { Self::not_supported("send pings") })
+    }
+
+    fn start_listener<'a>(&'a self, _address: &'a str) -> TransportFuture<'a, String> {
+        Box::pin(async { Self::not_supported("host an onion service") })
+    }
+
+    fn stop_listener(&self) -> TransportFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+}
@@ -0,0 +1,348 @@
+//! Pluggable peer transports
+//!
+//! `PeerTransport` is the seam between messaging logic and the carrier that
+//! moves bytes between peers. The HTTP `Transport` is the default carrier;
+//! others (in-process loopback, onion services) plug in beside it.
+//!
+//! Addresses carry a scheme tag (`loopback://alice`); plain `host:port`
+//! addresses use HTTP. `TransportRegistry` picks the carrier for each of a
+//! contact's addresses by that scheme.
+
+use super::{MessageRequest, PingResponse};
+use crate::{
+    cache::BoundedCache,
+    relay::{RelayCapabilities, RelayEnvelope},
+    storage::{split_address_scheme, Contact},
+    Error, Result,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Contacts whose working address or scheme is remembered; the least
+/// recently used is forgotten past that and tried in the usual order again
+pub const MAX_LEARNED_CONTACTS: usize = 1024;
+
+/// Boxed future returned by `PeerTransport` methods
+pub type TransportFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// What a transport can do
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransportCapabilities {
+    /// Can deliver messages and pings to peers
+    pub can_send: bool,
+    /// Can accept incoming messages and pings
+    pub can_listen: bool,
+    /// Hides the network location of both peers
+    pub anonymous: bool,
+}
+
+/// A carrier that delivers messages and pings between peers
+///
+/// Implementations only handle addresses tagged with their own `scheme()`;
+/// the scheme prefix is stripped before the carrier sees an address.
+pub trait PeerTransport: Send + Sync {
+    /// Address scheme this transport handles (e.g., "http", "loopback")
+    fn scheme(&self) -> &str;
+
+    /// Report what this transport can do
+    fn capabilities(&self) -> TransportCapabilities;
+
+    /// Deliver a message request to a contact
+    ///
+    /// Tries the contact's addresses with this transport's scheme; succeeds
+    /// as soon as one accepts the message.
+    fn send_message<'a>(
+        &'a self,
+        contact: &'a Contact,
+        request: &'a MessageRequest,
+    ) -> TransportFuture<'a, ()>;
+
+    /// Ping a contact, sending our contact token so the peer can import us
+    fn send_ping<'a>(
+        &'a self,
+        contact: &'a Contact,
+        my_contact_token: &'a str,
+    ) -> TransportFuture<'a, PingResponse>;
+
+    /// Hand an envelope for another contact to the relay contact `relay`
+    ///
+    /// Transports without a relay endpoint keep the default, which refuses.
+    fn relay_message<'a>(
+        &'a self,
+        relay: &'a Contact,
+        _envelope: &'a RelayEnvelope,
+    ) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            Err(Error::NotSupported(format!("{} transport cannot reach relay {}", self.scheme(), relay.uid)))
+        })
+    }
+
+    /// Ask `contact` for its capabilities, as the contact `from_uid`
+    ///
+    /// Only called right after a delivery to the contact succeeded (see
+    /// `storage::capability_probe`). Transports without a capabilities
+    /// endpoint keep the default, which refuses.
+    ///
+    /// # Returns
+    /// The capabilities, or None if the peer does not answer probes (legacy)
+    fn probe_capabilities<'a>(
+        &'a self,
+        contact: &'a Contact,
+        _from_uid: &'a str,
+    ) -> TransportFuture<'a, Option<RelayCapabilities>> {
+        Box::pin(async move {
+            Err(Error::NotSupported(format!("{} transport cannot probe {}", self.scheme(), contact.uid)))
+        })
+    }
+
+    /// Start accepting incoming messages and pings on `address`
+    ///
+    /// # Returns
+    /// The address actually bound (e.g. with the assigned port)
+    fn start_listener<'a>(&'a self, address: &'a str) -> TransportFuture<'a, String>;
+
+    /// Stop accepting incoming messages and pings
+    fn stop_listener(&self) -> TransportFuture<'_, ()>;
+}
+
+/// Addresses of `contact` handled by `scheme`, in delivery order
+///
+/// Each entry is the full address (as advertised) and the address with the
+/// scheme stripped (as the transport dials it).
+pub(crate) fn addresses_for_scheme(
+    contact: &Contact,
+    preferred: Option<&str>,
+    scheme: &str,
+) -> Vec<(String, String)> {
+    contact
+        .delivery_addresses(preferred)
+        .into_iter()
+        .filter_map(|address| {
+            let (s, rest) = split_address_scheme(&address);
+            (s == scheme).then(|| (address.clone(), rest.to_string()))
+        })
+        .collect()
+}
+
+/// Transports keyed by address scheme, dispatching each contact to the right carrier
+///
+/// The registry is itself a `PeerTransport`, so messaging code and the retry
+/// worker use it without knowing which carriers exist.
+#[derive(Clone)]
+pub struct TransportRegistry {
+    /// Registered transports by scheme
+    transports: HashMap<String, Arc<dyn PeerTransport>>,
+    /// Schemes in registration order (listener stop order, capability report)
+    order: Vec<String>,
+    /// Scheme that last accepted a message, by contact UID
+    learned_schemes: Arc<BoundedCache<String, String>>,
+}
+
+impl Default for TransportRegistry {
+    fn default() -> Self {
+        Self {
+            transports: HashMap::new(),
+            order: Vec::new(),
+            learned_schemes: Arc::new(BoundedCache::new("learned schemes", MAX_LEARNED_CONTACTS, None)),
+        }
+    }
+}
+
+impl TransportRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a transport for its scheme, replacing any previous one
+    pub fn register(&mut self, transport: Arc<dyn PeerTransport>) {
+        let scheme = transport.scheme().to_string();
+        if !self.order.contains(&scheme) {
+            self.order.push(scheme.clone());
+        }
+        self.transports.insert(scheme, transport);
+    }
+
+    /// Builder-style `register`
+    pub fn with(mut self, transport: Arc<dyn PeerTransport>) -> Self {
+        self.register(transport);
+        self
+    }
+
+    /// Get the transport handling `scheme`
+    pub fn get(&self, scheme: &str) -> Option<Arc<dyn PeerTransport>> {
+        self.transports.get(scheme).cloned()
+    }
+
+    /// Registered schemes in registration order
+    pub fn schemes(&self) -> Vec<String> {
+        self.order.clone()
+    }
+
+    /// Get the transport for an address by its scheme tag
+    ///
+    /// # Errors
+    /// `Error::NotSupported` if no transport handles the address's scheme
+    pub fn for_address(&self, address: &str) -> Result<Arc<dyn PeerTransport>> {
+        let (scheme, _) = split_address_scheme(address);
+        self.get(scheme)
+            .ok_or_else(|| Error::NotSupported(format!("No transport for scheme '{}'", scheme)))
+    }
+
+    /// Schemes of a contact's addresses in delivery order, preferred scheme first
+    fn schemes_for(&self, contact: &Contact, preferred: Option<&str>) -> Vec<String> {
+        let mut schemes: Vec<String> = Vec::new();
+        for address in contact.delivery_addresses(None) {
+            let scheme = split_address_scheme(&address).0.to_string();
+            if !schemes.contains(&scheme) {
+                schemes.push(scheme);
+            }
+        }
+        if let Some(pos) = preferred.and_then(|p| schemes.iter().position(|s| s == p)) {
+            let scheme = schemes.remove(pos);
+            schemes.insert(0, scheme);
+        }
+        schemes
+    }
+}
+
+impl PeerTransport for TransportRegistry {
+    fn scheme(&self) -> &str {
+        "registry"
+    }
+
+    fn capabilities(&self) -> TransportCapabilities {
+        self.transports
+            .values()
+            .map(|t| t.capabilities())
+            .fold(TransportCapabilities::default(), |acc, c| TransportCapabilities {
+                can_send: acc.can_send || c.can_send,
+                can_listen: acc.can_listen || c.can_listen,
+                anonymous: acc.anonymous || c.anonymous,
+            })
+    }
+
+    fn send_message<'a>(
+        &'a self,
+        contact: &'a Contact,
+        request: &'a MessageRequest,
+    ) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            let preferred = self.learned_schemes.get(&contact.uid);
+            let schemes = self.schemes_for(contact, preferred.as_deref());
+
+            let mut last_error = None;
+            for scheme in &schemes {
+                let Some(transport) = self.get(scheme) else {
+                    last_error = Some(Error::NotSupported(format!("No transport for scheme '{}'", scheme)));
+                    continue;
+                };
+                match transport.send_message(contact, request).await {
+                    Ok(()) => {
+                        if schemes.len() > 1 {
+                            self.learned_schemes.insert(contact.uid.clone(), scheme.clone());
+                        }
+                        return Ok(());
+                    }
+                    Err(e) => last_error = Some(e),
+                }
+            }
+
+            Err(last_error.unwrap_or_else(|| Error::Transport("No address to send message to".to_string())))
+        })
+    }
+
+    fn relay_message<'a>(
+        &'a self,
+        relay: &'a Contact,
+        envelope: &'a RelayEnvelope,
+    ) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            let mut last_error = None;
+            for scheme in self.schemes_for(relay, None) {
+                let Some(transport) = self.get(&scheme) else {
+                    last_error = Some(Error::NotSupported(format!("No transport for scheme '{}'", scheme)));
+                    continue;
+                };
+                match transport.relay_message(relay, envelope).await {
+                    Ok(()) => return Ok(()),
+                    Err(e) => last_error = Some(e),
+                }
+            }
+
+            Err(last_error.unwrap_or_else(|| Error::Transport(format!("No address for relay {}", relay.uid))))
+        })
+    }
+
+    fn probe_capabilities<'a>(
+        &'a self,
+        contact: &'a Contact,
+        from_uid: &'a str,
+    ) -> TransportFuture<'a, Option<RelayCapabilities>> {
+        Box::pin(async move {
+            let preferred = self.learned_schemes.get(&contact.uid);
+            let mut last_error = None;
+            for scheme in self.schemes_for(contact, preferred.as_deref()) {
+                let Some(transport) = self.get(&scheme) else {
+                    last_error = Some(Error::NotSupported(format!("No transport for scheme '{}'", scheme)));
+                    continue;
+                };
+                match transport.probe_capabilities(contact, from_uid).await {
+                    Ok(answer) => return Ok(answer),
+                    Err(e) => last_error = Some(e),
+                }
+            }
+
+            Err(last_error.unwrap_or_else(|| Error::Transport(format!("No address to probe {}", contact.uid))))
+        })
+    }
+
+    fn send_ping<'a>(
+        &'a self,
+        contact: &'a Contact,
+        my_contact_token: &'a str,
+    ) -> TransportFuture<'a, PingResponse> {
+        Box::pin(async move {
+            // A contact that pinned a certificate is pinged over TLS first
+            if contact.cert_fingerprint.is_some()
+                && split_address_scheme(&contact.ip).0 == super::DEFAULT_ADDRESS_SCHEME
+                && !addresses_for_scheme(contact, None, super::TLS_SCHEME).is_empty()
+                && let Some(tls) = self.get(super::TLS_SCHEME)
+            {
+                match tls.send_ping(contact, my_contact_token).await {
+                    Ok(response) => return Ok(response),
+                    Err(e) => debug!("TLS ping to {} failed, trying plain address: {}", contact.uid, e),
+                }
+            }
+            self.for_address(&contact.ip)?
+                .send_ping(contact, my_contact_token)
+                .await
+        })
+    }
+
+    fn start_listener<'a>(&'a self, address: &'a str) -> TransportFuture<'a, String> {
+        Box::pin(async move {
+            let (scheme, rest) = split_address_scheme(address);
+            let bound = self.for_address(address)?.start_listener(rest).await?;
+            if address.contains("://") {
+                Ok(format!("{}://{}", scheme, bound))
+            } else {
+                Ok(bound)
+            }
+        })
+    }
+
+    fn stop_listener(&self) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            for scheme in &self.order {
+                if let Some(transport) = self.transports.get(scheme) {
+                    transport.stop_listener().await?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,342 @@
+//! TLS for the HTTP transport, pinned by certificate fingerprint
+//!
+//! With `Settings::tls_enabled` on, the listener also speaks TLS on its
+//! existing port (one port, one router mapping): a connection whose first
+//! byte is a TLS handshake record is served over TLS, anything else as plain
+//! HTTP, so peers that do not know our certificate yet keep working during
+//! the transition. The certificate is self-signed (`TlsIdentity::generate`)
+//! and its SHA-256 fingerprint travels in our contact token as
+//! `cert_fingerprint`, next to an `https://` endpoint in front of the plain
+//! one. Peers verify the certificate against that fingerprint instead of the
+//! WebPKI (`connect_pinned`), before a single request byte is sent.
+//!
+//! The certificate is renewed `TLS_CERT_RENEW_DAYS` before it expires; new
+//! tokens (including the ones in our pings) carry the new fingerprint, and
+//! until a peer has it, it falls back to the plain endpoint.
+//!
+//! With `Settings::require_tls_external` on, plain HTTP is refused for
+//! non-LAN addresses of contacts whose token carries a fingerprint
+//! (`plain_http_allowed`).
+
+use super::{
+    peer::{PeerTransport, TransportCapabilities, TransportFuture},
+    MessageRequest, PingResponse, Transport,
+};
+use crate::{
+    relay::{RelayCapabilities, RelayEnvelope},
+    storage::{is_lan_address, validate_metadata, Contact},
+    Error, Result,
+};
+use bytes::Bytes;
+use chrono::{DateTime, Duration, Utc};
+use http_body_util::{BodyExt, Full};
+use hyper::{Method, Request, StatusCode};
+use hyper_util::rt::TokioIo;
+use openssl::{
+    asn1::Asn1Time,
+    bn::{BigNum, MsbOption},
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::PKey,
+    x509::{X509NameBuilder, X509},
+};
+use tokio::net::TcpStream;
+use tokio_native_tls::TlsStream;
+use tracing::debug;
+
+/// Address scheme of TLS endpoints (`https://host:port`)
+pub const TLS_SCHEME: &str = crate::storage::TLS_ADDRESS_SCHEME;
+
+/// Validity of a generated certificate
+pub const TLS_CERT_VALIDITY_DAYS: i64 = 365;
+
+/// A certificate closer than this to its expiry is replaced at startup
+pub const TLS_CERT_RENEW_DAYS: i64 = 30;
+
+/// First byte of a TLS handshake record (ClientHello)
+const TLS_HANDSHAKE_RECORD: u8 = 0x16;
+
+/// Time allowed for the TCP connect and the TLS handshake
+const TLS_CONNECT_TIMEOUT_SECS: u64 = 10;
+
+/// Time allowed for sending a request and reading the whole response
+const TLS_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// Name sent as SNI; certificates are pinned, so it is never checked
+const TLS_SERVER_NAME: &str = "pure2p";
+
+/// Our self-signed certificate and its private key
+#[derive(Clone)]
+pub struct TlsIdentity {
+    cert_pem: String,
+    key_pem: String,
+    fingerprint: String,
+    not_after: DateTime<Utc>,
+}
+
+impl std::fmt::Debug for TlsIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsIdentity")
+            .field("fingerprint", &self.fingerprint)
+            .field("not_after", &self.not_after)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TlsIdentity {
+    /// Generate a self-signed P-256 certificate valid for `TLS_CERT_VALIDITY_DAYS` from `now`
+    ///
+    /// # Errors
+    /// Returns `Error::Crypto` if key or certificate generation fails
+    pub fn generate(now: DateTime<Utc>) -> Result<Self> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).map_err(tls_error)?;
+        let key = PKey::from_ec_key(EcKey::generate(&group).map_err(tls_error)?).map_err(tls_error)?;
+
+        let mut name = X509NameBuilder::new().map_err(tls_error)?;
+        name.append_entry_by_nid(Nid::COMMONNAME, TLS_SERVER_NAME).map_err(tls_error)?;
+        let name = name.build();
+
+        let mut serial = BigNum::new().map_err(tls_error)?;
+        serial.rand(64, MsbOption::MAYBE_ZERO, false).map_err(tls_error)?;
+        let serial = serial.to_asn1_integer().map_err(tls_error)?;
+
+        let mut builder = X509::builder().map_err(tls_error)?;
+        builder.set_version(2).map_err(tls_error)?;
+        builder.set_serial_number(&serial).map_err(tls_error)?;
+        builder.set_subject_name(&name).map_err(tls_error)?;
+        builder.set_issuer_name(&name).map_err(tls_error)?;
+        builder.set_pubkey(&key).map_err(tls_error)?;
+        // Backdated an hour for peers whose clocks run a little behind
+        let not_before = Asn1Time::from_unix((now - Duration::hours(1)).timestamp()).map_err(tls_error)?;
+        let not_after = Asn1Time::from_unix((now + Duration::days(TLS_CERT_VALIDITY_DAYS)).timestamp()).map_err(tls_error)?;
+        builder.set_not_before(&not_before).map_err(tls_error)?;
+        builder.set_not_after(&not_after).map_err(tls_error)?;
+        builder.sign(&key, MessageDigest::sha256()).map_err(tls_error)?;
+
+        let cert_pem = String::from_utf8(builder.build().to_pem().map_err(tls_error)?)
+            .map_err(|e| Error::Crypto(format!("Certificate PEM is not UTF-8: {}", e)))?;
+        let key_pem = String::from_utf8(key.private_key_to_pem_pkcs8().map_err(tls_error)?)
+            .map_err(|e| Error::Crypto(format!("Key PEM is not UTF-8: {}", e)))?;
+        Self::from_pem(&cert_pem, &key_pem)
+    }
+
+    /// Load a stored certificate and key
+    ///
+    /// # Errors
+    /// Returns `Error::Crypto` if either does not parse
+    pub fn from_pem(cert_pem: &str, key_pem: &str) -> Result<Self> {
+        let cert = X509::from_pem(cert_pem.as_bytes()).map_err(tls_error)?;
+        PKey::private_key_from_pem(key_pem.as_bytes()).map_err(tls_error)?;
+        let epoch = Asn1Time::from_unix(0).map_err(tls_error)?;
+        let diff = epoch.diff(cert.not_after()).map_err(tls_error)?;
+        let not_after = DateTime::from_timestamp(i64::from(diff.days) * 86_400 + i64::from(diff.secs), 0)
+            .ok_or_else(|| Error::Crypto("Certificate expiry out of range".to_string()))?;
+        Ok(Self {
+            cert_pem: cert_pem.to_string(),
+            key_pem: key_pem.to_string(),
+            fingerprint: cert_fingerprint(&cert.to_der().map_err(tls_error)?),
+            not_after,
+        })
+    }
+
+    /// Certificate (PEM)
+    pub fn cert_pem(&self) -> &str {
+        &self.cert_pem
+    }
+
+    /// Private key (PKCS#8 PEM)
+    pub fn key_pem(&self) -> &str {
+        &self.key_pem
+    }
+
+    /// SHA-256 fingerprint of the certificate, as advertised in tokens
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// When the certificate expires
+    pub fn not_after(&self) -> DateTime<Utc> {
+        self.not_after
+    }
+
+    /// Whether the certificate should be replaced at `now`
+    pub fn needs_renewal(&self, now: DateTime<Utc>) -> bool {
+        self.not_after - now < Duration::days(TLS_CERT_RENEW_DAYS)
+    }
+
+    /// Acceptor serving this certificate
+    pub(crate) fn acceptor(&self) -> Result<tokio_native_tls::TlsAcceptor> {
+        let identity = native_tls::Identity::from_pkcs8(self.cert_pem.as_bytes(), self.key_pem.as_bytes())
+            .map_err(|e| Error::Crypto(format!("Invalid TLS identity: {}", e)))?;
+        let acceptor = native_tls::TlsAcceptor::new(identity)
+            .map_err(|e| Error::Crypto(format!("Failed to create TLS acceptor: {}", e)))?;
+        Ok(acceptor.into())
+    }
+}
+
+fn tls_error(e: openssl::error::ErrorStack) -> Error {
+    Error::Crypto(format!("TLS certificate error: {}", e))
+}
+
+/// Fingerprint of a DER certificate: lowercase hex SHA-256
+pub fn cert_fingerprint(der: &[u8]) -> String {
+    hex::encode(ring::digest::digest(&ring::digest::SHA256, der))
+}
+
+/// Whether plain HTTP may be used to reach `contact` at `host`
+///
+/// With `require_tls_external` on, a contact whose token carries a
+/// certificate fingerprint is only reached over plain HTTP on the LAN.
+pub fn plain_http_allowed(require_tls_external: bool, contact: &Contact, host: &str) -> bool {
+    !require_tls_external || contact.cert_fingerprint.is_none() || is_lan_address(host)
+}
+
+/// Whether the connection starts with a TLS handshake (peeked, not consumed)
+pub(crate) async fn starts_with_tls_handshake(stream: &TcpStream) -> bool {
+    let mut first = [0u8; 1];
+    matches!(stream.peek(&mut first).await, Ok(1) if first[0] == TLS_HANDSHAKE_RECORD)
+}
+
+/// Open a TLS connection to `host` and check its certificate against `fingerprint`
+///
+/// # Errors
+/// `Error::Transport` if the connection or handshake fails, or the
+/// certificate does not match the pinned fingerprint
+pub async fn connect_pinned(host: &str, fingerprint: &str) -> Result<TlsStream<TcpStream>> {
+    let timeout = std::time::Duration::from_secs(TLS_CONNECT_TIMEOUT_SECS);
+    let connector = native_tls::TlsConnector::builder()
+        // The WebPKI cannot vouch for a self-signed peer; the pin below does
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build()
+        .map_err(|e| Error::Transport(format!("Failed to create TLS connector: {}", e)))?;
+    let connector = tokio_native_tls::TlsConnector::from(connector);
+
+    let stream = tokio::time::timeout(timeout, async {
+        let tcp = TcpStream::connect(host)
+            .await
+            .map_err(|e| Error::Transport(format!("Failed to connect to {}: {}", host, e)))?;
+        connector
+            .connect(TLS_SERVER_NAME, tcp)
+            .await
+            .map_err(|e| Error::Transport(format!("TLS handshake with {} failed: {}", host, e)))
+    })
+    .await
+    .map_err(|_| Error::Transport(format!("TLS connection to {} timed out", host)))??;
+
+    let der = stream
+        .get_ref()
+        .peer_certificate()
+        .ok()
+        .flatten()
+        .and_then(|cert| cert.to_der().ok())
+        .ok_or_else(|| Error::Transport(format!("{} presented no TLS certificate", host)))?;
+    if !cert_fingerprint(&der).eq_ignore_ascii_case(fingerprint) {
+        return Err(Error::Transport(format!(
+            "TLS certificate of {} does not match the pinned fingerprint",
+            host
+        )));
+    }
+    Ok(stream)
+}
+
+/// POST a CBOR `body` to `path` at `host` over a pinned TLS connection
+///
+/// A peer that completes the handshake but never answers is given up on
+/// after `TLS_REQUEST_TIMEOUT_SECS`.
+///
+/// # Returns
+/// The response status and body
+pub(crate) async fn pinned_post(host: &str, fingerprint: &str, path: &str, body: Vec<u8>) -> Result<(StatusCode, Bytes)> {
+    let stream = connect_pinned(host, fingerprint).await?;
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|e| Error::Transport(format!("HTTP handshake with {} failed: {}", host, e)))?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            debug!("TLS connection closed with error: {}", e);
+        }
+    });
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(path)
+        .header(hyper::header::HOST, host)
+        .header("Content-Type", "application/cbor")
+        .body(Full::new(Bytes::from(body)))
+        .map_err(|e| Error::Transport(format!("Failed to build request: {}", e)))?;
+    let timeout = std::time::Duration::from_secs(TLS_REQUEST_TIMEOUT_SECS);
+    tokio::time::timeout(timeout, async {
+        let response = sender
+            .send_request(req)
+            .await
+            .map_err(|e| Error::Transport(e.to_string()))?;
+        let status = response.status();
+        let body = response
+            .collect()
+            .await
+            .map_err(|e| Error::Transport(format!("Failed to read response: {}", e)))?
+            .to_bytes();
+        Ok((status, body))
+    })
+    .await
+    .map_err(|_| Error::Transport(format!("TLS request to {} timed out", host)))?
+}
+
+/// `PeerTransport` for `https://` addresses
+///
+/// Shares the HTTP transport's handlers and listener (TLS is served on the
+/// same port); only outgoing requests differ.
+#[derive(Clone)]
+pub struct TlsTransport {
+    inner: Transport,
+}
+
+impl TlsTransport {
+    /// TLS carrier next to `transport`
+    pub fn new(transport: Transport) -> Self {
+        Self { inner: transport }
+    }
+}
+
+impl PeerTransport for TlsTransport {
+    fn scheme(&self) -> &str {
+        TLS_SCHEME
+    }
+
+    fn capabilities(&self) -> TransportCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn send_message<'a>(&'a self, contact: &'a Contact, request: &'a MessageRequest) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            validate_metadata(&request.metadata)?;
+            self.inner.deliver(contact, request, TLS_SCHEME).await
+        })
+    }
+
+    fn send_ping<'a>(&'a self, contact: &'a Contact, my_contact_token: &'a str) -> TransportFuture<'a, PingResponse> {
+        Box::pin(self.inner.ping(contact, my_contact_token, TLS_SCHEME))
+    }
+
+    fn relay_message<'a>(&'a self, relay: &'a Contact, envelope: &'a RelayEnvelope) -> TransportFuture<'a, ()> {
+        Box::pin(self.inner.post_relay(relay, envelope, TLS_SCHEME))
+    }
+
+    fn probe_capabilities<'a>(
+        &'a self,
+        contact: &'a Contact,
+        from_uid: &'a str,
+    ) -> TransportFuture<'a, Option<RelayCapabilities>> {
+        Box::pin(self.inner.post_capability_probe(contact, from_uid, TLS_SCHEME))
+    }
+
+    fn start_listener<'a>(&'a self, address: &'a str) -> TransportFuture<'a, String> {
+        PeerTransport::start_listener(&self.inner, address)
+    }
+
+    fn stop_listener(&self) -> TransportFuture<'_, ()> {
+        PeerTransport::stop_listener(&self.inner)
+    }
+}
@@ -0,0 +1,138 @@
+//! Self-probing of the transport server's port
+//!
+//! Every time the listener starts, it draws a fresh random boot nonce and
+//! returns it in the `X-Pure2P-Boot` header of `GET /health`. The nonce is
+//! random bytes only, unrelated to our keys or UID, and changes with every
+//! start, so it identifies this run of the listener to itself and nothing
+//! else. A 200 from our port without the current nonce means another
+//! process answered there; no answer at all means the listener is gone.
+//! `bind_verified` is the startup bind loop, reused to rebind after either.
+
+use super::Transport;
+use rand::RngCore;
+
+/// Response header carrying the listener's boot nonce
+pub const BOOT_NONCE_HEADER: &str = "x-pure2p-boot";
+
+/// Seconds between self-probes of a running listener
+pub const WATCHDOG_INTERVAL_SECS: u64 = 30;
+
+/// Consecutive unanswered probes before the port counts as lost
+pub const LOST_AFTER_FAILED_PROBES: u32 = 2;
+
+/// Attempts `bind_verified` makes before giving up
+pub const MAX_BIND_ATTEMPTS: usize = 10;
+
+/// Timeout of one self-probe
+const PROBE_TIMEOUT_SECS: u64 = 2;
+
+/// What answered a self-probe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    /// Our listener, with the current boot nonce
+    Ours,
+    /// Something else: wrong or missing nonce, or not a healthy response
+    Foreign,
+    /// Nothing answered
+    Unreachable,
+}
+
+/// Draw a new boot nonce (16 random bytes, hex-encoded)
+pub fn generate_boot_nonce() -> String {
+    let mut bytes = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Classify a `/health` answer against the nonce we expect
+///
+/// # Arguments
+/// * `success` - Whether the response status was 2xx
+/// * `nonce` - The `X-Pure2P-Boot` header, if present
+/// * `expected` - Boot nonce of our running listener
+pub fn classify(success: bool, nonce: Option<&str>, expected: &str) -> ProbeOutcome {
+    let matches = nonce.is_some_and(|n| crate::invite::constant_time_eq(n.as_bytes(), expected.as_bytes()));
+    if success && matches {
+        ProbeOutcome::Ours
+    } else {
+        ProbeOutcome::Foreign
+    }
+}
+
+/// Probe `GET /health` on our own port over loopback
+pub async fn probe_self(port: u16, expected: &str) -> ProbeOutcome {
+    let url = format!("http://127.0.0.1:{}/health", port);
+    match reqwest::Client::new()
+        .get(&url)
+        .timeout(std::time::Duration::from_secs(PROBE_TIMEOUT_SECS))
+        .send()
+        .await
+    {
+        Ok(response) => {
+            let nonce = response.headers().get(BOOT_NONCE_HEADER).and_then(|v| v.to_str().ok());
+            classify(response.status().is_success(), nonce, expected)
+        }
+        Err(_) => ProbeOutcome::Unreachable,
+    }
+}
+
+/// Start `transport` on `preferred_port`, or a random port if that fails
+///
+/// Each bind is confirmed by a self-probe carrying the new boot nonce, so a
+/// port answered by another process is never reported as ours.
+///
+/// # Returns
+/// The port the listener is verified on, or the last error after
+/// `max_attempts` attempts
+pub async fn bind_verified(transport: &Transport, preferred_port: u16, max_attempts: usize) -> std::result::Result<u16, String> {
+    let mut current_port = preferred_port;
+    let mut last_error = String::new();
+
+    for attempt in 0..max_attempts {
+        let addr = format!("0.0.0.0:{}", current_port).parse().expect("Invalid address");
+
+        tracing::info!("Attempting to start transport server on port {} (attempt {}/{})", current_port, attempt + 1, max_attempts);
+
+        match transport.clone().start(addr).await {
+            Ok(_) => {
+                tracing::info!("✓ Transport server successfully started on port {}", current_port);
+
+                // Wait a moment for server to fully initialize
+                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+                // Verify the listener answering locally is this one
+                match probe_self(current_port, &transport.boot_nonce()).await {
+                    ProbeOutcome::Ours => {
+                        tracing::info!("✓ Transport server verified listening on port {}", current_port);
+                        return Ok(current_port);
+                    }
+                    ProbeOutcome::Foreign => {
+                        last_error = format!("Port {} is answered by another process", current_port);
+                        tracing::warn!("{}", last_error);
+                        transport.stop();
+                        current_port = crate::storage::AppState::generate_random_port();
+                    }
+                    ProbeOutcome::Unreachable => {
+                        last_error = format!("Server started but health check failed on port {}", current_port);
+                        tracing::warn!("{}", last_error);
+                    }
+                }
+            }
+            Err(e) => {
+                last_error = format!("Port {} bind failed: {}", current_port, e);
+                tracing::warn!("{}", last_error);
+
+                // Try a different random port
+                current_port = crate::storage::AppState::generate_random_port();
+                tracing::info!("Will retry with port {}", current_port);
+            }
+        }
+
+        // Small delay between retries
+        if attempt < max_attempts - 1 {
+            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        }
+    }
+
+    Err(format!("Failed to start transport server after {} attempts. Last error: {}", max_attempts, last_error))
+}
@@ -0,0 +1,106 @@
+//! In-app alerts for new messages (terminal bell and header flash)
+//!
+//! Desktop-style notifications do not help when the terminal already has
+//! focus. Messages arriving in a chat other than the open one can ring the
+//! terminal bell and/or flash the header line, as chosen by
+//! `Settings::alert_mode`. Muted chats and quiet hours never alert.
+//!
+//! How loud an alert is comes from its `AlertStyle`: the contact's own
+//! choice, or `Settings::alert_style` (see `resolve_alert_style`). A
+//! `NotificationSink` turns a style into what the platform can do; the
+//! `TerminalSink` has only the bell and the header flash, so urgency is
+//! expressed as repeated bells and a stronger, longer flash.
+
+use crate::storage::{AlertMode, AlertStyle};
+use crate::tui::effects::UiEffect;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// The BEL control character
+pub const BELL: &[u8] = b"\x07";
+
+/// Gap between the bells of an urgent alert
+pub const URGENT_BELL_GAP: Duration = Duration::from_millis(250);
+
+/// Bells of an urgent alert
+pub const URGENT_BELLS: u32 = 3;
+
+/// Style a new message in a chat alerts with
+///
+/// Mute and quiet hours win over any style; otherwise the contact's own
+/// style wins over the global default.
+///
+/// # Arguments
+/// * `contact` - The contact's style (None follows the global default)
+/// * `global` - `Settings::alert_style`
+/// * `muted` - Whether the chat is muted
+/// * `quiet` - Whether quiet hours are in effect
+pub fn resolve_alert_style(contact: Option<AlertStyle>, global: AlertStyle, muted: bool, quiet: bool) -> AlertStyle {
+    if muted || quiet {
+        return AlertStyle::None;
+    }
+    contact.unwrap_or(global)
+}
+
+/// What one alert does: bells (as delays from now) and a header flash
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AlertSignal {
+    /// When to ring each bell, relative to the alert
+    pub bells: Vec<Duration>,
+    /// Header flash to draw, if any
+    pub flash: Option<UiEffect>,
+}
+
+/// Maps alert styles to what a platform can signal
+///
+/// The app asks the sink once per alert and plays the result; platform
+/// differences (urgency hints, sounds, flash support) stay in the sink.
+pub trait NotificationSink: Send + std::fmt::Debug {
+    /// Signal for `style` on the channels `mode` allows
+    fn signal(&mut self, style: AlertStyle, mode: AlertMode) -> AlertSignal;
+}
+
+/// Terminal bell and header flash
+#[derive(Debug, Default)]
+pub struct TerminalSink;
+
+impl NotificationSink for TerminalSink {
+    fn signal(&mut self, style: AlertStyle, mode: AlertMode) -> AlertSignal {
+        let (bells, flash) = match style {
+            AlertStyle::None => (0, None),
+            AlertStyle::Subtle => (0, Some(UiEffect::SubtleFlash)),
+            AlertStyle::Default => (1, Some(UiEffect::HeaderFlash)),
+            AlertStyle::Urgent => (URGENT_BELLS, Some(UiEffect::UrgentFlash)),
+        };
+        AlertSignal {
+            bells: if mode.rings_bell() { (0..bells).map(|i| URGENT_BELL_GAP * i).collect() } else { Vec::new() },
+            flash: flash.filter(|_| mode.flashes()),
+        }
+    }
+}
+
+/// Whether a new message in `chat_uid` should alert
+///
+/// # Arguments
+/// * `chat_uid` - Chat the message arrived in
+/// * `open_chat` - Chat currently open in the chat view, if any
+/// * `muted` - Whether `chat_uid` is muted
+/// * `quiet` - Whether quiet hours are in effect
+pub fn should_alert(chat_uid: &str, open_chat: Option<&str>, muted: bool, quiet: bool) -> bool {
+    !muted && !quiet && open_chat != Some(chat_uid)
+}
+
+/// Write the terminal bell to `out` if it is a terminal
+///
+/// Redirected output (a file or pipe) never gets a stray BEL byte.
+///
+/// # Returns
+/// Whether the bell was written
+pub fn ring_bell<W: Write>(out: &mut W, is_tty: bool) -> io::Result<bool> {
+    if !is_tty {
+        return Ok(false);
+    }
+    out.write_all(BELL)?;
+    out.flush()?;
+    Ok(true)
+}
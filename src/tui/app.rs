@@ -1,12 +1,72 @@
 //! Main TUI application state and logic
 
 use crate::crypto::KeyPair;
-use crate::storage::{AppState, Message, storage_db::Storage};
-use crate::tui::types::{Screen, MenuItem};
+use crate::messaging::SendAttempt;
+use crate::storage::{archive_file_name, ChatArchive, name_words, parse_introduction_list, PendingIntroduction, validate_contact_addresses, INVALID_ADDRESS_STATUS, bounded_timestamp, export_journal, journal_export_file_name, verify_chain, JournalExportFormat, JournalRecord, OutboundPolicy, TrustTier, diff_snapshot_files, list_snapshots, snapshot_file_name, SnapshotComparison, SNAPSHOTS_DIR, purge_cutoff, require_min_validity, CapabilityProbes, DeletedKind, create_download_file, download_file_name, export_file_name, format_size, JsonlExportOptions, is_busy_error, sanitize_text, signal_enabled, AddressSource, AppState, ContactIngest, DeferredWrites, Ephemeral, ErrorSeverity, Message, PrivacySignal, Protection, AlertMode, AlertStyle, format_utc_offset, is_night_at, local_time_at, parse_utc_offset, UtcOffset, process_alive, read_runtime_info, remove_runtime_info, write_runtime_info, RuntimeInfo, RUNTIME_INFO_FILE, begin_session, end_session, SessionMarker, SESSION_MARKER_FILE, check_not_live, WriteLog, DATABASE_FILE_NAME, DOWNLOADS_DIR, LEGACY_STATE_FILE, MAX_TEMPLATES, storage_db::Storage};
+use crate::tui::types::{Screen, MenuItem, StartupTimings};
+use crate::tui::notifications::Notifications;
+use crate::tui::alerts;
+use crate::tui::effects::{EffectQueue, UiEffect};
+use crate::tui::focus::FocusState;
+use crate::tui::badges::ChatSummary;
+use crate::tui::delivery_events::{DeliveryEvent, DeliveryUpdate, RECONCILE_INTERVAL};
+use crate::tui::diagnostics_actions::{
+    protocol_name, DiagnosticsAction, DiagnosticsActionResult, MappingActions, RouterMappingActions,
+};
 use crate::tui::screens::*;
-use crate::transport::Transport;
-use crate::queue::MessageQueue;
-use chrono::Utc;
+use crate::update_check::{
+    run_update_check, update_check_due, HttpManifestFetcher, ManifestFetcher, UpdateNotice, UPDATE_SIGNING_KEY,
+};
+use crate::tui::theme::Theme;
+use crate::tui::connectivity_indicator::ConnectivityIndicator;
+use crate::connectivity::{ConnectivityEvent, ConnectivityEvents, ConnectivityResult, SystemStrategies};
+use crate::tui::delivery_hint::{delivery_hint, DeliveryHint};
+use crate::tui::port_watchdog::{PortAlert, PortWatchdog};
+use crate::tui::error_reports::{is_local_failure, ErrorBanner, ErrorReporter};
+use crate::tui::contact_import::{
+    parse_token_batch, ping_renewing_token, suggest_batch_links, BatchEntryResult, BatchEntryStatus, BatchImport,
+    BatchReportEntry, ImportRefusal, OwnTokenSource, PingDispatch, PingDispatcher, FRESH_TOKEN_MARKER,
+};
+use crate::tui::undo::{UndoEntry, UndoList, UndoState};
+use crate::tui::send_leases::{run_send, SendLease, SendLeases, LEASE_SWEEP_INTERVAL, SEND_TRANSPORT_TIMEOUT_SECS};
+use crate::tui::recovery::{count_phrase, RecoveryReport, RecoveryStep, StepOutcome};
+use crate::tui::inspection::Inspection;
+use crate::logging::{rotated_path, LOGS_DIR};
+use crate::tui::startup_graph::{StageState, StartupEvent, StartupGraph, StartupStage};
+use crate::tui::send_preview::{preview_reasons, SendPreview};
+use crate::tui::expiry_warning::{
+    expiry_warning, ExpiryChoice, ExpiryWarning, EXPIRED_BLOCK_SYNC_SECS, TOKEN_REFRESH_REQUEST_TYPE,
+};
+use crate::tui::clipboard::{ClipboardError, ClipboardProvider, RealClipboard};
+use crate::tui::path_picker::{open_for_save, ChosenPath, PathPicker, SavePathError, SaveTarget};
+use crate::tui::paths::{default_save_dir, PathEnv, Platform};
+use crate::transport::{
+    bind_verified,
+    capture::{capture_file_name, list_captures, CAPTURES_DIR},
+    watchdog::{MAX_BIND_ATTEMPTS, WATCHDOG_INTERVAL_SECS},
+    MessageRequest, PeerTransport, TlsIdentity, TlsTransport, Transport, TransportRegistry,
+};
+use crate::queue::{MessageQueue, QueuedMessage, CONTACT_NOT_FOUND_ERROR};
+use crate::queue_migration::{MIGRATION_BATCH_ROWS, QUEUE_SCHEMA_VERSION};
+use crate::retry_schedule::{plan_next_cycle, retry_opportunities, RetryMode, RetryStatus, RetryWakeup, RETRY_BATCH_SIZE};
+use crate::relay::{apply_probe_answer, RelayCapabilities, RELAY_CAPABILITIES_TYPE};
+use crate::auto_import::{admit_or_audit, AutoImportLimiter, AUTO_IMPORT_LIFT_MINUTES};
+use crate::edits::{apply_incoming_edit, check_editable, correction_text, EditRequest, EditRoute, EDIT_TYPE};
+use crate::probe::{
+    admit_probe, is_probe_type, probe_address, probe_history, probe_request_message, record_probe_result,
+    send_probe_result, PeerProbeRecord, PendingProbe, ProbeLimiter, ProbeRequest, ProbeResult,
+    PROBE_CHECK_TIMEOUT_SECS, PROBE_CHECK_TYPE, PROBE_REQUEST_TYPE, PROBE_RESULT_TYPE,
+};
+use crate::signals::{is_signal_type, send_presence, send_read_receipt, send_typing, TYPING_RESEND_SECS};
+use crate::invite::weak_code_warning;
+use crate::sealing::{
+    apply_key_upgrade, arrival_protection, key_upgrade_request, key_upgrade_response, open_request, seal_request, send_security,
+    SendSecurity, ENCRYPTED_TEXT_TYPE, KEY_UPGRADE_REQUEST_TYPE, KEY_UPGRADE_RESPONSE_TYPE,
+};
+use chrono::{DateTime, FixedOffset, Utc};
+
+/// Capability probe answers by contact UID, shared with send threads
+type CapabilityAnswers = std::sync::Arc<std::sync::Mutex<Vec<(String, Option<RelayCapabilities>)>>>;
 
 /// Application state
 pub struct App {
@@ -32,32 +92,197 @@ pub struct App {
     pub chat_list_screen: Option<ChatListScreen>,
     /// Chat view screen (when active)
     pub chat_view_screen: Option<ChatViewScreen>,
+    /// Chats opened this session, most recent first (at most `RECENT_CHATS`)
+    pub recent_chats: Vec<String>,
     /// Settings screen (when active)
     pub settings_screen: Option<SettingsScreen>,
     /// Diagnostics screen (when active)
     pub diagnostics_screen: Option<DiagnosticsScreen>,
+    /// Snapshots screen (when active)
+    pub snapshots_screen: Option<SnapshotsScreen>,
+    /// Maintenance screen (when active)
+    pub maintenance_screen: Option<MaintenanceScreen>,
+    /// Chats and contacts deleted this session (undo entries)
+    pub undo: UndoList,
+    /// Capture screen state
+    pub capture_screen: Option<CaptureScreen>,
     /// Startup sync screen (when active)
     pub startup_sync_screen: Option<StartupSyncScreen>,
-    /// Background diagnostics refresh handle
-    pub diagnostics_refresh_handle: Option<std::thread::JoinHandle<crate::connectivity::ConnectivityResult>>,
+    /// Background connectivity check (startup or Diagnostics refresh); its result arrives as an event
+    pub diagnostics_refresh_handle: Option<std::thread::JoinHandle<()>>,
+    /// Background handle of a single-protocol test or mapping deletion
+    pub diagnostics_action_handle: Option<std::thread::JoinHandle<DiagnosticsActionResult>>,
+    /// Router operations behind the Diagnostics screen actions
+    pub mapping_actions: std::sync::Arc<dyn MappingActions>,
+    /// Fetches the release manifest for the update check
+    pub update_fetcher: std::sync::Arc<dyn ManifestFetcher>,
+    /// Background update check handle
+    update_check_handle: Option<std::thread::JoinHandle<Result<Option<UpdateNotice>, String>>>,
+    /// When the release manifest was last fetched
+    last_update_check: Option<chrono::DateTime<chrono::Utc>>,
+    /// Result of the last update check, shown on the main menu
+    pub update_notice: Option<UpdateNotice>,
     /// Connectivity result from startup or last refresh
-    pub connectivity_result: Option<crate::connectivity::ConnectivityResult>,
+    pub connectivity_result: Option<ConnectivityResult>,
+    /// Connectivity events of the checks, health check and port watchdog
+    pub connectivity_events: ConnectivityEvents,
+    /// The app's subscription to `connectivity_events`
+    connectivity_rx: tokio::sync::broadcast::Receiver<ConnectivityEvent>,
+    /// Finished check received from the stream, until a poll applies it
+    pending_connectivity_check: Option<ConnectivityResult>,
+    /// Background external reachability check of the startup mapping; its verdict arrives as an event
+    health_check_handle: Option<std::thread::JoinHandle<()>>,
+    /// Progress of the startup stages (shown in Diagnostics)
+    pub startup: StartupGraph,
+    /// Sender handed to the threads doing startup work to report their stage
+    startup_events_tx: std::sync::mpsc::Sender<StartupEvent>,
+    /// Stage reports waiting to be applied to `startup`
+    startup_events: std::sync::mpsc::Receiver<StartupEvent>,
+    /// Footer connectivity segment (recomputed when its inputs change)
+    pub connectivity_indicator: ConnectivityIndicator,
+    /// Transport server status the indicator was last derived from
+    indicator_transport: TransportServerStatus,
     /// Local port for connectivity
     pub local_port: u16,
     /// Path to app state file (legacy, kept for compatibility)
     state_path: String,
     /// Transport layer for sending/receiving messages
     pub transport: Transport,
+    /// Transports by address scheme, used for all outgoing delivery (HTTP first)
+    pub transports: TransportRegistry,
+    /// Fingerprint of our TLS certificate while `Settings::tls_enabled` is on
+    /// (pinned by the tokens we hand out)
+    pub tls_fingerprint: Option<String>,
     /// Message queue for retry logic
     pub queue: MessageQueue,
     /// SQLite storage backend
-    storage: Storage,
+    pub(crate) storage: Storage,
     /// Flag to signal retry worker to stop
     retry_worker_stop: std::sync::Arc<std::sync::atomic::AtomicBool>,
     /// Background retry worker thread handle
     retry_worker_handle: Option<std::thread::JoinHandle<()>>,
+    /// Wakes the retry worker early (message queued, settings or network changed)
+    pub retry_wakeup: RetryWakeup,
+    /// The retry worker's current schedule (shown in Diagnostics)
+    pub retry_status: RetryStatus,
+    /// Retry interval in ms, shared with the worker so changes apply at once
+    retry_interval: std::sync::Arc<std::sync::atomic::AtomicU64>,
     /// Transport server status
     pub transport_server_status: std::sync::Arc<std::sync::Mutex<TransportServerStatus>>,
+    /// Duration of each startup phase (shown in Diagnostics)
+    pub startup_timings: StartupTimings,
+    /// Whether message history has been loaded (deferred until after first render)
+    pub messages_loaded: bool,
+    /// In-app notifications (held back during quiet hours)
+    pub notifications: Notifications,
+    /// Changes not yet written because the database is locked by another process
+    pub deferred_writes: DeferredWrites,
+    /// Set by background handlers after they store incoming messages or pings
+    pub incoming_updates: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    /// Sender handed to background senders to publish delivery status changes
+    delivery_events_tx: std::sync::mpsc::Sender<DeliveryEvent>,
+    /// Delivery status changes waiting to be applied to the chat list
+    delivery_events: std::sync::mpsc::Receiver<DeliveryEvent>,
+    /// When pending flags were last reconciled against the full queue
+    last_reconcile: std::time::Instant,
+    /// Chat sends in flight, taken back if their thread dies (see `tui::send_leases`)
+    pub send_leases: SendLeases,
+    /// When send leases were last swept
+    last_lease_sweep: std::time::Instant,
+    /// `SendLeases::generation` when the leases were last stored
+    persisted_lease_generation: u64,
+    /// Marker left while the app runs (`SESSION_MARKER_FILE` in production)
+    pub session_marker_path: std::path::PathBuf,
+    /// Recovery summary shown once after an unclean exit (when active)
+    pub recovery_screen: Option<RecoveryScreen>,
+    /// Whether "sending as <profile>" was confirmed this session
+    pub sending_as_confirmed: bool,
+    /// Where binary message content is saved (`DOWNLOADS_DIR` in production)
+    pub downloads_dir: std::path::PathBuf,
+    /// Folder suggested by the save-path overlay (Downloads/Documents in production)
+    pub save_dir: std::path::PathBuf,
+    /// Where database snapshots are written (`SNAPSHOTS_DIR` in production)
+    pub snapshots_dir: std::path::PathBuf,
+    /// Where debug captures are written (`CAPTURES_DIR` in production)
+    pub captures_dir: std::path::PathBuf,
+    /// Where the running port is published for local tooling (`RUNTIME_INFO_FILE` in production)
+    pub runtime_info_path: std::path::PathBuf,
+    /// When this instance started, published in the runtime info file
+    pub started_at: DateTime<Utc>,
+    /// Save-path overlay, when open
+    pub path_picker: Option<PathPicker>,
+    /// Absolute path of the last file saved through the overlay
+    pub last_saved_path: Option<std::path::PathBuf>,
+    /// Contacts asked for their X25519 key this session
+    pub key_upgrades_requested: std::collections::HashSet<String>,
+    /// Contacts that asked for our X25519 key, waiting for an answer
+    key_upgrade_requests: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    /// Contacts that asked for a fresh contact token, waiting for an answer
+    token_refresh_requests: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    /// When queued messages were last checked against contact expiry
+    expired_blocks_synced_at: Option<DateTime<Utc>>,
+    /// Rate limit of capability probes piggybacked on sends
+    pub capability_probes: CapabilityProbes,
+    /// Capability probe answers by contact UID, waiting to be recorded
+    capability_answers: CapabilityAnswers,
+    /// Contacts heard from over an authenticated channel since the last check
+    heard_from: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    /// Caps on contacts and chats created by incoming pings and messages
+    pub auto_import_limiter: std::sync::Arc<std::sync::Mutex<AutoImportLimiter>>,
+    /// Newest incoming message acknowledged by a read receipt, by contact UID
+    pub(crate) read_receipts_sent: std::collections::HashMap<String, String>,
+    /// When a typing indicator was last sent, by contact UID
+    typing_sent_at: std::collections::HashMap<String, std::time::Instant>,
+    /// Transient UI effects (e.g. the new-message header flash)
+    pub effects: EffectQueue,
+    /// Bells asked for by new-message alerts, by when each is due
+    pending_bells: Vec<std::time::Instant>,
+    /// Maps alert styles to bells and flashes (see `alerts::NotificationSink`)
+    pub notification_sink: Box<dyn alerts::NotificationSink>,
+    /// Whether the terminal window is in front (see `focus`)
+    pub focus: FocusState,
+    /// Banner raised by the port watchdog (shared with the transport thread)
+    pub port_alert: std::sync::Arc<std::sync::Mutex<Option<PortAlert>>>,
+    /// Failures of background work (shared with handler and delivery threads)
+    pub error_reports: ErrorReporter,
+    /// Rotated log file, once file logging is set up (flushed on shutdown)
+    pub log_file: Option<crate::logging::RotatingLog>,
+    /// Probe requests from contacts, waiting to be served
+    probe_requests: std::sync::Arc<std::sync::Mutex<Vec<(String, ProbeRequest)>>>,
+    /// Answers to our probe requests (and requests that could not be sent)
+    probe_results: std::sync::Arc<std::sync::Mutex<Vec<(String, ProbeResult)>>>,
+    /// Limit on probes served per contact
+    pub probe_limiter: ProbeLimiter,
+    /// Probes we asked contacts for, waiting for their result
+    pub pending_probes: Vec<PendingProbe>,
+    /// Set when browsing a copied app_data directory read-only (see `tui::inspection`)
+    pub inspection: Option<Inspection>,
+}
+
+/// Probes awaiting a result at most; the oldest is forgotten beyond this
+pub const MAX_PENDING_PROBES: usize = 8;
+
+/// Chats remembered for Tab in the chat list and '`' in a chat
+pub const RECENT_CHATS: usize = 2;
+
+/// UID characters shown as the identity fingerprint next to the profile label
+pub const IDENTITY_FINGERPRINT_CHARS: usize = 8;
+
+/// Chats between progress log lines while chat summaries are built
+const SUMMARY_PROGRESS_STEP: usize = 100;
+
+/// Rows between progress log lines while the queue schema migrates
+const QUEUE_MIGRATION_PROGRESS_STEP: usize = 10 * MIGRATION_BATCH_ROWS;
+
+/// What happened to the message of a send that never finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnfinishedSend {
+    /// Queued for retry
+    Queued,
+    /// Already in the queue
+    AlreadyQueued,
+    /// No longer in its chat
+    Gone,
 }
 
 /// Status of the transport server
@@ -71,6 +296,10 @@ pub enum TransportServerStatus {
     Running(u16),
     /// Failed to start with error message
     Failed(String),
+    /// Another process answered on our port; rebinding
+    Hijacked(u16),
+    /// Our port stopped answering; rebinding
+    Lost(u16),
 }
 
 impl App {
@@ -83,9 +312,10 @@ impl App {
         // Determine state file path (legacy, used for migration)
         let state_path = state_path.as_ref()
             .map(|p| p.as_ref().to_string_lossy().to_string())
-            .unwrap_or_else(|| "app_state.json".to_string());
+            .unwrap_or_else(|| LEGACY_STATE_FILE.to_string());
 
         // Initialize SQLite storage
+        let phase_start = std::time::Instant::now();
         let storage = if state_path.contains("test") || state_path.contains("tmp") {
             // For tests, use in-memory database
             Storage::new_in_memory()?
@@ -94,14 +324,73 @@ impl App {
             Storage::new_with_default_path()?
         };
 
+        let mut startup_timings = StartupTimings::new();
+        startup_timings.record("storage open", phase_start.elapsed());
+
+        Self::new_with_storage(storage, state_path, startup_timings)
+    }
+
+    /// Create new application on an already opened storage backend
+    ///
+    /// Only the minimal startup path runs here (migration check, identity, settings,
+    /// contacts and chat headers). Message history, transport and connectivity are
+    /// started by `complete_deferred_startup()` once the first frame is drawn.
+    pub(crate) fn new_with_storage(
+        storage: Storage,
+        state_path: String,
+        startup_timings: StartupTimings,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::build(storage, state_path, startup_timings, None)
+    }
+
+    /// Open a copied app_data directory strictly read-only (`--inspect <dir>`)
+    ///
+    /// Refuses a directory a running instance uses. Nothing is migrated,
+    /// generated or saved, and `complete_deferred_startup()` only loads
+    /// message history (see `tui::inspection`).
+    pub fn new_for_inspection<P: AsRef<std::path::Path>>(dir: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = dir.as_ref();
+        check_not_live(dir, process_alive)?;
+
+        let phase_start = std::time::Instant::now();
+        let writes = WriteLog::new();
+        let storage = Storage::open_read_only(dir.join(DATABASE_FILE_NAME), &writes)?;
+        let mut startup_timings = StartupTimings::new();
+        startup_timings.record("storage open", phase_start.elapsed());
+
+        let state_path = dir.join(LEGACY_STATE_FILE).to_string_lossy().to_string();
+        Self::build(storage, state_path, startup_timings, Some(Inspection::new(dir.to_path_buf(), writes)))
+    }
+
+    /// Create the application; `inspection` opens everything read-only
+    fn build(
+        storage: Storage,
+        state_path: String,
+        mut startup_timings: StartupTimings,
+        mut inspection: Option<Inspection>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let phase_start = std::time::Instant::now();
+
         // Check for legacy app_state.json and migrate if exists
-        let migrated = AppState::migrate_from_json(&state_path, &storage)?;
-        if migrated {
+        if inspection.is_none() && AppState::migrate_from_json(&state_path, &storage)? {
             tracing::info!("Migrated app state from {} to SQLite database", state_path);
         }
 
-        // Load app state from SQLite (or create new if empty)
-        let mut app_state = AppState::load_from_db(&storage)?;
+        // Load app state from SQLite without message history (or create new if empty)
+        let mut app_state = AppState::load_headers_from_db(&storage)?;
+
+        // One-time identity scan of databases written before identities were checked
+        if app_state.user_keypair.is_some() && inspection.is_none() {
+            match app_state.run_identity_scan(&storage) {
+                Ok(Some(report)) if !report.conflicts.is_empty() => {
+                    tracing::warn!("{} identity conflict(s) found in stored contacts", report.conflicts.len());
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Identity scan failed: {}", e),
+            }
+        }
+        startup_timings.record("state load", phase_start.elapsed());
+        let phase_start = std::time::Instant::now();
 
         // Check if this is first run (no user keypair)
         let is_first_run = app_state.user_keypair.is_none();
@@ -109,6 +398,8 @@ impl App {
         // Load or generate user keypair (persistent identity)
         let keypair = if let Some(existing_keypair) = &app_state.user_keypair {
             existing_keypair.clone()
+        } else if inspection.is_some() {
+            return Err("The inspected database has no identity".into());
         } else {
             // First run: generate new identity
             let new_keypair = KeyPair::generate()?;
@@ -121,12 +412,24 @@ impl App {
 
         // Smart port selection: reuse saved port if IP hasn't changed, generate new if IP changed
         let local_port = Self::select_port(&app_state, &local_ip);
+        startup_timings.record("identity", phase_start.elapsed());
+        let phase_start = std::time::Instant::now();
 
-        // Create transport layer
+        // Create transport layer; relayed envelopes wake the retry worker
         let transport = Transport::new();
+        let retry_wakeup = RetryWakeup::new();
+        transport.relay().lock().unwrap().set_wakeup(retry_wakeup.clone());
+        let last_update_check = storage.last_update_check().unwrap_or_else(|e| {
+            tracing::warn!("Failed to load last update check: {}", e);
+            None
+        });
+        let retry_interval = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(
+            app_state.settings.get_global_retry_interval_ms(),
+        ));
 
-        // Create message queue in app_data directory
-        let queue_path = if state_path.contains("test") || state_path.contains("tmp") {
+        // Create message queue in app_data directory (an inspected one keeps its files together)
+        let beside_state = inspection.is_some() || state_path.contains("test") || state_path.contains("tmp");
+        let queue_path = if beside_state {
             // For tests, use the test directory
             std::path::Path::new(&state_path)
                 .parent()
@@ -137,18 +440,61 @@ impl App {
             // Production: use ./app_data/message_queue.db
             "./app_data/message_queue.db".to_string()
         };
-        let queue = MessageQueue::new_with_path(&queue_path)?;
+        // Seals queued content for this identity (and any plain rows left by older versions)
+        let queue = match &mut inspection {
+            Some(inspection) => {
+                inspection.fingerprint = keypair.uid.to_string().chars().take(IDENTITY_FINGERPRINT_CHARS).collect();
+                MessageQueue::open_read_only(&queue_path, &keypair, &inspection.writes)?
+            }
+            None => MessageQueue::new_for_identity(&queue_path, &keypair)?,
+        };
+
+        // Saved message content goes next to the queue (./app_data/downloads in production)
+        let downloads_dir = if beside_state {
+            std::path::Path::new(&queue_path).with_file_name("downloads")
+        } else {
+            std::path::PathBuf::from(DOWNLOADS_DIR)
+        };
+        let save_dir = if beside_state {
+            downloads_dir.clone()
+        } else {
+            default_save_dir(Platform::current(), &PathEnv::from_env(), std::path::Path::is_dir)
+        };
+        let snapshots_dir = if beside_state {
+            std::path::Path::new(&queue_path).with_file_name("snapshots")
+        } else {
+            std::path::PathBuf::from(SNAPSHOTS_DIR)
+        };
+        let captures_dir = if beside_state {
+            std::path::Path::new(&queue_path).with_file_name("captures")
+        } else {
+            std::path::PathBuf::from(CAPTURES_DIR)
+        };
+        let runtime_info_path = if beside_state {
+            std::path::Path::new(&queue_path).with_file_name("runtime.json")
+        } else {
+            std::path::PathBuf::from(RUNTIME_INFO_FILE)
+        };
+        let session_marker_path = if beside_state {
+            std::path::Path::new(&queue_path).with_file_name("session.marker")
+        } else {
+            std::path::PathBuf::from(SESSION_MARKER_FILE)
+        };
 
         // Sync pending message flags with actual queue state
         if let Ok(pending_uids) = queue.get_pending_contact_uids() {
             app_state.sync_pending_status(&pending_uids);
         }
+        startup_timings.record("queue open", phase_start.elapsed());
 
         // Always start at main menu (retry worker handles queue silently in background)
         let current_screen = Screen::MainMenu;
         let startup_sync_screen = None;
+        let (delivery_events_tx, delivery_events) = std::sync::mpsc::channel();
+        let (startup_events_tx, startup_events) = std::sync::mpsc::channel();
+        let connectivity_events = ConnectivityEvents::new();
 
-        let app = Self {
+        let mut app = Self {
             current_screen,
             selected_index: 0,
             menu_items: MenuItem::all(),
@@ -160,24 +506,100 @@ impl App {
             import_contact_screen: None,
             chat_list_screen: None,
             chat_view_screen: None,
+            recent_chats: Vec::new(),
             settings_screen: None,
             diagnostics_screen: None,
+            snapshots_screen: None,
+            maintenance_screen: None,
+            undo: UndoList::new(),
+            capture_screen: None,
             startup_sync_screen,
             diagnostics_refresh_handle: None,
+            diagnostics_action_handle: None,
+            mapping_actions: std::sync::Arc::new(RouterMappingActions),
+            update_fetcher: std::sync::Arc::new(HttpManifestFetcher),
+            update_check_handle: None,
+            last_update_check,
+            update_notice: None,
             connectivity_result: None,
+            connectivity_rx: connectivity_events.subscribe(),
+            connectivity_events,
+            pending_connectivity_check: None,
+            health_check_handle: None,
+            startup: StartupGraph::new(),
+            startup_events_tx,
+            startup_events,
+            connectivity_indicator: ConnectivityIndicator::Starting,
+            indicator_transport: TransportServerStatus::NotStarted,
             local_port,
             state_path,
+            transports: TransportRegistry::new()
+                .with(std::sync::Arc::new(transport.clone()))
+                .with(std::sync::Arc::new(TlsTransport::new(transport.clone()))),
+            tls_fingerprint: None,
             transport,
             queue,
             storage,
             retry_worker_stop: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
             retry_worker_handle: None,
+            retry_wakeup,
+            retry_status: RetryStatus::new(),
+            retry_interval,
             transport_server_status: std::sync::Arc::new(std::sync::Mutex::new(TransportServerStatus::NotStarted)),
+            startup_timings,
+            messages_loaded: false,
+            notifications: Notifications::new(),
+            deferred_writes: DeferredWrites::new(),
+            incoming_updates: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            delivery_events_tx,
+            delivery_events,
+            last_reconcile: std::time::Instant::now(),
+            send_leases: SendLeases::new(),
+            last_lease_sweep: std::time::Instant::now(),
+            persisted_lease_generation: 0,
+            session_marker_path,
+            recovery_screen: None,
+            sending_as_confirmed: false,
+            downloads_dir,
+            snapshots_dir,
+            captures_dir,
+            runtime_info_path,
+            started_at: Utc::now(),
+            save_dir,
+            path_picker: None,
+            last_saved_path: None,
+            key_upgrades_requested: std::collections::HashSet::new(),
+            key_upgrade_requests: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
+            token_refresh_requests: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
+            expired_blocks_synced_at: None,
+            capability_probes: CapabilityProbes::new(),
+            capability_answers: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
+            heard_from: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
+            auto_import_limiter: std::sync::Arc::new(std::sync::Mutex::new(AutoImportLimiter::default())),
+            read_receipts_sent: std::collections::HashMap::new(),
+            typing_sent_at: std::collections::HashMap::new(),
+            effects: EffectQueue::new(),
+            pending_bells: Vec::new(),
+            notification_sink: Box::new(alerts::TerminalSink),
+            focus: FocusState::new(),
+            port_alert: std::sync::Arc::new(std::sync::Mutex::new(None)),
+            error_reports: ErrorReporter::new(),
+            log_file: None,
+            probe_requests: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
+            probe_results: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
+            probe_limiter: ProbeLimiter::new(),
+            pending_probes: Vec::new(),
+            inspection,
         };
 
         // Save initial state on first run
         if is_first_run {
-            let _ = app.save_state();
+            app.save_or_report();
+        }
+        app.sync_relay();
+        app.sync_auto_import_caps();
+        if app.inspection.is_none() {
+            app.apply_tls_settings(Utc::now());
         }
 
         Ok(app)
@@ -191,852 +613,5458 @@ impl App {
         Self::new_with_settings(None::<&str>)
     }
 
-    /// Save application state to SQLite database
-    ///
-    /// Persists user identity, contacts, chats, messages, and settings to pure2p.db
-    pub fn save_state(&self) -> Result<(), Box<dyn std::error::Error>> {
-        self.app_state.save_to_db(&self.storage)?;
-        Ok(())
-    }
-
-    /// Reload application state from SQLite database
+    /// Run the startup work deferred until after the first frame is drawn
     ///
-    /// This is called periodically to pick up changes made by transport handlers
-    /// (e.g., incoming messages from other peers)
-    ///
-    /// Note: Only reloads in production (file-based storage). Tests use in-memory
-    /// databases which don't share state between connections, so reloading would
-    /// create a fresh empty database.
-    pub fn reload_state(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        // Skip reload for test environments (in-memory databases don't share state)
-        if self.state_path.contains("test") || self.state_path.contains("tmp") {
-            return Ok(());
+    /// Loads message history and starts the startup graph: the transport
+    /// server starts right away, connectivity detection and the retry worker
+    /// once their stages' dependencies report done (see `poll_startup`).
+    pub fn complete_deferred_startup(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // An inspected directory is only read: no transport, workers, migrations or session
+        if self.inspection.is_some() {
+            return self.load_chat_messages();
         }
+        self.poll_startup(std::time::Instant::now());
 
-        // Load fresh state from database
-        let mut loaded_state = AppState::load_from_db(&self.storage)?;
-
-        // Preserve user identity and network info if not in DB yet
-        if loaded_state.user_keypair.is_none() {
-            loaded_state.user_keypair = self.app_state.user_keypair.clone();
-        }
-        if loaded_state.user_ip.is_none() {
-            loaded_state.user_ip = self.app_state.user_ip.clone();
-        }
+        let phase_start = std::time::Instant::now();
+        self.migrate_chat_summaries();
+        self.startup_timings.record("chat summaries", phase_start.elapsed());
 
-        // Sync pending message flags with actual queue state
-        if let Ok(pending_uids) = self.queue.get_pending_contact_uids() {
-            loaded_state.sync_pending_status(&pending_uids);
+        if let Err(e) = self.load_chat_messages() {
+            let _ = self.startup_events_tx.send(StartupEvent::degraded(StartupStage::Storage, e.to_string()));
+            self.poll_startup(std::time::Instant::now());
+            return Err(e);
         }
-
-        // Update app state
-        self.app_state = loaded_state;
-
+        // Recovery resumes an interrupted queue migration itself, so it goes first
+        self.start_session(Utc::now());
+        self.migrate_queue_schema();
+        let _ = self.startup_events_tx.send(StartupEvent::done(StartupStage::Storage, format!("{} chats", self.app_state.chats.len())));
+        let _ = self.startup_events_tx.send(StartupEvent::done(StartupStage::Identity, self.keypair.uid.to_string()));
+        self.poll_startup(std::time::Instant::now());
+
+        tracing::debug!("Startup completed in {:?}", self.startup_timings.total());
         Ok(())
     }
 
-    /// Start transport server in background with automatic retry on failure
-    ///
-    /// The server starts immediately and runs until app shutdown, independent of connectivity.
-    /// This ensures peers can always send messages to the shared contact endpoint.
-    ///
-    /// # Server Lifecycle
-    /// 1. Binds to preferred port (or tries up to 10 random ports if unavailable)
-    /// 2. Runs health check to verify server is listening
-    /// 3. Updates database with actual running port
-    /// 4. Stays running until app exit (tokio runtime kept alive with oneshot channel)
-    ///
-    /// # Connectivity Independence
-    /// - Server starts BEFORE connectivity detection
-    /// - Connectivity uses the actual running port to create port mappings
-    /// - Server continues running regardless of connectivity success/failure
+    /// Leave the session marker and, if the previous session ended
+    /// uncleanly, recover from it at once
     ///
-    /// If the preferred port fails, it will try alternative ports automatically.
-    /// Handlers are set up to automatically create chats when pings are received.
-    pub fn start_transport(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        // Set local UID for transport
-        let uid = self.keypair.uid.to_string();
-        let transport = self.transport.clone();
-        let preferred_port = self.local_port;
-        let state_path = self.state_path.clone();
-        let status = self.transport_server_status.clone();
-        let storage = self.storage.clone();
-
-        // Create storage instances for handlers (they need their own connections in separate threads)
-        let use_in_memory = state_path.contains("test") || state_path.contains("tmp");
-
-        // Mark as starting
-        *status.lock().unwrap() = TransportServerStatus::Starting;
-
-        // Setup ping handler and message handler to receive messages and pings
-        // Use a channel to keep the runtime alive until app exits
-        std::thread::spawn(move || {
-            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
-            let (_tx, rx) = tokio::sync::oneshot::channel::<()>();
-
-            rt.block_on(async move {
-                // Set local UID
-                transport.set_local_uid(uid).await;
-
-                // Setup ping handler
-                let use_in_memory_ping = use_in_memory;
-                transport.set_ping_handler(move |contact_token: String| {
-                    // Create storage connection for this handler
-                    let storage_result = if use_in_memory_ping {
-                        Storage::new_in_memory()
-                    } else {
-                        Storage::new_with_default_path()
-                    };
-
-                    if let Ok(storage) = storage_result {
-                        if let Ok(mut app_state) = AppState::load_from_db(&storage) {
-                            // Parse and verify the contact token
-                            match crate::storage::Contact::parse_token(&contact_token) {
-                                Ok(sender_contact) => {
-                                    tracing::info!("Received ping from {} at {}", sender_contact.uid, sender_contact.ip);
-
-                                    // Check if contact already exists
-                                    let contact_exists = app_state.contacts.iter().any(|c| c.uid == sender_contact.uid);
-
-                                    if !contact_exists {
-                                        // Auto-import the sender as a new contact
-                                        app_state.contacts.push(sender_contact.clone());
-                                        tracing::info!("Auto-imported contact {} from ping", sender_contact.uid);
-                                    }
-
-                                    // Create or get existing chat (active status, not pending)
-                                    let chat = app_state.get_or_create_chat(&sender_contact.uid);
-                                    chat.mark_unread(); // Mark as active (new ping received)
-                                    let _ = app_state.save_to_db(&storage);
-                                    tracing::info!("Created/updated chat for ping from {}", sender_contact.uid);
-                                }
-                                Err(e) => {
-                                    tracing::error!("Failed to parse contact token from ping: {}", e);
-                                }
-                            }
-                        }
-                    }
-                }).await;
-
-                // Setup message handler
-                let use_in_memory_msg = use_in_memory;
-                transport.set_new_message_handler(move |msg_req: crate::transport::MessageRequest| {
-                    // Create storage connection for this handler
-                    let storage_result = if use_in_memory_msg {
-                        Storage::new_in_memory()
-                    } else {
-                        Storage::new_with_default_path()
-                    };
-
-                    if let Ok(storage) = storage_result {
-                        if let Ok(mut app_state) = AppState::load_from_db(&storage) {
-                            // Get to_uid before borrowing app_state mutably
-                            let to_uid = app_state.user_keypair.as_ref().map(|kp| kp.uid.to_string()).unwrap_or_default();
-
-                            let chat = app_state.get_or_create_chat(&msg_req.from_uid);
-
-                            // Create message from the request
-                            let message = Message::new(
-                                uuid::Uuid::new_v4().to_string(),
-                                msg_req.from_uid.clone(),
-                                to_uid,
-                                msg_req.payload,
-                                Utc::now().timestamp_millis(),
-                            );
-
-                            chat.append_message(message);
-                            chat.mark_unread(); // Mark as unread (new message received)
-
-                            let _ = app_state.save_to_db(&storage);
-                            tracing::info!("Received message from {} (type: {})", msg_req.from_uid, msg_req.message_type);
-                        }
-                    }
-                }).await;
-
-                // Try to start server with automatic port retry
-                let mut current_port = preferred_port;
-                let max_retries = 10;
-                let mut last_error = String::new();
-                let mut bind_successful = false;
-
-                for attempt in 0..max_retries {
-                    let addr = format!("0.0.0.0:{}", current_port).parse().expect("Invalid address");
-
-                    tracing::info!("Attempting to start transport server on port {} (attempt {}/{})", current_port, attempt + 1, max_retries);
-
-                    match transport.clone().start(addr).await {
-                        Ok(_) => {
-                            tracing::info!("✓ Transport server successfully started on port {}", current_port);
-
-                            // Wait a moment for server to fully initialize
-                            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-                            // Verify server is actually listening by testing locally
-                            let test_url = format!("http://127.0.0.1:{}/health", current_port);
-                            match reqwest::Client::new()
-                                .get(&test_url)
-                                .timeout(std::time::Duration::from_secs(2))
-                                .send()
-                                .await
-                            {
-                                Ok(response) if response.status().is_success() => {
-                                    tracing::info!("✓ Transport server verified listening on port {}", current_port);
-                                    *status.lock().unwrap() = TransportServerStatus::Running(current_port);
-                                    bind_successful = true;
-
-                                    // Always update database and app state with actual running port
-                                    if let Ok(mut app_state) = AppState::load_from_db(&storage) {
-                                        app_state.user_port = current_port;
-                                        let _ = app_state.save_to_db(&storage);
-                                        tracing::info!("Updated database with running port: {}", current_port);
-                                    }
-                                    break;
-                                }
-                                Ok(response) => {
-                                    last_error = format!("Server started but health check returned: {}", response.status());
-                                    tracing::warn!("{}", last_error);
-                                }
-                                Err(e) => {
-                                    last_error = format!("Server started but health check failed: {}", e);
-                                    tracing::warn!("{}", last_error);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            last_error = format!("Port {} bind failed: {}", current_port, e);
-                            tracing::warn!("{}", last_error);
+    /// Runs every `RecoveryStep` in order (see `tui::recovery`), reports
+    /// what could not be fixed to the error log, and shows the Recovery
+    /// screen. After a clean exit only the send leases are checked, in case
+    /// a send was still running when the app was closed.
+    pub fn start_session(&mut self, now: DateTime<Utc>) {
+        if self.inspection.is_some() {
+            return;
+        }
+        let marker = SessionMarker::for_this_process(now);
+        let previous = self
+            .error_reports
+            .check(ErrorSeverity::Warning, "session", begin_session(&self.session_marker_path, &marker, process_alive))
+            .flatten();
+        let Some(previous) = previous else {
+            let outcome = self.recover_send_leases();
+            for problem in &outcome.problems {
+                self.error_reports.report(ErrorSeverity::Error, "recovery", problem.clone());
+            }
+            return;
+        };
 
-                            // Try a different random port
-                            current_port = AppState::generate_random_port();
-                            tracing::info!("Will retry with port {}", current_port);
-                        }
-                    }
+        tracing::warn!("Previous session (pid {}) ended uncleanly; recovering", previous.pid);
+        let report = self.run_recovery(previous);
+        tracing::info!("{}", report.headline());
+        for problem in report.problems() {
+            self.error_reports.report(ErrorSeverity::Error, "recovery", problem);
+        }
+        self.recovery_screen = Some(RecoveryScreen::new(report));
+        self.current_screen = Screen::Recovery;
+    }
 
-                    // Small delay between retries
-                    if attempt < max_retries - 1 {
-                        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-                    }
+    /// Run every recovery step, in order
+    pub fn run_recovery(&mut self, previous: SessionMarker) -> RecoveryReport {
+        let steps = RecoveryStep::ALL
+            .into_iter()
+            .map(|step| {
+                let outcome = match step {
+                    RecoveryStep::Journal => self.recover_journal(),
+                    RecoveryStep::SendLeases => self.recover_send_leases(),
+                    RecoveryStep::Integrity => self.recover_integrity(),
+                    RecoveryStep::Migration => self.recover_migration(),
+                    RecoveryStep::RuntimeFile => self.recover_runtime_file(),
+                };
+                for detail in &outcome.details {
+                    tracing::info!("Recovery: {}: {}", step.label(), detail);
                 }
+                outcome
+            })
+            .collect();
+        RecoveryReport { previous, steps }
+    }
 
-                if !bind_successful {
-                    let final_error = format!("Failed to start transport server after {} attempts. Last error: {}", max_retries, last_error);
-                    tracing::error!("{}", final_error);
-                    *status.lock().unwrap() = TransportServerStatus::Failed(final_error);
-                } else {
-                    // Keep runtime alive by waiting on channel (which never receives)
-                    // This ensures the spawned server task continues running
-                    tracing::info!("Transport server is running, keeping runtime alive");
-                    let _ = rx.await;
+    /// Check the delivery journal's hash chain, which a crash can cut short
+    fn recover_journal(&self) -> StepOutcome {
+        let outcome = StepOutcome::new(RecoveryStep::Journal);
+        match self.storage.load_journal(None, None) {
+            Ok(records) if records.is_empty() => outcome.detail("journal is empty"),
+            Ok(records) => match verify_chain(&records) {
+                None => outcome
+                    .done("verified the delivery journal")
+                    .detail(format!("{} intact", count_phrase(records.len(), "record", "records"))),
+                Some(broken) => outcome.problem(broken.to_string()),
+            },
+            Err(e) => outcome.problem(format!("could not be read: {}", e)),
+        }
+    }
+
+    /// Queue the messages whose send was in flight when the process ended
+    fn recover_send_leases(&mut self) -> StepOutcome {
+        let mut outcome = StepOutcome::new(RecoveryStep::SendLeases);
+        let stored = match self.storage.load_send_leases() {
+            Ok(stored) => stored,
+            Err(e) => return outcome.problem(format!("could not be read: {}", e)),
+        };
+        let mut requeued = 0;
+        for (message_id, contact_uid, started_at) in stored {
+            let lease = SendLease { message_id, contact_uid, started_at };
+            outcome = match self.requeue_unfinished_send(&lease) {
+                Ok(UnfinishedSend::Queued) => {
+                    requeued += 1;
+                    outcome.detail(format!("queued {} to {} for retry", lease.message_id, lease.contact_uid))
                 }
-            });
-        });
+                Ok(UnfinishedSend::AlreadyQueued) => outcome.detail(format!("{} was already queued", lease.message_id)),
+                Ok(UnfinishedSend::Gone) => outcome.detail(format!("{} is no longer in its chat", lease.message_id)),
+                Err(e) => outcome.problem(format!("{} to {} could not be queued: {}", lease.message_id, lease.contact_uid, e)),
+            };
+        }
+        if requeued > 0 {
+            outcome = outcome.done(format!("recovered {}", count_phrase(requeued, "unsent message", "unsent messages")));
+        }
+        // Leases of this session's sends are stored again as they start
+        if let Err(e) = self.storage.replace_send_leases(&[]) {
+            outcome = outcome.problem(format!("could not be cleared: {}", e));
+        }
+        self.persisted_lease_generation = 0;
+        self.persist_send_leases();
+        outcome
+    }
 
-        Ok(())
+    /// Run SQLite's quick check and the identity scan
+    fn recover_integrity(&mut self) -> StepOutcome {
+        let mut outcome = StepOutcome::new(RecoveryStep::Integrity);
+        let report = match self.storage.verify_integrity() {
+            Ok(report) => report,
+            Err(e) => return outcome.problem(format!("check could not run: {}", e)),
+        };
+        if report.is_clean() {
+            return outcome.done("verified database integrity").detail("quick check passed, identities consistent");
+        }
+        for problem in &report.problems {
+            outcome = outcome.problem(format!("quick check: {}", problem));
+        }
+        for conflict in report.conflicts {
+            let uid = &conflict.contact.uid;
+            outcome = outcome.problem(format!(
+                "contact {}: {}, held for review",
+                &uid[..uid.len().min(IDENTITY_FINGERPRINT_CHARS)],
+                conflict.kind
+            ));
+            self.app_state.record_identity_conflict(conflict);
+        }
+        if let Err(e) = self.storage.save_identity_conflicts(&self.app_state.identity_conflicts) {
+            outcome = outcome.problem(format!("conflicts could not be saved: {}", e));
+        }
+        outcome
     }
 
-    /// Get the actual running port from transport server (or configured port if not running yet)
-    pub fn get_actual_port(&self) -> u16 {
-        if let Ok(status) = self.transport_server_status.lock() {
-            if let TransportServerStatus::Running(port) = *status {
-                return port;
+    /// Finish a queue migration the previous session started
+    fn recover_migration(&mut self) -> StepOutcome {
+        let outcome = StepOutcome::new(RecoveryStep::Migration);
+        let rows_done = match self.queue.interrupted_migration() {
+            Ok(Some(rows_done)) => rows_done,
+            Ok(None) => return outcome,
+            Err(e) => return outcome.problem(format!("progress could not be read: {}", e)),
+        };
+        let result = self.queue.migrate_schema(|progress| {
+            if progress.done % QUEUE_MIGRATION_PROGRESS_STEP == 0 || progress.is_complete() {
+                tracing::info!("Migrating message queue: {}/{} rows", progress.done, progress.total);
             }
+            std::ops::ControlFlow::Continue(())
+        });
+        match result {
+            Ok(migration) => outcome
+                .done("resumed 1 migration")
+                .detail(format!("continued from row {} of {}, now at version {}", rows_done, migration.total, migration.version)),
+            Err(e) => outcome.problem(format!("stopped at row {}: {}", rows_done, e)),
         }
-        self.local_port
     }
 
-    /// Trigger startup connectivity diagnostics (non-blocking)
+    /// Remove a runtime info file naming a process that is gone
+    fn recover_runtime_file(&self) -> StepOutcome {
+        let outcome = StepOutcome::new(RecoveryStep::RuntimeFile);
+        let stale = match read_runtime_info(&self.runtime_info_path) {
+            // The transport thread may already have written ours
+            Ok(Some(info)) if info.pid == std::process::id() || process_alive(info.pid) => return outcome,
+            Ok(Some(info)) => format!("removed the file of pid {} (port {})", info.pid, info.port),
+            Ok(None) => return outcome,
+            Err(_) => "removed an unreadable file".to_string(),
+        };
+        match std::fs::remove_file(&self.runtime_info_path) {
+            Ok(()) => outcome.done("removed a stale runtime file").detail(stale),
+            Err(e) => outcome.problem(format!("{} could not be removed: {}", self.runtime_info_path.display(), e)),
+        }
+    }
+
+    /// Remove the session marker: the exit is clean
     ///
-    /// Should be called after App::new() to detect external IP for contact sharing.
-    /// Waits for transport server to be running before starting connectivity check.
-    pub fn trigger_startup_connectivity(&mut self) {
-        // Don't start if already running
-        if self.diagnostics_refresh_handle.is_some() {
+    /// Called once state is saved; not from `Drop`, so a panic still
+    /// leaves the marker behind.
+    pub fn end_session(&self) {
+        if self.inspection.is_some() {
             return;
         }
+        if let Err(e) = end_session(&self.session_marker_path, std::process::id()) {
+            tracing::warn!("Failed to remove {}: {}", self.session_marker_path.display(), e);
+        }
+    }
 
-        // Wait for transport server to be running before checking connectivity
-        let status = self.transport_server_status.clone();
-        let port = self.local_port;
-
-        // Spawn background thread with tokio runtime
-        let handle = std::thread::spawn(move || {
-            // Wait up to 30 seconds for transport server to start
-            for _ in 0..60 {
-                if let Ok(current_status) = status.lock() {
-                    if let TransportServerStatus::Running(actual_port) = *current_status {
-                        // Server is running, use the actual port
-                        let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
-                        return rt.block_on(async move {
-                            crate::connectivity::establish_connectivity(actual_port).await
-                        });
-                    } else if matches!(*current_status, TransportServerStatus::Failed(_)) {
-                        // Server failed, return empty result
-                        tracing::error!("Transport server failed to start, skipping connectivity check");
-                        return crate::connectivity::ConnectivityResult::new();
-                    }
-                }
-                std::thread::sleep(std::time::Duration::from_millis(500));
+    /// Refuse `action` while inspecting, saying why in the banner
+    ///
+    /// # Returns
+    /// Whether the action was refused (the caller does nothing more)
+    pub fn refuse_in_inspection(&mut self, action: &str) -> bool {
+        match &mut self.inspection {
+            Some(inspection) => {
+                inspection.refuse(action, std::time::Instant::now());
+                true
             }
+            None => false,
+        }
+    }
 
-            // Timeout waiting for server, use configured port anyway
-            tracing::warn!("Timeout waiting for transport server, using configured port {}", port);
-            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
-            rt.block_on(async move {
-                crate::connectivity::establish_connectivity(port).await
-            })
-        });
+    /// Leave the Recovery screen for the main menu
+    pub fn dismiss_recovery_screen(&mut self) {
+        self.recovery_screen = None;
+        self.current_screen = Screen::MainMenu;
+    }
 
-        self.diagnostics_refresh_handle = Some(handle);
+    /// Leave the Recovery screen for the error log in Diagnostics
+    pub fn open_recovery_logs(&mut self) {
+        self.recovery_screen = None;
+        self.show_diagnostics_screen();
+        if let Some(screen) = &mut self.diagnostics_screen {
+            screen.show_error_log = true;
+        }
     }
 
-    /// Poll for startup connectivity completion and update local IP
-    ///
-    /// Returns true if connectivity completed this call.
-    pub fn poll_startup_connectivity(&mut self) -> bool {
-        if let Some(handle) = self.diagnostics_refresh_handle.take() {
-            if handle.is_finished() {
-                match handle.join() {
-                    Ok(result) => {
-                        // Update local_ip and port from the mapping result
-                        if let Some(mapping) = &result.mapping {
-                            let detected_ip = format!("{}:{}", mapping.external_ip, mapping.external_port);
-                            self.local_ip = detected_ip.clone();
-
-                            // Start retry worker now that connectivity is established
-                            if self.retry_worker_handle.is_none() {
-                                if let Err(e) = self.start_retry_worker() {
-                                    tracing::error!("Failed to start retry worker: {}", e);
-                                }
-                            }
+    /// Sender for reporting startup stages from other threads
+    pub fn startup_event_sender(&self) -> std::sync::mpsc::Sender<StartupEvent> {
+        self.startup_events_tx.clone()
+    }
 
-                            // Save detected IP and port to app_state for persistence
-                            self.app_state.user_ip = Some(detected_ip);
-                            self.app_state.user_port = mapping.external_port;
-                            let _ = self.save_state();
-
-                            // Run health check AFTER transport server has started (give it 1 second)
-                            // This verifies that our port is actually reachable from external networks
-                            tracing::info!("Scheduling external reachability health check...");
-                            let result_for_health = result.clone();
-                            std::thread::spawn(move || {
-                                // Wait for transport server to fully start
-                                std::thread::sleep(std::time::Duration::from_secs(2));
-
-                                let runtime = tokio::runtime::Runtime::new().unwrap();
-                                let verified_result = runtime.block_on(async {
-                                    crate::connectivity::verify_connectivity_health(result_for_health).await
-                                });
-
-                                if verified_result.externally_reachable == Some(true) {
-                                    tracing::info!("✓ External reachability confirmed - you can receive messages!");
-                                } else if verified_result.externally_reachable == Some(false) {
-                                    tracing::warn!("✗ Port is NOT reachable from external networks");
-                                    tracing::warn!("   You may need to manually configure port forwarding on your router");
-                                } else {
-                                    tracing::warn!("⚠ Health check inconclusive");
-                                }
-                            });
-                        }
-                        self.connectivity_result = Some(result.clone());
+    /// Apply the stage reports that arrived, time out stalled stages at
+    /// `now`, and run the work of the stages that can start
+    ///
+    /// Called every loop iteration; cheap once every stage has settled.
+    ///
+    /// # Returns
+    /// Whether any stage changed
+    pub fn poll_startup(&mut self, now: std::time::Instant) -> bool {
+        if self.inspection.is_some() {
+            return false;
+        }
+        let mut changed = false;
+        while let Ok(event) = self.startup_events.try_recv() {
+            changed |= self.startup.record(event, now);
+        }
+        if self.startup.is_settled() {
+            return changed;
+        }
+        for stage in self.startup.advance(now) {
+            changed = true;
+            self.run_startup_stage(stage, now);
+        }
+        changed
+    }
 
-                        // Apply result to diagnostics screen if it's already open
-                        self.apply_connectivity_result(result);
-                        return true;
-                    }
-                    Err(_) => {
-                        // Failed to get connectivity, keep default local_ip
-                        return true;
-                    }
+    /// Start the work of `stage`, whose dependencies are all done
+    fn run_startup_stage(&mut self, stage: StartupStage, now: std::time::Instant) {
+        match stage {
+            // Reported by `complete_deferred_startup` and the transport thread
+            StartupStage::Storage | StartupStage::Identity | StartupStage::TransportListening => {}
+            StartupStage::HandlersRegistered => {
+                let phase_start = std::time::Instant::now();
+                if let Err(e) = self.start_transport() {
+                    let _ = self.startup_events_tx.send(StartupEvent::degraded(stage, e.to_string()));
                 }
-            } else {
-                // Thread still running, put it back
-                self.diagnostics_refresh_handle = Some(handle);
+                self.startup_timings.record("transport start", phase_start.elapsed());
+            }
+            StartupStage::Connectivity => {
+                let phase_start = std::time::Instant::now();
+                self.trigger_startup_connectivity();
+                self.startup_timings.record("connectivity trigger", phase_start.elapsed());
             }
+            StartupStage::ReachabilityCheck => self.start_health_check(),
+            StartupStage::RetryWorker => self.start_connected_workers(),
         }
-        false
+        // Work that reported synchronously settles its stage right away
+        self.poll_startup(now);
     }
 
-
-    /// Get currently selected menu item
-    pub fn selected_item(&self) -> MenuItem {
-        self.menu_items[self.selected_index]
+    /// Settle the startup connectivity stage with the first check's outcome
+    ///
+    /// A mapping found after the stage was degraded still starts the retry
+    /// worker, which was held back for want of one.
+    fn settle_startup_connectivity(&mut self, result: std::result::Result<&ConnectivityResult, String>) {
+        let event = match result {
+            Ok(result) => match &result.mapping {
+                Some(mapping) => StartupEvent::done(
+                    StartupStage::Connectivity,
+                    format!("{}:{}", mapping.external_ip, mapping.external_port),
+                ),
+                None => StartupEvent::degraded(StartupStage::Connectivity, "no port mapping"),
+            },
+            Err(e) => StartupEvent::degraded(StartupStage::Connectivity, e),
+        };
+        let late = matches!(self.startup.state(StartupStage::Connectivity), StageState::Degraded { .. });
+        if self.startup.record(event, std::time::Instant::now())
+            && late
+            && matches!(self.startup.state(StartupStage::Connectivity), StageState::Done { .. })
+            && self.retry_worker_handle.is_none()
+        {
+            self.start_connected_workers();
+        }
     }
 
-    /// Move to next menu item
+    /// Start the retry worker and announce ourselves, once connectivity found a mapping
+    fn start_connected_workers(&mut self) {
+        if self.retry_worker_handle.is_some() {
+            return;
+        }
+        let event = match self.start_retry_worker() {
+            Ok(()) => StartupEvent::done(StartupStage::RetryWorker, ""),
+            Err(e) => {
+                tracing::error!("Failed to start retry worker: {}", e);
+                StartupEvent::degraded(StartupStage::RetryWorker, e.to_string())
+            }
+        };
+        let _ = self.startup_events_tx.send(event);
+        if self.app_state.settings.relay_enabled {
+            self.advertise_relay_capabilities();
+        }
+        self.announce_presence();
+    }
+
+    /// Check from outside that the startup mapping is reachable (background)
+    ///
+    /// Runs once connectivity is done, and so after the server listens on
+    /// the mapped port; the verdict also settles the reachability stage.
+    fn start_health_check(&mut self) {
+        let Some(result) = self.connectivity_result.clone().filter(|result| result.mapping.is_some()) else {
+            let _ = self.startup_events_tx.send(StartupEvent::degraded(StartupStage::ReachabilityCheck, "no mapping to check"));
+            return;
+        };
+        tracing::info!("Running external reachability health check...");
+        let events = self.connectivity_events.clone();
+        let startup_events = self.startup_events_tx.clone();
+        self.health_check_handle = Some(std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let verified_result = runtime.block_on(crate::connectivity::verify_connectivity_health_with(
+                result,
+                &SystemStrategies,
+                &events,
+            ));
+
+            let verdict = match verified_result.externally_reachable {
+                Some(true) => {
+                    tracing::info!("✓ External reachability confirmed - you can receive messages!");
+                    "reachable"
+                }
+                Some(false) => {
+                    tracing::warn!("✗ Port is NOT reachable from external networks");
+                    tracing::warn!("   You may need to manually configure port forwarding on your router");
+                    "not reachable from outside"
+                }
+                None => {
+                    tracing::warn!("⚠ Health check inconclusive");
+                    "inconclusive"
+                }
+            };
+            let _ = startup_events.send(StartupEvent::done(StartupStage::ReachabilityCheck, verdict));
+        }));
+    }
+
+    /// Build the chat summaries missing from a database written by an older version
+    ///
+    /// Logs progress every `SUMMARY_PROGRESS_STEP` chats; an interrupted run
+    /// continues on the next start.
+    fn migrate_chat_summaries(&self) {
+        let result = self.storage.migrate_chat_summaries(|progress| {
+            if progress.done % SUMMARY_PROGRESS_STEP == 0 || progress.is_complete() {
+                tracing::info!("Building chat summaries: {}/{} chats", progress.done, progress.total);
+            }
+            std::ops::ControlFlow::Continue(())
+        });
+        self.error_reports.check(ErrorSeverity::Warning, "chat summaries", result);
+    }
+
+    /// Path of the message queue database (next to the state file in tests and when inspecting)
+    fn queue_path(&self) -> String {
+        if self.inspection.is_some() || self.state_path.contains("test") || self.state_path.contains("tmp") {
+            std::path::Path::new(&self.state_path)
+                .parent()
+                .and_then(|p| p.to_str())
+                .map(|p| format!("{}/message_queue.db", p))
+                .unwrap_or_else(|| "message_queue.db".to_string())
+        } else {
+            "./app_data/message_queue.db".to_string()
+        }
+    }
+
+    /// Migrate a queue too large to migrate on open, in the background
+    ///
+    /// Batches pause queue writers briefly (see `queue_migration`), so the
+    /// retry worker keeps delivering meanwhile. Logs progress every
+    /// `QUEUE_MIGRATION_PROGRESS_STEP` rows; an interrupted run continues on
+    /// the next start.
+    fn migrate_queue_schema(&self) {
+        if self.queue.schema_version().is_ok_and(|version| version >= QUEUE_SCHEMA_VERSION) {
+            return;
+        }
+        let queue_path = self.queue_path();
+        let error_reports = self.error_reports.clone();
+        std::thread::spawn(move || {
+            let result = MessageQueue::new_with_path(&queue_path).and_then(|mut queue| {
+                queue.migrate_schema(|progress| {
+                    if progress.done % QUEUE_MIGRATION_PROGRESS_STEP == 0 || progress.is_complete() {
+                        tracing::info!("Migrating message queue: {}/{} rows", progress.done, progress.total);
+                    }
+                    std::ops::ControlFlow::Continue(())
+                })
+            });
+            error_reports.check(ErrorSeverity::Warning, "queue migration", result);
+        });
+    }
+
+    /// Load message history for all chats loaded as headers at startup
+    ///
+    /// Messages added in memory before history finished loading are kept.
+    pub fn load_chat_messages(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.messages_loaded {
+            return Ok(());
+        }
+
+        let phase_start = std::time::Instant::now();
+        Self::merge_stored_messages(&self.storage, &mut self.app_state.chats)?;
+        self.messages_loaded = true;
+        self.startup_timings.record("message load", phase_start.elapsed());
+        Ok(())
+    }
+
+    /// Prepend stored message history to chats, keeping messages only held in memory
+    ///
+    /// Stored messages already trimmed in memory by the history limit (not yet
+    /// saved) stay out.
+    fn merge_stored_messages(storage: &Storage, chats: &mut [crate::storage::Chat]) -> crate::Result<()> {
+        for chat in chats {
+            let mut messages = storage.load_chat_messages(&chat.contact_uid)?;
+            if let Some(trimmed_before) = chat.trimmed_before {
+                messages.retain(|m| m.timestamp >= trimmed_before || m.pinned || m.is_system());
+            }
+            for message in chat.messages.iter().cloned() {
+                if !messages.iter().any(|m| m.id == message.id) {
+                    messages.push(message);
+                }
+            }
+            chat.messages = std::sync::Arc::new(messages);
+        }
+        Ok(())
+    }
+
+    /// Save application state to SQLite database
+    ///
+    /// Persists user identity, contacts, chats, messages, and settings to pure2p.db.
+    /// If the database stays locked by another process after retries, the change
+    /// is kept in memory and counted in `deferred_writes` until
+    /// `flush_deferred_writes` succeeds.
+    pub fn save_state(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // Nothing is saved while inspecting; actions that change state are refused
+        if self.inspection.is_some() {
+            return Ok(());
+        }
+        self.sync_relay();
+        match self.write_state() {
+            Ok(()) => {
+                self.deferred_writes.flushed();
+                Ok(())
+            }
+            Err(e) if is_busy_error(&e) => {
+                self.deferred_writes.record(&e);
+                tracing::warn!(
+                    "Database locked, deferring write ({} changes pending)",
+                    self.deferred_writes.pending_count()
+                );
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Save state, reporting a failure instead of returning it
+    ///
+    /// For callers that have nothing better to do with the error than show it.
+    pub fn save_or_report(&mut self) {
+        if let Err(e) = self.save_state() {
+            self.error_reports.report(ErrorSeverity::Error, "storage", format!("Failed to save: {}", e));
+        }
+    }
+
+    /// Reload state, reporting a failure instead of returning it
+    pub fn reload_or_report(&mut self) {
+        if let Err(e) = self.reload_state() {
+            self.error_reports.report(ErrorSeverity::Warning, "storage", format!("Failed to reload: {}", e));
+        }
+    }
+
+    /// Error banner to show, following `Settings::error_banner_severity`
+    pub fn error_banner(&self) -> Option<ErrorBanner> {
+        self.error_reports.banner(self.app_state.settings.error_banner_severity)
+    }
+
+    /// Dismiss the error banner
+    ///
+    /// # Returns
+    /// Whether a banner was showing (and the key was used)
+    pub fn dismiss_error_banner(&mut self) -> bool {
+        if self.error_banner().is_none() {
+            return false;
+        }
+        self.error_reports.dismiss();
+        true
+    }
+
+    /// Retry deferred writes once the database may be writable again
+    ///
+    /// Attempts are throttled to `DEFERRED_FLUSH_INTERVAL`.
+    pub fn flush_deferred_writes(&mut self) {
+        if self.inspection.is_some() || !self.deferred_writes.start_flush_attempt(std::time::Instant::now()) {
+            return;
+        }
+        match self.write_state() {
+            Ok(()) => {
+                tracing::info!(
+                    "Database writable again, flushed {} deferred changes",
+                    self.deferred_writes.pending_count()
+                );
+                self.deferred_writes.flushed();
+            }
+            Err(e) => self.deferred_writes.flush_failed(&e),
+        }
+    }
+
+    fn write_state(&self) -> crate::Result<()> {
+        if self.messages_loaded {
+            self.app_state.save_to_db(&self.storage)
+        } else {
+            // Saving rewrites chats with their messages, so include the history
+            // that has not been loaded yet instead of dropping it
+            let mut state = self.app_state.clone();
+            Self::merge_stored_messages(&self.storage, &mut state.chats)?;
+            state.save_to_db(&self.storage)
+        }
+    }
+
+    /// Reload application state from SQLite database
+    ///
+    /// This is called periodically to pick up changes made by transport handlers
+    /// (e.g., incoming messages from other peers)
+    ///
+    /// Note: Only reloads in production (file-based storage). Tests use in-memory
+    /// databases which don't share state between connections, so reloading would
+    /// create a fresh empty database.
+    pub fn reload_state(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // Skip reload for test environments (in-memory databases don't share state),
+        // and for an inspected database, which nothing changes
+        if self.state_path.contains("test") || self.state_path.contains("tmp") || self.inspection.is_some() {
+            return Ok(());
+        }
+
+        // Reloading would discard changes that are only held in memory
+        if self.deferred_writes.is_pending() {
+            return Ok(());
+        }
+
+        // Load fresh state from database
+        let mut loaded_state = AppState::load_from_db(&self.storage)?;
+
+        // Preserve user identity and network info if not in DB yet
+        if loaded_state.user_keypair.is_none() {
+            loaded_state.user_keypair = self.app_state.user_keypair.clone();
+        }
+        if loaded_state.user_ip.is_none() {
+            loaded_state.user_ip = self.app_state.user_ip.clone();
+        }
+
+        // Pending/failed flags follow delivery events; the database copy may lag
+        for chat in &mut loaded_state.chats {
+            if let Some(known) = self.app_state.get_chat(&chat.contact_uid) {
+                chat.has_pending_messages = known.has_pending_messages;
+                chat.has_failed_messages = known.has_failed_messages;
+            }
+        }
+
+        // Notify about messages that arrived since the last load
+        if self.messages_loaded {
+            self.notify_new_messages(&loaded_state);
+        }
+
+        // Update app state
+        self.app_state = loaded_state;
+        self.messages_loaded = true;
+        self.sync_relay();
+        self.sync_auto_import_caps();
+
+        Ok(())
+    }
+
+    /// Give the transport's relay the current relay setting and contacts
+    pub fn sync_relay(&self) {
+        let relay = self.transport.relay();
+        let mut relay = relay.lock().unwrap();
+        relay.set_enabled(self.app_state.settings.relay_enabled);
+        relay.set_contacts(&self.app_state.contacts);
+    }
+
+    /// Give the transport our TLS certificate and strict mode from settings
+    ///
+    /// With TLS on, the stored certificate is used unless it is missing,
+    /// unreadable or due for renewal at `now`, in which case a new one is
+    /// generated and stored. Tokens handed out afterwards pin its fingerprint.
+    pub fn apply_tls_settings(&mut self, now: chrono::DateTime<Utc>) {
+        let settings = &self.app_state.settings;
+        self.transport.set_require_tls_external(settings.require_tls_external);
+        let identity = if settings.tls_enabled {
+            self.error_reports.check(ErrorSeverity::Error, "tls", self.load_or_renew_tls_identity(now))
+        } else {
+            None
+        };
+        let served = self
+            .error_reports
+            .check(ErrorSeverity::Error, "tls", self.transport.set_tls_identity(identity.as_ref()));
+        self.tls_fingerprint = identity
+            .filter(|_| served.is_some())
+            .map(|identity| identity.fingerprint().to_string());
+    }
+
+    /// Our stored TLS certificate, replaced by a new one when due
+    fn load_or_renew_tls_identity(&self, now: chrono::DateTime<Utc>) -> crate::Result<TlsIdentity> {
+        let stored = self
+            .storage
+            .load_tls_identity()?
+            .and_then(|(cert_pem, key_pem)| TlsIdentity::from_pem(&cert_pem, &key_pem).ok())
+            .filter(|identity| !identity.needs_renewal(now));
+        if let Some(identity) = stored {
+            return Ok(identity);
+        }
+        let identity = TlsIdentity::generate(now)?;
+        self.storage.save_tls_identity(identity.cert_pem(), identity.key_pem())?;
+        tracing::info!("Generated TLS certificate {} (valid until {})", identity.fingerprint(), identity.not_after());
+        Ok(identity)
+    }
+
+    /// Give the auto-import limiter the caps from settings
+    pub fn sync_auto_import_caps(&self) {
+        let settings = &self.app_state.settings;
+        self.auto_import_limiter
+            .lock()
+            .unwrap()
+            .set_caps(settings.auto_import_contacts_per_hour, settings.auto_import_chats_per_hour);
+    }
+
+    /// Lift the auto-import caps for `AUTO_IMPORT_LIFT_MINUTES`, or restore them
+    ///
+    /// Not saved: a restart restores the caps.
+    ///
+    /// # Returns
+    /// When the lift ends, if lifted
+    pub fn lift_auto_import_caps(&self, lift: bool, now: chrono::DateTime<Utc>) -> Option<chrono::DateTime<Utc>> {
+        let until = lift.then(|| now + chrono::Duration::minutes(AUTO_IMPORT_LIFT_MINUTES));
+        self.auto_import_limiter.lock().unwrap().lift_until(until);
+        until
+    }
+
+    /// Tell every contact whether we relay for them and whom we reach
+    ///
+    /// Temporary contacts only learn about edit support and restricted ones
+    /// learn nothing: we neither relay for them nor list them to others
+    /// (see `RelayCapabilities::for_contact`).
+    ///
+    /// Best effort: contacts that cannot be reached learn it the next time
+    /// capabilities are advertised (startup or when the setting changes).
+    pub fn advertise_relay_capabilities(&self) {
+        let enabled = self.app_state.settings.relay_enabled;
+        let contacts = self.app_state.contacts.clone();
+        let my_uid = self.keypair.uid.to_string();
+        let transports = self.transports.clone();
+
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+            rt.block_on(async move {
+                for contact in contacts.iter().filter(|c| !c.is_expired()) {
+                    let capabilities = RelayCapabilities::for_contact(enabled, &contacts, &contact.uid);
+                    let payload = match capabilities.to_payload() {
+                        Ok(payload) => payload,
+                        Err(e) => {
+                            tracing::error!("Failed to encode relay capabilities: {}", e);
+                            return;
+                        }
+                    };
+                    let request = MessageRequest {
+                        from_uid: my_uid.clone(),
+                        message_type: RELAY_CAPABILITIES_TYPE.to_string(),
+                        payload,
+                        metadata: Default::default(),
+                        message_id: None,
+                    };
+                    if let Err(e) = transports.send_message(contact, &request).await {
+                        tracing::debug!("Could not advertise relay capabilities to {}: {}", contact.uid, e);
+                    }
+                }
+            });
+        });
+    }
+
+    /// Raise a notification and an in-app alert for chats with new incoming messages in `loaded_state`
+    ///
+    /// Muted chats raise neither. Alerts follow `Settings::alert_mode` and
+    /// skip the open chat and quiet hours (see `alerts::should_alert`); when
+    /// several chats alert at once, the loudest of their styles plays.
+    fn notify_new_messages(&mut self, loaded_state: &AppState) {
+        let my_uid = self.keypair.uid.to_string();
+        // An open chat in a background window is not being read either
+        let open_chat = self.open_chat_uid().filter(|_| self.focus.is_focused());
+        let mut alert: Option<AlertStyle> = None;
+        for chat in &loaded_state.chats {
+            let known = self.app_state.get_chat(&chat.contact_uid);
+            let new_count = chat.messages
+                .iter()
+                .filter(|m| m.sender != my_uid && !m.is_system())
+                .filter(|m| !known.is_some_and(|k| k.messages.iter().any(|km| km.id == m.id)))
+                .count();
+            if new_count == 0 {
+                continue;
+            }
+
+            if self.app_state.settings.enable_notifications && !chat.muted {
+                let uid_short = &chat.contact_uid[..16.min(chat.contact_uid.len())];
+                self.notifications.notify(format!("{} new message(s) from {}", new_count, uid_short));
+            }
+            if alerts::should_alert(&chat.contact_uid, open_chat.as_deref(), chat.muted, self.notifications.quiet) {
+                let style = alerts::resolve_alert_style(
+                    self.app_state.contact_by_uid(&chat.contact_uid).and_then(|c| c.alert_style),
+                    self.app_state.settings.alert_style,
+                    chat.muted,
+                    self.notifications.quiet,
+                );
+                if alert.is_none_or(|loudest| style.loudness() > loudest.loudness()) {
+                    alert = Some(style);
+                }
+            }
+        }
+
+        if let Some(style) = alert {
+            self.play_alert(style, std::time::Instant::now());
+        }
+    }
+
+    /// Chat open in the chat view, if that is the current screen
+    pub fn open_chat_uid(&self) -> Option<String> {
+        if self.current_screen != Screen::ChatView {
+            return None;
+        }
+        self.chat_view_screen.as_ref().map(|screen| screen.contact_uid.clone())
+    }
+
+    /// Alert for a new message in the global default style (`Settings::alert_style`)
+    pub fn raise_alert(&mut self, now: std::time::Instant) {
+        self.play_alert(self.app_state.settings.alert_style, now);
+    }
+
+    /// Alert in `style` on the channels `Settings::alert_mode` allows
+    ///
+    /// The sink decides the bells and flash. The flash is queued as a UI
+    /// effect, or counted for `focus_gained` while the window is in the
+    /// background; bells are left for the main loop to ring (`take_bell`),
+    /// which knows whether stdout is a terminal.
+    pub fn play_alert(&mut self, style: AlertStyle, now: std::time::Instant) {
+        let signal = self.notification_sink.signal(style, self.app_state.settings.alert_mode);
+        self.pending_bells.extend(signal.bells.iter().map(|delay| now + *delay));
+        if let Some(flash) = signal.flash {
+            if self.focus.is_focused() {
+                self.effects.push(flash, now);
+            } else {
+                self.focus.miss_alert();
+            }
+        }
+    }
+
+    /// The terminal window went to the background
+    pub fn focus_lost(&mut self) {
+        self.focus.lost();
+    }
+
+    /// The terminal window came back to the front
+    ///
+    /// Flashes missed meanwhile are replayed as one, with a notification
+    /// counting them, and the open chat is marked read if its newest
+    /// message is on screen. The caller redraws the whole terminal.
+    pub fn focus_gained(&mut self, now: std::time::Instant) {
+        let missed = self.focus.gained();
+        if missed > 0 {
+            self.effects.push(UiEffect::HeaderFlash, now);
+            self.notifications.notify(format!("{} new message alert(s) while away", missed));
+        }
+        if let Some(contact_uid) = self.open_chat_uid() {
+            let at_bottom = self.chat_view_screen.as_ref().zip(self.app_state.get_chat(&contact_uid))
+                .is_some_and(|(screen, chat)| screen.is_at_bottom(chat));
+            if at_bottom {
+                self.send_read_receipt(&contact_uid);
+            }
+        }
+    }
+
+    /// Take one bell that is due, if any (an urgent alert rings several)
+    pub fn take_bell(&mut self) -> bool {
+        let now = std::time::Instant::now();
+        match self.pending_bells.iter().position(|due| *due <= now) {
+            Some(index) => {
+                self.pending_bells.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Store the sender of an incoming ping
+    ///
+    /// The token's signature and UID are verified, then the sender goes
+    /// through `AppState::ingest_contact`: new senders are auto-imported and
+    /// known ones get their address refreshed (or staged, for verified
+    /// contacts under address review). Their chat is created or marked
+    /// active, except for senders held back as identity conflicts. A new
+    /// contact or chat must get past `limiter`, counted against `source` (the
+    /// socket address of the ping, not the one its token advertises); when
+    /// refused, nothing is stored and the request log's hourly summary is
+    /// updated.
+    ///
+    /// # Errors
+    /// Returns an error if the token is invalid, its UID does not match its
+    /// key, or the database cannot be read or written; `Error::RateLimited`
+    /// if an auto-import cap is reached; `Error::TokenTooShortLived` if the
+    /// token has less than `Settings::min_token_validity_minutes` left
+    pub(crate) fn apply_incoming_ping(
+        storage: &Storage,
+        contact_token: &str,
+        source: Option<std::net::IpAddr>,
+        limiter: &std::sync::Mutex<AutoImportLimiter>,
+        now: chrono::DateTime<Utc>,
+    ) -> crate::Result<ContactIngest> {
+        let mut app_state = AppState::load_from_db(storage)?;
+        let sender_contact = crate::storage::Contact::parse_token(contact_token)?;
+        tracing::info!("Received ping from {} at {}", sender_contact.uid, sender_contact.ip);
+        require_min_validity(&sender_contact, now, app_state.settings.min_token_validity_minutes)?;
+
+        let sender_uid = sender_contact.uid.clone();
+        let source = source.map(|ip| ip.to_string());
+        let outcome = app_state.ingest_contact_from(sender_contact, AddressSource::PingToken, now)?;
+        let new_contact = matches!(outcome, ContactIngest::Added);
+        let new_chat = !matches!(outcome, ContactIngest::Conflict(_)) && app_state.get_chat(&sender_uid).is_none();
+        if new_contact || new_chat {
+            admit_or_audit(storage, limiter, new_contact, new_chat, source.as_deref(), now)?;
+        }
+        if matches!(outcome, ContactIngest::AddressStaged) {
+            tracing::info!("Ping from {} claims a new address; staged for review", sender_uid);
+        }
+        if let ContactIngest::Conflict(conflict) = &outcome {
+            tracing::warn!("Ping from {} held for review: {}", sender_uid, conflict.kind);
+        } else {
+            if matches!(outcome, ContactIngest::Added) {
+                tracing::info!("Auto-imported contact {} from ping", sender_uid);
+            }
+            // Create or get existing chat (active status, not pending)
+            app_state.get_or_create_chat(&sender_uid).mark_unread();
+        }
+
+        app_state.save_to_db(storage)?;
+        Ok(outcome)
+    }
+
+    /// Reload state if a background handler stored something new
+    ///
+    /// Called from the main loop so badges and the terminal title follow
+    /// incoming messages without leaving the current screen.
+    ///
+    /// # Returns
+    /// Whether an update was picked up
+    pub fn process_incoming_updates(&mut self) -> bool {
+        if !self.incoming_updates.swap(false, std::sync::atomic::Ordering::SeqCst) {
+            return false;
+        }
+        self.reload_or_report();
+        self.answer_key_upgrade_requests();
+        self.answer_token_refresh_requests();
+        self.apply_capability_answers(Utc::now());
+        self.process_probe_messages(Utc::now());
+        self.resume_dormant_messages();
+        // A ping may have brought a renewed token
+        self.sync_expired_blocks(Utc::now());
+        true
+    }
+
+    /// Note authenticated inbound traffic from `uid` (a working path to it)
+    pub fn record_heard_from(&self, uid: &str) {
+        self.heard_from.lock().unwrap().push(uid.to_string());
+    }
+
+    /// Bring back dormant messages to the contacts heard from since the last call
+    ///
+    /// Their attempts start over and the retry worker sends them on its next
+    /// pass, to whatever address the contact has now. Each contact's
+    /// `heard_at` is set, so its pings can leave out our token while it is
+    /// current (see `tui::contact_import`).
+    ///
+    /// # Returns
+    /// How many messages were brought back
+    pub fn resume_dormant_messages(&mut self) -> usize {
+        let mut senders = std::mem::take(&mut *self.heard_from.lock().unwrap());
+        senders.sort();
+        senders.dedup();
+
+        let now = Utc::now();
+        let mut heard = false;
+        for uid in &senders {
+            if let Some(contact) = self.app_state.contact_by_uid_mut(uid) {
+                contact.heard_at = Some(now);
+                heard = true;
+            }
+        }
+        if heard {
+            self.save_or_report();
+        }
+
+        let mut resumed = 0;
+        for uid in senders {
+            match self.queue.resurrect_for(&uid) {
+                Ok(0) => {}
+                Ok(count) => {
+                    resumed += count;
+                    tracing::info!("Resumed {} dormant message(s) to {}", count, uid);
+                    let uid_short = &uid[..16.min(uid.len())];
+                    self.notifications.notify(format!("{} is reachable again: resending {} message(s)", uid_short, count));
+                }
+                Err(e) => tracing::error!("Failed to resume dormant messages to {}: {}", uid, e),
+            }
+        }
+        if resumed > 0 {
+            self.refresh_queued_since();
+            self.retry_wakeup.notify();
+        }
+        resumed
+    }
+
+    /// Record the capability probe answers received since the last call
+    ///
+    /// A peer without the endpoint is marked legacy so it is not asked again
+    /// for a while (see `storage::capability_probe`).
+    ///
+    /// # Returns
+    /// How many contacts were updated
+    pub fn apply_capability_answers(&mut self, now: chrono::DateTime<Utc>) -> usize {
+        let answers = std::mem::take(&mut *self.capability_answers.lock().unwrap());
+        let updated = answers
+            .into_iter()
+            .filter(|(uid, answer)| apply_probe_answer(&mut self.app_state.contacts, uid, answer.clone(), now))
+            .count();
+        if updated > 0 {
+            self.save_or_report();
+        }
+        updated
+    }
+
+    /// Answer the key upgrade requests received since the last call
+    ///
+    /// Each requester gets our freshly signed contact token, minimized for
+    /// restricted contacts, which get no answer while we only know a LAN
+    /// address. Best effort, like relay capabilities: a requester that
+    /// cannot be reached asks again.
+    ///
+    /// # Returns
+    /// How many contacts are answered
+    pub fn answer_key_upgrade_requests(&self) -> usize {
+        let requesters = std::mem::take(&mut *self.key_upgrade_requests.lock().unwrap());
+        let my_uid = self.keypair.uid.to_string();
+        let answers: Vec<_> = self
+            .tokens_for_requesters(&requesters, "key upgrade")
+            .into_iter()
+            .map(|(token, contact)| (key_upgrade_response(&my_uid, &token), contact))
+            .collect();
+        let count = answers.len();
+        let transports = self.transports.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+            rt.block_on(async move {
+                for (response, contact) in &answers {
+                    if let Err(e) = transports.send_message(contact, response).await {
+                        tracing::debug!("Could not answer key upgrade request from {}: {}", contact.uid, e);
+                    }
+                }
+            });
+        });
+        count
+    }
+
+    /// Answer the token refresh requests received since the last call
+    ///
+    /// Each requester is pinged with our freshly signed contact token, which
+    /// renews its copy of it like any imported token (see
+    /// `tui::expiry_warning`). Restricted contacts get no answer while we only
+    /// know a LAN address. Best effort, like key upgrade answers.
+    ///
+    /// # Returns
+    /// How many contacts are answered
+    pub fn answer_token_refresh_requests(&self) -> usize {
+        let requesters = std::mem::take(&mut *self.token_refresh_requests.lock().unwrap());
+        let answers = self.tokens_for_requesters(&requesters, "token refresh");
+        let count = answers.len();
+        if count == 0 {
+            return 0;
+        }
+        let transports = self.transports.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+            rt.block_on(async move {
+                for (token, contact) in &answers {
+                    if let Err(e) = transports.send_ping(contact, token).await {
+                        tracing::debug!("Could not answer token refresh request from {}: {}", contact.uid, e);
+                    }
+                }
+            });
+        });
+        count
+    }
+
+    /// Our contact token for each known contact in `requesters`, minimized
+    /// for its recipient
+    ///
+    /// Restricted contacts are left out while we only know a LAN address;
+    /// nothing is returned if no token can be made.
+    fn tokens_for_requesters(&self, requesters: &[String], purpose: &str) -> Vec<(String, crate::storage::Contact)> {
+        let mut tokens = Vec::new();
+        for contact in self.app_state.contacts.iter().filter(|c| requesters.contains(&c.uid)) {
+            match self.my_contact_token_for(contact) {
+                Ok(Some(token)) => tokens.push((token, contact.clone())),
+                Ok(None) => tracing::debug!("Not answering {} request from restricted contact {}: no public address", purpose, contact.uid),
+                Err(e) => {
+                    tracing::error!("Failed to generate contact token for {}: {}", purpose, e);
+                    return Vec::new();
+                }
+            }
+        }
+        tokens
+    }
+
+    /// Contacts that can be asked to test our reachability (not expired)
+    pub fn probe_candidates(&self) -> Vec<&crate::storage::Contact> {
+        self.app_state.contacts.iter().filter(|c| c.is_active && !c.is_expired()).collect()
+    }
+
+    /// Ask `contact_uid` to test whether our advertised address reaches us
+    ///
+    /// The result arrives as a `probe_result` and is applied by
+    /// `apply_probe_result`. A request that cannot be delivered comes back
+    /// the same way, as not tested.
+    ///
+    /// # Returns
+    /// Whether the request is on its way
+    pub fn request_peer_probe(&mut self, contact_uid: &str) -> bool {
+        if self.refuse_in_inspection("probe contacts") {
+            return false;
+        }
+        let Some(contact) = self.app_state.contact_by_uid(contact_uid).cloned() else {
+            return false;
+        };
+        let request = ProbeRequest::new(self.local_ip.clone());
+        let message = match probe_request_message(&self.keypair.uid.to_string(), &request) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::error!("Failed to build probe request: {}", e);
+                return false;
+            }
+        };
+
+        if self.pending_probes.len() >= MAX_PENDING_PROBES {
+            self.pending_probes.remove(0);
+        }
+        self.pending_probes.push(PendingProbe {
+            probe_id: request.probe_id.clone(),
+            contact_uid: contact.uid.clone(),
+            address: request.address.clone(),
+            requested_at: Utc::now(),
+        });
+        if let Some(screen) = &mut self.diagnostics_screen {
+            let uid_short = &contact.uid[..8.min(contact.uid.len())];
+            screen.set_status_message(format!("Asked {} to test {}", uid_short, request.address));
+        }
+
+        let transports = self.transports.clone();
+        let results = self.probe_results.clone();
+        let updates = self.incoming_updates.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+            if let Err(e) = rt.block_on(transports.send_message(&contact, &message)) {
+                let reason = format!("request not delivered: {}", e);
+                results.lock().unwrap().push((contact.uid.clone(), ProbeResult::refused(&request, reason)));
+                updates.store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+        });
+        true
+    }
+
+    /// Serve probe requests and apply probe results received since the last call
+    pub fn process_probe_messages(&mut self, now: chrono::DateTime<Utc>) {
+        let requests = std::mem::take(&mut *self.probe_requests.lock().unwrap());
+        for (from_uid, request) in requests {
+            if let Err(e) = self.serve_probe_request(&from_uid, request, now) {
+                tracing::info!("Probe request from {} not served: {}", from_uid, e);
+            }
+        }
+        let results = std::mem::take(&mut *self.probe_results.lock().unwrap());
+        for (from_uid, result) in results {
+            self.apply_probe_result(&from_uid, &result, now);
+        }
+    }
+
+    /// Test the address a contact asked about and send it the result
+    ///
+    /// Requests that fail `probe::admit_probe` are answered with the reason
+    /// when the requester is a stored contact, and dropped otherwise.
+    ///
+    /// # Errors
+    /// The reason the request was refused (the answer is still sent)
+    pub fn serve_probe_request(&mut self, from_uid: &str, request: ProbeRequest, now: chrono::DateTime<Utc>) -> crate::Result<()> {
+        let admitted = admit_probe(&self.app_state.contacts, from_uid, &request, &mut self.probe_limiter, now);
+        let (requester, refusal) = match &admitted {
+            Ok(contact) => (contact.clone(), None),
+            Err(e) => match self.app_state.contact_by_uid(from_uid) {
+                Some(contact) => (contact.clone(), Some(e.to_string())),
+                None => return admitted.map(|_| ()),
+            },
+        };
+
+        let transports = self.transports.clone();
+        let local_uid = self.keypair.uid.to_string();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+            rt.block_on(async move {
+                let result = match refusal {
+                    Some(reason) => ProbeResult::refused(&request, reason),
+                    None => {
+                        let timeout = std::time::Duration::from_secs(PROBE_CHECK_TIMEOUT_SECS);
+                        probe_address(&transports, &local_uid, &requester, &request, timeout).await
+                    }
+                };
+                if let Err(e) = send_probe_result(&transports, &local_uid, &requester, &result).await {
+                    tracing::debug!("Could not send probe result to {}: {}", requester.uid, e);
+                }
+            });
+        });
+        admitted.map(|_| ())
+    }
+
+    /// Apply the result of a probe we asked `from_uid` for
+    ///
+    /// Results for probes we did not ask that contact for are ignored. The
+    /// result is added to the connectivity history and shown in Diagnostics.
+    ///
+    /// # Returns
+    /// Whether the result answered one of our probes
+    pub fn apply_probe_result(&mut self, from_uid: &str, result: &ProbeResult, now: chrono::DateTime<Utc>) -> bool {
+        let Some(pos) = self
+            .pending_probes
+            .iter()
+            .position(|p| p.probe_id == result.probe_id && p.contact_uid == from_uid)
+        else {
+            tracing::debug!("Ignoring probe result {} from {}", result.probe_id, from_uid);
+            return false;
+        };
+        let pending = self.pending_probes.remove(pos);
+
+        // The address is the one we asked about, whatever the answer says
+        let mut record = PeerProbeRecord::from_result(from_uid, result, now);
+        record.address = pending.address;
+        self.error_reports.check(
+            ErrorSeverity::Warning,
+            "reachability probe",
+            record_probe_result(&self.storage, &record),
+        );
+        self.notifications.notify(record.summary());
+        if let Some(screen) = &mut self.diagnostics_screen {
+            screen.set_status_message(record.summary());
+            screen.peer_probes.insert(0, record);
+            screen.peer_probes.truncate(crate::tui::screens::PEER_PROBE_HISTORY_SHOWN);
+        }
+        true
+    }
+
+    /// Security strip for the chat with `contact_uid`
+    ///
+    /// # Returns
+    /// The strip text and whether messages are sealed
+    pub fn security_strip(&self, contact_uid: &str) -> (String, bool) {
+        let Some(contact) = self.app_state.contact_by_uid(contact_uid) else {
+            return (SendSecurity::MissingKey.strip_text().to_string(), false);
+        };
+        let security = send_security(Some(&self.keypair), contact);
+        let mut text = security.strip_text().to_string();
+        if security == SendSecurity::MissingKey && self.key_upgrades_requested.contains(contact_uid) {
+            text.push_str(" (key upgrade requested)");
+        }
+        (text, security.is_encrypted())
+    }
+
+    /// Re-read when the open chat's queued messages were queued
+    pub fn refresh_queued_since(&mut self) {
+        let Some(screen) = self.chat_view_screen.as_mut() else {
+            return;
+        };
+        match self.queue.queued_at_for(&screen.contact_uid) {
+            Ok(queued) => screen.queued_since = queued,
+            Err(e) => tracing::warn!("Failed to read queued messages for {}: {}", screen.contact_uid, e),
+        }
+        match self.queue.failed_ids_for(&screen.contact_uid) {
+            Ok(failed) => screen.failed_ids = failed,
+            Err(e) => tracing::warn!("Failed to read failed messages for {}: {}", screen.contact_uid, e),
+        }
+    }
+
+    /// Open the review of the open chat's failed messages (Ctrl+F)
+    pub fn open_failed_review(&mut self) {
+        let Some(uid) = self.open_chat_uid() else {
+            return;
+        };
+        let failed = self.queue.failed_for(&uid);
+        let Some(screen) = &mut self.chat_view_screen else {
+            return;
+        };
+        match failed {
+            Ok(failed) if failed.is_empty() => screen.set_status("No failed messages".to_string()),
+            Ok(failed) => screen.failed_review = Some(FailedReview::new(failed)),
+            Err(e) => screen.set_status(format!("Could not read failed messages: {}", e)),
+        }
+    }
+
+    /// Re-queue failed messages from the review: all of them, or the marked ones
+    ///
+    /// They keep their original order and go out before anything composed
+    /// since (see `queue_dead_letters`). A notice in the chat records how
+    /// many were re-queued.
+    pub fn requeue_failed(&mut self, all: bool) {
+        if self.refuse_in_inspection("retry deliveries") {
+            return;
+        }
+        let Some(screen) = &mut self.chat_view_screen else {
+            return;
+        };
+        let Some(review) = screen.failed_review.take() else {
+            return;
+        };
+        let uid = screen.contact_uid.clone();
+        let ids = review.chosen_ids(all);
+        let status = match self.queue.requeue_failed(&uid, &ids) {
+            Ok(0) => "Nothing re-queued".to_string(),
+            Ok(count) => {
+                let notice = format!("Re-queued {} failed message(s) in their original order", count);
+                let remaining = review.messages.len() - count;
+                if let Some(chat) = self.app_state.chat_by_uid_mut(&uid) {
+                    chat.append_message(Message::system(&notice, Utc::now().timestamp_millis()));
+                    if remaining == 0 {
+                        chat.mark_no_failed();
+                    }
+                }
+                self.save_or_report();
+                let _ = self.delivery_events_tx.send(DeliveryEvent::new(&uid, DeliveryUpdate::Queued, true));
+                notice
+            }
+            Err(e) => format!("Could not re-queue: {}", e),
+        };
+        self.refresh_queued_since();
+        if let Some(screen) = &mut self.chat_view_screen {
+            screen.set_status(status);
+        }
+    }
+
+    /// Delivery hint for the open chat (None when nothing is queued)
+    ///
+    /// The contact counts as heard from when it last sent us a message.
+    pub fn chat_delivery_hint(&self, now: chrono::DateTime<Utc>) -> Option<DeliveryHint> {
+        let screen = self.chat_view_screen.as_ref()?;
+        let oldest = *screen.queued_since.values().min()?;
+        let queued_at = chrono::DateTime::from_timestamp_millis(oldest)?;
+        let uid = &screen.contact_uid;
+        let contact_expiry = self.app_state.contact_by_uid(uid).map(|c| c.expiry);
+        Some(delivery_hint(queued_at, contact_expiry, self.last_heard_from(uid), now))
+    }
+
+    /// When `uid` last sent us a message
+    fn last_heard_from(&self, uid: &str) -> Option<chrono::DateTime<Utc>> {
+        self.app_state
+            .get_chat(uid)
+            .and_then(|chat| chat.messages.iter().filter(|m| m.sender == uid).map(|m| m.timestamp).max())
+            .and_then(chrono::DateTime::from_timestamp_millis)
+    }
+
+    /// Run the action offered by the chat view's delivery banner (Ctrl+R)
+    ///
+    /// A stale address gets a new introduction ping; an expired token opens
+    /// the import screen for a new one.
+    pub fn delivery_banner_action(&mut self, now: chrono::DateTime<Utc>) {
+        if self.refuse_in_inspection("retry deliveries") {
+            return;
+        }
+        match self.chat_delivery_hint(now) {
+            Some(DeliveryHint::StaleAddress) => self.resend_introduction_ping(),
+            Some(DeliveryHint::Undeliverable) => self.show_import_contact_screen(),
+            _ => {}
+        }
+    }
+
+    /// Queue an introduction ping to the open chat's contact
+    ///
+    /// The retry worker sends it with urgent priority; one already waiting
+    /// is not duplicated.
+    fn resend_introduction_ping(&mut self) {
+        let Some(uid) = self.chat_view_screen.as_ref().map(|screen| screen.contact_uid.clone()) else {
+            return;
+        };
+        let status = match self.queue.has_queued_type_for(&uid, "ping") {
+            Ok(true) => "Introduction ping already queued".to_string(),
+            Ok(false) => {
+                let shares_address = match self.app_state.contact_by_uid(&uid) {
+                    Some(contact) => Ok(OutboundPolicy::for_contact(contact).token_address(&self.local_ip).is_some()),
+                    None => Err(crate::Error::Storage(format!("Contact {} not found", uid))),
+                };
+                let queued = shares_address.and_then(|shares_address| {
+                    if !shares_address {
+                        return Ok(false);
+                    }
+                    // The retry worker makes the token when it sends the ping
+                    let ping = Message::new(
+                        uuid::Uuid::new_v4().to_string(),
+                        self.keypair.uid.to_string(),
+                        uid.clone(),
+                        FRESH_TOKEN_MARKER.to_vec(),
+                        Utc::now().timestamp_millis(),
+                    );
+                    self.queue.enqueue_with_type(ping, crate::queue::Priority::Urgent, "ping").map(|()| true)
+                });
+                match queued {
+                    Ok(true) => {
+                        let _ = self.delivery_events_tx.send(DeliveryEvent::new(&uid, DeliveryUpdate::Queued, true));
+                        "Introduction ping queued".to_string()
+                    }
+                    Ok(false) => "Not sent: restricted contact and no public address to share".to_string(),
+                    Err(e) => format!("Could not queue introduction ping: {}", e),
+                }
+            }
+            Err(e) => format!("Could not queue introduction ping: {}", e),
+        };
+        self.refresh_queued_since();
+        if let Some(screen) = &mut self.chat_view_screen {
+            screen.set_status(status);
+        }
+    }
+
+    /// Our contact token as sent to `contact` in pings and key upgrade responses
+    ///
+    /// `None` for a restricted contact while we only know a LAN address
+    /// (see `storage::own_token_for`).
+    fn my_contact_token_for(&self, contact: &crate::storage::Contact) -> crate::Result<Option<String>> {
+        self.own_token_source().token_for(contact)
+    }
+
+    /// What our tokens are made from right now
+    fn own_token_source(&self) -> OwnTokenSource {
+        OwnTokenSource {
+            keypair: self.keypair.clone(),
+            address: self.local_ip.clone(),
+            cert_fingerprint: self.tls_fingerprint.clone(),
+            validity: self.app_state.settings.own_token_validity(),
+        }
+    }
+
+    /// Sender for background threads to publish delivery status changes
+    pub fn delivery_event_sender(&self) -> std::sync::mpsc::Sender<DeliveryEvent> {
+        self.delivery_events_tx.clone()
+    }
+
+    /// Apply delivery status changes published by background senders
+    ///
+    /// Called from the main loop each tick, so pending and failed indicators
+    /// update in place. Every `RECONCILE_INTERVAL` the flags are also checked
+    /// against the full queue in case an event was missed.
+    ///
+    /// # Returns
+    /// Whether any chat changed
+    pub fn process_delivery_events(&mut self) -> bool {
+        let mut changed = false;
+        let mut received = false;
+        while let Ok(event) = self.delivery_events.try_recv() {
+            received = true;
+            if event.update == DeliveryUpdate::Queued {
+                self.retry_wakeup.notify();
+            }
+            changed |= self.app_state.chat_by_uid_mut(&event.contact_uid).is_some_and(|chat| event.apply_to_chat(chat));
+            if event.update == DeliveryUpdate::Delivered
+                && let Some(message_id) = &event.message_id
+            {
+                if let Some(protection) = event.protection {
+                    changed |= self
+                        .app_state
+                        .chat_by_uid_mut(&event.contact_uid)
+                        .is_some_and(|chat| chat.record_protection(message_id, protection));
+                }
+                self.journal_delivery(&event.contact_uid, message_id, Utc::now());
+            }
+            // A send that crashed names its message
+            if event.update == DeliveryUpdate::Failed
+                && let Some(message_id) = &event.message_id
+                && let Some(message) = self
+                    .app_state
+                    .chat_by_uid_mut(&event.contact_uid)
+                    .and_then(|chat| chat.messages_mut().iter_mut().find(|m| m.id == *message_id))
+            {
+                message.mark_failed();
+                changed = true;
+            }
+
+            // Failures to reach the current address count towards a staged address change
+            let delivered = matches!(event.update, DeliveryUpdate::Delivered | DeliveryUpdate::PingAnswered);
+            if let Some(change) = self.app_state.record_delivery_outcome(&event.contact_uid, delivered) {
+                let uid_short = &change.uid[..16.min(change.uid.len())];
+                self.notifications.notify(format!(
+                    "Address of {} changed to {} after {} failed deliveries",
+                    uid_short, change.ip, change.failed_deliveries
+                ));
+                changed = true;
+            }
+        }
+        if changed {
+            self.save_or_report();
+        }
+        if received {
+            self.refresh_queued_since();
+        }
+
+        if self.last_reconcile.elapsed() >= RECONCILE_INTERVAL {
+            changed |= self.reconcile_delivery_status();
+        }
+        if self.last_lease_sweep.elapsed() >= LEASE_SWEEP_INTERVAL {
+            self.last_lease_sweep = std::time::Instant::now();
+            self.sweep_send_leases(Utc::now());
+        }
+        self.persist_send_leases();
+        changed
+    }
+
+    /// Queue for retry the messages whose send thread died without an outcome
+    ///
+    /// Takes back the send leases older than `LEASE_TIMEOUT_SECS` (see
+    /// `tui::send_leases`). A message already in the queue, or no longer in
+    /// its chat, is left as it is; the others are queued and the retry worker
+    /// woken. Called every `LEASE_SWEEP_INTERVAL` from `process_delivery_events`.
+    ///
+    /// # Returns
+    /// How many messages were queued
+    pub fn sweep_send_leases(&mut self, now: chrono::DateTime<Utc>) -> usize {
+        let mut requeued = 0;
+        for lease in self.send_leases.take_expired(now) {
+            let age = lease.age(now).num_seconds();
+            match self.requeue_unfinished_send(&lease) {
+                Ok(UnfinishedSend::Gone) => {
+                    tracing::warn!("Send of {} to {} never finished ({}s); the message is gone", lease.message_id, lease.contact_uid, age);
+                }
+                Ok(UnfinishedSend::AlreadyQueued) => {
+                    tracing::warn!("Send of {} to {} never finished ({}s); already queued", lease.message_id, lease.contact_uid, age);
+                }
+                Ok(UnfinishedSend::Queued) => {
+                    tracing::warn!("Send of {} to {} never finished ({}s); queued it for retry", lease.message_id, lease.contact_uid, age);
+                    requeued += 1;
+                }
+                Err(e) => self.error_reports.report(
+                    ErrorSeverity::Error,
+                    "message queue",
+                    format!("Failed to queue {} after its send died: {}", lease.message_id, e),
+                ),
+            }
+        }
+        self.persist_send_leases();
+        requeued
+    }
+
+    /// Queue the message of a send that never finished, unless it is
+    /// already queued or no longer in its chat
+    fn requeue_unfinished_send(&mut self, lease: &SendLease) -> crate::Result<UnfinishedSend> {
+        let Some(message) = self
+            .app_state
+            .get_chat(&lease.contact_uid)
+            .and_then(|chat| chat.messages.iter().find(|m| m.id == lease.message_id))
+            .cloned()
+        else {
+            return Ok(UnfinishedSend::Gone);
+        };
+        if self.queue.contains(&lease.message_id)? {
+            return Ok(UnfinishedSend::AlreadyQueued);
+        }
+        self.queue.enqueue(message, crate::queue::Priority::Normal)?;
+        let event = DeliveryEvent::new(&lease.contact_uid, DeliveryUpdate::Queued, true).with_message_id(&lease.message_id);
+        let _ = self.delivery_events_tx.send(event);
+        Ok(UnfinishedSend::Queued)
+    }
+
+    /// Store the send leases if they changed since they were last stored
+    ///
+    /// Leases still stored at the next start belong to sends the process
+    /// died in the middle of (see `start_session`).
+    pub fn persist_send_leases(&mut self) {
+        let generation = self.send_leases.generation();
+        if generation == self.persisted_lease_generation || self.inspection.is_some() {
+            return;
+        }
+        let leases: Vec<(String, String, chrono::DateTime<Utc>)> = self
+            .send_leases
+            .snapshot()
+            .into_iter()
+            .map(|lease| (lease.message_id, lease.contact_uid, lease.started_at))
+            .collect();
+        if self.error_reports.check(ErrorSeverity::Warning, "storage", self.storage.replace_send_leases(&leases)).is_some() {
+            self.persisted_lease_generation = generation;
+        }
+    }
+
+    /// Append the delivery of `message_id` to `contact_uid` to the outbound journal
+    ///
+    /// Does nothing while the journal is off, or for messages not in the chat
+    /// (pings and control messages). No delivery acknowledgement is signed
+    /// yet, so `ack_signature` stays empty.
+    pub fn journal_delivery(&mut self, contact_uid: &str, message_id: &str, now: chrono::DateTime<Utc>) {
+        if !self.app_state.settings.journal_enabled {
+            return;
+        }
+        let own_uid = self.keypair.uid.to_string();
+        let Some(content) = self
+            .app_state
+            .get_chat(contact_uid)
+            .and_then(|chat| chat.messages.iter().find(|m| m.id == message_id && m.sender == own_uid))
+            .map(|m| m.content.clone())
+        else {
+            return;
+        };
+        let Some(tail) = self.error_reports.check(ErrorSeverity::Error, "journal", self.storage.journal_tail()) else {
+            return;
+        };
+        let record = JournalRecord::delivered(tail.as_ref(), now, contact_uid, &content, None);
+        self.error_reports.check(ErrorSeverity::Error, "journal", self.storage.append_journal_record(&record));
+    }
+
+    /// Close the journal with a seal marker (when it is switched off)
+    pub fn seal_journal(&mut self, now: chrono::DateTime<Utc>) {
+        let Some(tail) = self.error_reports.check(ErrorSeverity::Error, "journal", self.storage.journal_tail()) else {
+            return;
+        };
+        let record = JournalRecord::seal(tail.as_ref(), now);
+        self.error_reports.check(ErrorSeverity::Error, "journal", self.storage.append_journal_record(&record));
+    }
+
+    /// Re-walk the journal chain and show the result on the Diagnostics screen
+    pub fn verify_journal(&mut self) {
+        let message = match self.storage.load_journal(None, None) {
+            Ok(records) if records.is_empty() => "Journal is empty".to_string(),
+            Ok(records) => match verify_chain(&records) {
+                Some(broken) => format!("✗ {}", broken),
+                None => format!("✓ Journal intact ({} records)", records.len()),
+            },
+            Err(e) => format!("Error: could not read the journal: {}", e),
+        };
+        if let Some(screen) = &mut self.diagnostics_screen {
+            screen.set_status_message(message);
+        }
+    }
+
+    /// Run SQLite's quick check and the identity scan, and show the result
+    /// on the Diagnostics screen (nothing is changed or held for review)
+    pub fn run_integrity_report(&mut self) {
+        let message = match self.storage.verify_integrity() {
+            Ok(report) if report.is_clean() => "✓ Database intact (quick check passed, identities consistent)".to_string(),
+            Ok(report) => {
+                let mut message = format!(
+                    "✗ {}, {}",
+                    count_phrase(report.problems.len(), "quick check problem", "quick check problems"),
+                    count_phrase(report.conflicts.len(), "identity conflict", "identity conflicts")
+                );
+                if let Some(first) = report.problems.first() {
+                    message.push_str(&format!(" (first: {})", first));
+                }
+                for problem in &report.problems {
+                    tracing::warn!("Integrity report: {}", problem);
+                }
+                message
+            }
+            Err(e) => format!("Error: could not check integrity: {}", e),
+        };
+        if let Some(screen) = &mut self.diagnostics_screen {
+            screen.set_status_message(message);
+        }
+    }
+
+    /// Open or close the queue contents on the Diagnostics screen
+    ///
+    /// Rows are redacted as in the `--export-queue` report: no content and
+    /// truncated UIDs.
+    pub fn toggle_queue_view(&mut self) {
+        let Some(screen) = &mut self.diagnostics_screen else {
+            return;
+        };
+        if screen.queue_rows.take().is_some() {
+            return;
+        }
+        match self.queue.debug_report(Utc::now().timestamp_millis()) {
+            Ok(report) => screen.queue_rows = Some(report.rows),
+            Err(e) => screen.set_status_message(format!("Error: could not read the queue: {}", e)),
+        }
+    }
+
+    /// Show the current log file (of the inspected directory when inspecting)
+    pub fn open_log_viewer(&mut self) {
+        let dir = match &self.inspection {
+            Some(inspection) => inspection.dir.join("logs"),
+            None => std::path::PathBuf::from(LOGS_DIR),
+        };
+        self.capture_screen = Some(CaptureScreen::log(rotated_path(&dir, 0)));
+        self.current_screen = Screen::Capture;
+    }
+
+    /// Reset pending flags from the full queue (safety net for missed events)
+    ///
+    /// # Returns
+    /// Whether any chat changed
+    pub fn reconcile_delivery_status(&mut self) -> bool {
+        self.last_reconcile = std::time::Instant::now();
+        let Ok(pending_uids) = self.queue.get_pending_contact_uids() else {
+            return false;
+        };
+        // A worker parked while messages are queued missed its wake-up
+        if !pending_uids.is_empty() && self.retry_status.plan().mode == RetryMode::Idle {
+            self.retry_wakeup.notify();
+        }
+        let changed = self.app_state.sync_pending_status(&pending_uids);
+        if changed {
+            self.save_or_report();
+        }
+        changed
+    }
+
+    /// Unread/pending counts shown on the main menu, chat list and terminal title
+    pub fn chat_summary(&self) -> ChatSummary {
+        ChatSummary::from_chats(&self.app_state.chats)
+    }
+
+    /// Re-evaluate quiet hours against the current local time
+    ///
+    /// Called from the main loop. When a quiet period ends, notifications held
+    /// back during it are shown as a single summary.
+    pub fn update_quiet_hours(&mut self) {
+        let quiet = crate::storage::is_quiet(&chrono::Local::now(), &self.app_state.settings);
+        self.notifications.set_quiet(quiet);
+    }
+
+    /// Warn and remove temporary contacts whose lifetime is ending at `now`
+    ///
+    /// Called from the main loop. Removed contacts lose their chat, their
+    /// database rows and their queued messages; their notes are not kept, so
+    /// importing them again starts fresh.
+    ///
+    /// # Returns
+    /// Whether anything changed
+    pub fn run_ephemeral_maintenance(&mut self, now: chrono::DateTime<Utc>) -> bool {
+        if self.inspection.is_some() {
+            return false;
+        }
+        let sweep = self.app_state.sweep_ephemeral(now);
+        if sweep.is_empty() {
+            return false;
+        }
+
+        for uid in &sweep.expired {
+            self.error_reports.check(ErrorSeverity::Error, "storage", self.storage.delete_chat(uid));
+            self.error_reports.check(ErrorSeverity::Error, "storage", self.storage.delete_contact(uid));
+            match self.queue.purge_for(uid) {
+                Ok(purged) if purged > 0 => tracing::info!("Dropped {} queued messages to expired temporary contact {}", purged, uid),
+                Ok(_) => {}
+                Err(e) => self.error_reports.report(
+                    ErrorSeverity::Warning,
+                    "message queue",
+                    format!("Failed to purge queued messages to {}: {}", uid, e),
+                ),
+            }
+            if self.open_chat_uid().as_deref() == Some(uid.as_str()) {
+                self.back_to_chat_list();
+            }
+        }
+        if let Some(screen) = &mut self.chat_list_screen {
+            if screen.contact_details.as_ref().is_some_and(|popup| sweep.expired.contains(&popup.contact_uid)) {
+                screen.contact_details = None;
+            }
+            if let Some(uid) = sweep.expired.first() {
+                screen.set_status(format!("Temporary contact {} expired and was deleted", &uid[..16.min(uid.len())]));
+            }
+            if screen.selected_index >= self.app_state.chats.len() {
+                screen.selected_index = self.app_state.chats.len().saturating_sub(1);
+            }
+        }
+
+        self.save_or_report();
+        if !sweep.expired.is_empty() {
+            self.sync_relay();
+        }
+        true
+    }
+
+    /// Start transport server in background with automatic retry on failure
+    ///
+    /// The server starts immediately and runs until app shutdown, independent of connectivity.
+    /// This ensures peers can always send messages to the shared contact endpoint.
+    ///
+    /// # Server Lifecycle
+    /// 1. Binds to preferred port (or tries up to 10 random ports if unavailable)
+    /// 2. Runs health check to verify server is listening
+    /// 3. Updates database with actual running port
+    /// 4. Stays running until app exit (tokio runtime kept alive with oneshot channel)
+    ///
+    /// # Connectivity Independence
+    /// - Server starts BEFORE connectivity detection
+    /// - Connectivity uses the actual running port to create port mappings
+    /// - Server continues running regardless of connectivity success/failure
+    ///
+    /// If the preferred port fails, it will try alternative ports automatically.
+    /// Handlers are set up to automatically create chats when pings are received.
+    pub fn start_transport(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.inspection.is_some() {
+            return Err("The transport is not started in inspection mode".into());
+        }
+        // Set local UID for transport
+        let uid = self.keypair.uid.to_string();
+        let transport = self.transport.clone();
+        let preferred_port = self.local_port;
+        let state_path = self.state_path.clone();
+        let status = self.transport_server_status.clone();
+        let storage = self.storage.clone();
+        let incoming_updates = self.incoming_updates.clone();
+        let key_upgrade_requests = self.key_upgrade_requests.clone();
+        let token_refresh_requests = self.token_refresh_requests.clone();
+        let probe_requests = self.probe_requests.clone();
+        let probe_results = self.probe_results.clone();
+        let heard_from = self.heard_from.clone();
+        let auto_import_limiter = self.auto_import_limiter.clone();
+        let port_alert = self.port_alert.clone();
+        let reports = self.error_reports.clone();
+        let connectivity_events = self.connectivity_events.clone();
+        let runtime_info_path = self.runtime_info_path.clone();
+        let started_at = self.started_at;
+        let startup_events = self.startup_events_tx.clone();
+
+        // Create storage instances for handlers (they need their own connections in separate threads)
+        let use_in_memory = state_path.contains("test") || state_path.contains("tmp");
+
+        // Mark as starting
+        *status.lock().unwrap() = TransportServerStatus::Starting;
+
+        // Setup ping handler and message handler to receive messages and pings
+        // Use a channel to keep the runtime alive until app exits
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+            let (_tx, rx) = tokio::sync::oneshot::channel::<()>();
+
+            rt.block_on(async move {
+                // Set local UID
+                transport.set_local_uid(uid).await;
+
+                // Setup ping handler
+                let use_in_memory_ping = use_in_memory;
+                let updates_ping = incoming_updates.clone();
+                let heard_from_ping = heard_from.clone();
+                let limiter_ping = auto_import_limiter.clone();
+                let reports_ping = reports.clone();
+                transport.set_ping_handler_with_source(move |contact_token: String, source| {
+                    // Create storage connection for this handler
+                    let storage_result = if use_in_memory_ping {
+                        Storage::new_in_memory()
+                    } else {
+                        Storage::new_with_default_path()
+                    };
+
+                    let applied = storage_result.and_then(|storage| {
+                        Self::apply_incoming_ping(&storage, &contact_token, source, &limiter_ping, Utc::now())
+                    });
+                    match applied {
+                        Ok(outcome) => {
+                            // The token's signature verified: the contact is reachable again
+                            if !matches!(outcome, ContactIngest::Conflict(_))
+                                && let Ok(sender) = crate::storage::Contact::parse_token(&contact_token)
+                            {
+                                heard_from_ping.lock().unwrap().push(sender.uid);
+                            }
+                            updates_ping.store(true, std::sync::atomic::Ordering::SeqCst);
+                        }
+                        Err(e @ (crate::Error::RateLimited(_) | crate::Error::TokenTooShortLived(_))) => return Err(e),
+                        Err(e) if is_local_failure(&e) => {
+                            reports_ping.report(ErrorSeverity::Error, "ping handler", format!("Failed to store an incoming ping: {}", e));
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed to handle ping: {}", e);
+                        }
+                    }
+                    Ok(())
+                }).await;
+
+                // Setup message handler
+                let use_in_memory_msg = use_in_memory;
+                let updates_msg = incoming_updates.clone();
+                let handle_message = move |msg_req: crate::transport::MessageRequest, source: Option<std::net::IpAddr>| -> crate::Result<()> {
+                    // Create storage connection for this handler
+                    let storage = if use_in_memory_msg {
+                        Storage::new_in_memory()?
+                    } else {
+                        Storage::new_with_default_path()?
+                    };
+
+                    let mut app_state = AppState::load_from_db(&storage)?;
+
+                    // Relay capabilities update the contact instead of landing in the chat
+                    if msg_req.message_type == RELAY_CAPABILITIES_TYPE {
+                        let capabilities = RelayCapabilities::from_payload(&msg_req.payload)?;
+                        if crate::relay::apply_capabilities(&mut app_state.contacts, &msg_req.from_uid, capabilities, Utc::now()) {
+                            app_state.save_to_db(&storage)?;
+                            updates_msg.store(true, std::sync::atomic::Ordering::SeqCst);
+                        }
+                        return Ok(());
+                    }
+
+                    // Key upgrade and token refresh requests are answered from the main loop
+                    if msg_req.message_type == KEY_UPGRADE_REQUEST_TYPE {
+                        if app_state.contact_by_uid(&msg_req.from_uid).is_some() {
+                            key_upgrade_requests.lock().unwrap().push(msg_req.from_uid);
+                            updates_msg.store(true, std::sync::atomic::Ordering::SeqCst);
+                        }
+                        return Ok(());
+                    }
+                    if msg_req.message_type == TOKEN_REFRESH_REQUEST_TYPE {
+                        if app_state.contact_by_uid(&msg_req.from_uid).is_some() {
+                            token_refresh_requests.lock().unwrap().push(msg_req.from_uid);
+                            updates_msg.store(true, std::sync::atomic::Ordering::SeqCst);
+                        }
+                        return Ok(());
+                    }
+                    if msg_req.message_type == KEY_UPGRADE_RESPONSE_TYPE {
+                        if apply_key_upgrade(&mut app_state.contacts, &msg_req.from_uid, &msg_req.payload)? {
+                            app_state.save_to_db(&storage)?;
+                            heard_from.lock().unwrap().push(msg_req.from_uid.clone());
+                            updates_msg.store(true, std::sync::atomic::Ordering::SeqCst);
+                            tracing::info!("Contact {} upgraded with an X25519 key", msg_req.from_uid);
+                        }
+                        return Ok(());
+                    }
+
+                    // Probes are handled from the main loop; a check only has to be accepted
+                    if is_probe_type(&msg_req.message_type) {
+                        match msg_req.message_type.as_str() {
+                            PROBE_REQUEST_TYPE => {
+                                let request = ProbeRequest::from_payload(&msg_req.payload)?;
+                                probe_requests.lock().unwrap().push((msg_req.from_uid, request));
+                                updates_msg.store(true, std::sync::atomic::Ordering::SeqCst);
+                            }
+                            PROBE_RESULT_TYPE => {
+                                let result = ProbeResult::from_payload(&msg_req.payload)?;
+                                probe_results.lock().unwrap().push((msg_req.from_uid, result));
+                                updates_msg.store(true, std::sync::atomic::Ordering::SeqCst);
+                            }
+                            PROBE_CHECK_TYPE => {
+                                tracing::debug!("Probe check from {} arrived", msg_req.from_uid);
+                            }
+                            _ => {}
+                        }
+                        return Ok(());
+                    }
+
+                    // Receipts, typing and presence are consumed, never stored as chat text
+                    if is_signal_type(&msg_req.message_type) {
+                        tracing::debug!("Received {} from {}", msg_req.message_type, msg_req.from_uid);
+                        return Ok(());
+                    }
+
+                    // Sealed text is opened before it is stored; opening it under the
+                    // stored key of a known contact authenticates the sender
+                    let protection = arrival_protection(&msg_req.message_type);
+                    let authenticated = msg_req.message_type == ENCRYPTED_TEXT_TYPE
+                        && app_state.contact_by_uid(&msg_req.from_uid).is_some_and(|c| c.x25519_pubkey.is_some());
+                    let msg_req = match &app_state.user_keypair {
+                        Some(keypair) => open_request(msg_req, keypair, &app_state.contacts)?,
+                        None => msg_req,
+                    };
+
+                    // Edits change a message already in the chat and never start one
+                    if msg_req.message_type == EDIT_TYPE {
+                        let mut edit = EditRequest::from_payload(&msg_req.payload)?;
+                        edit.edited_at = bounded_timestamp(edit.edited_at, Utc::now());
+                        let applied = app_state
+                            .chat_by_uid_mut(&msg_req.from_uid)
+                            .is_some_and(|chat| apply_incoming_edit(chat, &msg_req.from_uid, &edit));
+                        if applied {
+                            app_state.save_to_db(&storage)?;
+                            updates_msg.store(true, std::sync::atomic::Ordering::SeqCst);
+                        }
+                        tracing::debug!("Edit from {} for {} (applied: {})", msg_req.from_uid, edit.message_id, applied);
+                        return Ok(());
+                    }
+
+                    // Get to_uid before borrowing app_state mutably
+                    let to_uid = app_state.user_keypair.as_ref().map(|kp| kp.uid.to_string()).unwrap_or_default();
+                    let history_limit = app_state.settings.history_limit;
+
+                    // Chats for unknown senders count towards the auto-import cap
+                    if app_state.get_chat(&msg_req.from_uid).is_none() {
+                        let source = source.map(|ip| ip.to_string());
+                        admit_or_audit(&storage, &auto_import_limiter, false, true, source.as_deref(), Utc::now())?;
+                    }
+                    let chat = app_state.get_or_create_chat(&msg_req.from_uid);
+
+                    // Create message from the request
+                    let mut message = Message::new(
+                        uuid::Uuid::new_v4().to_string(),
+                        msg_req.from_uid.clone(),
+                        to_uid,
+                        msg_req.payload,
+                        Utc::now().timestamp_millis(),
+                    );
+                    message.metadata = msg_req.metadata;
+                    message.remote_id = msg_req.message_id;
+                    let message_id = message.id.clone();
+
+                    // Trimmed rows are deleted in the same transaction as the insert
+                    chat.append_with_limit(message, history_limit);
+                    chat.record_protection(&message_id, protection);
+                    chat.mark_unread(); // Mark as unread (new message received)
+                    chat.archived = false; // A new message brings an archived chat back
+
+                    // Propagate write failures (e.g. database locked) so the
+                    // sender gets 503 and retries instead of losing the message
+                    app_state.save_to_db(&storage)?;
+                    if authenticated && app_state.user_keypair.is_some() {
+                        heard_from.lock().unwrap().push(msg_req.from_uid.clone());
+                    }
+                    updates_msg.store(true, std::sync::atomic::Ordering::SeqCst);
+                    tracing::info!("Received message from {} (type: {})", msg_req.from_uid, msg_req.message_type);
+                    Ok(())
+                };
+                let reports_msg = reports.clone();
+                transport.set_new_message_handler_with_source(move |msg_req: crate::transport::MessageRequest, source| {
+                    let from_uid = msg_req.from_uid.clone();
+                    let result = handle_message(msg_req, source);
+                    if let Err(e) = &result
+                        && is_local_failure(e)
+                    {
+                        reports_msg.report(
+                            ErrorSeverity::Error,
+                            "message handler",
+                            format!("Failed to store a message from {}: {}", from_uid, e),
+                        );
+                    }
+                    result
+                }).await;
+                let _ = startup_events.send(StartupEvent::done(StartupStage::HandlersRegistered, ""));
+
+                // Try to start server with automatic port retry
+                match bind_verified(&transport, preferred_port, MAX_BIND_ATTEMPTS).await {
+                    Ok(port) => {
+                        *status.lock().unwrap() = TransportServerStatus::Running(port);
+
+                        // Always update database and app state with actual running port
+                        if let Some(mut app_state) = reports.check(ErrorSeverity::Error, "transport", AppState::load_from_db(&storage)) {
+                            app_state.user_port = port;
+                            if reports.check(ErrorSeverity::Error, "transport", app_state.save_to_db(&storage)).is_some() {
+                                tracing::info!("Updated database with running port: {}", port);
+                            }
+                        }
+
+                        // Publish the port for local tooling
+                        let runtime_info = RuntimeInfo::for_this_process(port, started_at);
+                        reports.check(ErrorSeverity::Warning, "transport", write_runtime_info(&runtime_info_path, &runtime_info));
+
+                        // Only now may connectivity map the port: it is final and stored
+                        let _ = startup_events.send(StartupEvent::listening(port));
+
+                        // Keep the runtime alive, probing our port until it can't be rebound
+                        tracing::info!("Transport server is running, keeping runtime alive");
+                        let mut watchdog = PortWatchdog::new(port)
+                            .with_events(connectivity_events)
+                            .with_runtime_info(runtime_info_path, runtime_info);
+                        let watch = async {
+                            while matches!(*status.lock().unwrap(), TransportServerStatus::Running(_)) {
+                                tokio::time::sleep(tokio::time::Duration::from_secs(WATCHDOG_INTERVAL_SECS)).await;
+                                watchdog.tick(&transport, &status, &storage, &port_alert, &reports).await;
+                            }
+                        };
+                        tokio::select! {
+                            _ = rx => {}
+                            _ = watch => {}
+                        }
+                    }
+                    Err(final_error) => {
+                        tracing::error!("{}", final_error);
+                        let _ = startup_events.send(StartupEvent::degraded(StartupStage::TransportListening, final_error.clone()));
+                        *status.lock().unwrap() = TransportServerStatus::Failed(final_error);
+                    }
+                }
+            });
+        });
+
+        Ok(())
+    }
+
+    /// Get the actual running port from transport server (or configured port if not running yet)
+    pub fn get_actual_port(&self) -> u16 {
+        if let Ok(status) = self.transport_server_status.lock() {
+            if let TransportServerStatus::Running(port) = *status {
+                return port;
+            }
+        }
+        self.local_port
+    }
+
+    /// Trigger startup connectivity diagnostics (non-blocking)
+    ///
+    /// Run by the startup graph once the transport listening stage is done,
+    /// so the port it maps is the one the server listens on and the
+    /// database holds.
+    pub fn trigger_startup_connectivity(&mut self) {
+        // Don't start if already running
+        if self.diagnostics_refresh_handle.is_some() {
+            return;
+        }
+
+        let port = self.startup.listening_port().unwrap_or_else(|| self.get_actual_port());
+        let events = self.connectivity_events.clone();
+        let handle = std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+            rt.block_on(crate::connectivity::establish_connectivity_with(port, &SystemStrategies, &events));
+        });
+
+        self.diagnostics_refresh_handle = Some(handle);
+    }
+
+    /// Poll for startup connectivity completion and update local IP
+    ///
+    /// Takes the finished check from the connectivity event stream.
+    ///
+    /// Returns true if connectivity completed this call.
+    pub fn poll_startup_connectivity(&mut self) -> bool {
+        let result = match self.take_connectivity_check() {
+            Some(Ok(result)) => result,
+            // Failed to get connectivity, keep default local_ip
+            Some(Err(e)) => {
+                self.settle_startup_connectivity(Err(e));
+                return true;
+            }
+            None => return false,
+        };
+
+        // Update local_ip and port from the mapping result
+        if let Some(mapping) = &result.mapping {
+            let detected_ip = format!("{}:{}", mapping.external_ip, mapping.external_port);
+            self.local_ip = detected_ip.clone();
+            // A new network may reach contacts the last cycle could not
+            self.retry_wakeup.notify();
+
+            // Save detected IP and port to app_state for persistence
+            self.app_state.user_ip = Some(detected_ip);
+            self.app_state.user_port = mapping.external_port;
+            self.save_or_report();
+        }
+        self.connectivity_result = Some(result.clone());
+        // The retry worker and the reachability check start from the startup graph
+        self.settle_startup_connectivity(Ok(&result));
+
+        // Apply result to diagnostics screen if it's already open
+        self.apply_connectivity_result(result);
+        true
+    }
+
+    /// Take the result of the running connectivity check, once it is known
+    ///
+    /// # Returns
+    /// The check's result, an error if its thread died without one, or
+    /// `None` while it is still running
+    fn take_connectivity_check(&mut self) -> Option<std::result::Result<ConnectivityResult, String>> {
+        // Whether the thread ended is read first: it emits its result before it ends
+        let finished = self.diagnostics_refresh_handle.as_ref().is_some_and(|handle| handle.is_finished());
+        self.poll_connectivity_events();
+        if let Some(result) = self.pending_connectivity_check.take() {
+            self.diagnostics_refresh_handle = None;
+            return Some(Ok(result));
+        }
+        if !finished {
+            return None;
+        }
+        match self.diagnostics_refresh_handle.take()?.join() {
+            Ok(()) => None,
+            Err(e) => Some(Err(format!("{:?}", e))),
+        }
+    }
+
+    /// Drain the connectivity event stream (non-blocking)
+    ///
+    /// Every event is written to the connectivity history. A health check
+    /// verdict is merged into `connectivity_result` unless a newer refresh
+    /// replaced the mapping it checked; a finished check is kept until the
+    /// startup or Diagnostics poll applies it.
+    ///
+    /// # Returns
+    /// Whether any event arrived
+    pub fn poll_connectivity_events(&mut self) -> bool {
+        use tokio::sync::broadcast::error::TryRecvError;
+
+        let mut received = false;
+        loop {
+            let event = match self.connectivity_rx.try_recv() {
+                Ok(event) => event,
+                Err(TryRecvError::Lagged(missed)) => {
+                    tracing::warn!("Missed {} connectivity events", missed);
+                    continue;
+                }
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            };
+            received = true;
+            let recorded = self.storage.record_connectivity_event(event.at(), event.kind(), &event.describe());
+            self.error_reports.check(ErrorSeverity::Warning, "connectivity history", recorded);
+            match event {
+                ConnectivityEvent::Checked { result, .. } => self.pending_connectivity_check = Some(*result),
+                ConnectivityEvent::ReachabilityChanged { mapping, reachable, .. } => {
+                    if let Some(result) = &mut self.connectivity_result
+                        && result.mapping.as_ref() == Some(&mapping)
+                    {
+                        result.externally_reachable = reachable;
+                        let result = result.clone();
+                        self.apply_connectivity_result(result);
+                    }
+                }
+                _ => {}
+            }
+        }
+        received
+    }
+
+    /// Get currently selected menu item
+    pub fn selected_item(&self) -> MenuItem {
+        self.menu_items[self.selected_index]
+    }
+
+    /// Move to next menu item
     pub fn next(&mut self) {
         self.selected_index = (self.selected_index + 1) % self.menu_items.len();
     }
 
-    /// Move to previous menu item
-    pub fn previous(&mut self) {
-        if self.selected_index > 0 {
-            self.selected_index -= 1;
+    /// Move to previous menu item
+    pub fn previous(&mut self) {
+        if self.selected_index > 0 {
+            self.selected_index -= 1;
+        } else {
+            self.selected_index = self.menu_items.len() - 1;
+        }
+    }
+
+    /// Select current menu item
+    pub fn select(&mut self) {
+        match self.selected_item() {
+            MenuItem::ChatList => {
+                self.show_chat_list_screen();
+            }
+            MenuItem::ShareContact => {
+                self.show_share_contact_screen();
+            }
+            MenuItem::ImportContact => {
+                self.show_import_contact_screen();
+            }
+            MenuItem::Diagnostics => {
+                self.show_diagnostics_screen();
+            }
+            MenuItem::Settings => {
+                self.show_settings_screen();
+            }
+            MenuItem::Exit => {
+                self.should_quit = true;
+            }
+        }
+    }
+
+    /// Show chat list screen
+    pub fn show_chat_list_screen(&mut self) {
+        // Reload state to pick up any new messages from transport handlers
+        self.reload_or_report();
+
+        self.chat_list_screen = Some(ChatListScreen::new());
+        self.current_screen = Screen::ChatList;
+    }
+
+    /// Show share contact screen
+    pub fn show_share_contact_screen(&mut self) {
+        if self.refuse_in_inspection("share a contact token") {
+            return;
+        }
+        let mut screen = ShareContactScreen::new(&self.keypair, &self.local_ip);
+        screen.set_cert_fingerprint(&self.keypair, self.tls_fingerprint.clone());
+        self.share_contact_screen = Some(screen);
+        self.current_screen = Screen::ShareContact;
+    }
+
+    /// Create an invite code for the shared token, redeemable over `POST /invite`
+    ///
+    /// The code uses the configured length and is valid until the token
+    /// expires; the status shows the code, with `weak_code_warning` appended.
+    pub fn create_invite(&mut self) {
+        let Some(screen) = &mut self.share_contact_screen else {
+            return;
+        };
+        let now = Utc::now();
+        let length = self.app_state.settings.invite_code_length;
+        let validity = screen.expiry - now;
+        let created = self
+            .transport
+            .invites()
+            .lock()
+            .unwrap()
+            .create(screen.token.clone(), length, validity, None, now);
+        screen.status_message = Some(match created {
+            Ok(code) => match weak_code_warning(length, validity) {
+                Some(warning) => format!("Invite code: {} {}", code, warning),
+                None => format!("Invite code: {}", code),
+            },
+            Err(e) => format!("Invite creation failed: {}", e),
+        });
+    }
+
+    /// Show import contact screen
+    pub fn show_import_contact_screen(&mut self) {
+        if self.refuse_in_inspection("import contacts") {
+            return;
+        }
+        let mut screen = ImportContactScreen::new();
+        let pending = self.storage.load_pending_introductions();
+        if let Some(pending) = self.error_reports.check(ErrorSeverity::Warning, "introductions", pending) {
+            screen.set_pending(pending);
+        }
+        self.import_contact_screen = Some(screen);
+        self.current_screen = Screen::ImportContact;
+    }
+
+    /// Show settings screen
+    pub fn show_settings_screen(&mut self) {
+        let current_interval = self.app_state.settings.retry_interval_minutes;
+        let mut screen = SettingsScreen::new(current_interval);
+        screen.load_quiet_hours(&self.app_state.settings);
+        screen.alert_mode = self.app_state.settings.alert_mode;
+        screen.alert_style = self.app_state.settings.alert_style;
+        screen.error_banner_severity = self.app_state.settings.error_banner_severity;
+        screen.relay_enabled = self.app_state.settings.relay_enabled;
+        screen.address_review_enabled = self.app_state.settings.address_review_enabled;
+        screen.auto_import_lifted = self.auto_import_limiter.lock().unwrap().lifted_until(Utc::now()).is_some();
+        screen.profile_label_input = self.app_state.settings.profile_label.clone();
+        screen.accent_color = self.app_state.settings.accent_color;
+        screen.history_limit_input = self.app_state.settings.history_limit.to_string();
+        screen.update_check_enabled = self.app_state.settings.update_check_enabled;
+        screen.tls_enabled = self.app_state.settings.tls_enabled;
+        screen.require_tls_external = self.app_state.settings.require_tls_external;
+        screen.journal_enabled = self.app_state.settings.journal_enabled;
+        screen.auto_send_preview = self.app_state.settings.auto_send_preview;
+        screen.night_warning_enabled = self.app_state.settings.night_warning_enabled;
+        self.settings_screen = Some(screen);
+        self.current_screen = Screen::Settings;
+    }
+
+    /// Validate the settings form and save it
+    pub fn save_settings(&mut self) {
+        if self.refuse_in_inspection("save settings") {
+            return;
+        }
+        let Some(screen) = &mut self.settings_screen else {
+            return;
+        };
+
+        let Some(minutes) = screen.validate() else {
+            return;
+        };
+        let Some(history_limit) = screen.validate_history_limit() else {
+            return;
+        };
+        let mut profile = self.app_state.settings.clone();
+        if let Err(e) = profile.set_profile_label(&screen.profile_label_input) {
+            screen.status_message = Some(format!("Error: {}", e));
+            screen.is_error = true;
+            return;
+        }
+        if !screen.apply_quiet_hours(&mut self.app_state.settings) {
+            return;
+        }
+
+        // A different label or accent is a different profile: confirm the next send again
+        let profile_changed = self.app_state.settings.profile_label != profile.profile_label
+            || self.app_state.settings.accent_color != screen.accent_color;
+        if profile_changed {
+            self.sending_as_confirmed = false;
+        }
+        self.app_state.settings.profile_label = profile.profile_label;
+        self.app_state.settings.accent_color = screen.accent_color;
+        self.app_state.settings.alert_mode = screen.alert_mode;
+        self.app_state.settings.alert_style = screen.alert_style;
+        self.app_state.settings.error_banner_severity = screen.error_banner_severity;
+
+        self.app_state.settings.retry_interval_minutes = minutes;
+        self.app_state.settings.global_retry_interval_ms = (minutes as u64) * 60 * 1000;
+        let relay_changed = self.app_state.settings.relay_enabled != screen.relay_enabled;
+        self.app_state.settings.relay_enabled = screen.relay_enabled;
+        self.app_state.settings.address_review_enabled = screen.address_review_enabled;
+        let lift = screen.auto_import_lifted;
+        // Takes effect lazily, at the next message added to each chat
+        self.app_state.settings.history_limit = history_limit;
+        self.app_state.settings.update_check_enabled = screen.update_check_enabled;
+        if !screen.update_check_enabled {
+            self.update_notice = None;
+        }
+        let tls_changed = self.app_state.settings.tls_enabled != screen.tls_enabled
+            || self.app_state.settings.require_tls_external != screen.require_tls_external;
+        self.app_state.settings.tls_enabled = screen.tls_enabled;
+        self.app_state.settings.require_tls_external = screen.require_tls_external;
+        let journal_switched_off = self.app_state.settings.journal_enabled && !screen.journal_enabled;
+        self.app_state.settings.journal_enabled = screen.journal_enabled;
+        self.app_state.settings.auto_send_preview = screen.auto_send_preview;
+        self.app_state.settings.night_warning_enabled = screen.night_warning_enabled;
+        screen.set_saved_message(minutes);
+
+        self.save_or_report();
+        self.update_quiet_hours();
+        // The worker replans with the new interval and quiet hours
+        self.retry_interval.store(
+            self.app_state.settings.get_global_retry_interval_ms(),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+        self.retry_wakeup.notify();
+        let now = Utc::now();
+        let lifted = self.auto_import_limiter.lock().unwrap().lifted_until(now).is_some();
+        if lift != lifted {
+            self.lift_auto_import_caps(lift, now);
+        }
+        if relay_changed {
+            self.advertise_relay_capabilities();
+        }
+        if tls_changed {
+            self.apply_tls_settings(now);
+        }
+        if journal_switched_off {
+            self.seal_journal(now);
+        }
+    }
+
+    /// Trim every chat to its history limit now instead of at its next message
+    ///
+    /// Loads message history first if startup deferred it. Reports the number
+    /// of removed messages in the settings status line.
+    pub fn apply_history_limit_now(&mut self) {
+        if self.refuse_in_inspection("change the history limit") {
+            return;
+        }
+        if let Err(e) = self.load_chat_messages() {
+            if let Some(screen) = &mut self.settings_screen {
+                screen.status_message = Some(format!("Error: {}", e));
+                screen.is_error = true;
+            }
+            return;
+        }
+
+        let removed = self.app_state.apply_history_limits();
+        let saved = self.save_state();
+        if let Some(screen) = &mut self.settings_screen {
+            screen.is_error = saved.is_err();
+            screen.status_message = Some(match saved {
+                Ok(()) => format!("History limit applied: {} old messages removed", removed),
+                Err(e) => format!("Error: {}", e),
+            });
+        }
+    }
+
+    /// Open the template list in the settings screen
+    pub fn open_template_manager(&mut self) {
+        if let Some(screen) = &mut self.settings_screen {
+            screen.template_selected = Some(0);
+            screen.status_message = Some(format!(
+                "{}/{} templates",
+                self.app_state.settings.templates.len(),
+                MAX_TEMPLATES
+            ));
+            screen.is_error = false;
+        }
+    }
+
+    /// Close the template list
+    pub fn close_template_manager(&mut self) {
+        if let Some(screen) = &mut self.settings_screen {
+            screen.template_selected = None;
+            screen.template_editor = None;
+        }
+    }
+
+    /// Open the editor for a new template
+    pub fn new_template(&mut self) {
+        if self.refuse_in_inspection("edit templates") {
+            return;
+        }
+        let Some(screen) = &mut self.settings_screen else {
+            return;
+        };
+        if self.app_state.settings.templates.len() >= MAX_TEMPLATES {
+            screen.status_message = Some(format!("Error: At most {} templates can be stored", MAX_TEMPLATES));
+            screen.is_error = true;
+            return;
+        }
+        screen.template_editor = Some(TemplateEditor::new());
+    }
+
+    /// Open the editor for the highlighted template
+    pub fn edit_selected_template(&mut self) {
+        if self.refuse_in_inspection("edit templates") {
+            return;
+        }
+        let Some(screen) = &mut self.settings_screen else {
+            return;
+        };
+        let Some(index) = screen.template_selected else {
+            return;
+        };
+        if let Some(template) = self.app_state.settings.templates.get(index) {
+            screen.template_editor = Some(TemplateEditor::for_template(index, template));
+        }
+    }
+
+    /// Delete the highlighted template
+    pub fn delete_selected_template(&mut self) {
+        if self.refuse_in_inspection("edit templates") {
+            return;
+        }
+        let Some(screen) = &mut self.settings_screen else {
+            return;
+        };
+        let Some(index) = screen.template_selected else {
+            return;
+        };
+        let Some(removed) = self.app_state.settings.remove_template(index) else {
+            return;
+        };
+
+        let remaining = self.app_state.settings.templates.len();
+        screen.template_selected = Some(index.min(remaining.saturating_sub(1)));
+        screen.status_message = Some(format!("✓ Deleted template '{}'", removed.name));
+        screen.is_error = false;
+        self.save_or_report();
+    }
+
+    /// Save the template editor contents, adding or replacing a template
+    pub fn save_template_editor(&mut self) {
+        let Some(screen) = &mut self.settings_screen else {
+            return;
+        };
+        let Some(editor) = &mut screen.template_editor else {
+            return;
+        };
+
+        let settings = &mut self.app_state.settings;
+        let result = match editor.index {
+            Some(index) => settings.update_template(index, &editor.name, &editor.body),
+            None => settings.add_template(&editor.name, &editor.body),
+        };
+        if let Err(e) = result {
+            let reason = match e {
+                crate::Error::Storage(msg) => msg,
+                other => other.to_string(),
+            };
+            editor.status_message = Some(format!("Error: {}", reason));
+            return;
+        }
+
+        let index = editor.index.unwrap_or(settings.templates.len() - 1);
+        screen.status_message = Some(format!("✓ Saved template '{}'", settings.templates[index].name));
+        screen.is_error = false;
+        screen.template_selected = Some(index);
+        screen.template_editor = None;
+        self.save_or_report();
+    }
+
+    /// Discard the template editor
+    pub fn cancel_template_editor(&mut self) {
+        if let Some(screen) = &mut self.settings_screen {
+            screen.template_editor = None;
+        }
+    }
+
+    /// Show diagnostics screen
+    pub fn show_diagnostics_screen(&mut self) {
+        let mut screen = DiagnosticsScreen::new(self.local_port);
+
+        // Populate with current app state data
+        self.update_diagnostics_with_app_state(&mut screen);
+
+        // Apply existing connectivity result if available
+        if let Some(result) = &self.connectivity_result {
+            screen.update_from_connectivity_result(result);
+        }
+        if let Some(history) = self.error_reports.check(
+            ErrorSeverity::Warning,
+            "storage",
+            probe_history(&self.storage, crate::tui::screens::PEER_PROBE_HISTORY_SHOWN),
+        ) {
+            screen.peer_probes = history;
+        }
+
+        self.diagnostics_screen = Some(screen);
+        self.current_screen = Screen::Diagnostics;
+    }
+
+    /// Show the snapshots screen (reached from Diagnostics)
+    pub fn show_snapshots_screen(&mut self) {
+        self.snapshots_screen = Some(SnapshotsScreen::new(list_snapshots(&self.snapshots_dir)));
+        self.current_screen = Screen::Snapshots;
+    }
+
+    /// Write a snapshot of the database to the snapshots folder, sealed with
+    /// this identity, and list it
+    ///
+    /// # Returns
+    /// The snapshot's path, if it was written
+    pub fn take_snapshot(&mut self) -> Option<std::path::PathBuf> {
+        if self.refuse_in_inspection("take snapshots") {
+            return None;
+        }
+        // Snapshot what the user sees, not what was last saved
+        self.save_or_report();
+        let path = self.snapshots_dir.join(snapshot_file_name(Utc::now()));
+        let result = self.storage.write_snapshot(&path, Some(&self.keypair));
+        if let Some(screen) = &mut self.snapshots_screen {
+            // New rows shift the old ones, so marks no longer point at them
+            screen.snapshots = list_snapshots(&self.snapshots_dir);
+            screen.marked.clear();
+            match &result {
+                Ok(()) => screen.set_status(format!("Snapshot saved to {}", path.display()), false),
+                Err(e) => screen.set_status(format!("Snapshot failed: {}", e), true),
+            }
+        }
+        result.ok().map(|()| path)
+    }
+
+    /// Compare the two marked snapshots, or the highlighted one with the live
+    /// database, and show the diff
+    pub fn compare_snapshots(&mut self) {
+        let Some(screen) = &mut self.snapshots_screen else {
+            return;
+        };
+        let Some((old_row, new_row)) = screen.comparison_rows() else {
+            screen.set_status("Highlight a snapshot, or mark two with Space".to_string(), true);
+            return;
+        };
+        let (Some(old), new) = (
+            screen.row_path(old_row).map(std::path::Path::to_path_buf),
+            screen.row_path(new_row).map(std::path::Path::to_path_buf),
+        ) else {
+            return;
+        };
+
+        let result = match new {
+            Some(new) => diff_snapshot_files(&old, &new, Some(&self.keypair)),
+            None => {
+                self.save_or_report();
+                self.storage.diff_against_snapshot(&old, Some(&self.keypair))
+            }
+        };
+        let Some(screen) = &mut self.snapshots_screen else {
+            return;
+        };
+        match result {
+            Ok(comparison) => {
+                let status = match &comparison {
+                    SnapshotComparison::Compared(diff) => (diff.summary(), false),
+                    SnapshotComparison::Encrypted(path) => (
+                        format!("{}: encrypted, cannot compare", path.file_name().unwrap_or_default().to_string_lossy()),
+                        true,
+                    ),
+                };
+                screen.show_comparison(comparison);
+                screen.set_status(status.0, status.1);
+            }
+            Err(e) => screen.set_status(format!("Compare failed: {}", e), true),
+        }
+    }
+
+    /// Update diagnostics screen with current app state
+    pub fn update_diagnostics_with_app_state(&self, screen: &mut DiagnosticsScreen) {
+        // Set IPv4 address from local_ip
+        if !self.local_ip.is_empty() {
+            // Parse to check if it's IPv4 or IPv6
+            if let Ok(ip) = self.local_ip.parse::<std::net::IpAddr>() {
+                if ip.is_ipv4() {
+                    screen.set_ipv4_address(Some(self.local_ip.clone()));
+                } else if ip.is_ipv6() {
+                    screen.set_ipv6_address(Some(self.local_ip.clone()));
+                }
+            } else {
+                // If it's not a valid IP, assume IPv4 for display
+                screen.set_ipv4_address(Some(self.local_ip.clone()));
+            }
+        }
+
+        // Set queue size from SQLite queue (not app_state.message_queue)
+        let queue_size = self.queue.count_pending().unwrap_or(0);
+        screen.set_queue_size(queue_size);
+        screen.dormant_count = self.queue.count_dormant().unwrap_or(0);
+        screen.blocked_count = self.queue.count_blocked().unwrap_or(0);
+    }
+
+    /// Refresh diagnostics screen with latest data
+    pub fn refresh_diagnostics(&mut self) {
+        // Extract necessary data first to avoid borrow conflicts
+        let local_ip = self.local_ip.clone();
+        let queue_size = self.queue.count_pending().unwrap_or(0);
+        let dormant_count = self.queue.count_dormant().unwrap_or(0);
+        let blocked_count = self.queue.count_blocked().unwrap_or(0);
+
+        if let Some(screen) = &mut self.diagnostics_screen {
+            // Set IPv4 address from local_ip
+            if !local_ip.is_empty() {
+                // Parse to check if it's IPv4 or IPv6
+                if let Ok(ip) = local_ip.parse::<std::net::IpAddr>() {
+                    if ip.is_ipv4() {
+                        screen.set_ipv4_address(Some(local_ip));
+                    } else if ip.is_ipv6() {
+                        screen.set_ipv6_address(Some(local_ip));
+                    }
+                } else {
+                    // If it's not a valid IP, assume IPv4 for display
+                    screen.set_ipv4_address(Some(local_ip));
+                }
+            }
+
+            // Set queue size
+            screen.set_queue_size(queue_size);
+            screen.dormant_count = dormant_count;
+            screen.blocked_count = blocked_count;
+        }
+    }
+
+    /// Trigger async diagnostics refresh (non-blocking)
+    ///
+    /// This spawns a background thread with a tokio runtime to perform
+    /// connectivity tests. Results are automatically polled in `poll_diagnostics_result()`.
+    pub fn trigger_diagnostics_refresh(&mut self) {
+        if self.refuse_in_inspection("run connectivity checks") {
+            if let Some(screen) = &mut self.diagnostics_screen {
+                screen.is_refreshing = false;
+            }
+            return;
+        }
+        // Don't start a new refresh if one (or a single-protocol action) is already running
+        if self.diagnostics_busy() {
+            return;
+        }
+
+        if let Some(screen) = &mut self.diagnostics_screen {
+            let port = screen.local_port;
+            let events = self.connectivity_events.clone();
+
+            // Spawn background thread with tokio runtime
+            let handle = std::thread::spawn(move || {
+                let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+                rt.block_on(crate::connectivity::establish_connectivity_with(port, &SystemStrategies, &events));
+            });
+
+            self.diagnostics_refresh_handle = Some(handle);
+        }
+    }
+
+    /// Poll for diagnostics refresh completion (non-blocking)
+    ///
+    /// Applies the finished check from the connectivity event stream.
+    /// Returns true if a refresh was completed this call.
+    pub fn poll_diagnostics_result(&mut self) -> bool {
+        let result = match self.take_connectivity_check() {
+            Some(Ok(result)) => result,
+            Some(Err(e)) => {
+                if let Some(screen) = &mut self.diagnostics_screen {
+                    screen.set_status_message(format!("Refresh failed: {}", e));
+                    screen.is_refreshing = false;
+                }
+                self.settle_startup_connectivity(Err(e));
+                return true;
+            }
+            None => return false,
+        };
+
+        // Update local_ip and port from the mapping result
+        if let Some(mapping) = &result.mapping {
+            let detected_ip = format!("{}:{}", mapping.external_ip, mapping.external_port);
+            self.local_ip = detected_ip.clone();
+            // A new network may reach contacts the last cycle could not
+            self.retry_wakeup.notify();
+
+            // Save detected IP and port to app_state for persistence
+            self.app_state.user_ip = Some(detected_ip);
+            self.app_state.user_port = mapping.external_port;
+            self.save_or_report();
+        }
+        self.connectivity_result = Some(result.clone());
+        self.settle_startup_connectivity(Ok(&result));
+        self.apply_connectivity_result(result);
+        true
+    }
+
+    /// Whether a full refresh or a single-protocol action is running
+    ///
+    /// Only one runs at a time; the Diagnostics screen greys out the other
+    /// actions meanwhile.
+    pub fn diagnostics_busy(&self) -> bool {
+        self.diagnostics_refresh_handle.is_some() || self.diagnostics_action_handle.is_some()
+    }
+
+    /// Start a Diagnostics screen action on a background thread
+    ///
+    /// # Returns
+    /// false (with a status message) if something else is running
+    fn start_diagnostics_action(&mut self, action: DiagnosticsAction) -> bool {
+        let busy = self.diagnostics_busy();
+        let Some(screen) = &mut self.diagnostics_screen else {
+            return false;
+        };
+        if busy {
+            screen.set_status_message("Wait for the running test to finish".to_string());
+            return false;
+        }
+
+        let actions = self.mapping_actions.clone();
+        self.diagnostics_action_handle = Some(std::thread::spawn(move || action.run(actions.as_ref())));
+        screen.pending_action = Some(action);
+        screen.set_status_message(match action {
+            DiagnosticsAction::Test { protocol, port } => {
+                format!("Testing {} on port {}...", protocol_name(protocol), port)
+            }
+            DiagnosticsAction::Delete { protocol, port } => {
+                format!("Deleting {} mapping for port {}...", protocol_name(protocol), port)
+            }
+        });
+        true
+    }
+
+    /// Test one protocol (PCP, NAT-PMP or UPnP) against the test port
+    ///
+    /// Only that protocol's panel is replaced when the test finishes; the
+    /// app's address and port are left alone.
+    ///
+    /// # Returns
+    /// Whether the test was started
+    pub fn run_single_protocol_test(&mut self, protocol: crate::connectivity::MappingProtocol) -> bool {
+        if self.refuse_in_inspection("run connectivity checks") {
+            return false;
+        }
+        let Some(port) = self.diagnostics_screen.as_ref().map(|s| s.test_port()) else {
+            return false;
+        };
+        self.start_diagnostics_action(DiagnosticsAction::Test { protocol, port })
+    }
+
+    /// Ask for confirmation before deleting the active mapping
+    pub fn request_delete_mapping(&mut self) {
+        if self.refuse_in_inspection("delete port mappings") {
+            return;
+        }
+        use crate::connectivity::MappingProtocol;
+
+        let busy = self.diagnostics_busy();
+        let Some(screen) = &mut self.diagnostics_screen else {
+            return;
+        };
+        if busy {
+            screen.set_status_message("Wait for the running test to finish".to_string());
+            return;
+        }
+        match screen.active_mapping() {
+            Some((_, mapping)) if matches!(mapping.protocol, MappingProtocol::IPv6 | MappingProtocol::Direct | MappingProtocol::Manual) => {
+                let name = protocol_name(mapping.protocol);
+                screen.set_status_message(format!("{} connectivity has no router mapping to delete", name));
+            }
+            Some(_) => screen.confirm_delete = true,
+            None => screen.set_status_message("No active mapping to delete".to_string()),
+        }
+    }
+
+    /// Delete the active mapping through the protocol that created it
+    ///
+    /// # Returns
+    /// Whether the deletion was started
+    pub fn confirm_delete_mapping(&mut self) -> bool {
+        let Some(screen) = &mut self.diagnostics_screen else {
+            return false;
+        };
+        screen.confirm_delete = false;
+        let Some((panel, mapping)) = screen.active_mapping() else {
+            return false;
+        };
+        let action = DiagnosticsAction::Delete {
+            protocol: mapping.protocol,
+            port: screen.tested_port(panel),
+        };
+        self.start_diagnostics_action(action)
+    }
+
+    /// Close the delete confirmation without deleting
+    pub fn cancel_delete_mapping(&mut self) {
+        if let Some(screen) = &mut self.diagnostics_screen {
+            screen.confirm_delete = false;
+        }
+    }
+
+    /// Poll for a finished Diagnostics screen action (non-blocking)
+    ///
+    /// # Returns
+    /// Whether an action finished this call
+    pub fn poll_diagnostics_action(&mut self) -> bool {
+        let Some(handle) = self.diagnostics_action_handle.take() else {
+            return false;
+        };
+        if !handle.is_finished() {
+            self.diagnostics_action_handle = Some(handle);
+            return false;
+        }
+
+        let result = handle.join();
+        let Some(screen) = &mut self.diagnostics_screen else {
+            return true;
+        };
+        screen.pending_action = None;
+        match result {
+            Ok(DiagnosticsActionResult::Tested { protocol, port, result }) => {
+                let name = protocol_name(protocol);
+                screen.set_status_message(match &result {
+                    Ok(mapping) => format!(
+                        "{} on port {}: mapped to {}:{}",
+                        name, port, mapping.external_ip, mapping.external_port
+                    ),
+                    Err(e) => format!("{} on port {} failed: {}", name, port, e),
+                });
+                screen.tested_ports.retain(|(p, _)| *p != protocol);
+                screen.tested_ports.push((protocol, port));
+                screen.set_protocol_status(protocol, Some(result));
+            }
+            Ok(DiagnosticsActionResult::Deleted { protocol, port, result }) => match result {
+                Ok(answer) => {
+                    screen.set_status_message(format!("Router: {}", answer));
+                    screen.set_protocol_status(protocol, None);
+                }
+                Err(e) => {
+                    screen.set_status_message(format!(
+                        "Deleting {} mapping for port {} failed: {}",
+                        protocol_name(protocol),
+                        port,
+                        e
+                    ));
+                }
+            },
+            Err(e) => screen.set_status_message(format!("Diagnostics action failed: {:?}", e)),
+        }
+        true
+    }
+
+    /// Start the daily update check if it is enabled and due
+    ///
+    /// The check is recorded before it runs, so a failing manifest host is
+    /// not asked again until the next day.
+    ///
+    /// # Returns
+    /// Whether a check was started
+    pub fn maybe_check_for_updates(&mut self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        if !self.app_state.settings.update_check_enabled
+            || self.inspection.is_some()
+            || self.update_check_handle.is_some()
+            || !update_check_due(self.last_update_check, now)
+        {
+            return false;
+        }
+        self.last_update_check = Some(now);
+        if let Err(e) = self.storage.record_update_check(now) {
+            tracing::warn!("Failed to record update check: {}", e);
+        }
+        let fetcher = self.update_fetcher.clone();
+        let url = self.app_state.settings.update_manifest_url.clone();
+        self.update_check_handle = Some(std::thread::spawn(move || {
+            run_update_check(fetcher.as_ref(), &url, &UPDATE_SIGNING_KEY)
+        }));
+        true
+    }
+
+    /// Poll for a finished update check (non-blocking)
+    ///
+    /// # Returns
+    /// Whether the check finished this call
+    pub fn poll_update_check(&mut self) -> bool {
+        let Some(handle) = self.update_check_handle.take() else {
+            return false;
+        };
+        if !handle.is_finished() {
+            self.update_check_handle = Some(handle);
+            return false;
+        }
+        match handle.join() {
+            Ok(Ok(notice)) => self.update_notice = notice,
+            Ok(Err(e)) => self.error_reports.report(ErrorSeverity::Info, "Update check", e),
+            Err(_) => self.error_reports.report(ErrorSeverity::Info, "Update check", "check panicked"),
+        }
+        true
+    }
+
+    /// Apply connectivity result to diagnostics screen and the footer indicator
+    pub fn apply_connectivity_result(&mut self, result: ConnectivityResult) {
+        if let Some(screen) = &mut self.diagnostics_screen {
+            screen.update_from_connectivity_result(&result);
+        }
+        self.refresh_connectivity_indicator();
+    }
+
+    /// Poll for the startup reachability check (non-blocking)
+    ///
+    /// Its verdict comes as a `ReachabilityChanged` event, merged by
+    /// `poll_connectivity_events` unless a newer refresh replaced the
+    /// mapping it checked.
+    ///
+    /// # Returns
+    /// Whether the check finished this call
+    pub fn poll_health_check(&mut self) -> bool {
+        // The thread emits its verdict before it ends, so read this first
+        let finished = self.health_check_handle.as_ref().is_some_and(|handle| handle.is_finished());
+        if finished {
+            self.health_check_handle = None;
+        }
+        self.poll_connectivity_events();
+        finished
+    }
+
+    /// Pick up transport server status changes made by the server thread
+    ///
+    /// Cheap enough for every loop iteration: the indicator is only
+    /// re-derived when the status differs from the one it was derived from.
+    ///
+    /// # Returns
+    /// Whether the indicator changed
+    pub fn poll_transport_status(&mut self) -> bool {
+        let current = match self.transport_server_status.lock() {
+            Ok(status) if *status != self.indicator_transport => status.clone(),
+            _ => return false,
+        };
+        self.indicator_transport = current;
+        self.refresh_connectivity_indicator()
+    }
+
+    /// Re-derive the footer connectivity indicator from the stored inputs
+    ///
+    /// # Returns
+    /// Whether the indicator changed
+    pub fn refresh_connectivity_indicator(&mut self) -> bool {
+        let indicator = ConnectivityIndicator::derive(&self.indicator_transport, self.connectivity_result.as_ref());
+        let changed = indicator != self.connectivity_indicator;
+        self.connectivity_indicator = indicator;
+        changed
+    }
+
+    /// Whether the focused input takes typed letters
+    ///
+    /// 'D' is typed there, so only Ctrl+D jumps to Diagnostics.
+    fn takes_text_input(&self) -> bool {
+        match self.current_screen {
+            Screen::ChatView | Screen::ImportContact | Screen::Settings => true,
+            Screen::ShareContact => self.share_contact_screen.as_ref().is_some_and(|s| s.new_endpoint_input.is_some()),
+            Screen::ChatList => self
+                .chat_list_screen
+                .as_ref()
+                .and_then(|s| s.contact_details.as_ref())
+                .is_some_and(|popup| popup.notes_editor.is_some()),
+            Screen::Diagnostics => self.diagnostics_screen.as_ref().is_some_and(|s| s.port_prompt.is_some()),
+            Screen::Snapshots => false,
+            Screen::Maintenance => false,
+            Screen::Capture => false,
+            Screen::Recovery => false,
+            Screen::MainMenu => false,
+        }
+    }
+
+    /// Key that jumps to Diagnostics on the current screen, for the footer
+    pub fn diagnostics_jump_hint(&self) -> &'static str {
+        if self.takes_text_input() { "Ctrl+D" } else { "D" }
+    }
+
+    /// Handle the jump-to-Diagnostics key ('D', or Ctrl+D while typing)
+    ///
+    /// # Arguments
+    /// * `c` - Character of the pressed key
+    /// * `ctrl` - Whether Ctrl was held
+    ///
+    /// # Returns
+    /// Whether the key was the jump key (and so is consumed)
+    pub fn handle_diagnostics_jump(&mut self, c: char, ctrl: bool) -> bool {
+        let is_jump = if ctrl { c == 'd' } else { c == 'D' && !self.takes_text_input() };
+        if is_jump && self.current_screen != Screen::Diagnostics {
+            self.show_diagnostics_screen();
+        }
+        is_jump
+    }
+
+    /// Return to main menu
+    pub fn back_to_main_menu(&mut self) {
+        // Reload state to pick up any changes while in other screens
+        self.reload_or_report();
+
+        self.current_screen = Screen::MainMenu;
+        self.share_contact_screen = None;
+        self.import_contact_screen = None;
+        self.chat_list_screen = None;
+        self.chat_view_screen = None;
+        self.settings_screen = None;
+        self.diagnostics_screen = None;
+    }
+
+    /// Return to chat list
+    pub fn back_to_chat_list(&mut self) {
+        // Reload state to show any new messages
+        self.reload_or_report();
+
+        self.current_screen = Screen::ChatList;
+        self.chat_view_screen = None;
+    }
+
+    /// Complete startup sync
+    pub fn complete_startup_sync(&mut self) {
+        self.current_screen = Screen::MainMenu;
+        self.startup_sync_screen = None;
+    }
+
+    /// Update startup sync progress
+    pub fn update_startup_sync(&mut self) {
+        // Simulate processing messages
+        // In a real implementation, this would actually send messages via the queue
+        if let Some(sync_screen) = &mut self.startup_sync_screen {
+            if !sync_screen.is_complete && sync_screen.current < sync_screen.total_messages {
+                // Simulate success/failure (80% success rate for demo)
+                let success = (sync_screen.current % 5) != 1;
+                sync_screen.process_message(success);
+            }
+        }
+    }
+
+    /// Open selected chat
+    pub fn open_selected_chat(&mut self) {
+        // Get selected index first to avoid borrow checker issues
+        let selected_index = if let Some(chat_list) = &self.chat_list_screen {
+            if self.app_state.chats.is_empty() {
+                return;
+            }
+            chat_list.selected_index
+        } else {
+            return;
+        };
+
+        let contact_uid = self.app_state.chats[selected_index].contact_uid.clone();
+        self.open_chat(&contact_uid);
+    }
+
+    /// Open the chat with `contact_uid`
+    ///
+    /// Every way of opening a chat goes through here, so it is recorded in
+    /// `recent_chats`.
+    pub fn open_chat(&mut self, contact_uid: &str) {
+        // Reload state before entering chat to show latest messages
+        self.reload_or_report();
+
+        self.recent_chats.retain(|uid| uid != contact_uid);
+        self.recent_chats.insert(0, contact_uid.to_string());
+        self.recent_chats.truncate(RECENT_CHATS);
+
+        // In the background the chat is not read until focus returns
+        if self.focus.is_focused() {
+            self.send_read_receipt(contact_uid);
+        }
+        // Marks for bulk actions do not outlive the visit to the list
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.marked.clear();
+        }
+        self.chat_view_screen = Some(ChatViewScreen::new(contact_uid.to_string()));
+        self.current_screen = Screen::ChatView;
+        self.refresh_queued_since();
+    }
+
+    /// Open the `number`th chat of the list (1-based, in list order)
+    ///
+    /// # Returns
+    /// Whether there is such a chat
+    pub fn open_chat_number(&mut self, number: usize) -> bool {
+        let Some(index) = number.checked_sub(1).filter(|&i| i < self.app_state.chats.len()) else {
+            if let Some(screen) = &mut self.chat_list_screen {
+                screen.set_status(format!("No chat {}", number));
+            }
+            return false;
+        };
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.selected_index = index;
+        }
+        let contact_uid = self.app_state.chats[index].contact_uid.clone();
+        self.open_chat(&contact_uid);
+        true
+    }
+
+    /// Tab in the chat list: toggle between the selection and the most
+    /// recently opened chat
+    pub fn toggle_recent_chat_selection(&mut self) {
+        let recent: Vec<usize> = self
+            .recent_chats
+            .iter()
+            .filter_map(|uid| self.app_state.chats.iter().position(|chat| &chat.contact_uid == uid))
+            .collect();
+        let chat_count = self.app_state.chats.len();
+        if let Some(screen) = &mut self.chat_list_screen {
+            if recent.is_empty() {
+                screen.set_status("No chat opened yet".to_string());
+                return;
+            }
+            screen.toggle_recent(&recent, chat_count);
+        }
+    }
+
+    /// '`' in a chat: switch to the chat opened before this one
+    ///
+    /// Only with an empty input, so a backtick can still be typed.
+    ///
+    /// # Returns
+    /// Whether the chat was switched (otherwise type '`' as usual)
+    pub fn switch_to_previous_chat(&mut self) -> bool {
+        let Some(screen) = &self.chat_view_screen else {
+            return false;
+        };
+        if !screen.input.is_empty() {
+            return false;
+        }
+        let previous = self
+            .recent_chats
+            .iter()
+            .find(|uid| **uid != screen.contact_uid && self.app_state.get_chat(uid).is_some())
+            .cloned();
+        let Some(previous) = previous else {
+            return false;
+        };
+        self.open_chat(&previous);
+        true
+    }
+
+    /// Acknowledge the newest incoming message of a chat being opened
+    ///
+    /// Sent once per message: reopening the chat without news sends nothing.
+    fn send_read_receipt(&mut self, contact_uid: &str) {
+        let newest_incoming = self.app_state.get_chat(contact_uid).and_then(|chat| {
+            chat.messages.iter().rev().find(|m| m.sender == contact_uid).map(|m| m.id.clone())
+        });
+        let Some(message_id) = newest_incoming else {
+            return;
+        };
+        if self.read_receipts_sent.get(contact_uid) != Some(&message_id)
+            && self.send_signal(contact_uid, PrivacySignal::ReadReceipts)
+        {
+            self.read_receipts_sent.insert(contact_uid.to_string(), message_id);
+        }
+    }
+
+    /// Tell the open chat's contact we are typing (at most every `TYPING_RESEND_SECS`)
+    pub fn note_typing(&mut self) {
+        let Some(contact_uid) = self.open_chat_uid() else {
+            return;
+        };
+        let now = std::time::Instant::now();
+        let resend = std::time::Duration::from_secs(TYPING_RESEND_SECS);
+        if self.typing_sent_at.get(&contact_uid).is_some_and(|sent| now.duration_since(*sent) < resend) {
+            return;
+        }
+        if self.send_signal(&contact_uid, PrivacySignal::Typing) {
+            self.typing_sent_at.insert(contact_uid, now);
+        }
+    }
+
+    /// Tell every contact we are online (temporary contacts are left out)
+    pub fn announce_presence(&self) {
+        for contact in self.app_state.contacts.iter().filter(|c| !c.is_ephemeral()) {
+            self.send_signal(&contact.uid, PrivacySignal::Presence);
+        }
+    }
+
+    /// Send a receipt, typing indicator or presence announcement in the background
+    ///
+    /// Best effort and never queued. The send path checks `signal_enabled`
+    /// again; checking here as well avoids starting a thread for nothing.
+    ///
+    /// # Returns
+    /// Whether the signal was handed to a sender thread
+    fn send_signal(&self, contact_uid: &str, signal: PrivacySignal) -> bool {
+        if self.inspection.is_some() {
+            return false;
+        }
+        let Some(contact) = self.app_state.contact_by_uid(contact_uid).filter(|c| !c.is_expired()).cloned() else {
+            return false;
+        };
+        if !signal_enabled(&self.app_state.settings, &contact, signal) {
+            return false;
+        }
+        let settings = self.app_state.settings.clone();
+        let transports = self.transports.clone();
+        let my_uid = self.keypair.uid.to_string();
+
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+            rt.block_on(async move {
+                let result = match signal {
+                    PrivacySignal::ReadReceipts => send_read_receipt(&transports, &settings, &contact, &my_uid, Utc::now()).await,
+                    PrivacySignal::Typing => send_typing(&transports, &settings, &contact, &my_uid).await,
+                    PrivacySignal::Presence => send_presence(&transports, &settings, &contact, &my_uid, true).await,
+                };
+                if let Err(e) = result {
+                    tracing::debug!("Could not send {} to {}: {}", signal.label(), contact.uid, e);
+                }
+            });
+        });
+        true
+    }
+
+    /// Show delete confirmation popup
+    pub fn show_delete_confirmation(&mut self) {
+        if self.refuse_in_inspection("delete chats") {
+            return;
+        }
+        if let Some(chat_list) = &mut self.chat_list_screen {
+            if self.app_state.chats.is_empty() {
+                return;
+            }
+            chat_list.show_delete_popup(chat_list.selected_index);
+        }
+    }
+
+    /// Confirm deletion of chat
+    ///
+    /// The chat is soft-deleted (see `storage::soft_delete`) and its queued
+    /// messages are suspended; U undoes it for `UNDO_STATUS_SECS`, the
+    /// Maintenance screen until it is purged.
+    pub fn confirm_delete_chat(&mut self) {
+        let Some(delete_index) = self.chat_list_screen.as_ref().and_then(|s| s.pending_delete_index) else {
+            return;
+        };
+        let Some(chat_uid) = self.app_state.chats.get(delete_index).map(|chat| chat.contact_uid.clone()) else {
+            return;
+        };
+
+        let status = self.soft_delete(&chat_uid, DeletedKind::Chat, false);
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.set_status(status);
+            screen.hide_delete_popup();
+
+            // Adjust selection if needed
+            if screen.selected_index >= self.app_state.chats.len() && !self.app_state.chats.is_empty() {
+                screen.selected_index = self.app_state.chats.len() - 1;
+            }
+        }
+
+        // TODO: Actually send delete request via transport if chat was active
+        // For now, we just delete locally
+    }
+
+    /// Cancel chat deletion
+    pub fn cancel_delete_chat(&mut self) {
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.hide_delete_popup();
+        }
+    }
+
+    /// Mark or unmark the highlighted chat for a bulk action (Space)
+    pub fn toggle_chat_marked(&mut self) {
+        let Some(screen) = &mut self.chat_list_screen else {
+            return;
+        };
+        if let Some(chat) = self.app_state.chats.get(screen.selected_index) {
+            screen.toggle_marked(&chat.contact_uid);
+        }
+    }
+
+    /// Mark every chat shown in the list (V)
+    ///
+    /// The list shows every chat, archived ones last, so all are marked.
+    pub fn mark_all_chats(&mut self) {
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.marked = self.app_state.chats.iter().map(|chat| chat.contact_uid.clone()).collect();
+        }
+    }
+
+    /// Drop the marks (Esc while chats are marked)
+    ///
+    /// # Returns
+    /// Whether any chat was marked
+    pub fn clear_chat_marks(&mut self) -> bool {
+        self.chat_list_screen
+            .as_mut()
+            .is_some_and(|screen| !std::mem::take(&mut screen.marked).is_empty())
+    }
+
+    /// Contact UIDs a bulk action applies to, in list order
+    ///
+    /// The marked chats still in the list, or the highlighted chat when
+    /// none is marked.
+    fn bulk_targets(&self) -> Vec<String> {
+        let Some(screen) = &self.chat_list_screen else {
+            return Vec::new();
+        };
+        if screen.marked.is_empty() {
+            return self.app_state.chats.get(screen.selected_index).map(|chat| vec![chat.contact_uid.clone()]).unwrap_or_default();
+        }
+        screen.marked_in(&self.app_state.chats).into_iter().map(|chat| chat.contact_uid.clone()).collect()
+    }
+
+    /// Apply `change` to the chats of `uids` and save them in one write
+    ///
+    /// The marks are dropped and `status(count)` is shown.
+    fn apply_to_chats(&mut self, uids: &[String], change: impl Fn(&mut crate::storage::Chat), status: impl Fn(usize) -> String) {
+        if uids.is_empty() {
+            return;
+        }
+        for uid in uids {
+            if let Some(chat) = self.app_state.chat_by_uid_mut(uid) {
+                change(chat);
+            }
+        }
+        self.save_or_report();
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.marked.clear();
+            screen.set_status(status(uids.len()));
+        }
+    }
+
+    /// Archive the marked chats, or bring them back if all are archived (a)
+    ///
+    /// Archived chats are listed last until a new message arrives; the
+    /// highlight stays on the chat it was on.
+    pub fn archive_marked_chats(&mut self) {
+        if self.refuse_in_inspection("archive chats") {
+            return;
+        }
+        let uids = self.bulk_targets();
+        let archive = !uids.iter().all(|uid| self.app_state.chat_by_uid(uid).is_some_and(|chat| chat.archived));
+        let highlighted = self
+            .chat_list_screen
+            .as_ref()
+            .and_then(|screen| self.app_state.chats.get(screen.selected_index))
+            .map(|chat| chat.contact_uid.clone());
+        self.apply_to_chats(&uids, |chat| chat.archived = archive, |count| {
+            format!("{} {} chat(s)", if archive { "Archived" } else { "Unarchived" }, count)
+        });
+        self.app_state.sort_archived_last();
+        if let Some(screen) = &mut self.chat_list_screen
+            && let Some(position) = highlighted.and_then(|uid| self.app_state.chats.iter().position(|chat| chat.contact_uid == uid))
+        {
+            screen.selected_index = position;
+        }
+    }
+
+    /// Mark the marked chats read (r)
+    ///
+    /// Only the unread indicator changes: no read receipts are sent, as
+    /// nothing was read.
+    pub fn mark_marked_chats_read(&mut self) {
+        if self.refuse_in_inspection("mark chats read") {
+            return;
+        }
+        let uids = self.bulk_targets();
+        self.apply_to_chats(&uids, |chat| chat.mark_read(), |count| format!("Marked {} chat(s) read", count));
+    }
+
+    /// Mute the marked chats, or unmute them if all are muted (m)
+    pub fn mute_marked_chats(&mut self) {
+        if self.refuse_in_inspection("mute chats") {
+            return;
+        }
+        let uids = self.bulk_targets();
+        let mute = !uids.iter().all(|uid| self.app_state.chat_by_uid(uid).is_some_and(|chat| chat.muted));
+        self.apply_to_chats(&uids, |chat| chat.muted = mute, |count| {
+            format!("{} {} chat(s)", if mute { "Muted" } else { "Unmuted" }, count)
+        });
+    }
+
+    /// Ask to delete the marked chats (d while chats are marked)
+    pub fn request_bulk_delete(&mut self) {
+        if self.refuse_in_inspection("delete chats") {
+            return;
+        }
+        if let Some(screen) = &mut self.chat_list_screen
+            && !screen.marked_in(&self.app_state.chats).is_empty()
+        {
+            screen.show_bulk_delete_confirmation = true;
+        }
+    }
+
+    /// Answer the bulk delete confirmation
+    ///
+    /// The chats are soft-deleted together in one write, each with its own
+    /// undo entry (U undoes the last one, the Maintenance screen any).
+    pub fn answer_bulk_delete(&mut self, delete: bool) {
+        let Some(screen) = &mut self.chat_list_screen else {
+            return;
+        };
+        if !std::mem::take(&mut screen.show_bulk_delete_confirmation) || !delete {
+            return;
+        }
+        let uids: Vec<String> = screen.marked_in(&self.app_state.chats).into_iter().map(|chat| chat.contact_uid.clone()).collect();
+        let deleted = self.soft_delete_chats(&uids);
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.marked.clear();
+            screen.set_status(format!("Deleted {} chat(s) — press U to undo the last", deleted));
+            if screen.selected_index >= self.app_state.chats.len() {
+                screen.selected_index = self.app_state.chats.len().saturating_sub(1);
+            }
+        }
+    }
+
+    /// Show contact details popup for the selected chat
+    pub fn show_contact_details(&mut self) {
+        let Some(chat_list) = &mut self.chat_list_screen else {
+            return;
+        };
+        chat_list.contact_details = self.app_state.chats
+            .get(chat_list.selected_index)
+            .map(|chat| ContactDetailsPopup::new(chat.contact_uid.clone()));
+    }
+
+    /// Close contact details popup
+    pub fn close_contact_details(&mut self) {
+        if let Some(chat_list) = &mut self.chat_list_screen {
+            chat_list.contact_details = None;
+        }
+    }
+
+    /// Open the identity conflict review popup (if anything awaits review)
+    pub fn open_conflict_review(&mut self) {
+        if let Some(chat_list) = &mut self.chat_list_screen
+            && !self.app_state.identity_conflicts.is_empty()
+        {
+            chat_list.conflict_review = Some(0);
+        }
+    }
+
+    /// Close the identity conflict review popup
+    pub fn close_conflict_review(&mut self) {
+        if let Some(chat_list) = &mut self.chat_list_screen {
+            chat_list.conflict_review = None;
+        }
+    }
+
+    /// Move the selection in the conflict review popup (wraps around)
+    pub fn move_conflict_selection(&mut self, forward: bool) {
+        let count = self.app_state.identity_conflicts.len();
+        if let Some(selected) = self.chat_list_screen.as_mut().and_then(|s| s.conflict_review.as_mut())
+            && count > 0
+        {
+            *selected = if forward { (*selected + 1) % count } else { (*selected + count - 1) % count };
+        }
+    }
+
+    /// Dismiss the selected identity conflict
+    ///
+    /// The held-back contact is discarded; stored contacts are not touched.
+    /// The popup closes once nothing is left to review.
+    pub fn dismiss_selected_conflict(&mut self) {
+        if self.refuse_in_inspection("dismiss identity conflicts") {
+            return;
+        }
+        let Some(selected) = self.chat_list_screen.as_ref().and_then(|s| s.conflict_review) else {
+            return;
+        };
+        if selected >= self.app_state.identity_conflicts.len() {
+            return;
+        }
+        let conflict = self.app_state.identity_conflicts.remove(selected);
+        let remaining = self.app_state.identity_conflicts.len();
+        if let Some(chat_list) = &mut self.chat_list_screen {
+            chat_list.conflict_review = (remaining > 0).then(|| selected.min(remaining - 1));
+            chat_list.set_status(format!(
+                "Dismissed identity conflict for {}",
+                &conflict.contact.uid[..16.min(conflict.contact.uid.len())]
+            ));
+        }
+        self.save_or_report();
+    }
+
+    /// Cycle the history limit override of the chat shown in the details popup
+    pub fn cycle_chat_history_limit(&mut self) {
+        if self.refuse_in_inspection("change the history limit") {
+            return;
+        }
+        let Some(popup) = self.chat_list_screen.as_ref().and_then(|s| s.contact_details.as_ref()) else {
+            return;
+        };
+        if let Some(chat) = self.app_state.chat_by_uid_mut(&popup.contact_uid) {
+            chat.cycle_history_limit();
+            self.save_or_report();
+        }
+    }
+
+    /// Mark the contact shown in the details popup as verified, or clear the mark
+    pub fn toggle_contact_verified(&mut self) {
+        if self.refuse_in_inspection("change contacts") {
+            return;
+        }
+        let Some(popup) = self.chat_list_screen.as_ref().and_then(|s| s.contact_details.as_ref()) else {
+            return;
+        };
+        if let Some(contact) = self.app_state.contact_by_uid_mut(&popup.contact_uid) {
+            contact.verified = !contact.verified;
+            self.save_or_report();
+        }
+    }
+
+    /// Keep the temporary contact shown in the details popup permanently
+    pub fn make_contact_permanent(&mut self) {
+        if self.refuse_in_inspection("change contacts") {
+            return;
+        }
+        let Some(popup) = self.chat_list_screen.as_ref().and_then(|s| s.contact_details.as_ref()) else {
+            return;
+        };
+        let uid = popup.contact_uid.clone();
+        if self.app_state.make_permanent(&uid) {
+            self.save_or_report();
+            self.sync_relay();
+            if let Some(screen) = &mut self.chat_list_screen {
+                screen.set_status("Contact kept permanently".to_string());
+            }
+        }
+    }
+
+    /// Whether `contact_uid`'s traffic is being captured (chat badge)
+    pub fn capture_badge(&self, contact_uid: &str) -> bool {
+        self.transport.capture().is_capturing(contact_uid, Utc::now())
+    }
+
+    /// Stop capturing the contact in the details popup, or ask before starting
+    ///
+    /// A capture is never started without the consent prompt.
+    pub fn toggle_contact_capture(&mut self) {
+        if self.refuse_in_inspection("capture traffic") {
+            return;
+        }
+        let Some(popup) = self.chat_list_screen.as_mut().and_then(|s| s.contact_details.as_mut()) else {
+            return;
+        };
+        if self.transport.capture().is_capturing(&popup.contact_uid, Utc::now()) {
+            let stopped = self.transport.capture().stop();
+            if let (Some(screen), Some(path)) = (&mut self.chat_list_screen, stopped) {
+                screen.set_status(format!("Capture stopped: {}", path.display()));
+            }
+        } else {
+            popup.capture_prompt = Some(CapturePrompt::Confirm);
+        }
+    }
+
+    /// Answer the capture consent prompt of the details popup
+    ///
+    /// 'y' at the first step captures with redacted bodies, 'b' asks the
+    /// second confirmation, 'y' there captures with bodies; anything else
+    /// cancels.
+    pub fn answer_capture_prompt(&mut self, c: char) {
+        let Some(popup) = self.chat_list_screen.as_mut().and_then(|s| s.contact_details.as_mut()) else {
+            return;
+        };
+        let uid = popup.contact_uid.clone();
+        match (popup.capture_prompt.take(), c.to_ascii_lowercase()) {
+            (Some(CapturePrompt::Confirm), 'y') => self.start_capture(&uid, false),
+            (Some(CapturePrompt::Confirm), 'b') => popup.capture_prompt = Some(CapturePrompt::ConfirmBodies),
+            (Some(CapturePrompt::ConfirmBodies), 'y') => self.start_capture(&uid, true),
+            _ => {}
+        }
+    }
+
+    /// Start capturing `contact_uid`, replacing any running capture
+    pub fn start_capture(&mut self, contact_uid: &str, include_bodies: bool) {
+        let result = self.transport.capture().start(contact_uid, include_bodies, &self.captures_dir, Utc::now());
+        let status = match self.error_reports.check(ErrorSeverity::Warning, "capture", result) {
+            Some(path) => format!("Capturing {} into {}", &contact_uid[..16.min(contact_uid.len())], path.display()),
+            None => "Could not start the capture".to_string(),
+        };
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.set_status(status);
+        }
+    }
+
+    /// Show the latest capture of the contact in the details popup
+    pub fn show_capture_screen(&mut self) {
+        let Some(popup) = self.chat_list_screen.as_ref().and_then(|s| s.contact_details.as_ref()) else {
+            return;
+        };
+        let prefix = capture_file_name(&popup.contact_uid, Utc::now());
+        let prefix = &prefix[..prefix.rfind('-').unwrap_or(0) + 1];
+        let latest = list_captures(&self.captures_dir)
+            .into_iter()
+            .find(|p| p.file_name().is_some_and(|n| n.to_string_lossy().starts_with(prefix)));
+        match latest {
+            Some(path) => {
+                self.capture_screen = Some(CaptureScreen::new(path));
+                self.current_screen = Screen::Capture;
+            }
+            None => {
+                if let Some(screen) = &mut self.chat_list_screen {
+                    screen.set_status("No capture of this contact yet".to_string());
+                }
+            }
+        }
+    }
+
+    /// Leave the capture screen for the screen it was opened from (for a
+    /// capture, the chat list with the details popup still open)
+    pub fn close_capture_screen(&mut self) {
+        let back_to = self.capture_screen.take().map_or(Screen::ChatList, |screen| screen.back_to);
+        self.current_screen = back_to;
+    }
+
+    /// Apply or ignore the address change staged for the contact in the details popup
+    pub fn resolve_address_change(&mut self, apply: bool) {
+        if self.refuse_in_inspection("change contacts") {
+            return;
+        }
+        let Some(popup) = self.chat_list_screen.as_ref().and_then(|s| s.contact_details.as_ref()) else {
+            return;
+        };
+        let uid = popup.contact_uid.clone();
+        let resolved = if apply {
+            self.app_state.apply_address_change(&uid)
+        } else {
+            self.app_state.ignore_address_change(&uid)
+        };
+        if resolved {
+            self.save_or_report();
+            if let Some(screen) = &mut self.chat_list_screen {
+                screen.set_status(if apply { "New address applied" } else { "Address change ignored" }.to_string());
+            }
+        }
+    }
+
+    /// Mute or unmute the chat of the contact shown in the details popup
+    ///
+    /// Muted chats raise no notification and no in-app alert.
+    pub fn toggle_chat_muted(&mut self) {
+        if self.refuse_in_inspection("mute chats") {
+            return;
+        }
+        let Some(popup) = self.chat_list_screen.as_ref().and_then(|s| s.contact_details.as_ref()) else {
+            return;
+        };
+        if let Some(chat) = self.app_state.chat_by_uid_mut(&popup.contact_uid) {
+            chat.muted = !chat.muted;
+            self.save_or_report();
+        }
+    }
+
+    /// Cycle the alert style of the contact shown in the details popup
+    ///
+    /// Goes from the global default through each style and back.
+    pub fn cycle_contact_alert_style(&mut self) {
+        if self.refuse_in_inspection("change contacts") {
+            return;
+        }
+        let Some(popup) = self.chat_list_screen.as_ref().and_then(|s| s.contact_details.as_ref()) else {
+            return;
+        };
+        let uid = popup.contact_uid.clone();
+        let Some(contact) = self.app_state.contact_by_uid_mut(&uid) else {
+            return;
+        };
+        contact.alert_style = AlertStyle::cycle_override(contact.alert_style);
+        self.save_or_report();
+    }
+
+    /// Play the alert style of the contact shown in the details popup now
+    ///
+    /// Ignores mute and quiet hours, which only hold back real alerts.
+    pub fn test_contact_alert_style(&mut self) {
+        let Some(popup) = self.chat_list_screen.as_ref().and_then(|s| s.contact_details.as_ref()) else {
+            return;
+        };
+        let contact_style = self.app_state.contact_by_uid(&popup.contact_uid).and_then(|c| c.alert_style);
+        let style = alerts::resolve_alert_style(contact_style, self.app_state.settings.alert_style, false, false);
+        let status = if self.app_state.settings.alert_mode == AlertMode::None {
+            "In-app alerts are off (Settings: Alert)".to_string()
+        } else {
+            format!("Playing the '{}' alert", style.name())
+        };
+        self.play_alert(style, std::time::Instant::now());
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.set_status(status);
+        }
+    }
+
+    /// Switch the details popup contact between the normal and restricted trust tier
+    ///
+    /// Capabilities are advertised again, so the contact and the relay
+    /// learn the change.
+    pub fn toggle_contact_trust(&mut self) {
+        if self.refuse_in_inspection("change contacts") {
+            return;
+        }
+        let Some(popup) = self.chat_list_screen.as_ref().and_then(|s| s.contact_details.as_ref()) else {
+            return;
+        };
+        let uid = popup.contact_uid.clone();
+        let Some(contact) = self.app_state.contact_by_uid_mut(&uid) else {
+            return;
+        };
+        contact.trust = contact.trust.toggled();
+        let trust = contact.trust;
+        self.save_or_report();
+        self.sync_relay();
+        self.advertise_relay_capabilities();
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.set_status(match trust {
+                TrustTier::Normal => "Contact trusted normally".to_string(),
+                TrustTier::Restricted => "Contact restricted: minimal profile, no signals, no introductions".to_string(),
+            });
+        }
+    }
+
+    /// Step the details popup contact's override for `signal`: default → on → off
+    pub fn cycle_contact_signal(&mut self, signal: PrivacySignal) {
+        if self.refuse_in_inspection("change contacts") {
+            return;
+        }
+        let Some(popup) = self.chat_list_screen.as_ref().and_then(|s| s.contact_details.as_ref()) else {
+            return;
+        };
+        if let Some(contact) = self.app_state.contact_by_uid_mut(&popup.contact_uid) {
+            let choice = contact.privacy.get_mut(signal);
+            *choice = choice.cycle();
+            self.save_or_report();
+        }
+    }
+
+    /// Open the notes editor for the contact shown in the details popup
+    pub fn start_editing_notes(&mut self) {
+        if self.refuse_in_inspection("edit contact notes") {
+            return;
+        }
+        if let Some(popup) = self.chat_list_screen.as_mut().and_then(|s| s.contact_details.as_mut()) {
+            let notes = self.app_state.contact_by_uid(&popup.contact_uid)
+                .map(|c| c.notes.as_str())
+                .unwrap_or("");
+            popup.notes_editor = Some(NotesEditor::new(notes));
+        }
+    }
+
+    /// Discard edits and close the notes editor
+    pub fn cancel_editing_notes(&mut self) {
+        if let Some(popup) = self.chat_list_screen.as_mut().and_then(|s| s.contact_details.as_mut()) {
+            popup.notes_editor = None;
+        }
+    }
+
+    /// Save the notes editor contents to the contact
+    pub fn save_contact_notes(&mut self) {
+        let Some(popup) = self.chat_list_screen.as_mut().and_then(|s| s.contact_details.as_mut()) else {
+            return;
+        };
+        let Some(editor) = &mut popup.notes_editor else {
+            return;
+        };
+
+        let Some(contact) = self.app_state.contact_by_uid_mut(&popup.contact_uid) else {
+            editor.status_message = Some("Error: Contact not found".to_string());
+            return;
+        };
+
+        if let Err(e) = contact.set_notes(&editor.buffer) {
+            editor.status_message = Some(format!("Error: {}", e));
+            return;
+        }
+
+        popup.notes_editor = None;
+        self.save_or_report();
+    }
+
+    /// Open the UTC offset input for the contact shown in the details popup
+    ///
+    /// Pre-filled with the offset set by hand, if any.
+    pub fn start_editing_utc_offset(&mut self) {
+        if self.refuse_in_inspection("change contacts") {
+            return;
+        }
+        if let Some(popup) = self.chat_list_screen.as_mut().and_then(|s| s.contact_details.as_mut()) {
+            let manual = self.app_state.contact_by_uid(&popup.contact_uid).and_then(|c| c.utc_offset_override);
+            popup.offset_input = Some(manual.map(format_utc_offset).unwrap_or_default());
+        }
+    }
+
+    /// Apply the UTC offset input to the contact in the details popup
+    ///
+    /// An empty input clears the override, so the offset is inferred again;
+    /// an invalid one keeps the input open.
+    pub fn save_contact_utc_offset(&mut self) {
+        let Some(screen) = self.chat_list_screen.as_mut() else {
+            return;
+        };
+        let Some((uid, input)) = screen
+            .contact_details
+            .as_ref()
+            .and_then(|popup| Some((popup.contact_uid.clone(), popup.offset_input.clone()?)))
+        else {
+            return;
+        };
+        let offset = if input.trim().is_empty() {
+            None
+        } else {
+            match parse_utc_offset(&input) {
+                Some(minutes) => Some(minutes),
+                None => {
+                    screen.set_status(format!("Not a UTC offset: '{}' (e.g. +5:30, -3, UTC+05:45)", input.trim()));
+                    return;
+                }
+            }
+        };
+        if let Some(popup) = screen.contact_details.as_mut() {
+            popup.offset_input = None;
+        }
+        let Some(contact) = self.app_state.contact_by_uid_mut(&uid) else {
+            return;
+        };
+        contact.utc_offset_override = offset;
+        screen.set_status(match offset {
+            Some(minutes) => format!("UTC offset set to {}", format_utc_offset(minutes)),
+            None => "UTC offset will be inferred from messages".to_string(),
+        });
+        self.save_or_report();
+    }
+
+    /// Ask whether to keep the notes before deleting the contact in the details popup
+    pub fn request_delete_contact(&mut self) {
+        if self.refuse_in_inspection("delete contacts") {
+            return;
+        }
+        if let Some(popup) = self.chat_list_screen.as_mut().and_then(|s| s.contact_details.as_mut()) {
+            popup.confirm_delete = true;
+        }
+    }
+
+    /// Delete the contact shown in the details popup along with its chat
+    ///
+    /// # Arguments
+    /// * `keep_notes` - Retain the contact's notes so they are restored if the
+    ///   contact is imported again
+    pub fn confirm_delete_contact(&mut self, keep_notes: bool) {
+        let Some(contact_uid) = self.chat_list_screen
+            .as_ref()
+            .and_then(|s| s.contact_details.as_ref())
+            .map(|p| p.contact_uid.clone())
+        else {
+            return;
+        };
+
+        let notes = self.app_state.contact_by_uid(&contact_uid)
+            .map(|c| c.notes.as_str())
+            .unwrap_or("");
+        if keep_notes && !notes.is_empty() {
+            self.error_reports.check(ErrorSeverity::Warning, "storage", self.storage.retain_contact_notes(&contact_uid, notes));
+        }
+
+        let status = self.soft_delete(&contact_uid, DeletedKind::Contact, keep_notes && !notes.is_empty());
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.contact_details = None;
+            let note_msg = if keep_notes { "notes kept" } else { "notes discarded" };
+            screen.set_status(format!("{} ({})", status, note_msg));
+
+            if screen.selected_index >= self.app_state.chats.len() && !self.app_state.chats.is_empty() {
+                screen.selected_index = self.app_state.chats.len() - 1;
+            }
+        }
+    }
+
+    /// Soft-delete a chat, or a contact with its chat, and record the undo entry
+    ///
+    /// # Returns
+    /// The chat list status offering the undo
+    fn soft_delete(&mut self, uid: &str, kind: DeletedKind, retained_notes: bool) -> String {
+        let now = Utc::now();
+        let chat_position = self.app_state.chats.iter().position(|chat| chat.contact_uid == uid);
+        let chat = chat_position.zip(self.app_state.remove_chat(uid));
+        let (contact, address_change) = match kind {
+            DeletedKind::Chat => (None, None),
+            DeletedKind::Contact => {
+                let address_change = self.app_state.address_change(uid).cloned();
+                self.app_state.ignore_address_change(uid);
+                let position = self.app_state.contacts.iter().position(|contact| contact.uid == uid);
+                (position.zip(self.app_state.remove_contact(uid)), address_change)
+            }
+        };
+
+        let storage = &self.storage;
+        self.error_reports.check(
+            ErrorSeverity::Error,
+            "storage",
+            storage.with_write_retry(|| {
+                storage.soft_delete_chat(uid, now)?;
+                if kind == DeletedKind::Contact {
+                    storage.soft_delete_contact(uid, now)?;
+                }
+                Ok(())
+            }),
+        );
+        self.error_reports.check(ErrorSeverity::Warning, "message queue", self.queue.suspend_for(uid, now.timestamp_millis()));
+
+        let entry = UndoEntry {
+            uid: uid.to_string(),
+            kind,
+            deleted_at: now,
+            chat,
+            contact,
+            address_change,
+            retained_notes,
+            state: UndoState::Pending,
+        };
+        let status = entry.status_text();
+        self.undo.push(entry);
+        self.save_or_report();
+        status
+    }
+
+    /// Soft-delete the chats of `uids` in one transaction (see `soft_delete`)
+    ///
+    /// # Returns
+    /// How many chats were deleted
+    fn soft_delete_chats(&mut self, uids: &[String]) -> usize {
+        let now = Utc::now();
+        let mut entries = Vec::new();
+        for uid in uids {
+            let position = self.app_state.chats.iter().position(|chat| chat.contact_uid == *uid);
+            let Some(chat) = position.zip(self.app_state.remove_chat(uid)) else {
+                continue;
+            };
+            entries.push(UndoEntry {
+                uid: uid.clone(),
+                kind: DeletedKind::Chat,
+                deleted_at: now,
+                chat: Some(chat),
+                contact: None,
+                address_change: None,
+                retained_notes: false,
+                state: UndoState::Pending,
+            });
+        }
+
+        let storage = &self.storage;
+        self.error_reports.check(
+            ErrorSeverity::Error,
+            "storage",
+            storage.with_write_retry(|| {
+                for entry in &entries {
+                    storage.soft_delete_chat(&entry.uid, now)?;
+                }
+                Ok(())
+            }),
+        );
+        for entry in &entries {
+            self.error_reports.check(ErrorSeverity::Warning, "message queue", self.queue.suspend_for(&entry.uid, now.timestamp_millis()));
+        }
+        let deleted = entries.len();
+        for entry in entries {
+            self.undo.push(entry);
+        }
+        deleted
+    }
+
+    /// Undo the latest delete while the chat list still offers it (U)
+    ///
+    /// # Returns
+    /// Whether a delete was undone
+    pub fn undo_last_delete(&mut self, now: chrono::DateTime<Utc>) -> bool {
+        if self.refuse_in_inspection("undo deletes") {
+            return false;
+        }
+        let Some(index) = self.undo.status_entry(now) else {
+            return false;
+        };
+        let status = self.undo_delete(index);
+        if let Some(screen) = &mut self.chat_list_screen {
+            screen.set_status(status.unwrap_or_else(|e| e));
+        }
+        true
+    }
+
+    /// Undo the delete highlighted on the Maintenance screen
+    pub fn undo_selected_delete(&mut self) {
+        if self.refuse_in_inspection("undo deletes") {
+            return;
+        }
+        let Some(index) = self.maintenance_screen.as_ref().map(|s| s.selected) else {
+            return;
+        };
+        let result = self.undo_delete(index);
+        if let Some(screen) = &mut self.maintenance_screen {
+            match result {
+                Ok(status) => screen.set_status(status, false),
+                Err(status) => screen.set_status(status, true),
+            }
+        }
+    }
+
+    /// Restore the item of undo entry `index`: its rows, queued messages and
+    /// the chat and contact as they were in memory
+    ///
+    /// # Returns
+    /// The status to show, as an error when it could not be undone
+    fn undo_delete(&mut self, index: usize) -> Result<String, String> {
+        let Some(entry) = self.undo.get_mut(index) else {
+            return Err("Nothing to undo".to_string());
+        };
+        match entry.state {
+            UndoState::Pending => {}
+            UndoState::Restored => return Err(format!("The {} is already restored", entry.label())),
+            UndoState::Purged => return Err(format!("The {} was purged and can no longer be undone", entry.label())),
+        }
+        // Something with the same UID was added since (e.g. imported again)
+        let replaced = self.app_state.chat_by_uid(&entry.uid).is_some()
+            || (entry.kind == DeletedKind::Contact && self.app_state.contact_by_uid(&entry.uid).is_some());
+        if replaced {
+            entry.state = UndoState::Purged;
+            return Err(format!("The {} was replaced and can no longer be undone", entry.label()));
+        }
+        entry.state = UndoState::Restored;
+        let entry = entry.clone();
+        let label = entry.label();
+
+        let storage = &self.storage;
+        self.error_reports.check(
+            ErrorSeverity::Error,
+            "storage",
+            storage.with_write_retry(|| {
+                if entry.kind == DeletedKind::Contact {
+                    storage.restore_contact(&entry.uid)?;
+                }
+                storage.restore_chat(&entry.uid)
+            }),
+        );
+        self.error_reports.check(ErrorSeverity::Warning, "message queue", self.queue.unsuspend_for(&entry.uid));
+        if entry.retained_notes {
+            self.error_reports.check(ErrorSeverity::Warning, "storage", self.storage.take_retained_contact_notes(&entry.uid));
+        }
+
+        if let Some((position, contact)) = entry.contact {
+            let position = position.min(self.app_state.contacts.len());
+            self.app_state.contacts.insert(position, contact);
+        }
+        if let Some((position, chat)) = entry.chat {
+            let position = position.min(self.app_state.chats.len());
+            self.app_state.chats.insert(position, chat);
+        }
+        self.app_state.address_changes.extend(entry.address_change);
+        self.app_state.reindex();
+        self.save_or_report();
+        Ok(format!("Restored {}", label))
+    }
+
+    /// Clear the undo status once U no longer applies, and purge the chats
+    /// and contacts deleted longer than `Settings::soft_delete_window_hours`
+    /// ago (at most every `SOFT_DELETE_PURGE_INTERVAL_SECS`)
+    ///
+    /// Called from the main loop. Queued messages to purged items are
+    /// dropped with them.
+    pub fn run_soft_delete_maintenance(&mut self, now: chrono::DateTime<Utc>) {
+        if let Some(screen) = &mut self.chat_list_screen
+            && let Some(status) = &screen.status_message
+            && self.undo.entries().iter().any(|entry| !entry.in_status_window(now) && status.starts_with(&entry.status_text()))
+        {
+            screen.clear_status();
+        }
+
+        if !self.undo.purge_due(now) || self.inspection.is_some() {
+            return;
+        }
+        let cutoff = purge_cutoff(now, self.app_state.settings.soft_delete_window_hours);
+        let Some(tombstones) = self.error_reports.check(ErrorSeverity::Warning, "storage", self.storage.purge_soft_deleted(cutoff, now)) else {
+            return;
+        };
+        if !tombstones.is_empty() {
+            tracing::info!("Purged {} soft-deleted chats and contacts", tombstones.len());
+        }
+        self.undo.mark_purged(&tombstones);
+
+        // Suspended messages whose chat or contact is no longer soft-deleted
+        // (purged here, or replaced by a new one) go too
+        let Some(mut keep) = self.error_reports.check(ErrorSeverity::Warning, "storage", self.storage.soft_deleted_uids()) else {
+            return;
+        };
+        keep.extend(self.undo.pending_uids().map(str::to_string));
+        let suspended = self.error_reports.check(ErrorSeverity::Warning, "message queue", self.queue.suspended_uids());
+        for uid in suspended.into_iter().flatten().filter(|uid| !keep.contains(uid)) {
+            match self.queue.purge_for(&uid) {
+                Ok(purged) if purged > 0 => tracing::info!("Dropped {} queued messages to deleted contact {}", purged, uid),
+                Ok(_) => {}
+                Err(e) => self.error_reports.report(
+                    ErrorSeverity::Warning,
+                    "message queue",
+                    format!("Failed to purge queued messages to {}: {}", uid, e),
+                ),
+            }
+        }
+    }
+
+    /// Show the maintenance screen (reached from Diagnostics)
+    pub fn show_maintenance_screen(&mut self) {
+        self.maintenance_screen = Some(MaintenanceScreen::new());
+        self.current_screen = Screen::Maintenance;
+    }
+
+    /// Import a contact and create a new chat
+    ///
+    /// With the Import screen's temporary toggle on, a new contact is stored
+    /// as temporary (see `storage::ephemeral`), and with its trust toggle on
+    /// as restricted (see `storage::trust`); a known one keeps its kind and tier.
+    pub fn import_contact(&mut self, mut contact: crate::storage::Contact) {
+        let min_validity = self.app_state.settings.min_token_validity_minutes;
+        if let Some(screen) = &mut self.import_contact_screen {
+            screen.check_token_expiry(Utc::now(), min_validity);
+        }
+        let temporary = self.import_contact_screen.as_ref().and_then(|s| s.temporary);
+        contact.trust = self.import_contact_screen.as_ref().map_or(TrustTier::Normal, |s| s.trust);
+        let link = self.import_contact_screen.as_ref().and_then(|s| s.linked_introduction().cloned());
+        let added = self.add_imported_contact(contact.clone(), temporary);
+        let linked = link
+            .filter(|_| added.as_ref().err().is_none_or(ImportRefusal::contact_known))
+            .filter(|intro| self.link_introduction(&contact.uid, intro))
+            .map(|intro| format!(" Linked to {} (name and notes carried over).", intro.name))
+            .unwrap_or_default();
+        if !matches!(added, Err(ImportRefusal::OwnToken)) {
+            // Auto-save after importing contact and creating chat
+            self.save_or_report();
+            // A known contact's new token lets its blocked messages go
+            self.sync_expired_blocks(Utc::now());
+        }
+        if let Err(refusal) = added {
+            if let Some(screen) = &mut self.import_contact_screen {
+                screen.is_error = refusal.is_error();
+                screen.status_message = Some(format!("{}{}", refusal.message(), linked));
+            }
+            return;
+        }
+
+        // The ping carries my contact token (so receiver can auto-import me)
+        let dispatcher = self.ping_dispatcher();
+        let restricted = if contact.trust.is_normal() { "" } else { " as restricted" };
+
+        // Try to send ping immediately, queue on failure (background thread)
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+            rt.block_on(dispatcher.introduce(&contact));
+        });
+
+        // Update import screen status
+        if let Some(screen) = &mut self.import_contact_screen {
+            let mut status = match temporary {
+                Some(lifetime) => format!("✓ Temporary contact imported{} (deleted after {}), ping sent!", restricted, lifetime.label()),
+                None => format!("✓ Contact imported{}, ping sent!", restricted),
+            };
+            status.push_str(&linked);
+            if let Some(warning) = &screen.expiry_warning {
+                status = format!("{} Warning: {}", status, warning);
+            }
+            screen.status_message = Some(status);
+            screen.is_error = false;
+        }
+    }
+
+    /// Add an imported contact and its pending chat (not saved)
+    ///
+    /// A known contact only has its address updated or staged, and a
+    /// conflicting identity is held for review; neither needs a ping.
+    ///
+    /// # Errors
+    /// Why no new contact was added
+    fn add_imported_contact(
+        &mut self,
+        mut contact: crate::storage::Contact,
+        temporary: Option<crate::storage::EphemeralLifetime>,
+    ) -> std::result::Result<(), ImportRefusal> {
+        // Check if trying to import own contact (self-import)
+        if contact.uid == self.keypair.uid.to_string() {
+            return Err(ImportRefusal::OwnToken);
+        }
+
+        let contact_uid = contact.uid.clone();
+        contact.ephemeral = temporary.map(|lifetime| Ephemeral::new(lifetime, Utc::now()));
+
+        // Add the contact unless it is known or its identity conflicts
+        match self.app_state.ingest_contact(contact) {
+            Ok(ContactIngest::Added) => {}
+            Ok(ContactIngest::AddressUpdated) => return Err(ImportRefusal::AddressUpdated),
+            Ok(ContactIngest::AddressStaged) => return Err(ImportRefusal::AddressStaged),
+            Ok(ContactIngest::Renewed) => return Err(ImportRefusal::Renewed),
+            Ok(ContactIngest::Unchanged) => return Err(ImportRefusal::Exists),
+            Ok(ContactIngest::Conflict(conflict)) => {
+                return Err(ImportRefusal::Conflict { kind: conflict.kind.to_string(), existing_uid: conflict.existing_uid });
+            }
+            Err(e) => return Err(ImportRefusal::Failed(e.to_string())),
+        }
+
+        // Restore notes kept from a previous deletion of this contact
+        if let Ok(Some(notes)) = self.storage.take_retained_contact_notes(&contact_uid)
+            && let Some(stored) = self.app_state.contact_by_uid_mut(&contact_uid)
+        {
+            stored.notes = notes;
+        }
+
+        // Create a new chat for this contact with pending status
+        // (will be updated to active if ping succeeds)
+        self.app_state.get_or_create_chat(&contact_uid).mark_has_pending(); // Pending until ping succeeds
+        Ok(())
+    }
+
+    /// Link the stored contact `contact_uid` to a pending introduction
+    ///
+    /// The introduction's name and notes are appended to the contact's notes
+    /// and it leaves the pending list (not saved).
+    ///
+    /// # Returns
+    /// Whether the contact was found
+    fn link_introduction(&mut self, contact_uid: &str, intro: &PendingIntroduction) -> bool {
+        let Some(contact) = self.app_state.contact_by_uid_mut(contact_uid) else {
+            return false;
+        };
+        intro.carry_over(contact);
+        tracing::info!("Linked contact {} to pending introduction {}", contact_uid, intro.id);
+        self.drop_pending_introduction(&intro.id);
+        true
+    }
+
+    /// Remove a pending introduction from the database and the Import screen
+    fn drop_pending_introduction(&mut self, id: &str) {
+        let deleted = self.storage.delete_pending_introduction(id);
+        self.error_reports.check(ErrorSeverity::Warning, "introductions", deleted);
+        if let Some(screen) = &mut self.import_contact_screen {
+            let pending = screen.pending.iter().filter(|intro| intro.id != id).cloned().collect();
+            screen.set_pending(pending);
+        }
+    }
+
+    /// Delete the pending introduction selected for linking (Ctrl+D)
+    pub fn dismiss_linked_introduction(&mut self) {
+        if self.refuse_in_inspection("dismiss introductions") {
+            return;
+        }
+        let Some(intro) = self.import_contact_screen.as_ref().and_then(|s| s.linked_introduction().cloned()) else {
+            return;
+        };
+        self.drop_pending_introduction(&intro.id);
+        if let Some(screen) = &mut self.import_contact_screen {
+            screen.status_message = Some(format!("Dismissed pending introduction {}", intro.name));
+            screen.is_error = false;
+        }
+    }
+
+    /// Read a contact list (CSV or vCard) from `chosen` into the pending
+    /// introductions
+    ///
+    /// Entries already pending with the same name and notes (the same list
+    /// read twice) are skipped; unusable rows are listed on the Import screen.
+    fn read_introduction_list(&mut self, chosen: &ChosenPath) -> std::result::Result<(), SavePathError> {
+        let bytes = std::fs::read(&chosen.path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => SavePathError::NotFound(chosen.path.clone()),
+            _ => SavePathError::Unreadable(chosen.path.clone(), e.to_string()),
+        })?;
+        let import = parse_introduction_list(&bytes, Utc::now());
+        let mut pending = self.import_contact_screen.as_ref().map(|s| s.pending.clone()).unwrap_or_default();
+        let mut seen: std::collections::HashSet<(Vec<String>, String)> =
+            pending.iter().map(|intro| (name_words(&intro.name), intro.notes.clone())).collect();
+        let fresh: Vec<PendingIntroduction> = import
+            .entries
+            .iter()
+            .filter(|intro| seen.insert((name_words(&intro.name), intro.notes.clone())))
+            .cloned()
+            .collect();
+        for error in &import.errors {
+            tracing::warn!("Skipped a row of {}: {}", chosen.path.display(), error);
+        }
+
+        let (status, is_error) = match self.storage.save_pending_introductions(&fresh) {
+            Ok(()) => {
+                pending.extend(fresh.iter().cloned());
+                let status = format!(
+                    "{} pending introduction(s) added, {} already pending, {} row(s) skipped",
+                    fresh.len(),
+                    import.entries.len() - fresh.len(),
+                    import.errors.len()
+                );
+                (status, import.entries.is_empty() && !import.errors.is_empty())
+            }
+            Err(e) => (format!("Error: Failed to save pending introductions: {}", e), true),
+        };
+        if let Some(screen) = &mut self.import_contact_screen {
+            screen.set_pending(pending);
+            screen.list_errors = import.errors;
+            screen.status_message = Some(status);
+            screen.is_error = is_error;
+        }
+        Ok(())
+    }
+
+    /// Dispatcher for introduction pings carrying a fresh token of ours
+    fn ping_dispatcher(&self) -> PingDispatcher {
+        PingDispatcher {
+            transports: self.transports.clone(),
+            storage: self.storage.clone(),
+            keypair: self.keypair.clone(),
+            token_source: self.own_token_source(),
+            delivery_events: self.delivery_event_sender(),
+            reports: self.error_reports.clone(),
+        }
+    }
+
+    /// Switch the Import screen between single-token and batch mode
+    pub fn toggle_batch_import(&mut self) {
+        if let Some(screen) = &mut self.import_contact_screen {
+            screen.toggle_batch();
+        }
+    }
+
+    /// Parse the tokens entered in batch mode and show the review table
+    pub fn review_token_batch(&mut self) {
+        let own_uid = self.keypair.uid.to_string();
+        let Some(screen) = &mut self.import_contact_screen else {
+            return;
+        };
+        let mut entries = parse_token_batch(&screen.input, &self.app_state, &own_uid, Utc::now());
+        suggest_batch_links(&mut entries, &screen.pending);
+        if entries.is_empty() {
+            screen.status_message = Some("Error: No tokens found (one per line)".to_string());
+            screen.is_error = true;
+            return;
+        }
+        let ok = entries.iter().filter(|e| e.status == BatchEntryStatus::Ok).count();
+        screen.status_message = Some(format!(
+            "{} token(s), {} new. Space: select | Enter: import {} selected | Esc: edit",
+            entries.len(),
+            ok,
+            ok
+        ));
+        screen.is_error = false;
+        screen.batch = Some(BatchImport::reviewing(entries));
+    }
+
+    /// Read tokens for batch mode from `chosen` and show the review table
+    fn read_token_batch(&mut self, chosen: &ChosenPath) -> std::result::Result<(), SavePathError> {
+        let text = std::fs::read_to_string(&chosen.path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => SavePathError::NotFound(chosen.path.clone()),
+            _ => SavePathError::Unreadable(chosen.path.clone(), e.to_string()),
+        })?;
+        if let Some(screen) = &mut self.import_contact_screen {
+            if screen.batch.is_none() {
+                screen.toggle_batch();
+            }
+            screen.input = text;
+        }
+        self.review_token_batch();
+        Ok(())
+    }
+
+    /// Import the entries selected in the review table
+    ///
+    /// All selected entries are added in one pass and saved once; an entry
+    /// that fails is reported and the rest go on. Imported contacts get an
+    /// introduction ping each, whose results reach the report through
+    /// `poll_batch_import`.
+    ///
+    /// # Returns
+    /// How many contacts were imported
+    pub fn confirm_batch_import(&mut self) -> usize {
+        let temporary = self.import_contact_screen.as_ref().and_then(|s| s.temporary);
+        let trust = self.import_contact_screen.as_ref().map_or(TrustTier::Normal, |s| s.trust);
+        let Some(selected) = self
+            .import_contact_screen
+            .as_ref()
+            .and_then(|s| s.batch.as_ref())
+            .filter(|b| b.in_review())
+            .map(|b| b.entries.iter().filter(|e| e.selected).cloned().collect::<Vec<_>>())
+        else {
+            return 0;
+        };
+
+        let mut report = Vec::new();
+        let mut imported = Vec::new();
+        for entry in selected {
+            let Some(mut contact) = entry.contact.clone() else {
+                continue;
+            };
+            contact.trust = trust;
+            let result = match self.add_imported_contact(contact.clone(), temporary) {
+                Ok(()) => {
+                    imported.push(contact.clone());
+                    BatchEntryResult::Imported
+                }
+                Err(refusal @ (ImportRefusal::OwnToken | ImportRefusal::Conflict { .. } | ImportRefusal::Failed(_))) => {
+                    BatchEntryResult::Failed(refusal.message())
+                }
+                Err(refusal) => BatchEntryResult::Skipped(refusal.message()),
+            };
+            let mut name = entry.display_name();
+            if let Some(intro) = &entry.link
+                && !matches!(result, BatchEntryResult::Failed(_))
+                && self.link_introduction(&contact.uid, intro)
+            {
+                name = format!("{} (linked to {})", name, intro.name);
+            }
+            let ping = (result == BatchEntryResult::Imported).then_some(PingDispatch::Pending);
+            report.push(BatchReportEntry { name, uid: contact.uid, result, ping });
+        }
+        self.save_or_report();
+        self.sync_expired_blocks(Utc::now());
+
+        // One thread pings the imported contacts in turn through the usual dispatcher
+        let sink = self.import_contact_screen.as_ref().and_then(|s| s.batch.as_ref()).map(|b| b.ping_sink());
+        if let Some(sink) = sink
+            && !imported.is_empty()
+        {
+            let dispatcher = self.ping_dispatcher();
+            let contacts = imported.clone();
+            std::thread::spawn(move || {
+                let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+                rt.block_on(async move {
+                    for contact in contacts {
+                        let ping = dispatcher.introduce(&contact).await;
+                        sink.lock().unwrap().push((contact.uid, ping));
+                    }
+                });
+            });
+        }
+
+        if let Some(screen) = &mut self.import_contact_screen
+            && let Some(batch) = &mut screen.batch
+        {
+            batch.report = Some(report);
+            screen.status_message = batch.summary();
+            screen.is_error = false;
+        }
+        imported.len()
+    }
+
+    /// Apply ping results of a batch import to its report
+    ///
+    /// # Returns
+    /// Whether any arrived
+    pub fn poll_batch_import(&mut self) -> bool {
+        let Some(screen) = &mut self.import_contact_screen else {
+            return false;
+        };
+        let Some(batch) = &mut screen.batch else {
+            return false;
+        };
+        if !batch.collect_pings() {
+            return false;
+        }
+        screen.status_message = batch.summary();
+        true
+    }
+
+    /// Pin or unpin the message selected in the chat view
+    pub fn toggle_pin_selected(&mut self) {
+        if self.refuse_in_inspection("pin messages") {
+            return;
+        }
+        let Some(screen) = self.chat_view_screen.as_mut() else {
+            return;
+        };
+        let Some(chat) = self.app_state.chat_by_uid_mut(&screen.contact_uid) else {
+            return;
+        };
+        let Some(id) = screen.selected_message(chat).map(|m| m.id.clone()) else {
+            return;
+        };
+
+        match chat.toggle_pin(&id) {
+            Ok(true) => screen.set_status("Message pinned".to_string()),
+            Ok(false) => screen.set_status("Message unpinned".to_string()),
+            Err(e) => {
+                screen.set_status(format!("Error: {}", e));
+                return;
+            }
+        }
+        self.save_or_report();
+    }
+
+    /// Star or unstar the message selected in the chat view
+    pub fn toggle_star_selected(&mut self) {
+        if self.refuse_in_inspection("star messages") {
+            return;
+        }
+        let Some(screen) = self.chat_view_screen.as_mut() else {
+            return;
+        };
+        let Some(chat) = self.app_state.chat_by_uid_mut(&screen.contact_uid) else {
+            return;
+        };
+        let Some(id) = screen.selected_message(chat).map(|m| m.id.clone()) else {
+            return;
+        };
+
+        match chat.toggle_star(&id) {
+            Ok(true) => screen.set_status("Message starred".to_string()),
+            Ok(false) => {
+                screen.set_status("Message unstarred".to_string());
+                // The filtered view no longer shows it
+                if screen.starred_only {
+                    screen.end_selection();
+                }
+            }
+            Err(e) => {
+                screen.set_status(format!("Error: {}", e));
+                return;
+            }
+        }
+        self.save_or_report();
+    }
+
+    /// Edit the message selected in the chat view: its text goes into the input
+    ///
+    /// Only our own text messages sent within `Settings::edit_window_minutes`
+    /// can be edited; otherwise the status line says why not.
+    pub fn start_edit_selected(&mut self) {
+        if self.refuse_in_inspection("edit messages") {
+            return;
+        }
+        let Some(screen) = self.chat_view_screen.as_mut() else {
+            return;
+        };
+        let Some(chat) = self.app_state.chat_by_uid(&screen.contact_uid) else {
+            return;
+        };
+        let Some(message) = screen.selected_message(chat) else {
+            return;
+        };
+
+        let window = self.app_state.settings.edit_window_minutes;
+        match check_editable(message, &self.keypair.uid.to_string(), window, Utc::now()) {
+            Ok(()) => {
+                screen.start_edit(message);
+                screen.set_status("Editing message - Enter to save, Esc to cancel".to_string());
+            }
+            Err(e) => screen.set_status(format!("Cannot edit: {}", e)),
+        }
+    }
+
+    /// Save the content of the message selected in the chat view to the downloads directory
+    ///
+    /// The file gets a generated name with an extension for the detected type
+    /// and never replaces an existing file. Content is streamed from the
+    /// database; only a message not yet written there is copied from memory.
+    /// Reports the path (or the error) in the status line.
+    pub fn save_selected_content(&mut self) {
+        if self.refuse_in_inspection("save files") {
+            return;
+        }
+        let Some(screen) = self.chat_view_screen.as_mut() else {
+            return;
+        };
+        let Some(chat) = self.app_state.chat_by_uid(&screen.contact_uid) else {
+            return;
+        };
+        let Some(message) = screen.selected_message(chat) else {
+            return;
+        };
+
+        let name = download_file_name(&message.id, message.content_kind());
+        let result = create_download_file(&self.downloads_dir, &name).and_then(|(path, mut file)| {
+            let written = match self.storage.copy_message_content(&message.id, &mut file) {
+                Ok(Some(written)) => written,
+                Ok(None) => {
+                    std::io::Write::write_all(&mut file, &message.content)?;
+                    message.content.len() as u64
+                }
+                Err(e) => {
+                    let _ = std::fs::remove_file(&path);
+                    return Err(std::io::Error::other(e.to_string()));
+                }
+            };
+            Ok((path, written))
+        });
+
+        match result {
+            Ok((path, written)) => screen.set_status(format!(
+                "Saved {} to {}",
+                format_size(written as usize),
+                path.display()
+            )),
+            Err(e) => screen.set_status(format!("Error: could not save content: {}", e)),
+        }
+    }
+
+    /// Ask where to export the open chat as JSON Lines
+    pub fn export_chat_jsonl(&mut self) {
+        let Some(contact_uid) = self.chat_view_screen.as_ref().map(|s| s.contact_uid.clone()) else {
+            return;
+        };
+        self.open_path_picker(SaveTarget::ChatExport { contact_uid });
+    }
+
+    /// Ask where to export the open chat as a signed, verifiable archive
+    pub fn export_chat_archive(&mut self) {
+        let Some(contact_uid) = self.chat_view_screen.as_ref().map(|s| s.contact_uid.clone()) else {
+            return;
+        };
+        self.open_path_picker(SaveTarget::ChatArchive { contact_uid });
+    }
+
+    /// Check a chat archive exported by a contact (see `storage::chat_archive`)
+    ///
+    /// The signature must verify with the identity key we hold for the
+    /// exporter, not the one in the archive. Every line is then checked
+    /// against the signed manifest and, if we have the chat, the manifest is
+    /// cross-referenced with our copy. Nothing is imported.
+    ///
+    /// # Returns
+    /// A one-line report; details go to the log
+    pub fn verify_chat_archive(&mut self, text: &str) -> String {
+        let archive = match ChatArchive::parse(text) {
+            Ok(archive) => archive,
+            Err(e) => return format!("✗ Not a chat archive: {}", e),
+        };
+        let owner = archive.manifest.owner_uid.clone();
+        let owner_short = &owner[..16.min(owner.len())];
+        let Some(public_key) = self.app_state.contact_by_uid(&owner).map(|c| c.pubkey.clone()) else {
+            return format!("✗ Archive signed by {}, who is not a contact", owner_short);
+        };
+        let verification = match archive.verify(&public_key) {
+            Ok(verification) => verification,
+            Err(e) => return format!("✗ Archive from {} rejected: {}", owner_short, e),
+        };
+        for problem in &verification.problems {
+            tracing::warn!("Chat archive from {}: {}", owner, problem);
+        }
+
+        let summary = if verification.is_intact() {
+            format!("✓ Archive from {}: {} messages intact", owner_short, verification.intact)
         } else {
-            self.selected_index = self.menu_items.len() - 1;
+            format!(
+                "✗ Archive from {}: {} intact, {} tampered (first: {})",
+                owner_short,
+                verification.intact,
+                verification.problems.len(),
+                verification.problems[0]
+            )
+        };
+        // The archive reads the database, so write pending changes first
+        self.save_or_report();
+        if self.app_state.get_chat(&owner).is_none() {
+            return format!("{}; we have no copy of this chat", summary);
+        }
+        match self.storage.cross_reference_archive(&verification.manifest) {
+            Ok(report) => {
+                for id in &report.mismatched {
+                    tracing::warn!("Chat archive from {}: {} differs from our copy", owner, id);
+                }
+                format!(
+                    "{}; our copy: {} matched, {} differ, {} only in archive, {} only here",
+                    summary,
+                    report.matched.len(),
+                    report.mismatched.len(),
+                    report.only_in_archive.len(),
+                    report.only_local.len()
+                )
+            }
+            Err(e) => format!("{}; could not read our copy: {}", summary, e),
+        }
+    }
+
+    /// Open the save-path overlay for `target`, suggesting a file in `save_dir`
+    pub fn open_path_picker(&mut self, target: SaveTarget) {
+        let refused = match target {
+            SaveTarget::ArchiveVerify => false,
+            SaveTarget::TokenBatch | SaveTarget::IntroductionList => self.refuse_in_inspection("import contacts"),
+            _ => self.refuse_in_inspection("save files"),
+        };
+        if refused {
+            return;
+        }
+        let name = match &target {
+            SaveTarget::ContactToken => ShareContactScreen::token_file_name(),
+            SaveTarget::ChatExport { contact_uid } => export_file_name(contact_uid),
+            SaveTarget::ChatArchive { contact_uid } => archive_file_name(contact_uid),
+            SaveTarget::TokenBatch => "contact_tokens.txt".to_string(),
+            SaveTarget::ArchiveVerify => archive_file_name(""),
+            SaveTarget::IntroductionList => "contacts.csv".to_string(),
+            SaveTarget::SnapshotDiff => format!("snapshot_diff_{}.json", Utc::now().format("%Y%m%d_%H%M%S")),
+            SaveTarget::JournalExport => journal_export_file_name(Utc::now()),
+        };
+        self.path_picker = Some(PathPicker::new(target, &self.save_dir.join(name)));
+    }
+
+    /// Close the save-path overlay without saving
+    pub fn cancel_path_picker(&mut self) {
+        self.path_picker = None;
+    }
+
+    /// Check the path entered in the overlay and save if it is free
+    ///
+    /// An existing file turns the overlay into an overwrite prompt instead.
+    pub fn submit_path_picker(&mut self) {
+        let home = PathEnv::from_env().home;
+        let cwd = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
+        let chosen = self.path_picker.as_mut().and_then(|p| p.submit(home.as_deref(), &cwd));
+        if let Some(chosen) = chosen {
+            self.save_to_chosen_path(chosen);
+        }
+    }
+
+    /// Answer the overlay's overwrite prompt
+    ///
+    /// Declining keeps the overlay open so another path can be entered.
+    pub fn answer_overwrite_prompt(&mut self, overwrite: bool) {
+        let Some(picker) = self.path_picker.as_mut() else {
+            return;
+        };
+        match picker.answer_overwrite(overwrite) {
+            Some(chosen) => self.save_to_chosen_path(chosen),
+            None => picker.error = Some("Not overwritten; edit the path or press Esc".to_string()),
+        }
+    }
+
+    /// Write the overlay's target to `chosen`, then close the overlay
+    ///
+    /// A failed write keeps the overlay open with the reason.
+    fn save_to_chosen_path(&mut self, chosen: ChosenPath) {
+        let Some(target) = self.path_picker.as_ref().map(|p| p.target.clone()) else {
+            return;
+        };
+        let result = match &target {
+            SaveTarget::ContactToken => self.write_contact_token(&chosen).map(|()| None),
+            SaveTarget::ChatExport { contact_uid } => self.write_chat_export(contact_uid, &chosen).map(Some),
+            SaveTarget::ChatArchive { contact_uid } => self.write_chat_archive(contact_uid, &chosen).map(Some),
+            SaveTarget::TokenBatch => self.read_token_batch(&chosen).map(|()| None),
+            SaveTarget::ArchiveVerify => self.read_chat_archive(&chosen).map(|()| None),
+            SaveTarget::IntroductionList => self.read_introduction_list(&chosen).map(|()| None),
+            SaveTarget::SnapshotDiff => self.write_snapshot_diff(&chosen).map(|()| None),
+            SaveTarget::JournalExport => self.write_journal_export(&chosen).map(Some),
+        };
+
+        match result {
+            Ok(_) if target.reads_file() => {
+                self.path_picker = None;
+            }
+            Ok(exported) => {
+                self.path_picker = None;
+                self.last_saved_path = Some(chosen.path.clone());
+                self.report_saved(&target, &chosen.path, exported);
+            }
+            Err(e) => {
+                if let Some(picker) = &mut self.path_picker {
+                    picker.set_error(&e);
+                }
+            }
+        }
+    }
+
+    fn write_contact_token(&self, chosen: &ChosenPath) -> std::result::Result<(), SavePathError> {
+        use std::io::Write;
+        let Some(screen) = &self.share_contact_screen else {
+            return Err(SavePathError::Io(chosen.path.clone(), "no token to save".to_string()));
+        };
+        let mut file = open_for_save(&chosen.path, chosen.overwrite)?;
+        file.write_all(screen.shown_token().as_bytes())
+            .and_then(|()| file.sync_all())
+            .map_err(|e| SavePathError::from_io(&e, &chosen.path))
+    }
+
+    /// Write the diff shown on the snapshots screen to `chosen` as JSON
+    fn write_snapshot_diff(&self, chosen: &ChosenPath) -> std::result::Result<(), SavePathError> {
+        use std::io::Write;
+        let Some(diff) = self.snapshots_screen.as_ref().and_then(|s| s.diff()) else {
+            return Err(SavePathError::Io(chosen.path.clone(), "no diff to export".to_string()));
+        };
+        let json = diff.to_json().map_err(|e| SavePathError::Io(chosen.path.clone(), e.to_string()))?;
+        let mut file = open_for_save(&chosen.path, chosen.overwrite)?;
+        file.write_all(json.as_bytes())
+            .and_then(|()| file.sync_all())
+            .map_err(|e| SavePathError::from_io(&e, &chosen.path))
+    }
+
+    /// Write the whole journal to `chosen`, signed by our identity key
+    ///
+    /// CSV for a `.csv` path, JSON Lines otherwise.
+    ///
+    /// # Returns
+    /// The number of exported records
+    fn write_journal_export(&self, chosen: &ChosenPath) -> std::result::Result<usize, SavePathError> {
+        use std::io::Write;
+        let io_error = |e: crate::Error| SavePathError::Io(chosen.path.clone(), e.to_string());
+        let records = self.storage.load_journal(None, None).map_err(io_error)?;
+        let text = export_journal(&records, JournalExportFormat::for_path(&chosen.path), &self.keypair).map_err(io_error)?;
+        let mut file = open_for_save(&chosen.path, chosen.overwrite)?;
+        file.write_all(text.as_bytes())
+            .and_then(|()| file.sync_all())
+            .map_err(|e| SavePathError::from_io(&e, &chosen.path))?;
+        Ok(records.len())
+    }
+
+    /// Stream the chat with `contact_uid` to `chosen` as a signed archive; a
+    /// partial file is removed on failure
+    ///
+    /// # Returns
+    /// The number of exported messages
+    fn write_chat_archive(&mut self, contact_uid: &str, chosen: &ChosenPath) -> std::result::Result<usize, SavePathError> {
+        self.save_or_report();
+        let file = open_for_save(&chosen.path, chosen.overwrite)?;
+        let mut writer = std::io::BufWriter::new(file);
+        self.storage
+            .export_chat_archive(contact_uid, &self.keypair, &mut writer)
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&chosen.path);
+                match e {
+                    crate::Error::Io(io) => SavePathError::from_io(&io, &chosen.path),
+                    e => SavePathError::Io(chosen.path.clone(), e.to_string()),
+                }
+            })
+    }
+
+    /// Read the chat archive at `chosen` and report it on Diagnostics
+    fn read_chat_archive(&mut self, chosen: &ChosenPath) -> std::result::Result<(), SavePathError> {
+        let text = std::fs::read_to_string(&chosen.path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => SavePathError::NotFound(chosen.path.clone()),
+            _ => SavePathError::Unreadable(chosen.path.clone(), e.to_string()),
+        })?;
+        let report = self.verify_chat_archive(&text);
+        if let Some(screen) = &mut self.diagnostics_screen {
+            screen.set_status_message(report);
         }
+        Ok(())
+    }
+
+    /// Stream the chat with `contact_uid` to `chosen` (every message, default
+    /// options); a partial file is removed on failure
+    ///
+    /// # Returns
+    /// The number of exported messages
+    fn write_chat_export(&mut self, contact_uid: &str, chosen: &ChosenPath) -> std::result::Result<usize, SavePathError> {
+        // The export reads the database, so write pending changes first
+        self.save_or_report();
+        let file = open_for_save(&chosen.path, chosen.overwrite)?;
+        let mut writer = std::io::BufWriter::new(file);
+        self.storage
+            .export_chat_jsonl(contact_uid, &mut writer, &JsonlExportOptions::default())
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&chosen.path);
+                match e {
+                    crate::Error::Io(io) => SavePathError::from_io(&io, &chosen.path),
+                    e => SavePathError::Io(chosen.path.clone(), e.to_string()),
+                }
+            })
     }
 
-    /// Select current menu item
-    pub fn select(&mut self) {
-        match self.selected_item() {
-            MenuItem::ChatList => {
-                self.show_chat_list_screen();
-            }
-            MenuItem::ShareContact => {
-                self.show_share_contact_screen();
+    /// Report a finished save in the status line of the screen it came from
+    fn report_saved(&mut self, target: &SaveTarget, path: &std::path::Path, exported: Option<usize>) {
+        match target {
+            SaveTarget::ContactToken => {
+                if let Some(screen) = &mut self.share_contact_screen {
+                    screen.status_message = Some(format!("Saved to {} (p: copy path)", path.display()));
+                }
             }
-            MenuItem::ImportContact => {
-                self.show_import_contact_screen();
+            SaveTarget::ChatExport { contact_uid } => {
+                let trimmed = self.app_state.get_chat(contact_uid).is_some_and(|c| c.is_trimmed());
+                let count = exported.unwrap_or(0);
+                if let Some(screen) = &mut self.chat_view_screen {
+                    screen.set_status(if trimmed {
+                        format!(
+                            "Exported {} messages to {} (incomplete: older messages were removed) (Ctrl+Y: copy path)",
+                            count,
+                            path.display()
+                        )
+                    } else {
+                        format!("Exported {} messages to {} (Ctrl+Y: copy path)", count, path.display())
+                    });
+                }
             }
-            MenuItem::Diagnostics => {
-                self.show_diagnostics_screen();
+            SaveTarget::ChatArchive { .. } => {
+                if let Some(screen) = &mut self.chat_view_screen {
+                    screen.set_status(format!(
+                        "Exported {} messages to {} (signed; the contact can verify it) (Ctrl+Y: copy path)",
+                        exported.unwrap_or(0),
+                        path.display()
+                    ));
+                }
             }
-            MenuItem::Settings => {
-                self.show_settings_screen();
+            SaveTarget::TokenBatch | SaveTarget::ArchiveVerify | SaveTarget::IntroductionList => {}
+            SaveTarget::SnapshotDiff => {
+                if let Some(screen) = &mut self.snapshots_screen {
+                    screen.set_status(format!("Diff exported to {}", path.display()), false);
+                }
             }
-            MenuItem::Exit => {
-                self.should_quit = true;
+            SaveTarget::JournalExport => {
+                if let Some(screen) = &mut self.diagnostics_screen {
+                    screen.set_status_message(format!(
+                        "Exported {} journal records to {} (signed)",
+                        exported.unwrap_or(0),
+                        path.display()
+                    ));
+                }
             }
         }
     }
 
-    /// Show chat list screen
-    pub fn show_chat_list_screen(&mut self) {
-        // Reload state to pick up any new messages from transport handlers
-        let _ = self.reload_state();
+    /// Copy the path of the last saved file to the clipboard
+    pub fn copy_saved_path(&mut self) {
+        self.copy_saved_path_with_provider(&mut RealClipboard::new());
+    }
 
-        self.chat_list_screen = Some(ChatListScreen::new());
-        self.current_screen = Screen::ChatList;
+    /// Copy the last saved path with a custom clipboard provider (for testing)
+    pub(crate) fn copy_saved_path_with_provider<P>(&mut self, clipboard_result: &mut std::result::Result<P, ClipboardError>)
+    where
+        P: ClipboardProvider,
+    {
+        let Some(path) = self.last_saved_path.as_ref().map(|p| p.display().to_string()) else {
+            return;
+        };
+        let message = match clipboard_result {
+            Ok(clipboard) => match clipboard.set_text(&path) {
+                Ok(()) => "Path copied to clipboard".to_string(),
+                Err(e) => format!("Copy failed: {}", e),
+            },
+            Err(_) => "Clipboard not available".to_string(),
+        };
+        match self.current_screen {
+            Screen::ShareContact => {
+                if let Some(screen) = &mut self.share_contact_screen {
+                    screen.status_message = Some(message);
+                }
+            }
+            Screen::ChatView => {
+                if let Some(screen) = &mut self.chat_view_screen {
+                    screen.set_status(message);
+                }
+            }
+            _ => {}
+        }
     }
 
-    /// Show share contact screen
-    pub fn show_share_contact_screen(&mut self) {
-        self.share_contact_screen = Some(ShareContactScreen::new(&self.keypair, &self.local_ip));
-        self.current_screen = Screen::ShareContact;
+    /// Unpin the message that has focus in the pinned strip
+    pub fn unpin_focused(&mut self) {
+        if self.refuse_in_inspection("pin messages") {
+            return;
+        }
+        let Some(screen) = self.chat_view_screen.as_mut() else {
+            return;
+        };
+        let Some(chat) = self.app_state.chat_by_uid_mut(&screen.contact_uid) else {
+            return;
+        };
+        let Some(id) = screen.focused_pinned(chat).map(|m| m.id.clone()) else {
+            return;
+        };
+
+        if let Err(e) = chat.toggle_pin(&id) {
+            screen.set_status(format!("Error: {}", e));
+            return;
+        }
+        screen.clamp_pinned_focus(chat);
+        screen.set_status("Message unpinned".to_string());
+        self.save_or_report();
     }
 
-    /// Show import contact screen
-    pub fn show_import_contact_screen(&mut self) {
-        self.import_contact_screen = Some(ImportContactScreen::new());
-        self.current_screen = Screen::ImportContact;
+    /// Handle '%' typed in the chat input
+    ///
+    /// At the start of a word, with templates stored, this opens the template
+    /// picker instead of typing the character.
+    ///
+    /// # Returns
+    /// Whether the picker was opened (otherwise type '%' as usual)
+    pub fn open_template_picker(&mut self) -> bool {
+        let Some(screen) = self.chat_view_screen.as_mut() else {
+            return false;
+        };
+        if self.app_state.settings.templates.is_empty() || !screen.cursor_at_word_start() {
+            return false;
+        }
+        screen.open_template_picker();
+        true
     }
 
-    /// Show settings screen
-    pub fn show_settings_screen(&mut self) {
-        let current_interval = self.app_state.settings.retry_interval_minutes;
-        self.settings_screen = Some(SettingsScreen::new(current_interval));
-        self.current_screen = Screen::Settings;
+    /// Templates matching the picker query, best match first (indices into the settings)
+    pub fn template_picker_matches(&self) -> Vec<usize> {
+        let Some(picker) = self.chat_view_screen.as_ref().and_then(|s| s.template_picker.as_ref()) else {
+            return Vec::new();
+        };
+        picker.matches(self.app_state.settings.templates.iter().map(|t| t.name.as_str()))
     }
 
-    /// Show diagnostics screen
-    pub fn show_diagnostics_screen(&mut self) {
-        let mut screen = DiagnosticsScreen::new(self.local_port);
+    /// Close the picker and type the '%' that opened it
+    pub fn cancel_template_picker(&mut self) {
+        if let Some(screen) = self.chat_view_screen.as_mut() {
+            screen.close_template_picker();
+            screen.add_char('%');
+        }
+    }
 
-        // Populate with current app state data
-        self.update_diagnostics_with_app_state(&mut screen);
+    /// Insert the highlighted template at the cursor and close the picker
+    ///
+    /// `{name}` and `{date}` are filled in; the message is not sent.
+    pub fn insert_selected_template(&mut self) {
+        let Some(screen) = self.chat_view_screen.as_mut() else {
+            return;
+        };
+        let Some(picker) = screen.template_picker.take() else {
+            return;
+        };
+        let templates = &self.app_state.settings.templates;
+        let Some(template) = picker
+            .selected_index(templates.iter().map(|t| t.name.as_str()))
+            .map(|i| &templates[i])
+        else {
+            return;
+        };
 
-        // Apply existing connectivity result if available
-        if let Some(result) = &self.connectivity_result {
-            screen.update_from_connectivity_result(result);
-        }
+        // Contacts have no display names; the chat list and header show the short UID
+        let uid = &screen.contact_uid;
+        let name = &uid[..16.min(uid.len())];
+        let text = template.render(name, chrono::Local::now().date_naive());
+        screen.insert_text(&text);
+    }
 
-        self.diagnostics_screen = Some(screen);
-        self.current_screen = Screen::Diagnostics;
+    /// Colours for this profile (the theme with its accent applied)
+    pub fn theme(&self) -> Theme {
+        Theme::default().with_accent(self.app_state.settings.accent_color)
     }
 
-    /// Update diagnostics screen with current app state
-    pub fn update_diagnostics_with_app_state(&self, screen: &mut DiagnosticsScreen) {
-        // Set IPv4 address from local_ip
-        if !self.local_ip.is_empty() {
-            // Parse to check if it's IPv4 or IPv6
-            if let Ok(ip) = self.local_ip.parse::<std::net::IpAddr>() {
-                if ip.is_ipv4() {
-                    screen.set_ipv4_address(Some(self.local_ip.clone()));
-                } else if ip.is_ipv6() {
-                    screen.set_ipv6_address(Some(self.local_ip.clone()));
-                }
-            } else {
-                // If it's not a valid IP, assume IPv4 for display
-                screen.set_ipv4_address(Some(self.local_ip.clone()));
-            }
+    /// Profile label and UID fingerprint, e.g. "work · 3f9a1c2e"
+    ///
+    /// Just the fingerprint when no label is set.
+    pub fn identity_label(&self) -> String {
+        let uid = self.keypair.uid.to_string();
+        let fingerprint = &uid[..IDENTITY_FINGERPRINT_CHARS.min(uid.len())];
+        match self.app_state.settings.profile_label.as_str() {
+            "" => fingerprint.to_string(),
+            label => format!("{} · {}", label, fingerprint),
         }
+    }
 
-        // Set queue size from SQLite queue (not app_state.message_queue)
-        let queue_size = self.queue.count_pending().unwrap_or(0);
-        screen.set_queue_size(queue_size);
+    /// Chat view title: the contact and the identity messages are sent as
+    ///
+    /// A temporary contact is marked "[temporary]".
+    pub fn chat_title(&self, contact_uid: &str) -> String {
+        let uid_short = &contact_uid[..16.min(contact_uid.len())];
+        let temporary = if self.app_state.contact_by_uid(contact_uid).is_some_and(|c| c.is_ephemeral()) {
+            " [temporary]"
+        } else {
+            ""
+        };
+        let capture = if self.capture_badge(contact_uid) { " [capturing]" } else { "" };
+        format!("Chat with {}{}{}  (as {})", uid_short, temporary, capture, self.identity_label())
     }
 
-    /// Refresh diagnostics screen with latest data
-    pub fn refresh_diagnostics(&mut self) {
-        // Extract necessary data first to avoid borrow conflicts
-        let local_ip = self.local_ip.clone();
-        let queue_size = self.queue.count_pending().unwrap_or(0);
+    /// Outgoing message for the chat input, with disallowed characters removed
+    fn outgoing_message(&self, contact_uid: &str, input: &str) -> Message {
+        Message::new(
+            uuid::Uuid::new_v4().to_string(),
+            self.keypair.uid.to_string(),
+            contact_uid.to_string(),
+            sanitize_text(input).into_bytes(),
+            Utc::now().timestamp_millis(),
+        )
+    }
 
-        if let Some(screen) = &mut self.diagnostics_screen {
-            // Set IPv4 address from local_ip
-            if !local_ip.is_empty() {
-                // Parse to check if it's IPv4 or IPv6
-                if let Ok(ip) = local_ip.parse::<std::net::IpAddr>() {
-                    if ip.is_ipv4() {
-                        screen.set_ipv4_address(Some(local_ip));
-                    } else if ip.is_ipv6() {
-                        screen.set_ipv6_address(Some(local_ip));
-                    }
-                } else {
-                    // If it's not a valid IP, assume IPv4 for display
-                    screen.set_ipv4_address(Some(local_ip));
-                }
-            }
+    /// Wall-clock time of `contact_uid` at `now` and the offset it comes from
+    ///
+    /// None until an offset is set by hand or inferred (see `storage::local_time`).
+    pub fn contact_local_time(&self, contact_uid: &str, now: DateTime<Utc>) -> Option<(DateTime<FixedOffset>, UtcOffset)> {
+        let offset = self.app_state.contact_by_uid(contact_uid)?.utc_offset(now)?;
+        Some((local_time_at(now, offset.minutes()), offset))
+    }
 
-            // Set queue size
-            screen.set_queue_size(queue_size);
+    /// Warning for the chat input while it is likely night for the open chat's contact
+    ///
+    /// Only with something typed and `Settings::night_warning_enabled`.
+    pub fn night_warning(&self, now: DateTime<Utc>) -> Option<String> {
+        let chat_view = self.chat_view_screen.as_ref()?;
+        if !self.app_state.settings.night_warning_enabled || chat_view.input.trim().is_empty() {
+            return None;
         }
+        let (local, offset) = self.contact_local_time(&chat_view.contact_uid, now)?;
+        is_night_at(now, offset.minutes()).then(|| format!("Likely night for them ({} their time)", local.format("%H:%M")))
     }
 
-    /// Trigger async diagnostics refresh (non-blocking)
+    /// Counter for the chat input footer (None outside the chat view)
+    pub fn chat_input_counter(&self) -> Option<InputCounter> {
+        let chat_view = self.chat_view_screen.as_ref()?;
+        let message = self.outgoing_message(&chat_view.contact_uid, &chat_view.input);
+        let encoded = crate::messaging::encoded_size(&message, "text").unwrap_or(usize::MAX);
+        Some(InputCounter::new(&chat_view.input, encoded))
+    }
+
+    /// Send message in current chat
     ///
-    /// This spawns a background thread with a tokio runtime to perform
-    /// connectivity tests. Results are automatically polled in `poll_diagnostics_result()`.
-    pub fn trigger_diagnostics_refresh(&mut self) {
-        // Don't start a new refresh if one is already running
-        if self.diagnostics_refresh_handle.is_some() {
+    /// Control characters are stripped first; a message whose encoded request
+    /// exceeds `MAX_MESSAGE_BYTES` is refused and left in the input. The first
+    /// send of a session from a labelled profile only shows "sending as
+    /// <label>"; pressing Enter again sends. Messages are sealed when the
+    /// contact's X25519 key is known; otherwise they go out in plaintext and
+    /// the contact is asked for its key once per session.
+    ///
+    /// Repeating the previous outgoing text within
+    /// `Settings::duplicate_window_secs` asks "send duplicate? [y/N]" first
+    /// (see `answer_duplicate_prompt`).
+    ///
+    /// A message near the size limit, going out in plaintext or to a
+    /// contact with messages still queued is previewed first when
+    /// `Settings::auto_send_preview` is on (see `answer_send_preview`).
+    /// Before that, a contact with messages still queued whose token expires
+    /// before the next retries gets a warning (see `answer_expiry_warning`).
+    pub fn send_message_in_chat(&mut self) {
+        if self.refuse_in_inspection("send messages") {
             return;
         }
+        self.send_chat_input(false, false);
+    }
 
-        if let Some(screen) = &mut self.diagnostics_screen {
-            let port = screen.local_port;
-
-            // Spawn background thread with tokio runtime
-            let handle = std::thread::spawn(move || {
-                let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
-                rt.block_on(async move {
-                    crate::connectivity::establish_connectivity(port).await
-                })
-            });
+    /// What sending `message` to `contact_uid` will do (None: unknown contact)
+    ///
+    /// Seals the request the way the send path does, so the security and
+    /// message type shown are the ones that go out.
+    pub fn send_preview(&self, message: &Message, contact_uid: &str, now: chrono::DateTime<Utc>) -> Option<SendPreview> {
+        let contact = self.app_state.contact_by_uid(contact_uid)?;
+        let bytes = crate::messaging::encoded_size(message, "text").ok()?;
+        let (request, security) =
+            crate::messaging::outgoing_request(message, "text", Some(&self.keypair), contact).ok()?;
+
+        let queued = self.queue.queued_at_for(contact_uid).unwrap_or_else(|e| {
+            tracing::warn!("Failed to read queued messages for {}: {}", contact_uid, e);
+            std::collections::HashMap::new()
+        });
+        let queue_hint = queued
+            .values()
+            .min()
+            .and_then(|oldest| chrono::DateTime::from_timestamp_millis(*oldest))
+            .map(|queued_at| delivery_hint(queued_at, Some(contact.expiry), self.last_heard_from(contact_uid), now));
+
+        let uid_short = &contact_uid[..16.min(contact_uid.len())];
+        Some(SendPreview {
+            bytes,
+            parts: 1,
+            message_type: request.message_type,
+            security,
+            destinations: vec![format!("{} ({})", uid_short, contact.ip)],
+            queued: queued.len(),
+            queue_hint,
+            reasons: preview_reasons(InputCounter::new(&String::from_utf8_lossy(&message.content), bytes), security, queue_hint),
+        })
+    }
 
-            self.diagnostics_refresh_handle = Some(handle);
-        }
+    /// Warning for sending to `contact_uid` at `now` (None: send right away)
+    ///
+    /// The contact counts as unreachable while earlier messages to it are
+    /// queued. The worker's attempts are replayed with the queue's backoff,
+    /// the retry interval and quiet hours (see `tui::expiry_warning`).
+    pub fn send_expiry_warning(&self, contact_uid: &str, now: DateTime<Utc>) -> Option<ExpiryWarning> {
+        let contact = self.app_state.contact_by_uid(contact_uid)?;
+        let reachable = self.queue.queued_at_for(contact_uid).map_or(true, |queued| queued.is_empty());
+        let settings = &self.app_state.settings;
+        let attempts = retry_opportunities(
+            now,
+            chrono::Duration::milliseconds(self.queue.base_delay_ms),
+            self.queue.max_retries,
+            chrono::Duration::milliseconds(settings.global_retry_interval_ms as i64),
+            |at| crate::storage::is_quiet(&at.with_timezone(&chrono::Local), settings),
+        );
+        expiry_warning(contact.expiry, reachable, &attempts, now)
     }
 
-    /// Poll for diagnostics refresh completion (non-blocking)
+    /// Answer the token expiry warning
     ///
-    /// Checks if the background refresh thread has completed and applies results.
-    /// Returns true if a refresh was completed this call.
-    pub fn poll_diagnostics_result(&mut self) -> bool {
-        if let Some(handle) = self.diagnostics_refresh_handle.take() {
-            // Check if thread is finished (non-blocking)
-            if handle.is_finished() {
-                // Thread is done, get the result
-                match handle.join() {
-                    Ok(result) => {
-                        // Update local_ip and port from the mapping result
-                        if let Some(mapping) = &result.mapping {
-                            let detected_ip = format!("{}:{}", mapping.external_ip, mapping.external_port);
-                            self.local_ip = detected_ip.clone();
-
-                            // Save detected IP and port to app_state for persistence
-                            self.app_state.user_ip = Some(detected_ip);
-                            self.app_state.user_port = mapping.external_port;
-                            let _ = self.save_state();
-                        }
-                        self.connectivity_result = Some(result.clone());
-                        self.apply_connectivity_result(result);
-                        return true;
-                    }
-                    Err(e) => {
-                        if let Some(screen) = &mut self.diagnostics_screen {
-                            screen.set_status_message(format!("Refresh failed: {:?}", e));
-                            screen.is_refreshing = false;
-                        }
-                        return true;
-                    }
+    /// Sending after the warning skips the send preview: the warning already
+    /// covers the unreachable contact it would show. Cancelling keeps the
+    /// input as typed.
+    pub fn answer_expiry_warning(&mut self, choice: ExpiryChoice) {
+        if self.refuse_in_inspection("send messages") {
+            return;
+        }
+        let Some(chat_view) = &mut self.chat_view_screen else {
+            return;
+        };
+        if chat_view.expiry_warning.take().is_none() {
+            return;
+        }
+        let contact_uid = chat_view.contact_uid.clone();
+        match choice {
+            ExpiryChoice::Cancel => chat_view.set_status("Not sent - still in the input".to_string()),
+            ExpiryChoice::SendAnyway => self.send_chat_input(true, true),
+            ExpiryChoice::SendAndRequestRefresh => {
+                self.send_chat_input(true, true);
+                let status = match self.request_token_refresh(&contact_uid, Utc::now()) {
+                    Ok(true) => "Sent; token refresh requested".to_string(),
+                    Ok(false) => "Sent; token refresh already requested".to_string(),
+                    Err(e) => format!("Sent, but could not request a token refresh: {}", e),
+                };
+                if let Some(chat_view) = &mut self.chat_view_screen {
+                    chat_view.set_status(status);
                 }
-            } else {
-                // Thread is still running, put the handle back
-                self.diagnostics_refresh_handle = Some(handle);
             }
         }
-        false
     }
 
-    /// Apply connectivity result to diagnostics screen
-    pub fn apply_connectivity_result(&mut self, result: crate::connectivity::ConnectivityResult) {
-        if let Some(screen) = &mut self.diagnostics_screen {
-            screen.update_from_connectivity_result(&result);
+    /// Queue a token refresh request to `contact_uid` at urgent priority
+    ///
+    /// The contact answers with a ping carrying a fresh token (see
+    /// `answer_token_refresh_requests`); one already queued is not duplicated.
+    ///
+    /// # Returns
+    /// Whether a request was queued
+    pub fn request_token_refresh(&mut self, contact_uid: &str, now: DateTime<Utc>) -> crate::Result<bool> {
+        if self.queue.has_queued_type_for(contact_uid, TOKEN_REFRESH_REQUEST_TYPE)? {
+            return Ok(false);
         }
+        let request = Message::new(
+            uuid::Uuid::new_v4().to_string(),
+            self.keypair.uid.to_string(),
+            contact_uid.to_string(),
+            Vec::new(),
+            now.timestamp_millis(),
+        );
+        self.queue.enqueue_with_type(request, crate::queue::Priority::Urgent, TOKEN_REFRESH_REQUEST_TYPE)?;
+        tracing::info!("Token refresh requested from {}", contact_uid);
+        self.refresh_queued_since();
+        self.retry_wakeup.notify();
+        Ok(true)
     }
 
-    /// Return to main menu
-    pub fn back_to_main_menu(&mut self) {
-        // Reload state to pick up any changes while in other screens
-        let _ = self.reload_state();
+    /// Block the queued messages to contacts whose token expired by `now`,
+    /// and unblock those whose contact has a valid token again
+    ///
+    /// Blocked messages are not retried until unblocked; then they are due
+    /// at once with fresh attempts (see `queue`). Messages to a contact that
+    /// is gone are unblocked too, so the retry worker drops them.
+    ///
+    /// # Returns
+    /// How many messages were blocked and unblocked
+    pub fn sync_expired_blocks(&mut self, now: DateTime<Utc>) -> (usize, usize) {
+        self.expired_blocks_synced_at = Some(now);
+        let Some(blocked_uids) = self.error_reports.check(ErrorSeverity::Warning, "message queue", self.queue.blocked_uids()) else {
+            return (0, 0);
+        };
 
-        self.current_screen = Screen::MainMenu;
-        self.share_contact_screen = None;
-        self.import_contact_screen = None;
-        self.chat_list_screen = None;
-        self.chat_view_screen = None;
-        self.settings_screen = None;
-        self.diagnostics_screen = None;
+        let mut blocked = 0;
+        for contact in self.app_state.contacts.iter().filter(|c| c.expiry <= now) {
+            match self.queue.block_for_at(&contact.uid, now.timestamp_millis()) {
+                Ok(0) => {}
+                Ok(count) => {
+                    blocked += count;
+                    tracing::info!("Blocked {} queued message(s) to {}: token expired", count, contact.uid);
+                }
+                Err(e) => tracing::error!("Failed to block queued messages to {}: {}", contact.uid, e),
+            }
+        }
+
+        let mut unblocked = 0;
+        for uid in blocked_uids {
+            if self.app_state.contact_by_uid(&uid).is_some_and(|c| c.expiry <= now) {
+                continue;
+            }
+            match self.queue.unblock_for_at(&uid, now.timestamp_millis()) {
+                Ok(0) => {}
+                Ok(count) => {
+                    unblocked += count;
+                    tracing::info!("Unblocked {} queued message(s) to {}", count, uid);
+                    let uid_short = &uid[..16.min(uid.len())];
+                    self.notifications.notify(format!("{} has a new token: resending {} message(s)", uid_short, count));
+                }
+                Err(e) => tracing::error!("Failed to unblock queued messages to {}: {}", uid, e),
+            }
+        }
+        if unblocked > 0 {
+            self.retry_wakeup.notify();
+        }
+        (blocked, unblocked)
     }
 
-    /// Return to chat list
-    pub fn back_to_chat_list(&mut self) {
-        // Reload state to show any new messages
-        let _ = self.reload_state();
+    /// Run `sync_expired_blocks` if `EXPIRED_BLOCK_SYNC_SECS` have passed
+    /// since it last ran (called from the main loop)
+    pub fn maybe_sync_expired_blocks(&mut self, now: DateTime<Utc>) {
+        if self.inspection.is_some() {
+            return;
+        }
+        let due = self
+            .expired_blocks_synced_at
+            .is_none_or(|at| now - at >= chrono::Duration::seconds(EXPIRED_BLOCK_SYNC_SECS));
+        if due {
+            self.sync_expired_blocks(now);
+        }
+    }
 
-        self.current_screen = Screen::ChatList;
-        self.chat_view_screen = None;
+    /// Preview sending the chat input without sending it (Ctrl+O)
+    pub fn preview_chat_input(&mut self) {
+        if self.refuse_in_inspection("send messages") {
+            return;
+        }
+        let Some(chat_view) = &self.chat_view_screen else {
+            return;
+        };
+        if chat_view.editing.is_some() {
+            return;
+        }
+        let message = self.outgoing_message(&chat_view.contact_uid, &chat_view.input);
+        if String::from_utf8_lossy(&message.content).trim().is_empty() {
+            return;
+        }
+        let preview = self.send_preview(&message, &chat_view.contact_uid, Utc::now());
+        if let Some(chat_view) = &mut self.chat_view_screen {
+            match preview {
+                Some(preview) => chat_view.send_preview = Some(preview),
+                None => chat_view.set_status("Error: Contact not found".to_string()),
+            }
+        }
     }
 
-    /// Complete startup sync
-    pub fn complete_startup_sync(&mut self) {
-        self.current_screen = Screen::MainMenu;
-        self.startup_sync_screen = None;
+    /// Answer the send preview: send the input as previewed, or keep it unsent
+    ///
+    /// Cancelling changes nothing but the status line; the input stays as typed.
+    pub fn answer_send_preview(&mut self, send: bool) {
+        if self.refuse_in_inspection("send messages") {
+            return;
+        }
+        let Some(chat_view) = &mut self.chat_view_screen else {
+            return;
+        };
+        if chat_view.send_preview.take().is_none() {
+            return;
+        }
+        if send {
+            self.send_chat_input(true, true);
+        } else {
+            chat_view.set_status("Not sent - still in the input".to_string());
+        }
     }
 
-    /// Update startup sync progress
-    pub fn update_startup_sync(&mut self) {
-        // Simulate processing messages
-        // In a real implementation, this would actually send messages via the queue
-        if let Some(sync_screen) = &mut self.startup_sync_screen {
-            if !sync_screen.is_complete && sync_screen.current < sync_screen.total_messages {
-                // Simulate success/failure (80% success rate for demo)
-                let success = (sync_screen.current % 5) != 1;
-                sync_screen.process_message(success);
-            }
+    /// Answer the "send duplicate?" prompt: send the input again or keep it unsent
+    pub fn answer_duplicate_prompt(&mut self, send: bool) {
+        if self.refuse_in_inspection("send messages") {
+            return;
+        }
+        let Some(chat_view) = &mut self.chat_view_screen else {
+            return;
+        };
+        if !std::mem::take(&mut chat_view.duplicate_prompt) {
+            return;
+        }
+        if send {
+            self.send_chat_input(true, false);
+        } else {
+            chat_view.set_status("Duplicate not sent".to_string());
         }
     }
 
-    /// Open selected chat
-    pub fn open_selected_chat(&mut self) {
-        // Get selected index first to avoid borrow checker issues
-        let selected_index = if let Some(chat_list) = &self.chat_list_screen {
-            if self.app_state.chats.is_empty() {
+    /// Send the chat input (see `send_message_in_chat`)
+    ///
+    /// `previewed`: the send preview was already confirmed.
+    fn send_chat_input(&mut self, allow_duplicate: bool, previewed: bool) {
+        if let Some(message_id) = self.chat_view_screen.as_ref().and_then(|s| s.editing.clone()) {
+            self.submit_edit(message_id);
+            return;
+        }
+
+        // Extract necessary data from chat_view_screen first
+        let (message, contact_uid) = if let Some(chat_view) = &self.chat_view_screen {
+            let message = self.outgoing_message(&chat_view.contact_uid, &chat_view.input);
+            if String::from_utf8_lossy(&message.content).trim().is_empty() {
                 return;
             }
-            chat_list.selected_index
+            (message, chat_view.contact_uid.clone())
         } else {
             return;
         };
 
-        // Reload state before entering chat to show latest messages
-        let _ = self.reload_state();
-
-        let contact_uid = self.app_state.chats[selected_index].contact_uid.clone();
-        self.chat_view_screen = Some(ChatViewScreen::new(contact_uid));
-        self.current_screen = Screen::ChatView;
-    }
-
-    /// Show delete confirmation popup
-    pub fn show_delete_confirmation(&mut self) {
-        if let Some(chat_list) = &mut self.chat_list_screen {
-            if self.app_state.chats.is_empty() {
-                return;
+        if let Err(e) = crate::messaging::validate_outgoing(&message, "text") {
+            if let Some(chat_view) = &mut self.chat_view_screen {
+                chat_view.set_status(format!("Not sent: {}", e));
             }
-            chat_list.show_delete_popup(chat_list.selected_index);
+            return;
         }
-    }
-
-    /// Confirm deletion of chat
-    pub fn confirm_delete_chat(&mut self) {
-        if let Some(chat_list) = &self.chat_list_screen {
-            if let Some(delete_index) = chat_list.pending_delete_index {
-                if delete_index >= self.app_state.chats.len() {
-                    return;
-                }
-
-                let chat = &self.app_state.chats[delete_index];
-                let chat_uid = chat.contact_uid.clone();
-                let is_active = chat.is_active;
-
-                // Delete the chat
-                self.app_state.chats.retain(|c| c.contact_uid != chat_uid);
-
-                // Update status based on whether it was active or inactive
-                if let Some(screen) = &mut self.chat_list_screen {
-                    let status_msg = if is_active {
-                        format!("Sent delete request and removed chat with {}", &chat_uid[..16.min(chat_uid.len())])
-                    } else {
-                        format!("Deleted inactive chat with {}", &chat_uid[..16.min(chat_uid.len())])
-                    };
-                    screen.set_status(status_msg);
-                    screen.hide_delete_popup();
-
-                    // Adjust selection if needed
-                    if screen.selected_index >= self.app_state.chats.len() && !self.app_state.chats.is_empty() {
-                        screen.selected_index = self.app_state.chats.len() - 1;
-                    }
-                }
 
-                // Auto-save after deleting chat
-                let _ = self.save_state();
-
-                // TODO: Actually send delete request via transport if chat was active
-                // For now, we just delete locally
+        // Nothing is queued for an address that can never be dialled
+        let address_check = self.app_state.contact_by_uid(&contact_uid).map(validate_contact_addresses);
+        if let Some(Err(e)) = address_check {
+            tracing::warn!("Not sending to {}: {}", contact_uid, e);
+            if let Some(chat_view) = &mut self.chat_view_screen {
+                chat_view.set_status(format!("Not sent: {}", INVALID_ADDRESS_STATUS));
             }
+            return;
         }
-    }
 
-    /// Cancel chat deletion
-    pub fn cancel_delete_chat(&mut self) {
-        if let Some(screen) = &mut self.chat_list_screen {
-            screen.hide_delete_popup();
+        if !self.sending_as_confirmed && !self.app_state.settings.profile_label.is_empty() {
+            self.sending_as_confirmed = true;
+            let identity = self.identity_label();
+            if let Some(chat_view) = &mut self.chat_view_screen {
+                chat_view.set_status(format!("Sending as {} - press Enter again to send", identity));
+            }
+            return;
         }
-    }
 
-    /// Import a contact and create a new chat
-    pub fn import_contact(&mut self, contact: crate::storage::Contact) {
-        // Check if trying to import own contact (self-import)
-        if contact.uid == self.keypair.uid.to_string() {
-            if let Some(screen) = &mut self.import_contact_screen {
-                screen.status_message = Some("Error: Cannot import your own contact token".to_string());
-                screen.is_error = true;
+        let window_secs = self.app_state.settings.duplicate_window_secs;
+        let duplicate = self
+            .app_state
+            .get_chat(&contact_uid)
+            .is_some_and(|chat| crate::messaging::is_duplicate_send(chat, &message, window_secs, allow_duplicate));
+        if duplicate {
+            if let Some(chat_view) = &mut self.chat_view_screen {
+                chat_view.duplicate_prompt = true;
+                chat_view.set_status("Same as your last message - send duplicate? [y/N]".to_string());
             }
             return;
         }
 
-        // Check if contact already exists
-        if !self.app_state.contacts.iter().any(|c| c.uid == contact.uid) {
-            let contact_uid = contact.uid.clone();
-
-            // Add contact to list
-            self.app_state.contacts.push(contact.clone());
-
-            // Create a new chat for this contact with pending status
-            // (will be updated to active if ping succeeds)
-            let mut new_chat = crate::storage::Chat::new(contact_uid.clone());
-            new_chat.mark_has_pending(); // Mark as pending until ping succeeds
-            self.app_state.chats.push(new_chat);
-
-            // Auto-save after importing contact and creating chat
-            let _ = self.save_state();
-
-            // Generate my contact token to send in ping (so receiver can auto-import me)
-            let my_contact = crate::storage::Contact::new(
-                self.keypair.uid.to_string(),
-                self.local_ip.clone(),
-                self.keypair.public_key.clone(),
-                self.keypair.x25519_public.clone(),
-                Utc::now() + chrono::Duration::days(1), // 24 hour expiry
-            );
-            let my_token = match my_contact.sign_token(&self.keypair) {
-                Ok(token) => token,
-                Err(e) => {
-                    tracing::error!("Failed to generate contact token for ping: {}", e);
-                    if let Some(screen) = &mut self.import_contact_screen {
-                        screen.status_message = Some(format!("Error generating token: {}", e));
-                        screen.is_error = true;
-                    }
-                    return;
-                }
-            };
-
-            // Try to send ping immediately, queue on failure (background thread)
-            let transport = self.transport.clone();
-            let contact_for_ping = contact.clone();
-            let storage_clone = self.storage.clone();
-            let sender_uid = self.keypair.uid.to_string();
-
-            std::thread::spawn(move || {
-                let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
-                rt.block_on(async move {
-                    // Try to send ping
-                    match transport.send_ping(&contact_for_ping, &my_token).await {
-                        Ok(ping_response) => {
-                            tracing::info!("Successfully pinged newly imported contact {} (response: {})", contact_for_ping.uid, ping_response.status);
-                            // Ping succeeded - mark chat as active and clear pending status
-                            if let Ok(mut app_state) = AppState::load_from_db(&storage_clone) {
-                                // Mark chat as active (connection confirmed) and clear pending flag
-                                if let Some(chat) = app_state.chats.iter_mut().find(|c| c.contact_uid == contact_for_ping.uid) {
-                                    chat.mark_unread(); // Mark as active
-                                    chat.mark_no_pending(); // Clear pending flag since ping succeeded
-                                    tracing::info!("Marked chat with {} as active after successful ping", contact_for_ping.uid);
-                                }
-                                let _ = app_state.save_to_db(&storage_clone);
-                            }
-                        }
-                        Err(e) => {
-                            tracing::warn!("Failed to ping newly imported contact {}: {}. Queueing for retry.", contact_for_ping.uid, e);
-
-                            // Create a special "ping" message to queue
-                            let ping_message = crate::storage::Message::new(
-                                uuid::Uuid::new_v4().to_string(),
-                                sender_uid,
-                                contact_for_ping.uid.clone(),
-                                my_token.as_bytes().to_vec(), // Store contact token as content
-                                Utc::now().timestamp_millis(),
-                            );
-
-                            // Create queue instance for this thread
-                            let mut queue = match crate::queue::MessageQueue::new_with_path("./app_data/message_queue.db") {
-                                Ok(q) => q,
-                                Err(e) => {
-                                    tracing::error!("Failed to create queue: {}", e);
-                                    return;
-                                }
-                            };
-
-                            // Queue the ping with Urgent priority
-                            if let Err(e) = queue.enqueue_with_type(
-                                ping_message,
-                                crate::queue::Priority::Urgent,
-                                "ping"
-                            ) {
-                                tracing::error!("Failed to queue ping for {}: {}", contact_for_ping.uid, e);
-                                return;
-                            }
-
-                            // Sync pending status with queue (will set has_pending_messages=true)
-                            if let Ok(mut app_state) = AppState::load_from_db(&storage_clone) {
-                                if let Ok(pending_uids) = queue.get_pending_contact_uids() {
-                                    app_state.sync_pending_status(&pending_uids);
-                                    let _ = app_state.save_to_db(&storage_clone);
-                                }
-                            }
-                        }
-                    }
-                });
-            });
-
-            // Update import screen status
-            if let Some(screen) = &mut self.import_contact_screen {
-                screen.status_message = Some(format!("✓ Contact imported, ping sent!"));
-                screen.is_error = false;
-            }
-        } else {
-            // Contact already exists
-            if let Some(screen) = &mut self.import_contact_screen {
-                screen.status_message = Some("Contact already exists".to_string());
-                screen.is_error = true;
-            }
+        if !previewed
+            && let Some(warning) = self.send_expiry_warning(&contact_uid, Utc::now())
+            && let Some(chat_view) = &mut self.chat_view_screen
+        {
+            chat_view.set_status(warning.text());
+            chat_view.expiry_warning = Some(warning);
+            return;
         }
-    }
 
-    /// Send message in current chat
-    pub fn send_message_in_chat(&mut self) {
-        // Extract necessary data from chat_view_screen first
-        let (message_content, contact_uid) = if let Some(chat_view) = &self.chat_view_screen {
-            if chat_view.input.trim().is_empty() {
+        if !previewed && self.app_state.settings.auto_send_preview {
+            let preview = self.send_preview(&message, &contact_uid, Utc::now());
+            if let Some(preview) = preview.filter(|p| p.needs_confirmation())
+                && let Some(chat_view) = &mut self.chat_view_screen
+            {
+                chat_view.send_preview = Some(preview);
                 return;
             }
-            (chat_view.input.clone(), chat_view.contact_uid.clone())
-        } else {
-            return;
-        };
-
-        // Find the chat and add the message
-        if let Some(chat) = self.app_state.chats.iter_mut().find(|c| c.contact_uid == contact_uid) {
-            // Create a new message
-            let message = Message::new(
-                uuid::Uuid::new_v4().to_string(),
-                self.keypair.uid.to_string(),
-                contact_uid.clone(),
-                message_content.as_bytes().to_vec(),
-                Utc::now().timestamp_millis(),
-            );
+        }
 
-            chat.append_message(message.clone());
+        // Find the chat and add the message (trimming history beyond the limit)
+        let history_limit = self.app_state.settings.history_limit;
+        if let Some(chat) = self.app_state.chat_by_uid_mut(&contact_uid) {
+            chat.append_with_limit(message.clone(), history_limit);
 
             // Clear input after adding message
             if let Some(chat_view) = &mut self.chat_view_screen {
@@ -1044,50 +6072,128 @@ impl App {
             }
 
             // Auto-save after sending message
-            let _ = self.save_state();
+            self.save_or_report();
 
             // Send message via messaging API (auto-queues on failure)
-            let contact_found = self.app_state.contacts.iter().find(|c| c.uid == contact_uid).cloned();
+            let contact_found = self.app_state.contact_by_uid(&contact_uid).cloned();
 
             if let Some(contact) = contact_found {
-                let transport = self.transport.clone();
+                let transports = self.transports.clone();
                 let message_clone = message.clone();
+                let delivery_events = self.delivery_event_sender();
+                let keypair = self.keypair.clone();
+                let reports = self.error_reports.clone();
+
+                // Contacts without an X25519 key are asked for it once per session
+                let security = send_security(Some(&self.keypair), &contact);
+                let request_upgrade = security == SendSecurity::MissingKey
+                    && self.key_upgrades_requested.insert(contact.uid.clone());
+
+                // Contacts without fresh capabilities are asked after a delivery
+                let probe = self.capability_probes.start(&contact, Utc::now());
+                let capability_answers = self.capability_answers.clone();
+                let updates = self.incoming_updates.clone();
+                // The queue the app reads, so older waiting messages are seen
+                let queue_path = self.queue_path();
+                // Taken back by the sweep if the thread dies without an outcome
+                let leases = self.send_leases.clone();
+                leases.begin(&message.id, &contact.uid, Utc::now());
 
                 std::thread::spawn(move || {
-                    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
-                    rt.block_on(async move {
+                    let (contact_uid, message_id) = (contact.uid.clone(), message_clone.id.clone());
+                    let (failures, crash_reports) = (delivery_events.clone(), reports.clone());
+                    // The attempt runs under the lease; its outcome is applied only if the lease was still held
+                    let attempted = run_send(&leases, &contact_uid, &message_id, &failures, &crash_reports, || {
+                        let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
                         // Create new MessageQueue instance (persistent SQLite allows multiple connections)
-                        let mut queue = match crate::queue::MessageQueue::new_with_path("./app_data/message_queue.db") {
+                        let queue = match crate::queue::MessageQueue::new_for_identity(&queue_path, &keypair) {
                             Ok(q) => q,
                             Err(e) => {
-                                tracing::error!("Failed to create queue: {}", e);
-                                return;
+                                reports.report(ErrorSeverity::Error, "message queue", format!("Failed to open the queue, message to {} not sent: {}", contact.uid, e));
+                                return None;
                             }
                         };
+                        let attempt = rt.block_on(async {
+                            let attempt = crate::messaging::attempt_sealed(
+                                &transports,
+                                &queue,
+                                Some(&keypair),
+                                &contact,
+                                &message_clone,
+                                "text",
+                                crate::queue::Priority::Normal,
+                            );
+                            let limit = std::time::Duration::from_secs(SEND_TRANSPORT_TIMEOUT_SECS as u64);
+                            tokio::time::timeout(limit, attempt).await.unwrap_or_else(|_| {
+                                let timed_out = crate::Error::Transport(format!("Send timed out after {}s", SEND_TRANSPORT_TIMEOUT_SECS));
+                                tracing::warn!("Message {} to {}: {}. Queueing for retry.", message_clone.id, contact.uid, timed_out);
+                                Ok((SendAttempt::Failed(timed_out), security))
+                            })
+                        });
+                        Some((rt, queue, attempt))
+                    });
+                    let Some(Some((rt, mut queue, attempt))) = attempted else {
+                        return;
+                    };
 
-                        match crate::messaging::send_message(
-                            &transport,
-                            &mut queue,
-                            &contact,
-                            &message_clone,
-                            crate::queue::Priority::Normal,
-                        ).await {
-                            Ok(delivered) => {
-                                if delivered {
-                                    tracing::info!("Message sent successfully to {}", contact.uid);
+                    rt.block_on(async move {
+                        let delivered = match attempt {
+                            Ok((attempt, security)) => {
+                                let delivered = attempt.is_delivered();
+                                let queued = if delivered {
+                                    Ok(())
                                 } else {
-                                    tracing::info!("Message queued for retry to {}", contact.uid);
+                                    queue.enqueue_with_type(message_clone.clone(), crate::queue::Priority::Normal, "text")
+                                };
+                                match queued {
+                                    Ok(()) => {
+                                        let update = if delivered {
+                                            tracing::info!("Message sent successfully to {}", contact.uid);
+                                            DeliveryUpdate::Delivered
+                                        } else {
+                                            tracing::info!("Message queued for retry to {}", contact.uid);
+                                            DeliveryUpdate::Queued
+                                        };
+                                        let event = DeliveryEvent::from_queue(&queue, &contact.uid, update)
+                                            .with_message_id(&message_clone.id)
+                                            .with_protection(security.protection());
+                                        let _ = delivery_events.send(event);
+                                    }
+                                    Err(e) => {
+                                        reports.report(ErrorSeverity::Error, "message queue", format!("Failed to send or queue message to {}: {}", contact.uid, e));
+                                    }
                                 }
+                                delivered
                             }
                             Err(e) => {
-                                tracing::error!("Failed to send/queue message to {}: {}", contact.uid, e);
+                                reports.report(ErrorSeverity::Error, "message queue", format!("Failed to send or queue message to {}: {}", contact.uid, e));
+                                false
+                            }
+                        };
+
+                        if delivered && probe {
+                            match transports.probe_capabilities(&contact, &keypair.uid.to_string()).await {
+                                Ok(answer) => {
+                                    capability_answers.lock().unwrap().push((contact.uid.clone(), answer));
+                                    updates.store(true, std::sync::atomic::Ordering::SeqCst);
+                                }
+                                Err(e) => tracing::debug!("Capability probe of {} failed: {}", contact.uid, e),
+                            }
+                        }
+
+                        if request_upgrade {
+                            let request = key_upgrade_request(&keypair.uid.to_string());
+                            if let Err(e) = transports.send_message(&contact, &request).await {
+                                tracing::debug!("Could not request a key upgrade from {}: {}", contact.uid, e);
                             }
                         }
                     });
                 });
+                self.persist_send_leases();
 
                 if let Some(chat_view) = &mut self.chat_view_screen {
-                    chat_view.set_status("Message sent".to_string());
+                    let status = if security.is_encrypted() { "Message sent" } else { "Message sent (not encrypted)" };
+                    chat_view.set_status(status.to_string());
                 }
             } else {
                 if let Some(chat_view) = &mut self.chat_view_screen {
@@ -1097,6 +6203,151 @@ impl App {
         }
     }
 
+    /// Save the edit in the chat input (see `start_edit_selected`)
+    ///
+    /// The new text is sanitized and size-checked like a send. A message still
+    /// in the queue is changed in place and nothing else is sent; otherwise
+    /// the contact gets an edit, or a quoted correction as a new message if it
+    /// has not advertised edit support (see `edits`).
+    fn submit_edit(&mut self, message_id: String) {
+        let Some(chat_view) = &self.chat_view_screen else {
+            return;
+        };
+        let contact_uid = chat_view.contact_uid.clone();
+        let draft = self.outgoing_message(&contact_uid, &chat_view.input);
+        let new_text = String::from_utf8_lossy(&draft.content).into_owned();
+        let Some(original) = self
+            .app_state
+            .get_chat(&contact_uid)
+            .and_then(|chat| chat.messages.iter().find(|m| m.id == message_id))
+            .cloned()
+        else {
+            if let Some(chat_view) = &mut self.chat_view_screen {
+                chat_view.cancel_edit();
+                chat_view.set_status("Not edited: the message is gone".to_string());
+            }
+            return;
+        };
+
+        let set_status = |app: &mut Self, status: String| {
+            if let Some(chat_view) = &mut app.chat_view_screen {
+                chat_view.set_status(status);
+            }
+        };
+        if new_text.trim().is_empty() || *new_text.as_bytes() == *original.content {
+            if let Some(chat_view) = &mut self.chat_view_screen {
+                chat_view.cancel_edit();
+                chat_view.set_status("Edit cancelled: nothing changed".to_string());
+            }
+            return;
+        }
+        let window = self.app_state.settings.edit_window_minutes;
+        let now = Utc::now();
+        if let Err(e) = check_editable(&original, &self.keypair.uid.to_string(), window, now) {
+            set_status(self, format!("Not edited: {}", e));
+            return;
+        }
+        let Some(contact) = self.app_state.contact_by_uid(&contact_uid).cloned() else {
+            set_status(self, "Error: Contact not found".to_string());
+            return;
+        };
+
+        let edit = EditRequest {
+            message_id: message_id.clone(),
+            content: new_text.clone().into_bytes(),
+            edited_at: now.timestamp_millis(),
+        };
+        let edit_message = match edit.to_payload() {
+            Ok(payload) => Message::new(
+                uuid::Uuid::new_v4().to_string(),
+                self.keypair.uid.to_string(),
+                contact_uid.clone(),
+                payload,
+                edit.edited_at,
+            ),
+            Err(e) => {
+                set_status(self, format!("Not edited: {}", e));
+                return;
+            }
+        };
+        let checked = crate::messaging::validate_outgoing(&draft, "text")
+            .and_then(|_| crate::messaging::validate_outgoing(&edit_message, EDIT_TYPE));
+        if let Err(e) = checked {
+            set_status(self, format!("Not edited: {}", e));
+            return;
+        }
+
+        let queued = match self.queue.update_queued_content(&message_id, &edit.content) {
+            Ok(queued) => queued,
+            Err(e) => {
+                set_status(self, format!("Not edited: {}", e));
+                return;
+            }
+        };
+        let route = EditRoute::choose(queued, contact.supports_edits);
+        if route == EditRoute::Correction {
+            // An ordinary message quoting what it corrects
+            if let Some(chat_view) = &mut self.chat_view_screen {
+                chat_view.editing = None;
+                chat_view.input = correction_text(&original.display_text(), &new_text);
+                chat_view.cursor = chat_view.input.chars().count();
+            }
+            self.send_chat_input(true, false);
+            if let Some(chat_view) = &mut self.chat_view_screen
+                && chat_view.input.is_empty()
+            {
+                chat_view.set_status("Contact cannot receive edits - correction sent as a new message".to_string());
+            }
+            return;
+        }
+
+        if let Some(chat) = self.app_state.chat_by_uid_mut(&contact_uid)
+            && let Some(message) = chat.messages_mut().iter_mut().find(|m| m.id == message_id)
+        {
+            message.replace_content(edit.content.clone(), edit.edited_at);
+        }
+        if let Some(chat_view) = &mut self.chat_view_screen {
+            chat_view.cancel_edit();
+        }
+        self.save_or_report();
+
+        if route == EditRoute::LocalOnly {
+            set_status(self, "Queued message edited".to_string());
+            return;
+        }
+
+        let transports = self.transports.clone();
+        let keypair = self.keypair.clone();
+        let reports = self.error_reports.clone();
+        let queue_path = self.queue_path();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+            rt.block_on(async move {
+                let mut queue = match crate::queue::MessageQueue::new_for_identity(&queue_path, &keypair) {
+                    Ok(q) => q,
+                    Err(e) => {
+                        reports.report(ErrorSeverity::Warning, "message queue", format!("Failed to open the queue, edit to {} not sent: {}", contact.uid, e));
+                        return;
+                    }
+                };
+                match crate::messaging::send_sealed_with_type(
+                    &transports,
+                    &mut queue,
+                    Some(&keypair),
+                    &contact,
+                    &edit_message,
+                    EDIT_TYPE,
+                    crate::queue::Priority::Normal,
+                ).await {
+                    Ok((true, _)) => tracing::info!("Edit of {} sent to {}", message_id, contact.uid),
+                    Ok((false, _)) => tracing::info!("Edit of {} queued for retry to {}", message_id, contact.uid),
+                    Err(e) => reports.report(ErrorSeverity::Warning, "message queue", format!("Failed to send or queue edit to {}: {}", contact.uid, e)),
+                }
+            });
+        });
+        set_status(self, "Message edited".to_string());
+    }
+
     /// Start background retry worker
     ///
     /// This spawns a background thread that periodically processes the message queue
@@ -1107,34 +6358,41 @@ impl App {
     /// 2. Then periodically checks for messages where next_retry <= now
     /// 3. Attempts delivery for each (handles both "ping" and "text" types)
     /// 4. Updates queue status appropriately (success/failure)
+    ///
+    /// Each contact's text messages go out oldest first: after one fails,
+    /// later ones wait for it (see `queue::order_per_contact`).
     pub fn start_retry_worker(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.inspection.is_some() {
+            return Err("The retry worker is not started in inspection mode".into());
+        }
         // Don't start if already running
         if self.retry_worker_handle.is_some() {
             return Ok(());
         }
 
-        let transport = self.transport.clone();
-        let queue_path = if self.state_path.contains("test") || self.state_path.contains("tmp") {
-            std::path::Path::new(&self.state_path)
-                .parent()
-                .and_then(|p| p.to_str())
-                .map(|p| format!("{}/message_queue.db", p))
-                .unwrap_or_else(|| "message_queue.db".to_string())
-        } else {
-            "./app_data/message_queue.db".to_string()
-        };
+        let transports = self.transports.clone();
+        let queue_path = self.queue_path();
         let storage_path = self.state_path.clone();
         let stop_flag = self.retry_worker_stop.clone();
-        let retry_interval_ms = self.app_state.settings.get_global_retry_interval_ms();
+        let incoming_updates = self.incoming_updates.clone();
+        let delivery_events = self.delivery_event_sender();
+        let retry_interval = self.retry_interval.clone();
+        let retry_wakeup = self.retry_wakeup.clone();
+        let retry_status = self.retry_status.clone();
+        let relay = self.transport.relay();
+        let keypair = self.keypair.clone();
+        let my_address = self.local_ip.clone();
+        let cert_fingerprint = self.tls_fingerprint.clone();
+        let reports = self.error_reports.clone();
 
         let handle = std::thread::spawn(move || {
             let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
             rt.block_on(async move {
                 // Create queue instance for this worker thread
-                let mut queue = match MessageQueue::new_with_path(&queue_path) {
+                let mut queue = match MessageQueue::new_for_identity(&queue_path, &keypair) {
                     Ok(q) => q,
                     Err(e) => {
-                        tracing::error!("Retry worker: Failed to create queue: {}", e);
+                        reports.report(ErrorSeverity::Error, "retry worker", format!("Failed to open the message queue, retries stopped: {}", e));
                         return;
                     }
                 };
@@ -1144,7 +6402,7 @@ impl App {
                     match Storage::new_in_memory() {
                         Ok(s) => s,
                         Err(e) => {
-                            tracing::error!("Retry worker: Failed to create storage: {}", e);
+                            reports.report(ErrorSeverity::Error, "retry worker", format!("Failed to open storage, retries stopped: {}", e));
                             return;
                         }
                     }
@@ -1152,23 +6410,34 @@ impl App {
                     match Storage::new_with_default_path() {
                         Ok(s) => s,
                         Err(e) => {
-                            tracing::error!("Retry worker: Failed to create storage: {}", e);
+                            reports.report(ErrorSeverity::Error, "retry worker", format!("Failed to open storage, retries stopped: {}", e));
                             return;
                         }
                     }
                 };
 
-                tracing::info!("Retry worker started with {}ms interval", retry_interval_ms);
+                tracing::info!(
+                    "Retry worker started with {}ms interval",
+                    retry_interval.load(std::sync::atomic::Ordering::Relaxed)
+                );
 
                 // PHASE 1: Startup - immediately retry ALL pending messages
                 tracing::info!("Retry worker: Starting initial retry of all pending messages");
                 match queue.fetch_all_pending() {
                     Ok(pending_messages) => {
+                        let pending_messages = crate::queue::select_retry_lanes(
+                            pending_messages,
+                            Self::bulk_lane_paused(&storage),
+                        );
+                        // Everything live is attempted, so nothing holds a contact back
+                        let pending_messages = crate::queue::order_per_contact(pending_messages, &std::collections::HashMap::new());
                         let count = pending_messages.len();
                         if count > 0 {
                             tracing::info!("Retry worker: Found {} pending messages to retry on startup", count);
                             let mut succeeded = 0;
                             let mut failed = 0;
+                            // Contacts whose bulk message just failed: later ones wait for it
+                            let mut stalled = std::collections::HashSet::new();
 
                             for queued_msg in pending_messages {
                                 if stop_flag.load(std::sync::atomic::Ordering::Relaxed) {
@@ -1178,6 +6447,9 @@ impl App {
 
                                 let message_id = queued_msg.message.id.clone();
                                 let target_uid = queued_msg.message.recipient.clone();
+                                if !queued_msg.priority.is_interactive() && stalled.contains(&target_uid) {
+                                    continue;
+                                }
 
                                 // Get message type from queue (need to query separately)
                                 let message_type: String = match queue.conn.query_row(
@@ -1195,7 +6467,7 @@ impl App {
                                 // Get contact info from storage
                                 let contact = match AppState::load_from_db(&storage) {
                                     Ok(app_state) => {
-                                        app_state.contacts.iter().find(|c| c.uid == target_uid).cloned()
+                                        app_state.contact_by_uid(&target_uid).cloned()
                                     }
                                     Err(e) => {
                                         tracing::error!("Failed to load app state for message {}: {}", message_id, e);
@@ -1207,7 +6479,9 @@ impl App {
                                     Some(c) => c,
                                     None => {
                                         tracing::warn!("Contact {} not found for message {}, marking as failed", target_uid, message_id);
-                                        let _ = queue.mark_failed(&message_id);
+                                        reports.check(ErrorSeverity::Warning, "message queue", queue.record_error(&message_id, CONTACT_NOT_FOUND_ERROR));
+                                        reports.check(ErrorSeverity::Warning, "message queue", queue.mark_failed(&message_id));
+                                        Self::publish_if_dropped(&delivery_events, &queue, &message_id, &target_uid);
                                         failed += 1;
                                         continue;
                                     }
@@ -1215,42 +6489,38 @@ impl App {
 
                                 // Attempt delivery based on message type
                                 let result = if message_type == "ping" {
-                                    // For ping, content is the contact token
-                                    let token = String::from_utf8_lossy(&queued_msg.message.content).to_string();
-                                    transport.send_ping(&contact, &token).await
+                                    // For ping, our token is made now (renewed if refused as too short-lived)
+                                    Self::send_queued_ping(&transports, &storage, &keypair, &my_address, cert_fingerprint.as_deref(), &contact, &queued_msg).await
                                         .map(|ping_response| {
                                             tracing::info!("Retry worker: Ping successful, response: {}", ping_response.status);
-                                            Some(ping_response)
+                                            (Some(ping_response), None)
                                         })
                                         .map_err(|e| crate::Error::Transport(e.to_string()))
                                 } else {
-                                    // For text messages, send normally
-                                    transport.send_message(
-                                        &contact,
-                                        &queued_msg.message.sender,
-                                        "text",
-                                        queued_msg.message.content.clone(),
-                                    ).await
-                                        .map(|_| None)
-                                        .map_err(|e| crate::Error::Transport(e.to_string()))
+                                    // For text messages, seal for the contact and send
+                                    Self::send_queued_text(&transports, &keypair, &contact, &queued_msg, &message_type).await
+                                        .map(|security| (None, security.protection()))
                                 };
 
                                 match result {
-                                    Ok(ping_response_opt) => {
+                                    Ok((ping_response_opt, protection)) => {
                                         if let Err(e) = queue.mark_success(&message_id) {
-                                            tracing::error!("Failed to mark message {} as delivered: {}", message_id, e);
+                                            reports.report(ErrorSeverity::Warning, "message queue", format!("Failed to mark message {} as delivered: {}", message_id, e));
                                         } else {
                                             succeeded += 1;
                                             tracing::info!("Retry worker: {} delivered successfully to {}", message_type, target_uid);
+                                            Self::publish_delivered(&delivery_events, &queue, &message_id, &target_uid, ping_response_opt.is_some(), protection);
 
                                             // If this was a successful ping, mark chat as active and clear pending
                                             if message_type == "ping" && ping_response_opt.is_some() {
                                                 if let Ok(mut app_state) = AppState::load_from_db(&storage) {
-                                                    if let Some(chat) = app_state.chats.iter_mut().find(|c| c.contact_uid == target_uid) {
+                                                    if let Some(chat) = app_state.chat_by_uid_mut(&target_uid) {
                                                         chat.mark_unread(); // Mark as active
                                                         chat.mark_no_pending(); // Clear pending flag since ping succeeded
                                                         tracing::info!("Retry worker: Marked chat with {} as active after successful ping", target_uid);
-                                                        let _ = app_state.save_to_db(&storage);
+                                                        if reports.check(ErrorSeverity::Error, "retry worker", app_state.save_to_db(&storage)).is_some() {
+                                                            incoming_updates.store(true, std::sync::atomic::Ordering::SeqCst);
+                                                        }
                                                     }
                                                 }
                                             }
@@ -1258,9 +6528,16 @@ impl App {
                                     }
                                     Err(e) => {
                                         tracing::warn!("Retry worker: Failed to deliver {} to {}: {}", message_type, target_uid, e);
-                                        if let Err(e) = queue.mark_failed(&message_id) {
-                                            tracing::error!("Failed to update retry status for {}: {}", message_id, e);
+                                        if Self::relay_fallback(&transports, &mut queue, &storage, &keypair, &queued_msg, &message_type).await {
+                                            succeeded += 1;
+                                            Self::publish_delivered(&delivery_events, &queue, &message_id, &target_uid, false, None);
+                                            incoming_updates.store(true, std::sync::atomic::Ordering::SeqCst);
+                                            continue;
                                         }
+                                        reports.check(ErrorSeverity::Warning, "message queue", queue.record_error(&message_id, &e.to_string()));
+                                        reports.check(ErrorSeverity::Warning, "message queue", queue.mark_failed(&message_id));
+                                        Self::publish_if_dropped(&delivery_events, &queue, &message_id, &target_uid);
+                                        stalled.insert(target_uid);
                                         failed += 1;
                                     }
                                 }
@@ -1272,32 +6549,80 @@ impl App {
                         }
                     }
                     Err(e) => {
-                        tracing::error!("Retry worker: Failed to fetch pending messages: {}", e);
+                        reports.report(ErrorSeverity::Warning, "retry worker", format!("Failed to fetch pending messages: {}", e));
                     }
                 }
 
                 // PHASE 2: Periodic retry loop
                 tracing::info!("Retry worker: Entering periodic retry loop");
 
+                let mut processed = 0;
                 loop {
-                    // Sleep for retry interval (check stop flag every 100ms)
-                    let sleep_iterations = (retry_interval_ms / 100).max(1);
-                    for _ in 0..sleep_iterations {
-                        if stop_flag.load(std::sync::atomic::Ordering::Relaxed) {
-                            tracing::info!("Retry worker: Stop signal received, exiting");
-                            return;
+                    // Wait until the earliest retry is due, the interval runs out,
+                    // or something wakes us (see retry_schedule)
+                    let now = chrono::Utc::now();
+                    let interval = chrono::Duration::milliseconds(
+                        retry_interval.load(std::sync::atomic::Ordering::Relaxed) as i64,
+                    );
+                    let earliest = match queue.earliest_next_retry() {
+                        Ok(earliest) => earliest.and_then(chrono::DateTime::from_timestamp_millis),
+                        Err(e) => {
+                            reports.report(ErrorSeverity::Warning, "retry worker", format!("Failed to read the retry schedule: {}", e));
+                            Some(now + interval)
+                        }
+                    };
+                    let has_deferred = queue.count_dormant().is_ok_and(|n| n > 0)
+                        || relay.lock().unwrap().total_queued() > 0;
+                    let plan = plan_next_cycle(now, interval, earliest, has_deferred, processed);
+                    retry_status.set(plan);
+                    if plan.mode != RetryMode::Backlog {
+                        retry_wakeup.wait(plan.wait_from(now)).await;
+                    }
+                    if stop_flag.load(std::sync::atomic::Ordering::Relaxed) {
+                        tracing::info!("Retry worker: Stop signal received, exiting");
+                        return;
+                    }
+                    processed = 0;
+
+                    // Pass on anything held for other contacts while acting as a relay
+                    let relayed = crate::relay::forward_queued(&relay, &transports).await;
+                    if relayed > 0 {
+                        tracing::info!("Retry worker: Forwarded {} relayed message(s)", relayed);
+                    }
+
+                    // Messages that waited too long for their contact finally fail
+                    match queue.expire_dormant() {
+                        Ok(targets) => {
+                            for target_uid in targets {
+                                tracing::warn!("Retry worker: Dormant messages to {} expired", target_uid);
+                                let _ = delivery_events.send(DeliveryEvent::from_queue(&queue, &target_uid, DeliveryUpdate::Failed));
+                            }
                         }
-                        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                        Err(e) => reports.report(ErrorSeverity::Warning, "retry worker", format!("Failed to expire dormant messages: {}", e)),
                     }
 
                     // Fetch messages that are ready for retry (next_retry <= now)
                     match queue.fetch_pending() {
                         Ok(ready_messages) => {
+                            // Bulk lane pauses during quiet hours; interactive lane keeps running
+                            let ready_messages = crate::queue::select_retry_lanes(
+                                ready_messages,
+                                Self::bulk_lane_paused(&storage),
+                            );
+                            // Each contact's messages go oldest first, none past one still waiting
+                            let waiting = queue.oldest_waiting_at(chrono::Utc::now().timestamp_millis()).unwrap_or_else(|e| {
+                                tracing::warn!("Retry worker: Failed to read waiting messages: {}", e);
+                                std::collections::HashMap::new()
+                            });
+                            let ready_messages = crate::queue::order_per_contact(ready_messages, &waiting);
+                            let ready_messages: Vec<_> = ready_messages.into_iter().take(RETRY_BATCH_SIZE).collect();
+                            processed = ready_messages.len();
                             if ready_messages.is_empty() {
                                 continue;
                             }
 
                             tracing::info!("Retry worker: Processing {} messages ready for retry", ready_messages.len());
+                            let mut stalled = std::collections::HashSet::new();
 
                             for queued_msg in ready_messages {
                                 if stop_flag.load(std::sync::atomic::Ordering::Relaxed) {
@@ -1307,6 +6632,9 @@ impl App {
 
                                 let message_id = queued_msg.message.id.clone();
                                 let target_uid = queued_msg.message.recipient.clone();
+                                if !queued_msg.priority.is_interactive() && stalled.contains(&target_uid) {
+                                    continue;
+                                }
 
                                 // Get message type
                                 let message_type: String = match queue.conn.query_row(
@@ -1324,7 +6652,7 @@ impl App {
                                 // Get contact info
                                 let contact = match AppState::load_from_db(&storage) {
                                     Ok(app_state) => {
-                                        app_state.contacts.iter().find(|c| c.uid == target_uid).cloned()
+                                        app_state.contact_by_uid(&target_uid).cloned()
                                     }
                                     Err(e) => {
                                         tracing::error!("Failed to load app state: {}", e);
@@ -1336,45 +6664,43 @@ impl App {
                                     Some(c) => c,
                                     None => {
                                         tracing::warn!("Contact {} not found, marking message as failed", target_uid);
-                                        let _ = queue.mark_failed(&message_id);
+                                        reports.check(ErrorSeverity::Warning, "message queue", queue.record_error(&message_id, CONTACT_NOT_FOUND_ERROR));
+                                        reports.check(ErrorSeverity::Warning, "message queue", queue.mark_failed(&message_id));
+                                        Self::publish_if_dropped(&delivery_events, &queue, &message_id, &target_uid);
                                         continue;
                                     }
                                 };
 
                                 // Attempt delivery
                                 let result = if message_type == "ping" {
-                                    let token = String::from_utf8_lossy(&queued_msg.message.content).to_string();
-                                    transport.send_ping(&contact, &token).await
+                                    Self::send_queued_ping(&transports, &storage, &keypair, &my_address, cert_fingerprint.as_deref(), &contact, &queued_msg).await
                                         .map(|ping_response| {
                                             tracing::info!("Retry worker (periodic): Ping successful, response: {}", ping_response.status);
-                                            Some(ping_response)
+                                            (Some(ping_response), None)
                                         })
                                         .map_err(|e| crate::Error::Transport(e.to_string()))
                                 } else {
-                                    transport.send_message(
-                                        &contact,
-                                        &queued_msg.message.sender,
-                                        "text",
-                                        queued_msg.message.content.clone(),
-                                    ).await
-                                        .map(|_| None)
-                                        .map_err(|e| crate::Error::Transport(e.to_string()))
+                                    Self::send_queued_text(&transports, &keypair, &contact, &queued_msg, &message_type).await
+                                        .map(|security| (None, security.protection()))
                                 };
 
                                 match result {
-                                    Ok(ping_response_opt) => {
+                                    Ok((ping_response_opt, protection)) => {
                                         if let Err(e) = queue.mark_success(&message_id) {
-                                            tracing::error!("Failed to mark message as delivered: {}", e);
+                                            reports.report(ErrorSeverity::Warning, "message queue", format!("Failed to mark message {} as delivered: {}", message_id, e));
                                         } else {
                                             tracing::info!("Retry worker (periodic): {} delivered to {}", message_type, target_uid);
+                                            Self::publish_delivered(&delivery_events, &queue, &message_id, &target_uid, ping_response_opt.is_some(), protection);
 
                                             // If this was a successful ping, mark chat as active
                                             if message_type == "ping" && ping_response_opt.is_some() {
                                                 if let Ok(mut app_state) = AppState::load_from_db(&storage) {
-                                                    if let Some(chat) = app_state.chats.iter_mut().find(|c| c.contact_uid == target_uid) {
+                                                    if let Some(chat) = app_state.chat_by_uid_mut(&target_uid) {
                                                         chat.mark_unread(); // Mark as active
                                                         tracing::info!("Retry worker (periodic): Marked chat with {} as active after successful ping", target_uid);
-                                                        let _ = app_state.save_to_db(&storage);
+                                                        if reports.check(ErrorSeverity::Error, "retry worker", app_state.save_to_db(&storage)).is_some() {
+                                                            incoming_updates.store(true, std::sync::atomic::Ordering::SeqCst);
+                                                        }
                                                     }
                                                 }
                                             }
@@ -1382,13 +6708,21 @@ impl App {
                                     }
                                     Err(e) => {
                                         tracing::warn!("Retry worker: Failed to deliver {} to {}: {}", message_type, target_uid, e);
-                                        let _ = queue.mark_failed(&message_id);
+                                        if Self::relay_fallback(&transports, &mut queue, &storage, &keypair, &queued_msg, &message_type).await {
+                                            Self::publish_delivered(&delivery_events, &queue, &message_id, &target_uid, false, None);
+                                            incoming_updates.store(true, std::sync::atomic::Ordering::SeqCst);
+                                            continue;
+                                        }
+                                        reports.check(ErrorSeverity::Warning, "message queue", queue.record_error(&message_id, &e.to_string()));
+                                        reports.check(ErrorSeverity::Warning, "message queue", queue.mark_failed(&message_id));
+                                        Self::publish_if_dropped(&delivery_events, &queue, &message_id, &target_uid);
+                                        stalled.insert(target_uid);
                                     }
                                 }
                             }
                         }
                         Err(e) => {
-                            tracing::error!("Retry worker: Failed to fetch ready messages: {}", e);
+                            reports.report(ErrorSeverity::Warning, "retry worker", format!("Failed to fetch ready messages: {}", e));
                         }
                     }
                 }
@@ -1400,6 +6734,169 @@ impl App {
         Ok(())
     }
 
+    /// Whether the retry worker's bulk lane is paused (quiet hours in effect)
+    fn bulk_lane_paused(storage: &Storage) -> bool {
+        storage
+            .load_settings()
+            .ok()
+            .flatten()
+            .map(|settings| crate::storage::is_quiet(&chrono::Local::now(), &settings))
+            .unwrap_or(false)
+    }
+
+    /// Send a queued introduction ping with our token as it is now
+    ///
+    /// The token is made from the address and validity stored at send time
+    /// (`OwnTokenSource::load`, `my_address` while none was detected), so a
+    /// ping queued before an address change carries the new address. Rows
+    /// queued by older versions hold a token instead of `FRESH_TOKEN_MARKER`;
+    /// it is never sent. A contact that already has our current token gets
+    /// an empty one.
+    pub(crate) async fn send_queued_ping(
+        transports: &TransportRegistry,
+        storage: &Storage,
+        keypair: &KeyPair,
+        my_address: &str,
+        cert_fingerprint: Option<&str>,
+        contact: &crate::storage::Contact,
+        queued_msg: &QueuedMessage,
+    ) -> crate::Result<crate::transport::PingResponse> {
+        if *queued_msg.message.content != *FRESH_TOKEN_MARKER {
+            tracing::debug!("Queued ping {} holds an old token; sending a fresh one", queued_msg.message.id);
+        }
+        let source = OwnTokenSource::load(storage, keypair, my_address, cert_fingerprint)?;
+        let token = source
+            .ping_token(storage, contact, Utc::now())?
+            .ok_or_else(|| crate::Error::Transport("restricted contact, no public address to share".to_string()))?;
+        ping_renewing_token(transports, contact, token, || source.token_for(contact))
+            .await
+            .0
+            .map_err(|e| crate::Error::Transport(e.to_string()))
+    }
+
+    /// Send a queued text message (or edit), sealed for the contact when its key is known
+    ///
+    /// Sealing happens at send time, so a key filled by an upgrade while the
+    /// message waited is used. Token refresh requests go out as they are.
+    /// Returns how the message was protected.
+    async fn send_queued_text(
+        transports: &TransportRegistry,
+        keypair: &KeyPair,
+        contact: &crate::storage::Contact,
+        queued_msg: &QueuedMessage,
+        message_type: &str,
+    ) -> crate::Result<SendSecurity> {
+        let message_type = match message_type {
+            EDIT_TYPE => EDIT_TYPE,
+            TOKEN_REFRESH_REQUEST_TYPE => TOKEN_REFRESH_REQUEST_TYPE,
+            _ => "text",
+        };
+        let request = MessageRequest {
+            from_uid: queued_msg.message.sender.clone(),
+            message_type: message_type.to_string(),
+            payload: queued_msg.message.content.to_vec(),
+            metadata: queued_msg.message.metadata.clone(),
+            message_id: Some(queued_msg.message.id.clone()),
+        };
+        let (request, security) = seal_request(request, Some(keypair), contact)?;
+        transports
+            .send_message(contact, &request)
+            .await
+            .map_err(|e| crate::Error::Transport(e.to_string()))?;
+        Ok(security)
+    }
+
+    /// Hand a message to a relay once its direct attempts are used up
+    ///
+    /// Runs only for the attempt that would otherwise drop the message. On
+    /// success the message is marked "delivered via relay" in its chat.
+    ///
+    /// # Returns
+    /// Whether a relay took the message (it has left the queue)
+    async fn relay_fallback(
+        transports: &TransportRegistry,
+        queue: &mut MessageQueue,
+        storage: &Storage,
+        keypair: &KeyPair,
+        queued_msg: &QueuedMessage,
+        message_type: &str,
+    ) -> bool {
+        let control = message_type == "ping" || message_type == TOKEN_REFRESH_REQUEST_TYPE;
+        if control || queued_msg.attempts + 1 < queue.max_retries {
+            return false;
+        }
+        let Ok(mut app_state) = AppState::load_from_db(storage) else {
+            return false;
+        };
+
+        // The worker sends every non-ping message as "text", except edits
+        let relay_type = if message_type == EDIT_TYPE { EDIT_TYPE } else { "text" };
+        let relay_uid = match crate::messaging::deliver_via_relay(
+            transports,
+            queue,
+            Some(keypair),
+            &app_state.contacts,
+            &queued_msg.message,
+            relay_type,
+        )
+        .await
+        {
+            Ok(Some(relay_uid)) => relay_uid,
+            Ok(None) => return false,
+            Err(e) => {
+                tracing::error!("Retry worker: Relay fallback for {} failed: {}", queued_msg.message.id, e);
+                return false;
+            }
+        };
+
+        if let Some(message) = app_state
+            .get_chat_mut(&queued_msg.message.recipient)
+            .and_then(|chat| chat.messages_mut().iter_mut().find(|m| m.id == queued_msg.message.id))
+        {
+            message.mark_delivered_via_relay(&relay_uid);
+            if let Err(e) = app_state.save_to_db(storage) {
+                tracing::error!("Retry worker: Failed to record relayed delivery: {}", e);
+            }
+        }
+        true
+    }
+
+    /// Publish a delivery by the retry worker (`ping` for answered pings)
+    ///
+    /// `protection` is how the worker sealed the message (None when a relay
+    /// carried it, or for pings).
+    fn publish_delivered(
+        events: &std::sync::mpsc::Sender<DeliveryEvent>,
+        queue: &MessageQueue,
+        message_id: &str,
+        target_uid: &str,
+        ping: bool,
+        protection: Option<Protection>,
+    ) {
+        let event = if ping {
+            DeliveryEvent::from_queue(queue, target_uid, DeliveryUpdate::PingAnswered)
+        } else {
+            DeliveryEvent::from_queue(queue, target_uid, DeliveryUpdate::Delivered)
+                .with_message_id(message_id)
+                .with_protection(protection)
+        };
+        let _ = events.send(event);
+    }
+
+    /// Publish a failed attempt if it made the queue drop the message
+    ///
+    /// Attempts that were merely rescheduled leave the chat pending.
+    fn publish_if_dropped(
+        events: &std::sync::mpsc::Sender<DeliveryEvent>,
+        queue: &MessageQueue,
+        message_id: &str,
+        target_uid: &str,
+    ) {
+        if !queue.contains(message_id).unwrap_or(true) {
+            let _ = events.send(DeliveryEvent::from_queue(queue, target_uid, DeliveryUpdate::Failed));
+        }
+    }
+
     /// Stop the background retry worker
     ///
     /// Signals the worker to stop and waits for it to finish gracefully.
@@ -1407,6 +6904,7 @@ impl App {
         if self.retry_worker_handle.is_some() {
             tracing::info!("Stopping retry worker...");
             self.retry_worker_stop.store(true, std::sync::atomic::Ordering::Relaxed);
+            self.retry_wakeup.notify();
 
             if let Some(handle) = self.retry_worker_handle.take() {
                 // Wait for worker to finish (with timeout)
@@ -1467,5 +6965,12 @@ impl Drop for App {
     fn drop(&mut self) {
         // Ensure retry worker is stopped when app is dropped
         self.stop_retry_worker();
+        // Tooling must not find a port nobody listens on any more (an inspected copy is left as it is)
+        if self.inspection.is_some() {
+            return;
+        }
+        if let Err(e) = remove_runtime_info(&self.runtime_info_path, std::process::id()) {
+            tracing::warn!("Failed to remove {}: {}", self.runtime_info_path.display(), e);
+        }
     }
 }
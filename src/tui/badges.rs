@@ -0,0 +1,140 @@
+//! Unread/pending badges and the terminal title
+//!
+//! A single summary of the chat list drives the badge on the main menu's
+//! Chat List entry, the chat list header and the terminal title, so the three
+//! never disagree.
+
+use crate::storage::Chat;
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two terminal title updates
+pub const TITLE_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Terminal title shown when nothing needs attention
+pub const APP_TITLE: &str = "Pure2P";
+
+/// Aggregate unread/pending counts across chats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChatSummary {
+    /// Chats with unread messages
+    pub unread: usize,
+    /// Chats with messages still queued for delivery
+    pub pending: usize,
+}
+
+impl ChatSummary {
+    /// Summarize a list of chats
+    ///
+    /// Every chat is counted: there is no archived or muted state to exclude.
+    pub fn from_chats(chats: &[Chat]) -> Self {
+        Self {
+            unread: chats.iter().filter(|c| c.is_active).count(),
+            pending: chats.iter().filter(|c| c.has_pending()).count(),
+        }
+    }
+
+    /// Badge text such as "3 unread, 1 pending", or `None` when both are zero
+    pub fn badge(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if self.unread > 0 {
+            parts.push(format!("{} unread", self.unread));
+        }
+        if self.pending > 0 {
+            parts.push(format!("{} pending", self.pending));
+        }
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(", "))
+        }
+    }
+
+    /// Label for the main menu's Chat List entry
+    pub fn menu_label(&self) -> String {
+        match self.badge() {
+            Some(badge) => format!("Chat List ({})", badge),
+            None => "Chat List".to_string(),
+        }
+    }
+
+    /// Terminal title such as "Pure2P — 3 unread"
+    pub fn terminal_title(&self) -> String {
+        match self.badge() {
+            Some(badge) => format!("{} — {}", APP_TITLE, badge),
+            None => APP_TITLE.to_string(),
+        }
+    }
+}
+
+/// Build the OSC 0 escape sequence that sets the terminal (icon and window) title
+///
+/// Control characters are dropped so a title can never terminate the sequence early.
+pub fn title_escape(title: &str) -> String {
+    let clean: String = title.chars().filter(|c| !c.is_control()).collect();
+    format!("\x1b]0;{}\x07", clean)
+}
+
+/// Rate-limited terminal title updates
+///
+/// Produces escape sequences for the caller to write; nothing is produced
+/// when stdout is not attached to a terminal.
+#[derive(Debug)]
+pub struct TerminalTitle {
+    /// Whether escape sequences may be emitted
+    enabled: bool,
+    /// Title currently shown by the terminal
+    shown: Option<String>,
+    /// When the title was last changed
+    last_update: Option<Instant>,
+}
+
+impl TerminalTitle {
+    /// Create a title updater; `enabled` should be false when not on a TTY
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            shown: None,
+            last_update: None,
+        }
+    }
+
+    /// Create a title updater enabled only if stdout is a terminal
+    pub fn for_stdout() -> Self {
+        Self::new(std::io::stdout().is_terminal())
+    }
+
+    /// Whether escape sequences may be emitted
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Request `title`, returning the escape sequence to write if it should change now
+    ///
+    /// Called every frame. Unchanged titles produce nothing, and a change
+    /// within `TITLE_UPDATE_INTERVAL` of the previous one is held back until
+    /// the interval has elapsed.
+    pub fn update(&mut self, title: &str, now: Instant) -> Option<String> {
+        if !self.enabled || self.shown.as_deref() == Some(title) {
+            return None;
+        }
+        if self
+            .last_update
+            .is_some_and(|last| now.saturating_duration_since(last) < TITLE_UPDATE_INTERVAL)
+        {
+            return None;
+        }
+
+        self.shown = Some(title.to_string());
+        self.last_update = Some(now);
+        Some(title_escape(title))
+    }
+
+    /// Escape sequence resetting the title on exit, if one was ever set
+    pub fn clear(&mut self) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        self.shown.take().map(|_| title_escape(""))
+    }
+}
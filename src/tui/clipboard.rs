@@ -1,6 +1,8 @@
 //! Clipboard abstraction for testing
 //!
 //! Provides a trait-based interface to clipboard operations that can be mocked in tests.
+//! The system clipboard needs the `clipboard` feature; without it copying
+//! fails like an unavailable clipboard and the screens offer saving to a file.
 
 use std::fmt;
 
@@ -37,10 +39,12 @@ pub trait ClipboardProvider {
 }
 
 /// Real clipboard implementation using arboard
+#[cfg(feature = "clipboard")]
 pub struct RealClipboard {
     inner: arboard::Clipboard,
 }
 
+#[cfg(feature = "clipboard")]
 impl RealClipboard {
     /// Create new real clipboard instance
     pub fn new() -> ClipboardResult<Self> {
@@ -51,6 +55,7 @@ impl RealClipboard {
     }
 }
 
+#[cfg(feature = "clipboard")]
 impl ClipboardProvider for RealClipboard {
     fn set_text(&mut self, text: &str) -> ClipboardResult<()> {
         self.inner
@@ -65,6 +70,29 @@ impl ClipboardProvider for RealClipboard {
     }
 }
 
+/// Without the `clipboard` feature there is no system clipboard
+#[cfg(not(feature = "clipboard"))]
+pub struct RealClipboard;
+
+#[cfg(not(feature = "clipboard"))]
+impl RealClipboard {
+    /// Always fails: clipboard support is not compiled in
+    pub fn new() -> ClipboardResult<Self> {
+        Err(ClipboardError::InitFailed("clipboard support is not compiled in".to_string()))
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+impl ClipboardProvider for RealClipboard {
+    fn set_text(&mut self, _text: &str) -> ClipboardResult<()> {
+        Err(ClipboardError::OperationFailed("clipboard support is not compiled in".to_string()))
+    }
+
+    fn get_text(&mut self) -> ClipboardResult<String> {
+        Err(ClipboardError::OperationFailed("clipboard support is not compiled in".to_string()))
+    }
+}
+
 #[cfg(test)]
 /// Mock clipboard implementation for testing
 pub mod mock {
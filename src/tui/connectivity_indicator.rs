@@ -0,0 +1,91 @@
+//! Connectivity segment shown in the footer of every screen
+//!
+//! `ConnectivityIndicator::derive` condenses the transport server status and
+//! the latest connectivity result (including the external reachability
+//! verdict of the health check) into one state. It is a pure function: the
+//! app stores its output and recomputes it only when one of the inputs
+//! changes (see `App::refresh_connectivity_indicator`), so rendering a frame
+//! never re-derives it.
+
+use crate::connectivity::{ConnectivityResult, MappingProtocol};
+use crate::tui::app::TransportServerStatus;
+use crate::tui::diagnostics_actions::protocol_name;
+
+/// Why the app is running but cannot be fully reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitReason {
+    /// The health check found the mapped port unreachable from outside
+    NoInbound,
+    /// The external address is behind carrier-grade NAT
+    Cgnat,
+    /// No port mapping or external address was found
+    NoMapping,
+}
+
+impl LimitReason {
+    /// Short description for the footer
+    pub fn describe(self) -> &'static str {
+        match self {
+            LimitReason::NoInbound => "no inbound",
+            LimitReason::Cgnat => "CGNAT",
+            LimitReason::NoMapping => "no mapping",
+        }
+    }
+}
+
+/// Overall connectivity, as shown in the footer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectivityIndicator {
+    /// The transport server has not started yet
+    Starting,
+    /// The server runs; connectivity detection has not finished
+    Checking,
+    /// Reachable through the given mapping
+    Online(MappingProtocol),
+    /// Running but not (verifiably) reachable from outside
+    Limited(LimitReason),
+    /// The transport server failed, nothing can be received
+    Offline,
+}
+
+impl ConnectivityIndicator {
+    /// Derive the indicator from its inputs
+    ///
+    /// # Arguments
+    /// * `transport` - Transport server status
+    /// * `result` - Latest connectivity result, if detection has finished
+    pub fn derive(transport: &TransportServerStatus, result: Option<&ConnectivityResult>) -> Self {
+        match transport {
+            TransportServerStatus::NotStarted | TransportServerStatus::Starting => ConnectivityIndicator::Starting,
+            TransportServerStatus::Failed(_) | TransportServerStatus::Hijacked(_) | TransportServerStatus::Lost(_) => {
+                ConnectivityIndicator::Offline
+            }
+            TransportServerStatus::Running(_) => {
+                let Some(result) = result else {
+                    return ConnectivityIndicator::Checking;
+                };
+                let verified = result.externally_reachable;
+                match &result.mapping {
+                    _ if verified == Some(false) => ConnectivityIndicator::Limited(LimitReason::NoInbound),
+                    None if result.cgnat_detected => ConnectivityIndicator::Limited(LimitReason::Cgnat),
+                    None => ConnectivityIndicator::Limited(LimitReason::NoMapping),
+                    Some(_) if result.cgnat_detected && verified != Some(true) => {
+                        ConnectivityIndicator::Limited(LimitReason::Cgnat)
+                    }
+                    Some(mapping) => ConnectivityIndicator::Online(mapping.protocol),
+                }
+            }
+        }
+    }
+
+    /// Footer text, e.g. "● online (UPnP)"
+    pub fn label(&self) -> String {
+        match self {
+            ConnectivityIndicator::Starting => "◌ starting".to_string(),
+            ConnectivityIndicator::Checking => "◌ checking".to_string(),
+            ConnectivityIndicator::Online(protocol) => format!("● online ({})", protocol_name(*protocol)),
+            ConnectivityIndicator::Limited(reason) => format!("◐ limited ({})", reason.describe()),
+            ConnectivityIndicator::Offline => "○ offline".to_string(),
+        }
+    }
+}
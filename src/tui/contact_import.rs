@@ -0,0 +1,574 @@
+//! Introduction pings for imported contacts and batch token import
+//!
+//! Every imported contact gets an introduction ping carrying our token,
+//! sent by `PingDispatcher`: an answered ping marks the chat active, an
+//! unanswered one is queued as an urgent `ping` for the retry worker. A ping
+//! refused because our token expires too soon (see `storage::bounds`) is
+//! sent once more with a fresh token (`ping_renewing_token`), here and in
+//! the retry worker.
+//!
+//! The token is made when the ping is sent, never when it is queued: a
+//! queued ping holds `FRESH_TOKEN_MARKER`, and the retry worker makes the
+//! token from the address and validity stored at that moment
+//! (`OwnTokenSource::load`). Tokens last `Settings::own_token_validity()`.
+//! A contact that sent us authenticated traffic since our token last changed
+//! already has it (`Contact::has_current_token`), so its pings carry an
+//! empty token and only check that it is reachable.
+//!
+//! Batch mode of the Import screen takes many tokens at once, pasted or
+//! read from a file, one per line; blank lines and `#` comments are skipped,
+//! and text before the token on its line is kept as a name guess (e.g.
+//! "Alice: <token>"). `parse_token_batch` checks each token on its own and
+//! classifies it for the review table. Only entries still selected when the
+//! batch is confirmed are imported, in one pass; a failing entry never stops
+//! the rest, and the ping of each imported contact is reported back to the
+//! batch as it completes.
+//!
+//! A name guess that matches a pending introduction (`suggest_batch_links`)
+//! links the imported contact to it, as does the name typed before a single
+//! token: the introduction's name and notes move to the contact's notes.
+
+use crate::crypto::KeyPair;
+use crate::queue::{MessageQueue, Priority};
+use crate::storage::{
+    best_match, own_token_for, own_token_inputs, parse_contact_token_any_expiry, AppState, Contact, ErrorSeverity, Message,
+    PendingIntroduction, Storage,
+};
+use crate::transport::{PeerTransport, PingResponse, TransportRegistry};
+use crate::tui::delivery_events::{DeliveryEvent, DeliveryUpdate};
+use crate::tui::error_reports::ErrorReporter;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// Why an imported token did not add a new contact
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportRefusal {
+    /// The token is our own
+    OwnToken,
+    /// Known contact; its address was updated
+    AddressUpdated,
+    /// Known verified contact; its new address was staged for review
+    AddressStaged,
+    /// Known contact at the same address; its token now expires later
+    Renewed,
+    /// Known contact, nothing changed
+    Exists,
+    /// The identity conflicts with a stored contact and is held for review
+    Conflict {
+        /// Kind of conflict
+        kind: String,
+        /// Stored contact it conflicts with
+        existing_uid: String,
+    },
+    /// Adding failed
+    Failed(String),
+}
+
+impl ImportRefusal {
+    /// Status message of the Import screen
+    pub fn message(&self) -> String {
+        match self {
+            ImportRefusal::OwnToken => "Error: Cannot import your own contact token".to_string(),
+            ImportRefusal::AddressUpdated => "✓ Contact address updated".to_string(),
+            ImportRefusal::AddressStaged => {
+                "Verified contact's new address staged for review (see contact details)".to_string()
+            }
+            ImportRefusal::Renewed => "✓ Contact token renewed".to_string(),
+            ImportRefusal::Exists => "Contact already exists".to_string(),
+            ImportRefusal::Conflict { kind, existing_uid } => {
+                format!("Identity conflict ({}) with {}; held for review", kind, existing_uid)
+            }
+            ImportRefusal::Failed(e) => format!("Error: {}", e),
+        }
+    }
+
+    /// Whether the message is shown as an error
+    pub fn is_error(&self) -> bool {
+        !matches!(self, ImportRefusal::AddressUpdated | ImportRefusal::AddressStaged | ImportRefusal::Renewed)
+    }
+
+    /// Whether the contact is stored anyway (it was already known)
+    pub fn contact_known(&self) -> bool {
+        matches!(
+            self,
+            ImportRefusal::AddressUpdated | ImportRefusal::AddressStaged | ImportRefusal::Renewed | ImportRefusal::Exists
+        )
+    }
+}
+
+/// What became of an introduction ping
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PingDispatch {
+    /// Not finished yet
+    Pending,
+    /// The contact answered; its chat is active
+    Answered,
+    /// No answer; queued for the retry worker
+    Queued,
+    /// Neither answered nor queued
+    Failed(String),
+}
+
+impl PingDispatch {
+    /// Short label for the batch report
+    pub fn label(&self) -> String {
+        match self {
+            PingDispatch::Pending => "ping pending".to_string(),
+            PingDispatch::Answered => "ping answered".to_string(),
+            PingDispatch::Queued => "ping queued".to_string(),
+            PingDispatch::Failed(e) => format!("ping failed: {}", e),
+        }
+    }
+}
+
+/// Content of a queued ping: the token is made when it is sent
+pub const FRESH_TOKEN_MARKER: &[u8] = b"pure2p:fresh-token";
+
+/// What our token is made from, read when a ping is sent
+#[derive(Debug, Clone)]
+pub struct OwnTokenSource {
+    /// Our identity, which signs the token
+    pub keypair: KeyPair,
+    /// Our address, put in the token (see `storage::own_token_for`)
+    pub address: String,
+    /// Fingerprint of our TLS certificate, pinned by the token when set
+    pub cert_fingerprint: Option<String>,
+    /// How long the token stays valid (`Settings::own_token_validity()`)
+    pub validity: Duration,
+}
+
+impl OwnTokenSource {
+    /// Source with the address and validity stored in `storage` now
+    ///
+    /// `fallback_address` is used while no address has been detected.
+    pub fn load(
+        storage: &Storage,
+        keypair: &KeyPair,
+        fallback_address: &str,
+        cert_fingerprint: Option<&str>,
+    ) -> crate::Result<Self> {
+        let address = storage.load_user_identity()?.and_then(|(_, ip, _)| ip);
+        let settings = storage.load_settings()?.unwrap_or_default();
+        Ok(Self {
+            keypair: keypair.clone(),
+            address: address.unwrap_or_else(|| fallback_address.to_string()),
+            cert_fingerprint: cert_fingerprint.map(str::to_string),
+            validity: settings.own_token_validity(),
+        })
+    }
+
+    /// A fresh token for `contact`
+    ///
+    /// # Returns
+    /// None when `contact`'s `OutboundPolicy` withholds our address
+    pub fn token_for(&self, contact: &Contact) -> crate::Result<Option<String>> {
+        own_token_for(&self.keypair, &self.address, self.cert_fingerprint.as_deref(), self.validity, contact)
+    }
+
+    /// The token for a ping to `contact` sent at `now`
+    ///
+    /// Empty when the contact already has our current token; when our token
+    /// last changed is kept in `storage` (`Storage::own_token_changed_at`).
+    ///
+    /// # Returns
+    /// None when `contact`'s `OutboundPolicy` withholds our address
+    pub fn ping_token(&self, storage: &Storage, contact: &Contact, now: DateTime<Utc>) -> crate::Result<Option<String>> {
+        let inputs = own_token_inputs(&self.address, self.cert_fingerprint.as_deref(), self.validity);
+        if contact.has_current_token(storage.own_token_changed_at(&inputs, now)?) {
+            tracing::debug!("{} already has our current token; pinging without it", contact.uid);
+            return Ok(Some(String::new()));
+        }
+        self.token_for(contact)
+    }
+}
+
+/// Ping `contact` with `token`, and once more with a fresh token if the
+/// first is refused as too short-lived
+///
+/// `fresh_token` is only called after that refusal; if it cannot make a
+/// token, the refusal stands.
+///
+/// # Returns
+/// The ping's result and the token it last carried
+pub async fn ping_renewing_token<F>(
+    transports: &TransportRegistry,
+    contact: &Contact,
+    token: String,
+    fresh_token: F,
+) -> (crate::Result<PingResponse>, String)
+where
+    F: FnOnce() -> crate::Result<Option<String>>,
+{
+    let refusal = match transports.send_ping(contact, &token).await {
+        Err(e @ crate::Error::TokenTooShortLived(_)) => e,
+        result => return (result, token),
+    };
+    match fresh_token() {
+        Ok(Some(fresh)) => {
+            tracing::info!("{} refused our token as too short-lived; retrying with a fresh one", contact.uid);
+            (transports.send_ping(contact, &fresh).await, fresh)
+        }
+        Ok(None) | Err(_) => (Err(refusal), token),
+    }
+}
+
+/// Sends introduction pings to imported contacts
+#[derive(Clone)]
+pub struct PingDispatcher {
+    /// Carriers to ping over
+    pub transports: TransportRegistry,
+    /// Database the chat's active flag is saved to
+    pub storage: Storage,
+    /// Our identity (sender of queued pings, queue sealing key)
+    pub keypair: KeyPair,
+    /// What the token sent in each ping is made from
+    pub token_source: OwnTokenSource,
+    /// Where answered and queued pings are published
+    pub delivery_events: std::sync::mpsc::Sender<DeliveryEvent>,
+    /// Where failures to save or queue are reported
+    pub reports: ErrorReporter,
+}
+
+impl PingDispatcher {
+    /// Ping `contact`, queueing the ping if it is not answered
+    ///
+    /// A restricted contact is not pinged while we only know a LAN address:
+    /// the token would have to carry it.
+    pub async fn introduce(&self, contact: &Contact) -> PingDispatch {
+        let my_token = match self.token_source.ping_token(&self.storage, contact, Utc::now()) {
+            Ok(Some(token)) => token,
+            Ok(None) => return PingDispatch::Failed("restricted contact, no public address to share".to_string()),
+            Err(e) => return PingDispatch::Failed(format!("no token: {}", e)),
+        };
+        let (result, _) = ping_renewing_token(&self.transports, contact, my_token, || self.token_source.token_for(contact)).await;
+        match result {
+            Ok(ping_response) => {
+                tracing::info!("Successfully pinged newly imported contact {} (response: {})", contact.uid, ping_response.status);
+                // Ping succeeded - mark chat as active and clear pending status
+                if let Some(mut app_state) =
+                    self.reports.check(ErrorSeverity::Error, "contact import", AppState::load_from_db(&self.storage))
+                {
+                    if let Some(chat) = app_state.chat_by_uid_mut(&contact.uid) {
+                        chat.mark_unread(); // Mark as active
+                        chat.mark_no_pending(); // Clear pending flag since ping succeeded
+                        tracing::info!("Marked chat with {} as active after successful ping", contact.uid);
+                    }
+                    self.reports.check(ErrorSeverity::Error, "contact import", app_state.save_to_db(&self.storage));
+                }
+                let _ = self.delivery_events.send(DeliveryEvent::new(contact.uid.clone(), DeliveryUpdate::PingAnswered, false));
+                PingDispatch::Answered
+            }
+            Err(e) => {
+                tracing::warn!("Failed to ping newly imported contact {}: {}. Queueing for retry.", contact.uid, e);
+
+                // Create a special "ping" message to queue; its token is made when it is sent
+                let ping_message = Message::new(
+                    uuid::Uuid::new_v4().to_string(),
+                    self.keypair.uid.to_string(),
+                    contact.uid.clone(),
+                    FRESH_TOKEN_MARKER.to_vec(),
+                    Utc::now().timestamp_millis(),
+                );
+
+                // Create queue instance for this thread
+                let mut queue = match MessageQueue::new_for_identity("./app_data/message_queue.db", &self.keypair) {
+                    Ok(q) => q,
+                    Err(e) => {
+                        let message = format!("Failed to open the queue, ping to {} dropped: {}", contact.uid, e);
+                        self.reports.report(ErrorSeverity::Error, "message queue", message.clone());
+                        return PingDispatch::Failed(message);
+                    }
+                };
+
+                // Queue the ping with Urgent priority
+                if let Err(e) = queue.enqueue_with_type(ping_message, Priority::Urgent, "ping") {
+                    let message = format!("Failed to queue ping for {}: {}", contact.uid, e);
+                    self.reports.report(ErrorSeverity::Error, "message queue", message.clone());
+                    return PingDispatch::Failed(message);
+                }
+
+                let _ = self.delivery_events.send(DeliveryEvent::from_queue(&queue, &contact.uid, DeliveryUpdate::Queued));
+                PingDispatch::Queued
+            }
+        }
+    }
+}
+
+/// Review status of one token of a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchEntryStatus {
+    /// Valid and new
+    Ok,
+    /// Already a contact, or earlier in the same batch
+    Duplicate,
+    /// Valid signature, but the token has expired
+    Expired,
+    /// Not a valid token
+    Invalid(String),
+    /// Our own token
+    OwnToken,
+}
+
+impl BatchEntryStatus {
+    /// Status column of the review table
+    pub fn label(&self) -> &'static str {
+        match self {
+            BatchEntryStatus::Ok => "ok",
+            BatchEntryStatus::Duplicate => "duplicate",
+            BatchEntryStatus::Expired => "expired",
+            BatchEntryStatus::Invalid(_) => "invalid",
+            BatchEntryStatus::OwnToken => "self",
+        }
+    }
+}
+
+/// One token of a batch, as shown in the review table
+#[derive(Debug, Clone)]
+pub struct BatchEntry {
+    /// Line of the input the token is on (1-based)
+    pub line: usize,
+    /// Text before the token on its line, if any
+    pub name: Option<String>,
+    /// The token as given
+    pub token: String,
+    /// Parsed contact (None for invalid tokens)
+    pub contact: Option<Contact>,
+    /// Review status
+    pub status: BatchEntryStatus,
+    /// Whether the entry is imported on confirmation
+    pub selected: bool,
+    /// Pending introduction the contact is linked to on import
+    pub link: Option<PendingIntroduction>,
+}
+
+impl BatchEntry {
+    /// Name guess, or the start of the UID when there is none
+    pub fn display_name(&self) -> String {
+        match (&self.name, &self.contact) {
+            (Some(name), _) => name.clone(),
+            (None, Some(contact)) => contact.uid[..16.min(contact.uid.len())].to_string(),
+            (None, None) => format!("line {}", self.line),
+        }
+    }
+
+    /// Whether the entry can be selected (it holds a contact that is not ours)
+    pub fn selectable(&self) -> bool {
+        self.contact.is_some() && self.status != BatchEntryStatus::OwnToken
+    }
+}
+
+/// Split a line into the text before the token (a name guess) and the token
+///
+/// "Alice: <token>", "Alice - <token>" and "Alice <token>" all give "Alice".
+pub fn split_name_guess(line: &str) -> (Option<String>, &str) {
+    let line = line.trim();
+    match line.rsplit_once(char::is_whitespace) {
+        Some((name, token)) => {
+            let name = name.trim().trim_end_matches([':', '-', '=']).trim();
+            ((!name.is_empty()).then(|| name.to_string()), token)
+        }
+        None => (None, line),
+    }
+}
+
+/// Offer each named entry of a batch the pending introduction its name
+/// matches best (see `storage::introductions`)
+///
+/// An introduction goes to one entry only, the first that matches it.
+pub fn suggest_batch_links(entries: &mut [BatchEntry], pending: &[PendingIntroduction]) {
+    let mut unclaimed = pending.to_vec();
+    for entry in entries.iter_mut() {
+        entry.link = None;
+        let Some(name) = entry.name.as_deref().filter(|_| entry.selectable()) else {
+            continue;
+        };
+        if let Some(found) = best_match(name, &unclaimed) {
+            entry.link = Some(found.clone());
+            let id = found.id.clone();
+            unclaimed.retain(|intro| intro.id != id);
+        }
+    }
+}
+
+/// Split `text` into tokens and classify each for review
+///
+/// # Arguments
+/// * `text` - Pasted text or file content, one token per line
+/// * `app_state` - Known contacts (their UIDs count as duplicates)
+/// * `own_uid` - Our UID
+/// * `now` - Time expiry is checked against
+///
+/// # Returns
+/// One entry per token in input order; only `Ok` entries start selected
+pub fn parse_token_batch(text: &str, app_state: &AppState, own_uid: &str, now: DateTime<Utc>) -> Vec<BatchEntry> {
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, token) = split_name_guess(line);
+
+        let (contact, status) = match parse_contact_token_any_expiry(token) {
+            Ok(contact) => {
+                let status = if contact.uid == own_uid {
+                    BatchEntryStatus::OwnToken
+                } else if !seen.insert(contact.uid.clone()) || app_state.contact_by_uid(&contact.uid).is_some() {
+                    BatchEntryStatus::Duplicate
+                } else if contact.expiry < now {
+                    BatchEntryStatus::Expired
+                } else {
+                    BatchEntryStatus::Ok
+                };
+                (Some(contact), status)
+            }
+            Err(e) => (None, BatchEntryStatus::Invalid(e.to_string())),
+        };
+        entries.push(BatchEntry {
+            line: index + 1,
+            name,
+            token: token.to_string(),
+            selected: status == BatchEntryStatus::Ok,
+            link: None,
+            contact,
+            status,
+        });
+    }
+    entries
+}
+
+/// What importing one selected entry did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchEntryResult {
+    /// Added with a new chat; an introduction ping was dispatched
+    Imported,
+    /// Not added, e.g. already a contact (nothing failed)
+    Skipped(String),
+    /// The import failed
+    Failed(String),
+}
+
+/// One line of the report shown after a batch import
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchReportEntry {
+    /// Name guess or UID start
+    pub name: String,
+    /// Contact UID
+    pub uid: String,
+    /// What the import did
+    pub result: BatchEntryResult,
+    /// How the introduction ping went (imported entries only)
+    pub ping: Option<PingDispatch>,
+}
+
+impl BatchReportEntry {
+    /// One line of the report, e.g. "Alice: imported, ping queued"
+    pub fn line(&self) -> String {
+        let result = match &self.result {
+            BatchEntryResult::Imported => "imported".to_string(),
+            BatchEntryResult::Skipped(reason) => format!("skipped ({})", reason),
+            BatchEntryResult::Failed(reason) => format!("failed: {}", reason),
+        };
+        match &self.ping {
+            Some(ping) => format!("{}: {}, {}", self.name, result, ping.label()),
+            None => format!("{}: {}", self.name, result),
+        }
+    }
+}
+
+/// Batch mode state of the Import screen
+#[derive(Debug, Default)]
+pub struct BatchImport {
+    /// Entries under review (empty while the tokens are being entered)
+    pub entries: Vec<BatchEntry>,
+    /// Highlighted row of the review table
+    pub selected_row: usize,
+    /// Per-entry results once the batch was imported
+    pub report: Option<Vec<BatchReportEntry>>,
+    /// Ping results from the dispatch thread, by contact UID
+    pings: Arc<Mutex<Vec<(String, PingDispatch)>>>,
+}
+
+impl BatchImport {
+    /// Empty batch, waiting for tokens
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Batch reviewing `entries`
+    pub fn reviewing(entries: Vec<BatchEntry>) -> Self {
+        Self { entries, ..Self::default() }
+    }
+
+    /// Whether the review table is shown
+    pub fn in_review(&self) -> bool {
+        !self.entries.is_empty() && self.report.is_none()
+    }
+
+    /// Move the highlight up or down the review table
+    pub fn move_selection(&mut self, down: bool) {
+        if down {
+            self.selected_row = (self.selected_row + 1).min(self.entries.len().saturating_sub(1));
+        } else {
+            self.selected_row = self.selected_row.saturating_sub(1);
+        }
+    }
+
+    /// Select or deselect the highlighted entry
+    ///
+    /// # Returns
+    /// Whether it changed (invalid entries and our own token cannot be selected)
+    pub fn toggle_selected(&mut self) -> bool {
+        match self.entries.get_mut(self.selected_row) {
+            Some(entry) if entry.selectable() => {
+                entry.selected = !entry.selected;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of entries selected for import
+    pub fn selected_count(&self) -> usize {
+        self.entries.iter().filter(|e| e.selected).count()
+    }
+
+    /// Handle the dispatch thread reports ping results through
+    pub fn ping_sink(&self) -> Arc<Mutex<Vec<(String, PingDispatch)>>> {
+        self.pings.clone()
+    }
+
+    /// Move ping results reported since the last call into the report
+    ///
+    /// # Returns
+    /// Whether any arrived
+    pub fn collect_pings(&mut self) -> bool {
+        let arrived = std::mem::take(&mut *self.pings.lock().unwrap());
+        let Some(report) = &mut self.report else {
+            return false;
+        };
+        for (uid, ping) in &arrived {
+            if let Some(entry) = report.iter_mut().find(|e| &e.uid == uid) {
+                entry.ping = Some(ping.clone());
+            }
+        }
+        !arrived.is_empty()
+    }
+
+    /// Totals of the report, e.g. "3 imported, 1 skipped, 0 failed; pings: 2 answered, 1 queued, 0 pending"
+    pub fn summary(&self) -> Option<String> {
+        let report = self.report.as_ref()?;
+        let count = |f: &dyn Fn(&BatchReportEntry) -> bool| report.iter().filter(|e| f(e)).count();
+        Some(format!(
+            "{} imported, {} skipped, {} failed; pings: {} answered, {} queued, {} pending",
+            count(&|e| e.result == BatchEntryResult::Imported),
+            count(&|e| matches!(e.result, BatchEntryResult::Skipped(_))),
+            count(&|e| matches!(e.result, BatchEntryResult::Failed(_))),
+            count(&|e| e.ping == Some(PingDispatch::Answered)),
+            count(&|e| e.ping == Some(PingDispatch::Queued)),
+            count(&|e| e.ping == Some(PingDispatch::Pending)),
+        ))
+    }
+}
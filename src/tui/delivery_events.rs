@@ -0,0 +1,114 @@
+//! Delivery status events from background senders
+//!
+//! The retry worker and the send/ping dispatch threads publish a
+//! `DeliveryEvent` whenever a contact's queue state changes. The main loop
+//! applies them to the chat list in place, so pending and failed indicators
+//! follow deliveries without a reload. A periodic full queue scan
+//! (`RECONCILE_INTERVAL`) corrects anything a lost event left behind.
+
+use crate::queue::MessageQueue;
+use crate::storage::{Chat, Protection};
+use std::time::Duration;
+
+/// How often pending flags are reconciled against the full queue
+pub const RECONCILE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What happened to a message for a contact
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryUpdate {
+    /// Delivery failed and the message was queued for retry
+    Queued,
+    /// A message was delivered
+    Delivered,
+    /// A queued ping was answered (the contact is reachable)
+    PingAnswered,
+    /// A message was dropped after exhausting its retries
+    Failed,
+}
+
+/// Queue state change for one contact
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryEvent {
+    /// Contact the message was addressed to
+    pub contact_uid: String,
+    /// What happened
+    pub update: DeliveryUpdate,
+    /// Whether other messages to this contact are still queued
+    pub still_pending: bool,
+    /// The chat message concerned, when the sender knows it (journaled on delivery)
+    pub message_id: Option<String>,
+    /// Protection the message was sent with, when the sender sealed it
+    /// itself (recorded on delivery)
+    pub protection: Option<Protection>,
+}
+
+impl DeliveryEvent {
+    /// Create an event
+    pub fn new(contact_uid: impl Into<String>, update: DeliveryUpdate, still_pending: bool) -> Self {
+        Self {
+            contact_uid: contact_uid.into(),
+            update,
+            still_pending,
+            message_id: None,
+            protection: None,
+        }
+    }
+
+    /// Name the chat message the event is about
+    pub fn with_message_id(mut self, message_id: impl Into<String>) -> Self {
+        self.message_id = Some(message_id.into());
+        self
+    }
+
+    /// Attach the protection the message was sent with (None for control messages)
+    pub fn with_protection(mut self, protection: Option<Protection>) -> Self {
+        self.protection = protection;
+        self
+    }
+
+    /// Create an event, reading whether the contact still has queued messages
+    ///
+    /// Assumes messages are still pending if the queue cannot be read; the
+    /// periodic reconciliation corrects that later.
+    pub fn from_queue(queue: &MessageQueue, contact_uid: &str, update: DeliveryUpdate) -> Self {
+        let still_pending = update == DeliveryUpdate::Queued
+            || queue.has_pending_for(contact_uid).unwrap_or(true);
+        Self::new(contact_uid, update, still_pending)
+    }
+
+    /// Update the matching chat's indicators
+    ///
+    /// # Returns
+    /// Whether a chat changed
+    pub fn apply(&self, chats: &mut [Chat]) -> bool {
+        chats
+            .iter_mut()
+            .find(|c| c.contact_uid == self.contact_uid)
+            .is_some_and(|chat| self.apply_to_chat(chat))
+    }
+
+    /// Update the indicators of `chat`, which the caller looked up by `contact_uid`
+    ///
+    /// # Returns
+    /// Whether the chat changed
+    pub fn apply_to_chat(&self, chat: &mut Chat) -> bool {
+        let before = (chat.is_active, chat.has_pending_messages, chat.has_failed_messages);
+
+        if self.still_pending {
+            chat.mark_has_pending();
+        } else {
+            chat.mark_no_pending();
+        }
+        match self.update {
+            DeliveryUpdate::Queued => {}
+            DeliveryUpdate::Delivered => chat.mark_no_failed(),
+            DeliveryUpdate::PingAnswered => {
+                chat.mark_unread();
+                chat.mark_no_failed();
+            }
+            DeliveryUpdate::Failed => chat.mark_has_failed(),
+        }
+
+        before != (chat.is_active, chat.has_pending_messages, chat.has_failed_messages)
+    }
+}
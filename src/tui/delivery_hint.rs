@@ -0,0 +1,80 @@
+//! Age annotations and hints for messages waiting in the queue
+//!
+//! Outgoing messages still in the queue show how long they have waited
+//! ("queued — waiting 3 days"), counted from the queue row's `created_at`.
+//! `delivery_hint` turns the age of the oldest queued message, the
+//! contact's token expiry and when the contact was last heard from into a
+//! hint. The chat view shows it as a banner with a one-key action; the
+//! function is pure so every view asking the same question gets the same
+//! answer.
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Queued this long without hearing from the contact, its address may be stale
+pub const STALE_ADDRESS_AFTER_HOURS: i64 = 24;
+
+/// What the sender should know about messages waiting for a contact
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryHint {
+    /// Still retrying normally
+    Waiting,
+    /// Waiting over a day without hearing from the contact; a new
+    /// introduction ping may reach it at its current address
+    StaleAddress,
+    /// The contact's token expired (or the contact is gone): nothing can be
+    /// delivered until a new token is imported
+    Undeliverable,
+}
+
+impl DeliveryHint {
+    /// Banner text for the chat view, fitting 80 columns (None: no banner)
+    pub fn banner(self) -> Option<&'static str> {
+        match self {
+            DeliveryHint::Waiting => None,
+            DeliveryHint::StaleAddress => {
+                Some("Waiting over a day, address may be stale | Ctrl+R: Resend introduction ping")
+            }
+            DeliveryHint::Undeliverable => {
+                Some("Token expired: undeliverable until a new token is imported | Ctrl+R: Import")
+            }
+        }
+    }
+}
+
+/// Hint for messages to a contact, the oldest of which was queued at `queued_at`
+///
+/// # Arguments
+/// * `queued_at` - When the oldest queued message was queued
+/// * `contact_expiry` - Expiry of the contact's token (None: contact unknown)
+/// * `last_heard` - When the contact last sent us something
+/// * `now` - Current time
+pub fn delivery_hint(
+    queued_at: DateTime<Utc>,
+    contact_expiry: Option<DateTime<Utc>>,
+    last_heard: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> DeliveryHint {
+    if contact_expiry.is_none_or(|expiry| expiry <= now) {
+        return DeliveryHint::Undeliverable;
+    }
+    let heard_since_queued = last_heard.is_some_and(|heard| heard >= queued_at);
+    if now - queued_at >= Duration::hours(STALE_ADDRESS_AFTER_HOURS) && !heard_since_queued {
+        DeliveryHint::StaleAddress
+    } else {
+        DeliveryHint::Waiting
+    }
+}
+
+/// Annotation for a message queued `age` ago, e.g. "queued — waiting 3 days"
+pub fn queued_annotation(age: Duration) -> String {
+    let plural = |n: i64, unit: &str| if n == 1 { format!("1 {}", unit) } else { format!("{} {}s", n, unit) };
+    if age < Duration::minutes(1) {
+        "queued — just now".to_string()
+    } else if age < Duration::hours(1) {
+        format!("queued — waiting {} min", age.num_minutes())
+    } else if age < Duration::days(1) {
+        format!("queued — waiting {}", plural(age.num_hours(), "hour"))
+    } else {
+        format!("queued — waiting {}", plural(age.num_days(), "day"))
+    }
+}
@@ -0,0 +1,115 @@
+//! Single-protocol actions on the Diagnostics screen
+//!
+//! Besides the full refresh, the Diagnostics screen can test one mapping
+//! protocol on its own or delete the active mapping. Each action runs on a
+//! background thread (see `App::diagnostics_action_handle`) through a
+//! `MappingActions` implementation, so tests can replace the router calls.
+
+use crate::connectivity::{MappingProtocol, PortMappingResult};
+
+/// Router operations used by the Diagnostics screen actions
+///
+/// Methods block; they are only called from background threads.
+pub trait MappingActions: Send + Sync {
+    /// Request a mapping for `port` using only `protocol`
+    fn map(&self, protocol: MappingProtocol, port: u16) -> Result<PortMappingResult, String>;
+
+    /// Delete the mapping `protocol` created for `port`
+    ///
+    /// # Returns
+    /// A line describing the router's answer
+    fn delete(&self, protocol: MappingProtocol, port: u16) -> Result<String, String>;
+}
+
+/// `MappingActions` talking to the real gateway
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RouterMappingActions;
+
+impl RouterMappingActions {
+    fn block_on<T>(future: impl std::future::Future<Output = T>) -> Result<T, String> {
+        let rt = tokio::runtime::Runtime::new().map_err(|e| format!("Failed to create runtime: {}", e))?;
+        Ok(rt.block_on(future))
+    }
+}
+
+impl MappingActions for RouterMappingActions {
+    fn map(&self, protocol: MappingProtocol, port: u16) -> Result<PortMappingResult, String> {
+        Self::block_on(crate::connectivity::try_single_protocol(protocol, port))?.map_err(|e| e.to_string())
+    }
+
+    fn delete(&self, protocol: MappingProtocol, port: u16) -> Result<String, String> {
+        Self::block_on(crate::connectivity::delete_port_mapping(protocol, port))?.map_err(|e| e.to_string())
+    }
+}
+
+/// An action started from the Diagnostics screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticsAction {
+    /// Test one protocol against a port
+    Test {
+        /// Protocol to test
+        protocol: MappingProtocol,
+        /// Local port to map
+        port: u16,
+    },
+    /// Delete the mapping a protocol created
+    Delete {
+        /// Protocol that created the mapping
+        protocol: MappingProtocol,
+        /// Local port the mapping points to
+        port: u16,
+    },
+}
+
+/// Outcome of a `DiagnosticsAction`
+#[derive(Debug, Clone)]
+pub enum DiagnosticsActionResult {
+    /// A single-protocol test finished
+    Tested {
+        /// Protocol tested
+        protocol: MappingProtocol,
+        /// Local port tested
+        port: u16,
+        /// Mapping or error message
+        result: Result<PortMappingResult, String>,
+    },
+    /// A mapping deletion finished
+    Deleted {
+        /// Protocol that created the mapping
+        protocol: MappingProtocol,
+        /// Local port the mapping pointed to
+        port: u16,
+        /// Router's answer or error message
+        result: Result<String, String>,
+    },
+}
+
+impl DiagnosticsAction {
+    /// Protocol the action works on
+    pub fn protocol(&self) -> MappingProtocol {
+        match self {
+            Self::Test { protocol, .. } | Self::Delete { protocol, .. } => *protocol,
+        }
+    }
+
+    /// Run the action (blocking)
+    pub fn run(self, actions: &dyn MappingActions) -> DiagnosticsActionResult {
+        match self {
+            Self::Test { protocol, port } => DiagnosticsActionResult::Tested {
+                protocol,
+                port,
+                result: actions.map(protocol, port),
+            },
+            Self::Delete { protocol, port } => DiagnosticsActionResult::Deleted {
+                protocol,
+                port,
+                result: actions.delete(protocol, port),
+            },
+        }
+    }
+}
+
+/// Short display name of a mapping protocol
+pub fn protocol_name(protocol: MappingProtocol) -> &'static str {
+    protocol.name()
+}
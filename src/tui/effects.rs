@@ -0,0 +1,76 @@
+//! Transient UI effects (short flashes drawn on top of the current screen)
+//!
+//! An effect is queued with the time it starts and stays active for its
+//! duration. The render loop asks which effects are active and shortens its
+//! event poll to `next_change()`, so an effect ends on time even without
+//! input. Every method takes `now`, which keeps the queue testable with a
+//! mocked clock.
+
+use std::time::{Duration, Instant};
+
+/// How long the new-message header flash lasts
+pub const HEADER_FLASH_DURATION: Duration = Duration::from_millis(200);
+
+/// How long the urgent header flash lasts
+pub const URGENT_FLASH_DURATION: Duration = Duration::from_millis(600);
+
+/// A transient effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UiEffect {
+    /// Invert the header line (new message alert)
+    HeaderFlash,
+    /// Underline the header line (subtle alert)
+    SubtleFlash,
+    /// Invert and embolden the header line for longer (urgent alert)
+    UrgentFlash,
+}
+
+impl UiEffect {
+    /// Header flashes, the one drawn first winning
+    pub const FLASHES: [UiEffect; 3] = [UiEffect::UrgentFlash, UiEffect::HeaderFlash, UiEffect::SubtleFlash];
+
+    /// How long the effect stays active
+    pub fn duration(self) -> Duration {
+        match self {
+            UiEffect::HeaderFlash | UiEffect::SubtleFlash => HEADER_FLASH_DURATION,
+            UiEffect::UrgentFlash => URGENT_FLASH_DURATION,
+        }
+    }
+}
+
+/// Effects currently running, with the time each one ends
+#[derive(Debug, Default)]
+pub struct EffectQueue {
+    running: Vec<(UiEffect, Instant)>,
+}
+
+impl EffectQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start `effect` at `now`; restarting a running effect extends it
+    pub fn push(&mut self, effect: UiEffect, now: Instant) {
+        self.running.retain(|(running, _)| *running != effect);
+        self.running.push((effect, now + effect.duration()));
+    }
+
+    /// Whether `effect` is active at `now`
+    pub fn is_active(&self, effect: UiEffect, now: Instant) -> bool {
+        self.running.iter().any(|(running, ends)| *running == effect && now < *ends)
+    }
+
+    /// Drop effects that have ended
+    pub fn prune(&mut self, now: Instant) {
+        self.running.retain(|(_, ends)| now < *ends);
+    }
+
+    /// Time until the next running effect ends (None when nothing runs)
+    pub fn next_change(&self, now: Instant) -> Option<Duration> {
+        self.running
+            .iter()
+            .map(|(_, ends)| ends.saturating_duration_since(now))
+            .min()
+    }
+}
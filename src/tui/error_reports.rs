@@ -0,0 +1,158 @@
+//! Reports of background failures and the error banner
+//!
+//! Saves, queue updates and handler threads report failures they cannot
+//! hand back to a caller through a shared `ErrorReporter` instead of
+//! dropping them. The reporter keeps the last `ERROR_REPORT_CAPACITY`
+//! reports for the Diagnostics error log; the newest one at or above
+//! `Settings::error_banner_severity` is shown as a banner on every screen
+//! until dismissed.
+
+use crate::storage::{is_busy_error, ErrorSeverity};
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Reports kept for the Diagnostics error log (older ones are dropped)
+pub const ERROR_REPORT_CAPACITY: usize = 100;
+
+/// Whether a handler error is a failure on our side worth reporting
+///
+/// Malformed or unauthenticated requests are the peer's problem, and a
+/// locked database is answered with 503 so the sender retries.
+pub fn is_local_failure(error: &crate::Error) -> bool {
+    matches!(error, crate::Error::Storage(_) | crate::Error::Database(_) | crate::Error::Io(_)) && !is_busy_error(error)
+}
+
+/// One background failure
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    /// How serious the failure is
+    pub severity: ErrorSeverity,
+    /// Component that failed, e.g. "storage" or "message handler"
+    pub source: String,
+    /// What went wrong
+    pub message: String,
+    /// When it was reported
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl ErrorReport {
+    /// One line of the Diagnostics error log, e.g. "14:02:11 error storage: ..."
+    pub fn log_line(&self) -> String {
+        format!(
+            "{} {} {}: {}",
+            self.occurred_at.with_timezone(&chrono::Local).format("%H:%M:%S"),
+            self.severity.name(),
+            self.source,
+            self.message
+        )
+    }
+}
+
+/// What the error banner shows
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorBanner {
+    /// Newest undismissed report at or above the threshold
+    pub report: ErrorReport,
+    /// Older undismissed reports at or above the threshold still in the log
+    pub suppressed: usize,
+}
+
+#[derive(Debug, Default)]
+struct ReportLog {
+    /// Reports with their sequence number, oldest first
+    reports: VecDeque<(u64, ErrorReport)>,
+    next_seq: u64,
+    /// Reports numbered below this were dismissed
+    dismissed_before: u64,
+}
+
+/// Shared, bounded log of background failures
+///
+/// Clones share the same log, so threads can be handed their own copy.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorReporter {
+    log: Arc<Mutex<ReportLog>>,
+}
+
+impl ErrorReporter {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A panic elsewhere must not stop failures from being reported
+    fn lock(&self) -> MutexGuard<'_, ReportLog> {
+        self.log.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Record a failure of `source` (also written to the log file)
+    pub fn report(&self, severity: ErrorSeverity, source: &str, message: impl Into<String>) {
+        let message = message.into();
+        match severity {
+            ErrorSeverity::Info => tracing::info!("{}: {}", source, message),
+            ErrorSeverity::Warning => tracing::warn!("{}: {}", source, message),
+            ErrorSeverity::Error => tracing::error!("{}: {}", source, message),
+        }
+
+        let mut log = self.lock();
+        let seq = log.next_seq;
+        log.next_seq += 1;
+        if log.reports.len() == ERROR_REPORT_CAPACITY {
+            log.reports.pop_front();
+        }
+        log.reports.push_back((
+            seq,
+            ErrorReport { severity, source: source.to_string(), message, occurred_at: Utc::now() },
+        ));
+    }
+
+    /// Report `result`'s error, if any, and pass its value on
+    pub fn check<T, E: std::fmt::Display>(
+        &self,
+        severity: ErrorSeverity,
+        source: &str,
+        result: Result<T, E>,
+    ) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.report(severity, source, e.to_string());
+                None
+            }
+        }
+    }
+
+    /// Reports in the log, oldest first
+    pub fn reports(&self) -> Vec<ErrorReport> {
+        self.lock().reports.iter().map(|(_, report)| report.clone()).collect()
+    }
+
+    /// Number of reports in the log
+    pub fn len(&self) -> usize {
+        self.lock().reports.len()
+    }
+
+    /// Whether nothing was reported (or everything was dropped)
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Banner for the newest undismissed report at or above `threshold`
+    pub fn banner(&self, threshold: ErrorSeverity) -> Option<ErrorBanner> {
+        let log = self.lock();
+        let mut shown = log
+            .reports
+            .iter()
+            .rev()
+            .filter(|(seq, report)| *seq >= log.dismissed_before && report.severity >= threshold);
+        let (_, report) = shown.next()?;
+        Some(ErrorBanner { report: report.clone(), suppressed: shown.count() })
+    }
+
+    /// Hide the banner until something new is reported (the log is kept)
+    pub fn dismiss(&self) {
+        let mut log = self.lock();
+        log.dismissed_before = log.next_seq;
+    }
+}
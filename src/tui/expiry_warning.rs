@@ -0,0 +1,98 @@
+//! Warning before sending to a contact whose token expires first
+//!
+//! A message to a contact that is not reachable right now (earlier messages
+//! to it are still queued) only goes out through the retry worker. When the
+//! contact's token expires before the worker's next
+//! `EXPIRY_LOOKAHEAD_ATTEMPTS` attempts, as replayed by
+//! `retry_schedule::retry_opportunities` with backoff and quiet hours, the
+//! message may never be delivered. The chat view then asks first: send
+//! anyway, send and ask the contact for a fresh token, or cancel.
+//!
+//! The refresh request is a `TOKEN_REFRESH_REQUEST_TYPE` control message,
+//! queued at urgent priority so quiet hours do not hold it back. The contact
+//! answers with a ping carrying its freshly signed token, which extends the
+//! stored expiry like any imported token.
+//!
+//! Once the token has expired, messages still queued to the contact are
+//! blocked (see `queue`) until a new token is imported. The main loop checks
+//! every `EXPIRED_BLOCK_SYNC_SECS`, and after every import or reload
+//! (`App::sync_expired_blocks`).
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::tui::ui::format_duration_until_at;
+
+/// Message type asking a contact for a fresh contact token
+pub const TOKEN_REFRESH_REQUEST_TYPE: &str = "token_refresh_request";
+
+/// Retry attempts the token must outlast for a send to go unwarned
+pub const EXPIRY_LOOKAHEAD_ATTEMPTS: usize = 3;
+
+/// How often the main loop checks queued messages against contact expiry
+pub const EXPIRED_BLOCK_SYNC_SECS: i64 = 30;
+
+/// The contact's token may expire before the message is delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpiryWarning {
+    /// When the contact's token expires
+    pub expiry: DateTime<Utc>,
+    /// When the warning was made
+    pub at: DateTime<Utc>,
+}
+
+impl ExpiryWarning {
+    /// How long the token has left
+    pub fn expires_in(&self) -> Duration {
+        self.expiry - self.at
+    }
+
+    /// Status line text, e.g. "Contact's token expires in 2 hours - ..."
+    pub fn text(&self) -> String {
+        let left = if self.expires_in() < Duration::minutes(1) {
+            "under a minute".to_string()
+        } else {
+            format_duration_until_at(self.expiry, self.at)
+        };
+        format!(
+            "Contact's token expires in {} - message may never be delivered | Enter: Send anyway | r: Send + request refresh | Esc: Cancel",
+            left
+        )
+    }
+}
+
+/// How the sender answered an `ExpiryWarning`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpiryChoice {
+    /// Send as if nothing was said
+    SendAnyway,
+    /// Send, and queue a token refresh request to the contact
+    SendAndRequestRefresh,
+    /// Keep the message in the input
+    Cancel,
+}
+
+/// Warning for a send at `now` (None: send right away)
+///
+/// # Arguments
+/// * `expiry` - Expiry of the contact's token
+/// * `reachable` - False when earlier messages to the contact are still queued
+/// * `attempts` - When the retry worker would attempt the message (see
+///   `retry_schedule::retry_opportunities`); with fewer than
+///   `EXPIRY_LOOKAHEAD_ATTEMPTS`, the token must outlast them all
+/// * `now` - Current time
+///
+/// An already expired token is not warned about: the chat view's delivery
+/// banner says the contact is undeliverable.
+pub fn expiry_warning(
+    expiry: DateTime<Utc>,
+    reachable: bool,
+    attempts: &[DateTime<Utc>],
+    now: DateTime<Utc>,
+) -> Option<ExpiryWarning> {
+    if reachable || expiry <= now {
+        return None;
+    }
+    // No attempt in sight (paused for over a week) cannot beat any expiry
+    let outlasted = attempts[..attempts.len().min(EXPIRY_LOOKAHEAD_ATTEMPTS)].last().is_some_and(|horizon| expiry >= *horizon);
+    (!outlasted).then_some(ExpiryWarning { expiry, at: now })
+}
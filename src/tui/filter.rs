@@ -0,0 +1,103 @@
+//! Fuzzy filtering and selection for list overlays
+//!
+//! `FilterList` holds the typed query and the highlighted entry of a list
+//! narrowed by `fuzzy_score`. Overlays keep one alongside whatever they list
+//! and ask it for the matching indices on every render.
+
+/// Score how well `query` matches `candidate` (higher is better)
+///
+/// Every query character must appear in the candidate in order, ignoring
+/// case. Consecutive matches and matches at the start of a word score
+/// higher. An empty query matches everything with score 0.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<u32> {
+    let mut score = 0;
+    let mut chars = candidate.chars().enumerate();
+    let mut previous: Option<char> = None;
+    let mut last_match: Option<usize> = None;
+
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let q = q.to_lowercase().next().unwrap_or(q);
+        loop {
+            let (i, c) = chars.next()?;
+            let word_start = previous.is_none_or(|p| !p.is_alphanumeric());
+            previous = Some(c);
+            if c.to_lowercase().next() == Some(q) {
+                score += 1;
+                if word_start {
+                    score += 2;
+                }
+                if last_match.is_some_and(|m| m + 1 == i) {
+                    score += 3;
+                }
+                last_match = Some(i);
+                break;
+            }
+        }
+    }
+
+    Some(score)
+}
+
+/// Query and selection of a fuzzy-filtered list
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterList {
+    /// Text typed so far
+    pub query: String,
+    /// Position of the highlighted entry among the matches
+    pub selected: usize,
+}
+
+impl FilterList {
+    /// Create an empty filter (everything matches)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indices of the candidates matching the query, best match first
+    ///
+    /// Candidates with equal scores keep their original order.
+    pub fn matches<'a>(&self, candidates: impl IntoIterator<Item = &'a str>) -> Vec<usize> {
+        let mut scored: Vec<(usize, u32)> = candidates
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, c)| fuzzy_score(&self.query, c).map(|s| (i, s)))
+            .collect();
+        scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        scored.into_iter().map(|(i, _)| i).collect()
+    }
+
+    /// Index of the highlighted candidate, if anything matches
+    pub fn selected_index<'a>(&self, candidates: impl IntoIterator<Item = &'a str>) -> Option<usize> {
+        let matches = self.matches(candidates);
+        matches.get(self.selected.min(matches.len().saturating_sub(1))).copied()
+    }
+
+    /// Append to the query (selection returns to the best match)
+    pub fn push_char(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    /// Remove the last query character
+    ///
+    /// # Returns
+    /// false if the query was already empty
+    pub fn pop_char(&mut self) -> bool {
+        self.selected = 0;
+        self.query.pop().is_some()
+    }
+
+    /// Highlight the next match (wraps around)
+    pub fn next(&mut self, match_count: usize) {
+        if match_count > 0 {
+            self.selected = (self.selected + 1) % match_count;
+        }
+    }
+
+    /// Highlight the previous match (wraps around)
+    pub fn previous(&mut self, match_count: usize) {
+        if match_count > 0 {
+            self.selected = (self.selected + match_count - 1) % match_count;
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Terminal focus tracking
+//!
+//! With focus reporting enabled, terminals that support it send
+//! `FocusGained`/`FocusLost`; others never send either. The app therefore
+//! starts focused and only leaves that state on a `FocusLost`, so without
+//! focus support everything behaves as if the window were always in front.
+//!
+//! While unfocused the main loop polls (and redraws) at
+//! `UNFOCUSED_POLL_INTERVAL`, the open chat is not marked read, and
+//! new-message flashes are counted instead of drawn. Regaining focus forces
+//! a full redraw and replays the missed flashes as one.
+
+use std::time::Duration;
+
+/// Main loop tick while the terminal is in the background (1 fps)
+pub const UNFOCUSED_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Focus state of the terminal window
+#[derive(Debug)]
+pub struct FocusState {
+    focused: bool,
+    reported: bool,
+    missed_alerts: usize,
+}
+
+impl Default for FocusState {
+    fn default() -> Self {
+        Self { focused: true, reported: false, missed_alerts: 0 }
+    }
+}
+
+impl FocusState {
+    /// Focused, with no focus event seen yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the window is in front (always, without focus support)
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Whether the terminal has sent a focus event
+    pub fn reports_focus(&self) -> bool {
+        self.reported
+    }
+
+    /// The window went to the background
+    pub fn lost(&mut self) {
+        self.reported = true;
+        self.focused = false;
+    }
+
+    /// The window came back to the front
+    ///
+    /// # Returns
+    /// New-message alerts missed while unfocused, now cleared
+    pub fn gained(&mut self) -> usize {
+        self.reported = true;
+        self.focused = true;
+        std::mem::take(&mut self.missed_alerts)
+    }
+
+    /// Count a new-message flash held back while unfocused
+    pub fn miss_alert(&mut self) {
+        self.missed_alerts += 1;
+    }
+
+    /// New-message alerts held back so far
+    pub fn missed_alerts(&self) -> usize {
+        self.missed_alerts
+    }
+
+    /// How long the main loop waits for input before its next tick
+    ///
+    /// `focused` is the normal interval; in the background the loop never
+    /// ticks faster than `UNFOCUSED_POLL_INTERVAL`.
+    pub fn poll_interval(&self, focused: Duration) -> Duration {
+        if self.focused { focused } else { focused.max(UNFOCUSED_POLL_INTERVAL) }
+    }
+}
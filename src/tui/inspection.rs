@@ -0,0 +1,60 @@
+//! Inspection mode: browsing a copied app_data directory, read only
+//!
+//! `pure2p-tui --inspect <dir>` opens another installation's files (a copy
+//! sent with a bug report, or a backup) with `App::new_for_inspection`.
+//! Every connection is read-only (see `storage::read_only`), and nothing
+//! that talks to the network or writes runs: no transport server, retry
+//! worker, connectivity checks, update check, maintenance or state saves.
+//!
+//! Chats, contacts, settings, the queue, the logs and the integrity report
+//! can be browsed. Actions that would change something are refused by
+//! `App::refuse_in_inspection`, which says why in the banner shown on every
+//! screen.
+
+use crate::storage::WriteLog;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How long a refusal is shown in the banner
+pub const REFUSAL_STATUS_SECS: u64 = 5;
+
+/// State of an inspection session
+#[derive(Debug, Clone)]
+pub struct Inspection {
+    /// Directory being inspected
+    pub dir: PathBuf,
+    /// Identity fingerprint of the installation the directory belongs to
+    pub fingerprint: String,
+    /// Writes denied on the inspected files (expected to stay empty)
+    pub writes: WriteLog,
+    /// Last refused action and when it was refused
+    refusal: Option<(String, Instant)>,
+}
+
+impl Inspection {
+    /// Start inspecting `dir`, whose connections record writes in `writes`
+    pub fn new(dir: PathBuf, writes: WriteLog) -> Self {
+        Self { dir, fingerprint: String::new(), writes, refusal: None }
+    }
+
+    /// Banner shown on every screen
+    pub fn banner_text(&self) -> String {
+        format!("INSPECTION MODE — read only — identity: {}", self.fingerprint)
+    }
+
+    /// Refuse `action` at `now`, returning the status that says why
+    pub fn refuse(&mut self, action: &str, now: Instant) -> String {
+        let status = format!("Read only: cannot {} in inspection mode", action);
+        tracing::info!("{}", status);
+        self.refusal = Some((status.clone(), now));
+        status
+    }
+
+    /// The refusal still shown at `now`, if any
+    pub fn status(&self, now: Instant) -> Option<&str> {
+        self.refusal
+            .as_ref()
+            .filter(|(_, at)| now.saturating_duration_since(*at) < Duration::from_secs(REFUSAL_STATUS_SECS))
+            .map(|(status, _)| status.as_str())
+    }
+}
@@ -8,9 +8,61 @@ pub mod screens;
 pub mod app;
 pub mod ui;
 pub mod clipboard;
+pub mod notifications;
+pub mod alerts;
+pub mod effects;
+pub mod focus;
+pub mod badges;
+pub mod delivery_events;
+pub mod filter;
+pub mod diagnostics_actions;
+pub mod theme;
+pub mod paths;
+pub mod path_picker;
+pub mod connectivity_indicator;
+pub mod delivery_hint;
+pub mod port_watchdog;
+pub mod error_reports;
+pub mod contact_import;
+pub mod undo;
+pub mod send_preview;
+pub mod expiry_warning;
+pub mod send_leases;
+pub mod startup_graph;
+pub mod recovery;
+pub mod inspection;
 
 // Re-export main types for convenience
-pub use types::{Screen, MenuItem};
+pub use types::{Screen, MenuItem, StartupTimings};
 pub use screens::*;
 pub use app::{App, TransportServerStatus};
 pub use clipboard::{ClipboardProvider, RealClipboard};
+pub use notifications::Notifications;
+pub use effects::{EffectQueue, UiEffect};
+pub use focus::{FocusState, UNFOCUSED_POLL_INTERVAL};
+pub use badges::{ChatSummary, TerminalTitle};
+pub use delivery_events::{DeliveryEvent, DeliveryUpdate};
+pub use filter::{fuzzy_score, FilterList};
+pub use theme::Theme;
+pub use paths::{default_save_dir, expand_tilde, PathEnv, Platform};
+pub use path_picker::{ChosenPath, PathPicker, SavePathError, SaveTarget};
+pub use connectivity_indicator::{ConnectivityIndicator, LimitReason};
+pub use delivery_hint::{delivery_hint, queued_annotation, DeliveryHint};
+pub use port_watchdog::{PortAlert, PortWatchdog, PORT_TAKEOVER_AUDIT_TYPE};
+pub use contact_import::{
+    parse_token_batch, split_name_guess, suggest_batch_links, BatchEntry, BatchEntryResult, BatchEntryStatus, BatchImport,
+    BatchReportEntry, ImportRefusal, OwnTokenSource, PingDispatch, PingDispatcher, FRESH_TOKEN_MARKER,
+};
+pub use send_preview::{preview_reasons, PreviewReason, SendPreview};
+pub use expiry_warning::{
+    expiry_warning, ExpiryChoice, ExpiryWarning, EXPIRED_BLOCK_SYNC_SECS, EXPIRY_LOOKAHEAD_ATTEMPTS, TOKEN_REFRESH_REQUEST_TYPE,
+};
+pub use send_leases::{run_send, SendLease, SendLeases, LEASE_SWEEP_INTERVAL, LEASE_TIMEOUT_SECS, SEND_TRANSPORT_TIMEOUT_SECS};
+pub use startup_graph::{StageOutcome, StageState, StartupEvent, StartupGraph, StartupStage};
+pub use recovery::{count_phrase, RecoveryReport, RecoveryStep, StepOutcome};
+pub use inspection::{Inspection, REFUSAL_STATUS_SECS};
+pub use undo::{UndoEntry, UndoList, UndoState, SOFT_DELETE_PURGE_INTERVAL_SECS, UNDO_STATUS_SECS};
+pub use error_reports::{is_local_failure, ErrorBanner, ErrorReport, ErrorReporter, ERROR_REPORT_CAPACITY};
+pub use diagnostics_actions::{
+    DiagnosticsAction, DiagnosticsActionResult, MappingActions, RouterMappingActions,
+};
@@ -0,0 +1,73 @@
+//! In-app notifications with quiet hours support
+//!
+//! Notifications raised during quiet hours are held back and coalesced into a
+//! single summary once the quiet period ends.
+
+use std::time::{Duration, Instant};
+
+/// How long a notification stays visible
+pub const NOTIFICATION_DISPLAY_DURATION: Duration = Duration::from_secs(5);
+
+/// Notification state for the TUI
+#[derive(Debug, Default)]
+pub struct Notifications {
+    /// Notification currently shown and when it was raised
+    pub current: Option<(String, Instant)>,
+    /// Notifications held back during quiet hours
+    pub suppressed: Vec<String>,
+    /// Whether quiet hours were in effect at the last update
+    pub quiet: bool,
+}
+
+impl Notifications {
+    /// Create empty notification state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Raise a notification, holding it back if quiet hours are in effect
+    pub fn notify(&mut self, text: String) {
+        if self.quiet {
+            self.suppressed.push(text);
+        } else {
+            self.current = Some((text, Instant::now()));
+        }
+    }
+
+    /// Update quiet hours state
+    ///
+    /// When a quiet period ends, held-back notifications are coalesced into a
+    /// summary notification.
+    ///
+    /// # Returns
+    /// The summary text if one was raised
+    pub fn set_quiet(&mut self, quiet: bool) -> Option<String> {
+        let period_ended = self.quiet && !quiet;
+        self.quiet = quiet;
+
+        if !period_ended || self.suppressed.is_empty() {
+            return None;
+        }
+
+        let summary = if self.suppressed.len() == 1 {
+            format!("During quiet hours: {}", self.suppressed[0])
+        } else {
+            format!(
+                "{} notifications during quiet hours (latest: {})",
+                self.suppressed.len(),
+                self.suppressed[self.suppressed.len() - 1]
+            )
+        };
+        self.suppressed.clear();
+        self.current = Some((summary.clone(), Instant::now()));
+        Some(summary)
+    }
+
+    /// Get the notification to display, if it has not timed out
+    pub fn visible(&self, now: Instant) -> Option<&str> {
+        self.current
+            .as_ref()
+            .filter(|(_, raised)| now.duration_since(*raised) < NOTIFICATION_DISPLAY_DURATION)
+            .map(|(text, _)| text.as_str())
+    }
+}
@@ -0,0 +1,283 @@
+//! Save-path overlay shared by every "save to file" action
+//!
+//! The overlay starts with a suggested path (see `paths::default_save_dir`)
+//! that the user can edit. `PathPicker::submit` expands `~`, makes the path
+//! absolute and checks that its folder exists and is writable. An existing
+//! file is only replaced after the user answers the overwrite prompt. What
+//! gets written is decided by the `SaveTarget` the overlay was opened for.
+//! Every failure maps to a `SavePathError` with its own status message.
+//!
+//! The targets that read instead (`SaveTarget::TokenBatch`,
+//! `SaveTarget::ArchiveVerify`, `SaveTarget::IntroductionList`) need an
+//! existing file and never ask about overwriting.
+
+use crate::tui::paths::expand_tilde;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What the overlay saves once a path is chosen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveTarget {
+    /// The contact token shown on the share screen
+    ContactToken,
+    /// JSON Lines export of the chat with this contact
+    ChatExport {
+        /// Contact whose chat is exported
+        contact_uid: String,
+    },
+    /// Signed archive of the chat with this contact, for them to verify
+    ChatArchive {
+        /// Contact whose chat is exported
+        contact_uid: String,
+    },
+    /// Contact tokens read into the Import screen's batch mode
+    TokenBatch,
+    /// Chat archive from a contact, read to verify it
+    ArchiveVerify,
+    /// Contact list (CSV or vCard) read into the pending introductions
+    IntroductionList,
+    /// JSON export of the diff shown on the snapshots screen
+    SnapshotDiff,
+    /// Signed export of the outbound delivery journal (CSV or JSON Lines)
+    JournalExport,
+}
+
+impl SaveTarget {
+    /// Whether the chosen file is read rather than written
+    pub fn reads_file(&self) -> bool {
+        matches!(self, SaveTarget::TokenBatch | SaveTarget::ArchiveVerify | SaveTarget::IntroductionList)
+    }
+}
+
+/// Why a path cannot be saved to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SavePathError {
+    /// Nothing was entered
+    Empty,
+    /// The path starts with `~` but there is no home directory
+    NoHome,
+    /// The path names a folder, not a file
+    NoFileName,
+    /// A folder already exists at the path
+    IsDirectory(PathBuf),
+    /// The folder the file would go in does not exist
+    ParentMissing(PathBuf),
+    /// The file's parent exists but is not a folder
+    ParentNotDirectory(PathBuf),
+    /// The folder is read-only or writing was denied
+    ReadOnly(PathBuf),
+    /// The disk (or quota) is full
+    DiskFull(PathBuf),
+    /// The file to read does not exist
+    NotFound(PathBuf),
+    /// The file to read could not be read
+    Unreadable(PathBuf, String),
+    /// Any other I/O failure
+    Io(PathBuf, String),
+}
+
+impl SavePathError {
+    /// Classify an I/O error from writing `path`
+    pub fn from_io(error: &io::Error, path: &Path) -> Self {
+        let folder = || path.parent().unwrap_or(path).to_path_buf();
+        match error.kind() {
+            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => SavePathError::ReadOnly(folder()),
+            io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => SavePathError::DiskFull(path.to_path_buf()),
+            io::ErrorKind::NotFound => SavePathError::ParentMissing(folder()),
+            io::ErrorKind::NotADirectory => SavePathError::ParentNotDirectory(folder()),
+            io::ErrorKind::IsADirectory => SavePathError::IsDirectory(path.to_path_buf()),
+            _ => SavePathError::Io(path.to_path_buf(), error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for SavePathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SavePathError::Empty => write!(f, "Enter a file path"),
+            SavePathError::NoHome => write!(f, "Cannot expand ~: no home directory is set"),
+            SavePathError::NoFileName => write!(f, "Path must end with a file name"),
+            SavePathError::IsDirectory(path) => write!(f, "{} is a folder; add a file name", path.display()),
+            SavePathError::ParentMissing(dir) => write!(f, "Folder {} does not exist", dir.display()),
+            SavePathError::ParentNotDirectory(dir) => write!(f, "{} is not a folder", dir.display()),
+            SavePathError::ReadOnly(dir) => write!(f, "Cannot write to {} (read-only or permission denied)", dir.display()),
+            SavePathError::DiskFull(path) => write!(f, "Disk full: could not write {}", path.display()),
+            SavePathError::NotFound(path) => write!(f, "File {} does not exist", path.display()),
+            SavePathError::Unreadable(path, e) => write!(f, "Could not read {}: {}", path.display(), e),
+            SavePathError::Io(path, e) => write!(f, "Could not write {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for SavePathError {}
+
+/// Turn user input into an absolute path that can be written
+///
+/// # Arguments
+/// * `input` - Path as typed (may start with `~`, may be relative)
+/// * `home` - Home directory for `~`
+/// * `cwd` - Base for relative paths
+///
+/// # Errors
+/// The `SavePathError` describing the first problem found
+pub fn validate_save_path(input: &str, home: Option<&Path>, cwd: &Path) -> Result<PathBuf, SavePathError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(SavePathError::Empty);
+    }
+    let expanded = expand_tilde(input, home).ok_or(SavePathError::NoHome)?;
+    let path = if expanded.is_absolute() { expanded } else { cwd.join(expanded) };
+
+    if path.is_dir() {
+        return Err(SavePathError::IsDirectory(path));
+    }
+    if input.ends_with(['/', '\\']) || path.file_name().is_none() {
+        return Err(SavePathError::NoFileName);
+    }
+    let parent = path.parent().map(Path::to_path_buf).unwrap_or_else(|| cwd.to_path_buf());
+    match std::fs::metadata(&parent) {
+        Ok(meta) if !meta.is_dir() => Err(SavePathError::ParentNotDirectory(parent)),
+        Ok(meta) if meta.permissions().readonly() => Err(SavePathError::ReadOnly(parent)),
+        Ok(_) => Ok(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(SavePathError::ParentMissing(parent)),
+        Err(e) => Err(SavePathError::from_io(&e, &path)),
+    }
+}
+
+/// Open `path` for writing
+///
+/// Without `overwrite`, an existing file is never truncated (the open fails
+/// if one appeared after the path was checked).
+///
+/// # Errors
+/// The classified I/O error
+pub fn open_for_save(path: &Path, overwrite: bool) -> Result<File, SavePathError> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if overwrite {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    options.open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::AlreadyExists => SavePathError::Io(path.to_path_buf(), "file appeared while saving".to_string()),
+        _ => SavePathError::from_io(&e, path),
+    })
+}
+
+/// Path chosen in the overlay, ready to be written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChosenPath {
+    /// Absolute path
+    pub path: PathBuf,
+    /// Whether the user agreed to replace an existing file
+    pub overwrite: bool,
+}
+
+/// Save-path overlay state
+#[derive(Debug, Clone)]
+pub struct PathPicker {
+    /// What is being saved
+    pub target: SaveTarget,
+    /// Path being edited
+    pub input: String,
+    /// Existing file waiting for the overwrite answer
+    pub pending_overwrite: Option<PathBuf>,
+    /// Why the last submit was refused
+    pub error: Option<String>,
+}
+
+impl PathPicker {
+    /// Open the overlay for `target` with `suggested` pre-filled
+    pub fn new(target: SaveTarget, suggested: &Path) -> Self {
+        Self {
+            target,
+            input: suggested.display().to_string(),
+            pending_overwrite: None,
+            error: None,
+        }
+    }
+
+    /// Overlay title
+    pub fn title(&self) -> &'static str {
+        match self.target {
+            SaveTarget::ContactToken => "Save Contact Token",
+            SaveTarget::ChatExport { .. } => "Export Chat (JSON Lines)",
+            SaveTarget::ChatArchive { .. } => "Export Chat (signed, verifiable)",
+            SaveTarget::TokenBatch => "Import Tokens From File",
+            SaveTarget::ArchiveVerify => "Verify Chat Archive",
+            SaveTarget::IntroductionList => "Import Contact List (.csv or .vcf)",
+            SaveTarget::SnapshotDiff => "Export Snapshot Diff (JSON)",
+            SaveTarget::JournalExport => "Export Journal (.csv or .jsonl, signed)",
+        }
+    }
+
+    /// Append a character to the path
+    pub fn add_char(&mut self, c: char) {
+        self.input.push(c);
+        self.pending_overwrite = None;
+        self.error = None;
+    }
+
+    /// Remove the last character of the path
+    pub fn backspace(&mut self) {
+        self.input.pop();
+        self.pending_overwrite = None;
+        self.error = None;
+    }
+
+    /// Check the entered path
+    ///
+    /// # Returns
+    /// The path if it can be written now; None if it was refused (see
+    /// `error`) or names an existing file (see `pending_overwrite`)
+    pub fn submit(&mut self, home: Option<&Path>, cwd: &Path) -> Option<ChosenPath> {
+        if self.target.reads_file() {
+            return match validate_save_path(&self.input, home, cwd) {
+                Ok(path) if path.is_file() => {
+                    self.error = None;
+                    Some(ChosenPath { path, overwrite: false })
+                }
+                Ok(path) => {
+                    self.error = Some(SavePathError::NotFound(path).to_string());
+                    None
+                }
+                Err(e) => {
+                    self.error = Some(e.to_string());
+                    None
+                }
+            };
+        }
+        match validate_save_path(&self.input, home, cwd) {
+            Ok(path) if path.exists() => {
+                self.error = None;
+                self.pending_overwrite = Some(path);
+                None
+            }
+            Ok(path) => {
+                self.error = None;
+                Some(ChosenPath { path, overwrite: false })
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                None
+            }
+        }
+    }
+
+    /// Answer the overwrite prompt
+    ///
+    /// # Returns
+    /// The path to replace if the user agreed
+    pub fn answer_overwrite(&mut self, overwrite: bool) -> Option<ChosenPath> {
+        let path = self.pending_overwrite.take()?;
+        overwrite.then_some(ChosenPath { path, overwrite: true })
+    }
+
+    /// Report a failed write and keep the overlay open for another path
+    pub fn set_error(&mut self, error: &SavePathError) {
+        self.error = Some(error.to_string());
+    }
+}
@@ -0,0 +1,96 @@
+//! User-facing file locations (default save folder, `~` expansion)
+//!
+//! Everything here takes the platform and home directory as arguments, so
+//! tests can resolve paths for any OS without touching the environment.
+//! `Platform::current()` and `PathEnv::from_env()` supply the real values.
+
+use std::path::{Path, PathBuf};
+
+/// Operating system family, as far as file locations are concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// Linux and other Unix-like systems
+    Linux,
+    /// macOS
+    MacOs,
+    /// Windows
+    Windows,
+}
+
+impl Platform {
+    /// Platform this binary was built for
+    pub fn current() -> Self {
+        if cfg!(target_os = "windows") {
+            Platform::Windows
+        } else if cfg!(target_os = "macos") {
+            Platform::MacOs
+        } else {
+            Platform::Linux
+        }
+    }
+}
+
+/// Environment values that decide where files go
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathEnv {
+    /// Home directory (`HOME`, or `USERPROFILE` on Windows)
+    pub home: Option<PathBuf>,
+    /// `XDG_DOWNLOAD_DIR`, honoured on Linux only
+    pub xdg_download_dir: Option<PathBuf>,
+}
+
+impl PathEnv {
+    /// Read the values from the process environment
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+        let home_var = if Platform::current() == Platform::Windows { "USERPROFILE" } else { "HOME" };
+        Self {
+            home: var(home_var),
+            xdg_download_dir: var("XDG_DOWNLOAD_DIR"),
+        }
+    }
+}
+
+/// Folder offered when saving a file
+///
+/// On Linux, `XDG_DOWNLOAD_DIR` if it exists. Otherwise the first existing
+/// folder among Downloads and Documents in the home directory, then the
+/// home directory itself, and without one the current directory.
+///
+/// # Arguments
+/// * `platform` - Platform whose conventions apply
+/// * `env` - Home and XDG directories
+/// * `is_dir` - Directory check (`Path::is_dir` outside tests)
+pub fn default_save_dir(platform: Platform, env: &PathEnv, is_dir: impl Fn(&Path) -> bool) -> PathBuf {
+    if platform == Platform::Linux
+        && let Some(xdg) = env.xdg_download_dir.as_deref().filter(|dir| is_dir(dir))
+    {
+        return xdg.to_path_buf();
+    }
+    let Some(home) = env.home.as_deref() else {
+        return PathBuf::from(".");
+    };
+    ["Downloads", "Documents"]
+        .iter()
+        .map(|name| home.join(name))
+        .find(|dir| is_dir(dir))
+        .unwrap_or_else(|| home.to_path_buf())
+}
+
+/// Expand a leading `~` to `home`
+///
+/// Only `~` and `~/...` (or `~\...`) are expanded; `~user` is left alone.
+///
+/// # Returns
+/// None if the path starts with `~` and there is no home directory
+pub fn expand_tilde(input: &str, home: Option<&Path>) -> Option<PathBuf> {
+    let Some(rest) = input.strip_prefix('~') else {
+        return Some(PathBuf::from(input));
+    };
+    if !rest.is_empty() && !rest.starts_with('/') && !rest.starts_with('\\') {
+        return Some(PathBuf::from(input));
+    }
+    let home = home?;
+    let rest = rest.trim_start_matches(['/', '\\']);
+    Some(if rest.is_empty() { home.to_path_buf() } else { home.join(rest) })
+}
@@ -0,0 +1,171 @@
+//! Recovery when the transport server's port is taken over mid-session
+//!
+//! The transport thread runs a `PortWatchdog` while the server is up. Each
+//! tick probes `/health` on our own port and checks the boot nonce; a
+//! foreign answer marks the server `Hijacked`, repeated silence marks it
+//! `Lost`. Either way the watchdog writes an audit row, raises the port
+//! banner and rebinds at once through the startup bind loop, preferring the
+//! same port. The takeover and the recovery are also reported to the app's
+//! `ConnectivityEvents` as `Degraded` and `Restored`, and the runtime info
+//! file, if one is kept, is rewritten with the new port (or removed when no
+//! port could be bound).
+
+use crate::connectivity::ConnectivityEvents;
+use crate::storage::{remove_runtime_info, storage_db::Storage, write_runtime_info, AppState, ErrorSeverity, RuntimeInfo};
+use crate::transport::{
+    bind_verified, probe_self,
+    watchdog::{LOST_AFTER_FAILED_PROBES, MAX_BIND_ATTEMPTS},
+    ProbeOutcome, Transport,
+};
+use crate::tui::{ErrorReporter, TransportServerStatus};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// `request_logs.request_type` of port takeover audit entries
+pub const PORT_TAKEOVER_AUDIT_TYPE: &str = "port_takeover";
+
+/// How long the banner stays up after the listener is back
+pub const PORT_ALERT_SECS: u64 = 60;
+
+/// Banner text and when it was raised
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortAlert {
+    /// Banner text
+    pub text: String,
+    /// When the alert was last updated
+    pub raised_at: Instant,
+}
+
+impl PortAlert {
+    fn new(text: String) -> Self {
+        Self { text, raised_at: Instant::now() }
+    }
+
+    /// Whether the banner is still shown at `now` given the server status
+    pub fn is_visible(&self, status: &TransportServerStatus, now: Instant) -> bool {
+        matches!(status, TransportServerStatus::Hijacked(_) | TransportServerStatus::Lost(_))
+            || now.duration_since(self.raised_at) < Duration::from_secs(PORT_ALERT_SECS)
+    }
+}
+
+/// Self-probe state of a running listener
+#[derive(Debug, Clone)]
+pub struct PortWatchdog {
+    port: u16,
+    failed_probes: u32,
+    events: ConnectivityEvents,
+    runtime_info: Option<(PathBuf, RuntimeInfo)>,
+}
+
+impl PortWatchdog {
+    /// Watch the listener verified on `port`
+    pub fn new(port: u16) -> Self {
+        Self { port, failed_probes: 0, events: ConnectivityEvents::new(), runtime_info: None }
+    }
+
+    /// Report takeovers and recoveries to `events`
+    pub fn with_events(mut self, events: ConnectivityEvents) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Keep the runtime info file at `path` current after rebinds
+    pub fn with_runtime_info(mut self, path: PathBuf, info: RuntimeInfo) -> Self {
+        self.runtime_info = Some((path, info));
+        self
+    }
+
+    /// Port currently watched
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Probe once and recover if the port is no longer ours
+    ///
+    /// On a takeover the status goes `Hijacked`/`Lost` → `Running(port)` (or
+    /// `Failed` if no port could be bound), and the running port is saved
+    /// like at startup, in the database and the runtime info file. Failures
+    /// to write the audit row, the port or the file are reported to `reports`.
+    ///
+    /// # Returns
+    /// What the probe found
+    pub async fn tick(
+        &mut self,
+        transport: &Transport,
+        status: &Mutex<TransportServerStatus>,
+        storage: &Storage,
+        alert: &Mutex<Option<PortAlert>>,
+        reports: &ErrorReporter,
+    ) -> ProbeOutcome {
+        let outcome = probe_self(self.port, &transport.boot_nonce()).await;
+        let lost_status = match outcome {
+            ProbeOutcome::Ours => {
+                self.failed_probes = 0;
+                return outcome;
+            }
+            ProbeOutcome::Unreachable => {
+                self.failed_probes += 1;
+                if self.failed_probes < LOST_AFTER_FAILED_PROBES {
+                    return outcome;
+                }
+                TransportServerStatus::Lost(self.port)
+            }
+            ProbeOutcome::Foreign => TransportServerStatus::Hijacked(self.port),
+        };
+        self.failed_probes = 0;
+
+        let what = match lost_status {
+            TransportServerStatus::Hijacked(_) => "answered by another process",
+            _ => "no longer answering",
+        };
+        tracing::error!("Transport port {} is {}, rebinding", self.port, what);
+        *status.lock().unwrap() = lost_status;
+        *alert.lock().unwrap() = Some(PortAlert::new(format!("Port {} is {} — rebinding…", self.port, what)));
+        self.events.observe_degraded(format!("Port {} is {}", self.port, what));
+        let logged = storage.log_request(
+            "incoming",
+            PORT_TAKEOVER_AUDIT_TYPE,
+            None,
+            Some(&format!("127.0.0.1:{}", self.port)),
+            None,
+            false,
+            Some(&format!("port {} {}", self.port, what)),
+            None,
+        );
+        reports.check(ErrorSeverity::Warning, "port watchdog", logged);
+
+        // Our listener may still hold the socket; the next start replaces it
+        transport.stop();
+        match bind_verified(transport, self.port, MAX_BIND_ATTEMPTS).await {
+            Ok(port) => {
+                let text = if port == self.port {
+                    format!("Port {} was {} — listening again", self.port, what)
+                } else {
+                    format!("Port {} was {} — now listening on {}, share a new token", self.port, what, port)
+                };
+                self.events.observe_restored(text.clone());
+                *alert.lock().unwrap() = Some(PortAlert::new(text));
+                *status.lock().unwrap() = TransportServerStatus::Running(port);
+                if let Some(mut app_state) = reports.check(ErrorSeverity::Error, "port watchdog", AppState::load_from_db(storage)) {
+                    app_state.user_port = port;
+                    reports.check(ErrorSeverity::Error, "port watchdog", app_state.save_to_db(storage));
+                }
+                if let Some((path, info)) = &mut self.runtime_info {
+                    info.port = port;
+                    reports.check(ErrorSeverity::Warning, "port watchdog", write_runtime_info(path, info));
+                }
+                self.port = port;
+            }
+            Err(error) => {
+                tracing::error!("{}", error);
+                *alert.lock().unwrap() = Some(PortAlert::new(format!("Port {} is {} — could not rebind", self.port, what)));
+                *status.lock().unwrap() = TransportServerStatus::Failed(error);
+                if let Some((path, info)) = &self.runtime_info {
+                    reports.check(ErrorSeverity::Warning, "port watchdog", remove_runtime_info(path, info.pid));
+                }
+            }
+        }
+        outcome
+    }
+}
@@ -0,0 +1,167 @@
+//! Recovery after a session that ended uncleanly
+//!
+//! When the session marker (see `storage::session_marker`) shows that the
+//! previous session did not exit cleanly, `App::start_session` runs every
+//! `RecoveryStep` at once, in order, instead of leaving the half-finished
+//! state to be noticed later:
+//!
+//! 1. `Journal` - the delivery journal's hash chain is checked, since a crash
+//!    can cut an append short
+//! 2. `SendLeases` - messages whose send was in flight are queued for retry
+//! 3. `Integrity` - SQLite's quick check and the identity scan
+//! 4. `Migration` - an interrupted message queue migration is finished
+//! 5. `RuntimeFile` - a runtime info file naming the dead process is removed
+//!
+//! The `RecoveryReport` is shown once, before the main menu. What a step
+//! could not put right is listed as a problem, reported to the error log,
+//! and stated in the headline; it is never only logged.
+
+use crate::storage::SessionMarker;
+use chrono::{DateTime, Utc};
+
+/// One reconciliation run after an unclean exit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryStep {
+    /// Hash chain of the outbound delivery journal
+    Journal,
+    /// Sends the previous session died in the middle of
+    SendLeases,
+    /// Database quick check and stored identities
+    Integrity,
+    /// Message queue schema migration left unfinished
+    Migration,
+    /// Runtime info file of the previous process
+    RuntimeFile,
+}
+
+impl RecoveryStep {
+    /// Every step, in the order they run
+    pub const ALL: [RecoveryStep; 5] = [
+        RecoveryStep::Journal,
+        RecoveryStep::SendLeases,
+        RecoveryStep::Integrity,
+        RecoveryStep::Migration,
+        RecoveryStep::RuntimeFile,
+    ];
+
+    /// Name shown in front of the step's details
+    pub fn label(&self) -> &'static str {
+        match self {
+            RecoveryStep::Journal => "Delivery journal",
+            RecoveryStep::SendLeases => "Unsent messages",
+            RecoveryStep::Integrity => "Database integrity",
+            RecoveryStep::Migration => "Queue migration",
+            RecoveryStep::RuntimeFile => "Runtime file",
+        }
+    }
+}
+
+/// What one step did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepOutcome {
+    /// The step
+    pub step: RecoveryStep,
+    /// Part of the headline, e.g. "recovered 2 unsent messages" (None if
+    /// there was nothing to do)
+    pub done: Option<String>,
+    /// One line per item handled
+    pub details: Vec<String>,
+    /// What the step found and could not fix
+    pub problems: Vec<String>,
+}
+
+impl StepOutcome {
+    /// Outcome of `step` that had nothing to do
+    pub fn new(step: RecoveryStep) -> Self {
+        Self { step, done: None, details: Vec::new(), problems: Vec::new() }
+    }
+
+    /// Set the headline part
+    pub fn done(mut self, done: impl Into<String>) -> Self {
+        self.done = Some(done.into());
+        self
+    }
+
+    /// Add a detail line
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.details.push(detail.into());
+        self
+    }
+
+    /// Add a problem that could not be fixed
+    pub fn problem(mut self, problem: impl Into<String>) -> Self {
+        self.problems.push(problem.into());
+        self
+    }
+}
+
+/// Everything recovery did, shown once after an unclean exit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Marker the previous session left behind
+    pub previous: SessionMarker,
+    /// Outcome of each step, in the order they ran
+    pub steps: Vec<StepOutcome>,
+}
+
+impl RecoveryReport {
+    /// Problems of every step, each with its step's label
+    pub fn problems(&self) -> Vec<String> {
+        self.steps
+            .iter()
+            .flat_map(|outcome| outcome.problems.iter().map(|problem| format!("{}: {}", outcome.step.label(), problem)))
+            .collect()
+    }
+
+    /// Whether any step found something it could not fix
+    pub fn has_problems(&self) -> bool {
+        self.steps.iter().any(|outcome| !outcome.problems.is_empty())
+    }
+
+    /// One-line summary, e.g. "Previous session ended unexpectedly — recovered
+    /// 2 unsent messages, verified database integrity, resumed 1 migration"
+    pub fn headline(&self) -> String {
+        let done: Vec<&str> = self.steps.iter().filter_map(|outcome| outcome.done.as_deref()).collect();
+        let mut headline = "Previous session ended unexpectedly".to_string();
+        if done.is_empty() {
+            headline.push_str(" — nothing needed recovering");
+        } else {
+            headline.push_str(" — ");
+            headline.push_str(&done.join(", "));
+        }
+        match self.problems().len() {
+            0 => {}
+            1 => headline.push_str(". 1 problem could not be fixed"),
+            n => headline.push_str(&format!(". {} problems could not be fixed", n)),
+        }
+        headline
+    }
+
+    /// Detail lines of every step, each with its step's label
+    pub fn detail_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(started) = self.previous.started() {
+            lines.push(format!("Previous session: pid {}, started {}", self.previous.pid, format_started(started)));
+        } else {
+            lines.push("Previous session: marker unreadable".to_string());
+        }
+        for outcome in &self.steps {
+            if outcome.details.is_empty() {
+                lines.push(format!("{}: nothing to do", outcome.step.label()));
+            }
+            for detail in &outcome.details {
+                lines.push(format!("{}: {}", outcome.step.label(), detail));
+            }
+        }
+        lines
+    }
+}
+
+fn format_started(started: DateTime<Utc>) -> String {
+    started.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// "1 unsent message", "2 unsent messages"
+pub fn count_phrase(count: usize, singular: &str, plural: &str) -> String {
+    format!("{} {}", count, if count == 1 { singular } else { plural })
+}
@@ -2,9 +2,30 @@
 
 use chrono::{DateTime, Duration, Utc};
 use crate::crypto::KeyPair;
-use crate::storage::{generate_contact_token, parse_contact_token, Contact};
+use crate::storage::{
+    armor_token, best_match, generate_contact_token, generate_multi_endpoint_token, generate_pinned_token, has_armor_footer, is_armored,
+    parse_contact_token, pinned_endpoints, token_from_input,
+    token_expires_soon, Chat, Contact, graphemes, ContactEndpoint, EphemeralLifetime, IntroductionRowError, Message,
+    PendingIntroduction, TrustTier, MAX_CONTACT_NOTES_BYTES, MAX_MESSAGE_BYTES,
+};
 use crate::tui::clipboard::{ClipboardProvider, RealClipboard, ClipboardError};
-use std::fs;
+use crate::tui::contact_import::split_name_guess;
+use crate::tui::filter::FilterList;
+use crate::tui::recovery::RecoveryReport;
+use crate::tui::types::Screen;
+use crate::queue_dead_letters::FailedMessage;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// An endpoint offered in the Share Contact endpoint editor
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointEntry {
+    /// The endpoint as it will appear in the token
+    pub endpoint: ContactEndpoint,
+    /// Whether the endpoint is included in the generated token
+    pub included: bool,
+    /// Whether the endpoint came from connectivity detection (cannot be removed)
+    pub detected: bool,
+}
 
 /// Share Contact screen state
 #[derive(Debug)]
@@ -15,25 +36,266 @@ pub struct ShareContactScreen {
     pub expiry: DateTime<Utc>,
     /// Status message (for copy/save feedback)
     pub status_message: Option<String>,
+    /// Endpoints offered for the token, in advertised order
+    pub endpoints: Vec<EndpointEntry>,
+    /// Index of the selected endpoint in the editor
+    pub selected_endpoint: usize,
+    /// Whether the endpoint editor is open
+    pub editing_endpoints: bool,
+    /// Input for a new endpoint (`<address> [label] [valid days]`), while adding one
+    pub new_endpoint_input: Option<String>,
+    /// Fingerprint of our TLS certificate, pinned by the token when set
+    pub cert_fingerprint: Option<String>,
+    /// Armored form of the token, while that view is shown (see `token_armor`)
+    pub armored: Option<String>,
 }
 
 impl ShareContactScreen {
     /// Create new share contact screen
+    ///
+    /// The detected local address is the initial (and only) endpoint.
     pub fn new(keypair: &KeyPair, local_ip: &str) -> Self {
         // Default: 1 day expiry
         let expiry = Utc::now() + Duration::days(1);
-        let token = generate_contact_token(
-            local_ip,
-            &keypair.public_key,
-            &keypair.private_key,
-            &keypair.x25519_public,
-            expiry,
-        ).expect("Failed to generate contact token");
 
-        Self {
-            token,
+        let mut screen = Self {
+            token: String::new(),
             expiry,
             status_message: None,
+            endpoints: vec![EndpointEntry {
+                endpoint: ContactEndpoint::new(local_ip, "detected"),
+                included: true,
+                detected: true,
+            }],
+            selected_endpoint: 0,
+            editing_endpoints: false,
+            new_endpoint_input: None,
+            cert_fingerprint: None,
+            armored: None,
+        };
+        screen.regenerate_token(keypair);
+        screen
+    }
+
+    /// Pin our TLS certificate in the token (or stop pinning with `None`)
+    pub fn set_cert_fingerprint(&mut self, keypair: &KeyPair, fingerprint: Option<String>) {
+        self.cert_fingerprint = fingerprint;
+        self.regenerate_token(keypair);
+    }
+
+    /// Endpoints that will be advertised in the token, in order
+    pub fn included_endpoints(&self) -> Vec<ContactEndpoint> {
+        self.endpoints
+            .iter()
+            .filter(|e| e.included)
+            .map(|e| e.endpoint.clone())
+            .collect()
+    }
+
+    /// Generate the token from the included endpoints
+    ///
+    /// A single endpoint without an expiry produces a single-endpoint token,
+    /// readable by every version. With a certificate fingerprint, each plain
+    /// endpoint is also advertised over TLS, ahead of the plain ones. Keeps
+    /// the previous token and returns false if no endpoint is included.
+    pub fn regenerate_token(&mut self, keypair: &KeyPair) -> bool {
+        let endpoints = self.included_endpoints();
+        let result = match (endpoints.as_slice(), &self.cert_fingerprint) {
+            ([], _) => {
+                self.status_message = Some("Include at least one endpoint".to_string());
+                return false;
+            }
+            (_, Some(fingerprint)) => generate_pinned_token(
+                &pinned_endpoints(&endpoints),
+                fingerprint,
+                &keypair.public_key,
+                &keypair.private_key,
+                &keypair.x25519_public,
+                self.expiry,
+            ),
+            ([only], None) if only.valid_until.is_none() => generate_contact_token(
+                &only.address,
+                &keypair.public_key,
+                &keypair.private_key,
+                &keypair.x25519_public,
+                self.expiry,
+            ),
+            (_, None) => generate_multi_endpoint_token(
+                &endpoints,
+                &keypair.public_key,
+                &keypair.private_key,
+                &keypair.x25519_public,
+                self.expiry,
+            ),
+        };
+
+        match result {
+            Ok(token) => {
+                self.token = token;
+                if self.armored.is_some() {
+                    self.armored = armor_token(&self.token).ok();
+                }
+                true
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Token generation failed: {}", e));
+                false
+            }
+        }
+    }
+
+    /// Switch between the base64 token and its armored form
+    ///
+    /// The armored form is for reading aloud or copying by hand; copying
+    /// and saving use whichever form is shown.
+    pub fn toggle_armored(&mut self) {
+        self.armored = match self.armored {
+            Some(_) => None,
+            None => match armor_token(&self.token) {
+                Ok(armored) => Some(armored),
+                Err(e) => {
+                    self.status_message = Some(format!("Armoring failed: {}", e));
+                    None
+                }
+            },
+        };
+    }
+
+    /// The token as currently shown (armored or base64)
+    pub fn shown_token(&self) -> &str {
+        self.armored.as_deref().unwrap_or(&self.token)
+    }
+
+    /// Open the endpoint editor
+    pub fn open_endpoint_editor(&mut self) {
+        self.editing_endpoints = true;
+        self.selected_endpoint = self.selected_endpoint.min(self.endpoints.len().saturating_sub(1));
+    }
+
+    /// Close the endpoint editor and regenerate the token
+    ///
+    /// The editor stays open if no endpoint is included.
+    pub fn close_endpoint_editor(&mut self, keypair: &KeyPair) {
+        self.new_endpoint_input = None;
+        if self.regenerate_token(keypair) {
+            self.editing_endpoints = false;
+            self.status_message = Some(format!(
+                "Token updated ({} endpoint(s))",
+                self.included_endpoints().len()
+            ));
+        }
+    }
+
+    /// Select the next endpoint
+    pub fn next_endpoint(&mut self) {
+        if !self.endpoints.is_empty() {
+            self.selected_endpoint = (self.selected_endpoint + 1) % self.endpoints.len();
+        }
+    }
+
+    /// Select the previous endpoint
+    pub fn previous_endpoint(&mut self) {
+        if !self.endpoints.is_empty() {
+            self.selected_endpoint = if self.selected_endpoint == 0 {
+                self.endpoints.len() - 1
+            } else {
+                self.selected_endpoint - 1
+            };
+        }
+    }
+
+    /// Include or exclude the selected endpoint
+    pub fn toggle_endpoint(&mut self) {
+        if let Some(entry) = self.endpoints.get_mut(self.selected_endpoint) {
+            entry.included = !entry.included;
+        }
+    }
+
+    /// Move the selected endpoint one place earlier in the advertised order
+    pub fn move_endpoint_up(&mut self) {
+        if self.selected_endpoint > 0 && self.selected_endpoint < self.endpoints.len() {
+            self.endpoints.swap(self.selected_endpoint, self.selected_endpoint - 1);
+            self.selected_endpoint -= 1;
+        }
+    }
+
+    /// Move the selected endpoint one place later in the advertised order
+    pub fn move_endpoint_down(&mut self) {
+        if self.selected_endpoint + 1 < self.endpoints.len() {
+            self.endpoints.swap(self.selected_endpoint, self.selected_endpoint + 1);
+            self.selected_endpoint += 1;
+        }
+    }
+
+    /// Remove the selected endpoint (detected endpoints can only be excluded)
+    pub fn remove_endpoint(&mut self) {
+        match self.endpoints.get(self.selected_endpoint) {
+            Some(entry) if entry.detected => {
+                self.status_message = Some("Detected endpoints can be excluded, not removed".to_string());
+            }
+            Some(_) => {
+                self.endpoints.remove(self.selected_endpoint);
+                self.selected_endpoint = self.selected_endpoint.min(self.endpoints.len().saturating_sub(1));
+            }
+            None => {}
+        }
+    }
+
+    /// Start typing a new endpoint
+    pub fn start_adding_endpoint(&mut self) {
+        self.new_endpoint_input = Some(String::new());
+    }
+
+    /// Discard the endpoint being typed
+    pub fn cancel_adding_endpoint(&mut self) {
+        self.new_endpoint_input = None;
+    }
+
+    /// Add a character to the new endpoint input
+    pub fn add_char(&mut self, c: char) {
+        if let Some(input) = &mut self.new_endpoint_input {
+            input.push(c);
+        }
+    }
+
+    /// Remove the last character from the new endpoint input
+    pub fn backspace(&mut self) {
+        if let Some(input) = &mut self.new_endpoint_input {
+            input.pop();
+        }
+    }
+
+    /// Parse the new endpoint input and append it to the list
+    ///
+    /// Input format: `<address:port> [label] [valid days]`.
+    ///
+    /// # Returns
+    /// True if the endpoint was added
+    pub fn confirm_endpoint(&mut self) -> bool {
+        let Some(input) = &self.new_endpoint_input else {
+            return false;
+        };
+
+        match parse_endpoint_input(input) {
+            Ok(endpoint) if self.endpoints.iter().any(|e| e.endpoint.address == endpoint.address) => {
+                self.status_message = Some(format!("{} is already in the list", endpoint.address));
+                false
+            }
+            Ok(endpoint) => {
+                self.endpoints.push(EndpointEntry {
+                    endpoint,
+                    included: true,
+                    detected: false,
+                });
+                self.selected_endpoint = self.endpoints.len() - 1;
+                self.new_endpoint_input = None;
+                self.status_message = None;
+                true
+            }
+            Err(e) => {
+                self.status_message = Some(e);
+                false
+            }
         }
     }
 
@@ -49,7 +311,7 @@ impl ShareContactScreen {
     {
         match clipboard_result {
             Ok(clipboard) => {
-                match clipboard.set_text(&self.token) {
+                match clipboard.set_text(self.shown_token()) {
                     Ok(_) => self.status_message = Some("Copied to clipboard!".to_string()),
                     Err(e) => self.status_message = Some(format!("Copy failed: {}. Use 's' to save to file", e)),
                 }
@@ -58,16 +320,41 @@ impl ShareContactScreen {
         }
     }
 
-    /// Save token to file
-    pub fn save_to_file(&mut self) {
-        let filename = format!("contact_token_{}.txt", Utc::now().format("%Y%m%d_%H%M%S"));
-        match fs::write(&filename, &self.token) {
-            Ok(_) => self.status_message = Some(format!("Saved to {}", filename)),
-            Err(e) => self.status_message = Some(format!("Save failed: {}", e)),
-        }
+    /// Suggested file name for saving the token (see `App::open_path_picker`)
+    pub fn token_file_name() -> String {
+        format!("contact_token_{}.txt", Utc::now().format("%Y%m%d_%H%M%S"))
     }
 }
 
+/// Parse `<address:port> [label] [valid days]` into an endpoint
+fn parse_endpoint_input(input: &str) -> std::result::Result<ContactEndpoint, String> {
+    let mut parts = input.split_whitespace();
+    let address = parts.next().ok_or("Enter an address as host:port")?;
+
+    let valid_port = address
+        .rsplit_once(':')
+        .filter(|(host, _)| !host.is_empty())
+        .and_then(|(_, port)| port.parse::<u16>().ok())
+        .is_some_and(|port| port != 0);
+    if !valid_port {
+        return Err(format!("Invalid address '{}' (expected host:port)", address));
+    }
+
+    let mut endpoint = ContactEndpoint::new(address, parts.next().unwrap_or("manual"));
+    if let Some(days) = parts.next() {
+        let days: i64 = days
+            .parse()
+            .ok()
+            .filter(|d| *d > 0)
+            .ok_or_else(|| format!("Invalid validity '{}' (expected days)", days))?;
+        endpoint.valid_until = Some(Utc::now() + Duration::days(days));
+    }
+    if parts.next().is_some() {
+        return Err("Too many fields (expected address, label, days)".to_string());
+    }
+    Ok(endpoint)
+}
+
 /// Import Contact screen state
 #[derive(Debug)]
 pub struct ImportContactScreen {
@@ -79,6 +366,22 @@ pub struct ImportContactScreen {
     pub status_message: Option<String>,
     /// Whether the status is an error
     pub is_error: bool,
+    /// Import as a temporary contact with this lifetime (None: permanent)
+    pub temporary: Option<EphemeralLifetime>,
+    /// Trust tier given to imported contacts
+    pub trust: TrustTier,
+    /// Batch mode: many tokens, one per line (None: single token)
+    pub batch: Option<crate::tui::contact_import::BatchImport>,
+    /// Warning for a parsed token about to expire (imported anyway)
+    pub expiry_warning: Option<String>,
+    /// Pending introductions awaiting a token (see `storage::introductions`)
+    pub pending: Vec<PendingIntroduction>,
+    /// ID of the pending introduction the next imported token is linked to
+    pub link: Option<String>,
+    /// Whether `link` was chosen with Ctrl+L rather than matched by name
+    pub link_chosen: bool,
+    /// Rows skipped when the last contact list was read
+    pub list_errors: Vec<IntroductionRowError>,
 }
 
 impl ImportContactScreen {
@@ -89,23 +392,115 @@ impl ImportContactScreen {
             parsed_contact: None,
             status_message: Some("Paste contact token and press Enter to import".to_string()),
             is_error: false,
+            temporary: None,
+            trust: TrustTier::Normal,
+            batch: None,
+            expiry_warning: None,
+            pending: Vec::new(),
+            link: None,
+            link_chosen: false,
+            list_errors: Vec::new(),
+        }
+    }
+
+    /// Replace the pending introductions shown and offered for linking
+    pub fn set_pending(&mut self, pending: Vec<PendingIntroduction>) {
+        self.pending = pending;
+        if self.link.as_ref().is_some_and(|id| !self.pending.iter().any(|intro| &intro.id == id)) {
+            self.link = None;
+            self.link_chosen = false;
+        }
+        self.suggest_link();
+    }
+
+    /// Link to the pending introduction matching the name typed before the
+    /// token, unless one was chosen by hand
+    pub fn suggest_link(&mut self) {
+        if self.link_chosen || self.batch.is_some() {
+            return;
+        }
+        let name = if is_armored(&self.input) { None } else { split_name_guess(&self.input).0 };
+        self.link = name.and_then(|name| best_match(&name, &self.pending)).map(|intro| intro.id.clone());
+    }
+
+    /// Step the link through the pending introductions, then to none
+    pub fn cycle_link(&mut self) {
+        let next = match &self.link {
+            None => 0,
+            Some(id) => self.pending.iter().position(|intro| &intro.id == id).map_or(0, |index| index + 1),
+        };
+        self.link = self.pending.get(next).map(|intro| intro.id.clone());
+        self.link_chosen = true;
+    }
+
+    /// Pending introduction the next imported token is linked to
+    pub fn linked_introduction(&self) -> Option<&PendingIntroduction> {
+        let id = self.link.as_ref()?;
+        self.pending.iter().find(|intro| &intro.id == id)
+    }
+
+    /// Switch between single-token and batch mode (the input is kept)
+    pub fn toggle_batch(&mut self) {
+        self.parsed_contact = None;
+        self.is_error = false;
+        self.link = None;
+        self.link_chosen = false;
+        if self.batch.take().is_some() {
+            self.status_message = Some("Paste contact token and press Enter to import".to_string());
+            self.suggest_link();
+        } else {
+            self.batch = Some(crate::tui::contact_import::BatchImport::new());
+            self.status_message =
+                Some("Batch: one token per line. Ctrl+R: review | Ctrl+O: read file | Ctrl+B: single".to_string());
         }
     }
 
+    /// Leave the review table or report and edit the tokens again
+    pub fn back_to_batch_input(&mut self) {
+        if let Some(batch) = &mut self.batch {
+            *batch = crate::tui::contact_import::BatchImport::new();
+            self.status_message = Some("Batch: one token per line. Ctrl+R: review | Ctrl+O: read file".to_string());
+            self.is_error = false;
+        }
+    }
+
+    /// Step the temporary import toggle: permanent → 24h → 7d → permanent
+    pub fn cycle_temporary(&mut self) {
+        self.temporary = EphemeralLifetime::cycle(self.temporary);
+    }
+
+    /// Switch the trust toggle between normal and restricted
+    pub fn toggle_trust(&mut self) {
+        self.trust = self.trust.toggled();
+    }
+
     /// Add character to input
     pub fn add_char(&mut self, c: char) {
         self.input.push(c);
+        self.suggest_link();
     }
 
     /// Remove last character from input
     pub fn backspace(&mut self) {
         self.input.pop();
+        self.suggest_link();
+    }
+
+    /// Whether an armored token is being typed and has no footer yet
+    ///
+    /// Enter then starts the next line instead of importing.
+    pub fn awaits_armor_lines(&self) -> bool {
+        is_armored(&self.input) && !has_armor_footer(&self.input)
     }
 
     /// Clear input and reset state
     pub fn clear(&mut self) {
         self.input.clear();
         self.parsed_contact = None;
+        self.expiry_warning = None;
+        self.link = None;
+        self.link_chosen = false;
+        self.list_errors.clear();
         self.status_message = Some("Input cleared. Paste contact token and press Enter".to_string());
         self.is_error = false;
     }
@@ -125,6 +520,7 @@ impl ImportContactScreen {
                 match clipboard.get_text() {
                     Ok(text) => {
                         self.input = text.trim().to_string();
+                        self.suggest_link();
                         self.status_message = Some("Pasted from clipboard. Press Enter to import".to_string());
                         self.is_error = false;
                     }
@@ -143,13 +539,16 @@ impl ImportContactScreen {
 
     /// Parse input token
     pub fn parse_token(&mut self) {
+        self.expiry_warning = None;
         if self.input.is_empty() {
             self.status_message = Some("Error: Token is empty".to_string());
             self.is_error = true;
             return;
         }
 
-        match parse_contact_token(&self.input) {
+        // A name before a plain token ("Alice: <token>") only picks the link
+        let input = if is_armored(&self.input) { self.input.as_str() } else { split_name_guess(&self.input).1 };
+        match token_from_input(input).and_then(|token| parse_contact_token(&token)) {
             Ok(contact) => {
                 self.parsed_contact = Some(contact.clone());
                 self.status_message = Some(format!(
@@ -167,6 +566,21 @@ impl ImportContactScreen {
         }
     }
 
+    /// Warn if the parsed token has less than `min_minutes` of validity left
+    ///
+    /// Unlike a token arriving in a ping, a pasted one is still imported:
+    /// the user may have been handed it on purpose.
+    pub fn check_token_expiry(&mut self, now: DateTime<Utc>, min_minutes: u32) {
+        self.expiry_warning = self
+            .parsed_contact
+            .as_ref()
+            .filter(|contact| token_expires_soon(contact, now, min_minutes))
+            .map(|contact| {
+                let left = contact.expiry.signed_duration_since(now).num_seconds();
+                format!("Token expires in {}: ask for a fresh one", DiagnosticsScreen::format_time_remaining(left))
+            });
+    }
+
     /// Get parsed contact
     pub fn get_contact(&self) -> Option<&Contact> {
         self.parsed_contact.as_ref()
@@ -184,6 +598,18 @@ pub struct ChatListScreen {
     pub show_delete_confirmation: bool,
     /// Index of chat pending deletion
     pub pending_delete_index: Option<usize>,
+    /// Contact details popup (when open)
+    pub contact_details: Option<ContactDetailsPopup>,
+    /// Selected entry of the identity conflict review popup (when open)
+    pub conflict_review: Option<usize>,
+    /// Position Tab jumped away from and the chat it jumped to, so the next
+    /// Tab jumps back
+    pub tab_return: Option<(usize, usize)>,
+    /// Chats marked for a bulk action (Space, V), by contact UID so the
+    /// marks follow their chats when the list changes order
+    pub marked: BTreeSet<String>,
+    /// Whether the bulk delete confirmation popup is shown
+    pub show_bulk_delete_confirmation: bool,
 }
 
 impl ChatListScreen {
@@ -194,6 +620,47 @@ impl ChatListScreen {
             status_message: None,
             show_delete_confirmation: false,
             pending_delete_index: None,
+            contact_details: None,
+            conflict_review: None,
+            tab_return: None,
+            marked: BTreeSet::new(),
+            show_bulk_delete_confirmation: false,
+        }
+    }
+
+    /// Mark or unmark the chat with `contact_uid`
+    pub fn toggle_marked(&mut self, contact_uid: &str) {
+        if !self.marked.remove(contact_uid) {
+            self.marked.insert(contact_uid.to_string());
+        }
+    }
+
+    /// Whether the chat with `contact_uid` is marked
+    pub fn is_marked(&self, contact_uid: &str) -> bool {
+        self.marked.contains(contact_uid)
+    }
+
+    /// Marked chats still in `chats`, in list order
+    pub fn marked_in<'a>(&self, chats: &'a [Chat]) -> Vec<&'a Chat> {
+        chats.iter().filter(|chat| self.marked.contains(&chat.contact_uid)).collect()
+    }
+
+    /// Toggle the selection between the current position and `recent`
+    ///
+    /// The first Tab selects `recent` (the most recently opened chat, or the
+    /// one before it when that is already selected); the next Tab returns to
+    /// where the selection was, unless it was moved in between.
+    pub fn toggle_recent(&mut self, recent: &[usize], chat_count: usize) {
+        if let Some((back, jumped_to)) = self.tab_return.take()
+            && jumped_to == self.selected_index
+            && back < chat_count
+        {
+            self.selected_index = back;
+            return;
+        }
+        if let Some(&target) = recent.iter().find(|&&i| i != self.selected_index && i < chat_count) {
+            self.tab_return = Some((self.selected_index, target));
+            self.selected_index = target;
         }
     }
 
@@ -238,17 +705,199 @@ impl ChatListScreen {
     }
 }
 
+/// Contact details popup state
+#[derive(Debug)]
+pub struct ContactDetailsPopup {
+    /// UID of the contact being shown
+    pub contact_uid: String,
+    /// Whether the full notes are shown (instead of the first two lines)
+    pub notes_expanded: bool,
+    /// Notes editor overlay (when editing)
+    pub notes_editor: Option<NotesEditor>,
+    /// Whether the keep/discard notes prompt for contact deletion is shown
+    pub confirm_delete: bool,
+    /// Consent prompt for a debug capture of this contact, when shown
+    pub capture_prompt: Option<CapturePrompt>,
+    /// UTC offset being typed (when editing; empty infers it again)
+    pub offset_input: Option<String>,
+}
+
+/// Steps of the consent prompt for a debug capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapturePrompt {
+    /// Capture with bodies reduced to length and hash?
+    Confirm,
+    /// Second confirmation: write the bodies themselves?
+    ConfirmBodies,
+}
+
+impl ContactDetailsPopup {
+    /// Number of note lines shown when collapsed
+    pub const PREVIEW_LINES: usize = 2;
+
+    /// Create new contact details popup
+    pub fn new(contact_uid: String) -> Self {
+        Self {
+            contact_uid,
+            notes_expanded: false,
+            notes_editor: None,
+            confirm_delete: false,
+            capture_prompt: None,
+            offset_input: None,
+        }
+    }
+
+    /// Longest UTC offset input ("UTC+05:30" plus a spare)
+    pub const MAX_OFFSET_INPUT_CHARS: usize = 10;
+
+    /// Type into the UTC offset input: digits, sign, ':' and "UTC"
+    pub fn offset_add_char(&mut self, c: char) {
+        if let Some(input) = &mut self.offset_input
+            && (c.is_ascii_digit() || "+-:UTCutc".contains(c))
+            && input.chars().count() < Self::MAX_OFFSET_INPUT_CHARS
+        {
+            input.push(c);
+        }
+    }
+
+    /// Toggle between notes preview and full notes
+    pub fn toggle_notes_expanded(&mut self) {
+        self.notes_expanded = !self.notes_expanded;
+    }
+}
+
+/// Multi-line editor for contact notes
+#[derive(Debug)]
+pub struct NotesEditor {
+    /// Text being edited
+    pub buffer: String,
+    /// Status message (e.g., size limit reached)
+    pub status_message: Option<String>,
+}
+
+impl NotesEditor {
+    /// Create new notes editor with existing notes
+    pub fn new(notes: &str) -> Self {
+        Self {
+            buffer: notes.to_string(),
+            status_message: None,
+        }
+    }
+
+    /// Add character to buffer (rejected once the size limit is reached)
+    pub fn add_char(&mut self, c: char) {
+        if self.buffer.len() + c.len_utf8() > MAX_CONTACT_NOTES_BYTES {
+            self.status_message = Some(format!("Notes limited to {} bytes", MAX_CONTACT_NOTES_BYTES));
+            return;
+        }
+        self.buffer.push(c);
+    }
+
+    /// Insert a line break
+    pub fn newline(&mut self) {
+        self.add_char('\n');
+    }
+
+    /// Remove last character from buffer
+    pub fn backspace(&mut self) {
+        self.buffer.pop();
+        self.status_message = None;
+    }
+
+    /// Number of bytes left before the size limit
+    pub fn remaining_bytes(&self) -> usize {
+        MAX_CONTACT_NOTES_BYTES.saturating_sub(self.buffer.len())
+    }
+}
+
+/// Number of messages the chat view scrolls by (one page of history)
+pub const CHAT_VIEW_PAGE_SIZE: usize = 10;
+
+/// Share of `MAX_MESSAGE_BYTES` (percent) from which the input counter shows bytes
+pub const BYTE_COUNTER_PERCENT: usize = 80;
+
+/// Counter shown under the chat input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputCounter {
+    /// Characters typed, while well under the limit
+    Chars(usize),
+    /// Encoded bytes, once near the limit
+    Bytes(usize),
+    /// Encoded bytes over the limit (sending is refused)
+    TooLarge(usize),
+}
+
+impl InputCounter {
+    /// Counter for `text`, whose request encodes to `encoded_bytes`
+    ///
+    /// Characters are counted as graphemes, so an accented letter or an emoji
+    /// sequence counts once.
+    pub fn new(text: &str, encoded_bytes: usize) -> Self {
+        if encoded_bytes > MAX_MESSAGE_BYTES {
+            Self::TooLarge(encoded_bytes)
+        } else if encoded_bytes >= MAX_MESSAGE_BYTES * BYTE_COUNTER_PERCENT / 100 {
+            Self::Bytes(encoded_bytes)
+        } else {
+            Self::Chars(graphemes(text).len())
+        }
+    }
+
+    /// Text shown in the input box footer
+    pub fn label(&self) -> String {
+        match self {
+            Self::Chars(count) => format!("{} chars", count),
+            Self::Bytes(bytes) => format!("{} / {} bytes", bytes, MAX_MESSAGE_BYTES),
+            Self::TooLarge(bytes) => format!("{} / {} bytes - too large", bytes, MAX_MESSAGE_BYTES),
+        }
+    }
+
+    /// Whether sending is refused
+    pub fn is_over_limit(&self) -> bool {
+        matches!(self, Self::TooLarge(_))
+    }
+}
+
 /// Chat View screen state
 #[derive(Debug)]
 pub struct ChatViewScreen {
     /// UID of the contact we're chatting with
     pub contact_uid: String,
-    /// Input buffer for message composition
+    /// Input buffer for message composition (may span several lines)
     pub input: String,
+    /// Cursor position in the input, in characters
+    pub cursor: usize,
+    /// Template picker opened with '%' (None when closed)
+    pub template_picker: Option<FilterList>,
     /// Scroll offset for message history
     pub scroll_offset: usize,
     /// Status message
     pub status_message: Option<String>,
+    /// Whether the message details popup (metadata pairs) is shown
+    pub show_message_details: bool,
+    /// Message selected in selection mode (None when not selecting)
+    pub selected_message_id: Option<String>,
+    /// Show only starred messages
+    pub starred_only: bool,
+    /// Show each message's recorded protection (see `storage::protection`)
+    pub show_protection: bool,
+    /// Entry of the pinned strip that has focus (None when the strip is not focused)
+    pub pinned_focus: Option<usize>,
+    /// Waiting for "send duplicate? [y/N]" after repeating the previous message
+    pub duplicate_prompt: bool,
+    /// Send preview waiting for Enter (send) or Esc (keep the input)
+    pub send_preview: Option<super::send_preview::SendPreview>,
+    /// Token expiry warning waiting for Enter (send), r (send and request a
+    /// refresh) or Esc (keep the input)
+    pub expiry_warning: Option<super::expiry_warning::ExpiryWarning>,
+    /// When each of our messages still in the queue was queued (message id
+    /// to Unix milliseconds)
+    pub queued_since: HashMap<String, i64>,
+    /// Our messages that failed for good or wait dormant for the contact
+    pub failed_ids: HashSet<String>,
+    /// Failed messages review opened with Ctrl+F (None when closed)
+    pub failed_review: Option<FailedReview>,
+    /// Message whose content the input is editing (None when composing)
+    pub editing: Option<String>,
 }
 
 impl ChatViewScreen {
@@ -257,24 +906,284 @@ impl ChatViewScreen {
         Self {
             contact_uid,
             input: String::new(),
+            cursor: 0,
+            template_picker: None,
             scroll_offset: 0,
             status_message: None,
+            show_message_details: false,
+            selected_message_id: None,
+            starred_only: false,
+            show_protection: true,
+            pinned_focus: None,
+            duplicate_prompt: false,
+            send_preview: None,
+            expiry_warning: None,
+            queued_since: HashMap::new(),
+            failed_ids: HashSet::new(),
+            failed_review: None,
+            editing: None,
         }
     }
 
-    /// Add character to input
+    /// Messages shown in the history (all, or only starred when filtering)
+    pub fn visible_messages<'a>(&self, chat: &'a Chat) -> Vec<&'a Message> {
+        chat.messages
+            .iter()
+            .filter(|m| !self.starred_only || m.starred)
+            .collect()
+    }
+
+    /// Toggle the starred-only filter
+    ///
+    /// Scrolls back to the top, and drops the selection if the selected
+    /// message is hidden by the filter.
+    pub fn toggle_starred_only(&mut self, chat: &Chat) {
+        self.starred_only = !self.starred_only;
+        self.scroll_offset = 0;
+        if self.selected_message(chat).is_none() {
+            self.selected_message_id = None;
+        }
+        if let Some(id) = self.selected_message_id.clone() {
+            self.scroll_to(chat, &id);
+        }
+    }
+
+    /// Whether selection mode is active
+    pub fn is_selecting(&self) -> bool {
+        self.selected_message_id.is_some()
+    }
+
+    /// Enter selection mode on the most recent visible message
+    pub fn start_selection(&mut self, chat: &Chat) {
+        self.pinned_focus = None;
+        if let Some(last) = self.visible_messages(chat).last() {
+            let id = last.id.clone();
+            self.scroll_to(chat, &id);
+            self.selected_message_id = Some(id);
+        }
+    }
+
+    /// Leave selection mode
+    pub fn end_selection(&mut self) {
+        self.selected_message_id = None;
+    }
+
+    /// Start editing `message`: its text goes into the input
+    pub fn start_edit(&mut self, message: &Message) {
+        self.input = String::from_utf8_lossy(&message.content).into_owned();
+        self.cursor = self.input.chars().count();
+        self.editing = Some(message.id.clone());
+        self.end_selection();
+    }
+
+    /// Stop editing and clear the input
+    pub fn cancel_edit(&mut self) {
+        if self.editing.take().is_some() {
+            self.clear_input();
+        }
+    }
+
+    /// Selected message, if it is visible
+    pub fn selected_message<'a>(&self, chat: &'a Chat) -> Option<&'a Message> {
+        let id = self.selected_message_id.as_deref()?;
+        self.visible_messages(chat).into_iter().find(|m| m.id == id)
+    }
+
+    /// Move the selection to the next (newer) visible message
+    pub fn select_next(&mut self, chat: &Chat) {
+        self.move_selection(chat, 1);
+    }
+
+    /// Move the selection to the previous (older) visible message
+    pub fn select_previous(&mut self, chat: &Chat) {
+        self.move_selection(chat, -1);
+    }
+
+    fn move_selection(&mut self, chat: &Chat, step: isize) {
+        let visible = self.visible_messages(chat);
+        let Some(current) = self
+            .selected_message_id
+            .as_deref()
+            .and_then(|id| visible.iter().position(|m| m.id == id))
+        else {
+            return;
+        };
+        let target = current.saturating_add_signed(step).min(visible.len().saturating_sub(1));
+        let id = visible[target].id.clone();
+        self.scroll_to(chat, &id);
+        self.selected_message_id = Some(id);
+    }
+
+    /// Adjust the scroll offset so `message_id` is on the visible page
+    fn scroll_to(&mut self, chat: &Chat, message_id: &str) {
+        let Some(position) = self.visible_messages(chat).iter().position(|m| m.id == message_id) else {
+            return;
+        };
+        if position < self.scroll_offset {
+            self.scroll_offset = position;
+        } else if position >= self.scroll_offset + CHAT_VIEW_PAGE_SIZE {
+            self.scroll_offset = position + 1 - CHAT_VIEW_PAGE_SIZE;
+        }
+    }
+
+    /// Give focus to the pinned strip (no-op when nothing is pinned)
+    pub fn focus_pinned_strip(&mut self, chat: &Chat) {
+        if !chat.pinned_messages().is_empty() {
+            self.pinned_focus = Some(0);
+            self.selected_message_id = None;
+        }
+    }
+
+    /// Return focus from the pinned strip to the input
+    pub fn unfocus_pinned_strip(&mut self) {
+        self.pinned_focus = None;
+    }
+
+    /// Move pinned strip focus to the next entry
+    pub fn next_pinned(&mut self, chat: &Chat) {
+        let count = chat.pinned_messages().len();
+        if let Some(focus) = self.pinned_focus.as_mut() {
+            *focus = (*focus + 1).min(count.saturating_sub(1));
+        }
+    }
+
+    /// Move pinned strip focus to the previous entry
+    pub fn previous_pinned(&mut self) {
+        if let Some(focus) = self.pinned_focus.as_mut() {
+            *focus = focus.saturating_sub(1);
+        }
+    }
+
+    /// Pinned message that has focus in the strip
+    pub fn focused_pinned<'a>(&self, chat: &'a Chat) -> Option<&'a Message> {
+        self.pinned_focus.and_then(|i| chat.pinned_messages().get(i).copied())
+    }
+
+    /// Keep pinned strip focus in range after a message is unpinned
+    pub fn clamp_pinned_focus(&mut self, chat: &Chat) {
+        let count = chat.pinned_messages().len();
+        self.pinned_focus = match self.pinned_focus {
+            Some(_) if count == 0 => None,
+            Some(focus) => Some(focus.min(count - 1)),
+            None => None,
+        };
+    }
+
+    /// Jump to the pinned message that has focus and select it
+    ///
+    /// Turns off the starred-only filter if it would hide the message.
+    pub fn jump_to_focused_pinned(&mut self, chat: &Chat) {
+        let Some(id) = self.focused_pinned(chat).map(|m| m.id.clone()) else {
+            return;
+        };
+        if self.starred_only && !chat.messages.iter().any(|m| m.id == id && m.starred) {
+            self.starred_only = false;
+        }
+        self.pinned_focus = None;
+        self.scroll_to(chat, &id);
+        self.selected_message_id = Some(id);
+    }
+
+    /// Toggle the message details popup
+    pub fn toggle_message_details(&mut self) {
+        self.show_message_details = !self.show_message_details;
+    }
+
+    /// Toggle the per-message protection markers
+    pub fn toggle_protection_markers(&mut self) {
+        self.show_protection = !self.show_protection;
+        self.set_status(
+            if self.show_protection { "Protection markers shown" } else { "Protection markers hidden" }.to_string(),
+        );
+    }
+
+    /// Insert a character at the cursor
     pub fn add_char(&mut self, c: char) {
-        self.input.push(c);
+        let at = self.cursor_byte_index();
+        self.input.insert(at, c);
+        self.cursor += 1;
     }
 
-    /// Remove last character from input
+    /// Insert a line break at the cursor
+    pub fn newline(&mut self) {
+        self.add_char('\n');
+    }
+
+    /// Insert text at the cursor, leaving the cursor after it
+    pub fn insert_text(&mut self, text: &str) {
+        let at = self.cursor_byte_index();
+        self.input.insert_str(at, text);
+        self.cursor += text.chars().count();
+    }
+
+    /// Remove the character before the cursor
     pub fn backspace(&mut self) {
-        self.input.pop();
+        if self.cursor == 0 {
+            return;
+        }
+        self.cursor -= 1;
+        let at = self.cursor_byte_index();
+        self.input.remove(at);
     }
 
     /// Clear input buffer
     pub fn clear_input(&mut self) {
         self.input.clear();
+        self.cursor = 0;
+    }
+
+    /// Move the cursor one character left
+    pub fn cursor_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    /// Move the cursor one character right
+    pub fn cursor_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.input.chars().count());
+    }
+
+    /// Move the cursor to the start of the input
+    pub fn cursor_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    /// Move the cursor to the end of the input
+    pub fn cursor_end(&mut self) {
+        self.cursor = self.input.chars().count();
+    }
+
+    /// Whether the cursor is at the start of a word (where '%' opens the template picker)
+    pub fn cursor_at_word_start(&self) -> bool {
+        self.input[..self.cursor_byte_index()]
+            .chars()
+            .next_back()
+            .is_none_or(char::is_whitespace)
+    }
+
+    /// Line and column of the cursor, counted in characters
+    pub fn cursor_line_column(&self) -> (usize, usize) {
+        let before = &self.input[..self.cursor_byte_index()];
+        let line = before.matches('\n').count();
+        let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count());
+        (line, column)
+    }
+
+    /// Open the template picker with an empty query
+    pub fn open_template_picker(&mut self) {
+        self.template_picker = Some(FilterList::new());
+    }
+
+    /// Close the template picker
+    pub fn close_template_picker(&mut self) {
+        self.template_picker = None;
+    }
+
+    /// Byte offset of the cursor in the input
+    fn cursor_byte_index(&self) -> usize {
+        self.input
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.input.len(), |(i, _)| i)
     }
 
     /// Scroll message history up
@@ -291,51 +1200,405 @@ impl ChatViewScreen {
         }
     }
 
+    /// Whether the newest of the messages shown is on the visible page
+    pub fn is_at_bottom(&self, chat: &Chat) -> bool {
+        self.scroll_offset + CHAT_VIEW_PAGE_SIZE >= self.visible_messages(chat).len()
+    }
+
     /// Set status message
     pub fn set_status(&mut self, message: String) {
         self.status_message = Some(message);
     }
 }
 
+/// Review of a chat's failed messages, oldest first (Ctrl+F in the chat view)
+#[derive(Debug, Clone)]
+pub struct FailedReview {
+    /// Failed and dormant messages in the order they were written
+    pub messages: Vec<FailedMessage>,
+    /// Highlighted entry
+    pub selected: usize,
+    /// Messages marked for a selective re-queue
+    pub marked: HashSet<String>,
+}
+
+impl FailedReview {
+    /// Review `messages`, none marked
+    pub fn new(messages: Vec<FailedMessage>) -> Self {
+        Self { messages, selected: 0, marked: HashSet::new() }
+    }
+
+    /// Move the highlight down (`down`) or up
+    pub fn move_selection(&mut self, down: bool) {
+        self.selected = if down {
+            (self.selected + 1).min(self.messages.len().saturating_sub(1))
+        } else {
+            self.selected.saturating_sub(1)
+        };
+    }
+
+    /// Mark or unmark the highlighted message
+    pub fn toggle_marked(&mut self) {
+        let Some(entry) = self.messages.get(self.selected) else {
+            return;
+        };
+        if !self.marked.remove(&entry.message.id) {
+            self.marked.insert(entry.message.id.clone());
+        }
+    }
+
+    /// IDs to re-queue: every message, or the marked ones (the highlighted
+    /// one if none is marked), oldest first
+    pub fn chosen_ids(&self, all: bool) -> Vec<String> {
+        let chosen = self.messages.iter().enumerate().filter(|(index, entry)| {
+            all || if self.marked.is_empty() { *index == self.selected } else { self.marked.contains(&entry.message.id) }
+        });
+        chosen.map(|(_, entry)| entry.message.id.clone()).collect()
+    }
+}
+
 /// Settings screen state
 #[derive(Debug)]
 pub struct SettingsScreen {
     /// Input buffer for retry interval
     pub retry_interval_input: String,
-    /// Currently selected field (0 = retry interval, can extend for more fields)
+    /// Currently selected field (see `SettingsScreen::FIELD_*`)
     pub selected_field: usize,
     /// Status/confirmation message
     pub status_message: Option<String>,
     /// Whether status is an error
     pub is_error: bool,
+    /// Quiet hours enabled toggle
+    pub quiet_hours_enabled: bool,
+    /// Input buffer for quiet hours start ("HH:MM")
+    pub quiet_start_input: String,
+    /// Input buffer for quiet hours end ("HH:MM")
+    pub quiet_end_input: String,
+    /// Days on which quiet hours start (bit 0 = Monday ... bit 6 = Sunday)
+    pub quiet_days: u8,
+    /// New-message alert mode
+    pub alert_mode: crate::storage::AlertMode,
+    /// Alert style of contacts without their own
+    pub alert_style: crate::storage::AlertStyle,
+    /// Lowest severity of background failure shown as a banner
+    pub error_banner_severity: crate::storage::ErrorSeverity,
+    /// Act as a relay for my contacts toggle
+    pub relay_enabled: bool,
+    /// Review address changes of verified contacts toggle
+    pub address_review_enabled: bool,
+    /// Lift the auto-import caps for an hour (applied on save, never stored)
+    pub auto_import_lifted: bool,
+    /// Input buffer for the profile label
+    pub profile_label_input: String,
+    /// Profile accent colour (None keeps the theme's colours)
+    pub accent_color: Option<crate::storage::AccentColor>,
+    /// Input buffer for the per-chat history limit (0 = unlimited)
+    pub history_limit_input: String,
+    /// Highlighted template while the template list is open (None when closed)
+    pub template_selected: Option<usize>,
+    /// Template being added or edited
+    pub template_editor: Option<TemplateEditor>,
+    /// Daily update check toggle
+    pub update_check_enabled: bool,
+    /// Serve TLS on the listener port toggle
+    pub tls_enabled: bool,
+    /// Refuse plain HTTP beyond the LAN toggle
+    pub require_tls_external: bool,
+    /// Outbound delivery journal toggle
+    pub journal_enabled: bool,
+    /// Automatic send preview toggle
+    pub auto_send_preview: bool,
+    /// Warn when composing during the contact's night toggle
+    pub night_warning_enabled: bool,
 }
 
 impl SettingsScreen {
+    /// Retry interval field
+    pub const FIELD_RETRY_INTERVAL: usize = 0;
+    /// Quiet hours enabled toggle
+    pub const FIELD_QUIET_ENABLED: usize = 1;
+    /// Quiet hours start time
+    pub const FIELD_QUIET_START: usize = 2;
+    /// Quiet hours end time
+    pub const FIELD_QUIET_END: usize = 3;
+    /// Quiet hours days of week
+    pub const FIELD_QUIET_DAYS: usize = 4;
+    /// New-message alert (bell/flash)
+    pub const FIELD_ALERT_MODE: usize = 5;
+    /// Global alert style (contacts may override)
+    pub const FIELD_ALERT_STYLE: usize = 6;
+    /// Lowest severity shown in the error banner
+    pub const FIELD_ERROR_BANNER: usize = 7;
+    /// Relay for my contacts toggle
+    pub const FIELD_RELAY_ENABLED: usize = 8;
+    /// Review address changes of verified contacts toggle
+    pub const FIELD_ADDRESS_REVIEW: usize = 9;
+    /// Lift the auto-import caps for an hour
+    pub const FIELD_AUTO_IMPORT_LIFT: usize = 10;
+    /// Profile label
+    pub const FIELD_PROFILE_LABEL: usize = 11;
+    /// Profile accent colour
+    pub const FIELD_ACCENT: usize = 12;
+    /// Messages kept per chat (Ctrl+A applies it to all chats now)
+    pub const FIELD_HISTORY_LIMIT: usize = 13;
+    /// Daily update check toggle
+    pub const FIELD_UPDATE_CHECK: usize = 14;
+    /// Serve TLS toggle
+    pub const FIELD_TLS: usize = 15;
+    /// Require TLS for external addresses toggle
+    pub const FIELD_REQUIRE_TLS: usize = 16;
+    /// Outbound delivery journal toggle
+    pub const FIELD_JOURNAL: usize = 17;
+    /// Automatic send preview toggle
+    pub const FIELD_SEND_PREVIEW: usize = 18;
+    /// Night warning toggle
+    pub const FIELD_NIGHT_WARNING: usize = 19;
+    /// Message templates (Enter opens the list)
+    pub const FIELD_TEMPLATES: usize = 20;
+    /// Number of fields
+    pub const FIELD_COUNT: usize = 21;
+
+    /// Longest history limit input, in digits
+    const HISTORY_LIMIT_DIGITS: usize = 6;
+
     /// Create new settings screen
     pub fn new(current_retry_interval: u32) -> Self {
+        let defaults = crate::storage::Settings::default();
         Self {
             retry_interval_input: current_retry_interval.to_string(),
-            selected_field: 0,
+            selected_field: Self::FIELD_RETRY_INTERVAL,
             status_message: Some("Edit retry interval and press Enter to save".to_string()),
             is_error: false,
+            quiet_hours_enabled: defaults.quiet_hours_enabled,
+            quiet_start_input: crate::storage::format_time_of_day(defaults.quiet_hours_start_minutes),
+            quiet_end_input: crate::storage::format_time_of_day(defaults.quiet_hours_end_minutes),
+            quiet_days: defaults.quiet_hours_days,
+            alert_mode: defaults.alert_mode,
+            alert_style: defaults.alert_style,
+            error_banner_severity: defaults.error_banner_severity,
+            relay_enabled: defaults.relay_enabled,
+            address_review_enabled: defaults.address_review_enabled,
+            auto_import_lifted: false,
+            profile_label_input: defaults.profile_label,
+            accent_color: defaults.accent_color,
+            history_limit_input: defaults.history_limit.to_string(),
+            template_selected: None,
+            template_editor: None,
+            update_check_enabled: defaults.update_check_enabled,
+            tls_enabled: defaults.tls_enabled,
+            require_tls_external: defaults.require_tls_external,
+            journal_enabled: defaults.journal_enabled,
+            auto_send_preview: defaults.auto_send_preview,
+            night_warning_enabled: defaults.night_warning_enabled,
+        }
+    }
+
+    /// Highlight the next template in the list (wraps around)
+    pub fn next_template(&mut self, template_count: usize) {
+        if let Some(selected) = &mut self.template_selected
+            && template_count > 0
+        {
+            *selected = (*selected + 1) % template_count;
         }
     }
 
-    /// Add character to input (only digits, max 4 characters)
+    /// Highlight the previous template in the list (wraps around)
+    pub fn previous_template(&mut self, template_count: usize) {
+        if let Some(selected) = &mut self.template_selected
+            && template_count > 0
+        {
+            *selected = (*selected + template_count - 1) % template_count;
+        }
+    }
+
+    /// Fill the quiet hours fields from settings
+    pub fn load_quiet_hours(&mut self, settings: &crate::storage::Settings) {
+        self.quiet_hours_enabled = settings.quiet_hours_enabled;
+        self.quiet_start_input = crate::storage::format_time_of_day(settings.quiet_hours_start_minutes);
+        self.quiet_end_input = crate::storage::format_time_of_day(settings.quiet_hours_end_minutes);
+        self.quiet_days = settings.quiet_hours_days;
+    }
+
+    /// Select next field
+    pub fn next_field(&mut self) {
+        self.selected_field = (self.selected_field + 1) % Self::FIELD_COUNT;
+    }
+
+    /// Select previous field
+    pub fn previous_field(&mut self) {
+        self.selected_field = (self.selected_field + Self::FIELD_COUNT - 1) % Self::FIELD_COUNT;
+    }
+
+    /// Add character to the selected field
+    ///
+    /// - Retry interval: digits only, max 4 characters
+    /// - Quiet hours toggle: space toggles
+    /// - Start/end: digits and ':', max 5 characters
+    /// - Days: '1'-'7' toggle Monday-Sunday
+    /// - Alert: space cycles none, bell, flash, both
+    /// - Alert style: space cycles default, subtle, urgent, none
+    /// - Error banner: space cycles info, warning, error
+    /// - Relay, address review, auto-import lift, update check, TLS, journal, send preview and night warning toggles: space toggles
+    /// - Profile label: any printable character, up to `MAX_PROFILE_LABEL_CHARS`
+    /// - Accent: space cycles through the colours (and back to the default)
+    /// - History limit: digits only, max 6 characters
     pub fn add_char(&mut self, c: char) {
-        if c.is_ascii_digit() && self.retry_interval_input.len() < 4 {
-            self.retry_interval_input.push(c);
+        match self.selected_field {
+            Self::FIELD_RETRY_INTERVAL if c.is_ascii_digit() && self.retry_interval_input.len() < 4 => {
+                self.retry_interval_input.push(c);
+            }
+            Self::FIELD_QUIET_ENABLED if c == ' ' => {
+                self.quiet_hours_enabled = !self.quiet_hours_enabled;
+            }
+            Self::FIELD_QUIET_START | Self::FIELD_QUIET_END => {
+                let input = if self.selected_field == Self::FIELD_QUIET_START {
+                    &mut self.quiet_start_input
+                } else {
+                    &mut self.quiet_end_input
+                };
+                if (c.is_ascii_digit() || c == ':') && input.len() < 5 {
+                    input.push(c);
+                }
+            }
+            Self::FIELD_QUIET_DAYS => {
+                if let Some(day) = c.to_digit(10).filter(|d| (1..=7).contains(d)) {
+                    self.quiet_days ^= 1 << (day - 1);
+                }
+            }
+            Self::FIELD_ALERT_MODE if c == ' ' => {
+                self.alert_mode = self.alert_mode.cycle();
+            }
+            Self::FIELD_ALERT_STYLE if c == ' ' => {
+                self.alert_style = self.alert_style.cycle();
+            }
+            Self::FIELD_ERROR_BANNER if c == ' ' => {
+                self.error_banner_severity = self.error_banner_severity.cycle();
+            }
+            Self::FIELD_RELAY_ENABLED if c == ' ' => {
+                self.relay_enabled = !self.relay_enabled;
+            }
+            Self::FIELD_ADDRESS_REVIEW if c == ' ' => {
+                self.address_review_enabled = !self.address_review_enabled;
+            }
+            Self::FIELD_AUTO_IMPORT_LIFT if c == ' ' => {
+                self.auto_import_lifted = !self.auto_import_lifted;
+            }
+            Self::FIELD_PROFILE_LABEL
+                if !c.is_control()
+                    && self.profile_label_input.chars().count() < crate::storage::MAX_PROFILE_LABEL_CHARS =>
+            {
+                self.profile_label_input.push(c);
+            }
+            Self::FIELD_ACCENT if c == ' ' => {
+                self.accent_color = crate::storage::AccentColor::cycle(self.accent_color);
+            }
+            Self::FIELD_HISTORY_LIMIT
+                if c.is_ascii_digit() && self.history_limit_input.len() < Self::HISTORY_LIMIT_DIGITS =>
+            {
+                self.history_limit_input.push(c);
+            }
+            Self::FIELD_UPDATE_CHECK if c == ' ' => {
+                self.update_check_enabled = !self.update_check_enabled;
+            }
+            Self::FIELD_TLS if c == ' ' => {
+                self.tls_enabled = !self.tls_enabled;
+            }
+            Self::FIELD_REQUIRE_TLS if c == ' ' => {
+                self.require_tls_external = !self.require_tls_external;
+            }
+            Self::FIELD_JOURNAL if c == ' ' => {
+                self.journal_enabled = !self.journal_enabled;
+            }
+            Self::FIELD_SEND_PREVIEW if c == ' ' => {
+                self.auto_send_preview = !self.auto_send_preview;
+            }
+            Self::FIELD_NIGHT_WARNING if c == ' ' => {
+                self.night_warning_enabled = !self.night_warning_enabled;
+            }
+            _ => {}
         }
     }
 
-    /// Remove last character from input
+    /// Remove last character from the selected input
     pub fn backspace(&mut self) {
-        self.retry_interval_input.pop();
+        match self.selected_field {
+            Self::FIELD_RETRY_INTERVAL => {
+                self.retry_interval_input.pop();
+            }
+            Self::FIELD_QUIET_START => {
+                self.quiet_start_input.pop();
+            }
+            Self::FIELD_QUIET_END => {
+                self.quiet_end_input.pop();
+            }
+            Self::FIELD_PROFILE_LABEL => {
+                self.profile_label_input.pop();
+            }
+            Self::FIELD_HISTORY_LIMIT => {
+                self.history_limit_input.pop();
+            }
+            _ => {}
+        }
     }
 
-    /// Clear input buffer
+    /// Clear the selected input buffer
     pub fn clear_input(&mut self) {
-        self.retry_interval_input.clear();
+        match self.selected_field {
+            Self::FIELD_RETRY_INTERVAL => self.retry_interval_input.clear(),
+            Self::FIELD_QUIET_START => self.quiet_start_input.clear(),
+            Self::FIELD_QUIET_END => self.quiet_end_input.clear(),
+            Self::FIELD_PROFILE_LABEL => self.profile_label_input.clear(),
+            Self::FIELD_HISTORY_LIMIT => self.history_limit_input.clear(),
+            _ => {}
+        }
+    }
+
+    /// Validate quiet hours fields and apply them to `settings`
+    ///
+    /// Returns true if valid; otherwise sets an error status and leaves
+    /// `settings` unchanged.
+    pub fn apply_quiet_hours(&mut self, settings: &mut crate::storage::Settings) -> bool {
+        let (Some(start), Some(end)) = (
+            crate::storage::parse_time_of_day(&self.quiet_start_input),
+            crate::storage::parse_time_of_day(&self.quiet_end_input),
+        ) else {
+            self.status_message = Some("Error: Quiet hours times must be HH:MM".to_string());
+            self.is_error = true;
+            return false;
+        };
+
+        let mut updated = settings.clone();
+        updated.quiet_hours_enabled = self.quiet_hours_enabled;
+        updated.quiet_hours_start_minutes = start;
+        updated.quiet_hours_end_minutes = end;
+        updated.quiet_hours_days = self.quiet_days;
+
+        if let Err(e) = updated.validate_quiet_hours() {
+            let reason = match e {
+                crate::Error::Storage(msg) => msg,
+                other => other.to_string(),
+            };
+            self.status_message = Some(format!("Error: {}", reason));
+            self.is_error = true;
+            return false;
+        }
+
+        *settings = updated;
+        true
+    }
+
+    /// Parse the history limit field
+    ///
+    /// Returns the limit (0 = unlimited), or sets an error status.
+    pub fn validate_history_limit(&mut self) -> Option<u32> {
+        match self.history_limit_input.parse::<u32>() {
+            Ok(limit) => Some(limit),
+            Err(_) => {
+                self.status_message = Some("Error: History limit must be a number (0 = unlimited)".to_string());
+                self.is_error = true;
+                None
+            }
+        }
     }
 
     /// Validate input and return the validated value
@@ -378,6 +1641,82 @@ impl SettingsScreen {
     }
 }
 
+/// Editor for one message template in the settings screen
+#[derive(Debug, Clone)]
+pub struct TemplateEditor {
+    /// Position of the template being edited (None for a new one)
+    pub index: Option<usize>,
+    /// Name buffer
+    pub name: String,
+    /// Body buffer (may span several lines)
+    pub body: String,
+    /// Whether typing goes to the body rather than the name
+    pub editing_body: bool,
+    /// Validation error from the last save attempt
+    pub status_message: Option<String>,
+}
+
+impl TemplateEditor {
+    /// Editor for a new template
+    pub fn new() -> Self {
+        Self {
+            index: None,
+            name: String::new(),
+            body: String::new(),
+            editing_body: false,
+            status_message: None,
+        }
+    }
+
+    /// Editor prefilled with an existing template
+    pub fn for_template(index: usize, template: &crate::storage::MessageTemplate) -> Self {
+        Self {
+            index: Some(index),
+            name: template.name.clone(),
+            body: template.body.clone(),
+            ..Self::new()
+        }
+    }
+
+    /// Switch between the name and body fields
+    pub fn toggle_field(&mut self) {
+        self.editing_body = !self.editing_body;
+    }
+
+    /// Add a character to the active field
+    pub fn add_char(&mut self, c: char) {
+        if self.editing_body {
+            self.body.push(c);
+        } else {
+            self.name.push(c);
+        }
+    }
+
+    /// Start a new line in the body (moves from the name to the body)
+    pub fn newline(&mut self) {
+        if self.editing_body {
+            self.body.push('\n');
+        } else {
+            self.editing_body = true;
+        }
+    }
+
+    /// Remove the last character of the active field
+    pub fn backspace(&mut self) {
+        if self.editing_body {
+            self.body.pop();
+        } else {
+            self.name.pop();
+        }
+    }
+}
+
+impl Default for TemplateEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Diagnostics screen state
 #[derive(Debug)]
 pub struct DiagnosticsScreen {
@@ -407,8 +1746,37 @@ pub struct DiagnosticsScreen {
     pub last_ping_rtt_ms: Option<u64>,
     /// Number of messages in queue
     pub queue_size: usize,
+    /// Queued messages waiting dormant for their contact
+    pub dormant_count: usize,
+    /// Queued messages blocked on a contact whose token expired
+    pub blocked_count: usize,
+    /// Per-gateway outcomes of the last PCP/NAT-PMP probes
+    pub gateway_probes: Vec<crate::connectivity::GatewayProbe>,
+    /// Single-protocol test or deletion currently running
+    pub pending_action: Option<crate::tui::DiagnosticsAction>,
+    /// Port used by single-protocol tests instead of `local_port`
+    ///
+    /// Only affects tests started from this screen; the app keeps its port.
+    pub alternate_port: Option<u16>,
+    /// Alternate port being typed (when the prompt is open)
+    pub port_prompt: Option<String>,
+    /// Whether deletion of the active mapping awaits confirmation
+    pub confirm_delete: bool,
+    /// Local port each protocol's panel was last tested against
+    pub tested_ports: Vec<(crate::connectivity::MappingProtocol, u16)>,
+    /// Whether the error log (reports of background failures) is open
+    pub show_error_log: bool,
+    /// Recent peer-assisted reachability tests, newest first
+    pub peer_probes: Vec<crate::probe::PeerProbeRecord>,
+    /// Selected row of the contact picker for a peer probe (when open)
+    pub probe_picker: Option<usize>,
+    /// Queued messages, redacted as in the debug report (when the queue view is open)
+    pub queue_rows: Option<Vec<crate::queue::QueueDebugRow>>,
 }
 
+/// Peer probe results listed in Diagnostics
+pub const PEER_PROBE_HISTORY_SHOWN: usize = 3;
+
 impl DiagnosticsScreen {
     /// Create new diagnostics screen
     pub fn new(local_port: u16) -> Self {
@@ -426,9 +1794,148 @@ impl DiagnosticsScreen {
             external_endpoint: None,
             last_ping_rtt_ms: None,
             queue_size: 0,
+            dormant_count: 0,
+            blocked_count: 0,
+            gateway_probes: Vec::new(),
+            pending_action: None,
+            alternate_port: None,
+            port_prompt: None,
+            confirm_delete: false,
+            tested_ports: Vec::new(),
+            show_error_log: false,
+            peer_probes: Vec::new(),
+            probe_picker: None,
+            queue_rows: None,
+        }
+    }
+
+    /// Port single-protocol tests run against
+    pub fn test_port(&self) -> u16 {
+        self.alternate_port.unwrap_or(self.local_port)
+    }
+
+    /// Local port the given protocol's panel was last tested against
+    pub fn tested_port(&self, protocol: crate::connectivity::MappingProtocol) -> u16 {
+        self.tested_ports
+            .iter()
+            .find(|(p, _)| *p == protocol)
+            .map(|(_, port)| *port)
+            .unwrap_or(self.local_port)
+    }
+
+    /// Panel status of PCP, NAT-PMP or UPnP
+    pub fn protocol_status(
+        &self,
+        protocol: crate::connectivity::MappingProtocol,
+    ) -> Option<&Result<crate::connectivity::PortMappingResult, String>> {
+        use crate::connectivity::MappingProtocol;
+
+        match protocol {
+            MappingProtocol::PCP => self.pcp_status.as_ref(),
+            MappingProtocol::NATPMP => self.natpmp_status.as_ref(),
+            MappingProtocol::UPnP => self.upnp_status.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Replace one protocol's panel, leaving the others untouched
+    pub fn set_protocol_status(
+        &mut self,
+        protocol: crate::connectivity::MappingProtocol,
+        status: Option<Result<crate::connectivity::PortMappingResult, String>>,
+    ) {
+        use crate::connectivity::MappingProtocol;
+
+        match protocol {
+            MappingProtocol::PCP => self.pcp_status = status,
+            MappingProtocol::NATPMP => self.natpmp_status = status,
+            MappingProtocol::UPnP => self.upnp_status = status,
+            _ => {}
         }
     }
 
+    /// Panel protocol and mapping currently in use (first successful of PCP, NAT-PMP, UPnP)
+    pub fn active_mapping(&self) -> Option<(crate::connectivity::MappingProtocol, &crate::connectivity::PortMappingResult)> {
+        use crate::connectivity::MappingProtocol;
+
+        [MappingProtocol::PCP, MappingProtocol::NATPMP, MappingProtocol::UPnP]
+            .into_iter()
+            .find_map(|protocol| match self.protocol_status(protocol) {
+                Some(Ok(mapping)) => Some((protocol, mapping)),
+                _ => None,
+            })
+    }
+
+    /// Open the alternate port prompt
+    pub fn start_port_prompt(&mut self) {
+        self.port_prompt = Some(self.alternate_port.map(|p| p.to_string()).unwrap_or_default());
+    }
+
+    /// Type into the alternate port prompt (digits only)
+    pub fn port_prompt_push(&mut self, c: char) {
+        if let Some(prompt) = &mut self.port_prompt
+            && c.is_ascii_digit()
+            && prompt.len() < 5
+        {
+            prompt.push(c);
+        }
+    }
+
+    /// Delete the last character of the alternate port prompt
+    pub fn port_prompt_pop(&mut self) {
+        if let Some(prompt) = &mut self.port_prompt {
+            prompt.pop();
+        }
+    }
+
+    /// Apply the alternate port prompt
+    ///
+    /// An empty prompt goes back to testing `local_port`.
+    ///
+    /// # Errors
+    /// Returns a message (and keeps the prompt open) if the port is not 1-65535
+    pub fn submit_port_prompt(&mut self) -> Result<(), String> {
+        let Some(prompt) = &self.port_prompt else {
+            return Ok(());
+        };
+        if prompt.is_empty() {
+            self.alternate_port = None;
+        } else {
+            match prompt.parse::<u16>() {
+                Ok(port) if port > 0 => self.alternate_port = Some(port),
+                _ => return Err(format!("Invalid port: {}", prompt)),
+            }
+        }
+        self.port_prompt = None;
+        Ok(())
+    }
+
+    /// One summary line per probed gateway (e.g. "PCP 192.168.1.1 (eth0): mapped")
+    pub fn gateway_probe_lines(&self) -> Vec<String> {
+        use crate::connectivity::{GatewayProbeOutcome, MappingProtocol};
+
+        self.gateway_probes
+            .iter()
+            .map(|probe| {
+                let protocol = match probe.protocol {
+                    MappingProtocol::NATPMP => "NAT-PMP",
+                    _ => "PCP",
+                };
+                let interface = probe
+                    .interface
+                    .as_ref()
+                    .map(|i| format!(" ({})", i))
+                    .unwrap_or_default();
+                let outcome = match &probe.outcome {
+                    GatewayProbeOutcome::Mapped(_) => format!("mapped in {}ms", probe.elapsed_ms),
+                    GatewayProbeOutcome::Failed(e) => e.clone(),
+                    GatewayProbeOutcome::Cancelled => "not needed".to_string(),
+                };
+                format!("{} {}{}: {}", protocol, probe.server.ip(), interface, outcome)
+            })
+            .collect()
+    }
+
     /// Set PCP status
     pub fn set_pcp_status(&mut self, status: Result<crate::connectivity::PortMappingResult, String>) {
         self.pcp_status = Some(status);
@@ -493,15 +2000,7 @@ impl DiagnosticsScreen {
 
     /// Calculate remaining lifetime seconds for the active mapping
     pub fn get_remaining_lifetime_secs(&self) -> Option<i64> {
-        let mapping = if let Some(Ok(m)) = &self.pcp_status {
-            Some(m)
-        } else if let Some(Ok(m)) = &self.natpmp_status {
-            Some(m)
-        } else if let Some(Ok(m)) = &self.upnp_status {
-            Some(m)
-        } else {
-            None
-        }?;
+        let (_, mapping) = self.active_mapping()?;
 
         let now_ms = chrono::Utc::now().timestamp_millis();
         let elapsed_secs = ((now_ms - mapping.created_at_ms) / 1000).max(0);
@@ -512,15 +2011,7 @@ impl DiagnosticsScreen {
 
     /// Calculate time until renewal (80% of lifetime)
     pub fn get_renewal_countdown_secs(&self) -> Option<i64> {
-        let mapping = if let Some(Ok(m)) = &self.pcp_status {
-            Some(m)
-        } else if let Some(Ok(m)) = &self.natpmp_status {
-            Some(m)
-        } else if let Some(Ok(m)) = &self.upnp_status {
-            Some(m)
-        } else {
-            None
-        }?;
+        let (_, mapping) = self.active_mapping()?;
 
         let now_ms = chrono::Utc::now().timestamp_millis();
         let elapsed_secs = ((now_ms - mapping.created_at_ms) / 1000).max(0);
@@ -531,8 +2022,13 @@ impl DiagnosticsScreen {
     }
 
     /// Format remaining time as human-readable string
+    ///
+    /// Negative times read "0s"; anything beyond 999 days reads "999+ days".
     pub fn format_time_remaining(secs: i64) -> String {
-        if secs >= 3600 {
+        let secs = secs.max(0);
+        if secs > crate::storage::MAX_DISPLAY_SECS {
+            crate::storage::SATURATED_DURATION_TEXT.to_string()
+        } else if secs >= 3600 {
             format!("{}h {}m", secs / 3600, (secs % 3600) / 60)
         } else if secs >= 60 {
             format!("{}m {}s", secs / 60, secs % 60)
@@ -595,6 +2091,8 @@ impl DiagnosticsScreen {
             crate::connectivity::StrategyAttempt::NotAttempted => {}
         }
 
+        self.gateway_probes = result.gateway_probes.clone();
+
         // Update external endpoint from successful mapping
         if let Some(mapping) = &result.mapping {
             self.external_endpoint = Some(format!("{}:{}", mapping.external_ip, mapping.external_port));
@@ -611,6 +2109,230 @@ impl DiagnosticsScreen {
     }
 }
 
+/// Snapshots screen state: the snapshot list, then a read-only diff
+#[derive(Debug)]
+pub struct SnapshotsScreen {
+    /// Snapshot files, newest first
+    pub snapshots: Vec<crate::storage::SnapshotFile>,
+    /// Highlighted row: 0 is the live database, then `snapshots`
+    pub selected: usize,
+    /// Rows marked for comparison (at most two)
+    pub marked: Vec<usize>,
+    /// The comparison being shown, if any
+    pub comparison: Option<crate::storage::SnapshotComparison>,
+    /// First diff line shown
+    pub scroll: usize,
+    /// Status message
+    pub status_message: Option<String>,
+    /// Whether the status message is an error
+    pub is_error: bool,
+}
+
+impl SnapshotsScreen {
+    /// Create the screen listing `snapshots`
+    pub fn new(snapshots: Vec<crate::storage::SnapshotFile>) -> Self {
+        Self {
+            snapshots,
+            selected: 0,
+            marked: Vec::new(),
+            comparison: None,
+            scroll: 0,
+            status_message: None,
+            is_error: false,
+        }
+    }
+
+    /// Rows in the list (the live database and every snapshot)
+    pub fn row_count(&self) -> usize {
+        self.snapshots.len() + 1
+    }
+
+    /// Move the highlight (list) or scroll the diff by one line
+    pub fn move_selection(&mut self, down: bool) {
+        if self.comparison.is_some() {
+            self.scroll = if down { self.scroll + 1 } else { self.scroll.saturating_sub(1) };
+        } else if down {
+            self.selected = (self.selected + 1).min(self.row_count() - 1);
+        } else {
+            self.selected = self.selected.saturating_sub(1);
+        }
+    }
+
+    /// Mark or unmark the highlighted row; a third mark replaces the oldest one
+    pub fn toggle_mark(&mut self) {
+        if let Some(position) = self.marked.iter().position(|&row| row == self.selected) {
+            self.marked.remove(position);
+        } else {
+            if self.marked.len() == 2 {
+                self.marked.remove(0);
+            }
+            self.marked.push(self.selected);
+        }
+    }
+
+    /// Rows to compare as (older, newer)
+    ///
+    /// The two marked rows if there are two, otherwise the highlighted
+    /// snapshot against the live database. Rows are newest first, so the
+    /// higher row is the older side.
+    pub fn comparison_rows(&self) -> Option<(usize, usize)> {
+        match self.marked.as_slice() {
+            &[a, b] if a != b => Some((a.max(b), a.min(b))),
+            _ if self.selected > 0 => Some((self.selected, 0)),
+            _ => None,
+        }
+    }
+
+    /// Path of a list row (None for the live database)
+    pub fn row_path(&self, row: usize) -> Option<&std::path::Path> {
+        row.checked_sub(1).and_then(|i| self.snapshots.get(i)).map(|s| s.path.as_path())
+    }
+
+    /// Show `comparison`
+    pub fn show_comparison(&mut self, comparison: crate::storage::SnapshotComparison) {
+        self.comparison = Some(comparison);
+        self.scroll = 0;
+    }
+
+    /// Leave the diff and go back to the list
+    pub fn close_comparison(&mut self) {
+        self.comparison = None;
+        self.scroll = 0;
+    }
+
+    /// The diff being shown, if both sides could be read
+    pub fn diff(&self) -> Option<&crate::storage::SnapshotDiff> {
+        match &self.comparison {
+            Some(crate::storage::SnapshotComparison::Compared(diff)) => Some(diff),
+            _ => None,
+        }
+    }
+
+    /// Set a status message
+    pub fn set_status(&mut self, message: String, is_error: bool) {
+        self.status_message = Some(message);
+        self.is_error = is_error;
+    }
+}
+
+/// Maintenance screen state: this session's deletes, with undo (from Diagnostics)
+#[derive(Debug, Default)]
+pub struct MaintenanceScreen {
+    /// Highlighted entry of `App::undo`
+    pub selected: usize,
+    /// Status message
+    pub status_message: Option<String>,
+    /// Whether the status message is an error
+    pub is_error: bool,
+}
+
+impl MaintenanceScreen {
+    /// Create the screen
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the highlight within `count` entries
+    pub fn move_selection(&mut self, down: bool, count: usize) {
+        self.selected = if down {
+            (self.selected + 1).min(count.saturating_sub(1))
+        } else {
+            self.selected.saturating_sub(1)
+        };
+    }
+
+    /// Set a status message
+    pub fn set_status(&mut self, message: String, is_error: bool) {
+        self.status_message = Some(message);
+        self.is_error = is_error;
+    }
+}
+
+/// Recovery screen state: what was reconciled after an unclean exit
+/// (shown once, before the main menu)
+#[derive(Debug)]
+pub struct RecoveryScreen {
+    /// What recovery did
+    pub report: RecoveryReport,
+    /// Whether the per-step details are expanded
+    pub show_details: bool,
+}
+
+impl RecoveryScreen {
+    /// Create the screen for `report`, details collapsed
+    pub fn new(report: RecoveryReport) -> Self {
+        Self { report, show_details: false }
+    }
+
+    /// Expand or collapse the details
+    pub fn toggle_details(&mut self) {
+        self.show_details = !self.show_details;
+    }
+}
+
+/// Capture screen state: the raw lines of one capture (or log) file
+#[derive(Debug)]
+pub struct CaptureScreen {
+    /// What the file is, shown in the title ("Capture" or "Log")
+    pub label: &'static str,
+    /// Screen Esc returns to
+    pub back_to: Screen,
+    /// The capture file
+    pub path: std::path::PathBuf,
+    /// Lines read from the file
+    pub lines: Vec<String>,
+    /// First line shown
+    pub scroll: usize,
+    /// Why the file could not be read, if it could not
+    pub error: Option<String>,
+}
+
+impl CaptureScreen {
+    /// Lines moved by PgUp/PgDn
+    pub const PAGE_LINES: usize = 10;
+
+    /// Show the capture file at `path`
+    pub fn new(path: std::path::PathBuf) -> Self {
+        Self::open("Capture", Screen::ChatList, path)
+    }
+
+    /// Show the log file at `path` (reached from Diagnostics)
+    pub fn log(path: std::path::PathBuf) -> Self {
+        Self::open("Log", Screen::Diagnostics, path)
+    }
+
+    fn open(label: &'static str, back_to: Screen, path: std::path::PathBuf) -> Self {
+        let mut screen = Self {
+            label,
+            back_to,
+            path,
+            lines: Vec::new(),
+            scroll: 0,
+            error: None,
+        };
+        screen.reload();
+        screen
+    }
+
+    /// Read the file again (a running capture keeps appending)
+    pub fn reload(&mut self) {
+        match std::fs::read_to_string(&self.path) {
+            Ok(text) => {
+                self.lines = text.lines().map(str::to_string).collect();
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+        self.scroll = self.scroll.min(self.lines.len().saturating_sub(1));
+    }
+
+    /// Scroll by `lines` (negative: up), staying within the file
+    pub fn scroll_by(&mut self, lines: isize) {
+        let last = self.lines.len().saturating_sub(1);
+        self.scroll = self.scroll.saturating_add_signed(lines).min(last);
+    }
+}
+
 /// Startup Sync screen state
 #[derive(Debug)]
 pub struct StartupSyncScreen {
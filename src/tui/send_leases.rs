@@ -0,0 +1,182 @@
+//! In-flight send leases
+//!
+//! A chat send runs on its own thread after the message is already in the
+//! chat history. When the send starts, the app takes a `SendLease` on the
+//! message; the thread gives it back once the send has an outcome (delivered,
+//! queued, or failed). A panic in the send thread is caught by `run_send` and
+//! published as a `DeliveryUpdate::Failed` for the message instead of
+//! unwinding into nothing.
+//!
+//! The delivery attempt is cut off after `SEND_TRANSPORT_TIMEOUT_SECS`, so a
+//! live send always has its outcome well before `LEASE_TIMEOUT_SECS`. Every
+//! `LEASE_SWEEP_INTERVAL` the app takes back leases older than that: their
+//! thread died or hung without an outcome, so the message is queued for the
+//! retry worker (see `App::sweep_send_leases`). Should the thread still
+//! finish, `run_send` drops its outcome, as the sweep already queued it.
+//!
+//! The app also stores the leases in the database whenever they change
+//! (`App::persist_send_leases`). Leases found there at the next start belong
+//! to sends the process died in the middle of; they are queued the same way.
+
+use crate::storage::ErrorSeverity;
+use crate::tui::delivery_events::{DeliveryEvent, DeliveryUpdate};
+use crate::tui::error_reports::ErrorReporter;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// How often leases are checked for sends that never finished
+pub const LEASE_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Longest a send's delivery attempt may take, all endpoints included,
+/// before it is cut off and the message queued
+pub const SEND_TRANSPORT_TIMEOUT_SECS: i64 = 60;
+
+/// Age after which a lease is taken back (twice `SEND_TRANSPORT_TIMEOUT_SECS`)
+pub const LEASE_TIMEOUT_SECS: i64 = 2 * SEND_TRANSPORT_TIMEOUT_SECS;
+
+/// A send that has started and has no outcome yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendLease {
+    /// The chat message being sent
+    pub message_id: String,
+    /// Contact it is addressed to
+    pub contact_uid: String,
+    /// When the send started
+    pub started_at: DateTime<Utc>,
+}
+
+impl SendLease {
+    /// How long the send has been running at `now`
+    pub fn age(&self, now: DateTime<Utc>) -> Duration {
+        now - self.started_at
+    }
+}
+
+/// Leases of the sends in flight, shared with the send threads
+#[derive(Debug, Clone, Default)]
+pub struct SendLeases {
+    leases: Arc<Mutex<HashMap<String, SendLease>>>,
+    /// Bumped on every change, so the app knows when to store them again
+    generation: Arc<AtomicU64>,
+}
+
+impl SendLeases {
+    /// Create an empty set of leases
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the send of `message_id` to `contact_uid` started at `now`
+    pub fn begin(&self, message_id: &str, contact_uid: &str, now: DateTime<Utc>) {
+        let lease = SendLease {
+            message_id: message_id.to_string(),
+            contact_uid: contact_uid.to_string(),
+            started_at: now,
+        };
+        self.leases.lock().unwrap().insert(message_id.to_string(), lease);
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Give back the lease of `message_id` (the send has an outcome)
+    ///
+    /// # Returns
+    /// Whether the lease was still held; false once it was swept
+    pub fn finish(&self, message_id: &str) -> bool {
+        let held = self.leases.lock().unwrap().remove(message_id).is_some();
+        if held {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+        }
+        held
+    }
+
+    /// Take back the leases older than `LEASE_TIMEOUT_SECS` at `now`
+    ///
+    /// Each lease is returned once: a later sweep, or the send finishing
+    /// late, no longer sees it.
+    pub fn take_expired(&self, now: DateTime<Utc>) -> Vec<SendLease> {
+        let timeout = Duration::seconds(LEASE_TIMEOUT_SECS);
+        let mut leases = self.leases.lock().unwrap();
+        let expired: Vec<String> = leases
+            .values()
+            .filter(|lease| lease.age(now) > timeout)
+            .map(|lease| lease.message_id.clone())
+            .collect();
+        let mut taken: Vec<SendLease> = expired.iter().filter_map(|id| leases.remove(id)).collect();
+        if !taken.is_empty() {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+        }
+        taken.sort_by_key(|lease| lease.started_at);
+        taken
+    }
+
+    /// Every lease held, oldest first
+    pub fn snapshot(&self) -> Vec<SendLease> {
+        let mut leases: Vec<SendLease> = self.leases.lock().unwrap().values().cloned().collect();
+        leases.sort_by_key(|lease| lease.started_at);
+        leases
+    }
+
+    /// Counter bumped by every change to the leases
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Number of sends in flight
+    pub fn len(&self) -> usize {
+        self.leases.lock().unwrap().len()
+    }
+
+    /// Whether no send is in flight
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Run `send`, the delivery attempt of the leased `message_id` to `contact_uid`
+///
+/// The lease is given back whatever happens. If `send` panics, the panic is
+/// reported and a `DeliveryUpdate::Failed` for the message is published on
+/// `events` (other messages to the contact are assumed still pending, as
+/// when the queue cannot be read). If the lease was swept while `send` ran,
+/// the message is already queued and the late outcome is dropped.
+///
+/// # Returns
+/// The outcome of `send` for the caller to queue or publish; None if it
+/// panicked or finished too late
+pub fn run_send<T, F: FnOnce() -> T>(
+    leases: &SendLeases,
+    contact_uid: &str,
+    message_id: &str,
+    events: &std::sync::mpsc::Sender<DeliveryEvent>,
+    reports: &ErrorReporter,
+    send: F,
+) -> Option<T> {
+    let outcome = std::panic::catch_unwind(AssertUnwindSafe(send));
+    let held = leases.finish(message_id);
+    let panic = match outcome {
+        Ok(outcome) if held => return Some(outcome),
+        Ok(_) => {
+            tracing::warn!("Send of {} to {} finished after its lease was swept; outcome dropped", message_id, contact_uid);
+            return None;
+        }
+        Err(panic) => panic,
+    };
+
+    let reason = panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    reports.report(
+        ErrorSeverity::Error,
+        "send",
+        format!("Sending message {} to {} crashed: {}", message_id, contact_uid, reason),
+    );
+    if held {
+        let _ = events.send(DeliveryEvent::new(contact_uid, DeliveryUpdate::Failed, true).with_message_id(message_id));
+    }
+    None
+}
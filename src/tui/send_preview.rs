@@ -0,0 +1,119 @@
+//! Preview of what sending the chat input will do
+//!
+//! Built from the helpers the send path itself uses: the request is sealed
+//! by `messaging::outgoing_request` exactly as `send_sealed_message` seals
+//! it, the size limit is the input counter's, and the queue forecast comes from the
+//! queue rows and `delivery_hint` behind the chat view's banner. Messages
+//! are never split or compressed and a chat has a single recipient, so a
+//! preview always shows one part and one destination.
+//!
+//! The chat view shows the preview before sending when any `PreviewReason`
+//! holds (unless `Settings::auto_send_preview` is off), and on Ctrl+O.
+
+use super::delivery_hint::DeliveryHint;
+use super::screens::InputCounter;
+use crate::sealing::SendSecurity;
+use crate::storage::MAX_MESSAGE_BYTES;
+
+/// Why a send is previewed before it goes out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewReason {
+    /// Close to the size limit (the input counter shows bytes)
+    NearSizeLimit,
+    /// Goes out in plaintext
+    Plaintext,
+    /// Earlier messages to the contact are still queued
+    Queued,
+}
+
+impl PreviewReason {
+    /// Short label, e.g. "not encrypted"
+    pub fn label(self) -> &'static str {
+        match self {
+            PreviewReason::NearSizeLimit => "near the size limit",
+            PreviewReason::Plaintext => "not encrypted",
+            PreviewReason::Queued => "contact unreachable",
+        }
+    }
+}
+
+/// Reasons to preview a send, in display order (empty: send right away)
+///
+/// # Arguments
+/// * `counter` - The input counter for the message
+/// * `security` - How the message would be protected
+/// * `queue` - Delivery hint for messages already queued to the contact (None: nothing queued)
+pub fn preview_reasons(counter: InputCounter, security: SendSecurity, queue: Option<DeliveryHint>) -> Vec<PreviewReason> {
+    let mut reasons = Vec::new();
+    if matches!(counter, InputCounter::Bytes(_) | InputCounter::TooLarge(_)) {
+        reasons.push(PreviewReason::NearSizeLimit);
+    }
+    if !security.is_encrypted() {
+        reasons.push(PreviewReason::Plaintext);
+    }
+    if queue.is_some() {
+        reasons.push(PreviewReason::Queued);
+    }
+    reasons
+}
+
+/// What sending the chat input will do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendPreview {
+    /// Encoded size of the message, as checked against `MAX_MESSAGE_BYTES`
+    /// (sealing adds its envelope on top)
+    pub bytes: usize,
+    /// Number of requests the message is sent as (always 1)
+    pub parts: usize,
+    /// Message type on the wire, e.g. "text_e2e"
+    pub message_type: String,
+    /// How the message is protected
+    pub security: SendSecurity,
+    /// Who receives it, e.g. "Bob (203.0.113.7:8080)"
+    pub destinations: Vec<String>,
+    /// Messages to the contact still queued
+    pub queued: usize,
+    /// Delivery hint for those messages (None: nothing queued)
+    pub queue_hint: Option<DeliveryHint>,
+    /// Why the preview was shown (empty when asked for)
+    pub reasons: Vec<PreviewReason>,
+}
+
+impl SendPreview {
+    /// Whether the send waits for confirmation when previews are automatic
+    pub fn needs_confirmation(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+
+    /// What happens to the message if it cannot be delivered right away
+    pub fn queue_forecast(&self) -> String {
+        match self.queue_hint {
+            None => "Delivered now if the contact is reachable, queued for retry otherwise".to_string(),
+            Some(DeliveryHint::Waiting) => {
+                format!("{} earlier message(s) still queued: likely queued for retry", self.queued)
+            }
+            Some(DeliveryHint::StaleAddress) => format!(
+                "{} message(s) queued over a day, address may be stale: likely queued",
+                self.queued
+            ),
+            Some(DeliveryHint::Undeliverable) => {
+                "Token expired: queued, undeliverable until a new token is imported".to_string()
+            }
+        }
+    }
+
+    /// Lines of the preview overlay
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if !self.reasons.is_empty() {
+            let reasons: Vec<&str> = self.reasons.iter().map(|r| r.label()).collect();
+            lines.push(format!("Check before sending: {}", reasons.join(", ")));
+        }
+        lines.push(format!("Size: {} / {} bytes", self.bytes, MAX_MESSAGE_BYTES));
+        lines.push(format!("Parts: {}", self.parts));
+        lines.push(format!("Security: {}", self.security.strip_text()));
+        lines.push(format!("To: {}", self.destinations.join(", ")));
+        lines.push(format!("Queue: {}", self.queue_forecast()));
+        lines
+    }
+}
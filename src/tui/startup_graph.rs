@@ -0,0 +1,306 @@
+//! Startup stages and the order they run in
+//!
+//! Startup is a fixed graph of `StartupStage`s. A stage starts once every
+//! stage it depends on is done, and settles when its work reports back
+//! with a `StartupEvent`, never after a timed wait:
+//!
+//! - storage → identity → handlers registered → transport listening
+//! - transport listening → connectivity → reachability check, retry worker
+//!
+//! A stage that does not report within its `timeout` is degraded, and so is
+//! every stage depending on it, without running. Each failure thus leaves a
+//! defined state instead of a thread waiting forever. A report arriving after
+//! the timeout still marks the stage done, but its dependents stay degraded
+//! (a Diagnostics refresh reruns connectivity).
+//!
+//! The graph takes the clock as an argument, so orderings and timeouts can
+//! be checked against a virtual clock.
+
+use std::time::{Duration, Instant};
+
+/// One step of startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StartupStage {
+    /// Schema migrations and message history loaded
+    Storage,
+    /// Our keypair is available
+    Identity,
+    /// Ping and message handlers installed on the transport
+    HandlersRegistered,
+    /// The server is bound and its port is written to the database
+    TransportListening,
+    /// Port mapping and external address detection
+    Connectivity,
+    /// External check that the mapped port is reachable
+    ReachabilityCheck,
+    /// The retry worker is running
+    RetryWorker,
+}
+
+impl StartupStage {
+    /// Every stage, in an order where dependencies come first
+    pub const ALL: [StartupStage; 7] = [
+        StartupStage::Storage,
+        StartupStage::Identity,
+        StartupStage::HandlersRegistered,
+        StartupStage::TransportListening,
+        StartupStage::Connectivity,
+        StartupStage::ReachabilityCheck,
+        StartupStage::RetryWorker,
+    ];
+
+    /// Stages that must be done before this one starts
+    pub fn dependencies(self) -> &'static [StartupStage] {
+        match self {
+            StartupStage::Storage => &[],
+            StartupStage::Identity => &[StartupStage::Storage],
+            StartupStage::HandlersRegistered => &[StartupStage::Identity],
+            StartupStage::TransportListening => &[StartupStage::HandlersRegistered],
+            StartupStage::Connectivity => &[StartupStage::TransportListening],
+            StartupStage::ReachabilityCheck => &[StartupStage::Connectivity],
+            StartupStage::RetryWorker => &[StartupStage::Connectivity],
+        }
+    }
+
+    /// How long the stage may run before it is degraded
+    pub fn timeout(self) -> Duration {
+        match self {
+            StartupStage::Storage => Duration::from_secs(60),
+            StartupStage::Identity => Duration::from_secs(5),
+            StartupStage::HandlersRegistered => Duration::from_secs(10),
+            StartupStage::TransportListening => Duration::from_secs(30),
+            StartupStage::Connectivity => Duration::from_secs(60),
+            StartupStage::ReachabilityCheck => Duration::from_secs(30),
+            StartupStage::RetryWorker => Duration::from_secs(10),
+        }
+    }
+
+    /// Name shown in Diagnostics
+    pub fn name(self) -> &'static str {
+        match self {
+            StartupStage::Storage => "Storage",
+            StartupStage::Identity => "Identity",
+            StartupStage::HandlersRegistered => "Handlers",
+            StartupStage::TransportListening => "Listening",
+            StartupStage::Connectivity => "Connectivity",
+            StartupStage::ReachabilityCheck => "Reachability",
+            StartupStage::RetryWorker => "Retry worker",
+        }
+    }
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|stage| *stage == self).unwrap_or(0)
+    }
+}
+
+/// Where a stage is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StageState {
+    /// Dependencies are not done yet
+    Waiting,
+    /// Started, no report yet
+    Running {
+        /// When it started
+        since: Instant,
+    },
+    /// Reported success
+    Done {
+        /// Time from start to the report
+        took: Duration,
+        /// What it reported, e.g. "port 8080"
+        detail: String,
+    },
+    /// Failed, timed out, or a dependency was degraded
+    Degraded {
+        /// Why
+        reason: String,
+    },
+}
+
+impl StageState {
+    /// Whether the stage is done or degraded
+    pub fn is_settled(&self) -> bool {
+        matches!(self, StageState::Done { .. } | StageState::Degraded { .. })
+    }
+}
+
+/// How a stage's work ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StageOutcome {
+    /// Succeeded, with a detail for Diagnostics
+    Done(String),
+    /// Failed, with the reason
+    Degraded(String),
+}
+
+/// Report of a stage's work, sent by whatever thread did it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupEvent {
+    /// Stage reported on
+    pub stage: StartupStage,
+    /// How it ended
+    pub outcome: StageOutcome,
+    /// Port the server listens on (transport listening only)
+    pub port: Option<u16>,
+}
+
+impl StartupEvent {
+    /// The stage succeeded
+    pub fn done(stage: StartupStage, detail: impl Into<String>) -> Self {
+        Self { stage, outcome: StageOutcome::Done(detail.into()), port: None }
+    }
+
+    /// The stage failed
+    pub fn degraded(stage: StartupStage, reason: impl Into<String>) -> Self {
+        Self { stage, outcome: StageOutcome::Degraded(reason.into()), port: None }
+    }
+
+    /// The server listens on `port`, already written to the database
+    pub fn listening(port: u16) -> Self {
+        Self {
+            stage: StartupStage::TransportListening,
+            outcome: StageOutcome::Done(format!("port {}", port)),
+            port: Some(port),
+        }
+    }
+}
+
+/// Progress of every startup stage
+#[derive(Debug, Clone)]
+pub struct StartupGraph {
+    states: [StageState; 7],
+    /// Reports that came before their stage could start, applied once it does
+    early: Vec<StartupEvent>,
+    port: Option<u16>,
+}
+
+impl Default for StartupGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StartupGraph {
+    /// A graph with every stage waiting
+    pub fn new() -> Self {
+        Self {
+            states: std::array::from_fn(|_| StageState::Waiting),
+            early: Vec::new(),
+            port: None,
+        }
+    }
+
+    /// State of `stage`
+    pub fn state(&self, stage: StartupStage) -> &StageState {
+        &self.states[stage.index()]
+    }
+
+    /// Port reported by the transport listening stage
+    ///
+    /// Known only once the port is in the database, so connectivity never
+    /// maps a port the server has since moved away from.
+    pub fn listening_port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Whether every stage is done or degraded
+    pub fn is_settled(&self) -> bool {
+        self.states.iter().all(StageState::is_settled)
+    }
+
+    /// Degrade the stages past their timeout at `now` and start those whose
+    /// dependencies are done
+    ///
+    /// # Returns
+    /// The stages started, in dependency order; the caller runs their work
+    pub fn advance(&mut self, now: Instant) -> Vec<StartupStage> {
+        let mut started = Vec::new();
+        for stage in StartupStage::ALL {
+            if let StageState::Running { since } = *self.state(stage)
+                && now.duration_since(since) > stage.timeout()
+            {
+                self.settle(stage, StageState::Degraded {
+                    reason: format!("timed out after {}s", stage.timeout().as_secs()),
+                });
+            }
+        }
+
+        // Dependencies come first in ALL, so one pass reaches a fixed point
+        for stage in StartupStage::ALL {
+            if *self.state(stage) != StageState::Waiting {
+                continue;
+            }
+            let degraded = stage
+                .dependencies()
+                .iter()
+                .find(|dependency| matches!(self.state(**dependency), StageState::Degraded { .. }));
+            if let Some(dependency) = degraded {
+                let reason = format!("{} degraded", dependency.name().to_lowercase());
+                self.settle(stage, StageState::Degraded { reason });
+            } else if stage.dependencies().iter().all(|dependency| matches!(self.state(*dependency), StageState::Done { .. })) {
+                self.states[stage.index()] = StageState::Running { since: now };
+                started.push(stage);
+                if let Some(position) = self.early.iter().position(|event| event.stage == stage) {
+                    let event = self.early.remove(position);
+                    self.record(event, now);
+                }
+            }
+        }
+        started
+    }
+
+    /// Apply a stage's report at `now`
+    ///
+    /// A report for a stage that has not started yet is held until it does.
+    /// A success reported after the stage was degraded marks it done.
+    ///
+    /// # Returns
+    /// Whether the stage's state changed
+    pub fn record(&mut self, event: StartupEvent, now: Instant) -> bool {
+        let (took, late) = match self.state(event.stage) {
+            StageState::Waiting => {
+                self.early.push(event);
+                return false;
+            }
+            StageState::Running { since } => (now.duration_since(*since), false),
+            StageState::Degraded { .. } if matches!(event.outcome, StageOutcome::Done(_)) => (Duration::ZERO, true),
+            StageState::Done { .. } | StageState::Degraded { .. } => return false,
+        };
+        if event.port.is_some() {
+            self.port = event.port;
+        }
+        let state = match event.outcome {
+            StageOutcome::Done(detail) if late => StageState::Done { took, detail: format!("{}, late", detail) },
+            StageOutcome::Done(detail) => StageState::Done { took, detail },
+            StageOutcome::Degraded(reason) => StageState::Degraded { reason },
+        };
+        self.settle(event.stage, state);
+        true
+    }
+
+    fn settle(&mut self, stage: StartupStage, state: StageState) {
+        match &state {
+            StageState::Degraded { reason } => tracing::warn!("Startup stage {} degraded: {}", stage.name(), reason),
+            StageState::Done { took, detail } => tracing::debug!("Startup stage {} done in {:?}: {}", stage.name(), took, detail),
+            _ => {}
+        }
+        self.states[stage.index()] = state;
+    }
+
+    /// One line per stage for Diagnostics, e.g. "Listening: done in 12ms (port 8080)"
+    pub fn describe(&self, now: Instant) -> Vec<String> {
+        StartupStage::ALL
+            .iter()
+            .map(|stage| {
+                let state = match self.state(*stage) {
+                    StageState::Waiting => "waiting".to_string(),
+                    StageState::Running { since } => format!("running for {}s", now.duration_since(*since).as_secs()),
+                    StageState::Done { took, detail } if detail.is_empty() => format!("done in {}ms", took.as_millis()),
+                    StageState::Done { took, detail } => format!("done in {}ms ({})", took.as_millis(), detail),
+                    StageState::Degraded { reason } => format!("degraded: {}", reason),
+                };
+                format!("{}: {}", stage.name(), state)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,62 @@
+//! Colour theme and per-profile accent
+//!
+//! Screens take their title and selection colours from a `Theme` instead of
+//! hard-coding them. A profile accent overrides only those slots, so the
+//! status colours (green delivered, yellow pending, red errors) keep their
+//! meaning whatever accent is chosen.
+
+use ratatui::style::Color;
+use crate::storage::AccentColor;
+
+/// Colours shared by every screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Screen title bars
+    pub title: Color,
+    /// Selected item and selection marker in lists
+    pub selection: Color,
+    /// Identity line (profile label and UID fingerprint)
+    pub identity: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            title: Color::Cyan,
+            selection: Color::Yellow,
+            identity: Color::Green,
+        }
+    }
+}
+
+impl Theme {
+    /// Apply a profile accent on top of this theme
+    ///
+    /// Overrides the title, selection and identity slots; `None` leaves the
+    /// theme unchanged.
+    pub fn with_accent(self, accent: Option<AccentColor>) -> Self {
+        match accent {
+            Some(accent) => {
+                let color = accent_color(accent);
+                Self {
+                    title: color,
+                    selection: color,
+                    identity: color,
+                }
+            }
+            None => self,
+        }
+    }
+}
+
+/// Terminal colour for an accent
+pub fn accent_color(accent: AccentColor) -> Color {
+    match accent {
+        AccentColor::Cyan => Color::Cyan,
+        AccentColor::Green => Color::Green,
+        AccentColor::Yellow => Color::Yellow,
+        AccentColor::Magenta => Color::Magenta,
+        AccentColor::Blue => Color::LightBlue,
+        AccentColor::Red => Color::LightRed,
+    }
+}
@@ -17,6 +17,14 @@ pub enum Screen {
     Settings,
     /// Network diagnostics
     Diagnostics,
+    /// Database snapshots and the snapshot diff (from Diagnostics)
+    Snapshots,
+    /// Deletes of this session that can be undone (from Diagnostics)
+    Maintenance,
+    /// Raw lines of a debug capture file (from the contact details popup)
+    Capture,
+    /// One-time summary of recovery after an unclean exit (before the main menu)
+    Recovery,
 }
 
 /// Main menu items
@@ -73,3 +81,33 @@ impl MenuItem {
         }
     }
 }
+
+/// Duration of each startup phase, in the order the phases ran
+#[derive(Debug, Clone, Default)]
+pub struct StartupTimings {
+    /// Phase name and duration
+    pub phases: Vec<(String, std::time::Duration)>,
+}
+
+impl StartupTimings {
+    /// Create an empty timing report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record how long a phase took
+    pub fn record(&mut self, phase: &str, duration: std::time::Duration) {
+        tracing::debug!("Startup phase '{}' took {:?}", phase, duration);
+        self.phases.push((phase.to_string(), duration));
+    }
+
+    /// Total time across all recorded phases
+    pub fn total(&self) -> std::time::Duration {
+        self.phases.iter().map(|(_, d)| *d).sum()
+    }
+
+    /// Get the duration of a recorded phase
+    pub fn get(&self, phase: &str) -> Option<std::time::Duration> {
+        self.phases.iter().find(|(name, _)| name == phase).map(|(_, d)| *d)
+    }
+}
@@ -0,0 +1,58 @@
+//! Capture screen rendering: raw lines of a debug capture (or log) file
+
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use crate::tui::app::App;
+use super::helpers::footer_block;
+
+/// Renders the screen
+pub fn render_capture(f: &mut Frame, app: &App) {
+    let size = f.size();
+    let Some(screen) = &app.capture_screen else {
+        return;
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3),  // Title
+            Constraint::Min(5),     // Raw lines
+            Constraint::Length(3),  // Help text
+        ])
+        .split(size);
+
+    let capturing = app
+        .transport
+        .capture()
+        .status(chrono::Utc::now())
+        .is_some_and(|status| status.path == screen.path);
+    let title = format!(
+        "{} {}{}",
+        screen.label,
+        screen.path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default(),
+        if capturing { " (recording)" } else { "" }
+    );
+    let title = Paragraph::new(title)
+        .style(Style::default().fg(app.theme().title).add_modifier(Modifier::BOLD))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(title, chunks[0]);
+
+    let body = match &screen.error {
+        Some(error) => Paragraph::new(format!("Cannot read the {}: {}", screen.label.to_lowercase(), error)).style(Style::default().fg(Color::Red)),
+        None => Paragraph::new(screen.lines.join("\n")).scroll((screen.scroll.min(u16::MAX as usize) as u16, 0)),
+    };
+    let lines_title = format!("{} lines", screen.lines.len());
+    f.render_widget(body.block(Block::default().borders(Borders::ALL).title(lines_title)), chunks[1]);
+
+    let help = Paragraph::new("↑↓/PgUp/PgDn: Scroll | r: Reload | Esc: Back")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center)
+        .block(footer_block(app));
+    f.render_widget(help, chunks[2]);
+}
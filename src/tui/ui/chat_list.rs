@@ -7,8 +7,104 @@ use ratatui::{
     widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
     Frame,
 };
-use crate::storage::Chat;
+use crate::storage::{local_time_at, AddressChange, AlertStyle, AppState, Chat, Contact, IdentityConflict, OutboundPolicy, PrivacySignal, Settings, MIN_OFFSET_SAMPLES};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use crate::tui::app::App;
+use super::helpers::footer_block;
+use crate::tui::screens::{CapturePrompt, ContactDetailsPopup};
+use crate::transport::capture::{CaptureStatus, CAPTURE_MAX_EXCHANGES, CAPTURE_MAX_MINUTES};
+
+/// Indicator of a chat list row, highest priority first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatRowStatus {
+    /// The contact's token has expired
+    Expired,
+    /// A message was dropped after its retries
+    Failed,
+    /// Messages are waiting in the queue
+    Pending,
+    /// New or unread messages
+    Active,
+    /// Nothing new
+    Read,
+}
+
+/// One row of the chat list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatListRow<'a> {
+    /// Contact UID of the chat
+    pub contact_uid: &'a str,
+    /// Messages in the chat: those loaded, or the stored count while
+    /// history is not loaded yet
+    pub message_count: usize,
+    /// Indicator shown in front of the row
+    pub status: ChatRowStatus,
+    /// The contact is marked verified
+    pub verified: bool,
+    /// For a temporary contact, when it is deleted
+    pub temporary_until: Option<DateTime<Utc>>,
+}
+
+/// Rows of the chat list at `now`, in chat order
+///
+/// Expiry and verification are looked up once per contact for the whole
+/// list, not once per row.
+pub fn chat_list_rows(app_state: &AppState, now: DateTime<Utc>) -> Vec<ChatListRow<'_>> {
+    let contacts: HashMap<&str, &Contact> = app_state.contacts.iter().map(|c| (c.uid.as_str(), c)).collect();
+
+    app_state
+        .chats
+        .iter()
+        .map(|chat| {
+            let contact = contacts.get(chat.contact_uid.as_str());
+            let expired = contact.is_some_and(|c| now > c.expiry);
+            let status = if expired {
+                ChatRowStatus::Expired
+            } else if chat.has_failed_messages {
+                ChatRowStatus::Failed
+            } else if chat.has_pending_messages {
+                ChatRowStatus::Pending
+            } else if chat.is_active {
+                ChatRowStatus::Active
+            } else {
+                ChatRowStatus::Read
+            };
+            ChatListRow {
+                contact_uid: &chat.contact_uid,
+                message_count: match &chat.stored_summary {
+                    Some(summary) if chat.messages.is_empty() => summary.message_count as usize,
+                    _ => chat.messages.len(),
+                },
+                status,
+                verified: contact.is_some_and(|c| c.verified),
+                temporary_until: contact.and_then(|c| c.ephemeral).map(|e| e.until),
+            }
+        })
+        .collect()
+}
+
+/// Badge of a temporary contact's row, e.g. " [temp 5h]"
+pub fn temporary_badge(until: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let left = until - now;
+    if left.num_hours() >= 48 {
+        format!(" [temp {}d]", left.num_days())
+    } else {
+        format!(" [temp {}h]", left.num_hours().max(0))
+    }
+}
+
+/// Question of the bulk delete popup, e.g. "Delete 3 chats (1 active, 2 inactive)?"
+pub fn bulk_delete_text(chats: &[&Chat]) -> String {
+    let active = chats.iter().filter(|chat| chat.is_active).count();
+    format!(
+        "Delete {} chat{} ({} active, {} inactive)?",
+        chats.len(),
+        if chats.len() == 1 { "" } else { "s" },
+        active,
+        chats.len() - active
+    )
+}
 
 /// Renders the screen
 
@@ -29,10 +125,30 @@ pub fn render_chat_list(f: &mut Frame, app: &App) {
             .split(size);
 
         // Title
-        let title = Paragraph::new(format!("Chat List ({} chats)", app.app_state.chats.len()))
+        let mut title_text = format!("Chat List ({} chats", app.app_state.chats.len());
+        if let Some(badge) = app.chat_summary().badge() {
+            title_text.push_str(&format!(", {}", badge));
+        }
+        if !app.messages_loaded {
+            title_text.push_str(", loading history...");
+        }
+        title_text.push(')');
+        if app.notifications.quiet {
+            title_text.push_str(" ☾ Quiet hours");
+        }
+        let conflict_count = app.app_state.identity_conflicts.len();
+        if conflict_count > 0 {
+            title_text.push_str(&format!(" ⚠ {} identity conflict(s), press ! to review", conflict_count));
+        }
+        let address_change_count = app.app_state.address_changes.len();
+        if address_change_count > 0 {
+            title_text.push_str(&format!(" ⚠ {} address change(s) to review", address_change_count));
+        }
+        let title_color = if conflict_count + address_change_count > 0 { Color::Yellow } else { app.theme().title };
+        let title = Paragraph::new(title_text)
             .style(
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(title_color)
                     .add_modifier(Modifier::BOLD),
             )
             .alignment(Alignment::Center)
@@ -47,58 +163,57 @@ pub fn render_chat_list(f: &mut Frame, app: &App) {
                 .block(Block::default().borders(Borders::ALL).title("Chats"));
             f.render_widget(empty_msg, chunks[1]);
         } else {
-            let chat_items: Vec<ListItem> = app
-                .app_state
-                .chats
-                .iter()
+            let now = Utc::now();
+            let chat_items: Vec<ListItem> = chat_list_rows(&app.app_state, now)
+                .into_iter()
                 .enumerate()
-                .map(|(i, chat)| {
-                    let uid_short = &chat.contact_uid[..16.min(chat.contact_uid.len())];
-                    let msg_count = chat.messages.len();
-
-                    // Check if contact is expired
-                    let contact_expired = app.app_state.contacts
-                        .iter()
-                        .find(|c| c.uid == chat.contact_uid)
-                        .map(|c| c.is_expired())
-                        .unwrap_or(false);
-
-                    // Determine style and indicator with priority system:
-                    // Priority 1: Expired contact (highest urgency)
-                    // Priority 2: Pending messages (action needed)
-                    // Priority 3: New/unread messages (active chat)
-                    // Priority 4: Inactive/read (lowest)
-                    let (style, indicator) = if contact_expired {
+                .map(|(i, row)| {
+                    let uid_short = &row.contact_uid[..16.min(row.contact_uid.len())];
+                    let (style, indicator) = match row.status {
                         // Expired contact - highest priority, red warning
-                        (Style::default().fg(Color::Red).add_modifier(Modifier::BOLD), "⚠ ")
-                    } else if chat.has_pending_messages {
+                        ChatRowStatus::Expired => (Style::default().fg(Color::Red).add_modifier(Modifier::BOLD), "⚠ "),
+                        // Failed delivery - red cross
+                        ChatRowStatus::Failed => (Style::default().fg(Color::Red), "✗ "),
                         // Pending messages - highlighted in yellow with hourglass
-                        (Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD), "⌛ ")
-                    } else if chat.is_active {
+                        ChatRowStatus::Pending => (Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD), "⌛ "),
                         // Active chat with new/unread messages - green dot
-                        (Style::default().fg(Color::Green).add_modifier(Modifier::BOLD), "● ")
-                    } else {
+                        ChatRowStatus::Active => (Style::default().fg(Color::Green).add_modifier(Modifier::BOLD), "● "),
                         // Inactive chat - dimmed gray circle
-                        (Style::default().fg(Color::DarkGray), "○ ")
+                        ChatRowStatus::Read => (Style::default().fg(Color::DarkGray), "○ "),
+                    };
+                    let verified = if row.verified { " ✓" } else { "" };
+                    let temporary = row.temporary_until.map(|until| temporary_badge(until, now)).unwrap_or_default();
+                    let capture = if app.capture_badge(row.contact_uid) { " [REC]" } else { "" };
+                    let archived = app.app_state.chats[i].archived;
+                    let style = if archived { style.add_modifier(Modifier::DIM) } else { style };
+                    let text = format!(
+                        "{} ({} msgs){}{}{}{}",
+                        uid_short,
+                        row.message_count,
+                        verified,
+                        temporary,
+                        capture,
+                        if archived { " [archived]" } else { "" }
+                    );
+                    let checkbox = match (screen.marked.is_empty(), screen.is_marked(row.contact_uid)) {
+                        (true, _) => "",
+                        (false, true) => "[x] ",
+                        (false, false) => "[ ] ",
                     };
 
                     let content = if i == screen.selected_index {
                         Line::from(vec![
-                            Span::styled("→ ", Style::default().fg(Color::Cyan)),
+                            Span::styled("→ ", Style::default().fg(app.theme().selection)),
+                            Span::raw(checkbox),
                             Span::styled(indicator, style),
-                            Span::styled(
-                                format!("{} ({} msgs)", uid_short, msg_count),
-                                style,
-                            ),
+                            Span::styled(text, style),
                         ])
                     } else {
                         Line::from(vec![
                             Span::raw("  "),
+                            Span::raw(checkbox),
                             Span::styled(indicator, style),
-                            Span::styled(
-                                format!("{} ({} msgs)", uid_short, msg_count),
-                                style,
-                            ),
+                            Span::styled(text, style),
                         ])
                     };
                     ListItem::new(content)
@@ -108,7 +223,7 @@ pub fn render_chat_list(f: &mut Frame, app: &App) {
             let chat_list = List::new(chat_items).block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .title("Chats (● New Messages | ⌛ Pending | ⚠ Expired | ○ Read)")
+                    .title("Chats (● New Messages | ⌛ Pending | ✗ Failed | ⚠ Expired | ○ Read)")
                     .style(Style::default()),
             );
             f.render_widget(chat_list, chunks[1]);
@@ -127,11 +242,15 @@ pub fn render_chat_list(f: &mut Frame, app: &App) {
         f.render_widget(status_widget, chunks[2]);
 
         // Help text
-        let help_text = "↑↓/j/k: Navigate | Enter: Open | d/Del: Delete | Esc: Back";
+        let help_text = if screen.marked.is_empty() {
+            "↑↓/j/k: Navigate | 1-9: Open Nth | Tab: Recent | Enter: Open | i: Details | d/Del: Delete | Space/V: Mark/all | a/r/m: Archive/Read/Mute | !: Conflicts | Esc: Back"
+        } else {
+            "Space: Mark | V: Mark all | a: Archive | r: Mark read | m: Mute | d: Delete marked | Esc: Clear marks"
+        };
         let help = Paragraph::new(help_text)
             .style(Style::default().fg(Color::DarkGray))
             .alignment(Alignment::Center)
-            .block(Block::default().borders(Borders::ALL));
+            .block(footer_block(app));
         f.render_widget(help, chunks[3]);
 
         // Render confirmation popup if shown
@@ -143,9 +262,385 @@ pub fn render_chat_list(f: &mut Frame, app: &App) {
                 }
             }
         }
+
+        if screen.show_bulk_delete_confirmation {
+            render_bulk_delete_popup(f, size, &screen.marked_in(&app.app_state.chats));
+        }
+
+        // Render contact details popup if shown
+        let details = screen.contact_details.as_ref().and_then(|popup| {
+            app.app_state.contact_by_uid(&popup.contact_uid).map(|contact| (contact, popup))
+        });
+        if let Some((contact, popup)) = details {
+            let chat = app.app_state.chat_by_uid(&contact.uid);
+            let mut history = history_limit_text(chat, app.app_state.settings.history_limit);
+            history.push_str(&alert_style_text(contact, chat, app.app_state.settings.alert_style));
+            let privacy = privacy_text(contact, &app.app_state.settings);
+            let change = app.app_state.address_change(&contact.uid);
+            let mut status = vec![Line::from(history), Line::from(privacy)];
+            status.push(local_time_line(contact, popup.offset_input.as_deref(), Utc::now()));
+            if let Some(capture) = app.transport.capture().status(Utc::now()).filter(|s| s.contact_uid == contact.uid) {
+                status.push(capture_line(&capture));
+            }
+            render_contact_details_popup(f, size, contact, popup, status, change);
+        }
+
+        // Render identity conflict review popup if shown
+        if let Some(selected) = screen.conflict_review {
+            render_conflict_review_popup(f, size, &app.app_state.identity_conflicts, selected);
+        }
+    }
+}
+
+fn render_conflict_review_popup(
+    f: &mut Frame,
+    area: ratatui::layout::Rect,
+    conflicts: &[IdentityConflict],
+    selected: usize,
+) {
+    let popup_width = 76;
+    let popup_height = 18;
+
+    let popup_area = ratatui::layout::Rect {
+        x: area.width.saturating_sub(popup_width) / 2,
+        y: area.height.saturating_sub(popup_height) / 2,
+        width: popup_width.min(area.width),
+        height: popup_height.min(area.height),
+    };
+
+    let popup_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(3),     // Conflict list
+            Constraint::Length(4),  // Selected conflict
+            Constraint::Length(1),  // Help
+        ])
+        .split(popup_area);
+
+    let background = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(format!("Identity Conflicts ({})", conflicts.len()))
+        .style(Style::default().bg(Color::Black));
+    f.render_widget(background, popup_area);
+
+    let items: Vec<ListItem> = conflicts
+        .iter()
+        .enumerate()
+        .map(|(i, conflict)| {
+            let uid_short = &conflict.contact.uid[..16.min(conflict.contact.uid.len())];
+            let text = format!("{}: {}", uid_short, conflict.kind);
+            if i == selected {
+                ListItem::new(Line::from(vec![
+                    Span::styled("→ ", Style::default().fg(Color::Cyan)),
+                    Span::styled(text, Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                ]))
+            } else {
+                ListItem::new(Line::from(vec![Span::raw("  "), Span::raw(text)]))
+            }
+        })
+        .collect();
+    f.render_widget(List::new(items), popup_chunks[0]);
+
+    if let Some(conflict) = conflicts.get(selected) {
+        let details = vec![
+            Line::from(format!("Held back: {} at {}", conflict.contact.uid, conflict.contact.ip)),
+            Line::from(format!("Stored:    {}", conflict.existing_uid)),
+            Line::from(format!("Detected:  {}", conflict.detected_at.format("%Y-%m-%d %H:%M UTC"))),
+        ];
+        f.render_widget(
+            Paragraph::new(details).block(Block::default().borders(Borders::TOP)),
+            popup_chunks[1],
+        );
+    }
+
+    let help = Paragraph::new("↑↓: Select | x: Dismiss | Esc: Close")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    f.render_widget(help, popup_chunks[2]);
+}
+
+/// "Sends: ..." line of the contact details popup, e.g. "Sends: receipts off | typing default (on) | ..."
+fn privacy_text(contact: &Contact, settings: &Settings) -> String {
+    if OutboundPolicy::for_contact(contact).is_minimized() {
+        return "Sends: nothing (restricted: receipts, typing and presence off)".to_string();
+    }
+    let parts: Vec<String> = PrivacySignal::ALL
+        .iter()
+        .map(|signal| format!("{} {}", signal.label(), contact.privacy.get(*signal).describe(settings.sends(*signal))))
+        .collect();
+    format!("Sends: {}", parts.join(" | "))
+}
+
+/// "History: ..." line of the contact details popup (also notes a muted chat)
+fn history_limit_text(chat: Option<&Chat>, global_limit: u32) -> String {
+    let describe = |limit: u32| match limit {
+        0 => "unlimited".to_string(),
+        limit => format!("last {} messages", limit),
+    };
+    let mut text = match chat.and_then(|c| c.history_limit) {
+        Some(limit) => format!("History: {}", describe(limit)),
+        None => format!("History: {} (default)", describe(global_limit)),
+    };
+    if chat.is_some_and(|c| c.is_trimmed()) {
+        text.push_str(", older messages removed");
+    }
+    if chat.is_some_and(|c| c.muted) {
+        text.push_str(" | Alerts muted");
+    }
+    text
+}
+
+/// " | Alert: ..." part of the contact details history line, e.g. " | Alert: urgent"
+fn alert_style_text(contact: &Contact, chat: Option<&Chat>, global: AlertStyle) -> String {
+    if chat.is_some_and(|c| c.muted) {
+        return String::new();
+    }
+    match contact.alert_style {
+        Some(style) => format!(" | Alert: {}", style.name()),
+        None => format!(" | Alert: {} (default)", global.name()),
+    }
+}
+
+/// Contact details line with the contact's local time, or the UTC offset input while editing
+fn local_time_line(contact: &Contact, offset_input: Option<&str>, now: DateTime<Utc>) -> Line<'static> {
+    if let Some(input) = offset_input {
+        return Line::from(Span::styled(
+            format!("UTC offset: {}_  (e.g. +5:30; empty infers it | Enter: Save | Esc: Cancel)", input),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    let text = match contact.utc_offset(now) {
+        Some(offset) => format!(
+            "Local time: {} ({}) | z: Set offset",
+            local_time_at(now, offset.minutes()).format("%H:%M"),
+            offset.describe()
+        ),
+        None if contact.offset_samples.len() >= MIN_OFFSET_SAMPLES => {
+            "Local time: unknown (message times fit no time zone) | z: Set offset".to_string()
+        }
+        None => format!(
+            "Local time: unknown ({}/{} messages to infer) | z: Set offset",
+            contact.offset_samples.len(),
+            MIN_OFFSET_SAMPLES
+        ),
+    };
+    Line::from(text)
+}
+
+/// Contact details line of a running capture of that contact
+fn capture_line(capture: &CaptureStatus) -> Line<'static> {
+    Line::from(Span::styled(
+        format!(
+            "● Capturing: {}/{} exchanges, until {}{} | c: Stop | w: View",
+            capture.exchanges,
+            CAPTURE_MAX_EXCHANGES,
+            capture.expires_at.format("%H:%M UTC"),
+            if capture.include_bodies { ", with bodies" } else { "" },
+        ),
+        Style::default().fg(Color::Red),
+    ))
+}
+
+fn render_contact_details_popup(
+    f: &mut Frame,
+    area: ratatui::layout::Rect,
+    contact: &Contact,
+    popup: &ContactDetailsPopup,
+    status: Vec<Line<'static>>,
+    change: Option<&AddressChange>,
+) {
+    let popup_width = 78;
+    let extra_rows = if change.is_some() { 2 } else { 0 } + u16::from(contact.is_ephemeral()) + (status.len() as u16).saturating_sub(2);
+    let popup_height = if popup.notes_expanded || popup.notes_editor.is_some() { 27 } else { 17 } + extra_rows;
+
+    let popup_area = ratatui::layout::Rect {
+        x: area.width.saturating_sub(popup_width) / 2,
+        y: area.height.saturating_sub(popup_height) / 2,
+        width: popup_width.min(area.width),
+        height: popup_height.min(area.height),
+    };
+
+    let popup_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(5 + extra_rows),  // Contact info, privacy (temporary marker, staged address, capture)
+            Constraint::Min(3),     // Notes
+            Constraint::Length(3),  // Help
+        ])
+        .split(popup_area);
+
+    let background = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .title("Contact Details")
+        .style(Style::default().bg(Color::Black));
+    f.render_widget(background, popup_area);
+
+    // Contact info
+    let mut info = vec![
+        Line::from(vec![
+            Span::raw("UID: "),
+            Span::styled(contact.uid.as_str(), Style::default().fg(Color::Cyan)),
+        ]),
+        Line::from(format!(
+            "Address: {} | Verified: {} | Trust: {}",
+            contact.ip,
+            if contact.verified { "yes" } else { "no" },
+            contact.trust.as_str()
+        )),
+        Line::from(format!(
+            "{} | Capabilities: {}",
+            match contact.requested_expiry {
+                Some(requested) => format!(
+                    "Expires: {} (clamped, token asked for {})",
+                    contact.expiry.format("%Y-%m-%d %H:%M UTC"),
+                    requested.format("%Y-%m-%d")
+                ),
+                None => format!("Expires: {}", contact.expiry.format("%Y-%m-%d %H:%M UTC")),
+            },
+            contact.capabilities.map_or("unknown".to_string(), |record| record.describe())
+        )),
+    ];
+    if let Some(ephemeral) = contact.ephemeral {
+        info.push(Line::from(Span::styled(
+            format!("Temporary until {} | k: Keep permanently", ephemeral.until.format("%Y-%m-%d %H:%M UTC")),
+            Style::default().fg(Color::Magenta),
+        )));
+    }
+    info.extend(status);
+    if let Some(change) = change {
+        let warning = Style::default().fg(Color::Yellow);
+        info.push(Line::from(Span::styled(
+            format!(
+                "⚠ Claims new address {} (via {}, {}, {})",
+                change.ip,
+                change.source.describe(),
+                if change.signature_verified { "signed" } else { "unsigned" },
+                change.reported_at.format("%Y-%m-%d %H:%M UTC"),
+            ),
+            warning,
+        )));
+        info.push(Line::from(Span::styled(
+            format!("a: Apply | g: Ignore ({} failed deliveries to the current address)", change.failed_deliveries),
+            warning,
+        )));
     }
+    f.render_widget(Paragraph::new(info), popup_chunks[0]);
+
+    // Notes (editor, full, or preview)
+    let (notes_text, notes_title, help_text) = if let Some(editor) = &popup.notes_editor {
+        let title = editor
+            .status_message
+            .clone()
+            .unwrap_or_else(|| format!("Edit Notes ({} bytes left)", editor.remaining_bytes()));
+        (format!("{}_", editor.buffer), title, "Ctrl+S: Save | Enter: New line | Esc: Cancel")
+    } else if let Some(prompt) = popup.capture_prompt {
+        let text = match prompt {
+            CapturePrompt::Confirm => format!(
+                "Record every request to and from this contact (never other contacts) into a file under app_data/captures/?\n\n\
+                 Stops after {} minutes or {} exchanges. Bodies are kept only as length and hash.",
+                CAPTURE_MAX_MINUTES, CAPTURE_MAX_EXCHANGES
+            ),
+            CapturePrompt::ConfirmBodies => "Also write the message bodies themselves into the capture file?\n\n\
+                 Anyone who gets the file can read them. The file is only shared if you add it to a diagnostics bundle."
+                .to_string(),
+        };
+        let help = match prompt {
+            CapturePrompt::Confirm => "y: Capture | b: Capture with bodies | Esc: Cancel",
+            CapturePrompt::ConfirmBodies => "y: Yes, include bodies | Esc: Cancel",
+        };
+        (text, "Debug Capture".to_string(), help)
+    } else if popup.confirm_delete {
+        (
+            "Delete this contact and its chat?\n\nKeep the notes in case the contact is imported again?".to_string(),
+            "Delete Contact".to_string(),
+            "k: Delete, keep notes | d: Delete, discard notes | Esc: Cancel",
+        )
+    } else if contact.notes.is_empty() {
+        ("No notes".to_string(), "Notes".to_string(), "n: Edit notes | h: History | m: Mute | s/b: Alert/test | v: Verify | r/t/p: Receipts/typing/presence | u: Trust | c: Capture | x: Delete | Esc: Close")
+    } else if popup.notes_expanded {
+        (contact.notes.clone(), "Notes".to_string(), "e: Collapse | n: Edit notes | h: History | m: Mute | s/b: Alert/test | v: Verify | r/t/p: Receipts/typing/presence | u: Trust | c: Capture | x: Delete | Esc: Close")
+    } else {
+        let (preview, truncated) = contact.notes_preview(ContactDetailsPopup::PREVIEW_LINES);
+        let help = if truncated {
+            "e: Expand | n: Edit notes | h: History | m: Mute | s/b: Alert/test | v: Verify | r/t/p: Receipts/typing/presence | u: Trust | c: Capture | x: Delete | Esc: Close"
+        } else {
+            "n: Edit notes | h: History | m: Mute | s/b: Alert/test | v: Verify | r/t/p: Receipts/typing/presence | u: Trust | c: Capture | x: Delete | Esc: Close"
+        };
+        let text = if truncated { format!("{}\n…", preview) } else { preview };
+        (text, "Notes".to_string(), help)
+    };
+
+    let notes = Paragraph::new(notes_text)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(notes_title));
+    f.render_widget(notes, popup_chunks[1]);
+
+    let help = Paragraph::new(help_text)
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    f.render_widget(help, popup_chunks[2]);
 }
 
+fn render_bulk_delete_popup(f: &mut Frame, area: ratatui::layout::Rect, chats: &[&Chat]) {
+    let popup_width = 60;
+    let popup_height = 10;
+
+    let popup_area = ratatui::layout::Rect {
+        x: area.width.saturating_sub(popup_width) / 2,
+        y: area.height.saturating_sub(popup_height) / 2,
+        width: popup_width.min(area.width),
+        height: popup_height.min(area.height),
+    };
+
+    let popup_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(3),  // Title
+            Constraint::Length(4),  // Message
+            Constraint::Length(2),  // Buttons
+        ])
+        .split(popup_area);
+
+    let background = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Red))
+        .style(Style::default().bg(Color::Black));
+    f.render_widget(background, popup_area);
+
+    let title = Paragraph::new("Confirm Delete")
+        .style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::BOTTOM));
+    f.render_widget(title, popup_chunks[0]);
+
+    let message_text = vec![
+        Line::from(bulk_delete_text(chats)),
+        Line::from(""),
+        Line::from(Span::styled(
+            "The chats are removed locally; U undoes the last one.",
+            Style::default().fg(Color::Yellow),
+        )),
+    ];
+    let message = Paragraph::new(message_text)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: false });
+    f.render_widget(message, popup_chunks[1]);
+
+    let buttons = Paragraph::new(Line::from(vec![
+        Span::styled("[Y]", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+        Span::raw("es  "),
+        Span::styled("[N]", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        Span::raw("o"),
+    ]))
+    .alignment(Alignment::Center);
+    f.render_widget(buttons, popup_chunks[2]);
+}
 
 fn render_delete_confirmation_popup(f: &mut Frame, area: ratatui::layout::Rect, chat: &Chat) {
     // Create a centered popup area
@@ -4,11 +4,33 @@ use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, Clear, Paragraph, Wrap,
+    },
     Frame,
 };
-use chrono::DateTime;
-use crate::tui::app::App;
+use chrono::{DateTime, Utc};
+use super::helpers::footer_block;
+use crate::{
+    edits::{word_diff, DiffSpan},
+    storage::{sanitize_text, Chat, Message},
+    tui::{
+        app::App,
+        delivery_hint::{queued_annotation, DeliveryHint},
+        screens::{ChatViewScreen, FailedReview, InputCounter},
+        send_preview::SendPreview,
+    },
+};
+
+/// Characters of message text shown per pinned strip entry
+const PINNED_PREVIEW_CHARS: usize = 50;
+
+/// Input lines shown before the input box stops growing
+const MAX_INPUT_LINES: usize = 6;
+
+/// Templates listed in the picker at once
+const TEMPLATE_PICKER_ROWS: usize = 8;
 
 /// Renders the screen
 
@@ -17,49 +39,96 @@ pub fn render_chat_view(f: &mut Frame, app: &App) {
 
     if let Some(screen) = &app.chat_view_screen {
         // Find the chat
-        let chat = app.app_state.chats.iter()
-            .find(|c| c.contact_uid == screen.contact_uid);
+        let chat = app.app_state.chat_by_uid(&screen.contact_uid);
 
         if let Some(chat) = chat {
-            // Create layout
-            let chunks = Layout::default()
+            // Create layout (pinned strip only when something is pinned)
+            let pinned_count = chat.pinned_messages().len();
+            let mut constraints = vec![Constraint::Length(3)]; // Title
+            if pinned_count > 0 {
+                constraints.push(Constraint::Length(pinned_count as u16 + 2)); // Pinned strip
+            }
+            let input_lines = screen.input.split('\n').count().min(MAX_INPUT_LINES);
+            let now = Utc::now();
+            let hint = app.chat_delivery_hint(now);
+            let banner = hint.and_then(DeliveryHint::banner);
+            let failed_banner = failed_banner(screen.failed_ids.len());
+            constraints.push(Constraint::Min(5)); // Message history
+            if banner.is_some() {
+                constraints.push(Constraint::Length(1)); // Delivery banner
+            }
+            if failed_banner.is_some() {
+                constraints.push(Constraint::Length(1)); // Failed messages banner
+            }
+            constraints.extend([
+                Constraint::Length(input_lines as u16 + 2),  // Input box
+                Constraint::Length(3),  // Status/Help
+            ]);
+            let mut chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .margin(2)
-                .constraints([
-                    Constraint::Length(3),  // Title
-                    Constraint::Min(5),     // Message history
-                    Constraint::Length(3),  // Input box
-                    Constraint::Length(3),  // Status/Help
-                ])
-                .split(size);
-
-            // Title - show contact UID
-            let uid_short = &chat.contact_uid[..16.min(chat.contact_uid.len())];
-            let title = Paragraph::new(format!("Chat with {}", uid_short))
+                .constraints(constraints)
+                .split(size)
+                .to_vec();
+            if pinned_count > 0 {
+                render_pinned_strip(f, chunks.remove(1), chat, screen);
+            }
+            if let Some(banner) = banner {
+                // Dim while the address may be stale, red once delivery is impossible
+                let style = if hint == Some(DeliveryHint::Undeliverable) {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::DIM)
+                };
+                let banner = Paragraph::new(banner).style(style).alignment(Alignment::Center);
+                f.render_widget(banner, chunks.remove(2));
+            }
+            if let Some(failed_banner) = &failed_banner {
+                let banner = Paragraph::new(failed_banner.as_str())
+                    .style(Style::default().fg(Color::Red))
+                    .alignment(Alignment::Center);
+                f.render_widget(banner, chunks.remove(2));
+            }
+
+            // Title - contact UID and the identity we send as, with the
+            // security strip (why messages are or are not encrypted) below
+            let theme = app.theme();
+            let (strip_text, encrypted) = app.security_strip(&chat.contact_uid);
+            let strip_color = if encrypted { Color::Green } else { Color::Yellow };
+            let strip = Title::from(Span::styled(format!(" {} ", strip_text), Style::default().fg(strip_color)))
+                .position(Position::Bottom)
+                .alignment(Alignment::Center);
+            let title = Paragraph::new(app.chat_title(&chat.contact_uid))
                 .style(
                     Style::default()
-                        .fg(Color::Cyan)
+                        .fg(theme.title)
                         .add_modifier(Modifier::BOLD),
                 )
                 .alignment(Alignment::Center)
-                .block(Block::default().borders(Borders::ALL));
+                .block(header_block(app, &chat.contact_uid, now).title(strip));
             f.render_widget(title, chunks[0]);
 
             // Message history
-            if chat.messages.is_empty() {
-                let empty_msg = Paragraph::new("No messages yet. Type a message below and press Enter to send.")
+            let visible = screen.visible_messages(chat);
+            if visible.is_empty() {
+                let empty_text = if screen.starred_only {
+                    "No starred messages. Ctrl+T shows all messages."
+                } else {
+                    "No messages yet. Type a message below and press Enter to send."
+                };
+                let empty_msg = Paragraph::new(empty_text)
                     .style(Style::default().fg(Color::DarkGray))
                     .alignment(Alignment::Center)
                     .block(Block::default().borders(Borders::ALL).title("Messages"));
                 f.render_widget(empty_msg, chunks[1]);
             } else {
                 // Calculate visible range based on scroll offset
-                let total_messages = chat.messages.len();
+                let total_messages = visible.len();
                 let visible_height = chunks[1].height.saturating_sub(2) as usize; // Subtract borders
                 let start_idx = screen.scroll_offset;
                 let end_idx = (start_idx + visible_height).min(total_messages);
 
-                let message_lines: Vec<Line> = chat.messages[start_idx..end_idx]
+                let message_lines: Vec<Line> = visible[start_idx..end_idx]
                     .iter()
                     .map(|msg| {
                         // Format timestamp
@@ -69,19 +138,44 @@ pub fn render_chat_view(f: &mut Frame, app: &App) {
 
                         // Determine if message is from us or them
                         let is_from_me = msg.sender == app.keypair.uid.to_string();
-                        let sender_label = if is_from_me { "You" } else { "Them" };
-                        let sender_color = if is_from_me { Color::Green } else { Color::Blue };
+                        let (sender_label, sender_color) = if msg.is_system() {
+                            ("Notice", Color::DarkGray)
+                        } else if is_from_me {
+                            ("You", Color::Green)
+                        } else {
+                            ("Them", Color::Blue)
+                        };
 
-                        // Decode message content
-                        let content = String::from_utf8(msg.content.clone())
-                            .unwrap_or_else(|_| "[binary data]".to_string());
+                        // Decode message content (control characters never reach the terminal)
+                        let content = msg.display_text();
 
                         // Build delivery status indicator (only for outgoing messages)
+                        let selected = screen.selected_message_id.as_deref() == Some(msg.id.as_str());
                         let mut spans = vec![
+                            Span::styled(
+                                if selected { "→ " } else { "  " },
+                                Style::default().fg(theme.selection),
+                            ),
+                            Span::styled(
+                                match (msg.pinned, msg.starred) {
+                                    (true, true) => "📌★ ",
+                                    (true, false) => "📌 ",
+                                    (false, true) => "★ ",
+                                    (false, false) => "",
+                                },
+                                Style::default().fg(Color::Yellow),
+                            ),
                             Span::styled(
                                 format!("[{}] ", timestamp),
                                 Style::default().fg(Color::DarkGray),
                             ),
+                            Span::styled(
+                                match msg.protection {
+                                    Some(protection) if screen.show_protection => format!("{} ", protection.marker()),
+                                    _ => String::new(),
+                                },
+                                Style::default().fg(Color::DarkGray),
+                            ),
                             Span::styled(
                                 format!("{}: ", sender_label),
                                 Style::default().fg(sender_color).add_modifier(Modifier::BOLD),
@@ -89,9 +183,27 @@ pub fn render_chat_view(f: &mut Frame, app: &App) {
                             Span::styled(content, Style::default().fg(Color::White)),
                         ];
 
+                        if msg.is_edited() {
+                            spans.push(Span::styled(" (edited)", Style::default().fg(Color::DarkGray)));
+                        }
+
+                        // Mark messages that carry application metadata
+                        if msg.has_metadata() {
+                            spans.push(Span::styled(" ⋯", Style::default().fg(Color::Magenta)));
+                        }
+
                         // Add delivery status for outgoing messages
                         if is_from_me {
                             let (status_text, status_color) = match msg.delivery_status {
+                                _ if screen.queued_since.contains_key(&msg.id) => {
+                                    // Still in the queue: how long it has waited
+                                    let queued_at = DateTime::from_timestamp_millis(screen.queued_since[&msg.id])
+                                        .unwrap_or(now);
+                                    (format!(" ↻ {}", queued_annotation(now - queued_at)), Color::Yellow)
+                                }
+                                _ if screen.failed_ids.contains(&msg.id) => {
+                                    (" ✗ failed".to_string(), Color::Red)
+                                }
                                 crate::storage::DeliveryStatus::Sent => {
                                     (" ✓ sent".to_string(), Color::Gray)
                                 }
@@ -113,40 +225,382 @@ pub fn render_chat_view(f: &mut Frame, app: &App) {
                             ));
                         }
 
-                        Line::from(spans)
+                        let line = Line::from(spans);
+                        if selected {
+                            line.style(Style::default().add_modifier(Modifier::REVERSED))
+                        } else {
+                            line
+                        }
                     })
                     .collect();
 
+                let filter_label = if screen.starred_only { " ★ starred only" } else { "" };
                 let messages_widget = Paragraph::new(message_lines)
                     .block(
                         Block::default()
                             .borders(Borders::ALL)
-                            .title(format!("Messages ({}/{})", end_idx, total_messages)),
+                            .title(format!("Messages ({}/{}){}", end_idx, total_messages, filter_label)),
                     );
                 f.render_widget(messages_widget, chunks[1]);
+
+                if screen.show_message_details {
+                    // Selection narrows the details to the selected message
+                    let details: Vec<&Message> = match screen.selected_message(chat) {
+                        Some(selected) => vec![selected],
+                        None => visible[start_idx..end_idx].to_vec(),
+                    };
+                    render_message_details_popup(f, size, &details);
+                }
             }
 
-            // Input box
+            if let Some(preview) = &screen.send_preview {
+                render_send_preview_popup(f, size, preview);
+            }
+
+            if let Some(review) = &screen.failed_review {
+                render_failed_review_popup(f, size, review);
+            }
+
+            // Input box, scrolled so the cursor line stays visible
+            let (cursor_line, cursor_column) = screen.cursor_line_column();
+            let input_scroll = cursor_line.saturating_sub(MAX_INPUT_LINES - 1);
+            let mut input_block = Block::default()
+                .borders(Borders::ALL)
+                .title(if screen.editing.is_some() { "Editing message (Esc: Cancel)" } else { "Type your message" });
+            if let Some(counter) = app.chat_input_counter() {
+                let color = match counter {
+                    InputCounter::Chars(_) => Color::DarkGray,
+                    InputCounter::Bytes(_) => Color::Yellow,
+                    InputCounter::TooLarge(_) => Color::Red,
+                };
+                input_block = input_block.title(
+                    Title::from(Span::styled(format!(" {} ", counter.label()), Style::default().fg(color)))
+                        .position(Position::Bottom)
+                        .alignment(Alignment::Right),
+                );
+            }
+            if let Some(warning) = app.night_warning(now) {
+                input_block = input_block.title(
+                    Title::from(Span::styled(
+                        format!(" {} ", warning),
+                        Style::default().fg(Color::Yellow).add_modifier(Modifier::DIM),
+                    ))
+                    .position(Position::Bottom)
+                    .alignment(Alignment::Left),
+                );
+            }
             let input_widget = Paragraph::new(screen.input.as_str())
                 .style(Style::default().fg(Color::Yellow))
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title("Type your message"),
-                );
+                .scroll((input_scroll as u16, 0))
+                .block(input_block);
             f.render_widget(input_widget, chunks[2]);
+            if screen.template_picker.is_none() {
+                f.set_cursor(
+                    chunks[2].x + 1 + cursor_column as u16,
+                    chunks[2].y + 1 + (cursor_line - input_scroll) as u16,
+                );
+            }
 
             // Status/Help
             let help_text = if let Some(status) = &screen.status_message {
                 status.clone()
+            } else if screen.send_preview.is_some() {
+                "Enter: Send | Esc: Keep editing".to_string()
+            } else if screen.failed_review.is_some() {
+                "↑↓: Choose | Space: Mark | Enter: Re-queue marked | a: Re-queue all | Esc: Close".to_string()
+            } else if screen.pinned_focus.is_some() {
+                "↑↓: Choose | Enter: Jump | p/Del: Unpin | Esc: Back".to_string()
+            } else if screen.is_selecting() {
+                "↑↓: Select | p: Pin | *: Star | E: Edit | S: Save content | Tab: Details | Esc: Done".to_string()
+            } else if screen.template_picker.is_some() {
+                "Type to filter | ↑↓: Choose | Enter: Insert | Esc: Type '%'".to_string()
             } else {
-                "Enter: Send | PgUp/PgDn: Scroll | Esc: Back to Chat List".to_string()
+                "Enter: Send | Alt+Enter: New line | %: Templates | `: Previous chat | ↑↓: Scroll | Ctrl+S: Select | Ctrl+P: Pinned | Ctrl+T: Starred | Ctrl+E: Export | Ctrl+G: Signed export | Ctrl+O: Preview | Ctrl+L: Protection | Ctrl+F: Failed | Tab: Details | Esc: Back".to_string()
             };
             let help = Paragraph::new(help_text)
                 .style(Style::default().fg(Color::DarkGray))
                 .alignment(Alignment::Center)
-                .block(Block::default().borders(Borders::ALL));
+                .block(footer_block(app));
             f.render_widget(help, chunks[3]);
+
+            if screen.template_picker.is_some() {
+                render_template_picker(f, chunks[2], app);
+            }
+        }
+    }
+}
+
+/// Banner under the history while messages have failed (None: no banner)
+/// Chat header block, with the contact's local time top right once their offset is known
+fn header_block(app: &App, contact_uid: &str, now: DateTime<Utc>) -> Block<'static> {
+    let block = Block::default().borders(Borders::ALL);
+    let Some((local, offset)) = app.contact_local_time(contact_uid, now) else {
+        return block;
+    };
+    block.title(
+        Title::from(Span::styled(
+            format!(" their local time: {} ({}) ", local.format("%H:%M"), offset.describe()),
+            Style::default().fg(Color::DarkGray),
+        ))
+        .alignment(Alignment::Right),
+    )
+}
+
+fn failed_banner(count: usize) -> Option<String> {
+    (count > 0).then(|| format!("{} failed message(s) | Ctrl+F: Review and re-queue", count))
+}
+
+/// Renders the failed messages review: oldest first, with why each failed
+fn render_failed_review_popup(f: &mut Frame, area: ratatui::layout::Rect, review: &FailedReview) {
+    let popup_width = 72.min(area.width);
+    let popup_height = 18.min(area.height);
+    let popup_area = ratatui::layout::Rect {
+        x: area.width.saturating_sub(popup_width) / 2,
+        y: area.height.saturating_sub(popup_height) / 2,
+        width: popup_width,
+        height: popup_height,
+    };
+
+    // Two lines per message; keep the highlighted one in view
+    let rows = (popup_height.saturating_sub(2) / 2).max(1) as usize;
+    let first = review.selected.saturating_sub(rows - 1);
+    let mut lines = Vec::new();
+    for (index, entry) in review.messages.iter().enumerate().skip(first).take(rows) {
+        let timestamp = DateTime::from_timestamp_millis(entry.message.timestamp)
+            .map(|dt| dt.format("%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "??-?? ??:??".to_string());
+        let mark = if review.marked.contains(&entry.message.id) { "[x]" } else { "[ ]" };
+        let line = Line::from(vec![
+            Span::styled(format!("{} {} ", mark, timestamp), Style::default().fg(Color::DarkGray)),
+            Span::styled(entry.message.preview(40), Style::default().fg(Color::White)),
+        ]);
+        lines.push(if index == review.selected {
+            line.style(Style::default().add_modifier(Modifier::REVERSED))
+        } else {
+            line
+        });
+        let state = if entry.dormant { "dormant, waiting for the contact" } else { "failed" };
+        let reason = entry.reason.as_deref().map(sanitize_text).unwrap_or_else(|| "no error recorded".to_string());
+        lines.push(Line::from(Span::styled(
+            format!("    {} after {} attempt(s): {}", state, entry.attempts, reason),
+            Style::default().fg(Color::Red),
+        )));
+    }
+
+    let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Red))
+            .title(format!("Failed messages ({}) - oldest first", review.messages.len()))
+            .style(Style::default().bg(Color::Black)),
+    );
+    f.render_widget(Clear, popup_area);
+    f.render_widget(popup, popup_area);
+}
+
+/// Renders the template picker just above the input box
+fn render_template_picker(f: &mut Frame, input_area: ratatui::layout::Rect, app: &App) {
+    let Some(picker) = app.chat_view_screen.as_ref().and_then(|s| s.template_picker.as_ref()) else {
+        return;
+    };
+    let templates = &app.app_state.settings.templates;
+    let matches = app.template_picker_matches();
+    let selected = picker.selected.min(matches.len().saturating_sub(1));
+    let first = selected.saturating_sub(TEMPLATE_PICKER_ROWS - 1);
+
+    let mut lines = vec![Line::from(vec![
+        Span::styled("% ", Style::default().fg(Color::DarkGray)),
+        Span::styled(picker.query.as_str(), Style::default().fg(Color::Yellow)),
+    ])];
+    if matches.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No matching templates",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    for (position, &index) in matches.iter().enumerate().skip(first).take(TEMPLATE_PICKER_ROWS) {
+        let template = &templates[index];
+        let preview: String = template.body.lines().next().unwrap_or("").chars().take(40).collect();
+        let line = Line::from(vec![
+            Span::styled(
+                format!("{:<16} ", template.name),
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(preview, Style::default().fg(Color::DarkGray)),
+        ]);
+        lines.push(if position == selected {
+            line.style(Style::default().add_modifier(Modifier::REVERSED))
+        } else {
+            line
+        });
+    }
+
+    let height = (lines.len() as u16 + 2).min(input_area.y);
+    let area = ratatui::layout::Rect {
+        x: input_area.x,
+        y: input_area.y.saturating_sub(height),
+        width: input_area.width.min(64),
+        height,
+    };
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan))
+            .title(format!("Templates ({}/{})", matches.len(), templates.len()))
+            .style(Style::default().bg(Color::Black)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// Renders the pinned messages strip under the chat header
+fn render_pinned_strip(f: &mut Frame, area: ratatui::layout::Rect, chat: &Chat, screen: &ChatViewScreen) {
+    let lines: Vec<Line> = chat
+        .pinned_messages()
+        .iter()
+        .enumerate()
+        .map(|(i, msg)| {
+            let timestamp = DateTime::from_timestamp_millis(msg.timestamp)
+                .map(|dt| dt.format("%H:%M").to_string())
+                .unwrap_or_else(|| "??:??".to_string());
+            let text = format!("📌 [{}] {}", timestamp, msg.preview(PINNED_PREVIEW_CHARS));
+            if screen.pinned_focus == Some(i) {
+                Line::from(Span::styled(
+                    text,
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::REVERSED),
+                ))
+            } else {
+                Line::from(Span::styled(text, Style::default().fg(Color::Yellow)))
+            }
+        })
+        .collect();
+
+    let strip = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow))
+            .title("Pinned"),
+    );
+    f.render_widget(strip, area);
+}
+
+/// Renders metadata pairs and edit history for the given messages that have them
+fn render_message_details_popup(f: &mut Frame, area: ratatui::layout::Rect, messages: &[&Message]) {
+    let popup_width = 60;
+    let popup_height = 16;
+
+    let popup_area = ratatui::layout::Rect {
+        x: area.width.saturating_sub(popup_width) / 2,
+        y: area.height.saturating_sub(popup_height) / 2,
+        width: popup_width.min(area.width),
+        height: popup_height.min(area.height),
+    };
+
+    let mut lines = Vec::new();
+    for msg in messages.iter().filter(|m| m.has_metadata() || m.is_edited()) {
+        let timestamp = DateTime::from_timestamp_millis(msg.timestamp)
+            .map(|dt| dt.format("%H:%M:%S").to_string())
+            .unwrap_or_else(|| "??:??:??".to_string());
+        lines.push(Line::from(Span::styled(
+            format!("[{}]", timestamp),
+            Style::default().fg(Color::DarkGray),
+        )));
+        for pair in msg.metadata_lines() {
+            lines.push(Line::from(Span::styled(
+                format!("  {}", pair),
+                Style::default().fg(Color::White),
+            )));
         }
+        lines.extend(edit_history_lines(msg));
     }
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No metadata or edits on visible messages",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+
+    let popup = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Magenta))
+                .title("Message Details (Tab: Close)")
+                .style(Style::default().bg(Color::Black)),
+        );
+    f.render_widget(Clear, popup_area);
+    f.render_widget(popup, popup_area);
+}
+
+/// Send preview overlay: what sending the input will do
+fn render_send_preview_popup(f: &mut Frame, area: ratatui::layout::Rect, preview: &SendPreview) {
+    let popup_width = 70;
+    let popup_height = preview.lines().len() as u16 + 4;
+
+    let popup_area = ratatui::layout::Rect {
+        x: area.width.saturating_sub(popup_width) / 2,
+        y: area.height.saturating_sub(popup_height) / 2,
+        width: popup_width.min(area.width),
+        height: popup_height.min(area.height),
+    };
+
+    let security_color = if preview.security.is_encrypted() { Color::Green } else { Color::Yellow };
+    let mut lines: Vec<Line> = preview
+        .lines()
+        .into_iter()
+        .map(|line| {
+            let color = if line.starts_with("Security:") { security_color } else { Color::White };
+            Line::from(Span::styled(line, Style::default().fg(color)))
+        })
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled("Enter: Send | Esc: Keep editing", Style::default().fg(Color::DarkGray))));
+
+    let popup = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title("Send Preview")
+                .style(Style::default().bg(Color::Black)),
+        );
+    f.render_widget(Clear, popup_area);
+    f.render_widget(popup, popup_area);
+}
+
+/// One line per edit of `msg`: when it was made and what changed, word by word
+fn edit_history_lines(msg: &Message) -> Vec<Line<'static>> {
+    let current = msg.display_text();
+    let versions: Vec<String> = msg
+        .edit_history
+        .iter()
+        .map(|edit| sanitize_text(&String::from_utf8_lossy(&edit.content)))
+        .chain(std::iter::once(current))
+        .collect();
+
+    msg.edit_history
+        .iter()
+        .zip(versions.windows(2))
+        .map(|(edit, pair)| {
+            let edited_at = DateTime::from_timestamp_millis(edit.replaced_at)
+                .map(|dt| dt.format("%H:%M:%S").to_string())
+                .unwrap_or_else(|| "??:??:??".to_string());
+            let mut spans = vec![Span::styled(format!("  edited {}: ", edited_at), Style::default().fg(Color::DarkGray))];
+            for (i, span) in word_diff(&pair[0], &pair[1]).into_iter().enumerate() {
+                if i > 0 {
+                    spans.push(Span::raw(" "));
+                }
+                spans.push(match span {
+                    DiffSpan::Same(text) => Span::styled(text, Style::default().fg(Color::White)),
+                    DiffSpan::Removed(text) => {
+                        Span::styled(text, Style::default().fg(Color::Red).add_modifier(Modifier::CROSSED_OUT))
+                    }
+                    DiffSpan::Added(text) => Span::styled(text, Style::default().fg(Color::Green)),
+                });
+            }
+            Line::from(spans)
+        })
+        .collect()
 }
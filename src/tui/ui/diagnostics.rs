@@ -1,13 +1,30 @@
 //! Diagnostics screen rendering
 
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
+use super::helpers::footer_block;
+use crate::connectivity::MappingProtocol;
+use crate::queue::{QueueDebugRow, QueueRowState};
+use crate::storage::ErrorSeverity;
+use chrono::DateTime;
 use crate::tui::app::App;
+use crate::tui::screens::DiagnosticsScreen;
+use crate::tui::startup_graph::{StageState, StartupStage};
+use crate::tui::diagnostics_actions::{protocol_name, DiagnosticsAction};
+
+/// Protocol panel title, marked while a single-protocol action runs on it
+fn panel_title(title: &str, protocol: MappingProtocol, screen: &DiagnosticsScreen) -> String {
+    match screen.pending_action {
+        Some(DiagnosticsAction::Test { protocol: p, .. }) if p == protocol => format!("{} - testing...", title),
+        Some(DiagnosticsAction::Delete { protocol: p, .. }) if p == protocol => format!("{} - deleting...", title),
+        _ => title.to_string(),
+    }
+}
 
 /// Renders the screen
 
@@ -30,7 +47,7 @@ pub fn render_diagnostics(f: &mut Frame, app: &App) {
         let title = Paragraph::new("Network Diagnostics & Port Mapping")
             .style(
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(app.theme().title)
                     .add_modifier(Modifier::BOLD),
             )
             .alignment(Alignment::Center)
@@ -64,8 +81,8 @@ pub fn render_diagnostics(f: &mut Frame, app: &App) {
             .constraints([
                 Constraint::Length(6),  // IPv4/IPv6 & External endpoint
                 Constraint::Length(5),  // Mapping lifetime & renewal
-                Constraint::Length(4),  // Network metrics (RTT, Queue)
-                Constraint::Min(3),     // Reserved for future use
+                Constraint::Length(6),  // Network metrics (RTT, Queue, Retry, Caches)
+                Constraint::Min(3),     // Startup stages and timings
             ])
             .split(content_columns[1]);
 
@@ -152,7 +169,7 @@ pub fn render_diagnostics(f: &mut Frame, app: &App) {
 
         let pcp_widget = Paragraph::new(pcp_text)
             .alignment(Alignment::Left)
-            .block(Block::default().borders(Borders::ALL).title("Port Control Protocol (PCP)"));
+            .block(Block::default().borders(Borders::ALL).title(panel_title("Port Control Protocol (PCP)", MappingProtocol::PCP, screen)));
         f.render_widget(pcp_widget, left_chunks[0]);
 
         // NAT-PMP Status
@@ -232,7 +249,7 @@ pub fn render_diagnostics(f: &mut Frame, app: &App) {
 
         let natpmp_widget = Paragraph::new(natpmp_text)
             .alignment(Alignment::Left)
-            .block(Block::default().borders(Borders::ALL).title("NAT Port Mapping Protocol (NAT-PMP)"));
+            .block(Block::default().borders(Borders::ALL).title(panel_title("NAT Port Mapping Protocol (NAT-PMP)", MappingProtocol::NATPMP, screen)));
         f.render_widget(natpmp_widget, left_chunks[1]);
 
         // UPnP Status
@@ -313,7 +330,7 @@ pub fn render_diagnostics(f: &mut Frame, app: &App) {
 
         let upnp_widget = Paragraph::new(upnp_text)
             .alignment(Alignment::Left)
-            .block(Block::default().borders(Borders::ALL).title("Universal Plug and Play (UPnP)"));
+            .block(Block::default().borders(Borders::ALL).title(panel_title("Universal Plug and Play (UPnP)", MappingProtocol::UPnP, screen)));
         f.render_widget(upnp_widget, left_chunks[2]);
 
         // HTTP Fallback Status
@@ -382,16 +399,46 @@ pub fn render_diagnostics(f: &mut Frame, app: &App) {
         f.render_widget(http_widget, left_chunks[3]);
 
         // Additional info / CGNAT warning (left column bottom)
-        let mut info_text = vec![
-            Line::from(vec![
-                Span::styled("Local Port: ", Style::default().fg(Color::DarkGray)),
-                Span::styled(
-                    format!("{}", screen.local_port),
-                    Style::default().fg(Color::Cyan),
-                ),
-            ]),
-            Line::from(""),
-        ];
+        let mut info_text = Vec::new();
+        if let Some(prompt) = &screen.port_prompt {
+            info_text.push(Line::from(vec![
+                Span::styled("Test port (empty = local): ", Style::default().fg(Color::Yellow)),
+                Span::styled(format!("{}_", prompt), Style::default().fg(Color::Cyan)),
+            ]));
+        } else if screen.confirm_delete {
+            let target = screen
+                .active_mapping()
+                .map(|(panel, m)| {
+                    format!(
+                        "{} mapping {}:{} (port {})",
+                        protocol_name(m.protocol),
+                        m.external_ip,
+                        m.external_port,
+                        screen.tested_port(panel)
+                    )
+                })
+                .unwrap_or_default();
+            info_text.push(Line::from(Span::styled(
+                format!("Delete {}? (y/n)", target),
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            )));
+        } else if let Some(message) = &screen.status_message {
+            info_text.push(Line::from(Span::styled(message.as_str(), Style::default().fg(Color::Cyan))));
+        }
+        info_text.push(Line::from(vec![
+            Span::styled("Local Port: ", Style::default().fg(Color::DarkGray)),
+            Span::styled(
+                format!("{}", screen.local_port),
+                Style::default().fg(Color::Cyan),
+            ),
+        ]));
+        if let Some(port) = screen.alternate_port {
+            info_text.push(Line::from(vec![
+                Span::styled("Test Port: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(format!("{} (alternate)", port), Style::default().fg(Color::Yellow)),
+            ]));
+        }
+        info_text.push(Line::from(""));
 
         // Add CGNAT warning if detected
         if screen.cgnat_detected {
@@ -410,6 +457,11 @@ pub fn render_diagnostics(f: &mut Frame, app: &App) {
             )));
         }
 
+        // Per-gateway probe outcomes
+        for line in screen.gateway_probe_lines() {
+            info_text.push(Line::from(Span::styled(line, Style::default().fg(Color::DarkGray))));
+        }
+
         let info_widget = Paragraph::new(info_text)
             .alignment(Alignment::Left)
             .block(Block::default().borders(Borders::ALL).title("Status"));
@@ -459,6 +511,19 @@ pub fn render_diagnostics(f: &mut Frame, app: &App) {
             }
         }
 
+        // Peer-assisted tests, newest first
+        if !screen.peer_probes.is_empty() {
+            ip_text.push(Line::from(Span::styled("Peer tests:", Style::default().fg(Color::DarkGray))));
+            for record in &screen.peer_probes {
+                let color = match (&record.refused, record.reachable) {
+                    (Some(_), _) => Color::DarkGray,
+                    (None, true) => Color::Green,
+                    (None, false) => Color::Red,
+                };
+                ip_text.push(Line::from(Span::styled(record.summary(), Style::default().fg(color))));
+            }
+        }
+
         let ip_widget = Paragraph::new(ip_text)
             .alignment(Alignment::Left)
             .block(Block::default().borders(Borders::ALL).title("IP Detection"));
@@ -497,7 +562,8 @@ pub fn render_diagnostics(f: &mut Frame, app: &App) {
             .block(Block::default().borders(Borders::ALL).title("Mapping Lifecycle"));
         f.render_widget(lifetime_widget, right_chunks[1]);
 
-        // Network metrics (RTT, Queue size)
+        // Network metrics (RTT, Queue size, retry schedule, in-memory caches)
+        let caches = crate::cache::CacheStats::total(&crate::cache::cache_stats());
         let metrics_text = vec![
             Line::from(vec![
                 Span::styled("Last Ping RTT: ", Style::default().fg(Color::DarkGray)),
@@ -520,6 +586,25 @@ pub fn render_diagnostics(f: &mut Frame, app: &App) {
                     else if screen.queue_size < 10 { Style::default().fg(Color::Yellow) }
                     else { Style::default().fg(Color::Red) },
                 ),
+                Span::styled(
+                    if screen.dormant_count > 0 { format!(" ({} dormant)", screen.dormant_count) } else { String::new() },
+                    Style::default().fg(Color::Magenta),
+                ),
+                Span::styled(
+                    if screen.blocked_count > 0 { format!(" ({} blocked on expired contacts)", screen.blocked_count) } else { String::new() },
+                    Style::default().fg(Color::Red),
+                ),
+            ]),
+            Line::from(vec![
+                Span::styled("Retry: ", Style::default().fg(Color::DarkGray)),
+                Span::raw(app.retry_status.plan().describe(chrono::Utc::now())),
+            ]),
+            Line::from(vec![
+                Span::styled("Caches: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(
+                    caches.summary(),
+                    if caches.evictions > 0 { Style::default().fg(Color::Yellow) } else { Style::default() },
+                ),
             ]),
         ];
 
@@ -528,12 +613,174 @@ pub fn render_diagnostics(f: &mut Frame, app: &App) {
             .block(Block::default().borders(Borders::ALL).title("Network Metrics"));
         f.render_widget(metrics_widget, right_chunks[2]);
 
-        // Help text
-        let help_text = "r/F5: Refresh | Esc: Back";
-        let help = Paragraph::new(help_text)
-            .style(Style::default().fg(Color::DarkGray))
+        // Startup stages, then timings (per phase)
+        let now = std::time::Instant::now();
+        let stage_lines = StartupStage::ALL.iter().zip(app.startup.describe(now)).map(|(stage, text)| {
+            let color = match app.startup.state(*stage) {
+                StageState::Done { .. } => Color::Green,
+                StageState::Degraded { .. } => Color::Yellow,
+                StageState::Running { .. } => Color::Cyan,
+                StageState::Waiting => Color::DarkGray,
+            };
+            Line::from(Span::styled(text, Style::default().fg(color)))
+        });
+        let mut timing_text: Vec<Line> = stage_lines.collect();
+        timing_text.extend(app.startup_timings.phases
+            .iter()
+            .map(|(phase, duration)| {
+                Line::from(vec![
+                    Span::styled(format!("{}: ", phase), Style::default().fg(Color::DarkGray)),
+                    Span::raw(format!("{}ms", duration.as_millis())),
+                ])
+            }));
+        timing_text.push(Line::from(vec![
+            Span::styled("Total: ", Style::default().fg(Color::DarkGray)),
+            Span::styled(
+                format!("{}ms", app.startup_timings.total().as_millis()),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+        ]));
+
+        let timings_widget = Paragraph::new(timing_text)
+            .alignment(Alignment::Left)
+            .block(Block::default().borders(Borders::ALL).title("Startup"));
+        f.render_widget(timings_widget, right_chunks[3]);
+
+        // Help text (actions greyed out while a test is running)
+        let action_style = if app.diagnostics_busy() {
+            Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT)
+        } else {
+            Style::default().fg(Color::Gray)
+        };
+        let help_line = Line::from(vec![
+            Span::styled("r/F5: Refresh | 1/2/3: Test PCP/NAT-PMP/UPnP | d: Delete mapping | p: Test port", action_style),
+            Span::styled(" | c: Ask contact | e/l: Error log/Log file | q: Queue | i: Integrity | s: Snapshots | m: Maintenance | v/j: Verify/export journal | a: Verify chat archive | Esc: Back", Style::default().fg(Color::Gray)),
+        ]);
+        let help = Paragraph::new(help_line)
             .alignment(Alignment::Center)
-            .block(Block::default().borders(Borders::ALL));
+            .block(footer_block(app));
         f.render_widget(help, main_chunks[2]);
+
+        if screen.show_error_log {
+            render_error_log(f, app, main_chunks[1]);
+        }
+        if let Some(rows) = &screen.queue_rows {
+            render_queue(f, rows, main_chunks[1]);
+        }
+        if let Some(selected) = screen.probe_picker {
+            render_probe_picker(f, app, selected, main_chunks[1]);
+        }
     }
 }
+
+/// Contacts to ask for a reachability test of our advertised address
+fn render_probe_picker(f: &mut Frame, app: &App, selected: usize, area: Rect) {
+    let lines: Vec<Line> = app
+        .probe_candidates()
+        .iter()
+        .enumerate()
+        .map(|(i, contact)| {
+            let style = if i == selected {
+                Style::default().fg(Color::Black).bg(Color::Cyan)
+            } else {
+                Style::default()
+            };
+            let uid_short = &contact.uid[..16.min(contact.uid.len())];
+            Line::from(Span::styled(format!("{} ({})", uid_short, contact.ip), style))
+        })
+        .collect();
+
+    let picker = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("Ask a contact to test {} - Enter: Ask | Esc: Cancel", app.local_ip)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(picker, area);
+}
+
+/// One line per queued message, in the order the worker takes them
+pub fn queue_row_line(row: &QueueDebugRow) -> String {
+    let state = match row.state {
+        QueueRowState::Due => "due",
+        QueueRowState::Waiting => "waiting",
+        QueueRowState::Dormant => "dormant",
+        QueueRowState::Blocked => "blocked",
+    };
+    let queued = DateTime::from_timestamp_millis(row.created_at)
+        .map(|at| at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "?".to_string());
+    let priority = format!("{:?}", row.priority).to_lowercase();
+    let mut line = format!(
+        "{} {} → {} {} ({} bytes) queued {}, {}, {} attempt(s)",
+        &row.message_id[..8.min(row.message_id.len())],
+        row.message_type,
+        row.target,
+        priority,
+        row.content_len,
+        queued,
+        state,
+        row.attempts
+    );
+    if let Some(error) = &row.last_error {
+        line.push_str(&format!(": {}", error));
+    }
+    line
+}
+
+/// Queue contents over the panels
+fn render_queue(f: &mut Frame, rows: &[QueueDebugRow], area: Rect) {
+    let lines: Vec<Line> = if rows.is_empty() {
+        vec![Line::from(Span::styled("The queue is empty", Style::default().fg(Color::DarkGray)))]
+    } else {
+        rows.iter().map(|row| Line::from(queue_row_line(row))).collect()
+    };
+    let queue = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Queue ({}) - q/Esc: Close", rows.len())),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(queue, area);
+}
+
+/// Error log over the panels, newest report first
+fn render_error_log(f: &mut Frame, app: &App, area: Rect) {
+    let reports = app.error_reports.reports();
+    let mut lines: Vec<Line> = if reports.is_empty() {
+        vec![Line::from(Span::styled("No background failures reported", Style::default().fg(Color::DarkGray)))]
+    } else {
+        reports
+            .iter()
+            .rev()
+            .map(|report| {
+                let color = match report.severity {
+                    ErrorSeverity::Error => Color::Red,
+                    ErrorSeverity::Warning => Color::Yellow,
+                    ErrorSeverity::Info => Color::Gray,
+                };
+                Line::from(Span::styled(report.log_line(), Style::default().fg(color)))
+            })
+            .collect()
+    };
+    if let Some(log) = &app.log_file {
+        let status = log.status();
+        let color = if status.rotated_away { Color::Yellow } else { Color::DarkGray };
+        lines.insert(0, Line::from(Span::styled(
+            format!("Log file {} ({})", log.path().display(), status.summary()),
+            Style::default().fg(color),
+        )));
+    }
+
+    let log = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Error Log ({}) - e/Esc: Close", reports.len())),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(log, area);
+}
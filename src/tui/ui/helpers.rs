@@ -1,13 +1,37 @@
 //! UI helper functions
 
 use chrono::{DateTime, Utc};
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{block::Title, Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+use crate::storage::{ErrorSeverity, MAX_DISPLAY_SECS, SATURATED_DURATION_TEXT};
+use crate::tui::app::App;
+use crate::tui::error_reports::ErrorBanner;
+use crate::tui::connectivity_indicator::ConnectivityIndicator;
+use crate::tui::effects::UiEffect;
+use crate::tui::inspection::Inspection;
+use crate::tui::path_picker::PathPicker;
+use crate::tui::types::Screen;
 
 /// Format a duration until expiry timestamp in human-readable form
 pub fn format_duration_until(expiry: DateTime<Utc>) -> String {
-    let now = Utc::now();
+    format_duration_until_at(expiry, Utc::now())
+}
+
+/// Format the duration from `now` until `expiry`
+///
+/// Total for any pair of times: past expiries read "expired" and anything
+/// beyond 999 days reads "999+ days".
+pub fn format_duration_until_at(expiry: DateTime<Utc>, now: DateTime<Utc>) -> String {
     let duration = expiry.signed_duration_since(now);
 
-    if duration.num_days() > 0 {
+    if duration.num_seconds() > MAX_DISPLAY_SECS {
+        SATURATED_DURATION_TEXT.to_string()
+    } else if duration.num_days() > 0 {
         format!("{} days", duration.num_days())
     } else if duration.num_hours() > 0 {
         format!("{} hours", duration.num_hours())
@@ -17,3 +41,234 @@ pub fn format_duration_until(expiry: DateTime<Utc>) -> String {
         "expired".to_string()
     }
 }
+
+/// Render a notification in the top-right corner of the screen
+pub fn render_notification(f: &mut Frame, text: &str) {
+    let area = f.size();
+    let width = 50.min(area.width);
+    let height = 4.min(area.height);
+    let toast_area = Rect {
+        x: area.width.saturating_sub(width),
+        y: 0,
+        width,
+        height,
+    };
+
+    let toast = Paragraph::new(text)
+        .wrap(Wrap { trim: true })
+        .style(Style::default().fg(Color::Yellow))
+        .block(Block::default().borders(Borders::ALL).title("Notification"));
+    f.render_widget(Clear, toast_area);
+    f.render_widget(toast, toast_area);
+}
+
+/// Render the save-path overlay in the middle of the screen
+pub fn render_path_picker(f: &mut Frame, picker: &PathPicker) {
+    let area = f.size();
+    let width = 76.min(area.width);
+    let height = 7.min(area.height);
+    let popup_area = Rect {
+        x: area.width.saturating_sub(width) / 2,
+        y: area.height.saturating_sub(height) / 2,
+        width,
+        height,
+    };
+
+    let (message, help) = match (&picker.pending_overwrite, &picker.error) {
+        (Some(path), _) => (
+            Line::from(Span::styled(
+                format!("{} exists. Overwrite?", path.display()),
+                Style::default().fg(Color::Yellow),
+            )),
+            "y: Overwrite | any other key: Keep it",
+        ),
+        (None, Some(error)) => (
+            Line::from(Span::styled(error.as_str(), Style::default().fg(Color::Red))),
+            "Enter: Save | Esc: Cancel | ~ is your home folder",
+        ),
+        (None, None) => (Line::from(""), "Enter: Save | Esc: Cancel | ~ is your home folder"),
+    };
+    let text = vec![
+        Line::from(Span::styled(format!("{}_", picker.input), Style::default().fg(Color::Cyan))),
+        message,
+        Line::from(""),
+        Line::from(Span::styled(help, Style::default().fg(Color::DarkGray))),
+    ];
+
+    let popup = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Cyan))
+                .title(picker.title()),
+        );
+    f.render_widget(Clear, popup_area);
+    f.render_widget(popup, popup_area);
+}
+
+/// Render a red banner across the top of the screen while writes are deferred
+pub fn render_storage_banner(f: &mut Frame, pending: usize) {
+    let area = f.size();
+    let banner_area = Rect {
+        x: 0,
+        y: 0,
+        width: area.width,
+        height: 1.min(area.height),
+    };
+
+    let text = format!(
+        " Storage temporarily locked — {} {} pending ",
+        pending,
+        if pending == 1 { "change" } else { "changes" }
+    );
+    let banner = Paragraph::new(text)
+        .alignment(ratatui::layout::Alignment::Center)
+        .style(Style::default().fg(Color::White).bg(Color::Red));
+    f.render_widget(Clear, banner_area);
+    f.render_widget(banner, banner_area);
+}
+
+/// Render a red banner across the top of the screen about the transport port
+pub fn render_port_banner(f: &mut Frame, text: &str) {
+    let area = f.size();
+    let banner_area = Rect {
+        x: 0,
+        y: 0,
+        width: area.width,
+        height: 1.min(area.height),
+    };
+
+    let banner = Paragraph::new(format!(" {} ", text))
+        .alignment(ratatui::layout::Alignment::Center)
+        .style(Style::default().fg(Color::White).bg(Color::Red).add_modifier(ratatui::style::Modifier::BOLD));
+    f.render_widget(Clear, banner_area);
+    f.render_widget(banner, banner_area);
+}
+
+/// Text of the inspection banner, with the last refused action while it is shown
+pub fn inspection_banner_text(inspection: &Inspection, now: std::time::Instant) -> String {
+    match inspection.status(now) {
+        Some(refusal) => format!(" {} — {} ", inspection.banner_text(), refusal),
+        None => format!(" {} ", inspection.banner_text()),
+    }
+}
+
+/// Render the inspection banner across the top of the screen
+pub fn render_inspection_banner(f: &mut Frame, text: &str) {
+    let area = f.size();
+    let banner_area = Rect {
+        x: 0,
+        y: 0,
+        width: area.width,
+        height: 1.min(area.height),
+    };
+
+    let banner = Paragraph::new(text.to_string())
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(Color::Black).bg(Color::Magenta).add_modifier(Modifier::BOLD));
+    f.render_widget(Clear, banner_area);
+    f.render_widget(banner, banner_area);
+}
+
+/// Text of the error banner: newest report, suppressed count and the dismiss key
+pub fn error_banner_text(banner: &ErrorBanner) -> String {
+    let more = if banner.suppressed > 0 {
+        format!(" (+{} more)", banner.suppressed)
+    } else {
+        String::new()
+    };
+    format!(
+        " {}: {}{} — Ctrl+X: dismiss ",
+        banner.report.source, banner.report.message, more
+    )
+}
+
+/// Render the error banner on `row` of the screen, coloured by severity
+pub fn render_error_banner(f: &mut Frame, banner: &ErrorBanner, row: u16) {
+    let area = f.size();
+    if row >= area.height {
+        return;
+    }
+    let banner_area = Rect {
+        x: 0,
+        y: row,
+        width: area.width,
+        height: 1,
+    };
+
+    let style = match banner.report.severity {
+        ErrorSeverity::Error => Style::default().fg(Color::White).bg(Color::Red),
+        ErrorSeverity::Warning => Style::default().fg(Color::Black).bg(Color::Yellow),
+        ErrorSeverity::Info => Style::default().fg(Color::White).bg(Color::Blue),
+    };
+    let widget = Paragraph::new(error_banner_text(banner))
+        .alignment(Alignment::Center)
+        .style(style);
+    f.render_widget(Clear, banner_area);
+    f.render_widget(widget, banner_area);
+}
+
+/// Rows covered by the new-message header flash (screen margin and title block)
+const HEADER_FLASH_ROWS: u16 = 5;
+
+/// Highlight the header rows of the current screen (new-message flash of `effect`'s strength)
+pub fn render_header_flash(f: &mut Frame, effect: UiEffect) {
+    let area = f.size();
+    let header_area = Rect {
+        x: 0,
+        y: 0,
+        width: area.width,
+        height: HEADER_FLASH_ROWS.min(area.height),
+    };
+    let modifier = match effect {
+        UiEffect::SubtleFlash => Modifier::UNDERLINED,
+        UiEffect::UrgentFlash => Modifier::REVERSED | Modifier::BOLD,
+        UiEffect::HeaderFlash => Modifier::REVERSED,
+    };
+    f.buffer_mut().set_style(header_area, Style::default().add_modifier(modifier));
+}
+
+/// Narrowest terminal the footers are laid out for
+pub const MIN_FOOTER_WIDTH: u16 = 80;
+
+/// Terminal columns taken by `text`
+///
+/// Every character the UI draws (arrows, bullets, box symbols) is one
+/// column wide, so this is the character count.
+pub fn display_width(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Whether a border title of `segment` fits a block `width` columns wide
+///
+/// The two corners and at least one border cell on each side stay visible.
+pub fn footer_fits(width: u16, segment: &str) -> bool {
+    display_width(segment) + 4 <= usize::from(width)
+}
+
+/// Connectivity segment for the footer, e.g. " ● online (UPnP) · D: Diagnostics "
+pub fn connectivity_segment(app: &App) -> String {
+    let label = app.connectivity_indicator.label();
+    if app.current_screen == Screen::Diagnostics {
+        format!(" {} ", label)
+    } else {
+        format!(" {} · {}: Diagnostics ", label, app.diagnostics_jump_hint())
+    }
+}
+
+/// Bordered footer block carrying the connectivity segment in its top border
+///
+/// The segment sits in the border, so the help line inside keeps its full
+/// width.
+pub fn footer_block(app: &App) -> Block<'static> {
+    let color = match app.connectivity_indicator {
+        ConnectivityIndicator::Online(_) => Color::Green,
+        ConnectivityIndicator::Limited(_) => Color::Yellow,
+        ConnectivityIndicator::Offline => Color::Red,
+        ConnectivityIndicator::Starting | ConnectivityIndicator::Checking => Color::Gray,
+    };
+    Block::default().borders(Borders::ALL).title(
+        Title::from(Span::styled(connectivity_segment(app), Style::default().fg(color))).alignment(Alignment::Right),
+    )
+}
@@ -1,13 +1,17 @@
 //! Import contact screen rendering
 
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
     widgets::{Block, Borders, Paragraph, Wrap},
     Frame,
 };
+use super::helpers::footer_block;
+use crate::storage::{Contact, EphemeralLifetime, TrustTier};
 use crate::tui::app::App;
+use crate::tui::contact_import::{BatchEntryResult, BatchEntryStatus, BatchImport, PingDispatch};
+use crate::tui::screens::ImportContactScreen;
 
 /// Renders the screen
 
@@ -22,23 +26,43 @@ pub fn render_import_contact(f: &mut Frame, app: &App) {
             .constraints([
                 Constraint::Length(3),  // Title
                 Constraint::Min(5),     // Input field
-                Constraint::Length(8),  // Contact info (if parsed)
+                Constraint::Length(9),  // Contact info (if parsed)
+                Constraint::Length(7),  // Awaiting token
                 Constraint::Length(3),  // Status message
                 Constraint::Length(3),  // Help text
             ])
             .split(size);
 
         // Title
-        let title = Paragraph::new("Import Contact")
+        let title = Paragraph::new(if screen.batch.is_some() { "Import Contacts (Batch)" } else { "Import Contact" })
             .style(
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(app.theme().title)
                     .add_modifier(Modifier::BOLD),
             )
             .alignment(Alignment::Center)
             .block(Block::default().borders(Borders::ALL));
         f.render_widget(title, chunks[0]);
 
+        if let Some(batch) = &screen.batch {
+            render_batch(f, app, batch, screen, &chunks);
+            render_pending(f, screen, chunks[3]);
+            render_status(f, screen.status_message.as_deref(), screen.is_error, chunks[4]);
+            let help_text = if batch.report.is_some() {
+                "Enter/Esc: New batch"
+            } else if batch.in_review() {
+                "↑/↓: Move | Space: Select | Tab: Temporary | Enter: Import | Esc: Edit"
+            } else {
+                "Ctrl+R: Review | Ctrl+O: File | Ctrl+V: Paste | Ctrl+B: Single | Esc: Back"
+            };
+            let help = Paragraph::new(help_text)
+                .style(Style::default().fg(Color::DarkGray))
+                .alignment(Alignment::Center)
+                .block(footer_block(app));
+            f.render_widget(help, chunks[5]);
+            return;
+        }
+
         // Input field
         let input_text = Text::from(screen.input.as_str());
         let input_widget = Paragraph::new(input_text)
@@ -58,6 +82,7 @@ pub fn render_import_contact(f: &mut Frame, app: &App) {
                     Span::styled("UID: ", Style::default().fg(Color::Yellow)),
                     Span::styled(&contact.uid, Style::default().fg(Color::Green)),
                 ]),
+                signature_line(contact),
                 Line::from(vec![
                     Span::styled("IP: ", Style::default().fg(Color::Yellow)),
                     Span::styled(&contact.ip, Style::default().fg(Color::Green)),
@@ -66,7 +91,11 @@ pub fn render_import_contact(f: &mut Frame, app: &App) {
                     Span::styled("Expires: ", Style::default().fg(Color::Yellow)),
                     Span::styled(
                         contact.expiry.format("%Y-%m-%d %H:%M UTC").to_string(),
-                        Style::default().fg(Color::Green),
+                        Style::default().fg(if screen.expiry_warning.is_some() { Color::Red } else { Color::Green }),
+                    ),
+                    Span::styled(
+                        screen.expiry_warning.as_deref().map(|w| format!(" ({})", w)).unwrap_or_default(),
+                        Style::default().fg(Color::Yellow),
                     ),
                 ]),
                 Line::from(vec![
@@ -76,6 +105,8 @@ pub fn render_import_contact(f: &mut Frame, app: &App) {
                         Style::default().fg(if contact.is_active { Color::Green } else { Color::Gray }),
                     ),
                 ]),
+                import_as_line(screen.temporary),
+                trust_line(screen.trust),
             ];
 
             let info_widget = Paragraph::new(info_lines)
@@ -86,7 +117,11 @@ pub fn render_import_contact(f: &mut Frame, app: &App) {
                 );
             f.render_widget(info_widget, chunks[2]);
         } else {
-            let placeholder = Paragraph::new("No contact parsed yet")
+            let placeholder = Paragraph::new(vec![
+                Line::from("No contact parsed yet"),
+                import_as_line(screen.temporary),
+                trust_line(screen.trust),
+            ])
                 .style(Style::default().fg(Color::DarkGray))
                 .alignment(Alignment::Center)
                 .block(
@@ -97,29 +132,200 @@ pub fn render_import_contact(f: &mut Frame, app: &App) {
             f.render_widget(placeholder, chunks[2]);
         }
 
+        render_pending(f, screen, chunks[3]);
+
         // Status message
-        let status_text = screen
-            .status_message
-            .as_ref()
-            .map(|s| s.as_str())
-            .unwrap_or("");
-        let status_color = if screen.is_error {
-            Color::Red
-        } else {
-            Color::Green
-        };
-        let status_widget = Paragraph::new(status_text)
-            .style(Style::default().fg(status_color))
-            .alignment(Alignment::Center)
-            .block(Block::default().borders(Borders::ALL).title("Status"));
-        f.render_widget(status_widget, chunks[3]);
+        render_status(f, screen.status_message.as_deref(), screen.is_error, chunks[4]);
 
         // Help text
-        let help_text = "Enter: Parse | Ctrl+V: Paste | Delete: Clear | Esc: Back";
+        let help_text = if screen.awaits_armor_lines() {
+            "Armored token: Enter: Next line (ends at the END line) | Delete: Clear | Esc: Back"
+        } else {
+            "Enter: Parse | Ctrl+V: Paste | Delete: Clear | Esc: Back | Ctrl+B: Batch"
+        };
         let help = Paragraph::new(help_text)
             .style(Style::default().fg(Color::DarkGray))
             .alignment(Alignment::Center)
-            .block(Block::default().borders(Borders::ALL));
-        f.render_widget(help, chunks[4]);
+            .block(footer_block(app));
+        f.render_widget(help, chunks[5]);
     }
 }
+
+/// Status line (green, or red for errors)
+fn render_status(f: &mut Frame, message: Option<&str>, is_error: bool, area: Rect) {
+    let status_widget = Paragraph::new(message.unwrap_or(""))
+        .style(Style::default().fg(if is_error { Color::Red } else { Color::Green }))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title("Status"));
+    f.render_widget(status_widget, area);
+}
+
+/// "Awaiting Token" section: pending introductions, with the one the next
+/// token is linked to marked, after the rows skipped from the last list
+fn render_pending(f: &mut Frame, screen: &ImportContactScreen, area: Rect) {
+    let mut lines: Vec<Line> = screen
+        .list_errors
+        .iter()
+        .map(|error| Line::from(Span::styled(format!("Skipped {}", error), Style::default().fg(Color::Red))))
+        .collect();
+    for intro in &screen.pending {
+        let linked = screen.link.as_deref() == Some(intro.id.as_str());
+        let name: String = intro.name.chars().take(24).collect();
+        let (mark, state) = if linked { ("→", "linked to the next token") } else { (" ", "awaiting token") };
+        let style = if linked { Style::default().fg(Color::Green).add_modifier(Modifier::BOLD) } else { Style::default() };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{} {:<24} ", mark, name), style),
+            Span::styled(format!("{:<24} ", state), Style::default().fg(Color::Yellow)),
+            Span::styled(intro.notes.lines().next().unwrap_or("").to_string(), Style::default().fg(Color::DarkGray)),
+        ]));
+    }
+    if screen.pending.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No pending introductions. Ctrl+P reads a contact list (.csv or .vcf); \"Name: <token>\" links a match",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    let title = match screen.pending.len() {
+        0 => "Awaiting Token (Ctrl+P: Read list)".to_string(),
+        n => format!("Awaiting Token ({}) | Ctrl+L: Link | Ctrl+D: Dismiss | Ctrl+P: Read list", n),
+    };
+    let widget = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(widget, area);
+}
+
+/// Batch mode: token input, review table or per-entry report
+fn render_batch(f: &mut Frame, app: &App, batch: &BatchImport, screen: &ImportContactScreen, chunks: &[Rect]) {
+    // The list takes the input and contact information areas together
+    let area = Rect { height: chunks[1].height + chunks[2].height, ..chunks[1] };
+
+    if let Some(report) = &batch.report {
+        let lines: Vec<Line> = report
+            .iter()
+            .map(|entry| {
+                let color = match (&entry.result, &entry.ping) {
+                    (BatchEntryResult::Failed(_), _) | (_, Some(PingDispatch::Failed(_))) => Color::Red,
+                    (BatchEntryResult::Skipped(_), _) => Color::DarkGray,
+                    (_, Some(PingDispatch::Pending)) => Color::Yellow,
+                    _ => Color::Green,
+                };
+                Line::from(Span::styled(entry.line(), Style::default().fg(color)))
+            })
+            .collect();
+        let widget = Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title(format!("Import Results ({})", report.len())));
+        f.render_widget(widget, area);
+        return;
+    }
+
+    if batch.in_review() {
+        let now = chrono::Utc::now();
+        let mut lines = vec![
+            Line::from(Span::styled(
+                format!("    {:<24} {:<17} {:<10}", "Name / UID", "Expires", "Status"),
+                Style::default().fg(Color::Yellow),
+            )),
+        ];
+        for (i, entry) in batch.entries.iter().enumerate() {
+            let mark = match (entry.selectable(), entry.selected) {
+                (false, _) => "   ",
+                (true, true) => "[x]",
+                (true, false) => "[ ]",
+            };
+            let expiry = entry
+                .contact
+                .as_ref()
+                .map(|c| c.expiry.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "-".to_string());
+            let detail = match &entry.status {
+                BatchEntryStatus::Invalid(reason) => format!(" ({})", reason),
+                BatchEntryStatus::Expired => entry
+                    .contact
+                    .as_ref()
+                    .map(|c| format!(" ({} days ago)", (now - c.expiry).num_days()))
+                    .unwrap_or_default(),
+                _ => entry.link.as_ref().map(|intro| format!(" → {}", intro.name)).unwrap_or_default(),
+            };
+            let name: String = entry.display_name().chars().take(24).collect();
+            let color = match entry.status {
+                BatchEntryStatus::Ok => Color::Green,
+                BatchEntryStatus::Duplicate | BatchEntryStatus::Expired => Color::Yellow,
+                BatchEntryStatus::Invalid(_) | BatchEntryStatus::OwnToken => Color::Red,
+            };
+            let mut style = Style::default().fg(color);
+            if i == batch.selected_row {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            lines.push(Line::from(Span::styled(
+                format!("{} {:<24} {:<17} {}{}", mark, name, expiry, entry.status.label(), detail),
+                style,
+            )));
+        }
+        lines.push(Line::from(""));
+        lines.push(import_as_line(screen.temporary));
+        lines.push(trust_line(screen.trust));
+        let widget = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(format!(
+            "Review ({} of {} selected)",
+            batch.selected_count(),
+            batch.entries.len()
+        )));
+        f.render_widget(widget, area);
+        return;
+    }
+
+    let input = app.import_contact_screen.as_ref().map(|s| s.input.as_str()).unwrap_or("");
+    let widget = Paragraph::new(Text::from(input))
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title("Contact Tokens (one per line, \"name: token\" allowed)"));
+    f.render_widget(widget, area);
+}
+
+/// "Import as:" line with the temporary import toggle
+fn import_as_line(temporary: Option<EphemeralLifetime>) -> Line<'static> {
+    let choice = match temporary {
+        Some(lifetime) => Span::styled(
+            format!("Temporary, deleted with its chat after {}", lifetime.label()),
+            Style::default().fg(Color::Magenta),
+        ),
+        None => Span::styled("Permanent", Style::default().fg(Color::Green)),
+    };
+    Line::from(vec![
+        Span::styled("Import as: ", Style::default().fg(Color::Yellow)),
+        choice,
+        Span::styled(" (Tab: change)", Style::default().fg(Color::DarkGray)),
+    ])
+}
+
+/// "Signature: valid, key <fingerprint>" line of a parsed token
+///
+/// Parsing already refused tokens whose signature does not verify; the line
+/// turns red if the UID is not the signing key's (see `Contact::uid_matches_key`).
+fn signature_line(contact: &Contact) -> Line<'static> {
+    let (verdict, color) = if contact.uid_matches_key() {
+        ("valid", Color::Green)
+    } else {
+        ("valid, but the UID is not this key's", Color::Red)
+    };
+    Line::from(vec![
+        Span::styled("Signature: ", Style::default().fg(Color::Yellow)),
+        Span::styled(verdict, Style::default().fg(color)),
+        Span::styled(format!(", key {}", contact.signing_key_fingerprint()), Style::default().fg(Color::Gray)),
+    ])
+}
+
+/// "Trust:" line with the restricted import toggle
+fn trust_line(trust: TrustTier) -> Line<'static> {
+    let choice = match trust {
+        TrustTier::Normal => Span::styled("Normal", Style::default().fg(Color::Green)),
+        TrustTier::Restricted => Span::styled(
+            "Restricted (minimal profile, no signals, no introductions)",
+            Style::default().fg(Color::Magenta),
+        ),
+    };
+    Line::from(vec![
+        Span::styled("Trust: ", Style::default().fg(Color::Yellow)),
+        choice,
+        Span::styled(" (Ctrl+T: change)", Style::default().fg(Color::DarkGray)),
+    ])
+}
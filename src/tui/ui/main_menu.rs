@@ -4,10 +4,12 @@ use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{block::{Position, Title}, Block, Borders, List, ListItem, Paragraph},
     Frame,
 };
+use super::helpers::footer_block;
 use crate::tui::app::App;
+use crate::tui::types::MenuItem;
 
 /// Renders the screen
 
@@ -47,19 +49,23 @@ pub fn render_main_menu(f: &mut Frame, app: &App) {
         .split(size);
 
     // Title
+    let theme = app.theme();
     let title = Paragraph::new("Pure2P - True P2P Messenger")
         .style(
             Style::default()
-                .fg(Color::Cyan)
+                .fg(theme.title)
                 .add_modifier(Modifier::BOLD),
         )
         .alignment(Alignment::Center)
         .block(Block::default().borders(Borders::ALL));
     f.render_widget(title, chunks[0]);
 
-    // IP display
+    // Identity: profile label and fingerprint, UID and IP
     let uid_short = &app.keypair.uid.to_string()[..16];
-    let ip_text = format!("Your UID: {}... | IP: {}", uid_short, app.local_ip);
+    let ip_text = Line::from(vec![
+        Span::styled(app.identity_label(), Style::default().fg(theme.identity).add_modifier(Modifier::BOLD)),
+        Span::raw(format!(" | Your UID: {}... | IP: {}", uid_short, app.local_ip)),
+    ]);
     let ip_widget = Paragraph::new(ip_text)
         .style(Style::default().fg(Color::Green))
         .alignment(Alignment::Center)
@@ -123,38 +129,51 @@ pub fn render_main_menu(f: &mut Frame, app: &App) {
         2 // Menu is at index 2 when no notification
     };
 
-    // Menu items
+    // Menu items (Chat List carries the unread/pending badge)
+    let chat_summary = app.chat_summary();
     let menu_items: Vec<ListItem> = app
         .menu_items
         .iter()
         .enumerate()
         .map(|(i, item)| {
+            let label = match item {
+                MenuItem::ChatList => chat_summary.menu_label(),
+                _ => item.label().to_string(),
+            };
             let content = if i == app.selected_index {
                 Line::from(vec![
-                    Span::styled("→ ", Style::default().fg(Color::Yellow)),
+                    Span::styled("→ ", Style::default().fg(theme.selection)),
                     Span::styled(
-                        item.label(),
+                        label,
                         Style::default()
-                            .fg(Color::Yellow)
+                            .fg(theme.selection)
                             .add_modifier(Modifier::BOLD),
                     ),
                 ])
             } else {
                 Line::from(vec![
                     Span::raw("  "),
-                    Span::styled(item.label(), Style::default().fg(Color::White)),
+                    Span::styled(label, Style::default().fg(Color::White)),
                 ])
             };
             ListItem::new(content)
         })
         .collect();
 
-    let menu = List::new(menu_items).block(
-        Block::default()
-            .borders(Borders::ALL)
-            .title("Main Menu")
-            .style(Style::default()),
-    );
+    let mut menu_block = Block::default()
+        .borders(Borders::ALL)
+        .title("Main Menu")
+        .style(Style::default());
+    // Result of the opt-in update check; red when peers will start refusing us
+    if let Some(notice) = &app.update_notice {
+        let color = if notice.is_urgent() { Color::Red } else { Color::Cyan };
+        menu_block = menu_block.title(
+            Title::from(Span::styled(format!(" {} ", notice), Style::default().fg(color).add_modifier(Modifier::BOLD)))
+                .position(Position::Bottom)
+                .alignment(Alignment::Center),
+        );
+    }
+    let menu = List::new(menu_items).block(menu_block);
     f.render_widget(menu, chunks[menu_chunk_index]);
 
     // Help text
@@ -179,6 +198,6 @@ pub fn render_main_menu(f: &mut Frame, app: &App) {
     let help = Paragraph::new(help_text)
         .style(Style::default().fg(Color::DarkGray))
         .alignment(Alignment::Center)
-        .block(Block::default().borders(Borders::ALL));
+        .block(footer_block(app));
     f.render_widget(help, chunks[menu_chunk_index + 1]);
 }
@@ -0,0 +1,85 @@
+//! Maintenance screen rendering: this session's deletes, with undo
+
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use crate::tui::app::App;
+use crate::tui::undo::{UndoEntry, UndoState};
+use super::helpers::footer_block;
+
+/// Text of one undo entry row
+pub fn undo_entry_line(entry: &UndoEntry, window_hours: u32) -> String {
+    let state = match entry.state {
+        UndoState::Pending => format!("can be undone (purged {}h after deletion)", window_hours),
+        UndoState::Restored => "restored".to_string(),
+        UndoState::Purged => "purged".to_string(),
+    };
+    format!("{:<34} deleted {}  {}", entry.label(), entry.deleted_at.format("%H:%M:%S UTC"), state)
+}
+
+/// Renders the screen
+pub fn render_maintenance(f: &mut Frame, app: &App) {
+    let Some(screen) = &app.maintenance_screen else {
+        return;
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3), // Title
+            Constraint::Min(5),    // Deletes
+            Constraint::Length(3), // Status message
+            Constraint::Length(3), // Help text
+        ])
+        .split(f.size());
+
+    let title = Paragraph::new("Maintenance")
+        .style(Style::default().fg(app.theme().title).add_modifier(Modifier::BOLD))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(title, chunks[0]);
+
+    let window_hours = app.app_state.settings.soft_delete_window_hours;
+    let lines: Vec<Line> = app
+        .undo
+        .entries()
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let text = undo_entry_line(entry, window_hours);
+            if i == screen.selected {
+                Line::from(vec![
+                    Span::styled("→ ", Style::default().fg(app.theme().selection)),
+                    Span::styled(text, Style::default().fg(app.theme().selection).add_modifier(Modifier::BOLD)),
+                ])
+            } else if entry.state == UndoState::Pending {
+                Line::from(vec![Span::raw("  "), Span::raw(text)])
+            } else {
+                Line::from(vec![Span::raw("  "), Span::styled(text, Style::default().fg(Color::DarkGray))])
+            }
+        })
+        .collect();
+    let list_title = if lines.is_empty() {
+        "No deletes this session".to_string()
+    } else {
+        format!("Deleted this session ({})", lines.len())
+    };
+    let list = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(list_title));
+    f.render_widget(list, chunks[1]);
+
+    let status = Paragraph::new(screen.status_message.as_deref().unwrap_or(""))
+        .style(Style::default().fg(if screen.is_error { Color::Red } else { Color::Green }))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title("Status"));
+    f.render_widget(status, chunks[2]);
+
+    let help = Paragraph::new("↑/↓: Move | u/Enter: Undo | Esc: Back")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center)
+        .block(footer_block(app));
+    f.render_widget(help, chunks[3]);
+}
@@ -10,6 +10,10 @@ mod chat_list;
 mod chat_view;
 mod settings;
 mod diagnostics;
+mod snapshots;
+mod maintenance;
+mod capture;
+mod recovery;
 mod helpers;
 
 use ratatui::Frame;
@@ -20,13 +24,21 @@ use crate::tui::app::App;
 pub use main_menu::render_main_menu;
 pub use share_contact::render_share_contact;
 pub use import_contact::render_import_contact;
-pub use chat_list::render_chat_list;
+pub use chat_list::{bulk_delete_text, chat_list_rows, render_chat_list, temporary_badge, ChatListRow, ChatRowStatus};
 pub use chat_view::render_chat_view;
 pub use settings::render_settings;
-pub use diagnostics::render_diagnostics;
+pub use diagnostics::{queue_row_line, render_diagnostics};
+pub use snapshots::{render_snapshots, snapshot_diff_lines};
+pub use maintenance::{render_maintenance, undo_entry_line};
+pub use capture::render_capture;
+pub use recovery::render_recovery;
 
 // Re-export helper functions
-pub use helpers::format_duration_until;
+pub use helpers::{
+    connectivity_segment, display_width, error_banner_text, footer_block, footer_fits, format_duration_until,
+    format_duration_until_at, inspection_banner_text, render_error_banner, render_header_flash, render_inspection_banner,
+    render_notification, render_path_picker, render_port_banner, render_storage_banner, MIN_FOOTER_WIDTH,
+};
 
 /// Main UI rendering function - dispatches to screen-specific render functions
 pub fn ui(f: &mut Frame, app: &App) {
@@ -38,5 +50,41 @@ pub fn ui(f: &mut Frame, app: &App) {
         Screen::ChatView => render_chat_view(f, app),
         Screen::Settings => render_settings(f, app),
         Screen::Diagnostics => render_diagnostics(f, app),
+        Screen::Snapshots => render_snapshots(f, app),
+        Screen::Maintenance => render_maintenance(f, app),
+        Screen::Capture => render_capture(f, app),
+        Screen::Recovery => render_recovery(f, app),
+    }
+
+    let now = std::time::Instant::now();
+    if let Some(flash) = crate::tui::UiEffect::FLASHES.into_iter().find(|flash| app.effects.is_active(*flash, now)) {
+        render_header_flash(f, flash);
+    }
+
+    if let Some(picker) = &app.path_picker {
+        render_path_picker(f, picker);
+    }
+
+    if let Some(text) = app.notifications.visible(now) {
+        render_notification(f, text);
+    }
+
+    // Inspection mode is bannered on every screen; nothing there writes or listens
+    let mut top_banner = true;
+    if let Some(inspection) = &app.inspection {
+        render_inspection_banner(f, &inspection_banner_text(inspection, now));
+    } else if app.deferred_writes.is_pending() {
+        render_storage_banner(f, app.deferred_writes.pending_count());
+    } else if let Some(alert) = app.port_alert.lock().unwrap().as_ref()
+        && alert.is_visible(&app.transport_server_status.lock().unwrap(), now)
+    {
+        render_port_banner(f, &alert.text);
+    } else {
+        top_banner = false;
+    }
+
+    // Background failures go below the storage/port banner if one is up
+    if let Some(banner) = app.error_banner() {
+        render_error_banner(f, &banner, if top_banner { 1 } else { 0 });
     }
 }
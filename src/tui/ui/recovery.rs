@@ -0,0 +1,67 @@
+//! Recovery screen rendering: what was reconciled after an unclean exit
+
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+use crate::tui::app::App;
+use super::helpers::footer_block;
+
+/// Renders the screen
+pub fn render_recovery(f: &mut Frame, app: &App) {
+    let Some(screen) = &app.recovery_screen else {
+        return;
+    };
+    let report = &screen.report;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3), // Title
+            Constraint::Length(4), // Headline
+            Constraint::Min(5),    // Problems, then details
+            Constraint::Length(3), // Help text
+        ])
+        .split(f.size());
+
+    let title = Paragraph::new("Recovery")
+        .style(Style::default().fg(app.theme().title).add_modifier(Modifier::BOLD))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(title, chunks[0]);
+
+    let headline = Paragraph::new(report.headline())
+        .style(Style::default().fg(if report.has_problems() { Color::Red } else { Color::Green }))
+        .wrap(Wrap { trim: true })
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(headline, chunks[1]);
+
+    // Problems are always shown; they are what the user has to act on
+    let problems = report.problems();
+    let mut lines: Vec<Line> = if problems.is_empty() {
+        vec![Line::styled("Nothing needs your attention.", Style::default().fg(Color::DarkGray))]
+    } else {
+        problems
+            .into_iter()
+            .map(|problem| Line::styled(format!("✗ {}", problem), Style::default().fg(Color::Red)))
+            .collect()
+    };
+    if screen.show_details {
+        lines.push(Line::raw(""));
+        lines.extend(report.detail_lines().into_iter().map(Line::raw));
+    }
+    let body_title = if screen.show_details { "Could not be fixed / Details" } else { "Could not be fixed" };
+    let body = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(body_title));
+    f.render_widget(body, chunks[2]);
+
+    let help = Paragraph::new("d: Details | l: Logs | Enter/Esc: Continue")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center)
+        .block(footer_block(app));
+    f.render_widget(help, chunks[3]);
+}
@@ -4,10 +4,14 @@ use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
-use crate::tui::app::App;
+use super::helpers::footer_block;
+use crate::{
+    storage::MAX_TEMPLATES,
+    tui::{app::App, screens::{SettingsScreen, TemplateEditor}, theme::{accent_color, Theme}},
+};
 
 /// Renders the screen
 
@@ -15,6 +19,8 @@ pub fn render_settings(f: &mut Frame, app: &App) {
     let size = f.size();
 
     if let Some(screen) = &app.settings_screen {
+        let theme = app.theme();
+
         // Create layout
         let chunks = Layout::default()
             .direction(Direction::Vertical)
@@ -22,6 +28,12 @@ pub fn render_settings(f: &mut Frame, app: &App) {
             .constraints([
                 Constraint::Length(3),  // Title
                 Constraint::Length(5),  // Retry interval field
+                Constraint::Length(6),  // Quiet hours and alert fields
+                Constraint::Length(5),  // Relay, address review and auto-import toggles
+                Constraint::Length(4),  // Profile label and accent
+                Constraint::Length(4),  // History limit and update check
+                Constraint::Length(4),  // TLS and strict mode
+                Constraint::Length(5),  // Journal, send preview and templates fields
                 Constraint::Length(5),  // Help/Info
                 Constraint::Length(3),  // Status message
                 Constraint::Length(3),  // Help text
@@ -32,7 +44,7 @@ pub fn render_settings(f: &mut Frame, app: &App) {
         let title = Paragraph::new("Settings")
             .style(
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(app.theme().title)
                     .add_modifier(Modifier::BOLD),
             )
             .alignment(Alignment::Center)
@@ -43,7 +55,7 @@ pub fn render_settings(f: &mut Frame, app: &App) {
         let retry_interval_text = vec![
             Line::from(Span::styled(
                 "Retry Interval (minutes)",
-                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                field_label_style(screen, &theme, SettingsScreen::FIELD_RETRY_INTERVAL),
             )),
             Line::from(""),
             Line::from(Span::styled(
@@ -57,6 +69,195 @@ pub fn render_settings(f: &mut Frame, app: &App) {
             .block(Block::default().borders(Borders::ALL).title("Field"));
         f.render_widget(retry_field, chunks[1]);
 
+        // Quiet Hours Fields
+        let value_style = Style::default().fg(Color::Green).add_modifier(Modifier::BOLD);
+        let mut day_spans = vec![Span::styled(
+            "Days: ",
+            field_label_style(screen, &theme, SettingsScreen::FIELD_QUIET_DAYS),
+        )];
+        for (i, day) in ["M", "T", "W", "T", "F", "S", "S"].iter().enumerate() {
+            let style = if screen.quiet_days & (1 << i) != 0 {
+                value_style
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            day_spans.push(Span::styled(format!("{} ", day), style));
+        }
+
+        let quiet_hours_text = vec![
+            Line::from(vec![
+                Span::styled(
+                    "Enabled: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_QUIET_ENABLED),
+                ),
+                Span::styled(if screen.quiet_hours_enabled { "[x]" } else { "[ ]" }, value_style),
+            ]),
+            Line::from(vec![
+                Span::styled(
+                    "Start: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_QUIET_START),
+                ),
+                Span::styled(&screen.quiet_start_input, value_style),
+                Span::raw("   "),
+                Span::styled(
+                    "End: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_QUIET_END),
+                ),
+                Span::styled(&screen.quiet_end_input, value_style),
+            ]),
+            Line::from(day_spans),
+            Line::from(vec![
+                Span::styled("Alert: ", field_label_style(screen, &theme, SettingsScreen::FIELD_ALERT_MODE)),
+                Span::styled(screen.alert_mode.name(), value_style),
+                Span::raw("   "),
+                Span::styled("Style: ", field_label_style(screen, &theme, SettingsScreen::FIELD_ALERT_STYLE)),
+                Span::styled(screen.alert_style.name(), value_style),
+                Span::raw("   "),
+                Span::styled(
+                    "Error banner: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_ERROR_BANNER),
+                ),
+                Span::styled(format!("{}+", screen.error_banner_severity.name()), value_style),
+                Span::styled("  (Space to change)", Style::default().fg(Color::DarkGray)),
+            ]),
+        ];
+
+        let quiet_field = Paragraph::new(quiet_hours_text)
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL).title("Quiet Hours & Alerts"));
+        f.render_widget(quiet_field, chunks[2]);
+
+        // Relay Field
+        let relay_text = vec![
+            Line::from(vec![
+                Span::styled(
+                    "Act as relay for my contacts: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_RELAY_ENABLED),
+                ),
+                Span::styled(if screen.relay_enabled { "[x]" } else { "[ ]" }, value_style),
+            ]),
+            Line::from(vec![
+                Span::styled(
+                    "Review address changes of verified contacts: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_ADDRESS_REVIEW),
+                ),
+                Span::styled(if screen.address_review_enabled { "[x]" } else { "[ ]" }, value_style),
+            ]),
+            Line::from(vec![
+                Span::styled(
+                    "Lift auto-import caps for an hour: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_AUTO_IMPORT_LIFT),
+                ),
+                Span::styled(if screen.auto_import_lifted { "[x]" } else { "[ ]" }, value_style),
+                Span::styled(auto_import_caps_hint(app), Style::default().fg(Color::DarkGray)),
+            ]),
+        ];
+        let relay_field = Paragraph::new(relay_text)
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL).title("Relay & Contacts"));
+        f.render_widget(relay_field, chunks[3]);
+
+        // Profile Fields
+        let (accent_name, accent_style) = match screen.accent_color {
+            Some(accent) => (accent.name(), Style::default().fg(accent_color(accent)).add_modifier(Modifier::BOLD)),
+            None => ("default", value_style),
+        };
+        let profile_text = vec![
+            Line::from(vec![
+                Span::styled("Label: ", field_label_style(screen, &theme, SettingsScreen::FIELD_PROFILE_LABEL)),
+                Span::styled(&screen.profile_label_input, value_style),
+            ]),
+            Line::from(vec![
+                Span::styled("Accent: ", field_label_style(screen, &theme, SettingsScreen::FIELD_ACCENT)),
+                Span::styled(accent_name, accent_style),
+                Span::styled("  (Space to change)", Style::default().fg(Color::DarkGray)),
+            ]),
+        ];
+        let profile_field = Paragraph::new(profile_text)
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL).title("Profile"));
+        f.render_widget(profile_field, chunks[4]);
+
+        // History Limit and Update Check Fields
+        let history_text = vec![
+            Line::from(vec![
+                Span::styled(
+                    "Messages kept per chat: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_HISTORY_LIMIT),
+                ),
+                Span::styled(&screen.history_limit_input, value_style),
+                Span::styled("  (0 = unlimited, Ctrl+A: apply now)", Style::default().fg(Color::DarkGray)),
+            ]),
+            Line::from(vec![
+                Span::styled(
+                    "Check for updates daily: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_UPDATE_CHECK),
+                ),
+                Span::styled(if screen.update_check_enabled { "[x]" } else { "[ ]" }, value_style),
+                Span::styled("  (fetches a signed release manifest only)", Style::default().fg(Color::DarkGray)),
+            ]),
+        ];
+        let history_field = Paragraph::new(history_text)
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL).title("History & Updates"));
+        f.render_widget(history_field, chunks[5]);
+
+        // Transport Security Fields
+        let tls_text = vec![
+            Line::from(vec![
+                Span::styled("Serve TLS (pinned): ", field_label_style(screen, &theme, SettingsScreen::FIELD_TLS)),
+                Span::styled(if screen.tls_enabled { "[x]" } else { "[ ]" }, value_style),
+                Span::styled("  (same port, plain HTTP still accepted)", Style::default().fg(Color::DarkGray)),
+            ]),
+            Line::from(vec![
+                Span::styled(
+                    "Require TLS beyond the LAN: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_REQUIRE_TLS),
+                ),
+                Span::styled(if screen.require_tls_external { "[x]" } else { "[ ]" }, value_style),
+                Span::styled("  (for contacts that pinned a certificate)", Style::default().fg(Color::DarkGray)),
+            ]),
+        ];
+        let tls_field = Paragraph::new(tls_text)
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL).title("Transport Security"));
+        f.render_widget(tls_field, chunks[6]);
+
+        // Journal, Send Preview and Templates Fields
+        let templates_text = vec![
+            Line::from(vec![
+                Span::styled("Delivery journal: ", field_label_style(screen, &theme, SettingsScreen::FIELD_JOURNAL)),
+                Span::styled(if screen.journal_enabled { "[x]" } else { "[ ]" }, value_style),
+                Span::styled("  (hashes only; off seals it, Diagnostics v/j)", Style::default().fg(Color::DarkGray)),
+            ]),
+            Line::from(vec![
+                Span::styled(
+                    "Preview risky sends: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_SEND_PREVIEW),
+                ),
+                Span::styled(if screen.auto_send_preview { "[x]" } else { "[ ]" }, value_style),
+                Span::styled("  (large, plaintext or queued; Ctrl+O always)", Style::default().fg(Color::DarkGray)),
+                Span::raw("   "),
+                Span::styled("Night warning: ", field_label_style(screen, &theme, SettingsScreen::FIELD_NIGHT_WARNING)),
+                Span::styled(if screen.night_warning_enabled { "[x]" } else { "[ ]" }, value_style),
+            ]),
+            Line::from(vec![
+                Span::styled(
+                    "Message templates: ",
+                    field_label_style(screen, &theme, SettingsScreen::FIELD_TEMPLATES),
+                ),
+                Span::styled(
+                    format!("{}/{}", app.app_state.settings.templates.len(), MAX_TEMPLATES),
+                    value_style,
+                ),
+                Span::styled("  (Enter to manage)", Style::default().fg(Color::DarkGray)),
+            ]),
+        ];
+        let templates_field = Paragraph::new(templates_text)
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL).title("Journal & Templates"));
+        f.render_widget(templates_field, chunks[7]);
+
         // Help/Info
         let info_text = vec![
             Line::from(Span::styled(
@@ -67,12 +268,16 @@ pub fn render_settings(f: &mut Frame, app: &App) {
                 "pending messages (range: 1-1440 minutes).",
                 Style::default().fg(Color::DarkGray),
             )),
+            Line::from(Span::styled(
+                "Quiet hours hold back notifications and bulk retries.",
+                Style::default().fg(Color::DarkGray),
+            )),
         ];
 
         let info_widget = Paragraph::new(info_text)
             .alignment(Alignment::Center)
             .block(Block::default().borders(Borders::ALL).title("Info"));
-        f.render_widget(info_widget, chunks[2]);
+        f.render_widget(info_widget, chunks[8]);
 
         // Status message
         let status_text = screen
@@ -91,14 +296,149 @@ pub fn render_settings(f: &mut Frame, app: &App) {
             .style(Style::default().fg(status_color))
             .alignment(Alignment::Center)
             .block(Block::default().borders(Borders::ALL).title("Status"));
-        f.render_widget(status_widget, chunks[3]);
+        f.render_widget(status_widget, chunks[9]);
 
         // Help text
-        let help_text = "Enter: Save | Delete: Clear | Esc: Back";
+        let help_text = if screen.template_editor.is_some() {
+            "Tab: Name/Text | Enter: New line | Ctrl+S: Save | Esc: Cancel"
+        } else if screen.template_selected.is_some() {
+            "↑↓: Choose | n: New | Enter/e: Edit | d: Delete | Esc: Back"
+        } else {
+            "↑↓/Tab: Field | Space: Toggle | 1-7: Days | Enter: Save | Delete: Clear | Esc: Back"
+        };
         let help = Paragraph::new(help_text)
             .style(Style::default().fg(Color::DarkGray))
             .alignment(Alignment::Center)
-            .block(Block::default().borders(Borders::ALL));
-        f.render_widget(help, chunks[4]);
+            .block(footer_block(app));
+        f.render_widget(help, chunks[10]);
+
+        if let Some(editor) = &screen.template_editor {
+            render_template_editor(f, editor);
+        } else if let Some(selected) = screen.template_selected {
+            render_template_list(f, app, selected);
+        }
+    }
+}
+
+/// Centered popup area of at most `width` x `height`
+fn popup_area(f: &Frame, width: u16, height: u16) -> ratatui::layout::Rect {
+    let size = f.size();
+    ratatui::layout::Rect {
+        x: size.width.saturating_sub(width) / 2,
+        y: size.height.saturating_sub(height) / 2,
+        width: width.min(size.width),
+        height: height.min(size.height),
+    }
+}
+
+/// Renders the list of stored templates
+fn render_template_list(f: &mut Frame, app: &App, selected: usize) {
+    let area = popup_area(f, 64, 18);
+    let templates = &app.app_state.settings.templates;
+
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let first = selected.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = if templates.is_empty() {
+        vec![Line::from(Span::styled(
+            "No templates yet. Press n to add one.",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        templates
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(visible_rows)
+            .map(|(i, template)| {
+                let preview: String = template.body.lines().next().unwrap_or("").chars().take(36).collect();
+                let line = Line::from(vec![
+                    Span::styled(
+                        format!("{:<20} ", template.name),
+                        Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(preview, Style::default().fg(Color::DarkGray)),
+                ]);
+                if i == selected {
+                    line.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            })
+            .collect()
+    };
+
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan))
+            .title(format!("Message Templates ({}/{})", templates.len(), MAX_TEMPLATES))
+            .style(Style::default().bg(Color::Black)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// Renders the template editor
+fn render_template_editor(f: &mut Frame, editor: &TemplateEditor) {
+    let area = popup_area(f, 64, 16);
+    let active = Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let inactive = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let value_style = Style::default().fg(Color::Green);
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("Name: ", if editor.editing_body { inactive } else { active }),
+            Span::styled(editor.name.as_str(), value_style),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled("Text:", if editor.editing_body { active } else { inactive })),
+    ];
+    lines.extend(editor.body.split('\n').map(|l| Line::from(Span::styled(l, value_style))));
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "{name}: contact name | {date}: today's date",
+        Style::default().fg(Color::DarkGray),
+    )));
+    if let Some(status) = &editor.status_message {
+        lines.push(Line::from(Span::styled(status.as_str(), Style::default().fg(Color::Red))));
+    }
+
+    let title = if editor.index.is_some() { "Edit Template" } else { "New Template" };
+    let popup = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Cyan))
+                .title(title)
+                .style(Style::default().bg(Color::Black)),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// Current auto-import caps, or how long they stay lifted
+fn auto_import_caps_hint(app: &App) -> String {
+    let now = chrono::Utc::now();
+    match app.auto_import_limiter.lock().unwrap().lifted_until(now) {
+        Some(until) => format!("  (lifted until {})", until.with_timezone(&chrono::Local).format("%H:%M")),
+        None => {
+            let cap = |n: u32| if n == 0 { "∞".to_string() } else { n.to_string() };
+            let settings = &app.app_state.settings;
+            format!(
+                "  ({} contacts, {} chats/h)",
+                cap(settings.auto_import_contacts_per_hour),
+                cap(settings.auto_import_chats_per_hour)
+            )
+        }
+    }
+}
+
+/// Label style for a settings field, highlighted when selected
+fn field_label_style(screen: &SettingsScreen, theme: &Theme, field: usize) -> Style {
+    if screen.selected_field == field {
+        Style::default().fg(Color::Black).bg(theme.selection).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
     }
 }
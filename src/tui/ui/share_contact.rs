@@ -3,12 +3,13 @@
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
-    text::Text,
+    text::{Line, Span, Text},
     widgets::{Block, Borders, Paragraph, Wrap},
     Frame,
 };
 use crate::tui::app::App;
-use super::helpers::format_duration_until;
+use crate::tui::screens::ShareContactScreen;
+use super::helpers::{footer_block, format_duration_until};
 
 /// Renders the screen
 
@@ -16,6 +17,9 @@ pub fn render_share_contact(f: &mut Frame, app: &App) {
     let size = f.size();
 
     if let Some(screen) = &app.share_contact_screen {
+        // Endpoint list height: one row per endpoint, plus the input row while adding
+        let endpoint_rows = screen.endpoints.len() + usize::from(screen.new_endpoint_input.is_some());
+
         // Create layout
         let chunks = Layout::default()
             .direction(Direction::Vertical)
@@ -24,6 +28,7 @@ pub fn render_share_contact(f: &mut Frame, app: &App) {
                 Constraint::Length(3),  // Title
                 Constraint::Length(3),  // UID and Port info
                 Constraint::Length(3),  // Expiry info
+                Constraint::Length(endpoint_rows as u16 + 2), // Advertised endpoints
                 Constraint::Min(3),     // Token display (reduced from 5 to 3)
                 Constraint::Length(3),  // Status message
                 Constraint::Length(3),  // Help text
@@ -34,7 +39,7 @@ pub fn render_share_contact(f: &mut Frame, app: &App) {
         let title = Paragraph::new("Share Contact Token")
             .style(
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(app.theme().title)
                     .add_modifier(Modifier::BOLD),
             )
             .alignment(Alignment::Center)
@@ -67,17 +72,20 @@ pub fn render_share_contact(f: &mut Frame, app: &App) {
             .block(Block::default().borders(Borders::ALL).title("Expiry"));
         f.render_widget(expiry_widget, chunks[2]);
 
+        // Advertised endpoints
+        render_endpoint_list(f, screen, chunks[3]);
+
         // Token display (wrapped and scrollable if needed)
-        let token_text = Text::from(screen.token.clone());
+        let token_text = Text::from(screen.shown_token().to_string());
         let token_widget = Paragraph::new(token_text)
             .style(Style::default().fg(Color::Green))
             .wrap(Wrap { trim: false })
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .title("Contact Token"),
+                    .title(if screen.armored.is_some() { "Contact Token (armored)" } else { "Contact Token" }),
             );
-        f.render_widget(token_widget, chunks[3]);
+        f.render_widget(token_widget, chunks[4]);
 
         // Status message
         let status_text = screen
@@ -87,6 +95,8 @@ pub fn render_share_contact(f: &mut Frame, app: &App) {
             .unwrap_or("");
         let status_color = if status_text.contains("failed") || status_text.contains("error") {
             Color::Red
+        } else if status_text.contains('⚠') {
+            Color::Yellow
         } else {
             Color::Green
         };
@@ -94,15 +104,67 @@ pub fn render_share_contact(f: &mut Frame, app: &App) {
             .style(Style::default().fg(status_color))
             .alignment(Alignment::Center)
             .block(Block::default().borders(Borders::ALL).title("Status"));
-        f.render_widget(status_widget, chunks[4]);
+        f.render_widget(status_widget, chunks[5]);
 
         // Help text
-        let help_text = "c: Copy to Clipboard | s: Save to File | Esc: Back to Menu";
+        let help_text = if screen.new_endpoint_input.is_some() {
+            "Type <address:port> [label] [valid days] | Enter: Add | Esc: Cancel"
+        } else if screen.editing_endpoints {
+            "↑↓: Select | Space: Include/Exclude | K/J: Move | a: Add | x: Remove | Enter: Done"
+        } else {
+            "c: Copy to Clipboard | s: Save to File | p: Copy Saved Path | e: Edit Endpoints | a: Armored | i: Invite Code | Esc: Back to Menu"
+        };
         let help = Paragraph::new(help_text)
             .style(Style::default().fg(Color::DarkGray))
             .alignment(Alignment::Center)
-            .block(Block::default().borders(Borders::ALL));
-        f.render_widget(help, chunks[5]);
+            .block(footer_block(app));
+        f.render_widget(help, chunks[6]);
     }
 }
 
+
+/// Renders the advertised endpoint list (and the new endpoint input while adding)
+fn render_endpoint_list(f: &mut Frame, screen: &ShareContactScreen, area: ratatui::layout::Rect) {
+    let mut lines: Vec<Line> = screen
+        .endpoints
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let checkbox = if entry.included { "[x]" } else { "[ ]" };
+            let validity = entry
+                .endpoint
+                .valid_until
+                .map(|until| format!(" (valid {})", format_duration_until(until)))
+                .unwrap_or_default();
+            let text = format!(
+                "{} {}. {:<10} {}{}",
+                checkbox,
+                i + 1,
+                entry.endpoint.label,
+                entry.endpoint.address,
+                validity
+            );
+
+            let mut style = if entry.included {
+                Style::default().fg(Color::White)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            if screen.editing_endpoints && i == screen.selected_endpoint {
+                style = style.bg(Color::Blue).add_modifier(Modifier::BOLD);
+            }
+            Line::from(Span::styled(text, style))
+        })
+        .collect();
+
+    if let Some(input) = &screen.new_endpoint_input {
+        lines.push(Line::from(Span::styled(
+            format!("+ {}_", input),
+            Style::default().fg(Color::Yellow),
+        )));
+    }
+
+    let title = if screen.editing_endpoints { "Endpoints (editing)" } else { "Endpoints" };
+    let widget = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(widget, area);
+}
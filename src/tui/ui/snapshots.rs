@@ -0,0 +1,187 @@
+//! Snapshots screen rendering: snapshot list and read-only diff
+
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+use crate::storage::{ChangeList, FieldChange, SnapshotComparison, SnapshotDiff};
+use crate::tui::app::App;
+use crate::tui::screens::SnapshotsScreen;
+use super::helpers::footer_block;
+
+/// Characters of a UID shown in the diff
+const UID_SHOWN_CHARS: usize = 16;
+
+fn short_uid(uid: &str) -> &str {
+    &uid[..uid.len().min(UID_SHOWN_CHARS)]
+}
+
+/// "  … and N more" for changes counted but not listed
+fn unlisted_line<T>(changes: &ChangeList<T>) -> Option<String> {
+    (changes.unlisted() > 0).then(|| format!("  … and {} more", changes.unlisted()))
+}
+
+fn field_line(indent: &str, field: &FieldChange) -> String {
+    format!(
+        "{}{}: {} → {}",
+        indent,
+        field.field,
+        field.old.as_deref().unwrap_or("–"),
+        field.new.as_deref().unwrap_or("–")
+    )
+}
+
+/// Text lines of a diff, one change per line (field changes indented below their contact)
+pub fn snapshot_diff_lines(diff: &SnapshotDiff) -> Vec<String> {
+    let mut lines = vec![format!("{} → {}", diff.old, diff.new), diff.summary()];
+    if diff.is_empty() {
+        return lines;
+    }
+    for (title, changes, sign) in [
+        ("Contacts added", &diff.contacts_added, '+'),
+        ("Contacts removed", &diff.contacts_removed, '−'),
+        ("Chats added", &diff.chats_added, '+'),
+        ("Chats removed", &diff.chats_removed, '−'),
+    ] {
+        if changes.total > 0 {
+            lines.push(String::new());
+            lines.push(format!("{} ({})", title, changes.total));
+            lines.extend(changes.listed.iter().map(|uid| format!("  {} {}", sign, short_uid(uid))));
+            lines.extend(unlisted_line(changes));
+        }
+    }
+    if diff.contacts_modified.total > 0 {
+        lines.push(String::new());
+        lines.push(format!("Contacts modified ({})", diff.contacts_modified.total));
+        for contact in &diff.contacts_modified.listed {
+            lines.push(format!("  ~ {}", short_uid(&contact.uid)));
+            lines.extend(contact.fields.iter().map(|field| field_line("      ", field)));
+        }
+        lines.extend(unlisted_line(&diff.contacts_modified));
+    }
+    if diff.message_counts.total > 0 {
+        lines.push(String::new());
+        lines.push(format!("Message counts ({})", diff.message_counts.total));
+        lines.extend(diff.message_counts.listed.iter().map(|count| {
+            format!("  {}: {} → {} ({:+})", short_uid(&count.chat_uid), count.old, count.new, count.delta())
+        }));
+        lines.extend(unlisted_line(&diff.message_counts));
+    }
+    if !diff.settings.is_empty() {
+        lines.push(String::new());
+        lines.push(format!("Settings ({})", diff.settings.len()));
+        lines.extend(diff.settings.iter().map(|field| field_line("  ", field)));
+    }
+    lines
+}
+
+/// Renders the screen
+pub fn render_snapshots(f: &mut Frame, app: &App) {
+    let Some(screen) = &app.snapshots_screen else {
+        return;
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3), // Title
+            Constraint::Min(5),    // List or diff
+            Constraint::Length(3), // Status message
+            Constraint::Length(3), // Help text
+        ])
+        .split(f.size());
+
+    let title_text = if screen.comparison.is_some() { "Snapshot Diff (read-only)" } else { "Snapshots" };
+    let title = Paragraph::new(title_text)
+        .style(Style::default().fg(app.theme().title).add_modifier(Modifier::BOLD))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(title, chunks[0]);
+
+    match &screen.comparison {
+        Some(comparison) => render_comparison(f, comparison, screen.scroll, chunks[1]),
+        None => render_list(f, app, screen, chunks[1]),
+    }
+
+    let status = Paragraph::new(screen.status_message.as_deref().unwrap_or(""))
+        .style(Style::default().fg(if screen.is_error { Color::Red } else { Color::Green }))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title("Status"));
+    f.render_widget(status, chunks[2]);
+
+    let help_text = match &screen.comparison {
+        Some(SnapshotComparison::Compared(_)) => "↑/↓: Scroll | x: Export JSON | Esc: Back to list",
+        Some(SnapshotComparison::Encrypted(_)) => "Esc: Back to list",
+        None => "↑/↓: Move | Space: Mark | Enter: Compare | s: Take snapshot | Esc: Back",
+    };
+    let help = Paragraph::new(help_text)
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center)
+        .block(footer_block(app));
+    f.render_widget(help, chunks[3]);
+}
+
+fn render_list(f: &mut Frame, app: &App, screen: &SnapshotsScreen, area: Rect) {
+    let mut rows = vec![("Live database".to_string(), "now".to_string(), String::new())];
+    rows.extend(screen.snapshots.iter().map(|snapshot| {
+        (
+            snapshot.name.clone(),
+            snapshot.modified.map(|m| m.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_else(|| "-".to_string()),
+            if snapshot.sealed { "sealed".to_string() } else { "plain".to_string() },
+        )
+    }));
+
+    let lines: Vec<Line> = rows
+        .into_iter()
+        .enumerate()
+        .map(|(i, (name, taken, kind))| {
+            let mark = if screen.marked.contains(&i) { "[x]" } else { "[ ]" };
+            let text = format!("{} {:<36} {:<17} {}", mark, name, taken, kind);
+            if i == screen.selected {
+                Line::from(vec![
+                    Span::styled("→ ", Style::default().fg(app.theme().selection)),
+                    Span::styled(text, Style::default().fg(app.theme().selection).add_modifier(Modifier::BOLD)),
+                ])
+            } else {
+                Line::from(vec![Span::raw("  "), Span::raw(text)])
+            }
+        })
+        .collect();
+    let title = if screen.snapshots.is_empty() {
+        "No snapshots yet (s: take one)".to_string()
+    } else {
+        format!("Snapshots ({})", screen.snapshots.len())
+    };
+    let list = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(list, area);
+}
+
+fn render_comparison(f: &mut Frame, comparison: &SnapshotComparison, scroll: usize, area: Rect) {
+    let lines: Vec<Line> = match comparison {
+        SnapshotComparison::Compared(diff) => snapshot_diff_lines(diff)
+            .into_iter()
+            .map(|line| {
+                let color = match line.trim_start().chars().next() {
+                    Some('+') => Color::Green,
+                    Some('−') => Color::Red,
+                    Some('~') => Color::Yellow,
+                    _ if line.starts_with(' ') => Color::Gray,
+                    _ => Color::Cyan,
+                };
+                Line::from(Span::styled(line, Style::default().fg(color)))
+            })
+            .collect(),
+        SnapshotComparison::Encrypted(path) => vec![Line::from(Span::styled(
+            format!("{}: encrypted, cannot compare", path.display()),
+            Style::default().fg(Color::Yellow),
+        ))],
+    };
+    let widget = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .scroll((scroll.min(u16::MAX as usize) as u16, 0))
+        .block(Block::default().borders(Borders::ALL).title("Differences"));
+    f.render_widget(widget, area);
+}
@@ -31,7 +31,7 @@ pub fn render_startup_sync(f: &mut Frame, app: &App) {
         let title = Paragraph::new("Syncing Pending Messages")
             .style(
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(app.theme().title)
                     .add_modifier(Modifier::BOLD),
             )
             .alignment(Alignment::Center)
@@ -0,0 +1,130 @@
+//! Deleted chats and contacts that can still be undone this session
+//!
+//! Confirming a delete soft-deletes the item (see `storage::soft_delete`)
+//! and records an `UndoEntry` with what was taken out of memory. For
+//! `UNDO_STATUS_SECS` the chat list shows "Deleted chat with X — press U to
+//! undo" and U restores it; the entry stays on the Maintenance screen for
+//! the rest of the session, until the maintenance pass purges the item.
+
+use chrono::{DateTime, Duration, Utc};
+use crate::storage::{AddressChange, Chat, Contact, DeletedKind, Tombstone};
+
+/// Seconds the chat list offers U to undo a delete
+pub const UNDO_STATUS_SECS: i64 = 30;
+
+/// Seconds between two purges of soft-deleted items
+pub const SOFT_DELETE_PURGE_INTERVAL_SECS: i64 = 60;
+
+/// Characters of a UID shown in undo messages
+const UID_SHOWN_CHARS: usize = 16;
+
+/// Where a deleted item stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoState {
+    /// Soft-deleted, can be undone
+    Pending,
+    /// Undone
+    Restored,
+    /// Purged; too late to undo
+    Purged,
+}
+
+/// A chat or contact deleted this session
+#[derive(Debug, Clone)]
+pub struct UndoEntry {
+    /// Contact UID of the chat or contact
+    pub uid: String,
+    /// What was deleted
+    pub kind: DeletedKind,
+    /// When the delete was confirmed
+    pub deleted_at: DateTime<Utc>,
+    /// The removed chat and its position in `AppState::chats`
+    pub chat: Option<(usize, Chat)>,
+    /// The removed contact and its position in `AppState::contacts`
+    pub contact: Option<(usize, Contact)>,
+    /// Address change that was waiting for review
+    pub address_change: Option<AddressChange>,
+    /// Whether the delete retained the contact's notes (dropped again on undo)
+    pub retained_notes: bool,
+    /// Where the item stands
+    pub state: UndoState,
+}
+
+impl UndoEntry {
+    /// "chat with <uid>" or "contact <uid>"
+    pub fn label(&self) -> String {
+        let uid = &self.uid[..self.uid.len().min(UID_SHOWN_CHARS)];
+        match self.kind {
+            DeletedKind::Chat => format!("chat with {}", uid),
+            DeletedKind::Contact => format!("contact {}", uid),
+        }
+    }
+
+    /// Chat list status shown while U can undo the delete
+    pub fn status_text(&self) -> String {
+        format!("Deleted {} — press U to undo", self.label())
+    }
+
+    /// Whether U still undoes this entry at `now`
+    pub fn in_status_window(&self, now: DateTime<Utc>) -> bool {
+        self.state == UndoState::Pending && now < self.deleted_at + Duration::seconds(UNDO_STATUS_SECS)
+    }
+}
+
+/// Deletes of this session, oldest first
+#[derive(Debug, Default)]
+pub struct UndoList {
+    entries: Vec<UndoEntry>,
+    /// When the next purge is due (None: at once)
+    next_purge: Option<DateTime<Utc>>,
+}
+
+impl UndoList {
+    /// Create an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a delete
+    pub fn push(&mut self, entry: UndoEntry) {
+        self.entries.push(entry);
+    }
+
+    /// All deletes of this session, oldest first
+    pub fn entries(&self) -> &[UndoEntry] {
+        &self.entries
+    }
+
+    /// The entry at `index`
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut UndoEntry> {
+        self.entries.get_mut(index)
+    }
+
+    /// Index of the latest delete U undoes at `now`
+    pub fn status_entry(&self, now: DateTime<Utc>) -> Option<usize> {
+        self.entries.iter().rposition(|entry| entry.in_status_window(now))
+    }
+
+    /// UIDs of the deletes that can still be undone
+    pub fn pending_uids(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().filter(|entry| entry.state == UndoState::Pending).map(|entry| entry.uid.as_str())
+    }
+
+    /// Mark the entries whose item was purged
+    pub fn mark_purged(&mut self, tombstones: &[Tombstone]) {
+        for entry in self.entries.iter_mut().filter(|entry| entry.state == UndoState::Pending) {
+            if tombstones.iter().any(|tombstone| tombstone.uid == entry.uid && tombstone.kind == entry.kind) {
+                entry.state = UndoState::Purged;
+            }
+        }
+    }
+
+    /// Whether a purge is due at `now`; if so, the next one is scheduled
+    pub fn purge_due(&mut self, now: DateTime<Utc>) -> bool {
+        if self.next_purge.is_some_and(|next| now < next) {
+            return false;
+        }
+        self.next_purge = Some(now + Duration::seconds(SOFT_DELETE_PURGE_INTERVAL_SECS));
+        true
+    }
+}
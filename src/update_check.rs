@@ -0,0 +1,270 @@
+//! Opt-in check for newer releases
+//!
+//! With `Settings::update_check_enabled` on (default off), the TUI fetches a
+//! small static manifest from `Settings::update_manifest_url` at most once
+//! every `UPDATE_CHECK_INTERVAL_HOURS`. The manifest is signed with the
+//! release key baked in as `UPDATE_SIGNING_KEY`; anything that does not
+//! verify is ignored. Nothing is downloaded and nothing about us is sent:
+//! the request is a plain GET without cookies or identifying headers
+//! (reqwest still honours the `HTTP(S)_PROXY` environment variables).
+//!
+//! Manifest file (JSON):
+//!
+//! ```json
+//! {"manifest": "{\"latest_version\":\"0.4.0\",\"min_protocol_version\":1,\"release_notes_url\":\"https://...\"}",
+//!  "signature": "<hex Ed25519 signature over the manifest string's bytes>"}
+//! ```
+//!
+//! The signed manifest is kept as a string so the signature covers exactly
+//! the bytes that were signed, whatever the JSON formatting.
+
+use crate::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Ed25519 public key of the release signing key
+pub const UPDATE_SIGNING_KEY: [u8; 32] = [
+    0x12, 0x22, 0xc2, 0x20, 0x1e, 0x20, 0xa4, 0x75,
+    0xc9, 0xd8, 0x23, 0x33, 0x68, 0x7e, 0x34, 0x25,
+    0x9a, 0x68, 0xf2, 0xae, 0xa1, 0x7c, 0x98, 0x95,
+    0x72, 0x6b, 0x27, 0x3b, 0xae, 0xa9, 0xe1, 0x15,
+];
+
+/// Default for `Settings::update_manifest_url`
+pub const DEFAULT_UPDATE_MANIFEST_URL: &str =
+    "https://github.com/Anton4ikk/pure2p/releases/latest/download/update-manifest.json";
+
+/// Minimum time between two checks
+pub const UPDATE_CHECK_INTERVAL_HOURS: i64 = 24;
+
+/// Version of this build
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// What the release manifest announces
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseManifest {
+    /// Latest released version (semver, e.g. "0.4.0" or "0.5.0-beta.1")
+    pub latest_version: String,
+    /// Oldest protocol version peers running the latest release still accept
+    pub min_protocol_version: u8,
+    /// Where the release notes are
+    pub release_notes_url: String,
+}
+
+/// The manifest file: the manifest JSON as a string plus its signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SignedManifest {
+    manifest: String,
+    signature: String,
+}
+
+/// Sign `manifest` into a manifest file (release tooling and tests)
+///
+/// # Errors
+/// Returns an error if serialization or signing fails
+pub fn sign_manifest(manifest: &ReleaseManifest, signing_key: &[u8; 32]) -> Result<String> {
+    let manifest = serde_json::to_string(manifest)?;
+    let signature = crate::crypto::sign_contact_token(signing_key, manifest.as_bytes())?;
+    Ok(serde_json::to_string(&SignedManifest { manifest, signature: hex::encode(signature) })?)
+}
+
+/// Parse a manifest file and verify its signature against `public_key`
+///
+/// # Errors
+/// Returns `Error::Crypto` if the signature is malformed or does not verify,
+/// and a serialization error if the file or manifest is not valid JSON
+pub fn parse_signed_manifest(text: &str, public_key: &[u8; 32]) -> Result<ReleaseManifest> {
+    let signed: SignedManifest = serde_json::from_str(text)?;
+    let signature: [u8; 64] = hex::decode(&signed.signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| Error::Crypto("Update manifest signature is malformed".to_string()))?;
+    if !crate::crypto::verify_contact_token(public_key, signed.manifest.as_bytes(), &signature)? {
+        return Err(Error::Crypto("Update manifest signature does not verify".to_string()));
+    }
+    Ok(serde_json::from_str(&signed.manifest)?)
+}
+
+/// A semantic version (build metadata is ignored)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    core: [u64; 3],
+    pre: Vec<String>,
+}
+
+impl Version {
+    /// Parse "1.2.3", "v1.2.3", "1.2.3-beta.1" or "1.2.3+build"
+    ///
+    /// # Returns
+    /// `None` unless there are exactly three numeric components
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let text = text.strip_prefix('v').unwrap_or(text);
+        let text = text.split('+').next()?;
+        let (core, pre) = match text.split_once('-') {
+            Some((core, pre)) if !pre.is_empty() => (core, pre.split('.').map(str::to_string).collect()),
+            Some(_) => return None,
+            None => (text, Vec::new()),
+        };
+        let parts: Vec<u64> = core.split('.').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+        let core: [u64; 3] = parts.try_into().ok()?;
+        Some(Self { core, pre })
+    }
+
+    /// Whether this is a pre-release (e.g. "0.5.0-rc.1")
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+}
+
+impl Ord for Version {
+    /// Semver precedence: a pre-release sorts before its release; numeric
+    /// identifiers compare numerically and before alphanumeric ones
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.core.cmp(&other.core).then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => {
+                for (a, b) in self.pre.iter().zip(&other.pre) {
+                    let order = match (a.parse::<u64>(), b.parse::<u64>()) {
+                        (Ok(a), Ok(b)) => a.cmp(&b),
+                        (Ok(_), Err(_)) => Ordering::Less,
+                        (Err(_), Ok(_)) => Ordering::Greater,
+                        (Err(_), Err(_)) => a.cmp(b),
+                    };
+                    if order != Ordering::Equal {
+                        return order;
+                    }
+                }
+                self.pre.len().cmp(&other.pre.len())
+            }
+        })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// What the main menu shows after a check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateNotice {
+    /// A newer release exists
+    Available {
+        /// Latest version
+        latest: String,
+        /// Release notes
+        release_notes_url: String,
+    },
+    /// Our protocol is older than peers on the latest release accept: they
+    /// will start refusing us (HTTP 426)
+    ProtocolOutdated {
+        /// Latest version
+        latest: String,
+        /// Oldest protocol version still accepted
+        min_protocol_version: u8,
+        /// Release notes
+        release_notes_url: String,
+    },
+}
+
+impl UpdateNotice {
+    /// Whether this is the stronger warning
+    pub fn is_urgent(&self) -> bool {
+        matches!(self, UpdateNotice::ProtocolOutdated { .. })
+    }
+}
+
+impl fmt::Display for UpdateNotice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateNotice::Available { latest, release_notes_url } => {
+                write!(f, "Update available: {} ({})", latest, release_notes_url)
+            }
+            UpdateNotice::ProtocolOutdated { latest, min_protocol_version, release_notes_url } => write!(
+                f,
+                "⚠ Update required: peers need protocol {}+ and refuse this build (protocol {}, HTTP 426). Get {} ({})",
+                min_protocol_version,
+                crate::protocol::PROTOCOL_VERSION,
+                latest,
+                release_notes_url
+            ),
+        }
+    }
+}
+
+/// Compare a manifest with this build
+///
+/// Pre-releases are only offered to builds that are pre-releases themselves.
+///
+/// # Returns
+/// The notice to show, if any (`None` also when a version does not parse)
+pub fn evaluate_manifest(manifest: &ReleaseManifest, current_version: &str, protocol_version: u8) -> Option<UpdateNotice> {
+    let latest = Version::parse(&manifest.latest_version)?;
+    let current = Version::parse(current_version)?;
+    if protocol_version < manifest.min_protocol_version {
+        return Some(UpdateNotice::ProtocolOutdated {
+            latest: manifest.latest_version.clone(),
+            min_protocol_version: manifest.min_protocol_version,
+            release_notes_url: manifest.release_notes_url.clone(),
+        });
+    }
+    let offered = !latest.is_prerelease() || current.is_prerelease();
+    (offered && latest > current).then(|| UpdateNotice::Available {
+        latest: manifest.latest_version.clone(),
+        release_notes_url: manifest.release_notes_url.clone(),
+    })
+}
+
+/// Whether a check is due at `now`, given the last one
+pub fn update_check_due(last_checked: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    last_checked.is_none_or(|last| now - last >= Duration::hours(UPDATE_CHECK_INTERVAL_HOURS) || last > now)
+}
+
+/// Fetches the manifest file
+///
+/// Methods block; they are only called from background threads.
+pub trait ManifestFetcher: Send + Sync {
+    /// Body of `url`
+    fn fetch(&self, url: &str) -> std::result::Result<String, String>;
+}
+
+/// `ManifestFetcher` over HTTP(S)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpManifestFetcher;
+
+impl ManifestFetcher for HttpManifestFetcher {
+    fn fetch(&self, url: &str) -> std::result::Result<String, String> {
+        let rt = tokio::runtime::Runtime::new().map_err(|e| format!("Failed to create runtime: {}", e))?;
+        rt.block_on(async {
+            let client = reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .map_err(|e| e.to_string())?;
+            let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!("HTTP {}", response.status()));
+            }
+            response.text().await.map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// Fetch, verify and evaluate the manifest at `url`
+///
+/// # Errors
+/// A description of the failed fetch or verification
+pub fn run_update_check(
+    fetcher: &dyn ManifestFetcher,
+    url: &str,
+    public_key: &[u8; 32],
+) -> std::result::Result<Option<UpdateNotice>, String> {
+    let text = fetcher.fetch(url)?;
+    let manifest = parse_signed_manifest(&text, public_key).map_err(|e| e.to_string())?;
+    Ok(evaluate_manifest(&manifest, CURRENT_VERSION, crate::protocol::PROTOCOL_VERSION))
+}